@@ -0,0 +1,9 @@
+ALTER TABLE packages DROP COLUMN deliveries_to_zones;
+
+DELETE FROM shipping_rates WHERE to_zone_id IS NOT NULL;
+DROP INDEX IF EXISTS shipping_rates_zone_idx;
+ALTER TABLE shipping_rates DROP CONSTRAINT shipping_rates_destination_check;
+ALTER TABLE shipping_rates DROP COLUMN to_zone_id;
+ALTER TABLE shipping_rates ALTER COLUMN to_alpha3 SET NOT NULL;
+
+DROP TABLE IF EXISTS zones;
@@ -0,0 +1,13 @@
+CREATE TABLE zones (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR NOT NULL UNIQUE,
+    countries JSONB NOT NULL DEFAULT '[]'
+);
+
+ALTER TABLE shipping_rates ALTER COLUMN to_alpha3 DROP NOT NULL;
+ALTER TABLE shipping_rates ADD COLUMN to_zone_id INTEGER REFERENCES zones (id) ON DELETE CASCADE;
+ALTER TABLE shipping_rates ADD CONSTRAINT shipping_rates_destination_check CHECK ((to_alpha3 IS NULL) <> (to_zone_id IS NULL));
+
+CREATE UNIQUE INDEX shipping_rates_zone_idx ON shipping_rates (company_package_id, from_alpha3, to_zone_id);
+
+ALTER TABLE packages ADD COLUMN deliveries_to_zones JSONB NOT NULL DEFAULT '[]';
@@ -0,0 +1,2 @@
+ALTER TABLE products DROP COLUMN hazard_classes;
+ALTER TABLE companies_packages DROP COLUMN restricted_hazard_classes;
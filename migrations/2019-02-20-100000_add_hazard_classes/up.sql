@@ -0,0 +1,2 @@
+ALTER TABLE companies_packages ADD COLUMN restricted_hazard_classes JSONB NOT NULL DEFAULT '[]';
+ALTER TABLE products ADD COLUMN hazard_classes JSONB NOT NULL DEFAULT '[]';
@@ -0,0 +1,12 @@
+CREATE TABLE backfills (
+    name VARCHAR PRIMARY KEY,
+    status VARCHAR NOT NULL,
+    total_rows BIGINT NOT NULL,
+    processed_rows BIGINT NOT NULL DEFAULT 0,
+    last_processed_id INTEGER,
+    error VARCHAR,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    updated_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+SELECT diesel_manage_updated_at('backfills');
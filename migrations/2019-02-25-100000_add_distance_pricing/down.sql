@@ -0,0 +1 @@
+ALTER TABLE companies_packages DROP COLUMN distance_pricing;
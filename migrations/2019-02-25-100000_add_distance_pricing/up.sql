@@ -0,0 +1 @@
+ALTER TABLE companies_packages ADD COLUMN distance_pricing JSONB;
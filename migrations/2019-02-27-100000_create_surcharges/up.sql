@@ -0,0 +1,12 @@
+CREATE TABLE surcharges (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR NOT NULL,
+    kind VARCHAR NOT NULL,
+    value DOUBLE PRECISION NOT NULL,
+    company_id INTEGER REFERENCES companies (id) ON DELETE CASCADE,
+    company_package_id INTEGER REFERENCES companies_packages (id) ON DELETE CASCADE,
+    to_alpha3 VARCHAR
+);
+
+CREATE INDEX surcharges_company_idx ON surcharges (company_id);
+CREATE INDEX surcharges_company_package_idx ON surcharges (company_package_id);
@@ -0,0 +1,5 @@
+ALTER TABLE shipping_rates DROP COLUMN delivery_time_max_days;
+ALTER TABLE shipping_rates DROP COLUMN delivery_time_min_days;
+
+ALTER TABLE companies_packages DROP COLUMN delivery_time_max_days;
+ALTER TABLE companies_packages DROP COLUMN delivery_time_min_days;
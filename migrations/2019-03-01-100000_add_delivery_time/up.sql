@@ -0,0 +1,5 @@
+ALTER TABLE companies_packages ADD COLUMN delivery_time_min_days INTEGER;
+ALTER TABLE companies_packages ADD COLUMN delivery_time_max_days INTEGER;
+
+ALTER TABLE shipping_rates ADD COLUMN delivery_time_min_days INTEGER;
+ALTER TABLE shipping_rates ADD COLUMN delivery_time_max_days INTEGER;
@@ -0,0 +1,20 @@
+CREATE TABLE package_templates (
+    id SERIAL PRIMARY KEY,
+    from_alpha3 VARCHAR NOT NULL,
+    company_package_id INTEGER NOT NULL REFERENCES companies_packages (id) ON DELETE CASCADE,
+    position INTEGER NOT NULL DEFAULT 0,
+    CONSTRAINT package_templates_from_company_package_key UNIQUE (from_alpha3, company_package_id)
+);
+
+CREATE INDEX package_templates_from_alpha3_idx ON package_templates (from_alpha3);
+
+-- every available company package becomes a default of the countries its company ships from
+INSERT INTO package_templates (from_alpha3, company_package_id, position)
+SELECT deliveries_from.alpha3,
+       companies_packages.id,
+       ROW_NUMBER() OVER (PARTITION BY deliveries_from.alpha3 ORDER BY companies_packages.id) - 1
+FROM companies_packages
+JOIN companies ON companies.id = companies_packages.company_id
+CROSS JOIN LATERAL jsonb_array_elements_text(companies.deliveries_from) AS deliveries_from (alpha3)
+WHERE companies_packages.shipping_rate_source <> 'not_available'
+ON CONFLICT DO NOTHING;
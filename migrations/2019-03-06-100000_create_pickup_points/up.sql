@@ -0,0 +1,13 @@
+CREATE TABLE pickup_points (
+    id SERIAL PRIMARY KEY,
+    company_id INTEGER NOT NULL REFERENCES companies (id) ON DELETE CASCADE,
+    name VARCHAR NOT NULL,
+    country VARCHAR NOT NULL,
+    address VARCHAR NOT NULL,
+    latitude DOUBLE PRECISION NOT NULL,
+    longitude DOUBLE PRECISION NOT NULL,
+    opening_hours JSONB NOT NULL DEFAULT '[]'
+);
+
+CREATE INDEX pickup_points_company_idx ON pickup_points (company_id);
+CREATE INDEX pickup_points_latitude_idx ON pickup_points (latitude);
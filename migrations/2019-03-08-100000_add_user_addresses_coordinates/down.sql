@@ -0,0 +1,2 @@
+ALTER TABLE user_addresses DROP COLUMN longitude;
+ALTER TABLE user_addresses DROP COLUMN latitude;
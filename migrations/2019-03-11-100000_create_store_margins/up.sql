@@ -0,0 +1,5 @@
+CREATE TABLE store_margins (
+    store_id INTEGER PRIMARY KEY,
+    kind VARCHAR NOT NULL,
+    value DOUBLE PRECISION NOT NULL
+);
@@ -0,0 +1,42 @@
+CREATE TABLE role_permissions (
+    id SERIAL PRIMARY KEY,
+    role VARCHAR NOT NULL,
+    resource VARCHAR NOT NULL,
+    action VARCHAR NOT NULL,
+    scope VARCHAR NOT NULL,
+
+    CONSTRAINT role_permission UNIQUE (role, resource, action, scope)
+);
+
+INSERT INTO role_permissions (role, resource, action, scope) VALUES
+    ('superuser', 'backfills', 'all', 'all'),
+    ('superuser', 'companies', 'all', 'all'),
+    ('superuser', 'companies_packages', 'all', 'all'),
+    ('superuser', 'countries', 'all', 'all'),
+    ('superuser', 'package_templates', 'all', 'all'),
+    ('superuser', 'packages', 'all', 'all'),
+    ('superuser', 'permissions', 'all', 'all'),
+    ('superuser', 'pickup_points', 'all', 'all'),
+    ('superuser', 'pickups', 'all', 'all'),
+    ('superuser', 'products', 'all', 'all'),
+    ('superuser', 'shipping_rates', 'all', 'all'),
+    ('superuser', 'store_margins', 'all', 'all'),
+    ('superuser', 'surcharges', 'all', 'all'),
+    ('superuser', 'user_addresses', 'all', 'all'),
+    ('superuser', 'user_roles', 'all', 'all'),
+    ('superuser', 'zones', 'all', 'all'),
+    ('user', 'companies', 'read', 'all'),
+    ('user', 'companies_packages', 'read', 'all'),
+    ('user', 'countries', 'read', 'all'),
+    ('user', 'package_templates', 'read', 'all'),
+    ('user', 'packages', 'read', 'all'),
+    ('user', 'pickup_points', 'read', 'all'),
+    ('user', 'pickups', 'read', 'all'),
+    ('user', 'products', 'read', 'all'),
+    ('user', 'shipping_rates', 'read', 'all'),
+    ('user', 'user_addresses', 'all', 'owned'),
+    ('user', 'user_roles', 'read', 'owned'),
+    ('user', 'zones', 'read', 'all'),
+    ('store_manager', 'pickups', 'all', 'owned'),
+    ('store_manager', 'products', 'all', 'owned'),
+    ('store_manager', 'store_margins', 'all', 'owned');
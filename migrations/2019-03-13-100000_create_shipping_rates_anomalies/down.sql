@@ -0,0 +1,5 @@
+DELETE FROM role_permissions WHERE resource = 'shipping_rates_anomalies';
+
+DROP TABLE IF EXISTS shipping_rates_anomalies;
+
+ALTER TABLE shipping_rates DROP COLUMN IF EXISTS is_active;
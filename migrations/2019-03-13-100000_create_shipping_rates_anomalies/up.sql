@@ -0,0 +1,16 @@
+ALTER TABLE shipping_rates ADD COLUMN is_active BOOLEAN NOT NULL DEFAULT TRUE;
+
+CREATE TABLE shipping_rates_anomalies (
+    id SERIAL PRIMARY KEY,
+    shipping_rates_id INTEGER NOT NULL REFERENCES shipping_rates (id) ON DELETE CASCADE,
+    kind VARCHAR NOT NULL,
+    weight_g INTEGER NOT NULL,
+    price DOUBLE PRECISION NOT NULL,
+    deactivated BOOLEAN NOT NULL,
+    detected_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX shipping_rates_anomalies_shipping_rates_id_idx ON shipping_rates_anomalies (shipping_rates_id);
+
+INSERT INTO role_permissions (role, resource, action, scope) VALUES
+    ('superuser', 'shipping_rates_anomalies', 'all', 'all');
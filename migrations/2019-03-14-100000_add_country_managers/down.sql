@@ -0,0 +1 @@
+DELETE FROM role_permissions WHERE role = 'store_manager' AND resource = 'companies' AND action = 'update' AND scope = 'owned';
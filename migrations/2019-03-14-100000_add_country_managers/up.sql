@@ -0,0 +1,2 @@
+INSERT INTO role_permissions (role, resource, action, scope) VALUES
+    ('store_manager', 'companies', 'update', 'owned');
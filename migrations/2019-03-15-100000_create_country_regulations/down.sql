@@ -0,0 +1,3 @@
+DELETE FROM role_permissions WHERE resource = 'country_regulations';
+
+DROP TABLE IF EXISTS country_regulations;
@@ -0,0 +1,18 @@
+CREATE TABLE country_regulations (
+    alpha3 VARCHAR PRIMARY KEY,
+    max_weight_g INTEGER,
+    max_volume_cubic_cm INTEGER,
+    prohibited_hazard_classes JSONB NOT NULL DEFAULT '[]',
+    source VARCHAR NOT NULL
+);
+
+-- Baseline of the postal union: parcels are accepted up to 30 kg, aerosols are dangerous goods prohibited in the post.
+-- Stricter national limits are set by admins per country
+INSERT INTO country_regulations (alpha3, max_weight_g, max_volume_cubic_cm, prohibited_hazard_classes, source)
+SELECT DISTINCT alpha3, 30000, NULL, '["Aerosols"]', 'UPU Convention'
+FROM countries
+WHERE level = 2;
+
+INSERT INTO role_permissions (role, resource, action, scope) VALUES
+    ('superuser', 'country_regulations', 'all', 'all'),
+    ('user', 'country_regulations', 'read', 'all');
@@ -0,0 +1 @@
+ALTER TABLE companies ADD COLUMN capabilities JSONB NOT NULL DEFAULT '[]';
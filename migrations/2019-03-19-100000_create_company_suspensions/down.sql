@@ -0,0 +1,3 @@
+DELETE FROM role_permissions WHERE resource = 'company_suspensions';
+
+DROP TABLE IF EXISTS company_suspensions;
@@ -0,0 +1,16 @@
+CREATE TABLE company_suspensions (
+    id SERIAL PRIMARY KEY,
+    company_id INTEGER NOT NULL REFERENCES companies (id) ON DELETE CASCADE,
+    company_package_id INTEGER REFERENCES companies_packages (id) ON DELETE CASCADE,
+    starts_on DATE NOT NULL,
+    ends_on DATE NOT NULL,
+    reason VARCHAR NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+
+    CONSTRAINT company_suspension_dates CHECK (starts_on <= ends_on)
+);
+
+CREATE INDEX company_suspensions_ends_on_idx ON company_suspensions (ends_on);
+
+INSERT INTO role_permissions (role, resource, action, scope) VALUES
+    ('superuser', 'company_suspensions', 'all', 'all');
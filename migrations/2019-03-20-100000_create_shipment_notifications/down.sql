@@ -0,0 +1,3 @@
+DELETE FROM role_permissions WHERE resource = 'shipment_notifications';
+
+DROP TABLE IF EXISTS shipment_notifications;
@@ -0,0 +1,12 @@
+CREATE TABLE shipment_notifications (
+    id SERIAL PRIMARY KEY,
+    event_id VARCHAR NOT NULL UNIQUE,
+    kind VARCHAR NOT NULL,
+    user_id INTEGER NOT NULL,
+    shipping_id INTEGER NOT NULL,
+    lang VARCHAR NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+INSERT INTO role_permissions (role, resource, action, scope) VALUES
+    ('superuser', 'shipment_notifications', 'all', 'all');
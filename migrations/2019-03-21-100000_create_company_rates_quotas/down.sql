@@ -0,0 +1,3 @@
+DELETE FROM role_permissions WHERE resource = 'company_rates_quotas';
+
+DROP TABLE IF EXISTS company_rates_quotas;
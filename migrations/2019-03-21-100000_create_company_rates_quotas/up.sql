@@ -0,0 +1,10 @@
+CREATE TABLE company_rates_quotas (
+    company_id INTEGER PRIMARY KEY REFERENCES companies (id) ON DELETE CASCADE,
+    max_entries BIGINT NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT now(),
+
+    CONSTRAINT company_rates_quota_max_entries CHECK (max_entries >= 0)
+);
+
+INSERT INTO role_permissions (role, resource, action, scope) VALUES
+    ('superuser', 'company_rates_quotas', 'all', 'all');
@@ -0,0 +1,7 @@
+DELETE FROM role_permissions WHERE resource = 'store_shipping_summaries';
+
+DROP INDEX IF EXISTS products_store_id_updated_at_idx;
+
+DROP TRIGGER IF EXISTS set_updated_at ON products;
+
+ALTER TABLE products DROP COLUMN IF EXISTS updated_at;
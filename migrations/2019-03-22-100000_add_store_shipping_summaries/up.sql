@@ -0,0 +1,9 @@
+ALTER TABLE products ADD COLUMN updated_at TIMESTAMP NOT NULL DEFAULT current_timestamp;
+
+SELECT diesel_manage_updated_at('products');
+
+CREATE INDEX products_store_id_updated_at_idx ON products (store_id, updated_at DESC);
+
+INSERT INTO role_permissions (role, resource, action, scope) VALUES
+    ('superuser', 'store_shipping_summaries', 'all', 'all'),
+    ('store_manager', 'store_shipping_summaries', 'read', 'owned');
@@ -0,0 +1 @@
+ALTER TABLE shipping_rates DROP COLUMN IF EXISTS components;
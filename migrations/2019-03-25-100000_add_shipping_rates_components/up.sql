@@ -0,0 +1 @@
+ALTER TABLE shipping_rates ADD COLUMN components JSONB NOT NULL DEFAULT '[]';
@@ -0,0 +1,3 @@
+DELETE FROM role_permissions WHERE resource = 'shipping_templates';
+
+DROP TABLE IF EXISTS shipping_templates;
@@ -0,0 +1,12 @@
+CREATE TABLE shipping_templates (
+    id SERIAL PRIMARY KEY,
+    store_id INTEGER NOT NULL,
+    name VARCHAR NOT NULL,
+    shipping JSONB NOT NULL
+);
+
+CREATE INDEX shipping_templates_store_id_idx ON shipping_templates (store_id);
+
+INSERT INTO role_permissions (role, resource, action, scope) VALUES
+    ('superuser', 'shipping_templates', 'all', 'all'),
+    ('store_manager', 'shipping_templates', 'all', 'owned');
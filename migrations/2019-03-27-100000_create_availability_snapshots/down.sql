@@ -0,0 +1,3 @@
+DELETE FROM role_permissions WHERE resource = 'availability_snapshots';
+
+DROP TABLE IF EXISTS availability_snapshots;
@@ -0,0 +1,10 @@
+CREATE TABLE availability_snapshots (
+    base_product_id INTEGER NOT NULL,
+    delivery_to VARCHAR NOT NULL,
+    packages JSONB NOT NULL,
+    taken_at TIMESTAMP NOT NULL DEFAULT now(),
+    PRIMARY KEY (base_product_id, delivery_to)
+);
+
+INSERT INTO role_permissions (role, resource, action, scope) VALUES
+    ('superuser', 'availability_snapshots', 'all', 'all');
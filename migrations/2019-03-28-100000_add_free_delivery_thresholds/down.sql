@@ -0,0 +1,2 @@
+ALTER TABLE shipping_templates DROP COLUMN IF EXISTS free_delivery_over;
+ALTER TABLE products DROP COLUMN IF EXISTS free_delivery_over;
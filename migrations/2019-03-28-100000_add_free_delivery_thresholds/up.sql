@@ -0,0 +1,2 @@
+ALTER TABLE products ADD COLUMN free_delivery_over DOUBLE PRECISION;
+ALTER TABLE shipping_templates ADD COLUMN free_delivery_over DOUBLE PRECISION;
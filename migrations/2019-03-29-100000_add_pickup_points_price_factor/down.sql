@@ -0,0 +1 @@
+ALTER TABLE pickup_points DROP COLUMN IF EXISTS price_factor;
@@ -0,0 +1 @@
+ALTER TABLE pickup_points ADD COLUMN price_factor DOUBLE PRECISION;
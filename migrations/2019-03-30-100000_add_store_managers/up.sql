@@ -0,0 +1,3 @@
+INSERT INTO role_permissions (role, resource, action, scope) VALUES
+    ('superuser', 'store_managers', 'all', 'all'),
+    ('store_manager', 'store_managers', 'all', 'owned');
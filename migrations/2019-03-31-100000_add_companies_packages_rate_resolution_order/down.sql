@@ -0,0 +1 @@
+ALTER TABLE companies_packages DROP COLUMN IF EXISTS rate_resolution_order;
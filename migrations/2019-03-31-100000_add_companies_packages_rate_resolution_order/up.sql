@@ -0,0 +1 @@
+ALTER TABLE companies_packages ADD COLUMN rate_resolution_order JSONB;
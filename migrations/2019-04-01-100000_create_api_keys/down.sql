@@ -0,0 +1,3 @@
+DELETE FROM role_permissions WHERE resource = 'api_keys';
+
+DROP TABLE IF EXISTS api_keys;
@@ -0,0 +1,2 @@
+ALTER TABLE packages DROP COLUMN IF EXISTS name_translations;
+ALTER TABLE companies DROP COLUMN IF EXISTS label_translations;
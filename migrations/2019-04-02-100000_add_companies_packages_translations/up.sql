@@ -0,0 +1,2 @@
+ALTER TABLE companies ADD COLUMN label_translations JSONB NOT NULL DEFAULT '[]';
+ALTER TABLE packages ADD COLUMN name_translations JSONB NOT NULL DEFAULT '[]';
@@ -0,0 +1 @@
+ALTER TABLE companies_packages DROP COLUMN IF EXISTS insurance_options;
@@ -0,0 +1 @@
+ALTER TABLE companies_packages ADD COLUMN insurance_options JSONB NOT NULL DEFAULT '[]';
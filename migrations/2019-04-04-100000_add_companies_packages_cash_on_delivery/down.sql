@@ -0,0 +1,2 @@
+ALTER TABLE companies_packages DROP COLUMN IF EXISTS cod_fee;
+ALTER TABLE companies_packages DROP COLUMN IF EXISTS cod_supported;
@@ -0,0 +1,2 @@
+ALTER TABLE companies_packages ADD COLUMN cod_supported BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE companies_packages ADD COLUMN cod_fee DOUBLE PRECISION;
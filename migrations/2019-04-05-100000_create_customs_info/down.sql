@@ -0,0 +1,3 @@
+DELETE FROM role_permissions WHERE resource = 'customs_info';
+
+DROP TABLE IF EXISTS customs_info;
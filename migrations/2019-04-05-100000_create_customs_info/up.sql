@@ -0,0 +1,15 @@
+CREATE TABLE customs_info (
+    id SERIAL PRIMARY KEY,
+    base_product_id INTEGER NOT NULL UNIQUE,
+    store_id INTEGER NOT NULL,
+    hs_code VARCHAR NOT NULL,
+    description VARCHAR NOT NULL,
+    declared_value DOUBLE PRECISION NOT NULL,
+    currency VARCHAR NOT NULL,
+    origin_country VARCHAR NOT NULL
+);
+
+INSERT INTO role_permissions (role, resource, action, scope) VALUES
+    ('superuser', 'customs_info', 'all', 'all'),
+    ('user', 'customs_info', 'read', 'all'),
+    ('store_manager', 'customs_info', 'all', 'owned');
@@ -0,0 +1,3 @@
+DELETE FROM role_permissions WHERE resource = 'shipping_restrictions';
+
+DROP TABLE IF EXISTS shipping_restrictions;
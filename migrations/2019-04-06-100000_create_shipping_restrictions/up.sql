@@ -0,0 +1,16 @@
+CREATE TABLE shipping_restrictions (
+    id SERIAL PRIMARY KEY,
+    company_id INTEGER NOT NULL REFERENCES companies (id) ON DELETE CASCADE,
+    to_alpha3 VARCHAR,
+    category VARCHAR,
+    keyword VARCHAR,
+    restriction_type VARCHAR NOT NULL,
+    reason VARCHAR NOT NULL
+);
+
+CREATE INDEX shipping_restrictions_company_idx ON shipping_restrictions (company_id);
+CREATE INDEX shipping_restrictions_to_alpha3_idx ON shipping_restrictions (to_alpha3);
+
+INSERT INTO role_permissions (role, resource, action, scope) VALUES
+    ('superuser', 'shipping_restrictions', 'all', 'all'),
+    ('user', 'shipping_restrictions', 'read', 'all');
@@ -0,0 +1,3 @@
+DELETE FROM role_permissions WHERE resource = 'delivery_quotes';
+
+DROP TABLE IF EXISTS delivery_quotes;
@@ -0,0 +1,16 @@
+CREATE TABLE delivery_quotes (
+    id UUID PRIMARY KEY,
+    company_package_id INTEGER NOT NULL REFERENCES companies_packages (id) ON DELETE CASCADE,
+    currency VARCHAR NOT NULL,
+    price DOUBLE PRECISION NOT NULL,
+    inputs JSONB NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    expires_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX delivery_quotes_expires_at_idx ON delivery_quotes (expires_at);
+
+INSERT INTO role_permissions (role, resource, action, scope) VALUES
+    ('superuser', 'delivery_quotes', 'all', 'all'),
+    ('user', 'delivery_quotes', 'create', 'all'),
+    ('user', 'delivery_quotes', 'read', 'all');
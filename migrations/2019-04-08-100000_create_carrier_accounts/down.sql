@@ -0,0 +1,3 @@
+DELETE FROM role_permissions WHERE resource = 'carrier_accounts';
+
+DROP TABLE IF EXISTS carrier_accounts;
@@ -0,0 +1,14 @@
+CREATE TABLE carrier_accounts (
+    id SERIAL PRIMARY KEY,
+    store_id INTEGER NOT NULL,
+    company_id INTEGER NOT NULL REFERENCES companies (id) ON DELETE CASCADE,
+    encrypted_account_number VARCHAR NOT NULL,
+    encrypted_credentials VARCHAR NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    updated_at TIMESTAMP NOT NULL DEFAULT now(),
+    UNIQUE (store_id, company_id)
+);
+
+INSERT INTO role_permissions (role, resource, action, scope) VALUES
+    ('superuser', 'carrier_accounts', 'all', 'all'),
+    ('store_manager', 'carrier_accounts', 'all', 'owned');
@@ -0,0 +1,2 @@
+ALTER TABLE companies_packages DROP COLUMN IF EXISTS fallback_markup_percent;
+ALTER TABLE companies_packages DROP COLUMN IF EXISTS carrier_fallback;
@@ -0,0 +1,2 @@
+ALTER TABLE companies_packages ADD COLUMN carrier_fallback VARCHAR NOT NULL DEFAULT 'stored_rates';
+ALTER TABLE companies_packages ADD COLUMN fallback_markup_percent DOUBLE PRECISION;
@@ -0,0 +1,3 @@
+DELETE FROM role_permissions WHERE resource = 'pricing_rules';
+
+DROP TABLE IF EXISTS pricing_rules;
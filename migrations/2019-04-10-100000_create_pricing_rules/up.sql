@@ -0,0 +1,16 @@
+CREATE TABLE pricing_rules (
+    id SERIAL PRIMARY KEY,
+    store_id INTEGER NOT NULL,
+    scope VARCHAR NOT NULL,
+    company_package_id INTEGER REFERENCES companies_packages (id) ON DELETE CASCADE,
+    to_alpha3 VARCHAR,
+    kind VARCHAR NOT NULL,
+    direction VARCHAR NOT NULL,
+    value DOUBLE PRECISION NOT NULL,
+    priority INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE INDEX pricing_rules_store_idx ON pricing_rules (store_id);
+
+INSERT INTO role_permissions (role, resource, action, scope) VALUES
+    ('superuser', 'pricing_rules', 'all', 'all');
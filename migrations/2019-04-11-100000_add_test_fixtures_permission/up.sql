@@ -0,0 +1,2 @@
+INSERT INTO role_permissions (role, resource, action, scope) VALUES
+    ('superuser', 'test_fixtures', 'all', 'all');
@@ -0,0 +1,3 @@
+DELETE FROM role_permissions WHERE resource = 'admin_overview';
+INSERT INTO role_permissions (role, resource, action, scope) VALUES
+    ('superuser', 'admin_overview', 'all', 'all');
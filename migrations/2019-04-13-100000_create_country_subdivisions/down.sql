@@ -0,0 +1,10 @@
+ALTER TABLE user_addresses DROP COLUMN subdivision;
+
+DELETE FROM shipping_rates WHERE to_subdivision IS NOT NULL;
+DROP INDEX IF EXISTS shipping_rates_subdivision_idx;
+DROP INDEX IF EXISTS shipping_rates_idx;
+CREATE UNIQUE INDEX shipping_rates_idx ON shipping_rates (company_package_id, from_alpha3, to_alpha3);
+ALTER TABLE shipping_rates DROP CONSTRAINT shipping_rates_subdivision_check;
+ALTER TABLE shipping_rates DROP COLUMN to_subdivision;
+
+DROP TABLE IF EXISTS country_subdivisions;
@@ -0,0 +1,16 @@
+CREATE TABLE country_subdivisions (
+    code VARCHAR PRIMARY KEY,
+    alpha3 VARCHAR NOT NULL,
+    name VARCHAR NOT NULL
+);
+
+CREATE INDEX country_subdivisions_alpha3_idx ON country_subdivisions (alpha3);
+
+ALTER TABLE shipping_rates ADD COLUMN to_subdivision VARCHAR REFERENCES country_subdivisions (code) ON DELETE CASCADE;
+ALTER TABLE shipping_rates ADD CONSTRAINT shipping_rates_subdivision_check CHECK (to_subdivision IS NULL OR to_alpha3 IS NOT NULL);
+
+DROP INDEX shipping_rates_idx;
+CREATE UNIQUE INDEX shipping_rates_idx ON shipping_rates (company_package_id, from_alpha3, to_alpha3) WHERE to_subdivision IS NULL;
+CREATE UNIQUE INDEX shipping_rates_subdivision_idx ON shipping_rates (company_package_id, from_alpha3, to_subdivision);
+
+ALTER TABLE user_addresses ADD COLUMN subdivision VARCHAR REFERENCES country_subdivisions (code) ON DELETE SET NULL;
@@ -0,0 +1,8 @@
+DELETE FROM shipping_rates WHERE to_postal_zone_id IS NOT NULL;
+DROP INDEX IF EXISTS shipping_rates_postal_zone_idx;
+DROP INDEX IF EXISTS shipping_rates_idx;
+CREATE UNIQUE INDEX shipping_rates_idx ON shipping_rates (company_package_id, from_alpha3, to_alpha3) WHERE to_subdivision IS NULL;
+ALTER TABLE shipping_rates DROP CONSTRAINT shipping_rates_postal_zone_check;
+ALTER TABLE shipping_rates DROP COLUMN to_postal_zone_id;
+
+DROP TABLE IF EXISTS postal_zones;
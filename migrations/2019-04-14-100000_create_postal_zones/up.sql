@@ -0,0 +1,18 @@
+CREATE TABLE postal_zones (
+    id SERIAL PRIMARY KEY,
+    alpha3 VARCHAR NOT NULL,
+    name VARCHAR NOT NULL,
+    ranges JSONB NOT NULL DEFAULT '[]'
+);
+
+CREATE UNIQUE INDEX postal_zones_alpha3_name_idx ON postal_zones (alpha3, name);
+
+ALTER TABLE shipping_rates ADD COLUMN to_postal_zone_id INTEGER REFERENCES postal_zones (id) ON DELETE CASCADE;
+ALTER TABLE shipping_rates ADD CONSTRAINT shipping_rates_postal_zone_check CHECK (
+    to_postal_zone_id IS NULL OR (to_alpha3 IS NOT NULL AND to_subdivision IS NULL)
+);
+
+DROP INDEX shipping_rates_idx;
+CREATE UNIQUE INDEX shipping_rates_idx ON shipping_rates (company_package_id, from_alpha3, to_alpha3)
+    WHERE to_subdivision IS NULL AND to_postal_zone_id IS NULL;
+CREATE UNIQUE INDEX shipping_rates_postal_zone_idx ON shipping_rates (company_package_id, from_alpha3, to_postal_zone_id);
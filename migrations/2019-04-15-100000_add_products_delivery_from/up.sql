@@ -0,0 +1 @@
+ALTER TABLE products ADD COLUMN delivery_from VARCHAR;
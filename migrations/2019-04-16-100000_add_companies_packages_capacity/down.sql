@@ -0,0 +1,4 @@
+DELETE FROM role_permissions WHERE resource = 'shipment_bookings';
+DROP TABLE IF EXISTS shipment_bookings;
+ALTER TABLE companies_packages DROP COLUMN IF EXISTS max_daily_shipments;
+ALTER TABLE companies_packages DROP COLUMN IF EXISTS daily_cutoff_time;
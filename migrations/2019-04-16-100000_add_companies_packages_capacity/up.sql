@@ -0,0 +1,13 @@
+ALTER TABLE companies_packages ADD COLUMN daily_cutoff_time TIME;
+ALTER TABLE companies_packages ADD COLUMN max_daily_shipments INTEGER CHECK (max_daily_shipments > 0);
+
+CREATE TABLE shipment_bookings (
+    company_package_id INTEGER NOT NULL REFERENCES companies_packages (id) ON DELETE CASCADE,
+    shipping_date DATE NOT NULL,
+    shipments_count INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (company_package_id, shipping_date)
+);
+
+DELETE FROM role_permissions WHERE resource = 'shipment_bookings';
+INSERT INTO role_permissions (role, resource, action, scope) VALUES
+    ('superuser', 'shipment_bookings', 'all', 'all');
@@ -0,0 +1,3 @@
+DELETE FROM role_permissions WHERE resource = 'test_fixtures';
+INSERT INTO role_permissions (role, resource, action, scope) VALUES
+    ('superuser', 'test_fixtures', 'all', 'all');
@@ -0,0 +1,3 @@
+DELETE FROM role_permissions WHERE resource = 'event_outbox';
+
+DROP TABLE IF EXISTS event_outbox;
@@ -0,0 +1,13 @@
+CREATE TABLE event_outbox (
+    id SERIAL PRIMARY KEY,
+    event JSONB NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    published_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX event_outbox_unpublished_idx ON event_outbox (id) WHERE published_at IS NULL;
+
+DELETE FROM role_permissions WHERE resource = 'event_outbox';
+INSERT INTO role_permissions (role, resource, action, scope) VALUES
+    ('superuser', 'event_outbox', 'all', 'all');
@@ -0,0 +1,2 @@
+ALTER TABLE country_regulations DROP COLUMN max_dimension_cm;
+ALTER TABLE country_regulations ADD COLUMN max_volume_cubic_cm INTEGER;
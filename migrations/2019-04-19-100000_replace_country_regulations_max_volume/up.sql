@@ -0,0 +1,6 @@
+-- Countries limit the longest side of the parcel, not its volume
+ALTER TABLE country_regulations DROP COLUMN max_volume_cubic_cm;
+ALTER TABLE country_regulations ADD COLUMN max_dimension_cm INTEGER;
+
+-- Baseline of the postal union: no side of the parcel is longer than 1.5 m
+UPDATE country_regulations SET max_dimension_cm = 150 WHERE source = 'UPU Convention';
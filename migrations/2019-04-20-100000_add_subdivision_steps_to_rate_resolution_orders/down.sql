@@ -0,0 +1,3 @@
+UPDATE companies_packages
+SET rate_resolution_order = NULLIF(rate_resolution_order - 'postal_zone' - 'subdivision', '[]'::jsonb)
+WHERE rate_resolution_order IS NOT NULL;
@@ -0,0 +1,21 @@
+-- Custom orders stored before the subdivision and postal zone rates get the new steps
+-- right before the exact destination step, or first if the order skips it
+UPDATE companies_packages
+SET rate_resolution_order = (
+    SELECT jsonb_agg(steps.step ORDER BY steps.position)
+    FROM (
+        SELECT stored.step, stored.ordinality * 10 AS position
+        FROM jsonb_array_elements(rate_resolution_order) WITH ORDINALITY AS stored(step, ordinality)
+        UNION ALL
+        SELECT to_jsonb(added.step), COALESCE(exact.ordinality, 0) * 10 - added.offset_from_exact
+        FROM (VALUES ('postal_zone', 2), ('subdivision', 1)) AS added(step, offset_from_exact)
+        LEFT JOIN (
+            SELECT ordinality
+            FROM jsonb_array_elements_text(rate_resolution_order) WITH ORDINALITY AS stored(step, ordinality)
+            WHERE stored.step = 'exact_destination'
+        ) AS exact ON TRUE
+        WHERE NOT rate_resolution_order ? added.step
+    ) AS steps
+)
+WHERE rate_resolution_order IS NOT NULL
+    AND NOT (rate_resolution_order ? 'postal_zone' AND rate_resolution_order ? 'subdivision');
@@ -0,0 +1 @@
+DROP INDEX zones_lower_name_idx;
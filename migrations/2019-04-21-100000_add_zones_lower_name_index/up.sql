@@ -0,0 +1,2 @@
+-- Zones referenced by the rate tables are looked up by their names ignoring the case
+CREATE INDEX zones_lower_name_idx ON zones (lower(name));
@@ -0,0 +1,104 @@
+//! Filtering of the access log, the `Request finished` lines of the controller.
+//!
+//! High-volume routes, like the diagnostics polled by monitoring, are excluded from the log and noisy ones,
+//! like availability, are sampled. Patterns are regexes matched against the operation name of the request,
+//! e.g. `GET /companies/:id/packages`. Mutations and server errors are always logged.
+use rand::{self, Rng};
+use regex::{self, Regex};
+
+use config;
+
+pub struct AccessLogFilter {
+    exclude: Vec<Regex>,
+    /// Patterns with the share of the matching requests to log, the first matching pattern applies
+    sampled: Vec<(Regex, f64)>,
+}
+
+impl AccessLogFilter {
+    /// Compiles the patterns, everything is logged if access log is not configured
+    pub fn new(config: Option<config::AccessLog>) -> Result<Self, regex::Error> {
+        let config = match config {
+            Some(config) => config,
+            None => {
+                return Ok(Self {
+                    exclude: vec![],
+                    sampled: vec![],
+                })
+            }
+        };
+
+        let exclude = config
+            .exclude
+            .iter()
+            .map(|pattern| Regex::new(pattern))
+            .collect::<Result<Vec<_>, _>>()?;
+        let sampled = config
+            .sampled
+            .iter()
+            .map(|sampled| Regex::new(&sampled.pattern).map(|pattern| (pattern, sampled.rate)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { exclude, sampled })
+    }
+
+    /// Whether the request with `operation` name finished with `status` is logged
+    pub fn should_log(&self, method: &str, operation: &str, status: u16) -> bool {
+        self.should_log_with_roll(method, operation, status, rand::thread_rng().gen::<f64>())
+    }
+
+    /// Same as `should_log`, `roll` is a random number in `[0, 1)` the sampling rate is compared with
+    fn should_log_with_roll(&self, method: &str, operation: &str, status: u16, roll: f64) -> bool {
+        let is_read = method == "GET" || method == "HEAD";
+        if !is_read || status >= 500 {
+            return true;
+        }
+
+        if self.exclude.iter().any(|pattern| pattern.is_match(operation)) {
+            return false;
+        }
+
+        match self.sampled.iter().find(|(pattern, _)| pattern.is_match(operation)) {
+            Some(&(_, rate)) => roll < rate,
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter() -> AccessLogFilter {
+        AccessLogFilter::new(Some(config::AccessLog {
+            exclude: vec!["^GET /slo/status$".to_string(), "^GET /diagnostics/".to_string()],
+            sampled: vec![config::SampledRoutes {
+                pattern: "^GET (/v2)?/available_packages".to_string(),
+                rate: 0.1,
+            }],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn excluded_routes_are_not_logged() {
+        assert!(!filter().should_log_with_roll("GET", "GET /diagnostics/connections", 200, 0.0));
+        assert!(filter().should_log_with_roll("GET", "GET /companies/:id", 200, 0.99));
+    }
+
+    #[test]
+    fn sampled_routes_are_logged_at_rate() {
+        let operation = "GET /v2/available_packages_for_user/:id";
+
+        assert!(filter().should_log_with_roll("GET", operation, 200, 0.05));
+        assert!(!filter().should_log_with_roll("GET", operation, 200, 0.5));
+    }
+
+    #[test]
+    fn mutations_and_server_errors_are_always_logged() {
+        assert!(filter().should_log_with_roll("POST", "POST /diagnostics/connections", 200, 0.99));
+        assert!(filter().should_log_with_roll("GET", "GET /available_packages", 500, 0.99));
+        assert!(AccessLogFilter::new(None)
+            .unwrap()
+            .should_log_with_roll("GET", "GET /slo/status", 200, 0.99));
+    }
+}
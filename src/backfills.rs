@@ -0,0 +1,218 @@
+//! Batched backfills for big tables.
+//!
+//! Rows are processed in batches ordered by id, every batch is committed in its own transaction
+//! together with the progress record, so that the table is never locked as a whole
+//! and an interrupted backfill is resumed right after the last processed row.
+use std::thread;
+use std::time::Duration;
+
+use failure::Error as FailureError;
+
+use config;
+use models::{BackfillProgress, BackfillStatus};
+use repos::{BackfillsRepo, DbConnection};
+
+const DEFAULT_BATCH_SIZE: i64 = 1000;
+const DEFAULT_THROTTLE_MS: u64 = 100;
+
+/// Result of processing one batch
+#[derive(Clone, Copy, Debug)]
+pub struct BatchResult {
+    pub processed_rows: i64,
+    pub last_id: i32,
+}
+
+/// Data migration that can be run in batches
+pub trait Backfill<T: DbConnection> {
+    /// Unique name the progress is tracked by
+    fn name(&self) -> String;
+
+    /// Number of rows not processed yet, a resumed backfill counts only the rows left.
+    /// Used only for the progress report
+    fn count_rows(&self, db_conn: &T) -> Result<i64, FailureError>;
+
+    /// Processes at most `batch_size` rows with id greater than `after_id`.
+    /// Returns `None` when there are no rows left
+    fn run_batch(&self, db_conn: &T, after_id: Option<i32>, batch_size: i64) -> Result<Option<BatchResult>, FailureError>;
+}
+
+#[derive(Clone, Debug)]
+pub struct BackfillSettings {
+    pub batch_size: i64,
+    /// Pause between batches, leaves room for the regular load on the table
+    pub throttle: Duration,
+}
+
+impl Default for BackfillSettings {
+    fn default() -> Self {
+        Self {
+            batch_size: DEFAULT_BATCH_SIZE,
+            throttle: Duration::from_millis(DEFAULT_THROTTLE_MS),
+        }
+    }
+}
+
+impl<'a> From<&'a config::Backfills> for BackfillSettings {
+    fn from(config: &'a config::Backfills) -> Self {
+        Self {
+            batch_size: config.batch_size,
+            throttle: Duration::from_millis(config.throttle_ms),
+        }
+    }
+}
+
+/// Runs the backfill to the end, skips it if it has already finished
+pub fn run_backfill<T, B>(
+    db_conn: &T,
+    backfills_repo: &dyn BackfillsRepo,
+    backfill: &B,
+    settings: &BackfillSettings,
+) -> Result<BackfillProgress, FailureError>
+where
+    T: DbConnection,
+    B: Backfill<T>,
+{
+    let name = backfill.name();
+
+    if let Some(progress) = backfills_repo.find(name.clone())? {
+        if progress.status == BackfillStatus::Finished {
+            return Ok(progress);
+        }
+    }
+
+    let rows_left = backfill.count_rows(db_conn)?;
+    let mut progress = backfills_repo.start(name.clone(), rows_left)?;
+    info!(
+        "Backfill {} started from id {:?}, rows left: {}",
+        name, progress.last_processed_id, rows_left
+    );
+
+    loop {
+        let after_id = progress.last_processed_id;
+        let batch = db_conn.transaction::<_, FailureError, _>(|| match backfill.run_batch(db_conn, after_id, settings.batch_size)? {
+            None => Ok(None),
+            Some(batch) => backfills_repo
+                .record_batch(name.clone(), batch.processed_rows, batch.last_id)
+                .map(Some),
+        });
+
+        match batch {
+            Ok(Some(updated)) => {
+                debug!(
+                    "Backfill {}: {} of {} rows processed",
+                    name, updated.processed_rows, updated.total_rows
+                );
+                progress = updated;
+                thread::sleep(settings.throttle);
+            }
+            Ok(None) => {
+                info!("Backfill {} finished, rows: {}", name, progress.processed_rows);
+                return backfills_repo.finish(name);
+            }
+            Err(e) => {
+                error!("Backfill {} failed after id {:?}: {}", name, after_id, e);
+                backfills_repo.fail(name.clone(), e.to_string())?;
+                return Err(e.context(format!("Backfill {} failed", name)).into());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use repos::repo_factory::tests::*;
+
+    use super::*;
+
+    struct IdsBackfill {
+        ids: Vec<i32>,
+        fail_after: Option<i32>,
+        batches: RefCell<Vec<Vec<i32>>>,
+    }
+
+    impl IdsBackfill {
+        fn new(ids: Vec<i32>, fail_after: Option<i32>) -> Self {
+            Self {
+                ids,
+                fail_after,
+                batches: RefCell::new(vec![]),
+            }
+        }
+    }
+
+    impl Backfill<MockConnection> for IdsBackfill {
+        fn name(&self) -> String {
+            "ids".to_string()
+        }
+
+        fn count_rows(&self, _db_conn: &MockConnection) -> Result<i64, FailureError> {
+            Ok(self.ids.len() as i64)
+        }
+
+        fn run_batch(
+            &self,
+            _db_conn: &MockConnection,
+            after_id: Option<i32>,
+            batch_size: i64,
+        ) -> Result<Option<BatchResult>, FailureError> {
+            if self.fail_after.is_some() && after_id >= self.fail_after {
+                return Err(format_err!("Batch after {:?} failed", after_id));
+            }
+
+            let batch = self
+                .ids
+                .iter()
+                .cloned()
+                .filter(|id| after_id.map(|after_id| *id > after_id).unwrap_or(true))
+                .take(batch_size as usize)
+                .collect::<Vec<_>>();
+
+            let result = batch.last().map(|last_id| BatchResult {
+                processed_rows: batch.len() as i64,
+                last_id: *last_id,
+            });
+
+            if !batch.is_empty() {
+                self.batches.borrow_mut().push(batch);
+            }
+
+            Ok(result)
+        }
+    }
+
+    fn sys_backfills_repo() -> BackfillsRepoMock {
+        BackfillsRepoMock {
+            user_id: Some(MOCK_USER_ID),
+        }
+    }
+
+    fn settings() -> BackfillSettings {
+        BackfillSettings {
+            batch_size: 2,
+            throttle: Duration::from_millis(0),
+        }
+    }
+
+    #[test]
+    fn run_backfill_processes_all_rows_in_batches() {
+        let db_conn = MockConnection::default();
+        let backfill = IdsBackfill::new(vec![1, 2, 3, 4, 5], None);
+
+        let progress = run_backfill(&db_conn, &sys_backfills_repo(), &backfill, &settings()).unwrap();
+
+        assert_eq!(progress.status, BackfillStatus::Finished);
+        assert_eq!(backfill.batches.into_inner(), vec![vec![1, 2], vec![3, 4], vec![5]]);
+    }
+
+    #[test]
+    fn run_backfill_stops_on_failed_batch() {
+        let db_conn = MockConnection::default();
+        let backfill = IdsBackfill::new(vec![1, 2, 3, 4, 5], Some(2));
+
+        run_backfill(&db_conn, &sys_backfills_repo(), &backfill, &settings()).unwrap_err();
+
+        assert_eq!(backfill.batches.into_inner(), vec![vec![1, 2]]);
+    }
+}
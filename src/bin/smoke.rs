@@ -0,0 +1,323 @@
+//! Smoke test of a deployed delivery instance, run after every deploy.
+//!
+//! Goes through the scenario of a seller through the REST API: creates a company, a package,
+//! a company package with rates and shipping of a product, then checks that the product is
+//! available for the buyer and the delivery price is calculated. Everything created is deleted
+//! in the end, whatever the result. Exits with non-zero code if any step fails.
+//!
+//! Usage: `smoke <delivery url>`, e.g. `smoke http://delivery:8000`. The url may also be set in
+//! `SMOKE_DELIVERY_URL`. Requests are made on behalf of the superuser, the id of which is
+//! `SMOKE_USER_ID` (1 by default).
+
+extern crate base64;
+extern crate delivery_lib;
+#[macro_use]
+extern crate failure;
+extern crate futures;
+extern crate hyper;
+extern crate rand;
+extern crate serde;
+extern crate serde_json;
+extern crate stq_http;
+extern crate stq_static_resources;
+extern crate stq_types;
+extern crate tokio_core;
+
+use std::env;
+use std::process;
+use std::str::FromStr;
+
+use failure::{Error as FailureError, Fail};
+use futures::Stream;
+use hyper::Method;
+use rand::Rng;
+use serde::de::DeserializeOwned;
+use tokio_core::reactor::Core;
+
+use stq_http::client::{Client as HttpClient, ClientHandle as HttpClientHandle, Config as HttpConfig};
+use stq_static_resources::Currency;
+use stq_types::*;
+
+use delivery_lib::models::*;
+use delivery_lib::services::companies_packages::{DeliveryPrice, ReplaceShippingRatesPayload};
+
+const DELIVERY_FROM: &str = "RUS";
+const DELIVERY_TO: &str = "USA";
+const VOLUME_CUBIC_CM: u32 = 1000;
+const WEIGHT_G: u32 = 500;
+
+/// Ids of the objects created by the scenario, deleted in the end
+#[derive(Default)]
+struct Created {
+    company_id: Option<CompanyId>,
+    package_id: Option<PackageId>,
+    company_package_id: Option<CompanyPackageId>,
+    base_product_id: Option<BaseProductId>,
+}
+
+struct Smoke {
+    core: Core,
+    client_handle: HttpClientHandle,
+    base_url: String,
+    user_id: UserId,
+    /// Suffix of the names, so that parallel runs do not conflict
+    run_id: u32,
+}
+
+impl Smoke {
+    fn new(base_url: String, user_id: UserId) -> Self {
+        let core = Core::new().expect("Unexpected error creating event loop core");
+        let client = HttpClient::new(
+            &HttpConfig {
+                http_client_retries: 3,
+                http_client_buffer_size: 3,
+                timeout_duration_ms: 10000,
+            },
+            &core.handle(),
+        );
+        let client_handle = client.handle();
+        core.handle().spawn(client.stream().for_each(|_| Ok(())));
+
+        Smoke {
+            core,
+            client_handle,
+            base_url: base_url.trim_right_matches('/').to_string(),
+            user_id,
+            run_id: rand::thread_rng().gen_range(100_000, 1_000_000),
+        }
+    }
+
+    fn request<T: DeserializeOwned + Send + 'static>(
+        &mut self,
+        method: Method,
+        path: &str,
+        body: Option<String>,
+    ) -> Result<T, FailureError> {
+        let url = format!("{}{}", self.base_url, path);
+        let description = format!("{} {}", method, url);
+        let request = self
+            .client_handle
+            .request_with_auth_header::<T>(method, url, body, Some(self.user_id.to_string()));
+
+        self.core
+            .run(request)
+            .map_err(|e| e.context(format!("Request {} failed", description)).into())
+    }
+
+    fn step<T, F: FnOnce(&mut Self) -> Result<T, FailureError>>(&mut self, name: &str, f: F) -> Result<T, FailureError> {
+        let res = f(self);
+        match res {
+            Ok(_) => println!("[ok] {}", name),
+            Err(ref e) => {
+                println!("[failed] {}", name);
+                for cause in e.iter_chain() {
+                    println!("    {}", cause);
+                }
+            }
+        }
+        res
+    }
+
+    fn run(&mut self, created: &mut Created) -> Result<(), FailureError> {
+        let run_id = self.run_id;
+
+        let company = self.step("create company", |smoke| {
+            let payload = NewCompany {
+                name: format!("Smoke test {}", run_id),
+                label: format!("SMOKE{}", run_id),
+                description: Some("Created by the smoke test, safe to delete".to_string()),
+                deliveries_from: vec![Alpha3(DELIVERY_FROM.to_string())],
+                logo: "".to_string(),
+                currency: Currency::RUB,
+                capabilities: vec![],
+                label_translations: vec![],
+            };
+            smoke.request::<Company>(Method::Post, "/companies", Some(serde_json::to_string(&payload)?))
+        })?;
+        created.company_id = Some(company.id);
+
+        let package = self.step("create package", |smoke| {
+            let payload = NewPackages {
+                name: format!("Smoke test {}", run_id),
+                max_size: VOLUME_CUBIC_CM * 10,
+                min_size: 0,
+                max_weight: WEIGHT_G * 10,
+                min_weight: 0,
+                deliveries_to: vec![Alpha3(DELIVERY_TO.to_string())],
+                deliveries_to_zones: vec![],
+                name_translations: vec![],
+            };
+            smoke.request::<Packages>(Method::Post, "/packages", Some(serde_json::to_string(&payload)?))
+        })?;
+        created.package_id = Some(package.id);
+
+        let company_package = self.step("create company package", |smoke| {
+            let payload = NewCompanyPackage {
+                company_id: company.id,
+                package_id: package.id,
+                shipping_rate_source: Some(ShippingRateSource::Static { dimensional_factor: None }),
+                restricted_hazard_classes: vec![],
+                delivery_time_min_days: Some(3),
+                delivery_time_max_days: Some(7),
+                rate_resolution_order: None,
+                insurance_options: vec![],
+                cod_supported: false,
+                cod_fee: None,
+                carrier_fallback: CarrierFallback::StoredRates,
+                fallback_markup_percent: None,
+                daily_cutoff_time: None,
+                max_daily_shipments: None,
+            };
+            smoke.request::<CompanyPackage>(Method::Post, "/companies_packages", Some(serde_json::to_string(&payload)?))
+        })?;
+        created.company_package_id = Some(company_package.id);
+
+        self.step("replace shipping rates", |smoke| {
+            let payload = ReplaceShippingRatesPayload {
+                rates_csv_base64: base64::encode("Weight,Zone\n,1\n1,100\n5,300\n"),
+                zones_csv_base64: base64::encode(&format!("From,To,Zone\n{},{},1\n", DELIVERY_FROM, DELIVERY_TO)),
+                delivery_time_min_days: None,
+                delivery_time_max_days: None,
+            };
+            smoke.request::<serde_json::Value>(
+                Method::Post,
+                &format!("/companies_packages/{}/rates", company_package.id),
+                Some(serde_json::to_string(&payload)?),
+            )
+        })?;
+
+        let base_product_id = BaseProductId(rand::thread_rng().gen_range(1_000_000_000, 2_000_000_000));
+        created.base_product_id = Some(base_product_id);
+        self.step("create shipping of the product", |smoke| {
+            let payload = NewShipping {
+                items: vec![NewProducts {
+                    base_product_id,
+                    store_id: StoreId(base_product_id.0),
+                    company_package_id: company_package.id,
+                    price: None,
+                    deliveries_to: vec![Alpha3(DELIVERY_TO.to_string())],
+                    shipping: ShippingVariant::International,
+                    measurements: Some(ShipmentMeasurements {
+                        volume_cubic_cm: VOLUME_CUBIC_CM,
+                        weight_g: WEIGHT_G,
+                    }),
+                    delivery_from: Some(Alpha3(DELIVERY_FROM.to_string())),
+                    currency: Currency::RUB,
+                    hazard_classes: vec![],
+                    free_delivery_over: None,
+                }],
+                pickup: None,
+                hazard_classes: vec![],
+            };
+            smoke.request::<Shipping>(
+                Method::Post,
+                &format!("/products/{}", base_product_id),
+                Some(serde_json::to_string(&payload)?),
+            )
+        })?;
+
+        self.step("query available packages", |smoke| {
+            let shipping = smoke.request::<AvailableShippingForUser>(
+                Method::Get,
+                &format!(
+                    "/v2/available_packages_for_user/{}?delivery_from={}&delivery_to={}&volume={}&weight={}",
+                    base_product_id, DELIVERY_FROM, DELIVERY_TO, VOLUME_CUBIC_CM, WEIGHT_G
+                ),
+                None,
+            )?;
+            if shipping.packages.iter().any(|package| package.id == company_package.id) {
+                Ok(())
+            } else {
+                Err(format_err!(
+                    "Company package {} is not available for the product {}",
+                    company_package.id,
+                    base_product_id
+                ))
+            }
+        })?;
+
+        self.step("query delivery price", |smoke| {
+            let price = smoke.request::<Option<DeliveryPrice>>(
+                Method::Get,
+                &format!(
+                    "/companies_packages/{}/price?from={}&to={}&volume={}&weight={}",
+                    company_package.id, DELIVERY_FROM, DELIVERY_TO, VOLUME_CUBIC_CM, WEIGHT_G
+                ),
+                None,
+            )?;
+            match price {
+                Some(ref price) if price.value > 0.0 => Ok(()),
+                _ => Err(format_err!(
+                    "Unexpected delivery price {:?} of the company package {}",
+                    price,
+                    company_package.id
+                )),
+            }
+        })
+    }
+
+    /// Deletes everything created by the scenario in reverse order, returns false if anything is left
+    fn clean_up(&mut self, created: &Created) -> bool {
+        let mut cleaned_up = true;
+
+        if let Some(base_product_id) = created.base_product_id {
+            cleaned_up &= self
+                .step("delete shipping of the product", |smoke| {
+                    smoke.request::<serde_json::Value>(Method::Delete, &format!("/products/{}", base_product_id), None)
+                })
+                .is_ok();
+        }
+        if let (Some(company_id), Some(package_id), Some(_)) = (created.company_id, created.package_id, created.company_package_id) {
+            cleaned_up &= self
+                .step("delete company package", |smoke| {
+                    smoke.request::<serde_json::Value>(Method::Delete, &format!("/companies/{}/packages/{}", company_id, package_id), None)
+                })
+                .is_ok();
+        }
+        if let Some(package_id) = created.package_id {
+            cleaned_up &= self
+                .step("delete package", |smoke| {
+                    smoke.request::<serde_json::Value>(Method::Delete, &format!("/packages/{}", package_id), None)
+                })
+                .is_ok();
+        }
+        if let Some(company_id) = created.company_id {
+            cleaned_up &= self
+                .step("delete company", |smoke| {
+                    smoke.request::<serde_json::Value>(Method::Delete, &format!("/companies/{}", company_id), None)
+                })
+                .is_ok();
+        }
+
+        cleaned_up
+    }
+}
+
+fn main() {
+    let base_url = match env::args().nth(1).or_else(|| env::var("SMOKE_DELIVERY_URL").ok()) {
+        Some(base_url) => base_url,
+        None => {
+            eprintln!("Usage: smoke <delivery url>");
+            process::exit(2);
+        }
+    };
+    let user_id = env::var("SMOKE_USER_ID")
+        .ok()
+        .map(|user_id| i32::from_str(&user_id).expect("SMOKE_USER_ID must be a number"))
+        .map(UserId)
+        .unwrap_or(UserId(1));
+
+    println!("Running smoke test against {}", base_url);
+
+    let mut smoke = Smoke::new(base_url, user_id);
+    let mut created = Created::default();
+    let run_res = smoke.run(&mut created);
+    let cleaned_up = smoke.clean_up(&created);
+
+    if run_res.is_err() || !cleaned_up {
+        println!("Smoke test failed");
+        process::exit(1);
+    }
+
+    println!("Smoke test passed");
+}
@@ -0,0 +1,90 @@
+//! ShippingRatesCache is a module that caches shipping rates of the routes read from db by the delivery price requests.
+//! Rates of the whole route are cached, so a single entry serves prices of any weight and volume on the route.
+//! Entries are removed when the rates of the route are replaced, other changes, like deactivation of the rates
+//! by the integrity check or a change of a zone, are picked up when the entry expires
+use std::fmt;
+
+use failure::Fail;
+use stq_cache::cache::Cache;
+use stq_types::{Alpha3, CompanyPackageId};
+
+use models::ShippingRates;
+use request_context::log_line;
+
+/// Route the rates are looked up for
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShippingRatesCacheKey {
+    pub company_package_id: CompanyPackageId,
+    pub delivery_from: Alpha3,
+    pub delivery_to: Alpha3,
+}
+
+impl fmt::Display for ShippingRatesCacheKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.company_package_id, self.delivery_from, self.delivery_to)
+    }
+}
+
+/// Rates of the route, routes without rates are cached too, so that they don't hit db either
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CachedShippingRates {
+    pub rates: Option<ShippingRates>,
+}
+
+pub trait ShippingRatesCache: Send + Sync {
+    fn get(&self, key: &ShippingRatesCacheKey) -> Option<CachedShippingRates>;
+
+    fn set(&self, key: &ShippingRatesCacheKey, rates: CachedShippingRates);
+
+    fn remove(&self, key: &ShippingRatesCacheKey) -> bool;
+}
+
+pub struct ShippingRatesCacheImpl<C>
+where
+    C: Cache<CachedShippingRates>,
+{
+    cache: C,
+}
+
+impl<C> ShippingRatesCacheImpl<C>
+where
+    C: Cache<CachedShippingRates>,
+{
+    pub fn new(cache: C) -> Self {
+        ShippingRatesCacheImpl { cache }
+    }
+}
+
+impl<C> ShippingRatesCache for ShippingRatesCacheImpl<C>
+where
+    C: Cache<CachedShippingRates> + Send + Sync,
+{
+    fn get(&self, key: &ShippingRatesCacheKey) -> Option<CachedShippingRates> {
+        debug!("{}", log_line(&format!("Getting rates from ShippingRatesCache at key '{}'", key)));
+
+        self.cache.get(key.to_string().as_str()).unwrap_or_else(|err| {
+            let err = err.context(format!("Failed to get rates from ShippingRatesCache at key '{}'", key));
+            error!("{}", log_line(&err.to_string()));
+            None
+        })
+    }
+
+    fn set(&self, key: &ShippingRatesCacheKey, rates: CachedShippingRates) {
+        debug!("{}", log_line(&format!("Setting rates in ShippingRatesCache at key '{}'", key)));
+
+        self.cache.set(key.to_string().as_str(), rates).unwrap_or_else(|err| {
+            let err = err.context(format!("Failed to set rates in ShippingRatesCache at key '{}'", key));
+            error!("{}", log_line(&err.to_string()));
+        })
+    }
+
+    fn remove(&self, key: &ShippingRatesCacheKey) -> bool {
+        debug!("{}", log_line(&format!("Removing rates from ShippingRatesCache at key '{}'", key)));
+
+        self.cache.remove(key.to_string().as_str()).unwrap_or_else(|err| {
+            let err = err.context(format!("Failed to remove rates from ShippingRatesCache at key '{}'", key));
+            error!("{}", log_line(&err.to_string()));
+            false
+        })
+    }
+}
@@ -0,0 +1,39 @@
+//! CarrierRatesCache is a module that caches rates received from carrier APIs
+
+use failure::Fail;
+use stq_cache::cache::Cache;
+
+pub struct CarrierRatesCacheImpl<C>
+where
+    C: Cache<f64>,
+{
+    cache: C,
+}
+
+impl<C> CarrierRatesCacheImpl<C>
+where
+    C: Cache<f64>,
+{
+    pub fn new(cache: C) -> Self {
+        CarrierRatesCacheImpl { cache }
+    }
+
+    pub fn get(&self, key: &str) -> Option<f64> {
+        debug!("Getting rate from CarrierRatesCache at key '{}'", key);
+
+        self.cache.get(key).unwrap_or_else(|err| {
+            let err = err.context(format!("Failed to get rate from CarrierRatesCache at key '{}'", key));
+            error!("{}", err);
+            None
+        })
+    }
+
+    pub fn set(&self, key: &str, price: f64) {
+        debug!("Setting rate in CarrierRatesCache at key '{}'", key);
+
+        self.cache.set(key, price).unwrap_or_else(|err| {
+            let err = err.context(format!("Failed to set rate in CarrierRatesCache at key '{}'", key));
+            error!("{}", err);
+        })
+    }
+}
@@ -0,0 +1,205 @@
+//! Carriers is a module responsible for getting live shipping rates from carrier APIs
+pub mod cache;
+pub mod resilience;
+
+pub use self::cache::CarrierRatesCacheImpl;
+pub use self::resilience::{CarrierCallStats, ResilienceSettings, ResilientCalls};
+
+use std::fmt;
+use std::sync::Arc;
+
+use failure::Error as FailureError;
+use failure::Fail;
+use futures::future;
+use futures::Future;
+use hyper::Method;
+use serde_json;
+
+use stq_cache::cache::Cache;
+use stq_http::client::ClientHandle;
+use stq_types::{Alpha3, CompanyPackageId};
+
+use errors::Error;
+use models::ShipmentMeasurements;
+use request_context::RequestContext;
+
+/// Carriers client Future
+pub type CarrierFuture<T> = Box<dyn Future<Item = T, Error = FailureError>>;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CarrierRateRequest {
+    pub company_package_id: CompanyPackageId,
+    pub company_label: String,
+    pub delivery_from: Alpha3,
+    pub delivery_to: Alpha3,
+    pub measurements: ShipmentMeasurements,
+    /// Account of the store with the carrier, the carrier answers with the negotiated rate of the account
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<CarrierAccountCredentials>,
+}
+
+/// Decrypted credentials of the carrier account of the store
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CarrierAccountCredentials {
+    pub account_number: String,
+    pub credentials: String,
+}
+
+impl fmt::Debug for CarrierAccountCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CarrierAccountCredentials")
+            .field("account_number", &self.account_number)
+            .field("credentials", &"<hidden>")
+            .finish()
+    }
+}
+
+impl CarrierRateRequest {
+    fn cache_key(&self) -> String {
+        let key = format!(
+            "{}:{}:{}:{}:{}",
+            self.company_package_id, self.delivery_from, self.delivery_to, self.measurements.volume_cubic_cm, self.measurements.weight_g
+        );
+        // negotiated rates differ between the accounts and from the marketplace rates
+        match self.account {
+            Some(ref account) => format!("{}:{}", key, account.account_number),
+            None => key,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CarrierRate {
+    pub price: f64,
+}
+
+pub trait CarriersClient {
+    /// Returns the current delivery price from the carrier, the request is traced with the context of the incoming one
+    fn get_rate(&self, request: CarrierRateRequest, request_context: &RequestContext) -> CarrierFuture<f64>;
+
+    /// Counters of the calls to the carriers and the states of their circuit breakers
+    fn stats(&self) -> Vec<CarrierCallStats> {
+        vec![]
+    }
+}
+
+/// Requests rates from the carriers gateway, caching them since carrier rates change at most daily.
+/// Failed requests are retried and every carrier has its own circuit breaker, see `resilience`
+pub struct HttpCarriersClient<C>
+where
+    C: Cache<f64>,
+{
+    client_handle: ClientHandle,
+    url: String,
+    cache: Arc<CarrierRatesCacheImpl<C>>,
+    calls: ResilientCalls,
+}
+
+impl<C> HttpCarriersClient<C>
+where
+    C: Cache<f64>,
+{
+    pub fn new(client_handle: ClientHandle, url: String, cache: CarrierRatesCacheImpl<C>, calls: ResilientCalls) -> Self {
+        HttpCarriersClient {
+            client_handle,
+            url,
+            cache: Arc::new(cache),
+            calls,
+        }
+    }
+}
+
+impl<C> CarriersClient for HttpCarriersClient<C>
+where
+    C: Cache<f64> + 'static,
+{
+    fn get_rate(&self, request: CarrierRateRequest, request_context: &RequestContext) -> CarrierFuture<f64> {
+        let key = request.cache_key();
+        if let Some(price) = self.cache.get(&key) {
+            return Box::new(future::ok(price));
+        }
+
+        info!(
+            "{}",
+            request_context.log_line_with(
+                "Requesting carrier rate",
+                json!({ "company_package_id": request.company_package_id }),
+            )
+        );
+
+        let body = match serde_json::to_string(&request) {
+            Ok(body) => body,
+            Err(e) => return Box::new(future::err(e.context(Error::Parse).into())),
+        };
+        let url = format!("{}/rates", self.url);
+        let cache = self.cache.clone();
+        let client_handle = self.client_handle.clone();
+        let headers = request_context.headers();
+
+        Box::new(
+            self.calls
+                .execute(&request.company_label, move || -> CarrierFuture<CarrierRate> {
+                    Box::new(
+                        client_handle
+                            .request_json::<CarrierRate>(Method::Post, url.clone(), Some(body.clone()), Some(headers.clone()))
+                            .map_err(|e| e.context(Error::HttpClient).into()),
+                    )
+                })
+                .map(move |CarrierRate { price }| {
+                    cache.set(&key, price);
+                    price
+                }),
+        )
+    }
+
+    fn stats(&self) -> Vec<CarrierCallStats> {
+        self.calls.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rate_request(account: Option<CarrierAccountCredentials>) -> CarrierRateRequest {
+        CarrierRateRequest {
+            company_package_id: CompanyPackageId(1),
+            company_label: "UPS".to_string(),
+            delivery_from: Alpha3("RUS".to_string()),
+            delivery_to: Alpha3("USA".to_string()),
+            measurements: ShipmentMeasurements {
+                volume_cubic_cm: 1000,
+                weight_g: 500,
+            },
+            account,
+        }
+    }
+
+    #[test]
+    fn rates_are_cached_by_route_measurements_and_account() {
+        let request = rate_request(None);
+        assert_eq!(request.cache_key(), rate_request(None).cache_key());
+
+        let heavier = CarrierRateRequest {
+            measurements: ShipmentMeasurements {
+                volume_cubic_cm: 1000,
+                weight_g: 600,
+            },
+            ..rate_request(None)
+        };
+        assert_ne!(request.cache_key(), heavier.cache_key());
+
+        let other_destination = CarrierRateRequest {
+            delivery_to: Alpha3("CAN".to_string()),
+            ..rate_request(None)
+        };
+        assert_ne!(request.cache_key(), other_destination.cache_key());
+
+        let negotiated = rate_request(Some(CarrierAccountCredentials {
+            account_number: "A1".to_string(),
+            credentials: "secret".to_string(),
+        }));
+        assert_ne!(request.cache_key(), negotiated.cache_key());
+        assert!(!negotiated.cache_key().contains("secret"));
+    }
+}
@@ -0,0 +1,402 @@
+//! Retries and circuit breakers of the outbound calls to the carriers.
+//!
+//! Calls failed in transport or with a server error of the carrier are retried with exponential backoff,
+//! other failures are returned right away since they would fail the same way again. Every carrier has its own circuit breaker:
+//! after `breaker_failure_threshold` calls in a row have failed, calls to the carrier are rejected
+//! without being sent for `breaker_open_sec`, then a single trial call decides whether the carrier
+//! is back. Rejected calls fall back to the stored rates right away instead of waiting for the timeout
+//! of a carrier that is down. Counters of the calls are reported by `GET /admin/stats`.
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use failure::Error as FailureError;
+use failure::Fail;
+use futures::future::{self, Loop};
+use futures::Future;
+use hyper;
+use stq_http::client::Error as HttpClientError;
+use tokio_core::reactor::{Handle, Timeout};
+
+use super::CarrierFuture;
+use config;
+use errors::Error;
+
+/// Retries of a failed call if they are not configured
+pub const DEFAULT_MAX_RETRIES: u32 = 2;
+/// Delay before the first retry if it is not configured, every next delay is twice as long
+pub const DEFAULT_RETRY_BASE_DELAY_MS: u64 = 100;
+pub const DEFAULT_RETRY_MAX_DELAY_MS: u64 = 2000;
+/// Failed calls in a row opening the circuit breaker if they are not configured
+pub const DEFAULT_BREAKER_FAILURE_THRESHOLD: u32 = 5;
+pub const DEFAULT_BREAKER_OPEN_SEC: u64 = 30;
+
+#[derive(Clone, Debug)]
+pub struct ResilienceSettings {
+    pub max_retries: u32,
+    pub retry_base_delay: Duration,
+    pub retry_max_delay: Duration,
+    pub breaker_failure_threshold: u32,
+    pub breaker_open: Duration,
+}
+
+impl Default for ResilienceSettings {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_MAX_RETRIES,
+            retry_base_delay: Duration::from_millis(DEFAULT_RETRY_BASE_DELAY_MS),
+            retry_max_delay: Duration::from_millis(DEFAULT_RETRY_MAX_DELAY_MS),
+            breaker_failure_threshold: DEFAULT_BREAKER_FAILURE_THRESHOLD,
+            breaker_open: Duration::from_secs(DEFAULT_BREAKER_OPEN_SEC),
+        }
+    }
+}
+
+impl<'a> From<&'a config::Carriers> for ResilienceSettings {
+    fn from(config: &'a config::Carriers) -> Self {
+        let defaults = Self::default();
+        Self {
+            max_retries: config.max_retries.unwrap_or(defaults.max_retries),
+            retry_base_delay: config
+                .retry_base_delay_ms
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.retry_base_delay),
+            retry_max_delay: config
+                .retry_max_delay_ms
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.retry_max_delay),
+            breaker_failure_threshold: config.breaker_failure_threshold.unwrap_or(defaults.breaker_failure_threshold),
+            breaker_open: config.breaker_open_sec.map(Duration::from_secs).unwrap_or(defaults.breaker_open),
+        }
+    }
+}
+
+impl ResilienceSettings {
+    /// Delay before the retry, `retry` starts from 0
+    pub fn retry_delay(&self, retry: u32) -> Duration {
+        let factor = 1u32.checked_shl(retry).unwrap_or(u32::max_value());
+        self.retry_base_delay
+            .checked_mul(factor)
+            .map(|delay| delay.min(self.retry_max_delay))
+            .unwrap_or(self.retry_max_delay)
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    Closed,
+    /// Calls are rejected without being sent
+    Open,
+    /// Trial call is allowed to find out whether the carrier is back
+    HalfOpen,
+}
+
+/// Counters of the calls to the carrier since the start of the service
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct CarrierCallStats {
+    pub carrier: String,
+    pub state: BreakerState,
+    pub calls: usize,
+    pub failed_calls: usize,
+    pub retries: usize,
+    /// Calls rejected by the open circuit breaker
+    pub rejected_calls: usize,
+}
+
+#[derive(Default)]
+struct CarrierBreaker {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    trial_in_flight: bool,
+    calls: usize,
+    failed_calls: usize,
+    retries: usize,
+    rejected_calls: usize,
+}
+
+impl CarrierBreaker {
+    fn state(&self, now: Instant, open: Duration) -> BreakerState {
+        match self.opened_at {
+            None => BreakerState::Closed,
+            Some(opened_at) if now.duration_since(opened_at) < open => BreakerState::Open,
+            Some(_) => BreakerState::HalfOpen,
+        }
+    }
+}
+
+/// Circuit breakers of all carriers, carriers are told apart by the company label
+pub struct CircuitBreakers {
+    settings: ResilienceSettings,
+    carriers: Mutex<HashMap<String, CarrierBreaker>>,
+}
+
+impl CircuitBreakers {
+    pub fn new(settings: ResilienceSettings) -> Self {
+        Self {
+            settings,
+            carriers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn carriers(&self) -> MutexGuard<HashMap<String, CarrierBreaker>> {
+        self.carriers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns `false` if the call to the carrier must be rejected
+    pub fn try_acquire(&self, carrier: &str, now: Instant) -> bool {
+        let mut carriers = self.carriers();
+        let breaker = carriers.entry(carrier.to_string()).or_insert_with(CarrierBreaker::default);
+
+        let allowed = match breaker.state(now, self.settings.breaker_open) {
+            BreakerState::Closed => true,
+            BreakerState::Open => false,
+            BreakerState::HalfOpen if breaker.trial_in_flight => false,
+            BreakerState::HalfOpen => {
+                breaker.trial_in_flight = true;
+                true
+            }
+        };
+
+        if allowed {
+            breaker.calls += 1;
+        } else {
+            breaker.rejected_calls += 1;
+        }
+        allowed
+    }
+
+    pub fn record_retry(&self, carrier: &str) {
+        if let Some(breaker) = self.carriers().get_mut(carrier) {
+            breaker.retries += 1;
+        }
+    }
+
+    /// Records the outcome of the call after all retries
+    pub fn record_result(&self, carrier: &str, success: bool, now: Instant) {
+        let threshold = self.settings.breaker_failure_threshold;
+        let mut carriers = self.carriers();
+        let breaker = match carriers.get_mut(carrier) {
+            Some(breaker) => breaker,
+            None => return,
+        };
+
+        let was_trial = breaker.trial_in_flight;
+        breaker.trial_in_flight = false;
+        if success {
+            breaker.consecutive_failures = 0;
+            breaker.opened_at = None;
+            return;
+        }
+
+        breaker.failed_calls += 1;
+        breaker.consecutive_failures += 1;
+        if was_trial || breaker.consecutive_failures >= threshold {
+            if breaker.opened_at.is_none() || was_trial {
+                warn!(
+                    "Circuit breaker of carrier {} is open after {} failed calls",
+                    carrier, breaker.consecutive_failures
+                );
+            }
+            breaker.opened_at = Some(now);
+        }
+    }
+
+    pub fn stats(&self, now: Instant) -> Vec<CarrierCallStats> {
+        let mut stats = self
+            .carriers()
+            .iter()
+            .map(|(carrier, breaker)| CarrierCallStats {
+                carrier: carrier.clone(),
+                state: breaker.state(now, self.settings.breaker_open),
+                calls: breaker.calls,
+                failed_calls: breaker.failed_calls,
+                retries: breaker.retries,
+                rejected_calls: breaker.rejected_calls,
+            })
+            .collect::<Vec<_>>();
+        stats.sort_by(|a, b| a.carrier.cmp(&b.carrier));
+        stats
+    }
+}
+
+/// Returns `true` if the failed call is worth retrying: the carrier could not be reached or answered
+/// with a server error. Rejected requests and unparsable responses are not retried
+pub fn is_transient(e: &FailureError) -> bool {
+    e.iter_chain().any(|cause| {
+        if let Some(client_error) = cause.downcast_ref::<HttpClientError>() {
+            return match *client_error {
+                HttpClientError::Network(_) => true,
+                HttpClientError::Api(ref status, _) => status.is_server_error(),
+                _ => false,
+            };
+        }
+        cause.downcast_ref::<hyper::Error>().is_some() || cause.downcast_ref::<io::Error>().is_some()
+    })
+}
+
+/// Wrapper of the outbound calls to the carriers adding the retries and the circuit breakers
+pub struct ResilientCalls {
+    handle: Handle,
+    settings: ResilienceSettings,
+    breakers: Arc<CircuitBreakers>,
+}
+
+impl ResilientCalls {
+    pub fn new(handle: Handle, settings: ResilienceSettings) -> Self {
+        Self {
+            handle,
+            breakers: Arc::new(CircuitBreakers::new(settings.clone())),
+            settings,
+        }
+    }
+
+    /// Runs the call to the carrier, `call` is invoked again for every retry of a transient failure
+    pub fn execute<T, F>(&self, carrier: &str, call: F) -> CarrierFuture<T>
+    where
+        T: 'static,
+        F: Fn() -> CarrierFuture<T> + 'static,
+    {
+        let carrier = carrier.to_string();
+        if !self.breakers.try_acquire(&carrier, Instant::now()) {
+            return Box::new(future::err(
+                format_err!("Circuit breaker of carrier {} is open, the call is rejected", carrier)
+                    .context(Error::HttpClient)
+                    .into(),
+            ));
+        }
+
+        let handle = self.handle.clone();
+        let settings = self.settings.clone();
+        let breakers = self.breakers.clone();
+        let retry_carrier = carrier.clone();
+
+        let calls = future::loop_fn(0u32, move |retry| {
+            let handle = handle.clone();
+            let settings = settings.clone();
+            let breakers = breakers.clone();
+            let carrier = retry_carrier.clone();
+
+            call().then(move |res| -> CarrierFuture<Loop<T, u32>> {
+                match res {
+                    Ok(value) => Box::new(future::ok(Loop::Break(value))),
+                    Err(e) => {
+                        if retry >= settings.max_retries || !is_transient(&e) {
+                            return Box::new(future::err(e));
+                        }
+                        debug!("Call to carrier {} failed, retry {}: {}", carrier, retry + 1, e);
+                        breakers.record_retry(&carrier);
+                        match Timeout::new(settings.retry_delay(retry), &handle) {
+                            Ok(timeout) => Box::new(timeout.map_err(FailureError::from).map(move |_| Loop::Continue(retry + 1))),
+                            Err(e) => Box::new(future::err(e.into())),
+                        }
+                    }
+                }
+            })
+        });
+
+        let breakers = self.breakers.clone();
+        Box::new(calls.then(move |res| {
+            breakers.record_result(&carrier, res.is_ok(), Instant::now());
+            res
+        }))
+    }
+
+    pub fn stats(&self) -> Vec<CarrierCallStats> {
+        self.breakers.stats(Instant::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use tokio_core::reactor::Core;
+
+    use super::*;
+
+    fn settings() -> ResilienceSettings {
+        ResilienceSettings {
+            max_retries: 2,
+            retry_base_delay: Duration::from_millis(0),
+            retry_max_delay: Duration::from_millis(0),
+            breaker_failure_threshold: 2,
+            breaker_open: Duration::from_secs(30),
+        }
+    }
+
+    #[test]
+    fn retry_delay_grows_up_to_max() {
+        let settings = ResilienceSettings::default();
+        assert_eq!(settings.retry_delay(0), Duration::from_millis(100));
+        assert_eq!(settings.retry_delay(2), Duration::from_millis(400));
+        assert_eq!(settings.retry_delay(10), Duration::from_millis(2000));
+        assert_eq!(settings.retry_delay(40), Duration::from_millis(2000));
+    }
+
+    #[test]
+    fn breaker_opens_after_failures_and_lets_single_trial_call() {
+        let breakers = CircuitBreakers::new(settings());
+        let now = Instant::now();
+
+        for _ in 0..2 {
+            assert!(breakers.try_acquire("fedex", now));
+            breakers.record_result("fedex", false, now);
+        }
+        assert!(!breakers.try_acquire("fedex", now));
+        assert!(breakers.try_acquire("ups", now));
+
+        let later = now + Duration::from_secs(31);
+        assert!(breakers.try_acquire("fedex", later));
+        assert!(!breakers.try_acquire("fedex", later));
+        breakers.record_result("fedex", true, later);
+        assert!(breakers.try_acquire("fedex", later));
+
+        let fedex = breakers.stats(later).into_iter().find(|stats| stats.carrier == "fedex").unwrap();
+        assert_eq!(fedex.state, BreakerState::Closed);
+        assert_eq!((fedex.calls, fedex.failed_calls, fedex.rejected_calls), (4, 2, 2));
+    }
+
+    #[test]
+    fn failed_calls_are_retried() {
+        let mut core = Core::new().unwrap();
+        let calls = ResilientCalls::new(core.handle(), settings());
+        let attempts = Rc::new(Cell::new(0));
+
+        let call_attempts = attempts.clone();
+        let work = calls.execute("fedex", move || -> CarrierFuture<f64> {
+            call_attempts.set(call_attempts.get() + 1);
+            if call_attempts.get() < 3 {
+                let e = io::Error::new(io::ErrorKind::TimedOut, "Carrier is unavailable");
+                Box::new(future::err(FailureError::from(e).context(Error::HttpClient).into()))
+            } else {
+                Box::new(future::ok(10.0))
+            }
+        });
+
+        assert_eq!(core.run(work).unwrap(), 10.0);
+        assert_eq!(attempts.get(), 3);
+        let stats = calls.stats();
+        assert_eq!((stats[0].calls, stats[0].retries, stats[0].failed_calls), (1, 2, 0));
+    }
+
+    #[test]
+    fn rejected_calls_are_not_retried() {
+        let mut core = Core::new().unwrap();
+        let calls = ResilientCalls::new(core.handle(), settings());
+        let attempts = Rc::new(Cell::new(0));
+
+        let call_attempts = attempts.clone();
+        let work = calls.execute("fedex", move || -> CarrierFuture<f64> {
+            call_attempts.set(call_attempts.get() + 1);
+            Box::new(future::err(format_err!("Unknown destination").context(Error::HttpClient).into()))
+        });
+
+        assert!(core.run(work).is_err());
+        assert_eq!(attempts.get(), 1);
+        let stats = calls.stats();
+        assert_eq!((stats[0].calls, stats[0].retries, stats[0].failed_calls), (1, 0, 1));
+    }
+}
@@ -0,0 +1,55 @@
+//! Clock is the source of the current time of the services. Prices depend on the date the rate components
+//! are effective on and the companies are suspended on, so the services read the date from the clock
+//! of the static context, and tests replace it with a fixed one to check the pricing on the particular date
+use chrono::{DateTime, NaiveDate, Utc};
+
+pub trait Clock: Send + Sync {
+    /// Current time in UTC
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Current date in UTC
+    fn today(&self) -> NaiveDate {
+        self.now().date().naive_utc()
+    }
+}
+
+/// Clock of the system time
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Clock stopped at the time
+#[derive(Clone, Copy, Debug)]
+pub struct FixedClock(pub DateTime<Utc>);
+
+impl FixedClock {
+    /// Clock stopped at the midnight of the date
+    pub fn on(date: NaiveDate) -> Self {
+        FixedClock(DateTime::from_utc(date.and_hms(0, 0, 0), Utc))
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_clock_is_on_the_date() {
+        let date = NaiveDate::from_ymd(2019, 3, 1);
+        let clock = FixedClock::on(date);
+
+        assert_eq!(clock.today(), date);
+        assert_eq!(clock.now(), clock.now());
+    }
+}
@@ -1,11 +1,14 @@
 //! Config module contains the top-level config for the app.
 use std::env;
 
+use models::PriceRoundingPolicy;
 use sentry_integration::SentryConfig;
+use slo::RouteGroup;
 
 use config_crate::{Config as RawConfig, ConfigError, Environment, File};
 use stq_http;
 use stq_logging::GrayLogConfig;
+use stq_types::{Alpha3, BaseProductId};
 
 /// Basic settings - HTTP binding address and database DSN
 #[derive(Debug, Deserialize, Clone)]
@@ -14,6 +17,31 @@ pub struct Config {
     pub client: Client,
     pub graylog: Option<GrayLogConfig>,
     pub sentry: Option<SentryConfig>,
+    pub carriers: Option<Carriers>,
+    pub geocoding: Option<Geocoding>,
+    pub notifications: Option<Notifications>,
+    pub events: Option<Events>,
+    pub backfills: Option<Backfills>,
+    pub rates_integrity: Option<RatesIntegrity>,
+    pub coverage_regressions: Option<CoverageRegressions>,
+    pub slo: Option<Slo>,
+    pub diagnostics: Option<Diagnostics>,
+    pub address_validation: Option<AddressValidation>,
+    pub payload_validation: Option<PayloadValidation>,
+    pub countries: Option<Countries>,
+    pub country_dependents: Option<CountryDependents>,
+    pub shipping_rates_cache: Option<ShippingRatesCache>,
+    pub rates_quota: Option<RatesQuota>,
+    pub read_replicas: Option<ReadReplicas>,
+    pub legacy_country_labels: Option<LegacyCountryLabels>,
+    pub access_log: Option<AccessLog>,
+    pub response_caching: Option<ResponseCaching>,
+    pub cors: Option<Cors>,
+    pub authentication: Option<Authentication>,
+    pub deprecated_routes: Option<DeprecatedRoutes>,
+    pub delivery_quotes: Option<DeliveryQuotes>,
+    pub carrier_accounts: Option<CarrierAccounts>,
+    pub price_rounding: Option<PriceRounding>,
 }
 
 /// Common server settings
@@ -24,7 +52,21 @@ pub struct Server {
     pub database: String,
     pub redis: Option<String>,
     pub thread_count: usize,
+    /// Threads reserved for admin imports and exports, so they don't starve price and availability requests
+    pub admin_thread_count: usize,
+    /// Maximum number of the database connections, 10 by default
+    pub db_pool_size: Option<u32>,
+    /// Idle database connections are closed after the timeout, 10 minutes by default
+    pub db_idle_timeout_sec: Option<u64>,
+    /// How long a request waits for a free database connection before it fails, 30 seconds by default
+    pub db_connection_timeout_ms: Option<u64>,
     pub cache_ttl_sec: u64,
+    /// Connections are kept alive between requests unless it is `false`
+    pub keep_alive: Option<bool>,
+    /// Reads handled longer get 503, changes and admin requests are never timed out, nor are any requests without it
+    pub request_timeout_ms: Option<u64>,
+    /// How long the requests in flight and the checked out database connections are waited for on shutdown
+    pub shutdown_grace_period_ms: Option<u64>,
 }
 
 /// Http client settings
@@ -36,6 +78,268 @@ pub struct Client {
     pub http_timeout_ms: u64,
 }
 
+/// Carriers gateway settings, used by company packages with on-demand shipping rates
+#[derive(Debug, Deserialize, Clone)]
+pub struct Carriers {
+    pub url: String,
+    pub timeout_ms: u64,
+    pub cache_ttl_sec: u64,
+    /// Retries of the failed requests and the circuit breakers of the carriers, defaults are in `carriers::resilience`
+    pub max_retries: Option<u32>,
+    pub retry_base_delay_ms: Option<u64>,
+    pub retry_max_delay_ms: Option<u64>,
+    pub breaker_failure_threshold: Option<u32>,
+    pub breaker_open_sec: Option<u64>,
+}
+
+/// Geocoding provider settings, user addresses are geocoded only if they are set
+#[derive(Debug, Deserialize, Clone)]
+pub struct Geocoding {
+    pub url: String,
+    pub api_key: String,
+    pub timeout_ms: u64,
+}
+
+/// Notifications microservice settings, buyers are not notified about their shipments if they are not set
+#[derive(Debug, Deserialize, Clone)]
+pub struct Notifications {
+    pub url: String,
+    pub timeout_ms: u64,
+}
+
+/// Message bus the shipping changes are published to
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+pub enum Broker {
+    #[serde(rename = "rabbitmq")]
+    RabbitMq,
+    #[serde(rename = "kafka")]
+    Kafka,
+}
+
+/// Message bus settings, changes are not published if they are not set. `url` is the HTTP interface of the broker:
+/// the exchanges API of the vhost for RabbitMQ, e.g. `http://rabbitmq:15672/api/exchanges/%2F`, or the Kafka REST proxy.
+/// `destination` is the exchange or the topic the events are published to.
+/// Events that failed to publish are published again from the outbox every `relay_interval_sec`
+#[derive(Debug, Deserialize, Clone)]
+pub struct Events {
+    pub broker: Broker,
+    pub url: String,
+    pub destination: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub timeout_ms: u64,
+    pub relay_interval_sec: Option<u64>,
+}
+
+/// Batched backfills settings
+#[derive(Debug, Deserialize, Clone)]
+pub struct Backfills {
+    pub batch_size: i64,
+    pub throttle_ms: u64,
+}
+
+/// Nightly shipping rates integrity check settings, the check runs every day at `run_at_hour_utc`.
+/// Weight bracket priced more than `price_cliff_factor` times the lighter one is reported as a price cliff
+#[derive(Debug, Deserialize, Clone)]
+pub struct RatesIntegrity {
+    pub run_at_hour_utc: u32,
+    pub price_cliff_factor: f64,
+    pub batch_size: i64,
+}
+
+/// Daily coverage regressions check settings. Availability of the sampled routes is snapshotted every day
+/// at `run_at_hour_utc` and compared with the previous snapshot. Losing more than `max_coverage_drop` share
+/// of the packages or a price change by more than `max_price_change` share of the previous price is alerted
+#[derive(Debug, Deserialize, Clone)]
+pub struct CoverageRegressions {
+    pub run_at_hour_utc: u32,
+    pub max_coverage_drop: f64,
+    pub max_price_change: f64,
+    #[serde(default)]
+    pub samples: Vec<CoverageSample>,
+}
+
+/// Base product delivered to the country, whose availability is snapshotted
+#[derive(Debug, Deserialize, Clone)]
+pub struct CoverageSample {
+    pub base_product_id: BaseProductId,
+    pub delivery_to: Alpha3,
+}
+
+/// Service level objectives, compliance is computed over the last `window_sec` seconds
+#[derive(Debug, Deserialize, Clone)]
+pub struct Slo {
+    pub window_sec: u64,
+    pub targets: Vec<SloTarget>,
+}
+
+/// Targets for requests of a route group, e.g. 99% of price requests faster than 150ms
+#[derive(Debug, Deserialize, Clone)]
+pub struct SloTarget {
+    pub route_group: RouteGroup,
+    pub latency_percentile: f64,
+    pub latency_ms: u64,
+    /// Share of requests allowed to fail with a server error
+    pub max_error_rate: f64,
+}
+
+/// Database connections diagnostics, checkouts held longer than `checkout_threshold_ms` are logged
+#[derive(Debug, Deserialize, Clone)]
+pub struct Diagnostics {
+    pub checkout_threshold_ms: u64,
+    pub check_interval_sec: u64,
+}
+
+/// User addresses validation settings
+#[derive(Debug, Deserialize, Clone)]
+pub struct AddressValidation {
+    pub postal_codes: Vec<PostalCodeFormat>,
+}
+
+/// Regex the postal codes of the country must match
+#[derive(Debug, Deserialize, Clone)]
+pub struct PostalCodeFormat {
+    pub country: Alpha3,
+    pub pattern: String,
+}
+
+/// Request payloads validation settings, with `json_schema` enabled bodies of create and update
+/// requests are checked against the payload JSON schemas before deserialization
+#[derive(Debug, Deserialize, Clone)]
+pub struct PayloadValidation {
+    pub json_schema: bool,
+}
+
+/// Countries tree settings, orphans are re-parented by the tree repair to `orphans_parent`,
+/// or to the root of the tree if it is not set
+#[derive(Debug, Deserialize, Clone)]
+pub struct Countries {
+    pub orphans_parent: Alpha3,
+}
+
+/// Refresh of data denormalized from the countries tree, the tree is checked for changes
+/// every `refresh_interval_sec`. Batches are sized and throttled by the backfills settings
+#[derive(Debug, Deserialize, Clone)]
+pub struct CountryDependents {
+    pub refresh_interval_sec: u64,
+}
+
+/// Cache of the shipping rates read by the delivery price requests, kept in the Redis set in the server settings.
+/// Entries live for `ttl_sec`, rates replaced by admins are removed from the cache at once
+#[derive(Debug, Deserialize, Clone)]
+pub struct ShippingRatesCache {
+    pub ttl_sec: u64,
+}
+
+/// Caching headers of the availability responses for the API gateway and the CDN, responses are cached
+/// for `max_age_sec`, but not longer than the entries of the shipping rates cache live
+#[derive(Debug, Deserialize, Clone)]
+pub struct ResponseCaching {
+    pub max_age_sec: u64,
+}
+
+/// CORS of the browser-based tools, `*` in `allowed_origins` allows any origin. Preflight requests are answered
+/// with `allowed_methods` and `allowed_headers`, browsers cache the answer for `max_age_sec` if it is set
+#[derive(Debug, Deserialize, Clone)]
+pub struct Cors {
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    pub max_age_sec: Option<u32>,
+}
+
+/// Authentication of the users by the JWT of the users service, `public_key` is the base64 of the DER-encoded RSA key
+/// the tokens are signed with. Requests with the user id in `Authorization` are accepted while `accept_legacy_header` is set
+#[derive(Debug, Deserialize, Clone)]
+pub struct Authentication {
+    pub public_key: String,
+    pub accept_legacy_header: bool,
+}
+
+/// Retirement of the deprecated routes. With `available_package_for_user_gone` the package available by the base product
+/// and the company package answers 410 Gone pointing the clients to the route by shipping id
+#[derive(Debug, Deserialize, Clone)]
+pub struct DeprecatedRoutes {
+    pub available_package_for_user_gone: bool,
+}
+
+/// Delivery quotes are redeemed by the orders service within `ttl_sec` seconds after they are issued.
+/// With `cleanup_interval_sec` quotes expired more than `retention_sec` seconds ago are purged in the background
+#[derive(Debug, Deserialize, Clone)]
+pub struct DeliveryQuotes {
+    pub ttl_sec: u64,
+    pub cleanup_interval_sec: Option<u64>,
+    pub retention_sec: Option<u64>,
+}
+
+/// Carrier accounts of the stores, their credentials are encrypted with `encryption_key` - the base64 of 32 random bytes.
+/// The key is also read from the `STQ_DELIV_CARRIER_ACCOUNTS_KEY` environment variable. To rotate it the old key is moved
+/// to `previous_encryption_keys`, credentials are decrypted with them until `POST /admin/carrier_accounts/reencrypt`
+/// re-encrypts all of them with the new key
+#[derive(Debug, Deserialize, Clone)]
+pub struct CarrierAccounts {
+    pub encryption_key: String,
+    #[serde(default)]
+    pub previous_encryption_keys: Vec<String>,
+}
+
+/// Rounding of the delivery prices by currency, prices in the currencies without a policy are not rounded
+#[derive(Debug, Deserialize, Clone)]
+pub struct PriceRounding {
+    pub policies: Vec<PriceRoundingPolicy>,
+}
+
+/// Environment variable with the encryption key of the carrier accounts, overrides the one from the config files
+pub const CARRIER_ACCOUNTS_KEY_ENV: &str = "STQ_DELIV_CARRIER_ACCOUNTS_KEY";
+
+/// Default quota of the shipping rates storage of a company, in rate entries - destinations multiplied by weight brackets.
+/// Admins override it for single companies, there is no quota if neither is set
+#[derive(Debug, Deserialize, Clone)]
+pub struct RatesQuota {
+    pub default_max_entries: i64,
+}
+
+/// Read replicas of the database, price and availability requests are served by the healthy replica
+/// with the lowest latency, probed every `probe_interval_sec`. Replicas slower than `max_latency_ms` are not used,
+/// the primary serves the reads if no replica is left
+#[derive(Debug, Deserialize, Clone)]
+pub struct ReadReplicas {
+    pub urls: Vec<String>,
+    pub probe_interval_sec: u64,
+    pub max_latency_ms: u64,
+    /// Replicas replaying the changes of the primary later than that are not used, 5 seconds by default
+    pub max_lag_ms: Option<u64>,
+    /// How long a read waits for a connection of the replica before the primary serves it, 250 ms by default
+    pub checkout_timeout_ms: Option<u64>,
+    /// Queries running on the replicas longer than that are canceled, 5 seconds by default
+    pub statement_timeout_ms: Option<u64>,
+}
+
+/// Compatibility with the clients sending country labels, like `Russia`, instead of alpha3 codes.
+/// Labels in the requests are replaced with the codes, with `emit_labels` the responses carry the labels
+/// next to the codes. Meant for the migration window only, every request loads the countries
+#[derive(Debug, Deserialize, Clone)]
+pub struct LegacyCountryLabels {
+    pub emit_labels: bool,
+}
+
+/// Access log filtering, requests to the routes matching `exclude` patterns are not logged,
+/// the ones matching `sampled` patterns are logged at the rate of the pattern. Mutations are always logged
+#[derive(Debug, Deserialize, Clone)]
+pub struct AccessLog {
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default)]
+    pub sampled: Vec<SampledRoutes>,
+}
+
+/// Regex matched against the operation name, e.g. `GET /companies/:id`, and the share of the requests to log
+#[derive(Debug, Deserialize, Clone)]
+pub struct SampledRoutes {
+    pub pattern: String,
+    pub rate: f64,
+}
+
 /// Creates new app config struct
 /// #Examples
 /// ```
@@ -55,9 +359,22 @@ impl Config {
         // Add in settings from the environment (with a prefix of STQ_USERS)
         s.merge(Environment::with_prefix("STQ_DELIV"))?;
 
+        // Nested settings are not read from the environment, the key is kept out of the config files this way
+        if let Ok(key) = env::var(CARRIER_ACCOUNTS_KEY_ENV) {
+            s.set("carrier_accounts.encryption_key", key)?;
+        }
+
         s.try_into()
     }
 
+    /// Rounding policies of the delivery prices, no price is rounded if they are not configured
+    pub fn price_rounding_policies(&self) -> Vec<PriceRoundingPolicy> {
+        self.price_rounding
+            .as_ref()
+            .map(|price_rounding| price_rounding.policies.clone())
+            .unwrap_or_default()
+    }
+
     pub fn to_http_config(&self) -> stq_http::client::Config {
         stq_http::client::Config {
             http_client_buffer_size: self.client.http_client_buffer_size,
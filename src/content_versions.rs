@@ -0,0 +1,304 @@
+//! Versions of the reference data the frontends download on every page load: the countries tree and the packages.
+//!
+//! The service computes a hash of the content when it serves it and drops the hash when the content is changed,
+//! so that requests with the current version in `If-None-Match` are answered with 304 without touching db.
+//! The version covers every translation of the content, so the tag of a localized response is derived from it
+//! and the requested language, e.g. `GET /packages?lang=ru` and `GET /packages` have different tags.
+//! Changes made through other instances are announced over the invalidation channel. A version is still trusted
+//! for a limited time only, since the announcements are lost while Redis is not reachable and are not made without it.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::{future, Future};
+use hyper;
+use hyper::header::{ETag, EntityTag, IfNoneMatch};
+use hyper::server::{Request, Response, Service};
+use hyper::{Method, StatusCode};
+use serde::Serialize;
+use serde_json;
+use sha3::{Digest, Sha3_256};
+
+use invalidation::{InvalidationKey, InvalidationPublisher};
+
+/// Versions older than this are computed again, so that the changes not announced by other instances are picked up
+const VERSION_TTL_SEC: u64 = 60;
+
+/// Length of the hash in the version
+const VERSION_BYTES: usize = 16;
+
+/// Content served with a version
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum VersionedContent {
+    /// `GET /countries`
+    Countries,
+    /// `GET /countries/flatten`
+    CountriesFlatten,
+    /// `GET /packages`
+    Packages,
+}
+
+/// Content changed with the countries tree, packages list the countries they deliver to
+pub const COUNTRIES_CONTENT: &[VersionedContent] = &[
+    VersionedContent::Countries,
+    VersionedContent::CountriesFlatten,
+    VersionedContent::Packages,
+];
+
+impl VersionedContent {
+    pub fn from_path(path: &str) -> Option<Self> {
+        match path {
+            "/countries" => Some(VersionedContent::Countries),
+            "/countries/flatten" => Some(VersionedContent::CountriesFlatten),
+            "/packages" => Some(VersionedContent::Packages),
+            _ => None,
+        }
+    }
+
+    /// Query parameters changing the body of the response
+    fn variant_params(self) -> &'static [&'static str] {
+        match self {
+            VersionedContent::Countries | VersionedContent::CountriesFlatten => &[],
+            VersionedContent::Packages => &["lang"],
+        }
+    }
+
+    /// Values of the query parameters changing the body, empty for the default body.
+    /// Languages are compared ignoring case like the translations are
+    fn variant(self, query: Option<&str>) -> String {
+        let params = self.variant_params();
+
+        query
+            .unwrap_or_default()
+            .split('&')
+            .filter_map(|pair| {
+                let mut pair = pair.splitn(2, '=');
+                let name = pair.next().unwrap_or_default();
+                let value = pair.next().unwrap_or_default();
+                if params.contains(&name) {
+                    Some(format!("{}={}", name, value.to_lowercase()))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+}
+
+/// Tag of the response with the variant of the content, the default body is tagged with the version itself
+fn variant_tag(version: &str, variant: &str) -> EntityTag {
+    if variant.is_empty() {
+        EntityTag::weak(version.to_string())
+    } else {
+        EntityTag::weak(content_hash(format!("{}?{}", version, variant).as_bytes()))
+    }
+}
+
+/// Hash of the serialized content
+pub fn content_hash(bytes: &[u8]) -> String {
+    let mut hasher = Sha3_256::default();
+    hasher.input(bytes);
+
+    hasher
+        .result()
+        .iter()
+        .take(VERSION_BYTES)
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[derive(Default)]
+pub struct ContentVersions {
+    versions: Mutex<HashMap<VersionedContent, (String, Instant)>>,
+    /// Announces invalidations to other instances, they are applied to this one only without it
+    publisher: Option<Arc<dyn InvalidationPublisher>>,
+}
+
+impl ContentVersions {
+    pub fn with_publisher(publisher: Arc<dyn InvalidationPublisher>) -> Self {
+        Self {
+            versions: Mutex::default(),
+            publisher: Some(publisher),
+        }
+    }
+
+    /// Current version of the content, `None` if it is not known or is too old to be trusted
+    pub fn get(&self, content: VersionedContent) -> Option<String> {
+        let versions = self.versions.lock().expect("Content versions lock is poisoned");
+        versions
+            .get(&content)
+            .filter(|(_, computed_at)| computed_at.elapsed() < Duration::from_secs(VERSION_TTL_SEC))
+            .map(|(version, _)| version.clone())
+    }
+
+    /// Computes the version of the content being served, unless the current one is known
+    pub fn update<V: Serialize>(&self, content: VersionedContent, value: &V) {
+        if self.get(content).is_some() {
+            return;
+        }
+
+        match serde_json::to_vec(value) {
+            Ok(bytes) => {
+                let mut versions = self.versions.lock().expect("Content versions lock is poisoned");
+                versions.insert(content, (content_hash(&bytes), Instant::now()));
+            }
+            Err(e) => error!("Failed to compute version of {:?}: {}", content, e),
+        }
+    }
+
+    /// Drops versions of the changed content on every instance
+    pub fn invalidate(&self, contents: &[VersionedContent]) {
+        self.forget(contents);
+
+        if let Some(publisher) = self.publisher.as_ref() {
+            for content in contents {
+                publisher.publish(InvalidationKey::Content(*content));
+            }
+        }
+    }
+
+    /// Drops versions of the content on this instance, the invalidations announced by other instances are applied with it
+    pub fn forget(&self, contents: &[VersionedContent]) {
+        let mut versions = self.versions.lock().expect("Content versions lock is poisoned");
+        for content in contents {
+            versions.remove(content);
+        }
+    }
+}
+
+/// Sets `ETag` on the successful responses with versioned content and answers requests for the current version with 304.
+/// Tags are weak, since the compressed and the plain responses have the same tag
+pub struct ConditionalGet<S> {
+    inner: S,
+    versions: Arc<ContentVersions>,
+}
+
+impl<S> ConditionalGet<S> {
+    pub fn new(inner: S, versions: Arc<ContentVersions>) -> Self {
+        Self { inner, versions }
+    }
+}
+
+impl<S> Service for ConditionalGet<S>
+where
+    S: Service<Request = Request, Response = Response, Error = hyper::Error>,
+    S::Future: 'static,
+{
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<dyn Future<Item = Response, Error = hyper::Error>>;
+
+    fn call(&self, req: Request) -> Self::Future {
+        let content = match (*req.method() == Method::Get, VersionedContent::from_path(req.path())) {
+            (true, Some(content)) => content,
+            _ => return Box::new(self.inner.call(req)),
+        };
+        let variant = content.variant(req.query());
+
+        if let Some(version) = self.versions.get(content) {
+            let tag = variant_tag(&version, &variant);
+            let not_modified = match req.headers().get::<IfNoneMatch>() {
+                Some(IfNoneMatch::Any) => true,
+                Some(IfNoneMatch::Items(ref tags)) => tags.iter().any(|requested| requested.weak_eq(&tag)),
+                None => false,
+            };
+            if not_modified {
+                return Box::new(future::ok(
+                    Response::new().with_status(StatusCode::NotModified).with_header(ETag(tag)),
+                ));
+            }
+        }
+
+        let versions = self.versions.clone();
+        Box::new(self.inner.call(req).map(move |mut res| {
+            if res.status() == StatusCode::Ok {
+                if let Some(version) = versions.get(content) {
+                    res.headers_mut().set(ETag(variant_tag(&version, &variant)));
+                }
+            }
+            res
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct PublisherMock {
+        published: Mutex<Vec<InvalidationKey>>,
+    }
+
+    impl InvalidationPublisher for PublisherMock {
+        fn publish(&self, key: InvalidationKey) {
+            self.published.lock().unwrap().push(key);
+        }
+    }
+
+    #[test]
+    fn version_changes_with_content_only() {
+        assert_eq!(content_hash(b"[\"RUS\"]"), content_hash(b"[\"RUS\"]"));
+        assert_ne!(content_hash(b"[\"RUS\"]"), content_hash(b"[\"USA\"]"));
+        assert_eq!(content_hash(b"").len(), VERSION_BYTES * 2);
+    }
+
+    #[test]
+    fn tags_change_with_body_changing_params_only() {
+        let version = content_hash(b"[\"package1\"]");
+        let tag = |content: VersionedContent, query: Option<&str>| variant_tag(&version, &content.variant(query));
+
+        let default_tag = tag(VersionedContent::Packages, None);
+        assert_eq!(default_tag, EntityTag::weak(version.clone()));
+        assert_eq!(tag(VersionedContent::Packages, Some("limit=10")), default_tag);
+
+        let ru_tag = tag(VersionedContent::Packages, Some("lang=ru"));
+        assert_ne!(ru_tag, default_tag);
+        assert_ne!(ru_tag, tag(VersionedContent::Packages, Some("lang=en")));
+        assert_eq!(ru_tag, tag(VersionedContent::Packages, Some("lang=RU&limit=10")));
+
+        // countries are not localized
+        assert_eq!(tag(VersionedContent::Countries, Some("lang=ru")), default_tag);
+    }
+
+    #[test]
+    fn versions_are_dropped_on_invalidation() {
+        let versions = ContentVersions::default();
+        versions.update(VersionedContent::Countries, &vec!["RUS"]);
+        versions.update(VersionedContent::Packages, &vec!["package1"]);
+        let countries_version = versions.get(VersionedContent::Countries).unwrap();
+
+        // current version is kept until the content is changed
+        versions.update(VersionedContent::Countries, &vec!["USA"]);
+        assert_eq!(versions.get(VersionedContent::Countries), Some(countries_version.clone()));
+
+        versions.invalidate(&[VersionedContent::Countries, VersionedContent::CountriesFlatten]);
+        assert_eq!(versions.get(VersionedContent::Countries), None);
+        assert!(versions.get(VersionedContent::Packages).is_some());
+
+        versions.update(VersionedContent::Countries, &vec!["USA"]);
+        assert_ne!(versions.get(VersionedContent::Countries), Some(countries_version));
+    }
+
+    #[test]
+    fn invalidations_are_announced_to_other_instances() {
+        let publisher = Arc::new(PublisherMock::default());
+        let versions = ContentVersions::with_publisher(publisher.clone());
+        versions.update(VersionedContent::Countries, &vec!["RUS"]);
+        versions.update(VersionedContent::Packages, &vec!["package1"]);
+
+        versions.invalidate(&[VersionedContent::Packages]);
+        assert!(versions.get(VersionedContent::Packages).is_none());
+        assert_eq!(
+            *publisher.published.lock().unwrap(),
+            vec![InvalidationKey::Content(VersionedContent::Packages)]
+        );
+
+        // invalidations of other instances are not announced again
+        versions.forget(&[VersionedContent::Countries]);
+        assert!(versions.get(VersionedContent::Countries).is_none());
+        assert_eq!(publisher.published.lock().unwrap().len(), 1);
+    }
+}
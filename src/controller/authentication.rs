@@ -0,0 +1,200 @@
+//! Authentication of the users by the JWT in `Authorization: Bearer <token>`, signed by the users service.
+//!
+//! Without authentication configured the header carries the user id as is, like it always did, so anyone
+//! can act as any user. Once it is configured, the legacy header is accepted only while the clients migrate.
+//!
+//! Other services authenticate with their api key in `X-Api-Key` instead of acting as a superuser.
+use std::str::FromStr;
+
+use base64;
+use failure::Error as FailureError;
+use jsonwebtoken::{self, Algorithm, Validation};
+
+use stq_types::{DeliveryRole, UserId};
+
+use config;
+use errors::Error;
+use models::ApiKey;
+
+const BEARER_PREFIX: &str = "Bearer ";
+
+/// Header other services send their api key in
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+
+/// Claims of the token the users service issues
+#[derive(Debug, Deserialize)]
+pub struct JwtClaims {
+    pub user_id: i32,
+    /// Roles of the user in the delivery service, the ones stored in db are used if there are none in the token
+    #[serde(default)]
+    pub roles: Option<Vec<DeliveryRole>>,
+    pub exp: i64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuthenticatedUser {
+    pub user_id: UserId,
+    pub roles: Option<Vec<DeliveryRole>>,
+}
+
+/// Who makes the request
+#[derive(Clone, Debug, PartialEq)]
+pub enum Caller {
+    Anonymous,
+    User(AuthenticatedUser),
+    /// Another service, allowed only the scopes of its key
+    Service(ApiKey),
+}
+
+impl From<Option<AuthenticatedUser>> for Caller {
+    fn from(user: Option<AuthenticatedUser>) -> Self {
+        user.map_or(Caller::Anonymous, Caller::User)
+    }
+}
+
+pub struct Authenticator {
+    /// DER-encoded RSA public key the tokens are verified with, `None` if authentication is not configured
+    public_key: Option<Vec<u8>>,
+    accept_legacy_header: bool,
+}
+
+impl Authenticator {
+    pub fn new(settings: Option<config::Authentication>) -> Result<Self, FailureError> {
+        match settings {
+            Some(settings) => {
+                let public_key = base64::decode(settings.public_key.trim())
+                    .map_err(|e| format_err!("Public key of the authentication is not valid base64: {}", e))?;
+                Ok(Self {
+                    public_key: Some(public_key),
+                    accept_legacy_header: settings.accept_legacy_header,
+                })
+            }
+            None => Ok(Self {
+                public_key: None,
+                accept_legacy_header: true,
+            }),
+        }
+    }
+
+    /// User of the request with the `Authorization` header, `None` for anonymous requests.
+    /// Invalid and expired tokens are rejected, so are the legacy headers once they are not accepted
+    pub fn authenticate(&self, authorization: Option<&str>) -> Result<Option<AuthenticatedUser>, FailureError> {
+        let authorization = match authorization {
+            Some(authorization) => authorization.trim(),
+            None => return Ok(None),
+        };
+
+        if let (Some(public_key), true) = (self.public_key.as_ref(), authorization.starts_with(BEARER_PREFIX)) {
+            let token = &authorization[BEARER_PREFIX.len()..];
+            let validation = Validation {
+                algorithms: vec![Algorithm::RS256],
+                ..Validation::default()
+            };
+
+            return jsonwebtoken::decode::<JwtClaims>(token, public_key, &validation)
+                .map(|token_data| {
+                    Some(AuthenticatedUser {
+                        user_id: UserId(token_data.claims.user_id),
+                        roles: token_data.claims.roles,
+                    })
+                })
+                .map_err(|e| format_err!("Invalid token: {}", e).context(Error::Unauthorized).into());
+        }
+
+        if !self.accept_legacy_header {
+            return Err(format_err!("Authorization header without a token")
+                .context(Error::Unauthorized)
+                .into());
+        }
+
+        // legacy header with the user id, anything else is treated as anonymous like before
+        Ok(i32::from_str(authorization).ok().map(|user_id| AuthenticatedUser {
+            user_id: UserId(user_id),
+            roles: None,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use jsonwebtoken::Header;
+
+    use super::*;
+
+    /// RSA key pair of the users service of the tests, in DER like the configured public key
+    const PRIVATE_KEY: &[u8] = include_bytes!("../../tests/fixtures/jwt_private_key.der");
+    const PUBLIC_KEY: &[u8] = include_bytes!("../../tests/fixtures/jwt_public_key.der");
+
+    fn token(user_id: i32, roles: Option<Vec<DeliveryRole>>, expires_in_sec: i64) -> String {
+        let claims = json!({
+            "user_id": user_id,
+            "roles": roles,
+            "exp": Utc::now().timestamp() + expires_in_sec,
+        });
+        jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, PRIVATE_KEY).unwrap()
+    }
+
+    fn configured_authenticator() -> Authenticator {
+        Authenticator::new(Some(config::Authentication {
+            public_key: base64::encode(PUBLIC_KEY),
+            accept_legacy_header: false,
+        }))
+        .unwrap()
+    }
+
+    fn authenticator(accept_legacy_header: bool) -> Authenticator {
+        Authenticator {
+            public_key: Some(vec![0; 16]),
+            accept_legacy_header,
+        }
+    }
+
+    #[test]
+    fn legacy_header_is_accepted_until_disabled() {
+        let user = Some(AuthenticatedUser {
+            user_id: UserId(42),
+            roles: None,
+        });
+
+        let not_configured = Authenticator::new(None).unwrap();
+        assert_eq!(not_configured.authenticate(Some("42")).unwrap(), user);
+        assert_eq!(authenticator(true).authenticate(Some("42")).unwrap(), user);
+        assert!(authenticator(false).authenticate(Some("42")).is_err());
+    }
+
+    #[test]
+    fn valid_token_authenticates_user_with_its_roles() {
+        let authenticator = configured_authenticator();
+
+        let authorization = format!("Bearer {}", token(42, Some(vec![DeliveryRole::StoreManager]), 3600));
+        assert_eq!(
+            authenticator.authenticate(Some(&authorization)).unwrap(),
+            Some(AuthenticatedUser {
+                user_id: UserId(42),
+                roles: Some(vec![DeliveryRole::StoreManager]),
+            })
+        );
+
+        let authorization = format!("Bearer {}", token(42, None, 3600));
+        assert_eq!(
+            authenticator.authenticate(Some(&authorization)).unwrap(),
+            Some(AuthenticatedUser {
+                user_id: UserId(42),
+                roles: None,
+            })
+        );
+    }
+
+    #[test]
+    fn expired_token_is_rejected() {
+        let authorization = format!("Bearer {}", token(42, None, -3600));
+        assert!(configured_authenticator().authenticate(Some(&authorization)).is_err());
+    }
+
+    #[test]
+    fn invalid_tokens_are_rejected() {
+        assert!(authenticator(true).authenticate(Some("Bearer not.a.token")).is_err());
+        assert_eq!(authenticator(false).authenticate(None).unwrap(), None);
+    }
+}
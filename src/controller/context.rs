@@ -1,67 +1,156 @@
 //! `Context` is a top level module contains static context and dynamic context for each request
 use std::sync::Arc;
 
-use diesel::connection::AnsiTransactionManager;
-use diesel::pg::Pg;
-use diesel::Connection;
 use futures_cpupool::CpuPool;
 use r2d2::{ManageConnection, Pool};
+use serde_json::Value;
 
 use stq_http::client::ClientHandle;
 use stq_router::RouteParser;
-use stq_types::UserId;
+use stq_types::{DeliveryRole, UserId};
 
+use super::authentication::Authenticator;
+use super::openapi;
 use super::routes::*;
+use access_log::AccessLogFilter;
+use cache::ShippingRatesCache;
+use carriers::CarriersClient;
+use clock::{Clock, SystemClock};
 use config::Config;
+use content_versions::ContentVersions;
+use diagnostics::{ConnectionTracker, TaskTracker};
+use events::EventsPublisher;
+use geocoding::Geocoder;
+use models::ApiKeyScope;
+use notifications::NotificationsClient;
+use quotes_cleanup::QuotesCleanupTracker;
+use replicas::ReplicaRouter;
 use repos::repo_factory::*;
+use repos::DbConnection;
+use request_context::RequestContext;
+use services::address_validation::AddressValidator;
+use slo::SloTracker;
 
 /// Static context for all app
 pub struct StaticContext<T, M, F>
 where
-    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    T: DbConnection,
     M: ManageConnection<Connection = T>,
     F: ReposFactory<T>,
 {
     pub db_pool: Pool<M>,
+    /// Connections of the read endpoints tolerating replication lag
+    pub read_replicas: Arc<ReplicaRouter<M>>,
     pub cpu_pool: CpuPool,
+    pub admin_cpu_pool: CpuPool,
+    /// Tasks of the CPU pools, reported by `GET /admin/stats`
+    pub cpu_pool_tasks: Arc<TaskTracker>,
+    pub admin_cpu_pool_tasks: Arc<TaskTracker>,
     pub config: Arc<Config>,
     pub route_parser: Arc<RouteParser<Route>>,
+    /// OpenAPI specification served at `GET /openapi.json`
+    pub openapi: Arc<Value>,
     pub client_handle: ClientHandle,
+    pub carriers_client: Option<Arc<dyn CarriersClient>>,
+    pub shipping_rates_cache: Option<Arc<dyn ShippingRatesCache>>,
+    pub geocoder: Option<Arc<dyn Geocoder>>,
+    pub notifications_client: Option<Arc<dyn NotificationsClient>>,
+    pub events_publisher: Option<Arc<dyn EventsPublisher>>,
+    pub slo_tracker: Arc<SloTracker>,
+    pub connection_tracker: Arc<ConnectionTracker>,
+    pub address_validator: Arc<AddressValidator>,
+    pub access_log_filter: Arc<AccessLogFilter>,
+    pub authenticator: Arc<Authenticator>,
+    /// Versions of the countries and the packages for the `ETag` of their responses
+    pub content_versions: Arc<ContentVersions>,
+    /// Source of the current date of the pricing, replaced with a fixed clock in tests
+    pub clock: Arc<dyn Clock>,
+    /// Counters of the delivery quotes cleanup, reported by `GET /admin/stats`
+    pub quotes_cleanup: Arc<QuotesCleanupTracker>,
     pub repo_factory: F,
 }
 
-impl<
-        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
-        M: ManageConnection<Connection = T>,
-        F: ReposFactory<T>,
-    > StaticContext<T, M, F>
-{
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> StaticContext<T, M, F> {
     /// Create a new static context
-    pub fn new(db_pool: Pool<M>, cpu_pool: CpuPool, client_handle: ClientHandle, config: Arc<Config>, repo_factory: F) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        db_pool: Pool<M>,
+        cpu_pool: CpuPool,
+        admin_cpu_pool: CpuPool,
+        client_handle: ClientHandle,
+        carriers_client: Option<Arc<dyn CarriersClient>>,
+        shipping_rates_cache: Option<Arc<dyn ShippingRatesCache>>,
+        geocoder: Option<Arc<dyn Geocoder>>,
+        notifications_client: Option<Arc<dyn NotificationsClient>>,
+        events_publisher: Option<Arc<dyn EventsPublisher>>,
+        read_replicas: ReplicaRouter<M>,
+        config: Arc<Config>,
+        repo_factory: F,
+    ) -> Self {
         let route_parser = Arc::new(create_route_parser());
+        let openapi = Arc::new(openapi::specification());
+        let slo_tracker = Arc::new(SloTracker::new(config.slo.clone()));
+        let connection_tracker = Arc::new(ConnectionTracker::new(config.diagnostics.clone()));
+        let address_validator = Arc::new(
+            AddressValidator::new(config.address_validation.clone()).expect("Invalid postal code format in address validation config"),
+        );
+        let access_log_filter =
+            Arc::new(AccessLogFilter::new(config.access_log.clone()).expect("Invalid route pattern in access log config"));
+        let authenticator = Arc::new(Authenticator::new(config.authentication.clone()).expect("Invalid authentication config"));
         Self {
             route_parser,
+            openapi,
             db_pool,
+            read_replicas: Arc::new(read_replicas),
             cpu_pool,
+            admin_cpu_pool,
+            cpu_pool_tasks: Arc::new(TaskTracker::default()),
+            admin_cpu_pool_tasks: Arc::new(TaskTracker::default()),
             client_handle,
+            carriers_client,
+            shipping_rates_cache,
+            geocoder,
+            notifications_client,
+            events_publisher,
+            slo_tracker,
+            connection_tracker,
+            address_validator,
+            access_log_filter,
+            authenticator,
+            content_versions: Arc::new(ContentVersions::default()),
+            clock: Arc::new(SystemClock),
+            quotes_cleanup: Arc::new(QuotesCleanupTracker::default()),
             config,
             repo_factory,
         }
     }
 }
 
-impl<
-        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
-        M: ManageConnection<Connection = T>,
-        F: ReposFactory<T>,
-    > Clone for StaticContext<T, M, F>
-{
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> Clone for StaticContext<T, M, F> {
     fn clone(&self) -> Self {
         Self {
             cpu_pool: self.cpu_pool.clone(),
+            admin_cpu_pool: self.admin_cpu_pool.clone(),
+            cpu_pool_tasks: self.cpu_pool_tasks.clone(),
+            admin_cpu_pool_tasks: self.admin_cpu_pool_tasks.clone(),
             db_pool: self.db_pool.clone(),
+            read_replicas: self.read_replicas.clone(),
             route_parser: self.route_parser.clone(),
+            openapi: self.openapi.clone(),
             client_handle: self.client_handle.clone(),
+            carriers_client: self.carriers_client.clone(),
+            shipping_rates_cache: self.shipping_rates_cache.clone(),
+            geocoder: self.geocoder.clone(),
+            notifications_client: self.notifications_client.clone(),
+            events_publisher: self.events_publisher.clone(),
+            slo_tracker: self.slo_tracker.clone(),
+            connection_tracker: self.connection_tracker.clone(),
+            address_validator: self.address_validator.clone(),
+            access_log_filter: self.access_log_filter.clone(),
+            authenticator: self.authenticator.clone(),
+            content_versions: self.content_versions.clone(),
+            clock: self.clock.clone(),
+            quotes_cleanup: self.quotes_cleanup.clone(),
             config: self.config.clone(),
             repo_factory: self.repo_factory.clone(),
         }
@@ -72,15 +161,28 @@ impl<
 #[derive(Clone)]
 pub struct DynamicContext {
     pub user_id: Option<UserId>,
-    pub correlation_token: String,
+    /// Correlation token and endpoint of the request for logging, connection checkouts are tracked by the endpoint
+    pub request_context: RequestContext,
 }
 
 impl DynamicContext {
     /// Create a new dynamic context for each request
-    pub fn new(user_id: Option<UserId>, correlation_token: String) -> Self {
+    pub fn new(user_id: Option<UserId>, correlation_token: String, operation: String) -> Self {
         Self {
             user_id,
-            correlation_token,
+            request_context: RequestContext::new(correlation_token, user_id, operation),
         }
     }
+
+    /// Roles of the user from the token of the request, repos use them instead of the ones stored in db
+    pub fn with_roles(mut self, roles: Option<Vec<DeliveryRole>>) -> Self {
+        self.request_context.roles = roles;
+        self
+    }
+
+    /// Scopes of the api key of the request, repos allow only the actions in them
+    pub fn with_api_key_scopes(mut self, api_key_scopes: Option<Vec<ApiKeyScope>>) -> Self {
+        self.request_context.api_key_scopes = api_key_scopes;
+        self
+    }
 }
@@ -0,0 +1,180 @@
+//! CORS of the browser-based admin tools calling the service directly, without a proxy in front of it.
+//!
+//! Preflight requests are answered here without reaching the routes, other requests from the allowed origins
+//! get `Access-Control-Allow-Origin` on their responses. Nothing is changed if CORS is not configured.
+use std::sync::Arc;
+
+use futures::{future, Future};
+use hyper;
+use hyper::header::{AccessControlAllowOrigin, AccessControlMaxAge};
+use hyper::server::{Request, Response, Service};
+use hyper::{Method, StatusCode};
+
+use config;
+
+/// Origin allowing any origin in the settings
+const ANY_ORIGIN: &str = "*";
+
+pub struct CorsPolicy {
+    settings: Option<config::Cors>,
+}
+
+impl CorsPolicy {
+    /// Requests from other origins are not allowed if CORS is not configured
+    pub fn new(config: &config::Config) -> Self {
+        Self {
+            settings: config.cors.clone(),
+        }
+    }
+
+    /// `Access-Control-Allow-Origin` of the responses to the origin, `None` if the origin is not allowed
+    pub fn allow_origin(&self, origin: &str) -> Option<AccessControlAllowOrigin> {
+        let settings = self.settings.as_ref()?;
+
+        if settings.allowed_origins.iter().any(|allowed| allowed == ANY_ORIGIN) {
+            Some(AccessControlAllowOrigin::Any)
+        } else if settings
+            .allowed_origins
+            .iter()
+            .any(|allowed| allowed.trim_end_matches('/') == origin)
+        {
+            Some(AccessControlAllowOrigin::Value(origin.to_string()))
+        } else {
+            None
+        }
+    }
+
+    /// Response to the preflight request from the origin, the requests of the origins not allowed are forbidden
+    pub fn preflight_response(&self, origin: &str) -> Response {
+        let (settings, allow_origin) = match (self.settings.as_ref(), self.allow_origin(origin)) {
+            (Some(settings), Some(allow_origin)) => (settings, allow_origin),
+            _ => return Response::new().with_status(StatusCode::Forbidden),
+        };
+
+        let mut res = Response::new().with_status(StatusCode::NoContent);
+        {
+            let headers = res.headers_mut();
+            if allow_origin != AccessControlAllowOrigin::Any {
+                headers.set_raw("Vary", "Origin");
+            }
+            headers.set(allow_origin);
+            headers.set_raw("Access-Control-Allow-Methods", settings.allowed_methods.join(", "));
+            if !settings.allowed_headers.is_empty() {
+                headers.set_raw("Access-Control-Allow-Headers", settings.allowed_headers.join(", "));
+            }
+            if let Some(max_age_sec) = settings.max_age_sec {
+                headers.set(AccessControlMaxAge(max_age_sec));
+            }
+        }
+        res
+    }
+}
+
+fn request_origin(req: &Request) -> Option<String> {
+    req.headers()
+        .get_raw("Origin")
+        .and_then(|origin| origin.one())
+        .map(|origin| String::from_utf8_lossy(origin).into_owned())
+}
+
+/// Answers the preflight requests and allows the allowed origins to read the responses of the inner service
+pub struct Cors<S> {
+    inner: S,
+    policy: Arc<CorsPolicy>,
+}
+
+impl<S> Cors<S> {
+    pub fn new(inner: S, policy: Arc<CorsPolicy>) -> Self {
+        Self { inner, policy }
+    }
+}
+
+impl<S> Service for Cors<S>
+where
+    S: Service<Request = Request, Response = Response, Error = hyper::Error>,
+    S::Future: 'static,
+{
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<dyn Future<Item = Response, Error = hyper::Error>>;
+
+    fn call(&self, req: Request) -> Self::Future {
+        let origin = match request_origin(&req) {
+            Some(origin) => origin,
+            None => return Box::new(self.inner.call(req)),
+        };
+
+        let is_preflight = *req.method() == Method::Options && req.headers().get_raw("Access-Control-Request-Method").is_some();
+        if is_preflight {
+            return Box::new(future::ok(self.policy.preflight_response(&origin)));
+        }
+
+        let allow_origin = self.policy.allow_origin(&origin);
+        Box::new(self.inner.call(req).map(move |mut res| {
+            if let Some(allow_origin) = allow_origin {
+                if allow_origin != AccessControlAllowOrigin::Any {
+                    let vary = match res
+                        .headers()
+                        .get_raw("Vary")
+                        .and_then(|vary| vary.one())
+                        .map(String::from_utf8_lossy)
+                    {
+                        Some(vary) => format!("{}, Origin", vary),
+                        None => "Origin".to_string(),
+                    };
+                    res.headers_mut().set_raw("Vary", vary);
+                }
+                res.headers_mut().set(allow_origin);
+            }
+            res
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(allowed_origins: &[&str]) -> CorsPolicy {
+        CorsPolicy {
+            settings: Some(config::Cors {
+                allowed_origins: allowed_origins.iter().map(|origin| origin.to_string()).collect(),
+                allowed_methods: vec!["GET".to_string(), "POST".to_string()],
+                allowed_headers: vec!["Authorization".to_string(), "Content-Type".to_string()],
+                max_age_sec: Some(600),
+            }),
+        }
+    }
+
+    #[test]
+    fn only_allowed_origins_are_allowed() {
+        let any_origin = policy(&["*"]);
+        let policy = policy(&["https://admin.storiqa.com/"]);
+
+        assert_eq!(
+            policy.allow_origin("https://admin.storiqa.com"),
+            Some(AccessControlAllowOrigin::Value("https://admin.storiqa.com".to_string()))
+        );
+        assert_eq!(policy.allow_origin("https://evil.com"), None);
+        assert_eq!(CorsPolicy { settings: None }.allow_origin("https://admin.storiqa.com"), None);
+        assert_eq!(any_origin.allow_origin("https://evil.com"), Some(AccessControlAllowOrigin::Any));
+    }
+
+    #[test]
+    fn preflight_is_answered_for_allowed_origins() {
+        let policy = policy(&["https://admin.storiqa.com"]);
+
+        let res = policy.preflight_response("https://admin.storiqa.com");
+        assert_eq!(res.status(), StatusCode::NoContent);
+        assert_eq!(res.headers().get::<AccessControlMaxAge>(), Some(&AccessControlMaxAge(600)));
+        assert_eq!(
+            res.headers()
+                .get_raw("Access-Control-Allow-Headers")
+                .and_then(|headers| headers.one()),
+            Some(&b"Authorization, Content-Type"[..])
+        );
+
+        assert_eq!(policy.preflight_response("https://evil.com").status(), StatusCode::Forbidden);
+    }
+}
@@ -0,0 +1,314 @@
+//! Compatibility layer for the legacy clients, which send and expect country labels, like `Russia`,
+//! where the API takes and returns alpha3 codes. Enabled by `legacy_country_labels` in config
+//! for the migration window.
+//!
+//! Only the country parameters and fields known to hold codes are translated and only values matching
+//! a country label are replaced, codes pass through as is. `country` of the user addresses is a free text,
+//! so it is never translated in the bodies.
+use std::collections::HashMap;
+use std::str::{self, FromStr};
+use std::sync::Arc;
+
+use failure::Error as FailureError;
+use failure::Fail;
+use futures::prelude::*;
+use hyper::header::ContentLength;
+use hyper::server::Request;
+use hyper::{Body, Uri};
+use serde_json::{self, Map, Value};
+
+use stq_types::{Alpha3, CountryLabel};
+
+use errors::Error;
+use models::Country;
+
+/// Query parameters holding a country code
+pub const COUNTRY_QUERY_PARAMS: &[&str] = &[
+    "country",
+    "from",
+    "to",
+    "delivery_from",
+    "delivery_to",
+    "user_country",
+    "deliveries_from",
+    "delivers_to",
+];
+
+/// Fields of the request and response bodies holding a country code or a list of them
+pub const COUNTRY_FIELDS: &[&str] = &[
+    "from",
+    "to",
+    "from_alpha3",
+    "to_alpha3",
+    "delivery_from",
+    "delivery_to",
+    "deliveries_from",
+    "deliveries_to",
+    "delivers_to",
+    "countries",
+    "region",
+];
+
+/// Suffix of the fields added next to the codes in the responses
+const LABEL_SUFFIX: &str = "_label";
+
+/// Mapping between the labels and the codes of all countries of the tree, labels are matched case-insensitively
+pub struct CountryLabels {
+    by_label: HashMap<String, Alpha3>,
+    by_alpha3: HashMap<String, CountryLabel>,
+}
+
+impl CountryLabels {
+    pub fn new(countries: &[Country]) -> Self {
+        let mut by_label = HashMap::new();
+        let mut by_alpha3 = HashMap::new();
+        for country in countries {
+            by_label.insert(country.label.0.to_lowercase(), country.alpha3.clone());
+            by_alpha3.insert(country.alpha3.0.clone(), country.label.clone());
+        }
+
+        Self { by_label, by_alpha3 }
+    }
+
+    /// Code of the country labeled `value`, `None` if `value` is a code already or is not a label
+    fn to_alpha3(&self, value: &str) -> Option<&Alpha3> {
+        if self.by_alpha3.contains_key(value) {
+            return None;
+        }
+        self.by_label.get(&value.to_lowercase())
+    }
+
+    /// Replaces labels with codes in the country parameters of the query, other parameters are kept as is
+    pub fn translate_query(&self, query: &str) -> String {
+        query
+            .split('&')
+            .map(|param| {
+                let mut parts = param.splitn(2, '=');
+                let (key, value) = (parts.next().unwrap_or(""), parts.next());
+                match value {
+                    Some(value) if COUNTRY_QUERY_PARAMS.contains(&key) => match self.to_alpha3(&decode_query_value(value)) {
+                        Some(alpha3) => format!("{}={}", key, alpha3),
+                        None => param.to_string(),
+                    },
+                    _ => param.to_string(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+
+    /// Replaces labels with codes in the country fields of the body, returns true if anything is replaced
+    pub fn translate_payload(&self, value: &mut Value) -> bool {
+        match *value {
+            Value::Object(ref mut object) => {
+                let mut translated = false;
+                for (key, field) in object.iter_mut() {
+                    translated |= if COUNTRY_FIELDS.contains(&key.as_str()) {
+                        self.translate_country_field(field)
+                    } else {
+                        self.translate_payload(field)
+                    };
+                }
+                translated
+            }
+            Value::Array(ref mut items) => items
+                .iter_mut()
+                .fold(false, |translated, item| self.translate_payload(item) || translated),
+            _ => false,
+        }
+    }
+
+    fn translate_country_field(&self, field: &mut Value) -> bool {
+        match *field {
+            Value::String(ref mut value) => match self.to_alpha3(value) {
+                Some(alpha3) => {
+                    *value = alpha3.0.clone();
+                    true
+                }
+                None => false,
+            },
+            Value::Array(ref mut items) => items
+                .iter_mut()
+                .fold(false, |translated, item| self.translate_country_field(item) || translated),
+            _ => false,
+        }
+    }
+
+    /// Adds `<field>_label` next to the country fields of the response holding known codes
+    pub fn add_labels(&self, value: &mut Value) {
+        match *value {
+            Value::Object(ref mut object) => {
+                for field in object.values_mut() {
+                    self.add_labels(field);
+                }
+
+                let labels = object
+                    .iter()
+                    .filter(|&(key, _)| COUNTRY_FIELDS.contains(&key.as_str()))
+                    .filter_map(|(key, field)| self.labels_of(field).map(|labels| (format!("{}{}", key, LABEL_SUFFIX), labels)))
+                    .collect::<Vec<_>>();
+                insert_missing(object, labels);
+            }
+            Value::Array(ref mut items) => {
+                for item in items.iter_mut() {
+                    self.add_labels(item);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn labels_of(&self, field: &Value) -> Option<Value> {
+        match *field {
+            Value::String(ref alpha3) => self.by_alpha3.get(alpha3).map(|label| Value::String(label.0.clone())),
+            Value::Array(ref items) => items
+                .iter()
+                .map(|item| self.labels_of(item))
+                .collect::<Option<Vec<_>>>()
+                .map(Value::Array),
+            _ => None,
+        }
+    }
+}
+
+/// Request with the labels replaced with codes in the query and the JSON body, bodies which are not JSON are kept as is
+pub fn translate_request(labels: Arc<CountryLabels>, req: Request) -> Box<dyn Future<Item = Request, Error = FailureError>> {
+    let (method, uri, version, mut headers, body) = req.deconstruct();
+    let translated_uri = uri
+        .query()
+        .map(|query| format!("{}?{}", uri.path(), labels.translate_query(query)))
+        .and_then(|translated| Uri::from_str(&translated).ok());
+    let uri = translated_uri.unwrap_or(uri);
+
+    Box::new(body.concat2().map_err(|e| e.context(Error::Parse).into()).map(move |chunk| {
+        let translated_body = serde_json::from_slice::<Value>(&chunk).ok().and_then(|mut value| {
+            if labels.translate_payload(&mut value) {
+                serde_json::to_vec(&value).ok()
+            } else {
+                None
+            }
+        });
+        let body = match translated_body {
+            Some(translated_body) => {
+                headers.remove::<ContentLength>();
+                Body::from(translated_body)
+            }
+            None => Body::from(chunk.to_vec()),
+        };
+
+        let mut req = Request::new(method, uri);
+        req.set_version(version);
+        *req.headers_mut() = headers;
+        req.set_body(body);
+        req
+    }))
+}
+
+/// Response with the labels added next to the codes, responses which are not JSON are kept as is
+pub fn translate_response(labels: &CountryLabels, body: String) -> String {
+    match serde_json::from_str::<Value>(&body) {
+        Ok(mut value) => {
+            labels.add_labels(&mut value);
+            serde_json::to_string(&value).unwrap_or(body)
+        }
+        Err(_) => body,
+    }
+}
+
+fn insert_missing(object: &mut Map<String, Value>, fields: Vec<(String, Value)>) {
+    for (key, value) in fields {
+        object.entry(key).or_insert(value);
+    }
+}
+
+/// Decodes `+` and percent-encoded bytes of the query parameter value, invalid sequences are kept as is
+fn decode_query_value(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => match str::from_utf8(&bytes[i + 1..i + 3])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 2;
+                }
+                None => decoded.push(b'%'),
+            },
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stq_types::Alpha2;
+
+    fn country(label: &str, alpha3: &str) -> Country {
+        Country {
+            label: CountryLabel(label.to_string()),
+            level: Country::COUNTRY_LEVEL,
+            alpha2: Alpha2(alpha3[..2].to_string()),
+            alpha3: Alpha3(alpha3.to_string()),
+            numeric: 0,
+            children: vec![],
+            is_selected: false,
+            parent: None,
+        }
+    }
+
+    fn labels() -> CountryLabels {
+        CountryLabels::new(&[country("Russia", "RUS"), country("United States", "USA")])
+    }
+
+    #[test]
+    fn query_labels_are_replaced_with_codes() {
+        assert_eq!(
+            labels().translate_query("from=Russia&to=United%20States&volume=1000&weight=united+states"),
+            "from=RUS&to=USA&volume=1000&weight=united+states".to_string()
+        );
+        assert_eq!(labels().translate_query("from=RUS&to=Atlantis"), "from=RUS&to=Atlantis".to_string());
+    }
+
+    #[test]
+    fn payload_labels_are_replaced_with_codes() {
+        let mut payload = json!({
+            "deliveries_to": ["united states", "RUS"],
+            "items": [{ "from": "Russia", "country": "Russia" }],
+        });
+
+        assert!(labels().translate_payload(&mut payload));
+        assert_eq!(
+            payload,
+            json!({
+                "deliveries_to": ["USA", "RUS"],
+                "items": [{ "from": "RUS", "country": "Russia" }],
+            })
+        );
+        assert!(!labels().translate_payload(&mut payload));
+    }
+
+    #[test]
+    fn labels_are_added_next_to_codes() {
+        let body = r#"[{"delivery_from":"RUS","deliveries_to":["USA"],"to":"XXX","delivery_from_label":"Rus"}]"#;
+
+        assert_eq!(
+            serde_json::from_str::<Value>(&translate_response(&labels(), body.to_string())).unwrap(),
+            json!([{
+                "delivery_from": "RUS",
+                "delivery_from_label": "Rus",
+                "deliveries_to": ["USA"],
+                "deliveries_to_label": ["United States"],
+                "to": "XXX",
+            }])
+        );
+    }
+}
@@ -1,18 +1,24 @@
+pub mod authentication;
 pub mod context;
+pub mod cors;
+pub mod legacy_labels;
+pub mod openapi;
 pub mod routes;
 
-use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
 
-use diesel::connection::AnsiTransactionManager;
-use diesel::pg::Pg;
-use diesel::Connection;
+use chrono::{DateTime, Utc};
+use failure::Error as FailureError;
 use failure::Fail;
 use futures::future;
 use futures::prelude::*;
 use hyper::header::Authorization;
 use hyper::server::Request;
-use hyper::{Delete, Get, Post, Put};
+use hyper::{Body, Delete, Get, Post, Put};
 use r2d2::ManageConnection;
+use serde::de::DeserializeOwned;
+use serde_json;
 use validator::Validate;
 
 use stq_http::{
@@ -22,87 +28,153 @@ use stq_http::{
 };
 use stq_types::*;
 
+use self::authentication::{Caller, API_KEY_HEADER};
 use self::context::{DynamicContext, StaticContext};
+use self::legacy_labels::CountryLabels;
 use self::routes::Route;
+use diagnostics::{self, ConnectionsReport, DbPoolStats, PoolState, PoolsStats};
 use errors::Error;
+use extras::option::transpose;
+use migrations;
 use models::*;
 use repos::repo_factory::*;
-use repos::CountrySearch;
+use repos::{CountrySearch, DbConnection};
 use sentry_integration::log_and_capture_error;
+use services::admin_overview::AdminOverviewService;
+use services::api_keys::ApiKeysService;
+use services::backfills::BackfillsService;
+use services::carrier_accounts::CarrierAccountsService;
 use services::companies::CompaniesService;
-use services::companies_packages::{CompaniesPackagesService, GetDeliveryPrice, ReplaceShippingRatesPayload};
+use services::companies_packages::{CompaniesPackagesService, DeliveryPriceBatchItem, GetDeliveryPrice, ReplaceShippingRatesPayload};
+use services::company_rates_quotas::CompanyRatesQuotasService;
+use services::company_suspensions::CompanySuspensionsService;
 use services::countries::CountriesService;
+use services::country_regulations::CountryRegulationsService;
+use services::country_subdivisions::CountrySubdivisionsService;
+use services::customs_info::CustomsInfoService;
+use services::delivery_quotes::DeliveryQuotesService;
+use services::notifications::NotificationsService;
+use services::package_templates::PackageTemplatesService;
 use services::packages::PackagesService;
+use services::pickup_points::PickupPointsService;
+use services::postal_zones::PostalZonesService;
+use services::pricing_rules::PricingRulesService;
 use services::products::ProductsService;
+use services::shipping_exports::ShippingExportsService;
+use services::shipping_rates_anomalies::ShippingRatesAnomaliesService;
+use services::shipping_resolver::ShippingResolverService;
+use services::shipping_restrictions::ShippingRestrictionsService;
+use services::shipping_templates::ShippingTemplatesService;
+use services::store_managers::StoreManagersService;
+use services::store_margins::StoreMarginsService;
+use services::store_shipping_summaries::StoreShippingSummariesService;
+use services::surcharges::SurchargesService;
+#[cfg(feature = "test-endpoints")]
+use services::test_fixtures::TestFixturesService;
+use services::types::ServiceFuture;
 use services::user_addresses::UserAddressService;
 use services::user_roles::UserRolesService;
+use services::zones::ZonesService;
 use services::Service;
 
 /// Controller handles route parsing and calling `Service` layer
 pub struct ControllerImpl<T, M, F>
 where
-    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    T: DbConnection,
     M: ManageConnection<Connection = T>,
     F: ReposFactory<T>,
 {
     pub static_context: StaticContext<T, M, F>,
 }
 
-impl<
-        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
-        M: ManageConnection<Connection = T>,
-        F: ReposFactory<T>,
-    > ControllerImpl<T, M, F>
-{
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> ControllerImpl<T, M, F> {
     /// Create a new controller based on services
     pub fn new(static_context: StaticContext<T, M, F>) -> Self {
         Self { static_context }
     }
-}
 
-impl<
-        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
-        M: ManageConnection<Connection = T>,
-        F: ReposFactory<T>,
-    > Controller for ControllerImpl<T, M, F>
-{
-    /// Handle a request and get future response
-    fn call(&self, req: Request) -> ControllerFuture {
-        let headers = req.headers().clone();
-        let auth_header = headers.get::<Authorization<String>>();
-        let user_id = auth_header
-            .map(|auth| auth.0.clone())
-            .and_then(|id| i32::from_str(&id).ok())
-            .map(UserId);
+    /// Handle a request with country codes and get future response
+    fn dispatch(&self, req: Request, caller: Caller) -> ControllerFuture {
+        let (user_id, roles, api_key_scopes) = match caller {
+            Caller::Anonymous => (None, None, None),
+            Caller::User(user) => (Some(user.user_id), user.roles, None),
+            Caller::Service(api_key) => (None, None, Some(api_key.scopes)),
+        };
 
         let correlation_token = request_util::get_correlation_token(&req);
 
-        let dynamic_context = DynamicContext::new(user_id, correlation_token.clone());
+        let operation = diagnostics::operation_name(&req.method().to_string(), req.path());
+        let dynamic_context = DynamicContext::new(user_id, correlation_token.clone(), operation)
+            .with_roles(roles)
+            .with_api_key_scopes(api_key_scopes);
+        let request_context = dynamic_context.request_context.clone();
         let service = Service::new(self.static_context.clone(), dynamic_context);
 
         let path = req.path().to_string();
 
+        let started_at = Instant::now();
+        let method = req.method().to_string();
+        let access_log_filter = self.static_context.access_log_filter.clone();
+        let slo_tracker = self.static_context.slo_tracker.clone();
+        let route_group = self.static_context.route_parser.test(req.path()).and_then(|route| route.group());
+        let schema_validation = self
+            .static_context
+            .config
+            .payload_validation
+            .as_ref()
+            .map(|payload_validation| payload_validation.json_schema)
+            .unwrap_or_default();
+
         let fut = match (&req.method().clone(), self.static_context.route_parser.test(req.path())) {
             (Get, Some(Route::RolesByUserId { user_id })) => serialize_future({ service.get_roles(user_id) }),
-            (Post, Some(Route::Roles)) => {
-                serialize_future({ parse_body::<NewUserRole>(req.body()).and_then(move |data| service.create_role(data)) })
-            }
+            (Post, Some(Route::Roles)) => serialize_future({
+                parse_payload::<NewUserRole>(req.body(), schema_validation).and_then(move |new_user_role| {
+                    new_user_role
+                        .validate()
+                        .map_err(|e| {
+                            format_err!("Validation failed, target: NewUserRole")
+                                .context(Error::Validate(e))
+                                .into()
+                        })
+                        .into_future()
+                        .and_then(move |_| service.create_role(new_user_role))
+                })
+            }),
             (Delete, Some(Route::RolesByUserId { user_id })) => serialize_future({ service.delete_by_user_id(user_id) }),
             (Delete, Some(Route::RoleById { id })) => serialize_future({ service.delete_by_id(id) }),
+            (Get, Some(Route::RolesPermissions)) => serialize_future({ service.get_permissions() }),
 
             // POST /products/<base_product_id>
-            (Post, Some(Route::ProductsById { base_product_id })) => serialize_future(
-                parse_body::<NewShipping>(req.body())
-                    .map_err(move |e| {
-                        e.context(format!(
-                            "Parsing body failed, target: NewShipping, base_product_id: {}",
-                            base_product_id
-                        ))
+            // POST /products/<base_product_id>?store_id=<store_id>&template_id=<template_id>
+            (Post, Some(Route::ProductsById { base_product_id })) => {
+                match parse_query!(req.query().unwrap_or_default(), "store_id" => i32, "template_id" => i32) {
+                    (Some(store_id), Some(template_id)) => serialize_future(service.upsert(
+                        base_product_id,
+                        ShippingUpsert::Template {
+                            store_id: StoreId(store_id),
+                            template_id,
+                        },
+                    )),
+                    (None, None) => serialize_future(
+                        parse_payload::<NewShipping>(req.body(), schema_validation)
+                            .map_err(move |e| {
+                                e.context(format!(
+                                    "Parsing body failed, target: NewShipping, base_product_id: {}",
+                                    base_product_id
+                                ))
+                                .into()
+                            })
+                            .and_then(move |new_shipping| service.upsert(base_product_id, ShippingUpsert::Shipping(new_shipping))),
+                    ),
+                    _ => Box::new(future::err(
+                        format_err!(
+                            "Parsing query parameters failed, action: upsert shipping by template, store_id and template_id are required"
+                        )
                         .context(Error::Parse)
-                        .into()
-                    })
-                    .and_then(move |new_shipping| service.upsert(base_product_id, new_shipping)),
-            ),
+                        .into(),
+                    )),
+                }
+            }
 
             // GET /products/<base_product_id>
             (Get, Some(Route::ProductsById { base_product_id })) => serialize_future(service.get_by_base_product_id(base_product_id)),
@@ -118,54 +190,181 @@ impl<
                     company_package_id,
                 }),
             ) => serialize_future(
-                parse_body::<UpdateProducts>(req.body())
+                parse_payload::<UpdateProducts>(req.body(), schema_validation)
                     .map_err(move |e| {
                         e.context(format!(
                             "Parsing body failed, target: UpdateProducts, base_product_id: {}, company_package_id: {}",
                             base_product_id, company_package_id
                         ))
-                        .context(Error::Parse)
                         .into()
                     })
                     .and_then(move |update_products| service.update_products(base_product_id, company_package_id, update_products)),
             ),
 
+            // POST /products/<base_product_id>/clone_to
+            (Post, Some(Route::ProductsCloneTo { base_product_id })) => serialize_future(
+                parse_payload::<Vec<BaseProductId>>(req.body(), schema_validation)
+                    .map_err(move |e| {
+                        e.context(format!(
+                            "Parsing body failed, target: CloneShippingTargets, base_product_id: {}",
+                            base_product_id
+                        ))
+                        .into()
+                    })
+                    .and_then(move |targets| service.clone_shipping(base_product_id, targets)),
+            ),
+
+            // GET /products/<base_product_id>/customs_info
+            (Get, Some(Route::ProductsCustomsInfo { base_product_id })) => serialize_future(service.get_customs_info(base_product_id)),
+
+            // PUT /products/<base_product_id>/customs_info
+            (Put, Some(Route::ProductsCustomsInfo { base_product_id })) => serialize_future(
+                parse_payload::<SetCustomsInfo>(req.body(), schema_validation)
+                    .map_err(move |e| {
+                        e.context(format!(
+                            "Parsing body failed, target: SetCustomsInfo, base_product_id: {}",
+                            base_product_id
+                        ))
+                        .into()
+                    })
+                    .and_then(move |customs_info| {
+                        customs_info
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: SetCustomsInfo")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.set_customs_info(base_product_id, customs_info))
+                    }),
+            ),
+
+            // DELETE /products/<base_product_id>/customs_info
+            (Delete, Some(Route::ProductsCustomsInfo { base_product_id })) => {
+                serialize_future(service.delete_customs_info(base_product_id))
+            }
+
             // POST /companies
             (Post, Some(Route::Companies)) => serialize_future(
-                parse_body::<NewCompany>(req.body())
-                    .map_err(|e| e.context("Parsing body failed, target: NewCompanies").context(Error::Parse).into())
-                    .and_then(move |new_company| service.create_company(new_company)),
+                parse_payload::<NewCompany>(req.body(), schema_validation)
+                    .map_err(|e| e.context("Parsing body failed, target: NewCompanies").into())
+                    .and_then(move |new_company| {
+                        new_company
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: NewCompany")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.create_company(new_company))
+                    }),
             ),
 
             // GET /companies
-            (Get, Some(Route::Companies)) => serialize_future(service.list_companies()),
+            // GET /companies/search
+            (Get, Some(Route::Companies)) | (Get, Some(Route::CompaniesSearch)) => {
+                let (deliveries_from, delivers_to, supports, label_contains, sort, order, lang) = parse_query!(
+                    req.query().unwrap_or_default(),
+                    "deliveries_from" => Alpha3,
+                    "delivers_to" => Alpha3,
+                    "supports" => CompanyCapabilities,
+                    "label_contains" => String,
+                    "sort" => CompaniesSortField,
+                    "order" => SortOrder,
+                    "lang" => String
+                );
+                let search = CompaniesSearch {
+                    deliveries_from,
+                    delivers_to,
+                    supports,
+                    label_contains,
+                    sort,
+                    order,
+                };
+                serialize_future(localize(service.list_companies(search), lang))
+            }
 
             // GET /companies/<company_id>
-            (Get, Some(Route::CompanyById { company_id })) => serialize_future(service.find_company(company_id)),
+            (Get, Some(Route::CompanyById { company_id })) => {
+                let lang = parse_query!(req.query().unwrap_or_default(), "lang" => String);
+                serialize_future(localize(service.find_company(company_id), lang))
+            }
 
             // PUT /companies/<company_id>
             (Put, Some(Route::CompanyById { company_id })) => serialize_future(
-                parse_body::<UpdateCompany>(req.body())
+                parse_payload::<UpdateCompany>(req.body(), schema_validation)
                     .map_err(move |e| {
                         e.context(format!("Parsing body failed, target: UpdateCompany, company id: {}", company_id))
-                            .context(Error::Parse)
                             .into()
                     })
-                    .and_then(move |update_company| service.update_company(company_id, update_company)),
+                    .and_then(move |update_company| {
+                        update_company
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: UpdateCompany")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.update_company(company_id, update_company))
+                    }),
             ),
 
             // DELETE /companies/<company_id>
-            (Delete, Some(Route::CompanyById { company_id })) => serialize_future(service.delete_company(company_id)),
+            (Delete, Some(Route::CompanyById { company_id })) => {
+                let cascade = parse_query!(req.query().unwrap_or_default(), "cascade" => bool).unwrap_or(false);
+                serialize_future(service.delete_company(company_id, cascade))
+            }
+
+            // GET /companies/<company_id>/deletion_impact
+            (Get, Some(Route::CompanyDeletionImpact { company_id })) => serialize_future(service.get_company_deletion_impact(company_id)),
+
+            // GET /companies/<company_id>/rates_quota
+            (Get, Some(Route::CompanyRatesQuota { company_id })) => serialize_future(service.get_company_rates_quota(company_id)),
+
+            // PUT /companies/<company_id>/rates_quota
+            (Put, Some(Route::CompanyRatesQuota { company_id })) => serialize_future(
+                parse_payload::<SetCompanyRatesQuota>(req.body(), schema_validation)
+                    .map_err(move |e| {
+                        e.context(format!(
+                            "Parsing body failed, target: SetCompanyRatesQuota, company id: {}",
+                            company_id
+                        ))
+                        .into()
+                    })
+                    .and_then(move |rates_quota| {
+                        rates_quota
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: SetCompanyRatesQuota")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.set_company_rates_quota(company_id, rates_quota))
+                    }),
+            ),
+
+            // DELETE /companies/<company_id>/rates_quota
+            (Delete, Some(Route::CompanyRatesQuota { company_id })) => serialize_future(service.delete_company_rates_quota(company_id)),
 
             // POST /companies_packages
             (Post, Some(Route::CompaniesPackages)) => serialize_future(
-                parse_body::<NewCompanyPackage>(req.body())
-                    .map_err(|e| {
-                        e.context("Parsing body failed, target: NewCompaniesPackages")
-                            .context(Error::Parse)
-                            .into()
-                    })
-                    .and_then(move |new_companies_packages| service.create_company_package(new_companies_packages)),
+                parse_payload::<NewCompanyPackage>(req.body(), schema_validation)
+                    .map_err(|e| e.context("Parsing body failed, target: NewCompaniesPackages").into())
+                    .and_then(move |new_companies_packages| {
+                        new_companies_packages
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: NewCompaniesPackages")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.create_company_package(new_companies_packages))
+                    }),
             ),
 
             // GET /companies_packages/<company_package_id>/rates
@@ -184,34 +383,235 @@ impl<
                 }
             }
 
+            // GET /companies_packages/<company_package_id>/products
+            (Get, Some(Route::CompanyPackageProducts { company_package_id })) => {
+                let (offset, limit) = parse_query!(req.query().unwrap_or_default(), "offset" => i64, "limit" => i64);
+                let offset = offset.unwrap_or(0);
+                let limit = limit.unwrap_or(DEFAULT_COMPANY_PACKAGE_PRODUCTS_LIMIT);
+                if offset >= 0 && limit > 0 && limit <= MAX_COMPANY_PACKAGE_PRODUCTS_LIMIT {
+                    serialize_future(service.get_company_package_products(company_package_id, offset, limit))
+                } else {
+                    Box::new(future::err(
+                        format_err!(
+                            "Parsing query parameters failed, action: get products of company package, offset: {}, limit: {}",
+                            offset,
+                            limit
+                        )
+                        .context(Error::Parse)
+                        .into(),
+                    ))
+                }
+            }
+
             // POST /companies_packages/<company_package_id>/rates
-            (Post, Some(Route::CompanyPackageRates { company_package_id })) => serialize_future(
-                parse_body::<ReplaceShippingRatesPayload>(req.body())
-                    .map_err(|e| {
-                        e.context("Parsing body failed, target: ReplaceShippingRatesPayload")
-                            .context(Error::Parse)
-                            .into()
+            (Post, Some(Route::CompanyPackageRates { company_package_id })) => {
+                let dry_run = parse_query!(req.query().unwrap_or_default(), "dry_run" => bool).unwrap_or(false);
+                let payload = parse_body::<ReplaceShippingRatesPayload>(req.body()).map_err(|e| {
+                    e.context("Parsing body failed, target: ReplaceShippingRatesPayload")
+                        .context(Error::Parse)
+                        .into()
+                });
+
+                if dry_run {
+                    serialize_future(
+                        payload.and_then(move |payload| service.preview_shipping_rates_replacement(company_package_id, payload)),
+                    )
+                } else {
+                    serialize_future(payload.and_then(move |payload| service.replace_shipping_rates(company_package_id, payload)))
+                }
+            }
+
+            // POST /companies_packages/<company_package_id>/rates/components
+            (Post, Some(Route::CompanyPackageRateComponents { company_package_id })) => serialize_future(
+                parse_payload::<NewRateComponents>(req.body(), schema_validation)
+                    .map_err(move |e| {
+                        e.context(format!(
+                            "Parsing body failed, target: NewRateComponents, company package id: {}",
+                            company_package_id
+                        ))
+                        .into()
+                    })
+                    .and_then(move |payload| {
+                        payload
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: NewRateComponents")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.add_rate_components(company_package_id, payload))
+                    }),
+            ),
+
+            // PUT /companies_packages/<company_package_id>/rate_resolution_order
+            (Put, Some(Route::CompanyPackageRateResolutionOrder { company_package_id })) => serialize_future(
+                parse_payload::<SetRateResolutionOrder>(req.body(), schema_validation)
+                    .map_err(move |e| {
+                        e.context(format!(
+                            "Parsing body failed, target: SetRateResolutionOrder, company package id: {}",
+                            company_package_id
+                        ))
+                        .into()
+                    })
+                    .and_then(move |payload| {
+                        payload
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: SetRateResolutionOrder")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.set_rate_resolution_order(company_package_id, payload))
+                    }),
+            ),
+
+            // PUT /companies_packages/<company_package_id>/cash_on_delivery
+            (Put, Some(Route::CompanyPackageCashOnDelivery { company_package_id })) => serialize_future(
+                parse_payload::<SetCashOnDelivery>(req.body(), schema_validation)
+                    .map_err(move |e| {
+                        e.context(format!(
+                            "Parsing body failed, target: SetCashOnDelivery, company package id: {}",
+                            company_package_id
+                        ))
+                        .into()
+                    })
+                    .and_then(move |payload| {
+                        payload
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: SetCashOnDelivery")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.set_cash_on_delivery(company_package_id, payload))
+                    }),
+            ),
+
+            // PUT /companies_packages/<company_package_id>/carrier_fallback
+            (Put, Some(Route::CompanyPackageCarrierFallback { company_package_id })) => serialize_future(
+                parse_payload::<SetCarrierFallback>(req.body(), schema_validation)
+                    .map_err(move |e| {
+                        e.context(format!(
+                            "Parsing body failed, target: SetCarrierFallback, company package id: {}",
+                            company_package_id
+                        ))
+                        .into()
                     })
-                    .and_then(move |payload| service.replace_shipping_rates(company_package_id, payload)),
+                    .and_then(move |payload| {
+                        payload
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: SetCarrierFallback")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.set_carrier_fallback(company_package_id, payload))
+                    }),
+            ),
+
+            // PUT /companies_packages/<company_package_id>/shipping_capacity
+            (Put, Some(Route::CompanyPackageShippingCapacity { company_package_id })) => serialize_future(
+                parse_payload::<SetShippingCapacity>(req.body(), schema_validation)
+                    .map_err(move |e| {
+                        e.context(format!(
+                            "Parsing body failed, target: SetShippingCapacity, company package id: {}",
+                            company_package_id
+                        ))
+                        .into()
+                    })
+                    .and_then(move |payload| {
+                        payload
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: SetShippingCapacity")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.set_shipping_capacity(company_package_id, payload))
+                    }),
+            ),
+
+            // POST /companies_packages/<company_package_id>/shipment_bookings
+            (Post, Some(Route::CompanyPackageShipmentBookings { company_package_id })) => serialize_future(
+                parse_payload::<BookShipment>(req.body(), schema_validation)
+                    .map_err(move |e| {
+                        e.context(format!(
+                            "Parsing body failed, target: BookShipment, company package id: {}",
+                            company_package_id
+                        ))
+                        .into()
+                    })
+                    .and_then(move |payload| service.book_shipment(company_package_id, payload)),
             ),
 
             // GET /companies_packages/<company_package_id>/price
             (Get, Some(Route::CompanyPackageDeliveryPrice { company_package_id })) => {
-                if let (Some(delivery_from), Some(delivery_to), Some(volume), Some(weight)) = parse_query!(
+                if let (
+                    Some(delivery_from),
+                    Some(delivery_to),
+                    Some(volume),
+                    Some(weight),
+                    volume_unit,
+                    weight_unit,
+                    detailed,
+                    pickup_point_id,
+                    declared_value,
+                    insurance,
+                    cod,
+                    store_id,
+                    base_product_id,
+                    delivery_to_subdivision,
+                    postal_code,
+                ) = parse_query!(
                     req.query().unwrap_or_default(),
                     "from" => Alpha3,
                     "to" => Alpha3,
-                    "volume" => u32,
-                    "weight" => u32
+                    "volume" => f64,
+                    "weight" => f64,
+                    "volume_unit" => String,
+                    "weight_unit" => String,
+                    "detailed" => bool,
+                    "pickup_point_id" => i32,
+                    "declared_value" => f64,
+                    "insurance" => String,
+                    "cod" => bool,
+                    "store_id" => StoreId,
+                    "base_product_id" => BaseProductId,
+                    "to_subdivision" => String,
+                    "postal_code" => String
                 ) {
-                    let payload = GetDeliveryPrice {
+                    let coordinates = parse_delivery_coordinates(req.query().unwrap_or_default());
+                    let payload = parse_measurements(volume, volume_unit, weight, weight_unit).map(|measurements| GetDeliveryPrice {
                         company_package_id,
                         delivery_from,
                         delivery_to,
-                        volume,
-                        weight,
-                    };
-                    serialize_future(service.get_delivery_price(payload))
+                        volume: measurements.volume_cubic_cm,
+                        weight: measurements.weight_g,
+                        coordinates,
+                        pickup_point_id,
+                        declared_value,
+                        insurance,
+                        cod: cod.unwrap_or(false),
+                        store_id,
+                        base_product_id,
+                        delivery_to_subdivision,
+                        postal_code,
+                    });
+                    match payload {
+                        Ok(payload) => {
+                            if detailed.unwrap_or(false) {
+                                serialize_future(service.get_delivery_price_details(payload))
+                            } else {
+                                serialize_future(service.get_delivery_price(payload))
+                            }
+                        }
+                        Err(e) => Box::new(future::err(e)),
+                    }
                 } else {
                     Box::new(future::err(
                         format_err!("Parsing query parameters failed, action: get delivery price")
@@ -221,12 +621,60 @@ impl<
                 }
             }
 
+            // POST /delivery_prices/batch
+            (Post, Some(Route::DeliveryPricesBatch)) => serialize_future(
+                parse_body::<Vec<DeliveryPriceBatchItem>>(req.body())
+                    .map_err(|e| {
+                        e.context("Parsing body failed, target: Vec<DeliveryPriceBatchItem>")
+                            .context(Error::Parse)
+                            .into()
+                    })
+                    .and_then(move |items| service.get_delivery_prices_batch(items)),
+            ),
+
+            // POST /delivery_quotes
+            (Post, Some(Route::DeliveryQuotes)) => serialize_future(
+                parse_body::<GetDeliveryPrice>(req.body())
+                    .map_err(|e| {
+                        e.context("Parsing body failed, target: GetDeliveryPrice")
+                            .context(Error::Parse)
+                            .into()
+                    })
+                    .and_then(move |payload| service.create_delivery_quote(payload)),
+            ),
+
+            // GET /delivery_quotes/<quote_id>
+            (Get, Some(Route::DeliveryQuoteById { quote_id })) => serialize_future(service.get_delivery_quote(quote_id)),
+
             // GET /available_packages
             (Get, Some(Route::AvailablePackages)) => {
-                if let (Some(country), Some(size), Some(weight)) =
-                    parse_query!(req.query().unwrap_or_default(), "country" => Alpha3, "size" => u32, "weight" => u32)
-                {
-                    serialize_future(service.get_available_packages(country, size, weight))
+                if let (Some(country), Some(size), Some(weight), volume_unit, weight_unit, explain, with_measurements) = parse_query!(
+                    req.query().unwrap_or_default(),
+                    "country" => Alpha3,
+                    "size" => f64,
+                    "weight" => f64,
+                    "volume_unit" => String,
+                    "weight_unit" => String,
+                    "explain" => bool,
+                    "with_measurements" => bool
+                ) {
+                    match parse_measurements(size, volume_unit, weight, weight_unit) {
+                        Ok(measurements) => {
+                            let ShipmentMeasurements { volume_cubic_cm, weight_g } = measurements;
+                            if explain.unwrap_or(false) {
+                                serialize_future(service.explain_available_packages(country, volume_cubic_cm, weight_g))
+                            } else if with_measurements.unwrap_or(false) {
+                                serialize_future(
+                                    service
+                                        .get_available_packages(country, volume_cubic_cm, weight_g)
+                                        .map(move |packages| AvailablePackagesForShipment { packages, measurements }),
+                                )
+                            } else {
+                                serialize_future(service.get_available_packages(country, volume_cubic_cm, weight_g))
+                            }
+                        }
+                        Err(e) => Box::new(future::err(e)),
+                    }
                 } else {
                     Box::new(future::err(
                         format_err!("Parsing query parameters failed, action: get available packages")
@@ -238,8 +686,20 @@ impl<
 
             // GET /available_packages_for_user/<base_product_id>
             (Get, Some(Route::AvailablePackagesForUser { base_product_id })) => {
-                if let Some(user_country) = parse_query!(req.query().unwrap_or_default(), "user_country" => Alpha3) {
-                    serialize_future(service.find_available_shipping_for_user(base_product_id, user_country))
+                if let (Some(user_country), explain, cod, order_time) = parse_query!(
+                    req.query().unwrap_or_default(),
+                    "user_country" => Alpha3,
+                    "explain" => bool,
+                    "cod" => bool,
+                    "order_time" => DateTime<Utc>
+                ) {
+                    serialize_future(service.find_available_shipping_for_user(
+                        base_product_id,
+                        user_country,
+                        explain.unwrap_or(false),
+                        cod.unwrap_or(false),
+                        order_time,
+                    ))
                 } else {
                     Box::new(future::err(
                         format_err!(
@@ -253,46 +713,115 @@ impl<
             }
 
             // GET /v2/available_packages_for_user/<base_product_id>
+            // `to_region` may be passed instead of `delivery_to` when the country of the visitor is unknown
             (Get, Some(Route::AvailablePackagesForUserV2 { base_product_id })) => {
-                if let (Some(delivery_from), Some(delivery_to), Some(volume), Some(weight)) = parse_query!(
+                let (
+                    delivery_from,
+                    delivery_to,
+                    to_region,
+                    volume,
+                    weight,
+                    volume_unit,
+                    weight_unit,
+                    explain,
+                    with_cost,
+                    order_total,
+                    cod,
+                    origin_preference,
+                    order_time,
+                ) = parse_query!(
                     req.query().unwrap_or_default(),
                     "delivery_from" => Alpha3,
                     "delivery_to" => Alpha3,
-                    "volume" => u32,
-                    "weight" => u32
-                ) {
-                    serialize_future(service.find_available_shipping_for_user_v2(
-                        base_product_id,
-                        delivery_from,
-                        delivery_to,
-                        volume,
-                        weight,
-                    ))
-                } else {
-                    Box::new(future::err(
-                        format_err!(
-                            "Parsing query parameters failed, action: get available packages for user v2, base product id: {}",
-                            base_product_id
-                        )
-                        .context(Error::Parse)
-                        .into(),
-                    ))
+                    "to_region" => String,
+                    "volume" => f64,
+                    "weight" => f64,
+                    "volume_unit" => String,
+                    "weight_unit" => String,
+                    "explain" => bool,
+                    "with_cost" => bool,
+                    "order_total" => f64,
+                    "cod" => bool,
+                    "origin_preference" => OriginPreference,
+                    "order_time" => DateTime<Utc>
+                );
+
+                let measurements = match (volume, weight) {
+                    (Some(volume), Some(weight)) => parse_measurements(volume, volume_unit, weight, weight_unit).map(Some),
+                    _ => Ok(None),
+                };
+
+                match measurements {
+                    Ok(Some(measurements)) => {
+                        if let (Some(delivery_from), Some(delivery_to)) = (delivery_from.clone(), delivery_to) {
+                            serialize_future(service.find_available_shipping_for_user_v2(
+                                base_product_id,
+                                delivery_from,
+                                delivery_to,
+                                measurements.volume_cubic_cm,
+                                measurements.weight_g,
+                                parse_delivery_coordinates(req.query().unwrap_or_default()),
+                                explain.unwrap_or(false),
+                                with_cost.unwrap_or(false),
+                                order_total,
+                                cod.unwrap_or(false),
+                                origin_preference.unwrap_or_default(),
+                                order_time,
+                            ))
+                        } else if let (Some(delivery_from), Some(region)) = (delivery_from, to_region) {
+                            serialize_future(service.find_available_shipping_for_region(
+                                base_product_id,
+                                delivery_from,
+                                region,
+                                measurements.volume_cubic_cm,
+                                measurements.weight_g,
+                                order_time,
+                            ))
+                        } else {
+                            Box::new(future::err(parse_available_packages_for_user_v2_error(base_product_id)))
+                        }
+                    }
+                    Ok(None) => Box::new(future::err(parse_available_packages_for_user_v2_error(base_product_id))),
+                    Err(e) => Box::new(future::err(e)),
                 }
             }
 
             // GET /available_packages_for_user/products/:id/companies_packages/:id
 
             // DEPRECATED
-            // BaseProductId and CompanyPackageId identifiers do not ensure uniqueness of the requested AvailablePackage.
-            // This means that the endpoint may return varying results for the same query
-            // "GET /v2/available_packages_for_user/by_shipping_id/:id" has to be used instead
+            // BaseProductId and CompanyPackageId identifiers do not ensure uniqueness of the requested AvailablePackage,
+            // ambiguous requests conflict. "GET /v2/available_packages_for_user/by_shipping_id/:id" has to be used instead
             (
                 Get,
                 Some(Route::AvailablePackageForUser {
                     base_product_id,
                     company_package_id,
                 }),
-            ) => serialize_future(service.get_available_package_for_user(base_product_id, company_package_id)),
+            ) => {
+                let is_gone = self
+                    .static_context
+                    .config
+                    .deprecated_routes
+                    .as_ref()
+                    .map(|deprecated_routes| deprecated_routes.available_package_for_user_gone)
+                    .unwrap_or_default();
+
+                if is_gone {
+                    Box::new(future::err(
+                        format_err!(
+                            "Deprecated route, base product id: {}, company package id: {}",
+                            base_product_id,
+                            company_package_id
+                        )
+                        .context(Error::Gone(validation_errors!({
+                            "route": ["gone" => "Use GET /v2/available_packages_for_user/by_shipping_id/:id"]
+                        })))
+                        .into(),
+                    ))
+                } else {
+                    serialize_future(service.resolve_available_package_for_user(base_product_id, company_package_id))
+                }
+            }
 
             // GET /available_packages_for_user/by_shipping_id/:id
             (Get, Some(Route::AvailablePackageForUserByShippingId { shipping_id })) => {
@@ -301,20 +830,44 @@ impl<
 
             // GET /v2/available_packages_for_user/by_shipping_id/:id
             (Get, Some(Route::AvailablePackageForUserByShippingIdV2 { shipping_id })) => {
-                if let (Some(delivery_from), Some(delivery_to), Some(volume), Some(weight)) = parse_query!(
+                if let (
+                    Some(delivery_from),
+                    Some(delivery_to),
+                    Some(volume),
+                    Some(weight),
+                    volume_unit,
+                    weight_unit,
+                    with_cost,
+                    option_id,
+                    order_time,
+                ) = parse_query!(
                     req.query().unwrap_or_default(),
                     "delivery_from" => Alpha3,
                     "delivery_to" => Alpha3,
-                    "volume" => u32,
-                    "weight" => u32
+                    "volume" => f64,
+                    "weight" => f64,
+                    "volume_unit" => String,
+                    "weight_unit" => String,
+                    "with_cost" => bool,
+                    "option_id" => ShippingOptionId,
+                    "order_time" => DateTime<Utc>
                 ) {
-                    serialize_future(service.get_available_package_for_user_by_shipping_id_v2(
-                        shipping_id,
-                        delivery_from,
-                        delivery_to,
-                        volume,
-                        weight,
-                    ))
+                    match parse_measurements(volume, volume_unit, weight, weight_unit) {
+                        Ok(ShipmentMeasurements { volume_cubic_cm, weight_g }) => {
+                            serialize_future(service.get_available_package_for_user_by_shipping_id_v2(
+                                shipping_id,
+                                delivery_from,
+                                delivery_to,
+                                volume_cubic_cm,
+                                weight_g,
+                                parse_delivery_coordinates(req.query().unwrap_or_default()),
+                                with_cost.unwrap_or(false),
+                                option_id,
+                                order_time,
+                            ))
+                        }
+                        Err(e) => Box::new(future::err(e)),
+                    }
                 } else {
                     Box::new(future::err(
                         format_err!(
@@ -340,7 +893,8 @@ impl<
 
             // DELETE /companies/<company_id>/packages/<package_id>
             (Delete, Some(Route::CompaniesPackagesByIds { company_id, package_id })) => {
-                serialize_future(service.delete_company_package(company_id, package_id))
+                let force = parse_query!(req.query().unwrap_or_default(), "force" => bool).unwrap_or(false);
+                serialize_future(service.delete_company_package(company_id, package_id, force))
             }
 
             // GET /countries
@@ -369,8 +923,8 @@ impl<
 
             // POST /countries
             (Post, Some(Route::Countries)) => serialize_future(
-                parse_body::<NewCountry>(req.body())
-                    .map_err(|e| e.context("Parsing body failed, target: NewCountry").context(Error::Parse).into())
+                parse_payload::<NewCountry>(req.body(), schema_validation)
+                    .map_err(|e| e.context("Parsing body failed, target: NewCountry").into())
                     .and_then(move |new_country| {
                         new_country
                             .validate()
@@ -384,44 +938,192 @@ impl<
                     }),
             ),
 
-            // POST /packages
-            (Post, Some(Route::Packages)) => serialize_future(
-                parse_body::<NewPackages>(req.body())
-                    .map_err(|e| e.context("Parsing body failed, target: NewPackages").context(Error::Parse).into())
-                    .and_then(move |new_package| service.create_package(new_package)),
-            ),
+            // GET /countries/validate
+            (Get, Some(Route::CountriesValidate)) => serialize_future(service.validate_countries_tree()),
 
-            // GET /packages/<package_id>
-            (Get, Some(Route::PackagesById { package_id })) => serialize_future(service.find_packages(package_id)),
+            // POST /countries/repair
+            (Post, Some(Route::CountriesRepair)) => serialize_future(service.repair_countries_tree()),
 
-            // GET /packages
-            (Get, Some(Route::Packages)) => serialize_future(service.list_packages()),
+            // GET /countries/dependents
+            (Get, Some(Route::CountriesDependents)) => serialize_future(service.get_country_dependents_refresh()),
 
-            // PUT /packages/<package_id>
-            (Put, Some(Route::PackagesById { package_id })) => serialize_future(
-                parse_body::<UpdatePackages>(req.body())
-                    .map_err(move |e| {
-                        e.context(format!("Parsing body failed, target: UpdatePackages, package id: {}", package_id))
-                            .context(Error::Parse)
-                            .into()
-                    })
-                    .and_then(move |update_package| service.update_package(package_id, update_package)),
+            // POST /countries/import
+            (Post, Some(Route::CountriesImport)) => serialize_future(
+                parse_payload::<ImportCountries>(req.body(), schema_validation)
+                    .map_err(|e| e.context("Parsing body failed, target: ImportCountries").into())
+                    .and_then(move |payload| {
+                        payload
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: ImportCountries")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.import_countries(payload))
+                    }),
+            ),
+
+            // GET /country_regulations
+            (Get, Some(Route::CountryRegulations)) => serialize_future(service.list_country_regulations()),
+
+            // GET /country_regulations/<alpha3>
+            (Get, Some(Route::CountryRegulationByAlpha3 { alpha3 })) => serialize_future(service.get_country_regulation(alpha3)),
+
+            // PUT /country_regulations/<alpha3>
+            (Put, Some(Route::CountryRegulationByAlpha3 { alpha3 })) => serialize_future(
+                parse_payload::<SetCountryRegulation>(req.body(), schema_validation)
+                    .map_err(|e| e.context("Parsing body failed, target: SetCountryRegulation").into())
+                    .and_then(move |country_regulation| {
+                        country_regulation
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: SetCountryRegulation")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.set_country_regulation(alpha3, country_regulation))
+                    }),
+            ),
+
+            // DELETE /country_regulations/<alpha3>
+            (Delete, Some(Route::CountryRegulationByAlpha3 { alpha3 })) => serialize_future(service.delete_country_regulation(alpha3)),
+
+            // GET /countries/<alpha3>/subdivisions
+            (Get, Some(Route::CountrySubdivisions { alpha3 })) => serialize_future(service.list_country_subdivisions(alpha3)),
+
+            // POST /countries/<alpha3>/subdivisions
+            (Post, Some(Route::CountrySubdivisions { alpha3 })) => serialize_future(
+                parse_payload::<NewCountrySubdivision>(req.body(), schema_validation)
+                    .map_err(|e| e.context("Parsing body failed, target: NewCountrySubdivision").into())
+                    .and_then(move |new_subdivision| {
+                        new_subdivision
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: NewCountrySubdivision")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.create_country_subdivision(alpha3, new_subdivision))
+                    }),
+            ),
+
+            // GET /countries/<alpha3>/subdivisions/<code>
+            (Get, Some(Route::CountrySubdivision { alpha3, code })) => serialize_future(service.get_country_subdivision(alpha3, code)),
+
+            // PUT /countries/<alpha3>/subdivisions/<code>
+            (Put, Some(Route::CountrySubdivision { alpha3, code })) => serialize_future(
+                parse_payload::<UpdateCountrySubdivision>(req.body(), schema_validation)
+                    .map_err(|e| e.context("Parsing body failed, target: UpdateCountrySubdivision").into())
+                    .and_then(move |update_subdivision| {
+                        update_subdivision
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: UpdateCountrySubdivision")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.update_country_subdivision(alpha3, code, update_subdivision))
+                    }),
+            ),
+
+            // DELETE /countries/<alpha3>/subdivisions/<code>
+            (Delete, Some(Route::CountrySubdivision { alpha3, code })) => {
+                serialize_future(service.delete_country_subdivision(alpha3, code))
+            }
+
+            // POST /packages
+            (Post, Some(Route::Packages)) => serialize_future(
+                parse_payload::<NewPackages>(req.body(), schema_validation)
+                    .map_err(|e| e.context("Parsing body failed, target: NewPackages").into())
+                    .and_then(move |new_package| {
+                        new_package
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: NewPackages")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.create_package(new_package))
+                    }),
+            ),
+
+            // GET /packages/<package_id>
+            (Get, Some(Route::PackagesById { package_id })) => {
+                let lang = parse_query!(req.query().unwrap_or_default(), "lang" => String);
+                serialize_future(localize(service.find_packages(package_id), lang))
+            }
+
+            // GET /packages
+            (Get, Some(Route::Packages)) => {
+                let lang = parse_query!(req.query().unwrap_or_default(), "lang" => String);
+                serialize_future(localize(service.list_packages(), lang))
+            }
+
+            // GET /packages/search?delivers_to=<alpha3>&max_weight=<weight_g>&offset=<offset>&limit=<limit>
+            (Get, Some(Route::PackagesSearch)) => {
+                let (delivers_to, max_weight, offset, limit) = parse_query!(
+                    req.query().unwrap_or_default(),
+                    "delivers_to" => Alpha3,
+                    "max_weight" => u32,
+                    "offset" => i64,
+                    "limit" => i64
+                );
+                let offset = offset.unwrap_or(0);
+                let limit = limit.unwrap_or(DEFAULT_PACKAGES_SEARCH_LIMIT);
+                match delivers_to {
+                    Some(delivers_to) if offset >= 0 && limit > 0 && limit <= MAX_PACKAGES_SEARCH_LIMIT => {
+                        serialize_future(service.search_packages(delivers_to, max_weight, offset, limit))
+                    }
+                    _ => Box::new(future::err(
+                        format_err!(
+                            "Parsing query parameters failed, action: search packages, offset: {}, limit: {}",
+                            offset,
+                            limit
+                        )
+                        .context(Error::Parse)
+                        .into(),
+                    )),
+                }
+            }
+
+            // PUT /packages/<package_id>
+            (Put, Some(Route::PackagesById { package_id })) => serialize_future(
+                parse_payload::<UpdatePackages>(req.body(), schema_validation)
+                    .map_err(move |e| {
+                        e.context(format!("Parsing body failed, target: UpdatePackages, package id: {}", package_id))
+                            .into()
+                    })
+                    .and_then(move |update_package| {
+                        update_package
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: UpdatePackages")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.update_package(package_id, update_package))
+                    }),
             ),
 
             // DELETE /packages/<package_id>
             (Delete, Some(Route::PackagesById { package_id })) => serialize_future(service.delete_package(package_id)),
 
             // GET /users/<user_id>/addresses
-            (Get, Some(Route::UserAddress { user_id })) => serialize_future(service.get_addresses(user_id)),
+            (Get, Some(Route::UserAddress { user_id })) => {
+                let with_coordinates = parse_query!(req.query().unwrap_or_default(), "with_coordinates" => bool).unwrap_or_default();
+                serialize_future(service.get_addresses(user_id, with_coordinates))
+            }
 
             // POST /users/addresses
             (Post, Some(Route::UsersAddresses)) => serialize_future(
-                parse_body::<NewUserAddress>(req.body())
-                    .map_err(|e| {
-                        e.context("Parsing body failed, target: NewUserAddress")
-                            .context(Error::Parse)
-                            .into()
-                    })
+                parse_payload::<NewUserAddress>(req.body(), schema_validation)
+                    .map_err(|e| e.context("Parsing body failed, target: NewUserAddress").into())
                     .and_then(move |new_address| {
                         new_address
                             .validate()
@@ -437,13 +1139,12 @@ impl<
 
             // PUT /users/addresses/<id>
             (Put, Some(Route::UserAddressById { user_address_id })) => serialize_future(
-                parse_body::<UpdateUserAddress>(req.body())
+                parse_payload::<UpdateUserAddress>(req.body(), schema_validation)
                     .map_err(move |e| {
                         e.context(format!(
                             "Parsing body failed, target: UpdateUserAddress, user address id: {}",
                             user_address_id
                         ))
-                        .context(Error::Parse)
                         .into()
                     })
                     .and_then(move |new_address| {
@@ -462,6 +1163,753 @@ impl<
             // DELETE /users/addresses/<id>
             (Delete, Some(Route::UserAddressById { user_address_id })) => serialize_future(service.delete_address(user_address_id)),
 
+            // POST /users/addresses/<id>/default
+            (Post, Some(Route::UserAddressDefault { user_address_id })) => serialize_future(service.set_default_address(user_address_id)),
+
+            // GET /zones
+            (Get, Some(Route::Zones)) => serialize_future(service.list_zones()),
+
+            // GET /zones/<zone_id>
+            (Get, Some(Route::ZoneById { zone_id })) => serialize_future(service.get_zone(zone_id)),
+
+            // POST /zones
+            (Post, Some(Route::Zones)) => serialize_future(
+                parse_payload::<NewZone>(req.body(), schema_validation)
+                    .map_err(|e| e.context("Parsing body failed, target: NewZone").into())
+                    .and_then(move |new_zone| {
+                        new_zone
+                            .validate()
+                            .map_err(|e| format_err!("Validation failed, target: NewZone").context(Error::Validate(e)).into())
+                            .into_future()
+                            .and_then(move |_| service.create_zone(new_zone))
+                    }),
+            ),
+
+            // PUT /zones/<zone_id>
+            (Put, Some(Route::ZoneById { zone_id })) => serialize_future(
+                parse_payload::<UpdateZone>(req.body(), schema_validation)
+                    .map_err(move |e| {
+                        e.context(format!("Parsing body failed, target: UpdateZone, zone id: {}", zone_id))
+                            .into()
+                    })
+                    .and_then(move |update_zone| {
+                        update_zone
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: UpdateZone")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.update_zone(zone_id, update_zone))
+                    }),
+            ),
+
+            // DELETE /zones/<zone_id>
+            (Delete, Some(Route::ZoneById { zone_id })) => serialize_future(service.delete_zone(zone_id)),
+
+            // GET /postal_zones
+            (Get, Some(Route::PostalZones)) => {
+                let alpha3 = parse_query!(req.query().unwrap_or_default(), "alpha3" => Alpha3);
+                serialize_future(service.list_postal_zones(alpha3))
+            }
+
+            // GET /postal_zones/<postal_zone_id>
+            (Get, Some(Route::PostalZoneById { postal_zone_id })) => serialize_future(service.get_postal_zone(postal_zone_id)),
+
+            // POST /postal_zones
+            (Post, Some(Route::PostalZones)) => serialize_future(
+                parse_payload::<NewPostalZone>(req.body(), schema_validation)
+                    .map_err(|e| e.context("Parsing body failed, target: NewPostalZone").into())
+                    .and_then(move |new_postal_zone| {
+                        new_postal_zone
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: NewPostalZone")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.create_postal_zone(new_postal_zone))
+                    }),
+            ),
+
+            // POST /postal_zones/import
+            (Post, Some(Route::PostalZonesImport)) => serialize_future(
+                parse_payload::<ImportPostalZone>(req.body(), schema_validation)
+                    .map_err(|e| e.context("Parsing body failed, target: ImportPostalZone").into())
+                    .and_then(move |import_postal_zone| {
+                        import_postal_zone
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: ImportPostalZone")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.import_postal_zone(import_postal_zone))
+                    }),
+            ),
+
+            // DELETE /postal_zones/<postal_zone_id>
+            (Delete, Some(Route::PostalZoneById { postal_zone_id })) => serialize_future(service.delete_postal_zone(postal_zone_id)),
+
+            // GET /surcharges
+            (Get, Some(Route::Surcharges)) => serialize_future(service.list_surcharges()),
+
+            // GET /surcharges/<surcharge_id>
+            (Get, Some(Route::SurchargeById { surcharge_id })) => serialize_future(service.get_surcharge(surcharge_id)),
+
+            // POST /surcharges
+            (Post, Some(Route::Surcharges)) => serialize_future(
+                parse_payload::<NewSurcharge>(req.body(), schema_validation)
+                    .map_err(|e| e.context("Parsing body failed, target: NewSurcharge").into())
+                    .and_then(move |new_surcharge| {
+                        new_surcharge
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: NewSurcharge")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.create_surcharge(new_surcharge))
+                    }),
+            ),
+
+            // PUT /surcharges/<surcharge_id>
+            (Put, Some(Route::SurchargeById { surcharge_id })) => serialize_future(
+                parse_payload::<UpdateSurcharge>(req.body(), schema_validation)
+                    .map_err(move |e| {
+                        e.context(format!(
+                            "Parsing body failed, target: UpdateSurcharge, surcharge id: {}",
+                            surcharge_id
+                        ))
+                        .into()
+                    })
+                    .and_then(move |update_surcharge| {
+                        update_surcharge
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: UpdateSurcharge")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.update_surcharge(surcharge_id, update_surcharge))
+                    }),
+            ),
+
+            // DELETE /surcharges/<surcharge_id>
+            (Delete, Some(Route::SurchargeById { surcharge_id })) => serialize_future(service.delete_surcharge(surcharge_id)),
+
+            // GET /pricing_rules
+            (Get, Some(Route::PricingRules)) => serialize_future(service.list_pricing_rules()),
+
+            // GET /pricing_rules/<pricing_rule_id>
+            (Get, Some(Route::PricingRuleById { pricing_rule_id })) => serialize_future(service.get_pricing_rule(pricing_rule_id)),
+
+            // POST /pricing_rules
+            (Post, Some(Route::PricingRules)) => serialize_future(
+                parse_payload::<NewPricingRule>(req.body(), schema_validation)
+                    .map_err(|e| e.context("Parsing body failed, target: NewPricingRule").into())
+                    .and_then(move |new_pricing_rule| {
+                        new_pricing_rule
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: NewPricingRule")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.create_pricing_rule(new_pricing_rule))
+                    }),
+            ),
+
+            // PUT /pricing_rules/<pricing_rule_id>
+            (Put, Some(Route::PricingRuleById { pricing_rule_id })) => serialize_future(
+                parse_payload::<UpdatePricingRule>(req.body(), schema_validation)
+                    .map_err(move |e| {
+                        e.context(format!(
+                            "Parsing body failed, target: UpdatePricingRule, pricing rule id: {}",
+                            pricing_rule_id
+                        ))
+                        .into()
+                    })
+                    .and_then(move |update_pricing_rule| {
+                        update_pricing_rule
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: UpdatePricingRule")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.update_pricing_rule(pricing_rule_id, update_pricing_rule))
+                    }),
+            ),
+
+            // DELETE /pricing_rules/<pricing_rule_id>
+            (Delete, Some(Route::PricingRuleById { pricing_rule_id })) => serialize_future(service.delete_pricing_rule(pricing_rule_id)),
+
+            // GET /restrictions
+            (Get, Some(Route::ShippingRestrictions)) => serialize_future(service.list_shipping_restrictions()),
+
+            // GET /restrictions/<restriction_id>
+            (Get, Some(Route::ShippingRestrictionById { restriction_id })) => {
+                serialize_future(service.get_shipping_restriction(restriction_id))
+            }
+
+            // POST /restrictions
+            (Post, Some(Route::ShippingRestrictions)) => serialize_future(
+                parse_payload::<NewShippingRestriction>(req.body(), schema_validation)
+                    .map_err(|e| e.context("Parsing body failed, target: NewShippingRestriction").into())
+                    .and_then(move |new_restriction| {
+                        new_restriction
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: NewShippingRestriction")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.create_shipping_restriction(new_restriction))
+                    }),
+            ),
+
+            // PUT /restrictions/<restriction_id>
+            (Put, Some(Route::ShippingRestrictionById { restriction_id })) => serialize_future(
+                parse_payload::<UpdateShippingRestriction>(req.body(), schema_validation)
+                    .map_err(move |e| {
+                        e.context(format!(
+                            "Parsing body failed, target: UpdateShippingRestriction, restriction id: {}",
+                            restriction_id
+                        ))
+                        .into()
+                    })
+                    .and_then(move |update_restriction| {
+                        update_restriction
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: UpdateShippingRestriction")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.update_shipping_restriction(restriction_id, update_restriction))
+                    }),
+            ),
+
+            // DELETE /restrictions/<restriction_id>
+            (Delete, Some(Route::ShippingRestrictionById { restriction_id })) => {
+                serialize_future(service.delete_shipping_restriction(restriction_id))
+            }
+
+            // POST /restrictions/check
+            (Post, Some(Route::ShippingRestrictionsCheck)) => serialize_future(
+                parse_payload::<CheckShippingRestrictions>(req.body(), schema_validation)
+                    .map_err(|e| e.context("Parsing body failed, target: CheckShippingRestrictions").into())
+                    .and_then(move |payload| service.check_shipping_restrictions(payload)),
+            ),
+
+            // GET /company_suspensions
+            (Get, Some(Route::CompanySuspensions)) => serialize_future(service.list_company_suspensions()),
+
+            // GET /company_suspensions/<suspension_id>
+            (Get, Some(Route::CompanySuspensionById { suspension_id })) => serialize_future(service.get_company_suspension(suspension_id)),
+
+            // POST /company_suspensions
+            (Post, Some(Route::CompanySuspensions)) => serialize_future(
+                parse_payload::<NewCompanySuspension>(req.body(), schema_validation)
+                    .map_err(|e| e.context("Parsing body failed, target: NewCompanySuspension").into())
+                    .and_then(move |new_suspension| {
+                        new_suspension
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: NewCompanySuspension")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.create_company_suspension(new_suspension))
+                    }),
+            ),
+
+            // PUT /company_suspensions/<suspension_id>
+            (Put, Some(Route::CompanySuspensionById { suspension_id })) => serialize_future(
+                parse_payload::<UpdateCompanySuspension>(req.body(), schema_validation)
+                    .map_err(move |e| {
+                        e.context(format!(
+                            "Parsing body failed, target: UpdateCompanySuspension, suspension id: {}",
+                            suspension_id
+                        ))
+                        .into()
+                    })
+                    .and_then(move |update_suspension| {
+                        update_suspension
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: UpdateCompanySuspension")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.update_company_suspension(suspension_id, update_suspension))
+                    }),
+            ),
+
+            // DELETE /company_suspensions/<suspension_id>
+            (Delete, Some(Route::CompanySuspensionById { suspension_id })) => {
+                serialize_future(service.delete_company_suspension(suspension_id))
+            }
+
+            // POST /shipment_events
+            (Post, Some(Route::ShipmentEvents)) => serialize_future(
+                parse_payload::<ShipmentEvent>(req.body(), schema_validation)
+                    .map_err(|e| e.context("Parsing body failed, target: ShipmentEvent").into())
+                    .and_then(move |event| {
+                        event
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: ShipmentEvent")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.handle_shipment_event(event))
+                    }),
+            ),
+
+            // GET /pickups
+            (Get, Some(Route::PickupPoints)) => {
+                let company_id = parse_query!(req.query().unwrap_or_default(), "company_id" => CompanyId);
+                serialize_future(service.list_pickup_points(company_id))
+            }
+
+            // GET /pickups/nearby
+            (Get, Some(Route::PickupPointsNearby)) => {
+                if let (Some(latitude), Some(longitude), Some(radius_km)) = parse_query!(
+                    req.query().unwrap_or_default(),
+                    "lat" => f64,
+                    "lon" => f64,
+                    "radius" => f64
+                ) {
+                    let search = NearbyPickupPointsSearch {
+                        coordinates: Coordinates { latitude, longitude },
+                        radius_km,
+                    };
+                    serialize_future(
+                        search
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: NearbyPickupPointsSearch")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.find_nearby_pickup_points(search)),
+                    )
+                } else {
+                    Box::new(future::err(
+                        format_err!("Parsing query parameters failed, action: find nearby pickup points")
+                            .context(Error::Parse)
+                            .into(),
+                    ))
+                }
+            }
+
+            // GET /pickups/<pickup_point_id>
+            (Get, Some(Route::PickupPointById { pickup_point_id })) => serialize_future(service.get_pickup_point(pickup_point_id)),
+
+            // POST /pickups
+            (Post, Some(Route::PickupPoints)) => serialize_future(
+                parse_payload::<NewPickupPoint>(req.body(), schema_validation)
+                    .map_err(|e| e.context("Parsing body failed, target: NewPickupPoint").into())
+                    .and_then(move |new_pickup_point| {
+                        new_pickup_point
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: NewPickupPoint")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.create_pickup_point(new_pickup_point))
+                    }),
+            ),
+
+            // PUT /pickups/<pickup_point_id>
+            (Put, Some(Route::PickupPointById { pickup_point_id })) => serialize_future(
+                parse_payload::<UpdatePickupPoint>(req.body(), schema_validation)
+                    .map_err(move |e| {
+                        e.context(format!(
+                            "Parsing body failed, target: UpdatePickupPoint, pickup point id: {}",
+                            pickup_point_id
+                        ))
+                        .into()
+                    })
+                    .and_then(move |update_pickup_point| {
+                        update_pickup_point
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: UpdatePickupPoint")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.update_pickup_point(pickup_point_id, update_pickup_point))
+                    }),
+            ),
+
+            // DELETE /pickups/<pickup_point_id>
+            (Delete, Some(Route::PickupPointById { pickup_point_id })) => serialize_future(service.delete_pickup_point(pickup_point_id)),
+
+            // GET /package_templates
+            (Get, Some(Route::PackageTemplates)) => {
+                let from_alpha3 = parse_query!(req.query().unwrap_or_default(), "from" => Alpha3);
+                serialize_future(service.list_package_templates(from_alpha3))
+            }
+
+            // GET /package_templates/<package_template_id>
+            (Get, Some(Route::PackageTemplateById { package_template_id })) => {
+                serialize_future(service.get_package_template(package_template_id))
+            }
+
+            // POST /package_templates
+            (Post, Some(Route::PackageTemplates)) => serialize_future(
+                parse_payload::<NewPackageTemplate>(req.body(), schema_validation)
+                    .map_err(|e| e.context("Parsing body failed, target: NewPackageTemplate").into())
+                    .and_then(move |new_package_template| {
+                        new_package_template
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: NewPackageTemplate")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.create_package_template(new_package_template))
+                    }),
+            ),
+
+            // PUT /package_templates/<package_template_id>
+            (Put, Some(Route::PackageTemplateById { package_template_id })) => serialize_future(
+                parse_payload::<UpdatePackageTemplate>(req.body(), schema_validation)
+                    .map_err(move |e| {
+                        e.context(format!(
+                            "Parsing body failed, target: UpdatePackageTemplate, package template id: {}",
+                            package_template_id
+                        ))
+                        .into()
+                    })
+                    .and_then(move |update_package_template| {
+                        update_package_template
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: UpdatePackageTemplate")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.update_package_template(package_template_id, update_package_template))
+                    }),
+            ),
+
+            // DELETE /package_templates/<package_template_id>
+            (Delete, Some(Route::PackageTemplateById { package_template_id })) => {
+                serialize_future(service.delete_package_template(package_template_id))
+            }
+
+            // GET /stores/<store_id>/suggested_packages
+            (Get, Some(Route::SuggestedPackages { store_id })) => {
+                if let Some(country) = parse_query!(req.query().unwrap_or_default(), "country" => Alpha3) {
+                    serialize_future(service.get_suggested_packages(store_id, country))
+                } else {
+                    Box::new(future::err(
+                        format_err!(
+                            "Parsing query parameters failed, action: get suggested packages, store id: {}",
+                            store_id
+                        )
+                        .context(Error::Parse)
+                        .into(),
+                    ))
+                }
+            }
+
+            // GET /stores/<store_id>/margin
+            (Get, Some(Route::StoreMargin { store_id })) => serialize_future(service.get_store_margin(store_id)),
+
+            // PUT /stores/<store_id>/margin
+            (Put, Some(Route::StoreMargin { store_id })) => serialize_future(
+                parse_payload::<SetStoreMargin>(req.body(), schema_validation)
+                    .map_err(move |e| {
+                        e.context(format!("Parsing body failed, target: SetStoreMargin, store id: {}", store_id))
+                            .into()
+                    })
+                    .and_then(move |store_margin| {
+                        store_margin
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: SetStoreMargin")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.set_store_margin(store_id, store_margin))
+                    }),
+            ),
+
+            // DELETE /stores/<store_id>/margin
+            (Delete, Some(Route::StoreMargin { store_id })) => serialize_future(service.delete_store_margin(store_id)),
+
+            // GET /stores/<store_id>/carrier_accounts
+            (Get, Some(Route::StoreCarrierAccounts { store_id })) => serialize_future(service.list_carrier_accounts(store_id)),
+
+            // POST /stores/<store_id>/carrier_accounts
+            (Post, Some(Route::StoreCarrierAccounts { store_id })) => serialize_future(
+                parse_payload::<CreateCarrierAccount>(req.body(), schema_validation)
+                    .map_err(move |e| {
+                        e.context(format!("Parsing body failed, target: CreateCarrierAccount, store id: {}", store_id))
+                            .into()
+                    })
+                    .and_then(move |payload| {
+                        payload
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: CreateCarrierAccount")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.create_carrier_account(store_id, payload))
+                    }),
+            ),
+
+            // PUT /stores/<store_id>/carrier_accounts/<account_id>
+            (Put, Some(Route::StoreCarrierAccountById { store_id, account_id })) => serialize_future(
+                parse_payload::<UpdateCarrierAccount>(req.body(), schema_validation)
+                    .map_err(move |e| {
+                        e.context(format!(
+                            "Parsing body failed, target: UpdateCarrierAccount, store id: {}, account id: {}",
+                            store_id, account_id
+                        ))
+                        .into()
+                    })
+                    .and_then(move |payload| {
+                        payload
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: UpdateCarrierAccount")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.update_carrier_account(store_id, account_id, payload))
+                    }),
+            ),
+
+            // DELETE /stores/<store_id>/carrier_accounts/<account_id>
+            (Delete, Some(Route::StoreCarrierAccountById { store_id, account_id })) => {
+                serialize_future(service.delete_carrier_account(store_id, account_id))
+            }
+
+            // GET /stores/<store_id>/managers
+            (Get, Some(Route::StoreManagers { store_id })) => serialize_future(service.list_store_managers(store_id)),
+
+            // POST /stores/<store_id>/managers
+            (Post, Some(Route::StoreManagers { store_id })) => serialize_future(
+                parse_payload::<NewStoreManager>(req.body(), schema_validation)
+                    .map_err(move |e| {
+                        e.context(format!("Parsing body failed, target: NewStoreManager, store id: {}", store_id))
+                            .into()
+                    })
+                    .and_then(move |new_manager| service.grant_store_manager(store_id, new_manager.user_id)),
+            ),
+
+            // DELETE /stores/<store_id>/managers/<user_id>
+            (Delete, Some(Route::StoreManager { store_id, user_id })) => serialize_future(service.revoke_store_manager(store_id, user_id)),
+
+            // GET /stores/<store_id>/shipping_templates
+            (Get, Some(Route::ShippingTemplates { store_id })) => serialize_future(service.list_shipping_templates(store_id)),
+
+            // POST /stores/<store_id>/shipping_templates
+            (Post, Some(Route::ShippingTemplates { store_id })) => serialize_future(
+                parse_payload::<NewShippingTemplate>(req.body(), schema_validation)
+                    .map_err(move |e| {
+                        e.context(format!("Parsing body failed, target: NewShippingTemplate, store id: {}", store_id))
+                            .into()
+                    })
+                    .and_then(move |new_template| {
+                        new_template
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: NewShippingTemplate")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.create_shipping_template(store_id, new_template))
+                    }),
+            ),
+
+            // GET /stores/<store_id>/shipping_templates/<template_id>
+            (Get, Some(Route::ShippingTemplateById { store_id, template_id })) => {
+                serialize_future(service.get_shipping_template(store_id, template_id))
+            }
+
+            // PUT /stores/<store_id>/shipping_templates/<template_id>
+            (Put, Some(Route::ShippingTemplateById { store_id, template_id })) => serialize_future(
+                parse_payload::<UpdateShippingTemplate>(req.body(), schema_validation)
+                    .map_err(move |e| {
+                        e.context(format!(
+                            "Parsing body failed, target: UpdateShippingTemplate, store id: {}, template id: {}",
+                            store_id, template_id
+                        ))
+                        .into()
+                    })
+                    .and_then(move |update_template| {
+                        update_template
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: UpdateShippingTemplate")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.update_shipping_template(store_id, template_id, update_template))
+                    }),
+            ),
+
+            // DELETE /stores/<store_id>/shipping_templates/<template_id>
+            (Delete, Some(Route::ShippingTemplateById { store_id, template_id })) => {
+                serialize_future(service.delete_shipping_template(store_id, template_id))
+            }
+
+            // GET /stores/<store_id>/shipping_summary
+            (Get, Some(Route::StoreShippingSummary { store_id })) => serialize_future(service.get_store_shipping_summary(store_id)),
+
+            // GET /stores/<store_id>/shipping/export
+            (Get, Some(Route::StoreShippingExport { store_id })) => serialize_future(service.export_store_shipping(store_id)),
+
+            // POST /stores/<store_id>/shipping/import
+            (Post, Some(Route::StoreShippingImport { store_id })) => {
+                let on_conflict = parse_query!(req.query().unwrap_or_default(), "on_conflict" => ShippingImportConflict).unwrap_or_default();
+                serialize_future(
+                    parse_payload::<StoreShippingExport>(req.body(), schema_validation)
+                        .map_err(move |e| {
+                            e.context(format!("Parsing body failed, target: StoreShippingExport, store id: {}", store_id))
+                                .into()
+                        })
+                        .and_then(move |export| {
+                            export
+                                .validate()
+                                .map_err(|e| {
+                                    format_err!("Validation failed, target: StoreShippingExport")
+                                        .context(Error::Validate(e))
+                                        .into()
+                                })
+                                .into_future()
+                                .and_then(move |_| service.import_store_shipping(store_id, export, on_conflict))
+                        }),
+                )
+            }
+
+            // GET /backfills
+            (Get, Some(Route::Backfills)) => serialize_future(service.list_backfills()),
+
+            // GET /backfills/<name>
+            (Get, Some(Route::BackfillByName { name })) => serialize_future(service.get_backfill(name)),
+
+            // GET /shipping_rates/anomalies
+            (Get, Some(Route::ShippingRatesAnomalies)) => serialize_future(service.list_shipping_rates_anomalies()),
+
+            // GET /slo/status
+            (Get, Some(Route::SloStatus)) => serialize_future(future::ok::<_, FailureError>(self.static_context.slo_tracker.status())),
+
+            // GET /schemas
+            (Get, Some(Route::PayloadSchemas)) => serialize_future(future::ok::<_, FailureError>(payload_schemas())),
+
+            // GET /openapi.json
+            (Get, Some(Route::OpenApi)) => serialize_future(future::ok::<_, FailureError>((*self.static_context.openapi).clone())),
+
+            // GET /version
+            (Get, Some(Route::Version)) => serialize_future(service.spawn_on_pool(|conn| migrations::version_info(&*conn))),
+
+            // GET /diagnostics/connections
+            (Get, Some(Route::DiagnosticsConnections)) => {
+                let state = self.static_context.db_pool.state();
+                let pool = PoolState {
+                    connections: state.connections,
+                    idle_connections: state.idle_connections,
+                };
+                let report = ConnectionsReport {
+                    read_replicas: self.static_context.read_replicas.report(),
+                    ..self.static_context.connection_tracker.report(pool)
+                };
+                serialize_future(service.check_diagnostics_access().map(move |_| report))
+            }
+
+            // GET /admin/stats
+            (Get, Some(Route::AdminStats)) => {
+                let state = self.static_context.db_pool.state();
+                let config = &self.static_context.config.server;
+                let stats = PoolsStats {
+                    db_pool: DbPoolStats {
+                        max_size: self.static_context.db_pool.max_size(),
+                        connections: state.connections,
+                        idle_connections: state.idle_connections,
+                        checkout_wait: self.static_context.connection_tracker.checkout_waits(),
+                    },
+                    cpu_pool: self.static_context.cpu_pool_tasks.stats(config.thread_count),
+                    admin_cpu_pool: self.static_context.admin_cpu_pool_tasks.stats(config.admin_thread_count),
+                    delivery_quotes_cleanup: self.static_context.quotes_cleanup.stats(),
+                    carriers: self
+                        .static_context
+                        .carriers_client
+                        .as_ref()
+                        .map(|carriers_client| carriers_client.stats())
+                        .unwrap_or_default(),
+                };
+                serialize_future(service.check_diagnostics_access().map(move |_| stats))
+            }
+
+            // GET /admin/overview
+            (Get, Some(Route::AdminOverview)) => serialize_future(service.get_admin_overview()),
+
+            // POST /admin/carrier_accounts/reencrypt
+            (Post, Some(Route::AdminCarrierCredentialsReencryption)) => serialize_future(service.reencrypt_carrier_credentials()),
+
+            // GET /admin/api_keys
+            (Get, Some(Route::ApiKeys)) => serialize_future(service.list_api_keys()),
+
+            // POST /admin/api_keys
+            (Post, Some(Route::ApiKeys)) => serialize_future(
+                parse_payload::<NewApiKey>(req.body(), schema_validation)
+                    .map_err(|e| e.context("Parsing body failed, target: NewApiKey").into())
+                    .and_then(move |new_api_key| {
+                        new_api_key
+                            .validate()
+                            .map_err(|e| {
+                                format_err!("Validation failed, target: NewApiKey")
+                                    .context(Error::Validate(e))
+                                    .into()
+                            })
+                            .into_future()
+                            .and_then(move |_| service.issue_api_key(new_api_key))
+                    }),
+            ),
+
+            // DELETE /admin/api_keys/<api_key_id>
+            (Delete, Some(Route::ApiKeyById { api_key_id })) => serialize_future(service.revoke_api_key(api_key_id)),
+
+            // POST /__test__/seed
+            #[cfg(feature = "test-endpoints")]
+            (Post, Some(Route::TestSeed)) => serialize_future(service.seed_test_fixtures()),
+
+            // POST /__test__/reset
+            #[cfg(feature = "test-endpoints")]
+            (Post, Some(Route::TestReset)) => serialize_future(service.reset_test_fixtures()),
+
             // Fallback
             (m, _) => Box::new(future::err(
                 format_err!("Request to non existing endpoint in delivery microservice! {:?} {:?}", m, path)
@@ -469,14 +1917,212 @@ impl<
                     .into(),
             )),
         }
-        .map_err(|err| {
-            let wrapper = ErrorMessageWrapper::<Error>::from(&err);
-            if wrapper.inner.code == 500 {
-                log_and_capture_error(&err);
+        .then(move |res| {
+            let status = match res {
+                Ok(_) => 200,
+                Err(ref err) => {
+                    let wrapper = ErrorMessageWrapper::<Error>::from(err);
+                    if wrapper.inner.code == 500 {
+                        log_and_capture_error(err, &request_context);
+                    }
+                    wrapper.inner.code
+                }
+            };
+
+            let elapsed = started_at.elapsed();
+            if access_log_filter.should_log(&method, &request_context.route, status) {
+                info!(
+                    "{}",
+                    request_context.log_line_with(
+                        "Request finished",
+                        json!({
+                            "status": status,
+                            "duration_ms": elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis()),
+                        })
+                    )
+                );
             }
-            err
+
+            if let Some(route_group) = route_group {
+                slo_tracker.record(route_group, elapsed, status >= 500);
+            }
+
+            res
         });
 
         Box::new(fut)
     }
 }
+
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> Controller for ControllerImpl<T, M, F> {
+    /// Handle a request and get future response. Requests with invalid credentials are rejected before anything else is done
+    fn call(&self, req: Request) -> ControllerFuture {
+        let api_key = req
+            .headers()
+            .get_raw(API_KEY_HEADER)
+            .and_then(|api_key| api_key.one())
+            .map(|api_key| String::from_utf8_lossy(api_key).trim().to_string());
+        if let Some(api_key) = api_key {
+            let correlation_token = request_util::get_correlation_token(&req);
+            let operation = diagnostics::operation_name(&req.method().to_string(), req.path());
+            let service = Service::new(self.static_context.clone(), DynamicContext::new(None, correlation_token, operation));
+            let controller = ControllerImpl::new(self.static_context.clone());
+
+            return Box::new(service.authenticate_api_key(api_key).and_then(move |api_key| -> ControllerFuture {
+                match api_key {
+                    Some(api_key) => controller.translate_and_dispatch(req, Caller::Service(api_key)),
+                    None => Box::new(future::err(
+                        format_err!("Unknown or revoked api key").context(Error::Unauthorized).into(),
+                    )),
+                }
+            }));
+        }
+
+        let authorization = req.headers().get::<Authorization<String>>().map(|auth| auth.0.clone());
+        match self
+            .static_context
+            .authenticator
+            .authenticate(authorization.as_ref().map(String::as_str))
+        {
+            Ok(user) => self.translate_and_dispatch(req, Caller::from(user)),
+            Err(e) => Box::new(future::err(e)),
+        }
+    }
+}
+
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> ControllerImpl<T, M, F> {
+    /// Dispatches the request of the authenticated caller, country labels of the legacy clients are translated if configured
+    fn translate_and_dispatch(&self, req: Request, caller: Caller) -> ControllerFuture {
+        let settings = match self.static_context.config.legacy_country_labels.clone() {
+            Some(settings) => settings,
+            None => return self.dispatch(req, caller),
+        };
+
+        let correlation_token = request_util::get_correlation_token(&req);
+        let operation = diagnostics::operation_name(&req.method().to_string(), req.path());
+        let service = Service::new(self.static_context.clone(), DynamicContext::new(None, correlation_token, operation));
+        let controller = ControllerImpl::new(self.static_context.clone());
+
+        Box::new(
+            service
+                .get_all_flatten()
+                .then(|res| -> Result<_, FailureError> {
+                    match res {
+                        Ok(countries) => Ok(Some(Arc::new(CountryLabels::new(&countries)))),
+                        Err(e) => {
+                            warn!("Country labels are not translated, failed to load countries: {}", e);
+                            Ok(None)
+                        }
+                    }
+                })
+                .and_then(move |labels| -> ControllerFuture {
+                    let labels = match labels {
+                        Some(labels) => labels,
+                        None => return controller.dispatch(req, caller),
+                    };
+
+                    Box::new(
+                        legacy_labels::translate_request(labels.clone(), req)
+                            .and_then(move |req| controller.dispatch(req, caller))
+                            .map(move |body| {
+                                if settings.emit_labels {
+                                    legacy_labels::translate_response(&labels, body)
+                                } else {
+                                    body
+                                }
+                            }),
+                    )
+                }),
+        )
+    }
+}
+
+/// Names of the content are translated to the language, if the request asks for one
+fn localize<T>(content: ServiceFuture<T>, lang: Option<String>) -> ServiceFuture<T>
+where
+    T: Localized + 'static,
+{
+    match lang {
+        Some(lang) => Box::new(content.map(move |content| content.localized(&lang))),
+        None => content,
+    }
+}
+
+/// Parses body of create and update requests. With JSON schema validation enabled in config the body
+/// is checked against the payload schema before deserialization, so all violations are returned at once
+fn parse_payload<T>(body: Body, schema_validation: bool) -> Box<dyn Future<Item = T, Error = FailureError>>
+where
+    T: DeserializeOwned + JsonSchema + 'static,
+{
+    if !schema_validation {
+        return Box::new(parse_body::<T>(body).map_err(|e| e.context(Error::Parse).into()));
+    }
+
+    Box::new(
+        body.concat2()
+            .map_err(|e| e.context(Error::Parse).into())
+            .and_then(|chunk| -> Result<T, FailureError> {
+                let value = serde_json::from_slice::<serde_json::Value>(&chunk).map_err(|e| e.context(Error::Parse))?;
+                T::json_schema()
+                    .validate(&value)
+                    .map_err(|errors| format_err!("Payload does not match JSON schema").context(Error::Unprocessable(errors)))?;
+                serde_json::from_value::<T>(value).map_err(|e| e.context(Error::Parse).into())
+            }),
+    )
+}
+
+/// Query of the v2 available packages for user misses the origin, the destination or the measurements
+fn parse_available_packages_for_user_v2_error(base_product_id: BaseProductId) -> FailureError {
+    format_err!(
+        "Parsing query parameters failed, action: get available packages for user v2, base product id: {}",
+        base_product_id
+    )
+    .context(Error::Parse)
+    .into()
+}
+
+/// Measurements in grams and cubic centimeters, the values are in `volume_unit` and `weight_unit` if they are passed
+fn parse_measurements(
+    volume: f64,
+    volume_unit: Option<String>,
+    weight: f64,
+    weight_unit: Option<String>,
+) -> Result<ShipmentMeasurements, FailureError> {
+    let volume_unit = transpose(volume_unit.map(|unit| unit.parse::<VolumeUnit>())).map_err(|e| {
+        e.context("Parsing query parameters failed, target: volume_unit")
+            .context(Error::Parse)
+    })?;
+    let weight_unit = transpose(weight_unit.map(|unit| unit.parse::<WeightUnit>())).map_err(|e| {
+        e.context("Parsing query parameters failed, target: weight_unit")
+            .context(Error::Parse)
+    })?;
+
+    normalize_measurements(volume, volume_unit.unwrap_or_default(), weight, weight_unit.unwrap_or_default()).map_err(|e| {
+        format_err!("Validation failed, target: ShipmentMeasurements")
+            .context(Error::Validate(e))
+            .into()
+    })
+}
+
+/// Seller and buyer coordinates are optional, they are taken into account only if all of them are present
+fn parse_delivery_coordinates(query: &str) -> Option<DeliveryCoordinates> {
+    match parse_query!(
+        query,
+        "from_lat" => f64,
+        "from_lng" => f64,
+        "to_lat" => f64,
+        "to_lng" => f64
+    ) {
+        (Some(from_lat), Some(from_lng), Some(to_lat), Some(to_lng)) => Some(DeliveryCoordinates {
+            from: Coordinates {
+                latitude: from_lat,
+                longitude: from_lng,
+            },
+            to: Coordinates {
+                latitude: to_lat,
+                longitude: to_lng,
+            },
+        }),
+        _ => None,
+    }
+}
@@ -0,0 +1,134 @@
+//! OpenAPI specification of the app. It is generated at startup from the endpoints registry in `routes`
+//! and the JSON schemas of the payloads, so it is served at `GET /openapi.json` in sync with the controller
+use std::collections::BTreeMap;
+
+use serde_json::{Map, Value};
+
+use super::routes::{endpoints, Endpoint, EndpointParameter};
+
+/// OpenAPI 3.0 document of all endpoints, payloads are described in `components` by their names
+pub fn specification() -> Value {
+    let mut paths = BTreeMap::<&str, Map<String, Value>>::new();
+    let mut schemas = BTreeMap::new();
+
+    for endpoint in endpoints() {
+        if let Some((name, ref schema)) = endpoint.payload {
+            schemas.insert(name, schema.to_openapi());
+        }
+
+        paths
+            .entry(endpoint.path)
+            .or_insert_with(Map::new)
+            .insert(endpoint.method.to_string().to_lowercase(), operation(&endpoint));
+    }
+
+    json!({
+        "openapi": "3.0.0",
+        "info": {
+            "title": "Delivery",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": {
+            "schemas": schemas,
+        },
+    })
+}
+
+fn operation(endpoint: &Endpoint) -> Value {
+    let parameters = endpoint
+        .path_params
+        .iter()
+        .map(|param| parameter(param, "path"))
+        .chain(endpoint.query_params.iter().map(|param| parameter(param, "query")))
+        .collect::<Vec<_>>();
+
+    let mut operation = json!({
+        "summary": endpoint.summary,
+        "parameters": parameters,
+        "responses": {
+            "200": {
+                "description": "Successful response",
+                "content": { "application/json": { "schema": {} } },
+            },
+            "default": {
+                "description": "Error with its code and description",
+            },
+        },
+    });
+
+    if let Some(operation) = operation.as_object_mut() {
+        if let Some((name, _)) = endpoint.payload {
+            operation.insert(
+                "requestBody".to_string(),
+                json!({
+                    "required": true,
+                    "content": {
+                        "application/json": {
+                            "schema": { "$ref": format!("#/components/schemas/{}", name) },
+                        },
+                    },
+                }),
+            );
+        }
+
+        if endpoint.deprecated {
+            operation.insert("deprecated".to_string(), Value::Bool(true));
+        }
+    }
+
+    operation
+}
+
+fn parameter(param: &EndpointParameter, location: &str) -> Value {
+    json!({
+        "name": param.name,
+        "in": location,
+        "required": param.required,
+        "schema": param.schema.to_openapi(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::routes::create_route_parser;
+    use super::*;
+    use models::Schema;
+
+    #[test]
+    fn endpoints_are_routed() {
+        let route_parser = create_route_parser();
+
+        for endpoint in endpoints() {
+            let mut path = endpoint.path.to_string();
+            for param in &endpoint.path_params {
+                let placeholder = format!("{{{}}}", param.name);
+                assert!(path.contains(&placeholder), "{} has no {} param", endpoint.path, param.name);
+
+                let sample = match param.schema {
+                    Schema::Integer { .. } => "1",
+                    _ => "00000000-0000-0000-0000-000000000001",
+                };
+                path = path.replace(&placeholder, sample);
+            }
+
+            assert!(!path.contains('{'), "{} has undeclared params", endpoint.path);
+            assert!(route_parser.test(&path).is_some(), "{} is not routed", endpoint.path);
+        }
+    }
+
+    #[test]
+    fn payloads_are_referenced_from_components() {
+        let specification = specification();
+
+        assert_eq!(
+            specification["paths"]["/companies"]["post"]["requestBody"]["content"]["application/json"]["schema"]["$ref"],
+            json!("#/components/schemas/NewCompany")
+        );
+        assert_eq!(specification["components"]["schemas"]["NewCompany"]["type"], json!("object"));
+        assert_eq!(
+            specification["paths"]["/companies/{company_id}"]["get"]["parameters"][0],
+            json!({ "name": "company_id", "in": "path", "required": true, "schema": { "type": "integer" } })
+        );
+    }
+}
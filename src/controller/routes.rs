@@ -1,10 +1,33 @@
+use chrono::{DateTime, Utc};
+use hyper::{Delete, Get, Method, Post, Put};
+use serde_json::Value;
+
 use stq_router::RouteParser;
 use stq_types::*;
+use uuid::Uuid;
+
+use models::{
+    BookShipment, CheckShippingRestrictions, CompaniesSortField, CompanyCapabilities, CreateCarrierAccount, ImportCountries,
+    ImportPostalZone, JsonSchema, NewApiKey, NewCompany, NewCompanyPackage, NewCompanySuspension, NewCountry, NewCountrySubdivision,
+    NewPackageTemplate, NewPackages, NewPickupPoint, NewPostalZone, NewPricingRule, NewRateComponents, NewShipping, NewShippingRestriction,
+    NewShippingTemplate, NewStoreManager, NewSurcharge, NewUserAddress, NewUserRole, NewZone, OriginPreference, Schema, SetCarrierFallback,
+    SetCashOnDelivery, SetCompanyRatesQuota, SetCountryRegulation, SetCustomsInfo, SetRateResolutionOrder, SetShippingCapacity,
+    SetStoreMargin, ShipmentEvent, ShippingImportConflict, ShippingOptionId, SortOrder, StoreShippingExport, UpdateCarrierAccount,
+    UpdateCompany, UpdateCompanySuspension, UpdateCountrySubdivision, UpdatePackageTemplate, UpdatePackages, UpdatePickupPoint,
+    UpdatePricingRule, UpdateProducts, UpdateShippingRestriction, UpdateShippingTemplate, UpdateSurcharge, UpdateUserAddress, UpdateZone,
+    VolumeUnit, WeightUnit,
+};
+use slo::RouteGroup;
 
 /// List of all routes with params for the app
 #[derive(Clone, Debug, PartialEq)]
 pub enum Route {
+    Backfills,
+    BackfillByName {
+        name: String,
+    },
     Roles,
+    RolesPermissions,
     RoleById {
         id: RoleId,
     },
@@ -13,6 +36,10 @@ pub enum Route {
     },
     Countries,
     CountriesFlatten,
+    CountriesImport,
+    CountriesValidate,
+    CountriesRepair,
+    CountriesDependents,
     CountryByAlpha2 {
         alpha2: Alpha2,
     },
@@ -22,6 +49,17 @@ pub enum Route {
     CountryByNumeric {
         numeric: i32,
     },
+    CountryRegulations,
+    CountryRegulationByAlpha3 {
+        alpha3: Alpha3,
+    },
+    CountrySubdivisions {
+        alpha3: Alpha3,
+    },
+    CountrySubdivision {
+        alpha3: Alpha3,
+        code: String,
+    },
     Products,
     ProductsById {
         base_product_id: BaseProductId,
@@ -30,11 +68,25 @@ pub enum Route {
         base_product_id: BaseProductId,
         company_package_id: CompanyPackageId,
     },
+    ProductsCloneTo {
+        base_product_id: BaseProductId,
+    },
+    ProductsCustomsInfo {
+        base_product_id: BaseProductId,
+    },
     Companies,
+    CompaniesSearch,
     CompanyById {
         company_id: CompanyId,
     },
+    CompanyRatesQuota {
+        company_id: CompanyId,
+    },
+    CompanyDeletionImpact {
+        company_id: CompanyId,
+    },
     Packages,
+    PackagesSearch,
     PackagesById {
         package_id: PackageId,
     },
@@ -58,6 +110,32 @@ pub enum Route {
     CompanyPackageRates {
         company_package_id: CompanyPackageId,
     },
+    CompanyPackageProducts {
+        company_package_id: CompanyPackageId,
+    },
+    CompanyPackageRateComponents {
+        company_package_id: CompanyPackageId,
+    },
+    CompanyPackageRateResolutionOrder {
+        company_package_id: CompanyPackageId,
+    },
+    CompanyPackageCashOnDelivery {
+        company_package_id: CompanyPackageId,
+    },
+    CompanyPackageCarrierFallback {
+        company_package_id: CompanyPackageId,
+    },
+    CompanyPackageShippingCapacity {
+        company_package_id: CompanyPackageId,
+    },
+    CompanyPackageShipmentBookings {
+        company_package_id: CompanyPackageId,
+    },
+    DeliveryPricesBatch,
+    DeliveryQuotes,
+    DeliveryQuoteById {
+        quote_id: Uuid,
+    },
     AvailablePackages,
     AvailablePackagesForUser {
         base_product_id: BaseProductId,
@@ -75,6 +153,69 @@ pub enum Route {
     AvailablePackageForUserByShippingIdV2 {
         shipping_id: ShippingId,
     },
+    Surcharges,
+    SurchargeById {
+        surcharge_id: i32,
+    },
+    PricingRules,
+    PricingRuleById {
+        pricing_rule_id: i32,
+    },
+    ShippingRestrictions,
+    ShippingRestrictionById {
+        restriction_id: i32,
+    },
+    ShippingRestrictionsCheck,
+    CompanySuspensions,
+    CompanySuspensionById {
+        suspension_id: i32,
+    },
+    ShipmentEvents,
+    PickupPoints,
+    PickupPointById {
+        pickup_point_id: i32,
+    },
+    PickupPointsNearby,
+    PackageTemplates,
+    PackageTemplateById {
+        package_template_id: i32,
+    },
+    SuggestedPackages {
+        store_id: StoreId,
+    },
+    StoreMargin {
+        store_id: StoreId,
+    },
+    StoreCarrierAccounts {
+        store_id: StoreId,
+    },
+    StoreCarrierAccountById {
+        store_id: StoreId,
+        account_id: i32,
+    },
+    StoreManagers {
+        store_id: StoreId,
+    },
+    StoreManager {
+        store_id: StoreId,
+        user_id: UserId,
+    },
+    ShippingTemplates {
+        store_id: StoreId,
+    },
+    ShippingTemplateById {
+        store_id: StoreId,
+        template_id: i32,
+    },
+    StoreShippingSummary {
+        store_id: StoreId,
+    },
+    StoreShippingExport {
+        store_id: StoreId,
+    },
+    StoreShippingImport {
+        store_id: StoreId,
+    },
     UsersAddresses,
     UserAddress {
         user_id: UserId,
@@ -82,12 +223,120 @@ pub enum Route {
     UserAddressById {
         user_address_id: i32,
     },
+    UserAddressDefault {
+        user_address_id: i32,
+    },
+    Zones,
+    ZoneById {
+        zone_id: i32,
+    },
+    PostalZones,
+    PostalZonesImport,
+    PostalZoneById {
+        postal_zone_id: i32,
+    },
+    ShippingRatesAnomalies,
+    SloStatus,
+    PayloadSchemas,
+    DiagnosticsConnections,
+    AdminStats,
+    AdminOverview,
+    AdminCarrierCredentialsReencryption,
+    ApiKeys,
+    ApiKeyById {
+        api_key_id: i32,
+    },
+    OpenApi,
+    Version,
+    #[cfg(feature = "test-endpoints")]
+    TestSeed,
+    #[cfg(feature = "test-endpoints")]
+    TestReset,
+}
+
+impl Route {
+    /// Route group the SLO of the route is tracked in
+    pub fn group(&self) -> Option<RouteGroup> {
+        match *self {
+            Route::CompanyPackageDeliveryPrice { .. }
+            | Route::DeliveryPricesBatch
+            | Route::DeliveryQuotes
+            | Route::DeliveryQuoteById { .. } => Some(RouteGroup::Price),
+            Route::AvailablePackages
+            | Route::AvailablePackagesForUser { .. }
+            | Route::AvailablePackagesForUserV2 { .. }
+            | Route::AvailablePackageForUser { .. }
+            | Route::AvailablePackageForUserByShippingId { .. }
+            | Route::AvailablePackageForUserByShippingIdV2 { .. }
+            | Route::SuggestedPackages { .. }
+            | Route::ShippingRestrictionsCheck
+            | Route::PickupPointsNearby => Some(RouteGroup::Availability),
+            Route::Backfills
+            | Route::BackfillByName { .. }
+            | Route::ShippingRatesAnomalies
+            | Route::CompanyPackageRates { .. }
+            | Route::CompanyPackageProducts { .. }
+            | Route::CompanyPackageRateComponents { .. }
+            | Route::CompanyPackageRateResolutionOrder { .. }
+            | Route::CompanyPackageCashOnDelivery { .. }
+            | Route::CompanyPackageCarrierFallback { .. }
+            | Route::CompanyPackageShippingCapacity { .. }
+            | Route::CompanyPackageShipmentBookings { .. }
+            | Route::Surcharges
+            | Route::SurchargeById { .. }
+            | Route::PricingRules
+            | Route::PricingRuleById { .. }
+            | Route::ShippingRestrictions
+            | Route::ShippingRestrictionById { .. }
+            | Route::CompanySuspensions
+            | Route::CompanySuspensionById { .. }
+            | Route::CompanyRatesQuota { .. }
+            | Route::CompanyDeletionImpact { .. }
+            | Route::ShipmentEvents
+            | Route::StoreMargin { .. }
+            | Route::StoreCarrierAccounts { .. }
+            | Route::StoreCarrierAccountById { .. }
+            | Route::StoreManagers { .. }
+            | Route::StoreManager { .. }
+            | Route::ShippingTemplates { .. }
+            | Route::ShippingTemplateById { .. }
+            | Route::StoreShippingSummary { .. }
+            | Route::StoreShippingExport { .. }
+            | Route::StoreShippingImport { .. }
+            | Route::CountriesImport
+            | Route::CountriesValidate
+            | Route::CountriesRepair
+            | Route::CountriesDependents
+            | Route::CountryRegulations
+            | Route::CountryRegulationByAlpha3 { .. }
+            | Route::CountrySubdivisions { .. }
+            | Route::CountrySubdivision { .. }
+            | Route::PickupPoints
+            | Route::PickupPointById { .. }
+            | Route::PackageTemplates
+            | Route::PackageTemplateById { .. }
+            | Route::Zones
+            | Route::ZoneById { .. }
+            | Route::PostalZones
+            | Route::PostalZonesImport
+            | Route::PostalZoneById { .. }
+            | Route::RolesPermissions
+            | Route::DiagnosticsConnections
+            | Route::AdminStats
+            | Route::AdminOverview
+            | Route::AdminCarrierCredentialsReencryption
+            | Route::ApiKeys
+            | Route::ApiKeyById { .. } => Some(RouteGroup::Admin),
+            _ => None,
+        }
+    }
 }
 
 pub fn create_route_parser() -> RouteParser<Route> {
     let mut route_parser = RouteParser::default();
 
     route_parser.add_route(r"^/roles$", || Route::Roles);
+    route_parser.add_route(r"^/roles/permissions$", || Route::RolesPermissions);
     route_parser.add_route_with_params(r"^/roles/by-user-id/(\d+)$", |params| {
         params
             .get(0)
@@ -103,6 +352,10 @@ pub fn create_route_parser() -> RouteParser<Route> {
 
     route_parser.add_route(r"^/countries$", || Route::Countries);
     route_parser.add_route(r"^/countries/flatten$", || Route::CountriesFlatten);
+    route_parser.add_route(r"^/countries/import$", || Route::CountriesImport);
+    route_parser.add_route(r"^/countries/validate$", || Route::CountriesValidate);
+    route_parser.add_route(r"^/countries/repair$", || Route::CountriesRepair);
+    route_parser.add_route(r"^/countries/dependents$", || Route::CountriesDependents);
 
     // Countries search
     route_parser.add_route_with_params(r"^/countries/alpha2/(\S+)$", |params| {
@@ -128,6 +381,35 @@ pub fn create_route_parser() -> RouteParser<Route> {
             .map(|numeric| Route::CountryByNumeric { numeric })
     });
 
+    // Parcel regulations of the countries
+    route_parser.add_route(r"^/country_regulations$", || Route::CountryRegulations);
+    route_parser.add_route_with_params(r"^/country_regulations/(\S+)$", |params| {
+        params
+            .get(0)
+            .map(|param| param.to_string().to_uppercase())
+            .map(Alpha3)
+            .map(|alpha3| Route::CountryRegulationByAlpha3 { alpha3 })
+    });
+
+    // Subdivisions of the countries
+    route_parser.add_route_with_params(r"^/countries/([A-Za-z0-9-]+)/subdivisions$", |params| {
+        params
+            .get(0)
+            .map(|param| param.to_string().to_uppercase())
+            .map(Alpha3)
+            .map(|alpha3| Route::CountrySubdivisions { alpha3 })
+    });
+    route_parser.add_route_with_params(r"^/countries/([A-Za-z0-9-]+)/subdivisions/([A-Za-z0-9-]+)$", |params| {
+        if let (Some(alpha3), Some(code)) = (params.get(0), params.get(1)) {
+            Some(Route::CountrySubdivision {
+                alpha3: Alpha3(alpha3.to_string().to_uppercase()),
+                code: code.to_string().to_uppercase(),
+            })
+        } else {
+            None
+        }
+    });
+
     route_parser.add_route(r"^/products$", || Route::Products);
     route_parser.add_route_with_params(r"^/products/(\d+)$", |params| {
         params
@@ -150,16 +432,42 @@ pub fn create_route_parser() -> RouteParser<Route> {
         }
         None
     });
+    route_parser.add_route_with_params(r"^/products/(\d+)/clone_to$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|base_product_id| Route::ProductsCloneTo { base_product_id })
+    });
+    route_parser.add_route_with_params(r"^/products/(\d+)/customs_info$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|base_product_id| Route::ProductsCustomsInfo { base_product_id })
+    });
 
     route_parser.add_route(r"^/companies$", || Route::Companies);
+    route_parser.add_route(r"^/companies/search$", || Route::CompaniesSearch);
     route_parser.add_route_with_params(r"^/companies/(\d+)$", |params| {
         params
             .get(0)
             .and_then(|string_id| string_id.parse().ok())
             .map(|company_id| Route::CompanyById { company_id })
     });
+    route_parser.add_route_with_params(r"^/companies/(\d+)/rates_quota$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|company_id| Route::CompanyRatesQuota { company_id })
+    });
+    route_parser.add_route_with_params(r"^/companies/(\d+)/deletion_impact$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|company_id| Route::CompanyDeletionImpact { company_id })
+    });
 
     route_parser.add_route(r"^/packages$", || Route::Packages);
+    route_parser.add_route(r"^/packages/search$", || Route::PackagesSearch);
     route_parser.add_route_with_params(r"^/packages/(\d+)$", |params| {
         params
             .get(0)
@@ -186,6 +494,61 @@ pub fn create_route_parser() -> RouteParser<Route> {
             .and_then(|string_id| string_id.parse().ok())
             .map(|company_package_id| Route::CompanyPackageRates { company_package_id })
     });
+    route_parser.add_route_with_params(r"^/companies_packages/(\d+)/products$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|company_package_id| Route::CompanyPackageProducts { company_package_id })
+    });
+    route_parser.add_route_with_params(r"^/companies_packages/(\d+)/rates/components$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|company_package_id| Route::CompanyPackageRateComponents { company_package_id })
+    });
+    route_parser.add_route_with_params(r"^/companies_packages/(\d+)/rate_resolution_order$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|company_package_id| Route::CompanyPackageRateResolutionOrder { company_package_id })
+    });
+    route_parser.add_route_with_params(r"^/companies_packages/(\d+)/cash_on_delivery$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|company_package_id| Route::CompanyPackageCashOnDelivery { company_package_id })
+    });
+    route_parser.add_route_with_params(r"^/companies_packages/(\d+)/carrier_fallback$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|company_package_id| Route::CompanyPackageCarrierFallback { company_package_id })
+    });
+    route_parser.add_route_with_params(r"^/companies_packages/(\d+)/shipping_capacity$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|company_package_id| Route::CompanyPackageShippingCapacity { company_package_id })
+    });
+    route_parser.add_route_with_params(r"^/companies_packages/(\d+)/shipment_bookings$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|company_package_id| Route::CompanyPackageShipmentBookings { company_package_id })
+    });
+
+    route_parser.add_route(r"^/delivery_prices/batch$", || Route::DeliveryPricesBatch);
+
+    // /delivery_quotes route
+    route_parser.add_route(r"^/delivery_quotes$", || Route::DeliveryQuotes);
+
+    // /delivery_quotes/:id route
+    route_parser.add_route_with_params(r"^/delivery_quotes/([a-fA-F0-9-]+)$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|quote_id| Route::DeliveryQuoteById { quote_id })
+    });
 
     route_parser.add_route_with_params(r"^/companies/(\d+)/packages$", |params| {
         params
@@ -262,5 +625,915 @@ pub fn create_route_parser() -> RouteParser<Route> {
             .map(|user_address_id| Route::UserAddressById { user_address_id })
     });
 
+    // /users/addresses/:id/default route
+    route_parser.add_route_with_params(r"^/users/addresses/(\d+)/default$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|user_address_id| Route::UserAddressDefault { user_address_id })
+    });
+
+    // /surcharges route
+    route_parser.add_route(r"^/surcharges$", || Route::Surcharges);
+
+    // /surcharges/:id route
+    route_parser.add_route_with_params(r"^/surcharges/(\d+)$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|surcharge_id| Route::SurchargeById { surcharge_id })
+    });
+
+    // /pricing_rules route
+    route_parser.add_route(r"^/pricing_rules$", || Route::PricingRules);
+
+    // /pricing_rules/:id route
+    route_parser.add_route_with_params(r"^/pricing_rules/(\d+)$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|pricing_rule_id| Route::PricingRuleById { pricing_rule_id })
+    });
+
+    // /restrictions route
+    route_parser.add_route(r"^/restrictions$", || Route::ShippingRestrictions);
+
+    // /restrictions/check route
+    route_parser.add_route(r"^/restrictions/check$", || Route::ShippingRestrictionsCheck);
+
+    // /restrictions/:id route
+    route_parser.add_route_with_params(r"^/restrictions/(\d+)$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|restriction_id| Route::ShippingRestrictionById { restriction_id })
+    });
+
+    // /company_suspensions route
+    route_parser.add_route(r"^/company_suspensions$", || Route::CompanySuspensions);
+
+    // /company_suspensions/:id route
+    route_parser.add_route_with_params(r"^/company_suspensions/(\d+)$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|suspension_id| Route::CompanySuspensionById { suspension_id })
+    });
+
+    // /shipment_events route
+    route_parser.add_route(r"^/shipment_events$", || Route::ShipmentEvents);
+
+    // /pickups route
+    route_parser.add_route(r"^/pickups$", || Route::PickupPoints);
+
+    // /pickups/nearby route
+    route_parser.add_route(r"^/pickups/nearby$", || Route::PickupPointsNearby);
+
+    // /pickups/:id route
+    route_parser.add_route_with_params(r"^/pickups/(\d+)$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|pickup_point_id| Route::PickupPointById { pickup_point_id })
+    });
+
+    // /package_templates route
+    route_parser.add_route(r"^/package_templates$", || Route::PackageTemplates);
+
+    // /package_templates/:id route
+    route_parser.add_route_with_params(r"^/package_templates/(\d+)$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|package_template_id| Route::PackageTemplateById { package_template_id })
+    });
+
+    // /stores/:store_id/suggested_packages route
+    route_parser.add_route_with_params(r"^/stores/(\d+)/suggested_packages$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|store_id| Route::SuggestedPackages { store_id })
+    });
+
+    // /stores/:store_id/margin route
+    route_parser.add_route_with_params(r"^/stores/(\d+)/margin$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|store_id| Route::StoreMargin { store_id })
+    });
+
+    // /stores/:store_id/carrier_accounts route
+    route_parser.add_route_with_params(r"^/stores/(\d+)/carrier_accounts$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|store_id| Route::StoreCarrierAccounts { store_id })
+    });
+
+    // /stores/:store_id/carrier_accounts/:id route
+    route_parser.add_route_with_params(r"^/stores/(\d+)/carrier_accounts/(\d+)$", |params| {
+        let store_id = params.get(0)?.parse().ok().map(StoreId)?;
+        let account_id = params.get(1)?.parse().ok()?;
+        Some(Route::StoreCarrierAccountById { store_id, account_id })
+    });
+
+    // /stores/:store_id/managers route
+    route_parser.add_route_with_params(r"^/stores/(\d+)/managers$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|store_id| Route::StoreManagers { store_id })
+    });
+
+    // /stores/:store_id/managers/:user_id route
+    route_parser.add_route_with_params(r"^/stores/(\d+)/managers/(\d+)$", |params| {
+        let store_id = params.get(0)?.parse().ok().map(StoreId)?;
+        let user_id = params.get(1)?.parse().ok().map(UserId)?;
+        Some(Route::StoreManager { store_id, user_id })
+    });
+
+    // /stores/:store_id/shipping_templates route
+    route_parser.add_route_with_params(r"^/stores/(\d+)/shipping_templates$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|store_id| Route::ShippingTemplates { store_id })
+    });
+
+    // /stores/:store_id/shipping_templates/:id route
+    route_parser.add_route_with_params(r"^/stores/(\d+)/shipping_templates/(\d+)$", |params| {
+        let store_id = params.get(0)?.parse().ok().map(StoreId)?;
+        let template_id = params.get(1)?.parse().ok()?;
+        Some(Route::ShippingTemplateById { store_id, template_id })
+    });
+
+    // /stores/:store_id/shipping_summary route
+    route_parser.add_route_with_params(r"^/stores/(\d+)/shipping_summary$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|store_id| Route::StoreShippingSummary { store_id })
+    });
+
+    // /stores/:store_id/shipping/export route
+    route_parser.add_route_with_params(r"^/stores/(\d+)/shipping/export$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|store_id| Route::StoreShippingExport { store_id })
+    });
+
+    // /stores/:store_id/shipping/import route
+    route_parser.add_route_with_params(r"^/stores/(\d+)/shipping/import$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|store_id| Route::StoreShippingImport { store_id })
+    });
+
+    // /zones route
+    route_parser.add_route(r"^/zones$", || Route::Zones);
+
+    // /zones/:id route
+    route_parser.add_route_with_params(r"^/zones/(\d+)$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|zone_id| Route::ZoneById { zone_id })
+    });
+
+    // /postal_zones route
+    route_parser.add_route(r"^/postal_zones$", || Route::PostalZones);
+
+    // /postal_zones/import route
+    route_parser.add_route(r"^/postal_zones/import$", || Route::PostalZonesImport);
+
+    // /postal_zones/:id route
+    route_parser.add_route_with_params(r"^/postal_zones/(\d+)$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|postal_zone_id| Route::PostalZoneById { postal_zone_id })
+    });
+
+    // /backfills route
+    route_parser.add_route(r"^/backfills$", || Route::Backfills);
+
+    // /backfills/:name route
+    route_parser.add_route_with_params(r"^/backfills/([\w-]+)$", |params| {
+        params.get(0).map(|name| Route::BackfillByName { name: name.to_string() })
+    });
+
+    // /shipping_rates/anomalies route
+    route_parser.add_route(r"^/shipping_rates/anomalies$", || Route::ShippingRatesAnomalies);
+
+    // /slo/status route
+    route_parser.add_route(r"^/slo/status$", || Route::SloStatus);
+
+    // /schemas route
+    route_parser.add_route(r"^/schemas$", || Route::PayloadSchemas);
+
+    // /diagnostics/connections route
+    route_parser.add_route(r"^/diagnostics/connections$", || Route::DiagnosticsConnections);
+
+    // /admin/stats route
+    route_parser.add_route(r"^/admin/stats$", || Route::AdminStats);
+
+    // /admin/overview route
+    route_parser.add_route(r"^/admin/overview$", || Route::AdminOverview);
+
+    // /admin/carrier_accounts/reencrypt route
+    route_parser.add_route(r"^/admin/carrier_accounts/reencrypt$", || {
+        Route::AdminCarrierCredentialsReencryption
+    });
+
+    // /admin/api_keys route
+    route_parser.add_route(r"^/admin/api_keys$", || Route::ApiKeys);
+
+    // /admin/api_keys/:id route
+    route_parser.add_route_with_params(r"^/admin/api_keys/(\d+)$", |params| {
+        params
+            .get(0)
+            .and_then(|string_id| string_id.parse().ok())
+            .map(|api_key_id| Route::ApiKeyById { api_key_id })
+    });
+
+    // /openapi.json route
+    route_parser.add_route(r"^/openapi\.json$", || Route::OpenApi);
+
+    // /version route
+    route_parser.add_route(r"^/version$", || Route::Version);
+
+    add_test_routes(&mut route_parser);
+
     route_parser
 }
+
+/// Routes loading the test fixtures, they are added only with `test-endpoints` feature
+#[cfg(feature = "test-endpoints")]
+fn add_test_routes(route_parser: &mut RouteParser<Route>) {
+    // /__test__/seed route
+    route_parser.add_route(r"^/__test__/seed$", || Route::TestSeed);
+
+    // /__test__/reset route
+    route_parser.add_route(r"^/__test__/reset$", || Route::TestReset);
+}
+
+#[cfg(not(feature = "test-endpoints"))]
+fn add_test_routes(_route_parser: &mut RouteParser<Route>) {}
+
+/// Parameter of an endpoint passed in the path or in the query
+#[derive(Clone, Debug)]
+pub struct EndpointParameter {
+    pub name: &'static str,
+    pub schema: Schema,
+    pub required: bool,
+}
+
+/// Endpoint of the API as it is described in the OpenAPI specification
+#[derive(Clone, Debug)]
+pub struct Endpoint {
+    pub method: Method,
+    /// Path with the params in braces, e.g. `/companies/{company_id}`
+    pub path: &'static str,
+    pub summary: &'static str,
+    pub path_params: Vec<EndpointParameter>,
+    pub query_params: Vec<EndpointParameter>,
+    /// Name and schema of the request body
+    pub payload: Option<(&'static str, Schema)>,
+    pub deprecated: bool,
+}
+
+impl Endpoint {
+    fn new(method: Method, path: &'static str, summary: &'static str) -> Self {
+        Self {
+            method,
+            path,
+            summary,
+            path_params: vec![],
+            query_params: vec![],
+            payload: None,
+            deprecated: false,
+        }
+    }
+
+    fn path_param<T: JsonSchema>(mut self, name: &'static str) -> Self {
+        self.path_params.push(EndpointParameter {
+            name,
+            schema: T::json_schema(),
+            required: true,
+        });
+        self
+    }
+
+    /// Query param, `Option` params are not required
+    fn query<T: JsonSchema>(mut self, name: &'static str) -> Self {
+        let schema = match T::json_schema() {
+            Schema::Nullable(inner) => *inner,
+            schema => schema,
+        };
+        self.query_params.push(EndpointParameter {
+            name,
+            schema,
+            required: !T::is_optional(),
+        });
+        self
+    }
+
+    /// Seller and buyer coordinates of the delivery price and availability requests
+    fn coordinates(self) -> Self {
+        self.query::<Option<f64>>("from_lat")
+            .query::<Option<f64>>("from_lng")
+            .query::<Option<f64>>("to_lat")
+            .query::<Option<f64>>("to_lng")
+    }
+
+    fn payload<T: JsonSchema>(mut self, name: &'static str) -> Self {
+        self.payload = Some((name, T::json_schema()));
+        self
+    }
+
+    fn deprecated(mut self) -> Self {
+        self.deprecated = true;
+        self
+    }
+}
+
+/// All endpoints of the app, handlers of them are in the controller.
+/// Payloads without JSON schema are described as any JSON value
+pub fn endpoints() -> Vec<Endpoint> {
+    let mut endpoints = vec![
+        // Roles
+        Endpoint::new(Get, "/roles/by-user-id/{user_id}", "Roles of the user").path_param::<UserId>("user_id"),
+        Endpoint::new(Post, "/roles", "Create role").payload::<NewUserRole>("NewUserRole"),
+        Endpoint::new(Delete, "/roles/by-user-id/{user_id}", "Delete roles of the user").path_param::<UserId>("user_id"),
+        Endpoint::new(Delete, "/roles/by-id/{id}", "Delete role").path_param::<RoleId>("id"),
+        Endpoint::new(Get, "/roles/permissions", "Permissions of the roles"),
+        // Products
+        Endpoint::new(
+            Post,
+            "/products/{base_product_id}",
+            "Replace shipping of the base product, the payload is ignored if a shipping template is given",
+        )
+        .path_param::<BaseProductId>("base_product_id")
+        .query::<Option<StoreId>>("store_id")
+        .query::<Option<i32>>("template_id")
+        .payload::<NewShipping>("NewShipping"),
+        Endpoint::new(Get, "/products/{base_product_id}", "Shipping of the base product").path_param::<BaseProductId>("base_product_id"),
+        Endpoint::new(Delete, "/products/{base_product_id}", "Delete shipping of the base product")
+            .path_param::<BaseProductId>("base_product_id"),
+        Endpoint::new(
+            Put,
+            "/products/{base_product_id}/company_package/{company_package_id}",
+            "Update shipping of the base product with the company package",
+        )
+        .path_param::<BaseProductId>("base_product_id")
+        .path_param::<CompanyPackageId>("company_package_id")
+        .payload::<UpdateProducts>("UpdateProducts"),
+        Endpoint::new(
+            Post,
+            "/products/{base_product_id}/clone_to",
+            "Replace shipping of the target base products with the shipping of the base product",
+        )
+        .path_param::<BaseProductId>("base_product_id")
+        .payload::<Vec<BaseProductId>>("CloneShippingTargets"),
+        Endpoint::new(Get, "/products/{base_product_id}/customs_info", "Customs info of the base product")
+            .path_param::<BaseProductId>("base_product_id"),
+        Endpoint::new(
+            Put,
+            "/products/{base_product_id}/customs_info",
+            "Set customs info of the base product",
+        )
+        .path_param::<BaseProductId>("base_product_id")
+        .payload::<SetCustomsInfo>("SetCustomsInfo"),
+        Endpoint::new(
+            Delete,
+            "/products/{base_product_id}/customs_info",
+            "Delete customs info of the base product",
+        )
+        .path_param::<BaseProductId>("base_product_id"),
+        // Companies
+        Endpoint::new(Post, "/companies", "Create company").payload::<NewCompany>("NewCompany"),
+        Endpoint::new(Get, "/companies", "List companies")
+            .query::<Option<Alpha3>>("deliveries_from")
+            .query::<Option<String>>("label_contains")
+            .query::<Option<CompaniesSortField>>("sort")
+            .query::<Option<SortOrder>>("order")
+            .query::<Option<String>>("lang"),
+        Endpoint::new(Get, "/companies/search", "Search companies by destination and capabilities")
+            .query::<Option<Alpha3>>("delivers_to")
+            .query::<Option<CompanyCapabilities>>("supports")
+            .query::<Option<Alpha3>>("deliveries_from")
+            .query::<Option<String>>("label_contains")
+            .query::<Option<CompaniesSortField>>("sort")
+            .query::<Option<SortOrder>>("order")
+            .query::<Option<String>>("lang"),
+        Endpoint::new(Get, "/companies/{company_id}", "Company by id")
+            .path_param::<CompanyId>("company_id")
+            .query::<Option<String>>("lang"),
+        Endpoint::new(Put, "/companies/{company_id}", "Update company")
+            .path_param::<CompanyId>("company_id")
+            .payload::<UpdateCompany>("UpdateCompany"),
+        Endpoint::new(
+            Delete,
+            "/companies/{company_id}",
+            "Delete company, company packages with their products and shipping rates are deleted only with `cascade`",
+        )
+        .path_param::<CompanyId>("company_id")
+        .query::<Option<bool>>("cascade"),
+        Endpoint::new(
+            Get,
+            "/companies/{company_id}/deletion_impact",
+            "Counts of the data deleted together with the company",
+        )
+        .path_param::<CompanyId>("company_id"),
+        Endpoint::new(
+            Get,
+            "/companies/{company_id}/rates_quota",
+            "Shipping rates storage of the company against its quota",
+        )
+        .path_param::<CompanyId>("company_id"),
+        Endpoint::new(
+            Put,
+            "/companies/{company_id}/rates_quota",
+            "Override shipping rates quota of the company",
+        )
+        .path_param::<CompanyId>("company_id")
+        .payload::<SetCompanyRatesQuota>("SetCompanyRatesQuota"),
+        Endpoint::new(
+            Delete,
+            "/companies/{company_id}/rates_quota",
+            "Reset shipping rates quota of the company to the default",
+        )
+        .path_param::<CompanyId>("company_id"),
+        // Companies packages
+        Endpoint::new(Post, "/companies_packages", "Create company package").payload::<NewCompanyPackage>("NewCompanyPackage"),
+        Endpoint::new(Get, "/companies_packages/{company_package_id}", "Company package by id")
+            .path_param::<CompanyPackageId>("company_package_id"),
+        Endpoint::new(
+            Get,
+            "/companies_packages/{company_package_id}/rates",
+            "Shipping rates of the company package",
+        )
+        .path_param::<CompanyPackageId>("company_package_id")
+        .query::<Alpha3>("from"),
+        Endpoint::new(
+            Post,
+            "/companies_packages/{company_package_id}/rates",
+            "Replace shipping rates of the company package, with `dry_run` the replacement is only previewed",
+        )
+        .path_param::<CompanyPackageId>("company_package_id")
+        .query::<Option<bool>>("dry_run")
+        .payload::<Value>("ReplaceShippingRatesPayload"),
+        Endpoint::new(
+            Post,
+            "/companies_packages/{company_package_id}/rates/components",
+            "Add dated components to the shipping rates of the company package",
+        )
+        .path_param::<CompanyPackageId>("company_package_id")
+        .payload::<NewRateComponents>("NewRateComponents"),
+        Endpoint::new(
+            Put,
+            "/companies_packages/{company_package_id}/rate_resolution_order",
+            "Set the order the price of the company package is resolved in, `null` steps reset it to the default order",
+        )
+        .path_param::<CompanyPackageId>("company_package_id")
+        .payload::<SetRateResolutionOrder>("SetRateResolutionOrder"),
+        Endpoint::new(
+            Put,
+            "/companies_packages/{company_package_id}/cash_on_delivery",
+            "Set whether the company package supports the cash on delivery and its fee",
+        )
+        .path_param::<CompanyPackageId>("company_package_id")
+        .payload::<SetCashOnDelivery>("SetCashOnDelivery"),
+        Endpoint::new(
+            Put,
+            "/companies_packages/{company_package_id}/carrier_fallback",
+            "Set the price quoted when the carrier quote of the company package fails",
+        )
+        .path_param::<CompanyPackageId>("company_package_id")
+        .payload::<SetCarrierFallback>("SetCarrierFallback"),
+        Endpoint::new(
+            Put,
+            "/companies_packages/{company_package_id}/shipping_capacity",
+            "Set the daily cut-off time (UTC) and the number of shipments the company package takes a day",
+        )
+        .path_param::<CompanyPackageId>("company_package_id")
+        .payload::<SetShippingCapacity>("SetShippingCapacity"),
+        Endpoint::new(
+            Post,
+            "/companies_packages/{company_package_id}/shipment_bookings",
+            "Book a shipment of the order with the company package, conflicts when it is booked up on the shipping date",
+        )
+        .path_param::<CompanyPackageId>("company_package_id")
+        .payload::<BookShipment>("BookShipment"),
+        Endpoint::new(
+            Get,
+            "/companies_packages/{company_package_id}/products",
+            "Base products shipped by the company package",
+        )
+        .path_param::<CompanyPackageId>("company_package_id")
+        .query::<Option<i64>>("offset")
+        .query::<Option<i64>>("limit"),
+        Endpoint::new(
+            Get,
+            "/companies_packages/{company_package_id}/price",
+            "Delivery price of the shipment",
+        )
+        .path_param::<CompanyPackageId>("company_package_id")
+        .query::<Alpha3>("from")
+        .query::<Alpha3>("to")
+        .query::<f64>("volume")
+        .query::<f64>("weight")
+        .query::<Option<VolumeUnit>>("volume_unit")
+        .query::<Option<WeightUnit>>("weight_unit")
+        .query::<Option<bool>>("detailed")
+        .query::<Option<i32>>("pickup_point_id")
+        .query::<Option<f64>>("declared_value")
+        .query::<Option<String>>("insurance")
+        .query::<Option<bool>>("cod")
+        .query::<Option<StoreId>>("store_id")
+        .query::<Option<BaseProductId>>("base_product_id")
+        .query::<Option<String>>("to_subdivision")
+        .query::<Option<String>>("postal_code")
+        .coordinates(),
+        Endpoint::new(Post, "/delivery_prices/batch", "Delivery prices of several shipments").payload::<Value>("DeliveryPriceBatchItems"),
+        Endpoint::new(Post, "/delivery_quotes", "Calculate the delivery price and persist it as a quote")
+            .payload::<Value>("GetDeliveryPrice"),
+        Endpoint::new(
+            Get,
+            "/delivery_quotes/{quote_id}",
+            "Delivery quote by id, answers 410 Gone once the quote has expired",
+        )
+        .path_param::<Uuid>("quote_id"),
+        Endpoint::new(Get, "/companies/{company_id}/packages", "Packages of the company").path_param::<CompanyId>("company_id"),
+        Endpoint::new(
+            Delete,
+            "/companies/{company_id}/packages/{package_id}",
+            "Delete company package, products shipped by it are deleted only with `force`",
+        )
+        .path_param::<CompanyId>("company_id")
+        .path_param::<PackageId>("package_id")
+        .query::<Option<bool>>("force"),
+        Endpoint::new(Get, "/packages/{package_id}/companies", "Companies delivering the package").path_param::<PackageId>("package_id"),
+        // Available packages
+        Endpoint::new(
+            Get,
+            "/available_packages",
+            "Packages available for the shipment, with `explain` all company packages are returned with the reasons they are not available, \
+             with `with_measurements` the packages are returned together with the measurements they were checked against",
+        )
+        .query::<Alpha3>("country")
+        .query::<f64>("size")
+        .query::<f64>("weight")
+        .query::<Option<VolumeUnit>>("volume_unit")
+        .query::<Option<WeightUnit>>("weight_unit")
+        .query::<Option<bool>>("explain")
+        .query::<Option<bool>>("with_measurements"),
+        Endpoint::new(
+            Get,
+            "/available_packages_for_user/{base_product_id}",
+            "Packages available for the base product",
+        )
+        .path_param::<BaseProductId>("base_product_id")
+        .query::<Alpha3>("user_country")
+        .query::<Option<bool>>("explain")
+        .query::<Option<bool>>("cod")
+        .query::<Option<DateTime<Utc>>>("order_time"),
+        Endpoint::new(
+            Get,
+            "/v2/available_packages_for_user/{base_product_id}",
+            "Packages available for the base product",
+        )
+        .path_param::<BaseProductId>("base_product_id")
+        .query::<Alpha3>("delivery_from")
+        .query::<Option<Alpha3>>("delivery_to")
+        .query::<Option<String>>("to_region")
+        .query::<f64>("volume")
+        .query::<f64>("weight")
+        .query::<Option<VolumeUnit>>("volume_unit")
+        .query::<Option<WeightUnit>>("weight_unit")
+        .query::<Option<bool>>("explain")
+        .query::<Option<bool>>("with_cost")
+        .query::<Option<f64>>("order_total")
+        .query::<Option<bool>>("cod")
+        .query::<Option<OriginPreference>>("origin_preference")
+        .query::<Option<DateTime<Utc>>>("order_time")
+        .coordinates(),
+        Endpoint::new(
+            Get,
+            "/available_packages_for_user/products/{base_product_id}/companies_packages/{company_package_id}",
+            "Package available for the base product, conflicts if the company package ships the base product several times",
+        )
+        .path_param::<BaseProductId>("base_product_id")
+        .path_param::<CompanyPackageId>("company_package_id")
+        .deprecated(),
+        Endpoint::new(
+            Get,
+            "/available_packages_for_user/by_shipping_id/{shipping_id}",
+            "Available package by shipping id",
+        )
+        .path_param::<ShippingId>("shipping_id"),
+        Endpoint::new(
+            Get,
+            "/v2/available_packages_for_user/by_shipping_id/{shipping_id}",
+            "Available package by shipping id",
+        )
+        .path_param::<ShippingId>("shipping_id")
+        .query::<Alpha3>("delivery_from")
+        .query::<Alpha3>("delivery_to")
+        .query::<f64>("volume")
+        .query::<f64>("weight")
+        .query::<Option<VolumeUnit>>("volume_unit")
+        .query::<Option<WeightUnit>>("weight_unit")
+        .query::<Option<bool>>("with_cost")
+        .query::<Option<ShippingOptionId>>("option_id")
+        .query::<Option<DateTime<Utc>>>("order_time")
+        .coordinates(),
+        // Countries
+        Endpoint::new(Get, "/countries", "Countries tree"),
+        Endpoint::new(Get, "/countries/flatten", "Countries list"),
+        Endpoint::new(Get, "/countries/alpha2/{alpha2}", "Country by alpha2 code").path_param::<Alpha2>("alpha2"),
+        Endpoint::new(Get, "/countries/alpha3/{alpha3}", "Country by alpha3 code").path_param::<Alpha3>("alpha3"),
+        Endpoint::new(Get, "/countries/numeric/{numeric}", "Country by numeric code").path_param::<i32>("numeric"),
+        Endpoint::new(Post, "/countries", "Create country").payload::<NewCountry>("NewCountry"),
+        Endpoint::new(Post, "/countries/import", "Import ISO-3166 countries").payload::<ImportCountries>("ImportCountries"),
+        Endpoint::new(Get, "/countries/validate", "Problems of the countries tree"),
+        Endpoint::new(Post, "/countries/repair", "Re-parent orphans of the countries tree"),
+        Endpoint::new(Get, "/countries/dependents", "Refresh progress of country dependents"),
+        Endpoint::new(Get, "/country_regulations", "Parcel regulations of all countries"),
+        Endpoint::new(Get, "/country_regulations/{alpha3}", "Parcel regulations of the country").path_param::<Alpha3>("alpha3"),
+        Endpoint::new(Put, "/country_regulations/{alpha3}", "Set parcel regulations of the country")
+            .path_param::<Alpha3>("alpha3")
+            .payload::<SetCountryRegulation>("SetCountryRegulation"),
+        Endpoint::new(Delete, "/country_regulations/{alpha3}", "Delete parcel regulations of the country").path_param::<Alpha3>("alpha3"),
+        Endpoint::new(Get, "/countries/{alpha3}/subdivisions", "Subdivisions of the country").path_param::<Alpha3>("alpha3"),
+        Endpoint::new(Post, "/countries/{alpha3}/subdivisions", "Create subdivision of the country")
+            .path_param::<Alpha3>("alpha3")
+            .payload::<NewCountrySubdivision>("NewCountrySubdivision"),
+        Endpoint::new(
+            Get,
+            "/countries/{alpha3}/subdivisions/{code}",
+            "Subdivision of the country by ISO 3166-2 code",
+        )
+        .path_param::<Alpha3>("alpha3")
+        .path_param::<String>("code"),
+        Endpoint::new(Put, "/countries/{alpha3}/subdivisions/{code}", "Rename subdivision of the country")
+            .path_param::<Alpha3>("alpha3")
+            .path_param::<String>("code")
+            .payload::<UpdateCountrySubdivision>("UpdateCountrySubdivision"),
+        Endpoint::new(
+            Delete,
+            "/countries/{alpha3}/subdivisions/{code}",
+            "Delete subdivision of the country",
+        )
+        .path_param::<Alpha3>("alpha3")
+        .path_param::<String>("code"),
+        // Packages
+        Endpoint::new(Post, "/packages", "Create package").payload::<NewPackages>("NewPackages"),
+        Endpoint::new(Get, "/packages", "List packages").query::<Option<String>>("lang"),
+        Endpoint::new(Get, "/packages/search", "Search packages delivering to the country")
+            .query::<Alpha3>("delivers_to")
+            .query::<Option<u32>>("max_weight")
+            .query::<Option<i64>>("offset")
+            .query::<Option<i64>>("limit"),
+        Endpoint::new(Get, "/packages/{package_id}", "Package by id")
+            .path_param::<PackageId>("package_id")
+            .query::<Option<String>>("lang"),
+        Endpoint::new(Put, "/packages/{package_id}", "Update package")
+            .path_param::<PackageId>("package_id")
+            .payload::<UpdatePackages>("UpdatePackages"),
+        Endpoint::new(Delete, "/packages/{package_id}", "Delete package").path_param::<PackageId>("package_id"),
+        // Users addresses
+        Endpoint::new(Get, "/users/{user_id}/addresses", "Addresses of the user")
+            .path_param::<UserId>("user_id")
+            .query::<Option<bool>>("with_coordinates"),
+        Endpoint::new(Post, "/users/addresses", "Create user address").payload::<NewUserAddress>("NewUserAddress"),
+        Endpoint::new(Put, "/users/addresses/{user_address_id}", "Update user address")
+            .path_param::<i32>("user_address_id")
+            .payload::<UpdateUserAddress>("UpdateUserAddress"),
+        Endpoint::new(Delete, "/users/addresses/{user_address_id}", "Delete user address").path_param::<i32>("user_address_id"),
+        Endpoint::new(Post, "/users/addresses/{user_address_id}/default", "Make user address default").path_param::<i32>("user_address_id"),
+        // Zones
+        Endpoint::new(Get, "/zones", "List zones"),
+        Endpoint::new(Get, "/zones/{zone_id}", "Zone by id").path_param::<i32>("zone_id"),
+        Endpoint::new(Post, "/zones", "Create zone").payload::<NewZone>("NewZone"),
+        Endpoint::new(Put, "/zones/{zone_id}", "Update zone")
+            .path_param::<i32>("zone_id")
+            .payload::<UpdateZone>("UpdateZone"),
+        Endpoint::new(Delete, "/zones/{zone_id}", "Delete zone").path_param::<i32>("zone_id"),
+        // Postal zones
+        Endpoint::new(Get, "/postal_zones", "List postal zones").query::<Option<Alpha3>>("alpha3"),
+        Endpoint::new(Get, "/postal_zones/{postal_zone_id}", "Postal zone by id").path_param::<i32>("postal_zone_id"),
+        Endpoint::new(Post, "/postal_zones", "Create postal zone").payload::<NewPostalZone>("NewPostalZone"),
+        Endpoint::new(Post, "/postal_zones/import", "Import postal codes list of the carrier")
+            .payload::<ImportPostalZone>("ImportPostalZone"),
+        Endpoint::new(Delete, "/postal_zones/{postal_zone_id}", "Delete postal zone").path_param::<i32>("postal_zone_id"),
+        // Surcharges
+        Endpoint::new(Get, "/surcharges", "List surcharges"),
+        Endpoint::new(Get, "/surcharges/{surcharge_id}", "Surcharge by id").path_param::<i32>("surcharge_id"),
+        Endpoint::new(Post, "/surcharges", "Create surcharge").payload::<NewSurcharge>("NewSurcharge"),
+        Endpoint::new(Put, "/surcharges/{surcharge_id}", "Update surcharge")
+            .path_param::<i32>("surcharge_id")
+            .payload::<UpdateSurcharge>("UpdateSurcharge"),
+        Endpoint::new(Delete, "/surcharges/{surcharge_id}", "Delete surcharge").path_param::<i32>("surcharge_id"),
+        // Pricing rules
+        Endpoint::new(
+            Get,
+            "/pricing_rules",
+            "List markups and discounts of the delivery prices of the stores",
+        ),
+        Endpoint::new(Get, "/pricing_rules/{pricing_rule_id}", "Pricing rule by id").path_param::<i32>("pricing_rule_id"),
+        Endpoint::new(Post, "/pricing_rules", "Create pricing rule").payload::<NewPricingRule>("NewPricingRule"),
+        Endpoint::new(Put, "/pricing_rules/{pricing_rule_id}", "Update pricing rule")
+            .path_param::<i32>("pricing_rule_id")
+            .payload::<UpdatePricingRule>("UpdatePricingRule"),
+        Endpoint::new(Delete, "/pricing_rules/{pricing_rule_id}", "Delete pricing rule").path_param::<i32>("pricing_rule_id"),
+        // Shipping restrictions
+        Endpoint::new(Get, "/restrictions", "List shipping restrictions of the carriers"),
+        Endpoint::new(Get, "/restrictions/{restriction_id}", "Shipping restriction by id").path_param::<i32>("restriction_id"),
+        Endpoint::new(Post, "/restrictions", "Create shipping restriction").payload::<NewShippingRestriction>("NewShippingRestriction"),
+        Endpoint::new(Put, "/restrictions/{restriction_id}", "Update shipping restriction")
+            .path_param::<i32>("restriction_id")
+            .payload::<UpdateShippingRestriction>("UpdateShippingRestriction"),
+        Endpoint::new(Delete, "/restrictions/{restriction_id}", "Delete shipping restriction").path_param::<i32>("restriction_id"),
+        Endpoint::new(
+            Post,
+            "/restrictions/check",
+            "Carriers refusing the goods or accepting them only with special handling",
+        )
+        .payload::<CheckShippingRestrictions>("CheckShippingRestrictions"),
+        // Company suspensions
+        Endpoint::new(Get, "/company_suspensions", "List company suspensions"),
+        Endpoint::new(Get, "/company_suspensions/{suspension_id}", "Company suspension by id").path_param::<i32>("suspension_id"),
+        Endpoint::new(Post, "/company_suspensions", "Suspend company or company package")
+            .payload::<NewCompanySuspension>("NewCompanySuspension"),
+        Endpoint::new(Put, "/company_suspensions/{suspension_id}", "Update company suspension")
+            .path_param::<i32>("suspension_id")
+            .payload::<UpdateCompanySuspension>("UpdateCompanySuspension"),
+        Endpoint::new(Delete, "/company_suspensions/{suspension_id}", "Delete company suspension").path_param::<i32>("suspension_id"),
+        // Shipment notifications
+        Endpoint::new(Post, "/shipment_events", "Notify buyer about shipment event").payload::<ShipmentEvent>("ShipmentEvent"),
+        // Pickup points
+        Endpoint::new(Get, "/pickups", "List pickup points").query::<Option<CompanyId>>("company_id"),
+        Endpoint::new(Get, "/pickups/nearby", "Pickup points near the buyer")
+            .query::<f64>("lat")
+            .query::<f64>("lon")
+            .query::<f64>("radius"),
+        Endpoint::new(Get, "/pickups/{pickup_point_id}", "Pickup point by id").path_param::<i32>("pickup_point_id"),
+        Endpoint::new(Post, "/pickups", "Create pickup point").payload::<NewPickupPoint>("NewPickupPoint"),
+        Endpoint::new(Put, "/pickups/{pickup_point_id}", "Update pickup point")
+            .path_param::<i32>("pickup_point_id")
+            .payload::<UpdatePickupPoint>("UpdatePickupPoint"),
+        Endpoint::new(Delete, "/pickups/{pickup_point_id}", "Delete pickup point").path_param::<i32>("pickup_point_id"),
+        // Package templates
+        Endpoint::new(Get, "/package_templates", "List package templates").query::<Option<Alpha3>>("from"),
+        Endpoint::new(Get, "/package_templates/{package_template_id}", "Package template by id").path_param::<i32>("package_template_id"),
+        Endpoint::new(Post, "/package_templates", "Create package template").payload::<NewPackageTemplate>("NewPackageTemplate"),
+        Endpoint::new(Put, "/package_templates/{package_template_id}", "Update package template")
+            .path_param::<i32>("package_template_id")
+            .payload::<UpdatePackageTemplate>("UpdatePackageTemplate"),
+        Endpoint::new(Delete, "/package_templates/{package_template_id}", "Delete package template")
+            .path_param::<i32>("package_template_id"),
+        // Stores
+        Endpoint::new(Get, "/stores/{store_id}/suggested_packages", "Packages suggested to the store")
+            .path_param::<StoreId>("store_id")
+            .query::<Alpha3>("country"),
+        Endpoint::new(Get, "/stores/{store_id}/margin", "Margin of the store").path_param::<StoreId>("store_id"),
+        Endpoint::new(Put, "/stores/{store_id}/margin", "Set margin of the store")
+            .path_param::<StoreId>("store_id")
+            .payload::<SetStoreMargin>("SetStoreMargin"),
+        Endpoint::new(Delete, "/stores/{store_id}/margin", "Delete margin of the store").path_param::<StoreId>("store_id"),
+        Endpoint::new(Get, "/stores/{store_id}/carrier_accounts", "Carrier accounts of the store").path_param::<StoreId>("store_id"),
+        Endpoint::new(Post, "/stores/{store_id}/carrier_accounts", "Add carrier account of the store")
+            .path_param::<StoreId>("store_id")
+            .payload::<CreateCarrierAccount>("CreateCarrierAccount"),
+        Endpoint::new(
+            Put,
+            "/stores/{store_id}/carrier_accounts/{account_id}",
+            "Update carrier account of the store",
+        )
+        .path_param::<StoreId>("store_id")
+        .path_param::<i32>("account_id")
+        .payload::<UpdateCarrierAccount>("UpdateCarrierAccount"),
+        Endpoint::new(
+            Delete,
+            "/stores/{store_id}/carrier_accounts/{account_id}",
+            "Delete carrier account of the store",
+        )
+        .path_param::<StoreId>("store_id")
+        .path_param::<i32>("account_id"),
+        Endpoint::new(Get, "/stores/{store_id}/managers", "Shipping managers of the store").path_param::<StoreId>("store_id"),
+        Endpoint::new(Post, "/stores/{store_id}/managers", "Grant shipping manager of the store")
+            .path_param::<StoreId>("store_id")
+            .payload::<NewStoreManager>("NewStoreManager"),
+        Endpoint::new(
+            Delete,
+            "/stores/{store_id}/managers/{user_id}",
+            "Revoke shipping manager of the store",
+        )
+        .path_param::<StoreId>("store_id")
+        .path_param::<UserId>("user_id"),
+        Endpoint::new(Get, "/stores/{store_id}/shipping_templates", "Shipping templates of the store").path_param::<StoreId>("store_id"),
+        Endpoint::new(
+            Post,
+            "/stores/{store_id}/shipping_templates",
+            "Create shipping template of the store",
+        )
+        .path_param::<StoreId>("store_id")
+        .payload::<NewShippingTemplate>("NewShippingTemplate"),
+        Endpoint::new(
+            Get,
+            "/stores/{store_id}/shipping_templates/{template_id}",
+            "Shipping template of the store by id",
+        )
+        .path_param::<StoreId>("store_id")
+        .path_param::<i32>("template_id"),
+        Endpoint::new(
+            Put,
+            "/stores/{store_id}/shipping_templates/{template_id}",
+            "Update shipping template of the store",
+        )
+        .path_param::<StoreId>("store_id")
+        .path_param::<i32>("template_id")
+        .payload::<UpdateShippingTemplate>("UpdateShippingTemplate"),
+        Endpoint::new(
+            Delete,
+            "/stores/{store_id}/shipping_templates/{template_id}",
+            "Delete shipping template of the store",
+        )
+        .path_param::<StoreId>("store_id")
+        .path_param::<i32>("template_id"),
+        Endpoint::new(Get, "/stores/{store_id}/shipping_summary", "Shipping summary of the store").path_param::<StoreId>("store_id"),
+        Endpoint::new(
+            Get,
+            "/stores/{store_id}/shipping/export",
+            "Shipping of all products of the store with its templates as one document",
+        )
+        .path_param::<StoreId>("store_id"),
+        Endpoint::new(
+            Post,
+            "/stores/{store_id}/shipping/import",
+            "Set up shipping and templates of the exported document in the store",
+        )
+        .path_param::<StoreId>("store_id")
+        .query::<Option<ShippingImportConflict>>("on_conflict")
+        .payload::<StoreShippingExport>("StoreShippingExport"),
+        // Admin
+        Endpoint::new(Get, "/backfills", "List backfills"),
+        Endpoint::new(Get, "/backfills/{name}", "Backfill by name").path_param::<String>("name"),
+        Endpoint::new(
+            Get,
+            "/shipping_rates/anomalies",
+            "Anomalies found by the last shipping rates integrity check",
+        ),
+        Endpoint::new(Get, "/slo/status", "SLO status of the route groups"),
+        Endpoint::new(Get, "/schemas", "JSON schemas of the payloads"),
+        Endpoint::new(Get, "/diagnostics/connections", "Database connection checkouts"),
+        Endpoint::new(
+            Get,
+            "/admin/stats",
+            "Live usage of the database and CPU pools, counters of the delivery quotes cleanup and of the carrier calls",
+        ),
+        Endpoint::new(
+            Get,
+            "/admin/overview",
+            "Counts of the companies, packages, company packages and rates, base products with shipping by destination country",
+        ),
+        Endpoint::new(
+            Post,
+            "/admin/carrier_accounts/reencrypt",
+            "Re-encrypt credentials of the carrier accounts with the current key",
+        ),
+        Endpoint::new(Get, "/admin/api_keys", "List api keys of the internal callers"),
+        Endpoint::new(Post, "/admin/api_keys", "Issue api key, the key is returned only once").payload::<NewApiKey>("NewApiKey"),
+        Endpoint::new(Delete, "/admin/api_keys/{api_key_id}", "Revoke api key").path_param::<i32>("api_key_id"),
+        Endpoint::new(Get, "/openapi.json", "OpenAPI specification"),
+        Endpoint::new(Get, "/version", "Version of the service and of its database schema"),
+    ];
+    endpoints.extend(test_endpoints());
+    endpoints
+}
+
+#[cfg(feature = "test-endpoints")]
+fn test_endpoints() -> Vec<Endpoint> {
+    vec![
+        Endpoint::new(
+            Post,
+            "/__test__/seed",
+            "Replace the data with the test fixtures, available only in the test environments",
+        ),
+        Endpoint::new(
+            Post,
+            "/__test__/reset",
+            "Delete the test data, available only in the test environments",
+        ),
+    ]
+}
+
+#[cfg(not(feature = "test-endpoints"))]
+fn test_endpoints() -> Vec<Endpoint> {
+    vec![]
+}
@@ -0,0 +1,219 @@
+//! Refresh of data denormalized from the countries tree.
+//!
+//! Products, packages and companies keep copies of country codes. When the tree changes, e.g. a country
+//! is renamed or removed, copies of the codes missing from the tree are dropped. Every dependent is refreshed
+//! by a batched backfill named after the version of the tree, so its progress is served by `GET /backfills`
+//! and `GET /countries/dependents`, and a refresh of the same version is never run twice.
+//! Rows of a batch are locked while they are refreshed, so concurrent updates of the codes are not lost,
+//! and the versions of the content the changed rows are served in are dropped after the refresh.
+//! The tree version is checked every `refresh_interval_sec`, that limits how often the refresh can start.
+use std::cell::Cell;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use failure::Error as FailureError;
+use futures::{Future, Stream};
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+use tokio_core::reactor::{Handle, Interval};
+
+use backfills::{run_backfill, Backfill, BackfillSettings, BatchResult};
+use content_versions::{ContentVersions, VersionedContent};
+use models::{countries_tree_version, BackfillProgress, CountryDependent};
+use repos::{BackfillsRepo, CountriesRepo, CountryDependentsRepo, DbConnection, ReposFactory};
+
+/// Drops codes missing from the countries tree from the rows of the dependent
+pub struct CountryDependentBackfill<'a> {
+    pub dependent: CountryDependent,
+    pub tree_version: String,
+    pub known_codes: BTreeSet<String>,
+    pub dependents_repo: &'a dyn CountryDependentsRepo,
+    /// Number of rows the unknown codes were dropped from
+    pub updated_rows: Cell<i64>,
+}
+
+impl<'a, T: DbConnection> Backfill<T> for CountryDependentBackfill<'a> {
+    fn name(&self) -> String {
+        self.dependent.refresh_name(&self.tree_version)
+    }
+
+    fn count_rows(&self, _db_conn: &T) -> Result<i64, FailureError> {
+        self.dependents_repo.count(self.dependent)
+    }
+
+    fn run_batch(&self, _db_conn: &T, after_id: Option<i32>, batch_size: i64) -> Result<Option<BatchResult>, FailureError> {
+        let rows = self.dependents_repo.get_batch(self.dependent, after_id, batch_size)?;
+        let last_id = match rows.last() {
+            Some(row) => row.id,
+            None => return Ok(None),
+        };
+
+        for row in &rows {
+            if let Some(codes) = row.without_unknown_codes(&self.known_codes) {
+                info!(
+                    "Unknown country codes dropped from {} row {}: {:?} -> {:?}",
+                    self.dependent, row.id, row.codes, codes
+                );
+                self.dependents_repo.set_codes(self.dependent, row.id, codes)?;
+                self.updated_rows.set(self.updated_rows.get() + 1);
+            }
+        }
+
+        Ok(Some(BatchResult {
+            processed_rows: rows.len() as i64,
+            last_id,
+        }))
+    }
+}
+
+/// Versioned content the rows of the dependent are served in
+fn versioned_content(dependent: CountryDependent) -> &'static [VersionedContent] {
+    match dependent {
+        CountryDependent::PackagesDeliveriesTo => &[VersionedContent::Packages],
+        CountryDependent::CompaniesDeliveriesFrom | CountryDependent::ProductsDeliveriesTo => &[],
+    }
+}
+
+/// Refreshes all dependents against the current countries tree. Dependents already refreshed
+/// against this version of the tree are skipped, versions of the content of the updated ones are dropped
+pub fn refresh_country_dependents<T>(
+    db_conn: &T,
+    countries_repo: &dyn CountriesRepo,
+    dependents_repo: &dyn CountryDependentsRepo,
+    backfills_repo: &dyn BackfillsRepo,
+    content_versions: &ContentVersions,
+    settings: &BackfillSettings,
+) -> Result<Vec<BackfillProgress>, FailureError>
+where
+    T: DbConnection,
+{
+    let countries = countries_repo.get_all_flatten()?;
+    let tree_version = countries_tree_version(&countries);
+    let known_codes = countries.into_iter().map(|country| country.alpha3.0).collect::<BTreeSet<_>>();
+
+    CountryDependent::all()
+        .into_iter()
+        .map(|dependent| {
+            let backfill = CountryDependentBackfill {
+                dependent,
+                tree_version: tree_version.clone(),
+                known_codes: known_codes.clone(),
+                dependents_repo,
+                updated_rows: Cell::new(0),
+            };
+            let progress = run_backfill(db_conn, backfills_repo, &backfill, settings);
+            // rows of the committed batches are changed even if the refresh failed later
+            if backfill.updated_rows.get() > 0 {
+                content_versions.invalidate(versioned_content(dependent));
+            }
+            progress
+        })
+        .collect()
+}
+
+/// Checks the countries tree version on `cpu_pool` every `interval` and refreshes the dependents
+/// if it has changed. The next check waits for the running refresh to finish
+pub fn schedule<T, M, F>(
+    handle: &Handle,
+    cpu_pool: CpuPool,
+    db_pool: Pool<M>,
+    repo_factory: F,
+    content_versions: Arc<ContentVersions>,
+    interval: Duration,
+    settings: BackfillSettings,
+) where
+    T: DbConnection,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+{
+    let interval = Interval::new(interval, handle).expect("Failed to create interval");
+
+    handle.spawn(
+        interval
+            .map_err(|e| error!("Country dependents refresh schedule error: {}", e))
+            .for_each(move |_| {
+                let db_pool = db_pool.clone();
+                let repo_factory = repo_factory.clone();
+                let content_versions = content_versions.clone();
+                let settings = settings.clone();
+
+                cpu_pool.spawn_fn(move || {
+                    let result = db_pool.get().map_err(FailureError::from).and_then(|conn| {
+                        let countries_repo = repo_factory.create_countries_repo(&*conn, None);
+                        let dependents_repo = repo_factory.create_country_dependents_repo_with_sys_acl(&*conn);
+                        let backfills_repo = repo_factory.create_backfills_repo_with_sys_acl(&*conn);
+                        refresh_country_dependents(
+                            &*conn,
+                            &*countries_repo,
+                            &*dependents_repo,
+                            &*backfills_repo,
+                            &content_versions,
+                            &settings,
+                        )
+                    });
+
+                    if let Err(e) = result {
+                        error!("Country dependents refresh failed: {}", e);
+                    }
+
+                    Ok(())
+                })
+            }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use models::BackfillStatus;
+    use repos::repo_factory::tests::*;
+
+    use super::*;
+
+    #[test]
+    fn dependents_are_refreshed_against_tree_version() {
+        let db_conn = MockConnection::default();
+        let countries_repo = CountriesRepoMock {
+            user_id: Some(MOCK_USER_ID),
+        };
+        let dependents_repo = CountryDependentsRepoMock {
+            user_id: Some(MOCK_USER_ID),
+        };
+        let backfills_repo = BackfillsRepoMock {
+            user_id: Some(MOCK_USER_ID),
+        };
+        let settings = BackfillSettings {
+            batch_size: 10,
+            throttle: Duration::from_millis(0),
+        };
+
+        let content_versions = ContentVersions::default();
+        content_versions.update(VersionedContent::Packages, &"packages");
+        content_versions.update(VersionedContent::Countries, &"countries");
+
+        let tree_version = countries_tree_version(&countries_repo.get_all_flatten().unwrap());
+        let progress = refresh_country_dependents(
+            &db_conn,
+            &countries_repo,
+            &dependents_repo,
+            &backfills_repo,
+            &content_versions,
+            &settings,
+        )
+        .unwrap();
+
+        assert_eq!(
+            progress
+                .iter()
+                .map(|progress| (progress.name.clone(), progress.status))
+                .collect::<Vec<_>>(),
+            CountryDependent::all()
+                .into_iter()
+                .map(|dependent| (dependent.refresh_name(&tree_version), BackfillStatus::Finished))
+                .collect::<Vec<_>>()
+        );
+        // unknown codes were dropped from the packages, the countries tree itself is not changed
+        assert!(content_versions.get(VersionedContent::Packages).is_none());
+        assert!(content_versions.get(VersionedContent::Countries).is_some());
+    }
+}
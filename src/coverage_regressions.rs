@@ -0,0 +1,220 @@
+//! Daily check of the availability results for regressions.
+//!
+//! Availability of the sampled routes, base products delivered to a country, is snapshotted every day
+//! and compared with the previous snapshot of the route. Packages lost and prices shifted over the configured
+//! thresholds are alerted, so that accidental changes of rates and coverage by admin edits are caught
+//! before the buyers notice them. Availability is computed the way it is for an anonymous buyer.
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use failure::Error as FailureError;
+use futures::{Future, Stream};
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+use tokio_core::reactor::{Handle, Interval};
+
+use stq_types::{Alpha3, BaseProductId};
+
+use clock::{Clock, SystemClock};
+use config;
+use models::{find_coverage_regressions, CoverageRegression, NewAvailabilitySnapshot, SnapshotPackage};
+use rates_integrity::duration_until_hour;
+use repos::{
+    AvailabilitySnapshotsRepo, CompaniesPackagesRepo, CompanySuspensionsRepo, CountryRegulationsRepo, DbConnection, ProductsRepo,
+    ReposFactory,
+};
+use sentry_integration::log_and_capture_warning;
+use services::products::find_available_packages;
+
+const CHECK_INTERVAL_SEC: u64 = 24 * 60 * 60;
+
+/// Regressions found on a sampled route
+#[derive(Clone, Debug, PartialEq)]
+pub struct RouteRegressions {
+    pub base_product_id: BaseProductId,
+    pub delivery_to: Alpha3,
+    pub regressions: Vec<CoverageRegression>,
+}
+
+/// Repos the check reads availability with and keeps the snapshots in
+pub struct CoverageRepos<'a> {
+    pub products_repo: &'a dyn ProductsRepo,
+    pub company_package_repo: &'a dyn CompaniesPackagesRepo,
+    pub country_regulations_repo: &'a dyn CountryRegulationsRepo,
+    pub company_suspensions_repo: &'a dyn CompanySuspensionsRepo,
+    pub snapshots_repo: &'a dyn AvailabilitySnapshotsRepo,
+}
+
+/// Snapshots availability of every sample on the date of the clock replacing the previous snapshots, returns the routes
+/// with regressions. Samples failing to be checked are skipped, a removed product must not stop the check of the others
+pub fn check_coverage(repos: &CoverageRepos, settings: &config::CoverageRegressions, clock: &dyn Clock) -> Vec<RouteRegressions> {
+    let mut found = vec![];
+    for sample in &settings.samples {
+        match check_sample(repos, settings, sample, clock) {
+            Ok(regressions) => {
+                if !regressions.is_empty() {
+                    found.push(RouteRegressions {
+                        base_product_id: sample.base_product_id,
+                        delivery_to: sample.delivery_to.clone(),
+                        regressions,
+                    });
+                }
+            }
+            Err(e) => warn!(
+                "Coverage regressions check skipped base product {} to {}: {}",
+                sample.base_product_id, sample.delivery_to, e
+            ),
+        }
+    }
+
+    found
+}
+
+fn check_sample(
+    repos: &CoverageRepos,
+    settings: &config::CoverageRegressions,
+    sample: &config::CoverageSample,
+    clock: &dyn Clock,
+) -> Result<Vec<CoverageRegression>, FailureError> {
+    let (packages, _) = find_available_packages(
+        repos.products_repo,
+        repos.company_package_repo,
+        repos.country_regulations_repo,
+        repos.company_suspensions_repo,
+        sample.base_product_id,
+        sample.delivery_to.clone(),
+        clock.today(),
+    )?;
+    let current = packages.iter().map(SnapshotPackage::from).collect::<Vec<_>>();
+
+    let previous = repos.snapshots_repo.get(sample.base_product_id, sample.delivery_to.clone())?;
+    repos.snapshots_repo.save(NewAvailabilitySnapshot::new(
+        sample.base_product_id,
+        sample.delivery_to.clone(),
+        &current,
+    )?)?;
+
+    Ok(previous
+        .map(|previous| find_coverage_regressions(&previous.packages, &current, settings.max_coverage_drop, settings.max_price_change))
+        .unwrap_or_default())
+}
+
+/// Runs the check on `cpu_pool` every day at the configured hour
+pub fn schedule<T, M, F>(handle: &Handle, cpu_pool: CpuPool, db_pool: Pool<M>, repo_factory: F, settings: config::CoverageRegressions)
+where
+    T: DbConnection,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+{
+    if settings.samples.is_empty() {
+        return;
+    }
+
+    let first_run = Instant::now() + duration_until_hour(Utc::now(), settings.run_at_hour_utc % 24);
+    let interval = Interval::new_at(first_run, Duration::from_secs(CHECK_INTERVAL_SEC), handle).expect("Failed to create interval");
+
+    handle.spawn(
+        interval
+            .map_err(|e| error!("Coverage regressions check schedule error: {}", e))
+            .for_each(move |_| {
+                let db_pool = db_pool.clone();
+                let repo_factory = repo_factory.clone();
+                let settings = settings.clone();
+
+                cpu_pool.spawn_fn(move || {
+                    let result = db_pool.get().map_err(FailureError::from).map(|conn| {
+                        let products_repo = repo_factory.create_products_repo(&*conn, None);
+                        let company_package_repo = repo_factory.create_companies_packages_repo(&*conn, None);
+                        let country_regulations_repo = repo_factory.create_country_regulations_repo_with_sys_acl(&*conn);
+                        let company_suspensions_repo = repo_factory.create_company_suspensions_repo_with_sys_acl(&*conn);
+                        let snapshots_repo = repo_factory.create_availability_snapshots_repo_with_sys_acl(&*conn);
+                        let repos = CoverageRepos {
+                            products_repo: &*products_repo,
+                            company_package_repo: &*company_package_repo,
+                            country_regulations_repo: &*country_regulations_repo,
+                            company_suspensions_repo: &*company_suspensions_repo,
+                            snapshots_repo: &*snapshots_repo,
+                        };
+                        check_coverage(&repos, &settings, &SystemClock)
+                    });
+
+                    match result {
+                        Ok(found) => {
+                            for route in &found {
+                                for regression in &route.regressions {
+                                    log_and_capture_warning(&format!(
+                                        "Availability of base product {} to {} regressed: {}",
+                                        route.base_product_id, route.delivery_to, regression
+                                    ));
+                                }
+                            }
+                            info!(
+                                "Coverage regressions check finished, routes: {}, regressed: {}",
+                                settings.samples.len(),
+                                found.len()
+                            );
+                        }
+                        Err(e) => error!("Coverage regressions check failed: {}", e),
+                    }
+
+                    Ok(())
+                })
+            }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use stq_types::CompanyPackageId;
+
+    use repos::repo_factory::tests::*;
+
+    use super::*;
+
+    #[test]
+    fn lost_packages_are_reported() {
+        let products_repo = ProductsRepoMock {
+            user_id: Some(MOCK_USER_ID),
+        };
+        let company_package_repo = CompaniesPackagesRepoMock {
+            user_id: Some(MOCK_USER_ID),
+        };
+        let country_regulations_repo = CountryRegulationsRepoMock {
+            user_id: Some(MOCK_USER_ID),
+        };
+        let company_suspensions_repo = CompanySuspensionsRepoMock {
+            user_id: Some(MOCK_USER_ID),
+        };
+        let snapshots_repo = AvailabilitySnapshotsRepoMock {
+            user_id: Some(MOCK_USER_ID),
+        };
+        let repos = CoverageRepos {
+            products_repo: &products_repo,
+            company_package_repo: &company_package_repo,
+            country_regulations_repo: &country_regulations_repo,
+            company_suspensions_repo: &company_suspensions_repo,
+            snapshots_repo: &snapshots_repo,
+        };
+        let settings = config::CoverageRegressions {
+            run_at_hour_utc: 4,
+            max_coverage_drop: 0.2,
+            max_price_change: 0.3,
+            samples: vec![config::CoverageSample {
+                base_product_id: MOCK_BASE_PRODUCT_ID,
+                delivery_to: Alpha3("RUS".to_string()),
+            }],
+        };
+
+        assert_eq!(
+            check_coverage(&repos, &settings, &SystemClock),
+            vec![RouteRegressions {
+                base_product_id: MOCK_BASE_PRODUCT_ID,
+                delivery_to: Alpha3("RUS".to_string()),
+                regressions: vec![CoverageRegression::CoverageDropped {
+                    lost: vec![CompanyPackageId(2)],
+                    share: 0.5,
+                }],
+            }]
+        );
+    }
+}
@@ -0,0 +1,261 @@
+//! Encryption of the credentials of the carrier accounts of the stores.
+//!
+//! Credentials are encrypted with AES-256-GCM by the key from the config before they are stored,
+//! and are decrypted only to request the negotiated rates from the carrier. Stored value is the base64
+//! of the random nonce, the authentication tag and the ciphertext, so a value changed in the database
+//! fails to decrypt instead of being sent to the carrier.
+//!
+//! Values are decrypted with the current key or with one of the previous keys, so the key is rotated
+//! without downtime: the re-encryption backfill named after the current key moves all values to it,
+//! after that the previous keys are removed from the config.
+use base64;
+use failure::Error as FailureError;
+use failure::Fail;
+use openssl::rand::rand_bytes;
+use openssl::sha::sha256;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+
+use backfills::{run_backfill, Backfill, BackfillSettings, BatchResult};
+use config;
+use errors::Error;
+use models::BackfillProgress;
+use repos::{BackfillsRepo, CarrierAccountsRepo, DbConnection};
+
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+pub struct CredentialsCipher {
+    /// Current key goes first, empty if carrier accounts are not configured
+    keys: Vec<Vec<u8>>,
+}
+
+fn decode_key(key: &str) -> Result<Vec<u8>, FailureError> {
+    let key = base64::decode(key).map_err(|e| FailureError::from(e).context("Encryption key of carrier accounts is not valid base64"))?;
+    if key.len() != KEY_LEN {
+        return Err(format_err!("Encryption key of carrier accounts must be {} bytes long", KEY_LEN));
+    }
+    Ok(key)
+}
+
+impl CredentialsCipher {
+    /// Keys in the config are the base64 of 32 random bytes
+    pub fn new(config: Option<config::CarrierAccounts>) -> Result<Self, FailureError> {
+        let keys = match config {
+            Some(config) => {
+                let mut keys = vec![decode_key(&config.encryption_key)?];
+                for key in &config.previous_encryption_keys {
+                    keys.push(decode_key(key)?);
+                }
+                keys
+            }
+            None => vec![],
+        };
+
+        Ok(Self { keys })
+    }
+
+    fn current_key(&self) -> Result<&[u8], FailureError> {
+        self.keys.first().map(Vec::as_slice).ok_or_else(|| {
+            Error::Unprocessable(validation_errors!({
+                "credentials": ["credentials" => "Carrier accounts are not configured"]
+            }))
+            .into()
+        })
+    }
+
+    /// Short hash of the current key, identifies the key without revealing it
+    pub fn key_fingerprint(&self) -> Result<String, FailureError> {
+        let key = self.current_key()?;
+        Ok(sha256(key).iter().take(8).map(|byte| format!("{:02x}", byte)).collect())
+    }
+
+    pub fn encrypt(&self, credentials: &str) -> Result<String, FailureError> {
+        let key = self.current_key()?;
+        let mut nonce = [0u8; NONCE_LEN];
+        rand_bytes(&mut nonce)?;
+        let mut tag = [0u8; TAG_LEN];
+        let ciphertext = encrypt_aead(Cipher::aes_256_gcm(), key, Some(&nonce), &[], credentials.as_bytes(), &mut tag)?;
+
+        let mut sealed = Vec::with_capacity(NONCE_LEN + TAG_LEN + ciphertext.len());
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&tag);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(base64::encode(&sealed))
+    }
+
+    /// Returns the credentials and the position of the key they were encrypted with
+    fn decrypt_with_key_index(&self, encrypted: &str) -> Result<(String, usize), FailureError> {
+        self.current_key()?;
+        let sealed = base64::decode(encrypted)?;
+        if sealed.len() < NONCE_LEN + TAG_LEN {
+            return Err(format_err!("Encrypted credentials are too short"));
+        }
+
+        let (nonce, rest) = sealed.split_at(NONCE_LEN);
+        let (tag, ciphertext) = rest.split_at(TAG_LEN);
+        // authentication tag does not match for the other keys
+        let (credentials, index) = self
+            .keys
+            .iter()
+            .enumerate()
+            .filter_map(|(index, key)| {
+                decrypt_aead(Cipher::aes_256_gcm(), key, Some(nonce), &[], ciphertext, tag)
+                    .ok()
+                    .map(|credentials| (credentials, index))
+            })
+            .next()
+            .ok_or_else(|| format_err!("Credentials could not be decrypted with any of the keys"))?;
+        let credentials = String::from_utf8(credentials)?;
+        Ok((credentials, index))
+    }
+
+    pub fn decrypt(&self, encrypted: &str) -> Result<String, FailureError> {
+        self.decrypt_with_key_index(encrypted).map(|(credentials, _)| credentials)
+    }
+
+    /// Encrypts the value with the current key, `None` if it is already encrypted with it
+    pub fn reencrypt(&self, encrypted: &str) -> Result<Option<String>, FailureError> {
+        match self.decrypt_with_key_index(encrypted)? {
+            (_, 0) => Ok(None),
+            (credentials, _) => self.encrypt(&credentials).map(Some),
+        }
+    }
+}
+
+/// Re-encrypts the credentials of all carrier accounts with the current key
+pub struct CredentialsReencryption<'a> {
+    pub key_fingerprint: String,
+    pub carrier_accounts_repo: &'a dyn CarrierAccountsRepo,
+}
+
+impl<'a, T: DbConnection> Backfill<T> for CredentialsReencryption<'a> {
+    fn name(&self) -> String {
+        format!("carrier_credentials_reencryption_{}", self.key_fingerprint)
+    }
+
+    fn count_rows(&self, _db_conn: &T) -> Result<i64, FailureError> {
+        self.carrier_accounts_repo.count_all()
+    }
+
+    fn run_batch(&self, _db_conn: &T, after_id: Option<i32>, batch_size: i64) -> Result<Option<BatchResult>, FailureError> {
+        let accounts = self.carrier_accounts_repo.get_batch(after_id, batch_size)?;
+        let last_id = match accounts.last() {
+            Some(account) => account.id,
+            None => return Ok(None),
+        };
+
+        for account in &accounts {
+            if self.carrier_accounts_repo.reencrypt(account)? {
+                info!("Credentials of carrier account {} re-encrypted", account.id);
+            }
+        }
+
+        Ok(Some(BatchResult {
+            processed_rows: accounts.len() as i64,
+            last_id,
+        }))
+    }
+}
+
+/// Re-encrypts the credentials with the current key. Re-encryption with the same key is run only once
+pub fn reencrypt_credentials<T>(
+    db_conn: &T,
+    carrier_accounts_repo: &dyn CarrierAccountsRepo,
+    backfills_repo: &dyn BackfillsRepo,
+    settings: &BackfillSettings,
+) -> Result<BackfillProgress, FailureError>
+where
+    T: DbConnection,
+{
+    let backfill = CredentialsReencryption {
+        key_fingerprint: carrier_accounts_repo.key_fingerprint()?,
+        carrier_accounts_repo,
+    };
+    run_backfill(db_conn, backfills_repo, &backfill, settings)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::time::Duration;
+
+    use models::BackfillStatus;
+    use repos::repo_factory::tests::*;
+
+    use super::*;
+
+    /// Cipher with a fixed key for the tests
+    pub fn test_cipher() -> CredentialsCipher {
+        CredentialsCipher::new(Some(config::CarrierAccounts {
+            encryption_key: base64::encode(&[7u8; KEY_LEN]),
+            previous_encryption_keys: vec![],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn credentials_are_decrypted_only_if_not_changed() {
+        let cipher = test_cipher();
+
+        let encrypted = cipher.encrypt("api-key-123").unwrap();
+        assert_ne!(encrypted, cipher.encrypt("api-key-123").unwrap());
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), "api-key-123");
+
+        let mut sealed = base64::decode(&encrypted).unwrap();
+        let last = sealed.len() - 1;
+        sealed[last] ^= 1;
+        assert!(cipher.decrypt(&base64::encode(&sealed)).is_err());
+    }
+
+    #[test]
+    fn credentials_are_not_encrypted_without_key() {
+        assert!(CredentialsCipher::new(None).unwrap().encrypt("api-key-123").is_err());
+        assert!(CredentialsCipher::new(Some(config::CarrierAccounts {
+            encryption_key: base64::encode(&[7u8; 16]),
+            previous_encryption_keys: vec![],
+        }))
+        .is_err());
+    }
+
+    #[test]
+    fn credentials_are_reencrypted_with_rotated_key() {
+        let old_cipher = test_cipher();
+        let rotated_cipher = CredentialsCipher::new(Some(config::CarrierAccounts {
+            encryption_key: base64::encode(&[9u8; KEY_LEN]),
+            previous_encryption_keys: vec![base64::encode(&[7u8; KEY_LEN])],
+        }))
+        .unwrap();
+
+        let encrypted = old_cipher.encrypt("api-key-123").unwrap();
+        assert_eq!(rotated_cipher.decrypt(&encrypted).unwrap(), "api-key-123");
+
+        let reencrypted = rotated_cipher.reencrypt(&encrypted).unwrap().unwrap();
+        assert!(old_cipher.decrypt(&reencrypted).is_err());
+        assert_eq!(rotated_cipher.decrypt(&reencrypted).unwrap(), "api-key-123");
+        assert_eq!(rotated_cipher.reencrypt(&reencrypted).unwrap(), None);
+        assert_ne!(old_cipher.key_fingerprint().unwrap(), rotated_cipher.key_fingerprint().unwrap());
+    }
+
+    #[test]
+    fn reencryption_is_named_after_current_key() {
+        let db_conn = MockConnection::default();
+        let carrier_accounts_repo = CarrierAccountsRepoMock {
+            user_id: Some(MOCK_USER_ID),
+        };
+        let backfills_repo = BackfillsRepoMock {
+            user_id: Some(MOCK_USER_ID),
+        };
+        let settings = BackfillSettings {
+            batch_size: 10,
+            throttle: Duration::from_millis(0),
+        };
+
+        let progress = reencrypt_credentials(&db_conn, &carrier_accounts_repo, &backfills_repo, &settings).unwrap();
+
+        assert_eq!(
+            progress.name,
+            format!("carrier_credentials_reencryption_{}", test_cipher().key_fingerprint().unwrap())
+        );
+        assert_eq!(progress.status, BackfillStatus::Finished);
+    }
+}
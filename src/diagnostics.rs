@@ -0,0 +1,510 @@
+//! Database connections diagnostics.
+//!
+//! Every connection checkout of the service layer is tracked together with the endpoint it was taken for.
+//! Checkouts held longer than the threshold are logged while they are still held - that is most likely
+//! a leak or a runaway query - and statistics per endpoint are reported by `GET /diagnostics/connections`.
+//! Paths may carry any strings, so the number of the endpoints with statistics is limited,
+//! the checkouts of the endpoints over the limit are counted together.
+//!
+//! Tasks of the CPU pools and the waits for a connection are tracked too, live usage of the pools
+//! is reported by `GET /admin/stats`.
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use carriers::CarrierCallStats;
+use config;
+use quotes_cleanup::QuotesCleanupStats;
+use replicas::ReadReplicasReport;
+use slo::duration_to_ms;
+
+const DEFAULT_CHECKOUT_THRESHOLD_MS: u64 = 5000;
+const DEFAULT_CHECK_INTERVAL_SEC: u64 = 10;
+/// Memory limit of the statistics per endpoint
+const MAX_TRACKED_OPERATIONS: usize = 500;
+/// Operation the checkouts of the endpoints over the limit are counted for
+const OTHER_OPERATIONS: &str = "other";
+
+struct ActiveCheckoutEntry {
+    operation: String,
+    started_at: Instant,
+    /// Long checkout is logged only once
+    reported: bool,
+}
+
+#[derive(Default)]
+struct OperationEntry {
+    checkouts: u64,
+    total: Duration,
+    max: Duration,
+    slow_checkouts: u64,
+}
+
+#[derive(Default)]
+struct WaitEntry {
+    waits: u64,
+    total: Duration,
+    max: Duration,
+}
+
+impl WaitEntry {
+    fn add(&mut self, wait: Duration) {
+        self.waits += 1;
+        self.total += wait;
+        self.max = self.max.max(wait);
+    }
+
+    fn report(&self) -> WaitTimes {
+        WaitTimes {
+            waits: self.waits,
+            avg_ms: duration_to_ms(self.total) / self.waits.max(1),
+            max_ms: duration_to_ms(self.max),
+        }
+    }
+}
+
+/// Wait times since the start of the service
+#[derive(Serialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct WaitTimes {
+    pub waits: u64,
+    pub avg_ms: u64,
+    pub max_ms: u64,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct PoolState {
+    pub connections: u32,
+    pub idle_connections: u32,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ActiveCheckout {
+    pub operation: String,
+    pub held_ms: u64,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct OperationCheckouts {
+    pub operation: String,
+    pub checkouts: u64,
+    pub avg_ms: u64,
+    pub max_ms: u64,
+    /// Checkouts held longer than the threshold
+    pub slow_checkouts: u64,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ConnectionsReport {
+    pub threshold_ms: u64,
+    pub pool: PoolState,
+    /// Connections currently checked out, longest held first
+    pub active: Vec<ActiveCheckout>,
+    pub operations: Vec<OperationCheckouts>,
+    /// Set if read replicas are configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_replicas: Option<ReadReplicasReport>,
+}
+
+pub struct ConnectionTracker {
+    threshold: Duration,
+    check_interval: Duration,
+    next_id: AtomicUsize,
+    active: Mutex<HashMap<usize, ActiveCheckoutEntry>>,
+    operations: Mutex<HashMap<String, OperationEntry>>,
+    checkout_waits: Mutex<WaitEntry>,
+}
+
+/// Connection checkout in progress, finished when dropped
+pub struct Checkout<'a> {
+    tracker: &'a ConnectionTracker,
+    id: usize,
+}
+
+impl<'a> Drop for Checkout<'a> {
+    fn drop(&mut self) {
+        self.tracker.finish(self.id, Instant::now());
+    }
+}
+
+/// Name of the endpoint for the request, ids in the path are replaced with `:id`
+/// so that all requests to the same endpoint are tracked together
+pub fn operation_name(method: &str, path: &str) -> String {
+    let path = path
+        .split('/')
+        .map(|segment| {
+            if !segment.is_empty() && segment.chars().all(|c| c.is_ascii_digit()) {
+                ":id"
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+
+    format!("{} {}", method, path)
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+impl ConnectionTracker {
+    pub fn new(config: Option<config::Diagnostics>) -> Self {
+        let (threshold_ms, check_interval_sec) = match config {
+            Some(config) => (config.checkout_threshold_ms, config.check_interval_sec),
+            None => (DEFAULT_CHECKOUT_THRESHOLD_MS, DEFAULT_CHECK_INTERVAL_SEC),
+        };
+
+        Self {
+            threshold: Duration::from_millis(threshold_ms),
+            check_interval: Duration::from_secs(check_interval_sec),
+            next_id: AtomicUsize::new(0),
+            active: Mutex::new(HashMap::new()),
+            operations: Mutex::new(HashMap::new()),
+            checkout_waits: Mutex::new(WaitEntry::default()),
+        }
+    }
+
+    /// Records how long getting the connection from the pool took
+    pub fn record_checkout_wait(&self, wait: Duration) {
+        lock(&self.checkout_waits).add(wait);
+    }
+
+    pub fn checkout_waits(&self) -> WaitTimes {
+        lock(&self.checkout_waits).report()
+    }
+
+    /// How often `check_long_checkouts` should be run
+    pub fn check_interval(&self) -> Duration {
+        self.check_interval
+    }
+
+    /// Starts tracking of a connection checkout, it lasts until the returned value is dropped
+    pub fn checkout(&self, operation: String) -> Checkout {
+        self.checkout_at(Instant::now(), operation)
+    }
+
+    fn checkout_at(&self, at: Instant, operation: String) -> Checkout {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        lock(&self.active).insert(
+            id,
+            ActiveCheckoutEntry {
+                operation,
+                started_at: at,
+                reported: false,
+            },
+        );
+
+        Checkout { tracker: self, id }
+    }
+
+    fn finish(&self, id: usize, at: Instant) {
+        let entry = match lock(&self.active).remove(&id) {
+            Some(entry) => entry,
+            None => return,
+        };
+
+        let held = at.duration_since(entry.started_at);
+        let slow = held > self.threshold;
+        if slow {
+            warn!(
+                "Connection checked out for {} was returned after {} ms, threshold is {} ms",
+                entry.operation,
+                duration_to_ms(held),
+                duration_to_ms(self.threshold)
+            );
+        }
+
+        let mut operations = lock(&self.operations);
+        let name = if operations.len() >= MAX_TRACKED_OPERATIONS && !operations.contains_key(&entry.operation) {
+            OTHER_OPERATIONS.to_string()
+        } else {
+            entry.operation
+        };
+        let operation = operations.entry(name).or_insert_with(OperationEntry::default);
+        operation.checkouts += 1;
+        operation.total += held;
+        operation.max = operation.max.max(held);
+        if slow {
+            operation.slow_checkouts += 1;
+        }
+    }
+
+    /// Logs connections held longer than the threshold, returns the number of newly found ones
+    pub fn check_long_checkouts(&self) -> usize {
+        self.check_long_checkouts_at(Instant::now())
+    }
+
+    fn check_long_checkouts_at(&self, now: Instant) -> usize {
+        let mut found = 0;
+        for entry in lock(&self.active).values_mut() {
+            let held = now.duration_since(entry.started_at);
+            if entry.reported || held <= self.threshold {
+                continue;
+            }
+
+            error!(
+                "Connection checked out for {} is held for {} ms, likely a leak or a runaway query",
+                entry.operation,
+                duration_to_ms(held)
+            );
+            entry.reported = true;
+            found += 1;
+        }
+
+        found
+    }
+
+    pub fn report(&self, pool: PoolState) -> ConnectionsReport {
+        self.report_at(Instant::now(), pool)
+    }
+
+    fn report_at(&self, now: Instant, pool: PoolState) -> ConnectionsReport {
+        let mut active = lock(&self.active)
+            .values()
+            .map(|entry| ActiveCheckout {
+                operation: entry.operation.clone(),
+                held_ms: duration_to_ms(now.duration_since(entry.started_at)),
+            })
+            .collect::<Vec<_>>();
+        active.sort_by(|a, b| b.held_ms.cmp(&a.held_ms));
+
+        let mut operations = lock(&self.operations)
+            .iter()
+            .map(|(operation, entry)| OperationCheckouts {
+                operation: operation.clone(),
+                checkouts: entry.checkouts,
+                avg_ms: duration_to_ms(entry.total) / entry.checkouts.max(1),
+                max_ms: duration_to_ms(entry.max),
+                slow_checkouts: entry.slow_checkouts,
+            })
+            .collect::<Vec<_>>();
+        operations.sort_by(|a, b| a.operation.cmp(&b.operation));
+
+        ConnectionsReport {
+            threshold_ms: duration_to_ms(self.threshold),
+            pool,
+            active,
+            operations,
+            read_replicas: None,
+        }
+    }
+}
+
+/// Tasks of a CPU pool: the ones waiting for a free thread and the running ones
+#[derive(Default)]
+pub struct TaskTracker {
+    queued: AtomicUsize,
+    running: AtomicUsize,
+    queue_waits: Mutex<WaitEntry>,
+}
+
+/// Task waiting for a thread, it is not queued anymore when started or dropped
+pub struct QueuedTask {
+    tracker: Arc<TaskTracker>,
+    queued_at: Instant,
+}
+
+impl QueuedTask {
+    pub fn start(self) -> RunningTask {
+        self.start_at(Instant::now())
+    }
+
+    fn start_at(self, at: Instant) -> RunningTask {
+        lock(&self.tracker.queue_waits).add(at.duration_since(self.queued_at));
+        self.tracker.running.fetch_add(1, Ordering::SeqCst);
+
+        RunningTask {
+            tracker: self.tracker.clone(),
+        }
+    }
+}
+
+impl Drop for QueuedTask {
+    fn drop(&mut self) {
+        self.tracker.queued.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Task running on a thread of the pool, finished when dropped
+pub struct RunningTask {
+    tracker: Arc<TaskTracker>,
+}
+
+impl Drop for RunningTask {
+    fn drop(&mut self) {
+        self.tracker.running.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct CpuPoolStats {
+    pub threads: usize,
+    /// Tasks waiting for a free thread
+    pub queued: usize,
+    pub running: usize,
+    pub queue_wait: WaitTimes,
+}
+
+impl TaskTracker {
+    /// Starts tracking of a task submitted to the pool
+    pub fn queue(tracker: &Arc<TaskTracker>) -> QueuedTask {
+        tracker.queued.fetch_add(1, Ordering::SeqCst);
+
+        QueuedTask {
+            tracker: tracker.clone(),
+            queued_at: Instant::now(),
+        }
+    }
+
+    pub fn stats(&self, threads: usize) -> CpuPoolStats {
+        CpuPoolStats {
+            threads,
+            queued: self.queued.load(Ordering::SeqCst),
+            running: self.running.load(Ordering::SeqCst),
+            queue_wait: lock(&self.queue_waits).report(),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct DbPoolStats {
+    pub max_size: u32,
+    pub connections: u32,
+    pub idle_connections: u32,
+    pub checkout_wait: WaitTimes,
+}
+
+/// Live usage of the pools, reported by `GET /admin/stats` together with the counters of the background cleanup
+/// and of the calls to the carriers
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct PoolsStats {
+    pub db_pool: DbPoolStats,
+    pub cpu_pool: CpuPoolStats,
+    pub admin_cpu_pool: CpuPoolStats,
+    pub delivery_quotes_cleanup: QuotesCleanupStats,
+    pub carriers: Vec<CarrierCallStats>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POOL: PoolState = PoolState {
+        connections: 10,
+        idle_connections: 9,
+    };
+
+    fn tracker() -> ConnectionTracker {
+        ConnectionTracker::new(Some(config::Diagnostics {
+            checkout_threshold_ms: 1000,
+            check_interval_sec: 10,
+        }))
+    }
+
+    #[test]
+    fn operation_name_replaces_ids() {
+        assert_eq!(operation_name("GET", "/companies/12/packages"), "GET /companies/:id/packages");
+        assert_eq!(operation_name("POST", "/delivery_prices/batch"), "POST /delivery_prices/batch");
+    }
+
+    #[test]
+    fn long_checkout_is_reported_once_while_held() {
+        let tracker = tracker();
+        let now = Instant::now();
+
+        let _checkout = tracker.checkout_at(now, "GET /companies".to_string());
+
+        assert_eq!(tracker.check_long_checkouts_at(now + Duration::from_millis(500)), 0);
+        assert_eq!(tracker.check_long_checkouts_at(now + Duration::from_millis(1500)), 1);
+        assert_eq!(tracker.check_long_checkouts_at(now + Duration::from_millis(2500)), 0);
+
+        let report = tracker.report_at(now + Duration::from_millis(2500), POOL);
+        assert_eq!(
+            report.active,
+            vec![ActiveCheckout {
+                operation: "GET /companies".to_string(),
+                held_ms: 2500,
+            }]
+        );
+    }
+
+    #[test]
+    fn finished_checkouts_are_aggregated_per_operation() {
+        let tracker = tracker();
+        let now = Instant::now();
+
+        let first = tracker.checkout_at(now, "GET /zones".to_string());
+        tracker.finish(first.id, now + Duration::from_millis(200));
+        let second = tracker.checkout_at(now, "GET /zones".to_string());
+        tracker.finish(second.id, now + Duration::from_millis(1800));
+
+        let report = tracker.report_at(now, POOL);
+        assert!(report.active.is_empty());
+        assert_eq!(
+            report.operations,
+            vec![OperationCheckouts {
+                operation: "GET /zones".to_string(),
+                checkouts: 2,
+                avg_ms: 1000,
+                max_ms: 1800,
+                slow_checkouts: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn operations_over_limit_are_counted_together() {
+        let tracker = tracker();
+        let now = Instant::now();
+
+        for i in 0..MAX_TRACKED_OPERATIONS + 2 {
+            let checkout = tracker.checkout_at(now, format!("GET /countries/C{}", i));
+            tracker.finish(checkout.id, now + Duration::from_millis(10));
+        }
+        let checkout = tracker.checkout_at(now, "GET /countries/C0".to_string());
+        tracker.finish(checkout.id, now + Duration::from_millis(10));
+
+        let report = tracker.report_at(now, POOL);
+        assert_eq!(report.operations.len(), MAX_TRACKED_OPERATIONS + 1);
+        let checkouts = |name: &str| {
+            report
+                .operations
+                .iter()
+                .find(|operation| operation.operation == name)
+                .map(|operation| operation.checkouts)
+        };
+        assert_eq!(checkouts("GET /countries/C0"), Some(2));
+        assert_eq!(checkouts(OTHER_OPERATIONS), Some(2));
+    }
+
+    #[test]
+    fn tasks_are_tracked_from_queue_to_finish() {
+        let tracker = Arc::new(TaskTracker::default());
+
+        let first = TaskTracker::queue(&tracker);
+        let queued_at = first.queued_at;
+        let second = TaskTracker::queue(&tracker);
+        assert_eq!(tracker.stats(4).queued, 2);
+
+        let running = first.start_at(queued_at + Duration::from_millis(300));
+        drop(second);
+        let stats = tracker.stats(4);
+        assert_eq!((stats.queued, stats.running), (0, 1));
+        assert_eq!(
+            stats.queue_wait,
+            WaitTimes {
+                waits: 1,
+                avg_ms: 300,
+                max_ms: 300,
+            }
+        );
+
+        drop(running);
+        assert_eq!(tracker.stats(4).running, 0);
+    }
+}
@@ -14,8 +14,16 @@ pub enum Error {
     Parse,
     #[fail(display = "Validation error")]
     Validate(ValidationErrors),
+    #[fail(display = "Unprocessable entity")]
+    Unprocessable(ValidationErrors),
     #[fail(display = "Server is refusing to fullfil the request")]
     Forbidden,
+    #[fail(display = "Invalid credentials")]
+    Unauthorized,
+    #[fail(display = "Conflict with the current state of the resource")]
+    Conflict(ValidationErrors),
+    #[fail(display = "Resource is no longer available")]
+    Gone(ValidationErrors),
     #[fail(display = "R2D2 connection error")]
     Connection,
     #[fail(display = "Http client error")]
@@ -30,8 +38,12 @@ impl Codeable for Error {
             Error::NotFound => StatusCode::NotFound,
             Error::Parse => StatusCode::UnprocessableEntity,
             Error::Validate(_) => StatusCode::BadRequest,
+            Error::Unprocessable(_) => StatusCode::UnprocessableEntity,
             Error::HttpClient | Error::Connection | Error::Internal => StatusCode::InternalServerError,
             Error::Forbidden => StatusCode::Forbidden,
+            Error::Unauthorized => StatusCode::Unauthorized,
+            Error::Conflict(_) => StatusCode::Conflict,
+            Error::Gone(_) => StatusCode::Gone,
         }
     }
 }
@@ -39,7 +51,9 @@ impl Codeable for Error {
 impl PayloadCarrier for Error {
     fn payload(&self) -> Option<serde_json::Value> {
         match *self {
-            Error::Validate(ref e) => serde_json::to_value(e.clone()).ok(),
+            Error::Validate(ref e) | Error::Unprocessable(ref e) | Error::Conflict(ref e) | Error::Gone(ref e) => {
+                serde_json::to_value(e.clone()).ok()
+            }
             _ => None,
         }
     }
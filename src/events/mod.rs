@@ -0,0 +1,353 @@
+//! Events is a module responsible for publishing changes of the shipping to the message bus,
+//! so that other services, like orders, react to them without polling the REST API.
+//! Events are published through the HTTP interface of the broker, the RabbitMQ management API
+//! or the Kafka REST proxy, after the change is committed.
+//!
+//! Events are written to the outbox in the transaction of the change, so the event is committed
+//! together with it. Events that failed to publish after the commit are published again by the relay,
+//! so every event is published at least once and consumers must tolerate duplicates
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use failure::Error as FailureError;
+use failure::Fail;
+use futures::future;
+use futures::{Future, Stream};
+use futures_cpupool::CpuPool;
+use hyper::header::{Authorization, Basic};
+use hyper::Method;
+use r2d2::{ManageConnection, Pool};
+use serde_json;
+use tokio_core::reactor::{Handle, Interval};
+
+use stq_http::client::ClientHandle;
+use stq_types::{Alpha3, BaseProductId, CompanyId, CompanyPackageId};
+
+use config::{Broker, Events};
+use errors::Error;
+use models::OutboxEvent;
+use repos::{DbConnection, EventOutboxRepo, RepoResult, ReposFactory};
+use request_context::RequestContext;
+
+/// Interval of the relay if it is not configured
+pub const DEFAULT_RELAY_INTERVAL_SEC: u64 = 60;
+/// Max number of events the relay publishes at once
+const RELAY_BATCH_SIZE: i64 = 100;
+
+/// Events publisher Future
+pub type EventFuture<T> = Box<dyn Future<Item = T, Error = FailureError>>;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DeliveryEvent {
+    CompanyCreated {
+        company_id: CompanyId,
+        name: String,
+        label: String,
+    },
+    /// Rates of the company package from the country are replaced, `deliveries_to` are the countries of the new rates,
+    /// `removed_deliveries_to` are the countries the old rates delivered to and the new ones don't
+    ShippingRatesReplaced {
+        company_package_id: CompanyPackageId,
+        delivery_from: Alpha3,
+        deliveries_to: Vec<Alpha3>,
+        #[serde(default)]
+        removed_deliveries_to: Vec<Alpha3>,
+    },
+    /// Shipping of the base product is changed, `company_package_ids` are the packages the product is shipped with
+    ProductShippingUpdated {
+        base_product_id: BaseProductId,
+        company_package_ids: Vec<CompanyPackageId>,
+    },
+}
+
+impl DeliveryEvent {
+    /// Name of the event, RabbitMQ routes the events by it
+    pub fn name(&self) -> &'static str {
+        match *self {
+            DeliveryEvent::CompanyCreated { .. } => "company_created",
+            DeliveryEvent::ShippingRatesReplaced { .. } => "shipping_rates_replaced",
+            DeliveryEvent::ProductShippingUpdated { .. } => "product_shipping_updated",
+        }
+    }
+
+    /// Key of the changed entity, Kafka keeps the events with the same key in order
+    pub fn key(&self) -> String {
+        match *self {
+            DeliveryEvent::CompanyCreated { company_id, .. } => format!("company:{}", company_id),
+            DeliveryEvent::ShippingRatesReplaced { company_package_id, .. } => format!("company_package:{}", company_package_id),
+            DeliveryEvent::ProductShippingUpdated { base_product_id, .. } => format!("base_product:{}", base_product_id),
+        }
+    }
+}
+
+pub trait EventsPublisher {
+    /// Publishes the event, the request is traced with the context of the incoming one
+    fn publish(&self, event: DeliveryEvent, request_context: &RequestContext) -> EventFuture<()>;
+}
+
+/// Path and body of the request publishing the event to `destination`,
+/// which is the exchange for RabbitMQ and the topic for Kafka
+pub fn publish_request(broker: Broker, destination: &str, event: &DeliveryEvent) -> Result<(String, String), FailureError> {
+    let body = match broker {
+        Broker::RabbitMq => json!({
+            "properties": { "content_type": "application/json", "delivery_mode": 2 },
+            "routing_key": event.name(),
+            "payload": serde_json::to_string(event)?,
+            "payload_encoding": "string",
+        }),
+        Broker::Kafka => json!({
+            "records": [{ "key": event.key(), "value": event }],
+        }),
+    };
+    let path = match broker {
+        Broker::RabbitMq => format!("/{}/publish", destination),
+        Broker::Kafka => format!("/topics/{}", destination),
+    };
+
+    Ok((path, serde_json::to_string(&body)?))
+}
+
+/// Publishes events through the HTTP interface of the broker
+pub struct HttpEventsPublisher {
+    client_handle: ClientHandle,
+    settings: Events,
+}
+
+impl HttpEventsPublisher {
+    pub fn new(client_handle: ClientHandle, settings: Events) -> Self {
+        HttpEventsPublisher { client_handle, settings }
+    }
+}
+
+impl EventsPublisher for HttpEventsPublisher {
+    fn publish(&self, event: DeliveryEvent, request_context: &RequestContext) -> EventFuture<()> {
+        info!(
+            "{}",
+            request_context.log_line_with("Publishing event", json!({ "event": event.name(), "key": event.key() }))
+        );
+
+        let (path, body) = match publish_request(self.settings.broker, &self.settings.destination, &event) {
+            Ok(request) => request,
+            Err(e) => return Box::new(future::err(e.context(Error::Parse).into())),
+        };
+        let url = format!("{}{}", self.settings.url, path);
+
+        let mut headers = request_context.headers();
+        if self.settings.broker == Broker::Kafka {
+            headers.set_raw("Content-Type", "application/vnd.kafka.json.v2+json");
+        }
+        if let Some(username) = self.settings.username.clone() {
+            headers.set(Authorization(Basic {
+                username,
+                password: self.settings.password.clone(),
+            }));
+        }
+
+        Box::new(
+            self.client_handle
+                .request_json::<serde_json::Value>(Method::Post, url, Some(body), Some(headers))
+                .map_err(|e| e.context(Error::HttpClient).into())
+                .map(|_| ()),
+        )
+    }
+}
+
+/// Writes the events to the outbox in the transaction of the change, if the message bus is configured.
+/// Returns the stored events, they are published after the commit
+pub fn store(outbox_repo: &dyn EventOutboxRepo, enabled: bool, events: Vec<DeliveryEvent>) -> RepoResult<Vec<OutboxEvent>> {
+    if !enabled {
+        return Ok(vec![]);
+    }
+
+    outbox_repo.add(events)
+}
+
+/// Publishes the events from the outbox, returns ids of the published ones. Changes are already committed,
+/// so failures are only logged and never fail the request, the relay publishes the events again
+pub fn publish(
+    publisher: &dyn EventsPublisher,
+    outbox_events: Vec<OutboxEvent>,
+    request_context: &RequestContext,
+) -> EventFuture<Vec<i32>> {
+    let published = outbox_events
+        .into_iter()
+        .map(|outbox_event| {
+            let id = outbox_event.id;
+            let name = outbox_event.event.name();
+            let request_context = request_context.clone();
+            publisher
+                .publish(outbox_event.event.into_inner(), &request_context)
+                .then(move |res| match res {
+                    Ok(_) => Ok(Some(id)),
+                    Err(e) => {
+                        error!(
+                            "{}",
+                            request_context.log_line_with(
+                                "Failed to publish event",
+                                json!({ "event": name, "outbox_id": id, "error": e.to_string() })
+                            )
+                        );
+                        Ok(None)
+                    }
+                })
+        })
+        .collect::<Vec<_>>();
+
+    Box::new(future::join_all(published).map(|ids| ids.into_iter().flatten().collect()))
+}
+
+/// Marks the published events, attempts of the rest are recorded
+pub fn record_published(outbox_repo: &dyn EventOutboxRepo, ids: &[i32], published: &[i32]) -> RepoResult<()> {
+    let published = published.iter().cloned().collect::<HashSet<_>>();
+    for id in ids {
+        if published.contains(id) {
+            outbox_repo.mark_published(*id)?;
+        } else {
+            outbox_repo.record_failure(*id)?;
+        }
+    }
+    Ok(())
+}
+
+/// Publishes the events left unpublished in the outbox every `interval`. Only the events older than
+/// `interval` are picked up, the newer ones are still being published by the requests that stored them
+pub fn schedule<T, M, F>(
+    handle: &Handle,
+    cpu_pool: CpuPool,
+    db_pool: Pool<M>,
+    repo_factory: F,
+    publisher: Arc<dyn EventsPublisher>,
+    interval: Duration,
+) where
+    T: DbConnection,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+{
+    let ticks = Interval::new(interval, handle).expect("Failed to create interval");
+
+    handle.spawn(
+        ticks
+            .map_err(|e| error!("Event outbox relay schedule error: {}", e))
+            .for_each(move |_| {
+                let (list_db_pool, list_repo_factory) = (db_pool.clone(), repo_factory.clone());
+                let unpublished = cpu_pool.spawn_fn(move || {
+                    list_db_pool.get().map_err(FailureError::from).and_then(|conn| {
+                        let outbox_repo = list_repo_factory.create_event_outbox_repo_with_sys_acl(&*conn);
+                        outbox_repo.list_unpublished(SystemTime::now() - interval, RELAY_BATCH_SIZE)
+                    })
+                });
+
+                let publisher = publisher.clone();
+                let request_context = RequestContext::new("event-outbox-relay".to_string(), None, "event outbox relay".to_string());
+                let published = unpublished.and_then(move |outbox_events| {
+                    let ids = outbox_events.iter().map(|outbox_event| outbox_event.id).collect::<Vec<_>>();
+                    publish(&*publisher, outbox_events, &request_context).map(move |published| (ids, published))
+                });
+
+                let (cpu_pool, db_pool, repo_factory) = (cpu_pool.clone(), db_pool.clone(), repo_factory.clone());
+                published
+                    .and_then(move |(ids, published)| {
+                        cpu_pool.spawn_fn(move || {
+                            db_pool.get().map_err(FailureError::from).and_then(|conn| {
+                                let outbox_repo = repo_factory.create_event_outbox_repo_with_sys_acl(&*conn);
+                                record_published(&*outbox_repo, &ids, &published).map(|_| (ids.len(), published.len()))
+                            })
+                        })
+                    })
+                    .then(|res| {
+                        match res {
+                            Ok((0, _)) => {}
+                            Ok((relayed, published)) => {
+                                info!("Event outbox relay finished, published events: {} of {}", published, relayed)
+                            }
+                            Err(e) => error!("Event outbox relay failed: {}", e),
+                        }
+                        Ok(())
+                    })
+            }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use repos::repo_factory::tests::*;
+
+    use super::*;
+
+    fn event() -> DeliveryEvent {
+        DeliveryEvent::ShippingRatesReplaced {
+            company_package_id: CompanyPackageId(1),
+            delivery_from: Alpha3("RUS".to_string()),
+            deliveries_to: vec![Alpha3("USA".to_string())],
+            removed_deliveries_to: vec![Alpha3("GBR".to_string())],
+        }
+    }
+
+    #[test]
+    fn events_are_tagged_with_their_names() {
+        assert_eq!(
+            serde_json::to_value(event()).unwrap(),
+            json!({
+                "type": "shipping_rates_replaced",
+                "company_package_id": 1,
+                "delivery_from": "RUS",
+                "deliveries_to": ["USA"],
+                "removed_deliveries_to": ["GBR"],
+            })
+        );
+    }
+
+    #[test]
+    fn rabbitmq_routes_events_by_name() {
+        let (path, body) = publish_request(Broker::RabbitMq, "delivery", &event()).unwrap();
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+
+        assert_eq!(path, "/delivery/publish".to_string());
+        assert_eq!(body["routing_key"], json!("shipping_rates_replaced"));
+        assert_eq!(
+            serde_json::from_str::<DeliveryEvent>(body["payload"].as_str().unwrap()).unwrap(),
+            event()
+        );
+    }
+
+    #[test]
+    fn kafka_keys_events_by_entity() {
+        let (path, body) = publish_request(Broker::Kafka, "delivery-events", &event()).unwrap();
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+
+        assert_eq!(path, "/topics/delivery-events".to_string());
+        assert_eq!(body["records"][0]["key"], json!("company_package:1"));
+        assert_eq!(body["records"][0]["value"]["type"], json!("shipping_rates_replaced"));
+    }
+
+    #[test]
+    fn events_are_stored_only_if_message_bus_is_configured() {
+        let outbox_repo = EventOutboxRepoMock {
+            user_id: Some(MOCK_USER_ID),
+        };
+
+        assert!(store(&outbox_repo, false, vec![event()]).unwrap().is_empty());
+
+        let stored = store(&outbox_repo, true, vec![event()]).unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(*stored[0].event, event());
+    }
+
+    #[test]
+    fn removed_destinations_default_to_empty() {
+        let event = serde_json::from_value::<DeliveryEvent>(json!({
+            "type": "shipping_rates_replaced",
+            "company_package_id": 1,
+            "delivery_from": "RUS",
+            "deliveries_to": ["USA"],
+        }))
+        .unwrap();
+
+        match event {
+            DeliveryEvent::ShippingRatesReplaced { removed_deliveries_to, .. } => assert!(removed_deliveries_to.is_empty()),
+            _ => panic!("Unexpected event {:?}", event),
+        }
+    }
+}
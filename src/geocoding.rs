@@ -0,0 +1,137 @@
+//! Geocoding is a module responsible for resolving coordinates of user addresses,
+//! they are used to find pickup points near the buyer
+use failure::Error as FailureError;
+use failure::Fail;
+use futures::future;
+use futures::Future;
+use hyper::Method;
+use serde_json;
+
+use stq_http::client::ClientHandle;
+
+use errors::Error;
+use models::{Coordinates, UserAddress};
+use request_context::RequestContext;
+
+/// Geocoder Future
+pub type GeocoderFuture<T> = Box<dyn Future<Item = T, Error = FailureError>>;
+
+#[derive(Clone, Debug, Serialize)]
+struct GeocodeRequest {
+    query: String,
+    key: String,
+}
+
+pub trait Geocoder {
+    /// Returns coordinates of the address, `None` if the provider could not find it.
+    /// The request is traced with the context of the incoming one
+    fn geocode(&self, query: String, request_context: &RequestContext) -> GeocoderFuture<Option<Coordinates>>;
+}
+
+/// Requests coordinates from the geocoding provider
+pub struct HttpGeocoder {
+    client_handle: ClientHandle,
+    url: String,
+    api_key: String,
+}
+
+impl HttpGeocoder {
+    pub fn new(client_handle: ClientHandle, url: String, api_key: String) -> Self {
+        HttpGeocoder {
+            client_handle,
+            url,
+            api_key,
+        }
+    }
+}
+
+impl Geocoder for HttpGeocoder {
+    fn geocode(&self, query: String, request_context: &RequestContext) -> GeocoderFuture<Option<Coordinates>> {
+        let request = GeocodeRequest {
+            query,
+            key: self.api_key.clone(),
+        };
+        let body = match serde_json::to_string(&request) {
+            Ok(body) => body,
+            Err(e) => return Box::new(future::err(e.context(Error::Parse).into())),
+        };
+        let url = format!("{}/geocode", self.url);
+
+        Box::new(
+            self.client_handle
+                .request_json::<Option<Coordinates>>(Method::Post, url, Some(body), Some(request_context.headers()))
+                .map_err(|e| e.context(Error::HttpClient).into()),
+        )
+    }
+}
+
+/// Address as a single line for the geocoding provider. Formatted address is used if the address has one,
+/// otherwise the line is made of the address components from the most to the least specific
+pub fn geocoding_query(address: &UserAddress) -> String {
+    if let Some(ref formatted) = address.address {
+        if !formatted.is_empty() {
+            return format!("{}, {}, {}", formatted, address.postal_code, address.country);
+        }
+    }
+
+    let components = vec![
+        address.street_number.as_ref(),
+        address.route.as_ref(),
+        address.locality.as_ref(),
+        address.administrative_area_level_2.as_ref(),
+        address.administrative_area_level_1.as_ref(),
+        Some(&address.postal_code),
+        Some(&address.country),
+    ];
+
+    components
+        .into_iter()
+        .filter_map(|component| component.map(String::as_str))
+        .filter(|component| !component.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use stq_types::UserId;
+
+    use super::*;
+
+    fn address(formatted: Option<&str>) -> UserAddress {
+        UserAddress {
+            id: 1,
+            user_id: UserId(1),
+            administrative_area_level_1: Some("Moscow".to_string()),
+            administrative_area_level_2: None,
+            country: "Russia".to_string(),
+            locality: Some("Moscow".to_string()),
+            political: None,
+            postal_code: "125009".to_string(),
+            route: Some("Tverskaya st.".to_string()),
+            street_number: Some("1".to_string()),
+            address: formatted.map(|formatted| formatted.to_string()),
+            is_priority: false,
+            created_at: SystemTime::now(),
+            updated_at: SystemTime::now(),
+            country_code: Some("RUS".to_string()),
+            latitude: None,
+            longitude: None,
+            subdivision: None,
+        }
+    }
+
+    #[test]
+    fn query_is_made_of_address_components() {
+        assert_eq!(
+            geocoding_query(&address(None)),
+            "1, Tverskaya st., Moscow, Moscow, 125009, Russia".to_string()
+        );
+        assert_eq!(
+            geocoding_query(&address(Some("Tverskaya st. 1, Moscow"))),
+            "Tverskaya st. 1, Moscow, 125009, Russia".to_string()
+        );
+    }
+}
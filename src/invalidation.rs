@@ -0,0 +1,163 @@
+//! Invalidation channel between the replicas.
+//!
+//! Every replica keeps the versions of the content served with `ETag` and the permissions matrix in memory.
+//! A replica changing the content publishes the invalidation keys to the Redis channel, and every replica
+//! subscribed to it drops its copy. The permissions matrix is changed outside of the service, so its key is published
+//! by hand after `role_permissions` is edited: `PUBLISH delivery:invalidation permissions`.
+//! Without Redis the keys are applied to the replica itself only, other replicas rely on the TTLs of their copies.
+use std::fmt;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+use failure::Error as FailureError;
+use r2d2::Pool;
+use r2d2_redis::redis;
+use r2d2_redis::RedisConnectionManager;
+
+use content_versions::VersionedContent;
+
+/// Redis channel the invalidation keys are published to
+pub const INVALIDATION_CHANNEL: &str = "delivery:invalidation";
+
+/// Delay before the subscription is restored after the connection to Redis is lost
+const RESUBSCRIBE_DELAY_SEC: u64 = 5;
+
+/// Data kept in memory by every replica
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InvalidationKey {
+    /// Version of the content, `content:<content>`
+    Content(VersionedContent),
+    /// Permissions matrix, `permissions`
+    Permissions,
+}
+
+impl InvalidationKey {
+    /// Every key, they are all applied once subscribed, since the keys published before are not delivered
+    pub fn all() -> Vec<Self> {
+        vec![
+            InvalidationKey::Content(VersionedContent::Countries),
+            InvalidationKey::Content(VersionedContent::CountriesFlatten),
+            InvalidationKey::Content(VersionedContent::Packages),
+            InvalidationKey::Permissions,
+        ]
+    }
+}
+
+impl fmt::Display for InvalidationKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InvalidationKey::Content(VersionedContent::Countries) => write!(f, "content:countries"),
+            InvalidationKey::Content(VersionedContent::CountriesFlatten) => write!(f, "content:countries_flatten"),
+            InvalidationKey::Content(VersionedContent::Packages) => write!(f, "content:packages"),
+            InvalidationKey::Permissions => write!(f, "permissions"),
+        }
+    }
+}
+
+impl FromStr for InvalidationKey {
+    type Err = FailureError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::all()
+            .into_iter()
+            .find(|key| key.to_string() == s)
+            .ok_or_else(|| format_err!("Unknown invalidation key '{}'", s))
+    }
+}
+
+/// Announces the changed data to every replica
+pub trait InvalidationPublisher: Send + Sync {
+    fn publish(&self, key: InvalidationKey);
+}
+
+pub struct RedisInvalidationPublisher {
+    pool: Pool<RedisConnectionManager>,
+}
+
+impl RedisInvalidationPublisher {
+    pub fn new(pool: Pool<RedisConnectionManager>) -> Self {
+        Self { pool }
+    }
+}
+
+impl InvalidationPublisher for RedisInvalidationPublisher {
+    fn publish(&self, key: InvalidationKey) {
+        debug!("Publishing invalidation of {}", key);
+
+        let published = self.pool.get().map_err(FailureError::from).and_then(|conn| {
+            redis::cmd("PUBLISH")
+                .arg(INVALIDATION_CHANNEL)
+                .arg(key.to_string())
+                .query::<i64>(&*conn)
+                .map_err(FailureError::from)
+        });
+        if let Err(e) = published {
+            error!(
+                "Failed to publish invalidation of {}, other replicas keep it until it expires: {}",
+                key, e
+            );
+        }
+    }
+}
+
+/// Applies the keys published by any replica, on a thread of its own since the subscription blocks.
+/// The subscription is restored when the connection is lost
+pub fn spawn_subscriber<F>(redis_url: String, apply: F)
+where
+    F: Fn(InvalidationKey) + Send + 'static,
+{
+    let spawned = thread::Builder::new().name("invalidation".to_string()).spawn(move || loop {
+        if let Err(e) = subscribe(&redis_url, &apply) {
+            error!(
+                "Invalidation channel is lost, subscribing again in {} sec: {}",
+                RESUBSCRIBE_DELAY_SEC, e
+            );
+        }
+        thread::sleep(Duration::from_secs(RESUBSCRIBE_DELAY_SEC));
+    });
+
+    if let Err(e) = spawned {
+        error!("Failed to start invalidation subscriber: {}", e);
+    }
+}
+
+fn subscribe<F: Fn(InvalidationKey)>(redis_url: &str, apply: &F) -> Result<(), FailureError> {
+    let client = redis::Client::open(redis_url)?;
+    let mut pubsub = client.get_pubsub()?;
+    pubsub.subscribe(INVALIDATION_CHANNEL)?;
+
+    // keys published while the replica was not subscribed are lost
+    for key in InvalidationKey::all() {
+        apply(key);
+    }
+
+    loop {
+        let message = pubsub.get_message()?;
+        let payload: String = message.get_payload()?;
+        match payload.parse::<InvalidationKey>() {
+            Ok(key) => {
+                debug!("Applying invalidation of {}", key);
+                apply(key);
+            }
+            Err(e) => warn!("Invalidation is ignored: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_parsed_from_their_names() {
+        for key in InvalidationKey::all() {
+            assert_eq!(key.to_string().parse::<InvalidationKey>().unwrap(), key);
+        }
+        assert_eq!(
+            "content:packages".parse::<InvalidationKey>().unwrap(),
+            InvalidationKey::Content(VersionedContent::Packages)
+        );
+        assert!("content:companies".parse::<InvalidationKey>().is_err());
+    }
+}
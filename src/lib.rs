@@ -5,7 +5,10 @@ extern crate config as config_crate;
 #[macro_use]
 extern crate diesel;
 #[macro_use]
+extern crate diesel_migrations;
+#[macro_use]
 extern crate failure;
+extern crate flate2;
 extern crate futures;
 extern crate futures_cpupool;
 extern crate hyper;
@@ -21,6 +24,8 @@ extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate mime;
+extern crate openssl;
+#[macro_use]
 extern crate serde_json;
 extern crate sha3;
 extern crate tokio_core;
@@ -41,18 +46,42 @@ extern crate stq_static_resources;
 extern crate stq_diesel_macro_derive;
 extern crate stq_types;
 
+pub mod access_log;
+pub mod backfills;
+pub mod cache;
+pub mod carriers;
+pub mod clock;
 pub mod config;
+pub mod content_versions;
 pub mod controller;
+pub mod credentials;
+pub mod country_dependents;
+pub mod coverage_regressions;
+pub mod diagnostics;
 pub mod errors;
+pub mod events;
 pub mod extras;
+pub mod geocoding;
+pub mod invalidation;
 #[macro_use]
 pub mod macros;
+pub mod migrations;
 pub mod models;
+pub mod notifications;
+pub mod quotes_cleanup;
+pub mod rates_integrity;
+pub mod replicas;
 pub mod repos;
+pub mod request_context;
+pub mod request_timeout;
+pub mod response_caching;
+pub mod response_compression;
 #[rustfmt::skip]
 pub mod schema;
 pub mod sentry_integration;
 pub mod services;
+pub mod shutdown;
+pub mod slo;
 
 use std::process;
 use std::sync::Arc;
@@ -62,22 +91,41 @@ use diesel::pg::PgConnection;
 use diesel::r2d2::ConnectionManager;
 use futures::future;
 use futures::prelude::*;
+use futures::sync::oneshot;
 use futures_cpupool::CpuPool;
 use hyper::server::Http;
 use r2d2_redis::RedisConnectionManager;
 use stq_cache::cache::{redis::RedisCache, Cache, NullCache, TypedCache};
 use stq_http::controller::Application;
-use tokio_core::reactor::Core;
+use tokio_core::reactor::{Core, Interval};
 
+use backfills::BackfillSettings;
+use cache::{ShippingRatesCache, ShippingRatesCacheImpl};
+use carriers::{CarrierRatesCacheImpl, CarriersClient, HttpCarriersClient, ResilienceSettings, ResilientCalls};
+use content_versions::{ConditionalGet, ContentVersions};
+use credentials::CredentialsCipher;
 use controller::context::StaticContext;
+use controller::cors::{Cors, CorsPolicy};
+use events::{EventsPublisher, HttpEventsPublisher};
+use geocoding::{Geocoder, HttpGeocoder};
+use invalidation::{InvalidationKey, InvalidationPublisher, RedisInvalidationPublisher};
+use notifications::{HttpNotificationsClient, NotificationsClient};
+use rates_integrity::RatesIntegritySettings;
+use replicas::{Replica, ReplicaRouter, StatementTimeout};
 use repos::acl::RolesCacheImpl;
 use repos::countries::CountryCacheImpl;
 use repos::repo_factory::ReposFactoryImpl;
+use request_timeout::RequestTimeout;
+use response_caching::{ResponseCaching, ResponseCachingPolicy};
+use response_compression::ResponseCompression;
+use shutdown::{InFlightRequests, TrackInFlight};
 
 /// Starts new web service from provided `Config`
 pub fn start_server<F: FnOnce() + 'static>(config: config::Config, port: Option<i32>, callback: F) {
     let thread_count = config.server.thread_count;
     let cpu_pool = CpuPool::new(thread_count);
+    let admin_thread_count = config.server.admin_thread_count;
+    let admin_cpu_pool = CpuPool::new(admin_thread_count);
 
     // Prepare reactor
     let mut core = Core::new().expect("Unexpected error creating event loop core");
@@ -86,17 +134,60 @@ pub fn start_server<F: FnOnce() + 'static>(config: config::Config, port: Option<
     // Prepare database pool
     let database_url: String = config.server.database.parse().expect("Database URL must be set in configuration");
     let db_manager = ConnectionManager::<PgConnection>::new(database_url);
-    let db_pool = r2d2::Pool::builder()
+    let mut db_pool_builder = r2d2::Pool::builder();
+    if let Some(db_idle_timeout_sec) = config.server.db_idle_timeout_sec {
+        db_pool_builder = db_pool_builder.idle_timeout(Some(Duration::from_secs(db_idle_timeout_sec)));
+    }
+    if let Some(db_pool_size) = config.server.db_pool_size {
+        db_pool_builder = db_pool_builder.max_size(db_pool_size);
+    }
+    if let Some(db_connection_timeout_ms) = config.server.db_connection_timeout_ms {
+        db_pool_builder = db_pool_builder.connection_timeout(Duration::from_millis(db_connection_timeout_ms));
+    }
+    let db_pool = db_pool_builder
         .build(db_manager)
         .expect("Failed to create DB connection pool");
 
+    // Replica pools are not checked on start, a replica is used only after it passes a probe
+    let read_replicas = match config.read_replicas.as_ref() {
+        Some(read_replicas) => {
+            let checkout_timeout = read_replicas.checkout_timeout_ms.unwrap_or(replicas::DEFAULT_CHECKOUT_TIMEOUT_MS);
+            let statement_timeout = read_replicas.statement_timeout_ms.unwrap_or(replicas::DEFAULT_STATEMENT_TIMEOUT_MS);
+            let replicas = read_replicas
+                .urls
+                .iter()
+                .map(|url| {
+                    let pool = r2d2::Pool::builder()
+                        .connection_timeout(Duration::from_millis(checkout_timeout))
+                        .connection_customizer(Box::new(StatementTimeout(Duration::from_millis(statement_timeout))))
+                        .build_unchecked(ConnectionManager::<PgConnection>::new(url.clone()));
+                    Replica::new(url, pool)
+                })
+                .collect();
+            ReplicaRouter::new(
+                replicas,
+                Duration::from_millis(read_replicas.max_latency_ms),
+                Duration::from_millis(read_replicas.max_lag_ms.unwrap_or(replicas::DEFAULT_MAX_LAG_MS)),
+            )
+        }
+        None => ReplicaRouter::default(),
+    };
+
     // Prepare server
     let address = {
         let port = port.as_ref().unwrap_or(&config.server.port);
         format!("{}:{}", config.server.host, port).parse().expect("Could not parse address")
     };
 
-    let (country_cache, roles_cache) = match &config.server.redis {
+    let carrier_rates_ttl = Duration::from_secs(
+        config
+            .carriers
+            .as_ref()
+            .map(|carriers| carriers.cache_ttl_sec)
+            .unwrap_or(config.server.cache_ttl_sec),
+    );
+
+    let (country_cache, roles_cache, carrier_rates_cache, shipping_rates_cache, redis_pool) = match &config.server.redis {
         Some(redis_url) => {
             // Prepare Redis pool
             let redis_url: String = redis_url.parse().expect("Redis URL must be set in configuration");
@@ -117,37 +208,247 @@ pub fn start_server<F: FnOnce() + 'static>(config: config::Config, port: Option<
             )) as Box<dyn Cache<_, Error = _> + Send + Sync>;
             let roles_cache = RolesCacheImpl::new(roles_cache_backend);
 
-            (country_cache, roles_cache)
+            let carrier_rates_cache_backend = Box::new(TypedCache::new(
+                RedisCache::new(redis_pool.clone(), "carrier_rates".to_string()).with_ttl(carrier_rates_ttl),
+            )) as Box<dyn Cache<_, Error = _> + Send + Sync>;
+            let carrier_rates_cache = CarrierRatesCacheImpl::new(carrier_rates_cache_backend);
+
+            let shipping_rates_cache = config.shipping_rates_cache.as_ref().map(|shipping_rates_cache| {
+                let shipping_rates_cache_backend = Box::new(TypedCache::new(
+                    RedisCache::new(redis_pool.clone(), "shipping_rates".to_string())
+                        .with_ttl(Duration::from_secs(shipping_rates_cache.ttl_sec)),
+                )) as Box<dyn Cache<_, Error = _> + Send + Sync>;
+                Arc::new(ShippingRatesCacheImpl::new(shipping_rates_cache_backend)) as Arc<dyn ShippingRatesCache>
+            });
+
+            (country_cache, roles_cache, carrier_rates_cache, shipping_rates_cache, Some(redis_pool))
         }
         None => (
             CountryCacheImpl::new(Box::new(NullCache::new()) as Box<_>),
             RolesCacheImpl::new(Box::new(NullCache::new()) as Box<_>),
+            CarrierRatesCacheImpl::new(Box::new(NullCache::new()) as Box<_>),
+            None,
+            None,
         ),
     };
 
     // Repo factory
-    let repo_factory = ReposFactoryImpl::new(country_cache, roles_cache);
+    let credentials_cipher = CredentialsCipher::new(config.carrier_accounts.clone()).expect("Invalid carrier accounts config");
+    let repo_factory = ReposFactoryImpl::new(country_cache, roles_cache, credentials_cipher);
 
     let client = stq_http::client::Client::new(&config.to_http_config(), &handle);
     let client_handle = client.handle();
     let client_stream = client.stream();
     handle.spawn(client_stream.for_each(|_| Ok(())));
 
-    let context = StaticContext::new(db_pool, cpu_pool, client_handle, Arc::new(config), repo_factory);
+    // Carriers client has its own http client, so that its timeout does not affect other requests
+    let carriers_client = config.carriers.clone().map(|carriers| {
+        let http_config = stq_http::client::Config {
+            timeout_duration_ms: carriers.timeout_ms,
+            ..config.to_http_config()
+        };
+        let carriers_http_client = stq_http::client::Client::new(&http_config, &handle);
+        let carriers_client_handle = carriers_http_client.handle();
+        handle.spawn(carriers_http_client.stream().for_each(|_| Ok(())));
+
+        let calls = ResilientCalls::new(handle.clone(), ResilienceSettings::from(&carriers));
+        Arc::new(HttpCarriersClient::new(carriers_client_handle, carriers.url, carrier_rates_cache, calls)) as Arc<dyn CarriersClient>
+    });
+
+    // Geocoder has its own http client too, addresses are saved without coordinates if the provider is slow
+    let geocoder = config.geocoding.clone().map(|geocoding| {
+        let http_config = stq_http::client::Config {
+            timeout_duration_ms: geocoding.timeout_ms,
+            ..config.to_http_config()
+        };
+        let geocoding_http_client = stq_http::client::Client::new(&http_config, &handle);
+        let geocoding_client_handle = geocoding_http_client.handle();
+        handle.spawn(geocoding_http_client.stream().for_each(|_| Ok(())));
+
+        Arc::new(HttpGeocoder::new(geocoding_client_handle, geocoding.url, geocoding.api_key)) as Arc<dyn Geocoder>
+    });
+
+    // Notifications are sent with their own http client as well, a slow notifications service must not hold other requests
+    let notifications_client = config.notifications.clone().map(|notifications| {
+        let http_config = stq_http::client::Config {
+            timeout_duration_ms: notifications.timeout_ms,
+            ..config.to_http_config()
+        };
+        let notifications_http_client = stq_http::client::Client::new(&http_config, &handle);
+        let notifications_client_handle = notifications_http_client.handle();
+        handle.spawn(notifications_http_client.stream().for_each(|_| Ok(())));
+
+        Arc::new(HttpNotificationsClient::new(notifications_client_handle, notifications.url)) as Arc<dyn NotificationsClient>
+    });
+
+    // Events publisher has its own http client too, the message bus must not hold the requests that changed the shipping
+    let events_publisher = config.events.clone().map(|events| {
+        let http_config = stq_http::client::Config {
+            timeout_duration_ms: events.timeout_ms,
+            ..config.to_http_config()
+        };
+        let events_http_client = stq_http::client::Client::new(&http_config, &handle);
+        let events_client_handle = events_http_client.handle();
+        handle.spawn(events_http_client.stream().for_each(|_| Ok(())));
+
+        Arc::new(HttpEventsPublisher::new(events_client_handle, events)) as Arc<dyn EventsPublisher>
+    });
+
+    // Events left in the outbox, because publishing failed after the commit, are published again in the background
+    if let (Some(events), Some(events_publisher)) = (config.events.as_ref(), events_publisher.clone()) {
+        events::schedule(
+            &handle,
+            admin_cpu_pool.clone(),
+            db_pool.clone(),
+            repo_factory.clone(),
+            events_publisher,
+            Duration::from_secs(events.relay_interval_sec.unwrap_or(events::DEFAULT_RELAY_INTERVAL_SEC)),
+        );
+    }
+
+    // Nightly integrity check of shipping rates runs on the admin pool, so it doesn't slow down price requests
+    if let Some(rates_integrity) = config.rates_integrity.as_ref() {
+        rates_integrity::schedule(
+            &handle,
+            admin_cpu_pool.clone(),
+            db_pool.clone(),
+            repo_factory.clone(),
+            RatesIntegritySettings::from(rates_integrity),
+        );
+    }
+
+    // Daily snapshots of the sampled availability results, regressions since the previous day are alerted
+    if let Some(coverage_regressions) = config.coverage_regressions.clone() {
+        coverage_regressions::schedule(
+            &handle,
+            admin_cpu_pool.clone(),
+            db_pool.clone(),
+            repo_factory.clone(),
+            coverage_regressions,
+        );
+    }
+
+    let mut context = StaticContext::new(
+        db_pool,
+        cpu_pool,
+        admin_cpu_pool,
+        client_handle,
+        carriers_client,
+        shipping_rates_cache,
+        geocoder,
+        notifications_client,
+        events_publisher,
+        read_replicas,
+        Arc::new(config),
+        repo_factory,
+    );
+
+    // Versions of the content and the permissions matrix kept in memory are dropped on every replica when they change
+    if let (Some(redis_url), Some(redis_pool)) = (context.config.server.redis.clone(), redis_pool) {
+        let publisher = Arc::new(RedisInvalidationPublisher::new(redis_pool)) as Arc<dyn InvalidationPublisher>;
+        context.content_versions = Arc::new(ContentVersions::with_publisher(publisher));
+
+        let content_versions = context.content_versions.clone();
+        let repo_factory = context.repo_factory.clone();
+        invalidation::spawn_subscriber(redis_url, move |key| match key {
+            InvalidationKey::Content(content) => content_versions.forget(&[content]),
+            InvalidationKey::Permissions => repo_factory.forget_permissions(),
+        });
+    }
+
+    if let Some(read_replicas) = context.config.read_replicas.as_ref() {
+        replicas::schedule(
+            &handle,
+            context.admin_cpu_pool.clone(),
+            context.read_replicas.clone(),
+            Duration::from_secs(read_replicas.probe_interval_sec),
+        );
+    }
 
+    // Data denormalized from the countries tree is refreshed in the background after the tree changes
+    if let Some(country_dependents) = context.config.country_dependents.as_ref() {
+        country_dependents::schedule(
+            &handle,
+            context.admin_cpu_pool.clone(),
+            context.db_pool.clone(),
+            context.repo_factory.clone(),
+            context.content_versions.clone(),
+            Duration::from_secs(country_dependents.refresh_interval_sec),
+            context.config.backfills.as_ref().map(BackfillSettings::from).unwrap_or_default(),
+        );
+    }
+
+    // Expired delivery quotes are purged by the service itself rather than by jobs outside of it
+    if let Some(delivery_quotes) = context.config.delivery_quotes.as_ref() {
+        if let Some(cleanup_interval_sec) = delivery_quotes.cleanup_interval_sec {
+            quotes_cleanup::schedule(
+                &handle,
+                context.admin_cpu_pool.clone(),
+                context.db_pool.clone(),
+                context.repo_factory.clone(),
+                context.clock.clone(),
+                context.quotes_cleanup.clone(),
+                Duration::from_secs(cleanup_interval_sec),
+                Duration::from_secs(
+                    delivery_quotes
+                        .retention_sec
+                        .unwrap_or(quotes_cleanup::DEFAULT_QUOTES_RETENTION_SEC),
+                ),
+            );
+        }
+    }
+
+    let connection_tracker = context.connection_tracker.clone();
+    let long_checkouts_check = Interval::new(connection_tracker.check_interval(), &handle).expect("Failed to create interval");
+    handle.spawn(
+        long_checkouts_check
+            .for_each(move |_| {
+                connection_tracker.check_long_checkouts();
+                Ok(())
+            })
+            .map_err(|e| error!("Connections diagnostics error: {}", e)),
+    );
+
+    let response_caching = Arc::new(ResponseCachingPolicy::new(&context.config));
+    let content_versions = context.content_versions.clone();
+    let cors = Arc::new(CorsPolicy::new(&context.config));
+    let keep_alive = context.config.server.keep_alive.unwrap_or(true);
+    let request_timeout = context.config.server.request_timeout_ms.map(Duration::from_millis);
+    let grace_period_ms = context
+        .config
+        .server
+        .shutdown_grace_period_ms
+        .unwrap_or(shutdown::DEFAULT_GRACE_PERIOD_MS);
+    let db_pool = context.db_pool.clone();
+    let in_flight = InFlightRequests::default();
     let serve = Http::new()
-        .serve_addr_handle(&address, &*handle, move || {
-            // Prepare application
-            let controller = controller::ControllerImpl::new(context.clone());
-            let app = Application::<errors::Error>::new(controller);
+        .keep_alive(keep_alive)
+        .serve_addr_handle(&address, &*handle, {
+            let handle = handle.clone();
+            let in_flight = in_flight.clone();
+            move || {
+                // Prepare application
+                let controller = controller::ControllerImpl::new(context.clone());
+                let app = RequestTimeout::new(
+                    Application::<errors::Error>::new(controller),
+                    request_timeout,
+                    (*handle).clone(),
+                    context.route_parser.clone(),
+                );
+                let app = ConditionalGet::new(ResponseCaching::new(app, response_caching.clone()), content_versions.clone());
+                let app = Cors::new(ResponseCompression::new(app), cors.clone());
+                let app = TrackInFlight::new(app, in_flight.clone());
 
-            Ok(app)
+                Ok(app)
+            }
         })
         .unwrap_or_else(|reason| {
             eprintln!("Http Server Initialization Error: {}", reason);
             process::exit(1);
         });
 
+    // Listener is closed once the shutdown starts, the connections already accepted are served until it ends
+    let (stop_accepting, accepting_stopped) = oneshot::channel::<()>();
     handle.spawn(
         serve
             .for_each({
@@ -157,18 +458,39 @@ pub fn start_server<F: FnOnce() + 'static>(config: config::Config, port: Option<
                     Ok(())
                 }
             })
+            .map_err(|_| ())
+            .select(accepting_stopped.map_err(|_| ()))
+            .map(|_| ())
             .map_err(|_| ()),
     );
 
-    info!("Listening on http://{}, threads: {}", address, thread_count);
+    info!(
+        "Listening on http://{}, threads: {}, admin threads: {}",
+        address, thread_count, admin_thread_count
+    );
     handle.spawn_fn(move || {
         callback();
         future::ok(())
     });
 
-    core.run(tokio_signal::ctrl_c().flatten_stream().take(1u64).for_each(|()| {
-        info!("Ctrl+C received. Exit");
-        Ok(())
-    }))
-    .unwrap();
+    let drained = core
+        .run(shutdown::signal().and_then(|signal| {
+            info!(
+                "{} received, waiting for {} requests in flight at most {} ms",
+                signal,
+                in_flight.count(),
+                grace_period_ms
+            );
+            in_flight.start_shutdown();
+            let _ = stop_accepting.send(());
+
+            shutdown::drain(&handle, in_flight.clone(), db_pool, Duration::from_millis(grace_period_ms))
+        }))
+        .unwrap();
+
+    if drained {
+        info!("Requests in flight are drained. Exit");
+    } else {
+        warn!("Shutdown grace period is over with {} requests in flight. Exit", in_flight.count());
+    }
 }
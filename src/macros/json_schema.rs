@@ -0,0 +1,66 @@
+#[macro_export]
+
+/// Macro for implementing `JsonSchema` for a payload struct. All fields of the struct
+/// must be listed with their types, otherwise the schema does not compile, so it can't
+/// get out of sync with the struct. `Option` fields are not required.
+/// Fields marked `#[default]` have `#[serde(default)]` and are not required either,
+/// fields marked `#[skip]` are not deserialized and are left out of the schema.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use] extern crate delivery_lib;
+///
+/// use delivery_lib::models::{JsonSchema, Schema};
+///
+/// struct NewThing {
+///     name: String,
+///     tags: Vec<String>,
+///     description: Option<String>,
+/// }
+///
+/// json_schema!(NewThing {
+///     name: String,
+///     #[default]
+///     tags: Vec<String>,
+///     description: Option<String>,
+/// });
+///
+/// fn main() {
+///     match NewThing::json_schema() {
+///         Schema::Object { required, .. } => assert_eq!(required, vec!["name"]),
+///         _ => unreachable!(),
+///     }
+/// }
+/// ```
+macro_rules! json_schema {
+    (@field $properties:ident, $required:ident, $field:ident, $ty:ty) => {
+        $properties.push((stringify!($field), <$ty as $crate::models::JsonSchema>::json_schema()));
+        if !<$ty as $crate::models::JsonSchema>::is_optional() {
+            $required.push(stringify!($field));
+        }
+    };
+    (@field $properties:ident, $required:ident, $field:ident, $ty:ty, default) => {
+        $properties.push((stringify!($field), <$ty as $crate::models::JsonSchema>::json_schema()));
+    };
+    (@field $properties:ident, $required:ident, $field:ident, $ty:ty, skip) => {};
+    ($name:ident { $($(#[$marker:ident])* $field:ident: $ty:ty),+ $(,)* }) => {
+        impl $crate::models::JsonSchema for $name {
+            #[allow(unused_mut)]
+            fn json_schema() -> $crate::models::Schema {
+                // Destructuring without `..` fails to compile when a field is missing from the schema
+                #[allow(dead_code)]
+                fn check_fields(payload: $name) {
+                    let $name { $($field),+ } = payload;
+                    $(let _: $ty = $field;)+
+                }
+
+                let mut properties = vec![];
+                let mut required = vec![];
+                $(json_schema!(@field properties, required, $field, $ty $(, $marker)*);)+
+
+                $crate::models::Schema::Object { properties, required }
+            }
+        }
+    };
+}
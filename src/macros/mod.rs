@@ -1,2 +1,4 @@
 #[macro_use]
+pub mod json_schema;
+#[macro_use]
 pub mod validation_errors;
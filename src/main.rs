@@ -1,8 +1,13 @@
 //! Delivery is a microservice.
+//!
+//! `--migrate` runs the pending database migrations before serving, `--migrate-only` runs them and exits.
 
 extern crate delivery_lib;
 extern crate stq_logging;
 
+use std::env;
+use std::process;
+
 fn main() {
     let config = delivery_lib::config::Config::new().expect("Can't load app config!");
 
@@ -12,5 +17,16 @@ fn main() {
     // Prepare logger
     stq_logging::init(config.graylog.as_ref());
 
+    let migrate_only = env::args().any(|arg| arg == "--migrate-only");
+    if migrate_only || env::args().any(|arg| arg == "--migrate") {
+        if let Err(e) = delivery_lib::migrations::run_pending(&config.server.database) {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+        if migrate_only {
+            return;
+        }
+    }
+
     delivery_lib::start_server(config, None, || ());
 }
@@ -0,0 +1,46 @@
+//! Database migrations embedded into the binary, so that the service migrates its database itself
+//! instead of diesel_cli run by hand in every environment.
+//!
+//! `delivery --migrate` runs the pending migrations before serving, `delivery --migrate-only` runs them and exits.
+//! The applied schema version is reported by `GET /version`.
+use std::io;
+
+use diesel::pg::PgConnection;
+use diesel::sql_types::{BigInt, Nullable, VarChar};
+use diesel::{sql_query, Connection, RunQueryDsl};
+use failure::Error as FailureError;
+use failure::Fail;
+
+use errors::Error;
+use repos::DbConnection;
+
+embed_migrations!("migrations");
+
+/// Version of the service and of its database schema
+#[derive(Clone, Debug, Serialize, QueryableByName)]
+pub struct VersionInfo {
+    #[sql_type = "VarChar"]
+    pub version: String,
+    /// Version of the last migration applied, `None` if the database is not migrated yet
+    #[sql_type = "Nullable<VarChar>"]
+    pub schema_version: Option<String>,
+    #[sql_type = "BigInt"]
+    pub applied_migrations: i64,
+}
+
+/// Runs the migrations not applied to the database yet, the applied ones are printed
+pub fn run_pending(database_url: &str) -> Result<(), FailureError> {
+    let conn = PgConnection::establish(database_url).map_err(|e| e.context(Error::Connection))?;
+
+    embedded_migrations::run_with_output(&conn, &mut io::stdout())
+        .map_err(|e| format_err!("Migrations failed: {}", e).context(Error::Internal))?;
+
+    Ok(())
+}
+
+pub fn version_info<T: DbConnection>(conn: &T) -> Result<VersionInfo, FailureError> {
+    sql_query("SELECT $1 AS version, MAX(version) AS schema_version, COUNT(*) AS applied_migrations FROM __diesel_schema_migrations")
+        .bind::<VarChar, _>(env!("CARGO_PKG_VERSION"))
+        .get_result(conn)
+        .map_err(|e| Error::from(e).into())
+}
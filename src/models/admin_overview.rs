@@ -0,0 +1,100 @@
+//! Models for the overview of the shipping data shown on the home screen of the admin dashboard
+use diesel::sql_types::{BigInt, Jsonb};
+
+use stq_types::Alpha3;
+
+use models::TypedJson;
+
+/// Number of the countries in the top destinations of the overview
+pub const ADMIN_OVERVIEW_TOP_DESTINATIONS_LIMIT: usize = 10;
+
+/// Base products with shipping configured to the country
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CountryProductsCount {
+    pub alpha3: Alpha3,
+    pub products_count: i64,
+}
+
+/// Row of the overview query, counts of the products by country are aggregated into a JSONB array
+#[derive(QueryableByName, Debug)]
+pub struct AdminOverviewRaw {
+    #[sql_type = "BigInt"]
+    pub companies_count: i64,
+    #[sql_type = "BigInt"]
+    pub packages_count: i64,
+    #[sql_type = "BigInt"]
+    pub companies_packages_count: i64,
+    #[sql_type = "BigInt"]
+    pub shipping_rates_count: i64,
+    #[sql_type = "Jsonb"]
+    pub products_by_country: TypedJson<Vec<CountryProductsCount>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AdminOverview {
+    pub companies_count: i64,
+    pub packages_count: i64,
+    pub companies_packages_count: i64,
+    /// Rows of the shipping rates, inactive ones included
+    pub shipping_rates_count: i64,
+    /// Ordered by country code
+    pub products_by_country: Vec<CountryProductsCount>,
+    /// Countries with the most products first
+    pub top_destinations: Vec<CountryProductsCount>,
+}
+
+impl From<AdminOverviewRaw> for AdminOverview {
+    fn from(raw: AdminOverviewRaw) -> Self {
+        let mut products_by_country = raw.products_by_country.into_inner();
+        products_by_country.sort_by(|a, b| a.alpha3.0.cmp(&b.alpha3.0));
+
+        let mut top_destinations = products_by_country.clone();
+        top_destinations.sort_by(|a, b| b.products_count.cmp(&a.products_count).then_with(|| a.alpha3.0.cmp(&b.alpha3.0)));
+        top_destinations.truncate(ADMIN_OVERVIEW_TOP_DESTINATIONS_LIMIT);
+
+        AdminOverview {
+            companies_count: raw.companies_count,
+            packages_count: raw.packages_count,
+            companies_packages_count: raw.companies_packages_count,
+            shipping_rates_count: raw.shipping_rates_count,
+            products_by_country,
+            top_destinations,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn country(alpha3: &str, products_count: i64) -> CountryProductsCount {
+        CountryProductsCount {
+            alpha3: Alpha3(alpha3.to_string()),
+            products_count,
+        }
+    }
+
+    #[test]
+    fn top_destinations_are_the_countries_with_the_most_products() {
+        let mut products_by_country = (0..ADMIN_OVERVIEW_TOP_DESTINATIONS_LIMIT as i64)
+            .map(|i| country(&format!("C{:02}", i), 1))
+            .collect::<Vec<_>>();
+        products_by_country.push(country("USA", 5));
+        products_by_country.push(country("RUS", 5));
+
+        let overview = AdminOverview::from(AdminOverviewRaw {
+            companies_count: 1,
+            packages_count: 2,
+            companies_packages_count: 3,
+            shipping_rates_count: 4,
+            products_by_country: TypedJson(products_by_country),
+        });
+
+        assert_eq!(overview.products_by_country.len(), ADMIN_OVERVIEW_TOP_DESTINATIONS_LIMIT + 2);
+        assert_eq!(overview.products_by_country[0], country("C00", 1));
+        assert_eq!(overview.top_destinations.len(), ADMIN_OVERVIEW_TOP_DESTINATIONS_LIMIT);
+        assert_eq!(overview.top_destinations[0], country("RUS", 5));
+        assert_eq!(overview.top_destinations[1], country("USA", 5));
+        assert_eq!(overview.top_destinations[2], country("C00", 1));
+    }
+}
@@ -0,0 +1,189 @@
+//! Models for API keys of the internal callers, e.g. orders and stores services. A key is allowed to do
+//! the actions listed in its scopes only, instead of acting as a superuser. Keys are stored hashed, the key
+//! itself is returned once when it is issued
+use std::time::SystemTime;
+
+use failure::Error as FailureError;
+use failure::Fail;
+use rand::{self, Rng};
+use serde_json;
+use sha3::{Digest, Sha3_256};
+use validator::{Validate, ValidationErrors};
+
+use errors::Error;
+use models::authorization::{Action, Resource};
+use schema::api_keys;
+
+/// Prefix of the issued keys, so that leaked keys are easy to recognize
+const API_KEY_PREFIX: &str = "dlv_";
+
+/// Random bytes of the issued keys
+const API_KEY_BYTES: usize = 32;
+
+/// Action the key is allowed to do on the resource, `all` allows every action
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ApiKeyScope {
+    pub resource: Resource,
+    pub action: Action,
+}
+
+json_schema!(ApiKeyScope {
+    resource: Resource,
+    action: Action,
+});
+
+impl ApiKeyScope {
+    pub fn allows(&self, resource: Resource, action: Action) -> bool {
+        self.resource == resource && (self.action == action || self.action == Action::All)
+    }
+}
+
+#[derive(Queryable, Debug)]
+pub struct ApiKeyRaw {
+    pub id: i32,
+    pub name: String,
+    pub key_hash: String,
+    pub scopes: serde_json::Value,
+    pub created_at: SystemTime,
+    pub revoked_at: Option<SystemTime>,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "api_keys"]
+pub struct InsertApiKey {
+    pub name: String,
+    pub key_hash: String,
+    pub scopes: serde_json::Value,
+}
+
+/// API key of an internal caller, revoked keys are kept for the audit
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ApiKey {
+    pub id: i32,
+    /// Caller the key is issued to, e.g. `orders`
+    pub name: String,
+    pub scopes: Vec<ApiKeyScope>,
+    pub created_at: SystemTime,
+    pub revoked_at: Option<SystemTime>,
+}
+
+impl ApiKeyRaw {
+    pub fn to_model(self) -> Result<ApiKey, FailureError> {
+        let ApiKeyRaw {
+            id,
+            name,
+            scopes,
+            created_at,
+            revoked_at,
+            ..
+        } = self;
+
+        let scopes = serde_json::from_value(scopes)
+            .map_err(|e| e.context(format!("Can not parse scopes of api key {}", id)).context(Error::Parse))?;
+
+        Ok(ApiKey {
+            id,
+            name,
+            scopes,
+            created_at,
+            revoked_at,
+        })
+    }
+}
+
+/// Payload issuing a key
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NewApiKey {
+    pub name: String,
+    pub scopes: Vec<ApiKeyScope>,
+}
+
+json_schema!(NewApiKey {
+    name: String,
+    scopes: Vec<ApiKeyScope>,
+});
+
+impl Validate for NewApiKey {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        if self.name.trim().is_empty() {
+            Err(validation_errors!({ "name": ["name" => "Name must not be empty"] }))?;
+        }
+
+        if self.scopes.is_empty() {
+            Err(validation_errors!({ "scopes": ["scopes" => "Key must have at least one scope"] }))?;
+        }
+
+        // keys must not be able to issue other keys with wider scopes
+        if self.scopes.iter().any(|scope| scope.resource == Resource::ApiKeys) {
+            Err(validation_errors!({ "scopes": ["scopes" => "Keys can not be scoped to api keys"] }))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl NewApiKey {
+    /// Record of the key with the hash of `key`
+    pub fn to_insert(self, key: &str) -> Result<InsertApiKey, FailureError> {
+        let scopes =
+            serde_json::to_value(self.scopes).map_err(|e| e.context("Can not parse scopes of api key from value").context(Error::Parse))?;
+
+        Ok(InsertApiKey {
+            name: self.name,
+            key_hash: api_key_hash(key),
+            scopes,
+        })
+    }
+}
+
+/// Issued key, the key itself is not stored and can not be returned again
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct IssuedApiKey {
+    pub api_key: ApiKey,
+    pub key: String,
+}
+
+/// New random key
+pub fn generate_api_key() -> String {
+    let mut rng = rand::thread_rng();
+    let random = (0..API_KEY_BYTES).map(|_| format!("{:02x}", rng.gen::<u8>())).collect::<String>();
+    format!("{}{}", API_KEY_PREFIX, random)
+}
+
+/// Hash the key is stored and looked up by
+pub fn api_key_hash(key: &str) -> String {
+    let mut hasher = Sha3_256::default();
+    hasher.input(key.as_bytes());
+
+    hasher.result().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_random_and_hashed() {
+        let key = generate_api_key();
+        assert!(key.starts_with(API_KEY_PREFIX));
+        assert_eq!(key.len(), API_KEY_PREFIX.len() + API_KEY_BYTES * 2);
+        assert_ne!(key, generate_api_key());
+
+        assert_eq!(api_key_hash(&key), api_key_hash(&key));
+        assert_ne!(api_key_hash(&key), key);
+    }
+
+    #[test]
+    fn keys_can_not_be_scoped_to_api_keys() {
+        let new_api_key = |resource| NewApiKey {
+            name: "orders".to_string(),
+            scopes: vec![ApiKeyScope {
+                resource,
+                action: Action::All,
+            }],
+        };
+
+        assert!(new_api_key(Resource::Products).validate().is_ok());
+        assert!(new_api_key(Resource::ApiKeys).validate().is_err());
+    }
+}
@@ -6,13 +6,18 @@ use std::fmt;
 // Create - create resource with id.
 // Update - update resource with id.
 // Delete - delete resource with id.
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+// Moderate - approve, reject or suspend resource of other users, i.e. suspend a company.
+// Publish - make resource visible to buyers, i.e. store new shipping rates.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize, DieselTypes)]
+#[serde(rename_all = "snake_case")]
 pub enum Action {
     All,
     Read,
     Create,
     Update,
     Delete,
+    Moderate,
+    Publish,
 }
 
 impl fmt::Display for Action {
@@ -23,6 +28,8 @@ impl fmt::Display for Action {
             Action::Create => write!(f, "create"),
             Action::Update => write!(f, "update"),
             Action::Delete => write!(f, "delete"),
+            Action::Moderate => write!(f, "moderate"),
+            Action::Publish => write!(f, "publish"),
         }
     }
 }
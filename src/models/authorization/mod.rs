@@ -3,9 +3,11 @@
 pub mod action;
 pub mod permission;
 pub mod resource;
+pub mod role_permission;
 pub mod scope;
 
 pub use self::action::Action;
 pub use self::permission::Permission;
 pub use self::resource::Resource;
+pub use self::role_permission::*;
 pub use self::scope::Scope;
@@ -1,31 +1,84 @@
 //! Enum for resources available in ACLs
 use std::fmt;
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize, DieselTypes)]
+#[serde(rename_all = "snake_case")]
 pub enum Resource {
+    AdminOverview,
+    ApiKeys,
+    AvailabilitySnapshots,
+    Backfills,
+    CarrierAccounts,
     Companies,
     CompaniesPackages,
+    CompanyRatesQuotas,
+    CompanySuspensions,
     Countries,
+    CountryRegulations,
+    CustomsInfo,
+    DeliveryQuotes,
+    EventOutbox,
+    PackageTemplates,
     Packages,
+    Permissions,
+    PickupPoints,
     Pickups,
+    PricingRules,
     Products,
+    ShipmentBookings,
+    ShipmentNotifications,
     ShippingRates,
+    ShippingRatesAnomalies,
+    ShippingRestrictions,
+    ShippingTemplates,
+    StoreManagers,
+    StoreMargins,
+    StoreShippingSummaries,
+    Surcharges,
+    TestFixtures,
     UserAddresses,
     UserRoles,
+    Zones,
 }
 
 impl fmt::Display for Resource {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
+            Resource::AdminOverview => write!(f, "admin overview"),
+            Resource::ApiKeys => write!(f, "api keys"),
+            Resource::AvailabilitySnapshots => write!(f, "availability snapshots"),
+            Resource::Backfills => write!(f, "backfills"),
+            Resource::CarrierAccounts => write!(f, "carrier accounts"),
             Resource::Companies => write!(f, "companies"),
             Resource::CompaniesPackages => write!(f, "companies_packages"),
+            Resource::CompanyRatesQuotas => write!(f, "company rates quotas"),
+            Resource::CompanySuspensions => write!(f, "company suspensions"),
             Resource::Countries => write!(f, "countries"),
+            Resource::CountryRegulations => write!(f, "country regulations"),
+            Resource::CustomsInfo => write!(f, "customs info"),
+            Resource::DeliveryQuotes => write!(f, "delivery quotes"),
+            Resource::EventOutbox => write!(f, "event outbox"),
+            Resource::PackageTemplates => write!(f, "package templates"),
             Resource::Packages => write!(f, "packages"),
+            Resource::Permissions => write!(f, "permissions"),
+            Resource::PickupPoints => write!(f, "pickup points"),
             Resource::Pickups => write!(f, "pickups"),
+            Resource::PricingRules => write!(f, "pricing rules"),
             Resource::Products => write!(f, "products"),
+            Resource::ShipmentBookings => write!(f, "shipment bookings"),
+            Resource::ShipmentNotifications => write!(f, "shipment notifications"),
             Resource::ShippingRates => write!(f, "shipping rates"),
+            Resource::ShippingRatesAnomalies => write!(f, "shipping rates anomalies"),
+            Resource::ShippingRestrictions => write!(f, "shipping restrictions"),
+            Resource::ShippingTemplates => write!(f, "shipping templates"),
+            Resource::StoreManagers => write!(f, "store managers"),
+            Resource::StoreMargins => write!(f, "store margins"),
+            Resource::StoreShippingSummaries => write!(f, "store shipping summaries"),
+            Resource::Surcharges => write!(f, "surcharges"),
+            Resource::TestFixtures => write!(f, "test fixtures"),
             Resource::UserAddresses => write!(f, "user addresses"),
             Resource::UserRoles => write!(f, "user roles"),
+            Resource::Zones => write!(f, "zones"),
         }
     }
 }
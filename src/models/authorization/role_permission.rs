@@ -0,0 +1,90 @@
+//! Permissions of the roles stored in DB, ACL of the user is built from the permissions of the user roles
+
+use std::collections::HashMap;
+
+use stq_types::DeliveryRole;
+
+use models::{Action, Permission, Resource, Scope};
+
+#[derive(Serialize, Deserialize, Queryable, Clone, Debug)]
+pub struct RolePermission {
+    pub id: i32,
+    pub role: DeliveryRole,
+    pub resource: Resource,
+    pub action: Action,
+    pub scope: Scope,
+}
+
+impl RolePermission {
+    pub fn permission(&self) -> Permission {
+        Permission {
+            resource: self.resource,
+            action: self.action,
+            scope: self.scope,
+        }
+    }
+}
+
+/// Everything the role may do, rendered by the admin UI
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RolePermissions {
+    pub role: DeliveryRole,
+    pub permissions: Vec<Permission>,
+}
+
+/// Groups permissions by role keeping the order of the roles
+pub fn group_permissions_by_role(role_permissions: Vec<RolePermission>) -> Vec<RolePermissions> {
+    let mut groups: Vec<RolePermissions> = vec![];
+    for role_permission in role_permissions {
+        let permission = role_permission.permission();
+        match groups.iter_mut().find(|group| group.role == role_permission.role) {
+            Some(group) => group.permissions.push(permission),
+            None => groups.push(RolePermissions {
+                role: role_permission.role,
+                permissions: vec![permission],
+            }),
+        }
+    }
+
+    groups
+}
+
+/// Permissions matrix in the form used by the ACL
+pub fn permissions_matrix(role_permissions: Vec<RolePermission>) -> HashMap<DeliveryRole, Vec<Permission>> {
+    group_permissions_by_role(role_permissions)
+        .into_iter()
+        .map(|group| (group.role, group.permissions))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn role_permission(id: i32, role: DeliveryRole, resource: Resource) -> RolePermission {
+        RolePermission {
+            id,
+            role,
+            resource,
+            action: Action::All,
+            scope: Scope::All,
+        }
+    }
+
+    #[test]
+    fn group_permissions_by_role_keeps_order() {
+        let groups = group_permissions_by_role(vec![
+            role_permission(1, DeliveryRole::Superuser, Resource::Companies),
+            role_permission(2, DeliveryRole::User, Resource::UserAddresses),
+            role_permission(3, DeliveryRole::Superuser, Resource::Permissions),
+        ]);
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].role, DeliveryRole::Superuser);
+        assert_eq!(
+            groups[0].permissions.iter().map(|p| p.resource).collect::<Vec<_>>(),
+            vec![Resource::Companies, Resource::Permissions]
+        );
+        assert_eq!(groups[1].role, DeliveryRole::User);
+    }
+}
@@ -0,0 +1,210 @@
+//! Models for snapshots of the availability results taken by the coverage regressions check
+use std::fmt;
+use std::time::SystemTime;
+
+use failure::Error as FailureError;
+use serde_json;
+
+use stq_types::{Alpha3, BaseProductId, CompanyPackageId};
+
+use models::AvailablePackageForUser;
+use schema::availability_snapshots;
+
+/// Package available on the route when the snapshot was taken
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SnapshotPackage {
+    pub company_package_id: CompanyPackageId,
+    pub price: Option<f64>,
+}
+
+impl<'a> From<&'a AvailablePackageForUser> for SnapshotPackage {
+    fn from(package: &'a AvailablePackageForUser) -> Self {
+        Self {
+            company_package_id: package.id,
+            price: package.price.as_ref().map(|price| price.0),
+        }
+    }
+}
+
+/// Last snapshot of the packages available for the base product on the route
+#[derive(Clone, Debug)]
+pub struct AvailabilitySnapshot {
+    pub base_product_id: BaseProductId,
+    pub delivery_to: Alpha3,
+    pub packages: Vec<SnapshotPackage>,
+    pub taken_at: SystemTime,
+}
+
+#[derive(Queryable, Clone, Debug)]
+pub struct AvailabilitySnapshotRaw {
+    pub base_product_id: BaseProductId,
+    pub delivery_to: Alpha3,
+    pub packages: serde_json::Value,
+    pub taken_at: SystemTime,
+}
+
+impl AvailabilitySnapshotRaw {
+    pub fn to_model(self) -> Result<AvailabilitySnapshot, FailureError> {
+        let AvailabilitySnapshotRaw {
+            base_product_id,
+            delivery_to,
+            packages,
+            taken_at,
+        } = self;
+        let packages = serde_json::from_value::<Vec<SnapshotPackage>>(packages).map_err(|e| {
+            FailureError::from(e)
+                .context(format!(
+                    "Could not parse JSON with packages of availability snapshot of base product {} to {}",
+                    base_product_id, delivery_to
+                ))
+                .into()
+        })?;
+
+        Ok(AvailabilitySnapshot {
+            base_product_id,
+            delivery_to,
+            packages,
+            taken_at,
+        })
+    }
+}
+
+#[derive(Insertable, Clone, Debug)]
+#[table_name = "availability_snapshots"]
+pub struct NewAvailabilitySnapshot {
+    pub base_product_id: BaseProductId,
+    pub delivery_to: Alpha3,
+    pub packages: serde_json::Value,
+}
+
+impl NewAvailabilitySnapshot {
+    pub fn new(base_product_id: BaseProductId, delivery_to: Alpha3, packages: &[SnapshotPackage]) -> Result<Self, FailureError> {
+        Ok(Self {
+            base_product_id,
+            delivery_to,
+            packages: serde_json::to_value(packages)?,
+        })
+    }
+}
+
+/// Change of the availability on the route since the previous snapshot exceeding the thresholds
+#[derive(Clone, Debug, PartialEq)]
+pub enum CoverageRegression {
+    /// Packages available in the previous snapshot are gone, `share` is the share of the previous packages lost
+    CoverageDropped { lost: Vec<CompanyPackageId>, share: f64 },
+    /// Price of the package changed by more than the threshold
+    PriceShifted {
+        company_package_id: CompanyPackageId,
+        previous: f64,
+        current: f64,
+    },
+}
+
+impl fmt::Display for CoverageRegression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CoverageRegression::CoverageDropped { ref lost, share } => {
+                write!(f, "{:.0}% of packages are not available anymore: {:?}", share * 100.0, lost)
+            }
+            CoverageRegression::PriceShifted {
+                company_package_id,
+                previous,
+                current,
+            } => write!(
+                f,
+                "price of company package {} changed from {} to {}",
+                company_package_id, previous, current
+            ),
+        }
+    }
+}
+
+/// Compares the packages of two snapshots of the same route. Coverage regresses when more than `max_coverage_drop`
+/// of the previous packages are lost, price shifts when it changes by more than `max_price_change` of the previous price
+pub fn find_coverage_regressions(
+    previous: &[SnapshotPackage],
+    current: &[SnapshotPackage],
+    max_coverage_drop: f64,
+    max_price_change: f64,
+) -> Vec<CoverageRegression> {
+    let mut regressions = vec![];
+    if previous.is_empty() {
+        return regressions;
+    }
+
+    let find_current =
+        |company_package_id: CompanyPackageId| current.iter().find(|package| package.company_package_id == company_package_id);
+
+    let lost = previous
+        .iter()
+        .filter(|package| find_current(package.company_package_id).is_none())
+        .map(|package| package.company_package_id)
+        .collect::<Vec<_>>();
+    let share = lost.len() as f64 / previous.len() as f64;
+    if share > max_coverage_drop {
+        regressions.push(CoverageRegression::CoverageDropped { lost, share });
+    }
+
+    for package in previous {
+        let prices = (
+            package.price,
+            find_current(package.company_package_id).and_then(|current| current.price),
+        );
+        if let (Some(previous), Some(current)) = prices {
+            if previous > 0.0 && (current - previous).abs() / previous > max_price_change {
+                regressions.push(CoverageRegression::PriceShifted {
+                    company_package_id: package.company_package_id,
+                    previous,
+                    current,
+                });
+            }
+        }
+    }
+
+    regressions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(id: i32, price: f64) -> SnapshotPackage {
+        SnapshotPackage {
+            company_package_id: CompanyPackageId(id),
+            price: Some(price),
+        }
+    }
+
+    #[test]
+    fn lost_coverage_is_reported_over_threshold() {
+        let previous = vec![package(1, 100.0), package(2, 100.0), package(3, 100.0), package(4, 100.0)];
+
+        assert_eq!(
+            find_coverage_regressions(&previous, &previous[1..], 0.25, 0.5),
+            Vec::<CoverageRegression>::new()
+        );
+        assert_eq!(
+            find_coverage_regressions(&previous, &previous[2..], 0.25, 0.5),
+            vec![CoverageRegression::CoverageDropped {
+                lost: vec![CompanyPackageId(1), CompanyPackageId(2)],
+                share: 0.5,
+            }]
+        );
+    }
+
+    #[test]
+    fn price_shifts_are_reported_over_threshold() {
+        let previous = vec![package(1, 100.0), package(2, 100.0)];
+        let current = vec![package(1, 140.0), package(2, 160.0), package(3, 1.0)];
+
+        assert_eq!(
+            find_coverage_regressions(&previous, &current, 0.0, 0.5),
+            vec![CoverageRegression::PriceShifted {
+                company_package_id: CompanyPackageId(2),
+                previous: 100.0,
+                current: 160.0,
+            }]
+        );
+        assert!(find_coverage_regressions(&[], &current, 0.0, 0.0).is_empty());
+    }
+}
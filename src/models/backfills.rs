@@ -0,0 +1,32 @@
+//! Models for tracking progress of batched backfills of big tables
+use std::time::SystemTime;
+
+use schema::backfills;
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, DieselTypes)]
+pub enum BackfillStatus {
+    Running,
+    Finished,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Queryable, Clone, Debug)]
+pub struct BackfillProgress {
+    pub name: String,
+    pub status: BackfillStatus,
+    pub total_rows: i64,
+    pub processed_rows: i64,
+    /// Id of the last processed row, the backfill is resumed right after it
+    pub last_processed_id: Option<i32>,
+    pub error: Option<String>,
+    pub created_at: SystemTime,
+    pub updated_at: SystemTime,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Clone, Debug)]
+#[table_name = "backfills"]
+pub struct NewBackfillProgress {
+    pub name: String,
+    pub status: BackfillStatus,
+    pub total_rows: i64,
+}
@@ -0,0 +1,107 @@
+//! Models for carrier accounts - accounts of the stores with the carriers, e.g. contracts of large sellers
+//! with negotiated rates. Prices of the carrier are requested with the account of the store if it has one
+use std::time::SystemTime;
+
+use validator::{Validate, ValidationErrors};
+
+use stq_types::{CompanyId, StoreId};
+
+use schema::carrier_accounts;
+
+/// Row of carrier_accounts table, the account number and the credentials are stored encrypted
+#[derive(Queryable, Clone, Debug)]
+pub struct DbCarrierAccount {
+    pub id: i32,
+    pub store_id: StoreId,
+    pub company_id: CompanyId,
+    pub encrypted_account_number: String,
+    pub encrypted_credentials: String,
+    pub created_at: SystemTime,
+    pub updated_at: SystemTime,
+}
+
+/// Account of the store with the carrier with the decrypted account number, credentials are never returned
+#[derive(Serialize, Clone, Debug)]
+pub struct CarrierAccount {
+    pub id: i32,
+    pub store_id: StoreId,
+    pub company_id: CompanyId,
+    pub account_number: String,
+    #[serde(skip_serializing)]
+    pub encrypted_account_number: String,
+    #[serde(skip_serializing)]
+    pub encrypted_credentials: String,
+    pub created_at: SystemTime,
+    pub updated_at: SystemTime,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "carrier_accounts"]
+pub struct NewCarrierAccount {
+    pub store_id: StoreId,
+    pub company_id: CompanyId,
+    pub encrypted_account_number: String,
+    pub encrypted_credentials: String,
+}
+
+#[derive(AsChangeset, Debug)]
+#[table_name = "carrier_accounts"]
+pub struct CarrierAccountChangeset {
+    pub encrypted_account_number: Option<String>,
+    pub encrypted_credentials: Option<String>,
+}
+
+fn validate_not_empty(field: &'static str, value: &str) -> Result<(), ValidationErrors> {
+    if value.trim().is_empty() {
+        Err(validation_errors!({ field: [field => "Value must not be empty"] }))?;
+    }
+
+    Ok(())
+}
+
+/// Payload adding the account of the store with the carrier
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CreateCarrierAccount {
+    pub company_id: CompanyId,
+    pub account_number: String,
+    /// Credentials the carrier authenticates the account with, e.g. an api key
+    pub credentials: String,
+}
+
+json_schema!(CreateCarrierAccount {
+    company_id: CompanyId,
+    account_number: String,
+    credentials: String,
+});
+
+impl Validate for CreateCarrierAccount {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        validate_not_empty("account_number", &self.account_number)?;
+        validate_not_empty("credentials", &self.credentials)
+    }
+}
+
+/// Payload changing the account number or the credentials, the carrier of the account is fixed
+#[derive(Serialize, Deserialize, Clone)]
+pub struct UpdateCarrierAccount {
+    pub account_number: Option<String>,
+    pub credentials: Option<String>,
+}
+
+json_schema!(UpdateCarrierAccount {
+    account_number: Option<String>,
+    credentials: Option<String>,
+});
+
+impl Validate for UpdateCarrierAccount {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        if let Some(ref account_number) = self.account_number {
+            validate_not_empty("account_number", account_number)?;
+        }
+        if let Some(ref credentials) = self.credentials {
+            validate_not_empty("credentials", credentials)?;
+        }
+
+        Ok(())
+    }
+}
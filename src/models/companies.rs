@@ -1,25 +1,68 @@
+use std::fmt;
+use std::str::FromStr;
+
 use failure::Error as FailureError;
-use failure::Fail;
-use serde_json;
+use validator::{Validate, ValidationErrors};
 
 use stq_static_resources::Currency;
 use stq_types::{Alpha3, CompanyId};
 
-use errors::Error;
-use models::Country;
+use models::{translate, validate_translations, Country, Localized, SortOrder, TextTranslation, TypedJson};
 use repos::countries::create_tree_used_countries;
 use schema::companies;
 
-#[derive(Serialize, Deserialize, Associations, Queryable, Debug, QueryableByName)]
+/// Services a company provides on top of the delivery
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CompanyCapability {
+    /// Cash on delivery
+    Cod,
+    Tracking,
+    Insurance,
+}
+
+impl FromStr for CompanyCapability {
+    type Err = FailureError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cod" => Ok(CompanyCapability::Cod),
+            "tracking" => Ok(CompanyCapability::Tracking),
+            "insurance" => Ok(CompanyCapability::Insurance),
+            other => Err(format_err!("Unknown company capability: {}", other)),
+        }
+    }
+}
+
+/// Comma separated list of capabilities, e.g. `cod,tracking`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CompanyCapabilities(pub Vec<CompanyCapability>);
+
+impl FromStr for CompanyCapabilities {
+    type Err = FailureError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|capability| !capability.is_empty())
+            .map(CompanyCapability::from_str)
+            .collect::<Result<Vec<_>, _>>()
+            .map(CompanyCapabilities)
+    }
+}
+
+#[derive(Serialize, Deserialize, Associations, Queryable, Clone, Debug, QueryableByName)]
 #[table_name = "companies"]
 pub struct CompanyRaw {
     pub id: CompanyId,
     pub name: String,
     pub label: String,
     pub description: Option<String>,
-    pub deliveries_from: serde_json::Value,
+    pub deliveries_from: TypedJson<Vec<Alpha3>>,
     pub logo: String,
     pub currency: Currency,
+    pub capabilities: TypedJson<Vec<CompanyCapability>>,
+    pub label_translations: TypedJson<Vec<TextTranslation>>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -31,15 +74,16 @@ pub struct Company {
     pub deliveries_from: Vec<Country>,
     pub logo: String,
     pub currency: Currency,
+    pub capabilities: Vec<CompanyCapability>,
+    /// Label is the text in the default language, it is replaced with the translation when a language is requested
+    pub label_translations: Vec<TextTranslation>,
 }
 
 impl Company {
-    pub fn from_raw(from: CompanyRaw, countries_arg: &Country) -> Result<Self, FailureError> {
-        let used_codes: Vec<Alpha3> = serde_json::from_value(from.deliveries_from)
-            .map_err(|e| e.context("Can not parse deliveries_from from db").context(Error::Parse))?;
-        let deliveries_from = create_tree_used_countries(countries_arg, &used_codes);
+    pub fn from_raw(from: CompanyRaw, countries_arg: &Country) -> Self {
+        let deliveries_from = create_tree_used_countries(countries_arg, &from.deliveries_from);
 
-        Ok(Self {
+        Self {
             id: from.id,
             name: from.name,
             label: from.label,
@@ -47,7 +91,19 @@ impl Company {
             deliveries_from,
             currency: from.currency,
             logo: from.logo,
-        })
+            capabilities: from.capabilities.into_inner(),
+            label_translations: from.label_translations.into_inner(),
+        }
+    }
+}
+
+impl Localized for Company {
+    fn localized(self, lang: &str) -> Self {
+        let label = translate(&self.label_translations, lang).map(str::to_string);
+        Self {
+            label: label.unwrap_or(self.label),
+            ..self
+        }
     }
 }
 
@@ -57,9 +113,11 @@ pub struct NewCompanyRaw {
     pub name: String,
     pub label: String,
     pub description: Option<String>,
-    pub deliveries_from: serde_json::Value,
+    pub deliveries_from: TypedJson<Vec<Alpha3>>,
     pub logo: String,
     pub currency: Currency,
+    pub capabilities: TypedJson<Vec<CompanyCapability>>,
+    pub label_translations: TypedJson<Vec<TextTranslation>>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -70,30 +128,43 @@ pub struct NewCompany {
     pub deliveries_from: Vec<Alpha3>,
     pub logo: String,
     pub currency: Currency,
+    #[serde(default)]
+    pub capabilities: Vec<CompanyCapability>,
+    #[serde(default)]
+    pub label_translations: Vec<TextTranslation>,
+}
+
+json_schema!(NewCompany {
+    name: String,
+    label: String,
+    description: Option<String>,
+    deliveries_from: Vec<Alpha3>,
+    logo: String,
+    currency: Currency,
+    #[default]
+    capabilities: Vec<CompanyCapability>,
+    #[default]
+    label_translations: Vec<TextTranslation>,
+});
+
+impl Validate for NewCompany {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        validate_translations("label_translations", &self.label_translations)
+    }
 }
 
 impl NewCompany {
-    pub fn to_raw(self) -> Result<NewCompanyRaw, FailureError> {
-        let Self {
-            name,
-            label,
-            deliveries_from,
-            description,
-            currency,
-            logo,
-        } = self;
-
-        let deliveries_from = serde_json::to_value(deliveries_from)
-            .map_err(|e| e.context("Can not parse deliveries_from from value").context(Error::Parse))?;
-
-        Ok(NewCompanyRaw {
-            name,
-            label,
-            description,
-            deliveries_from,
-            currency,
-            logo,
-        })
+    pub fn to_raw(self) -> NewCompanyRaw {
+        NewCompanyRaw {
+            name: self.name,
+            label: self.label,
+            description: self.description,
+            deliveries_from: TypedJson(self.deliveries_from),
+            currency: self.currency,
+            logo: self.logo,
+            capabilities: TypedJson(self.capabilities),
+            label_translations: TypedJson(self.label_translations),
+        }
     }
 }
 
@@ -103,9 +174,11 @@ pub struct UpdateCompanyRaw {
     pub name: Option<String>,
     pub label: Option<String>,
     pub description: Option<String>,
-    pub deliveries_from: Option<serde_json::Value>,
+    pub deliveries_from: Option<TypedJson<Vec<Alpha3>>>,
     pub logo: Option<String>,
     pub currency: Option<Currency>,
+    pub capabilities: Option<TypedJson<Vec<CompanyCapability>>>,
+    pub label_translations: Option<TypedJson<Vec<TextTranslation>>>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -116,33 +189,124 @@ pub struct UpdateCompany {
     pub deliveries_from: Option<Vec<Alpha3>>,
     pub logo: Option<String>,
     pub currency: Option<Currency>,
+    pub capabilities: Option<Vec<CompanyCapability>>,
+    pub label_translations: Option<Vec<TextTranslation>>,
+}
+
+json_schema!(UpdateCompany {
+    name: Option<String>,
+    label: Option<String>,
+    description: Option<String>,
+    deliveries_from: Option<Vec<Alpha3>>,
+    logo: Option<String>,
+    currency: Option<Currency>,
+    capabilities: Option<Vec<CompanyCapability>>,
+    label_translations: Option<Vec<TextTranslation>>,
+});
+
+impl Validate for UpdateCompany {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        match self.label_translations {
+            Some(ref label_translations) => validate_translations("label_translations", label_translations),
+            None => Ok(()),
+        }
+    }
 }
 
 impl UpdateCompany {
-    pub fn to_raw(self) -> Result<UpdateCompanyRaw, FailureError> {
-        let Self {
-            name,
-            label,
-            deliveries_from,
-            description,
-            currency,
-            logo,
-        } = self;
-
-        let deliveries_from = match deliveries_from {
-            Some(data) => {
-                Some(serde_json::to_value(data).map_err(|e| e.context("Can not parse deliveries_from from value").context(Error::Parse))?)
-            }
-            None => None,
-        };
-
-        Ok(UpdateCompanyRaw {
-            name,
-            label,
-            description,
-            deliveries_from,
-            currency,
-            logo,
-        })
+    pub fn to_raw(self) -> UpdateCompanyRaw {
+        UpdateCompanyRaw {
+            name: self.name,
+            label: self.label,
+            description: self.description,
+            deliveries_from: self.deliveries_from.map(TypedJson),
+            currency: self.currency,
+            logo: self.logo,
+            capabilities: self.capabilities.map(TypedJson),
+            label_translations: self.label_translations.map(TypedJson),
+        }
+    }
+}
+
+/// Field used to sort the list of companies
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CompaniesSortField {
+    Id,
+    Name,
+    Label,
+}
+
+impl Default for CompaniesSortField {
+    fn default() -> Self {
+        CompaniesSortField::Id
+    }
+}
+
+impl FromStr for CompaniesSortField {
+    type Err = FailureError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "id" => Ok(CompaniesSortField::Id),
+            "name" => Ok(CompaniesSortField::Name),
+            "label" => Ok(CompaniesSortField::Label),
+            other => Err(format_err!("Unknown companies sort field: {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for CompaniesSortField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CompaniesSortField::Id => write!(f, "id"),
+            CompaniesSortField::Name => write!(f, "name"),
+            CompaniesSortField::Label => write!(f, "label"),
+        }
+    }
+}
+
+/// Filters and ordering applied to the list of companies
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CompaniesSearch {
+    pub deliveries_from: Option<Alpha3>,
+    /// Companies with packages delivering to the country or to its regions
+    pub delivers_to: Option<Alpha3>,
+    /// Companies supporting all of the capabilities
+    pub supports: Option<CompanyCapabilities>,
+    pub label_contains: Option<String>,
+    pub sort: Option<CompaniesSortField>,
+    pub order: Option<SortOrder>,
+}
+
+/// Data deleted together with the company
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct CompanyDeletionImpact {
+    pub company_packages: i64,
+    /// Shipping of the base products by the company packages
+    pub products: i64,
+    /// Shipping rates of the routes of the company packages
+    pub shipping_rates: i64,
+}
+
+impl CompanyDeletionImpact {
+    /// Nothing depends on the company
+    pub fn is_empty(&self) -> bool {
+        self.company_packages == 0 && self.products == 0 && self.shipping_rates == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capabilities_are_parsed_from_comma_separated_list() {
+        assert_eq!(
+            "cod, tracking".parse::<CompanyCapabilities>().unwrap(),
+            CompanyCapabilities(vec![CompanyCapability::Cod, CompanyCapability::Tracking])
+        );
+        assert_eq!("".parse::<CompanyCapabilities>().unwrap(), CompanyCapabilities(vec![]));
+        assert!("cod,teleport".parse::<CompanyCapabilities>().is_err());
     }
 }
@@ -1,20 +1,38 @@
 use std::cmp::max;
+use std::fmt;
+use std::str::FromStr;
 
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Utc};
 use failure::Error as FailureError;
 use validator::{Validate, ValidationErrors};
 
-use models::{Country, Pickups, ShippingVariant};
+use models::{
+    default_rate_resolution_order, validate_rate_resolution_order, CompanySuspension, Country, DistancePricing, MeasurementsOutOfRange,
+    Packages, Pickups, RateResolutionStep, ShippingOptionId, ShippingVariant, TypedJson,
+};
 use stq_static_resources::Currency;
-use stq_types::{BaseProductId, CompanyId, CompanyPackageId, PackageId, ProductPrice, ShippingId, StoreId};
+use stq_types::{Alpha3, BaseProductId, CompanyId, CompanyPackageId, PackageId, ProductPrice, ShippingId, StoreId};
 
 use schema::companies_packages;
 
+/// Classes of hazardous goods that carriers may refuse to deliver
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum HazardClass {
+    LithiumBatteries,
+    Aerosols,
+}
+
 #[derive(Serialize, Deserialize, Clone, Copy, Debug)]
 pub struct ShipmentMeasurements {
     pub volume_cubic_cm: u32,
     pub weight_g: u32,
 }
 
+json_schema!(ShipmentMeasurements {
+    volume_cubic_cm: u32,
+    weight_g: u32,
+});
+
 impl ShipmentMeasurements {
     pub fn calculate_billable_weight(&self, dimensional_factor: Option<u32>) -> u32 {
         let ShipmentMeasurements { volume_cubic_cm, weight_g } = self;
@@ -29,6 +47,31 @@ impl ShipmentMeasurements {
     }
 }
 
+/// Delivery time is either not set or set with both bounds
+pub fn validate_delivery_time(min_days: Option<u32>, max_days: Option<u32>) -> Result<(), ValidationErrors> {
+    const MAX_DAYS: u32 = 365;
+
+    match (min_days, max_days) {
+        (None, None) => Ok(()),
+        (Some(min_days), Some(max_days)) => {
+            if max_days > MAX_DAYS {
+                Err(validation_errors!({ "delivery_time_max_days": ["delivery_time_max_days" => "Value is too big"] }))?;
+            }
+
+            if min_days > max_days {
+                Err(validation_errors!({
+                    "delivery_time_min_days": ["delivery_time_min_days" => "Minimum delivery time must not exceed the maximum one"]
+                }))?;
+            }
+
+            Ok(())
+        }
+        _ => Err(validation_errors!({
+            "delivery_time": ["delivery_time" => "Both minimum and maximum delivery time must be set"]
+        })),
+    }
+}
+
 impl Validate for ShipmentMeasurements {
     fn validate(&self) -> Result<(), ValidationErrors> {
         const MAX_VALUE: u32 = 1_000_000_000;
@@ -48,7 +91,15 @@ impl Validate for ShipmentMeasurements {
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum ShippingRateSource {
     NotAvailable,
-    Static { dimensional_factor: Option<u32> },
+    Static {
+        dimensional_factor: Option<u32>,
+    },
+    /// Prices come from the carrier API, stored rates are used when the carrier does not respond
+    OnDemand {
+        dimensional_factor: Option<u32>,
+    },
+    /// Price depends on the distance between the seller warehouse and the buyer, used by same-city couriers
+    Distance(DistancePricing),
 }
 
 impl Default for ShippingRateSource {
@@ -62,6 +113,194 @@ pub enum ShippingRateSourceRaw {
     NotAvailable,
     Static,
     OnDemand,
+    Distance,
+}
+
+/// Optional insurance tier of the carrier, the fee is the percentage of the declared value of the shipment
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct InsuranceOption {
+    pub name: String,
+    pub percentage: f64,
+    pub min_fee: Option<f64>,
+    pub max_fee: Option<f64>,
+}
+
+json_schema!(InsuranceOption {
+    name: String,
+    percentage: f64,
+    min_fee: Option<f64>,
+    max_fee: Option<f64>,
+});
+
+impl InsuranceOption {
+    /// Fee of insuring the shipment with the declared value, bounded by the min and max fee
+    pub fn fee(&self, declared_value: f64) -> f64 {
+        let fee = declared_value * self.percentage / 100.0;
+        let fee = self.min_fee.map_or(fee, |min_fee| fee.max(min_fee));
+        self.max_fee.map_or(fee, |max_fee| fee.min(max_fee))
+    }
+}
+
+/// Price quoted when the carrier quote of the company package fails or times out
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, DieselTypes)]
+#[serde(rename_all = "snake_case")]
+pub enum CarrierFallback {
+    /// Stored rates of the company package, with the fallback markup if it is set
+    StoredRates,
+    /// No price, the company package is not offered until the carrier answers
+    NoPrice,
+}
+
+impl Default for CarrierFallback {
+    fn default() -> Self {
+        CarrierFallback::StoredRates
+    }
+}
+
+/// Markup is only set for the fallback to the stored rates
+pub fn validate_carrier_fallback(carrier_fallback: CarrierFallback, fallback_markup_percent: Option<f64>) -> Result<(), ValidationErrors> {
+    if let Some(fallback_markup_percent) = fallback_markup_percent {
+        if carrier_fallback != CarrierFallback::StoredRates {
+            Err(validation_errors!({
+                "fallback_markup_percent": ["fallback_markup_percent" => "Markup is set while stored rates are not the fallback"]
+            }))?;
+        }
+
+        if fallback_markup_percent.is_nan() || fallback_markup_percent < 0.0 {
+            Err(validation_errors!({ "fallback_markup_percent": ["fallback_markup_percent" => "Markup must not be negative"] }))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Fallback of the carrier quote of the company package
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SetCarrierFallback {
+    pub carrier_fallback: CarrierFallback,
+    pub fallback_markup_percent: Option<f64>,
+}
+
+json_schema!(SetCarrierFallback {
+    carrier_fallback: CarrierFallback,
+    fallback_markup_percent: Option<f64>,
+});
+
+impl Validate for SetCarrierFallback {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        validate_carrier_fallback(self.carrier_fallback, self.fallback_markup_percent)
+    }
+}
+
+/// Fee is only set for the company packages supporting the cash on delivery
+pub fn validate_cash_on_delivery(cod_supported: bool, cod_fee: Option<f64>) -> Result<(), ValidationErrors> {
+    if let Some(cod_fee) = cod_fee {
+        if !cod_supported {
+            Err(validation_errors!({ "cod_fee": ["cod_fee" => "Fee is set while cash on delivery is not supported"] }))?;
+        }
+
+        if cod_fee.is_nan() || cod_fee < 0.0 {
+            Err(validation_errors!({ "cod_fee": ["cod_fee" => "Fee must not be negative"] }))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Cash on delivery settings of the company package
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SetCashOnDelivery {
+    pub cod_supported: bool,
+    pub cod_fee: Option<f64>,
+}
+
+json_schema!(SetCashOnDelivery {
+    cod_supported: bool,
+    cod_fee: Option<f64>,
+});
+
+impl Validate for SetCashOnDelivery {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        validate_cash_on_delivery(self.cod_supported, self.cod_fee)
+    }
+}
+
+/// Capacity is either not limited or at least one shipment a day
+pub fn validate_max_daily_shipments(max_daily_shipments: Option<u32>) -> Result<(), ValidationErrors> {
+    match max_daily_shipments {
+        Some(0) => Err(validation_errors!({
+            "max_daily_shipments": ["max_daily_shipments" => "Capacity must be at least one shipment a day"]
+        })),
+        Some(max_daily_shipments) if max_daily_shipments > i32::max_value() as u32 => Err(validation_errors!({
+            "max_daily_shipments": ["max_daily_shipments" => "Value is too big"]
+        })),
+        _ => Ok(()),
+    }
+}
+
+/// Dimensional weight is the volume divided by the factor, so it must be positive
+pub fn validate_dimensional_factor(dimensional_factor: Option<u32>) -> Result<(), ValidationErrors> {
+    match dimensional_factor {
+        Some(0) => Err(validation_errors!({
+            "dimensional_factor": ["dimensional_factor" => "Dimensional factor must be positive"]
+        })),
+        Some(dimensional_factor) if dimensional_factor > i32::max_value() as u32 => Err(validation_errors!({
+            "dimensional_factor": ["dimensional_factor" => "Value is too big"]
+        })),
+        _ => Ok(()),
+    }
+}
+
+/// Cut-off time and daily capacity of the company package
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SetShippingCapacity {
+    pub daily_cutoff_time: Option<NaiveTime>,
+    pub max_daily_shipments: Option<u32>,
+}
+
+json_schema!(SetShippingCapacity {
+    daily_cutoff_time: Option<NaiveTime>,
+    max_daily_shipments: Option<u32>,
+});
+
+impl Validate for SetShippingCapacity {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        validate_max_daily_shipments(self.max_daily_shipments)
+    }
+}
+
+pub fn validate_insurance_options(insurance_options: &[InsuranceOption]) -> Result<(), ValidationErrors> {
+    let mut names = vec![];
+    for option in insurance_options {
+        let message = if option.name.trim().is_empty() {
+            Some("Name of the insurance option must not be empty")
+        } else if names.contains(&option.name) {
+            Some("Name of the insurance option must be unique")
+        } else if option.percentage.is_nan() || option.percentage < 0.0 || option.percentage > 100.0 {
+            Some("Percentage must be between 0 and 100")
+        } else if option
+            .min_fee
+            .iter()
+            .chain(option.max_fee.iter())
+            .any(|fee| fee.is_nan() || *fee < 0.0)
+        {
+            Some("Fee bounds must not be negative")
+        } else if option.min_fee.unwrap_or(0.0) > option.max_fee.unwrap_or(f64::INFINITY) {
+            Some("Minimum fee must not exceed the maximum one")
+        } else {
+            None
+        };
+
+        if let Some(message) = message {
+            return Err(validation_errors!({
+                "insurance_options": ["insurance_options" => format!("{}: {}", message, option.name)]
+            }));
+        }
+
+        names.push(option.name.clone());
+    }
+
+    Ok(())
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -70,9 +309,85 @@ pub struct CompanyPackage {
     pub company_id: CompanyId,
     pub package_id: PackageId,
     pub shipping_rate_source: ShippingRateSource,
+    pub restricted_hazard_classes: Vec<HazardClass>,
+    /// Default delivery time, shipping rates of the route may override it
+    pub delivery_time_min_days: Option<u32>,
+    pub delivery_time_max_days: Option<u32>,
+    /// Order the price is resolved in, the default one of the shipping rate source is used if not set
+    pub rate_resolution_order: Option<Vec<RateResolutionStep>>,
+    /// Insurance tiers offered with the package, the first one is used when the tier is not chosen
+    #[serde(default)]
+    pub insurance_options: Vec<InsuranceOption>,
+    /// Buyers may pay for the parcel on delivery
+    #[serde(default)]
+    pub cod_supported: bool,
+    /// Fee of the cash on delivery, free if not set
+    pub cod_fee: Option<f64>,
+    /// Price quoted when the carrier quote fails
+    #[serde(default)]
+    pub carrier_fallback: CarrierFallback,
+    /// Percentage the stored rates are increased by when they replace the carrier quote
+    pub fallback_markup_percent: Option<f64>,
+    /// Parcels of the orders placed after this time of the day in UTC are shipped on the next day
+    #[serde(default)]
+    pub daily_cutoff_time: Option<NaiveTime>,
+    /// Number of shipments the company package takes a day, not limited if not set
+    #[serde(default)]
+    pub max_daily_shipments: Option<u32>,
+}
+
+impl CompanyPackage {
+    /// Configured price resolution order or the default one
+    pub fn rate_resolution_steps(&self) -> Vec<RateResolutionStep> {
+        self.rate_resolution_order
+            .clone()
+            .unwrap_or_else(|| default_rate_resolution_order(&self.shipping_rate_source))
+    }
+
+    /// Fee of the cash on delivery, `None` if the company package does not support it
+    pub fn cash_on_delivery_fee(&self) -> Option<f64> {
+        if self.cod_supported {
+            Some(self.cod_fee.unwrap_or(0.0))
+        } else {
+            None
+        }
+    }
+
+    /// Insurance tier with the name, the first one if the name is not set
+    pub fn insurance_option(&self, name: Option<&str>) -> Option<&InsuranceOption> {
+        match name {
+            Some(name) => self.insurance_options.iter().find(|option| option.name == name),
+            None => self.insurance_options.first(),
+        }
+    }
+
+    /// Date the parcel of the order placed at `order_time` is shipped on
+    pub fn shipping_date(&self, order_time: DateTime<Utc>) -> NaiveDate {
+        let order_date = order_time.date().naive_utc();
+        match self.daily_cutoff_time {
+            Some(cutoff_time) if order_time.time() > cutoff_time => order_date.succ(),
+            _ => order_date,
+        }
+    }
+
+    /// Company package takes no more shipments on the date with the shipments already booked on it
+    pub fn is_booked_up(&self, shipments_count: i32) -> bool {
+        self.max_daily_shipments.map_or(false, |max_daily_shipments| {
+            i64::from(shipments_count) >= i64::from(max_daily_shipments)
+        })
+    }
+
+    /// Returns hazard classes of the product that this company package does not accept
+    pub fn refused_hazard_classes(&self, hazard_classes: &[HazardClass]) -> Vec<HazardClass> {
+        hazard_classes
+            .iter()
+            .filter(|hazard_class| self.restricted_hazard_classes.contains(hazard_class))
+            .cloned()
+            .collect()
+    }
 }
 
-#[derive(Serialize, Deserialize, Associations, Queryable, Debug)]
+#[derive(Serialize, Deserialize, Associations, Queryable, Clone, Debug)]
 #[table_name = "companies_packages"]
 pub struct CompaniesPackagesRaw {
     pub id: CompanyPackageId,
@@ -80,9 +395,22 @@ pub struct CompaniesPackagesRaw {
     pub package_id: PackageId,
     pub shipping_rate_source: ShippingRateSourceRaw,
     pub dimensional_factor: Option<i32>,
+    pub restricted_hazard_classes: TypedJson<Vec<HazardClass>>,
+    pub distance_pricing: Option<TypedJson<DistancePricing>>,
+    pub delivery_time_min_days: Option<i32>,
+    pub delivery_time_max_days: Option<i32>,
+    pub rate_resolution_order: Option<TypedJson<Vec<RateResolutionStep>>>,
+    pub insurance_options: TypedJson<Vec<InsuranceOption>>,
+    pub cod_supported: bool,
+    pub cod_fee: Option<f64>,
+    pub carrier_fallback: CarrierFallback,
+    pub fallback_markup_percent: Option<f64>,
+    pub daily_cutoff_time: Option<NaiveTime>,
+    pub max_daily_shipments: Option<i32>,
 }
 
 impl CompaniesPackagesRaw {
+    /// JSONB columns are parsed with the row, only the rows breaking the invariants of the shipping rate source fail
     pub fn to_model(self) -> Result<CompanyPackage, FailureError> {
         let CompaniesPackagesRaw {
             id,
@@ -90,43 +418,58 @@ impl CompaniesPackagesRaw {
             package_id,
             shipping_rate_source,
             dimensional_factor,
+            restricted_hazard_classes,
+            distance_pricing,
+            delivery_time_min_days,
+            delivery_time_max_days,
+            rate_resolution_order,
+            insurance_options,
+            cod_supported,
+            cod_fee,
+            carrier_fallback,
+            fallback_markup_percent,
+            daily_cutoff_time,
+            max_daily_shipments,
         } = self;
 
-        match shipping_rate_source {
-            ShippingRateSourceRaw::NotAvailable => Ok(CompanyPackage {
-                id,
-                company_id,
-                package_id,
-                shipping_rate_source: ShippingRateSource::NotAvailable,
-            }),
-            ShippingRateSourceRaw::Static => match dimensional_factor {
-                None => Ok(CompanyPackage {
-                    id,
-                    company_id,
-                    package_id,
-                    shipping_rate_source: ShippingRateSource::Static { dimensional_factor: None },
-                }),
-                Some(dimensional_factor) => {
-                    if dimensional_factor < 0 {
-                        Err(format_err!("Negative dimensional factor value for CompanyPackage with id = {}", id))
-                    } else {
-                        Ok(CompanyPackage {
-                            id,
-                            company_id,
-                            package_id,
-                            shipping_rate_source: ShippingRateSource::Static {
-                                dimensional_factor: Some(dimensional_factor as u32),
-                            },
-                        })
-                    }
+        let dimensional_factor = match dimensional_factor {
+            None => None,
+            Some(dimensional_factor) => {
+                if dimensional_factor < 0 {
+                    return Err(format_err!("Negative dimensional factor value for CompanyPackage with id = {}", id));
                 }
-            },
-            ShippingRateSourceRaw::OnDemand => Err(format_err!(
-                "CompanyPackages with on-demand sources of shipping rates \
-                 are not yet supported (CompanyPackage id = {})",
-                id
-            )),
-        }
+                Some(dimensional_factor as u32)
+            }
+        };
+
+        let shipping_rate_source = match shipping_rate_source {
+            ShippingRateSourceRaw::NotAvailable => ShippingRateSource::NotAvailable,
+            ShippingRateSourceRaw::Static => ShippingRateSource::Static { dimensional_factor },
+            ShippingRateSourceRaw::OnDemand => ShippingRateSource::OnDemand { dimensional_factor },
+            ShippingRateSourceRaw::Distance => {
+                let distance_pricing =
+                    distance_pricing.ok_or(format_err!("Distance pricing is not set for CompanyPackage with id = {}", id))?;
+                ShippingRateSource::Distance(distance_pricing.into_inner())
+            }
+        };
+
+        Ok(CompanyPackage {
+            id,
+            company_id,
+            package_id,
+            shipping_rate_source,
+            restricted_hazard_classes: restricted_hazard_classes.into_inner(),
+            delivery_time_min_days: delivery_time_min_days.map(|days| days as u32),
+            delivery_time_max_days: delivery_time_max_days.map(|days| days as u32),
+            rate_resolution_order: rate_resolution_order.map(TypedJson::into_inner),
+            insurance_options: insurance_options.into_inner(),
+            cod_supported,
+            cod_fee,
+            carrier_fallback,
+            fallback_markup_percent,
+            daily_cutoff_time,
+            max_daily_shipments: max_daily_shipments.map(|max_daily_shipments| max_daily_shipments as u32),
+        })
     }
 }
 
@@ -135,8 +478,46 @@ pub struct NewCompanyPackage {
     pub company_id: CompanyId,
     pub package_id: PackageId,
     pub shipping_rate_source: Option<ShippingRateSource>,
+    #[serde(default)]
+    pub restricted_hazard_classes: Vec<HazardClass>,
+    pub delivery_time_min_days: Option<u32>,
+    pub delivery_time_max_days: Option<u32>,
+    pub rate_resolution_order: Option<Vec<RateResolutionStep>>,
+    #[serde(default)]
+    pub insurance_options: Vec<InsuranceOption>,
+    #[serde(default)]
+    pub cod_supported: bool,
+    pub cod_fee: Option<f64>,
+    #[serde(default)]
+    pub carrier_fallback: CarrierFallback,
+    pub fallback_markup_percent: Option<f64>,
+    #[serde(default)]
+    pub daily_cutoff_time: Option<NaiveTime>,
+    #[serde(default)]
+    pub max_daily_shipments: Option<u32>,
 }
 
+json_schema!(NewCompanyPackage {
+    company_id: CompanyId,
+    package_id: PackageId,
+    shipping_rate_source: Option<ShippingRateSource>,
+    #[default]
+    restricted_hazard_classes: Vec<HazardClass>,
+    delivery_time_min_days: Option<u32>,
+    delivery_time_max_days: Option<u32>,
+    rate_resolution_order: Option<Vec<RateResolutionStep>>,
+    #[default]
+    insurance_options: Vec<InsuranceOption>,
+    #[default]
+    cod_supported: bool,
+    cod_fee: Option<f64>,
+    #[default]
+    carrier_fallback: CarrierFallback,
+    fallback_markup_percent: Option<f64>,
+    daily_cutoff_time: Option<NaiveTime>,
+    max_daily_shipments: Option<u32>,
+});
+
 #[derive(Serialize, Deserialize, Insertable, Clone, Debug)]
 #[table_name = "companies_packages"]
 pub struct NewCompaniesPackagesRaw {
@@ -144,29 +525,92 @@ pub struct NewCompaniesPackagesRaw {
     pub package_id: PackageId,
     pub shipping_rate_source: ShippingRateSourceRaw,
     pub dimensional_factor: Option<i32>,
+    pub restricted_hazard_classes: TypedJson<Vec<HazardClass>>,
+    pub distance_pricing: Option<TypedJson<DistancePricing>>,
+    pub delivery_time_min_days: Option<i32>,
+    pub delivery_time_max_days: Option<i32>,
+    pub rate_resolution_order: Option<TypedJson<Vec<RateResolutionStep>>>,
+    pub insurance_options: TypedJson<Vec<InsuranceOption>>,
+    pub cod_supported: bool,
+    pub cod_fee: Option<f64>,
+    pub carrier_fallback: CarrierFallback,
+    pub fallback_markup_percent: Option<f64>,
+    pub daily_cutoff_time: Option<NaiveTime>,
+    pub max_daily_shipments: Option<i32>,
 }
 
-impl From<NewCompanyPackage> for NewCompaniesPackagesRaw {
-    fn from(new_company_package: NewCompanyPackage) -> Self {
+impl Validate for NewCompanyPackage {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        validate_delivery_time(self.delivery_time_min_days, self.delivery_time_max_days)?;
+
+        if let Some(ref rate_resolution_order) = self.rate_resolution_order {
+            validate_rate_resolution_order(rate_resolution_order)?;
+        }
+
+        validate_insurance_options(&self.insurance_options)?;
+        validate_cash_on_delivery(self.cod_supported, self.cod_fee)?;
+        validate_carrier_fallback(self.carrier_fallback, self.fallback_markup_percent)?;
+        validate_max_daily_shipments(self.max_daily_shipments)?;
+
+        let dimensional_factor = match self.shipping_rate_source {
+            Some(ShippingRateSource::Static { dimensional_factor }) | Some(ShippingRateSource::OnDemand { dimensional_factor }) => {
+                dimensional_factor
+            }
+            Some(ShippingRateSource::Distance(ref distance_pricing)) => return distance_pricing.validate(),
+            _ => None,
+        };
+
+        validate_dimensional_factor(dimensional_factor)
+    }
+}
+
+impl NewCompanyPackage {
+    pub fn to_raw(self) -> NewCompaniesPackagesRaw {
         let NewCompanyPackage {
             company_id,
             package_id,
             shipping_rate_source,
-        } = new_company_package;
-
-        match shipping_rate_source.unwrap_or_default() {
-            ShippingRateSource::NotAvailable => NewCompaniesPackagesRaw {
-                company_id,
-                package_id,
-                shipping_rate_source: ShippingRateSourceRaw::NotAvailable,
-                dimensional_factor: None,
-            },
-            ShippingRateSource::Static { dimensional_factor } => NewCompaniesPackagesRaw {
-                company_id,
-                package_id,
-                shipping_rate_source: ShippingRateSourceRaw::Static,
-                dimensional_factor: dimensional_factor.map(|df| df as i32),
-            },
+            restricted_hazard_classes,
+            delivery_time_min_days,
+            delivery_time_max_days,
+            rate_resolution_order,
+            insurance_options,
+            cod_supported,
+            cod_fee,
+            carrier_fallback,
+            fallback_markup_percent,
+            daily_cutoff_time,
+            max_daily_shipments,
+        } = self;
+
+        let (shipping_rate_source, dimensional_factor, distance_pricing) = match shipping_rate_source.unwrap_or_default() {
+            ShippingRateSource::NotAvailable => (ShippingRateSourceRaw::NotAvailable, None, None),
+            ShippingRateSource::Static { dimensional_factor } => {
+                (ShippingRateSourceRaw::Static, dimensional_factor.map(|df| df as i32), None)
+            }
+            ShippingRateSource::OnDemand { dimensional_factor } => {
+                (ShippingRateSourceRaw::OnDemand, dimensional_factor.map(|df| df as i32), None)
+            }
+            ShippingRateSource::Distance(distance_pricing) => (ShippingRateSourceRaw::Distance, None, Some(TypedJson(distance_pricing))),
+        };
+
+        NewCompaniesPackagesRaw {
+            company_id,
+            package_id,
+            shipping_rate_source,
+            dimensional_factor,
+            restricted_hazard_classes: TypedJson(restricted_hazard_classes),
+            distance_pricing,
+            delivery_time_min_days: delivery_time_min_days.map(|days| days as i32),
+            delivery_time_max_days: delivery_time_max_days.map(|days| days as i32),
+            rate_resolution_order: rate_resolution_order.map(TypedJson),
+            insurance_options: TypedJson(insurance_options),
+            cod_supported,
+            cod_fee,
+            carrier_fallback,
+            fallback_markup_percent,
+            daily_cutoff_time,
+            max_daily_shipments: max_daily_shipments.map(|max_daily_shipments| max_daily_shipments as i32),
         }
     }
 }
@@ -182,21 +626,619 @@ pub struct AvailablePackages {
     pub local_available: bool,
 }
 
+/// Company package of any company, checked against the shipment by the explain mode of the available packages
+#[derive(Debug)]
+pub struct CandidatePackage {
+    pub company_id: CompanyId,
+    /// Company delivers from the country of the shipment
+    pub delivers_from: bool,
+    pub package: Packages,
+    pub available_package: AvailablePackages,
+}
+
+impl CandidatePackage {
+    /// Reasons the company package does not take the shipment whatever its shipping rates are
+    pub fn rejection_reasons(
+        &self,
+        deliveries_from: &Alpha3,
+        measurements: ShipmentMeasurements,
+        suspensions: &[CompanySuspension],
+    ) -> Vec<UnavailabilityReason> {
+        let mut reasons = vec![];
+        if !self.delivers_from {
+            reasons.push(UnavailabilityReason::CountryNotCovered {
+                alpha3: deliveries_from.clone(),
+            });
+        }
+
+        let package = &self.package;
+        let weight_out_of_range = UnavailabilityReason::PackageWeightOutOfRange {
+            min_weight_g: package.min_weight,
+            max_weight_g: package.max_weight,
+        };
+        let volume_out_of_range = UnavailabilityReason::PackageVolumeOutOfRange {
+            min_volume_cubic_cm: package.min_size,
+            max_volume_cubic_cm: package.max_size,
+        };
+        match package.within_limits(measurements) {
+            Ok(()) => {}
+            Err(MeasurementsOutOfRange::WeightOutOfRange { .. }) => reasons.push(weight_out_of_range),
+            Err(MeasurementsOutOfRange::VolumeOutOfRange { .. }) => reasons.push(volume_out_of_range),
+            Err(MeasurementsOutOfRange::VolumeAndWeightOutOfRange { .. }) => {
+                reasons.push(weight_out_of_range);
+                reasons.push(volume_out_of_range);
+            }
+        }
+
+        reasons.extend(
+            suspensions
+                .iter()
+                .filter(|suspension| suspension.applies_to(self.company_id, self.available_package.id))
+                .map(|suspension| UnavailabilityReason::Suspended {
+                    reason: suspension.reason.clone(),
+                    ends_on: suspension.ends_on,
+                }),
+        );
+
+        reasons
+    }
+}
+
+/// Company package not taking the shipment with the reasons why
 #[derive(Serialize, Deserialize, Debug)]
+pub struct RejectedPackage {
+    pub id: CompanyPackageId,
+    pub name: String,
+    pub reasons: Vec<UnavailabilityReason>,
+}
+
+/// Packages available for the shipment, returned by `GET /available_packages` with `with_measurements`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AvailablePackagesForShipment {
+    pub packages: Vec<AvailablePackages>,
+    /// Measurements of the shipment in grams and cubic centimeters the packages were checked against
+    pub measurements: ShipmentMeasurements,
+}
+
+/// Available packages together with all other company packages and the reasons they do not take the shipment
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AvailablePackagesExplanation {
+    pub packages: Vec<AvailablePackages>,
+    pub unavailable_packages: Vec<RejectedPackage>,
+    /// Measurements of the shipment in grams and cubic centimeters the packages were checked against
+    pub measurements: ShipmentMeasurements,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct AvailablePackageForUser {
     pub id: CompanyPackageId,
     pub shipping_id: ShippingId,
     pub name: String,
     pub logo: String,
     pub price: Option<ProductPrice>,
+    /// Carrier cost of the delivery without the store margin, shown only to the managers of the store
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost: Option<ProductPrice>,
+    /// Stable id of the option, set by the endpoints the origin of the delivery is passed to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub option_id: Option<ShippingOptionId>,
     pub currency: Currency,
     pub shipping_variant: ShippingVariant,
     pub base_product_id: BaseProductId,
     pub store_id: StoreId,
+    pub delivery_time_min_days: Option<u32>,
+    pub delivery_time_max_days: Option<u32>,
+    /// Delivery is free for the orders of the buyer starting from this total
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub free_delivery_over: Option<ProductPrice>,
+    /// Set when the price is zeroed as the order total reached `free_delivery_over`
+    #[serde(default)]
+    pub free_shipping: bool,
+    /// Warehouse the package is shipped from, set by the endpoints the origin of the delivery is passed to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delivery_from: Option<Alpha3>,
+    /// Date the parcel is shipped on, the next day for the orders after the cut-off time of the company package
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shipping_date: Option<NaiveDate>,
+    /// Delivery dates estimated by the delivery time counted from the shipping date
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimated_delivery_date_min: Option<NaiveDate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimated_delivery_date_max: Option<NaiveDate>,
+}
+
+impl AvailablePackageForUser {
+    /// Sets the id of the option delivering the package on the route and the origin of the delivery,
+    /// packages shipped from a warehouse of the seller are delivered from it instead of `delivery_from`
+    pub fn with_option_id(mut self, delivery_from: &Alpha3, delivery_to: &Alpha3) -> Self {
+        let delivery_from = self.delivery_from.clone().unwrap_or_else(|| delivery_from.clone());
+        self.option_id = Some(ShippingOptionId::new(self.shipping_id, self.id, &delivery_from, delivery_to));
+        self.delivery_from = Some(delivery_from);
+        self
+    }
+
+    /// Sets the shipping date and the delivery dates estimated from it
+    pub fn with_shipping_date(mut self, shipping_date: NaiveDate) -> Self {
+        let delivery_date = |days: u32| shipping_date + Duration::days(i64::from(days));
+        self.shipping_date = Some(shipping_date);
+        self.estimated_delivery_date_min = self.delivery_time_min_days.map(delivery_date);
+        self.estimated_delivery_date_max = self.delivery_time_max_days.map(delivery_date);
+        self
+    }
+
+    /// Zeroes the price if the order total reaches the free delivery threshold of the product
+    pub fn with_order_total(mut self, order_total: f64) -> Self {
+        if let Some(ProductPrice(threshold)) = self.free_delivery_over {
+            if order_total >= threshold {
+                self.price = Some(ProductPrice(0.0));
+                self.free_shipping = true;
+            }
+        }
+        self
+    }
+}
+
+/// How the origin of the package is chosen when the seller ships it from several warehouses
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OriginPreference {
+    /// The lowest price, the shortest delivery time of the same price
+    Cheapest,
+    /// The shortest maximum delivery time, the lowest price of the same delivery time
+    Fastest,
+}
+
+impl OriginPreference {
+    /// Packages without a price or a delivery time lose to the ones with them
+    fn prefers(self, candidate: &AvailablePackageForUser, current: &AvailablePackageForUser) -> bool {
+        let price = |pkg: &AvailablePackageForUser| pkg.price.map(|price| price.0).unwrap_or(::std::f64::INFINITY);
+        let days = |pkg: &AvailablePackageForUser| pkg.delivery_time_max_days.unwrap_or(::std::u32::MAX);
+
+        match self {
+            OriginPreference::Cheapest => (price(candidate), days(candidate)) < (price(current), days(current)),
+            OriginPreference::Fastest => (days(candidate), price(candidate)) < (days(current), price(current)),
+        }
+    }
+}
+
+impl Default for OriginPreference {
+    fn default() -> Self {
+        OriginPreference::Cheapest
+    }
+}
+
+impl FromStr for OriginPreference {
+    type Err = FailureError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "cheapest" => Ok(OriginPreference::Cheapest),
+            "fastest" => Ok(OriginPreference::Fastest),
+            other => Err(format_err!("Unknown origin preference: {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for OriginPreference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OriginPreference::Cheapest => write!(f, "cheapest"),
+            OriginPreference::Fastest => write!(f, "fastest"),
+        }
+    }
+}
+
+/// Keeps one of the packages shipped by the same company package from the different warehouses of the seller,
+/// the packages keep the order of their first origins
+pub fn select_package_origins(packages: Vec<AvailablePackageForUser>, preference: OriginPreference) -> Vec<AvailablePackageForUser> {
+    let mut selected: Vec<AvailablePackageForUser> = vec![];
+    for pkg in packages {
+        match selected
+            .iter_mut()
+            .find(|selected_pkg| selected_pkg.id == pkg.id && selected_pkg.shipping_variant == pkg.shipping_variant)
+        {
+            Some(selected_pkg) => {
+                if preference.prefers(&pkg, selected_pkg) {
+                    *selected_pkg = pkg;
+                }
+            }
+            None => selected.push(pkg),
+        }
+    }
+
+    selected
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AvailableShippingForUser {
     pub packages: Vec<AvailablePackageForUser>,
     pub pickups: Option<Pickups>,
+    /// Packages filtered out of `packages`, filled only in explain mode
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unavailable_packages: Vec<UnavailablePackageForUser>,
+    /// Measurements of the shipment in grams and cubic centimeters the packages were priced by,
+    /// not set if the measurements are not requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub measurements: Option<ShipmentMeasurements>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type")]
+pub enum UnavailabilityReason {
+    HazardClassRestricted {
+        hazard_class: HazardClass,
+    },
+    /// Hazard class is prohibited by the destination country whatever the carrier accepts
+    ProhibitedInCountry {
+        hazard_class: HazardClass,
+    },
+    CountryWeightLimitExceeded {
+        max_weight_g: u32,
+    },
+    /// Parcel of the volume can not fit within the longest side the destination country accepts
+    CountryDimensionLimitExceeded {
+        max_dimension_cm: u32,
+    },
+    /// Company or company package does not take parcels until the end of the suspension
+    Suspended {
+        reason: String,
+        ends_on: NaiveDate,
+    },
+    /// Company does not deliver from the country
+    CountryNotCovered {
+        alpha3: Alpha3,
+    },
+    PackageWeightOutOfRange {
+        min_weight_g: u32,
+        max_weight_g: u32,
+    },
+    PackageVolumeOutOfRange {
+        min_volume_cubic_cm: u32,
+        max_volume_cubic_cm: u32,
+    },
+    /// Shipping rates of the company package price the parcel to none of the destinations
+    NoShippingRates,
+    /// Cash on delivery is requested while the company package does not support it
+    CashOnDeliveryNotSupported,
+    /// Company package takes no more shipments on the shipping date
+    BookedUp {
+        shipping_date: NaiveDate,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UnavailablePackageForUser {
+    pub id: CompanyPackageId,
+    pub shipping_id: ShippingId,
+    pub name: String,
+    pub reasons: Vec<UnavailabilityReason>,
+}
+
+/// Delivery of the product to a region, aggregated over the countries of the region the package delivers to
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AvailablePackageForRegion {
+    pub id: CompanyPackageId,
+    pub shipping_id: ShippingId,
+    pub name: String,
+    pub logo: String,
+    pub currency: Currency,
+    pub shipping_variant: ShippingVariant,
+    pub base_product_id: BaseProductId,
+    pub store_id: StoreId,
+    /// Countries of the region the package delivers to
+    pub countries: Vec<Alpha3>,
+    /// Price range over the countries, not set if the price is known for none of them
+    pub min_price: Option<ProductPrice>,
+    pub max_price: Option<ProductPrice>,
+    pub delivery_time_min_days: Option<u32>,
+    pub delivery_time_max_days: Option<u32>,
+}
+
+impl AvailablePackageForRegion {
+    fn new(country: Alpha3, pkg: AvailablePackageForUser) -> Self {
+        AvailablePackageForRegion {
+            id: pkg.id,
+            shipping_id: pkg.shipping_id,
+            name: pkg.name,
+            logo: pkg.logo,
+            currency: pkg.currency,
+            shipping_variant: pkg.shipping_variant,
+            base_product_id: pkg.base_product_id,
+            store_id: pkg.store_id,
+            countries: vec![country],
+            min_price: pkg.price,
+            max_price: pkg.price,
+            delivery_time_min_days: pkg.delivery_time_min_days,
+            delivery_time_max_days: pkg.delivery_time_max_days,
+        }
+    }
+
+    fn add_country(&mut self, country: Alpha3, pkg: AvailablePackageForUser) {
+        self.countries.push(country);
+        if let Some(ProductPrice(price)) = pkg.price {
+            self.min_price = Some(ProductPrice(self.min_price.map(|min| min.0.min(price)).unwrap_or(price)));
+            self.max_price = Some(ProductPrice(self.max_price.map(|max| max.0.max(price)).unwrap_or(price)));
+        }
+        if let Some(days) = pkg.delivery_time_min_days {
+            self.delivery_time_min_days = Some(self.delivery_time_min_days.map_or(days, |min| min.min(days)));
+        }
+        if let Some(days) = pkg.delivery_time_max_days {
+            self.delivery_time_max_days = Some(self.delivery_time_max_days.map_or(days, |max| max.max(days)));
+        }
+    }
+}
+
+/// Merges packages available to the countries of the region by shipping
+pub fn aggregate_region_packages(packages_by_country: Vec<(Alpha3, Vec<AvailablePackageForUser>)>) -> Vec<AvailablePackageForRegion> {
+    let mut region_packages: Vec<AvailablePackageForRegion> = vec![];
+    for (country, packages) in packages_by_country {
+        for pkg in packages {
+            match region_packages
+                .iter_mut()
+                .find(|region_pkg| region_pkg.shipping_id == pkg.shipping_id)
+            {
+                Some(region_pkg) => region_pkg.add_country(country.clone(), pkg),
+                None => region_packages.push(AvailablePackageForRegion::new(country.clone(), pkg)),
+            }
+        }
+    }
+
+    region_packages
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AvailableShippingForRegion {
+    /// Code of the region the packages are aggregated over
+    pub region: Alpha3,
+    pub packages: Vec<AvailablePackageForRegion>,
+    /// Measurements of the shipment in grams and cubic centimeters the packages were priced by
+    pub measurements: ShipmentMeasurements,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use chrono::TimeZone;
+    use serde_json;
+
+    use super::*;
+
+    fn candidate(delivers_from: bool) -> CandidatePackage {
+        CandidatePackage {
+            company_id: CompanyId(1),
+            delivers_from,
+            package: Packages {
+                id: PackageId(1),
+                name: "Avia".to_string(),
+                max_size: 1000,
+                min_size: 10,
+                max_weight: 5000,
+                min_weight: 100,
+                deliveries_to: vec![],
+                deliveries_to_zones: vec![],
+                name_translations: vec![],
+            },
+            available_package: AvailablePackages {
+                id: CompanyPackageId(5),
+                name: "UPS-avia".to_string(),
+                logo: "logo".to_string(),
+                deliveries_to: vec![],
+                shipping_rate_source: ShippingRateSource::NotAvailable,
+                currency: Currency::EUR,
+                local_available: false,
+            },
+        }
+    }
+
+    fn package(shipping_id: i32, price: Option<f64>, delivery_time_max_days: u32) -> AvailablePackageForUser {
+        AvailablePackageForUser {
+            id: CompanyPackageId(1),
+            shipping_id: ShippingId(shipping_id),
+            name: "UPS-avia".to_string(),
+            logo: "logo".to_string(),
+            price: price.map(ProductPrice),
+            cost: None,
+            option_id: None,
+            currency: Currency::EUR,
+            shipping_variant: ShippingVariant::International,
+            base_product_id: BaseProductId(1),
+            store_id: StoreId(1),
+            delivery_time_min_days: Some(2),
+            delivery_time_max_days: Some(delivery_time_max_days),
+            free_delivery_over: None,
+            free_shipping: false,
+            delivery_from: None,
+            shipping_date: None,
+            estimated_delivery_date_min: None,
+            estimated_delivery_date_max: None,
+        }
+    }
+
+    #[test]
+    fn candidate_package_is_rejected_with_reasons() {
+        let rus = Alpha3("RUS".to_string());
+        let measurements = |volume_cubic_cm, weight_g| ShipmentMeasurements { volume_cubic_cm, weight_g };
+        let suspension = CompanySuspension {
+            id: 1,
+            company_id: CompanyId(1),
+            company_package_id: None,
+            starts_on: NaiveDate::from_ymd(2019, 3, 1),
+            ends_on: NaiveDate::from_ymd(2019, 3, 31),
+            reason: "Strike".to_string(),
+            created_at: SystemTime::now(),
+        };
+
+        assert!(candidate(true).rejection_reasons(&rus, measurements(100, 1000), &[]).is_empty());
+
+        let reasons = candidate(false).rejection_reasons(&rus, measurements(2000, 1000), &[suspension]);
+        assert_eq!(reasons.len(), 3);
+        match (&reasons[0], &reasons[1], &reasons[2]) {
+            (
+                UnavailabilityReason::CountryNotCovered { alpha3 },
+                UnavailabilityReason::PackageVolumeOutOfRange {
+                    max_volume_cubic_cm: 1000, ..
+                },
+                UnavailabilityReason::Suspended { reason, .. },
+            ) => {
+                assert_eq!(alpha3, &rus);
+                assert_eq!(reason, "Strike");
+            }
+            _ => panic!("Unexpected reasons: {:?}", reasons),
+        }
+    }
+
+    #[test]
+    fn region_packages_are_aggregated_by_shipping() {
+        let deu = Alpha3("DEU".to_string());
+        let fra = Alpha3("FRA".to_string());
+        let packages = aggregate_region_packages(vec![
+            (deu.clone(), vec![package(1, Some(10.0), 5), package(2, None, 7)]),
+            (fra.clone(), vec![package(1, Some(15.0), 9)]),
+        ]);
+
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages[0].countries, vec![deu.clone(), fra]);
+        assert_eq!(packages[0].min_price.map(|price| price.0), Some(10.0));
+        assert_eq!(packages[0].max_price.map(|price| price.0), Some(15.0));
+        assert_eq!(packages[0].delivery_time_max_days, Some(9));
+        assert_eq!(packages[1].countries, vec![deu]);
+        assert!(packages[1].min_price.is_none());
+    }
+
+    #[test]
+    fn cheapest_or_fastest_origin_is_selected() {
+        let from = |alpha3: &str, pkg: AvailablePackageForUser| AvailablePackageForUser {
+            delivery_from: Some(Alpha3(alpha3.to_string())),
+            ..pkg
+        };
+        let packages = vec![
+            from("DEU", package(1, Some(10.0), 9)),
+            from("POL", package(2, Some(8.0), 12)),
+            from("CZE", package(3, Some(12.0), 5)),
+            from("AUT", package(4, None, 2)),
+        ];
+
+        let cheapest = select_package_origins(packages.clone(), OriginPreference::Cheapest);
+        assert_eq!(cheapest.len(), 1);
+        assert_eq!(cheapest[0].delivery_from, Some(Alpha3("POL".to_string())));
+
+        let fastest = select_package_origins(packages, OriginPreference::Fastest);
+        assert_eq!(fastest.len(), 1);
+        assert_eq!(fastest[0].delivery_from, Some(Alpha3("AUT".to_string())));
+    }
+
+    #[test]
+    fn orders_after_cutoff_time_are_shipped_next_day() {
+        let company_package = CompanyPackage {
+            id: CompanyPackageId(1),
+            company_id: CompanyId(1),
+            package_id: PackageId(1),
+            shipping_rate_source: ShippingRateSource::NotAvailable,
+            restricted_hazard_classes: vec![],
+            delivery_time_min_days: Some(2),
+            delivery_time_max_days: Some(5),
+            rate_resolution_order: None,
+            insurance_options: vec![],
+            cod_supported: false,
+            cod_fee: None,
+            carrier_fallback: CarrierFallback::StoredRates,
+            fallback_markup_percent: None,
+            daily_cutoff_time: Some(NaiveTime::from_hms(14, 0, 0)),
+            max_daily_shipments: Some(3),
+        };
+
+        assert_eq!(
+            company_package.shipping_date(Utc.ymd(2019, 4, 16).and_hms(13, 59, 0)),
+            NaiveDate::from_ymd(2019, 4, 16)
+        );
+        assert_eq!(
+            company_package.shipping_date(Utc.ymd(2019, 4, 16).and_hms(14, 1, 0)),
+            NaiveDate::from_ymd(2019, 4, 17)
+        );
+        assert!(!company_package.is_booked_up(2));
+        assert!(company_package.is_booked_up(3));
+
+        let unlimited = CompanyPackage {
+            daily_cutoff_time: None,
+            max_daily_shipments: None,
+            ..company_package
+        };
+        assert_eq!(
+            unlimited.shipping_date(Utc.ymd(2019, 4, 16).and_hms(23, 0, 0)),
+            NaiveDate::from_ymd(2019, 4, 16)
+        );
+        assert!(!unlimited.is_booked_up(1000));
+
+        let package = package(1, Some(10.0), 5).with_shipping_date(NaiveDate::from_ymd(2019, 4, 17));
+        assert_eq!(package.estimated_delivery_date_min, Some(NaiveDate::from_ymd(2019, 4, 19)));
+        assert_eq!(package.estimated_delivery_date_max, Some(NaiveDate::from_ymd(2019, 4, 22)));
+
+        assert!(validate_max_daily_shipments(Some(1)).is_ok());
+        assert!(validate_max_daily_shipments(None).is_ok());
+        assert!(validate_max_daily_shipments(Some(0)).is_err());
+    }
+
+    #[test]
+    fn delivery_is_free_over_threshold() {
+        let package = AvailablePackageForUser {
+            free_delivery_over: Some(ProductPrice(100.0)),
+            ..package(1, Some(10.0), 5)
+        };
+
+        let below = package.with_order_total(99.0);
+        assert_eq!((below.price.map(|price| price.0), below.free_shipping), (Some(10.0), false));
+        let over = below.with_order_total(100.0);
+        assert_eq!((over.price.map(|price| price.0), over.free_shipping), (Some(0.0), true));
+    }
+
+    #[test]
+    fn insurance_fee_is_bounded() {
+        let option = InsuranceOption {
+            name: "standard".to_string(),
+            percentage: 2.0,
+            min_fee: Some(5.0),
+            max_fee: Some(50.0),
+        };
+
+        assert_eq!(option.fee(100.0), 5.0);
+        assert_eq!(option.fee(1000.0), 20.0);
+        assert_eq!(option.fee(10_000.0), 50.0);
+
+        assert!(validate_insurance_options(&[option.clone()]).is_ok());
+        assert!(validate_insurance_options(&[option.clone(), option.clone()]).is_err());
+        assert!(validate_insurance_options(&[InsuranceOption {
+            min_fee: Some(100.0),
+            ..option
+        }])
+        .is_err());
+    }
+
+    #[test]
+    fn cash_on_delivery_fee_requires_support() {
+        assert!(validate_cash_on_delivery(true, Some(1.5)).is_ok());
+        assert!(validate_cash_on_delivery(true, None).is_ok());
+        assert!(validate_cash_on_delivery(false, Some(1.5)).is_err());
+        assert!(validate_cash_on_delivery(true, Some(-1.0)).is_err());
+    }
+
+    #[test]
+    fn fallback_markup_requires_stored_rates() {
+        assert!(validate_carrier_fallback(CarrierFallback::StoredRates, Some(10.0)).is_ok());
+        assert!(validate_carrier_fallback(CarrierFallback::NoPrice, None).is_ok());
+        assert!(validate_carrier_fallback(CarrierFallback::NoPrice, Some(10.0)).is_err());
+        assert!(validate_carrier_fallback(CarrierFallback::StoredRates, Some(-1.0)).is_err());
+    }
+
+    #[test]
+    fn dimensional_factor_must_be_positive() {
+        assert!(validate_dimensional_factor(Some(5000)).is_ok());
+        assert!(validate_dimensional_factor(None).is_ok());
+        assert!(validate_dimensional_factor(Some(0)).is_err());
+        assert!(validate_dimensional_factor(Some(i32::max_value() as u32 + 1)).is_err());
+
+        // negative factors are not even parsed
+        assert!(serde_json::from_str::<ShippingRateSource>(r#"{"Static":{"dimensional_factor":-1}}"#).is_err());
+        assert!(serde_json::from_str::<ShippingRateSource>(r#"{"Static":{"dimensional_factor":5000}}"#).is_ok());
+    }
 }
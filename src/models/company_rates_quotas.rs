@@ -0,0 +1,88 @@
+//! Models for rates quotas - limits of the shipping rates a company may store, so that a single
+//! carrier integration does not take up the database shared by all companies. Storage is counted
+//! in rate entries, every destination row of the rates takes as many entries as it has weight brackets
+use std::time::SystemTime;
+
+use validator::{Validate, ValidationErrors};
+
+use stq_types::CompanyId;
+
+use models::NewShippingRates;
+
+/// Quota set by admins for the company, it overrides the default quota from config
+#[derive(Serialize, Deserialize, Queryable, Clone, Debug, PartialEq)]
+pub struct CompanyRatesQuota {
+    pub company_id: CompanyId,
+    pub max_entries: i64,
+    pub updated_at: SystemTime,
+}
+
+/// Payload replacing the quota of the company
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SetCompanyRatesQuota {
+    pub max_entries: i64,
+}
+
+json_schema!(SetCompanyRatesQuota { max_entries: i64 });
+
+impl Validate for SetCompanyRatesQuota {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        if self.max_entries < 0 {
+            Err(validation_errors!({ "max_entries": ["max_entries" => "Quota must not be negative"] }))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Rate entries stored by the company against its quota. Companies without quota have `max_entries` unset
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RatesQuotaUsage {
+    pub company_id: CompanyId,
+    pub used_entries: i64,
+    pub max_entries: Option<i64>,
+    /// Quota is set by admins for the company rather than taken from config
+    pub overridden: bool,
+}
+
+impl RatesQuotaUsage {
+    /// Checks that `uploaded_entries` fit into the quota on top of the used ones
+    pub fn check_upload(&self, uploaded_entries: i64) -> Result<(), ValidationErrors> {
+        match self.max_entries {
+            Some(max_entries) if self.used_entries + uploaded_entries > max_entries => {
+                Err(validation_errors!({ "payload": ["rates_quota" => format!(
+                    "Rates quota of company {} is exceeded: {} rate entries are stored, {} are uploaded, quota is {}. \
+                     Rate entries are destinations multiplied by weight brackets, reduce the rates or ask admins to raise the quota",
+                    self.company_id, self.used_entries, uploaded_entries, max_entries
+                )] }))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Rate entries the rates take in storage
+pub fn count_rate_entries(rates: &[NewShippingRates]) -> i64 {
+    rates.iter().map(|rates| rates.rates.len() as i64).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(max_entries: Option<i64>) -> RatesQuotaUsage {
+        RatesQuotaUsage {
+            company_id: CompanyId(1),
+            used_entries: 900,
+            max_entries,
+            overridden: false,
+        }
+    }
+
+    #[test]
+    fn upload_must_fit_into_quota() {
+        assert!(usage(Some(1000)).check_upload(100).is_ok());
+        assert!(usage(Some(1000)).check_upload(101).is_err());
+        assert!(usage(None).check_upload(1_000_000).is_ok());
+    }
+}
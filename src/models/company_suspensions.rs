@@ -0,0 +1,151 @@
+//! Models for company suspensions - date ranges a carrier or one of its packages does not take parcels,
+//! e.g. during strikes or customs closures
+use std::time::SystemTime;
+
+use chrono::NaiveDate;
+use validator::{Validate, ValidationErrors};
+
+use stq_types::{CompanyId, CompanyPackageId};
+
+use schema::company_suspensions;
+
+/// Suspension of all packages of the company, or of one company package if it is set.
+/// Both `starts_on` and `ends_on` days are included
+#[derive(Serialize, Deserialize, Queryable, Clone, Debug, PartialEq)]
+pub struct CompanySuspension {
+    pub id: i32,
+    pub company_id: CompanyId,
+    pub company_package_id: Option<CompanyPackageId>,
+    pub starts_on: NaiveDate,
+    pub ends_on: NaiveDate,
+    pub reason: String,
+    pub created_at: SystemTime,
+}
+
+impl CompanySuspension {
+    pub fn is_active_on(&self, date: NaiveDate) -> bool {
+        self.starts_on <= date && date <= self.ends_on
+    }
+
+    pub fn applies_to(&self, company_id: CompanyId, company_package_id: CompanyPackageId) -> bool {
+        self.company_id == company_id && self.company_package_id.map(|id| id == company_package_id).unwrap_or(true)
+    }
+}
+
+fn validate_suspension_dates(starts_on: NaiveDate, ends_on: NaiveDate) -> Result<(), ValidationErrors> {
+    if starts_on > ends_on {
+        Err(validation_errors!({ "ends_on": ["ends_on" => "Suspension must not end before it starts"] }))?;
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Insertable, Clone, Debug)]
+#[table_name = "company_suspensions"]
+pub struct NewCompanySuspension {
+    pub company_id: CompanyId,
+    pub company_package_id: Option<CompanyPackageId>,
+    pub starts_on: NaiveDate,
+    pub ends_on: NaiveDate,
+    pub reason: String,
+}
+
+json_schema!(NewCompanySuspension {
+    company_id: CompanyId,
+    company_package_id: Option<CompanyPackageId>,
+    starts_on: NaiveDate,
+    ends_on: NaiveDate,
+    reason: String,
+});
+
+impl Validate for NewCompanySuspension {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        if self.reason.is_empty() {
+            Err(validation_errors!({ "reason": ["reason" => "Reason must not be empty"] }))?;
+        }
+
+        validate_suspension_dates(self.starts_on, self.ends_on)
+    }
+}
+
+/// Suspension can be shortened, prolonged or explained better, the suspended offering is fixed
+#[derive(Serialize, Deserialize, AsChangeset, Clone, Debug)]
+#[table_name = "company_suspensions"]
+pub struct UpdateCompanySuspension {
+    pub starts_on: Option<NaiveDate>,
+    pub ends_on: Option<NaiveDate>,
+    pub reason: Option<String>,
+}
+
+json_schema!(UpdateCompanySuspension {
+    starts_on: Option<NaiveDate>,
+    ends_on: Option<NaiveDate>,
+    reason: Option<String>,
+});
+
+impl Validate for UpdateCompanySuspension {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        if self.reason.as_ref().map(|reason| reason.is_empty()).unwrap_or(false) {
+            Err(validation_errors!({ "reason": ["reason" => "Reason must not be empty"] }))?;
+        }
+
+        match (self.starts_on, self.ends_on) {
+            (Some(starts_on), Some(ends_on)) => validate_suspension_dates(starts_on, ends_on),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn suspension(company_package_id: Option<CompanyPackageId>) -> CompanySuspension {
+        CompanySuspension {
+            id: 1,
+            company_id: CompanyId(1),
+            company_package_id,
+            starts_on: NaiveDate::from_ymd(2019, 3, 20),
+            ends_on: NaiveDate::from_ymd(2019, 3, 22),
+            reason: "Strike".to_string(),
+            created_at: SystemTime::now(),
+        }
+    }
+
+    #[test]
+    fn is_active_on_range_days_inclusive() {
+        let suspension = suspension(None);
+
+        assert!(!suspension.is_active_on(NaiveDate::from_ymd(2019, 3, 19)));
+        assert!(suspension.is_active_on(NaiveDate::from_ymd(2019, 3, 20)));
+        assert!(suspension.is_active_on(NaiveDate::from_ymd(2019, 3, 22)));
+        assert!(!suspension.is_active_on(NaiveDate::from_ymd(2019, 3, 23)));
+    }
+
+    #[test]
+    fn applies_to_company_or_its_package() {
+        assert!(suspension(None).applies_to(CompanyId(1), CompanyPackageId(5)));
+        assert!(!suspension(None).applies_to(CompanyId(2), CompanyPackageId(5)));
+        assert!(suspension(Some(CompanyPackageId(5))).applies_to(CompanyId(1), CompanyPackageId(5)));
+        assert!(!suspension(Some(CompanyPackageId(5))).applies_to(CompanyId(1), CompanyPackageId(6)));
+    }
+
+    #[test]
+    fn suspension_must_not_end_before_it_starts() {
+        let payload = NewCompanySuspension {
+            company_id: CompanyId(1),
+            company_package_id: None,
+            starts_on: NaiveDate::from_ymd(2019, 3, 22),
+            ends_on: NaiveDate::from_ymd(2019, 3, 20),
+            reason: "Customs closure".to_string(),
+        };
+
+        assert!(payload.validate().is_err());
+        assert!(NewCompanySuspension {
+            ends_on: NaiveDate::from_ymd(2019, 3, 22),
+            ..payload
+        }
+        .validate()
+        .is_ok());
+    }
+}
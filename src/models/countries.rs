@@ -1,13 +1,23 @@
 //! Models contains all structures that are used in different
 //! modules of the app
 //! EAV model countries
-use validator::Validate;
+use std::collections::{BTreeMap, BTreeSet};
+
+use failure::Error as FailureError;
+use failure::Fail;
+use serde_json;
+use validator::{Validate, ValidationErrors};
 
 use stq_types::{Alpha2, Alpha3, CountryLabel};
 
+use errors::Error;
 use models::validation_rules::*;
 use schema::countries;
 
+/// ISO-3166 dataset imported when no countries are passed to the import,
+/// each country has the region it is added to in the countries tree
+pub const ISO_3166_DATASET: &str = include_str!("../../resources/iso3166.json");
+
 /// RawCountry is an object stored in PG, used only for Country tree creation,
 #[derive(Debug, Serialize, Deserialize, Associations, Queryable, Clone)]
 #[table_name = "countries"]
@@ -35,6 +45,85 @@ pub struct NewCountry {
     pub parent: Option<Alpha3>,
 }
 
+json_schema!(NewCountry {
+    label: CountryLabel,
+    level: i32,
+    alpha2: Alpha2,
+    alpha3: Alpha3,
+    numeric: i32,
+    parent: Option<Alpha3>,
+});
+
+/// Country of the ISO-3166 dataset
+#[derive(Serialize, Deserialize, Clone, Validate, Debug)]
+pub struct IsoCountry {
+    #[validate(custom = "validate_alpha2")]
+    pub alpha2: Alpha2,
+    #[validate(custom = "validate_alpha3")]
+    pub alpha3: Alpha3,
+    pub numeric: i32,
+    pub name: String,
+    /// Region the country is added to if it is not in the tree yet, the root of the tree if not set.
+    /// Countries already in the tree stay in their regions
+    pub region: Option<Alpha3>,
+}
+
+json_schema!(IsoCountry {
+    alpha2: Alpha2,
+    alpha3: Alpha3,
+    numeric: i32,
+    name: String,
+    region: Option<Alpha3>,
+});
+
+/// Payload importing the ISO-3166 dataset, the embedded dataset is imported if countries are not passed
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ImportCountries {
+    pub countries: Option<Vec<IsoCountry>>,
+}
+
+json_schema!(ImportCountries {
+    countries: Option<Vec<IsoCountry>>,
+});
+
+impl Validate for ImportCountries {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let countries = match self.countries {
+            Some(ref countries) => countries,
+            None => return Ok(()),
+        };
+
+        for (i, country) in countries.iter().enumerate() {
+            country.validate()?;
+
+            if countries[..i].iter().any(|other| other.alpha3 == country.alpha3) {
+                Err(validation_errors!({ "countries": ["countries" => format!("Country {} is imported twice", country.alpha3)] }))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ImportCountries {
+    /// Countries of the payload or of the embedded dataset
+    pub fn into_countries(self) -> Result<Vec<IsoCountry>, FailureError> {
+        match self.countries {
+            Some(countries) => Ok(countries),
+            None => serde_json::from_str(ISO_3166_DATASET)
+                .map_err(|e| e.context("Can not parse embedded ISO-3166 dataset").context(Error::Parse).into()),
+        }
+    }
+}
+
+/// Numbers of countries created, updated and left as they are by the import
+#[derive(Default, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CountriesImportReport {
+    pub created: u32,
+    pub updated: u32,
+    pub skipped: u32,
+}
+
 #[derive(Default, Debug, Serialize, Deserialize, Clone)]
 pub struct Country {
     pub label: CountryLabel,
@@ -81,6 +170,141 @@ impl<'a> From<&'a RawCountry> for Country {
     }
 }
 
+/// Country whose parent is not in the countries table
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MissingParent {
+    pub alpha3: Alpha3,
+    pub parent: Alpha3,
+}
+
+/// Problems of the countries tree. Countries not reachable from the root are left out of the tree
+/// and so are never available for delivery
+#[derive(Default, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CountriesTreeValidation {
+    pub root: Option<Alpha3>,
+    /// Countries not reachable from the root
+    pub orphans: Vec<Alpha3>,
+    /// Top-most orphans, re-parenting them attaches all orphans back to the tree
+    pub detached: Vec<Alpha3>,
+    pub missing_parents: Vec<MissingParent>,
+    /// Countries of each cycle of parents
+    pub cycles: Vec<Vec<Alpha3>>,
+    pub duplicate_alpha2: Vec<Alpha2>,
+    pub duplicate_alpha3: Vec<Alpha3>,
+}
+
+impl CountriesTreeValidation {
+    pub fn is_valid(&self) -> bool {
+        self.root.is_some()
+            && self.orphans.is_empty()
+            && self.missing_parents.is_empty()
+            && self.cycles.is_empty()
+            && self.duplicate_alpha2.is_empty()
+            && self.duplicate_alpha3.is_empty()
+    }
+}
+
+/// Countries re-parented by the tree repair
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CountriesTreeRepair {
+    pub parent: Alpha3,
+    pub reparented: Vec<Alpha3>,
+}
+
+/// Walks the flat list of countries from each country up to the root. The root is the country
+/// without a parent with the lowest level, other countries without a parent are orphans
+pub fn validate_countries_tree(countries: &[Country]) -> CountriesTreeValidation {
+    let mut parents = BTreeMap::<&str, Option<&str>>::new();
+    for country in countries {
+        parents
+            .entry(country.alpha3.0.as_str())
+            .or_insert_with(|| country.parent.as_ref().map(|parent| parent.0.as_str()));
+    }
+
+    let root = countries
+        .iter()
+        .filter(|country| country.parent.is_none())
+        .min_by_key(|country| country.level)
+        .map(|country| country.alpha3.0.as_str());
+
+    let mut orphans = BTreeSet::new();
+    let mut detached = BTreeSet::new();
+    let mut missing_parents = vec![];
+    let mut cycles = BTreeSet::new();
+    for (&code, _) in &parents {
+        let mut path: Vec<&str> = vec![code];
+        loop {
+            let current = path[path.len() - 1];
+            if Some(current) == root {
+                break;
+            }
+
+            match parents.get(current).cloned().and_then(|parent| parent) {
+                None => {
+                    // country without a parent other than the root
+                    detached.insert(current);
+                }
+                Some(parent) if !parents.contains_key(parent) => {
+                    if current == code {
+                        missing_parents.push(MissingParent {
+                            alpha3: Alpha3(code.to_string()),
+                            parent: Alpha3(parent.to_string()),
+                        });
+                    }
+                    detached.insert(current);
+                }
+                Some(parent) => {
+                    if let Some(position) = path.iter().position(|visited| *visited == parent) {
+                        // cycles are listed starting from their lowest code, so each is listed once
+                        let mut cycle = path[position..].to_vec();
+                        let start = cycle.iter().enumerate().min_by_key(|(_, code)| **code).map(|(i, _)| i).unwrap_or(0);
+                        cycle.rotate_left(start);
+                        detached.insert(cycle[0]);
+                        cycles.insert(cycle);
+                    } else {
+                        path.push(parent);
+                        continue;
+                    }
+                }
+            }
+
+            orphans.insert(code);
+            break;
+        }
+    }
+
+    let mut alpha2_counts = BTreeMap::<&str, usize>::new();
+    let mut alpha3_counts = BTreeMap::<&str, usize>::new();
+    for country in countries {
+        // regions have no alpha2 code
+        if !country.alpha2.0.is_empty() {
+            *alpha2_counts.entry(country.alpha2.0.as_str()).or_insert(0) += 1;
+        }
+        *alpha3_counts.entry(country.alpha3.0.as_str()).or_insert(0) += 1;
+    }
+
+    CountriesTreeValidation {
+        root: root.map(|code| Alpha3(code.to_string())),
+        orphans: orphans.into_iter().map(|code| Alpha3(code.to_string())).collect(),
+        detached: detached.into_iter().map(|code| Alpha3(code.to_string())).collect(),
+        missing_parents,
+        cycles: cycles
+            .into_iter()
+            .map(|cycle| cycle.into_iter().map(|code| Alpha3(code.to_string())).collect())
+            .collect(),
+        duplicate_alpha2: alpha2_counts
+            .into_iter()
+            .filter(|(_, count)| *count > 1)
+            .map(|(code, _)| Alpha2(code.to_string()))
+            .collect(),
+        duplicate_alpha3: alpha3_counts
+            .into_iter()
+            .filter(|(_, count)| *count > 1)
+            .map(|(code, _)| Alpha3(code.to_string()))
+            .collect(),
+    }
+}
+
 pub fn get_country(country: &Country, country_id: &Alpha3) -> Option<Country> {
     if country.alpha3 == *country_id {
         Some(country.clone())
@@ -129,3 +353,102 @@ where
 {
     countries.fold(vec, |vec, country| get_countries_by_inner(country, predicate, vec))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embedded_dataset_is_valid() {
+        let payload = ImportCountries { countries: None };
+        let countries = payload.into_countries().unwrap();
+
+        assert_eq!(countries.len(), 250);
+        let payload = ImportCountries {
+            countries: Some(countries),
+        };
+        assert!(payload.validate().is_ok());
+    }
+
+    fn country(code: &str, level: i32, parent: Option<&str>) -> Country {
+        Country {
+            label: code.to_string().into(),
+            level,
+            alpha2: Alpha2(code[..2].to_string()),
+            alpha3: Alpha3(code.to_string()),
+            parent: parent.map(|parent| Alpha3(parent.to_string())),
+            ..Default::default()
+        }
+    }
+
+    fn codes(codes: &[&str]) -> Vec<Alpha3> {
+        codes.iter().map(|code| Alpha3(code.to_string())).collect()
+    }
+
+    #[test]
+    fn valid_tree() {
+        let countries = vec![
+            country("XAL", 0, None),
+            country("XEU", 1, Some("XAL")),
+            country("RUS", 2, Some("XEU")),
+        ];
+
+        let validation = validate_countries_tree(&countries);
+
+        assert_eq!(validation.root, Some(Alpha3("XAL".to_string())));
+        assert!(validation.is_valid());
+    }
+
+    #[test]
+    fn tree_problems_are_reported() {
+        let countries = vec![
+            country("XAL", 0, None),
+            country("XEU", 1, Some("XAL")),
+            country("RUS", 2, Some("XEU")),
+            // parent is missing, child of the country is an orphan too
+            country("DEU", 2, Some("XXX")),
+            country("DEA", 2, Some("DEU")),
+            // cycle
+            country("FRA", 2, Some("ITA")),
+            country("ITA", 2, Some("FRA")),
+            // second root
+            country("USA", 1, None),
+            country("RUS", 2, Some("XEU")),
+        ];
+
+        let validation = validate_countries_tree(&countries);
+
+        assert_eq!(validation.orphans, codes(&["DEA", "DEU", "FRA", "ITA", "USA"]));
+        assert_eq!(validation.detached, codes(&["DEU", "FRA", "USA"]));
+        assert_eq!(
+            validation.missing_parents,
+            vec![MissingParent {
+                alpha3: Alpha3("DEU".to_string()),
+                parent: Alpha3("XXX".to_string()),
+            }]
+        );
+        assert_eq!(validation.cycles, vec![codes(&["FRA", "ITA"])]);
+        assert_eq!(
+            validation.duplicate_alpha2,
+            vec![Alpha2("DE".to_string()), Alpha2("RU".to_string())]
+        );
+        assert_eq!(validation.duplicate_alpha3, codes(&["RUS"]));
+        assert!(!validation.is_valid());
+    }
+
+    #[test]
+    fn duplicate_countries_are_rejected() {
+        let country = IsoCountry {
+            alpha2: Alpha2("RU".to_string()),
+            alpha3: Alpha3("RUS".to_string()),
+            numeric: 643,
+            name: "Russian Federation".to_string(),
+            region: None,
+        };
+        let payload = ImportCountries {
+            countries: Some(vec![country.clone(), country]),
+        };
+
+        assert!(payload.validate().is_err());
+    }
+}
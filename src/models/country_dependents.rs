@@ -0,0 +1,169 @@
+//! Models for data denormalized from the countries tree. Products, packages and companies keep copies
+//! of country codes, the copies are refreshed by `country_dependents` every time the tree changes
+use std::collections::BTreeSet;
+use std::fmt;
+
+use sha3::{Digest, Sha3_256};
+
+use stq_types::Alpha3;
+
+use models::authorization::Resource;
+use models::{BackfillProgress, Country};
+
+/// Number of hash bytes kept in the tree version
+const TREE_VERSION_BYTES: usize = 8;
+
+/// Column keeping country codes copied from the countries tree
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CountryDependent {
+    CompaniesDeliveriesFrom,
+    PackagesDeliveriesTo,
+    ProductsDeliveriesTo,
+}
+
+impl CountryDependent {
+    pub fn all() -> Vec<CountryDependent> {
+        vec![
+            CountryDependent::CompaniesDeliveriesFrom,
+            CountryDependent::PackagesDeliveriesTo,
+            CountryDependent::ProductsDeliveriesTo,
+        ]
+    }
+
+    /// Resource the rows of the dependent belong to
+    pub fn resource(self) -> Resource {
+        match self {
+            CountryDependent::CompaniesDeliveriesFrom => Resource::Companies,
+            CountryDependent::PackagesDeliveriesTo => Resource::Packages,
+            CountryDependent::ProductsDeliveriesTo => Resource::Products,
+        }
+    }
+
+    /// Name of the backfill refreshing the dependent against the version of the countries tree.
+    /// Refresh of every version is tracked separately, so a finished refresh is not run again
+    /// until the tree changes
+    pub fn refresh_name(self, tree_version: &str) -> String {
+        format!("{}-{}", self, tree_version)
+    }
+}
+
+impl fmt::Display for CountryDependent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CountryDependent::CompaniesDeliveriesFrom => write!(f, "companies_deliveries_from"),
+            CountryDependent::PackagesDeliveriesTo => write!(f, "packages_deliveries_to"),
+            CountryDependent::ProductsDeliveriesTo => write!(f, "products_deliveries_to"),
+        }
+    }
+}
+
+/// Country codes of one row of the dependent
+#[derive(Clone, Debug, PartialEq)]
+pub struct CountryCodesRow {
+    pub id: i32,
+    pub codes: Vec<Alpha3>,
+}
+
+impl CountryCodesRow {
+    /// Codes of the row without the ones missing from the tree, e.g. renamed or removed countries.
+    /// Returns `None` if all codes are known and the row doesn't need an update
+    pub fn without_unknown_codes(&self, known_codes: &BTreeSet<String>) -> Option<Vec<Alpha3>> {
+        let codes = self
+            .codes
+            .iter()
+            .filter(|code| known_codes.contains(&code.0))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if codes.len() == self.codes.len() {
+            None
+        } else {
+            Some(codes)
+        }
+    }
+}
+
+/// Refresh progress of the dependent against the current version of the countries tree,
+/// progress is not set if the refresh has not started yet
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CountryDependentRefresh {
+    pub dependent: CountryDependent,
+    pub tree_version: String,
+    pub progress: Option<BackfillProgress>,
+}
+
+/// Version of the countries tree, changes with any code, label or parent of the flat list of countries
+pub fn countries_tree_version(countries: &[Country]) -> String {
+    let mut lines = countries
+        .iter()
+        .map(|country| {
+            format!(
+                "{}:{}:{}:{}:{}",
+                country.alpha3.0,
+                country.alpha2.0,
+                country.numeric,
+                country.label.0,
+                country.parent.as_ref().map(|parent| parent.0.as_str()).unwrap_or("")
+            )
+        })
+        .collect::<Vec<_>>();
+    lines.sort();
+
+    let mut hasher = Sha3_256::default();
+    hasher.input(lines.join("\n").as_bytes());
+
+    hasher
+        .result()
+        .iter()
+        .take(TREE_VERSION_BYTES)
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use stq_types::{Alpha2, CountryLabel};
+
+    use super::*;
+
+    fn country(alpha3: &str, parent: Option<&str>) -> Country {
+        Country {
+            label: CountryLabel(alpha3.to_lowercase()),
+            alpha2: Alpha2(alpha3[..2].to_string()),
+            alpha3: Alpha3(alpha3.to_string()),
+            parent: parent.map(|parent| Alpha3(parent.to_string())),
+            ..Country::default()
+        }
+    }
+
+    #[test]
+    fn tree_version_changes_with_the_tree() {
+        let countries = vec![country("XAL", None), country("RUS", Some("XAL")), country("USA", Some("XAL"))];
+        let reordered = vec![countries[2].clone(), countries[0].clone(), countries[1].clone()];
+        let renamed = vec![countries[0].clone(), countries[1].clone(), country("UST", Some("XAL"))];
+
+        assert_eq!(countries_tree_version(&countries), countries_tree_version(&reordered));
+        assert_ne!(countries_tree_version(&countries), countries_tree_version(&renamed));
+        assert_eq!(countries_tree_version(&countries).len(), TREE_VERSION_BYTES * 2);
+    }
+
+    #[test]
+    fn unknown_codes_are_dropped() {
+        let known_codes = vec!["RUS".to_string(), "USA".to_string()].into_iter().collect::<BTreeSet<_>>();
+        let row = CountryCodesRow {
+            id: 1,
+            codes: vec![Alpha3("RUS".to_string()), Alpha3("SUN".to_string())],
+        };
+
+        assert_eq!(row.without_unknown_codes(&known_codes), Some(vec![Alpha3("RUS".to_string())]));
+        assert_eq!(
+            CountryCodesRow {
+                id: 2,
+                codes: vec![Alpha3("USA".to_string())],
+            }
+            .without_unknown_codes(&known_codes),
+            None
+        );
+    }
+}
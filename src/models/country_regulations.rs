@@ -0,0 +1,192 @@
+//! Models for parcel regulations of the destination countries. They are applied to availability
+//! on top of the limits declared by carriers, so a package is not offered for a parcel the country does not accept
+use failure::Error as FailureError;
+use failure::Fail;
+use serde_json;
+use validator::{Validate, ValidationErrors};
+
+use stq_types::Alpha3;
+
+use errors::Error;
+use models::{HazardClass, ShipmentMeasurements, UnavailabilityReason};
+use schema::country_regulations;
+
+#[derive(Serialize, Deserialize, Queryable, Insertable, Debug)]
+#[table_name = "country_regulations"]
+pub struct CountryRegulationRaw {
+    pub alpha3: Alpha3,
+    pub max_weight_g: Option<i32>,
+    pub prohibited_hazard_classes: serde_json::Value,
+    pub source: String,
+    pub max_dimension_cm: Option<i32>,
+}
+
+/// Parcel regulations of the destination country, limits are not set if the country has none
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CountryRegulation {
+    pub alpha3: Alpha3,
+    pub max_weight_g: Option<u32>,
+    /// Longest side of the parcel
+    pub max_dimension_cm: Option<u32>,
+    pub prohibited_hazard_classes: Vec<HazardClass>,
+    /// Where the regulations come from, e.g. the postal union or the national post
+    pub source: String,
+}
+
+impl CountryRegulationRaw {
+    pub fn to_model(self) -> Result<CountryRegulation, FailureError> {
+        let CountryRegulationRaw {
+            alpha3,
+            max_weight_g,
+            prohibited_hazard_classes,
+            source,
+            max_dimension_cm,
+        } = self;
+
+        let prohibited_hazard_classes = serde_json::from_value(prohibited_hazard_classes).map_err(|e| {
+            e.context(format!("Can not parse prohibited_hazard_classes of country regulation {}", alpha3))
+                .context(Error::Parse)
+        })?;
+
+        Ok(CountryRegulation {
+            alpha3,
+            max_weight_g: max_weight_g.map(|value| value as u32),
+            max_dimension_cm: max_dimension_cm.map(|value| value as u32),
+            prohibited_hazard_classes,
+            source,
+        })
+    }
+}
+
+impl CountryRegulation {
+    /// Reasons the parcel is not accepted by the country. Limits are checked only if the measurements are known.
+    /// Parcels are measured by volume, so the dimension limit rejects the parcels whose volume does not fit
+    /// in a cube with the side of the limit: the longest side of such a parcel exceeds the limit whatever its shape
+    pub fn violations(&self, hazard_classes: &[HazardClass], measurements: Option<ShipmentMeasurements>) -> Vec<UnavailabilityReason> {
+        let mut reasons = hazard_classes
+            .iter()
+            .filter(|hazard_class| self.prohibited_hazard_classes.contains(hazard_class))
+            .map(|hazard_class| UnavailabilityReason::ProhibitedInCountry {
+                hazard_class: *hazard_class,
+            })
+            .collect::<Vec<_>>();
+
+        if let Some(measurements) = measurements {
+            if let Some(max_weight_g) = self.max_weight_g {
+                if measurements.weight_g > max_weight_g {
+                    reasons.push(UnavailabilityReason::CountryWeightLimitExceeded { max_weight_g });
+                }
+            }
+
+            if let Some(max_dimension_cm) = self.max_dimension_cm {
+                if f64::from(measurements.volume_cubic_cm).cbrt() > f64::from(max_dimension_cm) {
+                    reasons.push(UnavailabilityReason::CountryDimensionLimitExceeded { max_dimension_cm });
+                }
+            }
+        }
+
+        reasons
+    }
+}
+
+/// Payload replacing the regulations of the country
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SetCountryRegulation {
+    pub max_weight_g: Option<u32>,
+    pub max_dimension_cm: Option<u32>,
+    #[serde(default)]
+    pub prohibited_hazard_classes: Vec<HazardClass>,
+    pub source: String,
+}
+
+json_schema!(SetCountryRegulation {
+    max_weight_g: Option<u32>,
+    max_dimension_cm: Option<u32>,
+    #[default]
+    prohibited_hazard_classes: Vec<HazardClass>,
+    source: String,
+});
+
+impl Validate for SetCountryRegulation {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        if self.max_weight_g.map(|value| value > i32::max_value() as u32).unwrap_or(false) {
+            Err(validation_errors!({ "max_weight_g": ["max_weight_g" => "Value is too big"] }))?;
+        }
+
+        if self.max_dimension_cm.map(|value| value > i32::max_value() as u32).unwrap_or(false) {
+            Err(validation_errors!({ "max_dimension_cm": ["max_dimension_cm" => "Value is too big"] }))?;
+        }
+
+        if self.source.trim().is_empty() {
+            Err(validation_errors!({ "source": ["source" => "Source must not be empty"] }))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl SetCountryRegulation {
+    pub fn to_raw(self, alpha3: Alpha3) -> Result<CountryRegulationRaw, FailureError> {
+        let prohibited_hazard_classes = serde_json::to_value(self.prohibited_hazard_classes).map_err(|e| {
+            e.context("Can not parse prohibited_hazard_classes from value")
+                .context(Error::Parse)
+        })?;
+
+        Ok(CountryRegulationRaw {
+            alpha3,
+            max_weight_g: self.max_weight_g.map(|value| value as i32),
+            prohibited_hazard_classes,
+            source: self.source,
+            max_dimension_cm: self.max_dimension_cm.map(|value| value as i32),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn regulation() -> CountryRegulation {
+        CountryRegulation {
+            alpha3: Alpha3("RUS".to_string()),
+            max_weight_g: Some(20000),
+            max_dimension_cm: Some(100),
+            prohibited_hazard_classes: vec![HazardClass::Aerosols],
+            source: "UPU Convention".to_string(),
+        }
+    }
+
+    #[test]
+    fn violations_of_hazard_classes_and_limits() {
+        let measurements = ShipmentMeasurements {
+            volume_cubic_cm: 1100000,
+            weight_g: 25000,
+        };
+
+        let reasons = regulation().violations(&[HazardClass::LithiumBatteries, HazardClass::Aerosols], Some(measurements));
+
+        assert_eq!(
+            serde_json::to_value(reasons).unwrap(),
+            json!([
+                { "type": "ProhibitedInCountry", "hazard_class": "Aerosols" },
+                { "type": "CountryWeightLimitExceeded", "max_weight_g": 20000 },
+                { "type": "CountryDimensionLimitExceeded", "max_dimension_cm": 100 },
+            ])
+        );
+    }
+
+    #[test]
+    fn parcel_fitting_in_cube_of_max_dimension_is_accepted() {
+        let measurements = ShipmentMeasurements {
+            volume_cubic_cm: 1000000,
+            weight_g: 1000,
+        };
+
+        assert!(regulation().violations(&[], Some(measurements)).is_empty());
+    }
+
+    #[test]
+    fn limits_are_not_checked_without_measurements() {
+        assert!(regulation().violations(&[HazardClass::LithiumBatteries], None).is_empty());
+    }
+}
@@ -0,0 +1,117 @@
+//! Models for the subdivisions of the countries - states, regions, provinces - with their ISO 3166-2 codes.
+//! Subdivisions are the fourth level under the root, the regions and the countries, but they are kept
+//! out of the countries tree: leaves of the tree are the countries the packages deliver to, subdivisions
+//! are only referenced by the rates and the addresses where prices within a country differ
+use validator::{Validate, ValidationErrors};
+
+use stq_types::{Alpha2, Alpha3};
+
+use schema::country_subdivisions;
+
+#[derive(Serialize, Deserialize, Queryable, Insertable, Clone, Debug, PartialEq)]
+#[table_name = "country_subdivisions"]
+pub struct CountrySubdivision {
+    /// ISO 3166-2 code - alpha2 code of the country and the code of the subdivision, e.g. `US-CA`
+    pub code: String,
+    /// Country of the subdivision
+    pub alpha3: Alpha3,
+    pub name: String,
+}
+
+/// Payload creating subdivision of the country
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NewCountrySubdivision {
+    pub code: String,
+    pub name: String,
+}
+
+json_schema!(NewCountrySubdivision {
+    code: String,
+    name: String
+});
+
+impl Validate for NewCountrySubdivision {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        if subdivision_country_code(&self.code).is_none() {
+            Err(validation_errors!({
+                "code": ["code" => format!("{} is not an ISO 3166-2 subdivision code", self.code)]
+            }))?;
+        }
+
+        if self.name.trim().is_empty() {
+            Err(validation_errors!({ "name": ["name" => "Name must not be empty"] }))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl NewCountrySubdivision {
+    pub fn into_subdivision(self, alpha3: Alpha3) -> CountrySubdivision {
+        CountrySubdivision {
+            code: normalize_subdivision_code(&self.code),
+            alpha3,
+            name: self.name,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, AsChangeset, Clone, Debug, Validate)]
+#[table_name = "country_subdivisions"]
+pub struct UpdateCountrySubdivision {
+    #[validate(length(min = "1", message = "Name must not be empty"))]
+    pub name: String,
+}
+
+json_schema!(UpdateCountrySubdivision { name: String });
+
+/// Codes are stored uppercased, so that `us-ca` and `US-CA` reference the same subdivision
+pub fn normalize_subdivision_code(code: &str) -> String {
+    code.trim().to_uppercase()
+}
+
+/// Alpha2 code of the country the ISO 3166-2 code is of, `None` if the code is not an ISO 3166-2 one
+pub fn subdivision_country_code(code: &str) -> Option<Alpha2> {
+    let code = normalize_subdivision_code(code);
+    let mut parts = code.splitn(2, '-');
+    let country = parts.next().unwrap_or_default();
+    let subdivision = parts.next().unwrap_or_default();
+
+    let is_country = country.len() == 2 && country.chars().all(|c| c.is_ascii_alphabetic());
+    let is_subdivision = !subdivision.is_empty() && subdivision.len() <= 3 && subdivision.chars().all(|c| c.is_ascii_alphanumeric());
+
+    if is_country && is_subdivision {
+        Some(Alpha2(country.to_string()))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subdivision_codes_are_iso_3166_2_codes() {
+        assert_eq!(subdivision_country_code("US-CA"), Some(Alpha2("US".to_string())));
+        assert_eq!(subdivision_country_code(" ru-mow "), Some(Alpha2("RU".to_string())));
+        assert_eq!(subdivision_country_code("GB-ENG"), Some(Alpha2("GB".to_string())));
+
+        assert_eq!(subdivision_country_code("USA-CA"), None);
+        assert_eq!(subdivision_country_code("US-"), None);
+        assert_eq!(subdivision_country_code("US-CALI"), None);
+        assert_eq!(subdivision_country_code("USCA"), None);
+    }
+
+    #[test]
+    fn new_subdivision_is_stored_with_normalized_code() {
+        let payload = NewCountrySubdivision {
+            code: "us-ca".to_string(),
+            name: "California".to_string(),
+        };
+        assert!(payload.validate().is_ok());
+
+        let subdivision = payload.into_subdivision(Alpha3("USA".to_string()));
+        assert_eq!(subdivision.code, "US-CA");
+    }
+}
@@ -0,0 +1,113 @@
+//! Models for customs info - customs data of the base product for cross-border shipments, order fulfillment
+//! fills the CN22/CN23 forms with it
+use validator::{Validate, ValidationErrors};
+
+use stq_static_resources::Currency;
+use stq_types::{Alpha3, BaseProductId, StoreId};
+
+use schema::customs_info;
+
+/// Customs data of the base product, the quantity and the weight are taken from the order
+#[derive(Serialize, Deserialize, Queryable, Clone, Debug, PartialEq)]
+pub struct CustomsInfo {
+    pub id: i32,
+    pub base_product_id: BaseProductId,
+    pub store_id: StoreId,
+    /// Harmonized System code of the goods
+    pub hs_code: String,
+    /// Description of the goods for the customs declaration
+    pub description: String,
+    /// Value of a single item
+    pub declared_value: f64,
+    pub currency: Currency,
+    /// Country the goods are made in
+    pub origin_country: Alpha3,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "customs_info"]
+pub struct NewCustomsInfo {
+    pub base_product_id: BaseProductId,
+    pub store_id: StoreId,
+    pub hs_code: String,
+    pub description: String,
+    pub declared_value: f64,
+    pub currency: Currency,
+    pub origin_country: Alpha3,
+}
+
+/// Payload replacing the customs data of the base product
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SetCustomsInfo {
+    pub store_id: StoreId,
+    pub hs_code: String,
+    pub description: String,
+    pub declared_value: f64,
+    pub currency: Currency,
+    pub origin_country: Alpha3,
+}
+
+json_schema!(SetCustomsInfo {
+    store_id: StoreId,
+    hs_code: String,
+    description: String,
+    declared_value: f64,
+    currency: Currency,
+    origin_country: Alpha3,
+});
+
+impl Validate for SetCustomsInfo {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        // HS codes are 6 digits internationally, countries extend them up to 10 digits
+        let hs_code_length = self.hs_code.len();
+        if hs_code_length < 6 || hs_code_length > 10 || !self.hs_code.chars().all(|c| c.is_ascii_digit()) {
+            Err(validation_errors!({ "hs_code": ["hs_code" => "HS code must consist of 6 to 10 digits"] }))?;
+        }
+
+        if self.description.trim().is_empty() {
+            Err(validation_errors!({ "description": ["description" => "Description must not be empty"] }))?;
+        }
+
+        if self.declared_value.is_nan() || self.declared_value < 0.0 {
+            Err(validation_errors!({ "declared_value": ["declared_value" => "Value must not be negative"] }))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl SetCustomsInfo {
+    pub fn to_new(self, base_product_id: BaseProductId) -> NewCustomsInfo {
+        NewCustomsInfo {
+            base_product_id,
+            store_id: self.store_id,
+            hs_code: self.hs_code,
+            description: self.description,
+            declared_value: self.declared_value,
+            currency: self.currency,
+            origin_country: self.origin_country,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hs_code_is_validated() {
+        let payload = |hs_code: &str| SetCustomsInfo {
+            store_id: StoreId(1),
+            hs_code: hs_code.to_string(),
+            description: "Cotton t-shirts".to_string(),
+            declared_value: 15.0,
+            currency: Currency::USD,
+            origin_country: Alpha3("CHN".to_string()),
+        };
+
+        assert!(payload("610910").validate().is_ok());
+        assert!(payload("6109100010").validate().is_ok());
+        assert!(payload("6109").validate().is_err());
+        assert!(payload("6109.10").validate().is_err());
+    }
+}
@@ -0,0 +1,40 @@
+//! Models for delivery quotes - delivery prices persisted at checkout, so that the order placed later
+//! is charged the quoted price even if the rates have changed in between. A quote is redeemed until it expires
+use std::time::SystemTime;
+
+use serde_json;
+use uuid::Uuid;
+
+use stq_static_resources::Currency;
+use stq_types::CompanyPackageId;
+
+use schema::delivery_quotes;
+
+#[derive(Serialize, Deserialize, Queryable, Clone, Debug)]
+pub struct DeliveryQuote {
+    pub id: Uuid,
+    pub company_package_id: CompanyPackageId,
+    pub currency: Currency,
+    pub price: f64,
+    /// Request the price was calculated for
+    pub inputs: serde_json::Value,
+    pub created_at: SystemTime,
+    pub expires_at: SystemTime,
+}
+
+impl DeliveryQuote {
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires_at <= now
+    }
+}
+
+#[derive(Serialize, Deserialize, Insertable, Clone, Debug)]
+#[table_name = "delivery_quotes"]
+pub struct NewDeliveryQuote {
+    pub id: Uuid,
+    pub company_package_id: CompanyPackageId,
+    pub currency: Currency,
+    pub price: f64,
+    pub inputs: serde_json::Value,
+    pub expires_at: SystemTime,
+}
@@ -0,0 +1,23 @@
+//! Models for the outbox of the events. Events are written to the outbox in the transaction of the change
+//! and published after the commit, the events that failed to publish are published again by the relay
+use std::time::SystemTime;
+
+use events::DeliveryEvent;
+use models::TypedJson;
+use schema::event_outbox;
+
+#[derive(Serialize, Deserialize, Queryable, Clone, Debug)]
+pub struct OutboxEvent {
+    pub id: i32,
+    pub event: TypedJson<DeliveryEvent>,
+    /// Number of failed attempts to publish the event
+    pub attempts: i32,
+    pub published_at: Option<SystemTime>,
+    pub created_at: SystemTime,
+}
+
+#[derive(Insertable, Clone, Debug)]
+#[table_name = "event_outbox"]
+pub struct NewOutboxEvent {
+    pub event: TypedJson<DeliveryEvent>,
+}
@@ -0,0 +1,204 @@
+//! Models for pricing deliveries by the distance between the seller warehouse and the buyer
+use validator::{Validate, ValidationErrors};
+
+const EARTH_RADIUS_KM: f64 = 6371.0088;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Coordinates {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+json_schema!(Coordinates {
+    latitude: f64,
+    longitude: f64,
+});
+
+impl Coordinates {
+    /// Great-circle distance calculated with the haversine formula
+    pub fn straight_line_distance_km(&self, other: &Coordinates) -> f64 {
+        let (lat1, lat2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lon = (other.longitude - self.longitude).to_radians();
+
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+    }
+}
+
+impl Validate for Coordinates {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        if self.latitude.is_nan() || self.latitude < -90.0 || self.latitude > 90.0 {
+            Err(validation_errors!({ "latitude": ["latitude" => "Latitude must be between -90 and 90"] }))?;
+        }
+
+        if self.longitude.is_nan() || self.longitude < -180.0 || self.longitude > 180.0 {
+            Err(validation_errors!({ "longitude": ["longitude" => "Longitude must be between -180 and 180"] }))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Seller warehouse and buyer locations
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct DeliveryCoordinates {
+    pub from: Coordinates,
+    pub to: Coordinates,
+}
+
+impl Validate for DeliveryCoordinates {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        self.from.validate()?;
+        self.to.validate()
+    }
+}
+
+/// Pricing of same-city courier deliveries
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DistancePricing {
+    pub price_per_km: f64,
+    pub min_price: f64,
+    /// Deliveries further than that are not available
+    pub max_distance_km: Option<f64>,
+    /// Road distance is estimated as the straight-line distance multiplied by this factor.
+    /// The straight-line distance is used when it is not set
+    pub road_factor: Option<f64>,
+}
+
+impl Validate for DistancePricing {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        if self.price_per_km.is_nan() || self.price_per_km < 0.0 {
+            Err(validation_errors!({ "price_per_km": ["price_per_km" => "Value must not be negative"] }))?;
+        }
+
+        if self.min_price.is_nan() || self.min_price < 0.0 {
+            Err(validation_errors!({ "min_price": ["min_price" => "Value must not be negative"] }))?;
+        }
+
+        if let Some(max_distance_km) = self.max_distance_km {
+            if max_distance_km.is_nan() || max_distance_km <= 0.0 {
+                Err(validation_errors!({ "max_distance_km": ["max_distance_km" => "Value must be positive"] }))?;
+            }
+        }
+
+        if let Some(road_factor) = self.road_factor {
+            if road_factor.is_nan() || road_factor < 1.0 {
+                Err(
+                    validation_errors!({ "road_factor": ["road_factor" => "Road distance can not be shorter than straight-line distance"] }),
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DistancePriceBreakdown {
+    pub distance_km: f64,
+    /// `distance_km` multiplied by the price per km
+    pub base_rate: f64,
+    /// Added to `base_rate` to reach the minimum price
+    pub min_price_adjustment: f64,
+    pub price: f64,
+}
+
+impl DistancePricing {
+    pub fn distance_km(&self, coordinates: &DeliveryCoordinates) -> f64 {
+        let distance_km = coordinates.from.straight_line_distance_km(&coordinates.to);
+        distance_km * self.road_factor.unwrap_or(1.0)
+    }
+
+    /// Returns `None` if the delivery is further than the maximum distance
+    pub fn calculate_price_breakdown(&self, coordinates: &DeliveryCoordinates) -> Option<DistancePriceBreakdown> {
+        let distance_km = self.distance_km(coordinates);
+
+        if self
+            .max_distance_km
+            .map(|max_distance_km| distance_km > max_distance_km)
+            .unwrap_or(false)
+        {
+            return None;
+        }
+
+        let base_rate = distance_km * self.price_per_km;
+        let price = base_rate.max(self.min_price);
+
+        Some(DistancePriceBreakdown {
+            distance_km,
+            base_rate,
+            min_price_adjustment: price - base_rate,
+            price,
+        })
+    }
+
+    pub fn calculate_price(&self, coordinates: &DeliveryCoordinates) -> Option<f64> {
+        self.calculate_price_breakdown(coordinates).map(|breakdown| breakdown.price)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Red Square and Saint Isaac's Cathedral
+    fn moscow_to_saint_petersburg() -> DeliveryCoordinates {
+        DeliveryCoordinates {
+            from: Coordinates {
+                latitude: 55.7539,
+                longitude: 37.6208,
+            },
+            to: Coordinates {
+                latitude: 59.9343,
+                longitude: 30.3061,
+            },
+        }
+    }
+
+    fn pricing(max_distance_km: Option<f64>, road_factor: Option<f64>) -> DistancePricing {
+        DistancePricing {
+            price_per_km: 2.0,
+            min_price: 1000.0,
+            max_distance_km,
+            road_factor,
+        }
+    }
+
+    #[test]
+    fn straight_line_distance() {
+        let coordinates = moscow_to_saint_petersburg();
+        let distance_km = coordinates.from.straight_line_distance_km(&coordinates.to);
+        assert!((distance_km - 634.0).abs() < 1.0, "Unexpected distance: {}", distance_km);
+    }
+
+    #[test]
+    fn price_by_road_distance() {
+        let coordinates = moscow_to_saint_petersburg();
+        let straight = pricing(None, None).calculate_price_breakdown(&coordinates).unwrap();
+        let road = pricing(None, Some(1.5)).calculate_price_breakdown(&coordinates).unwrap();
+
+        assert!((road.distance_km - straight.distance_km * 1.5).abs() < 1e-6);
+        assert!((road.price - road.distance_km * 2.0).abs() < 1e-6);
+        assert!(road.min_price_adjustment.abs() < 1e-6);
+    }
+
+    #[test]
+    fn min_price_is_applied() {
+        let coordinates = moscow_to_saint_petersburg();
+        let pricing = DistancePricing {
+            min_price: 2000.0,
+            ..pricing(None, None)
+        };
+        let breakdown = pricing.calculate_price_breakdown(&coordinates).unwrap();
+
+        assert!((breakdown.price - 2000.0).abs() < 1e-6);
+        assert!((breakdown.base_rate + breakdown.min_price_adjustment - 2000.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn delivery_beyond_max_distance_is_not_available() {
+        let coordinates = moscow_to_saint_petersburg();
+        assert_eq!(pricing(Some(50.0), None).calculate_price(&coordinates), None);
+    }
+}
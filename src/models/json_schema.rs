@@ -0,0 +1,439 @@
+//! JSON schemas of the request payloads. Schemas are served to the client teams and,
+//! if enabled in config, request bodies are checked against them before deserialization,
+//! so that all structural errors of the payload are reported at once
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use serde_json::{Map, Value};
+use uuid::Uuid;
+use validator::{ValidationError, ValidationErrors};
+
+use stq_static_resources::Currency;
+use stq_types::{
+    Alpha2, Alpha3, BaseProductId, CompanyId, CompanyPackageId, CountryLabel, DeliveryRole, PackageId, ProductPrice, RoleId, ShippingId,
+    StoreId, UserId,
+};
+
+use models::*;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Schema {
+    /// Any value is accepted, used for the types with complex serde representation
+    Any,
+    Boolean,
+    Integer {
+        minimum: Option<i64>,
+    },
+    Number,
+    /// String, limited to the listed values if there are any
+    String {
+        values: Vec<&'static str>,
+    },
+    Array(Box<Schema>),
+    Object {
+        properties: Vec<(&'static str, Schema)>,
+        required: Vec<&'static str>,
+    },
+    Nullable(Box<Schema>),
+}
+
+/// Payload that does not match the schema
+struct SchemaViolation {
+    path: String,
+    code: &'static str,
+    message: String,
+}
+
+impl Schema {
+    pub fn string() -> Self {
+        Schema::String { values: vec![] }
+    }
+
+    pub fn enumeration(values: &[&'static str]) -> Self {
+        Schema::String { values: values.to_vec() }
+    }
+
+    fn type_name(&self) -> &'static str {
+        match self {
+            Schema::Any => "any",
+            Schema::Boolean => "boolean",
+            Schema::Integer { .. } => "integer",
+            Schema::Number => "number",
+            Schema::String { .. } => "string",
+            Schema::Array(_) => "array",
+            Schema::Object { .. } => "object",
+            Schema::Nullable(inner) => inner.type_name(),
+        }
+    }
+
+    /// Checks the value against the schema, all violations are returned as errors of `payload` field
+    pub fn validate(&self, value: &Value) -> Result<(), ValidationErrors> {
+        let mut violations = vec![];
+        self.collect_violations(value, "$", &mut violations);
+
+        if violations.is_empty() {
+            return Ok(());
+        }
+
+        let mut errors = ValidationErrors::new();
+        for violation in violations {
+            let mut error = ValidationError::new(violation.code);
+            error.message = Some(Cow::from(format!("{}: {}", violation.path, violation.message)));
+            error.add_param(Cow::from("path"), &violation.path);
+            errors.add("payload", error);
+        }
+
+        Err(errors)
+    }
+
+    fn collect_violations(&self, value: &Value, path: &str, violations: &mut Vec<SchemaViolation>) {
+        let violation = |code, message| SchemaViolation {
+            path: path.to_string(),
+            code,
+            message,
+        };
+
+        match (self, value) {
+            (Schema::Any, _)
+            | (Schema::Nullable(_), Value::Null)
+            | (Schema::Boolean, Value::Bool(_))
+            | (Schema::Number, Value::Number(_)) => {}
+            (Schema::Nullable(inner), _) => inner.collect_violations(value, path, violations),
+            (Schema::Integer { minimum }, Value::Number(number)) => match number.as_i64() {
+                Some(number) => {
+                    if let Some(minimum) = minimum {
+                        if number < *minimum {
+                            violations.push(violation("minimum", format!("must not be less than {}", minimum)));
+                        }
+                    }
+                }
+                None if number.is_u64() => {}
+                None => violations.push(violation("type", "expected integer".to_string())),
+            },
+            (Schema::String { values }, Value::String(string)) => {
+                if !values.is_empty() && !values.contains(&string.as_str()) {
+                    violations.push(violation("enum", format!("expected one of {}", values.join(", "))));
+                }
+            }
+            (Schema::Array(items), Value::Array(array)) => {
+                for (index, item) in array.iter().enumerate() {
+                    items.collect_violations(item, &format!("{}[{}]", path, index), violations);
+                }
+            }
+            (Schema::Object { properties, required }, Value::Object(object)) => {
+                for field in required {
+                    if !object.contains_key(*field) {
+                        violations.push(SchemaViolation {
+                            path: format!("{}.{}", path, field),
+                            code: "required",
+                            message: "is required".to_string(),
+                        });
+                    }
+                }
+
+                for (field, schema) in properties {
+                    if let Some(value) = object.get(*field) {
+                        schema.collect_violations(value, &format!("{}.{}", path, field), violations);
+                    }
+                }
+            }
+            _ => violations.push(violation("type", format!("expected {}", self.type_name()))),
+        }
+    }
+
+    /// JSON Schema document of the schema
+    pub fn to_json(&self) -> Value {
+        self.document(false)
+    }
+
+    /// Schema object of OpenAPI 3.0, it has no `null` type, nullable values are marked with `nullable` instead
+    pub fn to_openapi(&self) -> Value {
+        self.document(true)
+    }
+
+    fn document(&self, openapi: bool) -> Value {
+        match self {
+            Schema::Any => json!({}),
+            Schema::Integer { minimum: Some(minimum) } => json!({ "type": "integer", "minimum": minimum }),
+            Schema::String { values } if !values.is_empty() => json!({ "type": "string", "enum": values }),
+            Schema::Array(items) => json!({ "type": "array", "items": items.document(openapi) }),
+            Schema::Object { properties, required } => {
+                let properties = properties
+                    .iter()
+                    .map(|(field, schema)| (field.to_string(), schema.document(openapi)))
+                    .collect::<Map<String, Value>>();
+                json!({ "type": "object", "properties": properties, "required": required })
+            }
+            Schema::Nullable(inner) if openapi => {
+                let mut document = inner.document(openapi);
+                if let Some(document) = document.as_object_mut() {
+                    document.insert("nullable".to_string(), Value::Bool(true));
+                }
+                document
+            }
+            Schema::Nullable(inner) => json!({ "anyOf": [inner.document(openapi), { "type": "null" }] }),
+            _ => json!({ "type": self.type_name() }),
+        }
+    }
+}
+
+/// Type with JSON schema, implemented for the payloads with `json_schema!` macro
+pub trait JsonSchema {
+    fn json_schema() -> Schema;
+
+    /// Field of this type may be omitted from the payload
+    fn is_optional() -> bool {
+        false
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for Option<T> {
+    fn json_schema() -> Schema {
+        Schema::Nullable(Box::new(T::json_schema()))
+    }
+
+    fn is_optional() -> bool {
+        true
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for Vec<T> {
+    fn json_schema() -> Schema {
+        Schema::Array(Box::new(T::json_schema()))
+    }
+}
+
+macro_rules! impl_json_schema {
+    ($schema:expr => $($ty:ty),+) => {
+        $(
+            impl JsonSchema for $ty {
+                fn json_schema() -> Schema {
+                    $schema
+                }
+            }
+        )+
+    };
+}
+
+impl_json_schema!(Schema::Boolean => bool);
+impl_json_schema!(Schema::Integer { minimum: None } => i32, i64, BaseProductId, CompanyId, CompanyPackageId, PackageId, ShippingId, StoreId, UserId);
+impl_json_schema!(Schema::Integer { minimum: Some(0) } => u8, u32);
+impl_json_schema!(Schema::Number => f64, ProductPrice);
+impl_json_schema!(Schema::string() => String, Alpha2, Alpha3, CountryLabel, Currency, DeliveryRole, RoleId, ShippingOptionId, CompanyCapabilities, NaiveDate, NaiveTime, DateTime<Utc>, Uuid);
+impl_json_schema!(Schema::Any => Value, ShippingRateSource);
+impl_json_schema!(Schema::string() => Resource);
+impl_json_schema!(Schema::enumeration(&["LithiumBatteries", "Aerosols"]) => HazardClass);
+impl_json_schema!(Schema::enumeration(&["Percentage", "Fixed"]) => SurchargeKind);
+impl_json_schema!(Schema::enumeration(&["Local", "International"]) => ShippingVariant);
+impl_json_schema!(Schema::enumeration(&["prohibited", "restricted"]) => RestrictionType);
+impl_json_schema!(Schema::enumeration(&["id", "name", "label"]) => CompaniesSortField);
+impl_json_schema!(Schema::enumeration(&["cod", "tracking", "insurance"]) => CompanyCapability);
+impl_json_schema!(Schema::enumeration(&["asc", "desc"]) => SortOrder);
+impl_json_schema!(Schema::enumeration(&["all", "read", "create", "update", "delete", "moderate", "publish"]) => Action);
+impl_json_schema!(Schema::enumeration(&["option_chosen", "shipment_dispatched", "delivery_delayed"]) => ShipmentEventKind);
+impl_json_schema!(Schema::enumeration(&["base", "fuel_surcharge", "peak_season_surcharge"]) => RateComponentKind);
+impl_json_schema!(Schema::enumeration(&["g", "kg", "oz", "lb"]) => WeightUnit);
+impl_json_schema!(Schema::enumeration(&["cm3", "l", "in3", "ft3"]) => VolumeUnit);
+impl_json_schema!(Schema::enumeration(&["stored_rates", "no_price"]) => CarrierFallback);
+impl_json_schema!(Schema::enumeration(&["store", "company_package", "country"]) => PricingRuleScope);
+impl_json_schema!(Schema::enumeration(&["markup", "discount"]) => PricingRuleDirection);
+impl_json_schema!(Schema::enumeration(&["fail", "skip", "overwrite"]) => ShippingImportConflict);
+impl_json_schema!(Schema::enumeration(&["cheapest", "fastest"]) => OriginPreference);
+impl_json_schema!(
+    Schema::enumeration(&[
+        "postal_zone",
+        "subdivision",
+        "exact_destination",
+        "zone",
+        "region_group",
+        "fallback_rate",
+        "carrier_quote",
+    ]) => RateResolutionStep
+);
+
+/// Schemas of all create and update payloads by the payload name
+pub fn payload_schemas() -> BTreeMap<&'static str, Value> {
+    let mut schemas = BTreeMap::new();
+    schemas.insert("NewApiKey", NewApiKey::json_schema().to_json());
+    schemas.insert("NewCompany", NewCompany::json_schema().to_json());
+    schemas.insert("UpdateCompany", UpdateCompany::json_schema().to_json());
+    schemas.insert("NewCompanyPackage", NewCompanyPackage::json_schema().to_json());
+    schemas.insert("SetCompanyRatesQuota", SetCompanyRatesQuota::json_schema().to_json());
+    schemas.insert("NewCompanySuspension", NewCompanySuspension::json_schema().to_json());
+    schemas.insert("UpdateCompanySuspension", UpdateCompanySuspension::json_schema().to_json());
+    schemas.insert("NewCountry", NewCountry::json_schema().to_json());
+    schemas.insert("ImportCountries", ImportCountries::json_schema().to_json());
+    schemas.insert("SetCountryRegulation", SetCountryRegulation::json_schema().to_json());
+    schemas.insert("NewCountrySubdivision", NewCountrySubdivision::json_schema().to_json());
+    schemas.insert("UpdateCountrySubdivision", UpdateCountrySubdivision::json_schema().to_json());
+    schemas.insert("NewPackageTemplate", NewPackageTemplate::json_schema().to_json());
+    schemas.insert("UpdatePackageTemplate", UpdatePackageTemplate::json_schema().to_json());
+    schemas.insert("NewPackages", NewPackages::json_schema().to_json());
+    schemas.insert("UpdatePackages", UpdatePackages::json_schema().to_json());
+    schemas.insert("NewRateComponents", NewRateComponents::json_schema().to_json());
+    schemas.insert("SetRateResolutionOrder", SetRateResolutionOrder::json_schema().to_json());
+    schemas.insert("SetCashOnDelivery", SetCashOnDelivery::json_schema().to_json());
+    schemas.insert("SetCarrierFallback", SetCarrierFallback::json_schema().to_json());
+    schemas.insert("SetShippingCapacity", SetShippingCapacity::json_schema().to_json());
+    schemas.insert("BookShipment", BookShipment::json_schema().to_json());
+    schemas.insert("NewPickupPoint", NewPickupPoint::json_schema().to_json());
+    schemas.insert("UpdatePickupPoint", UpdatePickupPoint::json_schema().to_json());
+    schemas.insert("NewPostalZone", NewPostalZone::json_schema().to_json());
+    schemas.insert("ImportPostalZone", ImportPostalZone::json_schema().to_json());
+    schemas.insert("NewShipping", NewShipping::json_schema().to_json());
+    schemas.insert("UpdateProducts", UpdateProducts::json_schema().to_json());
+    schemas.insert("CloneShippingTargets", Vec::<BaseProductId>::json_schema().to_json());
+    schemas.insert("SetCustomsInfo", SetCustomsInfo::json_schema().to_json());
+    schemas.insert("NewShippingTemplate", NewShippingTemplate::json_schema().to_json());
+    schemas.insert("UpdateShippingTemplate", UpdateShippingTemplate::json_schema().to_json());
+    schemas.insert("StoreShippingExport", StoreShippingExport::json_schema().to_json());
+    schemas.insert("ShipmentEvent", ShipmentEvent::json_schema().to_json());
+    schemas.insert("SetStoreMargin", SetStoreMargin::json_schema().to_json());
+    schemas.insert("CreateCarrierAccount", CreateCarrierAccount::json_schema().to_json());
+    schemas.insert("UpdateCarrierAccount", UpdateCarrierAccount::json_schema().to_json());
+    schemas.insert("NewSurcharge", NewSurcharge::json_schema().to_json());
+    schemas.insert("UpdateSurcharge", UpdateSurcharge::json_schema().to_json());
+    schemas.insert("NewPricingRule", NewPricingRule::json_schema().to_json());
+    schemas.insert("UpdatePricingRule", UpdatePricingRule::json_schema().to_json());
+    schemas.insert("NewShippingRestriction", NewShippingRestriction::json_schema().to_json());
+    schemas.insert("UpdateShippingRestriction", UpdateShippingRestriction::json_schema().to_json());
+    schemas.insert("CheckShippingRestrictions", CheckShippingRestrictions::json_schema().to_json());
+    schemas.insert("NewUserAddress", NewUserAddress::json_schema().to_json());
+    schemas.insert("UpdateUserAddress", UpdateUserAddress::json_schema().to_json());
+    schemas.insert("NewUserRole", NewUserRole::json_schema().to_json());
+    schemas.insert("NewStoreManager", NewStoreManager::json_schema().to_json());
+    schemas.insert("NewZone", NewZone::json_schema().to_json());
+    schemas.insert("UpdateZone", UpdateZone::json_schema().to_json());
+    schemas
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+
+    use super::*;
+
+    fn violation_messages(errors: ValidationErrors) -> Vec<String> {
+        errors
+            .inner()
+            .remove("payload")
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|error| error.message.map(|message| message.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn all_violations_are_reported() {
+        let payload = json!({
+            "name": 1,
+            "max_size": -1,
+            "min_size": 0,
+            "max_weight": 10,
+            "deliveries_to": ["RUS", null],
+        });
+
+        let errors = NewPackages::json_schema().validate(&payload).unwrap_err();
+        let mut messages = violation_messages(errors);
+        messages.sort();
+
+        assert_eq!(
+            messages,
+            vec![
+                "$.deliveries_to[1]: expected string".to_string(),
+                "$.max_size: must not be less than 0".to_string(),
+                "$.min_weight: is required".to_string(),
+                "$.name: expected string".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn optional_and_default_fields_may_be_omitted() {
+        let payload = json!({
+            "company_id": 1,
+            "package_id": 2,
+            "delivery_time_min_days": null,
+        });
+
+        assert!(NewCompanyPackage::json_schema().validate(&payload).is_ok());
+    }
+
+    #[test]
+    fn openapi_schema_marks_nullable_values() {
+        let schema = Schema::Array(Box::new(Schema::Nullable(Box::new(Schema::Number))));
+
+        assert_eq!(
+            schema.to_json(),
+            json!({ "type": "array", "items": { "anyOf": [{ "type": "number" }, { "type": "null" }] } })
+        );
+        assert_eq!(
+            schema.to_openapi(),
+            json!({ "type": "array", "items": { "type": "number", "nullable": true } })
+        );
+    }
+
+    #[test]
+    fn enumeration_values_are_deserializable() {
+        for value in &["LithiumBatteries", "Aerosols"] {
+            assert!(serde_json::from_value::<HazardClass>(json!(value)).is_ok());
+        }
+        for value in &["Percentage", "Fixed"] {
+            assert!(serde_json::from_value::<SurchargeKind>(json!(value)).is_ok());
+        }
+        for value in &["Local", "International"] {
+            assert!(serde_json::from_value::<ShippingVariant>(json!(value)).is_ok());
+        }
+        for value in &["prohibited", "restricted"] {
+            assert!(serde_json::from_value::<RestrictionType>(json!(value)).is_ok());
+        }
+        for value in &["id", "name", "label"] {
+            assert!(serde_json::from_value::<CompaniesSortField>(json!(value)).is_ok());
+        }
+        for value in &["cod", "tracking", "insurance"] {
+            assert!(serde_json::from_value::<CompanyCapability>(json!(value)).is_ok());
+        }
+        for value in &["asc", "desc"] {
+            assert!(serde_json::from_value::<SortOrder>(json!(value)).is_ok());
+        }
+        for value in &["option_chosen", "shipment_dispatched", "delivery_delayed"] {
+            assert!(serde_json::from_value::<ShipmentEventKind>(json!(value)).is_ok());
+        }
+        for value in &["base", "fuel_surcharge", "peak_season_surcharge"] {
+            assert!(serde_json::from_value::<RateComponentKind>(json!(value)).is_ok());
+        }
+        for value in &["g", "kg", "oz", "lb"] {
+            assert!(serde_json::from_value::<WeightUnit>(json!(value)).is_ok());
+        }
+        for value in &["cm3", "l", "in3", "ft3"] {
+            assert!(serde_json::from_value::<VolumeUnit>(json!(value)).is_ok());
+        }
+        for value in &["stored_rates", "no_price"] {
+            assert!(serde_json::from_value::<CarrierFallback>(json!(value)).is_ok());
+        }
+        for value in &["store", "company_package", "country"] {
+            assert!(serde_json::from_value::<PricingRuleScope>(json!(value)).is_ok());
+        }
+        for value in &["markup", "discount"] {
+            assert!(serde_json::from_value::<PricingRuleDirection>(json!(value)).is_ok());
+        }
+        for value in &["fail", "skip", "overwrite"] {
+            assert!(serde_json::from_value::<ShippingImportConflict>(json!(value)).is_ok());
+        }
+        for value in &["cheapest", "fastest"] {
+            assert!(serde_json::from_value::<OriginPreference>(json!(value)).is_ok());
+        }
+        for value in &[
+            "postal_zone",
+            "subdivision",
+            "exact_destination",
+            "zone",
+            "region_group",
+            "fallback_rate",
+            "carrier_quote",
+        ] {
+            assert!(serde_json::from_value::<RateResolutionStep>(json!(value)).is_ok());
+        }
+    }
+}
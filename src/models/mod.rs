@@ -1,25 +1,101 @@
+pub mod admin_overview;
+pub mod api_keys;
 pub mod authorization;
+pub mod availability_snapshots;
+pub mod backfills;
+pub mod carrier_accounts;
 pub mod companies;
 pub mod companies_packages;
+pub mod company_rates_quotas;
+pub mod company_suspensions;
 pub mod countries;
+pub mod country_dependents;
+pub mod country_regulations;
+pub mod country_subdivisions;
+pub mod customs_info;
+pub mod delivery_quotes;
+pub mod event_outbox;
+pub mod geo;
+pub mod json_schema;
+pub mod package_templates;
 pub mod packages;
+pub mod pickup_points;
 pub mod pickups;
+pub mod postal_zones;
+pub mod price_rounding;
+pub mod pricing_rules;
 pub mod products;
+pub mod rate_resolution;
 pub mod roles;
+pub mod shipment_bookings;
+pub mod shipment_notifications;
 pub mod shipping;
+pub mod shipping_exports;
+pub mod shipping_options;
 pub mod shipping_rates;
+pub mod shipping_templates;
+pub mod shipping_rates_anomalies;
+pub mod shipping_restrictions;
+pub mod sorting;
+pub mod store_margins;
+pub mod store_shipping_summaries;
+pub mod surcharges;
+#[cfg(feature = "test-endpoints")]
+pub mod test_fixtures;
+pub mod translations;
+pub mod typed_json;
+pub mod units;
 pub mod user_addresses;
 pub mod validation_rules;
+pub mod zones;
 
+pub use self::admin_overview::*;
+pub use self::api_keys::*;
 pub use self::authorization::*;
+pub use self::availability_snapshots::*;
+pub use self::backfills::*;
+pub use self::carrier_accounts::*;
 pub use self::companies::*;
 pub use self::companies_packages::*;
+pub use self::company_rates_quotas::*;
+pub use self::company_suspensions::*;
 pub use self::countries::*;
+pub use self::country_dependents::*;
+pub use self::country_regulations::*;
+pub use self::country_subdivisions::*;
+pub use self::customs_info::*;
+pub use self::delivery_quotes::*;
+pub use self::event_outbox::*;
+pub use self::geo::*;
+pub use self::json_schema::*;
+pub use self::package_templates::*;
 pub use self::packages::*;
+pub use self::pickup_points::*;
 pub use self::pickups::*;
+pub use self::postal_zones::*;
+pub use self::price_rounding::*;
+pub use self::pricing_rules::*;
 pub use self::products::*;
+pub use self::rate_resolution::*;
 pub use self::roles::*;
+pub use self::shipment_bookings::*;
+pub use self::shipment_notifications::*;
 pub use self::shipping::*;
+pub use self::shipping_exports::*;
+pub use self::shipping_options::*;
 pub use self::shipping_rates::*;
+pub use self::shipping_templates::*;
+pub use self::shipping_rates_anomalies::*;
+pub use self::shipping_restrictions::*;
+pub use self::sorting::*;
+pub use self::store_margins::*;
+pub use self::store_shipping_summaries::*;
+pub use self::surcharges::*;
+#[cfg(feature = "test-endpoints")]
+pub use self::test_fixtures::*;
+pub use self::translations::*;
+pub use self::typed_json::*;
+pub use self::units::*;
 pub use self::user_addresses::*;
 pub use self::validation_rules::*;
+pub use self::zones::*;
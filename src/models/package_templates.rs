@@ -0,0 +1,81 @@
+//! Models for package templates - curated default company packages suggested to the stores of the origin country
+use validator::{Validate, ValidationErrors};
+
+use stq_static_resources::Currency;
+use stq_types::{Alpha3, CompanyId, CompanyPackageId, PackageId};
+
+use models::ShippingRateSource;
+use schema::package_templates;
+
+#[derive(Serialize, Deserialize, Queryable, Clone, Debug, PartialEq)]
+pub struct PackageTemplate {
+    pub id: i32,
+    pub from_alpha3: Alpha3,
+    pub company_package_id: CompanyPackageId,
+    /// Templates of the country are suggested in ascending order of position
+    pub position: i32,
+}
+
+fn validate_position(position: i32) -> Result<(), ValidationErrors> {
+    if position < 0 {
+        Err(validation_errors!({ "position": ["position" => "Position must not be negative"] }))?;
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Insertable, Clone, Debug)]
+#[table_name = "package_templates"]
+pub struct NewPackageTemplate {
+    pub from_alpha3: Alpha3,
+    pub company_package_id: CompanyPackageId,
+    #[serde(default)]
+    pub position: i32,
+}
+
+json_schema!(NewPackageTemplate {
+    from_alpha3: Alpha3,
+    company_package_id: CompanyPackageId,
+    #[default]
+    position: i32,
+});
+
+impl Validate for NewPackageTemplate {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        validate_position(self.position)
+    }
+}
+
+/// Only the position can be changed, the template is recreated to suggest another company package
+#[derive(Serialize, Deserialize, AsChangeset, Clone, Debug)]
+#[table_name = "package_templates"]
+pub struct UpdatePackageTemplate {
+    pub position: Option<i32>,
+}
+
+json_schema!(UpdatePackageTemplate {
+    position: Option<i32>,
+});
+
+impl Validate for UpdatePackageTemplate {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        match self.position {
+            Some(position) => validate_position(position),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Company package suggested to a store
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SuggestedPackage {
+    pub company_package_id: CompanyPackageId,
+    pub company_id: CompanyId,
+    pub company_name: String,
+    pub company_label: String,
+    pub logo: String,
+    pub package_id: PackageId,
+    pub package_name: String,
+    pub shipping_rate_source: ShippingRateSource,
+    pub currency: Currency,
+}
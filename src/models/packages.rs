@@ -1,11 +1,7 @@
-use failure::Error as FailureError;
-use failure::Fail;
-use serde_json;
-
 use stq_types::{Alpha3, PackageId};
+use validator::{Validate, ValidationErrors};
 
-use errors::Error;
-use models::{Country, ShipmentMeasurements};
+use models::{translate, validate_translations, Country, Localized, ShipmentMeasurements, TextTranslation, TypedJson, Zone};
 use repos::countries::create_tree_used_countries;
 use schema::packages;
 
@@ -31,7 +27,7 @@ pub enum MeasurementsOutOfRange {
     },
 }
 
-#[derive(Serialize, Deserialize, Associations, Queryable, Debug, QueryableByName)]
+#[derive(Serialize, Deserialize, Associations, Queryable, Clone, Debug, QueryableByName)]
 #[table_name = "packages"]
 pub struct PackagesRaw {
     pub id: PackageId,
@@ -40,9 +36,15 @@ pub struct PackagesRaw {
     pub min_size: i32,
     pub max_weight: i32,
     pub min_weight: i32,
-    pub deliveries_to: serde_json::Value,
+    pub deliveries_to: TypedJson<Vec<Alpha3>>,
+    pub deliveries_to_zones: TypedJson<Vec<i32>>,
+    pub name_translations: TypedJson<Vec<TextTranslation>>,
 }
 
+/// Default and maximum page size of the packages search
+pub const DEFAULT_PACKAGES_SEARCH_LIMIT: i64 = 100;
+pub const MAX_PACKAGES_SEARCH_LIMIT: i64 = 1000;
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Packages {
     pub id: PackageId,
@@ -51,7 +53,21 @@ pub struct Packages {
     pub min_size: u32,
     pub max_weight: u32,
     pub min_weight: u32,
+    /// Countries from `deliveries_to_zones` are included
     pub deliveries_to: Vec<Country>,
+    pub deliveries_to_zones: Vec<i32>,
+    /// Name is the text in the default language, it is replaced with the translation when a language is requested
+    pub name_translations: Vec<TextTranslation>,
+}
+
+impl Localized for Packages {
+    fn localized(self, lang: &str) -> Self {
+        let name = translate(&self.name_translations, lang).map(str::to_string);
+        Self {
+            name: name.unwrap_or(self.name),
+            ..self
+        }
+    }
 }
 
 impl Packages {
@@ -95,13 +111,20 @@ impl Packages {
     }
 }
 
+/// Page of the packages found by the search with the count of all of them
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PackagesSearchResult {
+    pub total_count: i64,
+    pub packages: Vec<Packages>,
+}
+
 impl PackagesRaw {
-    pub fn to_packages(self, countries_arg: &Country) -> Result<Packages, FailureError> {
-        let used_codes: Vec<Alpha3> =
-            serde_json::from_value(self.deliveries_to).map_err(|e| e.context("Can not parse deliveries_to from db"))?;
+    /// `zones` must contain the zones from `deliveries_to_zones`, their countries are added to `deliveries_to`
+    pub fn to_packages(self, countries_arg: &Country, zones: &[Zone]) -> Packages {
+        let used_codes = self.get_deliveries_to_with_zones(zones);
         let deliveries_to = create_tree_used_countries(countries_arg, &used_codes);
 
-        Ok(Packages {
+        Packages {
             id: self.id,
             name: self.name,
             max_size: self.max_size as u32,
@@ -109,14 +132,31 @@ impl PackagesRaw {
             max_weight: self.max_weight as u32,
             min_weight: self.min_weight as u32,
             deliveries_to,
-        })
+            deliveries_to_zones: self.deliveries_to_zones.into_inner(),
+            name_translations: self.name_translations.into_inner(),
+        }
+    }
+
+    pub fn get_deliveries_to(&self) -> &[Alpha3] {
+        &self.deliveries_to
     }
 
-    pub fn get_deliveries_to(&self) -> Result<Vec<Alpha3>, FailureError> {
-        let used_codes =
-            serde_json::from_value(self.deliveries_to.clone()).map_err(|e| e.context("Can not parse deliveries_to from db"))?;
+    /// Countries from `deliveries_to` together with the countries of `deliveries_to_zones`
+    pub fn get_deliveries_to_with_zones(&self, zones: &[Zone]) -> Vec<Alpha3> {
+        let mut used_codes = self.get_deliveries_to().to_vec();
+        for zone in zones.iter().filter(|zone| self.deliveries_to_zones.contains(&zone.id)) {
+            for alpha3 in &zone.countries {
+                if !used_codes.contains(alpha3) {
+                    used_codes.push(alpha3.clone());
+                }
+            }
+        }
+
+        used_codes
+    }
 
-        Ok(used_codes)
+    pub fn get_deliveries_to_zones(&self) -> &[i32] {
+        &self.deliveries_to_zones
     }
 }
 
@@ -128,7 +168,9 @@ pub struct NewPackagesRaw {
     pub min_size: i32,
     pub max_weight: i32,
     pub min_weight: i32,
-    pub deliveries_to: serde_json::Value,
+    pub deliveries_to: TypedJson<Vec<Alpha3>>,
+    pub deliveries_to_zones: TypedJson<Vec<i32>>,
+    pub name_translations: TypedJson<Vec<TextTranslation>>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -139,21 +181,43 @@ pub struct NewPackages {
     pub max_weight: u32,
     pub min_weight: u32,
     pub deliveries_to: Vec<Alpha3>,
+    #[serde(default)]
+    pub deliveries_to_zones: Vec<i32>,
+    #[serde(default)]
+    pub name_translations: Vec<TextTranslation>,
 }
 
-impl NewPackages {
-    pub fn to_raw(self) -> Result<NewPackagesRaw, FailureError> {
-        let deliveries_to = serde_json::to_value(self.deliveries_to)
-            .map_err(|e| e.context("Can not parse deliveries_to from value").context(Error::Parse))?;
+json_schema!(NewPackages {
+    name: String,
+    max_size: u32,
+    min_size: u32,
+    max_weight: u32,
+    min_weight: u32,
+    deliveries_to: Vec<Alpha3>,
+    #[default]
+    deliveries_to_zones: Vec<i32>,
+    #[default]
+    name_translations: Vec<TextTranslation>,
+});
+
+impl Validate for NewPackages {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        validate_translations("name_translations", &self.name_translations)
+    }
+}
 
-        Ok(NewPackagesRaw {
+impl NewPackages {
+    pub fn to_raw(self) -> NewPackagesRaw {
+        NewPackagesRaw {
             name: self.name,
             max_size: self.max_size as i32,
             min_size: self.min_size as i32,
             max_weight: self.max_weight as i32,
             min_weight: self.min_weight as i32,
-            deliveries_to,
-        })
+            deliveries_to: TypedJson(self.deliveries_to),
+            deliveries_to_zones: TypedJson(self.deliveries_to_zones),
+            name_translations: TypedJson(self.name_translations),
+        }
     }
 }
 
@@ -165,7 +229,9 @@ pub struct UpdatePackagesRaw {
     pub min_size: Option<i32>,
     pub max_weight: Option<i32>,
     pub min_weight: Option<i32>,
-    pub deliveries_to: Option<serde_json::Value>,
+    pub deliveries_to: Option<TypedJson<Vec<Alpha3>>>,
+    pub deliveries_to_zones: Option<TypedJson<Vec<i32>>>,
+    pub name_translations: Option<TypedJson<Vec<TextTranslation>>>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -176,24 +242,41 @@ pub struct UpdatePackages {
     pub max_weight: Option<u32>,
     pub min_weight: Option<u32>,
     pub deliveries_to: Option<Vec<Alpha3>>,
+    pub deliveries_to_zones: Option<Vec<i32>>,
+    pub name_translations: Option<Vec<TextTranslation>>,
 }
 
-impl UpdatePackages {
-    pub fn to_raw(self) -> Result<UpdatePackagesRaw, FailureError> {
-        let deliveries_to = match self.deliveries_to {
-            Some(info) => {
-                Some(serde_json::to_value(info).map_err(|e| e.context("Can not parse deliveries_to from value").context(Error::Parse))?)
-            }
-            None => None,
-        };
+json_schema!(UpdatePackages {
+    name: Option<String>,
+    max_size: Option<u32>,
+    min_size: Option<u32>,
+    max_weight: Option<u32>,
+    min_weight: Option<u32>,
+    deliveries_to: Option<Vec<Alpha3>>,
+    deliveries_to_zones: Option<Vec<i32>>,
+    name_translations: Option<Vec<TextTranslation>>,
+});
+
+impl Validate for UpdatePackages {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        match self.name_translations {
+            Some(ref name_translations) => validate_translations("name_translations", name_translations),
+            None => Ok(()),
+        }
+    }
+}
 
-        Ok(UpdatePackagesRaw {
+impl UpdatePackages {
+    pub fn to_raw(self) -> UpdatePackagesRaw {
+        UpdatePackagesRaw {
             name: self.name,
             max_size: self.max_size.map(|x| x as i32),
             min_size: self.min_size.map(|x| x as i32),
             max_weight: self.max_weight.map(|x| x as i32),
             min_weight: self.min_weight.map(|x| x as i32),
-            deliveries_to,
-        })
+            deliveries_to: self.deliveries_to.map(TypedJson),
+            deliveries_to_zones: self.deliveries_to_zones.map(TypedJson),
+            name_translations: self.name_translations.map(TypedJson),
+        }
     }
 }
@@ -0,0 +1,359 @@
+//! Models for pickup points - carrier locations buyers can collect their orders at
+use failure::Error as FailureError;
+use failure::Fail;
+use serde_json;
+use validator::{Validate, ValidationErrors};
+
+use stq_types::{Alpha3, CompanyId};
+
+use errors::Error;
+use models::Coordinates;
+use schema::pickup_points;
+
+/// Nearby search is limited to this radius
+pub const MAX_NEARBY_RADIUS_KM: f64 = 100.0;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct OpeningHours {
+    /// ISO weekday, 1 is Monday and 7 is Sunday
+    pub weekday: u8,
+    /// Local time in "HH:MM" format
+    pub opens: String,
+    /// Local time in "HH:MM" format
+    pub closes: String,
+}
+
+json_schema!(OpeningHours {
+    weekday: u8,
+    opens: String,
+    closes: String,
+});
+
+/// Minutes since midnight of "HH:MM" time
+fn parse_time(time: &str) -> Option<u32> {
+    let mut parts = time.splitn(2, ':');
+    let hours = parts.next()?;
+    let minutes = parts.next()?;
+    if hours.len() != 2 || minutes.len() != 2 {
+        return None;
+    }
+
+    let hours = hours.parse::<u32>().ok()?;
+    let minutes = minutes.parse::<u32>().ok()?;
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+
+    Some(hours * 60 + minutes)
+}
+
+impl Validate for OpeningHours {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        if self.weekday < 1 || self.weekday > 7 {
+            Err(validation_errors!({ "weekday": ["weekday" => "Weekday must be between 1 and 7"] }))?;
+        }
+
+        match (parse_time(&self.opens), parse_time(&self.closes)) {
+            (Some(opens), Some(closes)) => {
+                if opens >= closes {
+                    Err(validation_errors!({ "closes": ["closes" => "Closing time must be after opening time"] }))?;
+                }
+            }
+            _ => Err(validation_errors!({ "opening_hours": ["opening_hours" => "Time must be in HH:MM format"] }))?,
+        }
+
+        Ok(())
+    }
+}
+
+fn validate_pickup_point(
+    name: Option<&String>,
+    coordinates: Option<&Coordinates>,
+    opening_hours: Option<&Vec<OpeningHours>>,
+    price_factor: Option<f64>,
+) -> Result<(), ValidationErrors> {
+    if name.map(|name| name.is_empty()).unwrap_or(false) {
+        Err(validation_errors!({ "name": ["name" => "Name must not be empty"] }))?;
+    }
+
+    if price_factor.map(|price_factor| price_factor <= 0.0).unwrap_or(false) {
+        Err(validation_errors!({ "price_factor": ["price_factor" => "Price factor must be positive"] }))?;
+    }
+
+    if let Some(coordinates) = coordinates {
+        coordinates.validate()?;
+    }
+
+    if let Some(opening_hours) = opening_hours {
+        for hours in opening_hours {
+            hours.validate()?;
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Queryable, Debug)]
+pub struct PickupPointRaw {
+    pub id: i32,
+    pub company_id: CompanyId,
+    pub name: String,
+    pub country: Alpha3,
+    pub address: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub opening_hours: serde_json::Value,
+    pub price_factor: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PickupPoint {
+    pub id: i32,
+    pub company_id: CompanyId,
+    pub name: String,
+    pub country: Alpha3,
+    pub address: String,
+    pub coordinates: Coordinates,
+    pub opening_hours: Vec<OpeningHours>,
+    /// Delivery price to the point is multiplied by the factor, e.g. parcel lockers are cheaper than the counters
+    pub price_factor: Option<f64>,
+}
+
+impl PickupPointRaw {
+    pub fn to_model(self) -> Result<PickupPoint, FailureError> {
+        let PickupPointRaw {
+            id,
+            company_id,
+            name,
+            country,
+            address,
+            latitude,
+            longitude,
+            opening_hours,
+            price_factor,
+        } = self;
+
+        let opening_hours = serde_json::from_value(opening_hours).map_err(|e| {
+            e.context(format!("Can not parse opening hours of pickup point with id = {}", id))
+                .context(Error::Parse)
+        })?;
+
+        Ok(PickupPoint {
+            id,
+            company_id,
+            name,
+            country,
+            address,
+            coordinates: Coordinates { latitude, longitude },
+            opening_hours,
+            price_factor,
+        })
+    }
+}
+
+/// Pickup point found by the nearby search
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct NearbyPickupPoint {
+    pub pickup_point: PickupPoint,
+    pub distance_km: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NewPickupPoint {
+    pub company_id: CompanyId,
+    pub name: String,
+    pub country: Alpha3,
+    pub address: String,
+    pub coordinates: Coordinates,
+    #[serde(default)]
+    pub opening_hours: Vec<OpeningHours>,
+    #[serde(default)]
+    pub price_factor: Option<f64>,
+}
+
+json_schema!(NewPickupPoint {
+    company_id: CompanyId,
+    name: String,
+    country: Alpha3,
+    address: String,
+    coordinates: Coordinates,
+    #[default]
+    opening_hours: Vec<OpeningHours>,
+    price_factor: Option<f64>,
+});
+
+impl Validate for NewPickupPoint {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        validate_pickup_point(
+            Some(&self.name),
+            Some(&self.coordinates),
+            Some(&self.opening_hours),
+            self.price_factor,
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Insertable, Clone, Debug)]
+#[table_name = "pickup_points"]
+pub struct NewPickupPointRaw {
+    pub company_id: CompanyId,
+    pub name: String,
+    pub country: Alpha3,
+    pub address: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub opening_hours: serde_json::Value,
+    pub price_factor: Option<f64>,
+}
+
+impl NewPickupPoint {
+    pub fn to_raw(self) -> Result<NewPickupPointRaw, FailureError> {
+        let opening_hours = serde_json::to_value(self.opening_hours)
+            .map_err(|e| e.context("Can not parse opening hours from value").context(Error::Parse))?;
+
+        Ok(NewPickupPointRaw {
+            company_id: self.company_id,
+            name: self.name,
+            country: self.country,
+            address: self.address,
+            latitude: self.coordinates.latitude,
+            longitude: self.coordinates.longitude,
+            opening_hours,
+            price_factor: self.price_factor,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UpdatePickupPoint {
+    pub name: Option<String>,
+    pub address: Option<String>,
+    pub coordinates: Option<Coordinates>,
+    pub opening_hours: Option<Vec<OpeningHours>>,
+    pub price_factor: Option<f64>,
+}
+
+json_schema!(UpdatePickupPoint {
+    name: Option<String>,
+    address: Option<String>,
+    coordinates: Option<Coordinates>,
+    opening_hours: Option<Vec<OpeningHours>>,
+    price_factor: Option<f64>,
+});
+
+impl Validate for UpdatePickupPoint {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        validate_pickup_point(
+            self.name.as_ref(),
+            self.coordinates.as_ref(),
+            self.opening_hours.as_ref(),
+            self.price_factor,
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, AsChangeset, Clone, Debug)]
+#[table_name = "pickup_points"]
+pub struct UpdatePickupPointRaw {
+    pub name: Option<String>,
+    pub address: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub opening_hours: Option<serde_json::Value>,
+    pub price_factor: Option<f64>,
+}
+
+impl UpdatePickupPoint {
+    pub fn to_raw(self) -> Result<UpdatePickupPointRaw, FailureError> {
+        let opening_hours = match self.opening_hours {
+            Some(opening_hours) => Some(
+                serde_json::to_value(opening_hours)
+                    .map_err(|e| e.context("Can not parse opening hours from value").context(Error::Parse))?,
+            ),
+            None => None,
+        };
+
+        Ok(UpdatePickupPointRaw {
+            name: self.name,
+            address: self.address,
+            latitude: self.coordinates.map(|coordinates| coordinates.latitude),
+            longitude: self.coordinates.map(|coordinates| coordinates.longitude),
+            opening_hours,
+            price_factor: self.price_factor,
+        })
+    }
+}
+
+/// Parameters of the nearby pickup points search
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct NearbyPickupPointsSearch {
+    pub coordinates: Coordinates,
+    pub radius_km: f64,
+}
+
+impl Validate for NearbyPickupPointsSearch {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        self.coordinates.validate()?;
+
+        if self.radius_km.is_nan() || self.radius_km <= 0.0 || self.radius_km > MAX_NEARBY_RADIUS_KM {
+            Err(validation_errors!({
+                "radius": ["radius" => format!("Radius must be positive and not greater than {} km", MAX_NEARBY_RADIUS_KM)]
+            }))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opening_hours(weekday: u8, opens: &str, closes: &str) -> OpeningHours {
+        OpeningHours {
+            weekday,
+            opens: opens.to_string(),
+            closes: closes.to_string(),
+        }
+    }
+
+    #[test]
+    fn opening_hours_validation() {
+        assert!(opening_hours(1, "09:00", "18:30").validate().is_ok());
+        assert!(opening_hours(0, "09:00", "18:30").validate().is_err());
+        assert!(opening_hours(8, "09:00", "18:30").validate().is_err());
+        assert!(opening_hours(1, "9:00", "18:30").validate().is_err());
+        assert!(opening_hours(1, "09:00", "24:00").validate().is_err());
+        assert!(opening_hours(1, "18:30", "09:00").validate().is_err());
+    }
+
+    #[test]
+    fn nearby_search_radius_is_limited() {
+        let search = |radius_km| NearbyPickupPointsSearch {
+            coordinates: Coordinates {
+                latitude: 55.75,
+                longitude: 37.62,
+            },
+            radius_km,
+        };
+
+        assert!(search(5.0).validate().is_ok());
+        assert!(search(0.0).validate().is_err());
+        assert!(search(MAX_NEARBY_RADIUS_KM + 1.0).validate().is_err());
+    }
+
+    #[test]
+    fn price_factor_must_be_positive() {
+        let update = |price_factor| UpdatePickupPoint {
+            name: None,
+            address: None,
+            coordinates: None,
+            opening_hours: None,
+            price_factor,
+        };
+
+        assert!(update(None).validate().is_ok());
+        assert!(update(Some(0.8)).validate().is_ok());
+        assert!(update(Some(0.0)).validate().is_err());
+    }
+}
@@ -11,6 +11,18 @@ pub struct Pickups {
     pub price: Option<ProductPrice>,
 }
 
+impl Pickups {
+    /// Pickup of another base product of the same store with the same price
+    pub fn clone_to(&self, base_product_id: BaseProductId) -> NewPickups {
+        NewPickups {
+            base_product_id,
+            store_id: self.store_id,
+            pickup: self.pickup,
+            price: self.price,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Insertable, Clone, Debug)]
 #[table_name = "pickups"]
 pub struct NewPickups {
@@ -20,6 +32,13 @@ pub struct NewPickups {
     pub price: Option<ProductPrice>,
 }
 
+json_schema!(NewPickups {
+    base_product_id: BaseProductId,
+    store_id: StoreId,
+    pickup: bool,
+    price: Option<ProductPrice>,
+});
+
 #[derive(Serialize, Deserialize, Insertable, AsChangeset, Clone, Debug)]
 #[table_name = "pickups"]
 pub struct UpdatePickups {
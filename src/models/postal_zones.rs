@@ -0,0 +1,221 @@
+//! Models for postal zones - ranges of the postal codes within a country priced apart from the rest of it,
+//! e.g. the remote areas carriers charge extra for. Shipping rates reference the zones in the zone table
+//! as `<alpha3>:<name of the postal zone>`
+use failure::{err_msg, Error as FailureError, Fail};
+use validator::{Validate, ValidationErrors};
+
+use stq_types::Alpha3;
+
+use models::TypedJson;
+use schema::postal_zones;
+
+/// Range of the postal codes, both bounds inclusive. Bounds are compared with the same number
+/// of the leading characters of a postal code, so that `A0A`-`A0Z` covers every code starting with them
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PostalCodeRange {
+    pub from: String,
+    pub to: String,
+}
+
+impl PostalCodeRange {
+    pub fn contains(&self, postal_code: &str) -> bool {
+        let postal_code = normalize_postal_code(postal_code);
+        let from = normalize_postal_code(&self.from);
+        let to = normalize_postal_code(&self.to);
+
+        match postal_code.get(..from.len()) {
+            Some(prefix) => from.as_str() <= prefix && prefix <= to.as_str(),
+            None => false,
+        }
+    }
+
+    fn validate_bounds(&self) -> Result<(), String> {
+        let from = normalize_postal_code(&self.from);
+        let to = normalize_postal_code(&self.to);
+
+        if from.is_empty() || from.chars().chain(to.chars()).any(|c| !c.is_ascii_alphanumeric()) {
+            Err(format!("Invalid postal code range {}-{}", self.from, self.to))
+        } else if from.len() != to.len() {
+            Err(format!(
+                "Bounds of postal code range {}-{} must have the same length",
+                self.from, self.to
+            ))
+        } else if from > to {
+            Err(format!("Postal code range {}-{} is reversed", self.from, self.to))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+json_schema!(PostalCodeRange { from: String, to: String });
+
+/// Postal codes are compared uppercased and without the separators, so that `k1a 0b1` and `K1A0B1` are the same code
+pub fn normalize_postal_code(postal_code: &str) -> String {
+    postal_code
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect::<String>()
+        .to_uppercase()
+}
+
+#[derive(Serialize, Deserialize, Queryable, Clone, Debug, PartialEq)]
+pub struct PostalZone {
+    pub id: i32,
+    pub alpha3: Alpha3,
+    pub name: String,
+    pub ranges: TypedJson<Vec<PostalCodeRange>>,
+}
+
+impl PostalZone {
+    pub fn contains(&self, postal_code: &str) -> bool {
+        self.ranges.iter().any(|range| range.contains(postal_code))
+    }
+
+    /// Name of the zone in the zone table of the shipping rates
+    pub fn reference(&self) -> String {
+        format!("{}:{}", self.alpha3, self.name)
+    }
+}
+
+#[derive(Serialize, Deserialize, Insertable, Clone, Debug)]
+#[table_name = "postal_zones"]
+pub struct NewPostalZone {
+    pub alpha3: Alpha3,
+    pub name: String,
+    pub ranges: TypedJson<Vec<PostalCodeRange>>,
+}
+
+json_schema!(NewPostalZone {
+    alpha3: Alpha3,
+    name: String,
+    ranges: Vec<PostalCodeRange>,
+});
+
+impl Validate for NewPostalZone {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        validate_postal_zone_name(&self.name)?;
+
+        for range in self.ranges.iter() {
+            range
+                .validate_bounds()
+                .map_err(|message| validation_errors!({ "ranges": ["ranges" => message] }))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn validate_postal_zone_name(name: &str) -> Result<(), ValidationErrors> {
+    if name.trim().is_empty() {
+        Err(validation_errors!({ "name": ["name" => "Name must not be empty"] }))?;
+    }
+
+    if name.contains(':') {
+        Err(validation_errors!({ "name": ["name" => "Name must not contain \":\""] }))?;
+    }
+
+    Ok(())
+}
+
+/// Payload importing the postal codes list of the carrier, the ranges of an existing zone
+/// with the same country and name are replaced
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ImportPostalZone {
+    pub alpha3: Alpha3,
+    pub name: String,
+    /// CSV with the "From,To" header, "To" may be empty for a single postal code
+    pub postal_codes_csv_base64: String,
+}
+
+json_schema!(ImportPostalZone {
+    alpha3: Alpha3,
+    name: String,
+    postal_codes_csv_base64: String,
+});
+
+impl Validate for ImportPostalZone {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        validate_postal_zone_name(&self.name)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PostalCodesCsvData(pub Vec<PostalCodeRange>);
+
+impl PostalCodesCsvData {
+    pub fn parse_csv(csv: &[u8]) -> Result<PostalCodesCsvData, FailureError> {
+        let mut reader = csv::Reader::from_reader(csv);
+
+        let data = reader
+            .records()
+            .enumerate()
+            .try_fold(Vec::<PostalCodeRange>::new(), |mut ranges, (row_num, record)| {
+                let row_num = row_num + 2; // Count from 1, skip header row
+                let record = record.map_err(|e| FailureError::from(e.context(format!("Invalid CSV record (row {})", row_num))))?;
+
+                match record.iter().map(str::trim).collect::<Vec<_>>().as_slice() {
+                    [from, to] => {
+                        let range = PostalCodeRange {
+                            from: from.to_string(),
+                            to: if to.is_empty() { from.to_string() } else { to.to_string() },
+                        };
+                        range
+                            .validate_bounds()
+                            .map_err(|message| format_err!("{} (row {})", message, row_num))?;
+
+                        ranges.push(range);
+                        Ok(ranges)
+                    }
+                    _ => Err(format_err!("Invalid row {}", row_num)),
+                }
+            })?;
+
+        if data.is_empty() {
+            Err(err_msg("CSV is empty"))
+        } else {
+            Ok(PostalCodesCsvData(data))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(from: &str, to: &str) -> PostalCodeRange {
+        PostalCodeRange {
+            from: from.to_string(),
+            to: to.to_string(),
+        }
+    }
+
+    #[test]
+    fn ranges_match_leading_characters_of_postal_codes() {
+        let remote = PostalZone {
+            id: 1,
+            alpha3: Alpha3("CAN".to_string()),
+            name: "Remote".to_string(),
+            ranges: TypedJson(vec![range("X0A", "X0C"), range("Y1A 0A1", "Y1A 9Z9")]),
+        };
+
+        assert!(remote.contains("x0b 1h0"));
+        assert!(remote.contains("Y1A 2C6"));
+        assert!(!remote.contains("K1A 0B1"));
+        assert!(!remote.contains("X0"));
+        assert_eq!(remote.reference(), "CAN:Remote");
+    }
+
+    #[test]
+    fn postal_codes_csv_has_ranges_and_single_codes() {
+        let csv = "From,To\n0872,\n2898,2899\n";
+
+        assert_eq!(
+            PostalCodesCsvData::parse_csv(csv.as_bytes()).unwrap(),
+            PostalCodesCsvData(vec![range("0872", "0872"), range("2898", "2899")])
+        );
+        assert!(PostalCodesCsvData::parse_csv("From,To\n2899,2898\n".as_bytes()).is_err());
+        assert!(PostalCodesCsvData::parse_csv("From,To\n2898,289\n".as_bytes()).is_err());
+        assert!(PostalCodesCsvData::parse_csv("From,To\n".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,76 @@
+//! Rounding of the delivery prices, applied as the last step of the price calculation
+//! so that prices like 123.4567 never reach the checkout
+use stq_static_resources::Currency;
+
+/// Decimal places prices are kept with after rounding, hides the binary floating point error of the steps like 0.05
+const ROUNDED_PRICE_DECIMALS: i32 = 6;
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundingMode {
+    /// To the nearest multiple of the step, halves are rounded up
+    Nearest,
+    /// To the next multiple of the step, the price is never decreased
+    Up,
+}
+
+/// Rounding of the prices in the currency, e.g. to 0.01, 0.05 or whole units with `step` 1
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PriceRoundingPolicy {
+    pub currency: Currency,
+    pub step: f64,
+    pub mode: RoundingMode,
+}
+
+impl PriceRoundingPolicy {
+    /// Rounded price, the price is not changed if the step is not positive
+    pub fn round(&self, price: f64) -> f64 {
+        if self.step.is_nan() || self.step <= 0.0 {
+            return price;
+        }
+
+        let steps = price / self.step;
+        let steps = match self.mode {
+            RoundingMode::Nearest => steps.round(),
+            // prices already on the step must not be raised by the floating point error of the division
+            RoundingMode::Up => (steps - 1e-9).ceil(),
+        };
+
+        let precision = 10f64.powi(ROUNDED_PRICE_DECIMALS);
+        (steps * self.step * precision).round() / precision
+    }
+}
+
+/// Policy of the currency, prices in the currencies without a policy are not rounded
+pub fn find_rounding_policy(policies: &[PriceRoundingPolicy], currency: Currency) -> Option<&PriceRoundingPolicy> {
+    policies.iter().find(|policy| policy.currency == currency)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(step: f64, mode: RoundingMode) -> PriceRoundingPolicy {
+        PriceRoundingPolicy {
+            currency: Currency::USD,
+            step,
+            mode,
+        }
+    }
+
+    #[test]
+    fn prices_are_rounded_to_the_step() {
+        assert_eq!(policy(0.01, RoundingMode::Nearest).round(123.4567), 123.46);
+        assert_eq!(policy(0.05, RoundingMode::Nearest).round(123.4567), 123.45);
+        assert_eq!(policy(0.05, RoundingMode::Up).round(123.4567), 123.5);
+        assert_eq!(policy(1.0, RoundingMode::Nearest).round(123.4567), 123.0);
+        assert_eq!(policy(1.0, RoundingMode::Up).round(123.4567), 124.0);
+    }
+
+    #[test]
+    fn prices_on_the_step_are_not_changed() {
+        assert_eq!(policy(0.05, RoundingMode::Up).round(0.3), 0.3);
+        assert_eq!(policy(1.0, RoundingMode::Up).round(124.0), 124.0);
+        assert_eq!(policy(0.0, RoundingMode::Up).round(123.4567), 123.4567);
+    }
+}
@@ -0,0 +1,217 @@
+//! Models for pricing rules - markups and discounts the marketplace applies to the delivery prices of the stores
+use validator::{Validate, ValidationErrors};
+
+use stq_types::{Alpha3, CompanyPackageId, StoreId};
+
+use models::SurchargeKind;
+use schema::pricing_rules;
+
+/// Deliveries of the store the rule applies to
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, DieselTypes)]
+#[serde(rename_all = "snake_case")]
+pub enum PricingRuleScope {
+    /// All deliveries of the store
+    Store,
+    /// Deliveries of the store with the company package
+    CompanyPackage,
+    /// Deliveries of the store to the country
+    Country,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, DieselTypes)]
+#[serde(rename_all = "snake_case")]
+pub enum PricingRuleDirection {
+    /// Delivery price is increased
+    Markup,
+    /// Delivery is subsidized, the price is decreased but never below zero
+    Discount,
+}
+
+/// Markup or discount of the delivery price of the store. Applicable rules are applied to the base rate
+/// one after another from the highest priority, percentage rules are calculated from the price after the previous rules
+#[derive(Serialize, Deserialize, Queryable, Clone, Debug, PartialEq)]
+pub struct PricingRule {
+    pub id: i32,
+    pub store_id: StoreId,
+    pub scope: PricingRuleScope,
+    pub company_package_id: Option<CompanyPackageId>,
+    pub to_alpha3: Option<Alpha3>,
+    pub kind: SurchargeKind,
+    pub direction: PricingRuleDirection,
+    pub value: f64,
+    pub priority: i32,
+}
+
+impl PricingRule {
+    pub fn applies_to(&self, store_id: StoreId, company_package_id: CompanyPackageId, delivery_to: &Alpha3) -> bool {
+        self.store_id == store_id
+            && match self.scope {
+                PricingRuleScope::Store => true,
+                PricingRuleScope::CompanyPackage => self.company_package_id == Some(company_package_id),
+                PricingRuleScope::Country => self.to_alpha3.as_ref() == Some(delivery_to),
+            }
+    }
+
+    /// Amount the price changes by, negative for the discounts
+    pub fn calculate_adjustment(&self, price: f64) -> f64 {
+        let amount = match self.kind {
+            SurchargeKind::Percentage => price * self.value / 100.0,
+            SurchargeKind::Fixed => self.value,
+        };
+
+        match self.direction {
+            PricingRuleDirection::Markup => amount,
+            PricingRuleDirection::Discount => -amount.min(price.max(0.0)),
+        }
+    }
+}
+
+/// Price after all the rules, `rules` are expected in the order of priority
+pub fn apply_pricing_rules<'a, I>(rules: I, price: f64) -> f64
+where
+    I: IntoIterator<Item = &'a PricingRule>,
+{
+    rules
+        .into_iter()
+        .fold(price, |price, rule| price + rule.calculate_adjustment(price))
+}
+
+fn validate_pricing_rule_value(value: f64) -> Result<(), ValidationErrors> {
+    if value.is_nan() || value < 0.0 {
+        Err(validation_errors!({ "value": ["value" => "Value must not be negative"] }))?;
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Insertable, Clone, Debug)]
+#[table_name = "pricing_rules"]
+pub struct NewPricingRule {
+    pub store_id: StoreId,
+    pub scope: PricingRuleScope,
+    pub company_package_id: Option<CompanyPackageId>,
+    pub to_alpha3: Option<Alpha3>,
+    pub kind: SurchargeKind,
+    pub direction: PricingRuleDirection,
+    pub value: f64,
+    #[serde(default)]
+    pub priority: i32,
+}
+
+json_schema!(NewPricingRule {
+    store_id: StoreId,
+    scope: PricingRuleScope,
+    company_package_id: Option<CompanyPackageId>,
+    to_alpha3: Option<Alpha3>,
+    kind: SurchargeKind,
+    direction: PricingRuleDirection,
+    value: f64,
+    #[default]
+    priority: i32,
+});
+
+impl Validate for NewPricingRule {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let (company_package_set, country_set) = (self.company_package_id.is_some(), self.to_alpha3.is_some());
+        match (self.scope, company_package_set, country_set) {
+            (PricingRuleScope::Store, false, false)
+            | (PricingRuleScope::CompanyPackage, true, false)
+            | (PricingRuleScope::Country, false, true) => {}
+            _ => Err(validation_errors!({
+                "scope": ["scope" => "Company package is set only for the company package scope and country only for the country scope"]
+            }))?,
+        }
+
+        validate_pricing_rule_value(self.value)
+    }
+}
+
+/// Only the price change and the priority can be changed, the deliveries the rule applies to are fixed
+#[derive(Serialize, Deserialize, AsChangeset, Clone, Debug)]
+#[table_name = "pricing_rules"]
+pub struct UpdatePricingRule {
+    pub kind: Option<SurchargeKind>,
+    pub direction: Option<PricingRuleDirection>,
+    pub value: Option<f64>,
+    pub priority: Option<i32>,
+}
+
+json_schema!(UpdatePricingRule {
+    kind: Option<SurchargeKind>,
+    direction: Option<PricingRuleDirection>,
+    value: Option<f64>,
+    priority: Option<i32>,
+});
+
+impl Validate for UpdatePricingRule {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        match self.value {
+            Some(value) => validate_pricing_rule_value(value),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(id: i32, kind: SurchargeKind, direction: PricingRuleDirection, value: f64) -> PricingRule {
+        PricingRule {
+            id,
+            store_id: StoreId(1),
+            scope: PricingRuleScope::Store,
+            company_package_id: None,
+            to_alpha3: None,
+            kind,
+            direction,
+            value,
+            priority: 0,
+        }
+    }
+
+    #[test]
+    fn rules_are_applied_one_after_another() {
+        let markup = rule(1, SurchargeKind::Percentage, PricingRuleDirection::Markup, 10.0);
+        let discount = rule(2, SurchargeKind::Fixed, PricingRuleDirection::Discount, 20.0);
+
+        assert!((apply_pricing_rules(&[markup.clone(), discount.clone()], 200.0) - 200.0).abs() < 1e-6);
+        assert!((apply_pricing_rules(&[discount.clone(), markup], 200.0) - 198.0).abs() < 1e-6);
+        assert!((apply_pricing_rules(&[discount], 15.0)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn applies_to_deliveries_of_scope_only() {
+        let rus = Alpha3("RUS".to_string());
+        let rule = PricingRule {
+            scope: PricingRuleScope::Country,
+            to_alpha3: Some(rus.clone()),
+            ..rule(1, SurchargeKind::Fixed, PricingRuleDirection::Markup, 10.0)
+        };
+
+        assert!(rule.applies_to(StoreId(1), CompanyPackageId(5), &rus));
+        assert!(!rule.applies_to(StoreId(2), CompanyPackageId(5), &rus));
+        assert!(!rule.applies_to(StoreId(1), CompanyPackageId(5), &Alpha3("USA".to_string())));
+    }
+
+    #[test]
+    fn target_of_scope_is_required() {
+        let payload = NewPricingRule {
+            store_id: StoreId(1),
+            scope: PricingRuleScope::CompanyPackage,
+            company_package_id: None,
+            to_alpha3: None,
+            kind: SurchargeKind::Percentage,
+            direction: PricingRuleDirection::Discount,
+            value: 50.0,
+            priority: 0,
+        };
+        assert!(payload.validate().is_err());
+
+        let payload = NewPricingRule {
+            company_package_id: Some(CompanyPackageId(1)),
+            ..payload
+        };
+        assert!(payload.validate().is_ok());
+    }
+}
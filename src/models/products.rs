@@ -1,13 +1,11 @@
-use failure::Error as FailureError;
-use failure::Fail;
-use serde_json;
+use std::time::SystemTime;
+
 use validator::{Validate, ValidationError, ValidationErrors};
 
 use stq_static_resources::Currency;
 use stq_types::{Alpha3, BaseProductId, CompanyPackageId, ProductPrice, ShippingId, StoreId};
 
-use errors::Error;
-use models::{get_country_from_forest, Company, Packages, ShipmentMeasurements, ShippingRate};
+use models::{get_country_from_forest, Company, HazardClass, Packages, ShipmentMeasurements, ShippingRate, TypedJson};
 use schema::products;
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug, DieselTypes)]
@@ -24,9 +22,13 @@ pub struct ProductsRaw {
     pub store_id: StoreId,
     pub company_package_id: CompanyPackageId,
     pub price: Option<ProductPrice>,
-    pub deliveries_to: serde_json::Value,
+    pub deliveries_to: TypedJson<Vec<Alpha3>>,
     pub shipping: ShippingVariant,
     pub currency: Currency,
+    pub hazard_classes: TypedJson<Vec<HazardClass>>,
+    pub updated_at: SystemTime,
+    pub free_delivery_over: Option<ProductPrice>,
+    pub delivery_from: Option<Alpha3>,
 }
 
 #[derive(Serialize, Deserialize, Insertable, Clone, Debug)]
@@ -36,18 +38,22 @@ pub struct NewProductsRaw {
     pub store_id: StoreId,
     pub company_package_id: CompanyPackageId,
     pub price: Option<ProductPrice>,
-    pub deliveries_to: serde_json::Value,
+    pub deliveries_to: TypedJson<Vec<Alpha3>>,
     pub shipping: ShippingVariant,
     pub currency: Currency,
+    pub hazard_classes: TypedJson<Vec<HazardClass>>,
+    pub free_delivery_over: Option<ProductPrice>,
+    pub delivery_from: Option<Alpha3>,
 }
 
 #[derive(Serialize, Deserialize, Insertable, AsChangeset, Clone, Debug)]
 #[table_name = "products"]
 pub struct UpdateProductsRaw {
     pub price: Option<ProductPrice>,
-    pub deliveries_to: Option<serde_json::Value>,
+    pub deliveries_to: Option<TypedJson<Vec<Alpha3>>>,
     pub shipping: Option<ShippingVariant>,
     pub currency: Option<Currency>,
+    pub free_delivery_over: Option<ProductPrice>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -60,6 +66,12 @@ pub struct Products {
     pub deliveries_to: Vec<Alpha3>,
     pub shipping: ShippingVariant,
     pub currency: Currency,
+    pub hazard_classes: Vec<HazardClass>,
+    /// Delivery is free for the orders of the buyer starting from this total
+    pub free_delivery_over: Option<ProductPrice>,
+    /// Warehouse the package is shipped from, the same package may be listed once per warehouse of the seller
+    #[serde(default)]
+    pub delivery_from: Option<Alpha3>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -74,33 +86,81 @@ pub enum ShippingPrice {
     },
 }
 
+/// Default and maximum page size of the base products shipped by the company package
+pub const DEFAULT_COMPANY_PACKAGE_PRODUCTS_LIMIT: i64 = 100;
+pub const MAX_COMPANY_PACKAGE_PRODUCTS_LIMIT: i64 = 1000;
+
+/// Base product shipped by the company package
+#[derive(Serialize, Deserialize, Queryable, Clone, Debug, PartialEq)]
+pub struct CompanyPackageProduct {
+    pub base_product_id: BaseProductId,
+    pub store_id: StoreId,
+}
+
+/// Page of the base products shipped by the company package
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CompanyPackageProducts {
+    pub total_count: i64,
+    pub products: Vec<CompanyPackageProduct>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ProductWithShippingPrice {
     pub product: Products,
     pub shipping_price: ShippingPrice,
 }
 
+impl Products {
+    /// Shipping row of another base product with the same packages, prices and destinations
+    pub fn clone_to(&self, base_product_id: BaseProductId) -> NewProducts {
+        let Products {
+            store_id,
+            company_package_id,
+            price,
+            deliveries_to,
+            shipping,
+            currency,
+            hazard_classes,
+            free_delivery_over,
+            delivery_from,
+            ..
+        } = self.clone();
+
+        NewProducts {
+            base_product_id,
+            store_id,
+            company_package_id,
+            price,
+            deliveries_to,
+            shipping,
+            measurements: None,
+            delivery_from,
+            currency,
+            hazard_classes,
+            free_delivery_over,
+        }
+    }
+}
+
 impl ProductsRaw {
-    pub fn to_products(self) -> Result<Products, FailureError> {
-        let deliveries_to =
-            serde_json::from_value(self.deliveries_to).map_err(|e| e.context("Can not parse products from db").context(Error::Parse))?;
-        Ok(Products {
+    pub fn to_products(self) -> Products {
+        Products {
             id: self.id,
             base_product_id: self.base_product_id,
             store_id: self.store_id,
             company_package_id: self.company_package_id,
             price: self.price,
-            deliveries_to,
+            deliveries_to: self.deliveries_to.into_inner(),
             shipping: self.shipping,
             currency: self.currency,
-        })
+            hazard_classes: self.hazard_classes.into_inner(),
+            free_delivery_over: self.free_delivery_over,
+            delivery_from: self.delivery_from,
+        }
     }
 
-    pub fn get_deliveries_to(&self) -> Result<Vec<Alpha3>, FailureError> {
-        let used_codes = serde_json::from_value(self.deliveries_to.clone())
-            .map_err(|e| e.context("Can not parse deliveries_to from db").context(Error::Parse))?;
-
-        Ok(used_codes)
+    pub fn get_deliveries_to(&self) -> &[Alpha3] {
+        &self.deliveries_to
     }
 }
 
@@ -113,10 +173,33 @@ pub struct NewProducts {
     pub deliveries_to: Vec<Alpha3>,
     pub shipping: ShippingVariant,
     pub measurements: Option<ShipmentMeasurements>,
+    /// Warehouse the package is shipped from, packages of the sellers shipping from several warehouses
+    /// are listed once per warehouse
     pub delivery_from: Option<Alpha3>,
     pub currency: Currency,
+    /// Filled from `NewShipping::hazard_classes` on upsert
+    #[serde(default, skip_deserializing)]
+    pub hazard_classes: Vec<HazardClass>,
+    /// Delivery is free for the orders of the buyer starting from this total
+    #[serde(default)]
+    pub free_delivery_over: Option<ProductPrice>,
 }
 
+json_schema!(NewProducts {
+    base_product_id: BaseProductId,
+    store_id: StoreId,
+    company_package_id: CompanyPackageId,
+    price: Option<ProductPrice>,
+    deliveries_to: Vec<Alpha3>,
+    shipping: ShippingVariant,
+    measurements: Option<ShipmentMeasurements>,
+    delivery_from: Option<Alpha3>,
+    currency: Currency,
+    #[skip]
+    hazard_classes: Vec<HazardClass>,
+    free_delivery_over: Option<ProductPrice>,
+});
+
 impl Validate for NewProducts {
     fn validate(&self) -> Result<(), ValidationErrors> {
         // TODO: Also validate measurements when the price is specified (will break frontend at the moment)
@@ -212,41 +295,79 @@ impl Validate for ShippingValidation {
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct NewProductValidation {
     pub product: NewProducts,
-    pub package: Option<PackageValidation>,
     pub shipping: ShippingValidation,
 }
 
 impl Validate for NewProductValidation {
     fn validate(&self) -> Result<(), ValidationErrors> {
-        let NewProductValidation {
-            ref product,
-            ref package,
-            ref shipping,
-        } = self;
+        let NewProductValidation { ref product, ref shipping } = self;
 
         product.validate()?;
+        shipping.validate()
+    }
+}
 
-        if let Some(ref package) = package {
-            package.validate()?;
+/// Product measurements checked against the package of the selected company package
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PackageLimitsValidation {
+    pub company_package_id: CompanyPackageId,
+    pub measurements: ShipmentMeasurements,
+    pub package: Packages,
+}
+
+/// Checks all the selected company packages at once, so that the error lists every rejected package
+/// under the `packages` field along with the limits that were exceeded
+pub fn validate_packages_limits(validations: &[PackageLimitsValidation]) -> Result<(), ValidationErrors> {
+    let mut validation_errors = ValidationErrors::new();
+    let mut has_errors = false;
+
+    for validation in validations {
+        let PackageLimitsValidation {
+            company_package_id,
+            measurements,
+            package,
+        } = validation;
+
+        if let Err(reason) = package.within_limits(*measurements) {
+            let mut validation_error = ValidationError::new("package_limits");
+            validation_error.message = Some(
+                format!(
+                    "Measurements do not fit package {} of company package with id: {}",
+                    package.name, company_package_id
+                )
+                .into(),
+            );
+            validation_error.add_param("company_package_id".into(), company_package_id);
+            validation_error.add_param("package_id".into(), &package.id);
+            validation_error.add_param("package_name".into(), &package.name);
+            validation_error.add_param("reason".into(), &reason);
+
+            validation_errors.add("packages", validation_error);
+            has_errors = true;
         }
+    }
 
-        shipping.validate()
+    if has_errors {
+        Err(validation_errors)
+    } else {
+        Ok(())
     }
 }
 
 impl NewProducts {
-    pub fn to_raw(self) -> Result<NewProductsRaw, FailureError> {
-        let deliveries_to =
-            serde_json::to_value(self.deliveries_to).map_err(|e| e.context("Can not parse products from db").context(Error::Parse))?;
-        Ok(NewProductsRaw {
+    pub fn to_raw(self) -> NewProductsRaw {
+        NewProductsRaw {
             base_product_id: self.base_product_id,
             store_id: self.store_id,
             company_package_id: self.company_package_id,
             price: self.price,
-            deliveries_to,
+            deliveries_to: TypedJson(self.deliveries_to),
             shipping: self.shipping,
             currency: self.currency,
-        })
+            hazard_classes: TypedJson(self.hazard_classes),
+            free_delivery_over: self.free_delivery_over,
+            delivery_from: self.delivery_from,
+        }
     }
 }
 
@@ -256,22 +377,75 @@ pub struct UpdateProducts {
     pub deliveries_to: Option<Vec<Alpha3>>,
     pub shipping: Option<ShippingVariant>,
     pub currency: Option<Currency>,
+    pub free_delivery_over: Option<ProductPrice>,
 }
 
+json_schema!(UpdateProducts {
+    price: Option<ProductPrice>,
+    deliveries_to: Option<Vec<Alpha3>>,
+    shipping: Option<ShippingVariant>,
+    currency: Option<Currency>,
+    free_delivery_over: Option<ProductPrice>,
+});
+
 impl UpdateProducts {
-    pub fn to_raw(self) -> Result<UpdateProductsRaw, FailureError> {
-        let deliveries_to = match self.deliveries_to {
-            Some(v) => serde_json::to_value(v)
-                .map(Some)
-                .map_err(|e| e.context("Can not parse products from value").context(Error::Parse))?,
-            None => None,
-        };
-
-        Ok(UpdateProductsRaw {
+    pub fn to_raw(self) -> UpdateProductsRaw {
+        UpdateProductsRaw {
             price: self.price,
-            deliveries_to,
+            deliveries_to: self.deliveries_to.map(TypedJson),
             shipping: self.shipping,
             currency: self.currency,
-        })
+            free_delivery_over: self.free_delivery_over,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+
+    use stq_types::PackageId;
+
+    use super::*;
+
+    fn package_limits(company_package_id: i32, volume_cubic_cm: u32, weight_g: u32) -> PackageLimitsValidation {
+        PackageLimitsValidation {
+            company_package_id: CompanyPackageId(company_package_id),
+            measurements: ShipmentMeasurements { volume_cubic_cm, weight_g },
+            package: Packages {
+                id: PackageId(company_package_id),
+                name: format!("package{}", company_package_id),
+                max_size: 1000,
+                min_size: 10,
+                max_weight: 500,
+                min_weight: 1,
+                deliveries_to: vec![],
+                deliveries_to_zones: vec![],
+                name_translations: vec![],
+            },
+        }
+    }
+
+    #[test]
+    fn packages_limits_valid() {
+        validate_packages_limits(&[package_limits(1, 100, 100), package_limits(2, 1000, 1)]).unwrap();
+    }
+
+    #[test]
+    fn packages_limits_lists_every_rejected_package() {
+        let errors = validate_packages_limits(&[
+            package_limits(1, 5000, 100),
+            package_limits(2, 100, 100),
+            package_limits(3, 100, 1000),
+        ])
+        .unwrap_err()
+        .inner();
+
+        let rejected = errors["packages"]
+            .iter()
+            .map(|error| error.params["company_package_id"].clone())
+            .collect::<Vec<_>>();
+
+        assert_eq!(rejected, vec![serde_json::Value::from(1), serde_json::Value::from(3)]);
     }
 }
@@ -0,0 +1,396 @@
+//! Price resolution of a company package: the ordered steps the price is looked up by
+//! and the trace of the steps tried for the explanation of the price
+
+use std::iter;
+
+use validator::{Validate, ValidationErrors};
+
+use stq_types::Alpha3;
+
+use models::{ShippingRateSource, ShippingRates};
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RateResolutionStep {
+    /// Stored rates to a postal zone of the destination country, only tried when the postal code is known
+    PostalZone,
+    /// Stored rates to the subdivision of the destination country, only tried when the subdivision is known
+    Subdivision,
+    /// Stored rates to the destination country
+    ExactDestination,
+    /// Stored rates of a zone the destination country is in. Rates of the country itself take precedence over
+    /// the ones of its zones, so the step only matches the countries without their own rates
+    Zone,
+    /// Stored rates to a region the destination country is in, the closest region first
+    RegionGroup,
+    /// Stored rates to the root of the countries tree, they apply to every destination
+    FallbackRate,
+    /// Live quote of the carrier, only available for the company packages with on demand rates
+    CarrierQuote,
+}
+
+/// Order of the company packages without a configured one, the same as before the order was configurable
+pub fn default_rate_resolution_order(shipping_rate_source: &ShippingRateSource) -> Vec<RateResolutionStep> {
+    match *shipping_rate_source {
+        ShippingRateSource::OnDemand { .. } => vec![
+            RateResolutionStep::CarrierQuote,
+            RateResolutionStep::PostalZone,
+            RateResolutionStep::Subdivision,
+            RateResolutionStep::ExactDestination,
+            RateResolutionStep::Zone,
+        ],
+        _ => vec![
+            RateResolutionStep::PostalZone,
+            RateResolutionStep::Subdivision,
+            RateResolutionStep::ExactDestination,
+            RateResolutionStep::Zone,
+        ],
+    }
+}
+
+/// Region group and fallback rate steps look the rates up by the regions of the destination from the countries tree
+pub fn uses_countries_tree(steps: &[RateResolutionStep]) -> bool {
+    steps
+        .iter()
+        .any(|step| *step == RateResolutionStep::RegionGroup || *step == RateResolutionStep::FallbackRate)
+}
+
+/// Order must have at least one step and every step at most once
+pub fn validate_rate_resolution_order(steps: &[RateResolutionStep]) -> Result<(), ValidationErrors> {
+    if steps.is_empty() {
+        Err(validation_errors!({
+            "rate_resolution_order": ["rate_resolution_order" => "At least one step must be set"]
+        }))?;
+    }
+
+    if steps.iter().enumerate().any(|(i, step)| steps[..i].contains(step)) {
+        Err(validation_errors!({
+            "rate_resolution_order": ["rate_resolution_order" => "Every step must be set at most once"]
+        }))?;
+    }
+
+    Ok(())
+}
+
+/// Price resolution order of the company package, `None` resets it to the default one
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SetRateResolutionOrder {
+    pub steps: Option<Vec<RateResolutionStep>>,
+}
+
+json_schema!(SetRateResolutionOrder {
+    steps: Option<Vec<RateResolutionStep>>,
+});
+
+impl Validate for SetRateResolutionOrder {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        match self.steps {
+            Some(ref steps) => validate_rate_resolution_order(steps),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RateResolutionOutcome {
+    /// Step gave the price
+    Matched,
+    /// There are no stored rates for the step
+    NoRates,
+    /// Stored rates were found, but none of their brackets fits the shipment
+    NoMatchingRate,
+    /// Carrier quote is not available for the company package
+    NotApplicable,
+    /// Carrier did not give the quote
+    Failed,
+}
+
+/// Step tried by the price resolution
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct RateResolutionAttempt {
+    pub step: RateResolutionStep,
+    pub outcome: RateResolutionOutcome,
+}
+
+/// Destination of the shipment with the codes the stored rates steps look the rates up by
+#[derive(Clone, Debug)]
+pub struct RateRoute {
+    pub delivery_to: Alpha3,
+    /// ISO 3166-2 code of the subdivision of the destination country
+    pub delivery_to_subdivision: Option<String>,
+    /// Postal zones of the destination country the postal code of the shipment is in
+    pub postal_zone_ids: Vec<i32>,
+    /// Regions the destination country is in, the closest region first
+    pub region_codes: Vec<Alpha3>,
+    /// Root of the countries tree
+    pub fallback_code: Option<Alpha3>,
+}
+
+impl RateRoute {
+    /// `parent_codes` are the codes from the destination country up to the root of the countries tree
+    pub fn from_parent_codes(delivery_to: Alpha3, parent_codes: Vec<Alpha3>) -> Self {
+        let mut region_codes = parent_codes.into_iter().filter(|code| *code != delivery_to).collect::<Vec<_>>();
+        let fallback_code = region_codes.pop();
+
+        RateRoute {
+            delivery_to,
+            delivery_to_subdivision: None,
+            postal_zone_ids: vec![],
+            region_codes,
+            fallback_code,
+        }
+    }
+
+    pub fn with_subdivision(self, delivery_to_subdivision: Option<String>) -> Self {
+        RateRoute {
+            delivery_to_subdivision,
+            ..self
+        }
+    }
+
+    pub fn with_postal_zones(self, postal_zone_ids: Vec<i32>) -> Self {
+        RateRoute { postal_zone_ids, ..self }
+    }
+
+    /// Countries and regions the rates to the whole country are looked up by, the destination first
+    pub fn country_destinations(&self) -> Vec<Alpha3> {
+        iter::once(&self.delivery_to)
+            .chain(self.region_codes.iter())
+            .chain(self.fallback_code.iter())
+            .cloned()
+            .collect()
+    }
+}
+
+impl RateResolutionStep {
+    /// Stored rates the step looks through, the carrier quote step does not use them
+    pub fn find_rates<'a>(&self, route: &RateRoute, rates: &'a [ShippingRates]) -> Option<&'a ShippingRates> {
+        let find_country = |code: &Alpha3| {
+            rates.iter().find(|rates| {
+                rates.to_alpha3 == *code
+                    && rates.to_zone_id.is_none()
+                    && rates.to_subdivision.is_none()
+                    && rates.to_postal_zone_id.is_none()
+            })
+        };
+
+        match *self {
+            // The first of the postal zones with rates, in the order of the route
+            RateResolutionStep::PostalZone => route
+                .postal_zone_ids
+                .iter()
+                .filter_map(|postal_zone_id| {
+                    rates
+                        .iter()
+                        .find(|rates| rates.to_alpha3 == route.delivery_to && rates.to_postal_zone_id == Some(*postal_zone_id))
+                })
+                .next(),
+            RateResolutionStep::Subdivision => route.delivery_to_subdivision.as_ref().and_then(|code| {
+                rates
+                    .iter()
+                    .find(|rates| rates.to_alpha3 == route.delivery_to && rates.to_subdivision.as_ref() == Some(code))
+            }),
+            RateResolutionStep::ExactDestination => find_country(&route.delivery_to),
+            RateResolutionStep::Zone => rates
+                .iter()
+                .find(|rates| rates.to_alpha3 == route.delivery_to && rates.to_zone_id.is_some()),
+            RateResolutionStep::RegionGroup => route.region_codes.iter().filter_map(&find_country).next(),
+            RateResolutionStep::FallbackRate => route.fallback_code.as_ref().and_then(&find_country),
+            RateResolutionStep::CarrierQuote => None,
+        }
+    }
+}
+
+/// Steps before the carrier quote and the ones after it, `None` if the order has no carrier quote
+pub fn split_at_carrier_quote(steps: &[RateResolutionStep]) -> (&[RateResolutionStep], Option<&[RateResolutionStep]>) {
+    match steps.iter().position(|step| *step == RateResolutionStep::CarrierQuote) {
+        Some(position) => (&steps[..position], Some(&steps[position + 1..])),
+        None => (steps, None),
+    }
+}
+
+/// Tries the stored rates steps in order until one of them gives the price, every tried step is traced
+pub fn resolve_stored_rates<T, F>(
+    steps: &[RateResolutionStep],
+    route: &RateRoute,
+    rates: &[ShippingRates],
+    price: F,
+) -> (Option<(RateResolutionStep, T)>, Vec<RateResolutionAttempt>)
+where
+    F: Fn(&ShippingRates) -> Option<T>,
+{
+    let mut attempts = vec![];
+    for step in steps {
+        let (outcome, resolved) = match step.find_rates(route, rates) {
+            None => (RateResolutionOutcome::NoRates, None),
+            Some(rates) => match price(rates) {
+                None => (RateResolutionOutcome::NoMatchingRate, None),
+                Some(price) => (RateResolutionOutcome::Matched, Some(price)),
+            },
+        };
+
+        attempts.push(RateResolutionAttempt { step: *step, outcome });
+        if let Some(price) = resolved {
+            return (Some((*step, price)), attempts);
+        }
+    }
+
+    (None, attempts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use models::ShippingRate;
+    use stq_types::{CompanyPackageId, ShippingRatesId};
+
+    fn alpha3(code: &str) -> Alpha3 {
+        Alpha3(code.to_string())
+    }
+
+    fn rates(to: &str, to_zone_id: Option<i32>, max_weight_g: u32) -> ShippingRates {
+        ShippingRates {
+            id: ShippingRatesId(1),
+            company_package_id: CompanyPackageId(1),
+            from_alpha3: alpha3("RUS"),
+            to_alpha3: alpha3(to),
+            to_zone_id,
+            rates: vec![ShippingRate {
+                weight_g: max_weight_g,
+                price: max_weight_g as f64,
+            }],
+            delivery_time_min_days: None,
+            delivery_time_max_days: None,
+            components: vec![],
+            to_subdivision: None,
+            to_postal_zone_id: None,
+        }
+    }
+
+    fn route() -> RateRoute {
+        RateRoute::from_parent_codes(alpha3("DEU"), vec![alpha3("DEU"), alpha3("XWE"), alpha3("XEU"), alpha3("XAL")])
+    }
+
+    fn price(rates: &ShippingRates) -> Option<f64> {
+        rates.rates.iter().find(|rate| rate.weight_g >= 1000).map(|rate| rate.price)
+    }
+
+    #[test]
+    fn route_splits_parent_codes_into_regions_and_fallback() {
+        let route = route();
+
+        assert_eq!(route.region_codes, vec![alpha3("XWE"), alpha3("XEU")]);
+        assert_eq!(route.fallback_code, Some(alpha3("XAL")));
+        assert_eq!(
+            route.country_destinations(),
+            vec![alpha3("DEU"), alpha3("XWE"), alpha3("XEU"), alpha3("XAL")]
+        );
+    }
+
+    #[test]
+    fn steps_are_tried_in_order_until_one_gives_price() {
+        let stored = vec![rates("DEU", None, 500), rates("XEU", None, 2000), rates("XAL", None, 5000)];
+        let steps = [
+            RateResolutionStep::ExactDestination,
+            RateResolutionStep::Zone,
+            RateResolutionStep::RegionGroup,
+            RateResolutionStep::FallbackRate,
+        ];
+
+        let (resolved, attempts) = resolve_stored_rates(&steps, &route(), &stored, price);
+
+        assert_eq!(resolved, Some((RateResolutionStep::RegionGroup, 2000.0)));
+        assert_eq!(
+            attempts.into_iter().map(|attempt| attempt.outcome).collect::<Vec<_>>(),
+            vec![
+                RateResolutionOutcome::NoMatchingRate,
+                RateResolutionOutcome::NoRates,
+                RateResolutionOutcome::Matched,
+            ]
+        );
+    }
+
+    #[test]
+    fn zone_step_only_matches_zone_rates() {
+        let stored = vec![rates("DEU", Some(1), 1000)];
+
+        assert!(RateResolutionStep::ExactDestination.find_rates(&route(), &stored).is_none());
+        assert!(RateResolutionStep::Zone.find_rates(&route(), &stored).is_some());
+    }
+
+    #[test]
+    fn subdivision_step_only_matches_rates_of_route_subdivision() {
+        let stored = vec![
+            ShippingRates {
+                to_subdivision: Some("DE-BY".to_string()),
+                ..rates("DEU", None, 1000)
+            },
+            rates("DEU", None, 2000),
+        ];
+        let step = RateResolutionStep::Subdivision;
+
+        assert!(step.find_rates(&route(), &stored).is_none());
+        assert!(step
+            .find_rates(&route().with_subdivision(Some("DE-BE".to_string())), &stored)
+            .is_none());
+        assert_eq!(
+            step.find_rates(&route().with_subdivision(Some("DE-BY".to_string())), &stored)
+                .map(|rates| rates.rates[0].price),
+            Some(1000.0)
+        );
+        assert_eq!(
+            RateResolutionStep::ExactDestination
+                .find_rates(&route(), &stored)
+                .map(|rates| rates.rates[0].price),
+            Some(2000.0)
+        );
+    }
+
+    #[test]
+    fn postal_zone_step_only_matches_rates_of_route_postal_zones() {
+        let stored = vec![
+            ShippingRates {
+                to_postal_zone_id: Some(7),
+                ..rates("DEU", None, 1000)
+            },
+            rates("DEU", None, 2000),
+        ];
+        let step = RateResolutionStep::PostalZone;
+
+        assert!(step.find_rates(&route(), &stored).is_none());
+        assert!(step.find_rates(&route().with_postal_zones(vec![3]), &stored).is_none());
+        assert_eq!(
+            step.find_rates(&route().with_postal_zones(vec![3, 7]), &stored)
+                .map(|rates| rates.rates[0].price),
+            Some(1000.0)
+        );
+        assert_eq!(
+            RateResolutionStep::ExactDestination
+                .find_rates(&route().with_postal_zones(vec![7]), &stored)
+                .map(|rates| rates.rates[0].price),
+            Some(2000.0)
+        );
+    }
+
+    #[test]
+    fn order_is_split_at_carrier_quote() {
+        let steps = [
+            RateResolutionStep::ExactDestination,
+            RateResolutionStep::CarrierQuote,
+            RateResolutionStep::FallbackRate,
+        ];
+
+        let (before, after) = split_at_carrier_quote(&steps);
+
+        assert_eq!(before, &[RateResolutionStep::ExactDestination]);
+        assert_eq!(after, Some(&[RateResolutionStep::FallbackRate][..]));
+    }
+
+    #[test]
+    fn order_with_repeated_steps_is_invalid() {
+        assert!(validate_rate_resolution_order(&[]).is_err());
+        assert!(validate_rate_resolution_order(&[RateResolutionStep::Zone, RateResolutionStep::Zone]).is_err());
+        assert!(validate_rate_resolution_order(&[RateResolutionStep::Zone, RateResolutionStep::CarrierQuote]).is_ok());
+    }
+}
@@ -1,12 +1,14 @@
 //! Models for managing Roles
 
 use serde_json;
+use validator::{Validate, ValidationErrors};
 
-use stq_types::{DeliveryRole, RoleId, UserId};
+use stq_types::{Alpha3, DeliveryRole, RoleId, StoreId, UserId};
 
+use models::countries::{get_countries_from_forest_by, Country};
 use schema::roles;
 
-#[derive(Serialize, Deserialize, Queryable, Insertable, Debug)]
+#[derive(Serialize, Deserialize, Queryable, Insertable, Clone, Debug)]
 #[table_name = "roles"]
 pub struct UserRole {
     pub id: RoleId,
@@ -15,6 +17,74 @@ pub struct UserRole {
     pub data: Option<serde_json::Value>,
 }
 
+impl UserRole {
+    /// Typed data of the role, `None` if the role has no data or it can not be parsed
+    pub fn role_data(&self) -> Option<RoleData> {
+        self.data.as_ref().and_then(RoleData::from_value)
+    }
+}
+
+/// Objects the store manager role is scoped to
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RoleData {
+    /// Manages products, pickups and margin of the store
+    StoreManager { store_id: StoreId },
+    /// Manages companies delivering from the country and products shipped by them
+    CountryManager { alpha3: Alpha3 },
+    /// Manages shipping of products and pickups of the store, granted by the store manager
+    ShippingManager { store_id: StoreId },
+}
+
+impl RoleData {
+    /// Parses role data, a bare number is the store id of roles created before the data was typed
+    pub fn from_value(value: &serde_json::Value) -> Option<RoleData> {
+        match value.as_i64() {
+            Some(store_id) => Some(RoleData::StoreManager {
+                store_id: StoreId(store_id as i32),
+            }),
+            None => serde_json::from_value(value.clone()).ok(),
+        }
+    }
+
+    /// Store managers own the store, only they may delegate its management
+    pub fn manages_store(&self, store_id_arg: StoreId) -> bool {
+        match *self {
+            RoleData::StoreManager { store_id } => store_id == store_id_arg,
+            RoleData::CountryManager { .. } | RoleData::ShippingManager { .. } => false,
+        }
+    }
+
+    /// Shipping of the store is managed by both the store managers and the shipping managers of the store
+    pub fn manages_store_shipping(&self, store_id_arg: StoreId) -> bool {
+        match *self {
+            RoleData::StoreManager { store_id } | RoleData::ShippingManager { store_id } => store_id == store_id_arg,
+            RoleData::CountryManager { .. } => false,
+        }
+    }
+
+    pub fn manages_country(&self, alpha3_arg: &Alpha3) -> bool {
+        match *self {
+            RoleData::StoreManager { .. } | RoleData::ShippingManager { .. } => false,
+            RoleData::CountryManager { ref alpha3 } => alpha3 == alpha3_arg,
+        }
+    }
+}
+
+/// Country managers manage the companies delivering from their country. `deliveries_from` is the tree of the countries
+/// the company delivers from, the regions in it are expanded to their countries
+pub fn manages_company(roles_data: &[RoleData], deliveries_from: &[Country]) -> bool {
+    get_countries_from_forest_by(deliveries_from.iter(), |country| country.level == Country::COUNTRY_LEVEL)
+        .iter()
+        .any(|country| roles_data.iter().any(|role_data| role_data.manages_country(&country.alpha3)))
+}
+
+/// Store managers and shipping managers own products of their store, country managers own products shipped by
+/// companies delivering from their country
+pub fn manages_product(roles_data: &[RoleData], store_id: StoreId, deliveries_from: &[Country]) -> bool {
+    roles_data.iter().any(|role_data| role_data.manages_store_shipping(store_id)) || manages_company(roles_data, deliveries_from)
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
 #[table_name = "roles"]
 pub struct NewUserRole {
@@ -23,3 +93,155 @@ pub struct NewUserRole {
     pub name: DeliveryRole,
     pub data: Option<serde_json::Value>,
 }
+
+json_schema!(NewUserRole {
+    id: RoleId,
+    user_id: UserId,
+    name: DeliveryRole,
+    data: Option<serde_json::Value>,
+});
+
+impl Validate for NewUserRole {
+    /// Store manager role must be scoped with role data, other roles must not have any
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        match (&self.name, self.data.as_ref()) {
+            (&DeliveryRole::StoreManager, None) => Err(validation_errors!({ "data": ["required" => "Store manager role must have data"] })),
+            (&DeliveryRole::StoreManager, Some(data)) => match RoleData::from_value(data) {
+                Some(_) => Ok(()),
+                None => Err(validation_errors!({ "data": ["role_data" => "Unknown role data"] })),
+            },
+            (_, Some(_)) => Err(validation_errors!({ "data": ["not_allowed" => "Only store manager role may have data"] })),
+            (_, None) => Ok(()),
+        }
+    }
+}
+
+/// Payload of the shipping manager role granted by the store manager
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NewStoreManager {
+    pub user_id: UserId,
+}
+
+json_schema!(NewStoreManager { user_id: UserId });
+
+#[cfg(test)]
+mod tests {
+    use stq_types::{Alpha2, CountryLabel};
+
+    use super::*;
+
+    fn country(alpha3: &str, level: i32, children: Vec<Country>) -> Country {
+        Country {
+            label: CountryLabel(alpha3.to_lowercase()),
+            level,
+            alpha2: Alpha2(alpha3[..2].to_string()),
+            alpha3: Alpha3(alpha3.to_string()),
+            children,
+            ..Country::default()
+        }
+    }
+
+    /// Tree of the countries the company delivers from, as the company repo builds it for the region code `XEU`
+    fn europe() -> Vec<Country> {
+        vec![country(
+            "XAL",
+            0,
+            vec![country(
+                "XEU",
+                1,
+                vec![
+                    country("DEU", Country::COUNTRY_LEVEL, vec![]),
+                    country("FRA", Country::COUNTRY_LEVEL, vec![]),
+                ],
+            )],
+        )]
+    }
+
+    fn new_user_role(name: DeliveryRole, data: Option<serde_json::Value>) -> NewUserRole {
+        NewUserRole {
+            id: RoleId::new(),
+            user_id: UserId(1),
+            name,
+            data,
+        }
+    }
+
+    #[test]
+    fn role_data_from_value() {
+        assert_eq!(
+            RoleData::from_value(&json!(7)),
+            Some(RoleData::StoreManager { store_id: StoreId(7) })
+        );
+        assert_eq!(
+            RoleData::from_value(&json!({"type": "store_manager", "store_id": 7})),
+            Some(RoleData::StoreManager { store_id: StoreId(7) })
+        );
+        assert_eq!(
+            RoleData::from_value(&json!({"type": "country_manager", "alpha3": "RUS"})),
+            Some(RoleData::CountryManager {
+                alpha3: Alpha3("RUS".to_string())
+            })
+        );
+        assert_eq!(
+            RoleData::from_value(&json!({"type": "shipping_manager", "store_id": 7})),
+            Some(RoleData::ShippingManager { store_id: StoreId(7) })
+        );
+        assert_eq!(RoleData::from_value(&json!({"type": "unknown"})), None);
+    }
+
+    #[test]
+    fn shipping_manager_does_not_own_store() {
+        let shipping_manager = RoleData::ShippingManager { store_id: StoreId(7) };
+        let store_manager = RoleData::StoreManager { store_id: StoreId(7) };
+
+        assert!(shipping_manager.manages_store_shipping(StoreId(7)));
+        assert!(!shipping_manager.manages_store_shipping(StoreId(8)));
+        assert!(!shipping_manager.manages_store(StoreId(7)));
+        assert!(store_manager.manages_store_shipping(StoreId(7)));
+        assert!(store_manager.manages_store(StoreId(7)));
+    }
+
+    #[test]
+    fn country_manager_manages_companies_delivering_from_countries_of_region() {
+        let country_manager = |alpha3: &str| {
+            vec![RoleData::CountryManager {
+                alpha3: Alpha3(alpha3.to_string()),
+            }]
+        };
+
+        assert!(manages_company(&country_manager("FRA"), &europe()));
+        assert!(!manages_company(&country_manager("USA"), &europe()));
+        assert!(!manages_company(&country_manager("XEU"), &europe()));
+        assert!(!manages_company(&[RoleData::StoreManager { store_id: StoreId(7) }], &europe()));
+    }
+
+    #[test]
+    fn country_manager_owns_products_of_its_companies_only() {
+        let country_manager = vec![RoleData::CountryManager {
+            alpha3: Alpha3("DEU".to_string()),
+        }];
+        let store_manager = vec![RoleData::StoreManager { store_id: StoreId(7) }];
+
+        assert!(manages_product(&country_manager, StoreId(7), &europe()));
+        assert!(!manages_product(&country_manager, StoreId(7), &[]));
+        assert!(manages_product(&store_manager, StoreId(7), &[]));
+        assert!(!manages_product(&store_manager, StoreId(8), &europe()));
+        assert!(!country_manager[0].manages_store(StoreId(7)));
+        assert!(!country_manager[0].manages_store_shipping(StoreId(7)));
+    }
+
+    #[test]
+    fn new_user_role_validate() {
+        assert!(new_user_role(DeliveryRole::StoreManager, Some(json!(7))).validate().is_ok());
+        assert!(new_user_role(
+            DeliveryRole::StoreManager,
+            Some(json!({"type": "country_manager", "alpha3": "RUS"}))
+        )
+        .validate()
+        .is_ok());
+        assert!(new_user_role(DeliveryRole::StoreManager, None).validate().is_err());
+        assert!(new_user_role(DeliveryRole::StoreManager, Some(json!("7"))).validate().is_err());
+        assert!(new_user_role(DeliveryRole::Superuser, None).validate().is_ok());
+        assert!(new_user_role(DeliveryRole::User, Some(json!(7))).validate().is_err());
+    }
+}
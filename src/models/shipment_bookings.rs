@@ -0,0 +1,34 @@
+//! Models for shipments booked with the company packages. Shipments are counted by the shipping date,
+//! company packages with the daily capacity are not offered once the shipments of the date reach it
+use chrono::{DateTime, NaiveDate, Utc};
+
+use stq_types::CompanyPackageId;
+
+use schema::shipment_bookings;
+
+#[derive(Serialize, Deserialize, Queryable, Clone, Debug, PartialEq)]
+pub struct ShipmentBooking {
+    pub company_package_id: CompanyPackageId,
+    pub shipping_date: NaiveDate,
+    pub shipments_count: i32,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Clone, Debug)]
+#[table_name = "shipment_bookings"]
+pub struct NewShipmentBooking {
+    pub company_package_id: CompanyPackageId,
+    pub shipping_date: NaiveDate,
+    pub shipments_count: i32,
+}
+
+/// Payload booking the shipment of the order, the shipping date follows from the cut-off time of the company package
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct BookShipment {
+    /// Time the order is placed at, the time of the request if not set
+    #[serde(default)]
+    pub order_time: Option<DateTime<Utc>>,
+}
+
+json_schema!(BookShipment {
+    order_time: Option<DateTime<Utc>>,
+});
@@ -0,0 +1,140 @@
+//! Models for notifications of the buyers about their shipments. Shipments are owned by the orders,
+//! their events are sent here to be rendered into emails and pushes
+use std::fmt;
+use std::time::SystemTime;
+
+use chrono::NaiveDate;
+use validator::{Validate, ValidationErrors};
+
+use stq_types::{ShippingId, UserId};
+
+use schema::shipment_notifications;
+
+/// Language of the notification if the event doesn't set one or there are no templates in its language
+pub const DEFAULT_NOTIFICATION_LANG: &str = "en";
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, DieselTypes)]
+#[serde(rename_all = "snake_case")]
+pub enum ShipmentEventKind {
+    /// Buyer has chosen the shipping option of the order
+    OptionChosen,
+    /// Parcel is handed over to the carrier
+    ShipmentDispatched,
+    /// Delivery is not going to be on time
+    DeliveryDelayed,
+}
+
+impl fmt::Display for ShipmentEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ShipmentEventKind::OptionChosen => write!(f, "option_chosen"),
+            ShipmentEventKind::ShipmentDispatched => write!(f, "shipment_dispatched"),
+            ShipmentEventKind::DeliveryDelayed => write!(f, "delivery_delayed"),
+        }
+    }
+}
+
+/// Event of the shipment, `event_id` is unique for the event, so that a redelivered event is not notified twice
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ShipmentEvent {
+    pub event_id: String,
+    pub kind: ShipmentEventKind,
+    pub user_id: UserId,
+    pub shipping_id: ShippingId,
+    pub order_slug: String,
+    pub lang: Option<String>,
+    /// Set for the dispatched shipments of the carriers with tracking
+    pub tracking_number: Option<String>,
+    /// Required for the delayed deliveries
+    pub delayed_until: Option<NaiveDate>,
+}
+
+json_schema!(ShipmentEvent {
+    event_id: String,
+    kind: ShipmentEventKind,
+    user_id: UserId,
+    shipping_id: ShippingId,
+    order_slug: String,
+    lang: Option<String>,
+    tracking_number: Option<String>,
+    delayed_until: Option<NaiveDate>,
+});
+
+impl Validate for ShipmentEvent {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        if self.event_id.is_empty() {
+            Err(validation_errors!({ "event_id": ["event_id" => "Event id must not be empty"] }))?;
+        }
+
+        if self.kind == ShipmentEventKind::DeliveryDelayed && self.delayed_until.is_none() {
+            Err(validation_errors!({ "delayed_until": ["delayed_until" => "Delayed delivery must have the new delivery date"] }))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationChannel {
+    Email,
+    Push,
+}
+
+/// Notification rendered for the user, push notifications have no subject
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Notification {
+    pub channel: NotificationChannel,
+    pub user_id: UserId,
+    pub subject: Option<String>,
+    pub text: String,
+}
+
+/// Shipment event the user is notified about
+#[derive(Serialize, Deserialize, Queryable, Clone, Debug)]
+pub struct ShipmentNotification {
+    pub id: i32,
+    pub event_id: String,
+    pub kind: ShipmentEventKind,
+    pub user_id: UserId,
+    pub shipping_id: ShippingId,
+    pub lang: String,
+    pub created_at: SystemTime,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Clone, Debug)]
+#[table_name = "shipment_notifications"]
+pub struct NewShipmentNotification {
+    pub event_id: String,
+    pub kind: ShipmentEventKind,
+    pub user_id: UserId,
+    pub shipping_id: ShippingId,
+    pub lang: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delayed_delivery_must_have_new_date() {
+        let event = ShipmentEvent {
+            event_id: "order-1-delayed".to_string(),
+            kind: ShipmentEventKind::DeliveryDelayed,
+            user_id: UserId(1),
+            shipping_id: ShippingId(1),
+            order_slug: "1".to_string(),
+            lang: None,
+            tracking_number: None,
+            delayed_until: None,
+        };
+
+        assert!(event.validate().is_err());
+        assert!(ShipmentEvent {
+            delayed_until: Some(NaiveDate::from_ymd(2019, 3, 25)),
+            ..event
+        }
+        .validate()
+        .is_ok());
+    }
+}
@@ -1,17 +1,30 @@
-use models::{Country, NewPickups, NewProducts, Pickups, Products};
+use models::{Country, CustomsInfo, HazardClass, NewPickups, NewProducts, Pickups, Products};
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Shipping {
     pub items: Vec<ShippingProducts>,
     pub pickup: Option<Pickups>,
+    /// Customs data for the declarations of cross-border shipments
+    #[serde(default)]
+    pub customs_info: Option<CustomsInfo>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct NewShipping {
     pub items: Vec<NewProducts>,
     pub pickup: Option<NewPickups>,
+    /// Hazard classes of the goods, applied to every item
+    #[serde(default)]
+    pub hazard_classes: Vec<HazardClass>,
 }
 
+json_schema!(NewShipping {
+    items: Vec<NewProducts>,
+    pickup: Option<NewPickups>,
+    #[default]
+    hazard_classes: Vec<HazardClass>,
+});
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ShippingProducts {
     pub product: Products,
@@ -0,0 +1,306 @@
+//! Models for the export of the shipping configured by the store, the document is imported to another environment
+//! when the seller is moved or kept as a backup
+use std::fmt;
+use std::str::FromStr;
+
+use failure::Error as FailureError;
+use validator::{Validate, ValidationErrors};
+
+use stq_types::{BaseProductId, StoreId};
+
+use models::{NewPickups, NewProducts, NewShippingTemplate, Pickups, Products, ShippingTemplate};
+
+/// Format version of the document, documents of the other versions are refused by the import
+pub const SHIPPING_EXPORT_VERSION: u32 = 1;
+
+/// Shipping of the base product. Company packages are referenced by id, so they must be set up in the environment
+/// the document is imported to
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProductShippingExport {
+    pub base_product_id: BaseProductId,
+    pub items: Vec<NewProducts>,
+    pub pickup: Option<NewPickups>,
+}
+
+json_schema!(ProductShippingExport {
+    base_product_id: BaseProductId,
+    items: Vec<NewProducts>,
+    pickup: Option<NewPickups>,
+});
+
+/// Shipping of all base products of the store together with its shipping templates
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StoreShippingExport {
+    pub version: u32,
+    /// Store the document is exported from, rows are moved to the store the document is imported to
+    pub store_id: StoreId,
+    pub products: Vec<ProductShippingExport>,
+    pub templates: Vec<NewShippingTemplate>,
+}
+
+json_schema!(StoreShippingExport {
+    version: u32,
+    store_id: StoreId,
+    products: Vec<ProductShippingExport>,
+    templates: Vec<NewShippingTemplate>,
+});
+
+impl StoreShippingExport {
+    /// Groups shipping of the store by base product, base products are ordered by id
+    pub fn new(store_id: StoreId, products: Vec<Products>, pickups: Vec<Pickups>, templates: Vec<ShippingTemplate>) -> Self {
+        let mut base_product_ids = products
+            .iter()
+            .map(|product| product.base_product_id)
+            .chain(pickups.iter().map(|pickup| pickup.base_product_id))
+            .collect::<Vec<_>>();
+        base_product_ids.sort_by_key(|base_product_id| base_product_id.0);
+        base_product_ids.dedup();
+
+        let products = base_product_ids
+            .into_iter()
+            .map(|base_product_id| ProductShippingExport {
+                base_product_id,
+                items: products
+                    .iter()
+                    .filter(|product| product.base_product_id == base_product_id)
+                    .map(|product| product.clone_to(base_product_id))
+                    .collect(),
+                pickup: pickups
+                    .iter()
+                    .find(|pickup| pickup.base_product_id == base_product_id)
+                    .map(|pickup| pickup.clone_to(base_product_id)),
+            })
+            .collect();
+
+        let templates = templates
+            .into_iter()
+            .map(|template| NewShippingTemplate {
+                name: template.name,
+                shipping: template.shipping,
+                free_delivery_over: template.free_delivery_over,
+            })
+            .collect();
+
+        StoreShippingExport {
+            version: SHIPPING_EXPORT_VERSION,
+            store_id,
+            products,
+            templates,
+        }
+    }
+
+    /// Document with all the rows moved to the store
+    pub fn moved_to(self, store_id: StoreId) -> Self {
+        let StoreShippingExport {
+            version,
+            products,
+            templates,
+            ..
+        } = self;
+
+        let products = products
+            .into_iter()
+            .map(|mut product| {
+                for item in product.items.iter_mut() {
+                    item.base_product_id = product.base_product_id;
+                    item.store_id = store_id;
+                }
+                if let Some(ref mut pickup) = product.pickup {
+                    pickup.base_product_id = product.base_product_id;
+                    pickup.store_id = store_id;
+                }
+                product
+            })
+            .collect();
+
+        StoreShippingExport {
+            version,
+            store_id,
+            products,
+            templates,
+        }
+    }
+}
+
+impl Validate for StoreShippingExport {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        if self.version != SHIPPING_EXPORT_VERSION {
+            Err(validation_errors!({
+                "version": ["version" => format!("Only version {} of the document is supported", SHIPPING_EXPORT_VERSION)]
+            }))?;
+        }
+
+        for (i, product) in self.products.iter().enumerate() {
+            if self.products[..i]
+                .iter()
+                .any(|other| other.base_product_id == product.base_product_id)
+            {
+                Err(validation_errors!({
+                    "products": ["products" => format!("Base product {} is listed more than once", product.base_product_id)]
+                }))?;
+            }
+        }
+
+        for (i, template) in self.templates.iter().enumerate() {
+            template.validate()?;
+            if self.templates[..i].iter().any(|other| other.name == template.name) {
+                Err(validation_errors!({
+                    "templates": ["templates" => format!("Template {} is listed more than once", template.name)]
+                }))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// What the import does with the base products and the templates of the document already set up in the store
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ShippingImportConflict {
+    /// Nothing is imported
+    Fail,
+    /// Shipping set up in the store is kept
+    Skip,
+    /// Shipping of the document replaces the one set up in the store
+    Overwrite,
+}
+
+impl Default for ShippingImportConflict {
+    fn default() -> Self {
+        ShippingImportConflict::Fail
+    }
+}
+
+impl FromStr for ShippingImportConflict {
+    type Err = FailureError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fail" => Ok(ShippingImportConflict::Fail),
+            "skip" => Ok(ShippingImportConflict::Skip),
+            "overwrite" => Ok(ShippingImportConflict::Overwrite),
+            other => Err(format_err!("Unknown import conflict strategy: {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for ShippingImportConflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ShippingImportConflict::Fail => write!(f, "fail"),
+            ShippingImportConflict::Skip => write!(f, "skip"),
+            ShippingImportConflict::Overwrite => write!(f, "overwrite"),
+        }
+    }
+}
+
+/// Base products and templates of the document by what the import did with them
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+pub struct StoreShippingImportReport {
+    pub imported_products: Vec<BaseProductId>,
+    pub skipped_products: Vec<BaseProductId>,
+    pub imported_templates: Vec<String>,
+    pub skipped_templates: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use stq_static_resources::Currency;
+    use stq_types::{Alpha3, CompanyPackageId, ProductPrice, ShippingId};
+
+    use super::*;
+    use models::{NewShipping, ShippingVariant};
+
+    fn product(id: i32, base_product_id: i32, company_package_id: i32) -> Products {
+        Products {
+            id: ShippingId(id),
+            base_product_id: BaseProductId(base_product_id),
+            store_id: StoreId(1),
+            company_package_id: CompanyPackageId(company_package_id),
+            price: Some(ProductPrice(10.0)),
+            deliveries_to: vec![Alpha3("RUS".to_string())],
+            shipping: ShippingVariant::Local,
+            currency: Currency::STQ,
+            hazard_classes: vec![],
+            free_delivery_over: None,
+            delivery_from: None,
+        }
+    }
+
+    fn pickup(base_product_id: i32) -> Pickups {
+        Pickups {
+            id: 1,
+            base_product_id: BaseProductId(base_product_id),
+            store_id: StoreId(1),
+            pickup: true,
+            price: None,
+        }
+    }
+
+    fn template(name: &str) -> NewShippingTemplate {
+        NewShippingTemplate {
+            name: name.to_string(),
+            shipping: NewShipping {
+                items: vec![],
+                pickup: None,
+                hazard_classes: vec![],
+            },
+            free_delivery_over: None,
+        }
+    }
+
+    #[test]
+    fn shipping_is_grouped_by_base_product() {
+        let export = StoreShippingExport::new(
+            StoreId(1),
+            vec![product(1, 20, 1), product(2, 10, 1), product(3, 20, 2)],
+            vec![pickup(30)],
+            vec![],
+        );
+
+        let grouped = export
+            .products
+            .iter()
+            .map(|product| (product.base_product_id.0, product.items.len(), product.pickup.is_some()))
+            .collect::<Vec<_>>();
+        assert_eq!(grouped, vec![(10, 1, false), (20, 2, false), (30, 0, true)]);
+        assert_eq!(export.version, SHIPPING_EXPORT_VERSION);
+        assert!(export.validate().is_ok());
+    }
+
+    #[test]
+    fn moved_document_is_of_the_store() {
+        let export = StoreShippingExport::new(StoreId(1), vec![product(1, 10, 1)], vec![pickup(10)], vec![]).moved_to(StoreId(2));
+
+        assert_eq!(export.store_id, StoreId(2));
+        assert_eq!(export.products[0].items[0].store_id, StoreId(2));
+        assert_eq!(export.products[0].pickup.as_ref().map(|pickup| pickup.store_id), Some(StoreId(2)));
+    }
+
+    #[test]
+    fn unsupported_and_ambiguous_documents_are_refused() {
+        let mut export = StoreShippingExport::new(StoreId(1), vec![], vec![], vec![]);
+        export.version = SHIPPING_EXPORT_VERSION + 1;
+        assert!(export.validate().is_err());
+
+        let export = StoreShippingExport::new(StoreId(1), vec![], vec![], vec![]);
+        let export = StoreShippingExport {
+            templates: vec![template("Couriers"), template("Couriers")],
+            ..export
+        };
+        assert!(export.validate().is_err());
+    }
+
+    #[test]
+    fn conflict_strategy_is_parsed_from_query() {
+        for strategy in &[
+            ShippingImportConflict::Fail,
+            ShippingImportConflict::Skip,
+            ShippingImportConflict::Overwrite,
+        ] {
+            assert_eq!(strategy.to_string().parse::<ShippingImportConflict>().unwrap(), *strategy);
+        }
+        assert!("merge".parse::<ShippingImportConflict>().is_err());
+    }
+}
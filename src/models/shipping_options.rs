@@ -0,0 +1,66 @@
+//! Ids of the shipping options offered to customers. An option id is derived from the option itself,
+//! so the same option gets the same id every time the availability is calculated
+//! and the orders service can keep referencing the option the customer has chosen
+use std::fmt;
+use std::str::FromStr;
+
+use failure::Error as FailureError;
+use sha3::{Digest, Sha3_256};
+
+use stq_types::{Alpha3, CompanyPackageId, ShippingId};
+
+/// Number of hash bytes kept in the id
+const OPTION_ID_BYTES: usize = 16;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ShippingOptionId(pub String);
+
+impl ShippingOptionId {
+    pub fn new(shipping_id: ShippingId, company_package_id: CompanyPackageId, delivery_from: &Alpha3, delivery_to: &Alpha3) -> Self {
+        let mut hasher = Sha3_256::default();
+        hasher.input(format!("{}:{}:{}:{}", shipping_id.0, company_package_id.0, delivery_from.0, delivery_to.0).as_bytes());
+
+        let id = hasher
+            .result()
+            .iter()
+            .take(OPTION_ID_BYTES)
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+        ShippingOptionId(id)
+    }
+}
+
+impl FromStr for ShippingOptionId {
+    type Err = FailureError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            Err(format_err!("Shipping option id is empty"))
+        } else {
+            Ok(ShippingOptionId(s.to_lowercase()))
+        }
+    }
+}
+
+impl fmt::Display for ShippingOptionId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn option_id_is_stable_and_depends_on_route() {
+        let rus = Alpha3("RUS".to_string());
+        let deu = Alpha3("DEU".to_string());
+
+        let option_id = ShippingOptionId::new(ShippingId(1), CompanyPackageId(2), &rus, &deu);
+
+        assert_eq!(option_id, ShippingOptionId::new(ShippingId(1), CompanyPackageId(2), &rus, &deu));
+        assert_ne!(option_id, ShippingOptionId::new(ShippingId(1), CompanyPackageId(2), &deu, &rus));
+        assert_eq!(option_id.to_string().parse::<ShippingOptionId>().unwrap(), option_id);
+    }
+}
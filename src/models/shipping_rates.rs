@@ -1,10 +1,13 @@
+use chrono::{NaiveDate, Utc};
 use failure::{err_msg, Error as FailureError, Fail};
 use std::collections::HashMap;
+use std::fmt;
 use std::str::FromStr;
+use validator::{Validate, ValidationError, ValidationErrors};
 
 use stq_types::{Alpha3, CompanyPackageId, ShippingRatesId};
 
-use models::ShipmentMeasurements;
+use models::{CountrySubdivision, PostalZone, ShipmentMeasurements, SurchargeKind, Zone};
 use schema::shipping_rates;
 
 #[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
@@ -19,23 +22,291 @@ pub struct ShippingRates {
     pub company_package_id: CompanyPackageId,
     pub from_alpha3: Alpha3,
     pub to_alpha3: Alpha3,
+    pub to_zone_id: Option<i32>,
     pub rates: Vec<ShippingRate>,
+    pub delivery_time_min_days: Option<u32>,
+    pub delivery_time_max_days: Option<u32>,
+    #[serde(default)]
+    pub components: Vec<RateComponent>,
+    /// ISO 3166-2 code of the subdivision of `to_alpha3` the rates are for, `None` if they are for the whole country
+    #[serde(default)]
+    pub to_subdivision: Option<String>,
+    /// Postal zone of `to_alpha3` the rates are for, `None` if they are for the whole country
+    #[serde(default)]
+    pub to_postal_zone_id: Option<i32>,
 }
 
 impl ShippingRates {
     pub fn calculate_delivery_price(&self, measurements: ShipmentMeasurements, dimensional_factor: Option<u32>) -> Option<f64> {
+        self.calculate_delivery_price_on(measurements, dimensional_factor, Utc::today().naive_utc())
+    }
+
+    /// Price with the components effective on the date
+    pub fn calculate_delivery_price_on(
+        &self,
+        measurements: ShipmentMeasurements,
+        dimensional_factor: Option<u32>,
+        date: NaiveDate,
+    ) -> Option<f64> {
+        let billable_weight_g = measurements.calculate_billable_weight(dimensional_factor);
+        super::calculate_delivery_price(billable_weight_g, self.rates_on(date))
+    }
+
+    /// Same as `calculate_delivery_price`, but also explains how the price was obtained
+    pub fn calculate_delivery_price_breakdown(
+        &self,
+        measurements: ShipmentMeasurements,
+        dimensional_factor: Option<u32>,
+    ) -> Option<ShippingRatesPriceBreakdown> {
+        self.calculate_delivery_price_breakdown_on(measurements, dimensional_factor, Utc::today().naive_utc())
+    }
+
+    /// Breakdown of the price with the components effective on the date
+    pub fn calculate_delivery_price_breakdown_on(
+        &self,
+        measurements: ShipmentMeasurements,
+        dimensional_factor: Option<u32>,
+        date: NaiveDate,
+    ) -> Option<ShippingRatesPriceBreakdown> {
+        let rates = self.base_rates_on(date);
         let billable_weight_g = measurements.calculate_billable_weight(dimensional_factor);
-        super::calculate_delivery_price(billable_weight_g, self.rates.clone())
+        let matched_rate = find_shipping_rate(billable_weight_g, rates.clone())?;
+        let base_rate = find_shipping_rate(measurements.weight_g, rates)
+            .map(|rate| rate.price)
+            .unwrap_or(matched_rate.price);
+
+        Some(ShippingRatesPriceBreakdown {
+            physical_weight_g: measurements.weight_g,
+            billable_weight_g,
+            base_rate,
+            dimensional_weight_adjustment: matched_rate.price - base_rate,
+            rate_components: self.surcharges_on(matched_rate, date),
+            matched_rate: MatchedShippingRate {
+                shipping_rates_id: self.id,
+                from_alpha3: self.from_alpha3.clone(),
+                to_alpha3: self.to_alpha3.clone(),
+                to_zone_id: self.to_zone_id,
+                to_subdivision: self.to_subdivision.clone(),
+                to_postal_zone_id: self.to_postal_zone_id,
+                weight_g: matched_rate.weight_g,
+                price: matched_rate.price,
+            },
+        })
+    }
+
+    /// Brackets with the base prices effective on the date, the prices of the rate table are used if there are none
+    pub fn base_rates_on(&self, date: NaiveDate) -> Vec<ShippingRate> {
+        self.rates
+            .iter()
+            .map(|rate| ShippingRate {
+                weight_g: rate.weight_g,
+                price: find_rate_component(&self.components, RateComponentKind::Base, rate.weight_g, date)
+                    .map(|component| component.value)
+                    .unwrap_or(rate.price),
+            })
+            .collect()
+    }
+
+    /// Surcharges of the bracket effective on the date, calculated from the base price of the bracket
+    pub fn surcharges_on(&self, base_rate: ShippingRate, date: NaiveDate) -> Vec<AppliedRateComponent> {
+        SURCHARGE_COMPONENT_KINDS
+            .iter()
+            .filter_map(|kind| {
+                find_rate_component(&self.components, *kind, base_rate.weight_g, date).map(|component| AppliedRateComponent {
+                    kind: *kind,
+                    value: component.calculate_amount(base_rate.price),
+                })
+            })
+            .collect()
+    }
+
+    /// Brackets with all the components effective on the date combined into the prices
+    pub fn rates_on(&self, date: NaiveDate) -> Vec<ShippingRate> {
+        self.base_rates_on(date)
+            .into_iter()
+            .map(|rate| ShippingRate {
+                weight_g: rate.weight_g,
+                price: rate.price + self.surcharges_on(rate, date).iter().map(|surcharge| surcharge.value).sum::<f64>(),
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RateComponentKind {
+    /// Price of the bracket replacing the one of the rate table
+    Base,
+    /// Usually a percentage indexed to the fuel prices and changed weekly by the carrier
+    FuelSurcharge,
+    PeakSeasonSurcharge,
+}
+
+impl fmt::Display for RateComponentKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RateComponentKind::Base => write!(f, "base"),
+            RateComponentKind::FuelSurcharge => write!(f, "fuel_surcharge"),
+            RateComponentKind::PeakSeasonSurcharge => write!(f, "peak_season_surcharge"),
+        }
+    }
+}
+
+/// Kinds of the components added on top of the base price, in the order they are applied
+const SURCHARGE_COMPONENT_KINDS: &[RateComponentKind] = &[RateComponentKind::FuelSurcharge, RateComponentKind::PeakSeasonSurcharge];
+
+/// Dated part of the price of the weight brackets. Components are combined with the rates when the price
+/// is calculated, so that indexed surcharges are changed without replacing the rates
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct RateComponent {
+    pub kind: RateComponentKind,
+    /// Upper bound of the weight bracket the component is for, every bracket of the rates if not set
+    pub weight_g: Option<u32>,
+    /// Surcharges of `Percentage` kind are percents of the base price, base prices are always `Fixed`
+    pub value_kind: SurchargeKind,
+    pub value: f64,
+    pub effective_from: NaiveDate,
+    /// Last day the component is effective on, it never expires if not set
+    pub effective_until: Option<NaiveDate>,
+}
+
+json_schema!(RateComponent {
+    kind: RateComponentKind,
+    weight_g: Option<u32>,
+    value_kind: SurchargeKind,
+    value: f64,
+    effective_from: NaiveDate,
+    effective_until: Option<NaiveDate>,
+});
+
+impl RateComponent {
+    pub fn is_effective_on(&self, date: NaiveDate) -> bool {
+        self.effective_from <= date && self.effective_until.map(|until| date <= until).unwrap_or(true)
+    }
+
+    pub fn is_expired_on(&self, date: NaiveDate) -> bool {
+        self.effective_until.map(|until| until < date).unwrap_or(false)
+    }
+
+    /// Amount the surcharge component adds to the base price
+    pub fn calculate_amount(&self, base_price: f64) -> f64 {
+        match self.value_kind {
+            SurchargeKind::Percentage => base_price * self.value / 100.0,
+            SurchargeKind::Fixed => self.value,
+        }
+    }
+}
+
+impl Validate for RateComponent {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        if self.value.is_nan() || self.value < 0.0 {
+            Err(validation_errors!({ "value": ["value" => "Value must not be negative"] }))?;
+        }
+
+        if self.kind == RateComponentKind::Base && self.value_kind != SurchargeKind::Fixed {
+            Err(validation_errors!({ "value_kind": ["value_kind" => "Base price must be a fixed value"] }))?;
+        }
+
+        if self.effective_until.map(|until| until < self.effective_from).unwrap_or(false) {
+            Err(validation_errors!({ "effective_until": ["effective_until" => "Component must not expire before it is effective"] }))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Component of the kind effective on the date. Components of the bracket are preferred over
+/// the components of every bracket, then the one effective from the latest date is chosen
+fn find_rate_component(components: &[RateComponent], kind: RateComponentKind, weight_g: u32, date: NaiveDate) -> Option<&RateComponent> {
+    components
+        .iter()
+        .filter(|component| {
+            component.kind == kind
+                && component.weight_g.map(|bracket| bracket == weight_g).unwrap_or(true)
+                && component.is_effective_on(date)
+        })
+        .max_by_key(|component| (component.weight_g.is_some(), component.effective_from))
+}
+
+/// Surcharge component included in the delivery price
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct AppliedRateComponent {
+    pub kind: RateComponentKind,
+    pub value: f64,
+}
+
+/// Components added to the rates of the company package from the country
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NewRateComponents {
+    pub delivery_from: Alpha3,
+    pub components: Vec<RateComponent>,
+}
+
+json_schema!(NewRateComponents {
+    delivery_from: Alpha3,
+    components: Vec<RateComponent>,
+});
+
+impl Validate for NewRateComponents {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        if self.components.is_empty() {
+            Err(validation_errors!({ "components": ["components" => "Components must not be empty"] }))?;
+        }
+
+        for component in &self.components {
+            component.validate()?;
+        }
+
+        Ok(())
     }
 }
 
-pub fn calculate_delivery_price(billable_weight_g: u32, mut rates: Vec<ShippingRate>) -> Option<f64> {
+/// Rate row and weight bracket a delivery price was taken from
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct MatchedShippingRate {
+    pub shipping_rates_id: ShippingRatesId,
+    pub from_alpha3: Alpha3,
+    pub to_alpha3: Alpha3,
+    pub to_zone_id: Option<i32>,
+    #[serde(default)]
+    pub to_subdivision: Option<String>,
+    #[serde(default)]
+    pub to_postal_zone_id: Option<i32>,
+    /// Upper bound of the matched weight bracket
+    pub weight_g: u32,
+    pub price: f64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct ShippingRatesPriceBreakdown {
+    pub physical_weight_g: u32,
+    pub billable_weight_g: u32,
+    /// Price for the physical weight of the shipment
+    pub base_rate: f64,
+    /// Extra price charged because the dimensional weight exceeds the physical weight
+    pub dimensional_weight_adjustment: f64,
+    /// Surcharges of the rates on top of the price of the matched bracket
+    pub rate_components: Vec<AppliedRateComponent>,
+    pub matched_rate: MatchedShippingRate,
+}
+
+pub fn calculate_delivery_price(billable_weight_g: u32, rates: Vec<ShippingRate>) -> Option<f64> {
+    find_shipping_rate(billable_weight_g, rates).map(|rate| rate.price)
+}
+
+fn find_shipping_rate(billable_weight_g: u32, mut rates: Vec<ShippingRate>) -> Option<ShippingRate> {
     rates.sort_unstable_by_key(|rate| rate.weight_g);
 
-    rates
-        .into_iter()
-        .find(|rate| rate.weight_g >= billable_weight_g)
-        .map(|rate| rate.price)
+    rates.into_iter().find(|rate| rate.weight_g >= billable_weight_g)
+}
+
+/// Destination of a shipping rates row - a single country, a subdivision or a postal zone of a country or a zone
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ShippingRatesDestination {
+    Country(Alpha3),
+    Subdivision { alpha3: Alpha3, code: String },
+    PostalZone { alpha3: Alpha3, postal_zone_id: i32 },
+    Zone(i32),
 }
 
 #[derive(Clone, Serialize, Associations, Queryable, Debug)]
@@ -44,41 +315,129 @@ pub struct ShippingRatesRaw {
     pub id: ShippingRatesId,
     pub company_package_id: CompanyPackageId,
     pub from_alpha3: Alpha3,
-    pub to_alpha3: Alpha3,
+    pub to_alpha3: Option<Alpha3>,
     pub rates: serde_json::Value,
+    pub to_zone_id: Option<i32>,
+    pub delivery_time_min_days: Option<i32>,
+    pub delivery_time_max_days: Option<i32>,
+    /// Inactive rates are ignored when prices are calculated
+    pub is_active: bool,
+    pub components: serde_json::Value,
+    pub to_subdivision: Option<String>,
+    pub to_postal_zone_id: Option<i32>,
 }
 
 impl ShippingRatesRaw {
-    pub fn to_model(self) -> Result<ShippingRates, FailureError> {
+    /// Parses rates of the row
+    pub fn parse_rates(&self) -> Result<Vec<ShippingRate>, FailureError> {
+        serde_json::from_value::<Vec<ShippingRate>>(self.rates.clone()).map_err(|e| {
+            FailureError::from(e)
+                .context(format!("Could not parse JSON with rates for ShippingRates with id = {}", self.id))
+                .into()
+        })
+    }
+
+    /// Parses dated components of the rates of the row
+    pub fn parse_components(&self) -> Result<Vec<RateComponent>, FailureError> {
+        serde_json::from_value::<Vec<RateComponent>>(self.components.clone()).map_err(|e| {
+            FailureError::from(e)
+                .context(format!(
+                    "Could not parse JSON with components for ShippingRates with id = {}",
+                    self.id
+                ))
+                .into()
+        })
+    }
+
+    /// Converts the row into per-country rates. Zone rows are expanded into one entry per country of the zone.
+    pub fn to_models(self, zones: &[Zone]) -> Result<Vec<ShippingRates>, FailureError> {
+        let rates = self.parse_rates()?;
+        let components = self.parse_components()?;
+
         let ShippingRatesRaw {
             id,
             company_package_id,
             from_alpha3,
             to_alpha3,
-            rates,
+            to_zone_id,
+            delivery_time_min_days,
+            delivery_time_max_days,
+            to_subdivision,
+            to_postal_zone_id,
+            ..
         } = self;
 
-        serde_json::from_value::<Vec<ShippingRate>>(rates)
-            .map_err(|e| {
-                FailureError::from(e)
-                    .context(format!("Could not parse JSON with rates for ShippingRates with id = {}", id))
-                    .into()
-            })
-            .map(|rates| ShippingRates {
+        let deliveries_to = match (to_alpha3, to_zone_id) {
+            (Some(to_alpha3), None) => vec![to_alpha3],
+            (None, Some(zone_id)) => zones
+                .iter()
+                .find(|zone| zone.id == zone_id)
+                .map(|zone| zone.countries.clone())
+                .ok_or(format_err!(
+                    "Zone with id = {} for ShippingRates with id = {} not found",
+                    zone_id,
+                    id
+                ))?,
+            _ => Err(format_err!(
+                "ShippingRates with id = {} must have either a destination country or a zone",
+                id
+            ))?,
+        };
+
+        Ok(deliveries_to
+            .into_iter()
+            .map(|to_alpha3| ShippingRates {
                 id,
                 company_package_id,
-                from_alpha3,
+                from_alpha3: from_alpha3.clone(),
                 to_alpha3,
-                rates,
+                to_zone_id,
+                rates: rates.clone(),
+                delivery_time_min_days: delivery_time_min_days.map(|days| days as u32),
+                delivery_time_max_days: delivery_time_max_days.map(|days| days as u32),
+                components: components.clone(),
+                to_subdivision: to_subdivision.clone(),
+                to_postal_zone_id,
             })
+            .collect())
+    }
+
+    /// Resolves rows into per-country rates. If a country has both its own rates and rates of a zone,
+    /// the country-specific rates are preferred. Rates to the subdivisions and the postal zones of a country are kept apart
+    /// from the ones to the country.
+    pub fn resolve_zones(raws: Vec<ShippingRatesRaw>, zones: &[Zone]) -> Result<Vec<ShippingRates>, FailureError> {
+        let mut resolved = Vec::<ShippingRates>::new();
+        for raw in raws {
+            for rates in raw.to_models(zones)? {
+                let existing = resolved.iter().position(|r| {
+                    r.company_package_id == rates.company_package_id
+                        && r.from_alpha3 == rates.from_alpha3
+                        && r.to_alpha3 == rates.to_alpha3
+                        && r.to_subdivision == rates.to_subdivision
+                        && r.to_postal_zone_id == rates.to_postal_zone_id
+                });
+                match existing {
+                    None => resolved.push(rates),
+                    Some(i) => {
+                        if resolved[i].to_zone_id.is_some() && rates.to_zone_id.is_none() {
+                            resolved[i] = rates;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(resolved)
     }
 }
 
 pub struct NewShippingRates {
     pub company_package_id: CompanyPackageId,
     pub from_alpha3: Alpha3,
-    pub to_alpha3: Alpha3,
+    pub to: ShippingRatesDestination,
     pub rates: Vec<ShippingRate>,
+    pub delivery_time_min_days: Option<u32>,
+    pub delivery_time_max_days: Option<u32>,
 }
 
 #[derive(Serialize, Deserialize, Insertable, Clone, Debug)]
@@ -86,8 +445,25 @@ pub struct NewShippingRates {
 pub struct NewShippingRatesRaw {
     pub company_package_id: CompanyPackageId,
     pub from_alpha3: Alpha3,
-    pub to_alpha3: Alpha3,
+    pub to_alpha3: Option<Alpha3>,
+    pub to_zone_id: Option<i32>,
     pub rates: serde_json::Value,
+    pub delivery_time_min_days: Option<i32>,
+    pub delivery_time_max_days: Option<i32>,
+    pub to_subdivision: Option<String>,
+    pub to_postal_zone_id: Option<i32>,
+}
+
+impl ShippingRatesDestination {
+    /// Rows of the subdivisions and the postal zones keep their country in `to_alpha3`
+    fn into_columns(self) -> (Option<Alpha3>, Option<i32>, Option<String>, Option<i32>) {
+        match self {
+            ShippingRatesDestination::Country(alpha3) => (Some(alpha3), None, None, None),
+            ShippingRatesDestination::Subdivision { alpha3, code } => (Some(alpha3), None, Some(code), None),
+            ShippingRatesDestination::PostalZone { alpha3, postal_zone_id } => (Some(alpha3), None, None, Some(postal_zone_id)),
+            ShippingRatesDestination::Zone(zone_id) => (None, Some(zone_id), None, None),
+        }
+    }
 }
 
 impl NewShippingRatesRaw {
@@ -99,14 +475,20 @@ impl NewShippingRatesRaw {
         } = batch;
         delivery_to_rates
             .into_iter()
-            .map(|(to_alpha3, rates)| {
+            .map(|(to, rates)| {
+                let (to_alpha3, to_zone_id, to_subdivision, to_postal_zone_id) = to.into_columns();
                 serde_json::to_value(rates)
                     .map_err(FailureError::from)
                     .map(|rates| NewShippingRatesRaw {
                         company_package_id,
                         from_alpha3: delivery_from.clone(),
-                        to_alpha3: to_alpha3.clone(),
+                        to_alpha3,
+                        to_zone_id,
                         rates,
+                        delivery_time_min_days: None,
+                        delivery_time_max_days: None,
+                        to_subdivision,
+                        to_postal_zone_id,
                     })
             })
             .collect()
@@ -118,17 +500,25 @@ impl NewShippingRatesRaw {
         let NewShippingRates {
             company_package_id,
             from_alpha3,
-            to_alpha3,
+            to,
             rates,
+            delivery_time_min_days,
+            delivery_time_max_days,
         } = new_shipping_rates;
 
         let rates = serde_json::to_value(&rates).map_err(FailureError::from)?;
+        let (to_alpha3, to_zone_id, to_subdivision, to_postal_zone_id) = to.into_columns();
 
         Ok(NewShippingRatesRaw {
             company_package_id,
             from_alpha3,
             to_alpha3,
+            to_zone_id,
             rates,
+            delivery_time_min_days: delivery_time_min_days.map(|days| days as i32),
+            delivery_time_max_days: delivery_time_max_days.map(|days| days as i32),
+            to_subdivision,
+            to_postal_zone_id,
         })
     }
 }
@@ -136,7 +526,8 @@ impl NewShippingRatesRaw {
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct ZonesCsvEntry {
     pub from: Alpha3,
-    pub to: Alpha3,
+    /// ISO alpha 3 country code or name of a zone
+    pub to: String,
     pub zone: u32,
 }
 
@@ -164,10 +555,10 @@ impl ZonesCsvData {
                         let from = Alpha3(from.to_string());
 
                         to.make_ascii_uppercase();
-                        if to.len() != 3 || to.chars().any(|c| !c.is_alphabetic()) {
-                            Err(format_err!("Invalid ISO alpha 3 country code (row {}, column 2)", row_num))?;
+                        if to.trim().is_empty() {
+                            Err(format_err!("Empty country code or zone name (row {}, column 2)", row_num))?;
                         }
-                        let to = Alpha3(to.to_string());
+                        let to = to.trim().to_string();
 
                         let zone = u32::from_str(&zone).map_err(|e| {
                             FailureError::from(e.context(format!("Invalid zone number format (row {}, column 3)", row_num)))
@@ -227,6 +618,8 @@ impl RatesCsvData {
         }
 
         let mut shipping_rates_for_zones = HashMap::<u32, Vec<ShippingRate>>::new();
+        // Weight brackets are overlapping if the same weight is listed twice
+        let mut bracket_rows = HashMap::<u32, usize>::new();
         for (row_num, record) in records.enumerate() {
             let row_num = row_num + 3; // Count from 1, skip header row, skip zones row
             let record = record.map_err(|e| FailureError::from(e.context(format!("Row {} has invalid format", row_num))))?;
@@ -245,13 +638,43 @@ impl RatesCsvData {
             let weight_kg = f64::from_str(record_iter.next().ok_or(err_msg("Unexpected error"))?)
                 .map_err(|e| FailureError::from(e.context(format!("Invalid weight format (row {})", row_num))))?;
 
+            if !weight_kg.is_finite() {
+                Err(format_err!("Weight must be a finite number (row {})", row_num))?;
+            }
+
+            if weight_kg < 0.0 {
+                Err(format_err!("Negative weight (row {})", row_num))?;
+            }
+
+            let weight_g = f64::round(weight_kg * 1000.0);
+            if weight_g > f64::from(u32::max_value()) {
+                Err(format_err!("Weight is too big (row {})", row_num))?;
+            }
+            let weight_g = weight_g as u32;
+
+            if let Some(bracket_row_num) = bracket_rows.insert(weight_g, row_num) {
+                Err(format_err!(
+                    "Weight bracket of row {} overlaps with the one of row {}",
+                    row_num,
+                    bracket_row_num
+                ))?;
+            }
+
             for (i, (zone_price, zone_num)) in record_iter.zip(zones.clone()).enumerate() {
                 let col_num = i + 2; // Count from 1, skip weight column
                 let zone_price = f64::from_str(zone_price)
                     .map_err(|e| FailureError::from(e.context(format!("Invalid price format (row {}, column {})", row_num, col_num))))?;
 
+                if !zone_price.is_finite() {
+                    Err(format_err!("Price must be a finite number (row {}, column {})", row_num, col_num))?;
+                }
+
+                if zone_price < 0.0 {
+                    Err(format_err!("Negative price (row {}, column {})", row_num, col_num))?;
+                }
+
                 let shipping_rate = ShippingRate {
-                    weight_g: f64::round(weight_kg * 1000.0) as u32,
+                    weight_g,
                     price: zone_price,
                 };
 
@@ -268,14 +691,21 @@ impl RatesCsvData {
 pub struct NewShippingRatesBatch {
     pub company_package_id: CompanyPackageId,
     pub delivery_from: Alpha3,
-    pub delivery_to_rates: Vec<(Alpha3, Vec<ShippingRate>)>,
+    pub delivery_to_rates: Vec<(ShippingRatesDestination, Vec<ShippingRate>)>,
 }
 
 impl NewShippingRatesBatch {
+    /// "To" values of the zone table matching a name of one of `known_zones` (case insensitive) reference that zone,
+    /// the ones matching a code of one of `known_subdivisions` reference that subdivision,
+    /// the ones matching `<alpha3>:<name>` of one of `known_postal_zones` reference that postal zone,
+    /// all other values must be ISO alpha 3 country codes.
     pub fn try_from_csv_data(
         company_package_id: CompanyPackageId,
         zones: ZonesCsvData,
         rates: RatesCsvData,
+        known_zones: &[Zone],
+        known_subdivisions: &[CountrySubdivision],
+        known_postal_zones: &[PostalZone],
     ) -> Result<NewShippingRatesBatch, FailureError> {
         if zones.0.is_empty() {
             Err(err_msg("Zone table is empty"))?;
@@ -294,6 +724,7 @@ impl NewShippingRatesBatch {
             .0
             .into_iter()
             .map(|ZonesCsvEntry { to, zone, .. }| {
+                let to = parse_destination(&to, known_zones, known_subdivisions, known_postal_zones)?;
                 rates
                     .0
                     .get(&zone)
@@ -311,12 +742,370 @@ impl NewShippingRatesBatch {
     }
 }
 
+fn parse_destination(
+    to: &str,
+    known_zones: &[Zone],
+    known_subdivisions: &[CountrySubdivision],
+    known_postal_zones: &[PostalZone],
+) -> Result<ShippingRatesDestination, FailureError> {
+    if let Some(zone) = known_zones.iter().find(|zone| zone.name.eq_ignore_ascii_case(to)) {
+        return Ok(ShippingRatesDestination::Zone(zone.id));
+    }
+
+    if let Some(subdivision) = known_subdivisions
+        .iter()
+        .find(|subdivision| subdivision.code.eq_ignore_ascii_case(to))
+    {
+        return Ok(ShippingRatesDestination::Subdivision {
+            alpha3: subdivision.alpha3.clone(),
+            code: subdivision.code.clone(),
+        });
+    }
+
+    if let Some(postal_zone) = known_postal_zones
+        .iter()
+        .find(|postal_zone| postal_zone.reference().eq_ignore_ascii_case(to))
+    {
+        return Ok(ShippingRatesDestination::PostalZone {
+            alpha3: postal_zone.alpha3.clone(),
+            postal_zone_id: postal_zone.id,
+        });
+    }
+
+    if to.len() != 3 || to.chars().any(|c| !c.is_alphabetic()) {
+        Err(format_err!(
+            "\"{}\" is neither an ISO alpha 3 country code, a subdivision code, a postal zone nor a zone name",
+            to
+        ))?;
+    }
+
+    Ok(ShippingRatesDestination::Country(Alpha3(to.to_string())))
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ShippingRatesChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// Price of the weight bracket before and after the replacement, `None` if the bracket is added or removed
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ShippingRateChange {
+    pub weight_g: u32,
+    pub old_price: Option<f64>,
+    pub new_price: Option<f64>,
+}
+
+/// Change of the rates to the country, rates of a zone are compared for every country of the zone
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ShippingRatesChange {
+    pub to_alpha3: Alpha3,
+    pub kind: ShippingRatesChangeKind,
+    /// Changed weight brackets only, in the order of weight
+    pub rate_changes: Vec<ShippingRateChange>,
+}
+
+/// Result of the dry run of the replacement of the shipping rates, nothing is changed by it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ShippingRatesReplacementPreview {
+    pub company_package_id: CompanyPackageId,
+    pub delivery_from: Alpha3,
+    /// Number of the uploaded rate entries, counted against the rates quota of the company
+    pub entries_count: i64,
+    /// Number of the countries with the same rates after the replacement
+    pub unchanged_count: usize,
+    pub changes: Vec<ShippingRatesChange>,
+    /// Countries the package delivers to that are left without rates from `delivery_from`
+    pub missing_destinations: Vec<Alpha3>,
+}
+
+/// Resolves new rates into per-country rates, country-specific rates are preferred over the ones of a zone.
+/// Rates to the subdivisions and the postal zones are left out, they do not replace the rates to their country
+pub fn resolve_new_rates_zones<'a>(new_rates: &'a [NewShippingRates], zones: &[Zone]) -> Vec<(Alpha3, &'a [ShippingRate])> {
+    let mut resolved = Vec::<(Alpha3, &'a [ShippingRate], bool)>::new();
+    for new in new_rates {
+        let (deliveries_to, is_zone) = match new.to {
+            ShippingRatesDestination::Country(ref alpha3) => (vec![alpha3.clone()], false),
+            ShippingRatesDestination::Subdivision { .. } | ShippingRatesDestination::PostalZone { .. } => continue,
+            ShippingRatesDestination::Zone(zone_id) => (
+                zones
+                    .iter()
+                    .find(|zone| zone.id == zone_id)
+                    .map(|zone| zone.countries.clone())
+                    .unwrap_or_default(),
+                true,
+            ),
+        };
+
+        for to_alpha3 in deliveries_to {
+            match resolved.iter().position(|(alpha3, _, _)| *alpha3 == to_alpha3) {
+                None => resolved.push((to_alpha3, new.rates.as_slice(), is_zone)),
+                Some(i) => {
+                    if resolved[i].2 && !is_zone {
+                        resolved[i] = (to_alpha3, new.rates.as_slice(), is_zone);
+                    }
+                }
+            }
+        }
+    }
+
+    resolved.into_iter().map(|(to_alpha3, rates, _)| (to_alpha3, rates)).collect()
+}
+
+fn diff_rates(old_rates: &[ShippingRate], new_rates: &[ShippingRate]) -> Vec<ShippingRateChange> {
+    let mut weights = old_rates
+        .iter()
+        .chain(new_rates.iter())
+        .map(|rate| rate.weight_g)
+        .collect::<Vec<_>>();
+    weights.sort();
+    weights.dedup();
+
+    weights
+        .into_iter()
+        .map(|weight_g| ShippingRateChange {
+            weight_g,
+            old_price: old_rates.iter().find(|rate| rate.weight_g == weight_g).map(|rate| rate.price),
+            new_price: new_rates.iter().find(|rate| rate.weight_g == weight_g).map(|rate| rate.price),
+        })
+        .filter(|change| change.old_price != change.new_price)
+        .collect()
+}
+
+/// Compares current per-country rates with the resolved new ones. Returns the number of the countries
+/// with unchanged rates and the changes in the order of the country codes. Rates to the subdivisions and the postal zones are not compared
+pub fn diff_shipping_rates(old_rates: &[ShippingRates], new_rates: &[(Alpha3, &[ShippingRate])]) -> (usize, Vec<ShippingRatesChange>) {
+    let old_rates = old_rates
+        .iter()
+        .filter(|rates| rates.to_subdivision.is_none() && rates.to_postal_zone_id.is_none())
+        .collect::<Vec<_>>();
+    let mut countries = old_rates
+        .iter()
+        .map(|rates| rates.to_alpha3.clone())
+        .chain(new_rates.iter().map(|(to_alpha3, _)| to_alpha3.clone()))
+        .collect::<Vec<_>>();
+    countries.sort_by(|a, b| a.0.cmp(&b.0));
+    countries.dedup();
+
+    let mut unchanged_count = 0;
+    let mut changes = Vec::new();
+    for to_alpha3 in countries {
+        let old = old_rates
+            .iter()
+            .find(|rates| rates.to_alpha3 == to_alpha3)
+            .map(|rates| rates.rates.as_slice());
+        let new = new_rates.iter().find(|(alpha3, _)| *alpha3 == to_alpha3).map(|(_, rates)| *rates);
+
+        let kind = match (old, new) {
+            (None, _) => ShippingRatesChangeKind::Added,
+            (_, None) => ShippingRatesChangeKind::Removed,
+            _ => ShippingRatesChangeKind::Changed,
+        };
+        let rate_changes = diff_rates(old.unwrap_or_default(), new.unwrap_or_default());
+
+        if rate_changes.is_empty() && kind == ShippingRatesChangeKind::Changed {
+            unchanged_count += 1;
+        } else {
+            changes.push(ShippingRatesChange {
+                to_alpha3,
+                kind,
+                rate_changes,
+            });
+        }
+    }
+
+    (unchanged_count, changes)
+}
+
+/// Weight band of the rates, from the weight of the lighter band exclusive to `to_g` inclusive
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct WeightBand {
+    pub from_g: u32,
+    pub to_g: u32,
+    pub price: f64,
+}
+
+impl WeightBand {
+    fn overlaps(&self, other: &WeightBand) -> bool {
+        self.from_g.max(other.from_g) < self.to_g.min(other.to_g) || self.to_g == other.to_g
+    }
+}
+
+impl fmt::Display for WeightBand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{} g", self.from_g, self.to_g)
+    }
+}
+
+/// Weight bands of the rates in the order of weight
+pub fn weight_bands(rates: &[ShippingRate]) -> Vec<WeightBand> {
+    let mut rates = rates.to_vec();
+    rates.sort_by_key(|rate| rate.weight_g);
+
+    let mut from_g = 0;
+    rates
+        .into_iter()
+        .map(|rate| {
+            let band = WeightBand {
+                from_g,
+                to_g: rate.weight_g,
+                price: rate.price,
+            };
+            from_g = rate.weight_g;
+            band
+        })
+        .collect()
+}
+
+fn weight_band_error(code: &'static str, message: String, to: &str, bands: &[WeightBand]) -> ValidationError {
+    let mut error = ValidationError::new(code);
+    error.message = Some(message.into());
+    error.add_param("to".into(), &to);
+    error.add_param("bands".into(), &bands);
+    error
+}
+
+/// Checks that the price of every weight up to `max_weight_g` of the package is defined by exactly one weight band.
+/// Bands of a single destination are contiguous by construction, so they overlap only if the same weight is listed twice.
+/// Bands of different destinations overlap if a country is in several of them with different prices for the same weights,
+/// country-specific rates take precedence over the ones of a zone, like in `resolve_new_rates_zones`.
+/// There is a gap if the heaviest band of a country is lighter than the package.
+/// All offending bands are listed under the `rates` field.
+pub fn validate_weight_bands(new_rates: &[NewShippingRates], zones: &[Zone], max_weight_g: u32) -> Result<(), ValidationErrors> {
+    let mut errors = Vec::new();
+
+    let destination_name = |to: &ShippingRatesDestination| match *to {
+        ShippingRatesDestination::Country(ref alpha3) => alpha3.0.clone(),
+        ShippingRatesDestination::Subdivision { ref code, .. } => code.clone(),
+        ShippingRatesDestination::PostalZone {
+            ref alpha3,
+            postal_zone_id,
+        } => format!("postal zone {} of {}", postal_zone_id, alpha3),
+        ShippingRatesDestination::Zone(zone_id) => zones
+            .iter()
+            .find(|zone| zone.id == zone_id)
+            .map(|zone| format!("zone {}", zone.name))
+            .unwrap_or_else(|| format!("zone {}", zone_id)),
+    };
+
+    for new in new_rates {
+        let bands = weight_bands(&new.rates);
+        for pair in bands.windows(2).filter(|pair| pair[0].to_g == pair[1].to_g) {
+            let to = destination_name(&new.to);
+            errors.push(weight_band_error(
+                "weight_band_overlap",
+                format!("Rates to {} list the band up to {} g twice", to, pair[1].to_g),
+                &to,
+                pair,
+            ));
+        }
+    }
+
+    // Rows of the rates to every country, subdivision and postal zone, country-specific and zone ones apart
+    let mut countries_rows = Vec::<(String, Vec<usize>, Vec<usize>)>::new();
+    for (i, new) in new_rates.iter().enumerate() {
+        let (deliveries_to, is_zone) = match new.to {
+            ShippingRatesDestination::Country(ref alpha3) => (vec![alpha3.0.clone()], false),
+            ShippingRatesDestination::Subdivision { ref code, .. } => (vec![code.clone()], false),
+            ShippingRatesDestination::PostalZone {
+                ref alpha3,
+                postal_zone_id,
+            } => (vec![format!("postal zone {} of {}", postal_zone_id, alpha3)], false),
+            ShippingRatesDestination::Zone(zone_id) => (
+                zones
+                    .iter()
+                    .find(|zone| zone.id == zone_id)
+                    .map(|zone| zone.countries.iter().map(|alpha3| alpha3.0.clone()).collect())
+                    .unwrap_or_default(),
+                true,
+            ),
+        };
+
+        for to in deliveries_to {
+            let position = match countries_rows.iter().position(|(destination, _, _)| *destination == to) {
+                Some(position) => position,
+                None => {
+                    countries_rows.push((to, vec![], vec![]));
+                    countries_rows.len() - 1
+                }
+            };
+            let (_, ref mut country_rows, ref mut zone_rows) = countries_rows[position];
+            if is_zone {
+                zone_rows.push(i);
+            } else {
+                country_rows.push(i);
+            }
+        }
+    }
+
+    for (to, country_rows, zone_rows) in countries_rows {
+        let rows = if country_rows.is_empty() { zone_rows } else { country_rows };
+
+        for (n, &i) in rows.iter().enumerate() {
+            for &j in &rows[n + 1..] {
+                for a in weight_bands(&new_rates[i].rates) {
+                    for b in weight_bands(&new_rates[j].rates) {
+                        if a.overlaps(&b) && a.price != b.price {
+                            errors.push(weight_band_error(
+                                "weight_band_overlap",
+                                format!(
+                                    "Rates to {}: band {} of {} overlaps band {} of {} with a different price",
+                                    to,
+                                    a,
+                                    destination_name(&new_rates[i].to),
+                                    b,
+                                    destination_name(&new_rates[j].to)
+                                ),
+                                &to,
+                                &[a, b],
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        let bands = weight_bands(&new_rates[rows[0]].rates);
+        let covered_g = bands.last().map(|band| band.to_g).unwrap_or(0);
+        if covered_g < max_weight_g {
+            let missing = WeightBand {
+                from_g: covered_g,
+                to_g: max_weight_g,
+                price: 0.0,
+            };
+            errors.push(weight_band_error(
+                "weight_band_gap",
+                format!(
+                    "Rates to {}: no band covers {}, the package takes up to {} g",
+                    to, missing, max_weight_g
+                ),
+                &to,
+                &bands,
+            ));
+        }
+    }
+
+    if errors.is_empty() {
+        return Ok(());
+    }
+
+    let mut validation_errors = ValidationErrors::new();
+    for error in errors {
+        validation_errors.add("rates", error);
+    }
+    Err(validation_errors)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     use std::iter::FromIterator;
 
+    use models::TypedJson;
+
     #[test]
     fn calculate_billable_weight_dimensional_weight_is_chosen() {
         let dimensional_factor = Some(5);
@@ -435,6 +1224,7 @@ mod tests {
             company_package_id: CompanyPackageId(1),
             from_alpha3: Alpha3("RUS".to_string()),
             to_alpha3: Alpha3("USA".to_string()),
+            to_zone_id: None,
             rates: vec![
                 ShippingRate {
                     weight_g: 500,
@@ -445,6 +1235,11 @@ mod tests {
                     price: 1200.0,
                 },
             ],
+            delivery_time_min_days: None,
+            delivery_time_max_days: None,
+            components: vec![],
+            to_subdivision: None,
+            to_postal_zone_id: None,
         };
 
         assert_eq!(
@@ -503,6 +1298,182 @@ mod tests {
         );
     }
 
+    #[test]
+    fn shipping_rates_calculate_delivery_price_breakdown() {
+        let shipping_rates = ShippingRates {
+            id: ShippingRatesId(1),
+            company_package_id: CompanyPackageId(1),
+            from_alpha3: Alpha3("RUS".to_string()),
+            to_alpha3: Alpha3("USA".to_string()),
+            to_zone_id: None,
+            rates: vec![
+                ShippingRate {
+                    weight_g: 500,
+                    price: 600.0,
+                },
+                ShippingRate {
+                    weight_g: 1000,
+                    price: 1200.0,
+                },
+            ],
+            delivery_time_min_days: None,
+            delivery_time_max_days: None,
+            components: vec![],
+            to_subdivision: None,
+            to_postal_zone_id: None,
+        };
+
+        let breakdown = shipping_rates
+            .calculate_delivery_price_breakdown(
+                ShipmentMeasurements {
+                    volume_cubic_cm: 3000,
+                    weight_g: 100,
+                },
+                Some(5),
+            )
+            .unwrap();
+
+        assert_eq!(100, breakdown.physical_weight_g);
+        assert_eq!(600, breakdown.billable_weight_g);
+        assert_eq!(600.0, breakdown.base_rate);
+        assert_eq!(600.0, breakdown.dimensional_weight_adjustment);
+        assert_eq!(ShippingRatesId(1), breakdown.matched_rate.shipping_rates_id);
+        assert_eq!(1000, breakdown.matched_rate.weight_g);
+        assert_eq!(
+            Some(1200.0),
+            shipping_rates.calculate_delivery_price(
+                ShipmentMeasurements {
+                    volume_cubic_cm: 3000,
+                    weight_g: 100,
+                },
+                Some(5)
+            )
+        );
+
+        assert_eq!(
+            None,
+            shipping_rates.calculate_delivery_price_breakdown(
+                ShipmentMeasurements {
+                    volume_cubic_cm: 1,
+                    weight_g: 1001,
+                },
+                Some(5)
+            )
+        );
+    }
+
+    fn rate_component(kind: RateComponentKind, weight_g: Option<u32>, value: f64, from: (i32, u32, u32)) -> RateComponent {
+        RateComponent {
+            kind,
+            weight_g,
+            value_kind: match kind {
+                RateComponentKind::FuelSurcharge => SurchargeKind::Percentage,
+                _ => SurchargeKind::Fixed,
+            },
+            value,
+            effective_from: NaiveDate::from_ymd(from.0, from.1, from.2),
+            effective_until: None,
+        }
+    }
+
+    #[test]
+    fn shipping_rates_combine_components_effective_on_date() {
+        let shipping_rates = ShippingRates {
+            id: ShippingRatesId(1),
+            company_package_id: CompanyPackageId(1),
+            from_alpha3: Alpha3("RUS".to_string()),
+            to_alpha3: Alpha3("USA".to_string()),
+            to_zone_id: None,
+            rates: vec![
+                ShippingRate {
+                    weight_g: 500,
+                    price: 600.0,
+                },
+                ShippingRate {
+                    weight_g: 1000,
+                    price: 1200.0,
+                },
+            ],
+            delivery_time_min_days: None,
+            delivery_time_max_days: None,
+            components: vec![
+                rate_component(RateComponentKind::FuelSurcharge, None, 10.0, (2019, 3, 1)),
+                rate_component(RateComponentKind::FuelSurcharge, None, 20.0, (2019, 3, 8)),
+                rate_component(RateComponentKind::Base, Some(1000), 1000.0, (2019, 3, 1)),
+                RateComponent {
+                    effective_until: Some(NaiveDate::from_ymd(2019, 3, 31)),
+                    ..rate_component(RateComponentKind::PeakSeasonSurcharge, None, 50.0, (2019, 3, 20))
+                },
+            ],
+            to_subdivision: None,
+            to_postal_zone_id: None,
+        };
+
+        let prices_on = |date| shipping_rates.rates_on(date).into_iter().map(|rate| rate.price).collect::<Vec<_>>();
+        assert_eq!(vec![600.0, 1200.0], prices_on(NaiveDate::from_ymd(2019, 2, 28)));
+        assert_eq!(vec![660.0, 1100.0], prices_on(NaiveDate::from_ymd(2019, 3, 7)));
+        assert_eq!(vec![720.0, 1200.0], prices_on(NaiveDate::from_ymd(2019, 3, 8)));
+        assert_eq!(vec![770.0, 1250.0], prices_on(NaiveDate::from_ymd(2019, 3, 20)));
+        assert_eq!(vec![720.0, 1200.0], prices_on(NaiveDate::from_ymd(2019, 4, 1)));
+
+        let price_on = |date| {
+            shipping_rates.calculate_delivery_price_on(
+                ShipmentMeasurements {
+                    volume_cubic_cm: 1000,
+                    weight_g: 400,
+                },
+                None,
+                date,
+            )
+        };
+        assert_eq!(Some(600.0), price_on(NaiveDate::from_ymd(2019, 2, 28)));
+        assert_eq!(Some(770.0), price_on(NaiveDate::from_ymd(2019, 3, 20)));
+
+        let breakdown = shipping_rates
+            .calculate_delivery_price_breakdown_on(
+                ShipmentMeasurements {
+                    volume_cubic_cm: 1000,
+                    weight_g: 600,
+                },
+                None,
+                NaiveDate::from_ymd(2019, 3, 20),
+            )
+            .unwrap();
+        assert_eq!(1000.0, breakdown.matched_rate.price);
+        assert_eq!(
+            vec![
+                AppliedRateComponent {
+                    kind: RateComponentKind::FuelSurcharge,
+                    value: 200.0,
+                },
+                AppliedRateComponent {
+                    kind: RateComponentKind::PeakSeasonSurcharge,
+                    value: 50.0,
+                },
+            ],
+            breakdown.rate_components
+        );
+    }
+
+    #[test]
+    fn rate_component_validate() {
+        let component = rate_component(RateComponentKind::Base, None, 100.0, (2019, 3, 1));
+        assert!(component.validate().is_ok());
+
+        assert!(RateComponent {
+            value_kind: SurchargeKind::Percentage,
+            ..component.clone()
+        }
+        .validate()
+        .is_err());
+        assert!(RateComponent {
+            effective_until: Some(NaiveDate::from_ymd(2019, 2, 28)),
+            ..component
+        }
+        .validate()
+        .is_err());
+    }
+
     #[test]
     fn zones_parse_csv_empty() {
         let csv = "From,To,Zone\n".as_bytes();
@@ -519,7 +1490,7 @@ mod tests {
 
         let expected_data = ZonesCsvData(vec![ZonesCsvEntry {
             from: Alpha3("RUS".to_string()),
-            to: Alpha3("USA".to_string()),
+            to: "USA".to_string(),
             zone: 6,
         }]);
 
@@ -539,22 +1510,22 @@ mod tests {
         let expected_data = ZonesCsvData(vec![
             ZonesCsvEntry {
                 from: Alpha3("RUS".to_string()),
-                to: Alpha3("USA".to_string()),
+                to: "USA".to_string(),
                 zone: 6,
             },
             ZonesCsvEntry {
                 from: Alpha3("USA".to_string()),
-                to: Alpha3("SGP".to_string()),
+                to: "SGP".to_string(),
                 zone: 7,
             },
             ZonesCsvEntry {
                 from: Alpha3("SGP".to_string()),
-                to: Alpha3("RUS".to_string()),
+                to: "RUS".to_string(),
                 zone: 6,
             },
             ZonesCsvEntry {
                 from: Alpha3("USA".to_string()),
-                to: Alpha3("RUS".to_string()),
+                to: "RUS".to_string(),
                 zone: 8,
             },
         ]);
@@ -671,4 +1642,352 @@ mod tests {
 
         RatesCsvData::parse_csv(csv).unwrap_err();
     }
+
+    #[test]
+    fn rates_parse_csv_overlapping_brackets() {
+        let csv = "Weight,Zone\n,1\n0.5,100\n1,200\n0.50,300\n".as_bytes();
+
+        let e = RatesCsvData::parse_csv(csv).unwrap_err();
+        assert_eq!(e.to_string(), "Weight bracket of row 5 overlaps with the one of row 3".to_string());
+    }
+
+    #[test]
+    fn rates_parse_csv_negative_price() {
+        let csv = "Weight,Zone,\n,1,2\n0.5,100,-1\n".as_bytes();
+
+        let e = RatesCsvData::parse_csv(csv).unwrap_err();
+        assert_eq!(e.to_string(), "Negative price (row 3, column 3)".to_string());
+    }
+
+    #[test]
+    fn rates_parse_csv_not_finite_price() {
+        let csv = "Weight,Zone,\n,1,2\n0.5,100,NaN\n1,inf,200\n".as_bytes();
+
+        let e = RatesCsvData::parse_csv(csv).unwrap_err();
+        assert_eq!(e.to_string(), "Price must be a finite number (row 3, column 3)".to_string());
+    }
+
+    #[test]
+    fn rates_parse_csv_invalid_weight() {
+        let csv = "Weight,Zone\n,1\n0.5,100\nNaN,200\n".as_bytes();
+        let e = RatesCsvData::parse_csv(csv).unwrap_err();
+        assert_eq!(e.to_string(), "Weight must be a finite number (row 4)".to_string());
+
+        let csv = "Weight,Zone\n,1\ninf,100\n".as_bytes();
+        let e = RatesCsvData::parse_csv(csv).unwrap_err();
+        assert_eq!(e.to_string(), "Weight must be a finite number (row 3)".to_string());
+
+        let csv = "Weight,Zone\n,1\n-1,100\n".as_bytes();
+        let e = RatesCsvData::parse_csv(csv).unwrap_err();
+        assert_eq!(e.to_string(), "Negative weight (row 3)".to_string());
+
+        let csv = "Weight,Zone\n,1\n5000000,100\n".as_bytes();
+        let e = RatesCsvData::parse_csv(csv).unwrap_err();
+        assert_eq!(e.to_string(), "Weight is too big (row 3)".to_string());
+    }
+
+    fn shipping_rates_raw(id: i32, to_alpha3: Option<&str>, to_zone_id: Option<i32>, price: f64) -> ShippingRatesRaw {
+        ShippingRatesRaw {
+            id: ShippingRatesId(id),
+            company_package_id: CompanyPackageId(1),
+            from_alpha3: Alpha3("RUS".to_string()),
+            to_alpha3: to_alpha3.map(|alpha3| Alpha3(alpha3.to_string())),
+            rates: serde_json::to_value(vec![ShippingRate { weight_g: 1000, price }]).unwrap(),
+            to_zone_id,
+            delivery_time_min_days: None,
+            delivery_time_max_days: None,
+            is_active: true,
+            components: json!([]),
+            to_subdivision: None,
+            to_postal_zone_id: None,
+        }
+    }
+
+    fn europe() -> Zone {
+        Zone {
+            id: 1,
+            name: "Europe".to_string(),
+            countries: vec![Alpha3("DEU".to_string()), Alpha3("FRA".to_string())],
+        }
+    }
+
+    #[test]
+    fn shipping_rates_resolve_zones_prefers_country_rates() {
+        let raws = vec![
+            shipping_rates_raw(1, None, Some(1), 10.0),
+            shipping_rates_raw(2, Some("FRA"), None, 20.0),
+            shipping_rates_raw(3, Some("USA"), None, 30.0),
+        ];
+
+        let resolved = ShippingRatesRaw::resolve_zones(raws, &[europe()]).unwrap();
+        let prices = resolved
+            .into_iter()
+            .map(|rates| (rates.to_alpha3.0, rates.to_zone_id, rates.rates[0].price))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                ("DEU".to_string(), Some(1), 10.0),
+                ("FRA".to_string(), None, 20.0),
+                ("USA".to_string(), None, 30.0),
+            ],
+            prices
+        );
+    }
+
+    #[test]
+    fn shipping_rates_resolve_zones_unknown_zone() {
+        let raws = vec![shipping_rates_raw(1, None, Some(2), 10.0)];
+
+        ShippingRatesRaw::resolve_zones(raws, &[europe()]).unwrap_err();
+    }
+
+    #[test]
+    fn shipping_rates_batch_references_zones_by_name() {
+        let zones = ZonesCsvData::parse_csv("From,To,Zone\nRUS,europe,1\nRUS,USA,1\n".as_bytes()).unwrap();
+        let rates = RatesCsvData::parse_csv("Weight,Zone\n,1\n0.5,100\n".as_bytes()).unwrap();
+
+        let batch =
+            NewShippingRatesBatch::try_from_csv_data(CompanyPackageId(1), zones.clone(), rates.clone(), &[europe()], &[], &[]).unwrap();
+        let destinations = batch.delivery_to_rates.into_iter().map(|(to, _)| to).collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ShippingRatesDestination::Zone(1),
+                ShippingRatesDestination::Country(Alpha3("USA".to_string())),
+            ],
+            destinations
+        );
+
+        NewShippingRatesBatch::try_from_csv_data(CompanyPackageId(1), zones, rates, &[], &[], &[]).unwrap_err();
+    }
+
+    #[test]
+    fn shipping_rates_batch_references_subdivisions_by_code() {
+        let zones = ZonesCsvData::parse_csv("From,To,Zone\nRUS,USA,1\nRUS,us-ca,2\n".as_bytes()).unwrap();
+        let rates = RatesCsvData::parse_csv("Weight,Zone,\n,1,2\n0.5,100,150\n".as_bytes()).unwrap();
+        let california = CountrySubdivision {
+            code: "US-CA".to_string(),
+            alpha3: Alpha3("USA".to_string()),
+            name: "California".to_string(),
+        };
+
+        let batch =
+            NewShippingRatesBatch::try_from_csv_data(CompanyPackageId(1), zones.clone(), rates.clone(), &[], &[california], &[]).unwrap();
+        let destinations = batch.delivery_to_rates.into_iter().map(|(to, _)| to).collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ShippingRatesDestination::Country(Alpha3("USA".to_string())),
+                ShippingRatesDestination::Subdivision {
+                    alpha3: Alpha3("USA".to_string()),
+                    code: "US-CA".to_string(),
+                },
+            ],
+            destinations
+        );
+
+        NewShippingRatesBatch::try_from_csv_data(CompanyPackageId(1), zones, rates, &[], &[], &[]).unwrap_err();
+    }
+
+    #[test]
+    fn shipping_rates_batch_references_postal_zones_by_country_and_name() {
+        let zones = ZonesCsvData::parse_csv("From,To,Zone\nRUS,AUS,1\nRUS,aus:remote,2\n".as_bytes()).unwrap();
+        let rates = RatesCsvData::parse_csv("Weight,Zone,\n,1,2\n0.5,100,150\n".as_bytes()).unwrap();
+        let remote = PostalZone {
+            id: 3,
+            alpha3: Alpha3("AUS".to_string()),
+            name: "Remote".to_string(),
+            ranges: TypedJson(vec![]),
+        };
+
+        let batch =
+            NewShippingRatesBatch::try_from_csv_data(CompanyPackageId(1), zones.clone(), rates.clone(), &[], &[], &[remote]).unwrap();
+        let destinations = batch.delivery_to_rates.into_iter().map(|(to, _)| to).collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ShippingRatesDestination::Country(Alpha3("AUS".to_string())),
+                ShippingRatesDestination::PostalZone {
+                    alpha3: Alpha3("AUS".to_string()),
+                    postal_zone_id: 3,
+                },
+            ],
+            destinations
+        );
+
+        NewShippingRatesBatch::try_from_csv_data(CompanyPackageId(1), zones, rates, &[], &[], &[]).unwrap_err();
+    }
+
+    #[test]
+    fn shipping_rates_resolve_zones_keeps_subdivision_rates_apart() {
+        let raws = vec![
+            shipping_rates_raw(1, Some("USA"), None, 10.0),
+            ShippingRatesRaw {
+                to_subdivision: Some("US-CA".to_string()),
+                ..shipping_rates_raw(2, Some("USA"), None, 20.0)
+            },
+        ];
+
+        let resolved = ShippingRatesRaw::resolve_zones(raws, &[]).unwrap();
+        let prices = resolved
+            .into_iter()
+            .map(|rates| (rates.to_subdivision, rates.rates[0].price))
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![(None, 10.0), (Some("US-CA".to_string()), 20.0)], prices);
+    }
+
+    fn new_shipping_rates(to: ShippingRatesDestination, prices: &[(u32, f64)]) -> NewShippingRates {
+        NewShippingRates {
+            company_package_id: CompanyPackageId(1),
+            from_alpha3: Alpha3("RUS".to_string()),
+            to,
+            rates: prices.iter().map(|&(weight_g, price)| ShippingRate { weight_g, price }).collect(),
+            delivery_time_min_days: None,
+            delivery_time_max_days: None,
+        }
+    }
+
+    #[test]
+    fn shipping_rates_diff_by_country() {
+        let old_rates = vec![
+            shipping_rates_raw(1, Some("DEU"), None, 10.0),
+            shipping_rates_raw(2, Some("FRA"), None, 20.0),
+            shipping_rates_raw(3, Some("USA"), None, 30.0),
+        ];
+        let old_rates = ShippingRatesRaw::resolve_zones(old_rates, &[]).unwrap();
+        let new_rates = vec![
+            new_shipping_rates(ShippingRatesDestination::Zone(1), &[(1000, 10.0)]),
+            new_shipping_rates(
+                ShippingRatesDestination::Country(Alpha3("FRA".to_string())),
+                &[(1000, 25.0), (2000, 40.0)],
+            ),
+        ];
+        let zones = [europe()];
+        let new_rates = resolve_new_rates_zones(&new_rates, &zones);
+
+        let (unchanged_count, changes) = diff_shipping_rates(&old_rates, &new_rates);
+
+        assert_eq!(unchanged_count, 1);
+        assert_eq!(
+            changes,
+            vec![
+                ShippingRatesChange {
+                    to_alpha3: Alpha3("FRA".to_string()),
+                    kind: ShippingRatesChangeKind::Changed,
+                    rate_changes: vec![
+                        ShippingRateChange {
+                            weight_g: 1000,
+                            old_price: Some(20.0),
+                            new_price: Some(25.0),
+                        },
+                        ShippingRateChange {
+                            weight_g: 2000,
+                            old_price: None,
+                            new_price: Some(40.0),
+                        },
+                    ],
+                },
+                ShippingRatesChange {
+                    to_alpha3: Alpha3("USA".to_string()),
+                    kind: ShippingRatesChangeKind::Removed,
+                    rate_changes: vec![ShippingRateChange {
+                        weight_g: 1000,
+                        old_price: Some(30.0),
+                        new_price: None,
+                    }],
+                },
+            ]
+        );
+    }
+
+    fn weight_band_errors(result: Result<(), ValidationErrors>) -> Vec<(String, String)> {
+        result
+            .unwrap_err()
+            .inner()
+            .remove("rates")
+            .unwrap_or_default()
+            .into_iter()
+            .map(|error| {
+                (
+                    error.code.to_string(),
+                    error.message.map(|message| message.to_string()).unwrap_or_default(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn weight_bands_follow_each_other() {
+        assert_eq!(
+            weight_bands(&[
+                ShippingRate {
+                    weight_g: 1000,
+                    price: 20.0
+                },
+                ShippingRate {
+                    weight_g: 500,
+                    price: 10.0
+                }
+            ]),
+            vec![
+                WeightBand {
+                    from_g: 0,
+                    to_g: 500,
+                    price: 10.0,
+                },
+                WeightBand {
+                    from_g: 500,
+                    to_g: 1000,
+                    price: 20.0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn weight_bands_of_zones_overlap() {
+        let america = Zone {
+            id: 2,
+            name: "America".to_string(),
+            countries: vec![Alpha3("USA".to_string()), Alpha3("FRA".to_string())],
+        };
+        let new_rates = vec![
+            new_shipping_rates(ShippingRatesDestination::Zone(1), &[(500, 10.0), (1000, 20.0)]),
+            new_shipping_rates(ShippingRatesDestination::Zone(2), &[(1000, 20.0)]),
+            new_shipping_rates(ShippingRatesDestination::Country(Alpha3("DEU".to_string())), &[(1000, 30.0)]),
+        ];
+
+        assert_eq!(
+            weight_band_errors(validate_weight_bands(&new_rates, &[europe(), america], 1000)),
+            vec![(
+                "weight_band_overlap".to_string(),
+                "Rates to FRA: band 0-500 g of zone Europe overlaps band 0-1000 g of zone America with a different price".to_string(),
+            )]
+        );
+    }
+
+    #[test]
+    fn weight_bands_leave_gap_up_to_package_weight() {
+        let new_rates = vec![
+            new_shipping_rates(ShippingRatesDestination::Zone(1), &[(500, 10.0), (1000, 20.0)]),
+            new_shipping_rates(
+                ShippingRatesDestination::Country(Alpha3("FRA".to_string())),
+                &[(500, 10.0), (500, 15.0), (2000, 20.0)],
+            ),
+        ];
+
+        assert_eq!(
+            weight_band_errors(validate_weight_bands(&new_rates, &[europe()], 2000)),
+            vec![
+                (
+                    "weight_band_overlap".to_string(),
+                    "Rates to FRA list the band up to 500 g twice".to_string(),
+                ),
+                (
+                    "weight_band_gap".to_string(),
+                    "Rates to DEU: no band covers 1000-2000 g, the package takes up to 2000 g".to_string(),
+                ),
+            ]
+        );
+        validate_weight_bands(&new_rates[..1], &[europe()], 1000).unwrap();
+    }
 }
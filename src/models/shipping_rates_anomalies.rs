@@ -0,0 +1,152 @@
+//! Models for anomalies found in shipping rates by the integrity check
+use std::time::SystemTime;
+
+use stq_types::ShippingRatesId;
+
+use models::ShippingRate;
+use schema::shipping_rates_anomalies;
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, DieselTypes)]
+pub enum ShippingRatesAnomalyKind {
+    /// Zero or negative price, rates with it are deactivated
+    NonPositivePrice,
+    /// Price is lower than the price of the lighter bracket
+    NonMonotonicPrice,
+    /// Price is higher than the price of the lighter bracket more than by the configured factor
+    PriceCliff,
+}
+
+impl ShippingRatesAnomalyKind {
+    /// Rates with impossible values are deactivated, the others are only reported
+    pub fn deactivates_rates(self) -> bool {
+        match self {
+            ShippingRatesAnomalyKind::NonPositivePrice => true,
+            ShippingRatesAnomalyKind::NonMonotonicPrice | ShippingRatesAnomalyKind::PriceCliff => false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Queryable, Clone, Debug)]
+pub struct ShippingRatesAnomaly {
+    pub id: i32,
+    pub shipping_rates_id: ShippingRatesId,
+    pub kind: ShippingRatesAnomalyKind,
+    /// Weight bracket the anomaly was found in
+    pub weight_g: i32,
+    pub price: f64,
+    /// Whether the rates were deactivated because of the anomaly
+    pub deactivated: bool,
+    pub detected_at: SystemTime,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Clone, Debug, PartialEq)]
+#[table_name = "shipping_rates_anomalies"]
+pub struct NewShippingRatesAnomaly {
+    pub shipping_rates_id: ShippingRatesId,
+    pub kind: ShippingRatesAnomalyKind,
+    pub weight_g: i32,
+    pub price: f64,
+    pub deactivated: bool,
+}
+
+impl NewShippingRatesAnomaly {
+    fn new(shipping_rates_id: ShippingRatesId, kind: ShippingRatesAnomalyKind, rate: ShippingRate) -> Self {
+        Self {
+            shipping_rates_id,
+            kind,
+            weight_g: rate.weight_g as i32,
+            price: rate.price,
+            deactivated: kind.deactivates_rates(),
+        }
+    }
+}
+
+/// Scans weight brackets of the rates in the order of weight.
+/// A bracket priced more than `price_cliff_factor` times the previous one is a price cliff
+pub fn find_shipping_rates_anomalies(
+    shipping_rates_id: ShippingRatesId,
+    mut rates: Vec<ShippingRate>,
+    price_cliff_factor: f64,
+) -> Vec<NewShippingRatesAnomaly> {
+    rates.sort_unstable_by_key(|rate| rate.weight_g);
+
+    let mut anomalies = vec![];
+    let mut previous: Option<ShippingRate> = None;
+    for rate in rates {
+        if rate.price.is_nan() || rate.price <= 0.0 {
+            anomalies.push(NewShippingRatesAnomaly::new(
+                shipping_rates_id,
+                ShippingRatesAnomalyKind::NonPositivePrice,
+                rate,
+            ));
+            continue;
+        }
+
+        if let Some(previous) = previous {
+            if rate.price < previous.price {
+                anomalies.push(NewShippingRatesAnomaly::new(
+                    shipping_rates_id,
+                    ShippingRatesAnomalyKind::NonMonotonicPrice,
+                    rate,
+                ));
+            } else if rate.price > previous.price * price_cliff_factor {
+                anomalies.push(NewShippingRatesAnomaly::new(
+                    shipping_rates_id,
+                    ShippingRatesAnomalyKind::PriceCliff,
+                    rate,
+                ));
+            }
+        }
+
+        previous = Some(rate);
+    }
+
+    anomalies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rate(weight_g: u32, price: f64) -> ShippingRate {
+        ShippingRate { weight_g, price }
+    }
+
+    fn kinds(anomalies: Vec<NewShippingRatesAnomaly>) -> Vec<(ShippingRatesAnomalyKind, i32)> {
+        anomalies.into_iter().map(|anomaly| (anomaly.kind, anomaly.weight_g)).collect()
+    }
+
+    #[test]
+    fn find_shipping_rates_anomalies_valid_rates() {
+        let rates = vec![rate(1000, 150.0), rate(500, 100.0), rate(2000, 250.0)];
+
+        assert_eq!(find_shipping_rates_anomalies(ShippingRatesId(1), rates, 3.0), vec![]);
+    }
+
+    #[test]
+    fn find_shipping_rates_anomalies_all_kinds() {
+        let rates = vec![
+            rate(500, 100.0),
+            rate(1000, 0.0),
+            rate(2000, 90.0),
+            rate(3000, 400.0),
+            rate(4000, -1.0),
+        ];
+
+        let anomalies = find_shipping_rates_anomalies(ShippingRatesId(1), rates, 3.0);
+
+        assert_eq!(
+            kinds(anomalies.clone()),
+            vec![
+                (ShippingRatesAnomalyKind::NonPositivePrice, 1000),
+                (ShippingRatesAnomalyKind::NonMonotonicPrice, 2000),
+                (ShippingRatesAnomalyKind::PriceCliff, 3000),
+                (ShippingRatesAnomalyKind::NonPositivePrice, 4000),
+            ]
+        );
+        assert_eq!(
+            anomalies.iter().map(|anomaly| anomaly.deactivated).collect::<Vec<_>>(),
+            vec![true, false, false, true]
+        );
+    }
+}
@@ -0,0 +1,264 @@
+//! Models for shipping restrictions - goods the carriers refuse or accept only with special handling,
+//! per carrier and destination country. Goods are matched by their categories or by keywords
+use validator::{Validate, ValidationErrors};
+
+use stq_types::{Alpha3, CompanyId};
+
+use schema::shipping_restrictions;
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, DieselTypes)]
+#[serde(rename_all = "snake_case")]
+pub enum RestrictionType {
+    /// Carrier refuses the shipment
+    Prohibited,
+    /// Carrier accepts the shipment only with special handling, e.g. with a declaration or a permit
+    Restricted,
+}
+
+/// Restriction is applied to the goods of the category or containing the keyword, the destination
+/// is any country if it is not set
+#[derive(Serialize, Deserialize, Queryable, Clone, Debug, PartialEq)]
+pub struct ShippingRestriction {
+    pub id: i32,
+    pub company_id: CompanyId,
+    pub to_alpha3: Option<Alpha3>,
+    pub category: Option<String>,
+    pub keyword: Option<String>,
+    pub restriction_type: RestrictionType,
+    /// Why the carrier restricts the goods, shown to the store
+    pub reason: String,
+}
+
+impl ShippingRestriction {
+    /// Whether the goods of the categories with the description are restricted on delivery to the country.
+    /// Categories and keywords are matched case-insensitively
+    pub fn applies_to(&self, categories: &[String], description: Option<&str>, delivery_to: &Alpha3) -> bool {
+        if self.to_alpha3.as_ref().map(|alpha3| alpha3 != delivery_to).unwrap_or(false) {
+            return false;
+        }
+
+        let categories = categories.iter().map(|category| category.to_lowercase()).collect::<Vec<_>>();
+        let category_matches = self
+            .category
+            .as_ref()
+            .map(|category| categories.contains(&category.to_lowercase()))
+            .unwrap_or(false);
+        let keyword_matches = self
+            .keyword
+            .as_ref()
+            .map(|keyword| {
+                let keyword = keyword.to_lowercase();
+                categories.iter().any(|category| category.contains(&keyword))
+                    || description
+                        .map(|description| description.to_lowercase().contains(&keyword))
+                        .unwrap_or(false)
+            })
+            .unwrap_or(false);
+
+        category_matches || keyword_matches
+    }
+}
+
+fn validate_restriction_reason(reason: &str) -> Result<(), ValidationErrors> {
+    if reason.trim().is_empty() {
+        Err(validation_errors!({ "reason": ["reason" => "Reason must not be empty"] }))?;
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Insertable, Clone, Debug)]
+#[table_name = "shipping_restrictions"]
+pub struct NewShippingRestriction {
+    pub company_id: CompanyId,
+    pub to_alpha3: Option<Alpha3>,
+    pub category: Option<String>,
+    pub keyword: Option<String>,
+    pub restriction_type: RestrictionType,
+    pub reason: String,
+}
+
+json_schema!(NewShippingRestriction {
+    company_id: CompanyId,
+    to_alpha3: Option<Alpha3>,
+    category: Option<String>,
+    keyword: Option<String>,
+    restriction_type: RestrictionType,
+    reason: String,
+});
+
+impl Validate for NewShippingRestriction {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let is_set = |value: &Option<String>| value.as_ref().map(|value| !value.trim().is_empty()).unwrap_or(false);
+        if is_set(&self.category) == is_set(&self.keyword) {
+            Err(validation_errors!({ "category": ["category" => "Either category or keyword must be set"] }))?;
+        }
+
+        validate_restriction_reason(&self.reason)
+    }
+}
+
+/// Only the restriction itself can be changed, the goods and the deliveries it applies to are fixed
+#[derive(Serialize, Deserialize, AsChangeset, Clone, Debug)]
+#[table_name = "shipping_restrictions"]
+pub struct UpdateShippingRestriction {
+    pub restriction_type: Option<RestrictionType>,
+    pub reason: Option<String>,
+}
+
+json_schema!(UpdateShippingRestriction {
+    restriction_type: Option<RestrictionType>,
+    reason: Option<String>,
+});
+
+impl Validate for UpdateShippingRestriction {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        match self.reason {
+            Some(ref reason) => validate_restriction_reason(reason),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Payload checking the goods against the restrictions of the carriers
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CheckShippingRestrictions {
+    pub categories: Vec<String>,
+    /// Description of the goods the keywords are searched in
+    #[serde(default)]
+    pub description: Option<String>,
+    pub delivery_to: Alpha3,
+}
+
+json_schema!(CheckShippingRestrictions {
+    categories: Vec<String>,
+    #[default]
+    description: Option<String>,
+    delivery_to: Alpha3,
+});
+
+/// Restrictions of the carrier applied to the goods
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CarrierRestrictions {
+    pub company_id: CompanyId,
+    pub restrictions: Vec<ShippingRestriction>,
+}
+
+/// Carriers refusing the goods and carriers accepting them only with special handling.
+/// A carrier refusing the goods is not listed as restricting them
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ShippingRestrictionsCheck {
+    pub refused_by: Vec<CarrierRestrictions>,
+    pub restricted_by: Vec<CarrierRestrictions>,
+}
+
+impl ShippingRestrictionsCheck {
+    pub fn new(restrictions: Vec<ShippingRestriction>, payload: &CheckShippingRestrictions) -> Self {
+        let mut restrictions = restrictions
+            .into_iter()
+            .filter(|restriction| {
+                restriction.applies_to(
+                    &payload.categories,
+                    payload.description.as_ref().map(String::as_str),
+                    &payload.delivery_to,
+                )
+            })
+            .collect::<Vec<_>>();
+        restrictions.sort_by_key(|restriction| (restriction.company_id.0, restriction.id));
+
+        let mut by_company: Vec<CarrierRestrictions> = vec![];
+        for restriction in restrictions {
+            let is_same_company = by_company
+                .last()
+                .map(|carrier| carrier.company_id == restriction.company_id)
+                .unwrap_or(false);
+
+            if is_same_company {
+                if let Some(carrier) = by_company.last_mut() {
+                    carrier.restrictions.push(restriction);
+                }
+            } else {
+                by_company.push(CarrierRestrictions {
+                    company_id: restriction.company_id,
+                    restrictions: vec![restriction],
+                });
+            }
+        }
+
+        let mut check = ShippingRestrictionsCheck::default();
+        for CarrierRestrictions { company_id, restrictions } in by_company {
+            let prohibited = restrictions
+                .iter()
+                .filter(|restriction| restriction.restriction_type == RestrictionType::Prohibited)
+                .cloned()
+                .collect::<Vec<_>>();
+
+            if prohibited.is_empty() {
+                check.restricted_by.push(CarrierRestrictions { company_id, restrictions });
+            } else {
+                check.refused_by.push(CarrierRestrictions {
+                    company_id,
+                    restrictions: prohibited,
+                });
+            }
+        }
+
+        check
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn restriction(id: i32, company_id: i32, restriction_type: RestrictionType) -> ShippingRestriction {
+        ShippingRestriction {
+            id,
+            company_id: CompanyId(company_id),
+            to_alpha3: None,
+            category: Some("Perfume".to_string()),
+            keyword: None,
+            restriction_type,
+            reason: "Flammable liquids".to_string(),
+        }
+    }
+
+    #[test]
+    fn restrictions_apply_by_category_keyword_and_destination() {
+        let rus = Alpha3("RUS".to_string());
+        let categories = vec!["perfume".to_string()];
+        let by_keyword = ShippingRestriction {
+            category: None,
+            keyword: Some("Lithium".to_string()),
+            to_alpha3: Some(rus.clone()),
+            ..restriction(1, 1, RestrictionType::Prohibited)
+        };
+
+        assert!(restriction(1, 1, RestrictionType::Prohibited).applies_to(&categories, None, &rus));
+        assert!(!restriction(1, 1, RestrictionType::Prohibited).applies_to(&["Toys".to_string()], None, &rus));
+        assert!(by_keyword.applies_to(&[], Some("Phone with a lithium battery"), &rus));
+        assert!(!by_keyword.applies_to(&[], Some("Phone with a lithium battery"), &Alpha3("USA".to_string())));
+    }
+
+    #[test]
+    fn prohibited_goods_are_refused_by_carrier() {
+        let payload = CheckShippingRestrictions {
+            categories: vec!["Perfume".to_string()],
+            description: None,
+            delivery_to: Alpha3("RUS".to_string()),
+        };
+        let restrictions = vec![
+            restriction(1, 1, RestrictionType::Restricted),
+            restriction(2, 1, RestrictionType::Prohibited),
+            restriction(3, 2, RestrictionType::Restricted),
+        ];
+
+        let check = ShippingRestrictionsCheck::new(restrictions, &payload);
+
+        assert_eq!(check.refused_by.len(), 1);
+        assert_eq!(check.refused_by[0].company_id, CompanyId(1));
+        assert_eq!(check.refused_by[0].restrictions[0].id, 2);
+        assert_eq!(check.restricted_by.len(), 1);
+        assert_eq!(check.restricted_by[0].company_id, CompanyId(2));
+    }
+}
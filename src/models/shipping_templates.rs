@@ -0,0 +1,231 @@
+//! Models for shipping templates - shipping setups saved by the store to be applied to many products
+use failure::Error as FailureError;
+use serde_json;
+use validator::{Validate, ValidationErrors};
+
+use stq_types::{BaseProductId, ProductPrice, StoreId};
+
+use models::NewShipping;
+use schema::shipping_templates;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ShippingTemplate {
+    pub id: i32,
+    pub store_id: StoreId,
+    pub name: String,
+    /// Base product ids of the items and the pickup are replaced when the template is applied
+    pub shipping: NewShipping,
+    /// Free delivery threshold of the store, applied to the items without their own threshold
+    pub free_delivery_over: Option<ProductPrice>,
+}
+
+impl ShippingTemplate {
+    /// Shipping of the base product set up by the template
+    pub fn expand(&self, base_product_id: BaseProductId) -> NewShipping {
+        let mut shipping = self.shipping.clone();
+        for item in shipping.items.iter_mut() {
+            item.base_product_id = base_product_id;
+            item.store_id = self.store_id;
+            item.free_delivery_over = item.free_delivery_over.or(self.free_delivery_over);
+        }
+        if let Some(ref mut pickup) = shipping.pickup {
+            pickup.base_product_id = base_product_id;
+            pickup.store_id = self.store_id;
+        }
+        shipping
+    }
+}
+
+#[derive(Queryable, Clone, Debug)]
+pub struct ShippingTemplateRaw {
+    pub id: i32,
+    pub store_id: StoreId,
+    pub name: String,
+    pub shipping: serde_json::Value,
+    pub free_delivery_over: Option<ProductPrice>,
+}
+
+impl ShippingTemplateRaw {
+    pub fn to_model(self) -> Result<ShippingTemplate, FailureError> {
+        let ShippingTemplateRaw {
+            id,
+            store_id,
+            name,
+            shipping,
+            free_delivery_over,
+        } = self;
+        let shipping = serde_json::from_value::<NewShipping>(shipping).map_err(|e| {
+            FailureError::from(e)
+                .context(format!("Could not parse JSON with shipping for ShippingTemplate with id = {}", id))
+                .into()
+        })?;
+
+        Ok(ShippingTemplate {
+            id,
+            store_id,
+            name,
+            shipping,
+            free_delivery_over,
+        })
+    }
+}
+
+fn validate_name(name: &str) -> Result<(), ValidationErrors> {
+    if name.trim().is_empty() {
+        Err(validation_errors!({ "name": ["name" => "Name must not be empty"] }))?;
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NewShippingTemplate {
+    pub name: String,
+    pub shipping: NewShipping,
+    #[serde(default)]
+    pub free_delivery_over: Option<ProductPrice>,
+}
+
+json_schema!(NewShippingTemplate {
+    name: String,
+    shipping: NewShipping,
+    free_delivery_over: Option<ProductPrice>,
+});
+
+impl Validate for NewShippingTemplate {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        validate_name(&self.name)
+    }
+}
+
+#[derive(Insertable, Clone, Debug)]
+#[table_name = "shipping_templates"]
+pub struct NewShippingTemplateRaw {
+    pub store_id: StoreId,
+    pub name: String,
+    pub shipping: serde_json::Value,
+    pub free_delivery_over: Option<ProductPrice>,
+}
+
+impl NewShippingTemplateRaw {
+    pub fn from_model(store_id: StoreId, payload: NewShippingTemplate) -> Result<Self, FailureError> {
+        let NewShippingTemplate {
+            name,
+            shipping,
+            free_delivery_over,
+        } = payload;
+
+        Ok(NewShippingTemplateRaw {
+            store_id,
+            name,
+            shipping: serde_json::to_value(shipping)?,
+            free_delivery_over,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UpdateShippingTemplate {
+    pub name: Option<String>,
+    pub shipping: Option<NewShipping>,
+    pub free_delivery_over: Option<ProductPrice>,
+}
+
+json_schema!(UpdateShippingTemplate {
+    name: Option<String>,
+    shipping: Option<NewShipping>,
+    free_delivery_over: Option<ProductPrice>,
+});
+
+impl Validate for UpdateShippingTemplate {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        match self.name {
+            Some(ref name) => validate_name(name),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(AsChangeset, Clone, Debug)]
+#[table_name = "shipping_templates"]
+pub struct UpdateShippingTemplateRaw {
+    pub name: Option<String>,
+    pub shipping: Option<serde_json::Value>,
+    pub free_delivery_over: Option<ProductPrice>,
+}
+
+impl UpdateShippingTemplateRaw {
+    pub fn from_model(payload: UpdateShippingTemplate) -> Result<Self, FailureError> {
+        let UpdateShippingTemplate {
+            name,
+            shipping,
+            free_delivery_over,
+        } = payload;
+        let shipping = match shipping {
+            Some(shipping) => Some(serde_json::to_value(shipping)?),
+            None => None,
+        };
+
+        Ok(UpdateShippingTemplateRaw {
+            name,
+            shipping,
+            free_delivery_over,
+        })
+    }
+}
+
+/// Shipping of the base product is either given in full or set up by a template of the store
+#[derive(Clone, Debug)]
+pub enum ShippingUpsert {
+    Shipping(NewShipping),
+    Template { store_id: StoreId, template_id: i32 },
+}
+
+#[cfg(test)]
+mod tests {
+    use stq_static_resources::Currency;
+    use stq_types::{Alpha3, CompanyPackageId};
+
+    use super::*;
+    use models::{NewPickups, NewProducts, ShippingVariant};
+
+    #[test]
+    fn expanded_template_is_of_the_base_product() {
+        let template = ShippingTemplate {
+            id: 1,
+            store_id: StoreId(10),
+            name: "Couriers".to_string(),
+            shipping: NewShipping {
+                items: vec![NewProducts {
+                    base_product_id: BaseProductId(0),
+                    store_id: StoreId(0),
+                    company_package_id: CompanyPackageId(1),
+                    price: None,
+                    deliveries_to: vec![Alpha3("RUS".to_string())],
+                    shipping: ShippingVariant::International,
+                    measurements: None,
+                    delivery_from: None,
+                    currency: Currency::EUR,
+                    hazard_classes: vec![],
+                    free_delivery_over: None,
+                }],
+                pickup: Some(NewPickups {
+                    base_product_id: BaseProductId(0),
+                    store_id: StoreId(0),
+                    pickup: true,
+                    price: None,
+                }),
+                hazard_classes: vec![],
+            },
+            free_delivery_over: Some(ProductPrice(50.0)),
+        };
+
+        let shipping = template.expand(BaseProductId(5));
+
+        assert_eq!(shipping.items[0].base_product_id, BaseProductId(5));
+        assert_eq!(shipping.items[0].store_id, StoreId(10));
+        assert_eq!(shipping.items[0].free_delivery_over.map(|price| price.0), Some(50.0));
+        let pickup = shipping.pickup.unwrap();
+        assert_eq!((pickup.base_product_id, pickup.store_id), (BaseProductId(5), StoreId(10)));
+    }
+}
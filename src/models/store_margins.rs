@@ -0,0 +1,69 @@
+//! Models for store margins - markup the store adds on top of the carrier cost of the delivery
+use validator::{Validate, ValidationErrors};
+
+use stq_types::StoreId;
+
+use models::SurchargeKind;
+use schema::store_margins;
+
+/// Margin of the store, buyers see the carrier cost with the margin added as the delivery price
+#[derive(Serialize, Deserialize, Queryable, Insertable, Clone, Debug, PartialEq)]
+#[table_name = "store_margins"]
+pub struct StoreMargin {
+    pub store_id: StoreId,
+    pub kind: SurchargeKind,
+    pub value: f64,
+}
+
+impl StoreMargin {
+    /// Delivery price for the buyer with the margin added to the carrier cost
+    pub fn apply(&self, cost: f64) -> f64 {
+        match self.kind {
+            SurchargeKind::Percentage => cost + cost * self.value / 100.0,
+            SurchargeKind::Fixed => cost + self.value,
+        }
+    }
+}
+
+/// Payload replacing the margin of the store
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SetStoreMargin {
+    pub kind: SurchargeKind,
+    pub value: f64,
+}
+
+json_schema!(SetStoreMargin {
+    kind: SurchargeKind,
+    value: f64,
+});
+
+impl Validate for SetStoreMargin {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        if self.value.is_nan() || self.value < 0.0 {
+            Err(validation_errors!({ "value": ["value" => "Value must not be negative"] }))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply() {
+        let margin = StoreMargin {
+            store_id: StoreId(1),
+            kind: SurchargeKind::Percentage,
+            value: 20.0,
+        };
+        assert!((margin.apply(250.0) - 300.0).abs() < 1e-6);
+
+        let margin = StoreMargin {
+            kind: SurchargeKind::Fixed,
+            ..margin
+        };
+        assert!((margin.apply(250.0) - 270.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,53 @@
+//! Models for the shipping summary of the store shown on the home screen of the seller dashboard
+use std::time::SystemTime;
+
+use stq_static_resources::Currency;
+use stq_types::{BaseProductId, CompanyId, CompanyPackageId, ProductPrice, ShippingId, StoreId};
+
+/// Number of the latest changes of the products in the summary
+pub const STORE_SUMMARY_RECENT_CHANGES_LIMIT: i64 = 10;
+
+/// Shipping option of the product with a fixed price
+#[derive(Serialize, Deserialize, Queryable, Clone, Debug, PartialEq)]
+pub struct ConfiguredShippingOption {
+    pub shipping_id: ShippingId,
+    pub base_product_id: BaseProductId,
+    pub company_package_id: CompanyPackageId,
+    pub company_id: CompanyId,
+    pub company_name: String,
+    pub price: Option<ProductPrice>,
+    pub currency: Currency,
+}
+
+/// Shipping of the product changed lately, products without shipping anymore are not listed
+#[derive(Serialize, Deserialize, Queryable, Clone, Debug, PartialEq)]
+pub struct ShippingConfigurationChange {
+    pub shipping_id: ShippingId,
+    pub base_product_id: BaseProductId,
+    pub company_package_id: CompanyPackageId,
+    pub updated_at: SystemTime,
+}
+
+/// Cheapest and most expensive configured options priced in the currency,
+/// prices in different currencies are not compared
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ShippingPriceRange {
+    pub currency: Currency,
+    pub cheapest_option: ConfiguredShippingOption,
+    pub most_expensive_option: ConfiguredShippingOption,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StoreShippingSummary {
+    pub store_id: StoreId,
+    /// Base products of the store with shipping configured
+    pub products_count: i64,
+    /// Companies the products are shipped by
+    pub carriers_count: i64,
+    /// Countries the products are delivered to
+    pub destinations_count: i64,
+    /// Price ranges of the options with fixed prices, one per currency
+    pub price_ranges: Vec<ShippingPriceRange>,
+    /// Latest changes first
+    pub recent_changes: Vec<ShippingConfigurationChange>,
+}
@@ -0,0 +1,145 @@
+//! Models for surcharges - fees like fuel, remote-area or oversize added on top of the delivery price
+use validator::{Validate, ValidationErrors};
+
+use stq_types::{Alpha3, CompanyId, CompanyPackageId};
+
+use schema::surcharges;
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, DieselTypes)]
+pub enum SurchargeKind {
+    /// Percent of the delivery price
+    Percentage,
+    /// Fixed amount in the company currency
+    Fixed,
+}
+
+/// Surcharge is applied to deliveries matching all of its set criteria,
+/// criteria that are not set match any delivery
+#[derive(Serialize, Deserialize, Queryable, Clone, Debug, PartialEq)]
+pub struct Surcharge {
+    pub id: i32,
+    pub name: String,
+    pub kind: SurchargeKind,
+    pub value: f64,
+    pub company_id: Option<CompanyId>,
+    pub company_package_id: Option<CompanyPackageId>,
+    pub to_alpha3: Option<Alpha3>,
+}
+
+impl Surcharge {
+    pub fn applies_to(&self, company_id: CompanyId, company_package_id: CompanyPackageId, delivery_to: &Alpha3) -> bool {
+        self.company_id.map(|id| id == company_id).unwrap_or(true)
+            && self.company_package_id.map(|id| id == company_package_id).unwrap_or(true)
+            && self.to_alpha3.as_ref().map(|alpha3| alpha3 == delivery_to).unwrap_or(true)
+    }
+
+    /// Amount added to the delivery price
+    pub fn calculate_amount(&self, price: f64) -> f64 {
+        match self.kind {
+            SurchargeKind::Percentage => price * self.value / 100.0,
+            SurchargeKind::Fixed => self.value,
+        }
+    }
+}
+
+fn validate_surcharge_value(value: f64) -> Result<(), ValidationErrors> {
+    if value.is_nan() || value < 0.0 {
+        Err(validation_errors!({ "value": ["value" => "Value must not be negative"] }))?;
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Insertable, Clone, Debug)]
+#[table_name = "surcharges"]
+pub struct NewSurcharge {
+    pub name: String,
+    pub kind: SurchargeKind,
+    pub value: f64,
+    pub company_id: Option<CompanyId>,
+    pub company_package_id: Option<CompanyPackageId>,
+    pub to_alpha3: Option<Alpha3>,
+}
+
+json_schema!(NewSurcharge {
+    name: String,
+    kind: SurchargeKind,
+    value: f64,
+    company_id: Option<CompanyId>,
+    company_package_id: Option<CompanyPackageId>,
+    to_alpha3: Option<Alpha3>,
+});
+
+impl Validate for NewSurcharge {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        if self.name.is_empty() {
+            Err(validation_errors!({ "name": ["name" => "Name must not be empty"] }))?;
+        }
+
+        validate_surcharge_value(self.value)
+    }
+}
+
+/// Only the fee itself can be changed, the deliveries it applies to are fixed
+#[derive(Serialize, Deserialize, AsChangeset, Clone, Debug)]
+#[table_name = "surcharges"]
+pub struct UpdateSurcharge {
+    pub name: Option<String>,
+    pub kind: Option<SurchargeKind>,
+    pub value: Option<f64>,
+}
+
+json_schema!(UpdateSurcharge {
+    name: Option<String>,
+    kind: Option<SurchargeKind>,
+    value: Option<f64>,
+});
+
+impl Validate for UpdateSurcharge {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        if self.name.as_ref().map(|name| name.is_empty()).unwrap_or(false) {
+            Err(validation_errors!({ "name": ["name" => "Name must not be empty"] }))?;
+        }
+
+        match self.value {
+            Some(value) => validate_surcharge_value(value),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn surcharge(kind: SurchargeKind, value: f64) -> Surcharge {
+        Surcharge {
+            id: 1,
+            name: "fuel".to_string(),
+            kind,
+            value,
+            company_id: Some(CompanyId(1)),
+            company_package_id: None,
+            to_alpha3: None,
+        }
+    }
+
+    #[test]
+    fn calculate_amount() {
+        assert!((surcharge(SurchargeKind::Percentage, 10.0).calculate_amount(250.0) - 25.0).abs() < 1e-6);
+        assert!((surcharge(SurchargeKind::Fixed, 10.0).calculate_amount(250.0) - 10.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn applies_to_matching_deliveries_only() {
+        let rus = Alpha3("RUS".to_string());
+        let surcharge = Surcharge {
+            to_alpha3: Some(rus.clone()),
+            ..surcharge(SurchargeKind::Fixed, 10.0)
+        };
+
+        assert!(surcharge.applies_to(CompanyId(1), CompanyPackageId(5), &rus));
+        assert!(!surcharge.applies_to(CompanyId(2), CompanyPackageId(5), &rus));
+        assert!(!surcharge.applies_to(CompanyId(1), CompanyPackageId(5), &Alpha3("USA".to_string())));
+    }
+}
@@ -0,0 +1,198 @@
+//! Deterministic fixture set loaded by the test endpoints, so that QA and the contract tests of the consumers
+//! run against a known state. Tables are emptied with their sequences restarted before the fixtures are loaded,
+//! so ids of the fixtures are their 1-based positions in the lists below
+use stq_static_resources::Currency;
+use stq_types::{Alpha2, Alpha3, CompanyId, CompanyPackageId, CountryLabel, PackageId};
+
+use models::{
+    CarrierFallback, CompanyCapability, NewCompany, NewCompanyPackage, NewCountry, NewPackages, NewShippingRates, ShippingRate,
+    ShippingRateSource, ShippingRatesDestination,
+};
+
+/// Counts of the rows loaded by the seeding
+#[derive(Default, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TestFixturesReport {
+    pub countries: usize,
+    pub companies: usize,
+    pub packages: usize,
+    pub companies_packages: usize,
+    pub shipping_rates: usize,
+}
+
+pub struct TestFixtures {
+    /// Countries tree, parents go before their children
+    pub countries: Vec<NewCountry>,
+    pub companies: Vec<NewCompany>,
+    pub packages: Vec<NewPackages>,
+    pub companies_packages: Vec<NewCompanyPackage>,
+    pub shipping_rates: Vec<NewShippingRates>,
+}
+
+impl TestFixtures {
+    pub fn report(&self) -> TestFixturesReport {
+        TestFixturesReport {
+            countries: self.countries.len(),
+            companies: self.companies.len(),
+            packages: self.packages.len(),
+            companies_packages: self.companies_packages.len(),
+            shipping_rates: self.shipping_rates.len(),
+        }
+    }
+}
+
+fn alpha3(code: &str) -> Alpha3 {
+    Alpha3(code.to_string())
+}
+
+fn country(label: &str, level: i32, alpha2: &str, alpha3_code: &str, numeric: i32, parent: Option<&str>) -> NewCountry {
+    NewCountry {
+        label: CountryLabel(label.to_string()),
+        level,
+        alpha2: Alpha2(alpha2.to_string()),
+        alpha3: alpha3(alpha3_code),
+        numeric,
+        parent: parent.map(alpha3),
+    }
+}
+
+fn company(name: &str, label: &str, deliveries_from: &[&str], currency: Currency) -> NewCompany {
+    NewCompany {
+        name: name.to_string(),
+        label: label.to_string(),
+        description: None,
+        deliveries_from: deliveries_from.iter().map(|code| alpha3(code)).collect(),
+        logo: format!("https://example.com/{}.png", label.to_lowercase()),
+        currency,
+        capabilities: vec![CompanyCapability::Tracking],
+        label_translations: vec![],
+    }
+}
+
+fn package(name: &str, max_weight: u32, deliveries_to: &[&str]) -> NewPackages {
+    NewPackages {
+        name: name.to_string(),
+        max_size: 150,
+        min_size: 0,
+        max_weight,
+        min_weight: 0,
+        deliveries_to: deliveries_to.iter().map(|code| alpha3(code)).collect(),
+        deliveries_to_zones: vec![],
+        name_translations: vec![],
+    }
+}
+
+fn company_package(company_id: i32, package_id: i32) -> NewCompanyPackage {
+    NewCompanyPackage {
+        company_id: CompanyId(company_id),
+        package_id: PackageId(package_id),
+        shipping_rate_source: Some(ShippingRateSource::Static { dimensional_factor: None }),
+        restricted_hazard_classes: vec![],
+        delivery_time_min_days: Some(2),
+        delivery_time_max_days: Some(7),
+        rate_resolution_order: None,
+        insurance_options: vec![],
+        cod_supported: false,
+        cod_fee: None,
+        carrier_fallback: CarrierFallback::StoredRates,
+        fallback_markup_percent: None,
+        daily_cutoff_time: None,
+        max_daily_shipments: None,
+    }
+}
+
+/// Rates of the company package with the price growing by `price_per_kg` for every kilogram up to 5 kg
+fn rates(company_package_id: i32, from: &str, to: &str, base_price: f64, price_per_kg: f64) -> NewShippingRates {
+    NewShippingRates {
+        company_package_id: CompanyPackageId(company_package_id),
+        from_alpha3: alpha3(from),
+        to: ShippingRatesDestination::Country(alpha3(to)),
+        rates: (1..6)
+            .map(|kg| ShippingRate {
+                weight_g: kg * 1000,
+                price: base_price + price_per_kg * f64::from(kg - 1),
+            })
+            .collect(),
+        delivery_time_min_days: None,
+        delivery_time_max_days: None,
+    }
+}
+
+/// Fixtures loaded by `POST /__test__/seed`
+pub fn test_fixtures() -> TestFixtures {
+    TestFixtures {
+        countries: vec![
+            country("All", 0, "", "XAL", 0, None),
+            country("Europe", 1, "", "XEU", 0, Some("XAL")),
+            country("North America", 1, "", "XNA", 0, Some("XAL")),
+            country("Asia", 1, "", "XAS", 0, Some("XAL")),
+            country("Russian Federation", 2, "RU", "RUS", 643, Some("XEU")),
+            country("Germany", 2, "DE", "DEU", 276, Some("XEU")),
+            country("United States of America", 2, "US", "USA", 840, Some("XNA")),
+            country("China", 2, "CN", "CHN", 156, Some("XAS")),
+        ],
+        companies: vec![
+            company("Test Post", "TESTPOST", &["RUS", "DEU"], Currency::EUR),
+            company("Test Express", "TESTEXPRESS", &["USA", "CHN"], Currency::USD),
+        ],
+        packages: vec![package("Parcel", 5000, &["RUS", "DEU", "USA"]), package("Letter", 1000, &["XAL"])],
+        companies_packages: vec![company_package(1, 1), company_package(1, 2), company_package(2, 1)],
+        shipping_rates: vec![
+            rates(1, "RUS", "RUS", 5.0, 1.0),
+            rates(1, "RUS", "DEU", 12.0, 3.0),
+            rates(1, "DEU", "DEU", 6.0, 1.5),
+            rates(2, "RUS", "RUS", 2.0, 0.5),
+            rates(3, "USA", "USA", 8.0, 2.0),
+            rates(3, "CHN", "USA", 15.0, 4.0),
+        ],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use validator::Validate;
+
+    use super::*;
+    use models::{validate_countries_tree, Country};
+
+    #[test]
+    fn countries_make_valid_tree() {
+        let countries = test_fixtures()
+            .countries
+            .into_iter()
+            .map(|country| Country {
+                label: country.label,
+                level: country.level,
+                alpha2: country.alpha2,
+                alpha3: country.alpha3,
+                numeric: country.numeric,
+                parent: country.parent,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+
+        assert!(validate_countries_tree(&countries).is_valid());
+    }
+
+    #[test]
+    fn fixtures_reference_fixtures_only() {
+        let fixtures = test_fixtures();
+        let known_countries = fixtures.countries.iter().map(|country| country.alpha3.clone()).collect::<Vec<_>>();
+
+        for company in &fixtures.companies {
+            assert!(company.deliveries_from.iter().all(|code| known_countries.contains(code)));
+        }
+        for package in &fixtures.packages {
+            assert!(package.deliveries_to.iter().all(|code| known_countries.contains(code)));
+        }
+        for company_package in &fixtures.companies_packages {
+            assert!(company_package.validate().is_ok());
+            assert!(company_package.company_id.0 >= 1 && company_package.company_id.0 as usize <= fixtures.companies.len());
+            assert!(company_package.package_id.0 >= 1 && company_package.package_id.0 as usize <= fixtures.packages.len());
+        }
+        for rates in &fixtures.shipping_rates {
+            let company_package = &fixtures.companies_packages[rates.company_package_id.0 as usize - 1];
+            let company = &fixtures.companies[company_package.company_id.0 as usize - 1];
+            assert!(company.deliveries_from.contains(&rates.from_alpha3));
+        }
+    }
+}
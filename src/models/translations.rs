@@ -0,0 +1,107 @@
+//! Translations of the names shown on the marketplace, e.g. labels of the companies and names of the packages.
+//! The name itself is the text in the default language, it is returned when there is no translation to the
+//! requested language or to the default one
+use validator::ValidationErrors;
+
+/// Language the translations fall back to
+pub const DEFAULT_TRANSLATION_LANG: &str = "en";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TextTranslation {
+    /// Language code, e.g. `en` or `pt-BR`
+    pub lang: String,
+    pub text: String,
+}
+
+json_schema!(TextTranslation {
+    lang: String,
+    text: String
+});
+
+/// Translation of the text to the language. Regional languages fall back to the base one, e.g. `pt-BR` to `pt`,
+/// and any language falls back to the default one
+pub fn translate<'a>(translations: &'a [TextTranslation], lang: &str) -> Option<&'a str> {
+    let find = |lang: &str| {
+        translations
+            .iter()
+            .find(|translation| translation.lang.eq_ignore_ascii_case(lang))
+            .map(|translation| translation.text.as_str())
+    };
+    let base_lang = lang.split(|c| c == '-' || c == '_').next().unwrap_or(lang);
+
+    find(lang).or_else(|| find(base_lang)).or_else(|| find(DEFAULT_TRANSLATION_LANG))
+}
+
+pub fn validate_translations(field: &'static str, translations: &[TextTranslation]) -> Result<(), ValidationErrors> {
+    let mut langs = vec![];
+    for translation in translations {
+        let message = if translation.lang.trim().is_empty() {
+            Some("Language of the translation must not be empty")
+        } else if translation.text.trim().is_empty() {
+            Some("Text of the translation must not be empty")
+        } else if langs.contains(&translation.lang.to_lowercase()) {
+            Some("Language must be translated only once")
+        } else {
+            None
+        };
+
+        if let Some(message) = message {
+            return Err(validation_errors!({ field: ["translation" => format!("{}: {}", message, translation.lang)] }));
+        }
+
+        langs.push(translation.lang.to_lowercase());
+    }
+
+    Ok(())
+}
+
+/// Content with the names resolved to the requested language
+pub trait Localized {
+    fn localized(self, lang: &str) -> Self;
+}
+
+impl<T: Localized> Localized for Vec<T> {
+    fn localized(self, lang: &str) -> Self {
+        self.into_iter().map(|value| value.localized(lang)).collect()
+    }
+}
+
+impl<T: Localized> Localized for Option<T> {
+    fn localized(self, lang: &str) -> Self {
+        self.map(|value| value.localized(lang))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn translation(lang: &str, text: &str) -> TextTranslation {
+        TextTranslation {
+            lang: lang.to_string(),
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn translation_falls_back_to_base_and_default_language() {
+        let translations = vec![
+            translation("en", "Post"),
+            translation("ru", "Почта"),
+            translation("pt-BR", "Correio"),
+        ];
+
+        assert_eq!(translate(&translations, "RU"), Some("Почта"));
+        assert_eq!(translate(&translations, "ru-RU"), Some("Почта"));
+        assert_eq!(translate(&translations, "pt-BR"), Some("Correio"));
+        assert_eq!(translate(&translations, "de"), Some("Post"));
+        assert_eq!(translate(&translations[1..], "de"), None);
+    }
+
+    #[test]
+    fn languages_are_translated_once() {
+        assert!(validate_translations("label_translations", &[translation("en", "Post"), translation("ru", "Почта")]).is_ok());
+        assert!(validate_translations("label_translations", &[translation("en", "Post"), translation("EN", "Mail")]).is_err());
+        assert!(validate_translations("label_translations", &[translation("ru", " ")]).is_err());
+    }
+}
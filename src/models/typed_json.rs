@@ -0,0 +1,106 @@
+//! JSONB column with typed content. The content is (de)serialized by Diesel when the row is read or written,
+//! so the rows carry the models and a malformed column fails the query once instead of every conversion of the row
+use std::fmt;
+use std::io::Write;
+use std::ops::Deref;
+
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Jsonb;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json;
+
+#[derive(AsExpression, FromSqlRow, Clone, Debug, Default, PartialEq)]
+#[sql_type = "Jsonb"]
+pub struct TypedJson<T>(pub T);
+
+impl<T> TypedJson<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for TypedJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> From<T> for TypedJson<T> {
+    fn from(value: T) -> Self {
+        TypedJson(value)
+    }
+}
+
+impl<T: DeserializeOwned> FromSql<Jsonb, Pg> for TypedJson<T> {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let value = <serde_json::Value as FromSql<Jsonb, Pg>>::from_sql(bytes)?;
+        serde_json::from_value(value)
+            .map(TypedJson)
+            .map_err(|e| format!("Can not parse JSONB column: {}", e).into())
+    }
+}
+
+impl<T: Serialize + fmt::Debug> ToSql<Jsonb, Pg> for TypedJson<T> {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        let value = serde_json::to_value(&self.0)?;
+        <serde_json::Value as ToSql<Jsonb, Pg>>::to_sql(&value, out)
+    }
+}
+
+/// Column is serialized as its content, rows keep the format of the API
+impl<T: Serialize> Serialize for TypedJson<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for TypedJson<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(TypedJson)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::debug_query;
+    use diesel::prelude::*;
+
+    use stq_types::Alpha3;
+
+    use super::*;
+    use schema::packages::dsl::*;
+
+    #[test]
+    fn content_is_serialized_as_is() {
+        let codes = TypedJson(vec![Alpha3("RUS".to_string()), Alpha3("USA".to_string())]);
+
+        let value = serde_json::to_value(&codes).unwrap();
+        assert_eq!(value, json!(["RUS", "USA"]));
+        assert_eq!(serde_json::from_value::<TypedJson<Vec<Alpha3>>>(value).unwrap(), codes);
+    }
+
+    #[test]
+    fn malformed_column_fails_to_parse() {
+        // binary JSONB is the version byte followed by the text of the value
+        let zones = <TypedJson<Vec<i32>> as FromSql<Jsonb, Pg>>::from_sql(Some(&b"\x01[1, 2]"[..])).unwrap();
+        assert_eq!(zones, TypedJson(vec![1, 2]));
+
+        let malformed = <TypedJson<Vec<i32>> as FromSql<Jsonb, Pg>>::from_sql(Some(&b"\x01[\"RUS\"]"[..]));
+        assert!(malformed.is_err());
+    }
+
+    #[test]
+    fn column_is_bound_as_jsonb() {
+        let codes = TypedJson(vec![Alpha3("RUS".to_string())]);
+        let query = diesel::update(packages).set(deliveries_to.eq(&codes));
+
+        let sql = debug_query::<Pg, _>(&query).to_string();
+        assert!(sql.starts_with(r#"UPDATE "packages" SET "deliveries_to" = $1"#), "{}", sql);
+        assert!(sql.contains("RUS"), "{}", sql);
+    }
+}
@@ -0,0 +1,157 @@
+//! Units of the measurements sent by the clients. Measurements are stored and priced in grams and cubic centimeters,
+//! values in other units are converted and rounded up to whole grams and cubic centimeters
+
+use std::str::FromStr;
+
+use failure::Error as FailureError;
+use validator::{Validate, ValidationErrors};
+
+use models::ShipmentMeasurements;
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WeightUnit {
+    G,
+    Kg,
+    Oz,
+    Lb,
+}
+
+impl Default for WeightUnit {
+    fn default() -> Self {
+        WeightUnit::G
+    }
+}
+
+impl FromStr for WeightUnit {
+    type Err = FailureError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "g" => Ok(WeightUnit::G),
+            "kg" => Ok(WeightUnit::Kg),
+            "oz" => Ok(WeightUnit::Oz),
+            "lb" => Ok(WeightUnit::Lb),
+            _ => Err(format_err!("Unknown weight unit \"{}\", expected one of g, kg, oz, lb", s)),
+        }
+    }
+}
+
+impl WeightUnit {
+    fn grams(self) -> f64 {
+        match self {
+            WeightUnit::G => 1.0,
+            WeightUnit::Kg => 1000.0,
+            WeightUnit::Oz => 28.349_523_125,
+            WeightUnit::Lb => 453.592_37,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VolumeUnit {
+    Cm3,
+    L,
+    In3,
+    Ft3,
+}
+
+impl Default for VolumeUnit {
+    fn default() -> Self {
+        VolumeUnit::Cm3
+    }
+}
+
+impl FromStr for VolumeUnit {
+    type Err = FailureError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cm3" => Ok(VolumeUnit::Cm3),
+            "l" => Ok(VolumeUnit::L),
+            "in3" => Ok(VolumeUnit::In3),
+            "ft3" => Ok(VolumeUnit::Ft3),
+            _ => Err(format_err!("Unknown volume unit \"{}\", expected one of cm3, l, in3, ft3", s)),
+        }
+    }
+}
+
+impl VolumeUnit {
+    fn cubic_cm(self) -> f64 {
+        match self {
+            VolumeUnit::Cm3 => 1.0,
+            VolumeUnit::L => 1000.0,
+            VolumeUnit::In3 => 16.387_064,
+            VolumeUnit::Ft3 => 28_316.846_592,
+        }
+    }
+}
+
+/// Converts the value into whole base units, `None` if it is negative or does not fit.
+/// Float noise of the conversion is dropped before rounding up, so that 1.1 kg is 1100 g and not 1101 g
+fn to_base_units(value: f64, factor: f64) -> Option<u32> {
+    let converted = ((value * factor * 1000.0).round() / 1000.0).ceil();
+    if value.is_finite() && converted >= 0.0 && converted <= f64::from(u32::max_value()) {
+        Some(converted as u32)
+    } else {
+        None
+    }
+}
+
+/// Measurements of the shipment in grams and cubic centimeters from the values in the units the client sent
+pub fn normalize_measurements(
+    volume: f64,
+    volume_unit: VolumeUnit,
+    weight: f64,
+    weight_unit: WeightUnit,
+) -> Result<ShipmentMeasurements, ValidationErrors> {
+    let volume_cubic_cm = to_base_units(volume, volume_unit.cubic_cm())
+        .ok_or_else(|| validation_errors!({ "volume": ["volume" => "Volume must be a non-negative number"] }))?;
+    let weight_g = to_base_units(weight, weight_unit.grams())
+        .ok_or_else(|| validation_errors!({ "weight": ["weight" => "Weight must be a non-negative number"] }))?;
+
+    let measurements = ShipmentMeasurements { volume_cubic_cm, weight_g };
+    measurements.validate()?;
+    Ok(measurements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measurements_are_converted_and_rounded_up() {
+        let measurements = normalize_measurements(2.0, VolumeUnit::L, 1.0, WeightUnit::Lb).unwrap();
+        assert_eq!(measurements.volume_cubic_cm, 2000);
+        assert_eq!(measurements.weight_g, 454);
+
+        let measurements = normalize_measurements(10.0, VolumeUnit::In3, 16.0, WeightUnit::Oz).unwrap();
+        assert_eq!(measurements.volume_cubic_cm, 164);
+        assert_eq!(measurements.weight_g, 454);
+
+        let measurements = normalize_measurements(0.1, VolumeUnit::L, 1.1, WeightUnit::Kg).unwrap();
+        assert_eq!(measurements.volume_cubic_cm, 100);
+        assert_eq!(measurements.weight_g, 1100);
+    }
+
+    #[test]
+    fn values_in_base_units_are_kept() {
+        let measurements = normalize_measurements(100.0, VolumeUnit::default(), 250.0, WeightUnit::default()).unwrap();
+        assert_eq!(measurements.volume_cubic_cm, 100);
+        assert_eq!(measurements.weight_g, 250);
+    }
+
+    #[test]
+    fn negative_and_huge_values_are_rejected() {
+        assert!(normalize_measurements(-1.0, VolumeUnit::Cm3, 1.0, WeightUnit::G).is_err());
+        assert!(normalize_measurements(1.0, VolumeUnit::Cm3, 1.0e12, WeightUnit::Kg).is_err());
+    }
+
+    #[test]
+    fn unknown_units_are_not_parsed() {
+        assert_eq!("lb".parse::<WeightUnit>().unwrap(), WeightUnit::Lb);
+        assert_eq!("ft3".parse::<VolumeUnit>().unwrap(), VolumeUnit::Ft3);
+        assert!("pound".parse::<WeightUnit>().is_err());
+    }
+}
@@ -25,6 +25,32 @@ pub struct UserAddress {
     pub created_at: SystemTime,
     pub updated_at: SystemTime,
     pub country_code: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub subdivision: Option<String>,
+}
+
+/// User address as listed by `GET /users/:id/addresses`
+#[derive(Serialize, Debug)]
+pub struct ListedUserAddress {
+    #[serde(flatten)]
+    pub address: UserAddress,
+    /// Position of the address in the list, the default address has priority 0
+    pub priority: u32,
+}
+
+impl ListedUserAddress {
+    /// Numbers addresses already ordered with the default one first
+    pub fn from_ordered(addresses: Vec<UserAddress>) -> Vec<ListedUserAddress> {
+        addresses
+            .into_iter()
+            .enumerate()
+            .map(|(priority, address)| ListedUserAddress {
+                address,
+                priority: priority as u32,
+            })
+            .collect()
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Insertable, Validate)]
@@ -45,9 +71,32 @@ pub struct NewUserAddress {
     pub is_priority: bool,
     #[validate(length(min = "1", message = "Country code must not be empty"))]
     pub country_code: Option<String>,
+    /// Coordinates of the address are resolved with the geocoding provider if not set
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    /// ISO 3166-2 code of the subdivision of the country, can only be set together with the country code
+    pub subdivision: Option<String>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, Insertable, AsChangeset, Validate)]
+json_schema!(NewUserAddress {
+    user_id: UserId,
+    administrative_area_level_1: Option<String>,
+    administrative_area_level_2: Option<String>,
+    country: String,
+    locality: Option<String>,
+    political: Option<String>,
+    postal_code: String,
+    route: Option<String>,
+    street_number: Option<String>,
+    address: Option<String>,
+    is_priority: bool,
+    country_code: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    subdivision: Option<String>,
+});
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Insertable, AsChangeset, Validate)]
 #[table_name = "user_addresses"]
 pub struct UpdateUserAddress {
     pub administrative_area_level_1: Option<String>,
@@ -64,4 +113,85 @@ pub struct UpdateUserAddress {
     pub is_priority: Option<bool>,
     #[validate(length(min = "1", message = "Country code must not be empty"))]
     pub country_code: Option<String>,
+    /// Coordinates of the address are resolved with the geocoding provider if not set
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    /// ISO 3166-2 code of the subdivision of the country, can only be set together with the country code.
+    /// Changing the country code without the subdivision clears the subdivision of the previous country
+    pub subdivision: Option<String>,
+}
+
+impl UpdateUserAddress {
+    /// Update moves the address, so its coordinates must be resolved again
+    pub fn changes_location(&self) -> bool {
+        self.administrative_area_level_1.is_some()
+            || self.administrative_area_level_2.is_some()
+            || self.country.is_some()
+            || self.locality.is_some()
+            || self.postal_code.is_some()
+            || self.route.is_some()
+            || self.street_number.is_some()
+            || self.address.is_some()
+    }
+}
+
+json_schema!(UpdateUserAddress {
+    administrative_area_level_1: Option<String>,
+    administrative_area_level_2: Option<String>,
+    country: Option<String>,
+    locality: Option<String>,
+    political: Option<String>,
+    postal_code: Option<String>,
+    route: Option<String>,
+    street_number: Option<String>,
+    address: Option<String>,
+    is_priority: Option<bool>,
+    country_code: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    subdivision: Option<String>,
+});
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+
+    use super::*;
+
+    fn address(id: i32, is_priority: bool) -> UserAddress {
+        UserAddress {
+            id,
+            user_id: UserId(1),
+            administrative_area_level_1: None,
+            administrative_area_level_2: None,
+            country: "Russia".to_string(),
+            locality: Some("Moscow".to_string()),
+            political: None,
+            postal_code: "125009".to_string(),
+            route: None,
+            street_number: None,
+            address: None,
+            is_priority,
+            created_at: SystemTime::now(),
+            updated_at: SystemTime::now(),
+            country_code: Some("RUS".to_string()),
+            latitude: None,
+            longitude: None,
+            subdivision: None,
+        }
+    }
+
+    #[test]
+    fn listed_addresses_are_numbered_from_the_default_one() {
+        let listed = ListedUserAddress::from_ordered(vec![address(2, true), address(3, false), address(1, false)]);
+        let json = serde_json::to_value(&listed).unwrap();
+
+        assert_eq!(json[0]["id"], 2);
+        assert_eq!(json[0]["is_priority"], true);
+        assert_eq!(json[0]["priority"], 0);
+        assert_eq!(json[1]["id"], 3);
+        assert_eq!(json[1]["priority"], 1);
+        assert_eq!(json[2]["id"], 1);
+        assert_eq!(json[2]["priority"], 2);
+    }
 }
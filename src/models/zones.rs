@@ -0,0 +1,109 @@
+//! Models for shipping zones - named sets of countries carriers use for pricing
+use failure::Error as FailureError;
+use failure::Fail;
+use serde_json;
+use validator::Validate;
+
+use stq_types::Alpha3;
+
+use errors::Error;
+use schema::zones;
+
+#[derive(Serialize, Deserialize, Queryable, Debug)]
+pub struct ZoneRaw {
+    pub id: i32,
+    pub name: String,
+    pub countries: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Zone {
+    pub id: i32,
+    pub name: String,
+    pub countries: Vec<Alpha3>,
+}
+
+impl ZoneRaw {
+    pub fn to_model(self) -> Result<Zone, FailureError> {
+        let ZoneRaw { id, name, countries } = self;
+
+        let countries = serde_json::from_value(countries).map_err(|e| {
+            e.context(format!("Can not parse countries of zone with id = {}", id))
+                .context(Error::Parse)
+        })?;
+
+        Ok(Zone { id, name, countries })
+    }
+}
+
+impl Zone {
+    pub fn contains(&self, country: &Alpha3) -> bool {
+        self.countries.iter().any(|alpha3| alpha3 == country)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Validate)]
+pub struct NewZone {
+    #[validate(length(min = "1", message = "Name must not be empty"))]
+    pub name: String,
+    pub countries: Vec<Alpha3>,
+}
+
+json_schema!(NewZone {
+    name: String,
+    countries: Vec<Alpha3>,
+});
+
+#[derive(Serialize, Deserialize, Insertable, Clone, Debug)]
+#[table_name = "zones"]
+pub struct NewZoneRaw {
+    pub name: String,
+    pub countries: serde_json::Value,
+}
+
+impl NewZone {
+    pub fn to_raw(self) -> Result<NewZoneRaw, FailureError> {
+        let countries =
+            serde_json::to_value(self.countries).map_err(|e| e.context("Can not parse countries from value").context(Error::Parse))?;
+
+        Ok(NewZoneRaw {
+            name: self.name,
+            countries,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Validate)]
+pub struct UpdateZone {
+    #[validate(length(min = "1", message = "Name must not be empty"))]
+    pub name: Option<String>,
+    pub countries: Option<Vec<Alpha3>>,
+}
+
+json_schema!(UpdateZone {
+    name: Option<String>,
+    countries: Option<Vec<Alpha3>>,
+});
+
+#[derive(Serialize, Deserialize, AsChangeset, Clone, Debug)]
+#[table_name = "zones"]
+pub struct UpdateZoneRaw {
+    pub name: Option<String>,
+    pub countries: Option<serde_json::Value>,
+}
+
+impl UpdateZone {
+    pub fn to_raw(self) -> Result<UpdateZoneRaw, FailureError> {
+        let countries = match self.countries {
+            Some(countries) => {
+                Some(serde_json::to_value(countries).map_err(|e| e.context("Can not parse countries from value").context(Error::Parse))?)
+            }
+            None => None,
+        };
+
+        Ok(UpdateZoneRaw {
+            name: self.name,
+            countries,
+        })
+    }
+}
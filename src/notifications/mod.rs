@@ -0,0 +1,61 @@
+//! Notifications is a module responsible for rendering shipment notifications of the buyers
+//! and sending them via the notifications microservice
+pub mod templates;
+
+use failure::Error as FailureError;
+use failure::Fail;
+use futures::future;
+use futures::Future;
+use hyper::Method;
+use serde_json;
+
+use stq_http::client::ClientHandle;
+
+use errors::Error;
+use models::Notification;
+use request_context::RequestContext;
+
+/// Notifications client Future
+pub type NotificationFuture<T> = Box<dyn Future<Item = T, Error = FailureError>>;
+
+pub trait NotificationsClient {
+    /// Sends the notification to the user, the request is traced with the context of the incoming one
+    fn send(&self, notification: Notification, request_context: &RequestContext) -> NotificationFuture<()>;
+}
+
+/// Sends notifications to the notifications microservice, which delivers them by the channel of the notification
+pub struct HttpNotificationsClient {
+    client_handle: ClientHandle,
+    url: String,
+}
+
+impl HttpNotificationsClient {
+    pub fn new(client_handle: ClientHandle, url: String) -> Self {
+        HttpNotificationsClient { client_handle, url }
+    }
+}
+
+impl NotificationsClient for HttpNotificationsClient {
+    fn send(&self, notification: Notification, request_context: &RequestContext) -> NotificationFuture<()> {
+        info!(
+            "{}",
+            request_context.log_line_with(
+                "Sending notification",
+                json!({ "user_id": notification.user_id, "channel": notification.channel }),
+            )
+        );
+
+        let body = match serde_json::to_string(&notification) {
+            Ok(body) => body,
+            Err(e) => return Box::new(future::err(e.context(Error::Parse).into())),
+        };
+        let url = format!("{}/notifications", self.url);
+
+        Box::new(
+            self.client_handle
+                .request_json::<serde_json::Value>(Method::Post, url, Some(body), Some(request_context.headers()))
+                .map_err(|e| e.context(Error::HttpClient).into())
+                .map(|_| ()),
+        )
+    }
+}
@@ -0,0 +1,199 @@
+//! Templates of the shipment notifications. Placeholders `{order}`, `{carrier}`, `{delivery_time}`,
+//! `{tracking_number}` and `{delayed_until}` are replaced with the values of the event
+use models::{AvailablePackageForUser, Notification, NotificationChannel, ShipmentEvent, ShipmentEventKind, DEFAULT_NOTIFICATION_LANG};
+
+struct Template {
+    kind: ShipmentEventKind,
+    lang: &'static str,
+    email_subject: &'static str,
+    email_text: &'static str,
+    push_text: &'static str,
+}
+
+const TEMPLATES: &[Template] = &[
+    Template {
+        kind: ShipmentEventKind::OptionChosen,
+        lang: "en",
+        email_subject: "Delivery of order {order}",
+        email_text: "Your order {order} will be delivered by {carrier} in {delivery_time} days after dispatch.",
+        push_text: "Order {order} will be delivered by {carrier}",
+    },
+    Template {
+        kind: ShipmentEventKind::OptionChosen,
+        lang: "ru",
+        email_subject: "Доставка заказа {order}",
+        email_text: "Ваш заказ {order} доставит {carrier} за {delivery_time} дн. после отправки.",
+        push_text: "Заказ {order} доставит {carrier}",
+    },
+    Template {
+        kind: ShipmentEventKind::ShipmentDispatched,
+        lang: "en",
+        email_subject: "Order {order} is dispatched",
+        email_text: "Your order {order} is handed over to {carrier}. Tracking number: {tracking_number}.",
+        push_text: "Order {order} is dispatched",
+    },
+    Template {
+        kind: ShipmentEventKind::ShipmentDispatched,
+        lang: "ru",
+        email_subject: "Заказ {order} отправлен",
+        email_text: "Ваш заказ {order} передан в {carrier}. Трек-номер: {tracking_number}.",
+        push_text: "Заказ {order} отправлен",
+    },
+    Template {
+        kind: ShipmentEventKind::DeliveryDelayed,
+        lang: "en",
+        email_subject: "Delivery of order {order} is delayed",
+        email_text: "Sorry, delivery of your order {order} by {carrier} is delayed until {delayed_until}.",
+        push_text: "Order {order} is delayed until {delayed_until}",
+    },
+    Template {
+        kind: ShipmentEventKind::DeliveryDelayed,
+        lang: "ru",
+        email_subject: "Доставка заказа {order} задерживается",
+        email_text: "К сожалению, доставка вашего заказа {order} службой {carrier} задерживается до {delayed_until}.",
+        push_text: "Заказ {order} задерживается до {delayed_until}",
+    },
+];
+
+/// Language the event is notified in, the default one if there are no templates in the language of the event
+pub fn notification_lang(event: &ShipmentEvent) -> String {
+    event
+        .lang
+        .as_ref()
+        .map(|lang| lang.to_lowercase())
+        .filter(|lang| TEMPLATES.iter().any(|template| template.lang == lang.as_str()))
+        .unwrap_or_else(|| DEFAULT_NOTIFICATION_LANG.to_string())
+}
+
+fn delivery_time(package: &AvailablePackageForUser) -> String {
+    match (package.delivery_time_min_days, package.delivery_time_max_days) {
+        (Some(min), Some(max)) if min != max => format!("{}-{}", min, max),
+        (Some(days), _) | (_, Some(days)) => days.to_string(),
+        (None, None) => "?".to_string(),
+    }
+}
+
+fn fill(template: &str, event: &ShipmentEvent, package: &AvailablePackageForUser) -> String {
+    template
+        .replace("{order}", &event.order_slug)
+        .replace("{carrier}", &package.name)
+        .replace("{delivery_time}", &delivery_time(package))
+        .replace(
+            "{tracking_number}",
+            event.tracking_number.as_ref().map(String::as_str).unwrap_or("-"),
+        )
+        .replace(
+            "{delayed_until}",
+            &event.delayed_until.map(|date| date.to_string()).unwrap_or_default(),
+        )
+}
+
+/// Email and push notifications of the event, delivered with the package
+pub fn render(event: &ShipmentEvent, package: &AvailablePackageForUser) -> Vec<Notification> {
+    let lang = notification_lang(event);
+    let template = match TEMPLATES
+        .iter()
+        .find(|template| template.kind == event.kind && template.lang == lang.as_str())
+    {
+        Some(template) => template,
+        None => return vec![],
+    };
+
+    vec![
+        Notification {
+            channel: NotificationChannel::Email,
+            user_id: event.user_id,
+            subject: Some(fill(template.email_subject, event, package)),
+            text: fill(template.email_text, event, package),
+        },
+        Notification {
+            channel: NotificationChannel::Push,
+            user_id: event.user_id,
+            subject: None,
+            text: fill(template.push_text, event, package),
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use stq_static_resources::Currency;
+    use stq_types::{BaseProductId, CompanyPackageId, ShippingId, StoreId, UserId};
+
+    use models::ShippingVariant;
+
+    use super::*;
+
+    fn package() -> AvailablePackageForUser {
+        AvailablePackageForUser {
+            id: CompanyPackageId(1),
+            shipping_id: ShippingId(1),
+            name: "UPS-avia".to_string(),
+            logo: "logo".to_string(),
+            price: None,
+            cost: None,
+            option_id: None,
+            currency: Currency::STQ,
+            shipping_variant: ShippingVariant::International,
+            base_product_id: BaseProductId(1),
+            store_id: StoreId(1),
+            delivery_time_min_days: Some(3),
+            delivery_time_max_days: Some(7),
+            free_delivery_over: None,
+            free_shipping: false,
+            delivery_from: None,
+            shipping_date: None,
+            estimated_delivery_date_min: None,
+            estimated_delivery_date_max: None,
+        }
+    }
+
+    fn event(kind: ShipmentEventKind, lang: Option<&str>) -> ShipmentEvent {
+        ShipmentEvent {
+            event_id: "order-1".to_string(),
+            kind,
+            user_id: UserId(1),
+            shipping_id: ShippingId(1),
+            order_slug: "1042".to_string(),
+            lang: lang.map(|lang| lang.to_string()),
+            tracking_number: Some("RA123456789CN".to_string()),
+            delayed_until: Some(NaiveDate::from_ymd(2019, 3, 25)),
+        }
+    }
+
+    #[test]
+    fn email_and_push_are_rendered_from_templates() {
+        let notifications = render(&event(ShipmentEventKind::OptionChosen, Some("en")), &package());
+
+        assert_eq!(notifications.len(), 2);
+        assert_eq!(notifications[0].channel, NotificationChannel::Email);
+        assert_eq!(notifications[0].subject, Some("Delivery of order 1042".to_string()));
+        assert_eq!(
+            notifications[0].text,
+            "Your order 1042 will be delivered by UPS-avia in 3-7 days after dispatch.".to_string()
+        );
+        assert_eq!(notifications[1].channel, NotificationChannel::Push);
+        assert_eq!(notifications[1].subject, None);
+    }
+
+    #[test]
+    fn notifications_are_localized() {
+        let notifications = render(&event(ShipmentEventKind::DeliveryDelayed, Some("RU")), &package());
+
+        assert_eq!(notifications[1].text, "Заказ 1042 задерживается до 2019-03-25".to_string());
+    }
+
+    #[test]
+    fn unknown_language_falls_back_to_default() {
+        assert_eq!(
+            notification_lang(&event(ShipmentEventKind::ShipmentDispatched, Some("xx"))),
+            "en".to_string()
+        );
+        assert_eq!(
+            notification_lang(&event(ShipmentEventKind::ShipmentDispatched, None)),
+            "en".to_string()
+        );
+    }
+}
@@ -0,0 +1,145 @@
+//! Background cleanup of the delivery quotes.
+//!
+//! Expired quotes are kept for `retention_sec` after they expire, so that the orders service redeeming
+//! a quote late is answered that the quote has expired rather than that it does not exist. Quotes expired
+//! before that are purged every `cleanup_interval_sec`, the purged counts are reported by `GET /admin/stats`.
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use failure::Error as FailureError;
+use futures::{Future, Stream};
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+use tokio_core::reactor::{Handle, Interval};
+
+use clock::Clock;
+use repos::{DbConnection, DeliveryQuotesRepo, ReposFactory};
+
+/// Time the expired quotes are kept if it is not configured
+pub const DEFAULT_QUOTES_RETENTION_SEC: u64 = 86400;
+
+/// Counters of the cleanup runs since the start of the service
+#[derive(Default)]
+pub struct QuotesCleanupTracker {
+    runs: AtomicUsize,
+    failed_runs: AtomicUsize,
+    purged_quotes: AtomicUsize,
+    last_purged_quotes: AtomicUsize,
+}
+
+impl QuotesCleanupTracker {
+    pub fn record(&self, result: &Result<usize, FailureError>) {
+        self.runs.fetch_add(1, Ordering::SeqCst);
+        match *result {
+            Ok(purged) => {
+                self.purged_quotes.fetch_add(purged, Ordering::SeqCst);
+                self.last_purged_quotes.store(purged, Ordering::SeqCst);
+            }
+            Err(_) => {
+                self.failed_runs.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    pub fn stats(&self) -> QuotesCleanupStats {
+        QuotesCleanupStats {
+            runs: self.runs.load(Ordering::SeqCst),
+            failed_runs: self.failed_runs.load(Ordering::SeqCst),
+            purged_quotes: self.purged_quotes.load(Ordering::SeqCst),
+            last_purged_quotes: self.last_purged_quotes.load(Ordering::SeqCst),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct QuotesCleanupStats {
+    pub runs: usize,
+    pub failed_runs: usize,
+    pub purged_quotes: usize,
+    /// Quotes purged by the last successful run
+    pub last_purged_quotes: usize,
+}
+
+/// Purges quotes expired more than `retention` ago, returns the number of purged quotes
+pub fn purge_expired_quotes(quotes_repo: &dyn DeliveryQuotesRepo, now: SystemTime, retention: Duration) -> Result<usize, FailureError> {
+    let expired_before = now - retention;
+    quotes_repo.delete_expired(expired_before)
+}
+
+/// Purges the expired quotes on `cpu_pool` every `interval`
+#[allow(clippy::too_many_arguments)]
+pub fn schedule<T, M, F>(
+    handle: &Handle,
+    cpu_pool: CpuPool,
+    db_pool: Pool<M>,
+    repo_factory: F,
+    clock: Arc<dyn Clock>,
+    tracker: Arc<QuotesCleanupTracker>,
+    interval: Duration,
+    retention: Duration,
+) where
+    T: DbConnection,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+{
+    let interval = Interval::new(interval, handle).expect("Failed to create interval");
+
+    handle.spawn(
+        interval
+            .map_err(|e| error!("Delivery quotes cleanup schedule error: {}", e))
+            .for_each(move |_| {
+                let db_pool = db_pool.clone();
+                let repo_factory = repo_factory.clone();
+                let clock = clock.clone();
+                let tracker = tracker.clone();
+
+                cpu_pool.spawn_fn(move || {
+                    let result = db_pool.get().map_err(FailureError::from).and_then(|conn| {
+                        let quotes_repo = repo_factory.create_delivery_quotes_repo_with_sys_acl(&*conn);
+                        purge_expired_quotes(&*quotes_repo, SystemTime::from(clock.now()), retention)
+                    });
+
+                    match result {
+                        Ok(purged) => info!("Delivery quotes cleanup finished, purged quotes: {}", purged),
+                        Err(ref e) => error!("Delivery quotes cleanup failed: {}", e),
+                    }
+                    tracker.record(&result);
+
+                    Ok(())
+                })
+            }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use repos::repo_factory::tests::*;
+
+    use super::*;
+
+    #[test]
+    fn purged_quotes_are_counted() {
+        let quotes_repo = DeliveryQuotesRepoMock {
+            user_id: Some(MOCK_USER_ID),
+        };
+        let tracker = QuotesCleanupTracker::default();
+
+        tracker.record(&purge_expired_quotes(&quotes_repo, SystemTime::now(), Duration::from_secs(60)));
+        tracker.record(&purge_expired_quotes(
+            &DeliveryQuotesRepoMock { user_id: None },
+            SystemTime::now(),
+            Duration::from_secs(60),
+        ));
+
+        assert_eq!(
+            tracker.stats(),
+            QuotesCleanupStats {
+                runs: 2,
+                failed_runs: 1,
+                purged_quotes: 2,
+                last_purged_quotes: 2,
+            }
+        );
+    }
+}
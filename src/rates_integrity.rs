@@ -0,0 +1,195 @@
+//! Nightly integrity check of shipping rates.
+//!
+//! Active rates are scanned in batches ordered by id. Rates with impossible values (zero or negative prices)
+//! are deactivated, suspicious ones (prices going down with the weight, price cliffs) are only reported.
+//! Anomalies found by the last check are kept in `shipping_rates_anomalies` and served by
+//! `GET /shipping_rates/anomalies`. Deactivated rates are not scanned again, so their anomalies stay in
+//! the report with `deactivated: true` until the rates are replaced.
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use failure::Error as FailureError;
+use futures::{Future, Stream};
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+use tokio_core::reactor::{Handle, Interval};
+
+use config;
+use models::{find_shipping_rates_anomalies, ShippingRatesAnomaly};
+use repos::{DbConnection, ReposFactory, ShippingRatesAnomaliesRepo, ShippingRatesRepo};
+
+const DEFAULT_RUN_AT_HOUR_UTC: u32 = 3;
+const DEFAULT_PRICE_CLIFF_FACTOR: f64 = 3.0;
+const DEFAULT_BATCH_SIZE: i64 = 1000;
+const CHECK_INTERVAL_SEC: u64 = 24 * 60 * 60;
+
+#[derive(Clone, Debug)]
+pub struct RatesIntegritySettings {
+    pub run_at_hour_utc: u32,
+    pub price_cliff_factor: f64,
+    pub batch_size: i64,
+}
+
+impl Default for RatesIntegritySettings {
+    fn default() -> Self {
+        Self {
+            run_at_hour_utc: DEFAULT_RUN_AT_HOUR_UTC,
+            price_cliff_factor: DEFAULT_PRICE_CLIFF_FACTOR,
+            batch_size: DEFAULT_BATCH_SIZE,
+        }
+    }
+}
+
+impl<'a> From<&'a config::RatesIntegrity> for RatesIntegritySettings {
+    fn from(config: &'a config::RatesIntegrity) -> Self {
+        Self {
+            run_at_hour_utc: config.run_at_hour_utc % 24,
+            price_cliff_factor: config.price_cliff_factor,
+            batch_size: config.batch_size,
+        }
+    }
+}
+
+/// Scans all active shipping rates, deactivates the ones with impossible values
+/// and replaces the anomalies of active rates in the report. Returns the whole report
+pub fn check_shipping_rates<T>(
+    db_conn: &T,
+    shipping_rates_repo: &dyn ShippingRatesRepo,
+    anomalies_repo: &dyn ShippingRatesAnomaliesRepo,
+    settings: &RatesIntegritySettings,
+) -> Result<Vec<ShippingRatesAnomaly>, FailureError>
+where
+    T: DbConnection,
+{
+    let mut anomalies = vec![];
+    let mut after_id = None;
+    loop {
+        let batch = shipping_rates_repo.get_active_batch(after_id, settings.batch_size)?;
+        let last_id = match batch.last() {
+            Some(raw) => raw.id,
+            None => break,
+        };
+
+        for raw in batch {
+            match raw.parse_rates() {
+                Ok(rates) => anomalies.extend(find_shipping_rates_anomalies(raw.id, rates, settings.price_cliff_factor)),
+                Err(e) => warn!("Shipping rates {} skipped by integrity check: {}", raw.id, e),
+            }
+        }
+
+        after_id = Some(last_id);
+    }
+
+    let mut deactivated_ids = anomalies
+        .iter()
+        .filter(|anomaly| anomaly.deactivated)
+        .map(|anomaly| anomaly.shipping_rates_id)
+        .collect::<Vec<_>>();
+    deactivated_ids.dedup();
+
+    db_conn.transaction::<_, FailureError, _>(|| {
+        // Anomalies are replaced before the deactivation, while the scanned rates are still active
+        let report = anomalies_repo.replace_all(anomalies)?;
+
+        if !deactivated_ids.is_empty() {
+            let deactivated = shipping_rates_repo.deactivate(deactivated_ids.clone())?;
+            warn!(
+                "Shipping rates deactivated by integrity check: {} {:?}",
+                deactivated, deactivated_ids
+            );
+        }
+
+        Ok(report)
+    })
+}
+
+/// Time left until the next `hour` o'clock UTC
+pub fn duration_until_hour(now: DateTime<Utc>, hour: u32) -> Duration {
+    let today = now.date().and_hms(hour, 0, 0);
+    let next = if today > now { today } else { today + ChronoDuration::days(1) };
+
+    (next - now).to_std().unwrap_or_default()
+}
+
+/// Runs the check on `cpu_pool` every day at the configured hour
+pub fn schedule<T, M, F>(handle: &Handle, cpu_pool: CpuPool, db_pool: Pool<M>, repo_factory: F, settings: RatesIntegritySettings)
+where
+    T: DbConnection,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+{
+    let first_run = Instant::now() + duration_until_hour(Utc::now(), settings.run_at_hour_utc);
+    let interval = Interval::new_at(first_run, Duration::from_secs(CHECK_INTERVAL_SEC), handle).expect("Failed to create interval");
+
+    handle.spawn(
+        interval
+            .map_err(|e| error!("Shipping rates integrity check schedule error: {}", e))
+            .for_each(move |_| {
+                let db_pool = db_pool.clone();
+                let repo_factory = repo_factory.clone();
+                let settings = settings.clone();
+
+                cpu_pool.spawn_fn(move || {
+                    let result = db_pool.get().map_err(FailureError::from).and_then(|conn| {
+                        let shipping_rates_repo = repo_factory.create_shipping_rates_repo_with_sys_acl(&*conn);
+                        let anomalies_repo = repo_factory.create_shipping_rates_anomalies_repo_with_sys_acl(&*conn);
+                        check_shipping_rates(&*conn, &*shipping_rates_repo, &*anomalies_repo, &settings)
+                    });
+
+                    match result {
+                        Ok(anomalies) => info!("Shipping rates integrity check finished, anomalies: {}", anomalies.len()),
+                        Err(e) => error!("Shipping rates integrity check failed: {}", e),
+                    }
+
+                    Ok(())
+                })
+            }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use models::ShippingRatesAnomalyKind;
+    use repos::repo_factory::tests::*;
+    use stq_types::ShippingRatesId;
+
+    use super::*;
+
+    #[test]
+    fn check_shipping_rates_reports_anomalies() {
+        let db_conn = MockConnection::default();
+        let shipping_rates_repo = ShippingRatesRepoMock {
+            user_id: Some(MOCK_USER_ID),
+        };
+        let anomalies_repo = ShippingRatesAnomaliesRepoMock {
+            user_id: Some(MOCK_USER_ID),
+        };
+        let settings = RatesIntegritySettings {
+            batch_size: 2,
+            ..RatesIntegritySettings::default()
+        };
+
+        let anomalies = check_shipping_rates(&db_conn, &shipping_rates_repo, &anomalies_repo, &settings).unwrap();
+
+        assert_eq!(
+            anomalies
+                .iter()
+                .map(|anomaly| (anomaly.shipping_rates_id, anomaly.kind, anomaly.deactivated))
+                .collect::<Vec<_>>(),
+            vec![
+                (ShippingRatesId(2), ShippingRatesAnomalyKind::NonPositivePrice, true),
+                (ShippingRatesId(3), ShippingRatesAnomalyKind::PriceCliff, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn duration_until_hour_is_within_a_day() {
+        let now = Utc.ymd(2019, 3, 13).and_hms(2, 30, 0);
+
+        assert_eq!(duration_until_hour(now, 3), Duration::from_secs(30 * 60));
+        assert_eq!(duration_until_hour(now, 2), Duration::from_secs(23 * 60 * 60 + 30 * 60));
+    }
+}
@@ -13,18 +13,20 @@ use std::rc::Rc;
 
 use errors::Error;
 use failure::Error as FailureError;
+use request_context::log_line;
 
 use stq_types::{DeliveryRole, UserId};
 
 use self::legacy_acl::{Acl, CheckScope};
 
 use models::authorization::*;
+use models::ApiKeyScope;
 
 pub fn check<T>(
-    acl: &Acl<Resource, Action, Scope, FailureError, T>,
+    acl: &dyn Acl<Resource, Action, Scope, FailureError, T>,
     resource: Resource,
     action: Action,
-    scope_checker: &CheckScope<Scope, T>,
+    scope_checker: &dyn CheckScope<Scope, T>,
     obj: Option<&T>,
 ) -> Result<(), FailureError> {
     acl.allows(resource, action, scope_checker, obj).and_then(|allowed| {
@@ -47,60 +49,127 @@ pub struct ApplicationAcl {
 }
 
 impl ApplicationAcl {
+    /// ACL with the built-in permissions matrix
     pub fn new(roles: Vec<DeliveryRole>, user_id: UserId) -> Self {
-        let mut hash = ::std::collections::HashMap::new();
-
-        hash.insert(
-            DeliveryRole::Superuser,
-            vec![
-                permission!(Resource::Companies),
-                permission!(Resource::CompaniesPackages),
-                permission!(Resource::Countries),
-                permission!(Resource::Packages),
-                permission!(Resource::Pickups),
-                permission!(Resource::Products),
-                permission!(Resource::ShippingRates),
-                permission!(Resource::UserAddresses),
-                permission!(Resource::UserRoles),
-            ],
-        );
-
-        hash.insert(
-            DeliveryRole::User,
-            vec![
-                permission!(Resource::Companies, Action::Read),
-                permission!(Resource::CompaniesPackages, Action::Read),
-                permission!(Resource::Countries, Action::Read),
-                permission!(Resource::Packages, Action::Read),
-                permission!(Resource::Pickups, Action::Read),
-                permission!(Resource::Products, Action::Read),
-                permission!(Resource::ShippingRates, Action::Read),
-                permission!(Resource::UserAddresses, Action::All, Scope::Owned),
-                permission!(Resource::UserRoles, Action::Read, Scope::Owned),
-            ],
-        );
-
-        hash.insert(
-            DeliveryRole::StoreManager,
-            vec![
-                permission!(Resource::Pickups, Action::All, Scope::Owned),
-                permission!(Resource::Products, Action::All, Scope::Owned),
-            ],
-        );
+        Self::with_permissions(default_permissions(), roles, user_id)
+    }
 
+    /// ACL with the permissions matrix loaded from `role_permissions` table
+    pub fn with_permissions(permissions: HashMap<DeliveryRole, Vec<Permission>>, roles: Vec<DeliveryRole>, user_id: UserId) -> Self {
         ApplicationAcl {
-            acls: Rc::new(hash),
+            acls: Rc::new(permissions),
             roles,
             user_id,
         }
     }
 }
+
+/// Built-in permissions matrix, the ACL falls back to it when the matrix can not be loaded from DB.
+/// Initial content of `role_permissions` table is the same
+pub fn default_permissions() -> HashMap<DeliveryRole, Vec<Permission>> {
+    let mut hash = HashMap::new();
+
+    hash.insert(
+        DeliveryRole::Superuser,
+        vec![
+            permission!(Resource::AdminOverview),
+            permission!(Resource::ApiKeys),
+            permission!(Resource::AvailabilitySnapshots),
+            permission!(Resource::Backfills),
+            permission!(Resource::CarrierAccounts),
+            permission!(Resource::Companies),
+            permission!(Resource::CompaniesPackages),
+            permission!(Resource::CompanyRatesQuotas),
+            permission!(Resource::CompanySuspensions),
+            permission!(Resource::Countries),
+            permission!(Resource::CountryRegulations),
+            permission!(Resource::CustomsInfo),
+            permission!(Resource::DeliveryQuotes),
+            permission!(Resource::EventOutbox),
+            permission!(Resource::PackageTemplates),
+            permission!(Resource::Packages),
+            permission!(Resource::Permissions),
+            permission!(Resource::PickupPoints),
+            permission!(Resource::Pickups),
+            permission!(Resource::PricingRules),
+            permission!(Resource::Products),
+            permission!(Resource::ShipmentBookings),
+            permission!(Resource::ShipmentNotifications),
+            permission!(Resource::ShippingRates),
+            permission!(Resource::ShippingRatesAnomalies),
+            permission!(Resource::ShippingRestrictions),
+            permission!(Resource::ShippingTemplates),
+            permission!(Resource::StoreManagers),
+            permission!(Resource::StoreMargins),
+            permission!(Resource::StoreShippingSummaries),
+            permission!(Resource::Surcharges),
+            permission!(Resource::UserAddresses),
+            permission!(Resource::UserRoles),
+            permission!(Resource::Zones),
+        ],
+    );
+
+    hash.insert(
+        DeliveryRole::User,
+        vec![
+            permission!(Resource::Companies, Action::Read),
+            permission!(Resource::CompaniesPackages, Action::Read),
+            permission!(Resource::Countries, Action::Read),
+            permission!(Resource::CountryRegulations, Action::Read),
+            permission!(Resource::CustomsInfo, Action::Read),
+            permission!(Resource::DeliveryQuotes, Action::Create),
+            permission!(Resource::DeliveryQuotes, Action::Read),
+            permission!(Resource::PackageTemplates, Action::Read),
+            permission!(Resource::Packages, Action::Read),
+            permission!(Resource::PickupPoints, Action::Read),
+            permission!(Resource::Pickups, Action::Read),
+            permission!(Resource::Products, Action::Read),
+            permission!(Resource::ShippingRates, Action::Read),
+            permission!(Resource::ShippingRestrictions, Action::Read),
+            permission!(Resource::UserAddresses, Action::All, Scope::Owned),
+            permission!(Resource::UserRoles, Action::Read, Scope::Owned),
+            permission!(Resource::Zones, Action::Read),
+        ],
+    );
+
+    hash.insert(
+        DeliveryRole::StoreManager,
+        vec![
+            permission!(Resource::CarrierAccounts, Action::All, Scope::Owned),
+            permission!(Resource::Companies, Action::Update, Scope::Owned),
+            permission!(Resource::CustomsInfo, Action::All, Scope::Owned),
+            permission!(Resource::Pickups, Action::All, Scope::Owned),
+            permission!(Resource::Products, Action::All, Scope::Owned),
+            permission!(Resource::ShippingTemplates, Action::All, Scope::Owned),
+            permission!(Resource::StoreManagers, Action::All, Scope::Owned),
+            permission!(Resource::StoreMargins, Action::All, Scope::Owned),
+            permission!(Resource::StoreShippingSummaries, Action::Read, Scope::Owned),
+        ],
+    );
+
+    add_test_fixtures_permission(&mut hash);
+    hash
+}
+
+/// Test fixtures endpoints are built only with `test-endpoints` feature, so their permission is granted
+/// to the superusers by such builds only and is not kept in `role_permissions` table
+#[cfg(feature = "test-endpoints")]
+pub fn add_test_fixtures_permission(permissions: &mut HashMap<DeliveryRole, Vec<Permission>>) {
+    permissions
+        .entry(DeliveryRole::Superuser)
+        .or_insert_with(Vec::new)
+        .push(permission!(Resource::TestFixtures));
+}
+
+#[cfg(not(feature = "test-endpoints"))]
+pub fn add_test_fixtures_permission(_permissions: &mut HashMap<DeliveryRole, Vec<Permission>>) {}
+
 impl<T> Acl<Resource, Action, Scope, FailureError, T> for ApplicationAcl {
     fn allows(
         &self,
         resource: Resource,
         action: Action,
-        scope_checker: &CheckScope<Scope, T>,
+        scope_checker: &dyn CheckScope<Scope, T>,
         obj: Option<&T>,
     ) -> Result<bool, FailureError> {
         let empty: Vec<Permission> = Vec::new();
@@ -115,7 +184,10 @@ impl<T> Acl<Resource, Action, Scope, FailureError, T> for ApplicationAcl {
         if acls.count() > 0 {
             Ok(true)
         } else {
-            error!("Denied request from user {} to do {} on {}.", user_id, action, resource);
+            error!(
+                "{}",
+                log_line(&format!("Denied request from user {} to do {} on {}.", user_id, action, resource))
+            );
             Ok(false)
         }
     }
@@ -130,7 +202,7 @@ impl<T> Acl<Resource, Action, Scope, FailureError, T> for UnauthorizedAcl {
         &self,
         resource: Resource,
         action: Action,
-        _scope_checker: &CheckScope<Scope, T>,
+        _scope_checker: &dyn CheckScope<Scope, T>,
         _obj: Option<&T>,
     ) -> Result<bool, FailureError> {
         if action == Action::Read {
@@ -138,13 +210,54 @@ impl<T> Acl<Resource, Action, Scope, FailureError, T> for UnauthorizedAcl {
                 Resource::Companies => Ok(true),
                 Resource::CompaniesPackages => Ok(true),
                 Resource::Countries => Ok(true),
+                Resource::CountryRegulations => Ok(true),
+                Resource::CustomsInfo => Ok(true),
+                Resource::PackageTemplates => Ok(true),
                 Resource::Packages => Ok(true),
+                Resource::PickupPoints => Ok(true),
                 Resource::Pickups => Ok(true),
                 Resource::Products => Ok(true),
+                Resource::ShippingRestrictions => Ok(true),
+                Resource::Zones => Ok(true),
                 _ => Ok(false),
             }
         } else {
-            error!("Denied unauthorized request to do {} on {}.", action, resource);
+            error!(
+                "{}",
+                log_line(&format!("Denied unauthorized request to do {} on {}.", action, resource))
+            );
+            Ok(false)
+        }
+    }
+}
+
+/// ApiKeyAcl allows the internal callers only the actions in the scopes of their key
+#[derive(Clone)]
+pub struct ApiKeyAcl {
+    scopes: Vec<ApiKeyScope>,
+}
+
+impl ApiKeyAcl {
+    pub fn new(scopes: Vec<ApiKeyScope>) -> Self {
+        Self { scopes }
+    }
+}
+
+impl<T> Acl<Resource, Action, Scope, FailureError, T> for ApiKeyAcl {
+    fn allows(
+        &self,
+        resource: Resource,
+        action: Action,
+        _scope_checker: &dyn CheckScope<Scope, T>,
+        _obj: Option<&T>,
+    ) -> Result<bool, FailureError> {
+        if self.scopes.iter().any(|scope| scope.allows(resource, action)) {
+            Ok(true)
+        } else {
+            error!(
+                "{}",
+                log_line(&format!("Denied request with api key to do {} on {}.", action, resource))
+            );
             Ok(false)
         }
     }
@@ -152,5 +265,106 @@ impl<T> Acl<Resource, Action, Scope, FailureError, T> for UnauthorizedAcl {
 
 #[cfg(test)]
 mod tests {
-    // write tests
+    use stq_types::{Alpha2, Alpha3, CountryLabel, StoreId};
+
+    use super::*;
+    use models::roles::{manages_company, manages_product};
+    use models::{Country, RoleData};
+
+    struct AnyScope;
+
+    impl CheckScope<Scope, ()> for AnyScope {
+        fn is_in_scope(&self, _user_id: UserId, _scope: &Scope, _obj: Option<&()>) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn api_key_acl_allows_actions_in_scopes_only() {
+        let acl = ApiKeyAcl::new(vec![
+            ApiKeyScope {
+                resource: Resource::Products,
+                action: Action::All,
+            },
+            ApiKeyScope {
+                resource: Resource::Companies,
+                action: Action::Read,
+            },
+        ]);
+        let allows = |resource, action| Acl::<_, _, _, _, ()>::allows(&acl, resource, action, &AnyScope, None).unwrap();
+
+        assert!(allows(Resource::Products, Action::Update));
+        assert!(allows(Resource::Companies, Action::Read));
+        assert!(!allows(Resource::Companies, Action::Update));
+        assert!(!allows(Resource::UserRoles, Action::Read));
+    }
+
+    /// Scope of the object decided by the ownership rules of the repos
+    struct OwnedScope(bool);
+
+    impl CheckScope<Scope, ()> for OwnedScope {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&()>) -> bool {
+            match *scope {
+                Scope::All => true,
+                Scope::Owned => self.0,
+            }
+        }
+    }
+
+    #[test]
+    fn country_manager_manages_its_companies_and_their_products_only() {
+        let acl = ApplicationAcl::new(vec![DeliveryRole::StoreManager], UserId(1));
+        let allows = |resource, action, owned| Acl::<_, _, _, _, ()>::allows(&acl, resource, action, &OwnedScope(owned), None).unwrap();
+        let country_manager = vec![RoleData::CountryManager {
+            alpha3: Alpha3("DEU".to_string()),
+        }];
+        let germany = vec![Country {
+            label: CountryLabel("germany".to_string()),
+            level: Country::COUNTRY_LEVEL,
+            alpha2: Alpha2("DE".to_string()),
+            alpha3: Alpha3("DEU".to_string()),
+            ..Country::default()
+        }];
+
+        let manages_german_company = manages_company(&country_manager, &germany);
+        assert!(allows(Resource::Companies, Action::Update, manages_german_company));
+        assert!(!allows(Resource::Companies, Action::Update, manages_company(&country_manager, &[])));
+        assert!(!allows(Resource::Companies, Action::Create, manages_german_company));
+        assert!(!allows(Resource::Companies, Action::Delete, manages_german_company));
+
+        let owns_product = manages_product(&country_manager, StoreId(7), &germany);
+        assert!(allows(Resource::Products, Action::Update, owns_product));
+        let owns_other_product = manages_product(&country_manager, StoreId(7), &[]);
+        assert!(!allows(Resource::Products, Action::Update, owns_other_product));
+
+        // the store of the products is not managed by the country manager
+        let manages_store = country_manager.iter().any(|role_data| role_data.manages_store(StoreId(7)));
+        assert!(!allows(Resource::StoreMargins, Action::Update, manages_store));
+        let manages_store_shipping = country_manager.iter().any(|role_data| role_data.manages_store_shipping(StoreId(7)));
+        assert!(!allows(Resource::Pickups, Action::Update, manages_store_shipping));
+        assert!(!allows(Resource::CarrierAccounts, Action::Read, manages_store));
+        assert!(!allows(Resource::ShippingRates, Action::Update, true));
+    }
+
+    #[test]
+    fn moderate_and_publish_are_granted_apart_from_other_actions() {
+        let mut permissions = HashMap::new();
+        permissions.insert(
+            DeliveryRole::StoreManager,
+            vec![
+                permission!(Resource::CompanySuspensions, Action::Moderate),
+                permission!(Resource::ShippingRates, Action::Publish),
+            ],
+        );
+        let acl = ApplicationAcl::with_permissions(permissions, vec![DeliveryRole::StoreManager], UserId(1));
+        let allows = |resource, action| Acl::<_, _, _, _, ()>::allows(&acl, resource, action, &AnyScope, None).unwrap();
+
+        assert!(allows(Resource::CompanySuspensions, Action::Moderate));
+        assert!(!allows(Resource::CompanySuspensions, Action::Delete));
+        assert!(allows(Resource::ShippingRates, Action::Publish));
+        assert!(!allows(Resource::ShippingRates, Action::Update));
+
+        let superuser = ApplicationAcl::new(vec![DeliveryRole::Superuser], UserId(1));
+        assert!(Acl::<_, _, _, _, ()>::allows(&superuser, Resource::CompanySuspensions, Action::Moderate, &AnyScope, None).unwrap());
+    }
 }
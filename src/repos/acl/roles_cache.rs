@@ -1,6 +1,7 @@
 //! RolesCache is a module that caches received from db information about user and his roles
 
 use failure::Fail;
+use request_context::log_line;
 use stq_cache::cache::Cache;
 use stq_types::{DeliveryRole, UserId};
 
@@ -20,31 +21,31 @@ where
     }
 
     pub fn get(&self, user_id: UserId) -> Option<Vec<DeliveryRole>> {
-        debug!("Getting roles from RolesCache at key '{}'", user_id);
+        debug!("{}", log_line(&format!("Getting roles from RolesCache at key '{}'", user_id)));
 
         self.cache.get(user_id.to_string().as_str()).unwrap_or_else(|err| {
             let err = err.context(format!("Failed to get roles from RolesCache at key '{}'", user_id));
-            error!("{}", err);
+            error!("{}", log_line(&err.to_string()));
             None
         })
     }
 
     pub fn remove(&self, user_id: UserId) -> bool {
-        debug!("Removing roles from RolesCache at key '{}'", user_id);
+        debug!("{}", log_line(&format!("Removing roles from RolesCache at key '{}'", user_id)));
 
         self.cache.remove(user_id.to_string().as_str()).unwrap_or_else(|err| {
             let err = err.context(format!("Failed to remove roles from RolesCache at key '{}'", user_id));
-            error!("{}", err);
+            error!("{}", log_line(&err.to_string()));
             false
         })
     }
 
     pub fn set(&self, user_id: UserId, roles: Vec<DeliveryRole>) {
-        debug!("Setting roles in RolesCache at key '{}'", user_id);
+        debug!("{}", log_line(&format!("Setting roles in RolesCache at key '{}'", user_id)));
 
         self.cache.set(user_id.to_string().as_str(), roles).unwrap_or_else(|err| {
             let err = err.context(format!("Failed to set roles in RolesCache at key '{}'", user_id));
-            error!("{}", err);
+            error!("{}", log_line(&err.to_string()));
         })
     }
 }
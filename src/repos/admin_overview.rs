@@ -0,0 +1,85 @@
+//! Repo for the overview of the shipping data on the admin dashboard. All the counts are taken by a single query
+
+use diesel;
+use diesel::query_dsl::RunQueryDsl;
+use errors::Error;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId;
+
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::types::{DbConnection, RepoResult};
+use models::authorization::*;
+use models::{AdminOverview, AdminOverviewRaw};
+
+/// Counts of the tables, base products are counted by the countries they are delivered to
+const ADMIN_OVERVIEW_QUERY: &str = "
+    SELECT
+        (SELECT COUNT(*) FROM companies) AS companies_count,
+        (SELECT COUNT(*) FROM packages) AS packages_count,
+        (SELECT COUNT(*) FROM companies_packages) AS companies_packages_count,
+        (SELECT COUNT(*) FROM shipping_rates) AS shipping_rates_count,
+        COALESCE(
+            (
+                SELECT jsonb_agg(jsonb_build_object('alpha3', destinations.alpha3, 'products_count', destinations.products_count))
+                FROM (
+                    SELECT deliveries_to.alpha3, COUNT(DISTINCT products.base_product_id) AS products_count
+                    FROM products, jsonb_array_elements_text(products.deliveries_to) AS deliveries_to(alpha3)
+                    GROUP BY deliveries_to.alpha3
+                ) AS destinations
+            ),
+            '[]'::jsonb
+        ) AS products_by_country
+";
+
+/// Admin overview repository
+pub trait AdminOverviewRepo {
+    /// Returns overview of the shipping data of all companies and stores
+    fn get(&self) -> RepoResult<AdminOverview>;
+
+    /// Checks that the runtime internals of the service may be shown to the caller
+    fn check_diagnostics_access(&self) -> RepoResult<()>;
+}
+
+/// Implementation of AdminOverview trait
+pub struct AdminOverviewRepoImpl<'a, T: DbConnection> {
+    pub db_conn: &'a T,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, ()>>,
+}
+
+impl<'a, T: DbConnection> AdminOverviewRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, ()>>) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: DbConnection> AdminOverviewRepo for AdminOverviewRepoImpl<'a, T> {
+    /// Returns overview of the shipping data of all companies and stores
+    fn get(&self) -> RepoResult<AdminOverview> {
+        acl::check(&*self.acl, Resource::AdminOverview, Action::Read, self, None)?;
+
+        let query = diesel::sql_query(ADMIN_OVERVIEW_QUERY);
+
+        query
+            .get_result::<AdminOverviewRaw>(self.db_conn)
+            .map(AdminOverview::from)
+            .map_err(|e| Error::from(e).context("Get admin overview error occurred").into())
+    }
+
+    /// Checks that the runtime internals of the service may be shown to the caller
+    fn check_diagnostics_access(&self) -> RepoResult<()> {
+        acl::check(&*self.acl, Resource::AdminOverview, Action::Read, self, None)
+    }
+}
+
+impl<'a, T: DbConnection> CheckScope<Scope, ()> for AdminOverviewRepoImpl<'a, T> {
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&()>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
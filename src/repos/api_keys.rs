@@ -0,0 +1,122 @@
+//! Repo for api_keys table. Keys are looked up by their hash, revoked keys are kept with the time of the revocation
+
+use diesel;
+use diesel::dsl::now;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use errors::Error;
+use failure::Error as FailureError;
+
+use stq_types::UserId;
+
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::types::{DbConnection, RepoResult};
+use models::authorization::*;
+use models::{ApiKey, ApiKeyRaw, InsertApiKey};
+use schema::api_keys::dsl as DslApiKeys;
+
+/// Api keys repository
+pub trait ApiKeysRepo {
+    /// Stores the issued key
+    fn create(&self, payload: InsertApiKey) -> RepoResult<ApiKey>;
+
+    /// Returns all keys including the revoked ones
+    fn list(&self) -> RepoResult<Vec<ApiKey>>;
+
+    /// Returns the key with the hash unless it is revoked
+    fn find_active_by_hash(&self, key_hash: &str) -> RepoResult<Option<ApiKey>>;
+
+    /// Revokes the active key, `None` if there is no such key
+    fn revoke(&self, id: i32) -> RepoResult<Option<ApiKey>>;
+}
+
+/// Implementation of ApiKeys trait
+pub struct ApiKeysRepoImpl<'a, T: DbConnection> {
+    pub db_conn: &'a T,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, ApiKey>>,
+}
+
+impl<'a, T: DbConnection> ApiKeysRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, ApiKey>>) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: DbConnection> ApiKeysRepo for ApiKeysRepoImpl<'a, T> {
+    /// Stores the issued key
+    fn create(&self, payload: InsertApiKey) -> RepoResult<ApiKey> {
+        acl::check(&*self.acl, Resource::ApiKeys, Action::Create, self, None)?;
+
+        let name = payload.name.clone();
+        let query = diesel::insert_into(DslApiKeys::api_keys).values(&payload);
+
+        query
+            .get_result::<ApiKeyRaw>(self.db_conn)
+            .map_err(|e| Error::from(e).into())
+            .and_then(ApiKeyRaw::to_model)
+            .map_err(|e: FailureError| e.context(format!("Create api key {} error occurred", name)).into())
+    }
+
+    /// Returns all keys including the revoked ones
+    fn list(&self) -> RepoResult<Vec<ApiKey>> {
+        acl::check(&*self.acl, Resource::ApiKeys, Action::Read, self, None)?;
+
+        let query = DslApiKeys::api_keys.order(DslApiKeys::id);
+
+        query
+            .get_results::<ApiKeyRaw>(self.db_conn)
+            .map_err(|e| Error::from(e).into())
+            .and_then(|records| records.into_iter().map(ApiKeyRaw::to_model).collect::<RepoResult<Vec<_>>>())
+            .map_err(|e: FailureError| e.context("list of api keys error occurred").into())
+    }
+
+    /// Returns the key with the hash unless it is revoked
+    fn find_active_by_hash(&self, key_hash: &str) -> RepoResult<Option<ApiKey>> {
+        acl::check(&*self.acl, Resource::ApiKeys, Action::Read, self, None)?;
+
+        let query = DslApiKeys::api_keys
+            .filter(DslApiKeys::key_hash.eq(key_hash))
+            .filter(DslApiKeys::revoked_at.is_null());
+
+        query
+            .get_result::<ApiKeyRaw>(self.db_conn)
+            .optional()
+            .map_err(|e| Error::from(e).into())
+            .and_then(|record| match record {
+                Some(record) => record.to_model().map(Some),
+                None => Ok(None),
+            })
+            .map_err(|e: FailureError| e.context("Find api key by hash error occurred").into())
+    }
+
+    /// Revokes the active key, `None` if there is no such key
+    fn revoke(&self, id: i32) -> RepoResult<Option<ApiKey>> {
+        acl::check(&*self.acl, Resource::ApiKeys, Action::Delete, self, None)?;
+
+        let filtered = DslApiKeys::api_keys
+            .filter(DslApiKeys::id.eq(id))
+            .filter(DslApiKeys::revoked_at.is_null());
+        let query = diesel::update(filtered).set(DslApiKeys::revoked_at.eq(now.nullable()));
+
+        query
+            .get_result::<ApiKeyRaw>(self.db_conn)
+            .optional()
+            .map_err(|e| Error::from(e).into())
+            .and_then(|record| match record {
+                Some(record) => record.to_model().map(Some),
+                None => Ok(None),
+            })
+            .map_err(|e: FailureError| e.context(format!("Revoke api key {} error occurred", id)).into())
+    }
+}
+
+impl<'a, T: DbConnection> CheckScope<Scope, ApiKey> for ApiKeysRepoImpl<'a, T> {
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&ApiKey>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
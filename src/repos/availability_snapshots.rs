@@ -0,0 +1,101 @@
+//! Repo for availability_snapshots table. Keeps the last snapshot of the availability results
+//! of every sampled route taken by the coverage regressions check
+
+use diesel;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use errors::Error;
+use failure::Error as FailureError;
+
+use stq_types::{Alpha3, BaseProductId, UserId};
+
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::types::{DbConnection, RepoResult};
+use models::authorization::*;
+use models::{AvailabilitySnapshot, AvailabilitySnapshotRaw, NewAvailabilitySnapshot};
+use schema::availability_snapshots::dsl as DslAvailabilitySnapshots;
+
+/// Availability snapshots repository
+pub trait AvailabilitySnapshotsRepo {
+    /// Returns the last snapshot of the route
+    fn get(&self, base_product_id: BaseProductId, delivery_to: Alpha3) -> RepoResult<Option<AvailabilitySnapshot>>;
+
+    /// Replaces the last snapshot of the route with the new one
+    fn save(&self, snapshot: NewAvailabilitySnapshot) -> RepoResult<AvailabilitySnapshot>;
+}
+
+/// Implementation of AvailabilitySnapshots trait
+pub struct AvailabilitySnapshotsRepoImpl<'a, T: DbConnection> {
+    pub db_conn: &'a T,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, AvailabilitySnapshot>>,
+}
+
+impl<'a, T: DbConnection> AvailabilitySnapshotsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, AvailabilitySnapshot>>) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: DbConnection> AvailabilitySnapshotsRepo for AvailabilitySnapshotsRepoImpl<'a, T> {
+    /// Returns the last snapshot of the route
+    fn get(&self, base_product_id: BaseProductId, delivery_to: Alpha3) -> RepoResult<Option<AvailabilitySnapshot>> {
+        acl::check(&*self.acl, Resource::AvailabilitySnapshots, Action::Read, self, None)?;
+
+        let query = DslAvailabilitySnapshots::availability_snapshots
+            .filter(DslAvailabilitySnapshots::base_product_id.eq(base_product_id))
+            .filter(DslAvailabilitySnapshots::delivery_to.eq(delivery_to.clone()));
+
+        query
+            .get_result::<AvailabilitySnapshotRaw>(self.db_conn)
+            .optional()
+            .map_err(|e| FailureError::from(Error::from(e)))
+            .and_then(|raw| match raw {
+                Some(raw) => raw.to_model().map(Some),
+                None => Ok(None),
+            })
+            .map_err(|e| {
+                e.context(format!(
+                    "Get availability snapshot of base product {} to {} error occurred",
+                    base_product_id, delivery_to
+                ))
+                .into()
+            })
+    }
+
+    /// Replaces the last snapshot of the route with the new one
+    fn save(&self, snapshot: NewAvailabilitySnapshot) -> RepoResult<AvailabilitySnapshot> {
+        acl::check(&*self.acl, Resource::AvailabilitySnapshots, Action::Update, self, None)?;
+
+        let query = diesel::insert_into(DslAvailabilitySnapshots::availability_snapshots)
+            .values(&snapshot)
+            .on_conflict((DslAvailabilitySnapshots::base_product_id, DslAvailabilitySnapshots::delivery_to))
+            .do_update()
+            .set((
+                DslAvailabilitySnapshots::packages.eq(snapshot.packages.clone()),
+                DslAvailabilitySnapshots::taken_at.eq(diesel::dsl::now),
+            ));
+
+        query
+            .get_result::<AvailabilitySnapshotRaw>(self.db_conn)
+            .map_err(|e| FailureError::from(Error::from(e)))
+            .and_then(AvailabilitySnapshotRaw::to_model)
+            .map_err(|e| {
+                e.context(format!(
+                    "Save availability snapshot of base product {} to {} error occurred",
+                    snapshot.base_product_id, snapshot.delivery_to
+                ))
+                .into()
+            })
+    }
+}
+
+impl<'a, T: DbConnection> CheckScope<Scope, AvailabilitySnapshot> for AvailabilitySnapshotsRepoImpl<'a, T> {
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&AvailabilitySnapshot>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
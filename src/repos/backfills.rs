@@ -0,0 +1,164 @@
+//! Repo for backfills table. Every row keeps the progress of one batched backfill,
+//! so that it can be monitored and resumed after a restart
+
+use diesel;
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::sql_types::BigInt;
+use errors::Error;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId;
+
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::types::{DbConnection, RepoResult};
+use models::authorization::*;
+use models::{BackfillProgress, BackfillStatus, NewBackfillProgress};
+use schema::backfills::dsl as DslBackfills;
+
+/// Backfills repository for handling progress of backfills
+pub trait BackfillsRepo {
+    /// Returns list of backfills
+    fn list(&self) -> RepoResult<Vec<BackfillProgress>>;
+
+    /// Find specific backfill by name
+    fn find(&self, name: String) -> RepoResult<Option<BackfillProgress>>;
+
+    /// Starts a new backfill or resumes the existing one from the last processed row.
+    /// `rows_left` is added to the rows already processed, so that the resumed backfill is not reported past its end
+    fn start(&self, name: String, rows_left: i64) -> RepoResult<BackfillProgress>;
+
+    /// Records a processed batch, the total is raised if the batches process more rows than were counted at the start
+    fn record_batch(&self, name: String, processed_rows: i64, last_processed_id: i32) -> RepoResult<BackfillProgress>;
+
+    /// Marks backfill as finished
+    fn finish(&self, name: String) -> RepoResult<BackfillProgress>;
+
+    /// Marks backfill as failed with the error
+    fn fail(&self, name: String, error: String) -> RepoResult<BackfillProgress>;
+}
+
+/// Implementation of Backfills trait
+pub struct BackfillsRepoImpl<'a, T: DbConnection> {
+    pub db_conn: &'a T,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, BackfillProgress>>,
+}
+
+impl<'a, T: DbConnection> BackfillsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, BackfillProgress>>) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: DbConnection> BackfillsRepo for BackfillsRepoImpl<'a, T> {
+    /// Returns list of backfills
+    fn list(&self) -> RepoResult<Vec<BackfillProgress>> {
+        acl::check(&*self.acl, Resource::Backfills, Action::Read, self, None)?;
+
+        let query = DslBackfills::backfills.order(DslBackfills::created_at.desc());
+
+        query
+            .get_results::<BackfillProgress>(self.db_conn)
+            .map_err(|e| Error::from(e).context("list of backfills error occurred").into())
+    }
+
+    /// Find specific backfill by name
+    fn find(&self, name: String) -> RepoResult<Option<BackfillProgress>> {
+        acl::check(&*self.acl, Resource::Backfills, Action::Read, self, None)?;
+
+        let query = DslBackfills::backfills.find(name.clone());
+
+        query
+            .get_result::<BackfillProgress>(self.db_conn)
+            .optional()
+            .map_err(|e| Error::from(e).context(format!("Find backfill {} error occurred", name)).into())
+    }
+
+    /// Starts a new backfill or resumes the existing one from the last processed row.
+    /// `rows_left` is added to the rows already processed, so that the resumed backfill is not reported past its end
+    fn start(&self, name: String, rows_left: i64) -> RepoResult<BackfillProgress> {
+        acl::check(&*self.acl, Resource::Backfills, Action::Create, self, None)?;
+
+        let record = NewBackfillProgress {
+            name: name.clone(),
+            status: BackfillStatus::Running,
+            total_rows: rows_left,
+        };
+
+        let query = diesel::insert_into(DslBackfills::backfills)
+            .values(&record)
+            .on_conflict(DslBackfills::name)
+            .do_update()
+            .set((
+                DslBackfills::status.eq(BackfillStatus::Running),
+                DslBackfills::total_rows.eq(DslBackfills::processed_rows + rows_left),
+                DslBackfills::error.eq(None::<String>),
+            ));
+
+        query
+            .get_result::<BackfillProgress>(self.db_conn)
+            .map_err(|e| Error::from(e).context(format!("Start backfill {} error occurred", name)).into())
+    }
+
+    /// Records a processed batch, the total is raised if the batches process more rows than were counted at the start
+    fn record_batch(&self, name: String, processed_rows: i64, last_processed_id: i32) -> RepoResult<BackfillProgress> {
+        acl::check(&*self.acl, Resource::Backfills, Action::Update, self, None)?;
+
+        let filter = DslBackfills::backfills.filter(DslBackfills::name.eq(name.clone()));
+        // rows inserted after the start are counted by the batches too
+        let total_rows = sql::<BigInt>("GREATEST(total_rows, processed_rows + ")
+            .bind::<BigInt, _>(processed_rows)
+            .sql(")");
+        let query = diesel::update(filter).set((
+            DslBackfills::total_rows.eq(total_rows),
+            DslBackfills::processed_rows.eq(DslBackfills::processed_rows + processed_rows),
+            DslBackfills::last_processed_id.eq(Some(last_processed_id)),
+        ));
+
+        query.get_result::<BackfillProgress>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!(
+                    "Record batch of backfill {} up to id {} error occurred",
+                    name, last_processed_id
+                ))
+                .into()
+        })
+    }
+
+    /// Marks backfill as finished
+    fn finish(&self, name: String) -> RepoResult<BackfillProgress> {
+        acl::check(&*self.acl, Resource::Backfills, Action::Update, self, None)?;
+
+        let filter = DslBackfills::backfills.filter(DslBackfills::name.eq(name.clone()));
+        let query = diesel::update(filter).set(DslBackfills::status.eq(BackfillStatus::Finished));
+
+        query
+            .get_result::<BackfillProgress>(self.db_conn)
+            .map_err(|e| Error::from(e).context(format!("Finish backfill {} error occurred", name)).into())
+    }
+
+    /// Marks backfill as failed with the error
+    fn fail(&self, name: String, error: String) -> RepoResult<BackfillProgress> {
+        acl::check(&*self.acl, Resource::Backfills, Action::Update, self, None)?;
+
+        let filter = DslBackfills::backfills.filter(DslBackfills::name.eq(name.clone()));
+        let query = diesel::update(filter).set((DslBackfills::status.eq(BackfillStatus::Failed), DslBackfills::error.eq(Some(error))));
+
+        query
+            .get_result::<BackfillProgress>(self.db_conn)
+            .map_err(|e| Error::from(e).context(format!("Fail backfill {} error occurred", name)).into())
+    }
+}
+
+impl<'a, T: DbConnection> CheckScope<Scope, BackfillProgress> for BackfillsRepoImpl<'a, T> {
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&BackfillProgress>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
@@ -0,0 +1,309 @@
+//! Repo for carrier_accounts table. Accounts of the stores with the carriers, a store has at most
+//! one account with a carrier. Account numbers and credentials are encrypted by the repo, they are never
+//! stored in plain text
+
+use std::sync::Arc;
+
+use diesel;
+use diesel::dsl::now;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use errors::Error;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::{CompanyId, StoreId, UserId};
+
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::types::{DbConnection, RepoResult};
+use super::user_roles::get_user_roles_data;
+use credentials::CredentialsCipher;
+use models::authorization::*;
+use models::{CarrierAccount, CarrierAccountChangeset, CreateCarrierAccount, DbCarrierAccount, NewCarrierAccount, UpdateCarrierAccount};
+use schema::carrier_accounts::dsl as DslCarrierAccounts;
+
+/// Carrier accounts repository, ACL objects are the stores the accounts belong to
+pub trait CarrierAccountsRepo {
+    /// Returns accounts of the store
+    fn list(&self, store_id: StoreId) -> RepoResult<Vec<CarrierAccount>>;
+
+    /// Find specific account of the store
+    fn find(&self, store_id: StoreId, account_id: i32) -> RepoResult<Option<CarrierAccount>>;
+
+    /// Returns account of the store with the carrier
+    fn find_by_company(&self, store_id: StoreId, company_id: CompanyId) -> RepoResult<Option<CarrierAccount>>;
+
+    /// Returns account of the store with the carrier if the user manages the store, `None` otherwise
+    fn find_usable_by_company(&self, store_id: StoreId, company_id: CompanyId) -> RepoResult<Option<CarrierAccount>>;
+
+    /// Create a new account, account number and credentials are encrypted
+    fn create(&self, store_id: StoreId, payload: CreateCarrierAccount) -> RepoResult<CarrierAccount>;
+
+    /// Update account of the store, account number and credentials are encrypted
+    fn update(&self, store_id: StoreId, account_id: i32, payload: UpdateCarrierAccount) -> RepoResult<Option<CarrierAccount>>;
+
+    /// Delete account of the store
+    fn delete(&self, store_id: StoreId, account_id: i32) -> RepoResult<Option<CarrierAccount>>;
+
+    /// Returns decrypted credentials of the account
+    fn decrypt_credentials(&self, account: &CarrierAccount) -> RepoResult<String>;
+
+    /// Returns the number of accounts of all stores
+    fn count_all(&self) -> RepoResult<i64>;
+
+    /// Returns at most `batch_size` accounts of all stores with id greater than `after_id`, ordered by id
+    fn get_batch(&self, after_id: Option<i32>, batch_size: i64) -> RepoResult<Vec<CarrierAccount>>;
+
+    /// Encrypts account number and credentials of the account with the current key, returns `false` if they are already encrypted with it
+    fn reencrypt(&self, account: &CarrierAccount) -> RepoResult<bool>;
+
+    /// Returns fingerprint of the current encryption key
+    fn key_fingerprint(&self) -> RepoResult<String>;
+}
+
+/// Implementation of CarrierAccounts trait
+pub struct CarrierAccountsRepoImpl<'a, T: DbConnection> {
+    pub db_conn: &'a T,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, StoreId>>,
+    pub cipher: Arc<CredentialsCipher>,
+}
+
+impl<'a, T: DbConnection> CarrierAccountsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, StoreId>>, cipher: Arc<CredentialsCipher>) -> Self {
+        Self { db_conn, acl, cipher }
+    }
+
+    fn to_model(&self, account: DbCarrierAccount) -> RepoResult<CarrierAccount> {
+        let account_number = self
+            .cipher
+            .decrypt(&account.encrypted_account_number)
+            .map_err(|e| e.context(format!("Decrypt account number of carrier account {} error occurred", account.id)))?;
+
+        Ok(CarrierAccount {
+            id: account.id,
+            store_id: account.store_id,
+            company_id: account.company_id,
+            account_number,
+            encrypted_account_number: account.encrypted_account_number,
+            encrypted_credentials: account.encrypted_credentials,
+            created_at: account.created_at,
+            updated_at: account.updated_at,
+        })
+    }
+
+    fn to_optional_model(&self, account: Option<DbCarrierAccount>) -> RepoResult<Option<CarrierAccount>> {
+        match account {
+            Some(account) => self.to_model(account).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn update_encrypted(&self, store_id: StoreId, account_id: i32, payload: CarrierAccountChangeset) -> RepoResult<Option<CarrierAccount>> {
+        let filtered = DslCarrierAccounts::carrier_accounts
+            .filter(DslCarrierAccounts::store_id.eq(store_id))
+            .filter(DslCarrierAccounts::id.eq(account_id));
+        let query = diesel::update(filtered).set((&payload, DslCarrierAccounts::updated_at.eq(now)));
+
+        let account = query.get_result::<DbCarrierAccount>(self.db_conn).optional().map_err(|e| {
+            Error::from(e).context(format!(
+                "Update carrier account {} of store {} error occurred",
+                account_id, store_id
+            ))
+        })?;
+        self.to_optional_model(account)
+    }
+}
+
+impl<'a, T: DbConnection> CarrierAccountsRepo for CarrierAccountsRepoImpl<'a, T> {
+    /// Returns accounts of the store
+    fn list(&self, store_id: StoreId) -> RepoResult<Vec<CarrierAccount>> {
+        acl::check(&*self.acl, Resource::CarrierAccounts, Action::Read, self, Some(&store_id))?;
+
+        let query = DslCarrierAccounts::carrier_accounts
+            .filter(DslCarrierAccounts::store_id.eq(store_id))
+            .order(DslCarrierAccounts::id);
+
+        let accounts = query
+            .get_results::<DbCarrierAccount>(self.db_conn)
+            .map_err(|e| Error::from(e).context(format!("List carrier accounts of store {} error occurred", store_id)))?;
+        accounts.into_iter().map(|account| self.to_model(account)).collect()
+    }
+
+    /// Find specific account of the store
+    fn find(&self, store_id: StoreId, account_id: i32) -> RepoResult<Option<CarrierAccount>> {
+        acl::check(&*self.acl, Resource::CarrierAccounts, Action::Read, self, Some(&store_id))?;
+
+        let query = DslCarrierAccounts::carrier_accounts
+            .filter(DslCarrierAccounts::store_id.eq(store_id))
+            .filter(DslCarrierAccounts::id.eq(account_id));
+
+        let account = query
+            .get_result::<DbCarrierAccount>(self.db_conn)
+            .optional()
+            .map_err(|e| Error::from(e).context(format!("Find carrier account {} of store {} error occurred", account_id, store_id)))?;
+        self.to_optional_model(account)
+    }
+
+    /// Returns account of the store with the carrier
+    fn find_by_company(&self, store_id: StoreId, company_id: CompanyId) -> RepoResult<Option<CarrierAccount>> {
+        acl::check(&*self.acl, Resource::CarrierAccounts, Action::Read, self, Some(&store_id))?;
+
+        let query = DslCarrierAccounts::carrier_accounts
+            .filter(DslCarrierAccounts::store_id.eq(store_id))
+            .filter(DslCarrierAccounts::company_id.eq(company_id));
+
+        let account = query.get_result::<DbCarrierAccount>(self.db_conn).optional().map_err(|e| {
+            Error::from(e).context(format!(
+                "Find carrier account of store {} with company {} error occurred",
+                store_id, company_id
+            ))
+        })?;
+        self.to_optional_model(account)
+    }
+
+    /// Returns account of the store with the carrier if the user manages the store, `None` otherwise
+    fn find_usable_by_company(&self, store_id: StoreId, company_id: CompanyId) -> RepoResult<Option<CarrierAccount>> {
+        // Prices are requested with the account on behalf of its store only, the store of the request is not trusted
+        if acl::check(&*self.acl, Resource::CarrierAccounts, Action::Read, self, Some(&store_id)).is_err() {
+            return Ok(None);
+        }
+
+        self.find_by_company(store_id, company_id)
+    }
+
+    /// Create a new account, account number and credentials are encrypted
+    fn create(&self, store_id: StoreId, payload: CreateCarrierAccount) -> RepoResult<CarrierAccount> {
+        acl::check(&*self.acl, Resource::CarrierAccounts, Action::Create, self, Some(&store_id))?;
+
+        let company_id = payload.company_id;
+        let new_account = NewCarrierAccount {
+            store_id,
+            company_id,
+            encrypted_account_number: self.cipher.encrypt(&payload.account_number)?,
+            encrypted_credentials: self.cipher.encrypt(&payload.credentials)?,
+        };
+        let query = diesel::insert_into(DslCarrierAccounts::carrier_accounts).values(&new_account);
+
+        let account = query.get_result::<DbCarrierAccount>(self.db_conn).map_err(|e| {
+            Error::from(e).context(format!(
+                "Create carrier account of store {} with company {} error occurred",
+                store_id, company_id
+            ))
+        })?;
+        self.to_model(account)
+    }
+
+    /// Update account of the store, account number and credentials are encrypted
+    fn update(&self, store_id: StoreId, account_id: i32, payload: UpdateCarrierAccount) -> RepoResult<Option<CarrierAccount>> {
+        acl::check(&*self.acl, Resource::CarrierAccounts, Action::Update, self, Some(&store_id))?;
+
+        let encrypted_account_number = match payload.account_number {
+            Some(ref account_number) => Some(self.cipher.encrypt(account_number)?),
+            None => None,
+        };
+        let encrypted_credentials = match payload.credentials {
+            Some(ref credentials) => Some(self.cipher.encrypt(credentials)?),
+            None => None,
+        };
+        let changeset = CarrierAccountChangeset {
+            encrypted_account_number,
+            encrypted_credentials,
+        };
+        self.update_encrypted(store_id, account_id, changeset)
+    }
+
+    /// Delete account of the store
+    fn delete(&self, store_id: StoreId, account_id: i32) -> RepoResult<Option<CarrierAccount>> {
+        acl::check(&*self.acl, Resource::CarrierAccounts, Action::Delete, self, Some(&store_id))?;
+
+        let filtered = DslCarrierAccounts::carrier_accounts
+            .filter(DslCarrierAccounts::store_id.eq(store_id))
+            .filter(DslCarrierAccounts::id.eq(account_id));
+        let query = diesel::delete(filtered);
+
+        let account = query.get_result::<DbCarrierAccount>(self.db_conn).optional().map_err(|e| {
+            Error::from(e).context(format!(
+                "Delete carrier account {} of store {} error occurred",
+                account_id, store_id
+            ))
+        })?;
+        self.to_optional_model(account)
+    }
+
+    /// Returns decrypted credentials of the account
+    fn decrypt_credentials(&self, account: &CarrierAccount) -> RepoResult<String> {
+        acl::check(&*self.acl, Resource::CarrierAccounts, Action::Read, self, Some(&account.store_id))?;
+
+        self.cipher.decrypt(&account.encrypted_credentials).map_err(|e| {
+            e.context(format!("Decrypt credentials of carrier account {} error occurred", account.id))
+                .into()
+        })
+    }
+
+    /// Returns the number of accounts of all stores
+    fn count_all(&self) -> RepoResult<i64> {
+        acl::check(&*self.acl, Resource::CarrierAccounts, Action::Read, self, None)?;
+
+        DslCarrierAccounts::carrier_accounts
+            .count()
+            .get_result::<i64>(self.db_conn)
+            .map_err(|e| Error::from(e).context("Count carrier accounts error occurred").into())
+    }
+
+    /// Returns at most `batch_size` accounts of all stores with id greater than `after_id`, ordered by id
+    fn get_batch(&self, after_id: Option<i32>, batch_size: i64) -> RepoResult<Vec<CarrierAccount>> {
+        acl::check(&*self.acl, Resource::CarrierAccounts, Action::Read, self, None)?;
+
+        let query = DslCarrierAccounts::carrier_accounts
+            .filter(DslCarrierAccounts::id.gt(after_id.unwrap_or(0)))
+            .order(DslCarrierAccounts::id)
+            .limit(batch_size);
+
+        let accounts = query
+            .get_results::<DbCarrierAccount>(self.db_conn)
+            .map_err(|e| Error::from(e).context(format!("Get batch of carrier accounts after id {:?} error occurred", after_id)))?;
+        accounts.into_iter().map(|account| self.to_model(account)).collect()
+    }
+
+    /// Encrypts account number and credentials of the account with the current key, returns `false` if they are already encrypted with it
+    fn reencrypt(&self, account: &CarrierAccount) -> RepoResult<bool> {
+        acl::check(&*self.acl, Resource::CarrierAccounts, Action::Update, self, None)?;
+
+        // Either of them may be encrypted with a previous key, e.g. if only the credentials were updated after the rotation
+        let encrypted_account_number = self.cipher.reencrypt(&account.encrypted_account_number)?;
+        let encrypted_credentials = self.cipher.reencrypt(&account.encrypted_credentials)?;
+        if encrypted_account_number.is_none() && encrypted_credentials.is_none() {
+            return Ok(false);
+        }
+        let changeset = CarrierAccountChangeset {
+            encrypted_account_number,
+            encrypted_credentials,
+        };
+        self.update_encrypted(account.store_id, account.id, changeset)
+            .map(|updated| updated.is_some())
+    }
+
+    /// Returns fingerprint of the current encryption key
+    fn key_fingerprint(&self) -> RepoResult<String> {
+        self.cipher.key_fingerprint()
+    }
+}
+
+impl<'a, T: DbConnection> CheckScope<Scope, StoreId> for CarrierAccountsRepoImpl<'a, T> {
+    fn is_in_scope(&self, user_id_arg: UserId, scope: &Scope, obj: Option<&StoreId>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => {
+                if let Some(store_id) = obj {
+                    get_user_roles_data(self.db_conn, user_id_arg)
+                        .map(|roles_data| roles_data.iter().any(|role_data| role_data.manages_store(*store_id)))
+                        .unwrap_or_else(|_: FailureError| false)
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
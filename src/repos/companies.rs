@@ -1,13 +1,11 @@
 //! Repo Companies table.
 
 use diesel;
-use diesel::connection::AnsiTransactionManager;
 use diesel::dsl::sql;
-use diesel::pg::Pg;
+use diesel::pg::types::sql_types::Array;
 use diesel::prelude::*;
 use diesel::query_dsl::RunQueryDsl;
-use diesel::sql_types::VarChar;
-use diesel::Connection;
+use diesel::sql_types::{Bool, Int4, Jsonb, VarChar};
 
 use errors::Error;
 use failure::Error as FailureError;
@@ -18,18 +16,26 @@ use models::authorization::*;
 use repos::legacy_acl::*;
 use repos::types::RepoResult;
 
-use models::companies::{Company, CompanyRaw, NewCompany, UpdateCompany};
+use models::companies::{CompaniesSearch, CompaniesSortField, Company, CompanyDeletionImpact, CompanyRaw, NewCompany, UpdateCompany};
 use models::countries::Country;
+use models::roles::manages_company;
+use models::sorting::SortOrder;
+use models::TypedJson;
 use repos::*;
+use request_context::log_line;
 use schema::companies::dsl::*;
+use schema::companies_packages::dsl as DslCompaniesPackages;
+use schema::packages::dsl as DslPackages;
+use schema::products::dsl as DslProducts;
+use schema::shipping_rates::dsl as DslShippingRates;
 
 /// Companies repository for handling Companies
 pub trait CompaniesRepo {
     /// Create a new company
     fn create(&self, payload: NewCompany) -> RepoResult<Company>;
 
-    /// Returns list of companies
-    fn list(&self) -> RepoResult<Vec<Company>>;
+    /// Returns list of companies matching the search
+    fn list(&self, search: CompaniesSearch) -> RepoResult<Vec<Company>>;
 
     /// Find specific company by ID
     fn find(&self, id_arg: CompanyId) -> RepoResult<Option<Company>>;
@@ -40,58 +46,114 @@ pub trait CompaniesRepo {
     /// Update a company
     fn update(&self, id_arg: CompanyId, payload: UpdateCompany) -> RepoResult<Company>;
 
-    /// Delete a company
+    /// Returns counts of the data deleted together with the company
+    fn deletion_impact(&self, id_arg: CompanyId) -> RepoResult<CompanyDeletionImpact>;
+
+    /// Delete a company, its company packages with their products and shipping rates are deleted by the foreign keys
     fn delete(&self, id_arg: CompanyId) -> RepoResult<Company>;
 }
 
 /// Implementation of CompaniesRepo trait
-pub struct CompaniesRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+pub struct CompaniesRepoImpl<'a, T: DbConnection> {
     pub db_conn: &'a T,
-    pub acl: Box<Acl<Resource, Action, Scope, FailureError, Company>>,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, Company>>,
     pub countries: Country,
 }
 
-impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CompaniesRepoImpl<'a, T> {
-    pub fn new(db_conn: &'a T, acl: Box<Acl<Resource, Action, Scope, FailureError, Company>>, countries: Country) -> Self {
+impl<'a, T: DbConnection> CompaniesRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, Company>>, countries: Country) -> Self {
         Self { db_conn, acl, countries }
     }
 }
 
-impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CompaniesRepo for CompaniesRepoImpl<'a, T> {
+impl<'a, T: DbConnection> CompaniesRepo for CompaniesRepoImpl<'a, T> {
     fn create(&self, payload: NewCompany) -> RepoResult<Company> {
-        debug!("create new company {:?}.", payload);
-        let payload = payload.to_raw()?;
+        debug!("{}", log_line(&format!("create new company {:?}.", payload)));
+        let payload = payload.to_raw();
 
         let query = diesel::insert_into(companies).values(&payload);
         query
             .get_result::<CompanyRaw>(self.db_conn)
             .map_err(|e| Error::from(e).into())
-            .and_then(|v| Company::from_raw(v, &self.countries))
+            .map(|v| Company::from_raw(v, &self.countries))
             .and_then(|company| acl::check(&*self.acl, Resource::Companies, Action::Create, self, Some(&company)).and_then(|_| Ok(company)))
             .map_err(|e: FailureError| e.context(format!("create new company {:?}.", payload)).into())
     }
 
-    fn list(&self) -> RepoResult<Vec<Company>> {
-        debug!("List companies");
+    fn list(&self, search: CompaniesSearch) -> RepoResult<Vec<Company>> {
+        debug!("{}", log_line(&format!("List companies with search {:?}", search)));
+
+        let CompaniesSearch {
+            deliveries_from: deliveries_from_arg,
+            delivers_to,
+            supports,
+            label_contains,
+            sort,
+            order,
+        } = search.clone();
+
+        let mut query = companies.into_boxed();
+
+        if let Some(country) = deliveries_from_arg {
+            query = query.filter(sql("deliveries_from ? ").bind::<VarChar, _>(country));
+        }
+
+        if let Some(country) = delivers_to {
+            // packages delivering to a region deliver to all of its countries
+            let mut codes = vec![];
+            get_all_parent_codes(&self.countries, &country, &mut codes);
+            if codes.is_empty() {
+                codes.push(country);
+            }
+
+            let zone_ids = get_zone_ids_with_countries(self.db_conn, &codes)?;
+
+            // zone ids are stored as JSONB array of numbers, its text is turned into the array literal of Postgres
+            let pg_codes: Vec<String> = codes.into_iter().map(|code| code.0).collect();
+            let package_ids = DslPackages::packages.select(DslPackages::id).filter(
+                sql::<Bool>("deliveries_to ?| ")
+                    .bind::<Array<VarChar>, _>(pg_codes)
+                    .or(sql::<Bool>("translate(deliveries_to_zones::text, '[]', '{}')::int[] && ").bind::<Array<Int4>, _>(zone_ids)),
+            );
+            let company_ids = DslCompaniesPackages::companies_packages
+                .select(DslCompaniesPackages::company_id)
+                .filter(DslCompaniesPackages::package_id.eq_any(package_ids));
+            query = query.filter(id.eq_any(company_ids));
+        }
+
+        if let Some(capabilities_arg) = supports {
+            query = query.filter(sql("capabilities @> ").bind::<Jsonb, _>(TypedJson(capabilities_arg.0)));
+        }
+
+        if let Some(text) = label_contains {
+            query = query.filter(label.ilike(format!("%{}%", escape_like_pattern(&text))));
+        }
 
-        let query = companies.order(id);
+        let query = match (sort.unwrap_or_default(), order.unwrap_or_default()) {
+            (CompaniesSortField::Id, SortOrder::Asc) => query.order(id.asc()),
+            (CompaniesSortField::Id, SortOrder::Desc) => query.order(id.desc()),
+            (CompaniesSortField::Name, SortOrder::Asc) => query.order((name.asc(), id.asc())),
+            (CompaniesSortField::Name, SortOrder::Desc) => query.order((name.desc(), id.asc())),
+            (CompaniesSortField::Label, SortOrder::Asc) => query.order((label.asc(), id.asc())),
+            (CompaniesSortField::Label, SortOrder::Desc) => query.order((label.desc(), id.asc())),
+        };
 
         query
             .get_results(self.db_conn)
             .map_err(|e| Error::from(e).into())
-            .and_then(|raws: Vec<CompanyRaw>| raws.into_iter().map(|v| Company::from_raw(v, &self.countries)).collect())
+            .map(|raws: Vec<CompanyRaw>| raws.into_iter().map(|v| Company::from_raw(v, &self.countries)).collect())
             .and_then(|results: Vec<Company>| {
                 for company in &results {
                     acl::check(&*self.acl, Resource::Companies, Action::Read, self, Some(&company))?;
                 }
                 Ok(results)
             })
-            .map_err(|e: FailureError| e.context("Find in companies error occured").into())
+            .map_err(move |e: FailureError| e.context(format!("List companies with search {:?} error occured", search)).into())
     }
 
     /// Find specific company by ID
     fn find(&self, id_arg: CompanyId) -> RepoResult<Option<Company>> {
-        debug!("Find in company with id {}.", id_arg);
+        debug!("{}", log_line(&format!("Find in company with id {}.", id_arg)));
 
         let query = companies.find(id_arg);
         query
@@ -100,7 +162,7 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             .map_err(|e| Error::from(e).into())
             .and_then(|company_raw: Option<CompanyRaw>| match company_raw {
                 Some(value) => {
-                    let company = Company::from_raw(value, &self.countries)?;
+                    let company = Company::from_raw(value, &self.countries);
                     acl::check(&*self.acl, Resource::Companies, Action::Read, self, Some(&company))?;
                     Ok(Some(company))
                 }
@@ -111,14 +173,14 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
 
     /// Returns list of companies supported by the country
     fn find_deliveries_from(&self, country: Alpha3) -> RepoResult<Vec<Company>> {
-        debug!("Find in companies with country {:?}.", country);
+        debug!("{}", log_line(&format!("Find in companies with country {:?}.", country)));
 
         let query = companies.filter(sql("deliveries_from ? ").bind::<VarChar, _>(&country));
 
         query
             .get_results(self.db_conn)
             .map_err(|e| Error::from(e).into())
-            .and_then(|raw: Vec<CompanyRaw>| raw.into_iter().map(|v| Company::from_raw(v, &self.countries)).collect())
+            .map(|raw: Vec<CompanyRaw>| raw.into_iter().map(|v| Company::from_raw(v, &self.countries)).collect())
             .and_then(|results: Vec<Company>| {
                 for result in &results {
                     acl::check(&*self.acl, Resource::Companies, Action::Read, self, Some(&result))?;
@@ -132,15 +194,15 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
     }
 
     fn update(&self, id_arg: CompanyId, payload: UpdateCompany) -> RepoResult<Company> {
-        debug!("Updating company {} with payload {:?}.", id_arg, payload);
-        let payload = payload.to_raw()?;
+        debug!("{}", log_line(&format!("Updating company {} with payload {:?}.", id_arg, payload)));
+        let payload = payload.to_raw();
 
         let query = companies.filter(id.eq(id_arg));
 
         query
             .get_result::<CompanyRaw>(self.db_conn)
             .map_err(|e| Error::from(e).into())
-            .and_then(|v| Company::from_raw(v, &self.countries))
+            .map(|v| Company::from_raw(v, &self.countries))
             .and_then(|company: Company| acl::check(&*self.acl, Resource::Companies, Action::Update, self, Some(&company)))
             .and_then(|_| {
                 let filtered = companies.filter(id.eq(id_arg));
@@ -149,13 +211,50 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
                 query
                     .get_result::<CompanyRaw>(self.db_conn)
                     .map_err(|e| Error::from(e).into())
-                    .and_then(|v| Company::from_raw(v, &self.countries))
+                    .map(|v| Company::from_raw(v, &self.countries))
             })
             .map_err(|e: FailureError| e.context(format!("Updating company payload {:?} failed.", payload)).into())
     }
 
+    fn deletion_impact(&self, id_arg: CompanyId) -> RepoResult<CompanyDeletionImpact> {
+        debug!("{}", log_line(&format!("Get deletion impact of company {}.", id_arg)));
+
+        acl::check(&*self.acl, Resource::Companies, Action::Delete, self, None)?;
+
+        let company_packages_query = DslCompaniesPackages::companies_packages
+            .filter(DslCompaniesPackages::company_id.eq(id_arg))
+            .count();
+        let products_query = DslProducts::products
+            .inner_join(DslCompaniesPackages::companies_packages)
+            .filter(DslCompaniesPackages::company_id.eq(id_arg))
+            .count();
+        let shipping_rates_query = DslShippingRates::shipping_rates
+            .inner_join(DslCompaniesPackages::companies_packages)
+            .filter(DslCompaniesPackages::company_id.eq(id_arg))
+            .count();
+
+        company_packages_query
+            .get_result::<i64>(self.db_conn)
+            .and_then(|company_packages| {
+                products_query
+                    .get_result::<i64>(self.db_conn)
+                    .map(|products| (company_packages, products))
+            })
+            .and_then(|(company_packages, products)| {
+                shipping_rates_query
+                    .get_result::<i64>(self.db_conn)
+                    .map(|shipping_rates| CompanyDeletionImpact {
+                        company_packages,
+                        products,
+                        shipping_rates,
+                    })
+            })
+            .map_err(|e| Error::from(e).into())
+            .map_err(|e: FailureError| e.context(format!("Get deletion impact of company {} failed.", id_arg)).into())
+    }
+
     fn delete(&self, id_arg: CompanyId) -> RepoResult<Company> {
-        debug!("delete company by company_id: {}.", id_arg);
+        debug!("{}", log_line(&format!("delete company by company_id: {}.", id_arg)));
 
         acl::check(&*self.acl, Resource::Companies, Action::Delete, self, None)?;
 
@@ -165,18 +264,24 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
         query
             .get_result::<CompanyRaw>(self.db_conn)
             .map_err(|e| Error::from(e).into())
-            .and_then(|v| Company::from_raw(v, &self.countries))
-            .map_err(move |e| e.context(format!("delete company id: {}.", id_arg)).into())
+            .map(|v| Company::from_raw(v, &self.countries))
+            .map_err(move |e: FailureError| e.context(format!("delete company id: {}.", id_arg)).into())
     }
 }
 
-impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, Company>
-    for CompaniesRepoImpl<'a, T>
-{
-    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&Company>) -> bool {
+impl<'a, T: DbConnection> CheckScope<Scope, Company> for CompaniesRepoImpl<'a, T> {
+    fn is_in_scope(&self, user_id_arg: UserId, scope: &Scope, obj: Option<&Company>) -> bool {
         match *scope {
             Scope::All => true,
-            Scope::Owned => false,
+            Scope::Owned => {
+                if let Some(company) = obj {
+                    get_user_roles_data(self.db_conn, user_id_arg)
+                        .map(|roles_data| manages_company(&roles_data, &company.deliveries_from))
+                        .unwrap_or_else(|_: FailureError| false)
+                } else {
+                    false
+                }
+            }
         }
     }
 }
@@ -1,11 +1,8 @@
 //! Repo companies_packages table.
 
 use diesel;
-use diesel::connection::AnsiTransactionManager;
-use diesel::pg::Pg;
 use diesel::prelude::*;
 use diesel::query_dsl::RunQueryDsl;
-use diesel::Connection;
 
 use errors::Error;
 use failure::Error as FailureError;
@@ -19,10 +16,11 @@ use repos::types::RepoResult;
 
 use extras::option::transpose;
 use models::{
-    get_country, AvailablePackages, CompaniesPackagesRaw, Company, CompanyPackage, CompanyRaw, Country, NewCompaniesPackagesRaw,
-    NewCompanyPackage, Packages, PackagesRaw,
+    get_country, AvailablePackages, CandidatePackage, CompaniesPackagesRaw, Company, CompanyPackage, CompanyRaw, Country,
+    NewCompanyPackage, Packages, PackagesRaw, RateResolutionStep, SetCarrierFallback, SetCashOnDelivery, SetShippingCapacity, TypedJson,
 };
 use repos::*;
+use request_context::log_line;
 use schema::companies::dsl as DslCompanies;
 use schema::companies_packages::dsl::*;
 use schema::packages::dsl as DslPackages;
@@ -41,41 +39,66 @@ pub trait CompaniesPackagesRepo {
         deliveries_from: Alpha3,
     ) -> RepoResult<Vec<AvailablePackages>>;
 
+    /// Returns company packages of all companies with their packages, whatever the shipment and the origin are
+    fn get_candidate_packages(&self, deliveries_from: Alpha3) -> RepoResult<Vec<CandidatePackage>>;
+
     /// Returns company package by id
     fn get(&self, id: CompanyPackageId) -> RepoResult<Option<CompanyPackage>>;
 
+    /// Returns company packages by ids with their companies in a single query, the ones not found are skipped
+    fn get_many_with_companies(&self, ids: Vec<CompanyPackageId>) -> RepoResult<Vec<(CompanyPackage, Company)>>;
+
+    /// Returns company packages by ids with their companies and packages at once, the ones not found are skipped
+    fn get_many_with_companies_and_packages(&self, ids: Vec<CompanyPackageId>) -> RepoResult<Vec<(CompanyPackage, Company, Packages)>>;
+
     /// Returns companies by package id
     fn get_companies(&self, id: PackageId) -> RepoResult<Vec<Company>>;
 
     /// Returns packages by company id
     fn get_packages(&self, id: CompanyId) -> RepoResult<Vec<Packages>>;
 
+    /// Find companies_packages by company and package
+    fn find_by_ids(&self, company_id_arg: CompanyId, package_id_arg: PackageId) -> RepoResult<Option<CompanyPackage>>;
+
     /// Delete a companies_packages
     fn delete(&self, company_id_arg: CompanyId, package_id_arg: PackageId) -> RepoResult<CompanyPackage>;
+
+    /// Sets the price resolution order of the company package, `None` resets it to the default one
+    fn set_rate_resolution_order(
+        &self,
+        id_arg: CompanyPackageId,
+        steps: Option<Vec<RateResolutionStep>>,
+    ) -> RepoResult<Option<CompanyPackage>>;
+
+    /// Sets whether the company package supports the cash on delivery and its fee
+    fn set_cash_on_delivery(&self, id_arg: CompanyPackageId, payload: SetCashOnDelivery) -> RepoResult<Option<CompanyPackage>>;
+
+    /// Sets the price quoted when the carrier quote of the company package fails
+    fn set_carrier_fallback(&self, id_arg: CompanyPackageId, payload: SetCarrierFallback) -> RepoResult<Option<CompanyPackage>>;
+
+    /// Sets the cut-off time and the daily capacity of the company package
+    fn set_shipping_capacity(&self, id_arg: CompanyPackageId, payload: SetShippingCapacity) -> RepoResult<Option<CompanyPackage>>;
 }
 
 /// Implementation of CompaniesPackagesRepo trait
-pub struct CompaniesPackagesRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+pub struct CompaniesPackagesRepoImpl<'a, T: DbConnection> {
     pub db_conn: &'a T,
-    pub acl: Box<Acl<Resource, Action, Scope, FailureError, CompanyPackage>>,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, CompanyPackage>>,
     pub countries: Country,
 }
 
-impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CompaniesPackagesRepoImpl<'a, T> {
-    pub fn new(db_conn: &'a T, acl: Box<Acl<Resource, Action, Scope, FailureError, CompanyPackage>>, countries: Country) -> Self {
+impl<'a, T: DbConnection> CompaniesPackagesRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, CompanyPackage>>, countries: Country) -> Self {
         Self { db_conn, acl, countries }
     }
 }
 
-impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CompaniesPackagesRepo
-    for CompaniesPackagesRepoImpl<'a, T>
-{
+impl<'a, T: DbConnection> CompaniesPackagesRepo for CompaniesPackagesRepoImpl<'a, T> {
     fn create(&self, payload: NewCompanyPackage) -> RepoResult<CompanyPackage> {
-        debug!("create new companies_packages {:?}.", payload);
-        let record = NewCompaniesPackagesRaw::from(payload.clone());
-
-        let query = diesel::insert_into(companies_packages).values(&record);
-        query
+        debug!("{}", log_line(&format!("create new companies_packages {:?}.", payload)));
+        let record = payload.clone().to_raw();
+        diesel::insert_into(companies_packages)
+            .values(&record)
             .get_result::<CompaniesPackagesRaw>(self.db_conn)
             .map_err(|e| Error::from(e).into())
             .and_then(CompaniesPackagesRaw::to_model)
@@ -93,7 +116,7 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
     }
 
     fn get(&self, id_arg: CompanyPackageId) -> RepoResult<Option<CompanyPackage>> {
-        debug!("get companies_packages by id: {}.", id_arg);
+        debug!("{}", log_line(&format!("get companies_packages by id: {}.", id_arg)));
 
         acl::check(&*self.acl, Resource::CompaniesPackages, Action::Read, self, None)?;
         let query = companies_packages.filter(id.eq(id_arg));
@@ -104,6 +127,83 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             .and_then(|record| transpose(record.map(CompaniesPackagesRaw::to_model)))
     }
 
+    /// Returns company packages by ids with their companies in a single query, the ones not found are skipped
+    fn get_many_with_companies(&self, ids_arg: Vec<CompanyPackageId>) -> RepoResult<Vec<(CompanyPackage, Company)>> {
+        debug!(
+            "{}",
+            log_line(&format!("get companies_packages with companies by ids: {:?}.", ids_arg))
+        );
+
+        acl::check(&*self.acl, Resource::CompaniesPackages, Action::Read, self, None)?;
+        if ids_arg.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let query = companies_packages
+            .filter(id.eq_any(ids_arg.clone()))
+            .inner_join(DslCompanies::companies);
+
+        query
+            .get_results::<(CompaniesPackagesRaw, CompanyRaw)>(self.db_conn)
+            .map_err(|e| Error::from(e).into())
+            .and_then(|results| {
+                let mut data = vec![];
+                for (company_package_raw, company_raw) in results {
+                    let company_package = company_package_raw.to_model()?;
+                    let company = Company::from_raw(company_raw, &self.countries);
+                    data.push((company_package, company));
+                }
+
+                Ok(data)
+            })
+            .map_err(move |e: FailureError| {
+                e.context(format!("get companies_packages with companies by ids: {:?}.", ids_arg))
+                    .into()
+            })
+    }
+
+    /// Returns company packages by ids with their companies and packages, zones of the packages are loaded with one more query
+    fn get_many_with_companies_and_packages(&self, ids_arg: Vec<CompanyPackageId>) -> RepoResult<Vec<(CompanyPackage, Company, Packages)>> {
+        debug!(
+            "{}",
+            log_line(&format!(
+                "get companies_packages with companies and packages by ids: {:?}.",
+                ids_arg
+            ))
+        );
+
+        acl::check(&*self.acl, Resource::CompaniesPackages, Action::Read, self, None)?;
+        if ids_arg.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let query = companies_packages
+            .filter(id.eq_any(ids_arg.clone()))
+            .inner_join(DslCompanies::companies)
+            .inner_join(DslPackages::packages);
+
+        query
+            .get_results::<(CompaniesPackagesRaw, CompanyRaw, PackagesRaw)>(self.db_conn)
+            .map_err(|e| Error::from(e).into())
+            .and_then(|results| {
+                let packages_raw = results.iter().map(|(_, _, package_raw)| package_raw.clone()).collect::<Vec<_>>();
+                let zones = get_zones_for_packages(self.db_conn, &packages_raw)?;
+                let mut data = vec![];
+                for (company_package_raw, company_raw, package_raw) in results {
+                    let company_package = company_package_raw.to_model()?;
+                    let company = Company::from_raw(company_raw, &self.countries);
+                    let package = package_raw.to_packages(&self.countries, &zones);
+                    data.push((company_package, company, package));
+                }
+
+                Ok(data)
+            })
+            .map_err(move |e: FailureError| {
+                e.context(format!("get companies_packages with companies and packages by ids: {:?}.", ids_arg))
+                    .into()
+            })
+    }
+
     /// Getting available packages satisfying the constraints
     fn get_available_packages(
         &self,
@@ -116,8 +216,11 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
         let weight = weight as i32;
 
         debug!(
-            "Find in packages with companies: {:?}, size: {}, weight: {}.",
-            company_id_args, size, weight
+            "{}",
+            log_line(&format!(
+                "Find in packages with companies: {:?}, size: {}, weight: {}.",
+                company_id_args, size, weight
+            ))
         );
 
         let query = companies_packages
@@ -134,12 +237,14 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             .get_results::<(CompaniesPackagesRaw, CompanyRaw, PackagesRaw)>(self.db_conn)
             .map_err(|e| Error::from(e).into())
             .and_then(|results| {
+                let packages_raw = results.iter().map(|(_, _, package_raw)| package_raw.clone()).collect::<Vec<_>>();
+                let zones = get_zones_for_packages(self.db_conn, &packages_raw)?;
                 let mut data = vec![];
 
                 for result in results {
                     let (companies_package, company_raw, package_raw) = result;
                     let company_package = companies_package.to_model()?;
-                    let used_codes = package_raw.get_deliveries_to()?;
+                    let used_codes = package_raw.get_deliveries_to_with_zones(&zones);
 
                     let local_available = used_codes.iter().any(|country_code| {
                         get_country(&self.countries, country_code)
@@ -147,7 +252,7 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
                             .unwrap_or_default()
                     });
 
-                    let package = package_raw.to_packages(&self.countries)?;
+                    let package = package_raw.to_packages(&self.countries, &zones);
 
                     data.push(AvailablePackages {
                         id: company_package.id,
@@ -171,9 +276,63 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             })
     }
 
+    /// Returns company packages of all companies with their packages, whatever the shipment and the origin are
+    fn get_candidate_packages(&self, deliveries_from: Alpha3) -> RepoResult<Vec<CandidatePackage>> {
+        debug!("{}", log_line(&format!("Find candidate packages from {}.", deliveries_from)));
+        acl::check(&*self.acl, Resource::CompaniesPackages, Action::Read, self, None)?;
+
+        let query = companies_packages
+            .inner_join(DslCompanies::companies)
+            .inner_join(DslPackages::packages)
+            .order((DslCompanies::label, id));
+
+        query
+            .get_results::<(CompaniesPackagesRaw, CompanyRaw, PackagesRaw)>(self.db_conn)
+            .map_err(|e| Error::from(e).into())
+            .and_then(|results| {
+                let packages_raw = results.iter().map(|(_, _, package_raw)| package_raw.clone()).collect::<Vec<_>>();
+                let zones = get_zones_for_packages(self.db_conn, &packages_raw)?;
+                let mut data = vec![];
+
+                for (companies_package, company_raw, package_raw) in results {
+                    let company_package = companies_package.to_model()?;
+                    let used_codes = package_raw.get_deliveries_to_with_zones(&zones);
+
+                    let local_available = used_codes.iter().any(|country_code| {
+                        get_country(&self.countries, country_code)
+                            .map(|c| contains_country_code(&c, &deliveries_from))
+                            .unwrap_or_default()
+                    });
+
+                    let package = package_raw.to_packages(&self.countries, &zones);
+
+                    data.push(CandidatePackage {
+                        company_id: company_package.company_id,
+                        delivers_from: company_raw.deliveries_from.contains(&deliveries_from),
+                        available_package: AvailablePackages {
+                            id: company_package.id,
+                            name: get_company_package_name(&company_raw.label, &package.name),
+                            logo: company_raw.logo,
+                            deliveries_to: package.deliveries_to.clone(),
+                            shipping_rate_source: company_package.shipping_rate_source,
+                            currency: company_raw.currency,
+                            local_available,
+                        },
+                        package,
+                    });
+                }
+
+                Ok(data)
+            })
+            .map_err(|e: FailureError| {
+                e.context(format!("Find candidate packages from {} error occured", deliveries_from))
+                    .into()
+            })
+    }
+
     /// Returns companies by package id
     fn get_companies(&self, id_arg: PackageId) -> RepoResult<Vec<Company>> {
-        debug!("get companies_packages by package_id: {}.", id_arg);
+        debug!("{}", log_line(&format!("get companies_packages by package_id: {}.", id_arg)));
 
         let query = companies_packages.filter(package_id.eq(id_arg)).inner_join(DslCompanies::companies);
 
@@ -184,7 +343,7 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
                 let mut data = vec![];
                 for result in results {
                     let (_, company_raw) = result;
-                    let element = Company::from_raw(company_raw, &self.countries)?;
+                    let element = Company::from_raw(company_raw, &self.countries);
                     data.push(element);
                 }
 
@@ -195,7 +354,7 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
 
     /// Returns packages by company id
     fn get_packages(&self, id_arg: CompanyId) -> RepoResult<Vec<Packages>> {
-        debug!("get companies_packages by company_id: {}.", id_arg);
+        debug!("{}", log_line(&format!("get companies_packages by company_id: {}.", id_arg)));
 
         let query = companies_packages.filter(company_id.eq(id_arg)).inner_join(DslPackages::packages);
 
@@ -203,22 +362,49 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             .get_results::<(CompaniesPackagesRaw, PackagesRaw)>(self.db_conn)
             .map_err(|e| Error::from(e).into())
             .and_then(|results| {
-                let mut data = vec![];
-                for result in results {
-                    let (_, package_raw) = result;
-                    let element = package_raw.to_packages(&self.countries)?;
-                    data.push(element);
-                }
+                let packages_raw = results.into_iter().map(|(_, package_raw)| package_raw).collect::<Vec<_>>();
+                let zones = get_zones_for_packages(self.db_conn, &packages_raw)?;
 
-                Ok(data)
+                Ok(packages_raw
+                    .into_iter()
+                    .map(|package_raw| package_raw.to_packages(&self.countries, &zones))
+                    .collect())
             })
             .map_err(move |e: FailureError| e.context(format!("get companies_packages company_id: {}.", id_arg)).into())
     }
 
+    fn find_by_ids(&self, company_id_arg: CompanyId, package_id_arg: PackageId) -> RepoResult<Option<CompanyPackage>> {
+        debug!(
+            "{}",
+            log_line(&format!(
+                "find companies_packages by company_id: {}, package_id: {}.",
+                company_id_arg, package_id_arg
+            ))
+        );
+
+        acl::check(&*self.acl, Resource::CompaniesPackages, Action::Read, self, None)?;
+        let query = companies_packages.filter(company_id.eq(company_id_arg).and(package_id.eq(package_id_arg)));
+        query
+            .get_result::<CompaniesPackagesRaw>(self.db_conn)
+            .optional()
+            .map_err(move |e| {
+                Error::from(e)
+                    .context(format!(
+                        "find companies_packages company_id: {}, package_id: {}.",
+                        company_id_arg, package_id_arg
+                    ))
+                    .into()
+            })
+            .and_then(|record| transpose(record.map(CompaniesPackagesRaw::to_model)))
+    }
+
     fn delete(&self, company_id_arg: CompanyId, package_id_arg: PackageId) -> RepoResult<CompanyPackage> {
         debug!(
-            "delete companies_packages by company_id: {}, package_id: {}.",
-            company_id_arg, package_id_arg
+            "{}",
+            log_line(&format!(
+                "delete companies_packages by company_id: {}, package_id: {}.",
+                company_id_arg, package_id_arg
+            ))
         );
 
         acl::check(&*self.acl, Resource::CompaniesPackages, Action::Delete, self, None)?;
@@ -236,11 +422,112 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             })
             .and_then(CompaniesPackagesRaw::to_model)
     }
+
+    fn set_rate_resolution_order(
+        &self,
+        id_arg: CompanyPackageId,
+        steps: Option<Vec<RateResolutionStep>>,
+    ) -> RepoResult<Option<CompanyPackage>> {
+        debug!(
+            "{}",
+            log_line(&format!(
+                "set rate resolution order of companies_packages id: {}, {:?}.",
+                id_arg, steps
+            ))
+        );
+
+        acl::check(&*self.acl, Resource::CompaniesPackages, Action::Update, self, None)?;
+        let order = steps.map(TypedJson);
+        let filtered = companies_packages.filter(id.eq(id_arg));
+        let query = diesel::update(filtered).set(rate_resolution_order.eq(order));
+        query
+            .get_result::<CompaniesPackagesRaw>(self.db_conn)
+            .optional()
+            .map_err(move |e| {
+                Error::from(e)
+                    .context(format!("set rate resolution order of companies_packages id: {}.", id_arg))
+                    .into()
+            })
+            .and_then(|record| transpose(record.map(CompaniesPackagesRaw::to_model)))
+    }
+
+    fn set_cash_on_delivery(&self, id_arg: CompanyPackageId, payload: SetCashOnDelivery) -> RepoResult<Option<CompanyPackage>> {
+        debug!(
+            "{}",
+            log_line(&format!(
+                "set cash on delivery of companies_packages id: {}, {:?}.",
+                id_arg, payload
+            ))
+        );
+
+        acl::check(&*self.acl, Resource::CompaniesPackages, Action::Update, self, None)?;
+        let filtered = companies_packages.filter(id.eq(id_arg));
+        let query = diesel::update(filtered).set((cod_supported.eq(payload.cod_supported), cod_fee.eq(payload.cod_fee)));
+        query
+            .get_result::<CompaniesPackagesRaw>(self.db_conn)
+            .optional()
+            .map_err(move |e| {
+                Error::from(e)
+                    .context(format!("set cash on delivery of companies_packages id: {}.", id_arg))
+                    .into()
+            })
+            .and_then(|record| transpose(record.map(CompaniesPackagesRaw::to_model)))
+    }
+
+    fn set_carrier_fallback(&self, id_arg: CompanyPackageId, payload: SetCarrierFallback) -> RepoResult<Option<CompanyPackage>> {
+        debug!(
+            "{}",
+            log_line(&format!(
+                "set carrier fallback of companies_packages id: {}, {:?}.",
+                id_arg, payload
+            ))
+        );
+
+        acl::check(&*self.acl, Resource::CompaniesPackages, Action::Update, self, None)?;
+        let filtered = companies_packages.filter(id.eq(id_arg));
+        let query = diesel::update(filtered).set((
+            carrier_fallback.eq(payload.carrier_fallback),
+            fallback_markup_percent.eq(payload.fallback_markup_percent),
+        ));
+        query
+            .get_result::<CompaniesPackagesRaw>(self.db_conn)
+            .optional()
+            .map_err(move |e| {
+                Error::from(e)
+                    .context(format!("set carrier fallback of companies_packages id: {}.", id_arg))
+                    .into()
+            })
+            .and_then(|record| transpose(record.map(CompaniesPackagesRaw::to_model)))
+    }
+
+    fn set_shipping_capacity(&self, id_arg: CompanyPackageId, payload: SetShippingCapacity) -> RepoResult<Option<CompanyPackage>> {
+        debug!(
+            "{}",
+            log_line(&format!(
+                "set shipping capacity of companies_packages id: {}, {:?}.",
+                id_arg, payload
+            ))
+        );
+
+        acl::check(&*self.acl, Resource::CompaniesPackages, Action::Update, self, None)?;
+        let filtered = companies_packages.filter(id.eq(id_arg));
+        let query = diesel::update(filtered).set((
+            daily_cutoff_time.eq(payload.daily_cutoff_time),
+            max_daily_shipments.eq(payload.max_daily_shipments.map(|max_daily_shipments| max_daily_shipments as i32)),
+        ));
+        query
+            .get_result::<CompaniesPackagesRaw>(self.db_conn)
+            .optional()
+            .map_err(move |e| {
+                Error::from(e)
+                    .context(format!("set shipping capacity of companies_packages id: {}.", id_arg))
+                    .into()
+            })
+            .and_then(|record| transpose(record.map(CompaniesPackagesRaw::to_model)))
+    }
 }
 
-impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, CompanyPackage>
-    for CompaniesPackagesRepoImpl<'a, T>
-{
+impl<'a, T: DbConnection> CheckScope<Scope, CompanyPackage> for CompaniesPackagesRepoImpl<'a, T> {
     fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&CompanyPackage>) -> bool {
         match *scope {
             Scope::All => true,
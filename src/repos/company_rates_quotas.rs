@@ -0,0 +1,127 @@
+//! Repo for company_rates_quotas table. Quotas set by admins override the default
+//! quota of the shipping rates storage from config
+
+use diesel;
+use diesel::dsl::now;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use errors::Error;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::{CompanyId, UserId};
+
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::types::{DbConnection, RepoResult};
+use models::authorization::*;
+use models::{CompanyRatesQuota, SetCompanyRatesQuota};
+use schema::companies::dsl as DslCompanies;
+use schema::company_rates_quotas::dsl as DslCompanyRatesQuotas;
+
+/// Company rates quotas repository for handling CompanyRatesQuotas
+pub trait CompanyRatesQuotasRepo {
+    /// Returns quota of the company set by admins
+    fn get(&self, company_id: CompanyId) -> RepoResult<Option<CompanyRatesQuota>>;
+
+    /// Sets quota of the company replacing the previous one
+    fn set(&self, company_id: CompanyId, payload: SetCompanyRatesQuota) -> RepoResult<CompanyRatesQuota>;
+
+    /// Delete quota of the company, the default quota applies to it then
+    fn delete(&self, company_id: CompanyId) -> RepoResult<Option<CompanyRatesQuota>>;
+
+    /// Locks the company until the end of the transaction, so that concurrent uploads of its rates
+    /// are checked against the quota one after another
+    fn lock(&self, company_id: CompanyId) -> RepoResult<()>;
+}
+
+/// Implementation of CompanyRatesQuotas trait
+pub struct CompanyRatesQuotasRepoImpl<'a, T: DbConnection> {
+    pub db_conn: &'a T,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, CompanyRatesQuota>>,
+}
+
+impl<'a, T: DbConnection> CompanyRatesQuotasRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, CompanyRatesQuota>>) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: DbConnection> CompanyRatesQuotasRepo for CompanyRatesQuotasRepoImpl<'a, T> {
+    /// Returns quota of the company set by admins
+    fn get(&self, company_id: CompanyId) -> RepoResult<Option<CompanyRatesQuota>> {
+        acl::check(&*self.acl, Resource::CompanyRatesQuotas, Action::Read, self, None)?;
+
+        let query = DslCompanyRatesQuotas::company_rates_quotas.find(company_id);
+
+        query.get_result::<CompanyRatesQuota>(self.db_conn).optional().map_err(|e| {
+            Error::from(e)
+                .context(format!("Get rates quota of company {} error occurred", company_id))
+                .into()
+        })
+    }
+
+    /// Sets quota of the company replacing the previous one
+    fn set(&self, company_id: CompanyId, payload: SetCompanyRatesQuota) -> RepoResult<CompanyRatesQuota> {
+        acl::check(&*self.acl, Resource::CompanyRatesQuotas, Action::Update, self, None)?;
+
+        let query = diesel::insert_into(DslCompanyRatesQuotas::company_rates_quotas)
+            .values((
+                DslCompanyRatesQuotas::company_id.eq(company_id),
+                DslCompanyRatesQuotas::max_entries.eq(payload.max_entries),
+            ))
+            .on_conflict(DslCompanyRatesQuotas::company_id)
+            .do_update()
+            .set((
+                DslCompanyRatesQuotas::max_entries.eq(payload.max_entries),
+                DslCompanyRatesQuotas::updated_at.eq(now),
+            ));
+
+        query.get_result::<CompanyRatesQuota>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!("Set rates quota {:?} of company {} error occurred", payload, company_id))
+                .into()
+        })
+    }
+
+    /// Delete quota of the company, the default quota applies to it then
+    fn delete(&self, company_id: CompanyId) -> RepoResult<Option<CompanyRatesQuota>> {
+        acl::check(&*self.acl, Resource::CompanyRatesQuotas, Action::Delete, self, None)?;
+
+        let filtered = DslCompanyRatesQuotas::company_rates_quotas.filter(DslCompanyRatesQuotas::company_id.eq(company_id));
+        let query = diesel::delete(filtered);
+
+        query.get_result::<CompanyRatesQuota>(self.db_conn).optional().map_err(|e| {
+            Error::from(e)
+                .context(format!("Delete rates quota of company {} error occurred", company_id))
+                .into()
+        })
+    }
+
+    /// Locks the company until the end of the transaction, so that concurrent uploads of its rates
+    /// are checked against the quota one after another
+    fn lock(&self, company_id: CompanyId) -> RepoResult<()> {
+        acl::check(&*self.acl, Resource::CompanyRatesQuotas, Action::Read, self, None)?;
+
+        let query = DslCompanies::companies
+            .filter(DslCompanies::id.eq(company_id))
+            .select(DslCompanies::id)
+            .for_update();
+
+        query.execute(self.db_conn).map(|_| ()).map_err(|e| {
+            Error::from(e)
+                .context(format!("Lock rates quota of company {} error occurred", company_id))
+                .into()
+        })
+    }
+}
+
+impl<'a, T: DbConnection> CheckScope<Scope, CompanyRatesQuota> for CompanyRatesQuotasRepoImpl<'a, T> {
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&CompanyRatesQuota>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! Repo for company_suspensions table. Suspended companies and company packages
+//! are excluded from the available shipping for the suspension dates
+
+use chrono::NaiveDate;
+use diesel;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use errors::Error;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId;
+
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::types::{DbConnection, RepoResult};
+use models::authorization::*;
+use models::{CompanySuspension, NewCompanySuspension, UpdateCompanySuspension};
+use schema::company_suspensions::dsl as DslCompanySuspensions;
+
+/// Company suspensions repository for handling CompanySuspensions
+pub trait CompanySuspensionsRepo {
+    /// Returns list of company suspensions
+    fn list(&self) -> RepoResult<Vec<CompanySuspension>>;
+
+    /// Find specific company suspension by id
+    fn find(&self, suspension_id: i32) -> RepoResult<Option<CompanySuspension>>;
+
+    /// Returns suspensions active on the date
+    fn find_active_on(&self, date: NaiveDate) -> RepoResult<Vec<CompanySuspension>>;
+
+    /// Create a new company suspension
+    fn create(&self, payload: NewCompanySuspension) -> RepoResult<CompanySuspension>;
+
+    /// Update a company suspension
+    fn update(&self, suspension_id: i32, payload: UpdateCompanySuspension) -> RepoResult<CompanySuspension>;
+
+    /// Delete a company suspension
+    fn delete(&self, suspension_id: i32) -> RepoResult<CompanySuspension>;
+}
+
+/// Implementation of CompanySuspensions trait
+pub struct CompanySuspensionsRepoImpl<'a, T: DbConnection> {
+    pub db_conn: &'a T,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, CompanySuspension>>,
+}
+
+impl<'a, T: DbConnection> CompanySuspensionsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, CompanySuspension>>) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: DbConnection> CompanySuspensionsRepo for CompanySuspensionsRepoImpl<'a, T> {
+    /// Returns list of company suspensions
+    fn list(&self) -> RepoResult<Vec<CompanySuspension>> {
+        acl::check(&*self.acl, Resource::CompanySuspensions, Action::Read, self, None)?;
+
+        let query = DslCompanySuspensions::company_suspensions.order(DslCompanySuspensions::id);
+
+        query
+            .get_results::<CompanySuspension>(self.db_conn)
+            .map_err(|e| Error::from(e).context("list of company suspensions error occurred").into())
+    }
+
+    /// Find specific company suspension by id
+    fn find(&self, suspension_id: i32) -> RepoResult<Option<CompanySuspension>> {
+        acl::check(&*self.acl, Resource::CompanySuspensions, Action::Read, self, None)?;
+
+        let query = DslCompanySuspensions::company_suspensions.find(suspension_id);
+
+        query.get_result::<CompanySuspension>(self.db_conn).optional().map_err(|e| {
+            Error::from(e)
+                .context(format!("Find company suspension with id {} error occurred", suspension_id))
+                .into()
+        })
+    }
+
+    /// Returns suspensions active on the date
+    fn find_active_on(&self, date: NaiveDate) -> RepoResult<Vec<CompanySuspension>> {
+        acl::check(&*self.acl, Resource::CompanySuspensions, Action::Read, self, None)?;
+
+        let query = DslCompanySuspensions::company_suspensions
+            .filter(DslCompanySuspensions::starts_on.le(date))
+            .filter(DslCompanySuspensions::ends_on.ge(date))
+            .order(DslCompanySuspensions::id);
+
+        query.get_results::<CompanySuspension>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!("Find company suspensions active on {} error occurred", date))
+                .into()
+        })
+    }
+
+    /// Create a new company suspension
+    fn create(&self, payload: NewCompanySuspension) -> RepoResult<CompanySuspension> {
+        acl::check(&*self.acl, Resource::CompanySuspensions, Action::Moderate, self, None)?;
+
+        let query = diesel::insert_into(DslCompanySuspensions::company_suspensions).values(&payload);
+
+        query.get_result::<CompanySuspension>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!("Create a new company suspension {:?} error occurred", payload))
+                .into()
+        })
+    }
+
+    /// Update a company suspension
+    fn update(&self, suspension_id: i32, payload: UpdateCompanySuspension) -> RepoResult<CompanySuspension> {
+        acl::check(&*self.acl, Resource::CompanySuspensions, Action::Moderate, self, None)?;
+
+        let filter = DslCompanySuspensions::company_suspensions.filter(DslCompanySuspensions::id.eq(suspension_id));
+        let query = diesel::update(filter).set(&payload);
+
+        query.get_result::<CompanySuspension>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!(
+                    "Update company suspension {} with payload {:?} error occurred",
+                    suspension_id, payload
+                ))
+                .into()
+        })
+    }
+
+    /// Delete a company suspension
+    fn delete(&self, suspension_id: i32) -> RepoResult<CompanySuspension> {
+        acl::check(&*self.acl, Resource::CompanySuspensions, Action::Moderate, self, None)?;
+
+        let filtered = DslCompanySuspensions::company_suspensions.filter(DslCompanySuspensions::id.eq(suspension_id));
+        let query = diesel::delete(filtered);
+
+        query.get_result::<CompanySuspension>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!("Delete company suspension {} error occurred", suspension_id))
+                .into()
+        })
+    }
+}
+
+impl<'a, T: DbConnection> CheckScope<Scope, CompanySuspension> for CompanySuspensionsRepoImpl<'a, T> {
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&CompanySuspension>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
@@ -3,6 +3,7 @@ use failure::Fail;
 use stq_cache::cache::CacheSingle;
 
 use models::Country;
+use request_context::log_line;
 
 pub struct CountryCacheImpl<C>
 where
@@ -20,28 +21,34 @@ where
     }
 
     pub fn get(&self) -> Option<Country> {
-        debug!("Getting country from CountryCache");
+        debug!("{}", log_line(&format!("Getting country from CountryCache")));
 
         self.cache.get().unwrap_or_else(|err| {
-            error!("{}", err.context("Failed to get country from CountryCache"));
+            error!(
+                "{}",
+                log_line(&format!("{}", err.context("Failed to get country from CountryCache")))
+            );
             None
         })
     }
 
     pub fn remove(&self) -> bool {
-        debug!("Removing country from CountryCache");
+        debug!("{}", log_line(&format!("Removing country from CountryCache")));
 
         self.cache.remove().unwrap_or_else(|err| {
-            error!("{}", err.context("Failed to remove country from CountryCache"));
+            error!(
+                "{}",
+                log_line(&format!("{}", err.context("Failed to remove country from CountryCache")))
+            );
             false
         })
     }
 
     pub fn set(&self, country: &Country) {
-        debug!("Setting country in CountryCache");
+        debug!("{}", log_line(&format!("Setting country in CountryCache")));
 
         self.cache.set(country.clone()).unwrap_or_else(|err| {
-            error!("{}", err.context("Failed to set country in CountryCache"));
+            error!("{}", log_line(&format!("{}", err.context("Failed to set country in CountryCache"))));
         })
     }
 }
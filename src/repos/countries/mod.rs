@@ -1,11 +1,8 @@
 //! Repos contains all info about working with countries
 use diesel;
-use diesel::connection::AnsiTransactionManager;
-use diesel::pg::Pg;
 use diesel::prelude::*;
 use diesel::query_dsl::RunQueryDsl;
 use diesel::sql_types::Bool;
-use diesel::Connection;
 use errors::Error;
 use failure::Error as FailureError;
 use std::sync::Arc;
@@ -13,10 +10,11 @@ use stq_cache::cache::CacheSingle;
 use stq_types::{self, Alpha3, CountryLabel, UserId};
 
 use models::authorization::*;
-use models::{get_country, Country, NewCountry, RawCountry};
+use models::{get_country, CountriesImportReport, Country, IsoCountry, NewCountry, RawCountry};
 use repos::acl;
 use repos::legacy_acl::{Acl, CheckScope};
-use repos::types::RepoResult;
+use repos::types::{DbConnection, RepoResult};
+use request_context::log_line;
 use schema::countries::dsl::*;
 
 pub mod cache;
@@ -35,10 +33,10 @@ pub enum CountrySearch {
 pub struct CountriesRepoImpl<'a, C, T>
 where
     C: CacheSingle<Country>,
-    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    T: DbConnection,
 {
     pub db_conn: &'a T,
-    pub acl: Box<Acl<Resource, Action, Scope, FailureError, Country>>,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, Country>>,
     pub cache: Arc<CountryCacheImpl<C>>,
 }
 
@@ -57,14 +55,20 @@ pub trait CountriesRepo {
 
     /// Returns all countries as a vec
     fn get_all_flatten(&self) -> RepoResult<Vec<Country>>;
+
+    /// Upserts ISO-3166 countries, countries already in the tree keep their parents
+    fn import(&self, payload: Vec<IsoCountry>) -> RepoResult<CountriesImportReport>;
+
+    /// Moves the countries under the parent
+    fn set_parent(&self, countries_arg: Vec<Alpha3>, parent_arg: Alpha3) -> RepoResult<()>;
 }
 
 impl<'a, C, T> CountriesRepoImpl<'a, C, T>
 where
     C: CacheSingle<Country>,
-    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    T: DbConnection,
 {
-    pub fn new(db_conn: &'a T, acl: Box<Acl<Resource, Action, Scope, FailureError, Country>>, cache: Arc<CountryCacheImpl<C>>) -> Self {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, Country>>, cache: Arc<CountryCacheImpl<C>>) -> Self {
         Self { db_conn, acl, cache }
     }
 }
@@ -72,17 +76,17 @@ where
 impl<'a, C, T> CountriesRepo for CountriesRepoImpl<'a, C, T>
 where
     C: CacheSingle<Country>,
-    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    T: DbConnection,
 {
     /// Find specific country by label
     fn find(&self, arg: Alpha3) -> RepoResult<Option<Country>> {
-        debug!("Find in countries with aplha3 {}.", arg);
+        debug!("{}", log_line(&format!("Find in countries with aplha3 {}.", arg)));
         acl::check(&*self.acl, Resource::Countries, Action::Read, self, None)?;
         self.get_all().map(|root| get_country(&root, &arg))
     }
 
     fn find_by(&self, search: CountrySearch) -> RepoResult<Option<Country>> {
-        debug!("Get countries by search: {:?}.", search);
+        debug!("{}", log_line(&format!("Get countries by search: {:?}.", search)));
 
         let search_exp: Box<BoxableExpression<countries, _, SqlType = Bool>> = match search.clone() {
             CountrySearch::Label(value) => Box::new(label.eq(value)),
@@ -110,7 +114,7 @@ where
 
     /// Creates new country
     fn create(&self, payload: NewCountry) -> RepoResult<Country> {
-        debug!("Create new country {:?}.", payload);
+        debug!("{}", log_line(&format!("Create new country {:?}.", payload)));
         self.cache.remove();
         let query = diesel::insert_into(countries).values(&payload);
         query
@@ -123,10 +127,10 @@ where
 
     fn get_all(&self) -> RepoResult<Country> {
         if let Some(country) = self.cache.get() {
-            debug!("Get all countries from cache request.");
+            debug!("{}", log_line(&format!("Get all countries from cache request.")));
             Ok(country)
         } else {
-            debug!("Get all countries from db request.");
+            debug!("{}", log_line(&format!("Get all countries from db request.")));
             acl::check(&*self.acl, Resource::Countries, Action::Read, self, None)
                 .and_then(|_| {
                     let countries_ = countries.load::<RawCountry>(self.db_conn)?;
@@ -144,7 +148,7 @@ where
 
     /// Returns all countries as a vec
     fn get_all_flatten(&self) -> RepoResult<Vec<Country>> {
-        debug!("Get all countries as vec from db request.");
+        debug!("{}", log_line(&format!("Get all countries as vec from db request.")));
         acl::check(&*self.acl, Resource::Countries, Action::Read, self, None)
             .and_then(|_| {
                 let countries_ = countries.load::<RawCountry>(self.db_conn)?;
@@ -153,6 +157,79 @@ where
             })
             .map_err(|e: FailureError| e.context("Get all flatten countries error occured").into())
     }
+
+    /// Upserts ISO-3166 countries, countries already in the tree keep their parents
+    fn import(&self, payload: Vec<IsoCountry>) -> RepoResult<CountriesImportReport> {
+        debug!("{}", log_line(&format!("Import {} ISO-3166 countries.", payload.len())));
+        acl::check(&*self.acl, Resource::Countries, Action::Create, self, None)?;
+        acl::check(&*self.acl, Resource::Countries, Action::Update, self, None)?;
+        self.cache.remove();
+
+        let run = || {
+            let existing = countries.load::<RawCountry>(self.db_conn)?;
+            let root = existing
+                .iter()
+                .find(|country| country.parent.is_none())
+                .map(|country| country.alpha3.clone())
+                .ok_or_else(|| format_err!("Countries tree has no root"))?;
+
+            let mut report = CountriesImportReport::default();
+            for iso_country in payload {
+                let iso_label = CountryLabel(iso_country.name);
+                match existing.iter().find(|country| country.alpha3 == iso_country.alpha3) {
+                    Some(country) => {
+                        if country.label == iso_label && country.alpha2 == iso_country.alpha2 && country.numeric == iso_country.numeric {
+                            report.skipped += 1;
+                            continue;
+                        }
+
+                        let filtered = countries.filter(alpha3.eq(iso_country.alpha3));
+                        diesel::update(filtered)
+                            .set((label.eq(iso_label), alpha2.eq(iso_country.alpha2), numeric.eq(iso_country.numeric)))
+                            .execute(self.db_conn)?;
+                        report.updated += 1;
+                    }
+                    None => {
+                        let new_country = NewCountry {
+                            label: iso_label,
+                            level: Country::COUNTRY_LEVEL,
+                            alpha2: iso_country.alpha2,
+                            alpha3: iso_country.alpha3,
+                            numeric: iso_country.numeric,
+                            parent: Some(iso_country.region.unwrap_or_else(|| root.clone())),
+                        };
+                        diesel::insert_into(countries).values(&new_country).execute(self.db_conn)?;
+                        report.created += 1;
+                    }
+                }
+            }
+
+            Ok(report)
+        };
+
+        run().map_err(|e: FailureError| e.context("Import ISO-3166 countries error occured").into())
+    }
+
+    /// Moves the countries under the parent
+    fn set_parent(&self, countries_arg: Vec<Alpha3>, parent_arg: Alpha3) -> RepoResult<()> {
+        debug!(
+            "{}",
+            log_line(&format!("Set parent {} of countries {:?}.", parent_arg, countries_arg))
+        );
+        acl::check(&*self.acl, Resource::Countries, Action::Update, self, None)?;
+        self.cache.remove();
+
+        let filtered = countries.filter(alpha3.eq_any(countries_arg.clone()));
+        diesel::update(filtered)
+            .set(parent.eq(Some(parent_arg.clone())))
+            .execute(self.db_conn)
+            .map(|_| ())
+            .map_err(|e| Error::from(e).into())
+            .map_err(|e: FailureError| {
+                e.context(format!("Set parent {} of countries {:?} error occured", parent_arg, countries_arg))
+                    .into()
+            })
+    }
 }
 
 fn create_tree(countries_: &[RawCountry], parent_arg: Option<Alpha3>) -> RepoResult<Vec<Country>> {
@@ -306,7 +383,7 @@ pub fn contains_country_code(country: &Country, country_code: &Alpha3) -> bool {
 impl<'a, C, T> CheckScope<Scope, Country> for CountriesRepoImpl<'a, C, T>
 where
     C: CacheSingle<Country>,
-    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    T: DbConnection,
 {
     fn is_in_scope(&self, _user_label: UserId, scope: &Scope, _obj: Option<&Country>) -> bool {
         match *scope {
@@ -486,5 +563,4 @@ mod tests {
         country = remove_unused_countries(country, &used_codes);
         assert_eq!(country.children.len(), 2, "Mock countries not contains 2 regions after run test");
     }
-
 }
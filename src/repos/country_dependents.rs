@@ -0,0 +1,149 @@
+//! Repo for columns keeping country codes copied from the countries tree,
+//! they are read and updated in batches by the refresh of country dependents
+
+use diesel;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use errors::Error;
+use failure::Error as FailureError;
+use failure::Fail;
+use serde_json;
+
+use stq_types::{Alpha3, UserId};
+
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::types::{DbConnection, RepoResult};
+use models::authorization::*;
+use models::{CountryCodesRow, CountryDependent};
+use schema::companies::dsl as DslCompanies;
+use schema::packages::dsl as DslPackages;
+use schema::products::dsl as DslProducts;
+
+/// Country dependents repository
+pub trait CountryDependentsRepo {
+    /// Returns number of rows of the dependent
+    fn count(&self, dependent: CountryDependent) -> RepoResult<i64>;
+
+    /// Returns at most `batch_size` rows of the dependent with id greater than `after_id`, ordered by id.
+    /// Rows are locked until the end of the transaction, so the codes are not changed between the read and `set_codes`
+    fn get_batch(&self, dependent: CountryDependent, after_id: Option<i32>, batch_size: i64) -> RepoResult<Vec<CountryCodesRow>>;
+
+    /// Replaces country codes of the row
+    fn set_codes(&self, dependent: CountryDependent, id: i32, codes: Vec<Alpha3>) -> RepoResult<()>;
+}
+
+/// Implementation of CountryDependents trait
+pub struct CountryDependentsRepoImpl<'a, T: DbConnection> {
+    pub db_conn: &'a T,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, ()>>,
+}
+
+impl<'a, T: DbConnection> CountryDependentsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, ()>>) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+fn to_rows(dependent: CountryDependent, records: Vec<(i32, serde_json::Value)>) -> RepoResult<Vec<CountryCodesRow>> {
+    records
+        .into_iter()
+        .map(|(id, codes)| {
+            serde_json::from_value(codes)
+                .map(|codes| CountryCodesRow { id, codes })
+                .map_err(|e| {
+                    e.context(format!("Can not parse {} of row {}", dependent, id))
+                        .context(Error::Parse)
+                        .into()
+                })
+        })
+        .collect()
+}
+
+impl<'a, T: DbConnection> CountryDependentsRepo for CountryDependentsRepoImpl<'a, T> {
+    /// Returns number of rows of the dependent
+    fn count(&self, dependent: CountryDependent) -> RepoResult<i64> {
+        acl::check(&*self.acl, dependent.resource(), Action::Read, self, None)?;
+
+        let count = match dependent {
+            CountryDependent::CompaniesDeliveriesFrom => DslCompanies::companies.count().get_result::<i64>(self.db_conn),
+            CountryDependent::PackagesDeliveriesTo => DslPackages::packages.count().get_result::<i64>(self.db_conn),
+            CountryDependent::ProductsDeliveriesTo => DslProducts::products.count().get_result::<i64>(self.db_conn),
+        };
+
+        count.map_err(|e| Error::from(e).context(format!("Count rows of {} error occurred", dependent)).into())
+    }
+
+    /// Returns at most `batch_size` rows of the dependent with id greater than `after_id`, ordered by id.
+    /// Rows are locked until the end of the transaction, so the codes are not changed between the read and `set_codes`
+    fn get_batch(&self, dependent: CountryDependent, after_id: Option<i32>, batch_size: i64) -> RepoResult<Vec<CountryCodesRow>> {
+        acl::check(&*self.acl, dependent.resource(), Action::Read, self, None)?;
+
+        let after_id = after_id.unwrap_or(0);
+        let records = match dependent {
+            CountryDependent::CompaniesDeliveriesFrom => DslCompanies::companies
+                .select((DslCompanies::id, DslCompanies::deliveries_from))
+                .filter(DslCompanies::id.gt(after_id))
+                .order(DslCompanies::id)
+                .limit(batch_size)
+                .for_update()
+                .get_results::<(i32, serde_json::Value)>(self.db_conn),
+            CountryDependent::PackagesDeliveriesTo => DslPackages::packages
+                .select((DslPackages::id, DslPackages::deliveries_to))
+                .filter(DslPackages::id.gt(after_id))
+                .order(DslPackages::id)
+                .limit(batch_size)
+                .for_update()
+                .get_results::<(i32, serde_json::Value)>(self.db_conn),
+            CountryDependent::ProductsDeliveriesTo => DslProducts::products
+                .select((DslProducts::id, DslProducts::deliveries_to))
+                .filter(DslProducts::id.gt(after_id))
+                .order(DslProducts::id)
+                .limit(batch_size)
+                .for_update()
+                .get_results::<(i32, serde_json::Value)>(self.db_conn),
+        };
+
+        records
+            .map_err(|e| Error::from(e).into())
+            .and_then(|records| to_rows(dependent, records))
+            .map_err(|e: FailureError| {
+                e.context(format!("Get batch of {} after id {} error occurred", dependent, after_id))
+                    .into()
+            })
+    }
+
+    /// Replaces country codes of the row
+    fn set_codes(&self, dependent: CountryDependent, id: i32, codes: Vec<Alpha3>) -> RepoResult<()> {
+        acl::check(&*self.acl, dependent.resource(), Action::Update, self, None)?;
+
+        let codes = serde_json::to_value(codes).map_err(|e| e.context("Can not parse country codes from value").context(Error::Parse))?;
+        let updated = match dependent {
+            CountryDependent::CompaniesDeliveriesFrom => diesel::update(DslCompanies::companies.filter(DslCompanies::id.eq(id)))
+                .set(DslCompanies::deliveries_from.eq(codes))
+                .execute(self.db_conn),
+            CountryDependent::PackagesDeliveriesTo => diesel::update(DslPackages::packages.filter(DslPackages::id.eq(id)))
+                .set(DslPackages::deliveries_to.eq(codes))
+                .execute(self.db_conn),
+            CountryDependent::ProductsDeliveriesTo => diesel::update(DslProducts::products.filter(DslProducts::id.eq(id)))
+                .set(DslProducts::deliveries_to.eq(codes))
+                .execute(self.db_conn),
+        };
+
+        updated.map(|_| ()).map_err(|e| {
+            Error::from(e)
+                .context(format!("Set codes of {} row {} error occurred", dependent, id))
+                .into()
+        })
+    }
+}
+
+impl<'a, T: DbConnection> CheckScope<Scope, ()> for CountryDependentsRepoImpl<'a, T> {
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&()>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//! Repo for country_regulations table. Regulations limit parcels the destination country accepts
+//! independently of the limits declared by carriers
+
+use diesel;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use errors::Error;
+use failure::Error as FailureError;
+
+use stq_types::{Alpha3, UserId};
+
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::types::{DbConnection, RepoResult};
+use models::authorization::*;
+use models::{CountryRegulation, CountryRegulationRaw, SetCountryRegulation};
+use schema::country_regulations::dsl as DslCountryRegulations;
+
+/// Country regulations repository
+pub trait CountryRegulationsRepo {
+    /// Returns regulations of all countries
+    fn list(&self) -> RepoResult<Vec<CountryRegulation>>;
+
+    /// Returns regulations of the country
+    fn get(&self, alpha3: Alpha3) -> RepoResult<Option<CountryRegulation>>;
+
+    /// Sets regulations of the country replacing the previous ones
+    fn set(&self, alpha3: Alpha3, payload: SetCountryRegulation) -> RepoResult<CountryRegulation>;
+
+    /// Delete regulations of the country
+    fn delete(&self, alpha3: Alpha3) -> RepoResult<Option<CountryRegulation>>;
+}
+
+/// Implementation of CountryRegulations trait
+pub struct CountryRegulationsRepoImpl<'a, T: DbConnection> {
+    pub db_conn: &'a T,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, CountryRegulation>>,
+}
+
+impl<'a, T: DbConnection> CountryRegulationsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, CountryRegulation>>) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: DbConnection> CountryRegulationsRepo for CountryRegulationsRepoImpl<'a, T> {
+    /// Returns regulations of all countries
+    fn list(&self) -> RepoResult<Vec<CountryRegulation>> {
+        acl::check(&*self.acl, Resource::CountryRegulations, Action::Read, self, None)?;
+
+        let query = DslCountryRegulations::country_regulations.order(DslCountryRegulations::alpha3);
+
+        query
+            .get_results::<CountryRegulationRaw>(self.db_conn)
+            .map_err(|e| Error::from(e).into())
+            .and_then(|records| {
+                records
+                    .into_iter()
+                    .map(CountryRegulationRaw::to_model)
+                    .collect::<RepoResult<Vec<_>>>()
+            })
+            .map_err(|e: FailureError| e.context("list of country regulations error occurred").into())
+    }
+
+    /// Returns regulations of the country
+    fn get(&self, alpha3: Alpha3) -> RepoResult<Option<CountryRegulation>> {
+        acl::check(&*self.acl, Resource::CountryRegulations, Action::Read, self, None)?;
+
+        let query = DslCountryRegulations::country_regulations.find(alpha3.clone());
+
+        query
+            .get_result::<CountryRegulationRaw>(self.db_conn)
+            .optional()
+            .map_err(|e| Error::from(e).into())
+            .and_then(|record| match record {
+                Some(record) => record.to_model().map(Some),
+                None => Ok(None),
+            })
+            .map_err(|e: FailureError| e.context(format!("Get regulations of country {} error occurred", alpha3)).into())
+    }
+
+    /// Sets regulations of the country replacing the previous ones
+    fn set(&self, alpha3: Alpha3, payload: SetCountryRegulation) -> RepoResult<CountryRegulation> {
+        acl::check(&*self.acl, Resource::CountryRegulations, Action::Update, self, None)?;
+
+        let record = payload.to_raw(alpha3.clone())?;
+
+        let query = diesel::insert_into(DslCountryRegulations::country_regulations)
+            .values(&record)
+            .on_conflict(DslCountryRegulations::alpha3)
+            .do_update()
+            .set((
+                DslCountryRegulations::max_weight_g.eq(record.max_weight_g),
+                DslCountryRegulations::max_dimension_cm.eq(record.max_dimension_cm),
+                DslCountryRegulations::prohibited_hazard_classes.eq(&record.prohibited_hazard_classes),
+                DslCountryRegulations::source.eq(&record.source),
+            ));
+
+        query
+            .get_result::<CountryRegulationRaw>(self.db_conn)
+            .map_err(|e| Error::from(e).into())
+            .and_then(CountryRegulationRaw::to_model)
+            .map_err(|e: FailureError| e.context(format!("Set regulations of country {} error occurred", alpha3)).into())
+    }
+
+    /// Delete regulations of the country
+    fn delete(&self, alpha3: Alpha3) -> RepoResult<Option<CountryRegulation>> {
+        acl::check(&*self.acl, Resource::CountryRegulations, Action::Delete, self, None)?;
+
+        let filtered = DslCountryRegulations::country_regulations.filter(DslCountryRegulations::alpha3.eq(alpha3.clone()));
+        let query = diesel::delete(filtered);
+
+        query
+            .get_result::<CountryRegulationRaw>(self.db_conn)
+            .optional()
+            .map_err(|e| Error::from(e).into())
+            .and_then(|record| match record {
+                Some(record) => record.to_model().map(Some),
+                None => Ok(None),
+            })
+            .map_err(|e: FailureError| e.context(format!("Delete regulations of country {} error occurred", alpha3)).into())
+    }
+}
+
+impl<'a, T: DbConnection> CheckScope<Scope, CountryRegulation> for CountryRegulationsRepoImpl<'a, T> {
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&CountryRegulation>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
@@ -0,0 +1,138 @@
+//! Repo for country_subdivisions table. Subdivisions are referenced by the rates and the addresses
+//! where prices within a country differ
+
+use diesel;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use errors::Error;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::{Alpha3, UserId};
+
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::types::{DbConnection, RepoResult};
+use models::authorization::*;
+use models::{CountrySubdivision, UpdateCountrySubdivision};
+use schema::country_subdivisions::dsl as DslCountrySubdivisions;
+
+/// Country subdivisions repository, subdivisions are managed with the countries
+pub trait CountrySubdivisionsRepo {
+    /// Returns subdivisions of the country ordered by code
+    fn list(&self, alpha3: Alpha3) -> RepoResult<Vec<CountrySubdivision>>;
+
+    /// Returns subdivisions of all countries ordered by code
+    fn list_all(&self) -> RepoResult<Vec<CountrySubdivision>>;
+
+    /// Returns subdivision by ISO 3166-2 code
+    fn get(&self, code: String) -> RepoResult<Option<CountrySubdivision>>;
+
+    /// Creates subdivision
+    fn create(&self, payload: CountrySubdivision) -> RepoResult<CountrySubdivision>;
+
+    /// Renames subdivision
+    fn update(&self, code: String, payload: UpdateCountrySubdivision) -> RepoResult<CountrySubdivision>;
+
+    /// Deletes subdivision, its rates are deleted and the addresses in it are left without a subdivision
+    fn delete(&self, code: String) -> RepoResult<CountrySubdivision>;
+}
+
+/// Implementation of CountrySubdivisions trait
+pub struct CountrySubdivisionsRepoImpl<'a, T: DbConnection> {
+    pub db_conn: &'a T,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, CountrySubdivision>>,
+}
+
+impl<'a, T: DbConnection> CountrySubdivisionsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, CountrySubdivision>>) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: DbConnection> CountrySubdivisionsRepo for CountrySubdivisionsRepoImpl<'a, T> {
+    /// Returns subdivisions of the country ordered by code
+    fn list(&self, alpha3: Alpha3) -> RepoResult<Vec<CountrySubdivision>> {
+        acl::check(&*self.acl, Resource::Countries, Action::Read, self, None)?;
+
+        let query = DslCountrySubdivisions::country_subdivisions
+            .filter(DslCountrySubdivisions::alpha3.eq(alpha3.clone()))
+            .order(DslCountrySubdivisions::code);
+
+        query.get_results::<CountrySubdivision>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!("List subdivisions of country {} error occurred", alpha3))
+                .into()
+        })
+    }
+
+    /// Returns subdivisions of all countries ordered by code
+    fn list_all(&self) -> RepoResult<Vec<CountrySubdivision>> {
+        acl::check(&*self.acl, Resource::Countries, Action::Read, self, None)?;
+
+        let query = DslCountrySubdivisions::country_subdivisions.order(DslCountrySubdivisions::code);
+
+        query
+            .get_results::<CountrySubdivision>(self.db_conn)
+            .map_err(|e| Error::from(e).context("List subdivisions error occurred").into())
+    }
+
+    /// Returns subdivision by ISO 3166-2 code
+    fn get(&self, code: String) -> RepoResult<Option<CountrySubdivision>> {
+        acl::check(&*self.acl, Resource::Countries, Action::Read, self, None)?;
+
+        let query = DslCountrySubdivisions::country_subdivisions.find(code.clone());
+
+        query
+            .get_result::<CountrySubdivision>(self.db_conn)
+            .optional()
+            .map_err(|e| Error::from(e).context(format!("Get subdivision {} error occurred", code)).into())
+    }
+
+    /// Creates subdivision
+    fn create(&self, payload: CountrySubdivision) -> RepoResult<CountrySubdivision> {
+        acl::check(&*self.acl, Resource::Countries, Action::Create, self, Some(&payload))?;
+
+        let query = diesel::insert_into(DslCountrySubdivisions::country_subdivisions).values(&payload);
+
+        query.get_result::<CountrySubdivision>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!("Create subdivision {:?} error occurred", payload))
+                .into()
+        })
+    }
+
+    /// Renames subdivision
+    fn update(&self, code: String, payload: UpdateCountrySubdivision) -> RepoResult<CountrySubdivision> {
+        acl::check(&*self.acl, Resource::Countries, Action::Update, self, None)?;
+
+        let filtered = DslCountrySubdivisions::country_subdivisions.filter(DslCountrySubdivisions::code.eq(code.clone()));
+        let query = diesel::update(filtered).set(&payload);
+
+        query
+            .get_result::<CountrySubdivision>(self.db_conn)
+            .map_err(|e| Error::from(e).context(format!("Update subdivision {} error occurred", code)).into())
+    }
+
+    /// Deletes subdivision, its rates are deleted and the addresses in it are left without a subdivision
+    fn delete(&self, code: String) -> RepoResult<CountrySubdivision> {
+        acl::check(&*self.acl, Resource::Countries, Action::Delete, self, None)?;
+
+        let filtered = DslCountrySubdivisions::country_subdivisions.filter(DslCountrySubdivisions::code.eq(code.clone()));
+        let query = diesel::delete(filtered);
+
+        query
+            .get_result::<CountrySubdivision>(self.db_conn)
+            .map_err(|e| Error::from(e).context(format!("Delete subdivision {} error occurred", code)).into())
+    }
+}
+
+impl<'a, T: DbConnection> CheckScope<Scope, CountrySubdivision> for CountrySubdivisionsRepoImpl<'a, T> {
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&CountrySubdivision>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! Repo for customs_info table. Customs info is the customs data of the base product
+//! used for the declarations of cross-border shipments
+
+use diesel;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use errors::Error;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::{BaseProductId, StoreId, UserId};
+
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::types::{DbConnection, RepoResult};
+use super::user_roles::get_user_roles_data;
+use models::authorization::*;
+use models::{CustomsInfo, SetCustomsInfo};
+use schema::customs_info::dsl as DslCustomsInfo;
+
+/// Customs info repository, ACL objects are the stores the base products belong to
+pub trait CustomsInfoRepo {
+    /// Returns customs info of the base product
+    fn get(&self, base_product_id: BaseProductId) -> RepoResult<Option<CustomsInfo>>;
+
+    /// Sets customs info of the base product replacing the previous one
+    fn set(&self, base_product_id: BaseProductId, payload: SetCustomsInfo) -> RepoResult<CustomsInfo>;
+
+    /// Delete customs info of the base product
+    fn delete(&self, base_product_id: BaseProductId) -> RepoResult<Option<CustomsInfo>>;
+}
+
+/// Implementation of CustomsInfo trait
+pub struct CustomsInfoRepoImpl<'a, T: DbConnection> {
+    pub db_conn: &'a T,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, StoreId>>,
+}
+
+impl<'a, T: DbConnection> CustomsInfoRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, StoreId>>) -> Self {
+        Self { db_conn, acl }
+    }
+
+    fn find(&self, base_product_id: BaseProductId) -> RepoResult<Option<CustomsInfo>> {
+        let query = DslCustomsInfo::customs_info.filter(DslCustomsInfo::base_product_id.eq(base_product_id));
+
+        query.get_result::<CustomsInfo>(self.db_conn).optional().map_err(|e| {
+            Error::from(e)
+                .context(format!("Find customs info of base product {} error occurred", base_product_id))
+                .into()
+        })
+    }
+}
+
+impl<'a, T: DbConnection> CustomsInfoRepo for CustomsInfoRepoImpl<'a, T> {
+    /// Returns customs info of the base product
+    fn get(&self, base_product_id: BaseProductId) -> RepoResult<Option<CustomsInfo>> {
+        let customs_info = self.find(base_product_id)?;
+        if let Some(ref customs_info) = customs_info {
+            acl::check(&*self.acl, Resource::CustomsInfo, Action::Read, self, Some(&customs_info.store_id))?;
+        }
+
+        Ok(customs_info)
+    }
+
+    /// Sets customs info of the base product replacing the previous one
+    fn set(&self, base_product_id: BaseProductId, payload: SetCustomsInfo) -> RepoResult<CustomsInfo> {
+        // customs info of the base product of another store must not be taken over
+        if let Some(previous) = self.find(base_product_id)? {
+            acl::check(&*self.acl, Resource::CustomsInfo, Action::Update, self, Some(&previous.store_id))?;
+        }
+        acl::check(&*self.acl, Resource::CustomsInfo, Action::Update, self, Some(&payload.store_id))?;
+
+        let record = payload.to_new(base_product_id);
+        let query = diesel::insert_into(DslCustomsInfo::customs_info)
+            .values(&record)
+            .on_conflict(DslCustomsInfo::base_product_id)
+            .do_update()
+            .set((
+                DslCustomsInfo::store_id.eq(record.store_id),
+                DslCustomsInfo::hs_code.eq(&record.hs_code),
+                DslCustomsInfo::description.eq(&record.description),
+                DslCustomsInfo::declared_value.eq(record.declared_value),
+                DslCustomsInfo::currency.eq(record.currency),
+                DslCustomsInfo::origin_country.eq(&record.origin_country),
+            ));
+
+        query.get_result::<CustomsInfo>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!(
+                    "Set customs info {:?} of base product {} error occurred",
+                    record, base_product_id
+                ))
+                .into()
+        })
+    }
+
+    /// Delete customs info of the base product
+    fn delete(&self, base_product_id: BaseProductId) -> RepoResult<Option<CustomsInfo>> {
+        let customs_info = match self.find(base_product_id)? {
+            Some(customs_info) => customs_info,
+            None => return Ok(None),
+        };
+        acl::check(
+            &*self.acl,
+            Resource::CustomsInfo,
+            Action::Delete,
+            self,
+            Some(&customs_info.store_id),
+        )?;
+
+        let filtered = DslCustomsInfo::customs_info.filter(DslCustomsInfo::base_product_id.eq(base_product_id));
+        let query = diesel::delete(filtered);
+
+        query.get_result::<CustomsInfo>(self.db_conn).optional().map_err(|e| {
+            Error::from(e)
+                .context(format!("Delete customs info of base product {} error occurred", base_product_id))
+                .into()
+        })
+    }
+}
+
+impl<'a, T: DbConnection> CheckScope<Scope, StoreId> for CustomsInfoRepoImpl<'a, T> {
+    fn is_in_scope(&self, user_id_arg: UserId, scope: &Scope, obj: Option<&StoreId>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => {
+                if let Some(store_id) = obj {
+                    get_user_roles_data(self.db_conn, user_id_arg)
+                        .map(|roles_data| roles_data.iter().any(|role_data| role_data.manages_store_shipping(*store_id)))
+                        .unwrap_or_else(|_: FailureError| false)
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,97 @@
+//! Repo for delivery_quotes table. Quotes are written once, expired quotes are answered as gone
+//! until the background cleanup purges them
+
+use std::time::SystemTime;
+
+use diesel;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use errors::Error;
+use failure::Error as FailureError;
+use failure::Fail;
+use uuid::Uuid;
+
+use stq_types::UserId;
+
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::types::{DbConnection, RepoResult};
+use models::authorization::*;
+use models::{DeliveryQuote, NewDeliveryQuote};
+use schema::delivery_quotes::dsl as DslDeliveryQuotes;
+
+/// Delivery quotes repository
+pub trait DeliveryQuotesRepo {
+    /// Find specific quote by id
+    fn find(&self, quote_id: Uuid) -> RepoResult<Option<DeliveryQuote>>;
+
+    /// Create a new quote
+    fn create(&self, payload: NewDeliveryQuote) -> RepoResult<DeliveryQuote>;
+
+    /// Delete quotes expired before the time, returns the number of deleted quotes
+    fn delete_expired(&self, expired_before: SystemTime) -> RepoResult<usize>;
+}
+
+/// Implementation of DeliveryQuotes trait
+pub struct DeliveryQuotesRepoImpl<'a, T: DbConnection> {
+    pub db_conn: &'a T,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, DeliveryQuote>>,
+}
+
+impl<'a, T: DbConnection> DeliveryQuotesRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, DeliveryQuote>>) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: DbConnection> DeliveryQuotesRepo for DeliveryQuotesRepoImpl<'a, T> {
+    /// Find specific quote by id
+    fn find(&self, quote_id: Uuid) -> RepoResult<Option<DeliveryQuote>> {
+        acl::check(&*self.acl, Resource::DeliveryQuotes, Action::Read, self, None)?;
+
+        let query = DslDeliveryQuotes::delivery_quotes.find(quote_id);
+
+        query.get_result::<DeliveryQuote>(self.db_conn).optional().map_err(|e| {
+            Error::from(e)
+                .context(format!("Find delivery quote with id {} error occurred", quote_id))
+                .into()
+        })
+    }
+
+    /// Create a new quote
+    fn create(&self, payload: NewDeliveryQuote) -> RepoResult<DeliveryQuote> {
+        acl::check(&*self.acl, Resource::DeliveryQuotes, Action::Create, self, None)?;
+
+        let query = diesel::insert_into(DslDeliveryQuotes::delivery_quotes).values(&payload);
+
+        query.get_result::<DeliveryQuote>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!("Create a new delivery quote {:?} error occurred", payload))
+                .into()
+        })
+    }
+
+    /// Delete quotes expired before the time, returns the number of deleted quotes
+    fn delete_expired(&self, expired_before: SystemTime) -> RepoResult<usize> {
+        acl::check(&*self.acl, Resource::DeliveryQuotes, Action::Delete, self, None)?;
+
+        let filtered = DslDeliveryQuotes::delivery_quotes.filter(DslDeliveryQuotes::expires_at.lt(expired_before));
+        let query = diesel::delete(filtered);
+
+        query.execute(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!("Delete delivery quotes expired before {:?} error occurred", expired_before))
+                .into()
+        })
+    }
+}
+
+impl<'a, T: DbConnection> CheckScope<Scope, DeliveryQuote> for DeliveryQuotesRepoImpl<'a, T> {
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&DeliveryQuote>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! Repo for event_outbox table. Events are added in the transaction of the change they describe,
+//! so that an event is never lost between the commit and the message bus
+
+use std::time::SystemTime;
+
+use diesel;
+use diesel::dsl::now;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use errors::Error;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId;
+
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::types::{DbConnection, RepoResult};
+use events::DeliveryEvent;
+use models::authorization::*;
+use models::{NewOutboxEvent, OutboxEvent, TypedJson};
+use schema::event_outbox::dsl as DslEventOutbox;
+
+/// Event outbox repository
+pub trait EventOutboxRepo {
+    /// Adds the events to the outbox
+    fn add(&self, events: Vec<DeliveryEvent>) -> RepoResult<Vec<OutboxEvent>>;
+
+    /// Returns at most `limit` oldest unpublished events created before the time
+    fn list_unpublished(&self, created_before: SystemTime, limit: i64) -> RepoResult<Vec<OutboxEvent>>;
+
+    /// Marks the event as published
+    fn mark_published(&self, id: i32) -> RepoResult<OutboxEvent>;
+
+    /// Records a failed attempt to publish the event
+    fn record_failure(&self, id: i32) -> RepoResult<OutboxEvent>;
+}
+
+/// Implementation of EventOutbox trait
+pub struct EventOutboxRepoImpl<'a, T: DbConnection> {
+    pub db_conn: &'a T,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, OutboxEvent>>,
+}
+
+impl<'a, T: DbConnection> EventOutboxRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, OutboxEvent>>) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: DbConnection> EventOutboxRepo for EventOutboxRepoImpl<'a, T> {
+    /// Adds the events to the outbox
+    fn add(&self, events: Vec<DeliveryEvent>) -> RepoResult<Vec<OutboxEvent>> {
+        acl::check(&*self.acl, Resource::EventOutbox, Action::Create, self, None)?;
+
+        if events.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let records = events
+            .into_iter()
+            .map(|event| NewOutboxEvent { event: TypedJson(event) })
+            .collect::<Vec<_>>();
+        let query = diesel::insert_into(DslEventOutbox::event_outbox).values(&records);
+
+        query.get_results::<OutboxEvent>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!("Add events {:?} to outbox error occurred", records))
+                .into()
+        })
+    }
+
+    /// Returns at most `limit` oldest unpublished events created before the time
+    fn list_unpublished(&self, created_before: SystemTime, limit: i64) -> RepoResult<Vec<OutboxEvent>> {
+        acl::check(&*self.acl, Resource::EventOutbox, Action::Read, self, None)?;
+
+        let query = DslEventOutbox::event_outbox
+            .filter(DslEventOutbox::published_at.is_null())
+            .filter(DslEventOutbox::created_at.lt(created_before))
+            .order(DslEventOutbox::id)
+            .limit(limit);
+
+        query
+            .get_results::<OutboxEvent>(self.db_conn)
+            .map_err(|e| Error::from(e).context("List unpublished events error occurred").into())
+    }
+
+    /// Marks the event as published
+    fn mark_published(&self, id: i32) -> RepoResult<OutboxEvent> {
+        acl::check(&*self.acl, Resource::EventOutbox, Action::Update, self, None)?;
+
+        let filter = DslEventOutbox::event_outbox.filter(DslEventOutbox::id.eq(id));
+        let query = diesel::update(filter).set(DslEventOutbox::published_at.eq(now.nullable()));
+
+        query
+            .get_result::<OutboxEvent>(self.db_conn)
+            .map_err(|e| Error::from(e).context(format!("Mark event {} published error occurred", id)).into())
+    }
+
+    /// Records a failed attempt to publish the event
+    fn record_failure(&self, id: i32) -> RepoResult<OutboxEvent> {
+        acl::check(&*self.acl, Resource::EventOutbox, Action::Update, self, None)?;
+
+        let filter = DslEventOutbox::event_outbox.filter(DslEventOutbox::id.eq(id));
+        let query = diesel::update(filter).set(DslEventOutbox::attempts.eq(DslEventOutbox::attempts + 1));
+
+        query.get_result::<OutboxEvent>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!("Record failed attempt of event {} error occurred", id))
+                .into()
+        })
+    }
+}
+
+impl<'a, T: DbConnection> CheckScope<Scope, OutboxEvent> for EventOutboxRepoImpl<'a, T> {
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&OutboxEvent>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
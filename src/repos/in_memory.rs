@@ -0,0 +1,2500 @@
+//! In-memory repos factory, lets the services and the controller be tested without a database.
+//! Companies, packages, company packages, shipping rates, company suspensions, shipment bookings and user roles are kept in memory,
+//! repos of the other tables are created by the fallback factory. The fallback gets the in-memory connections,
+//! which run no queries, so it has to be a factory of mocks, e.g. the one of the service tests.
+//! Zones are not kept, so packages and shipping rates deliver to their countries only.
+//!
+//! Connections of `InMemoryConnectionManager` do not run queries, but their transactions are honored:
+//! the tables are copied when a transaction or a savepoint starts and restored if it is rolled back.
+//! Transactions of different connections are not isolated from each other.
+
+use std::cell::RefCell;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
+
+use chrono::NaiveDate;
+
+use futures::Stream;
+use futures_cpupool::CpuPool;
+use tokio_core::reactor::Handle;
+
+use diesel::connection::{AnsiTransactionManager, SimpleConnection};
+use diesel::deserialize::QueryableByName;
+use diesel::pg::Pg;
+use diesel::query_builder::{AsQuery, QueryFragment, QueryId};
+use diesel::result::{ConnectionError, Error as DieselError};
+use diesel::sql_types::HasSqlType;
+use diesel::{Connection, ConnectionResult, QueryResult, Queryable};
+use failure::Error as FailureError;
+use failure::Fail;
+use r2d2;
+use r2d2::ManageConnection;
+use serde_json;
+use stq_http;
+
+use stq_types::*;
+
+use carriers::CarriersClient;
+use config::Config;
+use controller::context::{DynamicContext, StaticContext};
+use errors::Error;
+use extras::option::transpose;
+use models::*;
+use replicas::ReplicaRouter;
+use repos::legacy_acl::{Acl, CheckScope, SystemACL};
+use repos::*;
+use request_context::RequestContext;
+use services::Service;
+
+/// Rows of the tables kept in memory with the last ids given by their sequences
+#[derive(Clone, Default)]
+pub struct InMemoryTables {
+    companies: Vec<CompanyRaw>,
+    packages: Vec<PackagesRaw>,
+    companies_packages: Vec<CompaniesPackagesRaw>,
+    shipping_rates: Vec<ShippingRatesRaw>,
+    company_suspensions: Vec<CompanySuspension>,
+    shipment_bookings: Vec<ShipmentBooking>,
+    roles: Vec<UserRole>,
+    last_company_id: i32,
+    last_package_id: i32,
+    last_company_package_id: i32,
+    last_shipping_rates_id: i32,
+    last_company_suspension_id: i32,
+}
+
+impl InMemoryTables {
+    fn next_company_id(&mut self) -> CompanyId {
+        self.last_company_id += 1;
+        CompanyId(self.last_company_id)
+    }
+
+    fn next_package_id(&mut self) -> PackageId {
+        self.last_package_id += 1;
+        PackageId(self.last_package_id)
+    }
+
+    fn next_company_package_id(&mut self) -> CompanyPackageId {
+        self.last_company_package_id += 1;
+        CompanyPackageId(self.last_company_package_id)
+    }
+
+    fn next_shipping_rates_id(&mut self) -> ShippingRatesId {
+        self.last_shipping_rates_id += 1;
+        ShippingRatesId(self.last_shipping_rates_id)
+    }
+
+    fn next_company_suspension_id(&mut self) -> i32 {
+        self.last_company_suspension_id += 1;
+        self.last_company_suspension_id
+    }
+
+    /// Company packages of the company and its suspensions are deleted with it, same as by the foreign keys of the tables
+    fn remove_company(&mut self, id: CompanyId) -> Option<CompanyRaw> {
+        let position = self.companies.iter().position(|raw| raw.id == id)?;
+        self.remove_companies_packages(|company_package| company_package.company_id == id);
+        self.company_suspensions.retain(|suspension| suspension.company_id != id);
+        Some(self.companies.remove(position))
+    }
+
+    /// Company packages of the package are deleted with it, same as by the foreign key of the table
+    fn remove_package(&mut self, id: PackageId) -> Option<PackagesRaw> {
+        let position = self.packages.iter().position(|raw| raw.id == id)?;
+        self.remove_companies_packages(|company_package| company_package.package_id == id);
+        Some(self.packages.remove(position))
+    }
+
+    /// Shipping rates, suspensions and shipment bookings of the company packages are deleted with them,
+    /// same as by the foreign keys of the tables
+    fn remove_companies_packages<P>(&mut self, predicate: P) -> Vec<CompaniesPackagesRaw>
+    where
+        P: Fn(&CompaniesPackagesRaw) -> bool,
+    {
+        let (removed, kept): (Vec<_>, Vec<_>) = self
+            .companies_packages
+            .drain(..)
+            .partition(|company_package| predicate(company_package));
+        self.companies_packages = kept;
+
+        let removed_ids = removed.iter().map(|company_package| company_package.id).collect::<Vec<_>>();
+        self.shipping_rates.retain(|rates| !removed_ids.contains(&rates.company_package_id));
+        self.company_suspensions.retain(|suspension| {
+            suspension
+                .company_package_id
+                .map(|company_package_id| !removed_ids.contains(&company_package_id))
+                .unwrap_or(true)
+        });
+        self.shipment_bookings
+            .retain(|booking| !removed_ids.contains(&booking.company_package_id));
+
+        removed
+    }
+}
+
+type SharedTables = Arc<Mutex<InMemoryTables>>;
+
+/// Tables are never left half written, so the lock poisoned by a panicked test is still usable
+fn lock(tables: &SharedTables) -> MutexGuard<InMemoryTables> {
+    tables.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn user_roles_data(tables: &SharedTables, user_id: UserId) -> Vec<RoleData> {
+    lock(tables)
+        .roles
+        .iter()
+        .filter(|role| role.user_id == user_id)
+        .filter_map(UserRole::role_data)
+        .collect()
+}
+
+fn not_found(message: String) -> FailureError {
+    Error::NotFound.context(message).into()
+}
+
+/// Connection of the in-memory repos, queries fail as there is no database behind it
+pub struct InMemoryConnection {
+    tr: AnsiTransactionManager,
+    tables: SharedTables,
+    /// Tables at the start of the transaction and of each of its savepoints
+    snapshots: RefCell<Vec<InMemoryTables>>,
+}
+
+impl InMemoryConnection {
+    fn new(tables: SharedTables) -> Self {
+        Self {
+            tr: AnsiTransactionManager::default(),
+            tables,
+            snapshots: RefCell::new(vec![]),
+        }
+    }
+}
+
+impl Connection for InMemoryConnection {
+    type Backend = Pg;
+    type TransactionManager = AnsiTransactionManager;
+
+    fn establish(_database_url: &str) -> ConnectionResult<InMemoryConnection> {
+        Ok(InMemoryConnection::new(SharedTables::default()))
+    }
+
+    fn execute(&self, query: &str) -> QueryResult<usize> {
+        Err(DieselError::QueryBuilderError(
+            format!("Query {} is not supported by the in-memory connection", query).into(),
+        ))
+    }
+
+    fn query_by_index<T, U>(&self, _source: T) -> QueryResult<Vec<U>>
+    where
+        T: AsQuery,
+        T::Query: QueryFragment<Pg> + QueryId,
+        Pg: HasSqlType<T::SqlType>,
+        U: Queryable<T::SqlType, Pg>,
+    {
+        Err(DieselError::QueryBuilderError(
+            "Table is not kept by the in-memory connection".into(),
+        ))
+    }
+
+    fn query_by_name<T, U>(&self, _source: &T) -> QueryResult<Vec<U>>
+    where
+        T: QueryFragment<Pg> + QueryId,
+        U: QueryableByName<Pg>,
+    {
+        Err(DieselError::QueryBuilderError(
+            "Table is not kept by the in-memory connection".into(),
+        ))
+    }
+
+    fn execute_returning_count<T>(&self, _source: &T) -> QueryResult<usize>
+    where
+        T: QueryFragment<Pg> + QueryId,
+    {
+        Err(DieselError::QueryBuilderError(
+            "Table is not kept by the in-memory connection".into(),
+        ))
+    }
+
+    fn transaction_manager(&self) -> &Self::TransactionManager {
+        &self.tr
+    }
+}
+
+impl SimpleConnection for InMemoryConnection {
+    /// Only the statements of the transaction manager are expected here
+    fn batch_execute(&self, query: &str) -> QueryResult<()> {
+        let mut snapshots = self.snapshots.borrow_mut();
+        if query.starts_with("BEGIN") || query.starts_with("SAVEPOINT") {
+            snapshots.push(lock(&self.tables).clone());
+        } else if query.starts_with("COMMIT") || query.starts_with("RELEASE SAVEPOINT") {
+            snapshots.pop();
+        } else if query.starts_with("ROLLBACK") {
+            if let Some(snapshot) = snapshots.pop() {
+                *lock(&self.tables) = snapshot;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Connections of the pool sharing the tables of `InMemoryReposFactory::connection_manager`
+#[derive(Clone, Default)]
+pub struct InMemoryConnectionManager {
+    tables: SharedTables,
+}
+
+impl ManageConnection for InMemoryConnectionManager {
+    type Connection = InMemoryConnection;
+    type Error = ConnectionError;
+
+    fn connect(&self) -> Result<InMemoryConnection, ConnectionError> {
+        Ok(InMemoryConnection::new(self.tables.clone()))
+    }
+
+    fn is_valid(&self, _conn: &mut InMemoryConnection) -> Result<(), ConnectionError> {
+        Ok(())
+    }
+
+    fn has_broken(&self, _conn: &mut InMemoryConnection) -> bool {
+        false
+    }
+}
+
+/// Repos factory keeping companies, packages, company packages, shipping rates, company suspensions and user roles in memory.
+/// Clones share the tables, so the data created by one request is seen by the following ones
+#[derive(Clone)]
+pub struct InMemoryReposFactory<F> {
+    fallback: F,
+    tables: SharedTables,
+    countries: Option<Arc<Country>>,
+}
+
+impl<F> InMemoryReposFactory<F> {
+    /// `fallback` creates the repos of the tables that are not kept in memory
+    pub fn new(fallback: F) -> Self {
+        Self {
+            fallback,
+            tables: SharedTables::default(),
+            countries: None,
+        }
+    }
+
+    /// Countries tree used by the in-memory repos, they are taken from the countries repo of the fallback otherwise
+    pub fn with_countries(mut self, countries: Country) -> Self {
+        self.countries = Some(Arc::new(countries));
+        self
+    }
+
+    /// Pool connections must come from this manager for the transactions of the services to be rolled back
+    pub fn connection_manager(&self) -> InMemoryConnectionManager {
+        InMemoryConnectionManager {
+            tables: self.tables.clone(),
+        }
+    }
+
+    fn get_acl<T>(&self, user_id: Option<UserId>) -> Box<dyn Acl<Resource, Action, Scope, FailureError, T>> {
+        if let Some(api_key_scopes) = RequestContext::current().and_then(|context| context.api_key_scopes) {
+            return Box::new(ApiKeyAcl::new(api_key_scopes));
+        }
+
+        user_id.map_or(
+            Box::new(UnauthorizedAcl::default()) as Box<dyn Acl<Resource, Action, Scope, FailureError, T>>,
+            |id| {
+                let roles = RequestContext::current()
+                    .filter(|context| context.user_id == Some(id))
+                    .and_then(|context| context.roles)
+                    .unwrap_or_else(|| {
+                        lock(&self.tables)
+                            .roles
+                            .iter()
+                            .filter(|role| role.user_id == id)
+                            .map(|role| role.name.clone())
+                            .collect()
+                    });
+                (Box::new(ApplicationAcl::new(roles, id)) as Box<dyn Acl<Resource, Action, Scope, FailureError, T>>)
+            },
+        )
+    }
+
+    fn get_countries<C: DbConnection>(&self, db_conn: &C, user_id: Option<UserId>) -> Country
+    where
+        F: ReposFactory<C>,
+    {
+        match self.countries {
+            Some(ref countries) => (**countries).clone(),
+            None => self
+                .fallback
+                .create_countries_repo(db_conn, user_id)
+                .get_all()
+                .ok()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl<C, F> ReposFactory<C> for InMemoryReposFactory<F>
+where
+    C: DbConnection,
+    F: ReposFactory<C>,
+{
+    fn create_admin_overview_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn AdminOverviewRepo + 'a> {
+        self.fallback.create_admin_overview_repo(db_conn, user_id)
+    }
+
+    fn create_api_keys_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ApiKeysRepo + 'a> {
+        self.fallback.create_api_keys_repo(db_conn, user_id)
+    }
+
+    fn create_api_keys_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn ApiKeysRepo + 'a> {
+        self.fallback.create_api_keys_repo_with_sys_acl(db_conn)
+    }
+
+    fn create_availability_snapshots_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn AvailabilitySnapshotsRepo + 'a> {
+        self.fallback.create_availability_snapshots_repo_with_sys_acl(db_conn)
+    }
+
+    fn create_backfills_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn BackfillsRepo + 'a> {
+        self.fallback.create_backfills_repo(db_conn, user_id)
+    }
+
+    fn create_backfills_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn BackfillsRepo + 'a> {
+        self.fallback.create_backfills_repo_with_sys_acl(db_conn)
+    }
+
+    fn create_carrier_accounts_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CarrierAccountsRepo + 'a> {
+        self.fallback.create_carrier_accounts_repo(db_conn, user_id)
+    }
+
+    fn create_carrier_accounts_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn CarrierAccountsRepo + 'a> {
+        self.fallback.create_carrier_accounts_repo_with_sys_acl(db_conn)
+    }
+
+    fn create_companies_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CompaniesRepo + 'a> {
+        Box::new(InMemoryCompaniesRepo {
+            tables: self.tables.clone(),
+            acl: self.get_acl(user_id),
+            countries: self.get_countries(db_conn, user_id),
+        }) as Box<dyn CompaniesRepo>
+    }
+
+    fn create_companies_packages_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CompaniesPackagesRepo + 'a> {
+        Box::new(InMemoryCompaniesPackagesRepo {
+            tables: self.tables.clone(),
+            acl: self.get_acl(user_id),
+            countries: self.get_countries(db_conn, user_id),
+        }) as Box<dyn CompaniesPackagesRepo>
+    }
+
+    fn create_company_rates_quotas_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CompanyRatesQuotasRepo + 'a> {
+        self.fallback.create_company_rates_quotas_repo(db_conn, user_id)
+    }
+
+    fn create_company_rates_quotas_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn CompanyRatesQuotasRepo + 'a> {
+        self.fallback.create_company_rates_quotas_repo_with_sys_acl(db_conn)
+    }
+
+    fn create_company_suspensions_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CompanySuspensionsRepo + 'a> {
+        Box::new(InMemoryCompanySuspensionsRepo {
+            tables: self.tables.clone(),
+            acl: self.get_acl(user_id),
+        }) as Box<dyn CompanySuspensionsRepo>
+    }
+
+    fn create_company_suspensions_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<dyn CompanySuspensionsRepo + 'a> {
+        Box::new(InMemoryCompanySuspensionsRepo {
+            tables: self.tables.clone(),
+            acl: Box::new(SystemACL::default()) as Box<dyn Acl<Resource, Action, Scope, FailureError, CompanySuspension>>,
+        }) as Box<dyn CompanySuspensionsRepo>
+    }
+
+    fn create_countries_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CountriesRepo + 'a> {
+        self.fallback.create_countries_repo(db_conn, user_id)
+    }
+
+    fn create_country_regulations_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CountryRegulationsRepo + 'a> {
+        self.fallback.create_country_regulations_repo(db_conn, user_id)
+    }
+
+    fn create_country_regulations_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn CountryRegulationsRepo + 'a> {
+        self.fallback.create_country_regulations_repo_with_sys_acl(db_conn)
+    }
+
+    fn create_country_subdivisions_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CountrySubdivisionsRepo + 'a> {
+        self.fallback.create_country_subdivisions_repo(db_conn, user_id)
+    }
+
+    fn create_country_dependents_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn CountryDependentsRepo + 'a> {
+        self.fallback.create_country_dependents_repo_with_sys_acl(db_conn)
+    }
+
+    fn create_customs_info_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CustomsInfoRepo + 'a> {
+        self.fallback.create_customs_info_repo(db_conn, user_id)
+    }
+
+    fn create_delivery_quotes_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn DeliveryQuotesRepo + 'a> {
+        self.fallback.create_delivery_quotes_repo(db_conn, user_id)
+    }
+
+    fn create_delivery_quotes_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn DeliveryQuotesRepo + 'a> {
+        self.fallback.create_delivery_quotes_repo_with_sys_acl(db_conn)
+    }
+
+    fn create_event_outbox_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn EventOutboxRepo + 'a> {
+        self.fallback.create_event_outbox_repo_with_sys_acl(db_conn)
+    }
+
+    fn create_products_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ProductsRepo + 'a> {
+        self.fallback.create_products_repo(db_conn, user_id)
+    }
+
+    fn create_package_templates_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn PackageTemplatesRepo + 'a> {
+        self.fallback.create_package_templates_repo(db_conn, user_id)
+    }
+
+    fn create_packages_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn PackagesRepo + 'a> {
+        Box::new(InMemoryPackagesRepo {
+            tables: self.tables.clone(),
+            acl: self.get_acl(user_id),
+            countries: self.get_countries(db_conn, user_id),
+        }) as Box<dyn PackagesRepo>
+    }
+
+    fn create_permissions_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn PermissionsRepo + 'a> {
+        self.fallback.create_permissions_repo(db_conn, user_id)
+    }
+
+    fn create_permissions_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn PermissionsRepo + 'a> {
+        self.fallback.create_permissions_repo_with_sys_acl(db_conn)
+    }
+
+    fn create_pickup_points_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn PickupPointsRepo + 'a> {
+        self.fallback.create_pickup_points_repo(db_conn, user_id)
+    }
+
+    fn create_pickups_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn PickupsRepo + 'a> {
+        self.fallback.create_pickups_repo(db_conn, user_id)
+    }
+
+    fn create_postal_zones_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn PostalZonesRepo + 'a> {
+        self.fallback.create_postal_zones_repo(db_conn, user_id)
+    }
+
+    fn create_pricing_rules_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn PricingRulesRepo + 'a> {
+        self.fallback.create_pricing_rules_repo(db_conn, user_id)
+    }
+
+    fn create_pricing_rules_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn PricingRulesRepo + 'a> {
+        self.fallback.create_pricing_rules_repo_with_sys_acl(db_conn)
+    }
+
+    fn create_shipment_bookings_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ShipmentBookingsRepo + 'a> {
+        Box::new(InMemoryShipmentBookingsRepo {
+            tables: self.tables.clone(),
+            acl: self.get_acl(user_id),
+        }) as Box<dyn ShipmentBookingsRepo>
+    }
+
+    fn create_shipment_bookings_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<dyn ShipmentBookingsRepo + 'a> {
+        Box::new(InMemoryShipmentBookingsRepo {
+            tables: self.tables.clone(),
+            acl: Box::new(SystemACL::default()) as Box<dyn Acl<Resource, Action, Scope, FailureError, ShipmentBooking>>,
+        }) as Box<dyn ShipmentBookingsRepo>
+    }
+
+    fn create_shipment_notifications_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ShipmentNotificationsRepo + 'a> {
+        self.fallback.create_shipment_notifications_repo(db_conn, user_id)
+    }
+
+    fn create_shipping_rates_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ShippingRatesRepo + 'a> {
+        Box::new(InMemoryShippingRatesRepo {
+            tables: self.tables.clone(),
+            acl: self.get_acl(user_id),
+        }) as Box<dyn ShippingRatesRepo>
+    }
+
+    fn create_shipping_rates_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<dyn ShippingRatesRepo + 'a> {
+        Box::new(InMemoryShippingRatesRepo {
+            tables: self.tables.clone(),
+            acl: Box::new(SystemACL::default()) as Box<dyn Acl<Resource, Action, Scope, FailureError, ()>>,
+        }) as Box<dyn ShippingRatesRepo>
+    }
+
+    fn create_shipping_rates_anomalies_repo<'a>(
+        &self,
+        db_conn: &'a C,
+        user_id: Option<UserId>,
+    ) -> Box<dyn ShippingRatesAnomaliesRepo + 'a> {
+        self.fallback.create_shipping_rates_anomalies_repo(db_conn, user_id)
+    }
+
+    fn create_shipping_rates_anomalies_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn ShippingRatesAnomaliesRepo + 'a> {
+        self.fallback.create_shipping_rates_anomalies_repo_with_sys_acl(db_conn)
+    }
+
+    fn create_shipping_restrictions_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ShippingRestrictionsRepo + 'a> {
+        self.fallback.create_shipping_restrictions_repo(db_conn, user_id)
+    }
+
+    fn create_shipping_templates_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ShippingTemplatesRepo + 'a> {
+        self.fallback.create_shipping_templates_repo(db_conn, user_id)
+    }
+
+    fn create_store_managers_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn StoreManagersRepo + 'a> {
+        self.fallback.create_store_managers_repo(db_conn, user_id)
+    }
+
+    fn create_store_margins_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn StoreMarginsRepo + 'a> {
+        self.fallback.create_store_margins_repo(db_conn, user_id)
+    }
+
+    fn create_store_margins_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn StoreMarginsRepo + 'a> {
+        self.fallback.create_store_margins_repo_with_sys_acl(db_conn)
+    }
+
+    fn create_store_shipping_summaries_repo<'a>(
+        &self,
+        db_conn: &'a C,
+        user_id: Option<UserId>,
+    ) -> Box<dyn StoreShippingSummariesRepo + 'a> {
+        self.fallback.create_store_shipping_summaries_repo(db_conn, user_id)
+    }
+
+    fn create_surcharges_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn SurchargesRepo + 'a> {
+        self.fallback.create_surcharges_repo(db_conn, user_id)
+    }
+
+    fn create_surcharges_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn SurchargesRepo + 'a> {
+        self.fallback.create_surcharges_repo_with_sys_acl(db_conn)
+    }
+
+    #[cfg(feature = "test-endpoints")]
+    fn create_test_fixtures_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn TestFixturesRepo + 'a> {
+        self.fallback.create_test_fixtures_repo(db_conn, user_id)
+    }
+
+    fn create_users_addresses_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn UserAddressesRepo + 'a> {
+        self.fallback.create_users_addresses_repo(db_conn, user_id)
+    }
+
+    fn create_user_roles_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<dyn UserRolesRepo + 'a> {
+        Box::new(InMemoryUserRolesRepo {
+            tables: self.tables.clone(),
+        }) as Box<dyn UserRolesRepo>
+    }
+
+    fn create_user_roles_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<dyn UserRolesRepo + 'a> {
+        Box::new(InMemoryUserRolesRepo {
+            tables: self.tables.clone(),
+        }) as Box<dyn UserRolesRepo>
+    }
+
+    fn create_zones_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ZonesRepo + 'a> {
+        self.fallback.create_zones_repo(db_conn, user_id)
+    }
+}
+
+/// Service of the user with the repos of the factory and the connections sharing its tables
+pub fn create_in_memory_service<F>(
+    repo_factory: InMemoryReposFactory<F>,
+    user_id: Option<UserId>,
+    handle: Arc<Handle>,
+) -> Service<InMemoryConnection, InMemoryConnectionManager, InMemoryReposFactory<F>>
+where
+    F: ReposFactory<InMemoryConnection>,
+{
+    create_in_memory_service_with_carriers(repo_factory, user_id, handle, None)
+}
+
+/// Service of the user with the repos of the factory, the live rates of the on-demand company packages
+/// are requested from the carriers client
+pub fn create_in_memory_service_with_carriers<F>(
+    repo_factory: InMemoryReposFactory<F>,
+    user_id: Option<UserId>,
+    handle: Arc<Handle>,
+    carriers_client: Option<Arc<dyn CarriersClient>>,
+) -> Service<InMemoryConnection, InMemoryConnectionManager, InMemoryReposFactory<F>>
+where
+    F: ReposFactory<InMemoryConnection>,
+{
+    let db_pool = r2d2::Pool::builder()
+        .build(repo_factory.connection_manager())
+        .expect("Failed to create connection pool");
+    let cpu_pool = CpuPool::new(1);
+    let admin_cpu_pool = CpuPool::new(1);
+
+    let config = Config::new().expect("Can't load app config!");
+    let client = stq_http::client::Client::new(&config.to_http_config(), &handle);
+    let client_handle = client.handle();
+    handle.spawn(client.stream().for_each(|_| Ok(())));
+    let static_context = StaticContext::new(
+        db_pool,
+        cpu_pool,
+        admin_cpu_pool,
+        client_handle,
+        carriers_client,
+        None,
+        None,
+        None,
+        None,
+        ReplicaRouter::default(),
+        Arc::new(config),
+        repo_factory,
+    );
+    let dynamic_context = DynamicContext::new(user_id, String::default(), String::default());
+
+    Service::new(static_context, dynamic_context)
+}
+
+/// Companies kept in memory
+pub struct InMemoryCompaniesRepo {
+    tables: SharedTables,
+    acl: Box<dyn Acl<Resource, Action, Scope, FailureError, Company>>,
+    countries: Country,
+}
+
+impl InMemoryCompaniesRepo {
+    fn to_companies(&self, raws: Vec<CompanyRaw>) -> RepoResult<Vec<Company>> {
+        let companies = raws
+            .into_iter()
+            .map(|raw| Company::from_raw(raw, &self.countries))
+            .collect::<Vec<Company>>();
+        for company in &companies {
+            acl::check(&*self.acl, Resource::Companies, Action::Read, self, Some(company))?;
+        }
+        Ok(companies)
+    }
+}
+
+impl CompaniesRepo for InMemoryCompaniesRepo {
+    fn create(&self, payload: NewCompany) -> RepoResult<Company> {
+        let NewCompanyRaw {
+            name,
+            label,
+            description,
+            deliveries_from,
+            logo,
+            currency,
+            capabilities,
+            label_translations,
+        } = payload.to_raw();
+
+        let raw = CompanyRaw {
+            id: lock(&self.tables).next_company_id(),
+            name,
+            label,
+            description,
+            deliveries_from,
+            logo,
+            currency,
+            capabilities,
+            label_translations,
+        };
+
+        let company = Company::from_raw(raw.clone(), &self.countries);
+        acl::check(&*self.acl, Resource::Companies, Action::Create, self, Some(&company))?;
+        lock(&self.tables).companies.push(raw);
+        Ok(company)
+    }
+
+    fn list(&self, search: CompaniesSearch) -> RepoResult<Vec<Company>> {
+        let CompaniesSearch {
+            deliveries_from,
+            delivers_to,
+            supports,
+            label_contains,
+            sort,
+            order,
+        } = search;
+
+        let mut raws = {
+            let tables = lock(&self.tables);
+
+            let delivering_company_ids = match delivers_to {
+                Some(country) => {
+                    // packages delivering to a region deliver to all of its countries
+                    let mut codes = vec![];
+                    get_all_parent_codes(&self.countries, &country, &mut codes);
+                    if codes.is_empty() {
+                        codes.push(country);
+                    }
+
+                    let mut package_ids = vec![];
+                    for package in &tables.packages {
+                        if package.get_deliveries_to().iter().any(|code| codes.contains(code)) {
+                            package_ids.push(package.id);
+                        }
+                    }
+
+                    let company_ids = tables
+                        .companies_packages
+                        .iter()
+                        .filter(|company_package| package_ids.contains(&company_package.package_id))
+                        .map(|company_package| company_package.company_id)
+                        .collect::<Vec<CompanyId>>();
+                    Some(company_ids)
+                }
+                None => None,
+            };
+            let label_contains = label_contains.map(|text| text.to_lowercase());
+
+            let mut raws = vec![];
+            for raw in &tables.companies {
+                if let Some(ref country) = deliveries_from {
+                    if !raw.deliveries_from.contains(country) {
+                        continue;
+                    }
+                }
+                if let Some(ref company_ids) = delivering_company_ids {
+                    if !company_ids.contains(&raw.id) {
+                        continue;
+                    }
+                }
+                if let Some(ref capabilities) = supports {
+                    if !capabilities.0.iter().all(|capability| raw.capabilities.contains(capability)) {
+                        continue;
+                    }
+                }
+                if let Some(ref text) = label_contains {
+                    if !raw.label.to_lowercase().contains(text.as_str()) {
+                        continue;
+                    }
+                }
+                raws.push(raw.clone());
+            }
+            raws
+        };
+
+        let (sort, order) = (sort.unwrap_or_default(), order.unwrap_or_default());
+        raws.sort_by(|a, b| {
+            let ordering = match sort {
+                CompaniesSortField::Id => a.id.0.cmp(&b.id.0),
+                CompaniesSortField::Name => a.name.cmp(&b.name),
+                CompaniesSortField::Label => a.label.cmp(&b.label),
+            };
+            let ordering = match order {
+                SortOrder::Asc => ordering,
+                SortOrder::Desc => ordering.reverse(),
+            };
+            ordering.then_with(|| a.id.0.cmp(&b.id.0))
+        });
+
+        self.to_companies(raws)
+    }
+
+    fn find(&self, id_arg: CompanyId) -> RepoResult<Option<Company>> {
+        let raw = lock(&self.tables).companies.iter().find(|raw| raw.id == id_arg).cloned();
+        Ok(self.to_companies(raw.into_iter().collect())?.pop())
+    }
+
+    fn find_deliveries_from(&self, country: Alpha3) -> RepoResult<Vec<Company>> {
+        let raws = {
+            let tables = lock(&self.tables);
+            let mut raws = vec![];
+            for raw in &tables.companies {
+                if raw.deliveries_from.contains(&country) {
+                    raws.push(raw.clone());
+                }
+            }
+            raws
+        };
+
+        self.to_companies(raws)
+    }
+
+    fn update(&self, id_arg: CompanyId, payload: UpdateCompany) -> RepoResult<Company> {
+        let UpdateCompanyRaw {
+            name,
+            label,
+            description,
+            deliveries_from,
+            logo,
+            currency,
+            capabilities,
+            label_translations,
+        } = payload.to_raw();
+
+        let raw = lock(&self.tables)
+            .companies
+            .iter()
+            .find(|raw| raw.id == id_arg)
+            .cloned()
+            .ok_or_else(|| not_found(format!("Company {} is not found", id_arg)))?;
+        let company = Company::from_raw(raw, &self.countries);
+        acl::check(&*self.acl, Resource::Companies, Action::Update, self, Some(&company))?;
+
+        let raw = lock(&self.tables)
+            .companies
+            .iter_mut()
+            .find(|raw| raw.id == id_arg)
+            .map(|raw| {
+                if let Some(name) = name {
+                    raw.name = name;
+                }
+                if let Some(label) = label {
+                    raw.label = label;
+                }
+                if let Some(description) = description {
+                    raw.description = Some(description);
+                }
+                if let Some(deliveries_from) = deliveries_from {
+                    raw.deliveries_from = deliveries_from;
+                }
+                if let Some(logo) = logo {
+                    raw.logo = logo;
+                }
+                if let Some(currency) = currency {
+                    raw.currency = currency;
+                }
+                if let Some(capabilities) = capabilities {
+                    raw.capabilities = capabilities;
+                }
+                if let Some(label_translations) = label_translations {
+                    raw.label_translations = label_translations;
+                }
+                raw.clone()
+            })
+            .ok_or_else(|| not_found(format!("Company {} is not found", id_arg)))?;
+
+        Ok(Company::from_raw(raw, &self.countries))
+    }
+
+    /// Products are not kept in memory, only the company packages and their shipping rates are counted
+    fn deletion_impact(&self, id_arg: CompanyId) -> RepoResult<CompanyDeletionImpact> {
+        acl::check(&*self.acl, Resource::Companies, Action::Delete, self, None)?;
+
+        let tables = lock(&self.tables);
+        let company_packages_ids = tables
+            .companies_packages
+            .iter()
+            .filter(|company_package| company_package.company_id == id_arg)
+            .map(|company_package| company_package.id)
+            .collect::<Vec<_>>();
+        let shipping_rates = tables
+            .shipping_rates
+            .iter()
+            .filter(|rates| company_packages_ids.contains(&rates.company_package_id))
+            .count();
+
+        Ok(CompanyDeletionImpact {
+            company_packages: company_packages_ids.len() as i64,
+            products: 0,
+            shipping_rates: shipping_rates as i64,
+        })
+    }
+
+    fn delete(&self, id_arg: CompanyId) -> RepoResult<Company> {
+        acl::check(&*self.acl, Resource::Companies, Action::Delete, self, None)?;
+
+        let raw = lock(&self.tables)
+            .remove_company(id_arg)
+            .ok_or_else(|| not_found(format!("Company {} is not found", id_arg)))?;
+
+        Ok(Company::from_raw(raw, &self.countries))
+    }
+}
+
+impl CheckScope<Scope, Company> for InMemoryCompaniesRepo {
+    fn is_in_scope(&self, user_id: UserId, scope: &Scope, obj: Option<&Company>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => match obj {
+                Some(company) => {
+                    let roles_data = user_roles_data(&self.tables, user_id);
+                    company
+                        .deliveries_from
+                        .iter()
+                        .any(|country| roles_data.iter().any(|role_data| role_data.manages_country(&country.alpha3)))
+                }
+                None => false,
+            },
+        }
+    }
+}
+
+/// Packages kept in memory
+pub struct InMemoryPackagesRepo {
+    tables: SharedTables,
+    acl: Box<dyn Acl<Resource, Action, Scope, FailureError, Packages>>,
+    countries: Country,
+}
+
+impl InMemoryPackagesRepo {
+    fn to_packages(&self, raws: Vec<PackagesRaw>) -> RepoResult<Vec<Packages>> {
+        let packages = raws
+            .into_iter()
+            .map(|raw| raw.to_packages(&self.countries, &[]))
+            .collect::<Vec<Packages>>();
+        for package in &packages {
+            acl::check(&*self.acl, Resource::Packages, Action::Read, self, Some(package))?;
+        }
+        Ok(packages)
+    }
+
+    /// Packages delivering to any of the countries, ordered by id
+    fn find_raws_delivering_to(&self, countries: &[Alpha3]) -> Vec<PackagesRaw> {
+        let mut raws = lock(&self.tables)
+            .packages
+            .iter()
+            .filter(|raw| raw.get_deliveries_to().iter().any(|code| countries.contains(code)))
+            .cloned()
+            .collect::<Vec<_>>();
+        raws.sort_by_key(|raw| raw.id.0);
+        raws
+    }
+}
+
+impl PackagesRepo for InMemoryPackagesRepo {
+    fn create(&self, payload: NewPackages) -> RepoResult<Packages> {
+        let NewPackagesRaw {
+            name,
+            max_size,
+            min_size,
+            max_weight,
+            min_weight,
+            deliveries_to,
+            deliveries_to_zones,
+            name_translations,
+        } = payload.to_raw();
+
+        let raw = PackagesRaw {
+            id: lock(&self.tables).next_package_id(),
+            name,
+            max_size,
+            min_size,
+            max_weight,
+            min_weight,
+            deliveries_to,
+            deliveries_to_zones,
+            name_translations,
+        };
+
+        let package = raw.clone().to_packages(&self.countries, &[]);
+        acl::check(&*self.acl, Resource::Packages, Action::Create, self, Some(&package))?;
+        lock(&self.tables).packages.push(raw);
+        Ok(package)
+    }
+
+    fn find_deliveries_to(&self, countries: Vec<Alpha3>) -> RepoResult<Vec<Packages>> {
+        let raws = self.find_raws_delivering_to(&countries);
+        self.to_packages(raws)
+    }
+
+    fn search(&self, countries: Vec<Alpha3>, max_weight_arg: Option<u32>, offset: i64, limit: i64) -> RepoResult<PackagesSearchResult> {
+        acl::check(&*self.acl, Resource::Packages, Action::Read, self, None)?;
+
+        let raws = self
+            .find_raws_delivering_to(&countries)
+            .into_iter()
+            .filter(|raw| max_weight_arg.map_or(true, |max_weight_arg| raw.max_weight >= max_weight_arg as i32))
+            .collect::<Vec<PackagesRaw>>();
+        let total_count = raws.len() as i64;
+        let raws = raws.into_iter().skip(offset.max(0) as usize).take(limit.max(0) as usize).collect();
+
+        Ok(PackagesSearchResult {
+            total_count,
+            packages: self.to_packages(raws)?,
+        })
+    }
+
+    fn list(&self) -> RepoResult<Vec<Packages>> {
+        let mut raws = lock(&self.tables).packages.clone();
+        raws.sort_by_key(|raw| raw.id.0);
+        self.to_packages(raws)
+    }
+
+    fn find(&self, id_arg: PackageId) -> RepoResult<Option<Packages>> {
+        let raw = lock(&self.tables).packages.iter().find(|raw| raw.id == id_arg).cloned();
+        Ok(self.to_packages(raw.into_iter().collect())?.pop())
+    }
+
+    fn update(&self, id_arg: PackageId, payload: UpdatePackages) -> RepoResult<Packages> {
+        let UpdatePackagesRaw {
+            name,
+            max_size,
+            min_size,
+            max_weight,
+            min_weight,
+            deliveries_to,
+            deliveries_to_zones,
+            name_translations,
+        } = payload.to_raw();
+
+        let raw = lock(&self.tables)
+            .packages
+            .iter()
+            .find(|raw| raw.id == id_arg)
+            .cloned()
+            .ok_or_else(|| not_found(format!("Package {} is not found", id_arg)))?;
+        let package = raw.to_packages(&self.countries, &[]);
+        acl::check(&*self.acl, Resource::Packages, Action::Update, self, Some(&package))?;
+
+        let raw = lock(&self.tables)
+            .packages
+            .iter_mut()
+            .find(|raw| raw.id == id_arg)
+            .map(|raw| {
+                if let Some(name) = name {
+                    raw.name = name;
+                }
+                if let Some(max_size) = max_size {
+                    raw.max_size = max_size;
+                }
+                if let Some(min_size) = min_size {
+                    raw.min_size = min_size;
+                }
+                if let Some(max_weight) = max_weight {
+                    raw.max_weight = max_weight;
+                }
+                if let Some(min_weight) = min_weight {
+                    raw.min_weight = min_weight;
+                }
+                if let Some(deliveries_to) = deliveries_to {
+                    raw.deliveries_to = deliveries_to;
+                }
+                if let Some(deliveries_to_zones) = deliveries_to_zones {
+                    raw.deliveries_to_zones = deliveries_to_zones;
+                }
+                if let Some(name_translations) = name_translations {
+                    raw.name_translations = name_translations;
+                }
+                raw.clone()
+            })
+            .ok_or_else(|| not_found(format!("Package {} is not found", id_arg)))?;
+
+        Ok(raw.to_packages(&self.countries, &[]))
+    }
+
+    fn delete(&self, id_arg: PackageId) -> RepoResult<Packages> {
+        acl::check(&*self.acl, Resource::Packages, Action::Delete, self, None)?;
+
+        let raw = lock(&self.tables)
+            .remove_package(id_arg)
+            .ok_or_else(|| not_found(format!("Package {} is not found", id_arg)))?;
+
+        Ok(raw.to_packages(&self.countries, &[]))
+    }
+}
+
+impl CheckScope<Scope, Packages> for InMemoryPackagesRepo {
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&Packages>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
+
+/// Company packages kept in memory, joined with the companies and the packages in memory
+pub struct InMemoryCompaniesPackagesRepo {
+    tables: SharedTables,
+    acl: Box<dyn Acl<Resource, Action, Scope, FailureError, CompanyPackage>>,
+    countries: Country,
+}
+
+impl InMemoryCompaniesPackagesRepo {
+    /// Company packages with their companies and packages, ordered by the label of the company, then by id
+    fn joined(&self) -> Vec<(CompaniesPackagesRaw, CompanyRaw, PackagesRaw)> {
+        let tables = lock(&self.tables);
+        let mut rows = tables
+            .companies_packages
+            .iter()
+            .filter_map(|company_package| {
+                let company = tables.companies.iter().find(|company| company.id == company_package.company_id);
+                let package = tables.packages.iter().find(|package| package.id == company_package.package_id);
+                match (company, package) {
+                    (Some(company), Some(package)) => Some((company_package.clone(), company.clone(), package.clone())),
+                    _ => None,
+                }
+            })
+            .collect::<Vec<_>>();
+        rows.sort_by(|a, b| a.1.label.cmp(&b.1.label).then_with(|| a.0.id.0.cmp(&b.0.id.0)));
+        rows
+    }
+
+    fn find_raw<P>(&self, predicate: P) -> Option<CompaniesPackagesRaw>
+    where
+        P: Fn(&CompaniesPackagesRaw) -> bool,
+    {
+        lock(&self.tables)
+            .companies_packages
+            .iter()
+            .find(|company_package| predicate(company_package))
+            .cloned()
+    }
+
+    /// Updates the company package with the id, `None` if there is no such company package
+    fn update_raw<U>(&self, id_arg: CompanyPackageId, update: U) -> RepoResult<Option<CompanyPackage>>
+    where
+        U: FnOnce(&mut CompaniesPackagesRaw),
+    {
+        let raw = lock(&self.tables)
+            .companies_packages
+            .iter_mut()
+            .find(|company_package| company_package.id == id_arg)
+            .map(|company_package| {
+                update(company_package);
+                company_package.clone()
+            });
+
+        transpose(raw.map(CompaniesPackagesRaw::to_model))
+    }
+
+    fn local_available(&self, package: &PackagesRaw, deliveries_from: &Alpha3) -> bool {
+        package.get_deliveries_to().iter().any(|country_code| {
+            get_country(&self.countries, country_code)
+                .map(|c| contains_country_code(&c, deliveries_from))
+                .unwrap_or_default()
+        })
+    }
+}
+
+impl CompaniesPackagesRepo for InMemoryCompaniesPackagesRepo {
+    fn create(&self, payload: NewCompanyPackage) -> RepoResult<CompanyPackage> {
+        let NewCompaniesPackagesRaw {
+            company_id,
+            package_id,
+            shipping_rate_source,
+            dimensional_factor,
+            restricted_hazard_classes,
+            distance_pricing,
+            delivery_time_min_days,
+            delivery_time_max_days,
+            rate_resolution_order,
+            insurance_options,
+            cod_supported,
+            cod_fee,
+            carrier_fallback,
+            fallback_markup_percent,
+            daily_cutoff_time,
+            max_daily_shipments,
+        } = payload.to_raw();
+
+        let mut tables = lock(&self.tables);
+        // same as the foreign keys of the table
+        if !tables.companies.iter().any(|company| company.id == company_id)
+            || !tables.packages.iter().any(|package| package.id == package_id)
+        {
+            return Err(Error::Internal
+                .context(format!("Company {} or package {} is not found", company_id, package_id))
+                .into());
+        }
+        let id = tables.next_company_package_id();
+        drop(tables);
+
+        let raw = CompaniesPackagesRaw {
+            id,
+            company_id,
+            package_id,
+            shipping_rate_source,
+            dimensional_factor,
+            restricted_hazard_classes,
+            distance_pricing,
+            delivery_time_min_days,
+            delivery_time_max_days,
+            rate_resolution_order,
+            insurance_options,
+            cod_supported,
+            cod_fee,
+            carrier_fallback,
+            fallback_markup_percent,
+            daily_cutoff_time,
+            max_daily_shipments,
+        };
+
+        let company_package = raw.clone().to_model()?;
+        acl::check(
+            &*self.acl,
+            Resource::CompaniesPackages,
+            Action::Create,
+            self,
+            Some(&company_package),
+        )?;
+        lock(&self.tables).companies_packages.push(raw);
+        Ok(company_package)
+    }
+
+    fn get_available_packages(
+        &self,
+        company_id_args: Vec<CompanyId>,
+        size: u32,
+        weight: u32,
+        deliveries_from: Alpha3,
+    ) -> RepoResult<Vec<AvailablePackages>> {
+        let (size, weight) = (size as i32, weight as i32);
+
+        let mut data = vec![];
+        for (company_package, company, package) in self.joined() {
+            if !company_id_args.contains(&company_package.company_id)
+                || package.max_size < size
+                || package.min_size > size
+                || package.max_weight < weight
+                || package.min_weight > weight
+            {
+                continue;
+            }
+
+            let company_package = company_package.to_model()?;
+            let local_available = self.local_available(&package, &deliveries_from);
+            let package = package.to_packages(&self.countries, &[]);
+
+            data.push(AvailablePackages {
+                id: company_package.id,
+                name: get_company_package_name(&company.label, &package.name),
+                logo: company.logo,
+                deliveries_to: package.deliveries_to,
+                shipping_rate_source: company_package.shipping_rate_source,
+                currency: company.currency,
+                local_available,
+            });
+        }
+
+        Ok(data)
+    }
+
+    fn get_candidate_packages(&self, deliveries_from: Alpha3) -> RepoResult<Vec<CandidatePackage>> {
+        acl::check(&*self.acl, Resource::CompaniesPackages, Action::Read, self, None)?;
+
+        let mut data = vec![];
+        for (company_package, company, package) in self.joined() {
+            let company_package = company_package.to_model()?;
+            let local_available = self.local_available(&package, &deliveries_from);
+            let package = package.to_packages(&self.countries, &[]);
+
+            data.push(CandidatePackage {
+                company_id: company_package.company_id,
+                delivers_from: company.deliveries_from.contains(&deliveries_from),
+                available_package: AvailablePackages {
+                    id: company_package.id,
+                    name: get_company_package_name(&company.label, &package.name),
+                    logo: company.logo,
+                    deliveries_to: package.deliveries_to.clone(),
+                    shipping_rate_source: company_package.shipping_rate_source,
+                    currency: company.currency,
+                    local_available,
+                },
+                package,
+            });
+        }
+
+        Ok(data)
+    }
+
+    fn get(&self, id_arg: CompanyPackageId) -> RepoResult<Option<CompanyPackage>> {
+        acl::check(&*self.acl, Resource::CompaniesPackages, Action::Read, self, None)?;
+
+        let raw = self.find_raw(|company_package| company_package.id == id_arg);
+        transpose(raw.map(CompaniesPackagesRaw::to_model))
+    }
+
+    fn get_many_with_companies(&self, ids: Vec<CompanyPackageId>) -> RepoResult<Vec<(CompanyPackage, Company)>> {
+        acl::check(&*self.acl, Resource::CompaniesPackages, Action::Read, self, None)?;
+
+        let mut data = vec![];
+        for (company_package, company, _) in self.joined() {
+            if ids.contains(&company_package.id) {
+                data.push((company_package.to_model()?, Company::from_raw(company, &self.countries)));
+            }
+        }
+
+        Ok(data)
+    }
+
+    fn get_many_with_companies_and_packages(&self, ids: Vec<CompanyPackageId>) -> RepoResult<Vec<(CompanyPackage, Company, Packages)>> {
+        acl::check(&*self.acl, Resource::CompaniesPackages, Action::Read, self, None)?;
+
+        let mut data = vec![];
+        for (company_package, company, package) in self.joined() {
+            if ids.contains(&company_package.id) {
+                data.push((
+                    company_package.to_model()?,
+                    Company::from_raw(company, &self.countries),
+                    package.to_packages(&self.countries, &[]),
+                ));
+            }
+        }
+
+        Ok(data)
+    }
+
+    fn get_companies(&self, id_arg: PackageId) -> RepoResult<Vec<Company>> {
+        Ok(self
+            .joined()
+            .into_iter()
+            .filter(|(company_package, _, _)| company_package.package_id == id_arg)
+            .map(|(_, company, _)| Company::from_raw(company, &self.countries))
+            .collect())
+    }
+
+    fn get_packages(&self, id_arg: CompanyId) -> RepoResult<Vec<Packages>> {
+        Ok(self
+            .joined()
+            .into_iter()
+            .filter(|(company_package, _, _)| company_package.company_id == id_arg)
+            .map(|(_, _, package)| package.to_packages(&self.countries, &[]))
+            .collect())
+    }
+
+    fn find_by_ids(&self, company_id_arg: CompanyId, package_id_arg: PackageId) -> RepoResult<Option<CompanyPackage>> {
+        acl::check(&*self.acl, Resource::CompaniesPackages, Action::Read, self, None)?;
+
+        let raw =
+            self.find_raw(|company_package| company_package.company_id == company_id_arg && company_package.package_id == package_id_arg);
+        transpose(raw.map(CompaniesPackagesRaw::to_model))
+    }
+
+    fn delete(&self, company_id_arg: CompanyId, package_id_arg: PackageId) -> RepoResult<CompanyPackage> {
+        acl::check(&*self.acl, Resource::CompaniesPackages, Action::Delete, self, None)?;
+
+        let raw = lock(&self.tables)
+            .remove_companies_packages(|company_package| {
+                company_package.company_id == company_id_arg && company_package.package_id == package_id_arg
+            })
+            .pop()
+            .ok_or_else(|| {
+                not_found(format!(
+                    "Company package of company {} and package {} is not found",
+                    company_id_arg, package_id_arg
+                ))
+            })?;
+
+        raw.to_model()
+    }
+
+    fn set_rate_resolution_order(
+        &self,
+        id_arg: CompanyPackageId,
+        steps: Option<Vec<RateResolutionStep>>,
+    ) -> RepoResult<Option<CompanyPackage>> {
+        acl::check(&*self.acl, Resource::CompaniesPackages, Action::Update, self, None)?;
+
+        let order = steps.map(TypedJson);
+        self.update_raw(id_arg, move |company_package| company_package.rate_resolution_order = order)
+    }
+
+    fn set_cash_on_delivery(&self, id_arg: CompanyPackageId, payload: SetCashOnDelivery) -> RepoResult<Option<CompanyPackage>> {
+        acl::check(&*self.acl, Resource::CompaniesPackages, Action::Update, self, None)?;
+
+        self.update_raw(id_arg, move |company_package| {
+            company_package.cod_supported = payload.cod_supported;
+            company_package.cod_fee = payload.cod_fee;
+        })
+    }
+
+    fn set_carrier_fallback(&self, id_arg: CompanyPackageId, payload: SetCarrierFallback) -> RepoResult<Option<CompanyPackage>> {
+        acl::check(&*self.acl, Resource::CompaniesPackages, Action::Update, self, None)?;
+
+        self.update_raw(id_arg, move |company_package| {
+            company_package.carrier_fallback = payload.carrier_fallback;
+            company_package.fallback_markup_percent = payload.fallback_markup_percent;
+        })
+    }
+
+    fn set_shipping_capacity(&self, id_arg: CompanyPackageId, payload: SetShippingCapacity) -> RepoResult<Option<CompanyPackage>> {
+        acl::check(&*self.acl, Resource::CompaniesPackages, Action::Update, self, None)?;
+
+        self.update_raw(id_arg, move |company_package| {
+            company_package.daily_cutoff_time = payload.daily_cutoff_time;
+            company_package.max_daily_shipments = payload.max_daily_shipments.map(|max_daily_shipments| max_daily_shipments as i32);
+        })
+    }
+}
+
+impl CheckScope<Scope, CompanyPackage> for InMemoryCompaniesPackagesRepo {
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&CompanyPackage>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
+
+/// User roles kept in memory, the ACL of the other in-memory repos is based on them.
+/// Same as the repo of the table, the actions on the roles are not checked
+pub struct InMemoryUserRolesRepo {
+    tables: SharedTables,
+}
+
+impl UserRolesRepo for InMemoryUserRolesRepo {
+    fn list_for_user(&self, user_id: UserId) -> RepoResult<Vec<DeliveryRole>> {
+        Ok(lock(&self.tables)
+            .roles
+            .iter()
+            .filter(|role| role.user_id == user_id)
+            .map(|role| role.name.clone())
+            .collect())
+    }
+
+    fn create(&self, payload: NewUserRole) -> RepoResult<UserRole> {
+        let NewUserRole { id, user_id, name, data } = payload;
+        let role = UserRole { id, user_id, name, data };
+        lock(&self.tables).roles.push(role.clone());
+        Ok(role)
+    }
+
+    fn delete_by_user_id(&self, user_id: UserId) -> RepoResult<Vec<UserRole>> {
+        let mut tables = lock(&self.tables);
+        let (deleted, kept): (Vec<UserRole>, Vec<UserRole>) = tables.roles.drain(..).partition(|role| role.user_id == user_id);
+        tables.roles = kept;
+        Ok(deleted)
+    }
+
+    fn delete_by_id(&self, id: RoleId) -> RepoResult<UserRole> {
+        let mut tables = lock(&self.tables);
+        let position = tables
+            .roles
+            .iter()
+            .position(|role| role.id == id)
+            .ok_or_else(|| not_found(format!("Role {} is not found", id)))?;
+        let role = tables.roles.remove(position);
+        Ok(role)
+    }
+}
+
+/// Shipping rates kept in memory. Rates to zones are refused, as zones are not kept
+pub struct InMemoryShippingRatesRepo {
+    tables: SharedTables,
+    acl: Box<dyn Acl<Resource, Action, Scope, FailureError, ()>>,
+}
+
+impl InMemoryShippingRatesRepo {
+    /// Active rows matching the predicate converted to rates, the latest rows first
+    fn find_active<P>(&self, predicate: P) -> RepoResult<Vec<ShippingRates>>
+    where
+        P: Fn(&ShippingRatesRaw) -> bool,
+    {
+        let raws = lock(&self.tables)
+            .shipping_rates
+            .iter()
+            .rev()
+            .filter(|raw| raw.is_active && predicate(raw))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        ShippingRatesRaw::resolve_zones(raws, &[])
+    }
+}
+
+/// Rows of the rates to the whole country, not to its subdivisions or postal zones
+fn is_country_row(raw: &ShippingRatesRaw) -> bool {
+    raw.to_subdivision.is_none() && raw.to_postal_zone_id.is_none()
+}
+
+fn is_route_row(raw: &ShippingRatesRaw, company_package_id: CompanyPackageId, delivery_from: &Alpha3) -> bool {
+    raw.company_package_id == company_package_id && raw.from_alpha3 == *delivery_from
+}
+
+impl ShippingRatesRepo for InMemoryShippingRatesRepo {
+    fn get_all_rates_from(&self, company_package_id: CompanyPackageId, delivery_from: Alpha3) -> RepoResult<Vec<ShippingRates>> {
+        acl::check(&*self.acl, Resource::ShippingRates, Action::Read, self, None)?;
+
+        self.find_active(|raw| is_route_row(raw, company_package_id, &delivery_from))
+    }
+
+    fn get_multiple_rates(
+        &self,
+        company_package_id: CompanyPackageId,
+        delivery_from: Alpha3,
+        deliveries_to: Vec<Alpha3>,
+    ) -> RepoResult<Vec<ShippingRates>> {
+        acl::check(&*self.acl, Resource::ShippingRates, Action::Read, self, None)?;
+
+        self.find_active(|raw| {
+            is_route_row(raw, company_package_id, &delivery_from)
+                && is_country_row(raw)
+                && raw.to_alpha3.as_ref().map(|to| deliveries_to.contains(to)).unwrap_or(false)
+        })
+    }
+
+    fn get_rates(
+        &self,
+        company_package_id: CompanyPackageId,
+        delivery_from: Alpha3,
+        delivery_to: Alpha3,
+    ) -> RepoResult<Option<ShippingRates>> {
+        acl::check(&*self.acl, Resource::ShippingRates, Action::Read, self, None)?;
+
+        self.find_active(|raw| {
+            is_route_row(raw, company_package_id, &delivery_from) && is_country_row(raw) && raw.to_alpha3.as_ref() == Some(&delivery_to)
+        })
+        .map(|rates| rates.into_iter().next())
+    }
+
+    fn get_subdivision_rates(
+        &self,
+        company_package_id: CompanyPackageId,
+        delivery_from: Alpha3,
+        delivery_to: Alpha3,
+        subdivision: String,
+    ) -> RepoResult<Option<ShippingRates>> {
+        acl::check(&*self.acl, Resource::ShippingRates, Action::Read, self, None)?;
+
+        self.find_active(|raw| {
+            is_route_row(raw, company_package_id, &delivery_from)
+                && raw.to_alpha3.as_ref() == Some(&delivery_to)
+                && raw.to_subdivision.as_ref() == Some(&subdivision)
+        })
+        .map(|rates| rates.into_iter().next())
+    }
+
+    fn get_postal_zone_rates(
+        &self,
+        company_package_id: CompanyPackageId,
+        delivery_from: Alpha3,
+        delivery_to: Alpha3,
+        postal_zone_ids: Vec<i32>,
+    ) -> RepoResult<Vec<ShippingRates>> {
+        acl::check(&*self.acl, Resource::ShippingRates, Action::Read, self, None)?;
+
+        self.find_active(|raw| {
+            is_route_row(raw, company_package_id, &delivery_from)
+                && raw.to_alpha3.as_ref() == Some(&delivery_to)
+                && raw.to_postal_zone_id.map(|id| postal_zone_ids.contains(&id)).unwrap_or(false)
+        })
+    }
+
+    fn get_rates_for_routes(&self, routes: Vec<(CompanyPackageId, Alpha3, Alpha3)>) -> RepoResult<Vec<ShippingRates>> {
+        acl::check(&*self.acl, Resource::ShippingRates, Action::Read, self, None)?;
+
+        self.find_active(|raw| {
+            is_country_row(raw)
+                && routes.iter().any(|&(company_package_id, ref delivery_from, ref delivery_to)| {
+                    is_route_row(raw, company_package_id, delivery_from) && raw.to_alpha3.as_ref() == Some(delivery_to)
+                })
+        })
+    }
+
+    fn insert_many(&self, shipping_rates: Vec<NewShippingRates>) -> RepoResult<Vec<ShippingRates>> {
+        acl::check(&*self.acl, Resource::ShippingRates, Action::Publish, self, None)?;
+
+        let new_raws = shipping_rates
+            .into_iter()
+            .map(NewShippingRatesRaw::from_model)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut tables = lock(&self.tables);
+        let mut raws = vec![];
+        for new_raw in new_raws {
+            let NewShippingRatesRaw {
+                company_package_id,
+                from_alpha3,
+                to_alpha3,
+                to_zone_id,
+                rates,
+                delivery_time_min_days,
+                delivery_time_max_days,
+                to_subdivision,
+                to_postal_zone_id,
+            } = new_raw;
+
+            if to_zone_id.is_some() {
+                return Err(Error::Internal.context("Rates to zones are not kept by the in-memory repo").into());
+            }
+            // same as the foreign key of the table
+            if !tables
+                .companies_packages
+                .iter()
+                .any(|company_package| company_package.id == company_package_id)
+            {
+                return Err(Error::Internal
+                    .context(format!("Company package {} is not found", company_package_id))
+                    .into());
+            }
+
+            raws.push(ShippingRatesRaw {
+                id: tables.next_shipping_rates_id(),
+                company_package_id,
+                from_alpha3,
+                to_alpha3,
+                rates,
+                to_zone_id,
+                delivery_time_min_days,
+                delivery_time_max_days,
+                is_active: true,
+                components: serde_json::Value::Array(vec![]),
+                to_subdivision,
+                to_postal_zone_id,
+            });
+        }
+        tables.shipping_rates.extend(raws.iter().cloned());
+
+        ShippingRatesRaw::resolve_zones(raws, &[])
+    }
+
+    fn delete_all_rates_from(&self, company_package_id: CompanyPackageId, delivery_from: Alpha3) -> RepoResult<Vec<ShippingRates>> {
+        acl::check(&*self.acl, Resource::ShippingRates, Action::Delete, self, None)?;
+
+        let mut tables = lock(&self.tables);
+        let (removed, kept): (Vec<_>, Vec<_>) = tables
+            .shipping_rates
+            .drain(..)
+            .partition(|raw| is_route_row(raw, company_package_id, &delivery_from));
+        tables.shipping_rates = kept;
+
+        ShippingRatesRaw::resolve_zones(removed, &[])
+    }
+
+    fn get_active_batch(&self, after_id: Option<ShippingRatesId>, batch_size: i64) -> RepoResult<Vec<ShippingRatesRaw>> {
+        acl::check(&*self.acl, Resource::ShippingRates, Action::Read, self, None)?;
+
+        let after_id = after_id.map(|id| id.0).unwrap_or(0);
+        Ok(lock(&self.tables)
+            .shipping_rates
+            .iter()
+            .filter(|raw| raw.is_active && raw.id.0 > after_id)
+            .take(batch_size as usize)
+            .cloned()
+            .collect())
+    }
+
+    fn deactivate(&self, ids: Vec<ShippingRatesId>) -> RepoResult<usize> {
+        acl::check(&*self.acl, Resource::ShippingRates, Action::Update, self, None)?;
+
+        let mut tables = lock(&self.tables);
+        let mut deactivated = 0;
+        for raw in tables.shipping_rates.iter_mut().filter(|raw| ids.contains(&raw.id)) {
+            raw.is_active = false;
+            deactivated += 1;
+        }
+
+        Ok(deactivated)
+    }
+
+    fn count_rate_entries(&self, company_id: CompanyId, except: Option<(CompanyPackageId, Alpha3)>) -> RepoResult<i64> {
+        acl::check(&*self.acl, Resource::ShippingRates, Action::Read, self, None)?;
+
+        let tables = lock(&self.tables);
+        let company_package_ids = tables
+            .companies_packages
+            .iter()
+            .filter(|company_package| company_package.company_id == company_id)
+            .map(|company_package| company_package.id)
+            .collect::<Vec<_>>();
+
+        Ok(tables
+            .shipping_rates
+            .iter()
+            .filter(|raw| company_package_ids.contains(&raw.company_package_id))
+            .filter(|raw| {
+                except
+                    .as_ref()
+                    .map(|&(company_package_id, ref delivery_from)| !is_route_row(raw, company_package_id, delivery_from))
+                    .unwrap_or(true)
+            })
+            .map(|raw| raw.rates.as_array().map(|rates| rates.len() as i64).unwrap_or_default())
+            .sum())
+    }
+
+    fn add_components(
+        &self,
+        company_package_id: CompanyPackageId,
+        delivery_from: Alpha3,
+        components: Vec<RateComponent>,
+        today: NaiveDate,
+    ) -> RepoResult<Vec<ShippingRates>> {
+        acl::check(&*self.acl, Resource::ShippingRates, Action::Update, self, None)?;
+
+        let mut tables = lock(&self.tables);
+        let mut updated_raws = vec![];
+        for raw in tables
+            .shipping_rates
+            .iter_mut()
+            .filter(|raw| is_route_row(raw, company_package_id, &delivery_from))
+        {
+            let mut row_components = raw.parse_components()?;
+            row_components.retain(|component| !component.is_expired_on(today));
+            row_components.extend(components.iter().cloned());
+            raw.components = serde_json::to_value(row_components)?;
+            updated_raws.push(raw.clone());
+        }
+
+        ShippingRatesRaw::resolve_zones(updated_raws, &[])
+    }
+}
+
+impl CheckScope<Scope, ()> for InMemoryShippingRatesRepo {
+    fn is_in_scope(&self, _user_id: UserId, _scope: &Scope, _obj: Option<&()>) -> bool {
+        true
+    }
+}
+
+/// Company suspensions kept in memory
+pub struct InMemoryCompanySuspensionsRepo {
+    tables: SharedTables,
+    acl: Box<dyn Acl<Resource, Action, Scope, FailureError, CompanySuspension>>,
+}
+
+impl CompanySuspensionsRepo for InMemoryCompanySuspensionsRepo {
+    fn list(&self) -> RepoResult<Vec<CompanySuspension>> {
+        acl::check(&*self.acl, Resource::CompanySuspensions, Action::Read, self, None)?;
+
+        Ok(lock(&self.tables).company_suspensions.clone())
+    }
+
+    fn find(&self, suspension_id: i32) -> RepoResult<Option<CompanySuspension>> {
+        acl::check(&*self.acl, Resource::CompanySuspensions, Action::Read, self, None)?;
+
+        Ok(lock(&self.tables)
+            .company_suspensions
+            .iter()
+            .find(|suspension| suspension.id == suspension_id)
+            .cloned())
+    }
+
+    fn find_active_on(&self, date: NaiveDate) -> RepoResult<Vec<CompanySuspension>> {
+        acl::check(&*self.acl, Resource::CompanySuspensions, Action::Read, self, None)?;
+
+        Ok(lock(&self.tables)
+            .company_suspensions
+            .iter()
+            .filter(|suspension| suspension.is_active_on(date))
+            .cloned()
+            .collect())
+    }
+
+    fn create(&self, payload: NewCompanySuspension) -> RepoResult<CompanySuspension> {
+        acl::check(&*self.acl, Resource::CompanySuspensions, Action::Moderate, self, None)?;
+
+        let NewCompanySuspension {
+            company_id,
+            company_package_id,
+            starts_on,
+            ends_on,
+            reason,
+        } = payload;
+
+        let mut tables = lock(&self.tables);
+        // same as the foreign keys of the table
+        let company_package_exists = company_package_id
+            .map(|id| tables.companies_packages.iter().any(|company_package| company_package.id == id))
+            .unwrap_or(true);
+        if !tables.companies.iter().any(|company| company.id == company_id) || !company_package_exists {
+            return Err(Error::Internal
+                .context(format!(
+                    "Company {} or company package {:?} is not found",
+                    company_id, company_package_id
+                ))
+                .into());
+        }
+
+        let suspension = CompanySuspension {
+            id: tables.next_company_suspension_id(),
+            company_id,
+            company_package_id,
+            starts_on,
+            ends_on,
+            reason,
+            created_at: SystemTime::now(),
+        };
+        tables.company_suspensions.push(suspension.clone());
+        Ok(suspension)
+    }
+
+    fn update(&self, suspension_id: i32, payload: UpdateCompanySuspension) -> RepoResult<CompanySuspension> {
+        acl::check(&*self.acl, Resource::CompanySuspensions, Action::Moderate, self, None)?;
+
+        let UpdateCompanySuspension {
+            starts_on,
+            ends_on,
+            reason,
+        } = payload;
+
+        let mut tables = lock(&self.tables);
+        let suspension = tables
+            .company_suspensions
+            .iter_mut()
+            .find(|suspension| suspension.id == suspension_id)
+            .ok_or_else(|| not_found(format!("Company suspension {} is not found", suspension_id)))?;
+        if let Some(starts_on) = starts_on {
+            suspension.starts_on = starts_on;
+        }
+        if let Some(ends_on) = ends_on {
+            suspension.ends_on = ends_on;
+        }
+        if let Some(reason) = reason {
+            suspension.reason = reason;
+        }
+
+        Ok(suspension.clone())
+    }
+
+    fn delete(&self, suspension_id: i32) -> RepoResult<CompanySuspension> {
+        acl::check(&*self.acl, Resource::CompanySuspensions, Action::Moderate, self, None)?;
+
+        let mut tables = lock(&self.tables);
+        let position = tables
+            .company_suspensions
+            .iter()
+            .position(|suspension| suspension.id == suspension_id)
+            .ok_or_else(|| not_found(format!("Company suspension {} is not found", suspension_id)))?;
+
+        Ok(tables.company_suspensions.remove(position))
+    }
+}
+
+impl CheckScope<Scope, CompanySuspension> for InMemoryCompanySuspensionsRepo {
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&CompanySuspension>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
+
+pub struct InMemoryShipmentBookingsRepo {
+    tables: SharedTables,
+    acl: Box<dyn Acl<Resource, Action, Scope, FailureError, ShipmentBooking>>,
+}
+
+impl ShipmentBookingsRepo for InMemoryShipmentBookingsRepo {
+    fn find_many(&self, keys: Vec<(CompanyPackageId, NaiveDate)>) -> RepoResult<Vec<ShipmentBooking>> {
+        acl::check(&*self.acl, Resource::ShipmentBookings, Action::Read, self, None)?;
+
+        Ok(lock(&self.tables)
+            .shipment_bookings
+            .iter()
+            .filter(|booking| keys.contains(&(booking.company_package_id, booking.shipping_date)))
+            .cloned()
+            .collect())
+    }
+
+    fn book(&self, company_package_id: CompanyPackageId, shipping_date: NaiveDate) -> RepoResult<ShipmentBooking> {
+        acl::check(&*self.acl, Resource::ShipmentBookings, Action::Create, self, None)?;
+
+        let mut tables = lock(&self.tables);
+        // same as the foreign key of the table
+        if !tables
+            .companies_packages
+            .iter()
+            .any(|company_package| company_package.id == company_package_id)
+        {
+            return Err(Error::Internal
+                .context(format!("Company package {} is not found", company_package_id))
+                .into());
+        }
+
+        let position = tables
+            .shipment_bookings
+            .iter()
+            .position(|booking| booking.company_package_id == company_package_id && booking.shipping_date == shipping_date);
+        match position {
+            Some(position) => {
+                let booking = &mut tables.shipment_bookings[position];
+                booking.shipments_count += 1;
+                Ok(booking.clone())
+            }
+            None => {
+                let booking = ShipmentBooking {
+                    company_package_id,
+                    shipping_date,
+                    shipments_count: 1,
+                };
+                tables.shipment_bookings.push(booking.clone());
+                Ok(booking)
+            }
+        }
+    }
+}
+
+impl CheckScope<Scope, ShipmentBooking> for InMemoryShipmentBookingsRepo {
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&ShipmentBooking>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio_core::reactor::Core;
+
+    use base64;
+    use chrono::NaiveDate;
+    use diesel::Connection;
+    use futures::future;
+    use hyper::header::Authorization;
+    use hyper::server::Request;
+    use hyper::Method;
+    use r2d2::ManageConnection;
+    use serde_json;
+
+    use stq_http::controller::Controller;
+    use stq_static_resources::Currency;
+    use stq_types::*;
+
+    use carriers::{CarrierFuture, CarrierRateRequest, CarriersClient};
+    use controller::ControllerImpl;
+    use errors::Error;
+    use models::*;
+    use repos::in_memory::*;
+    use repos::repo_factory::tests::*;
+    use repos::ReposFactory;
+    use request_context::RequestContext;
+    use services::companies::CompaniesService;
+    use services::companies_packages::{
+        CompaniesPackagesService, DeliveryPriceDetails, DeliveryPriceSource, GetDeliveryPrice, ReplaceShippingRatesPayload,
+    };
+    use services::products::ProductsService;
+
+    fn create_factory() -> InMemoryReposFactory<ReposFactoryMock> {
+        let factory = InMemoryReposFactory::new(MOCK_REPO_FACTORY);
+        let conn = factory.connection_manager().connect().unwrap();
+        factory
+            .create_user_roles_repo_with_sys_acl(&conn)
+            .create(NewUserRole {
+                id: RoleId::new(),
+                user_id: MOCK_USER_ID,
+                name: DeliveryRole::Superuser,
+                data: None,
+            })
+            .unwrap();
+        factory
+    }
+
+    fn new_company(label: &str) -> NewCompany {
+        NewCompany {
+            name: format!("{} Russia", label),
+            label: label.to_string(),
+            description: None,
+            deliveries_from: vec![Alpha3("RUS".to_string())],
+            logo: "".to_string(),
+            currency: Currency::USD,
+            capabilities: vec![],
+            label_translations: vec![],
+        }
+    }
+
+    fn new_packages(deliveries_to: Vec<Alpha3>) -> NewPackages {
+        NewPackages {
+            name: "package1".to_string(),
+            max_size: 100,
+            min_size: 0,
+            max_weight: 100,
+            min_weight: 0,
+            deliveries_to,
+            deliveries_to_zones: vec![],
+            name_translations: vec![],
+        }
+    }
+
+    fn new_company_package(company_id: CompanyId, package_id: PackageId) -> NewCompanyPackage {
+        NewCompanyPackage {
+            company_id,
+            package_id,
+            shipping_rate_source: None,
+            restricted_hazard_classes: vec![],
+            delivery_time_min_days: None,
+            delivery_time_max_days: None,
+            rate_resolution_order: None,
+            insurance_options: vec![],
+            cod_supported: false,
+            cod_fee: None,
+            carrier_fallback: CarrierFallback::StoredRates,
+            fallback_markup_percent: None,
+            daily_cutoff_time: None,
+            max_daily_shipments: None,
+        }
+    }
+
+    #[test]
+    fn tables_are_shared_by_clones_of_factory() {
+        let factory = create_factory();
+        let conn = factory.connection_manager().connect().unwrap();
+
+        let companies_repo = factory.create_companies_repo(&conn, Some(MOCK_USER_ID));
+        let ups = companies_repo.create(new_company("UPS")).unwrap();
+        let dhl = companies_repo.create(new_company("DHL")).unwrap();
+        assert_ne!(ups.id, dhl.id);
+
+        let factory = factory.clone();
+        let companies_repo = factory.create_companies_repo(&conn, Some(MOCK_USER_ID));
+        let search = CompaniesSearch {
+            sort: Some(CompaniesSortField::Label),
+            ..CompaniesSearch::default()
+        };
+        let labels = companies_repo
+            .list(search)
+            .unwrap()
+            .into_iter()
+            .map(|company| company.label)
+            .collect::<Vec<_>>();
+        assert_eq!(labels, vec!["DHL".to_string(), "UPS".to_string()]);
+    }
+
+    #[test]
+    fn company_packages_are_deleted_with_company() {
+        let factory = create_factory();
+        let conn = factory.connection_manager().connect().unwrap();
+
+        let company = factory
+            .create_companies_repo(&conn, Some(MOCK_USER_ID))
+            .create(new_company("UPS"))
+            .unwrap();
+        let package = factory
+            .create_packages_repo(&conn, Some(MOCK_USER_ID))
+            .create(new_packages(vec![Alpha3("RUS".to_string())]))
+            .unwrap();
+        let companies_packages_repo = factory.create_companies_packages_repo(&conn, Some(MOCK_USER_ID));
+        companies_packages_repo.create(new_company_package(company.id, package.id)).unwrap();
+        assert!(companies_packages_repo
+            .create(new_company_package(company.id, PackageId(package.id.0 + 1)))
+            .is_err());
+
+        let available = companies_packages_repo
+            .get_available_packages(vec![company.id], 10, 10, Alpha3("RUS".to_string()))
+            .unwrap();
+        assert_eq!(available.len(), 1);
+        assert_eq!(available[0].name, "UPS-package1");
+
+        let companies_repo = factory.create_companies_repo(&conn, Some(MOCK_USER_ID));
+        assert_eq!(companies_repo.deletion_impact(company.id).unwrap().company_packages, 1);
+        companies_repo.delete(company.id).unwrap();
+        assert!(companies_packages_repo.find_by_ids(company.id, package.id).unwrap().is_none());
+    }
+
+    #[test]
+    fn rolled_back_transaction_leaves_tables_unchanged() {
+        let factory = create_factory();
+        let conn = factory.connection_manager().connect().unwrap();
+        let companies_repo = factory.create_companies_repo(&conn, Some(MOCK_USER_ID));
+
+        let result = conn.transaction::<(), Error, _>(|| {
+            companies_repo.create(new_company("UPS")).unwrap();
+            Err(Error::Internal)
+        });
+        assert!(result.is_err());
+        assert!(companies_repo.list(CompaniesSearch::default()).unwrap().is_empty());
+
+        conn.transaction::<_, Error, _>(|| Ok(companies_repo.create(new_company("UPS")).unwrap()))
+            .unwrap();
+        assert_eq!(companies_repo.list(CompaniesSearch::default()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn actions_are_denied_without_roles() {
+        let factory = create_factory();
+        let conn = factory.connection_manager().connect().unwrap();
+
+        assert!(factory.create_companies_repo(&conn, None).create(new_company("UPS")).is_err());
+        assert!(factory
+            .create_companies_repo(&conn, Some(UserId(123)))
+            .create(new_company("UPS"))
+            .is_err());
+        assert!(factory
+            .create_companies_repo(&conn, Some(MOCK_USER_ID))
+            .create(new_company("UPS"))
+            .is_ok());
+    }
+
+    /// Stores the company package with the rates to the USA, the first company package is used by the mock product
+    fn create_used_company_package<C: DbConnection>(factory: &InMemoryReposFactory<ReposFactoryMock>, conn: &C) -> CompanyPackage {
+        create_company_package_with_rates(factory, conn, None)
+    }
+
+    /// Stores the company package with the price source and the rate of 10.0 for up to 1 kg from Russia to the USA
+    fn create_company_package_with_rates<C: DbConnection>(
+        factory: &InMemoryReposFactory<ReposFactoryMock>,
+        conn: &C,
+        shipping_rate_source: Option<ShippingRateSource>,
+    ) -> CompanyPackage {
+        let company = factory
+            .create_companies_repo(conn, Some(MOCK_USER_ID))
+            .create(new_company("UPS"))
+            .unwrap();
+        let package = factory
+            .create_packages_repo(conn, Some(MOCK_USER_ID))
+            .create(new_packages(vec![Alpha3("USA".to_string())]))
+            .unwrap();
+        let company_package = factory
+            .create_companies_packages_repo(conn, Some(MOCK_USER_ID))
+            .create(NewCompanyPackage {
+                shipping_rate_source,
+                ..new_company_package(company.id, package.id)
+            })
+            .unwrap();
+        assert_eq!(company_package.id, CompanyPackageId(1));
+
+        factory
+            .create_shipping_rates_repo(conn, Some(MOCK_USER_ID))
+            .insert_many(vec![NewShippingRates {
+                company_package_id: company_package.id,
+                from_alpha3: Alpha3("RUS".to_string()),
+                to: ShippingRatesDestination::Country(Alpha3("USA".to_string())),
+                rates: vec![ShippingRate {
+                    weight_g: 1000,
+                    price: 10.0,
+                }],
+                delivery_time_min_days: None,
+                delivery_time_max_days: None,
+            }])
+            .unwrap();
+        company_package
+    }
+
+    /// Company package with its rates is kept or deleted as a whole
+    fn assert_company_package_kept<C: DbConnection>(
+        factory: &InMemoryReposFactory<ReposFactoryMock>,
+        conn: &C,
+        company_package: &CompanyPackage,
+        kept: bool,
+    ) {
+        let stored = factory
+            .create_companies_packages_repo(conn, Some(MOCK_USER_ID))
+            .find_by_ids(company_package.company_id, company_package.package_id)
+            .unwrap();
+        let rates = factory
+            .create_shipping_rates_repo(conn, Some(MOCK_USER_ID))
+            .get_all_rates_from(company_package.id, Alpha3("RUS".to_string()))
+            .unwrap();
+        assert_eq!(stored.is_some(), kept);
+        assert_eq!(rates.len(), if kept { 1 } else { 0 });
+    }
+
+    #[test]
+    fn company_is_not_deleted_by_service_without_cascade() {
+        let mut core = Core::new().unwrap();
+        let handle = Arc::new(core.handle());
+        let factory = create_factory();
+        let conn = factory.connection_manager().connect().unwrap();
+        let company_package = create_used_company_package(&factory, &conn);
+        let company_id = company_package.company_id;
+        let service = create_in_memory_service(factory.clone(), Some(MOCK_USER_ID), handle);
+
+        let impact = core.run(service.get_company_deletion_impact(company_id)).unwrap();
+        assert_eq!(
+            impact,
+            CompanyDeletionImpact {
+                company_packages: 1,
+                products: 0,
+                shipping_rates: 1,
+            }
+        );
+
+        assert_conflict(
+            core.run(service.delete_company(company_id, false)),
+            "Company with packages must not be deleted",
+        );
+        assert!(core.run(service.find_company(company_id)).unwrap().is_some());
+        assert_company_package_kept(&factory, &conn, &company_package, true);
+
+        core.run(service.delete_company(company_id, true)).unwrap();
+        assert!(core.run(service.find_company(company_id)).unwrap().is_none());
+        assert_company_package_kept(&factory, &conn, &company_package, false);
+    }
+
+    #[test]
+    fn used_company_package_is_not_deleted_by_service_without_force() {
+        let mut core = Core::new().unwrap();
+        let handle = Arc::new(core.handle());
+        let factory = create_factory();
+        let conn = factory.connection_manager().connect().unwrap();
+        let company_package = create_used_company_package(&factory, &conn);
+        let service = create_in_memory_service(factory.clone(), Some(MOCK_USER_ID), handle);
+
+        assert_conflict(
+            core.run(service.delete_company_package(company_package.company_id, company_package.package_id, false)),
+            "Used company package must not be deleted",
+        );
+        assert_company_package_kept(&factory, &conn, &company_package, true);
+
+        core.run(service.delete_company_package(company_package.company_id, company_package.package_id, true))
+            .unwrap();
+        assert_company_package_kept(&factory, &conn, &company_package, false);
+    }
+
+    #[test]
+    fn rates_of_missing_company_package_are_not_found() {
+        let mut core = Core::new().unwrap();
+        let handle = Arc::new(core.handle());
+        let service = create_in_memory_service(create_factory(), Some(MOCK_USER_ID), handle);
+        let payload = ReplaceShippingRatesPayload {
+            rates_csv_base64: base64::encode("Weight,Zone\n,1\n0.5,100\n"),
+            zones_csv_base64: base64::encode("From,To,Zone\nRUS,USA,1\n"),
+            delivery_time_min_days: None,
+            delivery_time_max_days: None,
+        };
+
+        assert_not_found(
+            core.run(service.preview_shipping_rates_replacement(CompanyPackageId(1000), payload.clone())),
+            "Rates of missing company package must not be previewed",
+        );
+        assert_not_found(
+            core.run(service.replace_shipping_rates(CompanyPackageId(1000), payload)),
+            "Rates of missing company package must not be replaced",
+        );
+    }
+
+    /// Carrier answering with the price or timing out if there is none, the calls are counted
+    struct CarriersClientMock {
+        price: Option<f64>,
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl CarriersClient for CarriersClientMock {
+        fn get_rate(&self, _request: CarrierRateRequest, _request_context: &RequestContext) -> CarrierFuture<f64> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            match self.price {
+                Some(price) => Box::new(future::ok(price)),
+                None => Box::new(future::err(io::Error::new(io::ErrorKind::TimedOut, "Carrier timed out").into())),
+            }
+        }
+    }
+
+    /// Price of a parcel of the on-demand company package with the carrier answering with the price
+    fn on_demand_delivery_price(carrier_price: Option<f64>) -> (DeliveryPriceDetails, usize) {
+        let mut core = Core::new().unwrap();
+        let handle = Arc::new(core.handle());
+        let factory = create_factory();
+        let conn = factory.connection_manager().connect().unwrap();
+        let on_demand = ShippingRateSource::OnDemand { dimensional_factor: None };
+        let company_package = create_company_package_with_rates(&factory, &conn, Some(on_demand));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let carriers_client = CarriersClientMock {
+            price: carrier_price,
+            calls: calls.clone(),
+        };
+        let service = create_in_memory_service_with_carriers(factory, Some(MOCK_USER_ID), handle, Some(Arc::new(carriers_client)));
+
+        let payload = GetDeliveryPrice {
+            company_package_id: company_package.id,
+            delivery_from: Alpha3("RUS".to_string()),
+            delivery_to: Alpha3("USA".to_string()),
+            volume: 10,
+            weight: 10,
+            coordinates: None,
+            pickup_point_id: None,
+            declared_value: None,
+            insurance: None,
+            cod: false,
+            store_id: None,
+            base_product_id: None,
+            delivery_to_subdivision: None,
+            postal_code: None,
+        };
+        let price = core
+            .run(service.get_delivery_price_details(payload))
+            .unwrap()
+            .expect("On-demand company package must be priced");
+        (price, calls.load(Ordering::SeqCst))
+    }
+
+    #[test]
+    fn on_demand_company_package_is_priced_by_carrier() {
+        let (price, calls) = on_demand_delivery_price(Some(25.0));
+        assert_eq!(calls, 1);
+        assert_eq!(price.value, 25.0);
+        assert_eq!(price.resolved_by, Some(RateResolutionStep::CarrierQuote));
+        match price.source {
+            DeliveryPriceSource::Carrier => {}
+            source => panic!("Unexpected source of the carrier price: {:?}", source),
+        }
+    }
+
+    #[test]
+    fn timed_out_carrier_quote_falls_back_to_stored_rates() {
+        let (price, calls) = on_demand_delivery_price(None);
+        assert_eq!(calls, 1);
+        assert_eq!(price.value, 10.0);
+        assert_eq!(price.resolved_by, Some(RateResolutionStep::ExactDestination));
+        match price.source {
+            DeliveryPriceSource::Fallback => {}
+            source => panic!("Unexpected source of the fallback price: {:?}", source),
+        }
+    }
+
+    #[test]
+    fn rates_and_suspensions_are_deleted_with_company_package() {
+        let factory = create_factory();
+        let conn = factory.connection_manager().connect().unwrap();
+
+        let company = factory
+            .create_companies_repo(&conn, Some(MOCK_USER_ID))
+            .create(new_company("UPS"))
+            .unwrap();
+        let package = factory
+            .create_packages_repo(&conn, Some(MOCK_USER_ID))
+            .create(new_packages(vec![Alpha3("USA".to_string())]))
+            .unwrap();
+        let company_package = factory
+            .create_companies_packages_repo(&conn, Some(MOCK_USER_ID))
+            .create(new_company_package(company.id, package.id))
+            .unwrap();
+
+        let shipping_rates_repo = factory.create_shipping_rates_repo(&conn, Some(MOCK_USER_ID));
+        shipping_rates_repo
+            .insert_many(vec![NewShippingRates {
+                company_package_id: company_package.id,
+                from_alpha3: Alpha3("RUS".to_string()),
+                to: ShippingRatesDestination::Country(Alpha3("USA".to_string())),
+                rates: vec![ShippingRate {
+                    weight_g: 1000,
+                    price: 10.0,
+                }],
+                delivery_time_min_days: None,
+                delivery_time_max_days: None,
+            }])
+            .unwrap();
+        let rates = shipping_rates_repo
+            .get_rates(company_package.id, Alpha3("RUS".to_string()), Alpha3("USA".to_string()))
+            .unwrap()
+            .expect("Rates must be found");
+        assert_eq!(
+            rates.rates,
+            vec![ShippingRate {
+                weight_g: 1000,
+                price: 10.0
+            }]
+        );
+        assert_eq!(shipping_rates_repo.count_rate_entries(company.id, None).unwrap(), 1);
+
+        let company_suspensions_repo = factory.create_company_suspensions_repo(&conn, Some(MOCK_USER_ID));
+        let suspension = company_suspensions_repo
+            .create(NewCompanySuspension {
+                company_id: company.id,
+                company_package_id: Some(company_package.id),
+                starts_on: NaiveDate::from_ymd(2019, 3, 20),
+                ends_on: NaiveDate::from_ymd(2019, 3, 22),
+                reason: "Customs closure".to_string(),
+            })
+            .unwrap();
+        assert_eq!(
+            company_suspensions_repo.find_active_on(NaiveDate::from_ymd(2019, 3, 21)).unwrap(),
+            vec![suspension]
+        );
+
+        factory
+            .create_companies_packages_repo(&conn, Some(MOCK_USER_ID))
+            .delete(company.id, package.id)
+            .unwrap();
+        assert!(shipping_rates_repo
+            .get_all_rates_from(company_package.id, Alpha3("RUS".to_string()))
+            .unwrap()
+            .is_empty());
+        assert!(company_suspensions_repo.list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn available_packages_are_served_by_controller() {
+        let mut core = Core::new().unwrap();
+        let handle = Arc::new(core.handle());
+        let service = create_in_memory_service(create_factory(), Some(MOCK_USER_ID), handle);
+        let repo_factory = service.static_context.repo_factory.clone();
+        let conn = repo_factory.connection_manager().connect().unwrap();
+
+        let company = repo_factory
+            .create_companies_repo(&conn, Some(MOCK_USER_ID))
+            .create(new_company("UPS"))
+            .unwrap();
+        let package = repo_factory
+            .create_packages_repo(&conn, Some(MOCK_USER_ID))
+            .create(new_packages(vec![Alpha3("RUS".to_string())]))
+            .unwrap();
+        repo_factory
+            .create_companies_packages_repo(&conn, Some(MOCK_USER_ID))
+            .create(new_company_package(company.id, package.id))
+            .unwrap();
+
+        let controller = ControllerImpl::new(service.static_context.clone());
+        let get_available_packages = |core: &mut Core, with_measurements: bool| {
+            let uri = format!(
+                "/available_packages?country=RUS&size=10&weight=0.05&weight_unit=kg&with_measurements={}",
+                with_measurements
+            );
+            let mut req = Request::new(Method::Get, uri.parse().unwrap());
+            req.headers_mut().set(Authorization(MOCK_USER_ID.0.to_string()));
+            core.run(controller.call(req)).unwrap()
+        };
+
+        let available = serde_json::from_str::<AvailablePackagesForShipment>(&get_available_packages(&mut core, true)).unwrap();
+        assert_eq!(available.measurements.weight_g, 50);
+        assert_eq!(available.packages.len(), 1);
+        assert_eq!(available.packages[0].name, "UPS-package1");
+        // the packages are listed alone unless the measurements are requested
+        let available = serde_json::from_str::<Vec<AvailablePackages>>(&get_available_packages(&mut core, false)).unwrap();
+        assert_eq!(available.len(), 1);
+
+        let today = service.static_context.clock.today();
+        repo_factory
+            .create_company_suspensions_repo(&conn, Some(MOCK_USER_ID))
+            .create(NewCompanySuspension {
+                company_id: company.id,
+                company_package_id: None,
+                starts_on: today,
+                ends_on: today,
+                reason: "Customs closure".to_string(),
+            })
+            .unwrap();
+        assert!(
+            serde_json::from_str::<Vec<AvailablePackages>>(&get_available_packages(&mut core, false))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn booked_up_company_package_is_not_available() {
+        let mut core = Core::new().unwrap();
+        let handle = Arc::new(core.handle());
+        let factory = create_factory();
+        let conn = factory.connection_manager().connect().unwrap();
+        let static_rates = ShippingRateSource::Static { dimensional_factor: None };
+        let company_package = create_company_package_with_rates(&factory, &conn, Some(static_rates));
+        // the region of the mock countries is Russia, so the rates back to it are added
+        factory
+            .create_shipping_rates_repo(&conn, Some(MOCK_USER_ID))
+            .insert_many(vec![NewShippingRates {
+                company_package_id: company_package.id,
+                from_alpha3: Alpha3("USA".to_string()),
+                to: ShippingRatesDestination::Country(Alpha3("RUS".to_string())),
+                rates: vec![ShippingRate {
+                    weight_g: 1000,
+                    price: 10.0,
+                }],
+                delivery_time_min_days: None,
+                delivery_time_max_days: None,
+            }])
+            .unwrap();
+        let service = create_in_memory_service(factory, Some(MOCK_USER_ID), handle);
+        core.run(service.set_shipping_capacity(
+            company_package.id,
+            SetShippingCapacity {
+                daily_cutoff_time: None,
+                max_daily_shipments: Some(1),
+            },
+        ))
+        .unwrap();
+
+        let available_packages = |core: &mut Core| core.run(service.get_available_packages(Alpha3("RUS".to_string()), 10, 10)).unwrap();
+        let region_packages = |core: &mut Core| {
+            core.run(service.find_available_shipping_for_region(
+                MOCK_BASE_PRODUCT_ID,
+                Alpha3("USA".to_string()),
+                "RUS".to_string(),
+                10,
+                10,
+                None,
+            ))
+            .unwrap()
+            .packages
+        };
+        assert_eq!(available_packages(&mut core).len(), 1);
+        assert_eq!(region_packages(&mut core).len(), 1);
+
+        core.run(service.book_shipment(company_package.id, BookShipment::default()))
+            .unwrap();
+        assert!(available_packages(&mut core).is_empty());
+        assert!(region_packages(&mut core).is_empty());
+
+        let explanation = core
+            .run(service.explain_available_packages(Alpha3("RUS".to_string()), 10, 10))
+            .unwrap();
+        assert!(explanation.packages.is_empty());
+        match explanation.unavailable_packages[0].reasons[..] {
+            [UnavailabilityReason::BookedUp { .. }] => {}
+            ref reasons => panic!("Company package must be booked up, reasons: {:?}", reasons),
+        }
+    }
+}
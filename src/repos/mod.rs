@@ -1,38 +1,100 @@
 pub mod acl;
+pub mod admin_overview;
+pub mod api_keys;
+pub mod availability_snapshots;
+pub mod backfills;
+pub mod carrier_accounts;
 pub mod companies;
 pub mod companies_packages;
+pub mod company_rates_quotas;
+pub mod company_suspensions;
 pub mod countries;
+pub mod country_dependents;
+pub mod country_regulations;
+pub mod country_subdivisions;
+pub mod customs_info;
+pub mod delivery_quotes;
+pub mod event_outbox;
+#[cfg(any(test, feature = "in-memory-repos"))]
+pub mod in_memory;
+pub mod package_templates;
 pub mod packages;
+pub mod permissions;
+pub mod pickup_points;
 pub mod pickups;
+pub mod postal_zones;
+pub mod pricing_rules;
 pub mod products;
 pub mod repo_factory;
+pub mod shipment_bookings;
+pub mod shipment_notifications;
 pub mod shipping_rates;
+pub mod shipping_rates_anomalies;
+pub mod shipping_restrictions;
+pub mod shipping_templates;
+pub mod store_managers;
+pub mod store_margins;
+pub mod store_shipping_summaries;
+pub mod surcharges;
+#[cfg(feature = "test-endpoints")]
+pub mod test_fixtures;
 pub mod types;
 pub mod user_addresses;
 pub mod user_roles;
+pub mod zones;
 
 pub use self::acl::*;
+pub use self::admin_overview::*;
+pub use self::api_keys::*;
+pub use self::availability_snapshots::*;
+pub use self::backfills::*;
+pub use self::carrier_accounts::*;
 pub use self::companies::*;
 pub use self::companies_packages::*;
+pub use self::company_rates_quotas::*;
+pub use self::company_suspensions::*;
 pub use self::countries::*;
+pub use self::country_dependents::*;
+pub use self::country_regulations::*;
+pub use self::country_subdivisions::*;
+pub use self::customs_info::*;
+pub use self::delivery_quotes::*;
+pub use self::event_outbox::*;
+pub use self::package_templates::*;
 pub use self::packages::*;
+pub use self::permissions::*;
+pub use self::pickup_points::*;
 pub use self::pickups::*;
+pub use self::postal_zones::*;
+pub use self::pricing_rules::*;
 pub use self::products::*;
 pub use self::repo_factory::*;
+pub use self::shipment_bookings::*;
+pub use self::shipment_notifications::*;
 pub use self::shipping_rates::*;
+pub use self::shipping_rates_anomalies::*;
+pub use self::shipping_restrictions::*;
+pub use self::shipping_templates::*;
+pub use self::store_managers::*;
+pub use self::store_margins::*;
+pub use self::store_shipping_summaries::*;
+pub use self::surcharges::*;
+#[cfg(feature = "test-endpoints")]
+pub use self::test_fixtures::*;
 pub use self::types::*;
 pub use self::user_addresses::*;
 pub use self::user_roles::*;
+pub use self::zones::*;
 
-use stq_types::Alpha3;
-
-pub fn get_pg_str_json_array(countries: Vec<Alpha3>) -> String {
-    let res = countries
-        .into_iter()
-        .map(|s| format!("'{}'", s.0))
-        .collect::<Vec<String>>()
-        .join(",");
-    format!("array[{}]", res)
+/// Escapes `%`, `_` and backslashes so that user input is matched literally by `LIKE` / `ILIKE`
+pub fn escape_like_pattern(value: &str) -> String {
+    value.chars().fold(String::with_capacity(value.len()), |mut escaped, c| {
+        if c == '%' || c == '_' || c == '\\' {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+        escaped
+    })
 }
 
 pub fn get_company_package_name(company_name: &str, package_name: &str) -> String {
@@ -0,0 +1,140 @@
+//! Repo for package_templates table. Package templates are the default company packages
+//! suggested to the stores of the origin country
+
+use diesel;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use errors::Error;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::{Alpha3, UserId};
+
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::types::{DbConnection, RepoResult};
+use models::authorization::*;
+use models::{NewPackageTemplate, PackageTemplate, UpdatePackageTemplate};
+use schema::package_templates::dsl as DslPackageTemplates;
+
+/// Package templates repository for handling PackageTemplates
+pub trait PackageTemplatesRepo {
+    /// Returns list of package templates, optionally only of the origin country
+    fn list(&self, from_alpha3: Option<Alpha3>) -> RepoResult<Vec<PackageTemplate>>;
+
+    /// Find specific package template by id
+    fn find(&self, package_template_id: i32) -> RepoResult<Option<PackageTemplate>>;
+
+    /// Create a new package template
+    fn create(&self, payload: NewPackageTemplate) -> RepoResult<PackageTemplate>;
+
+    /// Update a package template
+    fn update(&self, package_template_id: i32, payload: UpdatePackageTemplate) -> RepoResult<PackageTemplate>;
+
+    /// Delete a package template
+    fn delete(&self, package_template_id: i32) -> RepoResult<PackageTemplate>;
+}
+
+/// Implementation of PackageTemplates trait
+pub struct PackageTemplatesRepoImpl<'a, T: DbConnection> {
+    pub db_conn: &'a T,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, PackageTemplate>>,
+}
+
+impl<'a, T: DbConnection> PackageTemplatesRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, PackageTemplate>>) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: DbConnection> PackageTemplatesRepo for PackageTemplatesRepoImpl<'a, T> {
+    /// Returns list of package templates, optionally only of the origin country
+    fn list(&self, from_alpha3: Option<Alpha3>) -> RepoResult<Vec<PackageTemplate>> {
+        acl::check(&*self.acl, Resource::PackageTemplates, Action::Read, self, None)?;
+
+        let mut query = DslPackageTemplates::package_templates
+            .order((
+                DslPackageTemplates::from_alpha3,
+                DslPackageTemplates::position,
+                DslPackageTemplates::id,
+            ))
+            .into_boxed();
+
+        if let Some(from_alpha3) = from_alpha3.clone() {
+            query = query.filter(DslPackageTemplates::from_alpha3.eq(from_alpha3));
+        }
+
+        query.get_results::<PackageTemplate>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!("list of package templates from {:?} error occurred", from_alpha3))
+                .into()
+        })
+    }
+
+    /// Find specific package template by id
+    fn find(&self, package_template_id: i32) -> RepoResult<Option<PackageTemplate>> {
+        acl::check(&*self.acl, Resource::PackageTemplates, Action::Read, self, None)?;
+
+        let query = DslPackageTemplates::package_templates.find(package_template_id);
+
+        query.get_result::<PackageTemplate>(self.db_conn).optional().map_err(|e| {
+            Error::from(e)
+                .context(format!("Find package template with id {} error occurred", package_template_id))
+                .into()
+        })
+    }
+
+    /// Create a new package template
+    fn create(&self, payload: NewPackageTemplate) -> RepoResult<PackageTemplate> {
+        acl::check(&*self.acl, Resource::PackageTemplates, Action::Create, self, None)?;
+
+        let query = diesel::insert_into(DslPackageTemplates::package_templates).values(&payload);
+
+        query.get_result::<PackageTemplate>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!("Create a new package template {:?} error occurred", payload))
+                .into()
+        })
+    }
+
+    /// Update a package template
+    fn update(&self, package_template_id: i32, payload: UpdatePackageTemplate) -> RepoResult<PackageTemplate> {
+        acl::check(&*self.acl, Resource::PackageTemplates, Action::Update, self, None)?;
+
+        let filter = DslPackageTemplates::package_templates.filter(DslPackageTemplates::id.eq(package_template_id));
+        let query = diesel::update(filter).set(&payload);
+
+        query.get_result::<PackageTemplate>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!(
+                    "Update package template {} with payload {:?} error occurred",
+                    package_template_id, payload
+                ))
+                .into()
+        })
+    }
+
+    /// Delete a package template
+    fn delete(&self, package_template_id: i32) -> RepoResult<PackageTemplate> {
+        acl::check(&*self.acl, Resource::PackageTemplates, Action::Delete, self, None)?;
+
+        let filtered = DslPackageTemplates::package_templates.filter(DslPackageTemplates::id.eq(package_template_id));
+        let query = diesel::delete(filtered);
+
+        query.get_result::<PackageTemplate>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!("Delete package template {} error occurred", package_template_id))
+                .into()
+        })
+    }
+}
+
+impl<'a, T: DbConnection> CheckScope<Scope, PackageTemplate> for PackageTemplatesRepoImpl<'a, T> {
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&PackageTemplate>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
@@ -1,15 +1,13 @@
 //! Repo Packages table.
 
 use diesel;
-use diesel::connection::AnsiTransactionManager;
 use diesel::dsl::sql;
 use diesel::pg::types::sql_types::Array;
 use diesel::pg::Pg;
 use diesel::prelude::*;
 use diesel::query_dsl::LoadQuery;
 use diesel::query_dsl::RunQueryDsl;
-use diesel::sql_types::VarChar;
-use diesel::Connection;
+use diesel::sql_types::{Bool, Int4, VarChar};
 
 use errors::Error;
 use failure::Error as FailureError;
@@ -18,12 +16,14 @@ use stq_types::{Alpha3, PackageId, UserId};
 
 use models::authorization::*;
 use models::countries::Country;
-use models::packages::{NewPackages, Packages, PackagesRaw, UpdatePackages};
+use models::packages::{NewPackages, Packages, PackagesRaw, PackagesSearchResult, UpdatePackages};
 use repos::legacy_acl::*;
 use repos::types::RepoResult;
 use repos::*;
 
+use request_context::log_line;
 use schema::packages::dsl::*;
+use schema::zones::dsl as DslZones;
 
 /// Packages repository for handling Packages
 pub trait PackagesRepo {
@@ -33,6 +33,10 @@ pub trait PackagesRepo {
     /// Returns list of packages supported by the country
     fn find_deliveries_to(&self, countries: Vec<Alpha3>) -> RepoResult<Vec<Packages>>;
 
+    /// Returns the page of packages delivering to any of the countries, directly or by a zone, ordered by id.
+    /// Packages with `max_weight` less than `max_weight_arg` are skipped
+    fn search(&self, countries: Vec<Alpha3>, max_weight_arg: Option<u32>, offset: i64, limit: i64) -> RepoResult<PackagesSearchResult>;
+
     /// Returns list of packages
     fn list(&self) -> RepoResult<Vec<Packages>>;
 
@@ -47,32 +51,63 @@ pub trait PackagesRepo {
 }
 
 /// Implementation of UserRoles trait
-pub struct PackagesRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+pub struct PackagesRepoImpl<'a, T: DbConnection> {
     pub db_conn: &'a T,
-    pub acl: Box<Acl<Resource, Action, Scope, FailureError, Packages>>,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, Packages>>,
     pub countries: Country,
 }
 
-impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> PackagesRepoImpl<'a, T> {
-    pub fn new(db_conn: &'a T, acl: Box<Acl<Resource, Action, Scope, FailureError, Packages>>, countries: Country) -> Self {
+impl<'a, T: DbConnection> PackagesRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, Packages>>, countries: Country) -> Self {
         Self { db_conn, acl, countries }
     }
 
+    fn to_packages(&self, raws: Vec<PackagesRaw>) -> RepoResult<Vec<Packages>> {
+        let zones = get_zones_for_packages(self.db_conn, &raws)?;
+        Ok(raws.into_iter().map(|raw| raw.to_packages(&self.countries, &zones)).collect())
+    }
+
+    fn to_package(&self, raw: PackagesRaw) -> RepoResult<Packages> {
+        let zones = get_zones_for_packages(self.db_conn, &[raw.clone()])?;
+        Ok(raw.to_packages(&self.countries, &zones))
+    }
+
+    /// Packages delivering to any of the countries directly or by any of the zones
+    fn search_query(
+        &self,
+        countries: &[String],
+        zone_ids: &[i32],
+        max_weight_arg: Option<u32>,
+    ) -> ::schema::packages::BoxedQuery<'static, Pg> {
+        // zone ids are stored as JSONB array of numbers, its text is turned into the array literal of Postgres
+        let query =
+            packages
+                .filter(sql::<Bool>("deliveries_to ?| ").bind::<Array<VarChar>, _>(countries.to_vec()).or(
+                    sql::<Bool>("translate(deliveries_to_zones::text, '[]', '{}')::int[] && ").bind::<Array<Int4>, _>(zone_ids.to_vec()),
+                ))
+                .into_boxed();
+
+        match max_weight_arg {
+            Some(max_weight_arg) => query.filter(max_weight.ge(max_weight_arg as i32)),
+            None => query,
+        }
+    }
+
     fn execute_query<Ty: Send + 'static, U: LoadQuery<T, Ty> + Send + 'static>(&self, query: U) -> RepoResult<Ty> {
         query.get_result::<Ty>(self.db_conn).map_err(|e| Error::from(e).into())
     }
 }
 
-impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> PackagesRepo for PackagesRepoImpl<'a, T> {
+impl<'a, T: DbConnection> PackagesRepo for PackagesRepoImpl<'a, T> {
     fn create(&self, payload: NewPackages) -> RepoResult<Packages> {
-        debug!("create new packages_ {:?}.", payload);
-        let payload = payload.to_raw()?;
+        debug!("{}", log_line(&format!("create new packages_ {:?}.", payload)));
+        let payload = payload.to_raw();
 
         let query = diesel::insert_into(packages).values(&payload);
         query
             .get_result::<PackagesRaw>(self.db_conn)
             .map_err(|e| Error::from(e).into())
-            .and_then(|p| p.to_packages(&self.countries))
+            .and_then(|p| self.to_package(p))
             .and_then(|packages_| {
                 acl::check(&*self.acl, Resource::Packages, Action::Create, self, Some(&packages_)).and_then(|_| Ok(packages_))
             })
@@ -81,20 +116,30 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
 
     /// Returns list of packages supported by the country
     fn find_deliveries_to(&self, countries: Vec<Alpha3>) -> RepoResult<Vec<Packages>> {
-        debug!("Find in packages with country {:?}.", countries);
+        debug!("{}", log_line(&format!("Find in packages with country {:?}.", countries)));
 
         let pg_countries: Vec<String> = countries.iter().cloned().map(|c| c.0).collect();
 
-        let query = packages.filter(sql("deliveries_to ?| ").bind::<Array<VarChar>, _>(pg_countries));
+        // packages delivering to zones are filtered after the zones are resolved
+        let query = packages.filter(
+            sql::<Bool>("deliveries_to ?| ")
+                .bind::<Array<VarChar>, _>(pg_countries)
+                .or(sql::<Bool>("jsonb_array_length(deliveries_to_zones) > 0")),
+        );
 
         query
             .get_results(self.db_conn)
             .map_err(|e| Error::from(e).into())
             .and_then(|packages_raw: Vec<PackagesRaw>| {
-                packages_raw
-                    .into_iter()
-                    .map(|packages_raw| packages_raw.to_packages(&self.countries))
-                    .collect()
+                let zones = get_zones_for_packages(self.db_conn, &packages_raw)?;
+                let mut packages_res = vec![];
+                for packages_raw in packages_raw {
+                    let used_codes = packages_raw.get_deliveries_to_with_zones(&zones);
+                    if used_codes.iter().any(|code| countries.contains(code)) {
+                        packages_res.push(packages_raw.to_packages(&self.countries, &zones));
+                    }
+                }
+                Ok(packages_res)
             })
             .and_then(|packages_res: Vec<Packages>| {
                 for packages_ in &packages_res {
@@ -108,16 +153,61 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             })
     }
 
+    /// Returns the page of packages delivering to any of the countries, directly or by a zone, ordered by id.
+    /// Packages with `max_weight` less than `max_weight_arg` are skipped
+    fn search(&self, countries: Vec<Alpha3>, max_weight_arg: Option<u32>, offset: i64, limit: i64) -> RepoResult<PackagesSearchResult> {
+        debug!(
+            "{}",
+            log_line(&format!("Search packages to {:?} with max weight {:?}.", countries, max_weight_arg))
+        );
+        acl::check(&*self.acl, Resource::Packages, Action::Read, self, None)?;
+
+        let pg_countries: Vec<String> = countries.iter().cloned().map(|c| c.0).collect();
+        let zones_query = DslZones::zones
+            .filter(sql::<Bool>("countries ?| ").bind::<Array<VarChar>, _>(pg_countries.clone()))
+            .select(DslZones::id);
+
+        zones_query
+            .get_results::<i32>(self.db_conn)
+            .and_then(|zone_ids| {
+                let total_count = self
+                    .search_query(&pg_countries, &zone_ids, max_weight_arg)
+                    .count()
+                    .get_result::<i64>(self.db_conn)?;
+                let raws = self
+                    .search_query(&pg_countries, &zone_ids, max_weight_arg)
+                    .order(id)
+                    .offset(offset)
+                    .limit(limit)
+                    .get_results::<PackagesRaw>(self.db_conn)?;
+                Ok((total_count, raws))
+            })
+            .map_err(|e| Error::from(e).into())
+            .and_then(|(total_count, raws)| {
+                Ok(PackagesSearchResult {
+                    total_count,
+                    packages: self.to_packages(raws)?,
+                })
+            })
+            .map_err(|e: FailureError| {
+                e.context(format!(
+                    "Search packages to {:?} with max weight {:?} error occured",
+                    countries, max_weight_arg
+                ))
+                .into()
+            })
+    }
+
     /// Returns list of packages
     fn list(&self) -> RepoResult<Vec<Packages>> {
-        debug!("List packages");
+        debug!("{}", log_line(&format!("List packages")));
 
         let query = packages.order(id);
 
         query
             .get_results(self.db_conn)
             .map_err(|e| Error::from(e).into())
-            .and_then(|raws: Vec<PackagesRaw>| raws.into_iter().map(|raw| raw.to_packages(&self.countries)).collect())
+            .and_then(|raws: Vec<PackagesRaw>| self.to_packages(raws))
             .and_then(|results: Vec<Packages>| {
                 for package in &results {
                     acl::check(&*self.acl, Resource::Packages, Action::Read, self, Some(&package))?;
@@ -129,7 +219,7 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
 
     /// Find specific package by ID
     fn find(&self, id_arg: PackageId) -> RepoResult<Option<Packages>> {
-        debug!("Find in package with id {}.", id_arg);
+        debug!("{}", log_line(&format!("Find in package with id {}.", id_arg)));
 
         let query = packages.find(id_arg);
         query
@@ -138,7 +228,7 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             .map_err(|e| Error::from(e).into())
             .and_then(|raw: Option<PackagesRaw>| match raw {
                 Some(value) => {
-                    let package = value.to_packages(&self.countries)?;
+                    let package = self.to_package(value)?;
                     acl::check(&*self.acl, Resource::Packages, Action::Read, self, Some(&package))?;
                     Ok(Some(package))
                 }
@@ -148,11 +238,11 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
     }
 
     fn update(&self, id_arg: PackageId, payload: UpdatePackages) -> RepoResult<Packages> {
-        debug!("Updating packages_ payload {:?}.", payload);
-        let payload = payload.to_raw()?;
+        debug!("{}", log_line(&format!("Updating packages_ payload {:?}.", payload)));
+        let payload = payload.to_raw();
 
         self.execute_query(packages.filter(id.eq(id_arg)))
-            .and_then(|packages_: PackagesRaw| packages_.to_packages(&self.countries))
+            .and_then(|packages_: PackagesRaw| self.to_package(packages_))
             .and_then(|packages_: Packages| acl::check(&*self.acl, Resource::Packages, Action::Update, self, Some(&packages_)))
             .and_then(|_| {
                 let filtered = packages.filter(id.eq(id_arg));
@@ -161,13 +251,13 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
                 query
                     .get_result::<PackagesRaw>(self.db_conn)
                     .map_err(|e| Error::from(e).into())
-                    .and_then(|packages_: PackagesRaw| packages_.to_packages(&self.countries))
+                    .and_then(|packages_: PackagesRaw| self.to_package(packages_))
             })
             .map_err(|e: FailureError| e.context(format!("Updating packages payload {:?} failed.", payload)).into())
     }
 
     fn delete(&self, id_arg: PackageId) -> RepoResult<Packages> {
-        debug!("delete packages_ id: {}.", id_arg);
+        debug!("{}", log_line(&format!("delete packages_ id: {}.", id_arg)));
 
         acl::check(&*self.acl, Resource::Packages, Action::Delete, self, None)?;
 
@@ -176,14 +266,12 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
         query
             .get_result::<PackagesRaw>(self.db_conn)
             .map_err(|e| Error::from(e).into())
-            .and_then(|packages_: PackagesRaw| packages_.to_packages(&self.countries))
+            .and_then(|packages_: PackagesRaw| self.to_package(packages_))
             .map_err(move |e| e.context(format!("delete packages id: {}.", id_arg)).into())
     }
 }
 
-impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, Packages>
-    for PackagesRepoImpl<'a, T>
-{
+impl<'a, T: DbConnection> CheckScope<Scope, Packages> for PackagesRepoImpl<'a, T> {
     fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&Packages>) -> bool {
         match *scope {
             Scope::All => true,
@@ -0,0 +1,56 @@
+//! Repo for role_permissions table. Permissions matrix of the roles the ACL is built from
+
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use errors::Error;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId;
+
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::types::{DbConnection, RepoResult};
+use models::authorization::*;
+use schema::role_permissions::dsl as DslRolePermissions;
+
+/// Permissions repository for reading the permissions matrix
+pub trait PermissionsRepo {
+    /// Returns permissions of all roles
+    fn list(&self) -> RepoResult<Vec<RolePermission>>;
+}
+
+/// Implementation of Permissions trait
+pub struct PermissionsRepoImpl<'a, T: DbConnection> {
+    pub db_conn: &'a T,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, RolePermission>>,
+}
+
+impl<'a, T: DbConnection> PermissionsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, RolePermission>>) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: DbConnection> PermissionsRepo for PermissionsRepoImpl<'a, T> {
+    /// Returns permissions of all roles
+    fn list(&self) -> RepoResult<Vec<RolePermission>> {
+        acl::check(&*self.acl, Resource::Permissions, Action::Read, self, None)?;
+
+        let query = DslRolePermissions::role_permissions.order(DslRolePermissions::id);
+
+        query
+            .get_results::<RolePermission>(self.db_conn)
+            .map_err(|e| Error::from(e).context("list of role permissions error occurred").into())
+    }
+}
+
+impl<'a, T: DbConnection> CheckScope<Scope, RolePermission> for PermissionsRepoImpl<'a, T> {
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&RolePermission>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
@@ -0,0 +1,217 @@
+//! Repo for pickup_points table. Pickup point is a carrier location
+//! buyers can collect their orders at
+
+use diesel;
+use diesel::dsl::sql;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::sql_types::Double;
+use errors::Error;
+use failure::Error as FailureError;
+
+use stq_types::{CompanyId, UserId};
+
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::types::{DbConnection, RepoResult};
+use models::authorization::*;
+use models::{NearbyPickupPoint, NearbyPickupPointsSearch, NewPickupPoint, PickupPoint, PickupPointRaw, UpdatePickupPoint};
+use schema::pickup_points::dsl as DslPickupPoints;
+use schema::pickup_points::table as pickup_points;
+
+const EARTH_RADIUS_KM: f64 = 6371.0088;
+/// Length of one degree of latitude, used to narrow the search down with the latitude index
+const KM_PER_LATITUDE_DEGREE: f64 = 111.0;
+const NEARBY_PICKUP_POINTS_LIMIT: i64 = 50;
+
+/// Pickup points repository for handling PickupPoints
+pub trait PickupPointsRepo {
+    /// Returns list of pickup points, optionally only of the company
+    fn list(&self, company_id: Option<CompanyId>) -> RepoResult<Vec<PickupPoint>>;
+
+    /// Find specific pickup point by id
+    fn find(&self, pickup_point_id: i32) -> RepoResult<Option<PickupPoint>>;
+
+    /// Returns pickup points within the radius ordered by distance, closest first
+    fn find_nearby(&self, search: NearbyPickupPointsSearch) -> RepoResult<Vec<NearbyPickupPoint>>;
+
+    /// Create a new pickup point
+    fn create(&self, payload: NewPickupPoint) -> RepoResult<PickupPoint>;
+
+    /// Update a pickup point
+    fn update(&self, pickup_point_id: i32, payload: UpdatePickupPoint) -> RepoResult<PickupPoint>;
+
+    /// Delete a pickup point
+    fn delete(&self, pickup_point_id: i32) -> RepoResult<PickupPoint>;
+}
+
+/// Implementation of PickupPoints trait
+pub struct PickupPointsRepoImpl<'a, T: DbConnection> {
+    pub db_conn: &'a T,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, PickupPoint>>,
+}
+
+impl<'a, T: DbConnection> PickupPointsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, PickupPoint>>) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+/// Great-circle distance in km from the point to the pickup point, calculated in SQL with the haversine formula.
+/// Coordinates of the point are bound as parameters of the query
+fn distance_km_sql(search: &NearbyPickupPointsSearch) -> Box<BoxableExpression<pickup_points, Pg, SqlType = Double>> {
+    let latitude = search.coordinates.latitude;
+    let longitude = search.coordinates.longitude;
+
+    Box::new(
+        sql::<Double>("(2 * ")
+            .bind::<Double, _>(EARTH_RADIUS_KM)
+            .sql(" * asin(sqrt(power(sin(radians(pickup_points.latitude - ")
+            .bind::<Double, _>(latitude)
+            .sql(") / 2), 2) + cos(radians(")
+            .bind::<Double, _>(latitude)
+            .sql(")) * cos(radians(pickup_points.latitude)) * power(sin(radians(pickup_points.longitude - ")
+            .bind::<Double, _>(longitude)
+            .sql(") / 2), 2))))"),
+    )
+}
+
+impl<'a, T: DbConnection> PickupPointsRepo for PickupPointsRepoImpl<'a, T> {
+    /// Returns list of pickup points, optionally only of the company
+    fn list(&self, company_id: Option<CompanyId>) -> RepoResult<Vec<PickupPoint>> {
+        acl::check(&*self.acl, Resource::PickupPoints, Action::Read, self, None)?;
+
+        let mut query = DslPickupPoints::pickup_points.order(DslPickupPoints::id).into_boxed();
+
+        if let Some(company_id) = company_id {
+            query = query.filter(DslPickupPoints::company_id.eq(company_id));
+        }
+
+        query
+            .get_results::<PickupPointRaw>(self.db_conn)
+            .map_err(|e| Error::from(e).into())
+            .and_then(|pickup_points| {
+                pickup_points
+                    .into_iter()
+                    .map(PickupPointRaw::to_model)
+                    .collect::<RepoResult<Vec<_>>>()
+            })
+            .map_err(|e: FailureError| {
+                e.context(format!("list of pickup points of company {:?} error occurred", company_id))
+                    .into()
+            })
+    }
+
+    /// Find specific pickup point by id
+    fn find(&self, pickup_point_id: i32) -> RepoResult<Option<PickupPoint>> {
+        acl::check(&*self.acl, Resource::PickupPoints, Action::Read, self, None)?;
+
+        let query = DslPickupPoints::pickup_points.find(pickup_point_id);
+
+        query
+            .get_result::<PickupPointRaw>(self.db_conn)
+            .optional()
+            .map_err(|e| Error::from(e).into())
+            .and_then(|pickup_point| match pickup_point {
+                Some(pickup_point) => pickup_point.to_model().map(Some),
+                None => Ok(None),
+            })
+            .map_err(|e: FailureError| {
+                e.context(format!("Find pickup point with id {} error occurred", pickup_point_id))
+                    .into()
+            })
+    }
+
+    /// Returns pickup points within the radius ordered by distance, closest first
+    fn find_nearby(&self, search: NearbyPickupPointsSearch) -> RepoResult<Vec<NearbyPickupPoint>> {
+        acl::check(&*self.acl, Resource::PickupPoints, Action::Read, self, None)?;
+
+        let latitude_delta = search.radius_km / KM_PER_LATITUDE_DEGREE;
+
+        let query = DslPickupPoints::pickup_points
+            .filter(DslPickupPoints::latitude.between(
+                search.coordinates.latitude - latitude_delta,
+                search.coordinates.latitude + latitude_delta,
+            ))
+            .filter(distance_km_sql(&search).le(search.radius_km))
+            .order(distance_km_sql(&search))
+            .limit(NEARBY_PICKUP_POINTS_LIMIT);
+
+        query
+            .get_results::<PickupPointRaw>(self.db_conn)
+            .map_err(|e| Error::from(e).into())
+            .and_then(|pickup_points| {
+                pickup_points
+                    .into_iter()
+                    .map(|pickup_point| {
+                        let pickup_point = pickup_point.to_model()?;
+                        let distance_km = search.coordinates.straight_line_distance_km(&pickup_point.coordinates);
+                        Ok(NearbyPickupPoint { pickup_point, distance_km })
+                    })
+                    .collect::<RepoResult<Vec<_>>>()
+            })
+            .map_err(|e: FailureError| e.context(format!("Find pickup points nearby {:?} error occurred", search)).into())
+    }
+
+    /// Create a new pickup point
+    fn create(&self, payload: NewPickupPoint) -> RepoResult<PickupPoint> {
+        acl::check(&*self.acl, Resource::PickupPoints, Action::Create, self, None)?;
+
+        payload
+            .clone()
+            .to_raw()
+            .and_then(|payload_raw| {
+                let query = diesel::insert_into(DslPickupPoints::pickup_points).values(&payload_raw);
+                query.get_result::<PickupPointRaw>(self.db_conn).map_err(|e| Error::from(e).into())
+            })
+            .and_then(PickupPointRaw::to_model)
+            .map_err(|e: FailureError| e.context(format!("Create a new pickup point {:?} error occurred", payload)).into())
+    }
+
+    /// Update a pickup point
+    fn update(&self, pickup_point_id: i32, payload: UpdatePickupPoint) -> RepoResult<PickupPoint> {
+        acl::check(&*self.acl, Resource::PickupPoints, Action::Update, self, None)?;
+
+        payload
+            .clone()
+            .to_raw()
+            .and_then(|payload_raw| {
+                let filter = DslPickupPoints::pickup_points.filter(DslPickupPoints::id.eq(pickup_point_id));
+                let query = diesel::update(filter).set(&payload_raw);
+                query.get_result::<PickupPointRaw>(self.db_conn).map_err(|e| Error::from(e).into())
+            })
+            .and_then(PickupPointRaw::to_model)
+            .map_err(|e: FailureError| {
+                e.context(format!(
+                    "Update pickup point {} with payload {:?} error occurred",
+                    pickup_point_id, payload
+                ))
+                .into()
+            })
+    }
+
+    /// Delete a pickup point
+    fn delete(&self, pickup_point_id: i32) -> RepoResult<PickupPoint> {
+        acl::check(&*self.acl, Resource::PickupPoints, Action::Delete, self, None)?;
+
+        let filtered = DslPickupPoints::pickup_points.filter(DslPickupPoints::id.eq(pickup_point_id));
+        let query = diesel::delete(filtered);
+
+        query
+            .get_result::<PickupPointRaw>(self.db_conn)
+            .map_err(|e| Error::from(e).into())
+            .and_then(PickupPointRaw::to_model)
+            .map_err(|e: FailureError| e.context(format!("Delete pickup point {} error occurred", pickup_point_id)).into())
+    }
+}
+
+impl<'a, T: DbConnection> CheckScope<Scope, PickupPoint> for PickupPointsRepoImpl<'a, T> {
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&PickupPoint>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
@@ -2,28 +2,25 @@
 //! contains info about local shipping of base_product.
 
 use diesel;
-use diesel::connection::AnsiTransactionManager;
-use diesel::pg::Pg;
 use diesel::prelude::*;
 use diesel::query_dsl::LoadQuery;
 use diesel::query_dsl::RunQueryDsl;
-use diesel::Connection;
 
 use errors::Error;
 use failure::Error as FailureError;
 use failure::Fail;
 
-use stq_types::{BaseProductId, UserId};
+use stq_types::{BaseProductId, StoreId, UserId};
 
 use models::authorization::*;
 use repos::legacy_acl::*;
-use repos::types::RepoResult;
+use repos::types::{DbConnection, RepoResult};
 
 use models::pickups::{NewPickups, Pickups, UpdatePickups};
-use models::roles::UserRole;
 use repos::acl;
+use repos::user_roles::get_user_roles_data;
+use request_context::log_line;
 use schema::pickups::dsl::*;
-use schema::roles::dsl as Roles;
 
 /// pickups repository for handling pickups model
 pub trait PickupsRepo {
@@ -36,6 +33,9 @@ pub trait PickupsRepo {
     /// Getting pickups by base_product_id
     fn get(&self, base_product_id_arg: BaseProductId) -> RepoResult<Option<Pickups>>;
 
+    /// Getting pickups of all base products of the store, ordered by base product
+    fn get_by_store_id(&self, store_id_arg: StoreId) -> RepoResult<Vec<Pickups>>;
+
     /// Update a pickups
     fn update(&self, base_product_id_arg: BaseProductId, payload: UpdatePickups) -> RepoResult<Pickups>;
 
@@ -44,13 +44,13 @@ pub trait PickupsRepo {
 }
 
 /// Implementation of PickupsRepo trait
-pub struct PickupsRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+pub struct PickupsRepoImpl<'a, T: DbConnection> {
     pub db_conn: &'a T,
-    pub acl: Box<Acl<Resource, Action, Scope, FailureError, Pickups>>,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, Pickups>>,
 }
 
-impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> PickupsRepoImpl<'a, T> {
-    pub fn new(db_conn: &'a T, acl: Box<Acl<Resource, Action, Scope, FailureError, Pickups>>) -> Self {
+impl<'a, T: DbConnection> PickupsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, Pickups>>) -> Self {
         Self { db_conn, acl }
     }
 
@@ -59,9 +59,9 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
     }
 }
 
-impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> PickupsRepo for PickupsRepoImpl<'a, T> {
+impl<'a, T: DbConnection> PickupsRepo for PickupsRepoImpl<'a, T> {
     fn create(&self, payload: NewPickups) -> RepoResult<Pickups> {
-        debug!("create new pickups {:?}.", payload);
+        debug!("{}", log_line(&format!("create new pickups {:?}.", payload)));
         let query = diesel::insert_into(pickups).values(&payload);
         query
             .get_result::<Pickups>(self.db_conn)
@@ -72,7 +72,7 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
 
     /// Getting pickups
     fn list(&self) -> RepoResult<Vec<Pickups>> {
-        debug!("List pickups");
+        debug!("{}", log_line(&format!("List pickups")));
         let query = pickups.order(id);
 
         query
@@ -89,7 +89,10 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
 
     /// Getting pickups by base_product_id
     fn get(&self, base_product_id_arg: BaseProductId) -> RepoResult<Option<Pickups>> {
-        debug!("Getting pickups by base_product_id {}", base_product_id_arg);
+        debug!(
+            "{}",
+            log_line(&format!("Getting pickups by base_product_id {}", base_product_id_arg))
+        );
         let query = pickups.filter(base_product_id.eq(base_product_id_arg)).order(id);
 
         query
@@ -108,9 +111,26 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             })
     }
 
+    /// Getting pickups of all base products of the store, ordered by base product
+    fn get_by_store_id(&self, store_id_arg: StoreId) -> RepoResult<Vec<Pickups>> {
+        debug!("{}", log_line(&format!("Getting pickups by store_id {}", store_id_arg)));
+        let query = pickups.filter(store_id.eq(store_id_arg)).order((base_product_id, id));
+
+        query
+            .get_results(self.db_conn)
+            .map_err(|e| Error::from(e).into())
+            .and_then(|results: Vec<Pickups>| {
+                for result in &results {
+                    acl::check(&*self.acl, Resource::Pickups, Action::Read, self, Some(&result))?;
+                }
+                Ok(results)
+            })
+            .map_err(|e: FailureError| e.context(format!("Getting pickups by store_id {}", store_id_arg)).into())
+    }
+
     /// Update a pickups
     fn update(&self, base_product_id_arg: BaseProductId, payload: UpdatePickups) -> RepoResult<Pickups> {
-        debug!("Updating pickups payload {:?}.", payload);
+        debug!("{}", log_line(&format!("Updating pickups payload {:?}.", payload)));
         self.execute_query(pickups.filter(base_product_id.eq(base_product_id_arg)))
             .and_then(|pickup_: Pickups| acl::check(&*self.acl, Resource::Pickups, Action::Update, self, Some(&pickup_)))
             .and_then(|_| {
@@ -122,7 +142,10 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
     }
 
     fn delete(&self, base_product_id_arg: BaseProductId) -> RepoResult<Option<Pickups>> {
-        debug!("delete pickups by base_product_id: {}.", base_product_id_arg);
+        debug!(
+            "{}",
+            log_line(&format!("delete pickups by base_product_id: {}.", base_product_id_arg))
+        );
         let query = pickups.filter(base_product_id.eq(base_product_id_arg)).order(id);
 
         query
@@ -150,23 +173,14 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
     }
 }
 
-impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, Pickups>
-    for PickupsRepoImpl<'a, T>
-{
+impl<'a, T: DbConnection> CheckScope<Scope, Pickups> for PickupsRepoImpl<'a, T> {
     fn is_in_scope(&self, user_id_arg: UserId, scope: &Scope, obj: Option<&Pickups>) -> bool {
         match *scope {
             Scope::All => true,
             Scope::Owned => {
                 if let Some(obj) = obj {
-                    Roles::roles
-                        .filter(Roles::user_id.eq(user_id_arg))
-                        .get_results::<UserRole>(self.db_conn)
-                        .map_err(|e| Error::from(e).into())
-                        .map(|user_roles_arg| {
-                            user_roles_arg
-                                .iter()
-                                .any(|user_role_arg| user_role_arg.data.clone().map(|data| data == obj.store_id.0).unwrap_or_default())
-                        })
+                    get_user_roles_data(self.db_conn, user_id_arg)
+                        .map(|roles_data| roles_data.iter().any(|role_data| role_data.manages_store_shipping(obj.store_id)))
                         .unwrap_or_else(|_: FailureError| false)
                 } else {
                     false
@@ -0,0 +1,163 @@
+//! Repo for postal_zones table. Postal zone is a named set of the postal code ranges within a country
+//! that shipping rates can be delivered to
+
+use diesel;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use errors::Error;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::{Alpha3, UserId};
+
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::types::{DbConnection, RepoResult};
+use models::authorization::*;
+use models::{NewPostalZone, PostalZone};
+use schema::postal_zones::dsl as DslPostalZones;
+
+/// Postal zones repository, postal zones are managed with the zones
+pub trait PostalZonesRepo {
+    /// Returns postal zones ordered by country and name, only the ones of the country if it is set
+    fn list(&self, alpha3: Option<Alpha3>) -> RepoResult<Vec<PostalZone>>;
+
+    /// Find specific postal zone by id
+    fn find(&self, postal_zone_id: i32) -> RepoResult<Option<PostalZone>>;
+
+    /// Returns postal zones of the country containing the postal code
+    fn find_by_postal_code(&self, alpha3: Alpha3, postal_code: String) -> RepoResult<Vec<PostalZone>>;
+
+    /// Create a new postal zone
+    fn create(&self, payload: NewPostalZone) -> RepoResult<PostalZone>;
+
+    /// Creates postal zone or replaces the ranges of the one of the country with the same name
+    fn upsert(&self, payload: NewPostalZone) -> RepoResult<PostalZone>;
+
+    /// Delete a postal zone together with its rates
+    fn delete(&self, postal_zone_id: i32) -> RepoResult<PostalZone>;
+}
+
+/// Implementation of PostalZones trait
+pub struct PostalZonesRepoImpl<'a, T: DbConnection> {
+    pub db_conn: &'a T,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, PostalZone>>,
+}
+
+impl<'a, T: DbConnection> PostalZonesRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, PostalZone>>) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: DbConnection> PostalZonesRepo for PostalZonesRepoImpl<'a, T> {
+    /// Returns postal zones ordered by country and name, only the ones of the country if it is set
+    fn list(&self, alpha3: Option<Alpha3>) -> RepoResult<Vec<PostalZone>> {
+        acl::check(&*self.acl, Resource::Zones, Action::Read, self, None)?;
+
+        let mut query = DslPostalZones::postal_zones
+            .order((DslPostalZones::alpha3, DslPostalZones::name))
+            .into_boxed();
+        if let Some(alpha3) = alpha3 {
+            query = query.filter(DslPostalZones::alpha3.eq(alpha3));
+        }
+
+        query
+            .get_results::<PostalZone>(self.db_conn)
+            .map_err(|e| Error::from(e).context("List postal zones error occurred").into())
+    }
+
+    /// Find specific postal zone by id
+    fn find(&self, postal_zone_id: i32) -> RepoResult<Option<PostalZone>> {
+        acl::check(&*self.acl, Resource::Zones, Action::Read, self, None)?;
+
+        let query = DslPostalZones::postal_zones.find(postal_zone_id);
+
+        query.get_result::<PostalZone>(self.db_conn).optional().map_err(|e| {
+            Error::from(e)
+                .context(format!("Find postal zone with id {} error occurred", postal_zone_id))
+                .into()
+        })
+    }
+
+    /// Returns postal zones of the country containing the postal code
+    fn find_by_postal_code(&self, alpha3: Alpha3, postal_code: String) -> RepoResult<Vec<PostalZone>> {
+        acl::check(&*self.acl, Resource::Zones, Action::Read, self, None)?;
+
+        // Ranges are matched by the leading characters of the postal code, which the JSONB operators can not do
+        let query = DslPostalZones::postal_zones
+            .filter(DslPostalZones::alpha3.eq(alpha3.clone()))
+            .order(DslPostalZones::id);
+
+        query
+            .get_results::<PostalZone>(self.db_conn)
+            .map(|postal_zones| {
+                postal_zones
+                    .into_iter()
+                    .filter(|postal_zone| postal_zone.contains(&postal_code))
+                    .collect()
+            })
+            .map_err(|e| {
+                Error::from(e)
+                    .context(format!(
+                        "Find postal zones of country {} with postal code {} error occurred",
+                        alpha3, postal_code
+                    ))
+                    .into()
+            })
+    }
+
+    /// Create a new postal zone
+    fn create(&self, payload: NewPostalZone) -> RepoResult<PostalZone> {
+        acl::check(&*self.acl, Resource::Zones, Action::Create, self, None)?;
+
+        let query = diesel::insert_into(DslPostalZones::postal_zones).values(&payload);
+
+        query.get_result::<PostalZone>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!("Create a new postal zone {:?} error occurred", payload))
+                .into()
+        })
+    }
+
+    /// Creates postal zone or replaces the ranges of the one of the country with the same name
+    fn upsert(&self, payload: NewPostalZone) -> RepoResult<PostalZone> {
+        acl::check(&*self.acl, Resource::Zones, Action::Update, self, None)?;
+
+        let query = diesel::insert_into(DslPostalZones::postal_zones)
+            .values(&payload)
+            .on_conflict((DslPostalZones::alpha3, DslPostalZones::name))
+            .do_update()
+            .set(DslPostalZones::ranges.eq(payload.ranges.clone()));
+
+        query.get_result::<PostalZone>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!("Upsert postal zone {:?} error occurred", payload))
+                .into()
+        })
+    }
+
+    /// Delete a postal zone together with its rates
+    fn delete(&self, postal_zone_id: i32) -> RepoResult<PostalZone> {
+        acl::check(&*self.acl, Resource::Zones, Action::Delete, self, None)?;
+
+        let filtered = DslPostalZones::postal_zones.filter(DslPostalZones::id.eq(postal_zone_id));
+        let query = diesel::delete(filtered);
+
+        query.get_result::<PostalZone>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!("Delete postal zone {} error occurred", postal_zone_id))
+                .into()
+        })
+    }
+}
+
+impl<'a, T: DbConnection> CheckScope<Scope, PostalZone> for PostalZonesRepoImpl<'a, T> {
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&PostalZone>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
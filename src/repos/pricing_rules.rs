@@ -0,0 +1,150 @@
+//! Repo for pricing_rules table. Pricing rules are markups and discounts of the delivery prices of the stores,
+//! they are managed by the marketplace administrators only
+
+use diesel;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use errors::Error;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::{StoreId, UserId};
+
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::types::{DbConnection, RepoResult};
+use models::authorization::*;
+use models::{NewPricingRule, PricingRule, UpdatePricingRule};
+use schema::pricing_rules::dsl as DslPricingRules;
+
+/// Pricing rules repository for handling PricingRules
+pub trait PricingRulesRepo {
+    /// Returns list of pricing rules
+    fn list(&self) -> RepoResult<Vec<PricingRule>>;
+
+    /// Find specific pricing rule by id
+    fn find(&self, pricing_rule_id: i32) -> RepoResult<Option<PricingRule>>;
+
+    /// Returns rules of the stores in the order they are applied: from the highest priority, then by id
+    fn find_by_stores(&self, store_ids: Vec<StoreId>) -> RepoResult<Vec<PricingRule>>;
+
+    /// Create a new pricing rule
+    fn create(&self, payload: NewPricingRule) -> RepoResult<PricingRule>;
+
+    /// Update a pricing rule
+    fn update(&self, pricing_rule_id: i32, payload: UpdatePricingRule) -> RepoResult<PricingRule>;
+
+    /// Delete a pricing rule
+    fn delete(&self, pricing_rule_id: i32) -> RepoResult<PricingRule>;
+}
+
+/// Implementation of PricingRules trait
+pub struct PricingRulesRepoImpl<'a, T: DbConnection> {
+    pub db_conn: &'a T,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, PricingRule>>,
+}
+
+impl<'a, T: DbConnection> PricingRulesRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, PricingRule>>) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: DbConnection> PricingRulesRepo for PricingRulesRepoImpl<'a, T> {
+    /// Returns list of pricing rules
+    fn list(&self) -> RepoResult<Vec<PricingRule>> {
+        acl::check(&*self.acl, Resource::PricingRules, Action::Read, self, None)?;
+
+        let query = DslPricingRules::pricing_rules.order(DslPricingRules::id);
+
+        query
+            .get_results::<PricingRule>(self.db_conn)
+            .map_err(|e| Error::from(e).context("list of pricing rules error occurred").into())
+    }
+
+    /// Find specific pricing rule by id
+    fn find(&self, pricing_rule_id: i32) -> RepoResult<Option<PricingRule>> {
+        acl::check(&*self.acl, Resource::PricingRules, Action::Read, self, None)?;
+
+        let query = DslPricingRules::pricing_rules.find(pricing_rule_id);
+
+        query.get_result::<PricingRule>(self.db_conn).optional().map_err(|e| {
+            Error::from(e)
+                .context(format!("Find pricing rule with id {} error occurred", pricing_rule_id))
+                .into()
+        })
+    }
+
+    /// Returns rules of the stores in the order they are applied: from the highest priority, then by id
+    fn find_by_stores(&self, store_ids: Vec<StoreId>) -> RepoResult<Vec<PricingRule>> {
+        acl::check(&*self.acl, Resource::PricingRules, Action::Read, self, None)?;
+
+        if store_ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let query = DslPricingRules::pricing_rules
+            .filter(DslPricingRules::store_id.eq_any(store_ids.clone()))
+            .order((DslPricingRules::priority.desc(), DslPricingRules::id));
+
+        query.get_results::<PricingRule>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!("Find pricing rules of stores {:?} error occurred", store_ids))
+                .into()
+        })
+    }
+
+    /// Create a new pricing rule
+    fn create(&self, payload: NewPricingRule) -> RepoResult<PricingRule> {
+        acl::check(&*self.acl, Resource::PricingRules, Action::Create, self, None)?;
+
+        let query = diesel::insert_into(DslPricingRules::pricing_rules).values(&payload);
+
+        query.get_result::<PricingRule>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!("Create a new pricing rule {:?} error occurred", payload))
+                .into()
+        })
+    }
+
+    /// Update a pricing rule
+    fn update(&self, pricing_rule_id: i32, payload: UpdatePricingRule) -> RepoResult<PricingRule> {
+        acl::check(&*self.acl, Resource::PricingRules, Action::Update, self, None)?;
+
+        let filter = DslPricingRules::pricing_rules.filter(DslPricingRules::id.eq(pricing_rule_id));
+        let query = diesel::update(filter).set(&payload);
+
+        query.get_result::<PricingRule>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!(
+                    "Update pricing rule {} with payload {:?} error occurred",
+                    pricing_rule_id, payload
+                ))
+                .into()
+        })
+    }
+
+    /// Delete a pricing rule
+    fn delete(&self, pricing_rule_id: i32) -> RepoResult<PricingRule> {
+        acl::check(&*self.acl, Resource::PricingRules, Action::Delete, self, None)?;
+
+        let filtered = DslPricingRules::pricing_rules.filter(DslPricingRules::id.eq(pricing_rule_id));
+        let query = diesel::delete(filtered);
+
+        query.get_result::<PricingRule>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!("Delete pricing rule {} error occurred", pricing_rule_id))
+                .into()
+        })
+    }
+}
+
+impl<'a, T: DbConnection> CheckScope<Scope, PricingRule> for PricingRulesRepoImpl<'a, T> {
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&PricingRule>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
@@ -2,35 +2,33 @@
 //! contains info about international shipping of base_product.
 
 use diesel;
-use diesel::connection::AnsiTransactionManager;
 use diesel::dsl::sql;
 use diesel::pg::types::sql_types::Array;
-use diesel::pg::Pg;
 use diesel::prelude::*;
 use diesel::query_dsl::LoadQuery;
 use diesel::query_dsl::RunQueryDsl;
 use diesel::sql_types::VarChar;
-use diesel::Connection;
 use errors::Error;
 use failure::Error as FailureError;
 
-use stq_types::{BaseProductId, CompanyPackageId, ShippingId, UserId};
+use stq_types::{Alpha3, BaseProductId, CompanyPackageId, ShippingId, StoreId, UserId};
 
 use models::authorization::*;
 use models::countries::Country;
+use models::roles::manages_product;
 use models::{
-    AvailablePackageForUser, CompaniesPackagesRaw, CompanyRaw, NewProducts, NewProductsRaw, PackagesRaw, Products, ProductsRaw,
-    ShippingVariant, UpdateProducts, UserRole,
+    AvailablePackageForUser, CompaniesPackagesRaw, CompanyPackageProduct, CompanyPackageProducts, CompanyRaw, NewProducts, NewProductsRaw,
+    PackagesRaw, Products, ProductsRaw, RoleData, ShippingVariant, TypedJson, UpdateProducts,
 };
 
 use repos::legacy_acl::*;
 use repos::types::RepoResult;
 use repos::*;
+use request_context::log_line;
 use schema::companies::dsl as DslCompanies;
 use schema::companies_packages::dsl as DslCompaniesPackages;
 use schema::packages::dsl as DslPackages;
 use schema::products::dsl as DslProducts;
-use schema::roles::dsl as Roles;
 
 pub struct ProductsWithAvailableCountries(pub Products, pub Vec<Alpha3>);
 
@@ -45,12 +43,37 @@ pub trait ProductsRepo {
     /// Get a products
     fn get_by_base_product_id(&self, base_product_id: BaseProductId) -> RepoResult<Vec<Products>>;
 
+    /// Returns shipping of the base products, ordered by id
+    fn get_by_base_product_ids(&self, base_product_ids: Vec<BaseProductId>) -> RepoResult<Vec<Products>>;
+
+    /// Returns shipping of all base products of the store, ordered by base product
+    fn get_by_store_id(&self, store_id: StoreId) -> RepoResult<Vec<Products>>;
+
+    /// Returns company packages the store ships its products with
+    fn get_company_package_ids_by_store_id(&self, store_id: StoreId) -> RepoResult<Vec<CompanyPackageId>>;
+
+    /// Returns the page of base products shipped by the company package with the count of all of them,
+    /// allowed to the admins only
+    fn get_by_company_package_id(
+        &self,
+        company_package_id: CompanyPackageId,
+        offset: i64,
+        limit: i64,
+    ) -> RepoResult<CompanyPackageProducts>;
+
     /// Get a products with available countries for delivery by package
     fn get_products_countries(&self, base_product_id: BaseProductId) -> RepoResult<Vec<ProductsWithAvailableCountries>>;
 
     /// find available product delivery to users country
     fn find_available_to(&self, base_product_id: BaseProductId, user_country: Alpha3) -> RepoResult<Vec<AvailablePackageForUser>>;
 
+    /// find available product delivery to each of the countries, packages are returned in the order of the countries
+    fn find_available_to_countries(
+        &self,
+        base_product_id: BaseProductId,
+        countries: Vec<Alpha3>,
+    ) -> RepoResult<Vec<(Alpha3, Vec<AvailablePackageForUser>)>>;
+
     /// Update a products
     fn update(
         &self,
@@ -59,13 +82,13 @@ pub trait ProductsRepo {
         payload: UpdateProducts,
     ) -> RepoResult<Products>;
 
-    /// Returns available package for user by id
-    /// DEPRECATED. Use `get_available_package_for_user_by_shipping_id` instead.
-    fn get_available_package_for_user(
+    /// Returns packages available for user by the base product and the company package, ordered by shipping id.
+    /// The pair does not identify the shipping, so there may be several of them
+    fn find_available_packages_for_user(
         &self,
         base_product_id_arg: BaseProductId,
         package_id_arg: CompanyPackageId,
-    ) -> RepoResult<Option<AvailablePackageForUser>>;
+    ) -> RepoResult<Vec<AvailablePackageForUser>>;
 
     /// Returns available package for user by shipping id
     fn get_available_package_for_user_by_shipping_id(
@@ -78,31 +101,59 @@ pub trait ProductsRepo {
     fn delete(&self, base_product_id_arg: BaseProductId) -> RepoResult<Vec<Products>>;
 }
 
-pub struct ProductsRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+pub struct ProductsRepoImpl<'a, T: DbConnection> {
     pub db_conn: &'a T,
-    pub acl: Box<Acl<Resource, Action, Scope, FailureError, Products>>,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, Products>>,
     pub countries: Country,
 }
 
-impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> ProductsRepoImpl<'a, T> {
-    pub fn new(db_conn: &'a T, acl: Box<Acl<Resource, Action, Scope, FailureError, Products>>, countries: Country) -> Self {
+impl<'a, T: DbConnection> ProductsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, Products>>, countries: Country) -> Self {
         Self { db_conn, acl, countries }
     }
 
     fn execute_query<Ty: Send + 'static, U: LoadQuery<T, Ty> + Send + 'static>(&self, query: U) -> RepoResult<Ty> {
         query.get_result::<Ty>(self.db_conn).map_err(|e| Error::from(e).into())
     }
+
+    /// Store managers and shipping managers own products of their store, country managers own products shipped by
+    /// companies delivering from their country
+    fn manages_product(&self, roles_data: &[RoleData], product: &Products) -> bool {
+        if manages_product(roles_data, product.store_id, &[]) {
+            return true;
+        }
+
+        let is_country_manager = roles_data.iter().any(|role_data| match *role_data {
+            RoleData::CountryManager { .. } => true,
+            RoleData::StoreManager { .. } | RoleData::ShippingManager { .. } => false,
+        });
+        if !is_country_manager {
+            return false;
+        }
+
+        DslCompaniesPackages::companies_packages
+            .inner_join(DslCompanies::companies)
+            .filter(DslCompaniesPackages::id.eq(product.company_package_id))
+            .select(DslCompanies::deliveries_from)
+            .get_result::<TypedJson<Vec<Alpha3>>>(self.db_conn)
+            .ok()
+            .map(|deliveries_from| {
+                let deliveries_from = create_tree_used_countries(&self.countries, &deliveries_from);
+                manages_product(roles_data, product.store_id, &deliveries_from)
+            })
+            .unwrap_or_default()
+    }
 }
 
-impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> ProductsRepo for ProductsRepoImpl<'a, T> {
+impl<'a, T: DbConnection> ProductsRepo for ProductsRepoImpl<'a, T> {
     fn create(&self, payload: NewProducts) -> RepoResult<Products> {
-        debug!("create new products {:?}.", payload);
-        let payload = payload.to_raw()?;
+        debug!("{}", log_line(&format!("create new products {:?}.", payload)));
+        let payload = payload.to_raw();
         let query = diesel::insert_into(DslProducts::products).values(&payload);
         query
             .get_result::<ProductsRaw>(self.db_conn)
             .map_err(|e| Error::from(e).into())
-            .and_then(|products_| products_.to_products())
+            .map(ProductsRaw::to_products)
             .and_then(|product| {
                 acl::check(&*self.acl, Resource::Products, Action::Create, self, Some(&product))?;
                 Ok(product)
@@ -111,11 +162,8 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
     }
 
     fn create_many(&self, payload: Vec<NewProducts>) -> RepoResult<Vec<Products>> {
-        debug!("create many new products {:?}.", payload);
-        let payload = payload
-            .into_iter()
-            .map(|v| v.to_raw().map_err(From::from))
-            .collect::<RepoResult<Vec<NewProductsRaw>>>()?;
+        debug!("{}", log_line(&format!("create many new products {:?}.", payload)));
+        let payload = payload.into_iter().map(NewProducts::to_raw).collect::<Vec<NewProductsRaw>>();
 
         let query = diesel::insert_into(DslProducts::products).values(&payload);
         query
@@ -124,7 +172,7 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             .and_then(|products_: Vec<ProductsRaw>| {
                 let mut new_products = vec![];
                 for product in products_ {
-                    let product = product.to_products()?;
+                    let product = product.to_products();
                     acl::check(&*self.acl, Resource::Products, Action::Create, self, Some(&product))?;
                     new_products.push(product);
                 }
@@ -137,7 +185,10 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
     }
 
     fn get_by_base_product_id(&self, base_product_id_arg: BaseProductId) -> RepoResult<Vec<Products>> {
-        debug!("get products by base_product_id {:?}.", base_product_id_arg);
+        debug!(
+            "{}",
+            log_line(&format!("get products by base_product_id {:?}.", base_product_id_arg))
+        );
         let query = DslProducts::products
             .filter(DslProducts::base_product_id.eq(base_product_id_arg))
             .order(DslProducts::id);
@@ -148,7 +199,7 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             .and_then(|products_: Vec<ProductsRaw>| {
                 let mut new_products = vec![];
                 for product in products_ {
-                    let product = product.to_products()?;
+                    let product = product.to_products();
                     acl::check(&*self.acl, Resource::Products, Action::Read, self, Some(&product))?;
                     new_products.push(product);
                 }
@@ -160,11 +211,129 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             })
     }
 
+    fn get_by_base_product_ids(&self, base_product_ids: Vec<BaseProductId>) -> RepoResult<Vec<Products>> {
+        debug!("{}", log_line(&format!("get products by base_product_ids {:?}.", base_product_ids)));
+        if base_product_ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let query = DslProducts::products
+            .filter(DslProducts::base_product_id.eq_any(base_product_ids.clone()))
+            .order(DslProducts::id);
+
+        query
+            .get_results(self.db_conn)
+            .map_err(|e| Error::from(e).into())
+            .and_then(|products_: Vec<ProductsRaw>| {
+                let mut new_products = vec![];
+                for product in products_ {
+                    let product = product.to_products();
+                    acl::check(&*self.acl, Resource::Products, Action::Read, self, Some(&product))?;
+                    new_products.push(product);
+                }
+                Ok(new_products)
+            })
+            .map_err(move |e: FailureError| {
+                e.context(format!("Getting products with base_product_ids {:?} failed.", base_product_ids))
+                    .into()
+            })
+    }
+
+    /// Returns shipping of all base products of the store, ordered by base product
+    fn get_by_store_id(&self, store_id_arg: StoreId) -> RepoResult<Vec<Products>> {
+        debug!("{}", log_line(&format!("get products by store_id {:?}.", store_id_arg)));
+        let query = DslProducts::products
+            .filter(DslProducts::store_id.eq(store_id_arg))
+            .order((DslProducts::base_product_id, DslProducts::id));
+
+        query
+            .get_results::<ProductsRaw>(self.db_conn)
+            .map_err(|e| Error::from(e).into())
+            .and_then(|products_: Vec<ProductsRaw>| {
+                let mut store_products = vec![];
+                for product in products_ {
+                    let product = product.to_products();
+                    acl::check(&*self.acl, Resource::Products, Action::Read, self, Some(&product))?;
+                    store_products.push(product);
+                }
+                Ok(store_products)
+            })
+            .map_err(|e: FailureError| {
+                e.context(format!("Getting products with store_id {:?} failed.", store_id_arg))
+                    .into()
+            })
+    }
+
+    /// Returns company packages the store ships its products with
+    fn get_company_package_ids_by_store_id(&self, store_id_arg: StoreId) -> RepoResult<Vec<CompanyPackageId>> {
+        debug!("{}", log_line(&format!("get company package ids by store_id {:?}.", store_id_arg)));
+        acl::check(&*self.acl, Resource::Products, Action::Read, self, None)?;
+
+        let query = DslProducts::products
+            .filter(DslProducts::store_id.eq(store_id_arg))
+            .select(DslProducts::company_package_id)
+            .distinct();
+
+        query
+            .get_results::<CompanyPackageId>(self.db_conn)
+            .map_err(|e| Error::from(e).into())
+            .map_err(|e: FailureError| {
+                e.context(format!("Getting company package ids with store_id {:?} failed.", store_id_arg))
+                    .into()
+            })
+    }
+
+    /// Returns the page of base products shipped by the company package with the count of all of them
+    fn get_by_company_package_id(
+        &self,
+        company_package_id_arg: CompanyPackageId,
+        offset: i64,
+        limit: i64,
+    ) -> RepoResult<CompanyPackageProducts> {
+        debug!(
+            "{}",
+            log_line(&format!("get products by company_package_id {}.", company_package_id_arg))
+        );
+        // Exposes the stores and base products of every seller, so only the admins may list them
+        acl::check(&*self.acl, Resource::AdminOverview, Action::Read, self, None)?;
+
+        let count_query = DslProducts::products
+            .inner_join(DslCompaniesPackages::companies_packages)
+            .filter(DslCompaniesPackages::id.eq(company_package_id_arg))
+            .count();
+        let page_query = DslProducts::products
+            .inner_join(DslCompaniesPackages::companies_packages)
+            .filter(DslCompaniesPackages::id.eq(company_package_id_arg))
+            .select((DslProducts::base_product_id, DslProducts::store_id))
+            .order((DslProducts::base_product_id, DslProducts::id))
+            .offset(offset)
+            .limit(limit);
+
+        count_query
+            .get_result::<i64>(self.db_conn)
+            .and_then(|total_count| {
+                page_query
+                    .get_results::<CompanyPackageProduct>(self.db_conn)
+                    .map(|products| CompanyPackageProducts { total_count, products })
+            })
+            .map_err(|e| Error::from(e).into())
+            .map_err(|e: FailureError| {
+                e.context(format!(
+                    "Getting products with company_package_id {} failed.",
+                    company_package_id_arg
+                ))
+                .into()
+            })
+    }
+
     /// Get a products with countries from packages
     fn get_products_countries(&self, base_product_id_arg: BaseProductId) -> RepoResult<Vec<ProductsWithAvailableCountries>> {
         debug!(
-            "Find in available countries for delivery by base_product_id: {:?}.",
-            base_product_id_arg
+            "{}",
+            log_line(&format!(
+                "Find in available countries for delivery by base_product_id: {:?}.",
+                base_product_id_arg
+            ))
         );
 
         let query = DslProducts::products
@@ -176,16 +345,18 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             .get_results::<(ProductsRaw, (CompaniesPackagesRaw, PackagesRaw))>(self.db_conn)
             .map_err(|e| Error::from(e).into())
             .and_then(|results| {
+                let packages_raw = results.iter().map(|(_, (_, package_raw))| package_raw.clone()).collect::<Vec<_>>();
+                let zones = get_zones_for_packages(self.db_conn, &packages_raw)?;
                 let mut data = vec![];
                 for result in results {
                     let (product_raw, (_, package_raw)) = result;
                     let countries_codes = package_raw
-                        .to_packages(&self.countries)?
+                        .to_packages(&self.countries, &zones)
                         .deliveries_to
                         .into_iter()
                         .map(|c| c.alpha3)
                         .collect();
-                    let element = ProductsWithAvailableCountries(product_raw.to_products()?, countries_codes);
+                    let element = ProductsWithAvailableCountries(product_raw.to_products(), countries_codes);
 
                     data.push(element);
                 }
@@ -202,12 +373,29 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
 
     /// find available product delivery to users country
     fn find_available_to(&self, base_product_id_arg: BaseProductId, user_country: Alpha3) -> RepoResult<Vec<AvailablePackageForUser>> {
+        self.find_available_to_countries(base_product_id_arg, vec![user_country])
+            .map(|mut packages_by_country| packages_by_country.pop().map(|(_, packages)| packages).unwrap_or_default())
+    }
+
+    /// find available product delivery to each of the countries with a single query
+    fn find_available_to_countries(
+        &self,
+        base_product_id_arg: BaseProductId,
+        countries: Vec<Alpha3>,
+    ) -> RepoResult<Vec<(Alpha3, Vec<AvailablePackageForUser>)>> {
         debug!(
-            "Find available product {} delivery to users country {}.",
-            base_product_id_arg, user_country
+            "{}",
+            log_line(&format!(
+                "Find available product {} delivery to users countries {:?}.",
+                base_product_id_arg, countries
+            ))
         );
 
-        let pg_countries: Vec<String> = vec![user_country.clone()].into_iter().map(|c| c.0).collect();
+        if countries.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let pg_countries: Vec<String> = countries.iter().map(|c| c.0.clone()).collect();
 
         let query = DslProducts::products
             .filter(DslProducts::base_product_id.eq(base_product_id_arg))
@@ -222,62 +410,82 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
         query
             .get_results::<(ProductsRaw, (CompaniesPackagesRaw, CompanyRaw, PackagesRaw))>(self.db_conn)
             .map(|results| {
-                let available_packages = results
-                    .into_iter()
-                    .map(|result| {
-                        let (product_raw, (companies_package, company_raw, package_raw)) = result;
-                        AvailablePackageForUser {
-                            id: companies_package.id,
-                            shipping_id: product_raw.id,
-                            name: get_company_package_name(&company_raw.label, &package_raw.name),
-                            logo: company_raw.logo.clone(),
-                            price: product_raw.price,
-                            currency: product_raw.currency,
-                            shipping_variant: product_raw.shipping.clone(),
-                            store_id: product_raw.store_id,
-                            base_product_id: product_raw.base_product_id,
-                        }
-                    })
-                    .collect::<Vec<_>>();
-
-                let local_package_ids = available_packages
+                countries
                     .iter()
-                    .filter_map(|package| {
-                        if package.shipping_variant.clone() == ShippingVariant::Local {
-                            Some(package.id)
-                        } else {
-                            None
-                        }
-                    })
-                    .collect::<Vec<_>>();
-
-                available_packages
-                    .into_iter()
-                    .filter(|package| {
-                        package.shipping_variant.clone() == ShippingVariant::Local || !local_package_ids.contains(&package.id)
+                    .map(|country| {
+                        let available_packages = results
+                            .iter()
+                            .filter(|(product_raw, _)| product_raw.deliveries_to.contains(country))
+                            .map(
+                                |(product_raw, (companies_package, company_raw, package_raw))| AvailablePackageForUser {
+                                    id: companies_package.id,
+                                    shipping_id: product_raw.id,
+                                    name: get_company_package_name(&company_raw.label, &package_raw.name),
+                                    logo: company_raw.logo.clone(),
+                                    price: product_raw.price,
+                                    cost: None,
+                                    option_id: None,
+                                    currency: product_raw.currency,
+                                    shipping_variant: product_raw.shipping.clone(),
+                                    store_id: product_raw.store_id,
+                                    base_product_id: product_raw.base_product_id,
+                                    delivery_time_min_days: companies_package.delivery_time_min_days.map(|days| days as u32),
+                                    delivery_time_max_days: companies_package.delivery_time_max_days.map(|days| days as u32),
+                                    free_delivery_over: product_raw.free_delivery_over,
+                                    free_shipping: false,
+                                    delivery_from: product_raw.delivery_from.clone(),
+                                    shipping_date: None,
+                                    estimated_delivery_date_min: None,
+                                    estimated_delivery_date_max: None,
+                                },
+                            )
+                            .collect::<Vec<_>>();
+
+                        let local_package_ids = available_packages
+                            .iter()
+                            .filter_map(|package| {
+                                if package.shipping_variant.clone() == ShippingVariant::Local {
+                                    Some(package.id)
+                                } else {
+                                    None
+                                }
+                            })
+                            .collect::<Vec<_>>();
+
+                        let available_packages = available_packages
+                            .into_iter()
+                            .filter(|package| {
+                                package.shipping_variant.clone() == ShippingVariant::Local || !local_package_ids.contains(&package.id)
+                            })
+                            .collect::<Vec<_>>();
+
+                        (country.clone(), available_packages)
                     })
                     .collect::<Vec<_>>()
             })
-            .map_err(move |e| {
+            .map_err(|e| {
                 FailureError::from(e)
                     .context(format!(
-                        "Find available product {} delivery to users country {} failure.",
-                        base_product_id_arg, user_country
+                        "Find available product {} delivery to users countries {:?} failure.",
+                        base_product_id_arg, countries
                     ))
                     .into()
             })
     }
 
-    /// Returns available package for user by id
-    /// DEPRECATED. Use `get_available_package_for_user_by_shipping_id` instead.
-    fn get_available_package_for_user(
+    /// Returns packages available for user by the base product and the company package, ordered by shipping id.
+    /// The pair does not identify the shipping, so there may be several of them
+    fn find_available_packages_for_user(
         &self,
         base_product_id_arg: BaseProductId,
         package_id_arg: CompanyPackageId,
-    ) -> RepoResult<Option<AvailablePackageForUser>> {
+    ) -> RepoResult<Vec<AvailablePackageForUser>> {
         debug!(
-            "Get available package for base product: {} with select company package id: {}.",
-            base_product_id_arg, package_id_arg
+            "{}",
+            log_line(&format!(
+                "Find available packages for base product: {} with select company package id: {}.",
+                base_product_id_arg, package_id_arg
+            ))
         );
 
         let query = DslProducts::products
@@ -288,31 +496,43 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             )
             .filter(DslProducts::base_product_id.eq(base_product_id_arg))
             .filter(DslProducts::company_package_id.eq(package_id_arg))
-            .order(DslCompanies::label);
+            .order(DslProducts::id);
 
         query
-            .get_result::<(ProductsRaw, (CompaniesPackagesRaw, CompanyRaw, PackagesRaw))>(self.db_conn)
-            .optional()
+            .get_results::<(ProductsRaw, (CompaniesPackagesRaw, CompanyRaw, PackagesRaw))>(self.db_conn)
             .map_err(|e| Error::from(e).into())
-            .map(|result| {
-                result.map(|result| {
-                    let (product_raw, (companies_package, company_raw, package_raw)) = result;
-                    AvailablePackageForUser {
-                        id: companies_package.id,
-                        shipping_id: product_raw.id,
-                        name: get_company_package_name(&company_raw.label, &package_raw.name),
-                        logo: company_raw.logo,
-                        price: product_raw.price,
-                        currency: product_raw.currency,
-                        shipping_variant: product_raw.shipping,
-                        store_id: product_raw.store_id,
-                        base_product_id: product_raw.base_product_id,
-                    }
-                })
+            .map(|results| {
+                results
+                    .into_iter()
+                    .map(|result| {
+                        let (product_raw, (companies_package, company_raw, package_raw)) = result;
+                        AvailablePackageForUser {
+                            id: companies_package.id,
+                            shipping_id: product_raw.id,
+                            name: get_company_package_name(&company_raw.label, &package_raw.name),
+                            logo: company_raw.logo,
+                            price: product_raw.price,
+                            cost: None,
+                            option_id: None,
+                            currency: product_raw.currency,
+                            shipping_variant: product_raw.shipping,
+                            store_id: product_raw.store_id,
+                            base_product_id: product_raw.base_product_id,
+                            delivery_time_min_days: companies_package.delivery_time_min_days.map(|days| days as u32),
+                            delivery_time_max_days: companies_package.delivery_time_max_days.map(|days| days as u32),
+                            free_delivery_over: product_raw.free_delivery_over,
+                            free_shipping: false,
+                            delivery_from: product_raw.delivery_from,
+                            shipping_date: None,
+                            estimated_delivery_date_min: None,
+                            estimated_delivery_date_max: None,
+                        }
+                    })
+                    .collect()
             })
             .map_err(move |e: FailureError| {
                 e.context(format!(
-                    "Get available package for base product: {} with select company package id: {} failure.",
+                    "Find available packages for base product: {} with select company package id: {} failure.",
                     base_product_id_arg, package_id_arg
                 ))
                 .into()
@@ -324,7 +544,10 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
         shipping_id_arg: ShippingId,
         delivery_to: Option<Alpha3>,
     ) -> RepoResult<Option<AvailablePackageForUser>> {
-        debug!("Get available package for shipping id: {}.", shipping_id_arg);
+        debug!(
+            "{}",
+            log_line(&format!("Get available package for shipping id: {}.", shipping_id_arg))
+        );
 
         let mut query = DslProducts::products
             .inner_join(
@@ -336,8 +559,7 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             .into_boxed();
 
         if let Some(delivery_to) = delivery_to {
-            let pg_str = get_pg_str_json_array(vec![delivery_to.clone()]);
-            query = query.filter(sql(format!("products.deliveries_to ?| {}", pg_str).as_ref()));
+            query = query.filter(sql("products.deliveries_to ?| ").bind::<Array<VarChar>, _>(vec![delivery_to.0]));
         };
 
         let query = query.order(DslCompanies::label);
@@ -355,10 +577,20 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
                         name: get_company_package_name(&company_raw.label, &package_raw.name),
                         logo: company_raw.logo,
                         price: product_raw.price,
+                        cost: None,
+                        option_id: None,
                         currency: product_raw.currency,
                         shipping_variant: product_raw.shipping,
                         store_id: product_raw.store_id,
                         base_product_id: product_raw.base_product_id,
+                        delivery_time_min_days: companies_package.delivery_time_min_days.map(|days| days as u32),
+                        delivery_time_max_days: companies_package.delivery_time_max_days.map(|days| days as u32),
+                        free_delivery_over: product_raw.free_delivery_over,
+                        free_shipping: false,
+                        delivery_from: product_raw.delivery_from,
+                        shipping_date: None,
+                        estimated_delivery_date_min: None,
+                        estimated_delivery_date_max: None,
                     }
                 })
             })
@@ -374,14 +606,14 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
         company_package_id_arg: CompanyPackageId,
         payload: UpdateProducts,
     ) -> RepoResult<Products> {
-        debug!("Updating products payload {:?}.", payload);
-        let payload = payload.to_raw()?;
+        debug!("{}", log_line(&format!("Updating products payload {:?}.", payload)));
+        let payload = payload.to_raw();
         self.execute_query(
             DslProducts::products
                 .filter(DslProducts::base_product_id.eq(base_product_id_arg))
                 .filter(DslProducts::company_package_id.eq(company_package_id_arg)),
         )
-        .and_then(|products_: ProductsRaw| products_.to_products())
+        .map(ProductsRaw::to_products)
         .and_then(|product: Products| acl::check(&*self.acl, Resource::Products, Action::Update, self, Some(&product)))
         .and_then(|_| {
             let filter = DslProducts::products
@@ -391,12 +623,12 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             let query = diesel::update(filter).set(&payload);
             query.get_result::<ProductsRaw>(self.db_conn).map_err(|e| Error::from(e).into())
         })
-        .and_then(|products_| products_.to_products())
+        .map(ProductsRaw::to_products)
         .map_err(|e: FailureError| e.context(format!("Updating products payload {:?} failed.", payload)).into())
     }
 
     fn delete(&self, base_product_id_arg: BaseProductId) -> RepoResult<Vec<Products>> {
-        debug!("delete products {:?}.", base_product_id_arg);
+        debug!("{}", log_line(&format!("delete products {:?}.", base_product_id_arg)));
 
         let filtered = DslProducts::products.filter(DslProducts::base_product_id.eq(base_product_id_arg));
         let query = diesel::delete(filtered);
@@ -407,7 +639,7 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             .and_then(|products_: Vec<ProductsRaw>| {
                 let mut delete_products = vec![];
                 for product in products_ {
-                    let product = product.to_products()?;
+                    let product = product.to_products();
                     acl::check(&*self.acl, Resource::Products, Action::Delete, self, Some(&product))?;
                     delete_products.push(product);
                 }
@@ -420,23 +652,14 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
     }
 }
 
-impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, Products>
-    for ProductsRepoImpl<'a, T>
-{
+impl<'a, T: DbConnection> CheckScope<Scope, Products> for ProductsRepoImpl<'a, T> {
     fn is_in_scope(&self, user_id_arg: UserId, scope: &Scope, obj: Option<&Products>) -> bool {
         match *scope {
             Scope::All => true,
             Scope::Owned => {
                 if let Some(obj) = obj {
-                    Roles::roles
-                        .filter(Roles::user_id.eq(user_id_arg))
-                        .get_results::<UserRole>(self.db_conn)
-                        .map_err(|e| Error::from(e).into())
-                        .map(|user_roles_arg| {
-                            user_roles_arg
-                                .iter()
-                                .any(|user_role_arg| user_role_arg.data.clone().map(|data| data == obj.store_id.0).unwrap_or_default())
-                        })
+                    get_user_roles_data(self.db_conn, user_id_arg)
+                        .map(|roles_data| self.manages_product(&roles_data, obj))
                         .unwrap_or_else(|_: FailureError| false)
                 } else {
                     false
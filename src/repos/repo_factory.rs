@@ -1,26 +1,73 @@
-use diesel::connection::AnsiTransactionManager;
-use diesel::pg::Pg;
-use diesel::Connection;
 use failure::Error as FailureError;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use stq_cache::cache::{Cache, CacheSingle};
 use stq_types::*;
 
+use credentials::CredentialsCipher;
 use models::*;
 use repos::legacy_acl::{Acl, SystemACL};
 use repos::*;
+use request_context::{log_line, RequestContext};
 
-pub trait ReposFactory<C: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static>: Clone + Send + 'static {
-    fn create_companies_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<CompaniesRepo + 'a>;
-    fn create_companies_packages_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<CompaniesPackagesRepo + 'a>;
-    fn create_countries_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<CountriesRepo + 'a>;
-    fn create_products_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<ProductsRepo + 'a>;
-    fn create_packages_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<PackagesRepo + 'a>;
-    fn create_pickups_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<PickupsRepo + 'a>;
-    fn create_shipping_rates_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<ShippingRatesRepo + 'a>;
-    fn create_users_addresses_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<UserAddressesRepo + 'a>;
-    fn create_user_roles_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<UserRolesRepo + 'a>;
-    fn create_user_roles_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<UserRolesRepo + 'a>;
+pub trait ReposFactory<C: DbConnection>: Clone + Send + 'static {
+    fn create_admin_overview_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn AdminOverviewRepo + 'a>;
+    fn create_api_keys_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ApiKeysRepo + 'a>;
+    fn create_api_keys_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn ApiKeysRepo + 'a>;
+    fn create_availability_snapshots_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn AvailabilitySnapshotsRepo + 'a>;
+    fn create_backfills_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn BackfillsRepo + 'a>;
+    fn create_backfills_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn BackfillsRepo + 'a>;
+    fn create_carrier_accounts_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CarrierAccountsRepo + 'a>;
+    fn create_carrier_accounts_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn CarrierAccountsRepo + 'a>;
+    fn create_companies_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CompaniesRepo + 'a>;
+    fn create_companies_packages_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CompaniesPackagesRepo + 'a>;
+    fn create_company_rates_quotas_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CompanyRatesQuotasRepo + 'a>;
+    fn create_company_rates_quotas_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn CompanyRatesQuotasRepo + 'a>;
+    fn create_company_suspensions_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CompanySuspensionsRepo + 'a>;
+    fn create_company_suspensions_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn CompanySuspensionsRepo + 'a>;
+    fn create_countries_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CountriesRepo + 'a>;
+    fn create_country_regulations_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CountryRegulationsRepo + 'a>;
+    fn create_country_regulations_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn CountryRegulationsRepo + 'a>;
+    fn create_country_dependents_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn CountryDependentsRepo + 'a>;
+    fn create_country_subdivisions_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CountrySubdivisionsRepo + 'a>;
+    fn create_customs_info_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CustomsInfoRepo + 'a>;
+    fn create_delivery_quotes_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn DeliveryQuotesRepo + 'a>;
+    fn create_delivery_quotes_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn DeliveryQuotesRepo + 'a>;
+    fn create_event_outbox_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn EventOutboxRepo + 'a>;
+    fn create_products_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ProductsRepo + 'a>;
+    fn create_package_templates_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn PackageTemplatesRepo + 'a>;
+    fn create_packages_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn PackagesRepo + 'a>;
+    fn create_permissions_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn PermissionsRepo + 'a>;
+    fn create_permissions_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn PermissionsRepo + 'a>;
+    fn create_pickup_points_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn PickupPointsRepo + 'a>;
+    fn create_pickups_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn PickupsRepo + 'a>;
+    fn create_postal_zones_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn PostalZonesRepo + 'a>;
+    fn create_pricing_rules_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn PricingRulesRepo + 'a>;
+    fn create_pricing_rules_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn PricingRulesRepo + 'a>;
+    fn create_shipment_bookings_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ShipmentBookingsRepo + 'a>;
+    fn create_shipment_bookings_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn ShipmentBookingsRepo + 'a>;
+    fn create_shipment_notifications_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ShipmentNotificationsRepo + 'a>;
+    fn create_shipping_rates_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ShippingRatesRepo + 'a>;
+    fn create_shipping_rates_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn ShippingRatesRepo + 'a>;
+    fn create_shipping_rates_anomalies_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>)
+        -> Box<dyn ShippingRatesAnomaliesRepo + 'a>;
+    fn create_shipping_rates_anomalies_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn ShippingRatesAnomaliesRepo + 'a>;
+    fn create_shipping_restrictions_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ShippingRestrictionsRepo + 'a>;
+    fn create_shipping_templates_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ShippingTemplatesRepo + 'a>;
+    fn create_store_managers_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn StoreManagersRepo + 'a>;
+    fn create_store_margins_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn StoreMarginsRepo + 'a>;
+    fn create_store_margins_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn StoreMarginsRepo + 'a>;
+    fn create_store_shipping_summaries_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>)
+        -> Box<dyn StoreShippingSummariesRepo + 'a>;
+    fn create_surcharges_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn SurchargesRepo + 'a>;
+    fn create_surcharges_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn SurchargesRepo + 'a>;
+    #[cfg(feature = "test-endpoints")]
+    fn create_test_fixtures_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn TestFixturesRepo + 'a>;
+    fn create_users_addresses_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn UserAddressesRepo + 'a>;
+    fn create_user_roles_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn UserRolesRepo + 'a>;
+    fn create_user_roles_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn UserRolesRepo + 'a>;
+    fn create_zones_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ZonesRepo + 'a>;
 }
 
 pub struct ReposFactoryImpl<C1, C2>
@@ -30,6 +77,20 @@ where
 {
     country_cache: Arc<CountryCacheImpl<C1>>,
     roles_cache: Arc<RolesCacheImpl<C2>>,
+    permissions: Arc<Mutex<CachedPermissions>>,
+    /// Encrypts the credentials of the carrier accounts
+    credentials_cipher: Arc<CredentialsCipher>,
+}
+
+/// Permissions matrix is reloaded from DB after this time, so that its changes are picked up even if they are not announced
+const PERMISSIONS_TTL_SEC: u64 = 60;
+
+#[derive(Default)]
+struct CachedPermissions {
+    /// Permissions matrix loaded from DB with the time it was loaded at
+    matrix: Option<(Instant, HashMap<DeliveryRole, Vec<Permission>>)>,
+    /// Number of the times the matrix was dropped, a matrix loaded before the last drop is not cached
+    forgotten: u64,
 }
 
 impl<C1, C2> Clone for ReposFactoryImpl<C1, C2>
@@ -41,6 +102,8 @@ where
         Self {
             country_cache: self.country_cache.clone(),
             roles_cache: self.roles_cache.clone(),
+            permissions: self.permissions.clone(),
+            credentials_cipher: self.credentials_cipher.clone(),
         }
     }
 }
@@ -50,34 +113,87 @@ where
     C1: CacheSingle<Country> + Send + Sync + 'static,
     C2: Cache<Vec<DeliveryRole>> + Send + Sync + 'static,
 {
-    pub fn new(country_cache: CountryCacheImpl<C1>, roles_cache: RolesCacheImpl<C2>) -> Self {
+    pub fn new(country_cache: CountryCacheImpl<C1>, roles_cache: RolesCacheImpl<C2>, credentials_cipher: CredentialsCipher) -> Self {
         Self {
             country_cache: Arc::new(country_cache),
             roles_cache: Arc::new(roles_cache),
+            permissions: Arc::new(Mutex::default()),
+            credentials_cipher: Arc::new(credentials_cipher),
         }
     }
 
-    pub fn get_roles<'a, C: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static>(
-        &self,
-        id: UserId,
-        db_conn: &'a C,
-    ) -> Vec<DeliveryRole> {
+    pub fn get_roles<'a, C: DbConnection>(&self, id: UserId, db_conn: &'a C) -> Vec<DeliveryRole> {
         self.create_user_roles_repo_with_sys_acl(db_conn)
             .list_for_user(id)
             .ok()
             .unwrap_or_default()
     }
 
-    fn get_acl<'a, T, C: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static>(
+    /// Returns permissions matrix from DB, the built-in matrix is used if it can not be loaded.
+    /// The lock is not held while the matrix is loaded, so that requests of other threads are not blocked by DB
+    pub fn get_permissions<'a, C: DbConnection>(&self, db_conn: &'a C) -> HashMap<DeliveryRole, Vec<Permission>> {
+        let forgotten = {
+            let cached = self.permissions.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some((loaded_at, ref permissions)) = cached.matrix {
+                if loaded_at.elapsed() < Duration::from_secs(PERMISSIONS_TTL_SEC) {
+                    return permissions.clone();
+                }
+            }
+            cached.forgotten
+        };
+
+        // the built-in matrix is cached as well, so that an empty or unreadable table is not queried by every request
+        let permissions = match self.create_permissions_repo_with_sys_acl(db_conn).list() {
+            Ok(ref role_permissions) if !role_permissions.is_empty() => {
+                let mut permissions = permissions_matrix(role_permissions.clone());
+                add_test_fixtures_permission(&mut permissions);
+                permissions
+            }
+            Ok(_) => default_permissions(),
+            Err(e) => {
+                error!(
+                    "{}",
+                    log_line(&format!("Failed to load permissions matrix, the built-in one is used: {}", e))
+                );
+                default_permissions()
+            }
+        };
+
+        let mut cached = self.permissions.lock().unwrap_or_else(|e| e.into_inner());
+        if cached.forgotten == forgotten {
+            cached.matrix = Some((Instant::now(), permissions.clone()));
+        }
+        permissions
+    }
+
+    /// Drops the permissions matrix, it is reloaded from DB by the next request
+    pub fn forget_permissions(&self) {
+        let mut cached = self.permissions.lock().unwrap_or_else(|e| e.into_inner());
+        cached.matrix = None;
+        cached.forgotten += 1;
+    }
+
+    fn get_acl<'a, T, C: DbConnection>(
         &self,
         db_conn: &'a C,
         user_id: Option<UserId>,
-    ) -> Box<Acl<Resource, Action, Scope, FailureError, T>> {
+    ) -> Box<dyn Acl<Resource, Action, Scope, FailureError, T>> {
+        // requests of other services are limited to the scopes of their api key
+        if let Some(api_key_scopes) = RequestContext::current().and_then(|context| context.api_key_scopes) {
+            return Box::new(ApiKeyAcl::new(api_key_scopes));
+        }
+
         user_id.map_or(
-            Box::new(UnauthorizedAcl::default()) as Box<Acl<Resource, Action, Scope, FailureError, T>>,
+            Box::new(UnauthorizedAcl::default()) as Box<dyn Acl<Resource, Action, Scope, FailureError, T>>,
             |id| {
-                let roles = self.get_roles(id, db_conn);
-                (Box::new(ApplicationAcl::new(roles, id)) as Box<Acl<Resource, Action, Scope, FailureError, T>>)
+                // roles from the token of the request are trusted, the ones in db are used for legacy requests
+                let roles = RequestContext::current()
+                    .filter(|context| context.user_id == Some(id))
+                    .and_then(|context| context.roles)
+                    .unwrap_or_else(|| self.get_roles(id, db_conn));
+                let permissions = self.get_permissions(db_conn);
+                (Box::new(ApplicationAcl::with_permissions(permissions, roles, id))
+                    as Box<dyn Acl<Resource, Action, Scope, FailureError, T>>)
             },
         )
     }
@@ -85,67 +201,327 @@ where
 
 impl<C, C1, C2> ReposFactory<C> for ReposFactoryImpl<C1, C2>
 where
-    C: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    C: DbConnection,
     C1: CacheSingle<Country> + Send + Sync + 'static,
     C2: Cache<Vec<DeliveryRole>> + Send + Sync + 'static,
 {
-    fn create_companies_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<CompaniesRepo + 'a> {
+    fn create_admin_overview_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn AdminOverviewRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(AdminOverviewRepoImpl::new(db_conn, acl)) as Box<dyn AdminOverviewRepo>
+    }
+
+    fn create_api_keys_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ApiKeysRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(ApiKeysRepoImpl::new(db_conn, acl)) as Box<dyn ApiKeysRepo>
+    }
+
+    fn create_api_keys_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn ApiKeysRepo + 'a> {
+        Box::new(ApiKeysRepoImpl::new(
+            db_conn,
+            Box::new(SystemACL::default()) as Box<dyn Acl<Resource, Action, Scope, FailureError, ApiKey>>,
+        )) as Box<dyn ApiKeysRepo>
+    }
+
+    fn create_availability_snapshots_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn AvailabilitySnapshotsRepo + 'a> {
+        Box::new(AvailabilitySnapshotsRepoImpl::new(
+            db_conn,
+            Box::new(SystemACL::default()) as Box<dyn Acl<Resource, Action, Scope, FailureError, AvailabilitySnapshot>>,
+        )) as Box<dyn AvailabilitySnapshotsRepo>
+    }
+
+    fn create_backfills_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn BackfillsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(BackfillsRepoImpl::new(db_conn, acl)) as Box<dyn BackfillsRepo>
+    }
+
+    fn create_backfills_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn BackfillsRepo + 'a> {
+        Box::new(BackfillsRepoImpl::new(
+            db_conn,
+            Box::new(SystemACL::default()) as Box<dyn Acl<Resource, Action, Scope, FailureError, BackfillProgress>>,
+        )) as Box<dyn BackfillsRepo>
+    }
+
+    fn create_carrier_accounts_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CarrierAccountsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(CarrierAccountsRepoImpl::new(db_conn, acl, self.credentials_cipher.clone())) as Box<dyn CarrierAccountsRepo>
+    }
+
+    fn create_carrier_accounts_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn CarrierAccountsRepo + 'a> {
+        Box::new(CarrierAccountsRepoImpl::new(
+            db_conn,
+            Box::new(SystemACL::default()) as Box<dyn Acl<Resource, Action, Scope, FailureError, StoreId>>,
+            self.credentials_cipher.clone(),
+        )) as Box<dyn CarrierAccountsRepo>
+    }
+
+    fn create_companies_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CompaniesRepo + 'a> {
         let acl = self.get_acl(db_conn, user_id);
         let all_countries = self.create_countries_repo(db_conn, user_id).get_all().ok().unwrap_or_default();
-        Box::new(CompaniesRepoImpl::new(db_conn, acl, all_countries)) as Box<CompaniesRepo>
+        Box::new(CompaniesRepoImpl::new(db_conn, acl, all_countries)) as Box<dyn CompaniesRepo>
     }
 
-    fn create_companies_packages_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<CompaniesPackagesRepo + 'a> {
+    fn create_companies_packages_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CompaniesPackagesRepo + 'a> {
         let acl = self.get_acl(db_conn, user_id);
         let all_countries = self.create_countries_repo(db_conn, user_id).get_all().ok().unwrap_or_default();
-        Box::new(CompaniesPackagesRepoImpl::new(db_conn, acl, all_countries)) as Box<CompaniesPackagesRepo>
+        Box::new(CompaniesPackagesRepoImpl::new(db_conn, acl, all_countries)) as Box<dyn CompaniesPackagesRepo>
+    }
+
+    fn create_company_rates_quotas_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CompanyRatesQuotasRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(CompanyRatesQuotasRepoImpl::new(db_conn, acl)) as Box<dyn CompanyRatesQuotasRepo>
+    }
+
+    fn create_company_rates_quotas_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn CompanyRatesQuotasRepo + 'a> {
+        Box::new(CompanyRatesQuotasRepoImpl::new(
+            db_conn,
+            Box::new(SystemACL::default()) as Box<dyn Acl<Resource, Action, Scope, FailureError, CompanyRatesQuota>>,
+        )) as Box<dyn CompanyRatesQuotasRepo>
+    }
+
+    fn create_company_suspensions_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CompanySuspensionsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(CompanySuspensionsRepoImpl::new(db_conn, acl)) as Box<dyn CompanySuspensionsRepo>
     }
 
-    fn create_countries_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<CountriesRepo + 'a> {
+    fn create_company_suspensions_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn CompanySuspensionsRepo + 'a> {
+        Box::new(CompanySuspensionsRepoImpl::new(
+            db_conn,
+            Box::new(SystemACL::default()) as Box<dyn Acl<Resource, Action, Scope, FailureError, CompanySuspension>>,
+        )) as Box<dyn CompanySuspensionsRepo>
+    }
+
+    fn create_countries_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CountriesRepo + 'a> {
         let acl = self.get_acl(db_conn, user_id);
         let cache = self.country_cache.clone();
-        Box::new(CountriesRepoImpl::new(db_conn, acl, cache)) as Box<CountriesRepo>
+        Box::new(CountriesRepoImpl::new(db_conn, acl, cache)) as Box<dyn CountriesRepo>
+    }
+
+    fn create_country_regulations_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CountryRegulationsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(CountryRegulationsRepoImpl::new(db_conn, acl)) as Box<dyn CountryRegulationsRepo>
+    }
+
+    fn create_country_regulations_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn CountryRegulationsRepo + 'a> {
+        Box::new(CountryRegulationsRepoImpl::new(
+            db_conn,
+            Box::new(SystemACL::default()) as Box<dyn Acl<Resource, Action, Scope, FailureError, CountryRegulation>>,
+        )) as Box<dyn CountryRegulationsRepo>
+    }
+
+    fn create_country_subdivisions_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CountrySubdivisionsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(CountrySubdivisionsRepoImpl::new(db_conn, acl)) as Box<dyn CountrySubdivisionsRepo>
     }
 
-    fn create_products_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<ProductsRepo + 'a> {
+    fn create_country_dependents_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn CountryDependentsRepo + 'a> {
+        Box::new(CountryDependentsRepoImpl::new(
+            db_conn,
+            Box::new(SystemACL::default()) as Box<dyn Acl<Resource, Action, Scope, FailureError, ()>>,
+        )) as Box<dyn CountryDependentsRepo>
+    }
+
+    fn create_customs_info_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CustomsInfoRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(CustomsInfoRepoImpl::new(db_conn, acl)) as Box<dyn CustomsInfoRepo>
+    }
+
+    fn create_delivery_quotes_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn DeliveryQuotesRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(DeliveryQuotesRepoImpl::new(db_conn, acl)) as Box<dyn DeliveryQuotesRepo>
+    }
+
+    fn create_delivery_quotes_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn DeliveryQuotesRepo + 'a> {
+        Box::new(DeliveryQuotesRepoImpl::new(
+            db_conn,
+            Box::new(SystemACL::default()) as Box<dyn Acl<Resource, Action, Scope, FailureError, DeliveryQuote>>,
+        )) as Box<dyn DeliveryQuotesRepo>
+    }
+
+    fn create_event_outbox_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn EventOutboxRepo + 'a> {
+        Box::new(EventOutboxRepoImpl::new(
+            db_conn,
+            Box::new(SystemACL::default()) as Box<dyn Acl<Resource, Action, Scope, FailureError, OutboxEvent>>,
+        )) as Box<dyn EventOutboxRepo>
+    }
+
+    fn create_products_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ProductsRepo + 'a> {
         let acl = self.get_acl(db_conn, user_id);
         let all_countries = self.create_countries_repo(db_conn, user_id).get_all().ok().unwrap_or_default();
-        Box::new(ProductsRepoImpl::new(db_conn, acl, all_countries)) as Box<ProductsRepo>
+        Box::new(ProductsRepoImpl::new(db_conn, acl, all_countries)) as Box<dyn ProductsRepo>
+    }
+
+    fn create_package_templates_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn PackageTemplatesRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(PackageTemplatesRepoImpl::new(db_conn, acl)) as Box<dyn PackageTemplatesRepo>
     }
 
-    fn create_packages_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<PackagesRepo + 'a> {
+    fn create_packages_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn PackagesRepo + 'a> {
         let acl = self.get_acl(db_conn, user_id);
         let all_countries = self.create_countries_repo(db_conn, user_id).get_all().ok().unwrap_or_default();
-        Box::new(PackagesRepoImpl::new(db_conn, acl, all_countries)) as Box<PackagesRepo>
+        Box::new(PackagesRepoImpl::new(db_conn, acl, all_countries)) as Box<dyn PackagesRepo>
+    }
+
+    fn create_permissions_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn PermissionsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(PermissionsRepoImpl::new(db_conn, acl)) as Box<dyn PermissionsRepo>
+    }
+
+    fn create_permissions_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn PermissionsRepo + 'a> {
+        Box::new(PermissionsRepoImpl::new(
+            db_conn,
+            Box::new(SystemACL::default()) as Box<dyn Acl<Resource, Action, Scope, FailureError, RolePermission>>,
+        )) as Box<dyn PermissionsRepo>
+    }
+
+    fn create_pickup_points_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn PickupPointsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(PickupPointsRepoImpl::new(db_conn, acl)) as Box<dyn PickupPointsRepo>
+    }
+
+    fn create_pickups_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn PickupsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(PickupsRepoImpl::new(db_conn, acl)) as Box<dyn PickupsRepo>
+    }
+
+    fn create_postal_zones_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn PostalZonesRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(PostalZonesRepoImpl::new(db_conn, acl)) as Box<dyn PostalZonesRepo>
+    }
+
+    fn create_pricing_rules_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn PricingRulesRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(PricingRulesRepoImpl::new(db_conn, acl)) as Box<dyn PricingRulesRepo>
+    }
+
+    fn create_pricing_rules_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn PricingRulesRepo + 'a> {
+        Box::new(PricingRulesRepoImpl::new(
+            db_conn,
+            Box::new(SystemACL::default()) as Box<dyn Acl<Resource, Action, Scope, FailureError, PricingRule>>,
+        )) as Box<dyn PricingRulesRepo>
+    }
+
+    fn create_shipment_bookings_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ShipmentBookingsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(ShipmentBookingsRepoImpl::new(db_conn, acl)) as Box<dyn ShipmentBookingsRepo>
+    }
+
+    fn create_shipment_bookings_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn ShipmentBookingsRepo + 'a> {
+        Box::new(ShipmentBookingsRepoImpl::new(
+            db_conn,
+            Box::new(SystemACL::default()) as Box<dyn Acl<Resource, Action, Scope, FailureError, ShipmentBooking>>,
+        )) as Box<dyn ShipmentBookingsRepo>
+    }
+
+    fn create_shipment_notifications_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ShipmentNotificationsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(ShipmentNotificationsRepoImpl::new(db_conn, acl)) as Box<dyn ShipmentNotificationsRepo>
+    }
+
+    fn create_shipping_rates_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ShippingRatesRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(ShippingRatesRepoImpl::new(db_conn, acl)) as Box<dyn ShippingRatesRepo>
+    }
+
+    fn create_shipping_rates_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn ShippingRatesRepo + 'a> {
+        Box::new(ShippingRatesRepoImpl::new(
+            db_conn,
+            Box::new(SystemACL::default()) as Box<dyn Acl<Resource, Action, Scope, FailureError, ()>>,
+        )) as Box<dyn ShippingRatesRepo>
+    }
+
+    fn create_shipping_rates_anomalies_repo<'a>(
+        &self,
+        db_conn: &'a C,
+        user_id: Option<UserId>,
+    ) -> Box<dyn ShippingRatesAnomaliesRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(ShippingRatesAnomaliesRepoImpl::new(db_conn, acl)) as Box<dyn ShippingRatesAnomaliesRepo>
+    }
+
+    fn create_shipping_rates_anomalies_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn ShippingRatesAnomaliesRepo + 'a> {
+        Box::new(ShippingRatesAnomaliesRepoImpl::new(
+            db_conn,
+            Box::new(SystemACL::default()) as Box<dyn Acl<Resource, Action, Scope, FailureError, ShippingRatesAnomaly>>,
+        )) as Box<dyn ShippingRatesAnomaliesRepo>
+    }
+
+    fn create_shipping_restrictions_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ShippingRestrictionsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(ShippingRestrictionsRepoImpl::new(db_conn, acl)) as Box<dyn ShippingRestrictionsRepo>
+    }
+
+    fn create_shipping_templates_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ShippingTemplatesRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(ShippingTemplatesRepoImpl::new(db_conn, acl)) as Box<dyn ShippingTemplatesRepo>
+    }
+
+    fn create_store_managers_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn StoreManagersRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        let cache = self.roles_cache.clone();
+        Box::new(StoreManagersRepoImpl::new(db_conn, acl, cache)) as Box<dyn StoreManagersRepo>
+    }
+
+    fn create_store_margins_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn StoreMarginsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(StoreMarginsRepoImpl::new(db_conn, acl)) as Box<dyn StoreMarginsRepo>
+    }
+
+    fn create_store_margins_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn StoreMarginsRepo + 'a> {
+        Box::new(StoreMarginsRepoImpl::new(
+            db_conn,
+            Box::new(SystemACL::default()) as Box<dyn Acl<Resource, Action, Scope, FailureError, StoreId>>,
+        )) as Box<dyn StoreMarginsRepo>
+    }
+
+    fn create_store_shipping_summaries_repo<'a>(
+        &self,
+        db_conn: &'a C,
+        user_id: Option<UserId>,
+    ) -> Box<dyn StoreShippingSummariesRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(StoreShippingSummariesRepoImpl::new(db_conn, acl)) as Box<dyn StoreShippingSummariesRepo>
     }
 
-    fn create_pickups_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<PickupsRepo + 'a> {
+    fn create_surcharges_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn SurchargesRepo + 'a> {
         let acl = self.get_acl(db_conn, user_id);
-        Box::new(PickupsRepoImpl::new(db_conn, acl)) as Box<PickupsRepo>
+        Box::new(SurchargesRepoImpl::new(db_conn, acl)) as Box<dyn SurchargesRepo>
+    }
+
+    fn create_surcharges_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn SurchargesRepo + 'a> {
+        Box::new(SurchargesRepoImpl::new(
+            db_conn,
+            Box::new(SystemACL::default()) as Box<dyn Acl<Resource, Action, Scope, FailureError, Surcharge>>,
+        )) as Box<dyn SurchargesRepo>
     }
 
-    fn create_shipping_rates_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<ShippingRatesRepo + 'a> {
+    #[cfg(feature = "test-endpoints")]
+    fn create_test_fixtures_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn TestFixturesRepo + 'a> {
         let acl = self.get_acl(db_conn, user_id);
-        Box::new(ShippingRatesRepoImpl::new(db_conn, acl)) as Box<ShippingRatesRepo>
+        Box::new(TestFixturesRepoImpl::new(db_conn, acl)) as Box<dyn TestFixturesRepo>
     }
 
-    fn create_users_addresses_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<UserAddressesRepo + 'a> {
+    fn create_users_addresses_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn UserAddressesRepo + 'a> {
         let acl = self.get_acl(db_conn, user_id);
-        Box::new(UserAddressesRepoImpl::new(db_conn, acl)) as Box<UserAddressesRepo>
+        Box::new(UserAddressesRepoImpl::new(db_conn, acl)) as Box<dyn UserAddressesRepo>
     }
 
-    fn create_user_roles_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<UserRolesRepo + 'a> {
+    fn create_user_roles_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<dyn UserRolesRepo + 'a> {
         let cache = self.roles_cache.clone();
         Box::new(UserRolesRepoImpl::new(
             db_conn,
-            Box::new(SystemACL::default()) as Box<Acl<Resource, Action, Scope, FailureError, UserRole>>,
+            Box::new(SystemACL::default()) as Box<dyn Acl<Resource, Action, Scope, FailureError, UserRole>>,
             cache,
-        )) as Box<UserRolesRepo>
+        )) as Box<dyn UserRolesRepo>
     }
-    fn create_user_roles_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<UserRolesRepo + 'a> {
+    fn create_user_roles_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn UserRolesRepo + 'a> {
         let acl = self.get_acl(db_conn, user_id);
         let cache = self.roles_cache.clone();
-        Box::new(UserRolesRepoImpl::new(db_conn, acl, cache)) as Box<UserRolesRepo>
+        Box::new(UserRolesRepoImpl::new(db_conn, acl, cache)) as Box<dyn UserRolesRepo>
+    }
+
+    fn create_zones_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ZonesRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(ZonesRepoImpl::new(db_conn, acl)) as Box<dyn ZonesRepo>
     }
 }
 
@@ -155,11 +531,13 @@ pub mod tests {
     extern crate r2d2;
     extern crate stq_http;
 
+    use std::cell::Cell;
     use std::error::Error;
     use std::fmt;
     use std::sync::Arc;
-    use std::time::SystemTime;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+    use chrono::NaiveDate;
     use diesel::connection::AnsiTransactionManager;
     use diesel::connection::SimpleConnection;
     use diesel::deserialize::QueryableByName;
@@ -172,17 +550,25 @@ pub mod tests {
     use diesel::ConnectionResult;
     use diesel::QueryResult;
     use diesel::Queryable;
+    use failure::Error as FailureError;
     use futures::Stream;
     use futures_cpupool::CpuPool;
     use r2d2::ManageConnection;
     use tokio_core::reactor::Handle;
+    use uuid::Uuid;
 
+    use stq_http::errors::ErrorMessageWrapper;
     use stq_static_resources::Currency;
     use stq_types::*;
 
     use config::Config;
     use controller::context::{DynamicContext, StaticContext};
+    use credentials::tests::test_cipher;
+    use errors;
+    use events::DeliveryEvent;
     use models::*;
+    use replicas::ReplicaRouter;
+    use repos::legacy_acl::{Acl, CheckScope};
     use repos::*;
     use services::*;
 
@@ -190,162 +576,580 @@ pub mod tests {
     pub static MOCK_USER_ID: UserId = UserId(1);
     pub static MOCK_STORE_ID: StoreId = StoreId(1);
     pub static MOCK_BASE_PRODUCT_ID: BaseProductId = BaseProductId(1);
+    /// Store manager of `MOCK_OTHER_STORE_ID`, must not be able to touch objects of `MOCK_STORE_ID`
+    pub static MOCK_STORE_MANAGER_ID: UserId = UserId(2);
+    pub static MOCK_OTHER_STORE_ID: StoreId = StoreId(2);
+
+    pub fn mock_roles(user_id: UserId) -> Vec<DeliveryRole> {
+        if user_id == MOCK_USER_ID {
+            vec![DeliveryRole::Superuser]
+        } else if user_id == MOCK_STORE_MANAGER_ID {
+            vec![DeliveryRole::User, DeliveryRole::StoreManager]
+        } else {
+            vec![DeliveryRole::User]
+        }
+    }
+
+    /// Roles data of the mock users, the mock repos check the scopes with it the same way the repos check
+    /// the roles data stored in db
+    pub fn mock_roles_data(user_id: Option<UserId>) -> Vec<RoleData> {
+        match user_id {
+            Some(id) if id == MOCK_STORE_MANAGER_ID => vec![RoleData::StoreManager {
+                store_id: MOCK_OTHER_STORE_ID,
+            }],
+            _ => vec![],
+        }
+    }
+
+    thread_local! {
+        static MOCK_QUERIES: Cell<usize> = Cell::new(0);
+    }
+
+    /// Number of queries the mock repos of the shipping availability have run on the current thread
+    pub fn mock_queries() -> usize {
+        MOCK_QUERIES.with(|queries| queries.get())
+    }
+
+    fn count_query() {
+        MOCK_QUERIES.with(|queries| queries.set(queries.get() + 1));
+    }
+
+    /// Runs the same ACL check as the real repos do, using the roles from `mock_roles`
+    fn check_acl<T>(
+        user_id: Option<UserId>,
+        resource: Resource,
+        action: Action,
+        scope_checker: &dyn CheckScope<Scope, T>,
+        obj: Option<&T>,
+    ) -> RepoResult<()> {
+        let acl = match user_id {
+            Some(id) => Box::new(ApplicationAcl::new(mock_roles(id), id)) as Box<dyn Acl<Resource, Action, Scope, FailureError, T>>,
+            None => Box::new(UnauthorizedAcl::default()) as Box<dyn Acl<Resource, Action, Scope, FailureError, T>>,
+        };
+        acl::check(&*acl, resource, action, scope_checker, obj)
+    }
 
     #[derive(Default, Copy, Clone)]
     pub struct ReposFactoryMock;
 
-    impl<C: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> ReposFactory<C> for ReposFactoryMock {
-        fn create_companies_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<CompaniesRepo + 'a> {
-            Box::new(CompaniesRepoMock::default()) as Box<CompaniesRepo>
+    impl<C: DbConnection> ReposFactory<C> for ReposFactoryMock {
+        fn create_admin_overview_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn AdminOverviewRepo + 'a> {
+            Box::new(AdminOverviewRepoMock { user_id }) as Box<dyn AdminOverviewRepo>
         }
 
-        fn create_companies_packages_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<CompaniesPackagesRepo + 'a> {
-            Box::new(CompaniesPackagesRepoMock::default()) as Box<CompaniesPackagesRepo>
+        fn create_api_keys_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ApiKeysRepo + 'a> {
+            Box::new(ApiKeysRepoMock { user_id }) as Box<dyn ApiKeysRepo>
         }
 
-        fn create_countries_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<CountriesRepo + 'a> {
-            Box::new(CountriesRepoMock::default()) as Box<CountriesRepo>
+        fn create_api_keys_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<dyn ApiKeysRepo + 'a> {
+            Box::new(ApiKeysRepoMock {
+                user_id: Some(MOCK_USER_ID),
+            }) as Box<dyn ApiKeysRepo>
         }
 
-        fn create_products_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<ProductsRepo + 'a> {
-            Box::new(ProductsRepoMock::default()) as Box<ProductsRepo>
+        fn create_availability_snapshots_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<dyn AvailabilitySnapshotsRepo + 'a> {
+            Box::new(AvailabilitySnapshotsRepoMock {
+                user_id: Some(MOCK_USER_ID),
+            }) as Box<dyn AvailabilitySnapshotsRepo>
         }
 
-        fn create_packages_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<PackagesRepo + 'a> {
-            Box::new(PackagesRepoMock::default()) as Box<PackagesRepo>
+        fn create_backfills_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn BackfillsRepo + 'a> {
+            Box::new(BackfillsRepoMock { user_id }) as Box<dyn BackfillsRepo>
         }
 
-        fn create_pickups_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<PickupsRepo + 'a> {
-            Box::new(PickupsRepoMock::default()) as Box<PickupsRepo>
+        fn create_backfills_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<dyn BackfillsRepo + 'a> {
+            Box::new(BackfillsRepoMock {
+                user_id: Some(MOCK_USER_ID),
+            }) as Box<dyn BackfillsRepo>
         }
 
-        fn create_shipping_rates_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<ShippingRatesRepo + 'a> {
-            Box::new(ShippingRatesRepoMock::default()) as Box<ShippingRatesRepo>
+        fn create_carrier_accounts_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CarrierAccountsRepo + 'a> {
+            Box::new(CarrierAccountsRepoMock { user_id }) as Box<dyn CarrierAccountsRepo>
         }
 
-        fn create_users_addresses_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<UserAddressesRepo + 'a> {
-            Box::new(UserAddressesRepoMock::default()) as Box<UserAddressesRepo>
+        fn create_carrier_accounts_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<dyn CarrierAccountsRepo + 'a> {
+            Box::new(CarrierAccountsRepoMock {
+                user_id: Some(MOCK_USER_ID),
+            }) as Box<dyn CarrierAccountsRepo>
         }
 
-        fn create_user_roles_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<UserRolesRepo + 'a> {
-            Box::new(UserRolesRepoMock::default()) as Box<UserRolesRepo>
+        fn create_companies_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CompaniesRepo + 'a> {
+            Box::new(CompaniesRepoMock { user_id }) as Box<dyn CompaniesRepo>
         }
-        fn create_user_roles_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<UserRolesRepo + 'a> {
-            Box::new(UserRolesRepoMock::default()) as Box<UserRolesRepo>
+
+        fn create_companies_packages_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CompaniesPackagesRepo + 'a> {
+            Box::new(CompaniesPackagesRepoMock { user_id }) as Box<dyn CompaniesPackagesRepo>
         }
-    }
 
-    pub fn create_service(
-        user_id: Option<UserId>,
-        handle: Arc<Handle>,
-    ) -> Service<MockConnection, MockConnectionManager, ReposFactoryMock> {
-        let manager = MockConnectionManager::default();
-        let db_pool = r2d2::Pool::builder().build(manager).expect("Failed to create connection pool");
-        let cpu_pool = CpuPool::new(1);
+        fn create_company_rates_quotas_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CompanyRatesQuotasRepo + 'a> {
+            Box::new(CompanyRatesQuotasRepoMock { user_id }) as Box<dyn CompanyRatesQuotasRepo>
+        }
 
-        let config = Config::new().unwrap();
-        let client = stq_http::client::Client::new(&config.to_http_config(), &handle);
-        let client_handle = client.handle();
-        let client_stream = client.stream();
-        handle.spawn(client_stream.for_each(|_| Ok(())));
-        let static_context = StaticContext::new(db_pool, cpu_pool, client_handle, Arc::new(config), MOCK_REPO_FACTORY);
-        let dynamic_context = DynamicContext::new(user_id, String::default());
+        fn create_company_rates_quotas_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<dyn CompanyRatesQuotasRepo + 'a> {
+            Box::new(CompanyRatesQuotasRepoMock {
+                user_id: Some(MOCK_USER_ID),
+            }) as Box<dyn CompanyRatesQuotasRepo>
+        }
 
-        Service::new(static_context, dynamic_context)
-    }
+        fn create_company_suspensions_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CompanySuspensionsRepo + 'a> {
+            Box::new(CompanySuspensionsRepoMock { user_id }) as Box<dyn CompanySuspensionsRepo>
+        }
 
-    #[derive(Clone, Default)]
-    pub struct UserRolesRepoMock;
+        fn create_company_suspensions_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<dyn CompanySuspensionsRepo + 'a> {
+            Box::new(CompanySuspensionsRepoMock {
+                user_id: Some(MOCK_USER_ID),
+            }) as Box<dyn CompanySuspensionsRepo>
+        }
 
-    impl UserRolesRepo for UserRolesRepoMock {
-        fn list_for_user(&self, user_id_value: UserId) -> RepoResult<Vec<DeliveryRole>> {
-            Ok(match user_id_value.0 {
-                1 => vec![DeliveryRole::Superuser],
-                _ => vec![DeliveryRole::User],
-            })
+        fn create_countries_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CountriesRepo + 'a> {
+            Box::new(CountriesRepoMock { user_id }) as Box<dyn CountriesRepo>
         }
 
-        fn create(&self, payload: NewUserRole) -> RepoResult<UserRole> {
-            Ok(UserRole {
-                id: RoleId::new(),
-                user_id: payload.user_id,
-                name: payload.name,
-                data: None,
-            })
+        fn create_country_regulations_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CountryRegulationsRepo + 'a> {
+            Box::new(CountryRegulationsRepoMock { user_id }) as Box<dyn CountryRegulationsRepo>
         }
 
-        fn delete_by_user_id(&self, user_id_arg: UserId) -> RepoResult<Vec<UserRole>> {
-            Ok(vec![UserRole {
-                id: RoleId::new(),
-                user_id: user_id_arg,
-                name: DeliveryRole::User,
-                data: None,
-            }])
+        fn create_country_regulations_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<dyn CountryRegulationsRepo + 'a> {
+            Box::new(CountryRegulationsRepoMock {
+                user_id: Some(MOCK_USER_ID),
+            }) as Box<dyn CountryRegulationsRepo>
         }
 
-        fn delete_by_id(&self, id: RoleId) -> RepoResult<UserRole> {
-            Ok(UserRole {
-                id,
-                user_id: UserId(1),
-                name: DeliveryRole::User,
-                data: None,
-            })
+        fn create_country_subdivisions_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CountrySubdivisionsRepo + 'a> {
+            Box::new(CountrySubdivisionsRepoMock { user_id }) as Box<dyn CountrySubdivisionsRepo>
         }
-    }
 
-    #[derive(Clone, Default)]
-    pub struct ProductsRepoMock;
+        fn create_country_dependents_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<dyn CountryDependentsRepo + 'a> {
+            Box::new(CountryDependentsRepoMock {
+                user_id: Some(MOCK_USER_ID),
+            }) as Box<dyn CountryDependentsRepo>
+        }
 
-    impl ProductsRepo for ProductsRepoMock {
-        /// Create a new products
-        fn create(&self, payload: NewProducts) -> RepoResult<Products> {
-            Ok(Products {
-                id: ShippingId(1),
-                base_product_id: payload.base_product_id,
-                store_id: payload.store_id,
-                company_package_id: payload.company_package_id,
-                shipping: payload.shipping,
-                price: payload.price,
-                deliveries_to: payload.deliveries_to,
-                currency: payload.currency,
-            })
+        fn create_customs_info_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn CustomsInfoRepo + 'a> {
+            Box::new(CustomsInfoRepoMock { user_id }) as Box<dyn CustomsInfoRepo>
         }
 
-        /// Create many a new products
-        fn create_many(&self, payloads: Vec<NewProducts>) -> RepoResult<Vec<Products>> {
-            let mut result = vec![];
-            for item in payloads {
-                result.push(Products {
-                    id: ShippingId(1),
-                    base_product_id: item.base_product_id,
-                    store_id: item.store_id,
-                    company_package_id: item.company_package_id,
-                    shipping: item.shipping,
-                    price: item.price,
-                    deliveries_to: item.deliveries_to,
-                    currency: item.currency,
-                });
-            }
+        fn create_delivery_quotes_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn DeliveryQuotesRepo + 'a> {
+            Box::new(DeliveryQuotesRepoMock { user_id }) as Box<dyn DeliveryQuotesRepo>
+        }
 
-            Ok(result)
+        fn create_delivery_quotes_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<dyn DeliveryQuotesRepo + 'a> {
+            Box::new(DeliveryQuotesRepoMock {
+                user_id: Some(MOCK_USER_ID),
+            }) as Box<dyn DeliveryQuotesRepo>
         }
 
-        /// Get a products
-        fn get_by_base_product_id(&self, base_product_id: BaseProductId) -> RepoResult<Vec<Products>> {
-            Ok(vec![Products {
-                id: ShippingId(1),
-                base_product_id: base_product_id,
-                store_id: StoreId(1),
-                company_package_id: CompanyPackageId(1),
-                shipping: ShippingVariant::Local,
-                price: None,
-                deliveries_to: vec![],
-                currency: Currency::USD,
-            }])
+        fn create_event_outbox_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<dyn EventOutboxRepo + 'a> {
+            Box::new(EventOutboxRepoMock {
+                user_id: Some(MOCK_USER_ID),
+            }) as Box<dyn EventOutboxRepo>
         }
 
-        fn get_products_countries(&self, base_product_id: BaseProductId) -> RepoResult<Vec<ProductsWithAvailableCountries>> {
-            let product = Products {
+        fn create_products_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ProductsRepo + 'a> {
+            Box::new(ProductsRepoMock { user_id }) as Box<dyn ProductsRepo>
+        }
+
+        fn create_package_templates_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn PackageTemplatesRepo + 'a> {
+            Box::new(PackageTemplatesRepoMock { user_id }) as Box<dyn PackageTemplatesRepo>
+        }
+
+        fn create_packages_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn PackagesRepo + 'a> {
+            Box::new(PackagesRepoMock { user_id }) as Box<dyn PackagesRepo>
+        }
+
+        fn create_permissions_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn PermissionsRepo + 'a> {
+            Box::new(PermissionsRepoMock { user_id }) as Box<dyn PermissionsRepo>
+        }
+
+        fn create_permissions_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<dyn PermissionsRepo + 'a> {
+            Box::new(PermissionsRepoMock {
+                user_id: Some(MOCK_USER_ID),
+            }) as Box<dyn PermissionsRepo>
+        }
+
+        fn create_pickup_points_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn PickupPointsRepo + 'a> {
+            Box::new(PickupPointsRepoMock { user_id }) as Box<dyn PickupPointsRepo>
+        }
+
+        fn create_pickups_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn PickupsRepo + 'a> {
+            Box::new(PickupsRepoMock { user_id }) as Box<dyn PickupsRepo>
+        }
+
+        fn create_postal_zones_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn PostalZonesRepo + 'a> {
+            Box::new(PostalZonesRepoMock { user_id }) as Box<dyn PostalZonesRepo>
+        }
+
+        fn create_pricing_rules_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn PricingRulesRepo + 'a> {
+            Box::new(PricingRulesRepoMock { user_id }) as Box<dyn PricingRulesRepo>
+        }
+
+        fn create_pricing_rules_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<dyn PricingRulesRepo + 'a> {
+            Box::new(PricingRulesRepoMock {
+                user_id: Some(MOCK_USER_ID),
+            }) as Box<dyn PricingRulesRepo>
+        }
+
+        fn create_shipment_bookings_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ShipmentBookingsRepo + 'a> {
+            Box::new(ShipmentBookingsRepoMock { user_id }) as Box<dyn ShipmentBookingsRepo>
+        }
+
+        fn create_shipment_bookings_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<dyn ShipmentBookingsRepo + 'a> {
+            Box::new(ShipmentBookingsRepoMock {
+                user_id: Some(MOCK_USER_ID),
+            }) as Box<dyn ShipmentBookingsRepo>
+        }
+
+        fn create_shipment_notifications_repo<'a>(
+            &self,
+            _db_conn: &'a C,
+            user_id: Option<UserId>,
+        ) -> Box<dyn ShipmentNotificationsRepo + 'a> {
+            Box::new(ShipmentNotificationsRepoMock { user_id }) as Box<dyn ShipmentNotificationsRepo>
+        }
+
+        fn create_shipping_rates_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ShippingRatesRepo + 'a> {
+            Box::new(ShippingRatesRepoMock { user_id }) as Box<dyn ShippingRatesRepo>
+        }
+
+        fn create_shipping_rates_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<dyn ShippingRatesRepo + 'a> {
+            Box::new(ShippingRatesRepoMock {
+                user_id: Some(MOCK_USER_ID),
+            }) as Box<dyn ShippingRatesRepo>
+        }
+
+        fn create_shipping_rates_anomalies_repo<'a>(
+            &self,
+            _db_conn: &'a C,
+            user_id: Option<UserId>,
+        ) -> Box<dyn ShippingRatesAnomaliesRepo + 'a> {
+            Box::new(ShippingRatesAnomaliesRepoMock { user_id }) as Box<dyn ShippingRatesAnomaliesRepo>
+        }
+
+        fn create_shipping_rates_anomalies_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<dyn ShippingRatesAnomaliesRepo + 'a> {
+            Box::new(ShippingRatesAnomaliesRepoMock {
+                user_id: Some(MOCK_USER_ID),
+            }) as Box<dyn ShippingRatesAnomaliesRepo>
+        }
+
+        fn create_shipping_restrictions_repo<'a>(
+            &self,
+            _db_conn: &'a C,
+            user_id: Option<UserId>,
+        ) -> Box<dyn ShippingRestrictionsRepo + 'a> {
+            Box::new(ShippingRestrictionsRepoMock { user_id }) as Box<dyn ShippingRestrictionsRepo>
+        }
+
+        fn create_shipping_templates_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ShippingTemplatesRepo + 'a> {
+            Box::new(ShippingTemplatesRepoMock { user_id }) as Box<dyn ShippingTemplatesRepo>
+        }
+
+        fn create_store_managers_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn StoreManagersRepo + 'a> {
+            Box::new(StoreManagersRepoMock { user_id }) as Box<dyn StoreManagersRepo>
+        }
+
+        fn create_store_margins_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn StoreMarginsRepo + 'a> {
+            Box::new(StoreMarginsRepoMock { user_id }) as Box<dyn StoreMarginsRepo>
+        }
+
+        fn create_store_margins_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<dyn StoreMarginsRepo + 'a> {
+            Box::new(StoreMarginsRepoMock {
+                user_id: Some(MOCK_USER_ID),
+            }) as Box<dyn StoreMarginsRepo>
+        }
+
+        fn create_store_shipping_summaries_repo<'a>(
+            &self,
+            _db_conn: &'a C,
+            user_id: Option<UserId>,
+        ) -> Box<dyn StoreShippingSummariesRepo + 'a> {
+            Box::new(StoreShippingSummariesRepoMock { user_id }) as Box<dyn StoreShippingSummariesRepo>
+        }
+
+        fn create_surcharges_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn SurchargesRepo + 'a> {
+            Box::new(SurchargesRepoMock { user_id }) as Box<dyn SurchargesRepo>
+        }
+
+        fn create_surcharges_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<dyn SurchargesRepo + 'a> {
+            Box::new(SurchargesRepoMock {
+                user_id: Some(MOCK_USER_ID),
+            }) as Box<dyn SurchargesRepo>
+        }
+
+        #[cfg(feature = "test-endpoints")]
+        fn create_test_fixtures_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn TestFixturesRepo + 'a> {
+            Box::new(TestFixturesRepoMock { user_id }) as Box<dyn TestFixturesRepo>
+        }
+
+        fn create_users_addresses_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn UserAddressesRepo + 'a> {
+            Box::new(UserAddressesRepoMock { user_id }) as Box<dyn UserAddressesRepo>
+        }
+
+        fn create_user_roles_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn UserRolesRepo + 'a> {
+            Box::new(UserRolesRepoMock { user_id }) as Box<dyn UserRolesRepo>
+        }
+        fn create_user_roles_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<dyn UserRolesRepo + 'a> {
+            Box::new(UserRolesRepoMock {
+                user_id: Some(MOCK_USER_ID),
+            }) as Box<dyn UserRolesRepo>
+        }
+
+        fn create_zones_repo<'a>(&self, _db_conn: &'a C, user_id: Option<UserId>) -> Box<dyn ZonesRepo + 'a> {
+            Box::new(ZonesRepoMock { user_id }) as Box<dyn ZonesRepo>
+        }
+    }
+
+    /// Checks that the request is refused with `409 Conflict`
+    pub fn assert_conflict<T: fmt::Debug>(result: Result<T, FailureError>, message: &str) {
+        let err = result.expect_err(message);
+        assert_eq!(ErrorMessageWrapper::<errors::Error>::from(&err).inner.code, 409);
+    }
+
+    pub fn assert_not_found<T: fmt::Debug>(result: Result<T, FailureError>, message: &str) {
+        let err = result.expect_err(message);
+        assert_eq!(ErrorMessageWrapper::<errors::Error>::from(&err).inner.code, 404);
+    }
+
+    pub fn create_service(
+        user_id: Option<UserId>,
+        handle: Arc<Handle>,
+    ) -> Service<MockConnection, MockConnectionManager, ReposFactoryMock> {
+        let manager = MockConnectionManager::default();
+        let db_pool = r2d2::Pool::builder().build(manager).expect("Failed to create connection pool");
+        let cpu_pool = CpuPool::new(1);
+        let admin_cpu_pool = CpuPool::new(1);
+
+        let config = Config::new().unwrap();
+        let client = stq_http::client::Client::new(&config.to_http_config(), &handle);
+        let client_handle = client.handle();
+        let client_stream = client.stream();
+        handle.spawn(client_stream.for_each(|_| Ok(())));
+        let static_context = StaticContext::new(
+            db_pool,
+            cpu_pool,
+            admin_cpu_pool,
+            client_handle,
+            None,
+            None,
+            None,
+            None,
+            None,
+            ReplicaRouter::default(),
+            Arc::new(config),
+            MOCK_REPO_FACTORY,
+        );
+        let dynamic_context = DynamicContext::new(user_id, String::default(), String::default());
+
+        Service::new(static_context, dynamic_context)
+    }
+
+    #[derive(Clone, Default)]
+    pub struct UserRolesRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    impl UserRolesRepo for UserRolesRepoMock {
+        fn list_for_user(&self, user_id_value: UserId) -> RepoResult<Vec<DeliveryRole>> {
+            Ok(mock_roles(user_id_value))
+        }
+
+        fn create(&self, payload: NewUserRole) -> RepoResult<UserRole> {
+            let role = UserRole {
+                id: RoleId::new(),
+                user_id: payload.user_id,
+                name: payload.name,
+                data: None,
+            };
+            check_acl(self.user_id, Resource::UserRoles, Action::Create, self, Some(&role))?;
+            Ok(role)
+        }
+
+        fn delete_by_user_id(&self, user_id_arg: UserId) -> RepoResult<Vec<UserRole>> {
+            let role = UserRole {
+                id: RoleId::new(),
+                user_id: user_id_arg,
+                name: DeliveryRole::User,
+                data: None,
+            };
+            check_acl(self.user_id, Resource::UserRoles, Action::Delete, self, Some(&role))?;
+            Ok(vec![role])
+        }
+
+        fn delete_by_id(&self, id: RoleId) -> RepoResult<UserRole> {
+            let role = UserRole {
+                id,
+                user_id: UserId(1),
+                name: DeliveryRole::User,
+                data: None,
+            };
+            check_acl(self.user_id, Resource::UserRoles, Action::Delete, self, Some(&role))?;
+            Ok(role)
+        }
+    }
+
+    impl CheckScope<Scope, UserRole> for UserRolesRepoMock {
+        fn is_in_scope(&self, user_id: UserId, scope: &Scope, obj: Option<&UserRole>) -> bool {
+            match *scope {
+                Scope::All => true,
+                Scope::Owned => obj.map(|role| role.user_id == user_id).unwrap_or_default(),
+            }
+        }
+    }
+
+    fn mock_available_package() -> AvailablePackageForUser {
+        AvailablePackageForUser {
+            id: CompanyPackageId(1),
+            shipping_id: ShippingId(1),
+            shipping_variant: ShippingVariant::Local,
+            name: "UPS-avia".to_string(),
+            logo: "logo".to_string(),
+            price: None,
+            cost: None,
+            option_id: None,
+            currency: Currency::STQ,
+            store_id: MOCK_STORE_ID,
+            base_product_id: MOCK_BASE_PRODUCT_ID,
+            delivery_time_min_days: Some(3),
+            delivery_time_max_days: Some(7),
+            free_delivery_over: None,
+            free_shipping: false,
+            delivery_from: None,
+            shipping_date: None,
+            estimated_delivery_date_min: None,
+            estimated_delivery_date_max: None,
+        }
+    }
+
+    fn mock_products(base_product_id: BaseProductId) -> Products {
+        Products {
+            id: ShippingId(1),
+            base_product_id,
+            store_id: StoreId(1),
+            company_package_id: CompanyPackageId(1),
+            shipping: ShippingVariant::Local,
+            price: None,
+            deliveries_to: vec![],
+            currency: Currency::USD,
+            hazard_classes: vec![],
+            free_delivery_over: None,
+            delivery_from: None,
+        }
+    }
+
+    #[derive(Clone, Default)]
+    pub struct ProductsRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    impl ProductsRepo for ProductsRepoMock {
+        /// Create a new products
+        fn create(&self, payload: NewProducts) -> RepoResult<Products> {
+            let product = Products {
+                id: ShippingId(1),
+                base_product_id: payload.base_product_id,
+                store_id: payload.store_id,
+                company_package_id: payload.company_package_id,
+                shipping: payload.shipping,
+                price: payload.price,
+                deliveries_to: payload.deliveries_to,
+                currency: payload.currency,
+                hazard_classes: payload.hazard_classes,
+                free_delivery_over: payload.free_delivery_over,
+                delivery_from: payload.delivery_from,
+            };
+            check_acl(self.user_id, Resource::Products, Action::Create, self, Some(&product))?;
+            Ok(product)
+        }
+
+        /// Create many a new products
+        fn create_many(&self, payloads: Vec<NewProducts>) -> RepoResult<Vec<Products>> {
+            let mut result = vec![];
+            for item in payloads {
+                let product = Products {
+                    id: ShippingId(1),
+                    base_product_id: item.base_product_id,
+                    store_id: item.store_id,
+                    company_package_id: item.company_package_id,
+                    shipping: item.shipping,
+                    price: item.price,
+                    deliveries_to: item.deliveries_to,
+                    currency: item.currency,
+                    hazard_classes: item.hazard_classes,
+                    free_delivery_over: item.free_delivery_over,
+                    delivery_from: item.delivery_from,
+                };
+                check_acl(self.user_id, Resource::Products, Action::Create, self, Some(&product))?;
+                result.push(product);
+            }
+
+            Ok(result)
+        }
+
+        /// Get a products
+        fn get_by_base_product_id(&self, base_product_id: BaseProductId) -> RepoResult<Vec<Products>> {
+            count_query();
+            Ok(vec![mock_products(base_product_id)])
+        }
+
+        fn get_by_base_product_ids(&self, base_product_ids: Vec<BaseProductId>) -> RepoResult<Vec<Products>> {
+            count_query();
+            Ok(base_product_ids.into_iter().map(mock_products).collect())
+        }
+
+        fn get_by_store_id(&self, store_id: StoreId) -> RepoResult<Vec<Products>> {
+            if store_id != MOCK_STORE_ID {
+                return Ok(vec![]);
+            }
+
+            let product = Products {
+                id: ShippingId(1),
+                base_product_id: BaseProductId(1),
+                store_id,
+                company_package_id: CompanyPackageId(1),
+                shipping: ShippingVariant::Local,
+                price: None,
+                deliveries_to: vec![],
+                currency: Currency::USD,
+                hazard_classes: vec![],
+                free_delivery_over: None,
+                delivery_from: None,
+            };
+            check_acl(self.user_id, Resource::Products, Action::Read, self, Some(&product))?;
+            Ok(vec![product])
+        }
+
+        fn get_company_package_ids_by_store_id(&self, store_id: StoreId) -> RepoResult<Vec<CompanyPackageId>> {
+            if store_id == MOCK_STORE_ID {
+                Ok(vec![CompanyPackageId(1)])
+            } else {
+                Ok(vec![])
+            }
+        }
+
+        /// Company package 1 ships the mock base product, the others are not used
+        fn get_by_company_package_id(
+            &self,
+            company_package_id: CompanyPackageId,
+            offset: i64,
+            limit: i64,
+        ) -> RepoResult<CompanyPackageProducts> {
+            check_acl(self.user_id, Resource::AdminOverview, Action::Read, self, None)?;
+            let all = if company_package_id == CompanyPackageId(1) {
+                vec![CompanyPackageProduct {
+                    base_product_id: MOCK_BASE_PRODUCT_ID,
+                    store_id: MOCK_STORE_ID,
+                }]
+            } else {
+                vec![]
+            };
+
+            Ok(CompanyPackageProducts {
+                total_count: all.len() as i64,
+                products: all.into_iter().skip(offset as usize).take(limit as usize).collect(),
+            })
+        }
+
+        fn get_products_countries(&self, base_product_id: BaseProductId) -> RepoResult<Vec<ProductsWithAvailableCountries>> {
+            let product = Products {
                 id: ShippingId(1),
                 base_product_id,
                 store_id: StoreId(1),
@@ -354,6 +1158,9 @@ pub mod tests {
                 price: None,
                 deliveries_to: vec![],
                 currency: Currency::USD,
+                hazard_classes: vec![],
+                free_delivery_over: None,
+                delivery_from: None,
             };
 
             Ok(vec![ProductsWithAvailableCountries(product, vec![])])
@@ -361,25 +1168,59 @@ pub mod tests {
 
         /// find available product delivery to users country
         fn find_available_to(&self, _base_product_id: BaseProductId, _user_country: Alpha3) -> RepoResult<Vec<AvailablePackageForUser>> {
-            Ok(vec![AvailablePackageForUser {
-                id: CompanyPackageId(1),
-                shipping_id: ShippingId(1),
-                shipping_variant: ShippingVariant::Local,
-                name: "UPS-avia".to_string(),
-                logo: "logo".to_string(),
-                price: None,
-                currency: Currency::STQ,
-                store_id: MOCK_STORE_ID,
-                base_product_id: MOCK_BASE_PRODUCT_ID,
-            }])
+            count_query();
+            Ok(vec![mock_available_package()])
         }
 
-        fn get_available_package_for_user(
+        fn find_available_to_countries(
             &self,
-            _base_product_id_arg: BaseProductId,
-            _package_id_arg: CompanyPackageId,
-        ) -> RepoResult<Option<AvailablePackageForUser>> {
-            Ok(None)
+            _base_product_id: BaseProductId,
+            countries: Vec<Alpha3>,
+        ) -> RepoResult<Vec<(Alpha3, Vec<AvailablePackageForUser>)>> {
+            count_query();
+            Ok(countries
+                .into_iter()
+                .map(|country| (country, vec![mock_available_package()]))
+                .collect())
+        }
+
+        /// Company package 1 ships the base product once, company package 2 twice - locally and internationally
+        fn find_available_packages_for_user(
+            &self,
+            base_product_id_arg: BaseProductId,
+            package_id_arg: CompanyPackageId,
+        ) -> RepoResult<Vec<AvailablePackageForUser>> {
+            let shipping_variants = match package_id_arg {
+                CompanyPackageId(1) => vec![ShippingVariant::Local],
+                CompanyPackageId(2) => vec![ShippingVariant::Local, ShippingVariant::International],
+                _ => vec![],
+            };
+
+            Ok(shipping_variants
+                .into_iter()
+                .enumerate()
+                .map(|(index, shipping_variant)| AvailablePackageForUser {
+                    id: package_id_arg,
+                    shipping_id: ShippingId(index as i32 + 1),
+                    shipping_variant,
+                    name: "UPS-avia".to_string(),
+                    logo: "logo".to_string(),
+                    price: None,
+                    cost: None,
+                    option_id: None,
+                    currency: Currency::STQ,
+                    store_id: MOCK_STORE_ID,
+                    base_product_id: base_product_id_arg,
+                    delivery_time_min_days: Some(3),
+                    delivery_time_max_days: Some(7),
+                    free_delivery_over: None,
+                    free_shipping: false,
+                    delivery_from: None,
+                    shipping_date: None,
+                    estimated_delivery_date_min: None,
+                    estimated_delivery_date_max: None,
+                })
+                .collect())
         }
 
         fn get_available_package_for_user_by_shipping_id(
@@ -397,35 +1238,66 @@ pub mod tests {
             company_package_id: CompanyPackageId,
             payload: UpdateProducts,
         ) -> RepoResult<Products> {
-            Ok(Products {
+            let current = Products {
                 id: ShippingId(1),
                 base_product_id: base_product_id_arg,
-                store_id: StoreId(1),
+                store_id: MOCK_STORE_ID,
                 company_package_id,
+                shipping: ShippingVariant::Local,
+                price: None,
+                deliveries_to: vec![],
+                currency: Currency::USD,
+                hazard_classes: vec![],
+                free_delivery_over: None,
+                delivery_from: None,
+            };
+            check_acl(self.user_id, Resource::Products, Action::Update, self, Some(&current))?;
+
+            Ok(Products {
                 shipping: payload.shipping.unwrap(),
                 price: payload.price,
                 deliveries_to: payload.deliveries_to.unwrap_or_default(),
                 currency: payload.currency.unwrap_or(Currency::USD),
+                free_delivery_over: payload.free_delivery_over,
+                ..current
             })
         }
 
         /// Delete a products
         fn delete(&self, base_product_id_arg: BaseProductId) -> RepoResult<Vec<Products>> {
-            Ok(vec![Products {
+            let product = Products {
                 id: ShippingId(1),
                 base_product_id: base_product_id_arg,
-                store_id: StoreId(1),
+                store_id: MOCK_STORE_ID,
                 company_package_id: CompanyPackageId(1),
                 shipping: ShippingVariant::Local,
                 price: None,
                 deliveries_to: vec![],
                 currency: Currency::USD,
-            }])
+                hazard_classes: vec![],
+                free_delivery_over: None,
+                delivery_from: None,
+            };
+            check_acl(self.user_id, Resource::Products, Action::Delete, self, Some(&product))?;
+            Ok(vec![product])
+        }
+    }
+
+    impl CheckScope<Scope, Products> for ProductsRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, obj: Option<&Products>) -> bool {
+            match *scope {
+                Scope::All => true,
+                Scope::Owned => obj
+                    .map(|product| manages_product(&mock_roles_data(self.user_id), product.store_id, &[]))
+                    .unwrap_or_default(),
+            }
         }
     }
 
     #[derive(Clone, Default)]
-    pub struct CountriesRepoMock;
+    pub struct CountriesRepoMock {
+        pub user_id: Option<UserId>,
+    }
 
     impl CountriesRepo for CountriesRepoMock {
         /// Find specific country by label
@@ -489,7 +1361,7 @@ pub mod tests {
 
         /// Creates new country
         fn create(&self, payload: NewCountry) -> RepoResult<Country> {
-            Ok(Country {
+            let country = Country {
                 label: payload.label,
                 children: vec![],
                 level: payload.level,
@@ -498,11 +1370,14 @@ pub mod tests {
                 alpha3: Alpha3("RUS".to_string()),
                 numeric: 0,
                 is_selected: false,
-            })
+            };
+            check_acl(self.user_id, Resource::Countries, Action::Create, self, Some(&country))?;
+            Ok(country)
         }
 
         /// Returns all countries as a tree
         fn get_all(&self) -> RepoResult<Country> {
+            count_query();
             Ok(create_mock_countries())
         }
 
@@ -510,6 +1385,27 @@ pub mod tests {
         fn get_all_flatten(&self) -> RepoResult<Vec<Country>> {
             Ok(create_mock_countries_flatten())
         }
+
+        fn import(&self, payload: Vec<IsoCountry>) -> RepoResult<CountriesImportReport> {
+            check_acl(self.user_id, Resource::Countries, Action::Create, self, None)?;
+            check_acl(self.user_id, Resource::Countries, Action::Update, self, None)?;
+            Ok(CountriesImportReport {
+                created: payload.len() as u32,
+                updated: 0,
+                skipped: 0,
+            })
+        }
+
+        fn set_parent(&self, _countries: Vec<Alpha3>, _parent: Alpha3) -> RepoResult<()> {
+            check_acl(self.user_id, Resource::Countries, Action::Update, self, None)?;
+            Ok(())
+        }
+    }
+
+    impl CheckScope<Scope, Country> for CountriesRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&Country>) -> bool {
+            *scope == Scope::All
+        }
     }
 
     fn create_mock_countries() -> Country {
@@ -546,24 +1442,48 @@ pub mod tests {
     }
 
     fn create_mock_countries_flatten() -> Vec<Country> {
-        vec![Country {
-            label: "RUS".to_string().into(),
-            children: vec![],
-            level: 2,
-            parent: Some("XEU".to_string().into()),
-            alpha2: Alpha2("RU".to_string()),
-            alpha3: Alpha3("RUS".to_string()),
-            numeric: 0,
-            is_selected: false,
-        }]
-    }
-
-    #[derive(Clone, Default)]
-    pub struct CompaniesRepoMock;
+        vec![
+            Country {
+                label: "All".to_string().into(),
+                children: vec![],
+                level: 0,
+                parent: None,
+                alpha2: Alpha2("".to_string()),
+                alpha3: Alpha3("XAL".to_string()),
+                numeric: 0,
+                is_selected: false,
+            },
+            Country {
+                label: "Europe".to_string().into(),
+                children: vec![],
+                level: 1,
+                parent: Some("XAL".to_string().into()),
+                alpha2: Alpha2("".to_string()),
+                alpha3: Alpha3("XEU".to_string()),
+                numeric: 0,
+                is_selected: false,
+            },
+            Country {
+                label: "RUS".to_string().into(),
+                children: vec![],
+                level: 2,
+                parent: Some("XEU".to_string().into()),
+                alpha2: Alpha2("RU".to_string()),
+                alpha3: Alpha3("RUS".to_string()),
+                numeric: 0,
+                is_selected: false,
+            },
+        ]
+    }
+
+    #[derive(Clone, Default)]
+    pub struct CompaniesRepoMock {
+        pub user_id: Option<UserId>,
+    }
 
     impl CompaniesRepo for CompaniesRepoMock {
         fn create(&self, payload: NewCompany) -> RepoResult<Company> {
-            let payload = payload.to_raw()?;
+            let payload = payload.to_raw();
 
             let raw = CompanyRaw {
                 id: CompanyId(1),
@@ -573,14 +1493,18 @@ pub mod tests {
                 deliveries_from: payload.deliveries_from,
                 logo: payload.logo,
                 currency: payload.currency,
+                capabilities: payload.capabilities,
+                label_translations: payload.label_translations,
             };
 
             let countries_arg = create_mock_countries();
 
-            Ok(Company::from_raw(raw, &countries_arg)?)
+            let company = Company::from_raw(raw, &countries_arg);
+            check_acl(self.user_id, Resource::Companies, Action::Create, self, Some(&company))?;
+            Ok(company)
         }
 
-        fn list(&self) -> RepoResult<Vec<Company>> {
+        fn list(&self, _search: CompaniesSearch) -> RepoResult<Vec<Company>> {
             Ok(vec![
                 Company {
                     id: CompanyId(1),
@@ -590,6 +1514,8 @@ pub mod tests {
                     deliveries_from: vec![],
                     logo: "".to_string(),
                     currency: Currency::STQ,
+                    capabilities: vec![],
+                    label_translations: vec![],
                 },
                 Company {
                     id: CompanyId(2),
@@ -599,11 +1525,14 @@ pub mod tests {
                     deliveries_from: vec![],
                     logo: "".to_string(),
                     currency: Currency::USD,
+                    capabilities: vec![],
+                    label_translations: vec![],
                 },
             ])
         }
 
         fn find(&self, _company_id: CompanyId) -> RepoResult<Option<Company>> {
+            count_query();
             Ok(None)
         }
 
@@ -617,6 +1546,8 @@ pub mod tests {
                     deliveries_from: vec![],
                     logo: "".to_string(),
                     currency: Currency::STQ,
+                    capabilities: vec![],
+                    label_translations: vec![],
                 },
                 Company {
                     id: CompanyId(2),
@@ -626,11 +1557,14 @@ pub mod tests {
                     deliveries_from: vec![],
                     logo: "".to_string(),
                     currency: Currency::USD,
+                    capabilities: vec![],
+                    label_translations: vec![],
                 },
             ])
         }
 
         fn update(&self, id_arg: CompanyId, payload: UpdateCompany) -> RepoResult<Company> {
+            check_acl(self.user_id, Resource::Companies, Action::Update, self, None)?;
             Ok(Company {
                 id: id_arg,
                 name: payload.name.unwrap(),
@@ -639,10 +1573,27 @@ pub mod tests {
                 deliveries_from: vec![],
                 logo: payload.logo.unwrap(),
                 currency: payload.currency.unwrap(),
+                capabilities: vec![],
+                label_translations: payload.label_translations.unwrap_or_default(),
             })
         }
 
+        /// Company 1 ships the mock base product, the others have nothing depending on them
+        fn deletion_impact(&self, id_arg: CompanyId) -> RepoResult<CompanyDeletionImpact> {
+            check_acl(self.user_id, Resource::Companies, Action::Delete, self, None)?;
+            if id_arg == CompanyId(1) {
+                Ok(CompanyDeletionImpact {
+                    company_packages: 1,
+                    products: 1,
+                    shipping_rates: 1,
+                })
+            } else {
+                Ok(CompanyDeletionImpact::default())
+            }
+        }
+
         fn delete(&self, id_arg: CompanyId) -> RepoResult<Company> {
+            check_acl(self.user_id, Resource::Companies, Action::Delete, self, None)?;
             Ok(Company {
                 id: id_arg,
                 name: "UPS USA".to_string(),
@@ -651,22 +1602,39 @@ pub mod tests {
                 deliveries_from: vec![],
                 logo: "".to_string(),
                 currency: Currency::STQ,
+                capabilities: vec![],
+                label_translations: vec![],
             })
         }
     }
 
+    impl CheckScope<Scope, Company> for CompaniesRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, obj: Option<&Company>) -> bool {
+            match *scope {
+                Scope::All => true,
+                Scope::Owned => obj
+                    .map(|company| manages_company(&mock_roles_data(self.user_id), &company.deliveries_from))
+                    .unwrap_or_default(),
+            }
+        }
+    }
+
     #[derive(Clone, Default)]
-    pub struct PickupsRepoMock;
+    pub struct PickupsRepoMock {
+        pub user_id: Option<UserId>,
+    }
 
     impl PickupsRepo for PickupsRepoMock {
         fn create(&self, payload: NewPickups) -> RepoResult<Pickups> {
-            Ok(Pickups {
+            let pickups = Pickups {
                 id: 1,
                 base_product_id: payload.base_product_id,
                 store_id: payload.store_id,
                 pickup: payload.pickup,
                 price: payload.price,
-            })
+            };
+            check_acl(self.user_id, Resource::Pickups, Action::Create, self, Some(&pickups))?;
+            Ok(pickups)
         }
 
         fn list(&self) -> RepoResult<Vec<Pickups>> {
@@ -680,6 +1648,7 @@ pub mod tests {
         }
 
         fn get(&self, base_product_id_arg: BaseProductId) -> RepoResult<Option<Pickups>> {
+            count_query();
             Ok(Some(Pickups {
                 id: 1,
                 base_product_id: base_product_id_arg,
@@ -689,33 +1658,333 @@ pub mod tests {
             }))
         }
 
+        fn get_by_store_id(&self, store_id_arg: StoreId) -> RepoResult<Vec<Pickups>> {
+            if store_id_arg != MOCK_STORE_ID {
+                return Ok(vec![]);
+            }
+
+            let pickups = Pickups {
+                id: 1,
+                base_product_id: BaseProductId(1),
+                store_id: store_id_arg,
+                pickup: false,
+                price: Some(ProductPrice(1.0)),
+            };
+            check_acl(self.user_id, Resource::Pickups, Action::Read, self, Some(&pickups))?;
+            Ok(vec![pickups])
+        }
+
         fn update(&self, base_product_id_arg: BaseProductId, payload: UpdatePickups) -> RepoResult<Pickups> {
-            Ok(Pickups {
+            let pickups = Pickups {
                 id: 1,
                 base_product_id: base_product_id_arg,
-                store_id: StoreId(1),
+                store_id: MOCK_STORE_ID,
                 pickup: payload.pickup.unwrap(),
                 price: payload.price,
-            })
+            };
+            check_acl(self.user_id, Resource::Pickups, Action::Update, self, Some(&pickups))?;
+            Ok(pickups)
         }
 
         fn delete(&self, base_product_id_arg: BaseProductId) -> RepoResult<Option<Pickups>> {
-            Ok(Some(Pickups {
+            let pickups = Pickups {
                 id: 1,
                 base_product_id: base_product_id_arg,
-                store_id: StoreId(1),
+                store_id: MOCK_STORE_ID,
                 pickup: false,
                 price: Some(ProductPrice(1.0)),
+            };
+            check_acl(self.user_id, Resource::Pickups, Action::Delete, self, Some(&pickups))?;
+            Ok(Some(pickups))
+        }
+    }
+
+    impl CheckScope<Scope, Pickups> for PickupsRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, obj: Option<&Pickups>) -> bool {
+            match *scope {
+                Scope::All => true,
+                Scope::Owned => obj
+                    .map(|pickups| {
+                        mock_roles_data(self.user_id)
+                            .iter()
+                            .any(|role_data| role_data.manages_store_shipping(pickups.store_id))
+                    })
+                    .unwrap_or_default(),
+            }
+        }
+    }
+
+    #[derive(Clone, Default)]
+    pub struct PostalZonesRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    fn create_mock_postal_zone(id: i32) -> PostalZone {
+        PostalZone {
+            id,
+            alpha3: Alpha3("AUS".to_string()),
+            name: "Remote".to_string(),
+            ranges: TypedJson(vec![PostalCodeRange {
+                from: "0872".to_string(),
+                to: "0872".to_string(),
+            }]),
+        }
+    }
+
+    impl PostalZonesRepo for PostalZonesRepoMock {
+        fn list(&self, alpha3: Option<Alpha3>) -> RepoResult<Vec<PostalZone>> {
+            check_acl(self.user_id, Resource::Zones, Action::Read, self, None)?;
+            Ok(vec![create_mock_postal_zone(1)]
+                .into_iter()
+                .filter(|postal_zone| alpha3.as_ref().map(|alpha3| postal_zone.alpha3 == *alpha3).unwrap_or(true))
+                .collect())
+        }
+
+        fn find(&self, postal_zone_id: i32) -> RepoResult<Option<PostalZone>> {
+            check_acl(self.user_id, Resource::Zones, Action::Read, self, None)?;
+            Ok(Some(create_mock_postal_zone(postal_zone_id)))
+        }
+
+        fn find_by_postal_code(&self, alpha3: Alpha3, postal_code: String) -> RepoResult<Vec<PostalZone>> {
+            Ok(self
+                .list(Some(alpha3))?
+                .into_iter()
+                .filter(|postal_zone| postal_zone.contains(&postal_code))
+                .collect())
+        }
+
+        fn create(&self, payload: NewPostalZone) -> RepoResult<PostalZone> {
+            check_acl(self.user_id, Resource::Zones, Action::Create, self, None)?;
+            Ok(PostalZone {
+                id: 1,
+                alpha3: payload.alpha3,
+                name: payload.name,
+                ranges: payload.ranges,
+            })
+        }
+
+        fn upsert(&self, payload: NewPostalZone) -> RepoResult<PostalZone> {
+            check_acl(self.user_id, Resource::Zones, Action::Update, self, None)?;
+            Ok(PostalZone {
+                id: 1,
+                alpha3: payload.alpha3,
+                name: payload.name,
+                ranges: payload.ranges,
+            })
+        }
+
+        fn delete(&self, postal_zone_id: i32) -> RepoResult<PostalZone> {
+            check_acl(self.user_id, Resource::Zones, Action::Delete, self, None)?;
+            Ok(create_mock_postal_zone(postal_zone_id))
+        }
+    }
+
+    impl CheckScope<Scope, PostalZone> for PostalZonesRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&PostalZone>) -> bool {
+            *scope == Scope::All
+        }
+    }
+
+    #[derive(Clone, Default)]
+    pub struct CustomsInfoRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    fn create_mock_customs_info(base_product_id: BaseProductId, store_id: StoreId) -> CustomsInfo {
+        CustomsInfo {
+            id: 1,
+            base_product_id,
+            store_id,
+            hs_code: "610910".to_string(),
+            description: "Cotton t-shirts".to_string(),
+            declared_value: 15.0,
+            currency: Currency::USD,
+            origin_country: Alpha3("CHN".to_string()),
+        }
+    }
+
+    impl CustomsInfoRepo for CustomsInfoRepoMock {
+        fn get(&self, base_product_id: BaseProductId) -> RepoResult<Option<CustomsInfo>> {
+            let customs_info = create_mock_customs_info(base_product_id, MOCK_STORE_ID);
+            check_acl(
+                self.user_id,
+                Resource::CustomsInfo,
+                Action::Read,
+                self,
+                Some(&customs_info.store_id),
+            )?;
+            Ok(Some(customs_info))
+        }
+
+        fn set(&self, base_product_id: BaseProductId, payload: SetCustomsInfo) -> RepoResult<CustomsInfo> {
+            check_acl(self.user_id, Resource::CustomsInfo, Action::Update, self, Some(&payload.store_id))?;
+            let NewCustomsInfo {
+                base_product_id,
+                store_id,
+                hs_code,
+                description,
+                declared_value,
+                currency,
+                origin_country,
+            } = payload.to_new(base_product_id);
+            Ok(CustomsInfo {
+                id: 1,
+                base_product_id,
+                store_id,
+                hs_code,
+                description,
+                declared_value,
+                currency,
+                origin_country,
+            })
+        }
+
+        fn delete(&self, base_product_id: BaseProductId) -> RepoResult<Option<CustomsInfo>> {
+            let customs_info = create_mock_customs_info(base_product_id, MOCK_STORE_ID);
+            check_acl(
+                self.user_id,
+                Resource::CustomsInfo,
+                Action::Delete,
+                self,
+                Some(&customs_info.store_id),
+            )?;
+            Ok(Some(customs_info))
+        }
+    }
+
+    impl CheckScope<Scope, StoreId> for CustomsInfoRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, obj: Option<&StoreId>) -> bool {
+            match *scope {
+                Scope::All => true,
+                Scope::Owned => obj
+                    .map(|store_id| {
+                        mock_roles_data(self.user_id)
+                            .iter()
+                            .any(|role_data| role_data.manages_store_shipping(*store_id))
+                    })
+                    .unwrap_or_default(),
+            }
+        }
+    }
+
+    /// Quote with nil id has expired, the others are valid for an hour
+    #[derive(Clone, Default)]
+    pub struct DeliveryQuotesRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    impl DeliveryQuotesRepo for DeliveryQuotesRepoMock {
+        fn find(&self, quote_id: Uuid) -> RepoResult<Option<DeliveryQuote>> {
+            check_acl(self.user_id, Resource::DeliveryQuotes, Action::Read, self, None)?;
+            let expires_at = if quote_id.is_nil() {
+                UNIX_EPOCH
+            } else {
+                SystemTime::now() + Duration::from_secs(3600)
+            };
+            Ok(Some(DeliveryQuote {
+                id: quote_id,
+                company_package_id: CompanyPackageId(1),
+                currency: Currency::STQ,
+                price: 100.0,
+                inputs: json!({}),
+                created_at: SystemTime::now(),
+                expires_at,
             }))
         }
+
+        fn create(&self, payload: NewDeliveryQuote) -> RepoResult<DeliveryQuote> {
+            check_acl(self.user_id, Resource::DeliveryQuotes, Action::Create, self, None)?;
+            Ok(DeliveryQuote {
+                id: payload.id,
+                company_package_id: payload.company_package_id,
+                currency: payload.currency,
+                price: payload.price,
+                inputs: payload.inputs,
+                created_at: SystemTime::now(),
+                expires_at: payload.expires_at,
+            })
+        }
+
+        fn delete_expired(&self, _expired_before: SystemTime) -> RepoResult<usize> {
+            check_acl(self.user_id, Resource::DeliveryQuotes, Action::Delete, self, None)?;
+            Ok(2)
+        }
+    }
+
+    impl CheckScope<Scope, DeliveryQuote> for DeliveryQuotesRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&DeliveryQuote>) -> bool {
+            *scope == Scope::All
+        }
+    }
+
+    fn create_mock_outbox_event(id: i32, event: DeliveryEvent) -> OutboxEvent {
+        OutboxEvent {
+            id,
+            event: TypedJson(event),
+            attempts: 0,
+            published_at: None,
+            created_at: SystemTime::now(),
+        }
+    }
+
+    #[derive(Clone, Default)]
+    pub struct EventOutboxRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    impl EventOutboxRepo for EventOutboxRepoMock {
+        fn add(&self, events: Vec<DeliveryEvent>) -> RepoResult<Vec<OutboxEvent>> {
+            check_acl(self.user_id, Resource::EventOutbox, Action::Create, self, None)?;
+            Ok(events
+                .into_iter()
+                .enumerate()
+                .map(|(i, event)| create_mock_outbox_event(i as i32 + 1, event))
+                .collect())
+        }
+
+        fn list_unpublished(&self, _created_before: SystemTime, _limit: i64) -> RepoResult<Vec<OutboxEvent>> {
+            check_acl(self.user_id, Resource::EventOutbox, Action::Read, self, None)?;
+            Ok(vec![create_mock_outbox_event(
+                1,
+                DeliveryEvent::CompanyCreated {
+                    company_id: CompanyId(1),
+                    name: "UPS Russia".to_string(),
+                    label: "UPS".to_string(),
+                },
+            )])
+        }
+
+        fn mark_published(&self, id: i32) -> RepoResult<OutboxEvent> {
+            check_acl(self.user_id, Resource::EventOutbox, Action::Update, self, None)?;
+            let published = self.list_unpublished(SystemTime::now(), 1)?.remove(0);
+            Ok(OutboxEvent {
+                id,
+                published_at: Some(SystemTime::now()),
+                ..published
+            })
+        }
+
+        fn record_failure(&self, id: i32) -> RepoResult<OutboxEvent> {
+            check_acl(self.user_id, Resource::EventOutbox, Action::Update, self, None)?;
+            let failed = self.list_unpublished(SystemTime::now(), 1)?.remove(0);
+            Ok(OutboxEvent { id, attempts: 1, ..failed })
+        }
+    }
+
+    impl CheckScope<Scope, OutboxEvent> for EventOutboxRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&OutboxEvent>) -> bool {
+            *scope == Scope::All
+        }
     }
 
     #[derive(Clone, Default)]
-    pub struct PackagesRepoMock;
+    pub struct PackagesRepoMock {
+        pub user_id: Option<UserId>,
+    }
 
     impl PackagesRepo for PackagesRepoMock {
         fn create(&self, payload: NewPackages) -> RepoResult<Packages> {
-            let payload = payload.to_raw()?;
+            let payload = payload.to_raw();
 
             let raw = PackagesRaw {
                 id: PackageId(1),
@@ -725,11 +1994,15 @@ pub mod tests {
                 max_weight: payload.max_weight,
                 min_weight: payload.min_weight,
                 deliveries_to: payload.deliveries_to,
+                deliveries_to_zones: payload.deliveries_to_zones,
+                name_translations: payload.name_translations,
             };
 
             let countries_arg = create_mock_countries();
 
-            Ok(raw.to_packages(&countries_arg)?)
+            let package = raw.to_packages(&countries_arg, &[]);
+            check_acl(self.user_id, Resource::Packages, Action::Create, self, Some(&package))?;
+            Ok(package)
         }
 
         fn find_deliveries_to(&self, _countries: Vec<Alpha3>) -> RepoResult<Vec<Packages>> {
@@ -741,9 +2014,29 @@ pub mod tests {
                 max_weight: 0,
                 min_weight: 0,
                 deliveries_to: vec![],
+                deliveries_to_zones: vec![],
+                name_translations: vec![],
             }])
         }
 
+        fn search(&self, _countries: Vec<Alpha3>, max_weight: Option<u32>, offset: i64, limit: i64) -> RepoResult<PackagesSearchResult> {
+            let all = vec![Packages {
+                id: PackageId(1),
+                name: "package1".to_string(),
+                max_size: 0,
+                min_size: 0,
+                max_weight: max_weight.unwrap_or_default(),
+                min_weight: 0,
+                deliveries_to: vec![],
+                deliveries_to_zones: vec![],
+                name_translations: vec![],
+            }];
+            Ok(PackagesSearchResult {
+                total_count: all.len() as i64,
+                packages: all.into_iter().skip(offset as usize).take(limit as usize).collect(),
+            })
+        }
+
         fn list(&self) -> RepoResult<Vec<Packages>> {
             Ok(vec![Packages {
                 id: PackageId(1),
@@ -753,6 +2046,8 @@ pub mod tests {
                 max_weight: 0,
                 min_weight: 0,
                 deliveries_to: vec![],
+                deliveries_to_zones: vec![],
+                name_translations: vec![],
             }])
         }
 
@@ -765,10 +2060,13 @@ pub mod tests {
                 max_weight: 0,
                 min_weight: 0,
                 deliveries_to: vec![],
+                deliveries_to_zones: vec![],
+                name_translations: vec![],
             }))
         }
 
         fn update(&self, id_arg: PackageId, payload: UpdatePackages) -> RepoResult<Packages> {
+            check_acl(self.user_id, Resource::Packages, Action::Update, self, None)?;
             Ok(Packages {
                 id: id_arg,
                 name: payload.name.unwrap(),
@@ -777,10 +2075,13 @@ pub mod tests {
                 max_weight: payload.max_weight.unwrap(),
                 min_weight: payload.min_weight.unwrap(),
                 deliveries_to: vec![],
+                deliveries_to_zones: payload.deliveries_to_zones.unwrap_or_default(),
+                name_translations: payload.name_translations.unwrap_or_default(),
             })
         }
 
         fn delete(&self, id_arg: PackageId) -> RepoResult<Packages> {
+            check_acl(self.user_id, Resource::Packages, Action::Delete, self, None)?;
             Ok(Packages {
                 id: id_arg,
                 name: "package1".to_string(),
@@ -789,12 +2090,22 @@ pub mod tests {
                 max_weight: 0,
                 min_weight: 0,
                 deliveries_to: vec![],
+                deliveries_to_zones: vec![],
+                name_translations: vec![],
             })
         }
     }
 
+    impl CheckScope<Scope, Packages> for PackagesRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&Packages>) -> bool {
+            *scope == Scope::All
+        }
+    }
+
     #[derive(Clone, Default)]
-    pub struct CompaniesPackagesRepoMock;
+    pub struct CompaniesPackagesRepoMock {
+        pub user_id: Option<UserId>,
+    }
 
     impl CompaniesPackagesRepo for CompaniesPackagesRepoMock {
         /// Create a new companies_packages
@@ -803,15 +2114,45 @@ pub mod tests {
                 company_id,
                 package_id,
                 shipping_rate_source,
+                restricted_hazard_classes,
+                delivery_time_min_days,
+                delivery_time_max_days,
+                rate_resolution_order,
+                insurance_options,
+                cod_supported,
+                cod_fee,
+                carrier_fallback,
+                fallback_markup_percent,
+                daily_cutoff_time,
+                max_daily_shipments,
             } = payload;
 
             let shipping_rate_source = shipping_rate_source.unwrap_or_default();
-            Ok(CompanyPackage {
+            let company_package = CompanyPackage {
                 id: CompanyPackageId(1),
                 company_id,
                 package_id,
                 shipping_rate_source,
-            })
+                restricted_hazard_classes,
+                delivery_time_min_days,
+                delivery_time_max_days,
+                rate_resolution_order,
+                insurance_options,
+                cod_supported,
+                cod_fee,
+                carrier_fallback,
+                fallback_markup_percent,
+                daily_cutoff_time,
+                max_daily_shipments,
+            };
+            check_acl(
+                self.user_id,
+                Resource::CompaniesPackages,
+                Action::Create,
+                self,
+                Some(&company_package),
+            )?;
+            Ok(company_package)
         }
 
         /// Getting available packages satisfying the constraints
@@ -838,15 +2179,107 @@ pub mod tests {
                 .collect())
         }
 
+        fn get_candidate_packages(&self, _deliveries_from: Alpha3) -> RepoResult<Vec<CandidatePackage>> {
+            Ok(vec![CandidatePackage {
+                company_id: CompanyId(1),
+                delivers_from: true,
+                package: Packages {
+                    id: PackageId(1),
+                    name: "package1".to_string(),
+                    max_size: 1000,
+                    min_size: 0,
+                    max_weight: 1000,
+                    min_weight: 0,
+                    deliveries_to: vec![],
+                    deliveries_to_zones: vec![],
+                    name_translations: vec![],
+                },
+                available_package: AvailablePackages {
+                    id: CompanyPackageId(1),
+                    name: "name".to_string(),
+                    logo: "logo".to_string(),
+                    deliveries_to: vec![],
+                    shipping_rate_source: ShippingRateSource::NotAvailable,
+                    local_available: false,
+                    currency: Currency::STQ,
+                },
+            }])
+        }
+
         fn get(&self, id_arg: CompanyPackageId) -> RepoResult<Option<CompanyPackage>> {
+            count_query();
             Ok(Some(CompanyPackage {
                 id: id_arg,
                 company_id: CompanyId(1),
                 package_id: PackageId(1),
                 shipping_rate_source: ShippingRateSource::NotAvailable,
+                restricted_hazard_classes: vec![],
+                delivery_time_min_days: Some(3),
+                delivery_time_max_days: Some(7),
+                rate_resolution_order: None,
+                insurance_options: vec![],
+                cod_supported: false,
+                cod_fee: None,
+                carrier_fallback: CarrierFallback::StoredRates,
+                fallback_markup_percent: None,
+                daily_cutoff_time: None,
+                max_daily_shipments: None,
             }))
         }
 
+        /// Company packages with static rates, so that their rates are loaded too
+        fn get_many_with_companies(&self, ids: Vec<CompanyPackageId>) -> RepoResult<Vec<(CompanyPackage, Company)>> {
+            count_query();
+            check_acl(self.user_id, Resource::CompaniesPackages, Action::Read, self, None)?;
+            Ok(ids
+                .into_iter()
+                .map(|id_arg| {
+                    let company_package = CompanyPackage {
+                        id: id_arg,
+                        company_id: CompanyId(1),
+                        package_id: PackageId(1),
+                        shipping_rate_source: ShippingRateSource::Static { dimensional_factor: None },
+                        restricted_hazard_classes: vec![],
+                        delivery_time_min_days: Some(3),
+                        delivery_time_max_days: Some(7),
+                        rate_resolution_order: None,
+                        insurance_options: vec![],
+                        cod_supported: false,
+                        cod_fee: None,
+                        carrier_fallback: CarrierFallback::StoredRates,
+                        fallback_markup_percent: None,
+                        daily_cutoff_time: None,
+                        max_daily_shipments: None,
+                    };
+                    let company = Company {
+                        id: CompanyId(1),
+                        name: "UPS USA".to_string(),
+                        label: "UPS".to_string(),
+                        description: None,
+                        deliveries_from: vec![],
+                        currency: Currency::STQ,
+                        logo: "".to_string(),
+                        capabilities: vec![],
+                        label_translations: vec![],
+                    };
+                    (company_package, company)
+                })
+                .collect())
+        }
+
+        fn get_many_with_companies_and_packages(&self, ids: Vec<CompanyPackageId>) -> RepoResult<Vec<(CompanyPackage, Company, Packages)>> {
+            let packages_repo = PackagesRepoMock { user_id: self.user_id };
+            self.get_many_with_companies(ids)?
+                .into_iter()
+                .map(|(company_package, company)| {
+                    let package = packages_repo
+                        .find(company_package.package_id)?
+                        .ok_or(format_err!("Package with id {} not found", company_package.package_id))?;
+                    Ok((company_package, company, package))
+                })
+                .collect()
+        }
+
         /// Returns companies by package id
         fn get_companies(&self, _package_id: PackageId) -> RepoResult<Vec<Company>> {
             Ok(vec![Company {
@@ -857,6 +2290,8 @@ pub mod tests {
                 deliveries_from: vec![],
                 currency: Currency::STQ,
                 logo: "".to_string(),
+                capabilities: vec![],
+                label_translations: vec![],
             }])
         }
 
@@ -870,22 +2305,153 @@ pub mod tests {
                 max_weight: 0,
                 min_weight: 0,
                 deliveries_to: vec![],
+                deliveries_to_zones: vec![],
+                name_translations: vec![],
             }])
         }
 
+        fn find_by_ids(&self, company_id_arg: CompanyId, package_id_arg: PackageId) -> RepoResult<Option<CompanyPackage>> {
+            check_acl(self.user_id, Resource::CompaniesPackages, Action::Read, self, None)?;
+            Ok(Some(CompanyPackage {
+                id: CompanyPackageId(1),
+                company_id: company_id_arg,
+                package_id: package_id_arg,
+                shipping_rate_source: ShippingRateSource::NotAvailable,
+                restricted_hazard_classes: vec![],
+                delivery_time_min_days: Some(3),
+                delivery_time_max_days: Some(7),
+                rate_resolution_order: None,
+                insurance_options: vec![],
+                cod_supported: false,
+                cod_fee: None,
+                carrier_fallback: CarrierFallback::StoredRates,
+                fallback_markup_percent: None,
+                daily_cutoff_time: None,
+                max_daily_shipments: None,
+            }))
+        }
+
         /// Delete a companies_packages
         fn delete(&self, company_id_arg: CompanyId, package_id_arg: PackageId) -> RepoResult<CompanyPackage> {
+            check_acl(self.user_id, Resource::CompaniesPackages, Action::Delete, self, None)?;
             Ok(CompanyPackage {
                 id: CompanyPackageId(1),
                 company_id: company_id_arg,
                 package_id: package_id_arg,
                 shipping_rate_source: ShippingRateSource::NotAvailable,
+                restricted_hazard_classes: vec![],
+                delivery_time_min_days: Some(3),
+                delivery_time_max_days: Some(7),
+                rate_resolution_order: None,
+                insurance_options: vec![],
+                cod_supported: false,
+                cod_fee: None,
+                carrier_fallback: CarrierFallback::StoredRates,
+                fallback_markup_percent: None,
+                daily_cutoff_time: None,
+                max_daily_shipments: None,
             })
         }
+
+        fn set_rate_resolution_order(
+            &self,
+            id_arg: CompanyPackageId,
+            steps: Option<Vec<RateResolutionStep>>,
+        ) -> RepoResult<Option<CompanyPackage>> {
+            check_acl(self.user_id, Resource::CompaniesPackages, Action::Update, self, None)?;
+            Ok(Some(CompanyPackage {
+                id: id_arg,
+                company_id: CompanyId(1),
+                package_id: PackageId(1),
+                shipping_rate_source: ShippingRateSource::NotAvailable,
+                restricted_hazard_classes: vec![],
+                delivery_time_min_days: Some(3),
+                delivery_time_max_days: Some(7),
+                rate_resolution_order: steps,
+                insurance_options: vec![],
+                cod_supported: false,
+                cod_fee: None,
+                carrier_fallback: CarrierFallback::StoredRates,
+                fallback_markup_percent: None,
+                daily_cutoff_time: None,
+                max_daily_shipments: None,
+            }))
+        }
+
+        fn set_cash_on_delivery(&self, id_arg: CompanyPackageId, payload: SetCashOnDelivery) -> RepoResult<Option<CompanyPackage>> {
+            check_acl(self.user_id, Resource::CompaniesPackages, Action::Update, self, None)?;
+            Ok(Some(CompanyPackage {
+                id: id_arg,
+                company_id: CompanyId(1),
+                package_id: PackageId(1),
+                shipping_rate_source: ShippingRateSource::NotAvailable,
+                restricted_hazard_classes: vec![],
+                delivery_time_min_days: Some(3),
+                delivery_time_max_days: Some(7),
+                rate_resolution_order: None,
+                insurance_options: vec![],
+                cod_supported: payload.cod_supported,
+                cod_fee: payload.cod_fee,
+                carrier_fallback: CarrierFallback::StoredRates,
+                fallback_markup_percent: None,
+                daily_cutoff_time: None,
+                max_daily_shipments: None,
+            }))
+        }
+
+        fn set_carrier_fallback(&self, id_arg: CompanyPackageId, payload: SetCarrierFallback) -> RepoResult<Option<CompanyPackage>> {
+            check_acl(self.user_id, Resource::CompaniesPackages, Action::Update, self, None)?;
+            Ok(Some(CompanyPackage {
+                id: id_arg,
+                company_id: CompanyId(1),
+                package_id: PackageId(1),
+                shipping_rate_source: ShippingRateSource::NotAvailable,
+                restricted_hazard_classes: vec![],
+                delivery_time_min_days: Some(3),
+                delivery_time_max_days: Some(7),
+                rate_resolution_order: None,
+                insurance_options: vec![],
+                cod_supported: false,
+                cod_fee: None,
+                carrier_fallback: payload.carrier_fallback,
+                fallback_markup_percent: payload.fallback_markup_percent,
+                daily_cutoff_time: None,
+                max_daily_shipments: None,
+            }))
+        }
+
+        fn set_shipping_capacity(&self, id_arg: CompanyPackageId, payload: SetShippingCapacity) -> RepoResult<Option<CompanyPackage>> {
+            check_acl(self.user_id, Resource::CompaniesPackages, Action::Update, self, None)?;
+            Ok(Some(CompanyPackage {
+                id: id_arg,
+                company_id: CompanyId(1),
+                package_id: PackageId(1),
+                shipping_rate_source: ShippingRateSource::NotAvailable,
+                restricted_hazard_classes: vec![],
+                delivery_time_min_days: Some(3),
+                delivery_time_max_days: Some(7),
+                rate_resolution_order: None,
+                insurance_options: vec![],
+                cod_supported: false,
+                cod_fee: None,
+                carrier_fallback: CarrierFallback::StoredRates,
+                fallback_markup_percent: None,
+                daily_cutoff_time: payload.daily_cutoff_time,
+                max_daily_shipments: payload.max_daily_shipments,
+            }))
+        }
+    }
+
+    impl CheckScope<Scope, CompanyPackage> for CompaniesPackagesRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&CompanyPackage>) -> bool {
+            *scope == Scope::All
+        }
     }
 
     #[derive(Clone, Default)]
-    pub struct UserAddressesRepoMock;
+    pub struct UserAddressesRepoMock {
+        pub user_id: Option<UserId>,
+    }
 
     impl UserAddressesRepo for UserAddressesRepoMock {
         /// Returns list of user_delivery_address for a specific user
@@ -906,12 +2472,41 @@ pub mod tests {
                 created_at: SystemTime::now(),
                 updated_at: SystemTime::now(),
                 country_code: None,
+                latitude: None,
+                longitude: None,
+                subdivision: None,
             }])
         }
 
+        /// Addresses are in Russia
+        fn find(&self, id: i32) -> RepoResult<Option<UserAddress>> {
+            let address = UserAddress {
+                id,
+                user_id: UserId(1),
+                administrative_area_level_1: None,
+                administrative_area_level_2: None,
+                country: "Russia".to_string(),
+                locality: None,
+                political: None,
+                postal_code: "101000".to_string(),
+                route: None,
+                street_number: None,
+                is_priority: true,
+                address: None,
+                created_at: SystemTime::now(),
+                updated_at: SystemTime::now(),
+                country_code: Some("RUS".to_string()),
+                latitude: None,
+                longitude: None,
+                subdivision: None,
+            };
+            check_acl(self.user_id, Resource::UserAddresses, Action::Read, self, Some(&address))?;
+            Ok(Some(address))
+        }
+
         /// Create a new user delivery address
         fn create(&self, payload: NewUserAddress) -> RepoResult<UserAddress> {
-            Ok(UserAddress {
+            let address = UserAddress {
                 id: 1,
                 user_id: payload.user_id,
                 administrative_area_level_1: payload.administrative_area_level_1,
@@ -927,12 +2522,17 @@ pub mod tests {
                 created_at: SystemTime::now(),
                 updated_at: SystemTime::now(),
                 country_code: payload.country_code,
-            })
+                latitude: payload.latitude,
+                longitude: payload.longitude,
+                subdivision: payload.subdivision,
+            };
+            check_acl(self.user_id, Resource::UserAddresses, Action::Create, self, Some(&address))?;
+            Ok(address)
         }
 
         /// Update a user delivery address
         fn update(&self, id: i32, payload: UpdateUserAddress) -> RepoResult<UserAddress> {
-            Ok(UserAddress {
+            let address = UserAddress {
                 id,
                 user_id: UserId(1),
                 administrative_area_level_1: payload.administrative_area_level_1,
@@ -948,12 +2548,17 @@ pub mod tests {
                 created_at: SystemTime::now(),
                 updated_at: SystemTime::now(),
                 country_code: payload.country_code,
-            })
+                latitude: payload.latitude,
+                longitude: payload.longitude,
+                subdivision: payload.subdivision,
+            };
+            check_acl(self.user_id, Resource::UserAddresses, Action::Update, self, Some(&address))?;
+            Ok(address)
         }
 
         /// Delete user delivery address
         fn delete(&self, id: i32) -> RepoResult<UserAddress> {
-            Ok(UserAddress {
+            let address = UserAddress {
                 id,
                 user_id: UserId(1),
                 administrative_area_level_1: None,
@@ -969,12 +2574,54 @@ pub mod tests {
                 created_at: SystemTime::now(),
                 updated_at: SystemTime::now(),
                 country_code: None,
-            })
+                latitude: None,
+                longitude: None,
+                subdivision: None,
+            };
+            check_acl(self.user_id, Resource::UserAddresses, Action::Delete, self, Some(&address))?;
+            Ok(address)
+        }
+
+        /// Make user delivery address the default one
+        fn set_default(&self, id: i32) -> RepoResult<UserAddress> {
+            let address = UserAddress {
+                id,
+                user_id: UserId(1),
+                administrative_area_level_1: None,
+                administrative_area_level_2: None,
+                country: "None".to_string(),
+                locality: None,
+                political: None,
+                postal_code: "None".to_string(),
+                route: None,
+                street_number: None,
+                is_priority: true,
+                address: None,
+                created_at: SystemTime::now(),
+                updated_at: SystemTime::now(),
+                country_code: None,
+                latitude: None,
+                longitude: None,
+                subdivision: None,
+            };
+            check_acl(self.user_id, Resource::UserAddresses, Action::Update, self, Some(&address))?;
+            Ok(address)
+        }
+    }
+
+    impl CheckScope<Scope, UserAddress> for UserAddressesRepoMock {
+        fn is_in_scope(&self, user_id: UserId, scope: &Scope, obj: Option<&UserAddress>) -> bool {
+            match *scope {
+                Scope::All => true,
+                Scope::Owned => obj.map(|address| address.user_id == user_id).unwrap_or_default(),
+            }
         }
     }
 
     #[derive(Clone, Default)]
-    pub struct ShippingRatesRepoMock;
+    pub struct ShippingRatesRepoMock {
+        pub user_id: Option<UserId>,
+    }
 
     impl ShippingRatesRepo for ShippingRatesRepoMock {
         fn get_all_rates_from(&self, _company_package_id: CompanyPackageId, _delivery_from: Alpha3) -> RepoResult<Vec<ShippingRates>> {
@@ -982,10 +2629,12 @@ pub mod tests {
         }
 
         fn insert_many(&self, _shipping_rates: Vec<NewShippingRates>) -> RepoResult<Vec<ShippingRates>> {
+            check_acl(self.user_id, Resource::ShippingRates, Action::Publish, self, None)?;
             Ok(vec![])
         }
 
         fn delete_all_rates_from(&self, _company_package_id: CompanyPackageId, _delivery_from: Alpha3) -> RepoResult<Vec<ShippingRates>> {
+            check_acl(self.user_id, Resource::ShippingRates, Action::Delete, self, None)?;
             Ok(vec![])
         }
 
@@ -1003,6 +2652,59 @@ pub mod tests {
                     company_package_id,
                     from_alpha3: delivery_from.clone(),
                     to_alpha3: delivery_to,
+                    to_zone_id: None,
+                    rates: vec![
+                        ShippingRate {
+                            weight_g: 500,
+                            price: 999.0,
+                        },
+                        ShippingRate {
+                            weight_g: 1000,
+                            price: 1499.0,
+                        },
+                    ],
+                    delivery_time_min_days: None,
+                    delivery_time_max_days: None,
+                    components: vec![],
+                    to_subdivision: None,
+                    to_postal_zone_id: None,
+                })
+                .collect::<Vec<_>>())
+        }
+
+        fn get_subdivision_rates(
+            &self,
+            _company_package_id: CompanyPackageId,
+            _delivery_from: Alpha3,
+            _delivery_to: Alpha3,
+            _subdivision: String,
+        ) -> RepoResult<Option<ShippingRates>> {
+            count_query();
+            Ok(None)
+        }
+
+        fn get_postal_zone_rates(
+            &self,
+            _company_package_id: CompanyPackageId,
+            _delivery_from: Alpha3,
+            _delivery_to: Alpha3,
+            _postal_zone_ids: Vec<i32>,
+        ) -> RepoResult<Vec<ShippingRates>> {
+            count_query();
+            Ok(vec![])
+        }
+
+        fn get_rates_for_routes(&self, routes: Vec<(CompanyPackageId, Alpha3, Alpha3)>) -> RepoResult<Vec<ShippingRates>> {
+            count_query();
+            Ok(routes
+                .into_iter()
+                .enumerate()
+                .map(|(i, (company_package_id, delivery_from, delivery_to))| ShippingRates {
+                    id: ShippingRatesId(i as i32 + 1),
+                    company_package_id,
+                    from_alpha3: delivery_from,
+                    to_alpha3: delivery_to,
+                    to_zone_id: None,
                     rates: vec![
                         ShippingRate {
                             weight_g: 500,
@@ -1013,6 +2715,11 @@ pub mod tests {
                             price: 1499.0,
                         },
                     ],
+                    delivery_time_min_days: None,
+                    delivery_time_max_days: None,
+                    components: vec![],
+                    to_subdivision: None,
+                    to_postal_zone_id: None,
                 })
                 .collect::<Vec<_>>())
         }
@@ -1023,11 +2730,13 @@ pub mod tests {
             delivery_from: Alpha3,
             delivery_to: Alpha3,
         ) -> RepoResult<Option<ShippingRates>> {
+            count_query();
             Ok(Some(ShippingRates {
                 id: ShippingRatesId(1),
                 company_package_id,
                 from_alpha3: delivery_from,
                 to_alpha3: delivery_to,
+                to_zone_id: None,
                 rates: vec![
                     ShippingRate {
                         weight_g: 500,
@@ -1038,8 +2747,1411 @@ pub mod tests {
                         price: 1499.0,
                     },
                 ],
+                delivery_time_min_days: Some(2),
+                delivery_time_max_days: Some(4),
+                components: vec![],
+                to_subdivision: None,
+                to_postal_zone_id: None,
+            }))
+        }
+
+        fn get_active_batch(&self, after_id: Option<ShippingRatesId>, batch_size: i64) -> RepoResult<Vec<ShippingRatesRaw>> {
+            check_acl(self.user_id, Resource::ShippingRates, Action::Read, self, None)?;
+            Ok(mock_shipping_rates_raws()
+                .into_iter()
+                .filter(|raw| after_id.map(|after_id| raw.id.0 > after_id.0).unwrap_or(true))
+                .take(batch_size as usize)
+                .collect())
+        }
+
+        fn deactivate(&self, ids: Vec<ShippingRatesId>) -> RepoResult<usize> {
+            check_acl(self.user_id, Resource::ShippingRates, Action::Update, self, None)?;
+            Ok(ids.len())
+        }
+
+        fn count_rate_entries(&self, _company_id: CompanyId, _except: Option<(CompanyPackageId, Alpha3)>) -> RepoResult<i64> {
+            check_acl(self.user_id, Resource::ShippingRates, Action::Read, self, None)?;
+            Ok(0)
+        }
+
+        fn add_components(
+            &self,
+            _company_package_id: CompanyPackageId,
+            _delivery_from: Alpha3,
+            _components: Vec<RateComponent>,
+            _today: NaiveDate,
+        ) -> RepoResult<Vec<ShippingRates>> {
+            check_acl(self.user_id, Resource::ShippingRates, Action::Update, self, None)?;
+            Ok(vec![])
+        }
+    }
+
+    impl CheckScope<Scope, ()> for ShippingRatesRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, _scope: &Scope, _obj: Option<&()>) -> bool {
+            true
+        }
+    }
+
+    /// Active shipping rates rows: valid rates, rates with zero price and rates with a price cliff
+    pub fn mock_shipping_rates_raws() -> Vec<ShippingRatesRaw> {
+        vec![
+            vec![(500, 999.0), (1000, 1499.0)],
+            vec![(500, 0.0), (1000, 1499.0)],
+            vec![(500, 999.0), (1000, 9999.0)],
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, rates)| ShippingRatesRaw {
+            id: ShippingRatesId(i as i32 + 1),
+            company_package_id: CompanyPackageId(1),
+            from_alpha3: Alpha3("RUS".to_string()),
+            to_alpha3: Some(Alpha3("USA".to_string())),
+            rates: serde_json::to_value(
+                rates
+                    .into_iter()
+                    .map(|(weight_g, price)| ShippingRate { weight_g, price })
+                    .collect::<Vec<_>>(),
+            )
+            .unwrap(),
+            to_zone_id: None,
+            delivery_time_min_days: None,
+            delivery_time_max_days: None,
+            is_active: true,
+            components: json!([]),
+            to_subdivision: None,
+            to_postal_zone_id: None,
+        })
+        .collect()
+    }
+
+    #[derive(Clone, Default)]
+    pub struct ShippingRatesAnomaliesRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    impl ShippingRatesAnomaliesRepo for ShippingRatesAnomaliesRepoMock {
+        fn list(&self) -> RepoResult<Vec<ShippingRatesAnomaly>> {
+            check_acl(self.user_id, Resource::ShippingRatesAnomalies, Action::Read, self, None)?;
+            Ok(vec![])
+        }
+
+        fn replace_all(&self, anomalies: Vec<NewShippingRatesAnomaly>) -> RepoResult<Vec<ShippingRatesAnomaly>> {
+            check_acl(self.user_id, Resource::ShippingRatesAnomalies, Action::Delete, self, None)?;
+            check_acl(self.user_id, Resource::ShippingRatesAnomalies, Action::Create, self, None)?;
+            Ok(anomalies
+                .into_iter()
+                .enumerate()
+                .map(|(i, anomaly)| ShippingRatesAnomaly {
+                    id: i as i32 + 1,
+                    shipping_rates_id: anomaly.shipping_rates_id,
+                    kind: anomaly.kind,
+                    weight_g: anomaly.weight_g,
+                    price: anomaly.price,
+                    deactivated: anomaly.deactivated,
+                    detected_at: SystemTime::now(),
+                })
+                .collect())
+        }
+    }
+
+    impl CheckScope<Scope, ShippingRatesAnomaly> for ShippingRatesAnomaliesRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&ShippingRatesAnomaly>) -> bool {
+            *scope == Scope::All
+        }
+    }
+
+    #[derive(Clone, Default)]
+    pub struct AvailabilitySnapshotsRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    impl AvailabilitySnapshotsRepo for AvailabilitySnapshotsRepoMock {
+        /// Previous snapshot has one more package than the mock products repo finds available
+        fn get(&self, base_product_id: BaseProductId, delivery_to: Alpha3) -> RepoResult<Option<AvailabilitySnapshot>> {
+            check_acl(self.user_id, Resource::AvailabilitySnapshots, Action::Read, self, None)?;
+            Ok(Some(AvailabilitySnapshot {
+                base_product_id,
+                delivery_to,
+                packages: vec![
+                    SnapshotPackage {
+                        company_package_id: CompanyPackageId(1),
+                        price: None,
+                    },
+                    SnapshotPackage {
+                        company_package_id: CompanyPackageId(2),
+                        price: Some(100.0),
+                    },
+                ],
+                taken_at: SystemTime::now(),
             }))
         }
+
+        fn save(&self, snapshot: NewAvailabilitySnapshot) -> RepoResult<AvailabilitySnapshot> {
+            check_acl(self.user_id, Resource::AvailabilitySnapshots, Action::Update, self, None)?;
+            Ok(AvailabilitySnapshot {
+                base_product_id: snapshot.base_product_id,
+                delivery_to: snapshot.delivery_to,
+                packages: serde_json::from_value(snapshot.packages)?,
+                taken_at: SystemTime::now(),
+            })
+        }
+    }
+
+    impl CheckScope<Scope, AvailabilitySnapshot> for AvailabilitySnapshotsRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&AvailabilitySnapshot>) -> bool {
+            *scope == Scope::All
+        }
+    }
+
+    #[derive(Clone, Default)]
+    pub struct BackfillsRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    fn create_mock_backfill(name: String, status: BackfillStatus) -> BackfillProgress {
+        BackfillProgress {
+            name,
+            status,
+            total_rows: 10,
+            processed_rows: 0,
+            last_processed_id: None,
+            error: None,
+            created_at: SystemTime::now(),
+            updated_at: SystemTime::now(),
+        }
+    }
+
+    impl BackfillsRepo for BackfillsRepoMock {
+        fn list(&self) -> RepoResult<Vec<BackfillProgress>> {
+            check_acl(self.user_id, Resource::Backfills, Action::Read, self, None)?;
+            Ok(vec![create_mock_backfill("backfill".to_string(), BackfillStatus::Running)])
+        }
+
+        fn find(&self, name: String) -> RepoResult<Option<BackfillProgress>> {
+            check_acl(self.user_id, Resource::Backfills, Action::Read, self, None)?;
+            Ok(Some(create_mock_backfill(name, BackfillStatus::Running)))
+        }
+
+        fn start(&self, name: String, rows_left: i64) -> RepoResult<BackfillProgress> {
+            check_acl(self.user_id, Resource::Backfills, Action::Create, self, None)?;
+            Ok(BackfillProgress {
+                total_rows: rows_left,
+                ..create_mock_backfill(name, BackfillStatus::Running)
+            })
+        }
+
+        fn record_batch(&self, name: String, processed_rows: i64, last_processed_id: i32) -> RepoResult<BackfillProgress> {
+            check_acl(self.user_id, Resource::Backfills, Action::Update, self, None)?;
+            Ok(BackfillProgress {
+                processed_rows,
+                last_processed_id: Some(last_processed_id),
+                ..create_mock_backfill(name, BackfillStatus::Running)
+            })
+        }
+
+        fn finish(&self, name: String) -> RepoResult<BackfillProgress> {
+            check_acl(self.user_id, Resource::Backfills, Action::Update, self, None)?;
+            Ok(create_mock_backfill(name, BackfillStatus::Finished))
+        }
+
+        fn fail(&self, name: String, error: String) -> RepoResult<BackfillProgress> {
+            check_acl(self.user_id, Resource::Backfills, Action::Update, self, None)?;
+            Ok(BackfillProgress {
+                error: Some(error),
+                ..create_mock_backfill(name, BackfillStatus::Failed)
+            })
+        }
+    }
+
+    impl CheckScope<Scope, BackfillProgress> for BackfillsRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&BackfillProgress>) -> bool {
+            *scope == Scope::All
+        }
+    }
+
+    #[derive(Clone, Default)]
+    pub struct ZonesRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    fn create_mock_zone(zone_id: i32) -> Zone {
+        Zone {
+            id: zone_id,
+            name: "Europe".to_string(),
+            countries: vec![Alpha3("DEU".to_string()), Alpha3("FRA".to_string())],
+        }
+    }
+
+    impl ZonesRepo for ZonesRepoMock {
+        fn list(&self) -> RepoResult<Vec<Zone>> {
+            Ok(vec![create_mock_zone(1)])
+        }
+
+        fn find(&self, zone_id: i32) -> RepoResult<Option<Zone>> {
+            Ok(Some(create_mock_zone(zone_id)))
+        }
+
+        fn find_by_names(&self, names: Vec<String>) -> RepoResult<Vec<Zone>> {
+            let zone = create_mock_zone(1);
+            if names.iter().any(|name| name.eq_ignore_ascii_case(&zone.name)) {
+                Ok(vec![zone])
+            } else {
+                Ok(vec![])
+            }
+        }
+
+        fn create(&self, payload: NewZone) -> RepoResult<Zone> {
+            check_acl(self.user_id, Resource::Zones, Action::Create, self, None)?;
+            Ok(Zone {
+                id: 1,
+                name: payload.name,
+                countries: payload.countries,
+            })
+        }
+
+        fn update(&self, zone_id: i32, payload: UpdateZone) -> RepoResult<Zone> {
+            check_acl(self.user_id, Resource::Zones, Action::Update, self, None)?;
+            let zone = create_mock_zone(zone_id);
+            Ok(Zone {
+                id: zone_id,
+                name: payload.name.unwrap_or(zone.name),
+                countries: payload.countries.unwrap_or(zone.countries),
+            })
+        }
+
+        fn delete(&self, zone_id: i32) -> RepoResult<Zone> {
+            check_acl(self.user_id, Resource::Zones, Action::Delete, self, None)?;
+            Ok(create_mock_zone(zone_id))
+        }
+    }
+
+    impl CheckScope<Scope, Zone> for ZonesRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&Zone>) -> bool {
+            *scope == Scope::All
+        }
+    }
+
+    #[derive(Clone, Default)]
+    pub struct SurchargesRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    fn create_mock_surcharge(surcharge_id: i32) -> Surcharge {
+        Surcharge {
+            id: surcharge_id,
+            name: "fuel".to_string(),
+            kind: SurchargeKind::Percentage,
+            value: 5.0,
+            company_id: Some(CompanyId(1)),
+            company_package_id: None,
+            to_alpha3: None,
+        }
+    }
+
+    impl SurchargesRepo for SurchargesRepoMock {
+        fn list(&self) -> RepoResult<Vec<Surcharge>> {
+            count_query();
+            check_acl(self.user_id, Resource::Surcharges, Action::Read, self, None)?;
+            Ok(vec![create_mock_surcharge(1)])
+        }
+
+        fn find(&self, surcharge_id: i32) -> RepoResult<Option<Surcharge>> {
+            check_acl(self.user_id, Resource::Surcharges, Action::Read, self, None)?;
+            Ok(Some(create_mock_surcharge(surcharge_id)))
+        }
+
+        fn find_applicable(
+            &self,
+            _company_id: CompanyId,
+            _company_package_id: CompanyPackageId,
+            _delivery_to: Alpha3,
+        ) -> RepoResult<Vec<Surcharge>> {
+            count_query();
+            check_acl(self.user_id, Resource::Surcharges, Action::Read, self, None)?;
+            Ok(vec![])
+        }
+
+        fn create(&self, payload: NewSurcharge) -> RepoResult<Surcharge> {
+            check_acl(self.user_id, Resource::Surcharges, Action::Create, self, None)?;
+            Ok(Surcharge {
+                id: 1,
+                name: payload.name,
+                kind: payload.kind,
+                value: payload.value,
+                company_id: payload.company_id,
+                company_package_id: payload.company_package_id,
+                to_alpha3: payload.to_alpha3,
+            })
+        }
+
+        fn update(&self, surcharge_id: i32, payload: UpdateSurcharge) -> RepoResult<Surcharge> {
+            check_acl(self.user_id, Resource::Surcharges, Action::Update, self, None)?;
+            let surcharge = create_mock_surcharge(surcharge_id);
+            Ok(Surcharge {
+                name: payload.name.unwrap_or(surcharge.name),
+                kind: payload.kind.unwrap_or(surcharge.kind),
+                value: payload.value.unwrap_or(surcharge.value),
+                ..surcharge
+            })
+        }
+
+        fn delete(&self, surcharge_id: i32) -> RepoResult<Surcharge> {
+            check_acl(self.user_id, Resource::Surcharges, Action::Delete, self, None)?;
+            Ok(create_mock_surcharge(surcharge_id))
+        }
+    }
+
+    impl CheckScope<Scope, Surcharge> for SurchargesRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&Surcharge>) -> bool {
+            *scope == Scope::All
+        }
+    }
+
+    #[cfg(feature = "test-endpoints")]
+    #[derive(Clone, Default)]
+    pub struct TestFixturesRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    #[cfg(feature = "test-endpoints")]
+    impl TestFixturesRepo for TestFixturesRepoMock {
+        fn reset(&self) -> RepoResult<()> {
+            check_acl(self.user_id, Resource::TestFixtures, Action::Delete, self, None)
+        }
+
+        fn delete_countries(&self) -> RepoResult<usize> {
+            check_acl(self.user_id, Resource::TestFixtures, Action::Delete, self, None)?;
+            Ok(0)
+        }
+    }
+
+    #[cfg(feature = "test-endpoints")]
+    impl CheckScope<Scope, ()> for TestFixturesRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&()>) -> bool {
+            *scope == Scope::All
+        }
+    }
+
+    #[derive(Clone, Default)]
+    pub struct PricingRulesRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    fn create_mock_pricing_rule(pricing_rule_id: i32) -> PricingRule {
+        PricingRule {
+            id: pricing_rule_id,
+            store_id: MOCK_STORE_ID,
+            scope: PricingRuleScope::Store,
+            company_package_id: None,
+            to_alpha3: None,
+            kind: SurchargeKind::Percentage,
+            direction: PricingRuleDirection::Discount,
+            value: 10.0,
+            priority: 0,
+        }
+    }
+
+    impl PricingRulesRepo for PricingRulesRepoMock {
+        fn list(&self) -> RepoResult<Vec<PricingRule>> {
+            check_acl(self.user_id, Resource::PricingRules, Action::Read, self, None)?;
+            Ok(vec![create_mock_pricing_rule(1)])
+        }
+
+        fn find(&self, pricing_rule_id: i32) -> RepoResult<Option<PricingRule>> {
+            check_acl(self.user_id, Resource::PricingRules, Action::Read, self, None)?;
+            Ok(Some(create_mock_pricing_rule(pricing_rule_id)))
+        }
+
+        fn find_by_stores(&self, _store_ids: Vec<StoreId>) -> RepoResult<Vec<PricingRule>> {
+            count_query();
+            check_acl(self.user_id, Resource::PricingRules, Action::Read, self, None)?;
+            Ok(vec![])
+        }
+
+        fn create(&self, payload: NewPricingRule) -> RepoResult<PricingRule> {
+            check_acl(self.user_id, Resource::PricingRules, Action::Create, self, None)?;
+            Ok(PricingRule {
+                id: 1,
+                store_id: payload.store_id,
+                scope: payload.scope,
+                company_package_id: payload.company_package_id,
+                to_alpha3: payload.to_alpha3,
+                kind: payload.kind,
+                direction: payload.direction,
+                value: payload.value,
+                priority: payload.priority,
+            })
+        }
+
+        fn update(&self, pricing_rule_id: i32, payload: UpdatePricingRule) -> RepoResult<PricingRule> {
+            check_acl(self.user_id, Resource::PricingRules, Action::Update, self, None)?;
+            let pricing_rule = create_mock_pricing_rule(pricing_rule_id);
+            Ok(PricingRule {
+                kind: payload.kind.unwrap_or(pricing_rule.kind),
+                direction: payload.direction.unwrap_or(pricing_rule.direction),
+                value: payload.value.unwrap_or(pricing_rule.value),
+                priority: payload.priority.unwrap_or(pricing_rule.priority),
+                ..pricing_rule
+            })
+        }
+
+        fn delete(&self, pricing_rule_id: i32) -> RepoResult<PricingRule> {
+            check_acl(self.user_id, Resource::PricingRules, Action::Delete, self, None)?;
+            Ok(create_mock_pricing_rule(pricing_rule_id))
+        }
+    }
+
+    impl CheckScope<Scope, PricingRule> for PricingRulesRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&PricingRule>) -> bool {
+            *scope == Scope::All
+        }
+    }
+
+    #[derive(Clone, Default)]
+    pub struct ShippingRestrictionsRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    fn create_mock_shipping_restriction(restriction_id: i32) -> ShippingRestriction {
+        ShippingRestriction {
+            id: restriction_id,
+            company_id: CompanyId(1),
+            to_alpha3: None,
+            category: Some("Perfume".to_string()),
+            keyword: None,
+            restriction_type: RestrictionType::Prohibited,
+            reason: "Flammable liquids".to_string(),
+        }
+    }
+
+    impl ShippingRestrictionsRepo for ShippingRestrictionsRepoMock {
+        fn list(&self) -> RepoResult<Vec<ShippingRestriction>> {
+            count_query();
+            check_acl(self.user_id, Resource::ShippingRestrictions, Action::Read, self, None)?;
+            Ok(vec![create_mock_shipping_restriction(1)])
+        }
+
+        fn find(&self, restriction_id: i32) -> RepoResult<Option<ShippingRestriction>> {
+            check_acl(self.user_id, Resource::ShippingRestrictions, Action::Read, self, None)?;
+            Ok(Some(create_mock_shipping_restriction(restriction_id)))
+        }
+
+        fn find_by_destination(&self, _delivery_to: Alpha3) -> RepoResult<Vec<ShippingRestriction>> {
+            count_query();
+            check_acl(self.user_id, Resource::ShippingRestrictions, Action::Read, self, None)?;
+            Ok(vec![create_mock_shipping_restriction(1)])
+        }
+
+        fn create(&self, payload: NewShippingRestriction) -> RepoResult<ShippingRestriction> {
+            check_acl(self.user_id, Resource::ShippingRestrictions, Action::Create, self, None)?;
+            Ok(ShippingRestriction {
+                id: 1,
+                company_id: payload.company_id,
+                to_alpha3: payload.to_alpha3,
+                category: payload.category,
+                keyword: payload.keyword,
+                restriction_type: payload.restriction_type,
+                reason: payload.reason,
+            })
+        }
+
+        fn update(&self, restriction_id: i32, payload: UpdateShippingRestriction) -> RepoResult<ShippingRestriction> {
+            check_acl(self.user_id, Resource::ShippingRestrictions, Action::Update, self, None)?;
+            let restriction = create_mock_shipping_restriction(restriction_id);
+            Ok(ShippingRestriction {
+                restriction_type: payload.restriction_type.unwrap_or(restriction.restriction_type),
+                reason: payload.reason.unwrap_or(restriction.reason),
+                ..restriction
+            })
+        }
+
+        fn delete(&self, restriction_id: i32) -> RepoResult<ShippingRestriction> {
+            check_acl(self.user_id, Resource::ShippingRestrictions, Action::Delete, self, None)?;
+            Ok(create_mock_shipping_restriction(restriction_id))
+        }
+    }
+
+    impl CheckScope<Scope, ShippingRestriction> for ShippingRestrictionsRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&ShippingRestriction>) -> bool {
+            *scope == Scope::All
+        }
+    }
+
+    #[derive(Clone, Default)]
+    pub struct CompanySuspensionsRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    fn create_mock_company_suspension(suspension_id: i32) -> CompanySuspension {
+        CompanySuspension {
+            id: suspension_id,
+            company_id: CompanyId(1),
+            company_package_id: None,
+            starts_on: NaiveDate::from_ymd(2019, 3, 20),
+            ends_on: NaiveDate::from_ymd(2019, 3, 22),
+            reason: "Strike".to_string(),
+            created_at: SystemTime::now(),
+        }
+    }
+
+    impl CompanySuspensionsRepo for CompanySuspensionsRepoMock {
+        fn list(&self) -> RepoResult<Vec<CompanySuspension>> {
+            check_acl(self.user_id, Resource::CompanySuspensions, Action::Read, self, None)?;
+            Ok(vec![create_mock_company_suspension(1)])
+        }
+
+        fn find(&self, suspension_id: i32) -> RepoResult<Option<CompanySuspension>> {
+            check_acl(self.user_id, Resource::CompanySuspensions, Action::Read, self, None)?;
+            Ok(Some(create_mock_company_suspension(suspension_id)))
+        }
+
+        fn find_active_on(&self, _date: NaiveDate) -> RepoResult<Vec<CompanySuspension>> {
+            count_query();
+            check_acl(self.user_id, Resource::CompanySuspensions, Action::Read, self, None)?;
+            Ok(vec![])
+        }
+
+        fn create(&self, payload: NewCompanySuspension) -> RepoResult<CompanySuspension> {
+            check_acl(self.user_id, Resource::CompanySuspensions, Action::Moderate, self, None)?;
+            Ok(CompanySuspension {
+                id: 1,
+                company_id: payload.company_id,
+                company_package_id: payload.company_package_id,
+                starts_on: payload.starts_on,
+                ends_on: payload.ends_on,
+                reason: payload.reason,
+                created_at: SystemTime::now(),
+            })
+        }
+
+        fn update(&self, suspension_id: i32, payload: UpdateCompanySuspension) -> RepoResult<CompanySuspension> {
+            check_acl(self.user_id, Resource::CompanySuspensions, Action::Moderate, self, None)?;
+            let suspension = create_mock_company_suspension(suspension_id);
+            Ok(CompanySuspension {
+                starts_on: payload.starts_on.unwrap_or(suspension.starts_on),
+                ends_on: payload.ends_on.unwrap_or(suspension.ends_on),
+                reason: payload.reason.unwrap_or(suspension.reason),
+                ..suspension
+            })
+        }
+
+        fn delete(&self, suspension_id: i32) -> RepoResult<CompanySuspension> {
+            check_acl(self.user_id, Resource::CompanySuspensions, Action::Moderate, self, None)?;
+            Ok(create_mock_company_suspension(suspension_id))
+        }
+    }
+
+    impl CheckScope<Scope, CompanySuspension> for CompanySuspensionsRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&CompanySuspension>) -> bool {
+            *scope == Scope::All
+        }
+    }
+
+    #[derive(Clone, Default)]
+    pub struct ShipmentBookingsRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    impl ShipmentBookingsRepo for ShipmentBookingsRepoMock {
+        fn find_many(&self, _keys: Vec<(CompanyPackageId, NaiveDate)>) -> RepoResult<Vec<ShipmentBooking>> {
+            count_query();
+            check_acl(self.user_id, Resource::ShipmentBookings, Action::Read, self, None)?;
+            Ok(vec![])
+        }
+
+        fn book(&self, company_package_id: CompanyPackageId, shipping_date: NaiveDate) -> RepoResult<ShipmentBooking> {
+            check_acl(self.user_id, Resource::ShipmentBookings, Action::Create, self, None)?;
+            Ok(ShipmentBooking {
+                company_package_id,
+                shipping_date,
+                shipments_count: 1,
+            })
+        }
+    }
+
+    impl CheckScope<Scope, ShipmentBooking> for ShipmentBookingsRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&ShipmentBooking>) -> bool {
+            *scope == Scope::All
+        }
+    }
+
+    #[derive(Clone, Default)]
+    pub struct ShipmentNotificationsRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    impl ShipmentNotificationsRepo for ShipmentNotificationsRepoMock {
+        fn claim(&self, payload: NewShipmentNotification) -> RepoResult<Option<ShipmentNotification>> {
+            check_acl(self.user_id, Resource::ShipmentNotifications, Action::Create, self, None)?;
+            Ok(Some(ShipmentNotification {
+                id: 1,
+                event_id: payload.event_id,
+                kind: payload.kind,
+                user_id: payload.user_id,
+                shipping_id: payload.shipping_id,
+                lang: payload.lang,
+                created_at: SystemTime::now(),
+            }))
+        }
+
+        fn release(&self, _event_id: String) -> RepoResult<()> {
+            check_acl(self.user_id, Resource::ShipmentNotifications, Action::Delete, self, None)
+        }
+    }
+
+    impl CheckScope<Scope, ShipmentNotification> for ShipmentNotificationsRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&ShipmentNotification>) -> bool {
+            *scope == Scope::All
+        }
+    }
+
+    #[derive(Clone, Default)]
+    pub struct PermissionsRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    impl PermissionsRepo for PermissionsRepoMock {
+        fn list(&self) -> RepoResult<Vec<RolePermission>> {
+            check_acl(self.user_id, Resource::Permissions, Action::Read, self, None)?;
+            let role_permissions = default_permissions()
+                .into_iter()
+                .flat_map(|(role, permissions)| {
+                    permissions.into_iter().map(move |permission| RolePermission {
+                        id: 1,
+                        role,
+                        resource: permission.resource,
+                        action: permission.action,
+                        scope: permission.scope,
+                    })
+                })
+                .collect();
+            Ok(role_permissions)
+        }
+    }
+
+    impl CheckScope<Scope, RolePermission> for PermissionsRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&RolePermission>) -> bool {
+            *scope == Scope::All
+        }
+    }
+
+    #[derive(Clone, Default)]
+    pub struct CountryRegulationsRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    impl CountryRegulationsRepo for CountryRegulationsRepoMock {
+        fn list(&self) -> RepoResult<Vec<CountryRegulation>> {
+            check_acl(self.user_id, Resource::CountryRegulations, Action::Read, self, None)?;
+            Ok(vec![])
+        }
+
+        fn get(&self, _alpha3: Alpha3) -> RepoResult<Option<CountryRegulation>> {
+            count_query();
+            check_acl(self.user_id, Resource::CountryRegulations, Action::Read, self, None)?;
+            Ok(None)
+        }
+
+        fn set(&self, alpha3: Alpha3, payload: SetCountryRegulation) -> RepoResult<CountryRegulation> {
+            check_acl(self.user_id, Resource::CountryRegulations, Action::Update, self, None)?;
+            Ok(CountryRegulation {
+                alpha3,
+                max_weight_g: payload.max_weight_g,
+                max_dimension_cm: payload.max_dimension_cm,
+                prohibited_hazard_classes: payload.prohibited_hazard_classes,
+                source: payload.source,
+            })
+        }
+
+        fn delete(&self, _alpha3: Alpha3) -> RepoResult<Option<CountryRegulation>> {
+            check_acl(self.user_id, Resource::CountryRegulations, Action::Delete, self, None)?;
+            Ok(None)
+        }
+    }
+
+    impl CheckScope<Scope, CountryRegulation> for CountryRegulationsRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&CountryRegulation>) -> bool {
+            *scope == Scope::All
+        }
+    }
+
+    #[derive(Clone, Default)]
+    pub struct CountrySubdivisionsRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    fn create_mock_country_subdivision(code: String) -> CountrySubdivision {
+        CountrySubdivision {
+            code,
+            alpha3: Alpha3("RUS".to_string()),
+            name: "Moscow".to_string(),
+        }
+    }
+
+    impl CountrySubdivisionsRepo for CountrySubdivisionsRepoMock {
+        fn list(&self, alpha3: Alpha3) -> RepoResult<Vec<CountrySubdivision>> {
+            check_acl(self.user_id, Resource::Countries, Action::Read, self, None)?;
+            Ok(self
+                .get("RU-MOW".to_string())?
+                .into_iter()
+                .filter(|subdivision| subdivision.alpha3 == alpha3)
+                .collect())
+        }
+
+        fn list_all(&self) -> RepoResult<Vec<CountrySubdivision>> {
+            check_acl(self.user_id, Resource::Countries, Action::Read, self, None)?;
+            Ok(vec![create_mock_country_subdivision("RU-MOW".to_string())])
+        }
+
+        fn get(&self, code: String) -> RepoResult<Option<CountrySubdivision>> {
+            check_acl(self.user_id, Resource::Countries, Action::Read, self, None)?;
+            if code.starts_with("RU-") {
+                Ok(Some(create_mock_country_subdivision(code)))
+            } else {
+                Ok(None)
+            }
+        }
+
+        fn create(&self, payload: CountrySubdivision) -> RepoResult<CountrySubdivision> {
+            check_acl(self.user_id, Resource::Countries, Action::Create, self, Some(&payload))?;
+            Ok(payload)
+        }
+
+        fn update(&self, code: String, payload: UpdateCountrySubdivision) -> RepoResult<CountrySubdivision> {
+            check_acl(self.user_id, Resource::Countries, Action::Update, self, None)?;
+            Ok(CountrySubdivision {
+                name: payload.name,
+                ..create_mock_country_subdivision(code)
+            })
+        }
+
+        fn delete(&self, code: String) -> RepoResult<CountrySubdivision> {
+            check_acl(self.user_id, Resource::Countries, Action::Delete, self, None)?;
+            Ok(create_mock_country_subdivision(code))
+        }
+    }
+
+    impl CheckScope<Scope, CountrySubdivision> for CountrySubdivisionsRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&CountrySubdivision>) -> bool {
+            *scope == Scope::All
+        }
+    }
+
+    #[derive(Clone, Default)]
+    pub struct CountryDependentsRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    impl CountryDependentsRepo for CountryDependentsRepoMock {
+        fn count(&self, dependent: CountryDependent) -> RepoResult<i64> {
+            check_acl(self.user_id, dependent.resource(), Action::Read, self, None)?;
+            Ok(1)
+        }
+
+        fn get_batch(&self, dependent: CountryDependent, after_id: Option<i32>, _batch_size: i64) -> RepoResult<Vec<CountryCodesRow>> {
+            check_acl(self.user_id, dependent.resource(), Action::Read, self, None)?;
+            match after_id {
+                None => Ok(vec![CountryCodesRow {
+                    id: 1,
+                    codes: vec![Alpha3("RUS".to_string()), Alpha3("SUN".to_string())],
+                }]),
+                Some(_) => Ok(vec![]),
+            }
+        }
+
+        fn set_codes(&self, dependent: CountryDependent, _id: i32, _codes: Vec<Alpha3>) -> RepoResult<()> {
+            check_acl(self.user_id, dependent.resource(), Action::Update, self, None)?;
+            Ok(())
+        }
+    }
+
+    impl CheckScope<Scope, ()> for CountryDependentsRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&()>) -> bool {
+            *scope == Scope::All
+        }
+    }
+
+    /// Key the api keys mock finds by its hash
+    pub const MOCK_API_KEY: &str = "dlv_mock";
+
+    fn create_mock_api_key(id: i32, name: String, scopes: Vec<ApiKeyScope>) -> ApiKey {
+        ApiKey {
+            id,
+            name,
+            scopes,
+            created_at: SystemTime::now(),
+            revoked_at: None,
+        }
+    }
+
+    #[derive(Clone, Default)]
+    pub struct ApiKeysRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    impl ApiKeysRepo for ApiKeysRepoMock {
+        fn create(&self, payload: InsertApiKey) -> RepoResult<ApiKey> {
+            check_acl(self.user_id, Resource::ApiKeys, Action::Create, self, None)?;
+            let scopes = serde_json::from_value(payload.scopes)?;
+            Ok(create_mock_api_key(1, payload.name, scopes))
+        }
+
+        fn list(&self) -> RepoResult<Vec<ApiKey>> {
+            check_acl(self.user_id, Resource::ApiKeys, Action::Read, self, None)?;
+            Ok(vec![])
+        }
+
+        fn find_active_by_hash(&self, key_hash: &str) -> RepoResult<Option<ApiKey>> {
+            check_acl(self.user_id, Resource::ApiKeys, Action::Read, self, None)?;
+            if key_hash != api_key_hash(MOCK_API_KEY) {
+                return Ok(None);
+            }
+
+            let scopes = vec![ApiKeyScope {
+                resource: Resource::Products,
+                action: Action::Read,
+            }];
+            Ok(Some(create_mock_api_key(1, "orders".to_string(), scopes)))
+        }
+
+        fn revoke(&self, _id: i32) -> RepoResult<Option<ApiKey>> {
+            check_acl(self.user_id, Resource::ApiKeys, Action::Delete, self, None)?;
+            Ok(None)
+        }
+    }
+
+    impl CheckScope<Scope, ApiKey> for ApiKeysRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&ApiKey>) -> bool {
+            *scope == Scope::All
+        }
+    }
+
+    #[derive(Clone, Default)]
+    pub struct CompanyRatesQuotasRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    impl CompanyRatesQuotasRepo for CompanyRatesQuotasRepoMock {
+        fn get(&self, _company_id: CompanyId) -> RepoResult<Option<CompanyRatesQuota>> {
+            check_acl(self.user_id, Resource::CompanyRatesQuotas, Action::Read, self, None)?;
+            Ok(None)
+        }
+
+        fn set(&self, company_id: CompanyId, payload: SetCompanyRatesQuota) -> RepoResult<CompanyRatesQuota> {
+            check_acl(self.user_id, Resource::CompanyRatesQuotas, Action::Update, self, None)?;
+            Ok(CompanyRatesQuota {
+                company_id,
+                max_entries: payload.max_entries,
+                updated_at: SystemTime::now(),
+            })
+        }
+
+        fn delete(&self, _company_id: CompanyId) -> RepoResult<Option<CompanyRatesQuota>> {
+            check_acl(self.user_id, Resource::CompanyRatesQuotas, Action::Delete, self, None)?;
+            Ok(None)
+        }
+
+        fn lock(&self, _company_id: CompanyId) -> RepoResult<()> {
+            check_acl(self.user_id, Resource::CompanyRatesQuotas, Action::Read, self, None)
+        }
+    }
+
+    impl CheckScope<Scope, CompanyRatesQuota> for CompanyRatesQuotasRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&CompanyRatesQuota>) -> bool {
+            *scope == Scope::All
+        }
+    }
+
+    #[derive(Clone, Default)]
+    pub struct ShippingTemplatesRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    fn create_mock_shipping_template(store_id: StoreId, template_id: i32) -> ShippingTemplate {
+        ShippingTemplate {
+            id: template_id,
+            store_id,
+            name: "Mock template".to_string(),
+            shipping: NewShipping {
+                items: vec![],
+                pickup: None,
+                hazard_classes: vec![],
+            },
+            free_delivery_over: None,
+        }
+    }
+
+    impl ShippingTemplatesRepo for ShippingTemplatesRepoMock {
+        fn list(&self, store_id: StoreId) -> RepoResult<Vec<ShippingTemplate>> {
+            check_acl(self.user_id, Resource::ShippingTemplates, Action::Read, self, Some(&store_id))?;
+            Ok(vec![create_mock_shipping_template(store_id, 1)])
+        }
+
+        fn find(&self, store_id: StoreId, template_id: i32) -> RepoResult<Option<ShippingTemplate>> {
+            check_acl(self.user_id, Resource::ShippingTemplates, Action::Read, self, Some(&store_id))?;
+            Ok(Some(create_mock_shipping_template(store_id, template_id)))
+        }
+
+        fn create(&self, store_id: StoreId, payload: NewShippingTemplate) -> RepoResult<ShippingTemplate> {
+            check_acl(self.user_id, Resource::ShippingTemplates, Action::Create, self, Some(&store_id))?;
+            Ok(ShippingTemplate {
+                id: 1,
+                store_id,
+                name: payload.name,
+                shipping: payload.shipping,
+                free_delivery_over: payload.free_delivery_over,
+            })
+        }
+
+        fn update(&self, store_id: StoreId, template_id: i32, payload: UpdateShippingTemplate) -> RepoResult<ShippingTemplate> {
+            check_acl(self.user_id, Resource::ShippingTemplates, Action::Update, self, Some(&store_id))?;
+            let template = create_mock_shipping_template(store_id, template_id);
+            Ok(ShippingTemplate {
+                name: payload.name.unwrap_or(template.name),
+                shipping: payload.shipping.unwrap_or(template.shipping),
+                free_delivery_over: payload.free_delivery_over.or(template.free_delivery_over),
+                ..template
+            })
+        }
+
+        fn delete(&self, store_id: StoreId, template_id: i32) -> RepoResult<Option<ShippingTemplate>> {
+            check_acl(self.user_id, Resource::ShippingTemplates, Action::Delete, self, Some(&store_id))?;
+            Ok(Some(create_mock_shipping_template(store_id, template_id)))
+        }
+    }
+
+    impl CheckScope<Scope, StoreId> for ShippingTemplatesRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, obj: Option<&StoreId>) -> bool {
+            match *scope {
+                Scope::All => true,
+                Scope::Owned => obj
+                    .map(|store_id| {
+                        mock_roles_data(self.user_id)
+                            .iter()
+                            .any(|role_data| role_data.manages_store_shipping(*store_id))
+                    })
+                    .unwrap_or_default(),
+            }
+        }
+    }
+
+    #[derive(Clone, Default)]
+    pub struct StoreManagersRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    fn create_mock_shipping_manager(store_id: StoreId, user_id: UserId) -> UserRole {
+        UserRole {
+            id: RoleId::new(),
+            user_id,
+            name: DeliveryRole::StoreManager,
+            data: Some(json!({"type": "shipping_manager", "store_id": store_id})),
+        }
+    }
+
+    impl StoreManagersRepo for StoreManagersRepoMock {
+        fn list(&self, store_id: StoreId) -> RepoResult<Vec<UserRole>> {
+            check_acl(self.user_id, Resource::StoreManagers, Action::Read, self, Some(&store_id))?;
+            Ok(vec![create_mock_shipping_manager(store_id, UserId(3))])
+        }
+
+        fn grant(&self, store_id: StoreId, user_id: UserId) -> RepoResult<UserRole> {
+            check_acl(self.user_id, Resource::StoreManagers, Action::Create, self, Some(&store_id))?;
+            Ok(create_mock_shipping_manager(store_id, user_id))
+        }
+
+        fn revoke(&self, store_id: StoreId, user_id: UserId) -> RepoResult<Option<UserRole>> {
+            check_acl(self.user_id, Resource::StoreManagers, Action::Delete, self, Some(&store_id))?;
+            Ok(Some(create_mock_shipping_manager(store_id, user_id)))
+        }
+    }
+
+    impl CheckScope<Scope, StoreId> for StoreManagersRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, obj: Option<&StoreId>) -> bool {
+            match *scope {
+                Scope::All => true,
+                Scope::Owned => obj
+                    .map(|store_id| {
+                        mock_roles_data(self.user_id)
+                            .iter()
+                            .any(|role_data| role_data.manages_store(*store_id))
+                    })
+                    .unwrap_or_default(),
+            }
+        }
+    }
+
+    /// Stores have no accounts with the carriers, so the prices are not affected by them
+    #[derive(Clone, Default)]
+    pub struct CarrierAccountsRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    fn create_mock_carrier_account(store_id: StoreId, account_id: i32) -> CarrierAccount {
+        CarrierAccount {
+            id: account_id,
+            store_id,
+            company_id: CompanyId(1),
+            account_number: "ACC-1".to_string(),
+            encrypted_account_number: test_cipher().encrypt("ACC-1").unwrap(),
+            encrypted_credentials: test_cipher().encrypt("api-key-123").unwrap(),
+            created_at: SystemTime::now(),
+            updated_at: SystemTime::now(),
+        }
+    }
+
+    impl CarrierAccountsRepo for CarrierAccountsRepoMock {
+        fn list(&self, store_id: StoreId) -> RepoResult<Vec<CarrierAccount>> {
+            check_acl(self.user_id, Resource::CarrierAccounts, Action::Read, self, Some(&store_id))?;
+            Ok(vec![create_mock_carrier_account(store_id, 1)])
+        }
+
+        fn find(&self, store_id: StoreId, account_id: i32) -> RepoResult<Option<CarrierAccount>> {
+            check_acl(self.user_id, Resource::CarrierAccounts, Action::Read, self, Some(&store_id))?;
+            Ok(Some(create_mock_carrier_account(store_id, account_id)))
+        }
+
+        fn find_by_company(&self, store_id: StoreId, _company_id: CompanyId) -> RepoResult<Option<CarrierAccount>> {
+            check_acl(self.user_id, Resource::CarrierAccounts, Action::Read, self, Some(&store_id))?;
+            Ok(None)
+        }
+
+        fn find_usable_by_company(&self, store_id: StoreId, company_id: CompanyId) -> RepoResult<Option<CarrierAccount>> {
+            if check_acl(self.user_id, Resource::CarrierAccounts, Action::Read, self, Some(&store_id)).is_err() {
+                return Ok(None);
+            }
+            self.find_by_company(store_id, company_id)
+        }
+
+        fn create(&self, store_id: StoreId, payload: CreateCarrierAccount) -> RepoResult<CarrierAccount> {
+            check_acl(self.user_id, Resource::CarrierAccounts, Action::Create, self, Some(&store_id))?;
+            Ok(CarrierAccount {
+                company_id: payload.company_id,
+                encrypted_account_number: test_cipher().encrypt(&payload.account_number)?,
+                account_number: payload.account_number,
+                encrypted_credentials: test_cipher().encrypt(&payload.credentials)?,
+                ..create_mock_carrier_account(store_id, 1)
+            })
+        }
+
+        fn update(&self, store_id: StoreId, account_id: i32, payload: UpdateCarrierAccount) -> RepoResult<Option<CarrierAccount>> {
+            check_acl(self.user_id, Resource::CarrierAccounts, Action::Update, self, Some(&store_id))?;
+            let account = create_mock_carrier_account(store_id, account_id);
+            let encrypted_credentials = match payload.credentials {
+                Some(ref credentials) => test_cipher().encrypt(credentials)?,
+                None => account.encrypted_credentials.clone(),
+            };
+            let encrypted_account_number = match payload.account_number {
+                Some(ref account_number) => test_cipher().encrypt(account_number)?,
+                None => account.encrypted_account_number.clone(),
+            };
+            Ok(Some(CarrierAccount {
+                account_number: payload.account_number.unwrap_or(account.account_number.clone()),
+                encrypted_account_number,
+                encrypted_credentials,
+                ..account
+            }))
+        }
+
+        fn delete(&self, store_id: StoreId, account_id: i32) -> RepoResult<Option<CarrierAccount>> {
+            check_acl(self.user_id, Resource::CarrierAccounts, Action::Delete, self, Some(&store_id))?;
+            Ok(Some(create_mock_carrier_account(store_id, account_id)))
+        }
+
+        fn decrypt_credentials(&self, account: &CarrierAccount) -> RepoResult<String> {
+            check_acl(self.user_id, Resource::CarrierAccounts, Action::Read, self, Some(&account.store_id))?;
+            test_cipher().decrypt(&account.encrypted_credentials)
+        }
+
+        fn count_all(&self) -> RepoResult<i64> {
+            check_acl(self.user_id, Resource::CarrierAccounts, Action::Read, self, None)?;
+            Ok(1)
+        }
+
+        fn get_batch(&self, after_id: Option<i32>, _batch_size: i64) -> RepoResult<Vec<CarrierAccount>> {
+            check_acl(self.user_id, Resource::CarrierAccounts, Action::Read, self, None)?;
+            match after_id {
+                None => Ok(vec![create_mock_carrier_account(MOCK_STORE_ID, 1)]),
+                Some(_) => Ok(vec![]),
+            }
+        }
+
+        fn reencrypt(&self, account: &CarrierAccount) -> RepoResult<bool> {
+            check_acl(self.user_id, Resource::CarrierAccounts, Action::Update, self, None)?;
+            let encrypted_account_number = test_cipher().reencrypt(&account.encrypted_account_number)?;
+            let encrypted_credentials = test_cipher().reencrypt(&account.encrypted_credentials)?;
+            Ok(encrypted_account_number.is_some() || encrypted_credentials.is_some())
+        }
+
+        fn key_fingerprint(&self) -> RepoResult<String> {
+            test_cipher().key_fingerprint()
+        }
+    }
+
+    impl CheckScope<Scope, StoreId> for CarrierAccountsRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, obj: Option<&StoreId>) -> bool {
+            match *scope {
+                Scope::All => true,
+                Scope::Owned => obj
+                    .map(|store_id| {
+                        mock_roles_data(self.user_id)
+                            .iter()
+                            .any(|role_data| role_data.manages_store(*store_id))
+                    })
+                    .unwrap_or_default(),
+            }
+        }
+    }
+
+    #[derive(Clone, Default)]
+    pub struct StoreMarginsRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    fn create_mock_store_margin(store_id: StoreId) -> StoreMargin {
+        StoreMargin {
+            store_id,
+            kind: SurchargeKind::Percentage,
+            value: 10.0,
+        }
+    }
+
+    impl StoreMarginsRepo for StoreMarginsRepoMock {
+        fn get(&self, store_id: StoreId) -> RepoResult<Option<StoreMargin>> {
+            count_query();
+            check_acl(self.user_id, Resource::StoreMargins, Action::Read, self, Some(&store_id))?;
+            Ok(Some(create_mock_store_margin(store_id)))
+        }
+
+        fn set(&self, store_id: StoreId, payload: SetStoreMargin) -> RepoResult<StoreMargin> {
+            check_acl(self.user_id, Resource::StoreMargins, Action::Update, self, Some(&store_id))?;
+            Ok(StoreMargin {
+                store_id,
+                kind: payload.kind,
+                value: payload.value,
+            })
+        }
+
+        fn delete(&self, store_id: StoreId) -> RepoResult<Option<StoreMargin>> {
+            check_acl(self.user_id, Resource::StoreMargins, Action::Delete, self, Some(&store_id))?;
+            Ok(Some(create_mock_store_margin(store_id)))
+        }
+    }
+
+    impl CheckScope<Scope, StoreId> for StoreMarginsRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, obj: Option<&StoreId>) -> bool {
+            match *scope {
+                Scope::All => true,
+                Scope::Owned => obj
+                    .map(|store_id| {
+                        mock_roles_data(self.user_id)
+                            .iter()
+                            .any(|role_data| role_data.manages_store(*store_id))
+                    })
+                    .unwrap_or_default(),
+            }
+        }
+    }
+
+    #[derive(Clone, Default)]
+    pub struct AdminOverviewRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    impl AdminOverviewRepo for AdminOverviewRepoMock {
+        fn get(&self) -> RepoResult<AdminOverview> {
+            check_acl(self.user_id, Resource::AdminOverview, Action::Read, self, None)?;
+            Ok(AdminOverview {
+                companies_count: 0,
+                packages_count: 0,
+                companies_packages_count: 0,
+                shipping_rates_count: 0,
+                products_by_country: vec![],
+                top_destinations: vec![],
+            })
+        }
+
+        fn check_diagnostics_access(&self) -> RepoResult<()> {
+            check_acl(self.user_id, Resource::AdminOverview, Action::Read, self, None)
+        }
+    }
+
+    impl CheckScope<Scope, ()> for AdminOverviewRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&()>) -> bool {
+            *scope == Scope::All
+        }
+    }
+
+    #[derive(Clone, Default)]
+    pub struct StoreShippingSummariesRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    impl StoreShippingSummariesRepo for StoreShippingSummariesRepoMock {
+        fn get(&self, store_id: StoreId) -> RepoResult<StoreShippingSummary> {
+            check_acl(self.user_id, Resource::StoreShippingSummaries, Action::Read, self, Some(&store_id))?;
+            Ok(StoreShippingSummary {
+                store_id,
+                products_count: 0,
+                carriers_count: 0,
+                destinations_count: 0,
+                price_ranges: vec![],
+                recent_changes: vec![],
+            })
+        }
+    }
+
+    impl CheckScope<Scope, StoreId> for StoreShippingSummariesRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, obj: Option<&StoreId>) -> bool {
+            match *scope {
+                Scope::All => true,
+                Scope::Owned => obj
+                    .map(|store_id| {
+                        mock_roles_data(self.user_id)
+                            .iter()
+                            .any(|role_data| role_data.manages_store(*store_id))
+                    })
+                    .unwrap_or_default(),
+            }
+        }
+    }
+
+    #[derive(Clone, Default)]
+    pub struct PickupPointsRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    fn create_mock_pickup_point(pickup_point_id: i32) -> PickupPoint {
+        PickupPoint {
+            id: pickup_point_id,
+            company_id: CompanyId(1),
+            name: "Tverskaya".to_string(),
+            country: Alpha3("RUS".to_string()),
+            address: "Tverskaya st. 1, Moscow".to_string(),
+            coordinates: Coordinates {
+                latitude: 55.7575,
+                longitude: 37.6137,
+            },
+            opening_hours: vec![],
+            price_factor: None,
+        }
+    }
+
+    impl PickupPointsRepo for PickupPointsRepoMock {
+        fn list(&self, _company_id: Option<CompanyId>) -> RepoResult<Vec<PickupPoint>> {
+            check_acl(self.user_id, Resource::PickupPoints, Action::Read, self, None)?;
+            Ok(vec![create_mock_pickup_point(1)])
+        }
+
+        fn find(&self, pickup_point_id: i32) -> RepoResult<Option<PickupPoint>> {
+            check_acl(self.user_id, Resource::PickupPoints, Action::Read, self, None)?;
+            Ok(Some(create_mock_pickup_point(pickup_point_id)))
+        }
+
+        fn find_nearby(&self, search: NearbyPickupPointsSearch) -> RepoResult<Vec<NearbyPickupPoint>> {
+            check_acl(self.user_id, Resource::PickupPoints, Action::Read, self, None)?;
+            let pickup_point = create_mock_pickup_point(1);
+            let distance_km = search.coordinates.straight_line_distance_km(&pickup_point.coordinates);
+            if distance_km <= search.radius_km {
+                Ok(vec![NearbyPickupPoint { pickup_point, distance_km }])
+            } else {
+                Ok(vec![])
+            }
+        }
+
+        fn create(&self, payload: NewPickupPoint) -> RepoResult<PickupPoint> {
+            check_acl(self.user_id, Resource::PickupPoints, Action::Create, self, None)?;
+            Ok(PickupPoint {
+                id: 1,
+                company_id: payload.company_id,
+                name: payload.name,
+                country: payload.country,
+                address: payload.address,
+                coordinates: payload.coordinates,
+                opening_hours: payload.opening_hours,
+                price_factor: payload.price_factor,
+            })
+        }
+
+        fn update(&self, pickup_point_id: i32, payload: UpdatePickupPoint) -> RepoResult<PickupPoint> {
+            check_acl(self.user_id, Resource::PickupPoints, Action::Update, self, None)?;
+            let pickup_point = create_mock_pickup_point(pickup_point_id);
+            Ok(PickupPoint {
+                name: payload.name.unwrap_or(pickup_point.name),
+                address: payload.address.unwrap_or(pickup_point.address),
+                coordinates: payload.coordinates.unwrap_or(pickup_point.coordinates),
+                opening_hours: payload.opening_hours.unwrap_or(pickup_point.opening_hours),
+                price_factor: payload.price_factor.or(pickup_point.price_factor),
+                ..pickup_point
+            })
+        }
+
+        fn delete(&self, pickup_point_id: i32) -> RepoResult<PickupPoint> {
+            check_acl(self.user_id, Resource::PickupPoints, Action::Delete, self, None)?;
+            Ok(create_mock_pickup_point(pickup_point_id))
+        }
+    }
+
+    impl CheckScope<Scope, PickupPoint> for PickupPointsRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&PickupPoint>) -> bool {
+            *scope == Scope::All
+        }
+    }
+
+    #[derive(Clone, Default)]
+    pub struct PackageTemplatesRepoMock {
+        pub user_id: Option<UserId>,
+    }
+
+    fn create_mock_package_template(package_template_id: i32) -> PackageTemplate {
+        PackageTemplate {
+            id: package_template_id,
+            from_alpha3: Alpha3("RUS".to_string()),
+            company_package_id: CompanyPackageId(package_template_id),
+            position: 0,
+        }
+    }
+
+    impl PackageTemplatesRepo for PackageTemplatesRepoMock {
+        fn list(&self, _from_alpha3: Option<Alpha3>) -> RepoResult<Vec<PackageTemplate>> {
+            check_acl(self.user_id, Resource::PackageTemplates, Action::Read, self, None)?;
+            Ok(vec![create_mock_package_template(1), create_mock_package_template(2)])
+        }
+
+        fn find(&self, package_template_id: i32) -> RepoResult<Option<PackageTemplate>> {
+            check_acl(self.user_id, Resource::PackageTemplates, Action::Read, self, None)?;
+            Ok(Some(create_mock_package_template(package_template_id)))
+        }
+
+        fn create(&self, payload: NewPackageTemplate) -> RepoResult<PackageTemplate> {
+            check_acl(self.user_id, Resource::PackageTemplates, Action::Create, self, None)?;
+            Ok(PackageTemplate {
+                id: 1,
+                from_alpha3: payload.from_alpha3,
+                company_package_id: payload.company_package_id,
+                position: payload.position,
+            })
+        }
+
+        fn update(&self, package_template_id: i32, payload: UpdatePackageTemplate) -> RepoResult<PackageTemplate> {
+            check_acl(self.user_id, Resource::PackageTemplates, Action::Update, self, None)?;
+            let package_template = create_mock_package_template(package_template_id);
+            Ok(PackageTemplate {
+                position: payload.position.unwrap_or(package_template.position),
+                ..package_template
+            })
+        }
+
+        fn delete(&self, package_template_id: i32) -> RepoResult<PackageTemplate> {
+            check_acl(self.user_id, Resource::PackageTemplates, Action::Delete, self, None)?;
+            Ok(create_mock_package_template(package_template_id))
+        }
+    }
+
+    impl CheckScope<Scope, PackageTemplate> for PackageTemplatesRepoMock {
+        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&PackageTemplate>) -> bool {
+            *scope == Scope::All
+        }
     }
 
     #[derive(Default)]
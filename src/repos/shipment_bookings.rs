@@ -0,0 +1,110 @@
+//! Repo for shipment_bookings table. Counts the shipments booked with the company packages by the shipping date
+
+use chrono::NaiveDate;
+use diesel;
+use diesel::pg::expression::dsl::any;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use errors::Error;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::{CompanyPackageId, UserId};
+
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::types::{DbConnection, RepoResult};
+use models::authorization::*;
+use models::{NewShipmentBooking, ShipmentBooking};
+use schema::shipment_bookings::dsl as DslShipmentBookings;
+
+/// Shipment bookings repository, the bookings are made by the orders
+pub trait ShipmentBookingsRepo {
+    /// Returns bookings of the company packages on the shipping dates, the dates without the shipments are missing
+    fn find_many(&self, keys: Vec<(CompanyPackageId, NaiveDate)>) -> RepoResult<Vec<ShipmentBooking>>;
+
+    /// Adds a shipment to the ones booked with the company package on the shipping date
+    fn book(&self, company_package_id: CompanyPackageId, shipping_date: NaiveDate) -> RepoResult<ShipmentBooking>;
+}
+
+/// Implementation of ShipmentBookings trait
+pub struct ShipmentBookingsRepoImpl<'a, T: DbConnection> {
+    pub db_conn: &'a T,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, ShipmentBooking>>,
+}
+
+impl<'a, T: DbConnection> ShipmentBookingsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, ShipmentBooking>>) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: DbConnection> ShipmentBookingsRepo for ShipmentBookingsRepoImpl<'a, T> {
+    /// Returns bookings of the company packages on the shipping dates, the dates without the shipments are missing
+    fn find_many(&self, keys: Vec<(CompanyPackageId, NaiveDate)>) -> RepoResult<Vec<ShipmentBooking>> {
+        acl::check(&*self.acl, Resource::ShipmentBookings, Action::Read, self, None)?;
+
+        if keys.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let company_package_ids = keys.iter().map(|key| key.0).collect::<Vec<_>>();
+        let shipping_dates = keys.iter().map(|key| key.1).collect::<Vec<_>>();
+
+        let query = DslShipmentBookings::shipment_bookings.filter(
+            DslShipmentBookings::company_package_id
+                .eq(any(company_package_ids))
+                .and(DslShipmentBookings::shipping_date.eq(any(shipping_dates))),
+        );
+
+        query
+            .get_results::<ShipmentBooking>(self.db_conn)
+            .map(|bookings| {
+                bookings
+                    .into_iter()
+                    .filter(|booking| keys.contains(&(booking.company_package_id, booking.shipping_date)))
+                    .collect()
+            })
+            .map_err(|e| {
+                Error::from(e)
+                    .context(format!("Find shipment bookings {:?} error occurred", keys))
+                    .into()
+            })
+    }
+
+    /// Adds a shipment to the ones booked with the company package on the shipping date
+    fn book(&self, company_package_id: CompanyPackageId, shipping_date: NaiveDate) -> RepoResult<ShipmentBooking> {
+        acl::check(&*self.acl, Resource::ShipmentBookings, Action::Create, self, None)?;
+
+        let payload = NewShipmentBooking {
+            company_package_id,
+            shipping_date,
+            shipments_count: 1,
+        };
+        // the row of the date stays locked by the update until the end of the transaction
+        let query = diesel::insert_into(DslShipmentBookings::shipment_bookings)
+            .values(&payload)
+            .on_conflict((DslShipmentBookings::company_package_id, DslShipmentBookings::shipping_date))
+            .do_update()
+            .set(DslShipmentBookings::shipments_count.eq(DslShipmentBookings::shipments_count + 1));
+
+        query.get_result::<ShipmentBooking>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!(
+                    "Book shipment with company package {} on {} error occurred",
+                    company_package_id, shipping_date
+                ))
+                .into()
+        })
+    }
+}
+
+impl<'a, T: DbConnection> CheckScope<Scope, ShipmentBooking> for ShipmentBookingsRepoImpl<'a, T> {
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&ShipmentBooking>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
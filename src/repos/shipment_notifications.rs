@@ -0,0 +1,83 @@
+//! Repo for shipment_notifications table. Every notified shipment event is recorded,
+//! so that the events redelivered by the orders are not notified twice
+
+use diesel;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use errors::Error;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId;
+
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::types::{DbConnection, RepoResult};
+use models::authorization::*;
+use models::{NewShipmentNotification, ShipmentNotification};
+use schema::shipment_notifications::dsl as DslShipmentNotifications;
+
+/// Shipment notifications repository for handling ShipmentNotifications
+pub trait ShipmentNotificationsRepo {
+    /// Records the shipment event as notified, returns `None` if it is already recorded.
+    /// Event ids are unique, so of the concurrent deliveries of the same event only one claims it
+    fn claim(&self, payload: NewShipmentNotification) -> RepoResult<Option<ShipmentNotification>>;
+
+    /// Deletes the record of the shipment event, so that its redelivery is notified again
+    fn release(&self, event_id: String) -> RepoResult<()>;
+}
+
+/// Implementation of ShipmentNotifications trait
+pub struct ShipmentNotificationsRepoImpl<'a, T: DbConnection> {
+    pub db_conn: &'a T,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, ShipmentNotification>>,
+}
+
+impl<'a, T: DbConnection> ShipmentNotificationsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, ShipmentNotification>>) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: DbConnection> ShipmentNotificationsRepo for ShipmentNotificationsRepoImpl<'a, T> {
+    /// Records the shipment event as notified, returns `None` if it is already recorded.
+    /// Event ids are unique, so of the concurrent deliveries of the same event only one claims it
+    fn claim(&self, payload: NewShipmentNotification) -> RepoResult<Option<ShipmentNotification>> {
+        acl::check(&*self.acl, Resource::ShipmentNotifications, Action::Create, self, None)?;
+
+        let query = diesel::insert_into(DslShipmentNotifications::shipment_notifications)
+            .values(&payload)
+            .on_conflict(DslShipmentNotifications::event_id)
+            .do_nothing();
+
+        query.get_result::<ShipmentNotification>(self.db_conn).optional().map_err(|e| {
+            Error::from(e)
+                .context(format!("Claim shipment notification {:?} error occurred", payload))
+                .into()
+        })
+    }
+
+    /// Deletes the record of the shipment event, so that its redelivery is notified again
+    fn release(&self, event_id: String) -> RepoResult<()> {
+        acl::check(&*self.acl, Resource::ShipmentNotifications, Action::Delete, self, None)?;
+
+        let filtered = DslShipmentNotifications::shipment_notifications.filter(DslShipmentNotifications::event_id.eq(&event_id));
+        let query = diesel::delete(filtered);
+
+        query.execute(self.db_conn).map(|_| ()).map_err(|e| {
+            Error::from(e)
+                .context(format!("Release shipment notification of event {} error occurred", event_id))
+                .into()
+        })
+    }
+}
+
+impl<'a, T: DbConnection> CheckScope<Scope, ShipmentNotification> for ShipmentNotificationsRepoImpl<'a, T> {
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&ShipmentNotification>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
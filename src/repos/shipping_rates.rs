@@ -1,23 +1,27 @@
 //! Repo for shipping_rates table. ShippingRates contains rates for every available shipping direction for company-package
 
-use diesel::connection::AnsiTransactionManager;
+use diesel::dsl::{not, sql};
 use diesel::pg::expression::dsl::any;
-use diesel::pg::Pg;
 use diesel::prelude::*;
 use diesel::query_dsl::RunQueryDsl;
-use diesel::Connection;
+use diesel::sql_types::BigInt;
 use errors::Error;
 use failure::Error as FailureError;
+use failure::Fail;
+use serde_json;
 
-use stq_types::{Alpha3, CompanyPackageId, UserId};
+use chrono::NaiveDate;
+
+use stq_types::{Alpha3, CompanyId, CompanyPackageId, ShippingRatesId, UserId};
 
 use repos::legacy_acl::*;
 
 use super::acl;
-use super::types::RepoResult;
-use extras::option;
+use super::types::{DbConnection, RepoResult};
+use super::zones::get_zones_by_ids;
 use models::authorization::*;
-use models::{NewShippingRates, NewShippingRatesRaw, ShippingRates, ShippingRatesRaw};
+use models::{NewShippingRates, NewShippingRatesRaw, RateComponent, ShippingRates, ShippingRatesRaw};
+use schema::companies_packages::dsl as DslCompaniesPackages;
 use schema::shipping_rates::dsl as DslShippingRates;
 
 /// Repository for static shipping rates
@@ -38,38 +42,88 @@ pub trait ShippingRatesRepo {
         delivery_to: Alpha3,
     ) -> RepoResult<Option<ShippingRates>>;
 
+    /// Returns rates to the subdivision of the destination country, rates to the country itself are not returned
+    fn get_subdivision_rates(
+        &self,
+        company_package_id: CompanyPackageId,
+        delivery_from: Alpha3,
+        delivery_to: Alpha3,
+        subdivision: String,
+    ) -> RepoResult<Option<ShippingRates>>;
+
+    /// Returns rates to the postal zones of the destination country, rates to the country itself are not returned
+    fn get_postal_zone_rates(
+        &self,
+        company_package_id: CompanyPackageId,
+        delivery_from: Alpha3,
+        delivery_to: Alpha3,
+        postal_zone_ids: Vec<i32>,
+    ) -> RepoResult<Vec<ShippingRates>>;
+
+    /// Returns rates of every (company package, from, to) route found, using a single query
+    fn get_rates_for_routes(&self, routes: Vec<(CompanyPackageId, Alpha3, Alpha3)>) -> RepoResult<Vec<ShippingRates>>;
+
     fn insert_many(&self, shipping_rates: Vec<NewShippingRates>) -> RepoResult<Vec<ShippingRates>>;
 
     fn delete_all_rates_from(&self, company_package_id: CompanyPackageId, delivery_from: Alpha3) -> RepoResult<Vec<ShippingRates>>;
+
+    /// Returns at most `batch_size` active rows with id greater than `after_id`, zone rows are not expanded
+    fn get_active_batch(&self, after_id: Option<ShippingRatesId>, batch_size: i64) -> RepoResult<Vec<ShippingRatesRaw>>;
+
+    /// Deactivates rows, they are ignored when prices are calculated until the rates are replaced
+    fn deactivate(&self, ids: Vec<ShippingRatesId>) -> RepoResult<usize>;
+
+    /// Returns number of rate entries - weight brackets of every row - stored for the packages of the company,
+    /// rates of the company package from the country in `except` are not counted
+    fn count_rate_entries(&self, company_id: CompanyId, except: Option<(CompanyPackageId, Alpha3)>) -> RepoResult<i64>;
+
+    /// Adds dated components to every row of the company package from the country,
+    /// components expired by `today` are dropped. Returns the changed rates
+    fn add_components(
+        &self,
+        company_package_id: CompanyPackageId,
+        delivery_from: Alpha3,
+        components: Vec<RateComponent>,
+        today: NaiveDate,
+    ) -> RepoResult<Vec<ShippingRates>>;
 }
 
-pub struct ShippingRatesRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+pub struct ShippingRatesRepoImpl<'a, T: DbConnection> {
     pub db_conn: &'a T,
-    pub acl: Box<Acl<Resource, Action, Scope, FailureError, ()>>,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, ()>>,
 }
 
-impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> ShippingRatesRepoImpl<'a, T> {
-    pub fn new(db_conn: &'a T, acl: Box<Acl<Resource, Action, Scope, FailureError, ()>>) -> Self {
+impl<'a, T: DbConnection> ShippingRatesRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, ()>>) -> Self {
         Self { db_conn, acl }
     }
+
+    /// Expands zone rows into per-country rates
+    fn resolve_zones(&self, rates: Vec<ShippingRatesRaw>) -> RepoResult<Vec<ShippingRates>> {
+        let mut zone_ids = rates.iter().filter_map(|rate| rate.to_zone_id).collect::<Vec<_>>();
+        zone_ids.sort();
+        zone_ids.dedup();
+
+        let zones = get_zones_by_ids(self.db_conn, zone_ids)?;
+        ShippingRatesRaw::resolve_zones(rates, &zones)
+    }
 }
 
-impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> ShippingRatesRepo
-    for ShippingRatesRepoImpl<'a, T>
-{
+impl<'a, T: DbConnection> ShippingRatesRepo for ShippingRatesRepoImpl<'a, T> {
     fn get_all_rates_from(&self, company_package_id: CompanyPackageId, delivery_from: Alpha3) -> RepoResult<Vec<ShippingRates>> {
         acl::check(&*self.acl, Resource::ShippingRates, Action::Read, self, None)?;
 
         let query = DslShippingRates::shipping_rates.filter(
             DslShippingRates::company_package_id
                 .eq(company_package_id)
-                .and(DslShippingRates::from_alpha3.eq(delivery_from.clone())),
+                .and(DslShippingRates::from_alpha3.eq(delivery_from.clone()))
+                .and(DslShippingRates::is_active.eq(true)),
         );
 
         query
             .get_results::<ShippingRatesRaw>(self.db_conn)
             .map_err(FailureError::from)
-            .and_then(|rates| rates.into_iter().map(ShippingRatesRaw::to_model).collect::<Result<Vec<_>, _>>())
+            .and_then(|rates| self.resolve_zones(rates))
             .map_err(|e| {
                 e.context(format!(
                     "error occurred in get_all_rates_from for CompanyPackage with id = {}, from {}",
@@ -91,13 +145,21 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             DslShippingRates::company_package_id
                 .eq(company_package_id)
                 .and(DslShippingRates::from_alpha3.eq(delivery_from.clone()))
-                .and(DslShippingRates::to_alpha3.eq(any(deliveries_to.clone()))),
+                .and(
+                    DslShippingRates::to_alpha3
+                        .eq(any(deliveries_to.clone()))
+                        .or(DslShippingRates::to_zone_id.is_not_null()),
+                )
+                .and(DslShippingRates::to_subdivision.is_null())
+                .and(DslShippingRates::to_postal_zone_id.is_null())
+                .and(DslShippingRates::is_active.eq(true)),
         );
 
         query
             .get_results::<ShippingRatesRaw>(self.db_conn)
             .map_err(FailureError::from)
-            .and_then(|rates| rates.into_iter().map(ShippingRatesRaw::to_model).collect::<Result<Vec<_>, _>>())
+            .and_then(|rates| self.resolve_zones(rates))
+            .map(|rates| rates.into_iter().filter(|rates| deliveries_to.contains(&rates.to_alpha3)).collect())
             .map_err(|e| {
                 e.context(format!(
                     "error occurred in get_multiple_rates for CompanyPackage with id = {}, {} -> {:?}",
@@ -120,15 +182,22 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
                 DslShippingRates::company_package_id
                     .eq(company_package_id)
                     .and(DslShippingRates::from_alpha3.eq(delivery_from.clone()))
-                    .and(DslShippingRates::to_alpha3.eq(delivery_to.clone())),
+                    .and(
+                        DslShippingRates::to_alpha3
+                            .eq(delivery_to.clone())
+                            .or(DslShippingRates::to_zone_id.is_not_null()),
+                    )
+                    .and(DslShippingRates::to_subdivision.is_null())
+                    .and(DslShippingRates::to_postal_zone_id.is_null())
+                    .and(DslShippingRates::is_active.eq(true)),
             )
             .order(DslShippingRates::id.desc());
 
         query
-            .get_result::<ShippingRatesRaw>(self.db_conn)
-            .optional()
+            .get_results::<ShippingRatesRaw>(self.db_conn)
             .map_err(FailureError::from)
-            .and_then(|rates| option::transpose(rates.map(ShippingRatesRaw::to_model)))
+            .and_then(|rates| self.resolve_zones(rates))
+            .map(|rates| rates.into_iter().find(|rates| rates.to_alpha3 == delivery_to))
             .map_err(|e| {
                 e.context(format!(
                     "error occurred in get_rates for CompanyPackage with id = {}, {} -> {}",
@@ -138,6 +207,126 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             })
     }
 
+    fn get_subdivision_rates(
+        &self,
+        company_package_id: CompanyPackageId,
+        delivery_from: Alpha3,
+        delivery_to: Alpha3,
+        subdivision: String,
+    ) -> RepoResult<Option<ShippingRates>> {
+        acl::check(&*self.acl, Resource::ShippingRates, Action::Read, self, None)?;
+
+        let query = DslShippingRates::shipping_rates
+            .filter(
+                DslShippingRates::company_package_id
+                    .eq(company_package_id)
+                    .and(DslShippingRates::from_alpha3.eq(delivery_from.clone()))
+                    .and(DslShippingRates::to_alpha3.eq(delivery_to.clone()))
+                    .and(DslShippingRates::to_subdivision.eq(subdivision.clone()))
+                    .and(DslShippingRates::is_active.eq(true)),
+            )
+            .order(DslShippingRates::id.desc());
+
+        query
+            .get_results::<ShippingRatesRaw>(self.db_conn)
+            .map_err(FailureError::from)
+            .and_then(|rates| self.resolve_zones(rates))
+            .map(|rates| rates.into_iter().next())
+            .map_err(|e| {
+                e.context(format!(
+                    "error occurred in get_subdivision_rates for CompanyPackage with id = {}, {} -> {} ({})",
+                    company_package_id, delivery_from, delivery_to, subdivision,
+                ))
+                .into()
+            })
+    }
+
+    fn get_postal_zone_rates(
+        &self,
+        company_package_id: CompanyPackageId,
+        delivery_from: Alpha3,
+        delivery_to: Alpha3,
+        postal_zone_ids: Vec<i32>,
+    ) -> RepoResult<Vec<ShippingRates>> {
+        acl::check(&*self.acl, Resource::ShippingRates, Action::Read, self, None)?;
+
+        if postal_zone_ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let query = DslShippingRates::shipping_rates
+            .filter(
+                DslShippingRates::company_package_id
+                    .eq(company_package_id)
+                    .and(DslShippingRates::from_alpha3.eq(delivery_from.clone()))
+                    .and(DslShippingRates::to_alpha3.eq(delivery_to.clone()))
+                    .and(DslShippingRates::to_postal_zone_id.eq(any(postal_zone_ids.clone())))
+                    .and(DslShippingRates::is_active.eq(true)),
+            )
+            .order(DslShippingRates::id.desc());
+
+        query
+            .get_results::<ShippingRatesRaw>(self.db_conn)
+            .map_err(FailureError::from)
+            .and_then(|rates| self.resolve_zones(rates))
+            .map_err(|e| {
+                e.context(format!(
+                    "error occurred in get_postal_zone_rates for CompanyPackage with id = {}, {} -> {} ({:?})",
+                    company_package_id, delivery_from, delivery_to, postal_zone_ids,
+                ))
+                .into()
+            })
+    }
+
+    fn get_rates_for_routes(&self, routes: Vec<(CompanyPackageId, Alpha3, Alpha3)>) -> RepoResult<Vec<ShippingRates>> {
+        acl::check(&*self.acl, Resource::ShippingRates, Action::Read, self, None)?;
+
+        if routes.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let company_package_ids = routes.iter().map(|route| route.0).collect::<Vec<_>>();
+        let deliveries_from = routes.iter().map(|route| route.1.clone()).collect::<Vec<_>>();
+        let deliveries_to = routes.iter().map(|route| route.2.clone()).collect::<Vec<_>>();
+
+        let query = DslShippingRates::shipping_rates
+            .filter(
+                DslShippingRates::company_package_id
+                    .eq(any(company_package_ids))
+                    .and(DslShippingRates::from_alpha3.eq(any(deliveries_from)))
+                    .and(
+                        DslShippingRates::to_alpha3
+                            .eq(any(deliveries_to))
+                            .or(DslShippingRates::to_zone_id.is_not_null()),
+                    )
+                    .and(DslShippingRates::to_subdivision.is_null())
+                    .and(DslShippingRates::to_postal_zone_id.is_null())
+                    .and(DslShippingRates::is_active.eq(true)),
+            )
+            .order(DslShippingRates::id.desc());
+
+        query
+            .get_results::<ShippingRatesRaw>(self.db_conn)
+            .map_err(FailureError::from)
+            .and_then(|rates| self.resolve_zones(rates))
+            .map(|rates| {
+                rates
+                    .into_iter()
+                    .filter(|rates| {
+                        routes.iter().any(|&(company_package_id, ref delivery_from, ref delivery_to)| {
+                            rates.company_package_id == company_package_id
+                                && rates.from_alpha3 == *delivery_from
+                                && rates.to_alpha3 == *delivery_to
+                        })
+                    })
+                    .collect()
+            })
+            .map_err(|e| {
+                e.context(format!("error occurred in get_rates_for_routes for routes {:?}", routes))
+                    .into()
+            })
+    }
+
     fn delete_all_rates_from(&self, company_package_id: CompanyPackageId, delivery_from: Alpha3) -> RepoResult<Vec<ShippingRates>> {
         acl::check(&*self.acl, Resource::ShippingRates, Action::Delete, self, None)?;
 
@@ -152,7 +341,7 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
         command
             .get_results::<ShippingRatesRaw>(self.db_conn)
             .map_err(|e| Error::from(e).into())
-            .and_then(|rates| rates.into_iter().map(ShippingRatesRaw::to_model).collect::<RepoResult<Vec<_>>>())
+            .and_then(|rates| self.resolve_zones(rates))
             .map_err(|e| {
                 e.context(format!(
                     "error occurred in delete_all_rates_from for CompanyPackage with id = {}, from {}",
@@ -163,7 +352,7 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
     }
 
     fn insert_many(&self, shipping_rates: Vec<NewShippingRates>) -> RepoResult<Vec<ShippingRates>> {
-        acl::check(&*self.acl, Resource::ShippingRates, Action::Create, self, None)?;
+        acl::check(&*self.acl, Resource::ShippingRates, Action::Publish, self, None)?;
 
         let shipping_rates = shipping_rates
             .into_iter()
@@ -175,14 +364,109 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
         command
             .get_results::<ShippingRatesRaw>(self.db_conn)
             .map_err(|e| Error::from(e).into())
-            .and_then(|rates| rates.into_iter().map(ShippingRatesRaw::to_model).collect::<RepoResult<Vec<_>>>())
+            .and_then(|rates| self.resolve_zones(rates))
             .map_err(|e| e.context("error occurred in insert_many").into())
     }
+
+    fn get_active_batch(&self, after_id: Option<ShippingRatesId>, batch_size: i64) -> RepoResult<Vec<ShippingRatesRaw>> {
+        acl::check(&*self.acl, Resource::ShippingRates, Action::Read, self, None)?;
+
+        let query = DslShippingRates::shipping_rates
+            .filter(DslShippingRates::is_active.eq(true))
+            .filter(DslShippingRates::id.gt(after_id.unwrap_or(ShippingRatesId(0))))
+            .order(DslShippingRates::id)
+            .limit(batch_size);
+
+        query.get_results::<ShippingRatesRaw>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!("error occurred in get_active_batch after id {:?}", after_id))
+                .into()
+        })
+    }
+
+    fn deactivate(&self, ids: Vec<ShippingRatesId>) -> RepoResult<usize> {
+        acl::check(&*self.acl, Resource::ShippingRates, Action::Update, self, None)?;
+
+        let command = diesel::update(DslShippingRates::shipping_rates.filter(DslShippingRates::id.eq(any(ids.clone()))))
+            .set(DslShippingRates::is_active.eq(false));
+
+        command.execute(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!("error occurred in deactivate for ids {:?}", ids))
+                .into()
+        })
+    }
+
+    fn count_rate_entries(&self, company_id: CompanyId, except: Option<(CompanyPackageId, Alpha3)>) -> RepoResult<i64> {
+        acl::check(&*self.acl, Resource::ShippingRates, Action::Read, self, None)?;
+
+        let mut query = DslShippingRates::shipping_rates
+            .inner_join(DslCompaniesPackages::companies_packages)
+            .filter(DslCompaniesPackages::company_id.eq(company_id))
+            .select(sql::<BigInt>("COALESCE(SUM(jsonb_array_length(shipping_rates.rates)), 0)::BIGINT"))
+            .into_boxed();
+
+        if let Some((company_package_id, delivery_from)) = except.clone() {
+            query = query.filter(not(DslShippingRates::company_package_id
+                .eq(company_package_id)
+                .and(DslShippingRates::from_alpha3.eq(delivery_from))));
+        }
+
+        query.get_result::<i64>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!(
+                    "error occurred in count_rate_entries for Company with id = {}, except {:?}",
+                    company_id, except
+                ))
+                .into()
+        })
+    }
+
+    fn add_components(
+        &self,
+        company_package_id: CompanyPackageId,
+        delivery_from: Alpha3,
+        components: Vec<RateComponent>,
+        today: NaiveDate,
+    ) -> RepoResult<Vec<ShippingRates>> {
+        acl::check(&*self.acl, Resource::ShippingRates, Action::Update, self, None)?;
+
+        let run = || -> RepoResult<Vec<ShippingRates>> {
+            let rows = DslShippingRates::shipping_rates
+                .filter(
+                    DslShippingRates::company_package_id
+                        .eq(company_package_id)
+                        .and(DslShippingRates::from_alpha3.eq(delivery_from.clone())),
+                )
+                .order(DslShippingRates::id)
+                .get_results::<ShippingRatesRaw>(self.db_conn)
+                .map_err(Error::from)?;
+
+            let mut updated_rows = vec![];
+            for row in rows {
+                let mut row_components = row.parse_components()?;
+                row_components.retain(|component| !component.is_expired_on(today));
+                row_components.extend(components.iter().cloned());
+
+                let command = diesel::update(DslShippingRates::shipping_rates.filter(DslShippingRates::id.eq(row.id)))
+                    .set(DslShippingRates::components.eq(serde_json::to_value(row_components)?));
+                updated_rows.push(command.get_result::<ShippingRatesRaw>(self.db_conn).map_err(Error::from)?);
+            }
+
+            self.resolve_zones(updated_rows)
+        };
+
+        run().map_err(|e| {
+            e.context(format!(
+                "error occurred in add_components for CompanyPackage with id = {}, from {}",
+                company_package_id, delivery_from,
+            ))
+            .into()
+        })
+    }
 }
 
-impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, ()>
-    for ShippingRatesRepoImpl<'a, T>
-{
+impl<'a, T: DbConnection> CheckScope<Scope, ()> for ShippingRatesRepoImpl<'a, T> {
     fn is_in_scope(&self, _user_id_arg: UserId, _scope: &Scope, _obj: Option<&()>) -> bool {
         true
     }
@@ -0,0 +1,91 @@
+//! Repo for shipping_rates_anomalies table. Keeps the report of the last shipping rates integrity check
+
+use diesel;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use errors::Error;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId;
+
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::types::{DbConnection, RepoResult};
+use models::authorization::*;
+use models::{NewShippingRatesAnomaly, ShippingRatesAnomaly};
+use schema::shipping_rates::dsl as DslShippingRates;
+use schema::shipping_rates_anomalies::dsl as DslShippingRatesAnomalies;
+
+/// Shipping rates anomalies repository
+pub trait ShippingRatesAnomaliesRepo {
+    /// Returns anomalies found by the last check
+    fn list(&self) -> RepoResult<Vec<ShippingRatesAnomaly>>;
+
+    /// Replaces anomalies of active rates found by the previous check with the new ones.
+    /// Anomalies of deactivated rates are kept until the rates are replaced. Returns the whole report
+    fn replace_all(&self, anomalies: Vec<NewShippingRatesAnomaly>) -> RepoResult<Vec<ShippingRatesAnomaly>>;
+}
+
+/// Implementation of ShippingRatesAnomalies trait
+pub struct ShippingRatesAnomaliesRepoImpl<'a, T: DbConnection> {
+    pub db_conn: &'a T,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, ShippingRatesAnomaly>>,
+}
+
+impl<'a, T: DbConnection> ShippingRatesAnomaliesRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, ShippingRatesAnomaly>>) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: DbConnection> ShippingRatesAnomaliesRepo for ShippingRatesAnomaliesRepoImpl<'a, T> {
+    /// Returns anomalies found by the last check
+    fn list(&self) -> RepoResult<Vec<ShippingRatesAnomaly>> {
+        acl::check(&*self.acl, Resource::ShippingRatesAnomalies, Action::Read, self, None)?;
+
+        let query = DslShippingRatesAnomalies::shipping_rates_anomalies.order(DslShippingRatesAnomalies::id);
+
+        query
+            .get_results::<ShippingRatesAnomaly>(self.db_conn)
+            .map_err(|e| Error::from(e).context("list of shipping rates anomalies error occurred").into())
+    }
+
+    /// Replaces anomalies of active rates found by the previous check with the new ones.
+    /// Anomalies of deactivated rates are kept until the rates are replaced. Returns the whole report
+    fn replace_all(&self, anomalies: Vec<NewShippingRatesAnomaly>) -> RepoResult<Vec<ShippingRatesAnomaly>> {
+        acl::check(&*self.acl, Resource::ShippingRatesAnomalies, Action::Delete, self, None)?;
+        acl::check(&*self.acl, Resource::ShippingRatesAnomalies, Action::Create, self, None)?;
+
+        let active_rates_ids = DslShippingRates::shipping_rates
+            .filter(DslShippingRates::is_active.eq(true))
+            .select(DslShippingRates::id);
+
+        diesel::delete(
+            DslShippingRatesAnomalies::shipping_rates_anomalies
+                .filter(DslShippingRatesAnomalies::shipping_rates_id.eq_any(active_rates_ids)),
+        )
+        .execute(self.db_conn)
+        .map_err(|e| Error::from(e).context("delete of shipping rates anomalies error occurred"))?;
+
+        diesel::insert_into(DslShippingRatesAnomalies::shipping_rates_anomalies)
+            .values(&anomalies)
+            .execute(self.db_conn)
+            .map_err(|e| Error::from(e).context("insert of shipping rates anomalies error occurred"))?;
+
+        DslShippingRatesAnomalies::shipping_rates_anomalies
+            .order(DslShippingRatesAnomalies::id)
+            .get_results::<ShippingRatesAnomaly>(self.db_conn)
+            .map_err(|e| Error::from(e).context("list of shipping rates anomalies error occurred").into())
+    }
+}
+
+impl<'a, T: DbConnection> CheckScope<Scope, ShippingRatesAnomaly> for ShippingRatesAnomaliesRepoImpl<'a, T> {
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&ShippingRatesAnomaly>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
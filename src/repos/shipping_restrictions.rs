@@ -0,0 +1,150 @@
+//! Repo for shipping_restrictions table. Restrictions are the goods carriers refuse or accept
+//! only with special handling on delivery to the country
+
+use diesel;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use errors::Error;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::{Alpha3, UserId};
+
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::types::{DbConnection, RepoResult};
+use models::authorization::*;
+use models::{NewShippingRestriction, ShippingRestriction, UpdateShippingRestriction};
+use schema::shipping_restrictions::dsl as DslShippingRestrictions;
+
+/// Shipping restrictions repository
+pub trait ShippingRestrictionsRepo {
+    /// Returns list of restrictions
+    fn list(&self) -> RepoResult<Vec<ShippingRestriction>>;
+
+    /// Find specific restriction by id
+    fn find(&self, restriction_id: i32) -> RepoResult<Option<ShippingRestriction>>;
+
+    /// Returns restrictions of all carriers applied to the deliveries to the country
+    fn find_by_destination(&self, delivery_to: Alpha3) -> RepoResult<Vec<ShippingRestriction>>;
+
+    /// Create a new restriction
+    fn create(&self, payload: NewShippingRestriction) -> RepoResult<ShippingRestriction>;
+
+    /// Update a restriction
+    fn update(&self, restriction_id: i32, payload: UpdateShippingRestriction) -> RepoResult<ShippingRestriction>;
+
+    /// Delete a restriction
+    fn delete(&self, restriction_id: i32) -> RepoResult<ShippingRestriction>;
+}
+
+/// Implementation of ShippingRestrictions trait
+pub struct ShippingRestrictionsRepoImpl<'a, T: DbConnection> {
+    pub db_conn: &'a T,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, ShippingRestriction>>,
+}
+
+impl<'a, T: DbConnection> ShippingRestrictionsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, ShippingRestriction>>) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: DbConnection> ShippingRestrictionsRepo for ShippingRestrictionsRepoImpl<'a, T> {
+    /// Returns list of restrictions
+    fn list(&self) -> RepoResult<Vec<ShippingRestriction>> {
+        acl::check(&*self.acl, Resource::ShippingRestrictions, Action::Read, self, None)?;
+
+        let query = DslShippingRestrictions::shipping_restrictions.order(DslShippingRestrictions::id);
+
+        query
+            .get_results::<ShippingRestriction>(self.db_conn)
+            .map_err(|e| Error::from(e).context("list of shipping restrictions error occurred").into())
+    }
+
+    /// Find specific restriction by id
+    fn find(&self, restriction_id: i32) -> RepoResult<Option<ShippingRestriction>> {
+        acl::check(&*self.acl, Resource::ShippingRestrictions, Action::Read, self, None)?;
+
+        let query = DslShippingRestrictions::shipping_restrictions.find(restriction_id);
+
+        query.get_result::<ShippingRestriction>(self.db_conn).optional().map_err(|e| {
+            Error::from(e)
+                .context(format!("Find shipping restriction with id {} error occurred", restriction_id))
+                .into()
+        })
+    }
+
+    /// Returns restrictions of all carriers applied to the deliveries to the country
+    fn find_by_destination(&self, delivery_to: Alpha3) -> RepoResult<Vec<ShippingRestriction>> {
+        acl::check(&*self.acl, Resource::ShippingRestrictions, Action::Read, self, None)?;
+
+        let query = DslShippingRestrictions::shipping_restrictions
+            .filter(
+                DslShippingRestrictions::to_alpha3
+                    .is_null()
+                    .or(DslShippingRestrictions::to_alpha3.eq(delivery_to.clone())),
+            )
+            .order((DslShippingRestrictions::company_id, DslShippingRestrictions::id));
+
+        query.get_results::<ShippingRestriction>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!("Find shipping restrictions to {} error occurred", delivery_to))
+                .into()
+        })
+    }
+
+    /// Create a new restriction
+    fn create(&self, payload: NewShippingRestriction) -> RepoResult<ShippingRestriction> {
+        acl::check(&*self.acl, Resource::ShippingRestrictions, Action::Create, self, None)?;
+
+        let query = diesel::insert_into(DslShippingRestrictions::shipping_restrictions).values(&payload);
+
+        query.get_result::<ShippingRestriction>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!("Create a new shipping restriction {:?} error occurred", payload))
+                .into()
+        })
+    }
+
+    /// Update a restriction
+    fn update(&self, restriction_id: i32, payload: UpdateShippingRestriction) -> RepoResult<ShippingRestriction> {
+        acl::check(&*self.acl, Resource::ShippingRestrictions, Action::Update, self, None)?;
+
+        let filter = DslShippingRestrictions::shipping_restrictions.filter(DslShippingRestrictions::id.eq(restriction_id));
+        let query = diesel::update(filter).set(&payload);
+
+        query.get_result::<ShippingRestriction>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!(
+                    "Update shipping restriction {} with payload {:?} error occurred",
+                    restriction_id, payload
+                ))
+                .into()
+        })
+    }
+
+    /// Delete a restriction
+    fn delete(&self, restriction_id: i32) -> RepoResult<ShippingRestriction> {
+        acl::check(&*self.acl, Resource::ShippingRestrictions, Action::Delete, self, None)?;
+
+        let filtered = DslShippingRestrictions::shipping_restrictions.filter(DslShippingRestrictions::id.eq(restriction_id));
+        let query = diesel::delete(filtered);
+
+        query.get_result::<ShippingRestriction>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!("Delete shipping restriction {} error occurred", restriction_id))
+                .into()
+        })
+    }
+}
+
+impl<'a, T: DbConnection> CheckScope<Scope, ShippingRestriction> for ShippingRestrictionsRepoImpl<'a, T> {
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&ShippingRestriction>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
@@ -0,0 +1,181 @@
+//! Repo for shipping_templates table. Shipping template is a shipping setup saved by the store
+//! to be applied to many products
+
+use diesel;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use failure::Error as FailureError;
+
+use stq_types::{StoreId, UserId};
+
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::types::{DbConnection, RepoResult};
+use super::user_roles::get_user_roles_data;
+use models::authorization::*;
+use models::{
+    NewShippingTemplate, NewShippingTemplateRaw, ShippingTemplate, ShippingTemplateRaw, UpdateShippingTemplate, UpdateShippingTemplateRaw,
+};
+use schema::shipping_templates::dsl as DslShippingTemplates;
+
+/// Shipping templates repository, ACL objects are the stores the templates belong to
+pub trait ShippingTemplatesRepo {
+    /// Returns list of shipping templates of the store
+    fn list(&self, store_id: StoreId) -> RepoResult<Vec<ShippingTemplate>>;
+
+    /// Find shipping template of the store by id
+    fn find(&self, store_id: StoreId, template_id: i32) -> RepoResult<Option<ShippingTemplate>>;
+
+    /// Create a new shipping template of the store
+    fn create(&self, store_id: StoreId, payload: NewShippingTemplate) -> RepoResult<ShippingTemplate>;
+
+    /// Update shipping template of the store
+    fn update(&self, store_id: StoreId, template_id: i32, payload: UpdateShippingTemplate) -> RepoResult<ShippingTemplate>;
+
+    /// Delete shipping template of the store
+    fn delete(&self, store_id: StoreId, template_id: i32) -> RepoResult<Option<ShippingTemplate>>;
+}
+
+/// Implementation of ShippingTemplates trait
+pub struct ShippingTemplatesRepoImpl<'a, T: DbConnection> {
+    pub db_conn: &'a T,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, StoreId>>,
+}
+
+impl<'a, T: DbConnection> ShippingTemplatesRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, StoreId>>) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: DbConnection> ShippingTemplatesRepo for ShippingTemplatesRepoImpl<'a, T> {
+    /// Returns list of shipping templates of the store
+    fn list(&self, store_id: StoreId) -> RepoResult<Vec<ShippingTemplate>> {
+        acl::check(&*self.acl, Resource::ShippingTemplates, Action::Read, self, Some(&store_id))?;
+
+        let query = DslShippingTemplates::shipping_templates
+            .filter(DslShippingTemplates::store_id.eq(store_id))
+            .order(DslShippingTemplates::id);
+
+        query
+            .get_results::<ShippingTemplateRaw>(self.db_conn)
+            .map_err(FailureError::from)
+            .and_then(|raws| raws.into_iter().map(ShippingTemplateRaw::to_model).collect())
+            .map_err(|e| {
+                e.context(format!("List shipping templates of store {} error occurred", store_id))
+                    .into()
+            })
+    }
+
+    /// Find shipping template of the store by id
+    fn find(&self, store_id: StoreId, template_id: i32) -> RepoResult<Option<ShippingTemplate>> {
+        acl::check(&*self.acl, Resource::ShippingTemplates, Action::Read, self, Some(&store_id))?;
+
+        let query = DslShippingTemplates::shipping_templates
+            .filter(DslShippingTemplates::store_id.eq(store_id))
+            .filter(DslShippingTemplates::id.eq(template_id));
+
+        query
+            .get_result::<ShippingTemplateRaw>(self.db_conn)
+            .optional()
+            .map_err(FailureError::from)
+            .and_then(|raw| match raw {
+                Some(raw) => raw.to_model().map(Some),
+                None => Ok(None),
+            })
+            .map_err(|e| {
+                e.context(format!(
+                    "Find shipping template {} of store {} error occurred",
+                    template_id, store_id
+                ))
+                .into()
+            })
+    }
+
+    /// Create a new shipping template of the store
+    fn create(&self, store_id: StoreId, payload: NewShippingTemplate) -> RepoResult<ShippingTemplate> {
+        acl::check(&*self.acl, Resource::ShippingTemplates, Action::Create, self, Some(&store_id))?;
+
+        let record = NewShippingTemplateRaw::from_model(store_id, payload)?;
+        let query = diesel::insert_into(DslShippingTemplates::shipping_templates).values(&record);
+
+        query
+            .get_result::<ShippingTemplateRaw>(self.db_conn)
+            .map_err(FailureError::from)
+            .and_then(ShippingTemplateRaw::to_model)
+            .map_err(|e| {
+                e.context(format!(
+                    "Create a new shipping template {:?} of store {} error occurred",
+                    record, store_id
+                ))
+                .into()
+            })
+    }
+
+    /// Update shipping template of the store
+    fn update(&self, store_id: StoreId, template_id: i32, payload: UpdateShippingTemplate) -> RepoResult<ShippingTemplate> {
+        acl::check(&*self.acl, Resource::ShippingTemplates, Action::Update, self, Some(&store_id))?;
+
+        let changeset = UpdateShippingTemplateRaw::from_model(payload)?;
+        let filtered = DslShippingTemplates::shipping_templates
+            .filter(DslShippingTemplates::store_id.eq(store_id))
+            .filter(DslShippingTemplates::id.eq(template_id));
+        let query = diesel::update(filtered).set(&changeset);
+
+        query
+            .get_result::<ShippingTemplateRaw>(self.db_conn)
+            .map_err(FailureError::from)
+            .and_then(ShippingTemplateRaw::to_model)
+            .map_err(|e| {
+                e.context(format!(
+                    "Update shipping template {} of store {} with payload {:?} error occurred",
+                    template_id, store_id, changeset
+                ))
+                .into()
+            })
+    }
+
+    /// Delete shipping template of the store
+    fn delete(&self, store_id: StoreId, template_id: i32) -> RepoResult<Option<ShippingTemplate>> {
+        acl::check(&*self.acl, Resource::ShippingTemplates, Action::Delete, self, Some(&store_id))?;
+
+        let filtered = DslShippingTemplates::shipping_templates
+            .filter(DslShippingTemplates::store_id.eq(store_id))
+            .filter(DslShippingTemplates::id.eq(template_id));
+        let query = diesel::delete(filtered);
+
+        query
+            .get_result::<ShippingTemplateRaw>(self.db_conn)
+            .optional()
+            .map_err(FailureError::from)
+            .and_then(|raw| match raw {
+                Some(raw) => raw.to_model().map(Some),
+                None => Ok(None),
+            })
+            .map_err(|e| {
+                e.context(format!(
+                    "Delete shipping template {} of store {} error occurred",
+                    template_id, store_id
+                ))
+                .into()
+            })
+    }
+}
+
+impl<'a, T: DbConnection> CheckScope<Scope, StoreId> for ShippingTemplatesRepoImpl<'a, T> {
+    fn is_in_scope(&self, user_id_arg: UserId, scope: &Scope, obj: Option<&StoreId>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => {
+                if let Some(store_id) = obj {
+                    get_user_roles_data(self.db_conn, user_id_arg)
+                        .map(|roles_data| roles_data.iter().any(|role_data| role_data.manages_store_shipping(*store_id)))
+                        .unwrap_or_else(|_: FailureError| false)
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! Repo for shipping manager roles of the stores. Shipping managers are the rows of roles table
+//! with store manager role and shipping manager data, so they pass store manager ACL and are scoped
+//! to the shipping of the store only
+
+use diesel;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use errors::Error;
+use failure::Error as FailureError;
+use failure::Fail;
+use serde_json;
+use std::sync::Arc;
+use stq_cache::cache::Cache;
+use stq_types::{DeliveryRole, RoleId, StoreId, UserId};
+
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::types::{DbConnection, RepoResult};
+use super::user_roles::get_user_roles_data;
+use models::authorization::*;
+use models::{NewUserRole, RoleData, UserRole};
+use repos::RolesCacheImpl;
+use schema::roles::dsl as DslRoles;
+
+/// Store managers repository, ACL objects are the stores the shipping managers are granted for
+pub trait StoreManagersRepo {
+    /// Returns shipping manager roles of the store
+    fn list(&self, store_id: StoreId) -> RepoResult<Vec<UserRole>>;
+
+    /// Grants shipping manager role of the store to the user, the existing role is returned if it is granted already
+    fn grant(&self, store_id: StoreId, user_id: UserId) -> RepoResult<UserRole>;
+
+    /// Revokes shipping manager role of the store from the user
+    fn revoke(&self, store_id: StoreId, user_id: UserId) -> RepoResult<Option<UserRole>>;
+}
+
+/// Implementation of StoreManagers trait
+pub struct StoreManagersRepoImpl<'a, C, T>
+where
+    C: Cache<Vec<DeliveryRole>>,
+    T: DbConnection,
+{
+    pub db_conn: &'a T,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, StoreId>>,
+    pub roles_cache: Arc<RolesCacheImpl<C>>,
+}
+
+impl<'a, C, T> StoreManagersRepoImpl<'a, C, T>
+where
+    C: Cache<Vec<DeliveryRole>>,
+    T: DbConnection,
+{
+    pub fn new(
+        db_conn: &'a T,
+        acl: Box<dyn Acl<Resource, Action, Scope, FailureError, StoreId>>,
+        roles_cache: Arc<RolesCacheImpl<C>>,
+    ) -> Self {
+        Self { db_conn, acl, roles_cache }
+    }
+}
+
+fn shipping_manager_data(store_id: StoreId) -> RepoResult<serde_json::Value> {
+    serde_json::to_value(RoleData::ShippingManager { store_id }).map_err(FailureError::from)
+}
+
+impl<'a, C, T> StoreManagersRepo for StoreManagersRepoImpl<'a, C, T>
+where
+    C: Cache<Vec<DeliveryRole>>,
+    T: DbConnection,
+{
+    /// Returns shipping manager roles of the store
+    fn list(&self, store_id: StoreId) -> RepoResult<Vec<UserRole>> {
+        acl::check(&*self.acl, Resource::StoreManagers, Action::Read, self, Some(&store_id))?;
+
+        let role_data = shipping_manager_data(store_id)?;
+        let query = DslRoles::roles
+            .filter(DslRoles::name.eq(DeliveryRole::StoreManager))
+            .filter(DslRoles::data.eq(role_data))
+            .order(DslRoles::user_id);
+
+        query.get_results::<UserRole>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!("List shipping managers of store {} error occurred", store_id))
+                .into()
+        })
+    }
+
+    /// Grants shipping manager role of the store to the user, the existing role is returned if it is granted already
+    fn grant(&self, store_id: StoreId, user_id: UserId) -> RepoResult<UserRole> {
+        acl::check(&*self.acl, Resource::StoreManagers, Action::Create, self, Some(&store_id))?;
+
+        let role_data = shipping_manager_data(store_id)?;
+        let existing = DslRoles::roles
+            .filter(DslRoles::user_id.eq(user_id))
+            .filter(DslRoles::name.eq(DeliveryRole::StoreManager))
+            .filter(DslRoles::data.eq(role_data.clone()))
+            .get_result::<UserRole>(self.db_conn)
+            .optional();
+
+        let new_role = NewUserRole {
+            id: RoleId::new(),
+            user_id,
+            name: DeliveryRole::StoreManager,
+            data: Some(role_data),
+        };
+
+        existing
+            .and_then(|existing| match existing {
+                Some(role) => Ok(role),
+                None => diesel::insert_into(DslRoles::roles)
+                    .values(&new_role)
+                    .get_result::<UserRole>(self.db_conn),
+            })
+            .map(|role| {
+                self.roles_cache.remove(user_id);
+                role
+            })
+            .map_err(|e| {
+                Error::from(e)
+                    .context(format!(
+                        "Grant shipping manager of store {} to user {} error occurred",
+                        store_id, user_id
+                    ))
+                    .into()
+            })
+    }
+
+    /// Revokes shipping manager role of the store from the user
+    fn revoke(&self, store_id: StoreId, user_id: UserId) -> RepoResult<Option<UserRole>> {
+        acl::check(&*self.acl, Resource::StoreManagers, Action::Delete, self, Some(&store_id))?;
+
+        let role_data = shipping_manager_data(store_id)?;
+        let filtered = DslRoles::roles
+            .filter(DslRoles::user_id.eq(user_id))
+            .filter(DslRoles::name.eq(DeliveryRole::StoreManager))
+            .filter(DslRoles::data.eq(role_data));
+
+        diesel::delete(filtered)
+            .get_result::<UserRole>(self.db_conn)
+            .optional()
+            .map(|role| {
+                self.roles_cache.remove(user_id);
+                role
+            })
+            .map_err(|e| {
+                Error::from(e)
+                    .context(format!(
+                        "Revoke shipping manager of store {} from user {} error occurred",
+                        store_id, user_id
+                    ))
+                    .into()
+            })
+    }
+}
+
+impl<'a, C, T> CheckScope<Scope, StoreId> for StoreManagersRepoImpl<'a, C, T>
+where
+    C: Cache<Vec<DeliveryRole>>,
+    T: DbConnection,
+{
+    /// Only the store managers may delegate the management of their store, shipping managers may not
+    fn is_in_scope(&self, user_id_arg: UserId, scope: &Scope, obj: Option<&StoreId>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => {
+                if let Some(store_id) = obj {
+                    get_user_roles_data(self.db_conn, user_id_arg)
+                        .map(|roles_data| roles_data.iter().any(|role_data| role_data.manages_store(*store_id)))
+                        .unwrap_or_else(|_: FailureError| false)
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Repo for store_margins table. Margin is set by the store and added on top of
+//! the carrier cost in the delivery prices shown to buyers
+
+use diesel;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use errors::Error;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::{StoreId, UserId};
+
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::types::{DbConnection, RepoResult};
+use super::user_roles::get_user_roles_data;
+use models::authorization::*;
+use models::{SetStoreMargin, StoreMargin};
+use schema::store_margins::dsl as DslStoreMargins;
+
+/// Store margins repository, ACL objects are the stores the margins belong to
+pub trait StoreMarginsRepo {
+    /// Returns margin of the store
+    fn get(&self, store_id: StoreId) -> RepoResult<Option<StoreMargin>>;
+
+    /// Sets margin of the store replacing the previous one
+    fn set(&self, store_id: StoreId, payload: SetStoreMargin) -> RepoResult<StoreMargin>;
+
+    /// Delete margin of the store
+    fn delete(&self, store_id: StoreId) -> RepoResult<Option<StoreMargin>>;
+}
+
+/// Implementation of StoreMargins trait
+pub struct StoreMarginsRepoImpl<'a, T: DbConnection> {
+    pub db_conn: &'a T,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, StoreId>>,
+}
+
+impl<'a, T: DbConnection> StoreMarginsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, StoreId>>) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: DbConnection> StoreMarginsRepo for StoreMarginsRepoImpl<'a, T> {
+    /// Returns margin of the store
+    fn get(&self, store_id: StoreId) -> RepoResult<Option<StoreMargin>> {
+        acl::check(&*self.acl, Resource::StoreMargins, Action::Read, self, Some(&store_id))?;
+
+        let query = DslStoreMargins::store_margins.find(store_id);
+
+        query.get_result::<StoreMargin>(self.db_conn).optional().map_err(|e| {
+            Error::from(e)
+                .context(format!("Get margin of store {} error occurred", store_id))
+                .into()
+        })
+    }
+
+    /// Sets margin of the store replacing the previous one
+    fn set(&self, store_id: StoreId, payload: SetStoreMargin) -> RepoResult<StoreMargin> {
+        acl::check(&*self.acl, Resource::StoreMargins, Action::Update, self, Some(&store_id))?;
+
+        let record = StoreMargin {
+            store_id,
+            kind: payload.kind,
+            value: payload.value,
+        };
+
+        let query = diesel::insert_into(DslStoreMargins::store_margins)
+            .values(&record)
+            .on_conflict(DslStoreMargins::store_id)
+            .do_update()
+            .set((DslStoreMargins::kind.eq(record.kind), DslStoreMargins::value.eq(record.value)));
+
+        query.get_result::<StoreMargin>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!("Set margin {:?} of store {} error occurred", payload, store_id))
+                .into()
+        })
+    }
+
+    /// Delete margin of the store
+    fn delete(&self, store_id: StoreId) -> RepoResult<Option<StoreMargin>> {
+        acl::check(&*self.acl, Resource::StoreMargins, Action::Delete, self, Some(&store_id))?;
+
+        let filtered = DslStoreMargins::store_margins.filter(DslStoreMargins::store_id.eq(store_id));
+        let query = diesel::delete(filtered);
+
+        query.get_result::<StoreMargin>(self.db_conn).optional().map_err(|e| {
+            Error::from(e)
+                .context(format!("Delete margin of store {} error occurred", store_id))
+                .into()
+        })
+    }
+}
+
+impl<'a, T: DbConnection> CheckScope<Scope, StoreId> for StoreMarginsRepoImpl<'a, T> {
+    fn is_in_scope(&self, user_id_arg: UserId, scope: &Scope, obj: Option<&StoreId>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => {
+                if let Some(store_id) = obj {
+                    get_user_roles_data(self.db_conn, user_id_arg)
+                        .map(|roles_data| roles_data.iter().any(|role_data| role_data.manages_store(*store_id)))
+                        .unwrap_or_else(|_: FailureError| false)
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! Repo for the shipping summary of the store. Summary is aggregated from the products table
+
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::sql_types::{BigInt, VarChar};
+use errors::Error;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_static_resources::Currency;
+use stq_types::{StoreId, UserId};
+
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::types::{DbConnection, RepoResult};
+use super::user_roles::get_user_roles_data;
+use models::authorization::*;
+use models::{
+    ConfiguredShippingOption, ShippingConfigurationChange, ShippingPriceRange, StoreShippingSummary, STORE_SUMMARY_RECENT_CHANGES_LIMIT,
+};
+use schema::companies::dsl as DslCompanies;
+use schema::companies_packages::dsl as DslCompaniesPackages;
+use schema::products::dsl as DslProducts;
+
+/// Store shipping summaries repository, ACL objects are the stores the summaries are of
+pub trait StoreShippingSummariesRepo {
+    /// Returns shipping summary of the store
+    fn get(&self, store_id: StoreId) -> RepoResult<StoreShippingSummary>;
+}
+
+/// Implementation of StoreShippingSummaries trait
+pub struct StoreShippingSummariesRepoImpl<'a, T: DbConnection> {
+    pub db_conn: &'a T,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, StoreId>>,
+}
+
+impl<'a, T: DbConnection> StoreShippingSummariesRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, StoreId>>) -> Self {
+        Self { db_conn, acl }
+    }
+
+    /// Price ranges of the configured options of the store with fixed prices, grouped by currency
+    fn get_price_ranges(&self, store_id: StoreId) -> Result<Vec<ShippingPriceRange>, FailureError> {
+        let currencies = DslProducts::products
+            .filter(DslProducts::store_id.eq(store_id))
+            .filter(DslProducts::price.is_not_null())
+            .select(DslProducts::currency)
+            .distinct()
+            .order(DslProducts::currency)
+            .get_results::<Currency>(self.db_conn)
+            .map_err(Error::from)?;
+
+        let mut price_ranges = vec![];
+        for currency in currencies {
+            let cheapest_option = self.get_option_by_price(store_id, currency.clone(), true)?;
+            let most_expensive_option = self.get_option_by_price(store_id, currency.clone(), false)?;
+            if let (Some(cheapest_option), Some(most_expensive_option)) = (cheapest_option, most_expensive_option) {
+                price_ranges.push(ShippingPriceRange {
+                    currency,
+                    cheapest_option,
+                    most_expensive_option,
+                });
+            }
+        }
+
+        Ok(price_ranges)
+    }
+
+    /// Configured option of the store with the lowest or the highest fixed price in the currency
+    fn get_option_by_price(
+        &self,
+        store_id: StoreId,
+        currency: Currency,
+        cheapest: bool,
+    ) -> Result<Option<ConfiguredShippingOption>, FailureError> {
+        let query = DslProducts::products
+            .inner_join(DslCompaniesPackages::companies_packages.inner_join(DslCompanies::companies))
+            .filter(DslProducts::store_id.eq(store_id))
+            .filter(DslProducts::currency.eq(currency))
+            .filter(DslProducts::price.is_not_null())
+            .select((
+                DslProducts::id,
+                DslProducts::base_product_id,
+                DslProducts::company_package_id,
+                DslCompanies::id,
+                DslCompanies::name,
+                DslProducts::price,
+                DslProducts::currency,
+            ))
+            .into_boxed();
+
+        let query = if cheapest {
+            query.order((DslProducts::price.asc(), DslProducts::id))
+        } else {
+            query.order((DslProducts::price.desc(), DslProducts::id))
+        };
+
+        query
+            .first::<ConfiguredShippingOption>(self.db_conn)
+            .optional()
+            .map_err(|e| Error::from(e).into())
+    }
+}
+
+impl<'a, T: DbConnection> StoreShippingSummariesRepo for StoreShippingSummariesRepoImpl<'a, T> {
+    /// Returns shipping summary of the store
+    fn get(&self, store_id: StoreId) -> RepoResult<StoreShippingSummary> {
+        acl::check(&*self.acl, Resource::StoreShippingSummaries, Action::Read, self, Some(&store_id))?;
+
+        let summary = || -> Result<StoreShippingSummary, FailureError> {
+            let (products_count, carriers_count) = DslProducts::products
+                .inner_join(DslCompaniesPackages::companies_packages)
+                .filter(DslProducts::store_id.eq(store_id))
+                .select((
+                    sql::<BigInt>("COUNT(DISTINCT products.base_product_id)"),
+                    sql::<BigInt>("COUNT(DISTINCT companies_packages.company_id)"),
+                ))
+                .get_result::<(i64, i64)>(self.db_conn)
+                .map_err(Error::from)?;
+
+            let destinations = DslProducts::products
+                .filter(DslProducts::store_id.eq(store_id))
+                .select(sql::<VarChar>("DISTINCT jsonb_array_elements_text(products.deliveries_to)"))
+                .get_results::<String>(self.db_conn)
+                .map_err(Error::from)?;
+
+            let recent_changes = DslProducts::products
+                .filter(DslProducts::store_id.eq(store_id))
+                .order((DslProducts::updated_at.desc(), DslProducts::id.desc()))
+                .limit(STORE_SUMMARY_RECENT_CHANGES_LIMIT)
+                .select((
+                    DslProducts::id,
+                    DslProducts::base_product_id,
+                    DslProducts::company_package_id,
+                    DslProducts::updated_at,
+                ))
+                .get_results::<ShippingConfigurationChange>(self.db_conn)
+                .map_err(Error::from)?;
+
+            Ok(StoreShippingSummary {
+                store_id,
+                products_count,
+                carriers_count,
+                destinations_count: destinations.len() as i64,
+                price_ranges: self.get_price_ranges(store_id)?,
+                recent_changes,
+            })
+        };
+
+        summary().map_err(|e| {
+            e.context(format!("Get shipping summary of store {} error occurred", store_id))
+                .into()
+        })
+    }
+}
+
+impl<'a, T: DbConnection> CheckScope<Scope, StoreId> for StoreShippingSummariesRepoImpl<'a, T> {
+    fn is_in_scope(&self, user_id_arg: UserId, scope: &Scope, obj: Option<&StoreId>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => {
+                if let Some(store_id) = obj {
+                    get_user_roles_data(self.db_conn, user_id_arg)
+                        .map(|roles_data| roles_data.iter().any(|role_data| role_data.manages_store(*store_id)))
+                        .unwrap_or_else(|_: FailureError| false)
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
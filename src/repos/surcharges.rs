@@ -0,0 +1,169 @@
+//! Repo for surcharges table. Surcharges are fees added on top of the delivery price
+//! and can be changed without touching the shipping rates
+
+use diesel;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use errors::Error;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::{Alpha3, CompanyId, CompanyPackageId, UserId};
+
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::types::{DbConnection, RepoResult};
+use models::authorization::*;
+use models::{NewSurcharge, Surcharge, UpdateSurcharge};
+use schema::surcharges::dsl as DslSurcharges;
+
+/// Surcharges repository for handling Surcharges
+pub trait SurchargesRepo {
+    /// Returns list of surcharges
+    fn list(&self) -> RepoResult<Vec<Surcharge>>;
+
+    /// Find specific surcharge by id
+    fn find(&self, surcharge_id: i32) -> RepoResult<Option<Surcharge>>;
+
+    /// Returns surcharges applied to the delivery with the company package to the country
+    fn find_applicable(
+        &self,
+        company_id: CompanyId,
+        company_package_id: CompanyPackageId,
+        delivery_to: Alpha3,
+    ) -> RepoResult<Vec<Surcharge>>;
+
+    /// Create a new surcharge
+    fn create(&self, payload: NewSurcharge) -> RepoResult<Surcharge>;
+
+    /// Update a surcharge
+    fn update(&self, surcharge_id: i32, payload: UpdateSurcharge) -> RepoResult<Surcharge>;
+
+    /// Delete a surcharge
+    fn delete(&self, surcharge_id: i32) -> RepoResult<Surcharge>;
+}
+
+/// Implementation of Surcharges trait
+pub struct SurchargesRepoImpl<'a, T: DbConnection> {
+    pub db_conn: &'a T,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, Surcharge>>,
+}
+
+impl<'a, T: DbConnection> SurchargesRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, Surcharge>>) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: DbConnection> SurchargesRepo for SurchargesRepoImpl<'a, T> {
+    /// Returns list of surcharges
+    fn list(&self) -> RepoResult<Vec<Surcharge>> {
+        acl::check(&*self.acl, Resource::Surcharges, Action::Read, self, None)?;
+
+        let query = DslSurcharges::surcharges.order(DslSurcharges::id);
+
+        query
+            .get_results::<Surcharge>(self.db_conn)
+            .map_err(|e| Error::from(e).context("list of surcharges error occurred").into())
+    }
+
+    /// Find specific surcharge by id
+    fn find(&self, surcharge_id: i32) -> RepoResult<Option<Surcharge>> {
+        acl::check(&*self.acl, Resource::Surcharges, Action::Read, self, None)?;
+
+        let query = DslSurcharges::surcharges.find(surcharge_id);
+
+        query.get_result::<Surcharge>(self.db_conn).optional().map_err(|e| {
+            Error::from(e)
+                .context(format!("Find surcharge with id {} error occurred", surcharge_id))
+                .into()
+        })
+    }
+
+    /// Returns surcharges applied to the delivery with the company package to the country
+    fn find_applicable(
+        &self,
+        company_id: CompanyId,
+        company_package_id: CompanyPackageId,
+        delivery_to: Alpha3,
+    ) -> RepoResult<Vec<Surcharge>> {
+        acl::check(&*self.acl, Resource::Surcharges, Action::Read, self, None)?;
+
+        let query = DslSurcharges::surcharges
+            .filter(DslSurcharges::company_id.is_null().or(DslSurcharges::company_id.eq(company_id)))
+            .filter(
+                DslSurcharges::company_package_id
+                    .is_null()
+                    .or(DslSurcharges::company_package_id.eq(company_package_id)),
+            )
+            .filter(
+                DslSurcharges::to_alpha3
+                    .is_null()
+                    .or(DslSurcharges::to_alpha3.eq(delivery_to.clone())),
+            )
+            .order(DslSurcharges::id);
+
+        query.get_results::<Surcharge>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!(
+                    "Find surcharges for company package {} to {} error occurred",
+                    company_package_id, delivery_to
+                ))
+                .into()
+        })
+    }
+
+    /// Create a new surcharge
+    fn create(&self, payload: NewSurcharge) -> RepoResult<Surcharge> {
+        acl::check(&*self.acl, Resource::Surcharges, Action::Create, self, None)?;
+
+        let query = diesel::insert_into(DslSurcharges::surcharges).values(&payload);
+
+        query.get_result::<Surcharge>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!("Create a new surcharge {:?} error occurred", payload))
+                .into()
+        })
+    }
+
+    /// Update a surcharge
+    fn update(&self, surcharge_id: i32, payload: UpdateSurcharge) -> RepoResult<Surcharge> {
+        acl::check(&*self.acl, Resource::Surcharges, Action::Update, self, None)?;
+
+        let filter = DslSurcharges::surcharges.filter(DslSurcharges::id.eq(surcharge_id));
+        let query = diesel::update(filter).set(&payload);
+
+        query.get_result::<Surcharge>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!(
+                    "Update surcharge {} with payload {:?} error occurred",
+                    surcharge_id, payload
+                ))
+                .into()
+        })
+    }
+
+    /// Delete a surcharge
+    fn delete(&self, surcharge_id: i32) -> RepoResult<Surcharge> {
+        acl::check(&*self.acl, Resource::Surcharges, Action::Delete, self, None)?;
+
+        let filtered = DslSurcharges::surcharges.filter(DslSurcharges::id.eq(surcharge_id));
+        let query = diesel::delete(filtered);
+
+        query.get_result::<Surcharge>(self.db_conn).map_err(|e| {
+            Error::from(e)
+                .context(format!("Delete surcharge {} error occurred", surcharge_id))
+                .into()
+        })
+    }
+}
+
+impl<'a, T: DbConnection> CheckScope<Scope, Surcharge> for SurchargesRepoImpl<'a, T> {
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&Surcharge>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! Repo emptying the tables of the test environments before the fixtures are loaded.
+//! Built only with `test-endpoints` feature, production builds can not wipe the data
+
+use diesel;
+use diesel::query_dsl::RunQueryDsl;
+use errors::Error;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId;
+
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::types::{DbConnection, RepoResult};
+use models::authorization::*;
+use schema::countries::dsl as DslCountries;
+
+/// Tables emptied by the reset. Roles, permissions, api keys and backfills are kept, so that the test users
+/// and the callers can still access the service after the reset
+const RESET_TABLES: &[&str] = &[
+    "availability_snapshots",
+    "carrier_accounts",
+    "companies",
+    "companies_packages",
+    "company_rates_quotas",
+    "company_suspensions",
+    "country_regulations",
+    "country_subdivisions",
+    "customs_info",
+    "delivery_quotes",
+    "package_templates",
+    "packages",
+    "pickup_points",
+    "pickups",
+    "postal_zones",
+    "pricing_rules",
+    "products",
+    "shipment_bookings",
+    "shipment_notifications",
+    "shipping_rates",
+    "shipping_rates_anomalies",
+    "shipping_restrictions",
+    "shipping_templates",
+    "store_margins",
+    "surcharges",
+    "user_addresses",
+    "zones",
+];
+
+/// Test fixtures repository, empties the tables of the test data
+pub trait TestFixturesRepo {
+    /// Deletes all rows of the test data tables and restarts their id sequences
+    fn reset(&self) -> RepoResult<()>;
+
+    /// Deletes the countries tree, it is replaced with the countries of the fixtures
+    fn delete_countries(&self) -> RepoResult<usize>;
+}
+
+/// Implementation of TestFixtures trait
+pub struct TestFixturesRepoImpl<'a, T: DbConnection> {
+    pub db_conn: &'a T,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, ()>>,
+}
+
+impl<'a, T: DbConnection> TestFixturesRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, ()>>) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: DbConnection> TestFixturesRepo for TestFixturesRepoImpl<'a, T> {
+    /// Deletes all rows of the test data tables and restarts their id sequences
+    fn reset(&self) -> RepoResult<()> {
+        acl::check(&*self.acl, Resource::TestFixtures, Action::Delete, self, None)?;
+
+        let query = diesel::sql_query(format!("TRUNCATE {} RESTART IDENTITY CASCADE", RESET_TABLES.join(", ")));
+
+        query
+            .execute(self.db_conn)
+            .map(|_| ())
+            .map_err(|e| Error::from(e).context("Reset of the test data tables error occurred").into())
+    }
+
+    /// Deletes the countries tree, it is replaced with the countries of the fixtures
+    fn delete_countries(&self) -> RepoResult<usize> {
+        acl::check(&*self.acl, Resource::TestFixtures, Action::Delete, self, None)?;
+
+        let query = diesel::delete(DslCountries::countries);
+
+        query
+            .execute(self.db_conn)
+            .map_err(|e| Error::from(e).context("Delete countries tree error occurred").into())
+    }
+}
+
+impl<'a, T: DbConnection> CheckScope<Scope, ()> for TestFixturesRepoImpl<'a, T> {
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&()>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
@@ -1,6 +1,17 @@
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
 use failure::Error as FailureError;
 use futures::future::Future;
 
 /// Repos layer Future
-pub type RepoFuture<T> = Box<Future<Item = T, Error = FailureError> + Send>;
+pub type RepoFuture<T> = Box<dyn Future<Item = T, Error = FailureError> + Send>;
 pub type RepoResult<T> = Result<T, FailureError>;
+
+/// Shorthand for the diesel connection bound of the repos, services and the repos factory, so that it
+/// is not repeated in every impl. It is not a backend abstraction: the repos build Postgres queries,
+/// so every connection must still be a Postgres one. Other storages are plugged in by their own
+/// `ReposFactory`, like the in-memory one of the tests, not by another `DbConnection`
+pub trait DbConnection: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static {}
+
+impl<T> DbConnection for T where T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static {}
@@ -3,11 +3,8 @@
 //! relationship
 
 use diesel;
-use diesel::connection::AnsiTransactionManager;
-use diesel::pg::Pg;
 use diesel::prelude::*;
 use diesel::query_dsl::RunQueryDsl;
-use diesel::Connection;
 use errors::Error;
 use failure::Error as FailureError;
 
@@ -16,16 +13,19 @@ use stq_types::UserId;
 use repos::legacy_acl::*;
 
 use super::acl;
-use super::types::RepoResult;
+use super::types::{DbConnection, RepoResult};
 use models::authorization::*;
 use models::{NewUserAddress, UpdateUserAddress, UserAddress};
 use schema::user_addresses::dsl::*;
 
 /// UserAddress repository for handling UserAddress
 pub trait UserAddressesRepo {
-    /// Returns list of user_address for a specific user
+    /// Returns list of user_address for a specific user, the default address goes first
     fn list_for_user(&self, user_id: UserId) -> RepoResult<Vec<UserAddress>>;
 
+    /// Returns user delivery address by id
+    fn find(&self, id: i32) -> RepoResult<Option<UserAddress>>;
+
     /// Create a new user delivery address
     fn create(&self, payload: NewUserAddress) -> RepoResult<UserAddress>;
 
@@ -34,26 +34,29 @@ pub trait UserAddressesRepo {
 
     /// Delete user delivery address
     fn delete(&self, id: i32) -> RepoResult<UserAddress>;
+
+    /// Make user delivery address the default one, other addresses of the user stop being default
+    fn set_default(&self, id: i32) -> RepoResult<UserAddress>;
 }
 
 /// Implementation of UserAddress trait
-pub struct UserAddressesRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+pub struct UserAddressesRepoImpl<'a, T: DbConnection> {
     pub db_conn: &'a T,
-    pub acl: Box<Acl<Resource, Action, Scope, FailureError, UserAddress>>,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, UserAddress>>,
 }
 
-impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> UserAddressesRepoImpl<'a, T> {
-    pub fn new(db_conn: &'a T, acl: Box<Acl<Resource, Action, Scope, FailureError, UserAddress>>) -> Self {
+impl<'a, T: DbConnection> UserAddressesRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, UserAddress>>) -> Self {
         Self { db_conn, acl }
     }
 }
 
-impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> UserAddressesRepo
-    for UserAddressesRepoImpl<'a, T>
-{
-    /// Returns list of user_address for a specific user
+impl<'a, T: DbConnection> UserAddressesRepo for UserAddressesRepoImpl<'a, T> {
+    /// Returns list of user_address for a specific user, the default address goes first
     fn list_for_user(&self, user_id_value: UserId) -> RepoResult<Vec<UserAddress>> {
-        let query = user_addresses.filter(user_id.eq(user_id_value)).order(id.desc());
+        let query = user_addresses
+            .filter(user_id.eq(user_id_value))
+            .order((is_priority.desc(), id.desc()));
         query
             .get_results::<UserAddress>(self.db_conn)
             .map_err(|e| Error::from(e).into())
@@ -69,6 +72,23 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             })
     }
 
+    /// Returns user delivery address by id
+    fn find(&self, id_arg: i32) -> RepoResult<Option<UserAddress>> {
+        let query = user_addresses.find(id_arg);
+
+        query
+            .get_result::<UserAddress>(self.db_conn)
+            .optional()
+            .map_err(|e| Error::from(e).into())
+            .and_then(|address_: Option<UserAddress>| {
+                if let Some(ref address_) = address_ {
+                    acl::check(&*self.acl, Resource::UserAddresses, Action::Read, self, Some(address_))?;
+                }
+                Ok(address_)
+            })
+            .map_err(|e: FailureError| e.context(format!("Find delivery address {} error occurred", id_arg)).into())
+    }
+
     /// Create a new user delivery address
     fn create(&self, payload: NewUserAddress) -> RepoResult<UserAddress> {
         let mut exist_query = user_addresses
@@ -159,12 +179,22 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
         query
             .get_result(self.db_conn)
             .map_err(|e| Error::from(e).into())
-            .and_then(|address_: UserAddress| acl::check(&*self.acl, Resource::UserAddresses, Action::Update, self, Some(&address_)))
-            .and_then(|_| {
+            .and_then(|address_: UserAddress| {
+                acl::check(&*self.acl, Resource::UserAddresses, Action::Update, self, Some(&address_))?;
+                Ok(address_)
+            })
+            .and_then(|address_| {
                 let filter = user_addresses.filter(id.eq(id_arg));
 
-                let query = diesel::update(filter).set(&payload);
-                query.get_result::<UserAddress>(self.db_conn).map_err(From::from)
+                // subdivision of the previous country does not belong to the new one
+                let moves_to_other_country = payload.country_code.is_some() && payload.country_code != address_.country_code;
+                if moves_to_other_country && payload.subdivision.is_none() && address_.subdivision.is_some() {
+                    let query = diesel::update(filter).set((&payload, subdivision.eq(None::<String>)));
+                    query.get_result::<UserAddress>(self.db_conn).map_err(From::from)
+                } else {
+                    let query = diesel::update(filter).set(&payload);
+                    query.get_result::<UserAddress>(self.db_conn).map_err(From::from)
+                }
             })
             .and_then(|updated_address| {
                 if let Some(is_priority_arg) = payload.is_priority {
@@ -198,11 +228,38 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             })
             .map_err(|e: FailureError| e.context(format!("Delete delivery address {} error occurred", id_arg)).into())
     }
+
+    /// Make user delivery address the default one, other addresses of the user stop being default
+    fn set_default(&self, id_arg: i32) -> RepoResult<UserAddress> {
+        let query = user_addresses.find(id_arg);
+
+        query
+            .get_result(self.db_conn)
+            .map_err(|e| Error::from(e).into())
+            .and_then(|address_: UserAddress| {
+                acl::check(&*self.acl, Resource::UserAddresses, Action::Update, self, Some(&address_))?;
+                Ok(address_)
+            })
+            .and_then(|address_| {
+                let others = user_addresses.filter(user_id.eq(address_.user_id).and(id.ne(address_.id)));
+                diesel::update(others)
+                    .set(is_priority.eq(false))
+                    .execute(self.db_conn)
+                    .map_err(From::from)
+            })
+            .and_then(|_| {
+                let filter = user_addresses.filter(id.eq(id_arg));
+                let query = diesel::update(filter).set(is_priority.eq(true));
+                query.get_result::<UserAddress>(self.db_conn).map_err(From::from)
+            })
+            .map_err(|e: FailureError| {
+                e.context(format!("Set delivery address {} as default error occurred", id_arg))
+                    .into()
+            })
+    }
 }
 
-impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, UserAddress>
-    for UserAddressesRepoImpl<'a, T>
-{
+impl<'a, T: DbConnection> CheckScope<Scope, UserAddress> for UserAddressesRepoImpl<'a, T> {
     fn is_in_scope(&self, user_id_arg: UserId, scope: &Scope, obj: Option<&UserAddress>) -> bool {
         match *scope {
             Scope::All => true,
@@ -3,11 +3,8 @@
 //! relationship
 
 use diesel;
-use diesel::connection::AnsiTransactionManager;
-use diesel::pg::Pg;
 use diesel::prelude::*;
 use diesel::query_dsl::RunQueryDsl;
-use diesel::Connection;
 use errors::Error;
 use failure::Error as FailureError;
 use failure::Fail;
@@ -16,12 +13,29 @@ use stq_cache::cache::Cache;
 use stq_types::{DeliveryRole, RoleId, UserId};
 
 use models::authorization::*;
-use models::{NewUserRole, UserRole};
+use models::{NewUserRole, RoleData, UserRole};
 use repos::legacy_acl::*;
-use repos::types::RepoResult;
+use repos::types::{DbConnection, RepoResult};
 use repos::RolesCacheImpl;
+use request_context::log_line;
 use schema::roles::dsl::*;
 
+/// Typed data of all roles of the user, `Owned` scope checks of store manager resources are based on it
+pub fn get_user_roles_data<T>(db_conn: &T, user_id_arg: UserId) -> RepoResult<Vec<RoleData>>
+where
+    T: DbConnection,
+{
+    roles
+        .filter(user_id.eq(user_id_arg))
+        .get_results::<UserRole>(db_conn)
+        .map(|user_roles| user_roles.iter().filter_map(UserRole::role_data).collect())
+        .map_err(|e| {
+            Error::from(e)
+                .context(format!("List roles data of user {} error occurred", user_id_arg))
+                .into()
+        })
+}
+
 /// UserRoles repository for handling UserRoles
 pub trait UserRolesRepo {
     /// Returns list of user_roles for a specific user
@@ -41,9 +55,9 @@ pub trait UserRolesRepo {
 pub struct UserRolesRepoImpl<'a, C, T>
 where
     C: Cache<Vec<DeliveryRole>>,
-    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    T: DbConnection,
 {
-    pub acl: Box<Acl<Resource, Action, Scope, FailureError, UserRole>>,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, UserRole>>,
     pub db_conn: &'a T,
     pub roles_cache: Arc<RolesCacheImpl<C>>,
 }
@@ -51,11 +65,11 @@ where
 impl<'a, C, T> UserRolesRepoImpl<'a, C, T>
 where
     C: Cache<Vec<DeliveryRole>>,
-    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    T: DbConnection,
 {
     pub fn new(
         db_conn: &'a T,
-        acl: Box<Acl<Resource, Action, Scope, FailureError, UserRole>>,
+        acl: Box<dyn Acl<Resource, Action, Scope, FailureError, UserRole>>,
         roles_cache: Arc<RolesCacheImpl<C>>,
     ) -> Self {
         Self { acl, db_conn, roles_cache }
@@ -65,11 +79,11 @@ where
 impl<'a, C, T> UserRolesRepo for UserRolesRepoImpl<'a, C, T>
 where
     C: Cache<Vec<DeliveryRole>>,
-    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    T: DbConnection,
 {
     /// Returns list of user_roles for a specific user
     fn list_for_user(&self, user_id_value: UserId) -> RepoResult<Vec<DeliveryRole>> {
-        debug!("list user roles for id {}.", user_id_value);
+        debug!("{}", log_line(&format!("list user roles for id {}.", user_id_value)));
 
         if let Some(user_roles) = self.roles_cache.get(user_id_value) {
             Ok(user_roles)
@@ -98,7 +112,7 @@ where
 
     /// Create a new user role
     fn create(&self, payload: NewUserRole) -> RepoResult<UserRole> {
-        debug!("create new user role {:?}.", payload);
+        debug!("{}", log_line(&format!("create new user role {:?}.", payload)));
         self.roles_cache.remove(payload.user_id);
         let query = diesel::insert_into(roles).values(&payload);
         query
@@ -108,7 +122,7 @@ where
 
     /// Delete roles of a user
     fn delete_by_user_id(&self, user_id_arg: UserId) -> RepoResult<Vec<UserRole>> {
-        debug!("delete user {} role.", user_id_arg);
+        debug!("{}", log_line(&format!("delete user {} role.", user_id_arg)));
         self.roles_cache.remove(user_id_arg);
         let filtered = roles.filter(user_id.eq(user_id_arg));
         let query = diesel::delete(filtered);
@@ -121,7 +135,7 @@ where
 
     /// Delete user roles by id
     fn delete_by_id(&self, id_arg: RoleId) -> RepoResult<UserRole> {
-        debug!("delete user role by id {}.", id_arg);
+        debug!("{}", log_line(&format!("delete user role by id {}.", id_arg)));
         let filtered = roles.filter(id.eq(id_arg));
         let query = diesel::delete(filtered);
         query
@@ -137,7 +151,7 @@ where
 impl<'a, C, T> CheckScope<Scope, UserRole> for UserRolesRepoImpl<'a, C, T>
 where
     C: Cache<Vec<DeliveryRole>>,
-    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    T: DbConnection,
 {
     fn is_in_scope(&self, user_id_arg: UserId, scope: &Scope, obj: Option<&UserRole>) -> bool {
         match *scope {
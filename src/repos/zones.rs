@@ -0,0 +1,226 @@
+//! Repo for zones table. Zone is a named set of countries that
+//! shipping rates and packages can be delivered to
+
+use diesel;
+use diesel::dsl::sql;
+use diesel::pg::expression::dsl::any;
+use diesel::pg::types::sql_types::Array;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::sql_types::{Bool, VarChar};
+use errors::Error;
+use failure::Error as FailureError;
+
+use stq_types::{Alpha3, UserId};
+
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::types::{DbConnection, RepoResult};
+use models::authorization::*;
+use models::{NewZone, PackagesRaw, UpdateZone, Zone, ZoneRaw};
+use schema::zones::dsl as DslZones;
+
+/// Zones repository for handling Zones
+pub trait ZonesRepo {
+    /// Returns list of zones
+    fn list(&self) -> RepoResult<Vec<Zone>>;
+
+    /// Find specific zone by id
+    fn find(&self, zone_id: i32) -> RepoResult<Option<Zone>>;
+
+    /// Returns zones with any of the names, the names are compared ignoring the case
+    fn find_by_names(&self, names: Vec<String>) -> RepoResult<Vec<Zone>>;
+
+    /// Create a new zone
+    fn create(&self, payload: NewZone) -> RepoResult<Zone>;
+
+    /// Update a zone
+    fn update(&self, zone_id: i32, payload: UpdateZone) -> RepoResult<Zone>;
+
+    /// Delete a zone
+    fn delete(&self, zone_id: i32) -> RepoResult<Zone>;
+}
+
+/// Implementation of Zones trait
+pub struct ZonesRepoImpl<'a, T: DbConnection> {
+    pub db_conn: &'a T,
+    pub acl: Box<dyn Acl<Resource, Action, Scope, FailureError, Zone>>,
+}
+
+impl<'a, T: DbConnection> ZonesRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: Box<dyn Acl<Resource, Action, Scope, FailureError, Zone>>) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+/// Loads zones with the given ids without ACL checks.
+/// Used by repos resolving zone references of packages and shipping rates.
+pub fn get_zones_by_ids<T>(db_conn: &T, zone_ids: Vec<i32>) -> RepoResult<Vec<Zone>>
+where
+    T: DbConnection,
+{
+    if zone_ids.is_empty() {
+        return Ok(vec![]);
+    }
+
+    DslZones::zones
+        .filter(DslZones::id.eq(any(zone_ids.clone())))
+        .get_results::<ZoneRaw>(db_conn)
+        .map_err(|e| Error::from(e).into())
+        .and_then(|zones| zones.into_iter().map(ZoneRaw::to_model).collect::<RepoResult<Vec<_>>>())
+        .map_err(|e: FailureError| e.context(format!("get zones by ids {:?} error occurred", zone_ids)).into())
+}
+
+/// Returns ids of the zones containing any of the countries without ACL checks
+pub fn get_zone_ids_with_countries<T>(db_conn: &T, countries: &[Alpha3]) -> RepoResult<Vec<i32>>
+where
+    T: DbConnection,
+{
+    let pg_countries: Vec<String> = countries.iter().map(|country| country.0.clone()).collect();
+
+    DslZones::zones
+        .select(DslZones::id)
+        .filter(sql::<Bool>("countries ?| ").bind::<Array<VarChar>, _>(pg_countries))
+        .get_results::<i32>(db_conn)
+        .map_err(|e| Error::from(e).into())
+        .map_err(|e: FailureError| e.context(format!("get zones with countries {:?} error occurred", countries)).into())
+}
+
+/// Loads zones referenced by `deliveries_to_zones` of the packages without ACL checks
+pub fn get_zones_for_packages<T>(db_conn: &T, packages: &[PackagesRaw]) -> RepoResult<Vec<Zone>>
+where
+    T: DbConnection,
+{
+    let mut zone_ids = vec![];
+    for package in packages {
+        zone_ids.extend_from_slice(package.get_deliveries_to_zones());
+    }
+    zone_ids.sort();
+    zone_ids.dedup();
+
+    get_zones_by_ids(db_conn, zone_ids)
+}
+
+impl<'a, T: DbConnection> ZonesRepo for ZonesRepoImpl<'a, T> {
+    /// Returns list of zones
+    fn list(&self) -> RepoResult<Vec<Zone>> {
+        let query = DslZones::zones.order(DslZones::name);
+
+        query
+            .get_results::<ZoneRaw>(self.db_conn)
+            .map_err(|e| Error::from(e).into())
+            .and_then(|zones| zones.into_iter().map(ZoneRaw::to_model).collect::<RepoResult<Vec<_>>>())
+            .and_then(|zones| {
+                for zone in &zones {
+                    acl::check(&*self.acl, Resource::Zones, Action::Read, self, Some(zone))?;
+                }
+                Ok(zones)
+            })
+            .map_err(|e: FailureError| e.context("list of zones error occurred").into())
+    }
+
+    /// Find specific zone by id
+    fn find(&self, zone_id: i32) -> RepoResult<Option<Zone>> {
+        let query = DslZones::zones.find(zone_id);
+
+        query
+            .get_result::<ZoneRaw>(self.db_conn)
+            .optional()
+            .map_err(|e| Error::from(e).into())
+            .and_then(|zone| match zone {
+                Some(zone) => {
+                    let zone = zone.to_model()?;
+                    acl::check(&*self.acl, Resource::Zones, Action::Read, self, Some(&zone))?;
+                    Ok(Some(zone))
+                }
+                None => Ok(None),
+            })
+            .map_err(|e: FailureError| e.context(format!("Find zone with id {} error occurred", zone_id)).into())
+    }
+
+    /// Returns zones with any of the names, the names are compared ignoring the case
+    fn find_by_names(&self, names: Vec<String>) -> RepoResult<Vec<Zone>> {
+        if names.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let lowercase_names: Vec<String> = names.iter().map(|name| name.to_lowercase()).collect();
+        let query = DslZones::zones
+            .filter(
+                sql::<Bool>("lower(name) = ANY(")
+                    .bind::<Array<VarChar>, _>(lowercase_names)
+                    .sql(")"),
+            )
+            .order(DslZones::name);
+
+        query
+            .get_results::<ZoneRaw>(self.db_conn)
+            .map_err(|e| Error::from(e).into())
+            .and_then(|zones| zones.into_iter().map(ZoneRaw::to_model).collect::<RepoResult<Vec<_>>>())
+            .and_then(|zones| {
+                for zone in &zones {
+                    acl::check(&*self.acl, Resource::Zones, Action::Read, self, Some(zone))?;
+                }
+                Ok(zones)
+            })
+            .map_err(|e: FailureError| e.context(format!("Find zones with names {:?} error occurred", names)).into())
+    }
+
+    /// Create a new zone
+    fn create(&self, payload: NewZone) -> RepoResult<Zone> {
+        acl::check(&*self.acl, Resource::Zones, Action::Create, self, None)?;
+
+        payload
+            .clone()
+            .to_raw()
+            .and_then(|payload_raw| {
+                let query = diesel::insert_into(DslZones::zones).values(&payload_raw);
+                query.get_result::<ZoneRaw>(self.db_conn).map_err(|e| Error::from(e).into())
+            })
+            .and_then(ZoneRaw::to_model)
+            .map_err(|e: FailureError| e.context(format!("Create a new zone {:?} error occurred", payload)).into())
+    }
+
+    /// Update a zone
+    fn update(&self, zone_id: i32, payload: UpdateZone) -> RepoResult<Zone> {
+        acl::check(&*self.acl, Resource::Zones, Action::Update, self, None)?;
+
+        payload
+            .clone()
+            .to_raw()
+            .and_then(|payload_raw| {
+                let filter = DslZones::zones.filter(DslZones::id.eq(zone_id));
+                let query = diesel::update(filter).set(&payload_raw);
+                query.get_result::<ZoneRaw>(self.db_conn).map_err(|e| Error::from(e).into())
+            })
+            .and_then(ZoneRaw::to_model)
+            .map_err(|e: FailureError| {
+                e.context(format!("Update zone {} with payload {:?} error occurred", zone_id, payload))
+                    .into()
+            })
+    }
+
+    /// Delete a zone
+    fn delete(&self, zone_id: i32) -> RepoResult<Zone> {
+        acl::check(&*self.acl, Resource::Zones, Action::Delete, self, None)?;
+
+        let filtered = DslZones::zones.filter(DslZones::id.eq(zone_id));
+        let query = diesel::delete(filtered);
+
+        query
+            .get_result::<ZoneRaw>(self.db_conn)
+            .map_err(|e| Error::from(e).into())
+            .and_then(ZoneRaw::to_model)
+            .map_err(|e: FailureError| e.context(format!("Delete zone {} error occurred", zone_id)).into())
+    }
+}
+
+impl<'a, T: DbConnection> CheckScope<Scope, Zone> for ZonesRepoImpl<'a, T> {
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&Zone>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
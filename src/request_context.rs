@@ -0,0 +1,143 @@
+//! Request context is passed from the controller through services into repos and requests to other services,
+//! so that all log lines of a request carry its correlation token and can be found in Graylog together.
+//!
+//! Log lines are JSON objects with the context fields next to the message. Repos run synchronously on the
+//! pool threads, services make the context current there for the duration of the task, so repos log with it
+//! without having it passed to each of them.
+use std::cell::RefCell;
+
+use hyper::header::Headers;
+use serde_json::Value;
+
+use stq_types::{DeliveryRole, UserId};
+
+use models::ApiKeyScope;
+
+/// Header the correlation token is forwarded to other services in
+pub const CORRELATION_TOKEN_HEADER: &str = "Correlation-Token";
+
+thread_local! {
+    static CURRENT: RefCell<Option<RequestContext>> = RefCell::new(None);
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RequestContext {
+    pub correlation_token: String,
+    pub user_id: Option<UserId>,
+    /// Endpoint of the request with ids replaced, e.g. `GET /companies/:id/packages`
+    pub route: String,
+    /// Roles of the user from the token of the request, `None` if they are to be loaded from db
+    pub roles: Option<Vec<DeliveryRole>>,
+    /// Scopes of the api key of the request made by another service, they replace the roles of the user
+    pub api_key_scopes: Option<Vec<ApiKeyScope>>,
+}
+
+impl RequestContext {
+    pub fn new(correlation_token: String, user_id: Option<UserId>, route: String) -> Self {
+        Self {
+            correlation_token,
+            user_id,
+            route,
+            roles: None,
+            api_key_scopes: None,
+        }
+    }
+
+    /// Context made current on this thread by `enter`, if any
+    pub fn current() -> Option<RequestContext> {
+        CURRENT.with(|current| current.borrow().clone())
+    }
+
+    /// Makes the context current on this thread until the returned guard is dropped
+    pub fn enter(&self) -> EnteredRequestContext {
+        let previous = CURRENT.with(|current| current.replace(Some(self.clone())));
+        EnteredRequestContext { previous }
+    }
+
+    /// Headers forwarding the correlation token in requests to other services
+    pub fn headers(&self) -> Headers {
+        let mut headers = Headers::new();
+        if !self.correlation_token.is_empty() {
+            headers.set_raw(CORRELATION_TOKEN_HEADER, self.correlation_token.clone());
+        }
+        headers
+    }
+
+    /// JSON log line with the message and the context fields
+    pub fn log_line(&self, message: &str) -> String {
+        self.log_line_with(message, json!({}))
+    }
+
+    /// Same as `log_line`, with additional fields of the event
+    pub fn log_line_with(&self, message: &str, fields: Value) -> String {
+        let mut line = json!({
+            "message": message,
+            "correlation_token": self.correlation_token,
+            "user_id": self.user_id.map(|user_id| user_id.0),
+            "route": self.route,
+        });
+
+        if let (Some(line), Value::Object(fields)) = (line.as_object_mut(), fields) {
+            line.extend(fields);
+        }
+
+        line.to_string()
+    }
+}
+
+/// Restores the previous context of the thread on drop
+pub struct EnteredRequestContext {
+    previous: Option<RequestContext>,
+}
+
+impl Drop for EnteredRequestContext {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+/// JSON log line with the message and the fields of the context current on this thread.
+/// Messages logged outside of requests, e.g. by scheduled jobs, have empty context fields
+pub fn log_line(message: &str) -> String {
+    RequestContext::current().unwrap_or_default().log_line(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+
+    use super::*;
+
+    fn context() -> RequestContext {
+        RequestContext::new("token".to_string(), Some(UserId(1)), "GET /companies/:id".to_string())
+    }
+
+    #[test]
+    fn log_line_contains_context_fields() {
+        let line = context().log_line_with("Request finished", json!({ "status": 200 }));
+
+        assert_eq!(
+            serde_json::from_str::<Value>(&line).unwrap(),
+            json!({
+                "message": "Request finished",
+                "correlation_token": "token",
+                "user_id": 1,
+                "route": "GET /companies/:id",
+                "status": 200,
+            })
+        );
+    }
+
+    #[test]
+    fn entered_context_is_current_until_dropped() {
+        assert_eq!(RequestContext::current(), None);
+
+        {
+            let _entered = context().enter();
+            assert_eq!(RequestContext::current(), Some(context()));
+        }
+
+        assert_eq!(RequestContext::current(), None);
+    }
+}
@@ -0,0 +1,76 @@
+//! Timeout of the request handling. Requests taking longer get 503 instead of holding the connection
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::{self, Either};
+use futures::Future;
+use hyper;
+use hyper::server::{Request, Response, Service};
+use hyper::{Method, StatusCode};
+use stq_router::RouteParser;
+use tokio_core::reactor::{Handle, Timeout};
+
+use controller::routes::Route;
+use slo::RouteGroup;
+
+/// Answers 503 if the inner service does not respond in time, requests are never timed out without the timeout set.
+/// The work of the timed out request is not cancelled, it keeps running on the pools, so only the reads are timed out:
+/// changes and long-running admin requests, e.g. rates replacements and imports, are committed after the client got 503
+pub struct RequestTimeout<S> {
+    inner: S,
+    timeout: Option<Duration>,
+    handle: Handle,
+    route_parser: Arc<RouteParser<Route>>,
+}
+
+impl<S> RequestTimeout<S> {
+    pub fn new(inner: S, timeout: Option<Duration>, handle: Handle, route_parser: Arc<RouteParser<Route>>) -> Self {
+        Self {
+            inner,
+            timeout,
+            handle,
+            route_parser,
+        }
+    }
+
+    fn is_timed_out(&self, req: &Request) -> bool {
+        let read = match *req.method() {
+            Method::Get | Method::Head => true,
+            _ => false,
+        };
+        read && self.route_parser.test(req.path()).and_then(|route| route.group()) != Some(RouteGroup::Admin)
+    }
+}
+
+impl<S> Service for RequestTimeout<S>
+where
+    S: Service<Request = Request, Response = Response, Error = hyper::Error>,
+    S::Future: 'static,
+{
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<dyn Future<Item = Response, Error = hyper::Error>>;
+
+    fn call(&self, req: Request) -> Self::Future {
+        let timeout = match self.timeout {
+            Some(timeout) if self.is_timed_out(&req) => timeout,
+            _ => return Box::new(self.inner.call(req)),
+        };
+        let timeout = match Timeout::new(timeout, &self.handle) {
+            Ok(timeout) => timeout,
+            Err(e) => return Box::new(future::err(e.into())),
+        };
+
+        let path = req.path().to_string();
+        Box::new(self.inner.call(req).select2(timeout).then(move |res| match res {
+            Ok(Either::A((res, _))) => Ok(res),
+            Ok(Either::B(_)) => {
+                warn!("Request to {} timed out", path);
+                Ok(Response::new().with_status(StatusCode::ServiceUnavailable))
+            }
+            Err(Either::A((e, _))) => Err(e),
+            Err(Either::B((e, _))) => Err(e.into()),
+        }))
+    }
+}
@@ -0,0 +1,163 @@
+//! Caching headers of the responses of the hot availability endpoints, so that the API gateway and the CDN
+//! absorb repeat requests for the same product.
+//!
+//! Destination, measurements and the other parameters of the availability are passed in the query string,
+//! which is a part of the cache key, so the responses only vary on `Authorization` and `X-Api-Key`: responses
+//! to authorized users and services carry the costs for managers and are private. Rates replaced by admins are removed from the internal cache
+//! at once, but not from the shared caches, so the TTL is kept short and never exceeds the TTL of the internal cache.
+use std::sync::Arc;
+
+use futures::Future;
+use hyper;
+use hyper::header::{Authorization, CacheControl, CacheDirective, Headers};
+use hyper::server::{Request, Response, Service};
+use hyper::{Method, StatusCode};
+use regex::Regex;
+
+use config;
+use controller::authentication::API_KEY_HEADER;
+
+/// Paths of the cached endpoints: availability of the base product for the user
+const CACHED_PATHS: &[&str] = &[r"^/v2/available_packages_for_user/\d+$"];
+
+pub struct ResponseCachingPolicy {
+    max_age_sec: Option<u32>,
+    paths: Vec<Regex>,
+}
+
+impl ResponseCachingPolicy {
+    /// Responses are not cached if response caching is not configured
+    pub fn new(config: &config::Config) -> Self {
+        let internal_ttl_sec = config.shipping_rates_cache.as_ref().map(|cache| cache.ttl_sec);
+        let max_age_sec = config.response_caching.as_ref().map(|caching| match internal_ttl_sec {
+            Some(internal_ttl_sec) => caching.max_age_sec.min(internal_ttl_sec),
+            None => caching.max_age_sec,
+        });
+
+        Self {
+            max_age_sec: max_age_sec.map(|max_age_sec| max_age_sec as u32),
+            paths: CACHED_PATHS
+                .iter()
+                .map(|pattern| Regex::new(pattern).expect("Invalid cached path pattern"))
+                .collect(),
+        }
+    }
+
+    /// `Cache-Control` of the successful response to the request, `None` if it is not cached
+    pub fn cache_control(&self, method: &Method, path: &str, authorized: bool) -> Option<CacheControl> {
+        let max_age_sec = self.max_age_sec?;
+        if *method != Method::Get || !self.paths.iter().any(|pattern| pattern.is_match(path)) {
+            return None;
+        }
+
+        let visibility = if authorized {
+            CacheDirective::Private
+        } else {
+            CacheDirective::Public
+        };
+        Some(CacheControl(vec![visibility, CacheDirective::MaxAge(max_age_sec)]))
+    }
+}
+
+/// Requests of the users and of the services with an api key may get the costs for managers
+fn is_authorized(headers: &Headers) -> bool {
+    headers.has::<Authorization<String>>() || headers.get_raw(API_KEY_HEADER).is_some()
+}
+
+/// Sets the caching headers on the successful responses of the inner service
+pub struct ResponseCaching<S> {
+    inner: S,
+    policy: Arc<ResponseCachingPolicy>,
+}
+
+impl<S> ResponseCaching<S> {
+    pub fn new(inner: S, policy: Arc<ResponseCachingPolicy>) -> Self {
+        Self { inner, policy }
+    }
+}
+
+impl<S> Service for ResponseCaching<S>
+where
+    S: Service<Request = Request, Response = Response, Error = hyper::Error>,
+    S::Future: 'static,
+{
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<dyn Future<Item = Response, Error = hyper::Error>>;
+
+    fn call(&self, req: Request) -> Self::Future {
+        let authorized = is_authorized(req.headers());
+        let cache_control = self.policy.cache_control(req.method(), req.path(), authorized);
+
+        Box::new(self.inner.call(req).map(move |mut res| {
+            if let Some(cache_control) = cache_control {
+                if res.status() == StatusCode::Ok {
+                    res.headers_mut().set(cache_control);
+                    res.headers_mut().set_raw("Vary", format!("Authorization, {}", API_KEY_HEADER));
+                }
+            }
+            res
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(max_age_sec: u32) -> ResponseCachingPolicy {
+        ResponseCachingPolicy {
+            max_age_sec: Some(max_age_sec),
+            paths: CACHED_PATHS.iter().map(|pattern| Regex::new(pattern).unwrap()).collect(),
+        }
+    }
+
+    #[test]
+    fn availability_is_cached_publicly_for_anonymous_users() {
+        let policy = policy(30);
+
+        assert_eq!(
+            policy.cache_control(&Method::Get, "/v2/available_packages_for_user/1", false),
+            Some(CacheControl(vec![CacheDirective::Public, CacheDirective::MaxAge(30)]))
+        );
+        assert_eq!(
+            policy.cache_control(&Method::Get, "/v2/available_packages_for_user/1", true),
+            Some(CacheControl(vec![CacheDirective::Private, CacheDirective::MaxAge(30)]))
+        );
+    }
+
+    #[test]
+    fn requests_with_api_key_are_authorized() {
+        let mut headers = Headers::new();
+        assert!(!is_authorized(&headers));
+
+        headers.set_raw(API_KEY_HEADER, "key");
+        assert!(is_authorized(&headers));
+
+        let mut headers = Headers::new();
+        headers.set(Authorization("token".to_string()));
+        assert!(is_authorized(&headers));
+    }
+
+    #[test]
+    fn other_requests_are_not_cached() {
+        let policy = policy(30);
+
+        assert_eq!(
+            policy.cache_control(&Method::Post, "/v2/available_packages_for_user/1", false),
+            None
+        );
+        assert_eq!(policy.cache_control(&Method::Get, "/available_packages_for_user/1", false), None);
+        assert_eq!(policy.cache_control(&Method::Get, "/companies/1", false), None);
+
+        let disabled = ResponseCachingPolicy {
+            max_age_sec: None,
+            ..policy
+        };
+        assert_eq!(
+            disabled.cache_control(&Method::Get, "/v2/available_packages_for_user/1", false),
+            None
+        );
+    }
+}
@@ -0,0 +1,149 @@
+//! Compression of the responses for the clients accepting it. Countries tree and package lists are hundreds of KB of JSON,
+//! gzip shrinks them several times. Small responses are sent as is, compressing them does not pay off
+use std::io::Write;
+
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+use futures::{future, Future, Stream};
+use hyper;
+use hyper::header::{AcceptEncoding, ContentEncoding, ContentLength, Encoding};
+use hyper::server::{Request, Response, Service};
+
+/// Responses smaller than this are not compressed
+const MIN_COMPRESSED_SIZE_BYTES: usize = 1024;
+
+/// Encoding of the response, gzip is preferred if the client accepts both with the same quality
+fn preferred_encoding(accept_encoding: Option<&AcceptEncoding>) -> Option<Encoding> {
+    let accept_encoding = accept_encoding?;
+
+    accept_encoding
+        .iter()
+        .filter(|item| item.quality > hyper::header::q(0))
+        .filter(|item| item.item == Encoding::Gzip || item.item == Encoding::Deflate)
+        .fold(
+            None,
+            |preferred: Option<&hyper::header::QualityItem<Encoding>>, item| match preferred {
+                Some(preferred)
+                    if preferred.quality > item.quality || (preferred.quality == item.quality && preferred.item == Encoding::Gzip) =>
+                {
+                    Some(preferred)
+                }
+                _ => Some(item),
+            },
+        )
+        .map(|item| item.item.clone())
+}
+
+/// HTTP deflate is the zlib format
+fn compress(body: &[u8], encoding: &Encoding) -> Option<Vec<u8>> {
+    match *encoding {
+        Encoding::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(body).ok()?;
+            encoder.finish().ok()
+        }
+        Encoding::Deflate => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(body).ok()?;
+            encoder.finish().ok()
+        }
+        _ => None,
+    }
+}
+
+/// Compresses the responses of the inner service with the encoding the client prefers
+pub struct ResponseCompression<S> {
+    inner: S,
+}
+
+impl<S> ResponseCompression<S> {
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+impl<S> Service for ResponseCompression<S>
+where
+    S: Service<Request = Request, Response = Response, Error = hyper::Error>,
+    S::Future: 'static,
+{
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<dyn Future<Item = Response, Error = hyper::Error>>;
+
+    fn call(&self, req: Request) -> Self::Future {
+        let encoding = preferred_encoding(req.headers().get::<AcceptEncoding>());
+
+        Box::new(self.inner.call(req).and_then(move |res| -> Self::Future {
+            let encoding = match encoding {
+                Some(ref encoding) if !res.headers().has::<ContentEncoding>() => encoding.clone(),
+                _ => return Box::new(future::ok(res)),
+            };
+
+            let status = res.status();
+            let mut headers = res.headers().clone();
+            Box::new(res.body().concat2().map(move |body| {
+                let vary = match headers.get_raw("Vary").and_then(|vary| vary.one()).map(String::from_utf8_lossy) {
+                    Some(vary) => format!("{}, Accept-Encoding", vary),
+                    None => "Accept-Encoding".to_string(),
+                };
+                headers.set_raw("Vary", vary);
+
+                let compressed = if body.len() >= MIN_COMPRESSED_SIZE_BYTES {
+                    compress(&body, &encoding)
+                } else {
+                    None
+                };
+                let body = match compressed {
+                    Some(compressed) => {
+                        headers.set(ContentEncoding(vec![encoding]));
+                        compressed
+                    }
+                    None => body.to_vec(),
+                };
+                headers.set(ContentLength(body.len() as u64));
+
+                Response::new().with_status(status).with_headers(headers).with_body(body)
+            }))
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+    use hyper::header::{q, qitem, QualityItem};
+
+    use super::*;
+
+    #[test]
+    fn gzip_is_preferred_over_deflate() {
+        let accept_encoding = AcceptEncoding(vec![qitem(Encoding::Deflate), qitem(Encoding::Gzip)]);
+        assert_eq!(preferred_encoding(Some(&accept_encoding)), Some(Encoding::Gzip));
+
+        let accept_encoding = AcceptEncoding(vec![QualityItem::new(Encoding::Gzip, q(500)), qitem(Encoding::Deflate)]);
+        assert_eq!(preferred_encoding(Some(&accept_encoding)), Some(Encoding::Deflate));
+    }
+
+    #[test]
+    fn unsupported_and_refused_encodings_are_not_used() {
+        let accept_encoding = AcceptEncoding(vec![qitem(Encoding::Brotli), QualityItem::new(Encoding::Gzip, q(0))]);
+        assert_eq!(preferred_encoding(Some(&accept_encoding)), None);
+        assert_eq!(preferred_encoding(None), None);
+    }
+
+    #[test]
+    fn gzip_body_is_decompressed_back() {
+        let body = "{\"alpha3\":\"XAL\"}".repeat(100);
+
+        let compressed = compress(body.as_bytes(), &Encoding::Gzip).unwrap();
+        assert!(compressed.len() < body.len());
+
+        let mut decompressed = String::new();
+        GzDecoder::new(compressed.as_slice()).read_to_string(&mut decompressed).unwrap();
+        assert_eq!(decompressed, body);
+    }
+}
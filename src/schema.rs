@@ -1,3 +1,48 @@
+table! {
+    api_keys (id) {
+        id -> Int4,
+        name -> Varchar,
+        key_hash -> Varchar,
+        scopes -> Jsonb,
+        created_at -> Timestamp,
+        revoked_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    availability_snapshots (base_product_id, delivery_to) {
+        base_product_id -> Int4,
+        delivery_to -> Varchar,
+        packages -> Jsonb,
+        taken_at -> Timestamp,
+    }
+}
+
+table! {
+    backfills (name) {
+        name -> Varchar,
+        status -> Varchar,
+        total_rows -> Int8,
+        processed_rows -> Int8,
+        last_processed_id -> Nullable<Int4>,
+        error -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    carrier_accounts (id) {
+        id -> Int4,
+        store_id -> Int4,
+        company_id -> Int4,
+        encrypted_account_number -> Varchar,
+        encrypted_credentials -> Varchar,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 table! {
     companies (id) {
         id -> Int4,
@@ -7,6 +52,8 @@ table! {
         deliveries_from -> Jsonb,
         logo -> Varchar,
         currency -> Varchar,
+        capabilities -> Jsonb,
+        label_translations -> Jsonb,
     }
 }
 
@@ -17,6 +64,38 @@ table! {
         package_id -> Int4,
         shipping_rate_source -> Varchar,
         dimensional_factor -> Nullable<Int4>,
+        restricted_hazard_classes -> Jsonb,
+        distance_pricing -> Nullable<Jsonb>,
+        delivery_time_min_days -> Nullable<Int4>,
+        delivery_time_max_days -> Nullable<Int4>,
+        rate_resolution_order -> Nullable<Jsonb>,
+        insurance_options -> Jsonb,
+        cod_supported -> Bool,
+        cod_fee -> Nullable<Float8>,
+        carrier_fallback -> Varchar,
+        fallback_markup_percent -> Nullable<Float8>,
+        daily_cutoff_time -> Nullable<Time>,
+        max_daily_shipments -> Nullable<Int4>,
+    }
+}
+
+table! {
+    company_rates_quotas (company_id) {
+        company_id -> Int4,
+        max_entries -> Int8,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    company_suspensions (id) {
+        id -> Int4,
+        company_id -> Int4,
+        company_package_id -> Nullable<Int4>,
+        starts_on -> Date,
+        ends_on -> Date,
+        reason -> Varchar,
+        created_at -> Timestamp,
     }
 }
 
@@ -31,6 +110,68 @@ table! {
     }
 }
 
+table! {
+    country_regulations (alpha3) {
+        alpha3 -> Varchar,
+        max_weight_g -> Nullable<Int4>,
+        prohibited_hazard_classes -> Jsonb,
+        source -> Varchar,
+        max_dimension_cm -> Nullable<Int4>,
+    }
+}
+
+table! {
+    country_subdivisions (code) {
+        code -> Varchar,
+        alpha3 -> Varchar,
+        name -> Varchar,
+    }
+}
+
+table! {
+    customs_info (id) {
+        id -> Int4,
+        base_product_id -> Int4,
+        store_id -> Int4,
+        hs_code -> Varchar,
+        description -> Varchar,
+        declared_value -> Float8,
+        currency -> Varchar,
+        origin_country -> Varchar,
+    }
+}
+
+table! {
+    delivery_quotes (id) {
+        id -> Uuid,
+        company_package_id -> Int4,
+        currency -> Varchar,
+        price -> Float8,
+        inputs -> Jsonb,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+    }
+}
+
+table! {
+    event_outbox (id) {
+        id -> Int4,
+        event -> Jsonb,
+        attempts -> Int4,
+        published_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    package_templates (id) {
+        id -> Int4,
+        from_alpha3 -> Varchar,
+        company_package_id -> Int4,
+        position -> Int4,
+    }
+}
+
 table! {
     packages (id) {
         id -> Int4,
@@ -40,6 +181,22 @@ table! {
         max_weight -> Int4,
         min_weight -> Int4,
         deliveries_to -> Jsonb,
+        deliveries_to_zones -> Jsonb,
+        name_translations -> Jsonb,
+    }
+}
+
+table! {
+    pickup_points (id) {
+        id -> Int4,
+        company_id -> Int4,
+        name -> Varchar,
+        country -> Varchar,
+        address -> Varchar,
+        latitude -> Float8,
+        longitude -> Float8,
+        opening_hours -> Jsonb,
+        price_factor -> Nullable<Float8>,
     }
 }
 
@@ -53,6 +210,29 @@ table! {
     }
 }
 
+table! {
+    postal_zones (id) {
+        id -> Int4,
+        alpha3 -> Varchar,
+        name -> Varchar,
+        ranges -> Jsonb,
+    }
+}
+
+table! {
+    pricing_rules (id) {
+        id -> Int4,
+        store_id -> Int4,
+        scope -> Varchar,
+        company_package_id -> Nullable<Int4>,
+        to_alpha3 -> Nullable<Varchar>,
+        kind -> Varchar,
+        direction -> Varchar,
+        value -> Float8,
+        priority -> Int4,
+    }
+}
+
 table! {
     products (id) {
         id -> Int4,
@@ -63,6 +243,20 @@ table! {
         deliveries_to -> Jsonb,
         shipping -> Varchar,
         currency -> Varchar,
+        hazard_classes -> Jsonb,
+        updated_at -> Timestamp,
+        free_delivery_over -> Nullable<Float8>,
+        delivery_from -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    role_permissions (id) {
+        id -> Int4,
+        role -> Varchar,
+        resource -> Varchar,
+        action -> Varchar,
+        scope -> Varchar,
     }
 }
 
@@ -75,13 +269,94 @@ table! {
     }
 }
 
+table! {
+    shipment_bookings (company_package_id, shipping_date) {
+        company_package_id -> Int4,
+        shipping_date -> Date,
+        shipments_count -> Int4,
+    }
+}
+
+table! {
+    shipment_notifications (id) {
+        id -> Int4,
+        event_id -> Varchar,
+        kind -> Varchar,
+        user_id -> Int4,
+        shipping_id -> Int4,
+        lang -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     shipping_rates (id) {
         id -> Int4,
         company_package_id -> Int4,
         from_alpha3 -> Varchar,
-        to_alpha3 -> Varchar,
+        to_alpha3 -> Nullable<Varchar>,
         rates -> Jsonb,
+        to_zone_id -> Nullable<Int4>,
+        delivery_time_min_days -> Nullable<Int4>,
+        delivery_time_max_days -> Nullable<Int4>,
+        is_active -> Bool,
+        components -> Jsonb,
+        to_subdivision -> Nullable<Varchar>,
+        to_postal_zone_id -> Nullable<Int4>,
+    }
+}
+
+table! {
+    shipping_rates_anomalies (id) {
+        id -> Int4,
+        shipping_rates_id -> Int4,
+        kind -> Varchar,
+        weight_g -> Int4,
+        price -> Float8,
+        deactivated -> Bool,
+        detected_at -> Timestamp,
+    }
+}
+
+table! {
+    shipping_restrictions (id) {
+        id -> Int4,
+        company_id -> Int4,
+        to_alpha3 -> Nullable<Varchar>,
+        category -> Nullable<Varchar>,
+        keyword -> Nullable<Varchar>,
+        restriction_type -> Varchar,
+        reason -> Varchar,
+    }
+}
+
+table! {
+    shipping_templates (id) {
+        id -> Int4,
+        store_id -> Int4,
+        name -> Varchar,
+        shipping -> Jsonb,
+        free_delivery_over -> Nullable<Float8>,
+    }
+}
+
+table! {
+    store_margins (store_id) {
+        store_id -> Int4,
+        kind -> Varchar,
+        value -> Float8,
+    }
+}
+
+table! {
+    surcharges (id) {
+        id -> Int4,
+        name -> Varchar,
+        kind -> Varchar,
+        value -> Float8,
+        company_id -> Nullable<Int4>,
+        company_package_id -> Nullable<Int4>,
+        to_alpha3 -> Nullable<Varchar>,
     }
 }
 
@@ -102,22 +377,74 @@ table! {
         created_at -> Timestamp,
         updated_at -> Timestamp,
         country_code -> Nullable<Varchar>,
+        latitude -> Nullable<Float8>,
+        longitude -> Nullable<Float8>,
+        subdivision -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    zones (id) {
+        id -> Int4,
+        name -> Varchar,
+        countries -> Jsonb,
     }
 }
 
+joinable!(carrier_accounts -> companies (company_id));
 joinable!(companies_packages -> companies (company_id));
 joinable!(companies_packages -> packages (package_id));
+joinable!(company_rates_quotas -> companies (company_id));
+joinable!(company_suspensions -> companies (company_id));
+joinable!(company_suspensions -> companies_packages (company_package_id));
+joinable!(delivery_quotes -> companies_packages (company_package_id));
+joinable!(package_templates -> companies_packages (company_package_id));
+joinable!(pickup_points -> companies (company_id));
+joinable!(pricing_rules -> companies_packages (company_package_id));
 joinable!(products -> companies_packages (company_package_id));
+joinable!(shipment_bookings -> companies_packages (company_package_id));
 joinable!(shipping_rates -> companies_packages (company_package_id));
+joinable!(shipping_rates -> country_subdivisions (to_subdivision));
+joinable!(shipping_rates -> postal_zones (to_postal_zone_id));
+joinable!(shipping_rates -> zones (to_zone_id));
+joinable!(shipping_rates_anomalies -> shipping_rates (shipping_rates_id));
+joinable!(shipping_restrictions -> companies (company_id));
+joinable!(surcharges -> companies (company_id));
+joinable!(surcharges -> companies_packages (company_package_id));
+joinable!(user_addresses -> country_subdivisions (subdivision));
 
 allow_tables_to_appear_in_same_query!(
+    api_keys,
+    availability_snapshots,
+    backfills,
+    carrier_accounts,
     companies,
     companies_packages,
+    company_rates_quotas,
+    company_suspensions,
     countries,
+    country_regulations,
+    country_subdivisions,
+    customs_info,
+    delivery_quotes,
+    event_outbox,
+    package_templates,
     packages,
+    pickup_points,
     pickups,
+    postal_zones,
+    pricing_rules,
     products,
+    role_permissions,
     roles,
+    shipment_bookings,
+    shipment_notifications,
     shipping_rates,
+    shipping_rates_anomalies,
+    shipping_restrictions,
+    shipping_templates,
+    store_margins,
+    surcharges,
     user_addresses,
+    zones,
 );
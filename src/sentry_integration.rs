@@ -2,6 +2,8 @@ use failure::Error;
 use sentry;
 use sentry::integrations::failure::capture_error;
 
+use request_context::RequestContext;
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct SentryConfig {
     pub dsn: String,
@@ -24,7 +26,13 @@ pub fn init(sentry_config: Option<&SentryConfig>) -> Option<sentry::internals::C
     })
 }
 
-pub fn log_and_capture_error(error: &Error) {
-    error!("Internal server error: {:?}", error);
+/// Logs a problem found by a background check and sends it to Sentry as a warning
+pub fn log_and_capture_warning(message: &str) {
+    warn!("{}", message);
+    sentry::capture_message(message, sentry::Level::Warning);
+}
+
+pub fn log_and_capture_error(error: &Error, request_context: &RequestContext) {
+    error!("{}", request_context.log_line(&format!("Internal server error: {:?}", error)));
     capture_error(error);
 }
@@ -0,0 +1,712 @@
+//! Checks that every service mutation is rejected with 403 for users
+//! that are not allowed to perform it: an unauthorized user and a store manager
+//! of another store (`MOCK_STORE_MANAGER_ID`).
+//!
+//! To cover a new mutation add it to the `acl_denied_tests!` invocation below,
+//! `every_mutation_is_covered` fails for the service trait methods changing data that are missing from it.
+//! The same store manager is allowed to do the store mutations on the own store
+//! (`MOCK_OTHER_STORE_ID`), so the denials come from the permissions matrix and not from the mocks.
+use std::fs;
+use std::sync::Arc;
+
+use base64;
+use chrono::NaiveDate;
+use futures::Future;
+use regex::Regex;
+use tokio_core::reactor::Core;
+
+use stq_http::errors::ErrorMessageWrapper;
+use stq_static_resources::Currency;
+use stq_types::*;
+
+use errors::Error;
+use models::*;
+use repos::repo_factory::tests::*;
+use services::admin_overview::AdminOverviewService;
+use services::api_keys::ApiKeysService;
+use services::backfills::BackfillsService;
+use services::carrier_accounts::CarrierAccountsService;
+use services::companies::CompaniesService;
+use services::companies_packages::{CompaniesPackagesService, ReplaceShippingRatesPayload};
+use services::company_rates_quotas::CompanyRatesQuotasService;
+use services::company_suspensions::CompanySuspensionsService;
+use services::countries::CountriesService;
+use services::country_regulations::CountryRegulationsService;
+use services::country_subdivisions::CountrySubdivisionsService;
+use services::customs_info::CustomsInfoService;
+use services::notifications::NotificationsService;
+use services::package_templates::PackageTemplatesService;
+use services::packages::PackagesService;
+use services::pickup_points::PickupPointsService;
+use services::postal_zones::PostalZonesService;
+use services::pricing_rules::PricingRulesService;
+use services::products::ProductsService;
+use services::shipping_exports::ShippingExportsService;
+use services::shipping_rates_anomalies::ShippingRatesAnomaliesService;
+use services::shipping_restrictions::ShippingRestrictionsService;
+use services::shipping_templates::ShippingTemplatesService;
+use services::store_managers::StoreManagersService;
+use services::store_margins::StoreMarginsService;
+use services::store_shipping_summaries::StoreShippingSummariesService;
+use services::surcharges::SurchargesService;
+use services::types::ServiceFuture;
+use services::user_addresses::UserAddressService;
+use services::user_roles::UserRolesService;
+use services::zones::ZonesService;
+use services::Service;
+
+type MockService = Service<MockConnection, MockConnectionManager, ReposFactoryMock>;
+
+fn assert_forbidden<T, F>(user_id: Option<UserId>, call: F)
+where
+    T: 'static,
+    F: FnOnce(&MockService) -> ServiceFuture<T>,
+{
+    let mut core = Core::new().unwrap();
+    let handle = Arc::new(core.handle());
+    let service = create_service(user_id, handle);
+    let work = call(&service).map(|_| ());
+    let err = core.run(work).expect_err("Mutation must be denied");
+    assert_eq!(
+        ErrorMessageWrapper::<Error>::from(&err).inner.code,
+        403,
+        "Unexpected error: {}",
+        err
+    );
+}
+
+fn assert_allowed<T, F>(user_id: Option<UserId>, call: F)
+where
+    T: 'static,
+    F: FnOnce(&MockService) -> ServiceFuture<T>,
+{
+    let mut core = Core::new().unwrap();
+    let handle = Arc::new(core.handle());
+    let service = create_service(user_id, handle);
+    let work = call(&service).map(|_| ());
+    if let Err(err) = core.run(work) {
+        panic!("Mutation must be allowed, got: {}", err);
+    }
+}
+
+macro_rules! acl_denied_tests {
+    ($($name:ident => $call:expr;)+) => {
+        $(
+            mod $name {
+                use super::*;
+
+                #[test]
+                fn unauthorized() {
+                    assert_forbidden(None, $call);
+                }
+
+                #[test]
+                fn wrong_store_user() {
+                    assert_forbidden(Some(MOCK_STORE_MANAGER_ID), $call);
+                }
+            }
+        )+
+    };
+}
+
+fn new_api_key() -> NewApiKey {
+    NewApiKey {
+        name: "orders".to_string(),
+        scopes: vec![ApiKeyScope {
+            resource: Resource::Products,
+            action: Action::Read,
+        }],
+    }
+}
+
+fn new_company() -> NewCompany {
+    NewCompany {
+        name: "UPS Russia".to_string(),
+        label: "UPS".to_string(),
+        description: None,
+        deliveries_from: vec![Alpha3("RUS".to_string())],
+        logo: "".to_string(),
+        currency: Currency::STQ,
+        capabilities: vec![],
+        label_translations: vec![],
+    }
+}
+
+fn update_company() -> UpdateCompany {
+    UpdateCompany {
+        name: None,
+        label: None,
+        description: None,
+        deliveries_from: None,
+        logo: None,
+        currency: None,
+        capabilities: None,
+        label_translations: None,
+    }
+}
+
+fn new_country() -> NewCountry {
+    NewCountry {
+        label: CountryLabel("Russia".to_string()),
+        level: 2,
+        alpha2: Alpha2("RU".to_string()),
+        alpha3: Alpha3("RUS".to_string()),
+        numeric: 643,
+        parent: None,
+    }
+}
+
+fn new_packages() -> NewPackages {
+    NewPackages {
+        name: "package1".to_string(),
+        max_size: 0,
+        min_size: 0,
+        max_weight: 0,
+        min_weight: 0,
+        deliveries_to: vec![],
+        deliveries_to_zones: vec![],
+        name_translations: vec![],
+    }
+}
+
+fn update_packages() -> UpdatePackages {
+    UpdatePackages {
+        name: None,
+        max_size: None,
+        min_size: None,
+        max_weight: None,
+        min_weight: None,
+        deliveries_to: None,
+        deliveries_to_zones: None,
+        name_translations: None,
+    }
+}
+
+fn new_company_package() -> NewCompanyPackage {
+    NewCompanyPackage {
+        company_id: CompanyId(1),
+        package_id: PackageId(1),
+        shipping_rate_source: None,
+        restricted_hazard_classes: vec![],
+        delivery_time_min_days: None,
+        delivery_time_max_days: None,
+        rate_resolution_order: None,
+        insurance_options: vec![],
+        cod_supported: false,
+        cod_fee: None,
+        carrier_fallback: CarrierFallback::StoredRates,
+        fallback_markup_percent: None,
+        daily_cutoff_time: None,
+        max_daily_shipments: None,
+    }
+}
+
+fn replace_shipping_rates_payload() -> ReplaceShippingRatesPayload {
+    ReplaceShippingRatesPayload {
+        rates_csv_base64: base64::encode("Weight,Zone\n,1\n0.5,100\n"),
+        zones_csv_base64: base64::encode("From,To,Zone\nRUS,USA,1\n"),
+        delivery_time_min_days: Some(3),
+        delivery_time_max_days: Some(7),
+    }
+}
+
+fn new_rate_components() -> NewRateComponents {
+    NewRateComponents {
+        delivery_from: Alpha3("RUS".to_string()),
+        components: vec![RateComponent {
+            kind: RateComponentKind::FuelSurcharge,
+            weight_g: None,
+            value_kind: SurchargeKind::Percentage,
+            value: 12.5,
+            effective_from: NaiveDate::from_ymd(2019, 3, 25),
+            effective_until: None,
+        }],
+    }
+}
+
+fn new_shipping() -> NewShipping {
+    NewShipping {
+        items: vec![],
+        pickup: None,
+        hazard_classes: vec![],
+    }
+}
+
+fn update_products() -> UpdateProducts {
+    UpdateProducts {
+        price: None,
+        deliveries_to: None,
+        shipping: None,
+        currency: None,
+        free_delivery_over: None,
+    }
+}
+
+fn new_user_address() -> NewUserAddress {
+    NewUserAddress {
+        user_id: MOCK_USER_ID,
+        administrative_area_level_1: None,
+        administrative_area_level_2: None,
+        country: "Russia".to_string(),
+        locality: None,
+        political: None,
+        postal_code: "0".to_string(),
+        route: None,
+        street_number: None,
+        address: None,
+        is_priority: false,
+        country_code: None,
+        latitude: None,
+        longitude: None,
+        subdivision: None,
+    }
+}
+
+fn update_user_address() -> UpdateUserAddress {
+    UpdateUserAddress {
+        administrative_area_level_1: None,
+        administrative_area_level_2: None,
+        country: None,
+        locality: None,
+        political: None,
+        postal_code: None,
+        route: None,
+        street_number: None,
+        address: None,
+        is_priority: None,
+        country_code: None,
+        latitude: None,
+        longitude: None,
+        subdivision: None,
+    }
+}
+
+fn new_surcharge() -> NewSurcharge {
+    NewSurcharge {
+        name: "fuel".to_string(),
+        kind: SurchargeKind::Percentage,
+        value: 5.0,
+        company_id: Some(CompanyId(1)),
+        company_package_id: None,
+        to_alpha3: None,
+    }
+}
+
+fn new_pricing_rule() -> NewPricingRule {
+    NewPricingRule {
+        store_id: MOCK_STORE_ID,
+        scope: PricingRuleScope::Store,
+        company_package_id: None,
+        to_alpha3: None,
+        kind: SurchargeKind::Percentage,
+        direction: PricingRuleDirection::Discount,
+        value: 10.0,
+        priority: 0,
+    }
+}
+
+fn update_pricing_rule() -> UpdatePricingRule {
+    UpdatePricingRule {
+        kind: None,
+        direction: None,
+        value: Some(15.0),
+        priority: None,
+    }
+}
+
+fn new_shipping_restriction() -> NewShippingRestriction {
+    NewShippingRestriction {
+        company_id: CompanyId(1),
+        to_alpha3: Some(Alpha3("RUS".to_string())),
+        category: Some("Perfume".to_string()),
+        keyword: None,
+        restriction_type: RestrictionType::Prohibited,
+        reason: "Flammable liquids".to_string(),
+    }
+}
+
+fn update_surcharge() -> UpdateSurcharge {
+    UpdateSurcharge {
+        name: None,
+        kind: None,
+        value: Some(7.5),
+    }
+}
+
+fn new_company_suspension() -> NewCompanySuspension {
+    NewCompanySuspension {
+        company_id: CompanyId(1),
+        company_package_id: None,
+        starts_on: NaiveDate::from_ymd(2019, 3, 20),
+        ends_on: NaiveDate::from_ymd(2019, 3, 22),
+        reason: "Strike".to_string(),
+    }
+}
+
+fn update_company_suspension() -> UpdateCompanySuspension {
+    UpdateCompanySuspension {
+        starts_on: None,
+        ends_on: Some(NaiveDate::from_ymd(2019, 3, 25)),
+        reason: None,
+    }
+}
+
+fn shipment_event() -> ShipmentEvent {
+    ShipmentEvent {
+        event_id: "order-1-dispatched".to_string(),
+        kind: ShipmentEventKind::ShipmentDispatched,
+        user_id: UserId(1),
+        shipping_id: ShippingId(1),
+        order_slug: "1".to_string(),
+        lang: None,
+        tracking_number: Some("RA123456789CN".to_string()),
+        delayed_until: None,
+    }
+}
+
+fn set_country_regulation() -> SetCountryRegulation {
+    SetCountryRegulation {
+        max_weight_g: Some(20000),
+        max_dimension_cm: None,
+        prohibited_hazard_classes: vec![HazardClass::LithiumBatteries],
+        source: "Russian Post".to_string(),
+    }
+}
+
+fn set_customs_info() -> SetCustomsInfo {
+    SetCustomsInfo {
+        store_id: MOCK_STORE_ID,
+        hs_code: "610910".to_string(),
+        description: "Cotton t-shirts".to_string(),
+        declared_value: 15.0,
+        currency: Currency::USD,
+        origin_country: Alpha3("CHN".to_string()),
+    }
+}
+
+fn create_carrier_account() -> CreateCarrierAccount {
+    CreateCarrierAccount {
+        company_id: CompanyId(1),
+        account_number: "ACC-1".to_string(),
+        credentials: "api-key-123".to_string(),
+    }
+}
+
+fn update_carrier_account() -> UpdateCarrierAccount {
+    UpdateCarrierAccount {
+        account_number: Some("ACC-2".to_string()),
+        credentials: None,
+    }
+}
+
+fn set_store_margin() -> SetStoreMargin {
+    SetStoreMargin {
+        kind: SurchargeKind::Percentage,
+        value: 10.0,
+    }
+}
+
+fn new_shipping_template() -> NewShippingTemplate {
+    NewShippingTemplate {
+        name: "Couriers".to_string(),
+        shipping: new_shipping(),
+        free_delivery_over: None,
+    }
+}
+
+fn update_shipping_template() -> UpdateShippingTemplate {
+    UpdateShippingTemplate {
+        name: Some("Couriers".to_string()),
+        shipping: None,
+        free_delivery_over: None,
+    }
+}
+
+fn new_pickup_point() -> NewPickupPoint {
+    NewPickupPoint {
+        company_id: CompanyId(1),
+        name: "Tverskaya".to_string(),
+        country: Alpha3("RUS".to_string()),
+        address: "Tverskaya st. 1, Moscow".to_string(),
+        coordinates: Coordinates {
+            latitude: 55.7575,
+            longitude: 37.6137,
+        },
+        opening_hours: vec![],
+        price_factor: None,
+    }
+}
+
+fn update_pickup_point() -> UpdatePickupPoint {
+    UpdatePickupPoint {
+        name: None,
+        address: Some("Tverskaya st. 2, Moscow".to_string()),
+        coordinates: None,
+        opening_hours: None,
+        price_factor: None,
+    }
+}
+
+fn new_package_template() -> NewPackageTemplate {
+    NewPackageTemplate {
+        from_alpha3: Alpha3("RUS".to_string()),
+        company_package_id: CompanyPackageId(1),
+        position: 0,
+    }
+}
+
+fn update_package_template() -> UpdatePackageTemplate {
+    UpdatePackageTemplate { position: Some(1) }
+}
+
+fn new_user_role() -> NewUserRole {
+    NewUserRole {
+        id: RoleId::new(),
+        user_id: MOCK_USER_ID,
+        name: DeliveryRole::Superuser,
+        data: None,
+    }
+}
+
+fn new_zone() -> NewZone {
+    NewZone {
+        name: "Europe".to_string(),
+        countries: vec![Alpha3("DEU".to_string())],
+    }
+}
+
+fn update_zone() -> UpdateZone {
+    UpdateZone {
+        name: None,
+        countries: None,
+    }
+}
+
+fn new_postal_zone() -> NewPostalZone {
+    NewPostalZone {
+        alpha3: Alpha3("AUS".to_string()),
+        name: "Remote".to_string(),
+        ranges: TypedJson(vec![PostalCodeRange {
+            from: "0872".to_string(),
+            to: "0872".to_string(),
+        }]),
+    }
+}
+
+fn import_postal_zone() -> ImportPostalZone {
+    ImportPostalZone {
+        alpha3: Alpha3("AUS".to_string()),
+        name: "Remote".to_string(),
+        postal_codes_csv_base64: base64::encode("From,To\n0872,\n"),
+    }
+}
+
+acl_denied_tests! {
+    issue_api_key => |service: &MockService| service.issue_api_key(new_api_key());
+    list_api_keys => |service: &MockService| service.list_api_keys();
+    revoke_api_key => |service: &MockService| service.revoke_api_key(1);
+
+    create_company => |service: &MockService| service.create_company(new_company());
+    update_company => |service: &MockService| service.update_company(CompanyId(1), update_company());
+    get_company_deletion_impact => |service: &MockService| service.get_company_deletion_impact(CompanyId(1));
+    delete_company => |service: &MockService| service.delete_company(CompanyId(1), false);
+
+    create_company_package => |service: &MockService| service.create_company_package(new_company_package());
+    delete_company_package => |service: &MockService| service.delete_company_package(CompanyId(1), PackageId(1), false);
+    replace_shipping_rates => |service: &MockService| service.replace_shipping_rates(CompanyPackageId(1), replace_shipping_rates_payload());
+    add_rate_components => |service: &MockService| service.add_rate_components(CompanyPackageId(1), new_rate_components());
+    set_rate_resolution_order => |service: &MockService| service.set_rate_resolution_order(CompanyPackageId(1), SetRateResolutionOrder { steps: None });
+    set_cash_on_delivery => |service: &MockService| service.set_cash_on_delivery(CompanyPackageId(1), SetCashOnDelivery { cod_supported: true, cod_fee: None });
+    set_carrier_fallback => |service: &MockService| service.set_carrier_fallback(CompanyPackageId(1), SetCarrierFallback { carrier_fallback: CarrierFallback::NoPrice, fallback_markup_percent: None });
+    set_shipping_capacity => |service: &MockService| service.set_shipping_capacity(CompanyPackageId(1), SetShippingCapacity { daily_cutoff_time: None, max_daily_shipments: Some(10) });
+    book_shipment => |service: &MockService| service.book_shipment(CompanyPackageId(1), BookShipment { order_time: None });
+    get_company_package_products => |service: &MockService| service.get_company_package_products(CompanyPackageId(1), 0, 10);
+
+    create_country => |service: &MockService| service.create_country(new_country());
+    import_countries => |service: &MockService| service.import_countries(ImportCountries { countries: Some(vec![]) });
+    repair_countries_tree => |service: &MockService| service.repair_countries_tree();
+
+    set_country_regulation => |service: &MockService| service.set_country_regulation(Alpha3("RUS".to_string()), set_country_regulation());
+    delete_country_regulation => |service: &MockService| service.delete_country_regulation(Alpha3("RUS".to_string()));
+    create_country_subdivision => |service: &MockService| service.create_country_subdivision(Alpha3("RUS".to_string()), NewCountrySubdivision { code: "RU-MOW".to_string(), name: "Moscow".to_string() });
+    update_country_subdivision => |service: &MockService| service.update_country_subdivision(Alpha3("RUS".to_string()), "RU-MOW".to_string(), UpdateCountrySubdivision { name: "Moscow".to_string() });
+    delete_country_subdivision => |service: &MockService| service.delete_country_subdivision(Alpha3("RUS".to_string()), "RU-MOW".to_string());
+
+    create_package => |service: &MockService| service.create_package(new_packages());
+    update_package => |service: &MockService| service.update_package(PackageId(1), update_packages());
+    delete_package => |service: &MockService| service.delete_package(PackageId(1));
+
+    upsert_products => |service: &MockService| service.upsert(MOCK_BASE_PRODUCT_ID, ShippingUpsert::Shipping(new_shipping()));
+    upsert_products_by_template => |service: &MockService| service.upsert(MOCK_BASE_PRODUCT_ID, ShippingUpsert::Template { store_id: MOCK_STORE_ID, template_id: 1 });
+    update_products => |service: &MockService| service.update_products(MOCK_BASE_PRODUCT_ID, CompanyPackageId(1), update_products());
+    delete_products => |service: &MockService| service.delete_products(MOCK_BASE_PRODUCT_ID);
+    clone_shipping => |service: &MockService| service.clone_shipping(MOCK_BASE_PRODUCT_ID, vec![BaseProductId(2)]);
+
+    list_surcharges => |service: &MockService| service.list_surcharges();
+    get_surcharge => |service: &MockService| service.get_surcharge(1);
+    create_surcharge => |service: &MockService| service.create_surcharge(new_surcharge());
+    update_surcharge => |service: &MockService| service.update_surcharge(1, update_surcharge());
+    delete_surcharge => |service: &MockService| service.delete_surcharge(1);
+
+    list_pricing_rules => |service: &MockService| service.list_pricing_rules();
+    get_pricing_rule => |service: &MockService| service.get_pricing_rule(1);
+    create_pricing_rule => |service: &MockService| service.create_pricing_rule(new_pricing_rule());
+    update_pricing_rule => |service: &MockService| service.update_pricing_rule(1, update_pricing_rule());
+    delete_pricing_rule => |service: &MockService| service.delete_pricing_rule(1);
+
+    create_shipping_restriction => |service: &MockService| service.create_shipping_restriction(new_shipping_restriction());
+    update_shipping_restriction => |service: &MockService| service.update_shipping_restriction(1, UpdateShippingRestriction { restriction_type: None, reason: None });
+    delete_shipping_restriction => |service: &MockService| service.delete_shipping_restriction(1);
+
+    get_company_rates_quota => |service: &MockService| service.get_company_rates_quota(CompanyId(1));
+    set_company_rates_quota => |service: &MockService| service.set_company_rates_quota(CompanyId(1), SetCompanyRatesQuota { max_entries: 1000 });
+    delete_company_rates_quota => |service: &MockService| service.delete_company_rates_quota(CompanyId(1));
+
+    list_company_suspensions => |service: &MockService| service.list_company_suspensions();
+    get_company_suspension => |service: &MockService| service.get_company_suspension(1);
+    create_company_suspension => |service: &MockService| service.create_company_suspension(new_company_suspension());
+    update_company_suspension => |service: &MockService| service.update_company_suspension(1, update_company_suspension());
+    delete_company_suspension => |service: &MockService| service.delete_company_suspension(1);
+
+    handle_shipment_event => |service: &MockService| service.handle_shipment_event(shipment_event());
+
+    set_customs_info => |service: &MockService| service.set_customs_info(BaseProductId(1), set_customs_info());
+    delete_customs_info => |service: &MockService| service.delete_customs_info(BaseProductId(1));
+
+    set_store_margin => |service: &MockService| service.set_store_margin(MOCK_STORE_ID, set_store_margin());
+    delete_store_margin => |service: &MockService| service.delete_store_margin(MOCK_STORE_ID);
+    create_carrier_account => |service: &MockService| service.create_carrier_account(MOCK_STORE_ID, create_carrier_account());
+    update_carrier_account => |service: &MockService| service.update_carrier_account(MOCK_STORE_ID, 1, update_carrier_account());
+    delete_carrier_account => |service: &MockService| service.delete_carrier_account(MOCK_STORE_ID, 1);
+    reencrypt_carrier_credentials => |service: &MockService| service.reencrypt_carrier_credentials();
+    grant_store_manager => |service: &MockService| service.grant_store_manager(MOCK_STORE_ID, UserId(3));
+    revoke_store_manager => |service: &MockService| service.revoke_store_manager(MOCK_STORE_ID, UserId(3));
+    get_store_shipping_summary => |service: &MockService| service.get_store_shipping_summary(MOCK_STORE_ID);
+    get_admin_overview => |service: &MockService| service.get_admin_overview();
+    check_diagnostics_access => |service: &MockService| service.check_diagnostics_access();
+
+    create_shipping_template => |service: &MockService| service.create_shipping_template(MOCK_STORE_ID, new_shipping_template());
+    update_shipping_template => |service: &MockService| service.update_shipping_template(MOCK_STORE_ID, 1, update_shipping_template());
+    delete_shipping_template => |service: &MockService| service.delete_shipping_template(MOCK_STORE_ID, 1);
+
+    export_store_shipping => |service: &MockService| service.export_store_shipping(MOCK_STORE_ID);
+    import_store_shipping => |service: &MockService| service.import_store_shipping(
+        MOCK_STORE_ID,
+        StoreShippingExport::new(MOCK_STORE_ID, vec![], vec![], vec![]),
+        ShippingImportConflict::Overwrite,
+    );
+
+    create_pickup_point => |service: &MockService| service.create_pickup_point(new_pickup_point());
+    update_pickup_point => |service: &MockService| service.update_pickup_point(1, update_pickup_point());
+    delete_pickup_point => |service: &MockService| service.delete_pickup_point(1);
+
+    create_package_template => |service: &MockService| service.create_package_template(new_package_template());
+    update_package_template => |service: &MockService| service.update_package_template(1, update_package_template());
+    delete_package_template => |service: &MockService| service.delete_package_template(1);
+
+    create_address => |service: &MockService| service.create_address(new_user_address());
+    update_address => |service: &MockService| service.update_address(1, update_user_address());
+    delete_address => |service: &MockService| service.delete_address(1);
+    set_default_address => |service: &MockService| service.set_default_address(1);
+
+    create_role => |service: &MockService| service.create_role(new_user_role());
+    delete_roles_by_user_id => |service: &MockService| service.delete_by_user_id(MOCK_USER_ID);
+    delete_role_by_id => |service: &MockService| service.delete_by_id(RoleId::new());
+    get_permissions => |service: &MockService| service.get_permissions();
+
+    create_zone => |service: &MockService| service.create_zone(new_zone());
+    update_zone => |service: &MockService| service.update_zone(1, update_zone());
+    delete_zone => |service: &MockService| service.delete_zone(1);
+    create_postal_zone => |service: &MockService| service.create_postal_zone(new_postal_zone());
+    import_postal_zone => |service: &MockService| service.import_postal_zone(import_postal_zone());
+    delete_postal_zone => |service: &MockService| service.delete_postal_zone(1);
+
+    list_backfills => |service: &MockService| service.list_backfills();
+    get_backfill => |service: &MockService| service.get_backfill("backfill".to_string());
+
+    list_shipping_rates_anomalies => |service: &MockService| service.list_shipping_rates_anomalies();
+}
+
+/// Prefixes of the names of the service methods changing data
+const MUTATION_PREFIXES: &[&str] = &[
+    "add_",
+    "book_",
+    "clone_",
+    "create_",
+    "delete_",
+    "grant_",
+    "handle_",
+    "import_",
+    "issue_",
+    "reencrypt_",
+    "repair_",
+    "replace_",
+    "reset_",
+    "revoke_",
+    "seed_",
+    "set_",
+    "update_",
+    "upsert",
+];
+
+/// Mutations allowed to every user, so there is nobody to deny them to
+const ALLOWED_TO_EVERYONE: &[&str] = &[
+    // quotes are requested by the buyers, including the unauthorized ones
+    "create_delivery_quote",
+];
+
+#[test]
+fn every_mutation_is_covered() {
+    let service_trait = Regex::new(r"(?s)pub trait \w+Service\b[^{]*\{(.*?)\n\}").unwrap();
+    let trait_method = Regex::new(r"\bfn (\w+)").unwrap();
+    let covered_method = Regex::new(r"service\.(\w+)\(").unwrap();
+
+    let covered = covered_method
+        .captures_iter(include_str!("acl_denied_tests.rs"))
+        .map(|captures| captures[1].to_string())
+        .collect::<Vec<_>>();
+
+    let services_dir = concat!(env!("CARGO_MANIFEST_DIR"), "/src/services");
+    let mut missing = vec![];
+    for entry in fs::read_dir(services_dir).unwrap() {
+        let path = entry.unwrap().path();
+        let source = fs::read_to_string(&path).unwrap();
+        for methods in service_trait.captures_iter(&source) {
+            for method in trait_method.captures_iter(&methods[1]) {
+                let method = &method[1];
+                let is_mutation = MUTATION_PREFIXES.iter().any(|prefix| method.starts_with(prefix));
+                if is_mutation && !ALLOWED_TO_EVERYONE.contains(&method) && !covered.iter().any(|covered| covered == method) {
+                    missing.push(format!("{} in {}", method, path.display()));
+                }
+            }
+        }
+    }
+
+    assert!(missing.is_empty(), "Mutations missing from acl_denied_tests!: {:?}", missing);
+}
+
+#[test]
+fn store_manager_is_allowed_on_own_store() {
+    let user_id = Some(MOCK_STORE_MANAGER_ID);
+    assert_allowed(user_id, |service: &MockService| {
+        service.set_store_margin(MOCK_OTHER_STORE_ID, set_store_margin())
+    });
+    assert_allowed(user_id, |service: &MockService| service.delete_store_margin(MOCK_OTHER_STORE_ID));
+    assert_allowed(user_id, |service: &MockService| {
+        service.grant_store_manager(MOCK_OTHER_STORE_ID, UserId(3))
+    });
+    assert_allowed(user_id, |service: &MockService| {
+        service.get_store_shipping_summary(MOCK_OTHER_STORE_ID)
+    });
+}
+
+#[cfg(feature = "test-endpoints")]
+mod test_fixtures {
+    use super::*;
+    use services::test_fixtures::TestFixturesService;
+
+    acl_denied_tests! {
+        seed_test_fixtures => |service: &MockService| service.seed_test_fixtures();
+        reset_test_fixtures => |service: &MockService| service.reset_test_fixtures();
+    }
+}
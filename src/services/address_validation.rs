@@ -0,0 +1,131 @@
+//! Validation of user delivery addresses against the countries tree and postal code formats.
+//! `validator` only checks that the address fields are not empty, this module checks that
+//! the country is known to the delivery service, the postal code is valid for that country
+//! and the subdivision of the address is one of the subdivisions of that country
+
+use std::collections::HashMap;
+
+use regex::{self, Regex};
+
+use config;
+use errors::Error;
+use models::{get_countries_by, normalize_subdivision_code};
+use repos::{CountriesRepo, CountrySubdivisionsRepo, RepoResult};
+
+pub struct AddressValidator {
+    /// Postal code formats by alpha3 code of the country
+    postal_codes: HashMap<String, Regex>,
+}
+
+impl AddressValidator {
+    /// Compiles postal code formats, countries without a format accept any postal code
+    pub fn new(config: Option<config::AddressValidation>) -> Result<Self, regex::Error> {
+        let mut postal_codes = HashMap::new();
+        for format in config.map(|config| config.postal_codes).unwrap_or_default() {
+            postal_codes.insert(format.country.0, Regex::new(&format.pattern)?);
+        }
+
+        Ok(Self { postal_codes })
+    }
+
+    /// Checks that the country with alpha2 or alpha3 code exists and the postal code matches its format.
+    /// Returns alpha3 code of the country, addresses are stored with alpha3 codes like the rest of the service
+    pub fn validate(&self, countries_repo: &dyn CountriesRepo, country_code: &str, postal_code: Option<&str>) -> RepoResult<String> {
+        let code = country_code.trim().to_uppercase();
+        let root = countries_repo.get_all()?;
+        let country = get_countries_by(&root, |country| country.alpha2.0 == code || country.alpha3.0 == code)
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                Error::Validate(validation_errors!({
+                    "country_code": ["country_code" => format!("Country with code {} is not supported", country_code)]
+                }))
+            })?;
+
+        if let (Some(postal_code), Some(format)) = (postal_code, self.postal_codes.get(&country.alpha3.0)) {
+            if !format.is_match(postal_code.trim()) {
+                return Err(Error::Validate(validation_errors!({
+                    "postal_code": ["postal_code" => format!("Postal code {} is not valid for country {}", postal_code, country.alpha3)]
+                }))
+                .into());
+            }
+        }
+
+        Ok(country.alpha3.0)
+    }
+
+    /// Checks that the subdivision with ISO 3166-2 code is a subdivision of the country with alpha3 code,
+    /// returns the code of the subdivision it is stored with
+    pub fn validate_subdivision(
+        &self,
+        country_subdivisions_repo: &dyn CountrySubdivisionsRepo,
+        country_alpha3: &str,
+        subdivision: &str,
+    ) -> RepoResult<String> {
+        let code = normalize_subdivision_code(subdivision);
+        let subdivision = country_subdivisions_repo
+            .get(code.clone())?
+            .filter(|subdivision| subdivision.alpha3.0 == country_alpha3)
+            .ok_or_else(|| {
+                Error::Validate(validation_errors!({
+                    "subdivision": ["subdivision" => format!("Subdivision {} is not a subdivision of country {}", code, country_alpha3)]
+                }))
+            })?;
+
+        Ok(subdivision.code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use stq_types::Alpha3;
+
+    use super::*;
+    use repos::repo_factory::tests::{CountriesRepoMock, CountrySubdivisionsRepoMock};
+
+    fn validator() -> AddressValidator {
+        AddressValidator::new(Some(config::AddressValidation {
+            postal_codes: vec![config::PostalCodeFormat {
+                country: Alpha3("RUS".to_string()),
+                pattern: r"^\d{6}$".to_string(),
+            }],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn country_code_is_normalized_to_alpha3() {
+        let countries_repo = CountriesRepoMock::default();
+        let validator = validator();
+
+        assert_eq!(validator.validate(&countries_repo, "ru", Some("123456")).unwrap(), "RUS");
+        assert_eq!(validator.validate(&countries_repo, "RUS", None).unwrap(), "RUS");
+        assert!(validator.validate(&countries_repo, "DE", None).is_err());
+    }
+
+    #[test]
+    fn postal_code_is_checked_against_country_format() {
+        let countries_repo = CountriesRepoMock::default();
+        let validator = validator();
+
+        assert!(validator.validate(&countries_repo, "RUS", Some("12345")).is_err());
+        assert!(validator.validate(&countries_repo, "RUS", Some("ABC123")).is_err());
+        assert!(AddressValidator::new(None)
+            .unwrap()
+            .validate(&countries_repo, "RUS", Some("ABC123"))
+            .is_ok());
+    }
+
+    #[test]
+    fn subdivision_must_be_of_address_country() {
+        let country_subdivisions_repo = CountrySubdivisionsRepoMock::default();
+        let validator = validator();
+
+        assert_eq!(
+            validator.validate_subdivision(&country_subdivisions_repo, "RUS", "ru-mow").unwrap(),
+            "RU-MOW"
+        );
+        assert!(validator.validate_subdivision(&country_subdivisions_repo, "USA", "RU-MOW").is_err());
+        assert!(validator.validate_subdivision(&country_subdivisions_repo, "RUS", "US-CA").is_err());
+    }
+}
@@ -0,0 +1,46 @@
+//! Admin overview Services, presents the counts of the shipping data on the home screen of the admin dashboard
+
+use r2d2::ManageConnection;
+
+use super::types::{Service, ServiceFuture};
+use models::AdminOverview;
+use repos::{DbConnection, ReposFactory};
+
+pub trait AdminOverviewService {
+    /// Returns overview of the shipping data for the admin dashboard
+    fn get_admin_overview(&self) -> ServiceFuture<AdminOverview>;
+
+    /// Resolves if the caller may see the pools, connections and carriers state of the service
+    fn check_diagnostics_access(&self) -> ServiceFuture<()>;
+}
+
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> AdminOverviewService for Service<T, M, F> {
+    /// Returns overview of the shipping data for the admin dashboard
+    fn get_admin_overview(&self) -> ServiceFuture<AdminOverview> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        // the query scans all products, so it is kept off the threads serving price and availability requests
+        self.spawn_on_admin_pool(move |conn| {
+            let admin_overview_repo = repo_factory.create_admin_overview_repo(&*conn, user_id);
+            admin_overview_repo.get().map_err(|e| {
+                e.context("Service AdminOverview, get_admin_overview endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Resolves if the caller may see the pools, connections and carriers state of the service
+    fn check_diagnostics_access(&self) -> ServiceFuture<()> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_admin_pool(move |conn| {
+            let admin_overview_repo = repo_factory.create_admin_overview_repo(&*conn, user_id);
+            admin_overview_repo.check_diagnostics_access().map_err(|e| {
+                e.context("Service AdminOverview, check_diagnostics_access endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+}
@@ -0,0 +1,74 @@
+//! Api keys Services, presents issuing and revoking of the keys of the internal callers and their authentication
+
+use r2d2::ManageConnection;
+
+use super::types::{Service, ServiceFuture};
+use models::{api_key_hash, generate_api_key, ApiKey, IssuedApiKey, NewApiKey};
+use repos::{ApiKeysRepo, DbConnection, ReposFactory};
+
+pub trait ApiKeysService {
+    /// Issues a key, the key itself is returned only here
+    fn issue_api_key(&self, payload: NewApiKey) -> ServiceFuture<IssuedApiKey>;
+    /// Returns all keys including the revoked ones
+    fn list_api_keys(&self) -> ServiceFuture<Vec<ApiKey>>;
+    /// Revokes the key, requests with it are rejected after that
+    fn revoke_api_key(&self, id: i32) -> ServiceFuture<Option<ApiKey>>;
+    /// Returns the active key of the request, `None` if the key is unknown or revoked
+    fn authenticate_api_key(&self, key: String) -> ServiceFuture<Option<ApiKey>>;
+}
+
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> ApiKeysService for Service<T, M, F> {
+    /// Issues a key, the key itself is returned only here
+    fn issue_api_key(&self, payload: NewApiKey) -> ServiceFuture<IssuedApiKey> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let api_keys_repo = repo_factory.create_api_keys_repo(&*conn, user_id);
+            let key = generate_api_key();
+            payload
+                .to_insert(&key)
+                .and_then(|payload| api_keys_repo.create(payload))
+                .map(|api_key| IssuedApiKey { api_key, key })
+                .map_err(|e| e.context("Service ApiKeys, issue_api_key endpoint error occured.").into())
+        })
+    }
+
+    /// Returns all keys including the revoked ones
+    fn list_api_keys(&self) -> ServiceFuture<Vec<ApiKey>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let api_keys_repo = repo_factory.create_api_keys_repo(&*conn, user_id);
+            api_keys_repo
+                .list()
+                .map_err(|e| e.context("Service ApiKeys, list_api_keys endpoint error occured.").into())
+        })
+    }
+
+    /// Revokes the key, requests with it are rejected after that
+    fn revoke_api_key(&self, id: i32) -> ServiceFuture<Option<ApiKey>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let api_keys_repo = repo_factory.create_api_keys_repo(&*conn, user_id);
+            api_keys_repo
+                .revoke(id)
+                .map_err(|e| e.context("Service ApiKeys, revoke_api_key endpoint error occured.").into())
+        })
+    }
+
+    /// Returns the active key of the request, `None` if the key is unknown or revoked
+    fn authenticate_api_key(&self, key: String) -> ServiceFuture<Option<ApiKey>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+
+        self.spawn_on_pool(move |conn| {
+            let api_keys_repo = repo_factory.create_api_keys_repo_with_sys_acl(&*conn);
+            api_keys_repo
+                .find_active_by_hash(&api_key_hash(&key))
+                .map_err(|e| e.context("Service ApiKeys, authenticate_api_key endpoint error occured.").into())
+        })
+    }
+}
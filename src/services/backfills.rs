@@ -0,0 +1,42 @@
+//! Backfills Services, presents monitoring of batched backfills progress
+
+use r2d2::ManageConnection;
+
+use super::types::{Service, ServiceFuture};
+use models::BackfillProgress;
+use repos::{DbConnection, ReposFactory};
+
+pub trait BackfillsService {
+    /// Returns progress of all backfills
+    fn list_backfills(&self) -> ServiceFuture<Vec<BackfillProgress>>;
+    /// Returns progress of backfill by name
+    fn get_backfill(&self, name: String) -> ServiceFuture<Option<BackfillProgress>>;
+}
+
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> BackfillsService for Service<T, M, F> {
+    /// Returns progress of all backfills
+    fn list_backfills(&self) -> ServiceFuture<Vec<BackfillProgress>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_admin_pool(move |conn| {
+            let backfills_repo = repo_factory.create_backfills_repo(&*conn, user_id);
+            backfills_repo
+                .list()
+                .map_err(|e| e.context("Service Backfills, list_backfills endpoint error occured.").into())
+        })
+    }
+
+    /// Returns progress of backfill by name
+    fn get_backfill(&self, name: String) -> ServiceFuture<Option<BackfillProgress>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_admin_pool(move |conn| {
+            let backfills_repo = repo_factory.create_backfills_repo(&*conn, user_id);
+            backfills_repo
+                .find(name)
+                .map_err(|e| e.context("Service Backfills, get_backfill endpoint error occured.").into())
+        })
+    }
+}
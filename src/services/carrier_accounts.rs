@@ -0,0 +1,156 @@
+//! Carrier accounts Services, presents CRUD operations with accounts of the stores with the carriers
+use r2d2::ManageConnection;
+
+use failure::Error as FailureError;
+use futures::Future;
+
+use stq_types::StoreId;
+
+use backfills::BackfillSettings;
+use credentials::reencrypt_credentials;
+use errors::Error;
+use models::{BackfillProgress, CarrierAccount, CreateCarrierAccount, UpdateCarrierAccount};
+use repos::{DbConnection, ReposFactory};
+use services::types::{Service, ServiceFuture};
+
+pub trait CarrierAccountsService {
+    /// Returns accounts of the store
+    fn list_carrier_accounts(&self, store_id: StoreId) -> ServiceFuture<Vec<CarrierAccount>>;
+
+    /// Add account of the store with the carrier, credentials are stored encrypted
+    fn create_carrier_account(&self, store_id: StoreId, payload: CreateCarrierAccount) -> ServiceFuture<CarrierAccount>;
+
+    /// Update account of the store
+    fn update_carrier_account(
+        &self,
+        store_id: StoreId,
+        account_id: i32,
+        payload: UpdateCarrierAccount,
+    ) -> ServiceFuture<Option<CarrierAccount>>;
+
+    /// Delete account of the store, the marketplace prices of the carrier are used after that
+    fn delete_carrier_account(&self, store_id: StoreId, account_id: i32) -> ServiceFuture<Option<CarrierAccount>>;
+
+    /// Re-encrypts credentials of all accounts with the current key after the key rotation
+    fn reencrypt_carrier_credentials(&self) -> ServiceFuture<BackfillProgress>;
+}
+
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> CarrierAccountsService for Service<T, M, F> {
+    /// Returns accounts of the store
+    fn list_carrier_accounts(&self, store_id: StoreId) -> ServiceFuture<Vec<CarrierAccount>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let carrier_accounts_repo = repo_factory.create_carrier_accounts_repo(&*conn, user_id);
+            carrier_accounts_repo
+                .list(store_id)
+                .map_err(|e| e.context("Service CarrierAccounts, list endpoint error occured.").into())
+        })
+    }
+
+    /// Add account of the store with the carrier, credentials are stored encrypted
+    fn create_carrier_account(&self, store_id: StoreId, payload: CreateCarrierAccount) -> ServiceFuture<CarrierAccount> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        Box::new(
+            self.spawn_on_pool(move |conn| {
+                let carrier_accounts_repo = repo_factory.create_carrier_accounts_repo(&*conn, user_id);
+
+                if carrier_accounts_repo.find_by_company(store_id, payload.company_id)?.is_some() {
+                    return Err(Error::Conflict(validation_errors!({
+                        "company_id": ["company_id" => "Store already has an account with the company"]
+                    }))
+                    .into());
+                }
+
+                carrier_accounts_repo.create(store_id, payload)
+            })
+            .map_err(|e: FailureError| e.context("Service CarrierAccounts, create endpoint error occured.").into()),
+        )
+    }
+
+    /// Update account of the store
+    fn update_carrier_account(
+        &self,
+        store_id: StoreId,
+        account_id: i32,
+        payload: UpdateCarrierAccount,
+    ) -> ServiceFuture<Option<CarrierAccount>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let carrier_accounts_repo = repo_factory.create_carrier_accounts_repo(&*conn, user_id);
+            carrier_accounts_repo
+                .update(store_id, account_id, payload)
+                .map_err(|e| e.context("Service CarrierAccounts, update endpoint error occured.").into())
+        })
+    }
+
+    /// Delete account of the store, the marketplace prices of the carrier are used after that
+    fn delete_carrier_account(&self, store_id: StoreId, account_id: i32) -> ServiceFuture<Option<CarrierAccount>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let carrier_accounts_repo = repo_factory.create_carrier_accounts_repo(&*conn, user_id);
+            carrier_accounts_repo
+                .delete(store_id, account_id)
+                .map_err(|e| e.context("Service CarrierAccounts, delete endpoint error occured.").into())
+        })
+    }
+
+    /// Re-encrypts credentials of all accounts with the current key after the key rotation
+    fn reencrypt_carrier_credentials(&self) -> ServiceFuture<BackfillProgress> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+        let settings = self
+            .static_context
+            .config
+            .backfills
+            .as_ref()
+            .map(BackfillSettings::from)
+            .unwrap_or_default();
+
+        self.spawn_on_admin_pool(move |conn| {
+            let carrier_accounts_repo = repo_factory.create_carrier_accounts_repo(&*conn, user_id);
+            let backfills_repo = repo_factory.create_backfills_repo(&*conn, user_id);
+            reencrypt_credentials(&*conn, &*carrier_accounts_repo, &*backfills_repo, &settings)
+                .map_err(|e| e.context("Service CarrierAccounts, reencrypt endpoint error occured.").into())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use tokio_core::reactor::Core;
+
+    use stq_types::*;
+
+    use credentials::tests::test_cipher;
+    use models::CreateCarrierAccount;
+    use repos::repo_factory::tests::*;
+    use services::carrier_accounts::CarrierAccountsService;
+
+    #[test]
+    fn created_account_stores_encrypted_account_number_and_credentials() {
+        let mut core = Core::new().unwrap();
+        let handle = Arc::new(core.handle());
+        let service = create_service(Some(MOCK_USER_ID), handle);
+        let payload = CreateCarrierAccount {
+            company_id: CompanyId(1),
+            account_number: "ACC-1".to_string(),
+            credentials: "api-key-123".to_string(),
+        };
+        let work = service.create_carrier_account(MOCK_STORE_ID, payload);
+        let account = core.run(work).unwrap();
+
+        assert_eq!(account.account_number, "ACC-1");
+        assert_eq!(test_cipher().decrypt(&account.encrypted_account_number).unwrap(), "ACC-1");
+        assert_ne!(account.encrypted_credentials, "api-key-123");
+        assert_eq!(test_cipher().decrypt(&account.encrypted_credentials).unwrap(), "api-key-123");
+    }
+}
@@ -1,23 +1,22 @@
 //! Companies Service, presents CRUD operations
-use diesel::connection::AnsiTransactionManager;
-use diesel::pg::Pg;
-use diesel::Connection;
 use r2d2::ManageConnection;
 
-use failure::Error as FailureError;
+use futures::Future;
 
 use stq_types::{Alpha3, CompanyId};
 
-use models::companies::{Company, NewCompany, UpdateCompany};
-use repos::ReposFactory;
+use errors::Error;
+use events::{self, DeliveryEvent};
+use models::companies::{CompaniesSearch, Company, CompanyDeletionImpact, NewCompany, UpdateCompany};
+use repos::{DbConnection, ReposFactory};
 use services::types::{Service, ServiceFuture};
 
 pub trait CompaniesService {
     /// Create a new company
     fn create_company(&self, payload: NewCompany) -> ServiceFuture<Company>;
 
-    /// Returns list of companies
-    fn list_companies(&self) -> ServiceFuture<Vec<Company>>;
+    /// Returns list of companies matching the search
+    fn list_companies(&self, search: CompaniesSearch) -> ServiceFuture<Vec<Company>>;
 
     /// Find specific company by ID
     fn find_company(&self, id: CompanyId) -> ServiceFuture<Option<Company>>;
@@ -28,40 +27,48 @@ pub trait CompaniesService {
     /// Update a company
     fn update_company(&self, id: CompanyId, payload: UpdateCompany) -> ServiceFuture<Company>;
 
-    /// Delete a company
-    fn delete_company(&self, id: CompanyId) -> ServiceFuture<Company>;
+    /// Returns counts of the data deleted together with the company
+    fn get_company_deletion_impact(&self, company_id: CompanyId) -> ServiceFuture<CompanyDeletionImpact>;
+
+    /// Delete a company, company packages with their products and shipping rates are deleted only with `cascade`
+    fn delete_company(&self, id: CompanyId, cascade: bool) -> ServiceFuture<Company>;
 }
 
-impl<
-        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
-        M: ManageConnection<Connection = T>,
-        F: ReposFactory<T>,
-    > CompaniesService for Service<T, M, F>
-{
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> CompaniesService for Service<T, M, F> {
     /// Create a new company
     fn create_company(&self, payload: NewCompany) -> ServiceFuture<Company> {
         let repo_factory = self.static_context.repo_factory.clone();
         let user_id = self.dynamic_context.user_id;
-
-        self.spawn_on_pool(move |conn| {
-            let company_repo = repo_factory.create_companies_repo(&*conn, user_id);
-            conn.transaction::<Company, FailureError, _>(move || {
-                company_repo
-                    .create(payload)
-                    .map_err(|e| e.context("Service Companies, create endpoint error occured.").into())
+        let events_enabled = self.events_enabled();
+        let service = self.clone();
+
+        Box::new(
+            self.spawn_transaction_on_pool(move |conn| {
+                let company_repo = repo_factory.create_companies_repo(&*conn, user_id);
+                let event_outbox_repo = repo_factory.create_event_outbox_repo_with_sys_acl(&*conn);
+                let company = company_repo.create(payload)?;
+                let event = DeliveryEvent::CompanyCreated {
+                    company_id: company.id,
+                    name: company.name.clone(),
+                    label: company.label.clone(),
+                };
+                let outbox_events = events::store(&*event_outbox_repo, events_enabled, vec![event])?;
+                Ok((company, outbox_events))
             })
-        })
+            .map_err(|e| e.context("Service Companies, create endpoint error occured.").into())
+            .and_then(move |(company, outbox_events)| service.publish_events(outbox_events).map(move |_| company)),
+        )
     }
 
-    /// Returns list of companies
-    fn list_companies(&self) -> ServiceFuture<Vec<Company>> {
+    /// Returns list of companies matching the search
+    fn list_companies(&self, search: CompaniesSearch) -> ServiceFuture<Vec<Company>> {
         let repo_factory = self.static_context.repo_factory.clone();
         let user_id = self.dynamic_context.user_id;
 
         self.spawn_on_pool(move |conn| {
             let company_repo = repo_factory.create_companies_repo(&*conn, user_id);
             company_repo
-                .list()
+                .list(search)
                 .map_err(|e| e.context("Service Companies, list endpoint error occured.").into())
         })
     }
@@ -105,16 +112,44 @@ impl<
         })
     }
 
-    /// Delete a company
-    fn delete_company(&self, company_id: CompanyId) -> ServiceFuture<Company> {
+    /// Returns counts of the data deleted together with the company
+    fn get_company_deletion_impact(&self, company_id: CompanyId) -> ServiceFuture<CompanyDeletionImpact> {
         let repo_factory = self.static_context.repo_factory.clone();
         let user_id = self.dynamic_context.user_id;
 
         self.spawn_on_pool(move |conn| {
             let company_repo = repo_factory.create_companies_repo(&*conn, user_id);
             company_repo
-                .delete(company_id)
-                .map_err(|e| e.context("Service Companies, delete endpoint error occured.").into())
+                .deletion_impact(company_id)
+                .map_err(|e| e.context("Service Companies, deletion_impact endpoint error occured.").into())
         })
     }
+
+    /// Delete a company
+    fn delete_company(&self, company_id: CompanyId, cascade: bool) -> ServiceFuture<Company> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        Box::new(
+            self.spawn_transaction_on_pool(move |conn| {
+                let company_repo = repo_factory.create_companies_repo(&*conn, user_id);
+
+                let impact = company_repo.deletion_impact(company_id)?;
+                // dependent data is deleted together with the company, so the deletion is rolled back if it is not cascaded
+                let company = company_repo.delete(company_id)?;
+                if !impact.is_empty() && !cascade {
+                    return Err(Error::Conflict(validation_errors!({
+                        "company": ["company" => format!(
+                            "Company has {} company packages used by {} products with {} shipping rates, deletion must be cascaded",
+                            impact.company_packages, impact.products, impact.shipping_rates
+                        )]
+                    }))
+                    .into());
+                }
+
+                Ok(company)
+            })
+            .map_err(|e| e.context("Service Companies, delete endpoint error occured.").into()),
+        )
+    }
 }
@@ -1,21 +1,35 @@
 //! CompaniesPackages Service, presents CRUD operations
 
-use diesel::connection::AnsiTransactionManager;
-use diesel::pg::Pg;
-use diesel::Connection;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::{DateTime, NaiveDate, Utc};
 use failure::Error as FailureError;
+use futures::future;
+use futures::Future;
 use r2d2::ManageConnection;
 use stq_static_resources::Currency;
-use stq_types::{Alpha3, CompanyId, CompanyPackageId, PackageId};
+use stq_types::{Alpha3, BaseProductId, CompanyId, CompanyPackageId, PackageId, StoreId};
 use validator::Validate;
 
+use cache::{CachedShippingRates, ShippingRatesCache, ShippingRatesCacheKey};
+use carriers::{CarrierAccountCredentials, CarrierRateRequest};
 use errors::Error;
+use events::{self, DeliveryEvent};
 use models::{
-    get_countries_from_forest_by, AvailablePackages, Company, CompanyPackage, Country, NewCompanyPackage, NewShippingRates,
-    NewShippingRatesBatch, PackageValidation, Packages, RatesCsvData, ShipmentMeasurements, ShippingRateSource, ShippingRates,
-    ShippingValidation, ZonesCsvData,
+    count_rate_entries, diff_shipping_rates, find_rounding_policy, get_countries_from_forest_by, normalize_subdivision_code,
+    resolve_new_rates_zones, resolve_stored_rates, split_at_carrier_quote, uses_countries_tree, validate_delivery_time,
+    validate_weight_bands, AvailablePackages, AvailablePackagesExplanation, BookShipment, CarrierFallback, Company, CompanyPackage,
+    CompanyPackageProducts, Country, CountrySubdivision, DeliveryCoordinates, DistancePriceBreakdown, InsuranceOption, MatchedShippingRate,
+    NewCompanyPackage, NewRateComponents, NewShippingRates, NewShippingRatesBatch, PackageValidation, Packages, PickupPoint, PostalZone,
+    PriceRoundingPolicy, PricingRule, RateResolutionAttempt, RateResolutionOutcome, RateResolutionStep, RateRoute, RatesCsvData,
+    RejectedPackage, RoundingMode, SetCarrierFallback, SetCashOnDelivery, SetRateResolutionOrder, SetShippingCapacity, ShipmentBooking,
+    ShipmentMeasurements, ShippingRateSource, ShippingRates, ShippingRatesPriceBreakdown, ShippingRatesReplacementPreview,
+    ShippingValidation, Surcharge, UnavailabilityReason, Zone, ZonesCsvData,
 };
-use repos::ReposFactory;
+use repos::countries::get_all_parent_codes;
+use repos::{CompaniesPackagesRepo, DbConnection, ProductsRepo, ReposFactory, ShipmentBookingsRepo, ShippingRatesRepo, ZonesRepo};
+use services::company_rates_quotas::{insert_rates_within_quota, rates_quota_usage};
 use services::types::{Service, ServiceFuture};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -25,18 +39,462 @@ pub struct GetDeliveryPrice {
     pub delivery_to: Alpha3,
     pub volume: u32,
     pub weight: u32,
+    /// Required by company packages priced by distance
+    pub coordinates: Option<DeliveryCoordinates>,
+    /// Pickup point of the company in the destination country the shipment is delivered to
+    pub pickup_point_id: Option<i32>,
+    /// Value of the shipment the insurance fee is calculated from, the shipment is not insured if not set
+    #[serde(default)]
+    pub declared_value: Option<f64>,
+    /// Insurance option of the company package, the first one is used if not set
+    #[serde(default)]
+    pub insurance: Option<String>,
+    /// Buyer pays for the parcel on delivery, the fee of the cash on delivery is added to the price
+    #[serde(default)]
+    pub cod: bool,
+    /// Store shipping the parcel, the negotiated rate of its account with the carrier is preferred if the user manages the store
+    #[serde(default)]
+    pub store_id: Option<StoreId>,
+    /// Product shipped in the parcel, markups and discounts of its store and the negotiated rate of the store account are applied
+    #[serde(default)]
+    pub base_product_id: Option<BaseProductId>,
+    /// ISO 3166-2 code of the subdivision of the destination country, rates to the subdivision are preferred
+    #[serde(default)]
+    pub delivery_to_subdivision: Option<String>,
+    /// Postal code of the destination, rates to the postal zones it is in are preferred
+    #[serde(default)]
+    pub postal_code: Option<String>,
+}
+
+/// Price of the stored rates with the carrier requests resolved on the DB pool: the marketplace quote with the attempts
+/// before it, the negotiated quote of the store account, what is added to the price and the pricing rules of the store
+type PricingInputs = (
+    Currency,
+    Option<DeliveryPriceDetails>,
+    Option<(CarrierRateRequest, Vec<RateResolutionAttempt>)>,
+    Option<CarrierRateRequest>,
+    Vec<Surcharge>,
+    Option<PickupPoint>,
+    Option<(InsuranceOption, f64)>,
+    Option<f64>,
+    Vec<PricingRule>,
+);
+
+/// Maximum number of shipments priced by a single batch request
+pub const MAX_DELIVERY_PRICES_BATCH_SIZE: usize = 100;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DeliveryPriceBatchItem {
+    pub company_package_id: CompanyPackageId,
+    pub from: Alpha3,
+    pub to: Alpha3,
+    pub volume: u32,
+    pub weight: u32,
+    #[serde(default)]
+    pub declared_value: Option<f64>,
+    #[serde(default)]
+    pub insurance: Option<String>,
+    #[serde(default)]
+    pub cod: bool,
+    /// Product shipped in the parcel, markups and discounts of its store are applied
+    #[serde(default)]
+    pub base_product_id: Option<BaseProductId>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DeliveryPriceBatchResult {
+    pub company_package_id: CompanyPackageId,
+    pub from: Alpha3,
+    pub to: Alpha3,
+    pub price: Option<DeliveryPrice>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub enum DeliveryPriceSource {
+    StoredRates,
+    Carrier,
+    /// Rate of the account of the store with the carrier
+    NegotiatedRates,
+    Distance,
+    /// Stored rates quoted because the carrier quote failed
+    Fallback,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct DeliveryPrice {
     pub currency: Currency,
     pub value: f64,
+    pub source: DeliveryPriceSource,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pickup_point: Option<PickupPoint>,
+    /// Measurements of the shipment in grams and cubic centimeters the price was calculated for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub measurements: Option<ShipmentMeasurements>,
+}
+
+impl From<DeliveryPriceDetails> for DeliveryPrice {
+    fn from(details: DeliveryPriceDetails) -> Self {
+        DeliveryPrice {
+            currency: details.currency,
+            value: details.value,
+            source: details.source,
+            pickup_point: details.pickup_point,
+            measurements: details.measurements,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DeliveryPriceSurcharge {
+    pub name: String,
+    pub value: f64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DeliveryPriceInsurance {
+    pub name: String,
+    pub declared_value: f64,
+    pub fee: f64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DeliveryPriceRounding {
+    pub step: f64,
+    pub mode: RoundingMode,
+    /// Amount the price changed by, negative if it is rounded down
+    pub adjustment: f64,
+}
+
+/// Insurance option chosen for the shipment together with its declared value, `None` if the shipment is not insured
+fn select_insurance(
+    company_package: &CompanyPackage,
+    declared_value: Option<f64>,
+    insurance: Option<&str>,
+) -> Result<Option<(InsuranceOption, f64)>, Error> {
+    let declared_value = match declared_value {
+        Some(declared_value) => declared_value,
+        None => return Ok(None),
+    };
+
+    if declared_value.is_nan() || declared_value < 0.0 {
+        return Err(Error::Validate(validation_errors!({
+            "declared_value": ["declared_value" => "Declared value must not be negative"]
+        })));
+    }
+
+    match company_package.insurance_option(insurance) {
+        Some(option) => Ok(Some((option.clone(), declared_value))),
+        None => match insurance {
+            Some(insurance) => Err(Error::Validate(validation_errors!({
+                "insurance": ["insurance" => format!("Insurance option {} of company package {} not found", insurance, company_package.id)]
+            }))),
+            // the company package is not insured
+            None => Ok(None),
+        },
+    }
+}
+
+/// Fee of the cash on delivery if it is requested, fails if the company package does not support it
+fn select_cash_on_delivery(company_package: &CompanyPackage, cod: bool) -> Result<Option<f64>, Error> {
+    if !cod {
+        return Ok(None);
+    }
+
+    company_package.cash_on_delivery_fee().map(Some).ok_or_else(|| {
+        Error::Validate(validation_errors!({
+            "cod": ["cod" => format!("Company package {} does not support cash on delivery", company_package.id)]
+        }))
+    })
+}
+
+/// Store of the product the parcel is priced for. Pricing rules are applied by the store of the product only,
+/// so that the buyers can neither avoid the markups nor apply the discounts of the other stores
+fn product_store(
+    products_repo: &dyn ProductsRepo,
+    base_product_id: Option<BaseProductId>,
+    store_id: Option<StoreId>,
+) -> Result<Option<StoreId>, FailureError> {
+    let base_product_id = match base_product_id {
+        Some(base_product_id) => base_product_id,
+        None => return Ok(None),
+    };
+
+    let product_store_id = products_repo
+        .get_by_base_product_id(base_product_id)?
+        .first()
+        .map(|product| product.store_id)
+        .ok_or(Error::Validate(validation_errors!({
+            "base_product_id": ["base_product_id" => format!("Shipping of base product with id: {} not found", base_product_id)]
+        })))?;
+
+    match store_id {
+        Some(store_id) if store_id != product_store_id => Err(Error::Validate(validation_errors!({
+            "store_id": ["store_id" => format!("Base product with id: {} does not belong to store {}", base_product_id, store_id)]
+        }))
+        .into()),
+        _ => Ok(Some(product_store_id)),
+    }
+}
+
+/// Route the stored rates steps of the company package look through. `countries` is the countries tree,
+/// it is only needed if the steps look the rates up by the regions of the destination
+pub fn stored_rates_route(steps: &[RateResolutionStep], countries: Option<&Country>, delivery_to: Alpha3) -> RateRoute {
+    let mut parent_codes = vec![];
+    if let Some(countries) = countries {
+        if uses_countries_tree(steps) {
+            get_all_parent_codes(countries, &delivery_to, &mut parent_codes);
+        }
+    }
+    RateRoute::from_parent_codes(delivery_to, parent_codes)
+}
+
+/// Delivery price together with the parts it consists of
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DeliveryPriceDetails {
+    pub currency: Currency,
+    pub value: f64,
+    pub source: DeliveryPriceSource,
+    pub base_rate: f64,
+    pub dimensional_weight_adjustment: f64,
+    /// Added to `base_rate` to reach the minimum price, only available for prices calculated by distance
+    #[serde(default)]
+    pub min_price_adjustment: f64,
+    pub surcharges: Vec<DeliveryPriceSurcharge>,
+    /// Only available for prices calculated from stored rates
+    pub billable_weight_g: Option<u32>,
+    /// Only available for prices calculated from stored rates
+    pub matched_rate: Option<MatchedShippingRate>,
+    /// Only available for prices calculated by distance
+    pub distance_km: Option<f64>,
+    /// Pickup point the shipment is delivered to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pickup_point: Option<PickupPoint>,
+    /// Step of the price resolution order that gave the price, not available for prices calculated by distance
+    #[serde(default)]
+    pub resolved_by: Option<RateResolutionStep>,
+    /// Steps of the price resolution order tried until the price was found
+    #[serde(default)]
+    pub rate_resolution: Vec<RateResolutionAttempt>,
+    /// Measurements of the shipment in grams and cubic centimeters the price was calculated for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub measurements: Option<ShipmentMeasurements>,
+    /// Insurance of the shipment, its fee is included in the price
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub insurance: Option<DeliveryPriceInsurance>,
+    /// Rounding policy of the currency applied to the final price
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rounding: Option<DeliveryPriceRounding>,
+}
+
+impl DeliveryPriceDetails {
+    /// Surcharges of the rates are listed before the configured surcharges
+    fn from_stored_rates(currency: Currency, breakdown: ShippingRatesPriceBreakdown) -> Self {
+        let surcharges = breakdown
+            .rate_components
+            .iter()
+            .map(|component| DeliveryPriceSurcharge {
+                name: component.kind.to_string(),
+                value: component.value,
+            })
+            .collect::<Vec<_>>();
+
+        DeliveryPriceDetails {
+            currency,
+            value: breakdown.matched_rate.price + surcharges.iter().map(|surcharge| surcharge.value).sum::<f64>(),
+            source: DeliveryPriceSource::StoredRates,
+            base_rate: breakdown.base_rate,
+            dimensional_weight_adjustment: breakdown.dimensional_weight_adjustment,
+            min_price_adjustment: 0.0,
+            surcharges,
+            billable_weight_g: Some(breakdown.billable_weight_g),
+            matched_rate: Some(breakdown.matched_rate),
+            distance_km: None,
+            pickup_point: None,
+            resolved_by: None,
+            rate_resolution: vec![],
+            measurements: None,
+            insurance: None,
+            rounding: None,
+        }
+    }
+
+    fn from_carrier(currency: Currency, value: f64) -> Self {
+        DeliveryPriceDetails {
+            currency,
+            value,
+            source: DeliveryPriceSource::Carrier,
+            base_rate: value,
+            dimensional_weight_adjustment: 0.0,
+            min_price_adjustment: 0.0,
+            surcharges: vec![],
+            billable_weight_g: None,
+            matched_rate: None,
+            distance_km: None,
+            pickup_point: None,
+            resolved_by: None,
+            rate_resolution: vec![],
+            measurements: None,
+            insurance: None,
+            rounding: None,
+        }
+    }
+
+    fn from_negotiated_rate(currency: Currency, value: f64) -> Self {
+        DeliveryPriceDetails {
+            source: DeliveryPriceSource::NegotiatedRates,
+            ..DeliveryPriceDetails::from_carrier(currency, value)
+        }
+    }
+
+    fn from_distance(currency: Currency, breakdown: DistancePriceBreakdown) -> Self {
+        DeliveryPriceDetails {
+            currency,
+            value: breakdown.price,
+            source: DeliveryPriceSource::Distance,
+            base_rate: breakdown.base_rate,
+            dimensional_weight_adjustment: 0.0,
+            min_price_adjustment: breakdown.min_price_adjustment,
+            surcharges: vec![],
+            billable_weight_g: None,
+            matched_rate: None,
+            distance_km: Some(breakdown.distance_km),
+            pickup_point: None,
+            resolved_by: None,
+            rate_resolution: vec![],
+            measurements: None,
+            insurance: None,
+            rounding: None,
+        }
+    }
+
+    /// Sets the steps tried by the price resolution, the last one gave the price if it is matched
+    fn with_rate_resolution(mut self, attempts: Vec<RateResolutionAttempt>) -> Self {
+        self.resolved_by = attempts
+            .last()
+            .filter(|attempt| attempt.outcome == RateResolutionOutcome::Matched)
+            .map(|attempt| attempt.step);
+        self.rate_resolution = attempts;
+        self
+    }
+
+    /// Price replacing the failed carrier quote, `None` if the company package has no fallback.
+    /// The fallback markup is calculated from the price of the stored rates
+    fn into_carrier_fallback(mut self, carrier_fallback: CarrierFallback, markup_percent: Option<f64>) -> Option<Self> {
+        if carrier_fallback == CarrierFallback::NoPrice {
+            return None;
+        }
+
+        self.source = DeliveryPriceSource::Fallback;
+        if let Some(markup_percent) = markup_percent {
+            let value = self.value * markup_percent / 100.0;
+            self.value += value;
+            self.surcharges.push(DeliveryPriceSurcharge {
+                name: "fallback_markup".to_string(),
+                value,
+            });
+        }
+        Some(self)
+    }
+
+    /// Reports the measurements in grams and cubic centimeters the price was calculated for
+    fn with_measurements(mut self, measurements: ShipmentMeasurements) -> Self {
+        self.measurements = Some(measurements);
+        self
+    }
+
+    /// Marks up or discounts the base price by the pricing rules of the store, the rules are expected in the order of priority
+    fn with_pricing_rules(mut self, rules: &[PricingRule]) -> Self {
+        for rule in rules {
+            let value = rule.calculate_adjustment(self.value);
+            self.value += value;
+            self.surcharges.push(DeliveryPriceSurcharge {
+                name: format!("pricing_rule_{}", rule.id),
+                value,
+            });
+        }
+        self
+    }
+
+    /// Adds surcharges on top of the price, percentage surcharges are calculated from the price without surcharges
+    fn with_surcharges(mut self, surcharges: &[Surcharge]) -> Self {
+        let price = self.value;
+        for surcharge in surcharges {
+            let value = surcharge.calculate_amount(price);
+            self.value += value;
+            self.surcharges.push(DeliveryPriceSurcharge {
+                name: surcharge.name.clone(),
+                value,
+            });
+        }
+        self
+    }
+
+    /// Applies the price factor of the pickup point to the whole price including the surcharges
+    fn with_pickup_point(mut self, pickup_point: Option<PickupPoint>) -> Self {
+        let pickup_point = match pickup_point {
+            Some(pickup_point) => pickup_point,
+            None => return self,
+        };
+        if let Some(price_factor) = pickup_point.price_factor {
+            let value = self.value * price_factor - self.value;
+            self.value += value;
+            self.surcharges.push(DeliveryPriceSurcharge {
+                name: "pickup_point".to_string(),
+                value,
+            });
+        }
+        self.pickup_point = Some(pickup_point);
+        self
+    }
+
+    /// Adds the insurance fee of the declared value, the pickup point factor does not apply to it
+    fn with_insurance(mut self, insurance: Option<(InsuranceOption, f64)>) -> Self {
+        if let Some((option, declared_value)) = insurance {
+            let fee = option.fee(declared_value);
+            self.value += fee;
+            self.insurance = Some(DeliveryPriceInsurance {
+                name: option.name,
+                declared_value,
+                fee,
+            });
+        }
+        self
+    }
+
+    /// Adds the fee of the cash on delivery to the surcharges
+    fn with_cash_on_delivery(mut self, cod_fee: Option<f64>) -> Self {
+        if let Some(value) = cod_fee {
+            self.value += value;
+            self.surcharges.push(DeliveryPriceSurcharge {
+                name: "cash_on_delivery".to_string(),
+                value,
+            });
+        }
+        self
+    }
+
+    /// Rounds the final price by the policy of its currency, must be the last step of the price calculation
+    fn with_rounding(mut self, policies: &[PriceRoundingPolicy]) -> Self {
+        if let Some(policy) = find_rounding_policy(policies, self.currency) {
+            let value = policy.round(self.value);
+            self.rounding = Some(DeliveryPriceRounding {
+                step: policy.step,
+                mode: policy.mode,
+                adjustment: value - self.value,
+            });
+            self.value = value;
+        }
+        self
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct ReplaceShippingRatesPayload {
     pub rates_csv_base64: String,
     pub zones_csv_base64: String,
+    /// Delivery time of all the replaced rates, the company package one is used if not set
+    pub delivery_time_min_days: Option<u32>,
+    pub delivery_time_max_days: Option<u32>,
 }
 
 pub trait CompaniesPackagesService {
@@ -46,6 +504,9 @@ pub trait CompaniesPackagesService {
     /// Returns available packages supported by the country
     fn get_available_packages(&self, country: Alpha3, size: u32, weight: u32) -> ServiceFuture<Vec<AvailablePackages>>;
 
+    /// Returns available packages together with all other company packages and the reasons they do not take the shipment
+    fn explain_available_packages(&self, country: Alpha3, size: u32, weight: u32) -> ServiceFuture<AvailablePackagesExplanation>;
+
     /// Returns company package by id
     fn get_company_package(&self, id: CompanyPackageId) -> ServiceFuture<Option<CompanyPackage>>;
 
@@ -56,40 +517,93 @@ pub trait CompaniesPackagesService {
     fn get_packages(&self, id: CompanyId) -> ServiceFuture<Vec<Packages>>;
 
     /// Delete a companies_packages
-    fn delete_company_package(&self, company_id: CompanyId, package_id: PackageId) -> ServiceFuture<CompanyPackage>;
+    /// Delete a companies_packages, refused while products are shipped by it unless `force` is set
+    fn delete_company_package(&self, company_id: CompanyId, package_id: PackageId, force: bool) -> ServiceFuture<CompanyPackage>;
 
-    /// Get delivery price
+    /// Returns the page of base products shipped by the company package, allowed to the admins only
+    fn get_company_package_products(
+        &self,
+        company_package_id: CompanyPackageId,
+        offset: i64,
+        limit: i64,
+    ) -> ServiceFuture<CompanyPackageProducts>;
+
+    /// Get delivery price, stored rates of the route are cached if the shipping rates cache is set
     fn get_delivery_price(&self, payload: GetDeliveryPrice) -> ServiceFuture<Option<DeliveryPrice>>;
 
+    /// Get delivery price with the explanation of how it was calculated
+    fn get_delivery_price_details(&self, payload: GetDeliveryPrice) -> ServiceFuture<Option<DeliveryPriceDetails>>;
+
+    /// Get delivery prices of several shipments at once, results are in the order of the items.
+    /// Only stored rates are used, looked up in the rate resolution order of the company packages:
+    /// carriers are not requested and packages priced by distance have no price
+    fn get_delivery_prices_batch(&self, items: Vec<DeliveryPriceBatchItem>) -> ServiceFuture<Vec<DeliveryPriceBatchResult>>;
+
     /// Get shipping rates for the particular "from" country in the company package
     fn get_shipping_rates(&self, company_package_id: CompanyPackageId, delivery_from: Alpha3) -> ServiceFuture<Vec<ShippingRates>>;
 
-    /// Replace shipping rates for the particular "from" country in the company package
+    /// Replace shipping rates for the particular "from" country in the company package,
+    /// cached rates of the routes are removed
     fn replace_shipping_rates(
         &self,
         company_package_id: CompanyPackageId,
         payload: ReplaceShippingRatesPayload,
     ) -> ServiceFuture<Vec<ShippingRates>>;
+
+    /// Validates the replacement of the shipping rates and compares them with the current ones, nothing is changed
+    fn preview_shipping_rates_replacement(
+        &self,
+        company_package_id: CompanyPackageId,
+        payload: ReplaceShippingRatesPayload,
+    ) -> ServiceFuture<ShippingRatesReplacementPreview>;
+
+    /// Add dated components to the shipping rates from the country in the company package,
+    /// so that indexed surcharges are changed without replacing the rates. Components are dropped when the rates are replaced
+    fn add_rate_components(&self, company_package_id: CompanyPackageId, payload: NewRateComponents) -> ServiceFuture<Vec<ShippingRates>>;
+
+    /// Sets the order the price of the company package is resolved in
+    fn set_rate_resolution_order(
+        &self,
+        company_package_id: CompanyPackageId,
+        payload: SetRateResolutionOrder,
+    ) -> ServiceFuture<Option<CompanyPackage>>;
+
+    /// Sets whether the company package supports the cash on delivery and its fee
+    fn set_cash_on_delivery(
+        &self,
+        company_package_id: CompanyPackageId,
+        payload: SetCashOnDelivery,
+    ) -> ServiceFuture<Option<CompanyPackage>>;
+
+    /// Sets the price quoted when the carrier quote of the company package fails
+    fn set_carrier_fallback(
+        &self,
+        company_package_id: CompanyPackageId,
+        payload: SetCarrierFallback,
+    ) -> ServiceFuture<Option<CompanyPackage>>;
+
+    /// Sets the daily cut-off time and the number of shipments the company package takes a day
+    fn set_shipping_capacity(
+        &self,
+        company_package_id: CompanyPackageId,
+        payload: SetShippingCapacity,
+    ) -> ServiceFuture<Option<CompanyPackage>>;
+
+    /// Books a shipment of the order on the shipping date by the cut-off time, conflicts if the company package is booked up on it
+    fn book_shipment(&self, company_package_id: CompanyPackageId, payload: BookShipment) -> ServiceFuture<ShipmentBooking>;
 }
 
-impl<
-        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
-        M: ManageConnection<Connection = T>,
-        F: ReposFactory<T>,
-    > CompaniesPackagesService for Service<T, M, F>
-{
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> CompaniesPackagesService for Service<T, M, F> {
     /// Create a new companies_packages
     fn create_company_package(&self, payload: NewCompanyPackage) -> ServiceFuture<CompanyPackage> {
         let repo_factory = self.static_context.repo_factory.clone();
         let user_id = self.dynamic_context.user_id;
 
-        self.spawn_on_pool(move |conn| {
+        self.spawn_transaction_on_pool(move |conn| {
             let companies_packages_repo = repo_factory.create_companies_packages_repo(&*conn, user_id);
-            conn.transaction::<CompanyPackage, FailureError, _>(move || {
-                companies_packages_repo
-                    .create(payload)
-                    .map_err(|e| e.context("Service CompaniesPackages, create endpoint error occured.").into())
-            })
+            companies_packages_repo
+                .create(payload)
+                .map_err(|e| e.context("Service CompaniesPackages, create endpoint error occured.").into())
         })
     }
 
@@ -136,40 +650,44 @@ impl<
     fn get_available_packages(&self, deliveries_from: Alpha3, size: u32, weight: u32) -> ServiceFuture<Vec<AvailablePackages>> {
         let repo_factory = self.static_context.repo_factory.clone();
         let user_id = self.dynamic_context.user_id;
+        let clock = self.static_context.clock.clone();
 
-        self.spawn_on_pool(move |conn| {
+        self.spawn_on_read_pool(move |conn| {
             let companies_repo = repo_factory.create_companies_repo(&*conn, user_id);
             let companies_packages_repo = repo_factory.create_companies_packages_repo(&*conn, user_id);
             let shipping_rates_repo = repo_factory.create_shipping_rates_repo(&*conn, user_id);
+            let company_suspensions_repo = repo_factory.create_company_suspensions_repo_with_sys_acl(&*conn);
+            let shipment_bookings_repo = repo_factory.create_shipment_bookings_repo_with_sys_acl(&*conn);
+            let today = clock.today();
 
             companies_repo
                 .find_deliveries_from(deliveries_from.clone())
                 .and_then(|companies| {
+                    let suspensions = company_suspensions_repo.find_active_on(today)?;
                     let companies_ids = companies.into_iter().map(|company| company.id).collect();
-                    companies_packages_repo
-                        .get_available_packages(companies_ids, size, weight, deliveries_from.clone())?
-                        .into_iter()
-                        .map(|pkg| {
-                            let deliveries_to =
-                                get_countries_from_forest_by(pkg.deliveries_to.iter(), |country| country.level == Country::COUNTRY_LEVEL)
-                                    .into_iter()
-                                    .map(|country| country.alpha3)
-                                    .collect::<Vec<_>>();
-
-                            match pkg.shipping_rate_source {
-                                ShippingRateSource::NotAvailable => Ok((pkg, None)),
-                                ShippingRateSource::Static { dimensional_factor } => shipping_rates_repo
-                                    .get_multiple_rates(pkg.id, deliveries_from.clone(), deliveries_to)
-                                    .map(move |rates| (pkg, Some((dimensional_factor, rates)))),
+                    let mut packages = vec![];
+                    for pkg in companies_packages_repo.get_available_packages(companies_ids, size, weight, deliveries_from.clone())? {
+                        if !suspensions.is_empty() {
+                            let company_package = companies_packages_repo
+                                .get(pkg.id)?
+                                .ok_or(format_err!("Company package with id {} not found", pkg.id))?;
+                            if suspensions
+                                .iter()
+                                .any(|suspension| suspension.applies_to(company_package.company_id, pkg.id))
+                            {
+                                continue;
                             }
-                        })
-                        .collect::<Result<Vec<_>, _>>()
-                        .map(|package_rates| {
-                            package_rates
-                                .into_iter()
-                                .filter_map(|(pkg, rates)| determine_package_availability(rates, size, weight, pkg))
-                                .collect::<Vec<_>>()
-                        })
+                        }
+
+                        let rates = package_rates(&*shipping_rates_repo, &deliveries_from, &pkg)?;
+                        packages.extend(determine_package_availability(rates, size, weight, today, pkg));
+                    }
+
+                    let ids = packages.iter().map(|pkg| pkg.id).collect();
+                    let booked_up = find_booked_up_packages(&*companies_packages_repo, &*shipment_bookings_repo, ids, clock.now())?;
+                    packages.retain(|pkg| !booked_up.iter().any(|&(id, _)| id == pkg.id));
+
+                    Ok(packages)
                 })
                 .map_err(|e| {
                     e.context("Service CompaniesPackages, find_deliveries_from endpoint error occured.")
@@ -178,23 +696,141 @@ impl<
         })
     }
 
-    /// Delete a companies_packages
-    fn delete_company_package(&self, company_id: CompanyId, package_id: PackageId) -> ServiceFuture<CompanyPackage> {
+    /// Returns available packages together with all other company packages and the reasons they do not take the shipment
+    fn explain_available_packages(&self, deliveries_from: Alpha3, size: u32, weight: u32) -> ServiceFuture<AvailablePackagesExplanation> {
         let repo_factory = self.static_context.repo_factory.clone();
         let user_id = self.dynamic_context.user_id;
+        let clock = self.static_context.clock.clone();
 
-        self.spawn_on_pool(move |conn| {
+        self.spawn_on_read_pool(move |conn| {
             let companies_packages_repo = repo_factory.create_companies_packages_repo(&*conn, user_id);
+            let shipping_rates_repo = repo_factory.create_shipping_rates_repo(&*conn, user_id);
+            let company_suspensions_repo = repo_factory.create_company_suspensions_repo_with_sys_acl(&*conn);
+            let shipment_bookings_repo = repo_factory.create_shipment_bookings_repo_with_sys_acl(&*conn);
+            let today = clock.today();
+            let measurements = ShipmentMeasurements {
+                volume_cubic_cm: size,
+                weight_g: weight,
+            };
+
             companies_packages_repo
-                .delete(company_id, package_id)
-                .map_err(|e| e.context("Service CompaniesPackages, delete endpoint error occured.").into())
+                .get_candidate_packages(deliveries_from.clone())
+                .and_then(|candidates| {
+                    let suspensions = company_suspensions_repo.find_active_on(today)?;
+                    let mut explanation = AvailablePackagesExplanation {
+                        packages: vec![],
+                        unavailable_packages: vec![],
+                        measurements,
+                    };
+
+                    for candidate in candidates {
+                        let reasons = candidate.rejection_reasons(&deliveries_from, measurements, &suspensions);
+                        let pkg = candidate.available_package;
+                        let (id, name) = (pkg.id, pkg.name.clone());
+                        let reasons = if reasons.is_empty() {
+                            let rates = package_rates(&*shipping_rates_repo, &deliveries_from, &pkg)?;
+                            match determine_package_availability(rates, size, weight, today, pkg) {
+                                Some(pkg) => {
+                                    explanation.packages.push(pkg);
+                                    continue;
+                                }
+                                None => vec![UnavailabilityReason::NoShippingRates],
+                            }
+                        } else {
+                            reasons
+                        };
+                        explanation.unavailable_packages.push(RejectedPackage { id, name, reasons });
+                    }
+
+                    let ids = explanation.packages.iter().map(|pkg| pkg.id).collect();
+                    let booked_up = find_booked_up_packages(&*companies_packages_repo, &*shipment_bookings_repo, ids, clock.now())?;
+                    for (id, shipping_date) in booked_up {
+                        if let Some(position) = explanation.packages.iter().position(|pkg| pkg.id == id) {
+                            let pkg = explanation.packages.remove(position);
+                            explanation.unavailable_packages.push(RejectedPackage {
+                                id,
+                                name: pkg.name,
+                                reasons: vec![UnavailabilityReason::BookedUp { shipping_date }],
+                            });
+                        }
+                    }
+
+                    Ok(explanation)
+                })
+                .map_err(|e| {
+                    e.context("Service CompaniesPackages, explain_available_packages endpoint error occured.")
+                        .into()
+                })
+        })
+    }
+
+    /// Delete a companies_packages
+    fn delete_company_package(&self, company_id: CompanyId, package_id: PackageId, force: bool) -> ServiceFuture<CompanyPackage> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        Box::new(
+            self.spawn_transaction_on_pool(move |conn| {
+                let companies_packages_repo = repo_factory.create_companies_packages_repo(&*conn, user_id);
+                let products_repo = repo_factory.create_products_repo(&*conn, user_id);
+
+                let products_count = match companies_packages_repo.find_by_ids(company_id, package_id)? {
+                    Some(company_package) => products_repo.get_by_company_package_id(company_package.id, 0, 0)?.total_count,
+                    None => 0,
+                };
+                // products are deleted together with the company package, so the deletion is rolled back if they are not forced to
+                let company_package = companies_packages_repo.delete(company_id, package_id)?;
+                if products_count > 0 && !force {
+                    return Err(Error::Conflict(validation_errors!({
+                        "products": ["products" => format!("Company package is used by {} products, deletion must be forced", products_count)]
+                    }))
+                    .into());
+                }
+
+                Ok(company_package)
+            })
+            .map_err(|e| e.context("Service CompaniesPackages, delete endpoint error occured.").into()),
+        )
+    }
+
+    fn get_company_package_products(
+        &self,
+        company_package_id: CompanyPackageId,
+        offset: i64,
+        limit: i64,
+    ) -> ServiceFuture<CompanyPackageProducts> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_read_pool(move |conn| {
+            let products_repo = repo_factory.create_products_repo(&*conn, user_id);
+            products_repo
+                .get_by_company_package_id(company_package_id, offset, limit)
+                .map_err(|e| {
+                    e.context("Service CompaniesPackages, get_company_package_products endpoint error occurred.")
+                        .into()
+                })
         })
     }
 
     /// Get delivery price
     fn get_delivery_price(&self, payload: GetDeliveryPrice) -> ServiceFuture<Option<DeliveryPrice>> {
+        Box::new(
+            self.get_delivery_price_details(payload)
+                .map(|details| details.map(DeliveryPrice::from)),
+        )
+    }
+
+    /// Get delivery price with the explanation of how it was calculated
+    fn get_delivery_price_details(&self, payload: GetDeliveryPrice) -> ServiceFuture<Option<DeliveryPriceDetails>> {
         let repo_factory = self.static_context.repo_factory.clone();
+        let carriers_client = self.static_context.carriers_client.clone();
+        let shipping_rates_cache = self.static_context.shipping_rates_cache.clone();
         let user_id = self.dynamic_context.user_id;
+        let clock = self.static_context.clock.clone();
+        let request_context = self.dynamic_context.request_context.clone();
+        let carrier_available = carriers_client.is_some();
+        let rounding_policies = self.static_context.config.price_rounding_policies();
 
         let GetDeliveryPrice {
             company_package_id,
@@ -202,6 +838,15 @@ impl<
             weight,
             delivery_from,
             delivery_to,
+            coordinates,
+            pickup_point_id,
+            declared_value,
+            insurance,
+            cod,
+            store_id,
+            base_product_id,
+            delivery_to_subdivision,
+            postal_code,
         } = payload;
 
         let measurements = ShipmentMeasurements {
@@ -209,69 +854,559 @@ impl<
             weight_g: weight,
         };
 
-        self.spawn_on_pool(move |conn| {
+        let stored_price = self.spawn_on_read_pool(move |conn| {
             let companies_repo = repo_factory.create_companies_repo(&*conn, user_id);
             let packages_repo = repo_factory.create_packages_repo(&*conn, user_id);
             let companies_packages_repo = repo_factory.create_companies_packages_repo(&*conn, user_id);
             let shipping_rates_repo = repo_factory.create_shipping_rates_repo(&*conn, user_id);
+            let surcharges_repo = repo_factory.create_surcharges_repo_with_sys_acl(&*conn);
+            let pickup_points_repo = repo_factory.create_pickup_points_repo(&*conn, user_id);
+            let countries_repo = repo_factory.create_countries_repo(&*conn, user_id);
+            let carrier_accounts_repo = repo_factory.create_carrier_accounts_repo(&*conn, user_id);
+            let sys_carrier_accounts_repo = repo_factory.create_carrier_accounts_repo_with_sys_acl(&*conn);
+            let pricing_rules_repo = repo_factory.create_pricing_rules_repo_with_sys_acl(&*conn);
+            let postal_zones_repo = repo_factory.create_postal_zones_repo(&*conn, user_id);
+            let products_repo = repo_factory.create_products_repo(&*conn, user_id);
 
             let run = move || {
+                let product_store_id = product_store(&*products_repo, base_product_id, store_id)?;
+
                 let company_package = companies_packages_repo
                     .get(company_package_id)?
                     .ok_or(Error::Validate(validation_errors!({
                         "company_package": ["company_package" => format!("Company package with id: {} not found", company_package_id)]
                     })))?;
 
-                let delivery_price = match company_package.shipping_rate_source.clone() {
-                    ShippingRateSource::NotAvailable => None,
-                    ShippingRateSource::Static { dimensional_factor } => {
-                        let company = companies_repo
-                            .find(company_package.company_id)?
-                            .ok_or(format_err!("Company with id {} not found", company_package.company_id))?;
+                let insurance = select_insurance(&company_package, declared_value, insurance.as_ref().map(String::as_str))?;
+                let cod_fee = select_cash_on_delivery(&company_package, cod)?;
 
-                        let package = packages_repo
-                            .find(company_package.package_id)?
-                            .ok_or(format_err!("Package with id {} not found", company_package.package_id))?;
+                let pickup_point = match pickup_point_id {
+                    Some(pickup_point_id) => {
+                        let pickup_point = pickup_points_repo
+                            .find(pickup_point_id)?
+                            .filter(|pickup_point| pickup_point.company_id == company_package.company_id && pickup_point.country == delivery_to)
+                            .ok_or(Error::Validate(validation_errors!({
+                                "pickup_point_id": ["pickup_point_id" => format!("Pickup point with id: {} of the company in {} not found", pickup_point_id, delivery_to)]
+                            })))?;
+                        Some(pickup_point)
+                    }
+                    None => None,
+                };
 
-                        PackageValidation {
-                            measurements: measurements.clone(),
-                            package: package.clone(),
-                        }
-                        .validate()
-                        .map_err(Error::Validate)?;
+                if let Some(ref coordinates) = coordinates {
+                    coordinates.validate().map_err(Error::Validate)?;
+                }
 
-                        let currency = company.currency;
+                let (dimensional_factor, on_demand, distance_pricing) = match company_package.shipping_rate_source.clone() {
+                    ShippingRateSource::NotAvailable => return Ok(None),
+                    ShippingRateSource::Static { dimensional_factor } => (dimensional_factor, false, None),
+                    ShippingRateSource::OnDemand { dimensional_factor } => (dimensional_factor, true, None),
+                    ShippingRateSource::Distance(distance_pricing) => (None, false, Some(distance_pricing)),
+                };
 
-                        let shipping_available = ShippingValidation {
-                            delivery_from: Some(delivery_from.clone()),
-                            deliveries_to: vec![delivery_to.clone()],
-                            company,
-                            package,
-                        }
-                        .validate()
-                        .is_ok();
+                let company = companies_repo
+                    .find(company_package.company_id)?
+                    .ok_or(format_err!("Company with id {} not found", company_package.company_id))?;
+
+                let package = packages_repo
+                    .find(company_package.package_id)?
+                    .ok_or(format_err!("Package with id {} not found", company_package.package_id))?;
+
+                PackageValidation {
+                    measurements: measurements.clone(),
+                    package: package.clone(),
+                }
+                .validate()
+                .map_err(Error::Validate)?;
+
+                let currency = company.currency;
+                let company_label = company.label.clone();
+                let carrier_request = if on_demand && carrier_available {
+                    Some(CarrierRateRequest {
+                        company_package_id,
+                        company_label: company_label.clone(),
+                        delivery_from: delivery_from.clone(),
+                        delivery_to: delivery_to.clone(),
+                        measurements,
+                        account: None,
+                    })
+                } else {
+                    None
+                };
+
+                let shipping_available = ShippingValidation {
+                    delivery_from: Some(delivery_from.clone()),
+                    deliveries_to: vec![delivery_to.clone()],
+                    company,
+                    package,
+                }
+                .validate()
+                .is_ok();
+
+                if !shipping_available {
+                    return Ok(None);
+                }
+
+                let surcharges = surcharges_repo.find_applicable(company_package.company_id, company_package_id, delivery_to.clone())?;
+                let pricing_rules = match product_store_id {
+                    Some(store_id) => pricing_rules_repo
+                        .find_by_stores(vec![store_id])?
+                        .into_iter()
+                        .filter(|rule| rule.applies_to(store_id, company_package_id, &delivery_to))
+                        .collect(),
+                    None => vec![],
+                };
+
+                if let Some(distance_pricing) = distance_pricing {
+                    let distance_price = coordinates
+                        .and_then(|coordinates| distance_pricing.calculate_price_breakdown(&coordinates))
+                        .map(|breakdown| DeliveryPriceDetails::from_distance(currency, breakdown));
+                    return Ok(Some((
+                        currency,
+                        distance_price,
+                        None,
+                        None,
+                        surcharges,
+                        pickup_point,
+                        insurance,
+                        cod_fee,
+                        pricing_rules,
+                    )));
+                }
+
+                // Negotiated rate of the account of the store is requested before the marketplace rates. The store
+                // of the request is chosen by the caller, so its account is used only if the user manages the store
+                let account = match (product_store_id, store_id) {
+                    _ if !carrier_available => None,
+                    (Some(store_id), _) => sys_carrier_accounts_repo.find_by_company(store_id, company_package.company_id)?,
+                    (None, Some(store_id)) => carrier_accounts_repo.find_usable_by_company(store_id, company_package.company_id)?,
+                    (None, None) => None,
+                };
+                let negotiated_request = account.and_then(|account| match sys_carrier_accounts_repo.decrypt_credentials(&account) {
+                    Ok(credentials) => Some(CarrierRateRequest {
+                        company_package_id,
+                        company_label: company_label.clone(),
+                        delivery_from: delivery_from.clone(),
+                        delivery_to: delivery_to.clone(),
+                        measurements,
+                        account: Some(CarrierAccountCredentials {
+                            account_number: account.account_number,
+                            credentials,
+                        }),
+                    }),
+                    Err(e) => {
+                        error!("Credentials of carrier account {} could not be used: {}", account.id, e);
+                        None
+                    }
+                });
+
+                let steps = company_package.rate_resolution_steps();
+                let countries = if uses_countries_tree(&steps) {
+                    Some(countries_repo.get_all()?)
+                } else {
+                    None
+                };
+                let postal_zone_ids = match postal_code {
+                    Some(ref postal_code) if steps.contains(&RateResolutionStep::PostalZone) => postal_zones_repo
+                        .find_by_postal_code(delivery_to.clone(), postal_code.clone())?
+                        .into_iter()
+                        .map(|postal_zone| postal_zone.id)
+                        .collect(),
+                    _ => vec![],
+                };
+                let route = stored_rates_route(&steps, countries.as_ref(), delivery_to)
+                    .with_subdivision(delivery_to_subdivision.as_ref().map(|code| normalize_subdivision_code(code)))
+                    .with_postal_zones(postal_zone_ids);
 
-                        if !shipping_available {
-                            None
+                let mut rates = vec![];
+                // Rates to the postal zones and the subdivisions are not cached, the cache is keyed by the countries
+                if !route.postal_zone_ids.is_empty() {
+                    rates.extend(shipping_rates_repo.get_postal_zone_rates(
+                        company_package_id,
+                        delivery_from.clone(),
+                        route.delivery_to.clone(),
+                        route.postal_zone_ids.clone(),
+                    )?);
+                }
+                if let Some(ref code) = route.delivery_to_subdivision {
+                    if steps.contains(&RateResolutionStep::Subdivision) {
+                        rates.extend(shipping_rates_repo.get_subdivision_rates(
+                            company_package_id,
+                            delivery_from.clone(),
+                            route.delivery_to.clone(),
+                            code.clone(),
+                        )?);
+                    }
+                }
+                for rates_to in route.country_destinations() {
+                    let key = ShippingRatesCacheKey {
+                        company_package_id,
+                        delivery_from: delivery_from.clone(),
+                        delivery_to: rates_to,
+                    };
+                    rates.extend(get_route_rates(shipping_rates_cache.as_ref(), &*shipping_rates_repo, key)?);
+                }
+
+                let today = clock.today();
+                let price = |rates: &ShippingRates| {
+                    rates
+                        .calculate_delivery_price_breakdown_on(measurements, dimensional_factor, today)
+                        .map(|breakdown| DeliveryPriceDetails::from_stored_rates(currency, breakdown))
+                };
+
+                // Steps after the carrier quote give the price the carrier quote falls back to
+                let (before_carrier, after_carrier) = split_at_carrier_quote(&steps);
+                let (resolved, mut attempts) = resolve_stored_rates(before_carrier, &route, &rates, &price);
+                let (stored_price, carrier_request) = match (resolved, after_carrier) {
+                    (Some((_, details)), _) => (Some(details.with_rate_resolution(attempts)), None),
+                    (None, None) => (None, None),
+                    (None, Some(after_carrier)) => {
+                        let carrier_attempts = attempts.clone();
+                        attempts.push(RateResolutionAttempt {
+                            step: RateResolutionStep::CarrierQuote,
+                            outcome: if carrier_request.is_some() {
+                                RateResolutionOutcome::Failed
+                            } else {
+                                RateResolutionOutcome::NotApplicable
+                            },
+                        });
+                        let (resolved, after_attempts) = resolve_stored_rates(after_carrier, &route, &rates, &price);
+                        attempts.extend(after_attempts);
+                        let stored_price = resolved.map(|(_, details)| details.with_rate_resolution(attempts));
+                        // Stored price replaces the carrier quote only if it is requested
+                        let stored_price = if carrier_request.is_some() {
+                            stored_price.and_then(|price| {
+                                price.into_carrier_fallback(company_package.carrier_fallback, company_package.fallback_markup_percent)
+                            })
                         } else {
-                            shipping_rates_repo
-                                .get_rates(company_package_id, delivery_from, delivery_to)?
-                                .and_then(|rates| {
-                                    rates
-                                        .calculate_delivery_price(measurements, dimensional_factor)
-                                        .map(|price| DeliveryPrice { currency, value: price })
-                                })
-                        }
+                            stored_price
+                        };
+                        (stored_price, carrier_request.map(|request| (request, carrier_attempts)))
                     }
                 };
 
-                Ok(delivery_price)
+                Ok(Some((
+                    currency,
+                    stored_price,
+                    carrier_request,
+                    negotiated_request,
+                    surcharges,
+                    pickup_point,
+                    insurance,
+                    cod_fee,
+                    pricing_rules,
+                )))
             };
 
             run().map_err(|e: FailureError| {
                 e.context("Service CompaniesPackages, get_delivery_price endpoint error occurred.")
                     .into()
             })
+        });
+
+        let negotiated_carriers_client = carriers_client.clone();
+        let negotiated_request_context = request_context.clone();
+        let negotiated_rounding_policies = rounding_policies.clone();
+        let marketplace_price = move |prices: Option<PricingInputs>| -> ServiceFuture<Option<DeliveryPriceDetails>> {
+            match (prices, carriers_client) {
+                (
+                    Some((
+                        currency,
+                        stored_price,
+                        Some((carrier_request, mut attempts)),
+                        _,
+                        surcharges,
+                        pickup_point,
+                        insurance,
+                        cod_fee,
+                        pricing_rules,
+                    )),
+                    Some(carriers_client),
+                ) => Box::new(carriers_client.get_rate(carrier_request, &request_context).then(move |res| {
+                    let price = match res {
+                        Ok(value) => {
+                            attempts.push(RateResolutionAttempt {
+                                step: RateResolutionStep::CarrierQuote,
+                                outcome: RateResolutionOutcome::Matched,
+                            });
+                            Some(DeliveryPriceDetails::from_carrier(currency, value).with_rate_resolution(attempts))
+                        }
+                        Err(e) => {
+                            warn!(
+                                "{}",
+                                request_context.log_line(&format!(
+                                    "Falling back to stored rates for company package {}: {}",
+                                    company_package_id, e
+                                ))
+                            );
+                            stored_price
+                        }
+                    };
+                    Ok(price.map(|price| {
+                        price
+                            .with_pricing_rules(&pricing_rules)
+                            .with_surcharges(&surcharges)
+                            .with_pickup_point(pickup_point)
+                            .with_insurance(insurance)
+                            .with_cash_on_delivery(cod_fee)
+                            .with_rounding(&rounding_policies)
+                            .with_measurements(measurements)
+                    }))
+                })),
+                (prices, _) => Box::new(future::ok(prices.and_then(
+                    |(_, stored_price, _, _, surcharges, pickup_point, insurance, cod_fee, pricing_rules)| {
+                        stored_price.map(|price| {
+                            price
+                                .with_pricing_rules(&pricing_rules)
+                                .with_surcharges(&surcharges)
+                                .with_pickup_point(pickup_point)
+                                .with_insurance(insurance)
+                                .with_cash_on_delivery(cod_fee)
+                                .with_rounding(&rounding_policies)
+                                .with_measurements(measurements)
+                        })
+                    },
+                ))),
+            }
+        };
+
+        Box::new(
+            stored_price.and_then(move |mut prices| -> ServiceFuture<Option<DeliveryPriceDetails>> {
+                let negotiated_request = prices.as_mut().and_then(|prices| prices.3.take());
+                match (negotiated_request, negotiated_carriers_client) {
+                    (Some(negotiated_request), Some(carriers_client)) => {
+                        Box::new(carriers_client.get_rate(negotiated_request, &negotiated_request_context).then(
+                            move |res| -> ServiceFuture<Option<DeliveryPriceDetails>> {
+                                match res {
+                                    Ok(value) => Box::new(future::ok(prices.map(
+                                        |(currency, _, _, _, surcharges, pickup_point, insurance, cod_fee, pricing_rules)| {
+                                            DeliveryPriceDetails::from_negotiated_rate(currency, value)
+                                                .with_pricing_rules(&pricing_rules)
+                                                .with_surcharges(&surcharges)
+                                                .with_pickup_point(pickup_point)
+                                                .with_insurance(insurance)
+                                                .with_cash_on_delivery(cod_fee)
+                                                .with_rounding(&negotiated_rounding_policies)
+                                                .with_measurements(measurements)
+                                        },
+                                    ))),
+                                    Err(e) => {
+                                        warn!(
+                                            "{}",
+                                            negotiated_request_context.log_line(&format!(
+                                                "Falling back to marketplace rates for company package {}: {}",
+                                                company_package_id, e
+                                            ))
+                                        );
+                                        marketplace_price(prices)
+                                    }
+                                }
+                            },
+                        ))
+                    }
+                    (_, _) => marketplace_price(prices),
+                }
+            }),
+        )
+    }
+
+    /// Get delivery prices of several shipments at once, results are in the order of the items.
+    /// Only stored rates are used, looked up in the rate resolution order of the company packages:
+    /// carriers are not requested and packages priced by distance have no price.
+    /// Products, company packages, rates and surcharges of all the items are loaded at once,
+    /// so the number of queries does not depend on the number of items
+    fn get_delivery_prices_batch(&self, items: Vec<DeliveryPriceBatchItem>) -> ServiceFuture<Vec<DeliveryPriceBatchResult>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+        let clock = self.static_context.clock.clone();
+        let rounding_policies = self.static_context.config.price_rounding_policies();
+
+        self.spawn_on_read_pool(move |conn| {
+            let companies_packages_repo = repo_factory.create_companies_packages_repo(&*conn, user_id);
+            let shipping_rates_repo = repo_factory.create_shipping_rates_repo(&*conn, user_id);
+            let surcharges_repo = repo_factory.create_surcharges_repo_with_sys_acl(&*conn);
+            let pricing_rules_repo = repo_factory.create_pricing_rules_repo_with_sys_acl(&*conn);
+            let products_repo = repo_factory.create_products_repo(&*conn, user_id);
+            let countries_repo = repo_factory.create_countries_repo(&*conn, user_id);
+            let today = clock.today();
+
+            let run = move || {
+                if items.len() > MAX_DELIVERY_PRICES_BATCH_SIZE {
+                    return Err(Error::Validate(validation_errors!({
+                        "items": ["items" => format!("Batch must not contain more than {} items", MAX_DELIVERY_PRICES_BATCH_SIZE)]
+                    }))
+                    .into());
+                }
+
+                // pricing rules are applied by the stores of the products like in the price of the single parcel
+                let mut base_product_ids = items.iter().filter_map(|item| item.base_product_id).collect::<Vec<_>>();
+                base_product_ids.sort_by_key(|base_product_id| base_product_id.0);
+                base_product_ids.dedup();
+                let mut product_stores = HashMap::<BaseProductId, StoreId>::new();
+                for product in products_repo.get_by_base_product_ids(base_product_ids.clone())? {
+                    product_stores.entry(product.base_product_id).or_insert(product.store_id);
+                }
+                if let Some(base_product_id) = base_product_ids.into_iter().find(|id| !product_stores.contains_key(id)) {
+                    return Err(Error::Validate(validation_errors!({
+                        "base_product_id": ["base_product_id" => format!("Shipping of base product with id: {} not found", base_product_id)]
+                    }))
+                    .into());
+                }
+                let pricing_rules = pricing_rules_repo.find_by_stores(product_stores.values().cloned().collect())?;
+
+                let mut company_package_ids = items.iter().map(|item| item.company_package_id).collect::<Vec<_>>();
+                company_package_ids.sort_by_key(|company_package_id| company_package_id.0);
+                company_package_ids.dedup();
+                let company_packages = companies_packages_repo
+                    .get_many_with_companies_and_packages(company_package_ids.clone())?
+                    .into_iter()
+                    .map(|(company_package, company, package)| (company_package.id, (company_package, company, package)))
+                    .collect::<HashMap<CompanyPackageId, (CompanyPackage, Company, Packages)>>();
+                if let Some(company_package_id) = company_package_ids.into_iter().find(|id| !company_packages.contains_key(id)) {
+                    return Err(Error::Validate(validation_errors!({
+                        "company_package": ["company_package" => format!("Company package with id: {} not found", company_package_id)]
+                    }))
+                    .into());
+                }
+
+                let options = items
+                    .iter()
+                    .map(|item| {
+                        let (ref company_package, _, _) = company_packages[&item.company_package_id];
+                        let insurance =
+                            select_insurance(company_package, item.declared_value, item.insurance.as_ref().map(String::as_str))?;
+                        let cod_fee = select_cash_on_delivery(company_package, item.cod)?;
+                        Ok((insurance, cod_fee))
+                    })
+                    .collect::<Result<Vec<_>, Error>>()?;
+
+                let countries = if company_packages
+                    .values()
+                    .any(|&(ref company_package, _, _)| uses_countries_tree(&company_package.rate_resolution_steps()))
+                {
+                    Some(countries_repo.get_all()?)
+                } else {
+                    None
+                };
+
+                // measurements, dimensional factor and route of the shipments that can be priced by stored rates
+                let mut shipments = Vec::with_capacity(items.len());
+                let mut routes = vec![];
+                for item in &items {
+                    let (ref company_package, ref company, ref package) = company_packages[&item.company_package_id];
+
+                    let dimensional_factor = match company_package.shipping_rate_source {
+                        ShippingRateSource::Static { dimensional_factor } | ShippingRateSource::OnDemand { dimensional_factor } => {
+                            dimensional_factor
+                        }
+                        ShippingRateSource::NotAvailable | ShippingRateSource::Distance(_) => {
+                            shipments.push(None);
+                            continue;
+                        }
+                    };
+
+                    let measurements = ShipmentMeasurements {
+                        volume_cubic_cm: item.volume,
+                        weight_g: item.weight,
+                    };
+
+                    PackageValidation {
+                        measurements,
+                        package: package.clone(),
+                    }
+                    .validate()
+                    .map_err(Error::Validate)?;
+
+                    let shipping_available = ShippingValidation {
+                        delivery_from: Some(item.from.clone()),
+                        deliveries_to: vec![item.to.clone()],
+                        company: company.clone(),
+                        package: package.clone(),
+                    }
+                    .validate()
+                    .is_ok();
+
+                    if !shipping_available {
+                        shipments.push(None);
+                        continue;
+                    }
+
+                    let route = stored_rates_route(&company_package.rate_resolution_steps(), countries.as_ref(), item.to.clone());
+                    for rates_to in route.country_destinations() {
+                        let rates_route = (item.company_package_id, item.from.clone(), rates_to);
+                        if !routes.contains(&rates_route) {
+                            routes.push(rates_route);
+                        }
+                    }
+                    shipments.push(Some((measurements, dimensional_factor, route)));
+                }
+
+                let rates = shipping_rates_repo.get_rates_for_routes(routes)?;
+                let surcharges = surcharges_repo.list()?;
+
+                let results = items
+                    .into_iter()
+                    .zip(shipments)
+                    .zip(options)
+                    .map(|((item, shipment), (insurance, cod_fee))| {
+                        let (ref company_package, ref company, _) = company_packages[&item.company_package_id];
+
+                        let price = shipment.and_then(|(measurements, dimensional_factor, route)| {
+                            let route_rates = rates
+                                .iter()
+                                .filter(|rates| rates.company_package_id == item.company_package_id && rates.from_alpha3 == item.from)
+                                .cloned()
+                                .collect::<Vec<_>>();
+                            let (resolved, attempts) =
+                                resolve_stored_rates(&company_package.rate_resolution_steps(), &route, &route_rates, |rates| {
+                                    rates.calculate_delivery_price_breakdown_on(measurements, dimensional_factor, today)
+                                });
+                            let (_, breakdown) = resolved?;
+
+                            let applicable_surcharges = surcharges
+                                .iter()
+                                .filter(|surcharge| surcharge.applies_to(company_package.company_id, item.company_package_id, &item.to))
+                                .cloned()
+                                .collect::<Vec<_>>();
+                            let applicable_pricing_rules = item
+                                .base_product_id
+                                .and_then(|base_product_id| product_stores.get(&base_product_id))
+                                .map(|&store_id| {
+                                    pricing_rules
+                                        .iter()
+                                        .filter(|rule| rule.applies_to(store_id, item.company_package_id, &item.to))
+                                        .cloned()
+                                        .collect::<Vec<_>>()
+                                })
+                                .unwrap_or_default();
+
+                            Some(DeliveryPrice::from(
+                                DeliveryPriceDetails::from_stored_rates(company.currency, breakdown)
+                                    .with_rate_resolution(attempts)
+                                    .with_pricing_rules(&applicable_pricing_rules)
+                                    .with_surcharges(&applicable_surcharges)
+                                    .with_insurance(insurance)
+                                    .with_cash_on_delivery(cod_fee)
+                                    .with_rounding(&rounding_policies),
+                            ))
+                        });
+
+                        DeliveryPriceBatchResult {
+                            company_package_id: item.company_package_id,
+                            from: item.from,
+                            to: item.to,
+                            price,
+                        }
+                    })
+                    .collect();
+
+                Ok(results)
+            };
+
+            run().map_err(|e: FailureError| {
+                e.context("Service CompaniesPackages, get_delivery_prices_batch endpoint error occurred.")
+                    .into()
+            })
         })
     }
 
@@ -280,7 +1415,7 @@ impl<
         let repo_factory = self.static_context.repo_factory.clone();
         let user_id = self.dynamic_context.user_id;
 
-        self.spawn_on_pool(move |conn| {
+        self.spawn_on_admin_pool(move |conn| {
             let shipping_rates_repo = repo_factory.create_shipping_rates_repo(&*conn, user_id);
             shipping_rates_repo
                 .get_all_rates_from(company_package_id, delivery_from)
@@ -298,81 +1433,481 @@ impl<
         payload: ReplaceShippingRatesPayload,
     ) -> ServiceFuture<Vec<ShippingRates>> {
         let repo_factory = self.static_context.repo_factory.clone();
+        let shipping_rates_cache = self.static_context.shipping_rates_cache.clone();
         let user_id = self.dynamic_context.user_id;
+        let default_max_entries = self
+            .static_context
+            .config
+            .rates_quota
+            .as_ref()
+            .map(|quota| quota.default_max_entries);
+        let events_enabled = self.events_enabled();
+        let service = self.clone();
 
-        self.spawn_on_pool(move |conn| {
-            let ReplaceShippingRatesPayload {
-                rates_csv_base64,
-                zones_csv_base64,
-            } = payload;
-
-            let rates = base64::decode(&rates_csv_base64)
-                .map_err(|_| {
-                    let errors = validation_errors!({ "payload": ["rates_csv_base64" => "Failed to decode base64 rates CSV"] });
-                    Error::Validate(errors).into()
-                })
-                .and_then(|csv| {
-                    RatesCsvData::parse_csv(csv.as_slice()).map_err(|e| {
-                        let errors = validation_errors!({ "payload": ["rates_csv_base64" => e.to_string()] });
-                        FailureError::from(Error::Validate(errors))
-                    })
-                })?;
+        let replaced = self.spawn_transaction_on_admin_pool(move |conn| {
+            let zones_repo = repo_factory.create_zones_repo(&*conn, user_id);
+            let country_subdivisions_repo = repo_factory.create_country_subdivisions_repo(&*conn, user_id);
+            let postal_zones_repo = repo_factory.create_postal_zones_repo(&*conn, user_id);
+            let companies_packages_repo = repo_factory.create_companies_packages_repo(&*conn, user_id);
+            let packages_repo = repo_factory.create_packages_repo(&*conn, user_id);
+            let shipping_rates_repo = repo_factory.create_shipping_rates_repo(&*conn, user_id);
+            let company_rates_quotas_repo = repo_factory.create_company_rates_quotas_repo_with_sys_acl(&*conn);
+            let event_outbox_repo = repo_factory.create_event_outbox_repo_with_sys_acl(&*conn);
 
-            let zones = base64::decode(&zones_csv_base64)
-                .map_err(|_| {
-                    let errors = validation_errors!({ "payload": ["zones_csv_base64" => "Failed to decode base64 zones CSV"] });
-                    Error::Validate(errors).into()
-                })
-                .and_then(|csv| {
-                    ZonesCsvData::parse_csv(csv.as_slice()).map_err(|e| {
-                        let errors = validation_errors!({ "payload": ["zones_csv_base64" => e.to_string()] });
-                        FailureError::from(Error::Validate(errors))
-                    })
-                })?;
+            let company_package = companies_packages_repo
+                .get(company_package_id)?
+                .ok_or_else(|| format_err!("Company package with id = {} not found", company_package_id).context(Error::NotFound))?;
+
+            let known_subdivisions = country_subdivisions_repo.list_all()?;
+            let known_postal_zones = postal_zones_repo.list(None)?;
+
+            let (delivery_from, new_shipping_rates, known_zones) =
+                parse_shipping_rates_payload(company_package_id, payload, &*zones_repo, &known_subdivisions, &known_postal_zones)?;
+
+            let package = packages_repo
+                .find(company_package.package_id)?
+                .ok_or(format_err!("Package with id = {} not found", company_package.package_id))?;
 
-            let NewShippingRatesBatch {
+            validate_weight_bands(&new_shipping_rates, &known_zones, package.max_weight).map_err(Error::Validate)?;
+
+            // Rates being replaced are not counted, they are deleted before the new ones are inserted
+            let old_shipping_rates = shipping_rates_repo.delete_all_rates_from(company_package_id, delivery_from.clone())?;
+            let new_shipping_rates = insert_rates_within_quota(
+                &*company_rates_quotas_repo,
+                &*shipping_rates_repo,
+                default_max_entries,
+                company_package.company_id,
+                new_shipping_rates,
+            )?;
+
+            let deliveries_to = new_shipping_rates.iter().map(|rates| rates.to_alpha3.clone()).collect::<Vec<_>>();
+            let mut removed_deliveries_to = vec![];
+            for rates in &old_shipping_rates {
+                if !deliveries_to.contains(&rates.to_alpha3) && !removed_deliveries_to.contains(&rates.to_alpha3) {
+                    removed_deliveries_to.push(rates.to_alpha3.clone());
+                }
+            }
+            let event = DeliveryEvent::ShippingRatesReplaced {
                 company_package_id,
                 delivery_from,
-                delivery_to_rates,
-            } = NewShippingRatesBatch::try_from_csv_data(company_package_id, zones, rates).map_err(|e| {
-                let errors = validation_errors!({ "payload": ["payload" => e.to_string()] });
-                FailureError::from(Error::Validate(errors))
-            })?;
+                deliveries_to,
+                removed_deliveries_to,
+            };
+            let outbox_events = events::store(&*event_outbox_repo, events_enabled, vec![event])?;
 
-            let new_shipping_rates = delivery_to_rates
-                .into_iter()
-                .map(|(to_alpha3, rates)| NewShippingRates {
-                    company_package_id: company_package_id.clone(),
-                    from_alpha3: delivery_from.clone(),
-                    to_alpha3,
-                    rates,
-                })
-                .collect::<Vec<_>>();
+            Ok((old_shipping_rates, new_shipping_rates, outbox_events))
+        });
+
+        Box::new(
+            replaced
+                .map_err(|e| FailureError::from(e.context("Service CompaniesPackages, replace_shipping_rates endpoint error occured.")))
+                .and_then(move |(old_shipping_rates, new_shipping_rates, outbox_events)| {
+                    // Cache is cleared after the commit, otherwise a concurrent price request could cache the old rates again
+                    remove_cached_route_rates(
+                        shipping_rates_cache.as_ref(),
+                        old_shipping_rates.iter().chain(new_shipping_rates.iter()),
+                    );
+                    service.publish_events(outbox_events).map(move |_| new_shipping_rates)
+                }),
+        )
+    }
 
+    /// Validates the replacement of the shipping rates and compares them with the current ones
+    fn preview_shipping_rates_replacement(
+        &self,
+        company_package_id: CompanyPackageId,
+        payload: ReplaceShippingRatesPayload,
+    ) -> ServiceFuture<ShippingRatesReplacementPreview> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+        let default_max_entries = self
+            .static_context
+            .config
+            .rates_quota
+            .as_ref()
+            .map(|quota| quota.default_max_entries);
+
+        self.spawn_on_admin_pool(move |conn| {
+            let zones_repo = repo_factory.create_zones_repo(&*conn, user_id);
+            let country_subdivisions_repo = repo_factory.create_country_subdivisions_repo(&*conn, user_id);
+            let postal_zones_repo = repo_factory.create_postal_zones_repo(&*conn, user_id);
             let companies_packages_repo = repo_factory.create_companies_packages_repo(&*conn, user_id);
+            let packages_repo = repo_factory.create_packages_repo(&*conn, user_id);
+            let shipping_rates_repo = repo_factory.create_shipping_rates_repo(&*conn, user_id);
+            let company_rates_quotas_repo = repo_factory.create_company_rates_quotas_repo_with_sys_acl(&*conn);
+
+            let preview = || -> Result<ShippingRatesReplacementPreview, FailureError> {
+                let company_package = companies_packages_repo
+                    .get(company_package_id)?
+                    .ok_or_else(|| format_err!("Company package with id = {} not found", company_package_id).context(Error::NotFound))?;
+
+                let known_subdivisions = country_subdivisions_repo.list_all()?;
+                let known_postal_zones = postal_zones_repo.list(None)?;
+                let (delivery_from, new_shipping_rates, known_zones) =
+                    parse_shipping_rates_payload(company_package_id, payload, &*zones_repo, &known_subdivisions, &known_postal_zones)?;
+
+                let package = packages_repo
+                    .find(company_package.package_id)?
+                    .ok_or(format_err!("Package with id = {} not found", company_package.package_id))?;
+
+                validate_weight_bands(&new_shipping_rates, &known_zones, package.max_weight).map_err(Error::Validate)?;
+
+                let entries_count = count_rate_entries(&new_shipping_rates);
+                rates_quota_usage(
+                    &*company_rates_quotas_repo,
+                    &*shipping_rates_repo,
+                    default_max_entries,
+                    company_package.company_id,
+                    Some((company_package_id, delivery_from.clone())),
+                )?
+                .check_upload(entries_count)
+                .map_err(Error::Validate)?;
+
+                let old_shipping_rates = shipping_rates_repo.get_all_rates_from(company_package_id, delivery_from.clone())?;
+                let new_shipping_rates = resolve_new_rates_zones(&new_shipping_rates, &known_zones);
+                let (unchanged_count, changes) = diff_shipping_rates(&old_shipping_rates, &new_shipping_rates);
+
+                let missing_destinations =
+                    get_countries_from_forest_by(package.deliveries_to.iter(), |country| country.level == Country::COUNTRY_LEVEL)
+                        .into_iter()
+                        .map(|country| country.alpha3)
+                        .filter(|alpha3| !new_shipping_rates.iter().any(|(to_alpha3, _)| to_alpha3 == alpha3))
+                        .collect();
+
+                Ok(ShippingRatesReplacementPreview {
+                    company_package_id,
+                    delivery_from,
+                    entries_count,
+                    unchanged_count,
+                    changes,
+                    missing_destinations,
+                })
+            };
+
+            preview().map_err(|e| {
+                e.context("Service CompaniesPackages, preview_shipping_rates_replacement endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Add dated components to the shipping rates from the country in the company package
+    fn add_rate_components(&self, company_package_id: CompanyPackageId, payload: NewRateComponents) -> ServiceFuture<Vec<ShippingRates>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let shipping_rates_cache = self.static_context.shipping_rates_cache.clone();
+        let user_id = self.dynamic_context.user_id;
+        let clock = self.static_context.clock.clone();
+
+        let added = self.spawn_transaction_on_admin_pool(move |conn| {
             let shipping_rates_repo = repo_factory.create_shipping_rates_repo(&*conn, user_id);
+            let NewRateComponents { delivery_from, components } = payload;
 
+            shipping_rates_repo
+                .add_components(company_package_id, delivery_from, components, clock.today())
+                .map_err(|e| {
+                    e.context("Service CompaniesPackages, add_rate_components endpoint error occured.")
+                        .into()
+                })
+        });
+
+        Box::new(added.map(move |rates| {
+            // Cache is cleared after the commit, otherwise a concurrent price request could cache the old rates again
+            remove_cached_route_rates(shipping_rates_cache.as_ref(), rates.iter());
+            rates
+        }))
+    }
+
+    /// Sets the order the price of the company package is resolved in
+    fn set_rate_resolution_order(
+        &self,
+        company_package_id: CompanyPackageId,
+        payload: SetRateResolutionOrder,
+    ) -> ServiceFuture<Option<CompanyPackage>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let companies_packages_repo = repo_factory.create_companies_packages_repo(&*conn, user_id);
             companies_packages_repo
-                .get(company_package_id)
-                .map_err(|e| FailureError::from(e.context("Service CompaniesPackages, replace_shipping_rates endpoint error occured.")))?
-                .ok_or(format_err!("Company package with id = {} not found", company_package_id))?;
+                .set_rate_resolution_order(company_package_id, payload.steps)
+                .map_err(|e| {
+                    e.context("Service CompaniesPackages, set_rate_resolution_order endpoint error occured.")
+                        .into()
+                })
+        })
+    }
+
+    /// Sets whether the company package supports the cash on delivery and its fee
+    fn set_cash_on_delivery(
+        &self,
+        company_package_id: CompanyPackageId,
+        payload: SetCashOnDelivery,
+    ) -> ServiceFuture<Option<CompanyPackage>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
 
-            conn.transaction::<Vec<ShippingRates>, FailureError, _>(move || {
-                shipping_rates_repo.delete_all_rates_from(company_package_id, delivery_from)?;
-                shipping_rates_repo.insert_many(new_shipping_rates)
+        self.spawn_on_pool(move |conn| {
+            let companies_packages_repo = repo_factory.create_companies_packages_repo(&*conn, user_id);
+            companies_packages_repo
+                .set_cash_on_delivery(company_package_id, payload)
+                .map_err(|e| {
+                    e.context("Service CompaniesPackages, set_cash_on_delivery endpoint error occured.")
+                        .into()
+                })
+        })
+    }
+
+    /// Sets the price quoted when the carrier quote of the company package fails
+    fn set_carrier_fallback(
+        &self,
+        company_package_id: CompanyPackageId,
+        payload: SetCarrierFallback,
+    ) -> ServiceFuture<Option<CompanyPackage>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let companies_packages_repo = repo_factory.create_companies_packages_repo(&*conn, user_id);
+            companies_packages_repo
+                .set_carrier_fallback(company_package_id, payload)
+                .map_err(|e| {
+                    e.context("Service CompaniesPackages, set_carrier_fallback endpoint error occured.")
+                        .into()
+                })
+        })
+    }
+
+    /// Sets the daily cut-off time and the number of shipments the company package takes a day
+    fn set_shipping_capacity(
+        &self,
+        company_package_id: CompanyPackageId,
+        payload: SetShippingCapacity,
+    ) -> ServiceFuture<Option<CompanyPackage>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let companies_packages_repo = repo_factory.create_companies_packages_repo(&*conn, user_id);
+            companies_packages_repo
+                .set_shipping_capacity(company_package_id, payload)
+                .map_err(|e| {
+                    e.context("Service CompaniesPackages, set_shipping_capacity endpoint error occured.")
+                        .into()
+                })
+        })
+    }
+
+    /// Books a shipment of the order on the shipping date by the cut-off time, conflicts if the company package is booked up on it
+    fn book_shipment(&self, company_package_id: CompanyPackageId, payload: BookShipment) -> ServiceFuture<ShipmentBooking> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+        let clock = self.static_context.clock.clone();
+
+        Box::new(
+            self.spawn_transaction_on_pool(move |conn| {
+                let companies_packages_repo = repo_factory.create_companies_packages_repo(&*conn, user_id);
+                let shipment_bookings_repo = repo_factory.create_shipment_bookings_repo(&*conn, user_id);
+
+                let company_package = companies_packages_repo
+                    .get(company_package_id)?
+                    .ok_or_else(|| format_err!("Company package {} not found", company_package_id).context(Error::NotFound))?;
+                let shipping_date = company_package.shipping_date(payload.order_time.unwrap_or_else(|| clock.now()));
+
+                // the booking is rolled back if the shipments already booked on the date reached the capacity
+                let booking = shipment_bookings_repo.book(company_package_id, shipping_date)?;
+                if company_package.is_booked_up(booking.shipments_count - 1) {
+                    return Err(Error::Conflict(validation_errors!({
+                        "shipping_date": ["booked_up" => format!("Company package is booked up on {}", shipping_date)]
+                    }))
+                    .into());
+                }
+
+                Ok(booking)
             })
-            .map_err(|e| {
-                e.context("Service CompaniesPackages, replace_shipping_rates endpoint error occured.")
-                    .into()
+            .map_err(|e| e.context("Service CompaniesPackages, book_shipment endpoint error occured.").into()),
+        )
+    }
+}
+
+/// Validates the uploaded CSV tables, returns the "from" country and the new rates of it
+fn parse_shipping_rates_payload(
+    company_package_id: CompanyPackageId,
+    payload: ReplaceShippingRatesPayload,
+    zones_repo: &dyn ZonesRepo,
+    known_subdivisions: &[CountrySubdivision],
+    known_postal_zones: &[PostalZone],
+) -> Result<(Alpha3, Vec<NewShippingRates>, Vec<Zone>), FailureError> {
+    let ReplaceShippingRatesPayload {
+        rates_csv_base64,
+        zones_csv_base64,
+        delivery_time_min_days,
+        delivery_time_max_days,
+    } = payload;
+
+    validate_delivery_time(delivery_time_min_days, delivery_time_max_days).map_err(Error::Validate)?;
+
+    let rates = base64::decode(&rates_csv_base64)
+        .map_err(|_| {
+            let errors = validation_errors!({ "payload": ["rates_csv_base64" => "Failed to decode base64 rates CSV"] });
+            Error::Validate(errors).into()
+        })
+        .and_then(|csv| {
+            RatesCsvData::parse_csv(csv.as_slice()).map_err(|e| {
+                let errors = validation_errors!({ "payload": ["rates_csv_base64" => e.to_string()] });
+                FailureError::from(Error::Validate(errors))
+            })
+        })?;
+
+    let zones = base64::decode(&zones_csv_base64)
+        .map_err(|_| {
+            let errors = validation_errors!({ "payload": ["zones_csv_base64" => "Failed to decode base64 zones CSV"] });
+            Error::Validate(errors).into()
+        })
+        .and_then(|csv| {
+            ZonesCsvData::parse_csv(csv.as_slice()).map_err(|e| {
+                let errors = validation_errors!({ "payload": ["zones_csv_base64" => e.to_string()] });
+                FailureError::from(Error::Validate(errors))
             })
+        })?;
+
+    // Only the zones named in the zone table are loaded, its other "to" values are not found by the names
+    let mut zone_names = zones.0.iter().map(|entry| entry.to.clone()).collect::<Vec<_>>();
+    zone_names.sort();
+    zone_names.dedup();
+    let known_zones = zones_repo.find_by_names(zone_names)?;
+
+    let NewShippingRatesBatch {
+        company_package_id,
+        delivery_from,
+        delivery_to_rates,
+    } = NewShippingRatesBatch::try_from_csv_data(
+        company_package_id,
+        zones,
+        rates,
+        &known_zones,
+        known_subdivisions,
+        known_postal_zones,
+    )
+    .map_err(|e| {
+        let errors = validation_errors!({ "payload": ["payload" => e.to_string()] });
+        FailureError::from(Error::Validate(errors))
+    })?;
+
+    let new_shipping_rates = delivery_to_rates
+        .into_iter()
+        .map(|(to, rates)| NewShippingRates {
+            company_package_id: company_package_id.clone(),
+            from_alpha3: delivery_from.clone(),
+            to,
+            rates,
+            delivery_time_min_days,
+            delivery_time_max_days,
         })
+        .collect::<Vec<_>>();
+
+    Ok((delivery_from, new_shipping_rates, known_zones))
+}
+
+/// Removes cached rates of the routes, so that the changed rates are read from db
+fn remove_cached_route_rates<'a, I: Iterator<Item = &'a ShippingRates>>(
+    shipping_rates_cache: Option<&Arc<dyn ShippingRatesCache>>,
+    rates: I,
+) {
+    if let Some(shipping_rates_cache) = shipping_rates_cache {
+        for rates in rates {
+            shipping_rates_cache.remove(&ShippingRatesCacheKey {
+                company_package_id: rates.company_package_id,
+                delivery_from: rates.from_alpha3.clone(),
+                delivery_to: rates.to_alpha3.clone(),
+            });
+        }
+    }
+}
+
+/// Rates of the route, taken from the cache if it is set. Rates read from db are put in the cache
+fn get_route_rates(
+    shipping_rates_cache: Option<&Arc<dyn ShippingRatesCache>>,
+    shipping_rates_repo: &dyn ShippingRatesRepo,
+    key: ShippingRatesCacheKey,
+) -> Result<Option<ShippingRates>, FailureError> {
+    if let Some(cached) = shipping_rates_cache.and_then(|cache| cache.get(&key)) {
+        return Ok(cached.rates);
     }
+
+    let rates = shipping_rates_repo.get_rates(key.company_package_id, key.delivery_from.clone(), key.delivery_to.clone())?;
+    if let Some(cache) = shipping_rates_cache {
+        cache.set(&key, CachedShippingRates { rates: rates.clone() });
+    }
+
+    Ok(rates)
+}
+
+/// Stored shipping rates of the company package to the countries it delivers to with its dimensional factor,
+/// `None` if the price of the company package is not based on them
+fn package_rates(
+    shipping_rates_repo: &dyn ShippingRatesRepo,
+    deliveries_from: &Alpha3,
+    pkg: &AvailablePackages,
+) -> Result<Option<(Option<u32>, Vec<ShippingRates>)>, FailureError> {
+    let deliveries_to = get_countries_from_forest_by(pkg.deliveries_to.iter(), |country| country.level == Country::COUNTRY_LEVEL)
+        .into_iter()
+        .map(|country| country.alpha3)
+        .collect::<Vec<_>>();
+
+    match pkg.shipping_rate_source {
+        // Distance is not known without the buyer coordinates
+        ShippingRateSource::NotAvailable | ShippingRateSource::Distance(_) => Ok(None),
+        ShippingRateSource::Static { dimensional_factor } | ShippingRateSource::OnDemand { dimensional_factor } => shipping_rates_repo
+            .get_multiple_rates(pkg.id, deliveries_from.clone(), deliveries_to)
+            .map(|rates| Some((dimensional_factor, rates))),
+    }
+}
+
+/// Company packages out of `ids` booked up on the shipping date of the order placed at `order_time`, with the date.
+/// Bookings are looked up only for the company packages with the daily capacity
+fn find_booked_up_packages(
+    companies_packages_repo: &dyn CompaniesPackagesRepo,
+    shipment_bookings_repo: &dyn ShipmentBookingsRepo,
+    ids: Vec<CompanyPackageId>,
+    order_time: DateTime<Utc>,
+) -> Result<Vec<(CompanyPackageId, NaiveDate)>, FailureError> {
+    let scheduled = companies_packages_repo
+        .get_many_with_companies(ids)?
+        .into_iter()
+        .filter(|&(ref company_package, _)| company_package.max_daily_shipments.is_some())
+        .map(|(company_package, _)| {
+            let shipping_date = company_package.shipping_date(order_time);
+            (company_package, shipping_date)
+        })
+        .collect::<Vec<_>>();
+    if scheduled.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let keys = scheduled
+        .iter()
+        .map(|&(ref company_package, shipping_date)| (company_package.id, shipping_date))
+        .collect();
+    let bookings = shipment_bookings_repo.find_many(keys)?;
+
+    Ok(scheduled
+        .into_iter()
+        .filter(|&(ref company_package, shipping_date)| {
+            let shipments_count = bookings
+                .iter()
+                .find(|booking| booking.company_package_id == company_package.id && booking.shipping_date == shipping_date)
+                .map_or(0, |booking| booking.shipments_count);
+            company_package.is_booked_up(shipments_count)
+        })
+        .map(|(company_package, shipping_date)| (company_package.id, shipping_date))
+        .collect())
 }
 
 fn determine_package_availability(
     rates: Option<(Option<u32>, Vec<ShippingRates>)>,
     volume: u32,
     weight: u32,
+    today: NaiveDate,
     mut pkg: AvailablePackages,
 ) -> Option<AvailablePackages> {
     match rates {
@@ -390,7 +1925,7 @@ fn determine_package_availability(
                         weight_g: weight,
                     };
                     rates
-                        .calculate_delivery_price(measurements, dimensional_factor)
+                        .calculate_delivery_price_on(measurements, dimensional_factor, today)
                         .map(move |_| rates.to_alpha3)
                 })
                 .collect::<Vec<_>>();
@@ -410,3 +1945,203 @@ fn determine_package_availability(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use tokio_core::reactor::Core;
+
+    use stq_http::errors::ErrorMessageWrapper;
+    use stq_types::*;
+
+    use errors::Error;
+    use stq_static_resources::Currency;
+
+    use models::{
+        CarrierFallback, DistancePriceBreakdown, PriceRoundingPolicy, RateResolutionStep, RoundingMode, SetCarrierFallback,
+        SetCashOnDelivery, SetRateResolutionOrder, UnavailabilityReason,
+    };
+    use repos::repo_factory::tests::*;
+    use services::companies_packages::{CompaniesPackagesService, DeliveryPriceDetails, DeliveryPriceSource, GetDeliveryPrice};
+
+    #[test]
+    fn packages_out_of_limits_are_explained() {
+        let mut core = Core::new().unwrap();
+        let handle = Arc::new(core.handle());
+        let service = create_service(Some(MOCK_USER_ID), handle);
+        let rus = Alpha3("RUS".to_string());
+
+        let explanation = core.run(service.explain_available_packages(rus.clone(), 100, 100)).unwrap();
+        assert_eq!(explanation.packages.len(), 1);
+        assert!(explanation.unavailable_packages.is_empty());
+
+        let explanation = core.run(service.explain_available_packages(rus, 100, 2000)).unwrap();
+        assert!(explanation.packages.is_empty());
+        match explanation.unavailable_packages[0].reasons[..] {
+            [UnavailabilityReason::PackageWeightOutOfRange { max_weight_g, .. }] => assert_eq!(max_weight_g, 1000),
+            ref reasons => panic!("Unexpected reasons: {:?}", reasons),
+        }
+    }
+
+    #[test]
+    fn rate_resolution_order_falls_back_to_default() {
+        let mut core = Core::new().unwrap();
+        let handle = Arc::new(core.handle());
+        let service = create_service(Some(MOCK_USER_ID), handle);
+        let steps = vec![RateResolutionStep::RegionGroup, RateResolutionStep::FallbackRate];
+
+        let payload = SetRateResolutionOrder {
+            steps: Some(steps.clone()),
+        };
+        let company_package = core
+            .run(service.set_rate_resolution_order(CompanyPackageId(1), payload))
+            .unwrap()
+            .unwrap();
+        assert_eq!(company_package.rate_resolution_steps(), steps);
+
+        let payload = SetRateResolutionOrder { steps: None };
+        let company_package = core
+            .run(service.set_rate_resolution_order(CompanyPackageId(1), payload))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            company_package.rate_resolution_steps(),
+            vec![
+                RateResolutionStep::PostalZone,
+                RateResolutionStep::Subdivision,
+                RateResolutionStep::ExactDestination,
+                RateResolutionStep::Zone
+            ]
+        );
+    }
+
+    #[test]
+    fn product_of_another_store_is_not_priced_for_the_store() {
+        let mut core = Core::new().unwrap();
+        let handle = Arc::new(core.handle());
+        let service = create_service(None, handle);
+
+        let payload = GetDeliveryPrice {
+            company_package_id: CompanyPackageId(1),
+            delivery_from: Alpha3("RUS".to_string()),
+            delivery_to: Alpha3("USA".to_string()),
+            volume: 100,
+            weight: 100,
+            coordinates: None,
+            pickup_point_id: None,
+            declared_value: None,
+            insurance: None,
+            cod: false,
+            store_id: Some(StoreId(MOCK_STORE_ID.0 + 1)),
+            base_product_id: Some(MOCK_BASE_PRODUCT_ID),
+            delivery_to_subdivision: None,
+            postal_code: None,
+        };
+        let err = core
+            .run(service.get_delivery_price(payload))
+            .expect_err("Pricing rules of the store must not be applied to the product of another store");
+        assert_eq!(ErrorMessageWrapper::<Error>::from(&err).inner.code, 400);
+    }
+
+    #[test]
+    fn cash_on_delivery_is_priced_only_when_supported() {
+        let mut core = Core::new().unwrap();
+        let handle = Arc::new(core.handle());
+        let service = create_service(Some(MOCK_USER_ID), handle);
+
+        let payload = SetCashOnDelivery {
+            cod_supported: true,
+            cod_fee: Some(2.5),
+        };
+        let company_package = core
+            .run(service.set_cash_on_delivery(CompanyPackageId(1), payload))
+            .unwrap()
+            .unwrap();
+        assert_eq!(company_package.cash_on_delivery_fee(), Some(2.5));
+
+        let payload = GetDeliveryPrice {
+            company_package_id: CompanyPackageId(1),
+            delivery_from: Alpha3("RUS".to_string()),
+            delivery_to: Alpha3("USA".to_string()),
+            volume: 100,
+            weight: 100,
+            coordinates: None,
+            pickup_point_id: None,
+            declared_value: None,
+            insurance: None,
+            cod: true,
+            store_id: None,
+            base_product_id: None,
+            delivery_to_subdivision: None,
+            postal_code: None,
+        };
+        let err = core
+            .run(service.get_delivery_price(payload))
+            .expect_err("Cash on delivery must not be priced for the company package not supporting it");
+        assert_eq!(ErrorMessageWrapper::<Error>::from(&err).inner.code, 400);
+    }
+
+    #[test]
+    fn carrier_fallback_marks_up_stored_rates() {
+        let mut core = Core::new().unwrap();
+        let handle = Arc::new(core.handle());
+        let service = create_service(Some(MOCK_USER_ID), handle);
+
+        let payload = SetCarrierFallback {
+            carrier_fallback: CarrierFallback::StoredRates,
+            fallback_markup_percent: Some(10.0),
+        };
+        let company_package = core
+            .run(service.set_carrier_fallback(CompanyPackageId(1), payload))
+            .unwrap()
+            .unwrap();
+
+        let price = DeliveryPriceDetails::from_carrier(Currency::STQ, 20.0)
+            .into_carrier_fallback(company_package.carrier_fallback, company_package.fallback_markup_percent)
+            .unwrap();
+        assert_eq!(price.value, 22.0);
+        assert_eq!(price.surcharges[0].name, "fallback_markup");
+        match price.source {
+            DeliveryPriceSource::Fallback => {}
+            source => panic!("Unexpected source of the fallback price: {:?}", source),
+        }
+
+        let price = DeliveryPriceDetails::from_carrier(Currency::STQ, 20.0).into_carrier_fallback(CarrierFallback::NoPrice, None);
+        assert!(price.is_none());
+    }
+
+    #[test]
+    fn final_price_is_rounded_by_policy_of_currency() {
+        let policies = vec![PriceRoundingPolicy {
+            currency: Currency::STQ,
+            step: 0.05,
+            mode: RoundingMode::Up,
+        }];
+
+        let price = DeliveryPriceDetails::from_carrier(Currency::STQ, 123.4567).with_rounding(&policies);
+        assert_eq!(price.value, 123.5);
+        let rounding = price.rounding.expect("Rounding policy must be reported in the breakdown");
+        assert_eq!(rounding.step, 0.05);
+        assert!((rounding.adjustment - 0.0433).abs() < 1e-9);
+
+        let price = DeliveryPriceDetails::from_carrier(Currency::USD, 123.4567).with_rounding(&policies);
+        assert_eq!(price.value, 123.4567);
+        assert!(price.rounding.is_none());
+    }
+
+    #[test]
+    fn min_price_is_reported_apart_from_surcharges() {
+        let breakdown = DistancePriceBreakdown {
+            distance_km: 3.0,
+            base_rate: 1500.0,
+            min_price_adjustment: 500.0,
+            price: 2000.0,
+        };
+
+        let price = DeliveryPriceDetails::from_distance(Currency::STQ, breakdown);
+        assert_eq!(price.value, 2000.0);
+        assert_eq!(price.base_rate, 1500.0);
+        assert_eq!(price.min_price_adjustment, 500.0);
+        assert!(price.surcharges.is_empty());
+    }
+}
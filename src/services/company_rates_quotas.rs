@@ -0,0 +1,214 @@
+//! Company rates quotas Services, presents operations with quotas of the shipping rates storage of the companies
+
+use r2d2::ManageConnection;
+
+use failure::Error as FailureError;
+
+use stq_types::{Alpha3, CompanyId, CompanyPackageId};
+
+use super::types::{Service, ServiceFuture};
+use errors::Error;
+use models::{count_rate_entries, NewShippingRates, RatesQuotaUsage, SetCompanyRatesQuota, ShippingRates};
+use repos::{CompanyRatesQuotasRepo, DbConnection, ReposFactory, ShippingRatesRepo};
+
+pub trait CompanyRatesQuotasService {
+    /// Returns rate entries stored by the company against its quota
+    fn get_company_rates_quota(&self, company_id: CompanyId) -> ServiceFuture<RatesQuotaUsage>;
+    /// Sets quota of the company overriding the default one
+    fn set_company_rates_quota(&self, company_id: CompanyId, payload: SetCompanyRatesQuota) -> ServiceFuture<RatesQuotaUsage>;
+    /// Delete quota of the company, the default quota applies to it after that
+    fn delete_company_rates_quota(&self, company_id: CompanyId) -> ServiceFuture<RatesQuotaUsage>;
+}
+
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> CompanyRatesQuotasService for Service<T, M, F> {
+    /// Returns rate entries stored by the company against its quota
+    fn get_company_rates_quota(&self, company_id: CompanyId) -> ServiceFuture<RatesQuotaUsage> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+        let default_max_entries = self
+            .static_context
+            .config
+            .rates_quota
+            .as_ref()
+            .map(|quota| quota.default_max_entries);
+
+        self.spawn_on_pool(move |conn| {
+            let company_rates_quotas_repo = repo_factory.create_company_rates_quotas_repo(&*conn, user_id);
+            let shipping_rates_repo = repo_factory.create_shipping_rates_repo(&*conn, user_id);
+            rates_quota_usage(
+                &*company_rates_quotas_repo,
+                &*shipping_rates_repo,
+                default_max_entries,
+                company_id,
+                None,
+            )
+            .map_err(|e| {
+                e.context("Service CompanyRatesQuotas, get_company_rates_quota endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Sets quota of the company overriding the default one
+    fn set_company_rates_quota(&self, company_id: CompanyId, payload: SetCompanyRatesQuota) -> ServiceFuture<RatesQuotaUsage> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+        let default_max_entries = self
+            .static_context
+            .config
+            .rates_quota
+            .as_ref()
+            .map(|quota| quota.default_max_entries);
+
+        self.spawn_on_pool(move |conn| {
+            let company_rates_quotas_repo = repo_factory.create_company_rates_quotas_repo(&*conn, user_id);
+            let shipping_rates_repo = repo_factory.create_shipping_rates_repo(&*conn, user_id);
+            company_rates_quotas_repo
+                .set(company_id, payload)
+                .and_then(|_| {
+                    rates_quota_usage(
+                        &*company_rates_quotas_repo,
+                        &*shipping_rates_repo,
+                        default_max_entries,
+                        company_id,
+                        None,
+                    )
+                })
+                .map_err(|e| {
+                    e.context("Service CompanyRatesQuotas, set_company_rates_quota endpoint error occured.")
+                        .into()
+                })
+        })
+    }
+
+    /// Delete quota of the company, the default quota applies to it after that
+    fn delete_company_rates_quota(&self, company_id: CompanyId) -> ServiceFuture<RatesQuotaUsage> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+        let default_max_entries = self
+            .static_context
+            .config
+            .rates_quota
+            .as_ref()
+            .map(|quota| quota.default_max_entries);
+
+        self.spawn_on_pool(move |conn| {
+            let company_rates_quotas_repo = repo_factory.create_company_rates_quotas_repo(&*conn, user_id);
+            let shipping_rates_repo = repo_factory.create_shipping_rates_repo(&*conn, user_id);
+            company_rates_quotas_repo
+                .delete(company_id)
+                .and_then(|_| {
+                    rates_quota_usage(
+                        &*company_rates_quotas_repo,
+                        &*shipping_rates_repo,
+                        default_max_entries,
+                        company_id,
+                        None,
+                    )
+                })
+                .map_err(|e| {
+                    e.context("Service CompanyRatesQuotas, delete_company_rates_quota endpoint error occured.")
+                        .into()
+                })
+        })
+    }
+}
+
+/// Rate entries stored by the company against the quota set by admins, or the default one.
+/// Rates of the company package from the country in `except` are not counted, they are the ones being replaced
+pub fn rates_quota_usage(
+    company_rates_quotas_repo: &dyn CompanyRatesQuotasRepo,
+    shipping_rates_repo: &dyn ShippingRatesRepo,
+    default_max_entries: Option<i64>,
+    company_id: CompanyId,
+    except: Option<(CompanyPackageId, Alpha3)>,
+) -> Result<RatesQuotaUsage, FailureError> {
+    let quota = company_rates_quotas_repo.get(company_id)?;
+    let used_entries = shipping_rates_repo.count_rate_entries(company_id, except)?;
+
+    Ok(RatesQuotaUsage {
+        company_id,
+        used_entries,
+        max_entries: quota.as_ref().map(|quota| quota.max_entries).or(default_max_entries),
+        overridden: quota.is_some(),
+    })
+}
+
+/// Inserts the rates of the company if they fit into its quota, every path storing the rates goes through it.
+/// The company is locked until the end of the transaction, so that concurrent uploads can not exceed the quota together.
+/// Rates being replaced must be deleted in the same transaction before, so that they are not counted
+pub fn insert_rates_within_quota(
+    company_rates_quotas_repo: &dyn CompanyRatesQuotasRepo,
+    shipping_rates_repo: &dyn ShippingRatesRepo,
+    default_max_entries: Option<i64>,
+    company_id: CompanyId,
+    shipping_rates: Vec<NewShippingRates>,
+) -> Result<Vec<ShippingRates>, FailureError> {
+    company_rates_quotas_repo.lock(company_id)?;
+    rates_quota_usage(
+        company_rates_quotas_repo,
+        shipping_rates_repo,
+        default_max_entries,
+        company_id,
+        None,
+    )?
+    .check_upload(count_rate_entries(&shipping_rates))
+    .map_err(Error::Validate)?;
+
+    shipping_rates_repo.insert_many(shipping_rates)
+}
+
+#[cfg(test)]
+mod tests {
+    use stq_http::errors::ErrorMessageWrapper;
+    use stq_types::CompanyPackageId;
+
+    use super::*;
+    use models::{ShippingRate, ShippingRatesDestination};
+    use repos::repo_factory::tests::*;
+
+    fn new_rates(brackets: u32) -> Vec<NewShippingRates> {
+        vec![NewShippingRates {
+            company_package_id: CompanyPackageId(1),
+            from_alpha3: Alpha3("RUS".to_string()),
+            to: ShippingRatesDestination::Country(Alpha3("USA".to_string())),
+            rates: (1..=brackets)
+                .map(|kg| ShippingRate {
+                    weight_g: kg * 1000,
+                    price: 10.0,
+                })
+                .collect(),
+            delivery_time_min_days: None,
+            delivery_time_max_days: None,
+        }]
+    }
+
+    #[test]
+    fn rates_are_inserted_only_within_quota() {
+        let company_rates_quotas_repo = CompanyRatesQuotasRepoMock {
+            user_id: Some(MOCK_USER_ID),
+        };
+        let shipping_rates_repo = ShippingRatesRepoMock {
+            user_id: Some(MOCK_USER_ID),
+        };
+
+        let err = insert_rates_within_quota(
+            &company_rates_quotas_repo,
+            &shipping_rates_repo,
+            Some(2),
+            CompanyId(1),
+            new_rates(3),
+        )
+        .unwrap_err();
+        assert_eq!(ErrorMessageWrapper::<Error>::from(&err).inner.code, 400);
+
+        assert!(insert_rates_within_quota(
+            &company_rates_quotas_repo,
+            &shipping_rates_repo,
+            Some(3),
+            CompanyId(1),
+            new_rates(3)
+        )
+        .is_ok());
+    }
+}
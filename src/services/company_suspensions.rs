@@ -0,0 +1,92 @@
+//! CompanySuspensions Services, presents CRUD operations with company suspensions
+
+use r2d2::ManageConnection;
+
+use super::types::{Service, ServiceFuture};
+use models::{CompanySuspension, NewCompanySuspension, UpdateCompanySuspension};
+use repos::{DbConnection, ReposFactory};
+
+pub trait CompanySuspensionsService {
+    /// Returns list of company suspensions
+    fn list_company_suspensions(&self) -> ServiceFuture<Vec<CompanySuspension>>;
+    /// Returns company suspension by id
+    fn get_company_suspension(&self, suspension_id: i32) -> ServiceFuture<Option<CompanySuspension>>;
+    /// Create a new company suspension
+    fn create_company_suspension(&self, payload: NewCompanySuspension) -> ServiceFuture<CompanySuspension>;
+    /// Update a company suspension
+    fn update_company_suspension(&self, suspension_id: i32, payload: UpdateCompanySuspension) -> ServiceFuture<CompanySuspension>;
+    /// Delete a company suspension
+    fn delete_company_suspension(&self, suspension_id: i32) -> ServiceFuture<CompanySuspension>;
+}
+
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> CompanySuspensionsService for Service<T, M, F> {
+    /// Returns list of company suspensions
+    fn list_company_suspensions(&self) -> ServiceFuture<Vec<CompanySuspension>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let company_suspensions_repo = repo_factory.create_company_suspensions_repo(&*conn, user_id);
+            company_suspensions_repo.list().map_err(|e| {
+                e.context("Service CompanySuspensions, list_company_suspensions endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Returns company suspension by id
+    fn get_company_suspension(&self, suspension_id: i32) -> ServiceFuture<Option<CompanySuspension>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let company_suspensions_repo = repo_factory.create_company_suspensions_repo(&*conn, user_id);
+            company_suspensions_repo.find(suspension_id).map_err(|e| {
+                e.context("Service CompanySuspensions, get_company_suspension endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Create a new company suspension
+    fn create_company_suspension(&self, payload: NewCompanySuspension) -> ServiceFuture<CompanySuspension> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_transaction_on_pool(move |conn| {
+            let company_suspensions_repo = repo_factory.create_company_suspensions_repo(&*conn, user_id);
+            company_suspensions_repo.create(payload).map_err(|e| {
+                e.context("Service CompanySuspensions, create_company_suspension endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Update a company suspension
+    fn update_company_suspension(&self, suspension_id: i32, payload: UpdateCompanySuspension) -> ServiceFuture<CompanySuspension> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let company_suspensions_repo = repo_factory.create_company_suspensions_repo(&*conn, user_id);
+            company_suspensions_repo.update(suspension_id, payload).map_err(|e| {
+                e.context("Service CompanySuspensions, update_company_suspension endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Delete a company suspension
+    fn delete_company_suspension(&self, suspension_id: i32) -> ServiceFuture<CompanySuspension> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let company_suspensions_repo = repo_factory.create_company_suspensions_repo(&*conn, user_id);
+            company_suspensions_repo.delete(suspension_id).map_err(|e| {
+                e.context("Service CompanySuspensions, delete_company_suspension endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+}
@@ -1,16 +1,19 @@
 //! Countries Services, presents CRUD operations with countries
 
-use diesel::connection::AnsiTransactionManager;
-use diesel::pg::Pg;
-use diesel::Connection;
 use failure::Error as FailureError;
+use futures::Future;
 use r2d2::ManageConnection;
 
 use stq_types::Alpha3;
 
 use super::types::{Service, ServiceFuture};
-use models::{Country, NewCountry};
-use repos::{CountrySearch, ReposFactory};
+use content_versions::{VersionedContent, COUNTRIES_CONTENT};
+use errors::Error;
+use models::{
+    countries_tree_version, validate_countries_tree, CountriesImportReport, CountriesTreeRepair, CountriesTreeValidation, Country,
+    CountryDependent, CountryDependentRefresh, ImportCountries, NewCountry,
+};
+use repos::{CountrySearch, DbConnection, ReposFactory};
 
 pub trait CountriesService {
     /// Creates new country
@@ -23,14 +26,17 @@ pub trait CountriesService {
     fn get_all(&self) -> ServiceFuture<Country>;
     /// Returns all countries as a flat Vec
     fn get_all_flatten(&self) -> ServiceFuture<Vec<Country>>;
+    /// Imports ISO-3166 countries, existing countries are updated and keep their regions
+    fn import_countries(&self, payload: ImportCountries) -> ServiceFuture<CountriesImportReport>;
+    /// Reports orphans, cycles, missing parents and duplicate codes of the countries tree
+    fn validate_countries_tree(&self) -> ServiceFuture<CountriesTreeValidation>;
+    /// Re-parents orphans of the countries tree to the configured parent or to the root of the tree
+    fn repair_countries_tree(&self) -> ServiceFuture<CountriesTreeRepair>;
+    /// Returns refresh progress of data denormalized from the current version of the countries tree
+    fn get_country_dependents_refresh(&self) -> ServiceFuture<Vec<CountryDependentRefresh>>;
 }
 
-impl<
-        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
-        M: ManageConnection<Connection = T>,
-        F: ReposFactory<T>,
-    > CountriesService for Service<T, M, F>
-{
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> CountriesService for Service<T, M, F> {
     /// Returns country by code
     fn get_country(&self, code: Alpha3) -> ServiceFuture<Option<Country>> {
         let repo_factory = self.static_context.repo_factory.clone();
@@ -61,23 +67,35 @@ impl<
     fn create_country(&self, new_country: NewCountry) -> ServiceFuture<Country> {
         let repo_factory = self.static_context.repo_factory.clone();
         let user_id = self.dynamic_context.user_id;
+        let content_versions = self.static_context.content_versions.clone();
 
-        self.spawn_on_pool(move |conn| {
+        let created = self.spawn_transaction_on_pool(move |conn| {
             let countries_repo = repo_factory.create_countries_repo(&*conn, user_id);
-            conn.transaction::<(Country), FailureError, _>(move || countries_repo.create(new_country))
+            countries_repo
+                .create(new_country)
                 .map_err(|e| e.context("Service Countries, create endpoint error occured.").into())
-        })
+        });
+
+        Box::new(created.map(move |country| {
+            content_versions.invalidate(COUNTRIES_CONTENT);
+            country
+        }))
     }
 
     /// Returns all countries
     fn get_all(&self) -> ServiceFuture<Country> {
         let repo_factory = self.static_context.repo_factory.clone();
         let user_id = self.dynamic_context.user_id;
+        let content_versions = self.static_context.content_versions.clone();
 
         self.spawn_on_pool(move |conn| {
             let countries_repo = repo_factory.create_countries_repo(&*conn, user_id);
             countries_repo
                 .get_all()
+                .map(|root| {
+                    content_versions.update(VersionedContent::Countries, &root);
+                    root
+                })
                 .map_err(|e| e.context("Service Countries, get_all endpoint error occured.").into())
         })
     }
@@ -86,12 +104,149 @@ impl<
     fn get_all_flatten(&self) -> ServiceFuture<Vec<Country>> {
         let repo_factory = self.static_context.repo_factory.clone();
         let user_id = self.dynamic_context.user_id;
+        let content_versions = self.static_context.content_versions.clone();
 
         self.spawn_on_pool(move |conn| {
             let countries_repo = repo_factory.create_countries_repo(&*conn, user_id);
             countries_repo
                 .get_all_flatten()
+                .map(|countries| {
+                    content_versions.update(VersionedContent::CountriesFlatten, &countries);
+                    countries
+                })
                 .map_err(|e| e.context("Service Countries, get_all_flatten endpoint error occured.").into())
         })
     }
+
+    /// Imports ISO-3166 countries, existing countries are updated and keep their regions
+    fn import_countries(&self, payload: ImportCountries) -> ServiceFuture<CountriesImportReport> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+        let content_versions = self.static_context.content_versions.clone();
+
+        let imported = self.spawn_transaction_on_pool(move |conn| {
+            let countries_repo = repo_factory.create_countries_repo(&*conn, user_id);
+
+            let run = || {
+                let iso_countries = payload.into_countries()?;
+
+                let regions = countries_repo
+                    .get_all_flatten()?
+                    .into_iter()
+                    .filter(|country| country.level < Country::COUNTRY_LEVEL)
+                    .map(|country| country.alpha3)
+                    .collect::<Vec<_>>();
+                for iso_country in &iso_countries {
+                    if let Some(ref region) = iso_country.region {
+                        if !regions.contains(region) {
+                            let errors = validation_errors!({
+                                "region": ["region" => format!("Region {} of country {} is not in the countries tree", region, iso_country.alpha3)]
+                            });
+                            return Err(Error::Validate(errors).into());
+                        }
+                    }
+                }
+
+                countries_repo.import(iso_countries)
+            };
+
+            run().map_err(|e: FailureError| e.context("Service Countries, import_countries endpoint error occured.").into())
+        });
+
+        Box::new(imported.map(move |report| {
+            content_versions.invalidate(COUNTRIES_CONTENT);
+            report
+        }))
+    }
+
+    /// Reports orphans, cycles, missing parents and duplicate codes of the countries tree
+    fn validate_countries_tree(&self) -> ServiceFuture<CountriesTreeValidation> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let countries_repo = repo_factory.create_countries_repo(&*conn, user_id);
+            countries_repo
+                .get_all_flatten()
+                .map(|countries| validate_countries_tree(&countries))
+                .map_err(|e| {
+                    e.context("Service Countries, validate_countries_tree endpoint error occured.")
+                        .into()
+                })
+        })
+    }
+
+    /// Re-parents orphans of the countries tree to the configured parent or to the root of the tree
+    fn repair_countries_tree(&self) -> ServiceFuture<CountriesTreeRepair> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+        let orphans_parent = self
+            .static_context
+            .config
+            .countries
+            .as_ref()
+            .map(|countries| countries.orphans_parent.clone());
+        let content_versions = self.static_context.content_versions.clone();
+
+        self.spawn_on_pool(move |conn| {
+            let countries_repo = repo_factory.create_countries_repo(&*conn, user_id);
+
+            let run = || {
+                let countries = countries_repo.get_all_flatten()?;
+                let validation = validate_countries_tree(&countries);
+
+                let parent = orphans_parent
+                    .or_else(|| validation.root.clone())
+                    .ok_or_else(|| format_err!("Countries tree has no root"))?;
+                let parent_in_tree = countries.iter().any(|country| country.alpha3 == parent) && !validation.orphans.contains(&parent);
+                if !parent_in_tree {
+                    let errors = validation_errors!({
+                        "orphans_parent": ["orphans_parent" => format!("Parent {} of orphans is not in the countries tree", parent)]
+                    });
+                    return Err(Error::Validate(errors).into());
+                }
+
+                let reparented = validation.detached;
+                countries_repo.set_parent(reparented.clone(), parent.clone())?;
+                content_versions.invalidate(COUNTRIES_CONTENT);
+
+                Ok(CountriesTreeRepair { parent, reparented })
+            };
+
+            run().map_err(|e: FailureError| e.context("Service Countries, repair_countries_tree endpoint error occured.").into())
+        })
+    }
+
+    /// Returns refresh progress of data denormalized from the current version of the countries tree
+    fn get_country_dependents_refresh(&self) -> ServiceFuture<Vec<CountryDependentRefresh>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_admin_pool(move |conn| {
+            let countries_repo = repo_factory.create_countries_repo(&*conn, user_id);
+            let backfills_repo = repo_factory.create_backfills_repo(&*conn, user_id);
+
+            let run = || {
+                let tree_version = countries_tree_version(&countries_repo.get_all_flatten()?);
+
+                CountryDependent::all()
+                    .into_iter()
+                    .map(|dependent| {
+                        backfills_repo
+                            .find(dependent.refresh_name(&tree_version))
+                            .map(|progress| CountryDependentRefresh {
+                                dependent,
+                                tree_version: tree_version.clone(),
+                                progress,
+                            })
+                    })
+                    .collect::<Result<Vec<_>, FailureError>>()
+            };
+
+            run().map_err(|e: FailureError| {
+                e.context("Service Countries, get_country_dependents_refresh endpoint error occured.")
+                    .into()
+            })
+        })
+    }
 }
@@ -0,0 +1,78 @@
+//! Country regulations Services, presents CRUD operations with parcel regulations of the destination countries
+
+use r2d2::ManageConnection;
+
+use stq_types::Alpha3;
+
+use super::types::{Service, ServiceFuture};
+use models::{CountryRegulation, SetCountryRegulation};
+use repos::{DbConnection, ReposFactory};
+
+pub trait CountryRegulationsService {
+    /// Returns regulations of all countries
+    fn list_country_regulations(&self) -> ServiceFuture<Vec<CountryRegulation>>;
+    /// Returns regulations of the country
+    fn get_country_regulation(&self, alpha3: Alpha3) -> ServiceFuture<Option<CountryRegulation>>;
+    /// Sets regulations of the country
+    fn set_country_regulation(&self, alpha3: Alpha3, payload: SetCountryRegulation) -> ServiceFuture<CountryRegulation>;
+    /// Delete regulations of the country, only carrier limits are applied to it after that
+    fn delete_country_regulation(&self, alpha3: Alpha3) -> ServiceFuture<Option<CountryRegulation>>;
+}
+
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> CountryRegulationsService for Service<T, M, F> {
+    /// Returns regulations of all countries
+    fn list_country_regulations(&self) -> ServiceFuture<Vec<CountryRegulation>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let country_regulations_repo = repo_factory.create_country_regulations_repo(&*conn, user_id);
+            country_regulations_repo.list().map_err(|e| {
+                e.context("Service CountryRegulations, list_country_regulations endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Returns regulations of the country
+    fn get_country_regulation(&self, alpha3: Alpha3) -> ServiceFuture<Option<CountryRegulation>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let country_regulations_repo = repo_factory.create_country_regulations_repo(&*conn, user_id);
+            country_regulations_repo.get(alpha3).map_err(|e| {
+                e.context("Service CountryRegulations, get_country_regulation endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Sets regulations of the country
+    fn set_country_regulation(&self, alpha3: Alpha3, payload: SetCountryRegulation) -> ServiceFuture<CountryRegulation> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let country_regulations_repo = repo_factory.create_country_regulations_repo(&*conn, user_id);
+            country_regulations_repo.set(alpha3, payload).map_err(|e| {
+                e.context("Service CountryRegulations, set_country_regulation endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Delete regulations of the country, only carrier limits are applied to it after that
+    fn delete_country_regulation(&self, alpha3: Alpha3) -> ServiceFuture<Option<CountryRegulation>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let country_regulations_repo = repo_factory.create_country_regulations_repo(&*conn, user_id);
+            country_regulations_repo.delete(alpha3).map_err(|e| {
+                e.context("Service CountryRegulations, delete_country_regulation endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+}
@@ -0,0 +1,152 @@
+//! Country subdivisions Services, presents CRUD operations with the states and the regions of the countries
+
+use failure::Error as FailureError;
+use failure::Fail;
+use r2d2::ManageConnection;
+
+use stq_types::Alpha3;
+
+use super::types::{Service, ServiceFuture};
+use errors::Error;
+use models::{
+    normalize_subdivision_code, subdivision_country_code, Country, CountrySubdivision, NewCountrySubdivision, UpdateCountrySubdivision,
+};
+use repos::{CountrySubdivisionsRepo, DbConnection, ReposFactory};
+
+pub trait CountrySubdivisionsService {
+    /// Returns subdivisions of the country
+    fn list_country_subdivisions(&self, alpha3: Alpha3) -> ServiceFuture<Vec<CountrySubdivision>>;
+    /// Returns subdivision of the country by ISO 3166-2 code
+    fn get_country_subdivision(&self, alpha3: Alpha3, code: String) -> ServiceFuture<Option<CountrySubdivision>>;
+    /// Creates subdivision of the country, the code must start with alpha2 code of the country
+    fn create_country_subdivision(&self, alpha3: Alpha3, payload: NewCountrySubdivision) -> ServiceFuture<CountrySubdivision>;
+    /// Renames subdivision of the country
+    fn update_country_subdivision(
+        &self,
+        alpha3: Alpha3,
+        code: String,
+        payload: UpdateCountrySubdivision,
+    ) -> ServiceFuture<CountrySubdivision>;
+    /// Deletes subdivision of the country together with its rates
+    fn delete_country_subdivision(&self, alpha3: Alpha3, code: String) -> ServiceFuture<CountrySubdivision>;
+}
+
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> CountrySubdivisionsService for Service<T, M, F> {
+    /// Returns subdivisions of the country
+    fn list_country_subdivisions(&self, alpha3: Alpha3) -> ServiceFuture<Vec<CountrySubdivision>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let country_subdivisions_repo = repo_factory.create_country_subdivisions_repo(&*conn, user_id);
+            country_subdivisions_repo.list(alpha3).map_err(|e| {
+                e.context("Service CountrySubdivisions, list_country_subdivisions endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Returns subdivision of the country by ISO 3166-2 code
+    fn get_country_subdivision(&self, alpha3: Alpha3, code: String) -> ServiceFuture<Option<CountrySubdivision>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let country_subdivisions_repo = repo_factory.create_country_subdivisions_repo(&*conn, user_id);
+            country_subdivisions_repo
+                .get(normalize_subdivision_code(&code))
+                .map(|subdivision| subdivision.filter(|subdivision| subdivision.alpha3 == alpha3))
+                .map_err(|e| {
+                    e.context("Service CountrySubdivisions, get_country_subdivision endpoint error occured.")
+                        .into()
+                })
+        })
+    }
+
+    /// Creates subdivision of the country, the code must start with alpha2 code of the country
+    fn create_country_subdivision(&self, alpha3: Alpha3, payload: NewCountrySubdivision) -> ServiceFuture<CountrySubdivision> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_transaction_on_pool(move |conn| {
+            let countries_repo = repo_factory.create_countries_repo(&*conn, user_id);
+            let country_subdivisions_repo = repo_factory.create_country_subdivisions_repo(&*conn, user_id);
+
+            let run = move || {
+                let country = countries_repo
+                    .find(alpha3.clone())?
+                    .filter(|country| country.level == Country::COUNTRY_LEVEL)
+                    .ok_or_else(|| format_err!("Country {} not found", alpha3).context(Error::NotFound))?;
+
+                if subdivision_country_code(&payload.code) != Some(country.alpha2.clone()) {
+                    return Err(Error::Validate(validation_errors!({
+                        "code": ["code" => format!("Subdivision code must start with {}- for country {}", country.alpha2, alpha3)]
+                    }))
+                    .into());
+                }
+
+                country_subdivisions_repo.create(payload.into_subdivision(alpha3))
+            };
+
+            run().map_err(|e: FailureError| {
+                e.context("Service CountrySubdivisions, create_country_subdivision endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Renames subdivision of the country
+    fn update_country_subdivision(
+        &self,
+        alpha3: Alpha3,
+        code: String,
+        payload: UpdateCountrySubdivision,
+    ) -> ServiceFuture<CountrySubdivision> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_transaction_on_pool(move |conn| {
+            let country_subdivisions_repo = repo_factory.create_country_subdivisions_repo(&*conn, user_id);
+
+            find_country_subdivision(&*country_subdivisions_repo, &alpha3, &code)
+                .and_then(|subdivision| country_subdivisions_repo.update(subdivision.code, payload))
+                .map_err(|e| {
+                    e.context("Service CountrySubdivisions, update_country_subdivision endpoint error occured.")
+                        .into()
+                })
+        })
+    }
+
+    /// Deletes subdivision of the country together with its rates
+    fn delete_country_subdivision(&self, alpha3: Alpha3, code: String) -> ServiceFuture<CountrySubdivision> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_transaction_on_pool(move |conn| {
+            let country_subdivisions_repo = repo_factory.create_country_subdivisions_repo(&*conn, user_id);
+
+            find_country_subdivision(&*country_subdivisions_repo, &alpha3, &code)
+                .and_then(|subdivision| country_subdivisions_repo.delete(subdivision.code))
+                .map_err(|e| {
+                    e.context("Service CountrySubdivisions, delete_country_subdivision endpoint error occured.")
+                        .into()
+                })
+        })
+    }
+}
+
+/// Subdivision with the code, subdivisions of the other countries are not found
+fn find_country_subdivision(
+    country_subdivisions_repo: &dyn CountrySubdivisionsRepo,
+    alpha3: &Alpha3,
+    code: &str,
+) -> Result<CountrySubdivision, FailureError> {
+    country_subdivisions_repo
+        .get(normalize_subdivision_code(code))?
+        .filter(|subdivision| subdivision.alpha3 == *alpha3)
+        .ok_or_else(|| {
+            format_err!("Subdivision {} of country {} not found", code, alpha3)
+                .context(Error::NotFound)
+                .into()
+        })
+}
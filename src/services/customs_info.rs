@@ -0,0 +1,59 @@
+//! Customs info Services, presents operations with customs data of the base products for cross-border shipments
+
+use r2d2::ManageConnection;
+
+use stq_types::BaseProductId;
+
+use super::types::{Service, ServiceFuture};
+use models::{CustomsInfo, SetCustomsInfo};
+use repos::{DbConnection, ReposFactory};
+
+pub trait CustomsInfoService {
+    /// Returns customs info of the base product
+    fn get_customs_info(&self, base_product_id: BaseProductId) -> ServiceFuture<Option<CustomsInfo>>;
+    /// Sets customs info of the base product
+    fn set_customs_info(&self, base_product_id: BaseProductId, payload: SetCustomsInfo) -> ServiceFuture<CustomsInfo>;
+    /// Delete customs info of the base product
+    fn delete_customs_info(&self, base_product_id: BaseProductId) -> ServiceFuture<Option<CustomsInfo>>;
+}
+
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> CustomsInfoService for Service<T, M, F> {
+    /// Returns customs info of the base product
+    fn get_customs_info(&self, base_product_id: BaseProductId) -> ServiceFuture<Option<CustomsInfo>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let customs_info_repo = repo_factory.create_customs_info_repo(&*conn, user_id);
+            customs_info_repo
+                .get(base_product_id)
+                .map_err(|e| e.context("Service CustomsInfo, get_customs_info endpoint error occured.").into())
+        })
+    }
+
+    /// Sets customs info of the base product
+    fn set_customs_info(&self, base_product_id: BaseProductId, payload: SetCustomsInfo) -> ServiceFuture<CustomsInfo> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let customs_info_repo = repo_factory.create_customs_info_repo(&*conn, user_id);
+            customs_info_repo
+                .set(base_product_id, payload)
+                .map_err(|e| e.context("Service CustomsInfo, set_customs_info endpoint error occured.").into())
+        })
+    }
+
+    /// Delete customs info of the base product
+    fn delete_customs_info(&self, base_product_id: BaseProductId) -> ServiceFuture<Option<CustomsInfo>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let customs_info_repo = repo_factory.create_customs_info_repo(&*conn, user_id);
+            customs_info_repo
+                .delete(base_product_id)
+                .map_err(|e| e.context("Service CustomsInfo, delete_customs_info endpoint error occured.").into())
+        })
+    }
+}
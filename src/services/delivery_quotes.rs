@@ -0,0 +1,130 @@
+//! DeliveryQuotes Services, persists the delivery price calculated at checkout as a quote,
+//! the orders service redeems the quote later to charge the quoted price
+
+use std::time::{Duration, SystemTime};
+
+use failure::Error as FailureError;
+use futures::Future;
+use r2d2::ManageConnection;
+use serde_json;
+use uuid::Uuid;
+
+use super::types::{Service, ServiceFuture};
+use errors::Error;
+use models::{DeliveryQuote, NewDeliveryQuote};
+use repos::{DbConnection, ReposFactory};
+use services::companies_packages::{CompaniesPackagesService, GetDeliveryPrice};
+
+/// Time the quote is valid for if it is not configured
+pub const DEFAULT_DELIVERY_QUOTE_TTL_SEC: u64 = 900;
+
+pub trait DeliveryQuotesService {
+    /// Calculates the delivery price and persists it as a quote
+    fn create_delivery_quote(&self, payload: GetDeliveryPrice) -> ServiceFuture<DeliveryQuote>;
+    /// Returns the quote by id, the quote is gone once it has expired
+    fn get_delivery_quote(&self, quote_id: Uuid) -> ServiceFuture<Option<DeliveryQuote>>;
+}
+
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> DeliveryQuotesService for Service<T, M, F> {
+    /// Calculates the delivery price and persists it as a quote
+    fn create_delivery_quote(&self, payload: GetDeliveryPrice) -> ServiceFuture<DeliveryQuote> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+        let clock = self.static_context.clock.clone();
+        let ttl_sec = self
+            .static_context
+            .config
+            .delivery_quotes
+            .as_ref()
+            .map(|delivery_quotes| delivery_quotes.ttl_sec)
+            .unwrap_or(DEFAULT_DELIVERY_QUOTE_TTL_SEC);
+        let service = self.clone();
+
+        Box::new(self.get_delivery_price(payload.clone()).and_then(move |price| {
+            service.spawn_on_pool(move |conn| {
+                let delivery_quotes_repo = repo_factory.create_delivery_quotes_repo(&*conn, user_id);
+
+                let run = move || {
+                    let price = price.ok_or_else(|| {
+                        format_err!(
+                            "Delivery of company package {} from {} to {} has no price",
+                            payload.company_package_id,
+                            payload.delivery_from,
+                            payload.delivery_to
+                        )
+                        .context(Error::NotFound)
+                    })?;
+
+                    let new_quote = NewDeliveryQuote {
+                        id: Uuid::new_v4(),
+                        company_package_id: payload.company_package_id,
+                        currency: price.currency,
+                        price: price.value,
+                        inputs: serde_json::to_value(&payload)?,
+                        expires_at: SystemTime::from(clock.now()) + Duration::from_secs(ttl_sec),
+                    };
+
+                    delivery_quotes_repo.create(new_quote)
+                };
+
+                run().map_err(|e: FailureError| {
+                    e.context("Service DeliveryQuotes, create_delivery_quote endpoint error occurred.")
+                        .into()
+                })
+            })
+        }))
+    }
+
+    /// Returns the quote by id, the quote is gone once it has expired
+    fn get_delivery_quote(&self, quote_id: Uuid) -> ServiceFuture<Option<DeliveryQuote>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+        let clock = self.static_context.clock.clone();
+
+        self.spawn_on_pool(move |conn| {
+            let delivery_quotes_repo = repo_factory.create_delivery_quotes_repo(&*conn, user_id);
+
+            let run = move || match delivery_quotes_repo.find(quote_id)? {
+                Some(ref quote) if quote.is_expired(SystemTime::from(clock.now())) => Err(Error::Gone(validation_errors!({
+                    "quote_id": ["expired" => format!("Delivery quote {} has expired, request a new one", quote_id)]
+                }))
+                .into()),
+                quote => Ok(quote),
+            };
+
+            run().map_err(|e: FailureError| {
+                e.context("Service DeliveryQuotes, get_delivery_quote endpoint error occurred.")
+                    .into()
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use tokio_core::reactor::Core;
+    use uuid::Uuid;
+
+    use stq_http::errors::ErrorMessageWrapper;
+
+    use errors::Error;
+    use repos::repo_factory::tests::*;
+    use services::delivery_quotes::DeliveryQuotesService;
+
+    #[test]
+    fn expired_quote_is_gone() {
+        let mut core = Core::new().unwrap();
+        let handle = Arc::new(core.handle());
+        let service = create_service(Some(MOCK_USER_ID), handle);
+
+        let quote_id = Uuid::new_v4();
+        let quote = core.run(service.get_delivery_quote(quote_id)).unwrap().unwrap();
+        assert_eq!(quote.id, quote_id);
+
+        let err = core
+            .run(service.get_delivery_quote(Uuid::nil()))
+            .expect_err("Expired delivery quote must not be redeemed");
+        assert_eq!(ErrorMessageWrapper::<Error>::from(&err).inner.code, 410);
+    }
+}
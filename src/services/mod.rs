@@ -1,10 +1,41 @@
+pub mod address_validation;
+pub mod admin_overview;
+pub mod api_keys;
+pub mod backfills;
+pub mod carrier_accounts;
 pub mod companies;
 pub mod companies_packages;
+pub mod company_rates_quotas;
+pub mod company_suspensions;
 pub mod countries;
+pub mod country_regulations;
+pub mod country_subdivisions;
+pub mod customs_info;
+pub mod delivery_quotes;
+pub mod notifications;
+pub mod package_templates;
 pub mod packages;
+pub mod pickup_points;
+pub mod postal_zones;
+pub mod pricing_rules;
 pub mod products;
+pub mod shipping_rates_anomalies;
+pub mod shipping_exports;
+pub mod shipping_resolver;
+pub mod shipping_restrictions;
+pub mod shipping_templates;
+pub mod store_managers;
+pub mod store_margins;
+pub mod store_shipping_summaries;
+pub mod surcharges;
+#[cfg(feature = "test-endpoints")]
+pub mod test_fixtures;
 pub mod types;
 pub mod user_addresses;
 pub mod user_roles;
+pub mod zones;
 
 pub use self::types::Service;
+
+#[cfg(test)]
+mod acl_denied_tests;
@@ -0,0 +1,124 @@
+//! Notifications Services, notifies the buyers about the events of their shipments
+
+use r2d2::ManageConnection;
+
+use failure::Error as FailureError;
+use failure::Fail;
+use futures::future;
+use futures::prelude::*;
+
+use super::types::{Service, ServiceFuture};
+use errors::Error;
+use models::{NewShipmentNotification, Notification, ShipmentEvent};
+use notifications::templates;
+use repos::{DbConnection, ReposFactory};
+
+pub trait NotificationsService {
+    /// Renders notifications of the shipment event and sends them to the buyer.
+    /// Events that are already notified are skipped, so that nothing is returned for them.
+    /// The event is claimed before the notifications are sent and released if sending fails, so that
+    /// concurrent deliveries of the event notify the buyer once and a failed one is notified on redelivery.
+    /// Events received while the notifications are not configured are claimed as well and never notified
+    fn handle_shipment_event(&self, event: ShipmentEvent) -> ServiceFuture<Vec<Notification>>;
+}
+
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> NotificationsService for Service<T, M, F> {
+    /// Renders notifications of the shipment event and sends them to the buyer
+    fn handle_shipment_event(&self, event: ShipmentEvent) -> ServiceFuture<Vec<Notification>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+        let notifications_client = self.static_context.notifications_client.clone();
+        let request_context = self.dynamic_context.request_context.clone();
+        let service = self.clone();
+
+        let event_clone = event.clone();
+        // the claim is rolled back if the shipping is not found, so that the event is not lost
+        let package = self.spawn_transaction_on_pool(move |conn| {
+            let shipment_notifications_repo = repo_factory.create_shipment_notifications_repo(&*conn, user_id);
+            let products_repo = repo_factory.create_products_repo(&*conn, user_id);
+
+            let claim = NewShipmentNotification {
+                event_id: event_clone.event_id.clone(),
+                kind: event_clone.kind,
+                user_id: event_clone.user_id,
+                shipping_id: event_clone.shipping_id,
+                lang: templates::notification_lang(&event_clone),
+            };
+            if shipment_notifications_repo.claim(claim)?.is_none() {
+                return Ok(None);
+            }
+
+            products_repo
+                .get_available_package_for_user_by_shipping_id(event_clone.shipping_id, None)?
+                .ok_or_else(|| {
+                    Error::Validate(validation_errors!({
+                        "shipping_id": ["shipping_id" => format!("Shipping with id {} not found", event_clone.shipping_id)]
+                    }))
+                    .into()
+                })
+                .map(Some)
+        });
+
+        Box::new(
+            package
+                .and_then(move |package| -> ServiceFuture<Vec<Notification>> {
+                    let package = match package {
+                        Some(package) => package,
+                        None => {
+                            info!(
+                                "{}",
+                                request_context.log_line(&format!("Shipment event {} is already notified", event.event_id))
+                            );
+                            return Box::new(future::ok(vec![]));
+                        }
+                    };
+
+                    let notifications = templates::render(&event, &package);
+                    let notifications_client = match notifications_client {
+                        Some(notifications_client) => notifications_client,
+                        None => {
+                            warn!(
+                                "{}",
+                                request_context.log_line(&format!(
+                                    "Notifications are not configured, shipment event {} is not notified",
+                                    event.event_id
+                                ))
+                            );
+                            return Box::new(future::ok(notifications));
+                        }
+                    };
+
+                    let sends = notifications
+                        .iter()
+                        .cloned()
+                        .map(|notification| notifications_client.send(notification, &request_context))
+                        .collect::<Vec<_>>();
+                    let event_id = event.event_id.clone();
+
+                    Box::new(future::join_all(sends).then(move |res| -> ServiceFuture<Vec<Notification>> {
+                        match res {
+                            Ok(_) => Box::new(future::ok(notifications)),
+                            Err(e) => {
+                                let repo_factory = service.static_context.repo_factory.clone();
+                                let user_id = service.dynamic_context.user_id;
+
+                                Box::new(
+                                    service
+                                        .spawn_on_pool(move |conn| {
+                                            let shipment_notifications_repo =
+                                                repo_factory.create_shipment_notifications_repo(&*conn, user_id);
+                                            shipment_notifications_repo.release(event_id)
+                                        })
+                                        .then(move |_| Err(e)),
+                                )
+                            }
+                        }
+                    }))
+                })
+                .map_err(|e: FailureError| {
+                    e.context("Service Notifications, handle_shipment_event endpoint error occured.")
+                        .into()
+                }),
+        )
+    }
+}
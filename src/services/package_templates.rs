@@ -0,0 +1,153 @@
+//! PackageTemplates Services, presents CRUD operations with package templates
+//! and suggests the default company packages to stores
+
+use r2d2::ManageConnection;
+
+use failure::Error as FailureError;
+
+use stq_types::{Alpha3, StoreId};
+
+use super::types::{Service, ServiceFuture};
+use models::{NewPackageTemplate, PackageTemplate, SuggestedPackage, UpdatePackageTemplate};
+use repos::{DbConnection, ReposFactory};
+
+pub trait PackageTemplatesService {
+    /// Returns list of package templates, optionally only of the origin country
+    fn list_package_templates(&self, from_alpha3: Option<Alpha3>) -> ServiceFuture<Vec<PackageTemplate>>;
+    /// Returns package template by id
+    fn get_package_template(&self, package_template_id: i32) -> ServiceFuture<Option<PackageTemplate>>;
+    /// Create a new package template
+    fn create_package_template(&self, payload: NewPackageTemplate) -> ServiceFuture<PackageTemplate>;
+    /// Update a package template
+    fn update_package_template(&self, package_template_id: i32, payload: UpdatePackageTemplate) -> ServiceFuture<PackageTemplate>;
+    /// Delete a package template
+    fn delete_package_template(&self, package_template_id: i32) -> ServiceFuture<PackageTemplate>;
+    /// Returns package templates of the store country, except the company packages the store already ships with
+    fn get_suggested_packages(&self, store_id: StoreId, country: Alpha3) -> ServiceFuture<Vec<SuggestedPackage>>;
+}
+
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> PackageTemplatesService for Service<T, M, F> {
+    /// Returns list of package templates, optionally only of the origin country
+    fn list_package_templates(&self, from_alpha3: Option<Alpha3>) -> ServiceFuture<Vec<PackageTemplate>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let package_templates_repo = repo_factory.create_package_templates_repo(&*conn, user_id);
+            package_templates_repo.list(from_alpha3).map_err(|e| {
+                e.context("Service PackageTemplates, list_package_templates endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Returns package template by id
+    fn get_package_template(&self, package_template_id: i32) -> ServiceFuture<Option<PackageTemplate>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let package_templates_repo = repo_factory.create_package_templates_repo(&*conn, user_id);
+            package_templates_repo.find(package_template_id).map_err(|e| {
+                e.context("Service PackageTemplates, get_package_template endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Create a new package template
+    fn create_package_template(&self, payload: NewPackageTemplate) -> ServiceFuture<PackageTemplate> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_transaction_on_pool(move |conn| {
+            let package_templates_repo = repo_factory.create_package_templates_repo(&*conn, user_id);
+            package_templates_repo.create(payload).map_err(|e| {
+                e.context("Service PackageTemplates, create_package_template endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Update a package template
+    fn update_package_template(&self, package_template_id: i32, payload: UpdatePackageTemplate) -> ServiceFuture<PackageTemplate> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let package_templates_repo = repo_factory.create_package_templates_repo(&*conn, user_id);
+            package_templates_repo.update(package_template_id, payload).map_err(|e| {
+                e.context("Service PackageTemplates, update_package_template endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Delete a package template
+    fn delete_package_template(&self, package_template_id: i32) -> ServiceFuture<PackageTemplate> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let package_templates_repo = repo_factory.create_package_templates_repo(&*conn, user_id);
+            package_templates_repo.delete(package_template_id).map_err(|e| {
+                e.context("Service PackageTemplates, delete_package_template endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Returns package templates of the store country, except the company packages the store already ships with
+    fn get_suggested_packages(&self, store_id: StoreId, country: Alpha3) -> ServiceFuture<Vec<SuggestedPackage>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_read_pool(move |conn| {
+            let package_templates_repo = repo_factory.create_package_templates_repo(&*conn, user_id);
+            let products_repo = repo_factory.create_products_repo(&*conn, user_id);
+            let companies_packages_repo = repo_factory.create_companies_packages_repo(&*conn, user_id);
+
+            let run = move || {
+                let used_company_package_ids = products_repo.get_company_package_ids_by_store_id(store_id)?;
+                let company_package_ids = package_templates_repo
+                    .list(Some(country))?
+                    .into_iter()
+                    .map(|template| template.company_package_id)
+                    .filter(|company_package_id| !used_company_package_ids.contains(company_package_id))
+                    .collect::<Vec<_>>();
+
+                // company packages with their companies and packages are loaded at once, in the order of the templates
+                let mut company_packages = companies_packages_repo.get_many_with_companies_and_packages(company_package_ids.clone())?;
+                let mut suggested_packages = vec![];
+                for company_package_id in company_package_ids {
+                    let (company_package, company, package) = match company_packages
+                        .iter()
+                        .position(|(company_package, _, _)| company_package.id == company_package_id)
+                    {
+                        Some(position) => company_packages.remove(position),
+                        None => continue,
+                    };
+
+                    suggested_packages.push(SuggestedPackage {
+                        company_package_id: company_package.id,
+                        company_id: company.id,
+                        company_name: company.name,
+                        company_label: company.label,
+                        logo: company.logo,
+                        package_id: package.id,
+                        package_name: package.name,
+                        shipping_rate_source: company_package.shipping_rate_source,
+                        currency: company.currency,
+                    });
+                }
+
+                Ok(suggested_packages)
+            };
+
+            run().map_err(|e: FailureError| {
+                e.context("Service PackageTemplates, get_suggested_packages endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+}
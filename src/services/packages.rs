@@ -1,18 +1,15 @@
 //! Packages Services, presents CRUD operations with countries
 
-use diesel::connection::AnsiTransactionManager;
-use diesel::pg::Pg;
-use diesel::Connection;
+use futures::Future;
 use r2d2::ManageConnection;
 
-use failure::Error as FailureError;
-
 use stq_types::{Alpha3, PackageId};
 
 use super::types::{Service, ServiceFuture};
-use models::packages::{NewPackages, Packages, UpdatePackages};
+use content_versions::VersionedContent;
+use models::packages::{NewPackages, Packages, PackagesSearchResult, UpdatePackages};
 use repos::countries::get_all_parent_codes;
-use repos::ReposFactory;
+use repos::{DbConnection, ReposFactory};
 
 pub trait PackagesService {
     /// Create a new packages
@@ -21,6 +18,10 @@ pub trait PackagesService {
     /// Returns list of packages supported by the country
     fn find_packages_by_country(&self, country: Alpha3) -> ServiceFuture<Vec<Packages>>;
 
+    /// Returns the page of packages delivering to the country, `max_weight` is the weight in grams the packages must carry
+    fn search_packages(&self, delivers_to: Alpha3, max_weight: Option<u32>, offset: i64, limit: i64)
+        -> ServiceFuture<PackagesSearchResult>;
+
     /// Returns list of packages
     fn list_packages(&self) -> ServiceFuture<Vec<Packages>>;
 
@@ -33,24 +34,23 @@ pub trait PackagesService {
     fn delete_package(&self, id: PackageId) -> ServiceFuture<Packages>;
 }
 
-impl<
-        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
-        M: ManageConnection<Connection = T>,
-        F: ReposFactory<T>,
-    > PackagesService for Service<T, M, F>
-{
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> PackagesService for Service<T, M, F> {
     fn create_package(&self, payload: NewPackages) -> ServiceFuture<Packages> {
         let repo_factory = self.static_context.repo_factory.clone();
         let user_id = self.dynamic_context.user_id;
+        let content_versions = self.static_context.content_versions.clone();
 
-        self.spawn_on_pool(move |conn| {
+        let created = self.spawn_transaction_on_pool(move |conn| {
             let packages_repo = repo_factory.create_packages_repo(&*conn, user_id);
-            conn.transaction::<Packages, FailureError, _>(move || {
-                packages_repo
-                    .create(payload)
-                    .map_err(|e| e.context("Service Packages, create endpoint error occured.").into())
-            })
-        })
+            packages_repo
+                .create(payload)
+                .map_err(|e| e.context("Service Packages, create endpoint error occured.").into())
+        });
+
+        Box::new(created.map(move |package| {
+            content_versions.invalidate(&[VersionedContent::Packages]);
+            package
+        }))
     }
 
     fn find_packages_by_country(&self, country: Alpha3) -> ServiceFuture<Vec<Packages>> {
@@ -71,15 +71,45 @@ impl<
         })
     }
 
+    /// Returns the page of packages delivering to the country, `max_weight` is the weight in grams the packages must carry
+    fn search_packages(
+        &self,
+        delivers_to: Alpha3,
+        max_weight: Option<u32>,
+        offset: i64,
+        limit: i64,
+    ) -> ServiceFuture<PackagesSearchResult> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let packages_repo = repo_factory.create_packages_repo(&*conn, user_id);
+            let countries_repo = repo_factory.create_countries_repo(&*conn, user_id);
+            countries_repo
+                .get_all()
+                .and_then(|countries| {
+                    let mut countries_list = vec![];
+                    get_all_parent_codes(&countries, &delivers_to, &mut countries_list);
+                    packages_repo.search(countries_list, max_weight, offset, limit)
+                })
+                .map_err(|e| e.context("Service Packages, search endpoint error occured.").into())
+        })
+    }
+
     /// Returns list of packages
     fn list_packages(&self) -> ServiceFuture<Vec<Packages>> {
         let repo_factory = self.static_context.repo_factory.clone();
         let user_id = self.dynamic_context.user_id;
+        let content_versions = self.static_context.content_versions.clone();
 
         self.spawn_on_pool(move |conn| {
             let packages_repo = repo_factory.create_packages_repo(&*conn, user_id);
             packages_repo
                 .list()
+                .map(|packages| {
+                    content_versions.update(VersionedContent::Packages, &packages);
+                    packages
+                })
                 .map_err(|e| e.context("Service Packages, list endpoint error occured.").into())
         })
     }
@@ -99,11 +129,16 @@ impl<
     fn update_package(&self, id: PackageId, payload: UpdatePackages) -> ServiceFuture<Packages> {
         let repo_factory = self.static_context.repo_factory.clone();
         let user_id = self.dynamic_context.user_id;
+        let content_versions = self.static_context.content_versions.clone();
 
         self.spawn_on_pool(move |conn| {
             let packages_repo = repo_factory.create_packages_repo(&*conn, user_id);
             packages_repo
                 .update(id, payload)
+                .map(|package| {
+                    content_versions.invalidate(&[VersionedContent::Packages]);
+                    package
+                })
                 .map_err(|e| e.context("Service Packages, update endpoint error occured.").into())
         })
     }
@@ -111,11 +146,16 @@ impl<
     fn delete_package(&self, id: PackageId) -> ServiceFuture<Packages> {
         let repo_factory = self.static_context.repo_factory.clone();
         let user_id = self.dynamic_context.user_id;
+        let content_versions = self.static_context.content_versions.clone();
 
         self.spawn_on_pool(move |conn| {
             let packages_repo = repo_factory.create_packages_repo(&*conn, user_id);
             packages_repo
                 .delete(id)
+                .map(|package| {
+                    content_versions.invalidate(&[VersionedContent::Packages]);
+                    package
+                })
                 .map_err(|e| e.context("Service Packages, delete endpoint error occured.").into())
         })
     }
@@ -140,6 +180,8 @@ pub mod tests {
             max_weight: 0,
             min_weight: 0,
             deliveries_to: vec![],
+            deliveries_to_zones: vec![],
+            name_translations: vec![],
         }
     }
 
@@ -153,6 +195,22 @@ pub mod tests {
         assert_eq!(result.unwrap().id, PackageId(1));
     }
 
+    #[test]
+    fn test_search_packages() {
+        let mut core = Core::new().unwrap();
+        let handle = Arc::new(core.handle());
+        let service = create_service(Some(MOCK_USER_ID), handle);
+        let work = service.search_packages(Alpha3("BRA".to_string()), Some(1000), 0, 10);
+        let result = core.run(work).unwrap();
+        assert_eq!(result.total_count, 1);
+        assert_eq!(result.packages[0].max_weight, 1000);
+
+        let work = service.search_packages(Alpha3("BRA".to_string()), None, 1, 10);
+        let result = core.run(work).unwrap();
+        assert_eq!(result.total_count, 1);
+        assert!(result.packages.is_empty());
+    }
+
     #[test]
     fn test_create_packages() {
         let mut core = Core::new().unwrap();
@@ -163,5 +221,4 @@ pub mod tests {
         let result = core.run(work).unwrap();
         assert_eq!(result.name, "package1".to_string());
     }
-
 }
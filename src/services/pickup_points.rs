@@ -0,0 +1,108 @@
+//! PickupPoints Services, presents CRUD operations with pickup points and the nearby search
+
+use r2d2::ManageConnection;
+
+use stq_types::CompanyId;
+
+use super::types::{Service, ServiceFuture};
+use models::{NearbyPickupPoint, NearbyPickupPointsSearch, NewPickupPoint, PickupPoint, UpdatePickupPoint};
+use repos::{DbConnection, ReposFactory};
+
+pub trait PickupPointsService {
+    /// Returns list of pickup points, optionally only of the company
+    fn list_pickup_points(&self, company_id: Option<CompanyId>) -> ServiceFuture<Vec<PickupPoint>>;
+    /// Returns pickup point by id
+    fn get_pickup_point(&self, pickup_point_id: i32) -> ServiceFuture<Option<PickupPoint>>;
+    /// Returns pickup points within the radius ordered by distance, closest first
+    fn find_nearby_pickup_points(&self, search: NearbyPickupPointsSearch) -> ServiceFuture<Vec<NearbyPickupPoint>>;
+    /// Create a new pickup point
+    fn create_pickup_point(&self, payload: NewPickupPoint) -> ServiceFuture<PickupPoint>;
+    /// Update a pickup point
+    fn update_pickup_point(&self, pickup_point_id: i32, payload: UpdatePickupPoint) -> ServiceFuture<PickupPoint>;
+    /// Delete a pickup point
+    fn delete_pickup_point(&self, pickup_point_id: i32) -> ServiceFuture<PickupPoint>;
+}
+
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> PickupPointsService for Service<T, M, F> {
+    /// Returns list of pickup points, optionally only of the company
+    fn list_pickup_points(&self, company_id: Option<CompanyId>) -> ServiceFuture<Vec<PickupPoint>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let pickup_points_repo = repo_factory.create_pickup_points_repo(&*conn, user_id);
+            pickup_points_repo
+                .list(company_id)
+                .map_err(|e| e.context("Service PickupPoints, list_pickup_points endpoint error occured.").into())
+        })
+    }
+
+    /// Returns pickup point by id
+    fn get_pickup_point(&self, pickup_point_id: i32) -> ServiceFuture<Option<PickupPoint>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let pickup_points_repo = repo_factory.create_pickup_points_repo(&*conn, user_id);
+            pickup_points_repo
+                .find(pickup_point_id)
+                .map_err(|e| e.context("Service PickupPoints, get_pickup_point endpoint error occured.").into())
+        })
+    }
+
+    /// Returns pickup points within the radius ordered by distance, closest first
+    fn find_nearby_pickup_points(&self, search: NearbyPickupPointsSearch) -> ServiceFuture<Vec<NearbyPickupPoint>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_read_pool(move |conn| {
+            let pickup_points_repo = repo_factory.create_pickup_points_repo(&*conn, user_id);
+            pickup_points_repo.find_nearby(search).map_err(|e| {
+                e.context("Service PickupPoints, find_nearby_pickup_points endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Create a new pickup point
+    fn create_pickup_point(&self, payload: NewPickupPoint) -> ServiceFuture<PickupPoint> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_transaction_on_pool(move |conn| {
+            let pickup_points_repo = repo_factory.create_pickup_points_repo(&*conn, user_id);
+            pickup_points_repo.create(payload).map_err(|e| {
+                e.context("Service PickupPoints, create_pickup_point endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Update a pickup point
+    fn update_pickup_point(&self, pickup_point_id: i32, payload: UpdatePickupPoint) -> ServiceFuture<PickupPoint> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let pickup_points_repo = repo_factory.create_pickup_points_repo(&*conn, user_id);
+            pickup_points_repo.update(pickup_point_id, payload).map_err(|e| {
+                e.context("Service PickupPoints, update_pickup_point endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Delete a pickup point
+    fn delete_pickup_point(&self, pickup_point_id: i32) -> ServiceFuture<PickupPoint> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let pickup_points_repo = repo_factory.create_pickup_points_repo(&*conn, user_id);
+            pickup_points_repo.delete(pickup_point_id).map_err(|e| {
+                e.context("Service PickupPoints, delete_pickup_point endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+}
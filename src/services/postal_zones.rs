@@ -0,0 +1,133 @@
+//! Postal zones Services, presents CRUD operations with postal zones and the import of the postal codes lists of the carriers
+
+use base64;
+use failure::Error as FailureError;
+use failure::Fail;
+use r2d2::ManageConnection;
+
+use stq_types::Alpha3;
+
+use super::types::{Service, ServiceFuture};
+use errors::Error;
+use models::{Country, ImportPostalZone, NewPostalZone, PostalCodesCsvData, PostalZone, TypedJson};
+use repos::{CountriesRepo, DbConnection, ReposFactory};
+
+pub trait PostalZonesService {
+    /// Returns list of postal zones, only the ones of the country if it is set
+    fn list_postal_zones(&self, alpha3: Option<Alpha3>) -> ServiceFuture<Vec<PostalZone>>;
+    /// Returns postal zone by id
+    fn get_postal_zone(&self, postal_zone_id: i32) -> ServiceFuture<Option<PostalZone>>;
+    /// Create a new postal zone
+    fn create_postal_zone(&self, payload: NewPostalZone) -> ServiceFuture<PostalZone>;
+    /// Creates postal zone from the postal codes list of the carrier, the ranges of the existing zone are replaced
+    fn import_postal_zone(&self, payload: ImportPostalZone) -> ServiceFuture<PostalZone>;
+    /// Delete a postal zone together with its rates
+    fn delete_postal_zone(&self, postal_zone_id: i32) -> ServiceFuture<PostalZone>;
+}
+
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> PostalZonesService for Service<T, M, F> {
+    /// Returns list of postal zones, only the ones of the country if it is set
+    fn list_postal_zones(&self, alpha3: Option<Alpha3>) -> ServiceFuture<Vec<PostalZone>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let postal_zones_repo = repo_factory.create_postal_zones_repo(&*conn, user_id);
+            postal_zones_repo
+                .list(alpha3)
+                .map_err(|e| e.context("Service PostalZones, list_postal_zones endpoint error occured.").into())
+        })
+    }
+
+    /// Returns postal zone by id
+    fn get_postal_zone(&self, postal_zone_id: i32) -> ServiceFuture<Option<PostalZone>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let postal_zones_repo = repo_factory.create_postal_zones_repo(&*conn, user_id);
+            postal_zones_repo
+                .find(postal_zone_id)
+                .map_err(|e| e.context("Service PostalZones, get_postal_zone endpoint error occured.").into())
+        })
+    }
+
+    /// Create a new postal zone
+    fn create_postal_zone(&self, payload: NewPostalZone) -> ServiceFuture<PostalZone> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_transaction_on_pool(move |conn| {
+            let countries_repo = repo_factory.create_countries_repo(&*conn, user_id);
+            let postal_zones_repo = repo_factory.create_postal_zones_repo(&*conn, user_id);
+
+            check_country(&*countries_repo, &payload.alpha3)
+                .and_then(|_| postal_zones_repo.create(payload))
+                .map_err(|e| e.context("Service PostalZones, create_postal_zone endpoint error occured.").into())
+        })
+    }
+
+    /// Creates postal zone from the postal codes list of the carrier, the ranges of the existing zone are replaced
+    fn import_postal_zone(&self, payload: ImportPostalZone) -> ServiceFuture<PostalZone> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_transaction_on_pool(move |conn| {
+            let countries_repo = repo_factory.create_countries_repo(&*conn, user_id);
+            let postal_zones_repo = repo_factory.create_postal_zones_repo(&*conn, user_id);
+
+            let run = move || {
+                let ImportPostalZone {
+                    alpha3,
+                    name,
+                    postal_codes_csv_base64,
+                } = payload;
+
+                let ranges = base64::decode(&postal_codes_csv_base64)
+                    .map_err(|_| {
+                        let errors =
+                            validation_errors!({ "payload": ["postal_codes_csv_base64" => "Failed to decode base64 postal codes CSV"] });
+                        FailureError::from(Error::Validate(errors))
+                    })
+                    .and_then(|csv| {
+                        PostalCodesCsvData::parse_csv(&csv).map_err(|e| {
+                            let errors = validation_errors!({ "payload": ["postal_codes_csv_base64" => e.to_string()] });
+                            FailureError::from(Error::Validate(errors))
+                        })
+                    })?;
+
+                check_country(&*countries_repo, &alpha3)?;
+
+                postal_zones_repo.upsert(NewPostalZone {
+                    alpha3,
+                    name,
+                    ranges: TypedJson(ranges.0),
+                })
+            };
+
+            run().map_err(|e: FailureError| e.context("Service PostalZones, import_postal_zone endpoint error occured.").into())
+        })
+    }
+
+    /// Delete a postal zone together with its rates
+    fn delete_postal_zone(&self, postal_zone_id: i32) -> ServiceFuture<PostalZone> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_transaction_on_pool(move |conn| {
+            let postal_zones_repo = repo_factory.create_postal_zones_repo(&*conn, user_id);
+            postal_zones_repo
+                .delete(postal_zone_id)
+                .map_err(|e| e.context("Service PostalZones, delete_postal_zone endpoint error occured.").into())
+        })
+    }
+}
+
+/// Postal zones are only created for the countries, not for the regions of the countries tree
+fn check_country(countries_repo: &dyn CountriesRepo, alpha3: &Alpha3) -> Result<(), FailureError> {
+    countries_repo
+        .find(alpha3.clone())?
+        .filter(|country| country.level == Country::COUNTRY_LEVEL)
+        .map(|_| ())
+        .ok_or_else(|| format_err!("Country {} not found", alpha3).context(Error::NotFound).into())
+}
@@ -0,0 +1,90 @@
+//! Pricing rules Services, presents CRUD operations with markups and discounts of the delivery prices of the stores
+
+use r2d2::ManageConnection;
+
+use super::types::{Service, ServiceFuture};
+use models::{NewPricingRule, PricingRule, UpdatePricingRule};
+use repos::{DbConnection, ReposFactory};
+
+pub trait PricingRulesService {
+    /// Returns list of pricing rules
+    fn list_pricing_rules(&self) -> ServiceFuture<Vec<PricingRule>>;
+    /// Returns pricing rule by id
+    fn get_pricing_rule(&self, pricing_rule_id: i32) -> ServiceFuture<Option<PricingRule>>;
+    /// Create a new pricing rule
+    fn create_pricing_rule(&self, payload: NewPricingRule) -> ServiceFuture<PricingRule>;
+    /// Update a pricing rule
+    fn update_pricing_rule(&self, pricing_rule_id: i32, payload: UpdatePricingRule) -> ServiceFuture<PricingRule>;
+    /// Delete a pricing rule
+    fn delete_pricing_rule(&self, pricing_rule_id: i32) -> ServiceFuture<PricingRule>;
+}
+
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> PricingRulesService for Service<T, M, F> {
+    /// Returns list of pricing rules
+    fn list_pricing_rules(&self) -> ServiceFuture<Vec<PricingRule>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let pricing_rules_repo = repo_factory.create_pricing_rules_repo(&*conn, user_id);
+            pricing_rules_repo
+                .list()
+                .map_err(|e| e.context("Service PricingRules, list_pricing_rules endpoint error occured.").into())
+        })
+    }
+
+    /// Returns pricing rule by id
+    fn get_pricing_rule(&self, pricing_rule_id: i32) -> ServiceFuture<Option<PricingRule>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let pricing_rules_repo = repo_factory.create_pricing_rules_repo(&*conn, user_id);
+            pricing_rules_repo
+                .find(pricing_rule_id)
+                .map_err(|e| e.context("Service PricingRules, get_pricing_rule endpoint error occured.").into())
+        })
+    }
+
+    /// Create a new pricing rule
+    fn create_pricing_rule(&self, payload: NewPricingRule) -> ServiceFuture<PricingRule> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_transaction_on_pool(move |conn| {
+            let pricing_rules_repo = repo_factory.create_pricing_rules_repo(&*conn, user_id);
+            pricing_rules_repo.create(payload).map_err(|e| {
+                e.context("Service PricingRules, create_pricing_rule endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Update a pricing rule
+    fn update_pricing_rule(&self, pricing_rule_id: i32, payload: UpdatePricingRule) -> ServiceFuture<PricingRule> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let pricing_rules_repo = repo_factory.create_pricing_rules_repo(&*conn, user_id);
+            pricing_rules_repo.update(pricing_rule_id, payload).map_err(|e| {
+                e.context("Service PricingRules, update_pricing_rule endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Delete a pricing rule
+    fn delete_pricing_rule(&self, pricing_rule_id: i32) -> ServiceFuture<PricingRule> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let pricing_rules_repo = repo_factory.create_pricing_rules_repo(&*conn, user_id);
+            pricing_rules_repo.delete(pricing_rule_id).map_err(|e| {
+                e.context("Service PricingRules, delete_pricing_rule endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+}
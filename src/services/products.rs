@@ -1,42 +1,71 @@
 //! Products Service, presents CRUD operations
-use diesel::connection::AnsiTransactionManager;
-use diesel::pg::Pg;
-use diesel::Connection;
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDate, Utc};
 use failure::Error as FailureError;
+use futures::Future;
 use validator::Validate;
 
 use r2d2::ManageConnection;
 
-use stq_types::{Alpha3, BaseProductId, CompanyPackageId, ProductPrice, ShippingId};
+use stq_types::{Alpha3, BaseProductId, CompanyPackageId, ProductPrice, ShippingId, StoreId};
 
 use errors::Error;
+use events::{self, DeliveryEvent};
 use models::{
-    AvailablePackageForUser, AvailableShippingForUser, NewProductValidation, NewProducts, NewShipping, PackageValidation, Products,
-    ShipmentMeasurements, Shipping, ShippingProducts, ShippingRateSource, ShippingValidation, UpdateProducts,
+    aggregate_region_packages, apply_pricing_rules, find_rounding_policy, get_countries_by, resolve_stored_rates, select_package_origins,
+    uses_countries_tree, validate_packages_limits, AvailablePackageForUser, AvailableShippingForRegion, AvailableShippingForUser, Company,
+    CompanyPackage, Country, DeliveryCoordinates, HazardClass, NewProductValidation, NewProducts, OriginPreference,
+    PackageLimitsValidation, PriceRoundingPolicy, PricingRule, Products, RateRoute, ShipmentMeasurements, Shipping, ShippingOptionId,
+    ShippingProducts, ShippingRateSource, ShippingRates, ShippingUpsert, ShippingValidation, StoreMargin, Surcharge, UnavailabilityReason,
+    UnavailablePackageForUser, UpdateProducts,
 };
 use repos::companies::CompaniesRepo;
 use repos::companies_packages::CompaniesPackagesRepo;
-use repos::countries::create_tree_used_countries;
-use repos::products::ProductsWithAvailableCountries;
+use repos::company_suspensions::CompanySuspensionsRepo;
+use repos::countries::{create_tree_used_countries, CountriesRepo};
+use repos::country_regulations::CountryRegulationsRepo;
+use repos::packages::PackagesRepo;
+use repos::pricing_rules::PricingRulesRepo;
+use repos::products::{ProductsRepo, ProductsWithAvailableCountries};
+use repos::shipment_bookings::ShipmentBookingsRepo;
 use repos::shipping_rates::ShippingRatesRepo;
-use repos::ReposFactory;
+use repos::store_margins::StoreMarginsRepo;
+use repos::surcharges::SurchargesRepo;
+use repos::{DbConnection, ReposFactory};
+use services::companies_packages::stored_rates_route;
 use services::types::{Service, ServiceFuture};
 
 pub trait ProductsService {
-    /// Delete and Insert shipping values
-    fn upsert(&self, base_product_id: BaseProductId, payload: NewShipping) -> ServiceFuture<Shipping>;
+    /// Delete and Insert shipping values, given in full or set up by a shipping template of the store
+    fn upsert(&self, base_product_id: BaseProductId, payload: ShippingUpsert) -> ServiceFuture<Shipping>;
 
     /// Get products
     fn get_by_base_product_id(&self, base_product_id: BaseProductId) -> ServiceFuture<Shipping>;
 
-    /// find available product delivery to users country
+    /// find available product delivery to users country, suspended companies and packages are excluded.
+    /// Packages refusing hazard classes of the product or suspended are listed with reasons when `explain` is set.
+    /// Only packages supporting the cash on delivery are available with `cod`.
+    /// Packages are shipped on the date by their cut-off time and `order_time`, the ones booked up on it are excluded
     fn find_available_shipping_for_user(
         &self,
         base_product_id: BaseProductId,
         user_country: Alpha3,
+        explain: bool,
+        cod: bool,
+        order_time: Option<DateTime<Utc>>,
     ) -> ServiceFuture<AvailableShippingForUser>;
 
-    /// find available product delivery to user's country with correct prices
+    /// find available product delivery to user's country with correct prices, suspended companies and packages are excluded.
+    /// Packages refusing hazard classes of the product or suspended are listed with reasons when `explain` is set.
+    /// Packages priced by distance get a price only when `coordinates` are supplied.
+    /// Carrier costs without the store margin are returned with `with_cost` to the store managers only.
+    /// Delivery is free when `order_total` reaches the free delivery threshold of the product.
+    /// Only packages supporting the cash on delivery are available with `cod`.
+    /// Packages shipped from the warehouses of the seller are delivered from them instead of `delivery_from`,
+    /// of the ones shipped from several warehouses the origin by `origin_preference` is kept.
+    /// Packages are shipped on the date by their cut-off time and `order_time`, the ones booked up on it are excluded
+    #[allow(clippy::too_many_arguments)]
     fn find_available_shipping_for_user_v2(
         &self,
         base_product_id: BaseProductId,
@@ -44,8 +73,27 @@ pub trait ProductsService {
         delivery_to: Alpha3,
         volume: u32,
         weight: u32,
+        coordinates: Option<DeliveryCoordinates>,
+        explain: bool,
+        with_cost: bool,
+        order_total: Option<f64>,
+        cod: bool,
+        origin_preference: OriginPreference,
+        order_time: Option<DateTime<Utc>>,
     ) -> ServiceFuture<AvailableShippingForUser>;
 
+    /// find available product delivery to the countries of the region for visitors whose country is unknown,
+    /// packages are aggregated over the countries with the price range, the ones booked up for the order are left out
+    fn find_available_shipping_for_region(
+        &self,
+        base_product_id: BaseProductId,
+        delivery_from: Alpha3,
+        region: String,
+        volume: u32,
+        weight: u32,
+        order_time: Option<DateTime<Utc>>,
+    ) -> ServiceFuture<AvailableShippingForRegion>;
+
     /// Update a product
     fn update_products(
         &self,
@@ -54,18 +102,15 @@ pub trait ProductsService {
         payload: UpdateProducts,
     ) -> ServiceFuture<Products>;
 
-    /// Returns available package for user by id
-    /// DEPRECATED. Use `get_available_package_for_user_by_shipping_id_v2` instead.
-    fn get_available_package_for_user(
-        &self,
-        base_product_id: BaseProductId,
-        package_id: CompanyPackageId,
-    ) -> ServiceFuture<Option<AvailablePackageForUser>>;
-
     /// Returns available package for user by shipping id
     fn get_available_package_for_user_by_shipping_id(&self, shipping_id: ShippingId) -> ServiceFuture<Option<AvailablePackageForUser>>;
 
-    /// Returns available package for user by shipping id with correct price
+    /// Returns available package for user by shipping id with correct price.
+    /// Packages priced by distance get a price only when `coordinates` are supplied.
+    /// Carrier cost without the store margin is returned with `with_cost` to the store managers only.
+    /// If `option_id` chosen by the customer is passed, it must be the id of the returned option.
+    /// Package is shipped on the date by its cut-off time and `order_time`, none is returned if it is booked up on it
+    #[allow(clippy::too_many_arguments)]
     fn get_available_package_for_user_by_shipping_id_v2(
         &self,
         shipping_id: ShippingId,
@@ -73,72 +118,57 @@ pub trait ProductsService {
         delivery_to: Alpha3,
         volume: u32,
         weight: u32,
+        coordinates: Option<DeliveryCoordinates>,
+        with_cost: bool,
+        option_id: Option<ShippingOptionId>,
+        order_time: Option<DateTime<Utc>>,
     ) -> ServiceFuture<Option<AvailablePackageForUser>>;
 
     fn delete_products(&self, base_product_id_arg: BaseProductId) -> ServiceFuture<()>;
+
+    /// Replaces shipping of the target base products with the copy of the shipping of the source base product
+    fn clone_shipping(&self, base_product_id: BaseProductId, targets: Vec<BaseProductId>) -> ServiceFuture<Vec<Shipping>>;
 }
 
-impl<
-        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
-        M: ManageConnection<Connection = T>,
-        F: ReposFactory<T>,
-    > ProductsService for Service<T, M, F>
-{
-    fn upsert(&self, base_product_id: BaseProductId, payload: NewShipping) -> ServiceFuture<Shipping> {
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> ProductsService for Service<T, M, F> {
+    fn upsert(&self, base_product_id: BaseProductId, payload: ShippingUpsert) -> ServiceFuture<Shipping> {
         let repo_factory = self.static_context.repo_factory.clone();
         let user_id = self.dynamic_context.user_id;
+        let events_enabled = self.events_enabled();
+        let service = self.clone();
+
+        let shipping = self
+            .spawn_transaction_on_pool(move |conn| {
+                let shipping_templates_repo = repo_factory.create_shipping_templates_repo(&*conn, user_id);
+                let mut payload = match payload {
+                    ShippingUpsert::Shipping(payload) => payload,
+                    ShippingUpsert::Template { store_id, template_id } => shipping_templates_repo
+                        .find(store_id, template_id)?
+                        .ok_or_else(|| {
+                            Error::Validate(validation_errors!({
+                                "template_id": ["template_id" => format!("Shipping template with id: {} not found", template_id)]
+                            }))
+                        })?
+                        .expand(base_product_id),
+                };
+                for item in payload.items.iter_mut() {
+                    item.hazard_classes = payload.hazard_classes.clone();
+                }
 
-        self.spawn_on_pool(move |conn| {
-            conn.transaction::<Shipping, _, _>(|| {
                 let products_repo = repo_factory.create_products_repo(&*conn, user_id);
                 let pickups_repo = repo_factory.create_pickups_repo(&*conn, user_id);
                 let countries_repo = repo_factory.create_countries_repo(&*conn, user_id);
                 let companies_repo = repo_factory.create_companies_repo(&*conn, user_id);
                 let packages_repo = repo_factory.create_packages_repo(&*conn, user_id);
                 let company_packages_repo = repo_factory.create_companies_packages_repo(&*conn, user_id);
+                let customs_info_repo = repo_factory.create_customs_info_repo(&*conn, user_id);
+                let event_outbox_repo = repo_factory.create_event_outbox_repo_with_sys_acl(&*conn);
                 let pickup = payload.pickup.clone();
 
                 products_repo
                     .delete(base_product_id)
                     .and_then(|_| {
-                        payload
-                            .items
-                            .clone()
-                            .into_iter()
-                            .map(|new_product| {
-                                let company_package = company_packages_repo.get(new_product.company_package_id)?.ok_or(Error::Validate(
-                                    validation_errors!({
-                                        "company_package_id": ["company_package_id" => format!("Company package with id: {} not found", new_product.company_package_id)]
-                                    }),
-                                ))?;
-                                let company = companies_repo
-                                    .find(company_package.company_id)?
-                                    .ok_or(format_err!("Company with id = {} not found", company_package.company_id))?;
-                                let package = packages_repo
-                                    .find(company_package.package_id)?
-                                    .ok_or(format_err!("Package with id = {} not found", company_package.package_id))?;
-
-                                let package_validation = new_product.measurements.clone().map(|measurements| PackageValidation {
-                                    measurements,
-                                    package: package.clone(),
-                                });
-
-                                NewProductValidation {
-                                    product: new_product.clone(),
-                                    package: package_validation,
-                                    shipping: ShippingValidation {
-                                        delivery_from: new_product.delivery_from.clone(),
-                                        deliveries_to: new_product.deliveries_to.clone(),
-                                        company,
-                                        package,
-                                    },
-                                }
-                                .validate()
-                                .map(|_| new_product)
-                                .map_err(|e| FailureError::from(Error::Validate(e)))
-                            })
-                            .collect::<Result<Vec<NewProducts>, _>>()?;
-
+                        validate_new_products(&*company_packages_repo, &*companies_repo, &*packages_repo, &payload.items)?;
                         products_repo.create_many(payload.items)
                     })
                     .and_then(|_| products_repo.get_products_countries(base_product_id))
@@ -166,14 +196,25 @@ impl<
                         } else {
                             Ok(None)
                         }
-                        .map(|pickups| Shipping {
-                            items: products,
-                            pickup: pickups,
+                        .and_then(|pickups| {
+                            customs_info_repo.get(base_product_id).map(|customs_info| Shipping {
+                                items: products,
+                                pickup: pickups,
+                                customs_info,
+                            })
                         })
                     })
+                    .and_then(|shipping| {
+                        let event = DeliveryEvent::ProductShippingUpdated {
+                            base_product_id,
+                            company_package_ids: shipping.items.iter().map(|item| item.product.company_package_id).collect(),
+                        };
+                        events::store(&*event_outbox_repo, events_enabled, vec![event]).map(|outbox_events| (shipping, outbox_events))
+                    })
             })
-            .map_err(|e: FailureError| e.context("Service Products, upsert endpoint error occured.").into())
-        })
+            .map_err(|e: FailureError| e.context("Service Products, upsert endpoint error occured.").into());
+
+        Box::new(shipping.and_then(move |(shipping, outbox_events)| service.publish_events(outbox_events).map(move |_| shipping)))
     }
 
     fn get_by_base_product_id(&self, base_product_id: BaseProductId) -> ServiceFuture<Shipping> {
@@ -184,6 +225,7 @@ impl<
             let products_repo = repo_factory.create_products_repo(&*conn, user_id);
             let pickups_repo = repo_factory.create_pickups_repo(&*conn, user_id);
             let countries_repo = repo_factory.create_countries_repo(&*conn, user_id);
+            let customs_info_repo = repo_factory.create_customs_info_repo(&*conn, user_id);
             products_repo
                 .get_products_countries(base_product_id)
                 .and_then(|products_with_countries| {
@@ -202,9 +244,12 @@ impl<
                     })
                 })
                 .and_then(|products| {
-                    pickups_repo.get(base_product_id).map(|pickups| Shipping {
+                    let pickups = pickups_repo.get(base_product_id)?;
+                    let customs_info = customs_info_repo.get(base_product_id)?;
+                    Ok(Shipping {
                         items: products,
                         pickup: pickups,
+                        customs_info,
                     })
                 })
                 .map_err(|e| {
@@ -219,25 +264,59 @@ impl<
         &self,
         base_product_id: BaseProductId,
         user_country: Alpha3,
+        explain: bool,
+        cod: bool,
+        order_time: Option<DateTime<Utc>>,
     ) -> ServiceFuture<AvailableShippingForUser> {
         let repo_factory = self.static_context.repo_factory.clone();
         let user_id = self.dynamic_context.user_id;
+        let clock = self.static_context.clock.clone();
 
-        self.spawn_on_pool(move |conn| {
+        self.spawn_on_read_pool(move |conn| {
             let products_repo = repo_factory.create_products_repo(&*conn, user_id);
+            let company_package_repo = repo_factory.create_companies_packages_repo(&*conn, user_id);
             let pickups_repo = repo_factory.create_pickups_repo(&*conn, user_id);
-            products_repo
-                .find_available_to(base_product_id, user_country)
-                .and_then(|packages| {
-                    pickups_repo
-                        .get(base_product_id)
-                        .map(|pickups| AvailableShippingForUser { packages, pickups })
+            let country_regulations_repo = repo_factory.create_country_regulations_repo_with_sys_acl(&*conn);
+            let company_suspensions_repo = repo_factory.create_company_suspensions_repo_with_sys_acl(&*conn);
+            let shipment_bookings_repo = repo_factory.create_shipment_bookings_repo_with_sys_acl(&*conn);
+
+            let run = || {
+                let (packages, mut unavailable_packages) = find_available_packages(
+                    &*products_repo,
+                    &*company_package_repo,
+                    &*country_regulations_repo,
+                    &*company_suspensions_repo,
+                    base_product_id,
+                    user_country,
+                    clock.today(),
+                )?;
+
+                let company_packages = load_company_packages(&*company_package_repo, &packages)?;
+                let packages = if cod {
+                    let (packages, cod_unsupported_packages) = filter_by_cash_on_delivery(&company_packages, packages)?;
+                    unavailable_packages.extend(cod_unsupported_packages);
+                    packages
+                } else {
+                    packages
+                };
+                let order_time = order_time.unwrap_or_else(|| clock.now());
+                let (packages, booked_up_packages) = schedule_shipments(&*shipment_bookings_repo, &company_packages, order_time, packages)?;
+                unavailable_packages.extend(booked_up_packages);
+
+                pickups_repo.get(base_product_id).map(|pickups| AvailableShippingForUser {
+                    packages,
+                    pickups,
+                    unavailable_packages: if explain { unavailable_packages } else { vec![] },
+                    measurements: None,
                 })
-                .map_err(|e| e.context("Service Products, find_available_to endpoint error occurred.").into())
+            };
+
+            run().map_err(|e: FailureError| e.context("Service Products, find_available_to endpoint error occurred.").into())
         })
     }
 
     /// find available product delivery to user's country with correct prices
+    #[allow(clippy::too_many_arguments)]
     fn find_available_shipping_for_user_v2(
         &self,
         base_product_id: BaseProductId,
@@ -245,66 +324,226 @@ impl<
         delivery_to: Alpha3,
         volume: u32,
         weight: u32,
+        coordinates: Option<DeliveryCoordinates>,
+        explain: bool,
+        with_cost: bool,
+        order_total: Option<f64>,
+        cod: bool,
+        origin_preference: OriginPreference,
+        order_time: Option<DateTime<Utc>>,
     ) -> ServiceFuture<AvailableShippingForUser> {
         let repo_factory = self.static_context.repo_factory.clone();
         let user_id = self.dynamic_context.user_id;
+        let clock = self.static_context.clock.clone();
+        let rounding_policies = self.static_context.config.price_rounding_policies();
 
-        self.spawn_on_pool(move |conn| {
+        self.spawn_on_read_pool(move |conn| {
             let products_repo = repo_factory.create_products_repo(&*conn, user_id);
             let company_package_repo = repo_factory.create_companies_packages_repo(&*conn, user_id);
-            let company_repo = repo_factory.create_companies_repo(&*conn, user_id);
+            let countries_repo = repo_factory.create_countries_repo(&*conn, user_id);
             let shipping_rates_repo = repo_factory.create_shipping_rates_repo(&*conn, user_id);
+            let surcharges_repo = repo_factory.create_surcharges_repo_with_sys_acl(&*conn);
+            let store_margins_repo = repo_factory.create_store_margins_repo_with_sys_acl(&*conn);
+            let pricing_rules_repo = repo_factory.create_pricing_rules_repo_with_sys_acl(&*conn);
+            let user_store_margins_repo = repo_factory.create_store_margins_repo(&*conn, user_id);
             let pickups_repo = repo_factory.create_pickups_repo(&*conn, user_id);
+            let country_regulations_repo = repo_factory.create_country_regulations_repo_with_sys_acl(&*conn);
+            let company_suspensions_repo = repo_factory.create_company_suspensions_repo_with_sys_acl(&*conn);
+            let shipment_bookings_repo = repo_factory.create_shipment_bookings_repo_with_sys_acl(&*conn);
 
             let run = || {
-                let packages = products_repo
-                    .find_available_to(base_product_id, delivery_to.clone())?
-                    .into_iter()
-                    .map(|pkg| {
-                        with_price_from_rates(
-                            &*company_package_repo,
-                            &*company_repo,
-                            &*shipping_rates_repo,
-                            delivery_from.clone(),
-                            delivery_to.clone(),
-                            volume,
-                            weight,
-                            pkg,
-                        )
-                    })
-                    .collect::<Result<Vec<_>, _>>()?
-                    .into_iter()
-                    .filter_map(|x| x)
-                    .collect::<Vec<_>>();
+                if let Some(ref coordinates) = coordinates {
+                    coordinates.validate().map_err(Error::Validate)?;
+                }
+                let today = clock.today();
+
+                let packages = products_repo.find_available_to(base_product_id, delivery_to.clone())?;
+                let company_packages = load_company_packages(&*company_package_repo, &packages)?;
+                let (packages, mut unavailable_packages) =
+                    filter_by_suspensions(&*company_suspensions_repo, &company_packages, today, packages)?;
+                let (packages, refusing_packages) =
+                    filter_by_hazard_classes(&*products_repo, &company_packages, base_product_id, packages)?;
+                unavailable_packages.extend(refusing_packages);
+                let packages = if cod {
+                    let (packages, cod_unsupported_packages) = filter_by_cash_on_delivery(&company_packages, packages)?;
+                    unavailable_packages.extend(cod_unsupported_packages);
+                    packages
+                } else {
+                    packages
+                };
 
-                pickups_repo
-                    .get(base_product_id)
-                    .map(|pickups| AvailableShippingForUser { packages, pickups })
+                let packages = price_packages(
+                    &company_packages,
+                    &*countries_repo,
+                    &*shipping_rates_repo,
+                    &*surcharges_repo,
+                    &*store_margins_repo,
+                    &*pricing_rules_repo,
+                    delivery_from.clone(),
+                    delivery_to.clone(),
+                    volume,
+                    weight,
+                    coordinates,
+                    today,
+                    &rounding_policies,
+                    packages,
+                )?
+                .into_iter()
+                .map(|pkg| pkg.with_option_id(&delivery_from, &delivery_to))
+                .map(|pkg| match order_total {
+                    Some(order_total) => pkg.with_order_total(order_total),
+                    None => pkg,
+                })
+                .map(|pkg| with_cost_for_managers(&*user_store_margins_repo, with_cost, pkg))
+                .collect::<Result<Vec<_>, _>>()?;
+                let packages = select_package_origins(packages, origin_preference);
+
+                let measurements = ShipmentMeasurements {
+                    volume_cubic_cm: volume,
+                    weight_g: weight,
+                };
+                let (packages, prohibited_packages) = filter_by_country_regulations(
+                    &*country_regulations_repo,
+                    &*products_repo,
+                    base_product_id,
+                    delivery_to.clone(),
+                    Some(measurements),
+                    packages,
+                )?;
+                unavailable_packages.extend(prohibited_packages);
+                let order_time = order_time.unwrap_or_else(|| clock.now());
+                let (packages, booked_up_packages) = schedule_shipments(&*shipment_bookings_repo, &company_packages, order_time, packages)?;
+                unavailable_packages.extend(booked_up_packages);
+
+                pickups_repo.get(base_product_id).map(|pickups| AvailableShippingForUser {
+                    packages,
+                    pickups,
+                    unavailable_packages: if explain { unavailable_packages } else { vec![] },
+                    measurements: Some(measurements),
+                })
             };
 
             run().map_err(|e: FailureError| e.context("Service Products, find_available_to endpoint error occurred.").into())
         })
     }
 
-    /// Returns available package for user by id
-    /// DEPRECATED. Use `get_available_package_for_user_by_shipping_id_v2` instead.
-    fn get_available_package_for_user(
+    /// find available product delivery to the countries of the region for visitors whose country is unknown,
+    /// packages are aggregated over the countries with the price range, the ones booked up for the order are left out
+    fn find_available_shipping_for_region(
         &self,
         base_product_id: BaseProductId,
-        package_id: CompanyPackageId,
-    ) -> ServiceFuture<Option<AvailablePackageForUser>> {
+        delivery_from: Alpha3,
+        region: String,
+        volume: u32,
+        weight: u32,
+        order_time: Option<DateTime<Utc>>,
+    ) -> ServiceFuture<AvailableShippingForRegion> {
         let repo_factory = self.static_context.repo_factory.clone();
         let user_id = self.dynamic_context.user_id;
+        let clock = self.static_context.clock.clone();
+        let rounding_policies = self.static_context.config.price_rounding_policies();
 
-        self.spawn_on_pool(move |conn| {
+        self.spawn_on_read_pool(move |conn| {
             let products_repo = repo_factory.create_products_repo(&*conn, user_id);
+            let company_package_repo = repo_factory.create_companies_packages_repo(&*conn, user_id);
+            let countries_repo = repo_factory.create_countries_repo(&*conn, user_id);
+            let shipping_rates_repo = repo_factory.create_shipping_rates_repo(&*conn, user_id);
+            let surcharges_repo = repo_factory.create_surcharges_repo_with_sys_acl(&*conn);
+            let store_margins_repo = repo_factory.create_store_margins_repo_with_sys_acl(&*conn);
+            let pricing_rules_repo = repo_factory.create_pricing_rules_repo_with_sys_acl(&*conn);
+            let country_regulations_repo = repo_factory.create_country_regulations_repo_with_sys_acl(&*conn);
+            let company_suspensions_repo = repo_factory.create_company_suspensions_repo_with_sys_acl(&*conn);
+            let shipment_bookings_repo = repo_factory.create_shipment_bookings_repo_with_sys_acl(&*conn);
 
-            products_repo
-                .get_available_package_for_user(base_product_id, package_id)
-                .map_err(|e| {
-                    e.context("Service Products, get_available_package_for_user endpoint error occurred.")
-                        .into()
+            let run = || {
+                let root = countries_repo.get_all()?;
+                let region = find_region(&root, &region)?;
+                let today = clock.today();
+
+                let measurements = ShipmentMeasurements {
+                    volume_cubic_cm: volume,
+                    weight_g: weight,
+                };
+                let countries = get_countries_by(&region, |country| country.level == Country::COUNTRY_LEVEL)
+                    .into_iter()
+                    .map(|country| country.alpha3)
+                    .collect::<Vec<_>>();
+
+                // products, company packages, suspensions and rates of all the countries are loaded at once
+                let packages_by_country = products_repo.find_available_to_countries(base_product_id, countries)?;
+                let mut packages = vec![];
+                for pkg in packages_by_country.iter().flat_map(|&(_, ref packages)| packages.iter()) {
+                    if !packages
+                        .iter()
+                        .any(|known: &AvailablePackageForUser| known.shipping_id == pkg.shipping_id)
+                    {
+                        packages.push(pkg.clone());
+                    }
+                }
+                let company_packages = load_company_packages(&*company_package_repo, &packages)?;
+                let (packages, _) = filter_by_suspensions(&*company_suspensions_repo, &company_packages, today, packages)?;
+                let (packages, _) = filter_by_hazard_classes(&*products_repo, &company_packages, base_product_id, packages)?;
+                let order_time = order_time.unwrap_or_else(|| clock.now());
+                let (packages, _) = schedule_shipments(&*shipment_bookings_repo, &company_packages, order_time, packages)?;
+                let shipping_ids = packages.into_iter().map(|pkg| pkg.shipping_id).collect::<Vec<_>>();
+                let packages_by_country = packages_by_country
+                    .into_iter()
+                    .map(|(country, packages)| {
+                        let packages = packages.into_iter().filter(|pkg| shipping_ids.contains(&pkg.shipping_id)).collect();
+                        (country, packages)
+                    })
+                    .collect();
+
+                let packages_by_country = price_packages_to_countries(
+                    &company_packages,
+                    &*countries_repo,
+                    &*shipping_rates_repo,
+                    &*surcharges_repo,
+                    &*store_margins_repo,
+                    &*pricing_rules_repo,
+                    delivery_from.clone(),
+                    volume,
+                    weight,
+                    None,
+                    today,
+                    &rounding_policies,
+                    packages_by_country,
+                )?;
+
+                let regulations = country_regulations_repo.list()?;
+                let hazard_classes = if regulations.is_empty() {
+                    vec![]
+                } else {
+                    product_hazard_classes(&*products_repo, base_product_id)?
+                };
+                let packages_by_country = packages_by_country
+                    .into_iter()
+                    .map(|(country, packages)| {
+                        // the destination country not accepting the parcel has none of the packages available
+                        let violates_regulation = regulations
+                            .iter()
+                            .find(|regulation| regulation.alpha3 == country)
+                            .map(|regulation| !regulation.violations(&hazard_classes, Some(measurements)).is_empty())
+                            .unwrap_or_default();
+                        if violates_regulation {
+                            (country, vec![])
+                        } else {
+                            (country, packages)
+                        }
+                    })
+                    .collect();
+
+                Ok(AvailableShippingForRegion {
+                    region: region.alpha3,
+                    packages: aggregate_region_packages(packages_by_country),
+                    measurements,
                 })
+            };
+
+            run().map_err(|e: FailureError| {
+                e.context("Service Products, find_available_shipping_for_region endpoint error occurred.")
+                    .into()
+            })
         })
     }
 
@@ -313,7 +552,7 @@ impl<
         let repo_factory = self.static_context.repo_factory.clone();
         let user_id = self.dynamic_context.user_id;
 
-        self.spawn_on_pool(move |conn| {
+        self.spawn_on_read_pool(move |conn| {
             let products_repo = repo_factory.create_products_repo(&*conn, user_id);
 
             products_repo
@@ -326,6 +565,7 @@ impl<
     }
 
     /// Returns available package for user by shipping id with correct price
+    #[allow(clippy::too_many_arguments)]
     fn get_available_package_for_user_by_shipping_id_v2(
         &self,
         shipping_id: ShippingId,
@@ -333,34 +573,99 @@ impl<
         delivery_to: Alpha3,
         volume: u32,
         weight: u32,
+        coordinates: Option<DeliveryCoordinates>,
+        with_cost: bool,
+        option_id: Option<ShippingOptionId>,
+        order_time: Option<DateTime<Utc>>,
     ) -> ServiceFuture<Option<AvailablePackageForUser>> {
         let repo_factory = self.static_context.repo_factory.clone();
         let user_id = self.dynamic_context.user_id;
+        let clock = self.static_context.clock.clone();
+        let rounding_policies = self.static_context.config.price_rounding_policies();
 
-        self.spawn_on_pool(move |conn| {
+        self.spawn_on_read_pool(move |conn| {
             let products_repo = repo_factory.create_products_repo(&*conn, user_id);
             let company_package_repo = repo_factory.create_companies_packages_repo(&*conn, user_id);
-            let company_repo = repo_factory.create_companies_repo(&*conn, user_id);
+            let countries_repo = repo_factory.create_countries_repo(&*conn, user_id);
             let shipping_rates_repo = repo_factory.create_shipping_rates_repo(&*conn, user_id);
+            let surcharges_repo = repo_factory.create_surcharges_repo_with_sys_acl(&*conn);
+            let store_margins_repo = repo_factory.create_store_margins_repo_with_sys_acl(&*conn);
+            let pricing_rules_repo = repo_factory.create_pricing_rules_repo_with_sys_acl(&*conn);
+            let user_store_margins_repo = repo_factory.create_store_margins_repo(&*conn, user_id);
+            let country_regulations_repo = repo_factory.create_country_regulations_repo_with_sys_acl(&*conn);
+            let shipment_bookings_repo = repo_factory.create_shipment_bookings_repo_with_sys_acl(&*conn);
+            let company_suspensions_repo = repo_factory.create_company_suspensions_repo_with_sys_acl(&*conn);
 
             let run = || {
+                if let Some(ref coordinates) = coordinates {
+                    coordinates.validate().map_err(Error::Validate)?;
+                }
+
                 let pkg_for_user = products_repo.get_available_package_for_user_by_shipping_id(shipping_id, Some(delivery_to.clone()))?;
                 let pkg_for_user = match pkg_for_user {
                     None => {
                         return Ok(None);
                     }
-                    Some(pkg) => pkg,
+                    Some(pkg) => pkg.with_option_id(&delivery_from, &delivery_to),
                 };
-                with_price_from_rates(
-                    &*company_package_repo,
-                    &*company_repo,
+
+                // option chosen by the customer must be the one delivering the package on the route
+                if let Some(option_id) = option_id {
+                    if pkg_for_user.option_id.as_ref() != Some(&option_id) {
+                        return Err(Error::Validate(validation_errors!({
+                            "option_id": ["option_id" => format!("Shipping option {} does not match shipping id {} on the route", option_id, shipping_id)]
+                        }))
+                        .into());
+                    }
+                }
+
+                let measurements = ShipmentMeasurements {
+                    volume_cubic_cm: volume,
+                    weight_g: weight,
+                };
+                let violations = country_regulation_violations(
+                    &*country_regulations_repo,
+                    &*products_repo,
+                    pkg_for_user.base_product_id,
+                    delivery_to.clone(),
+                    Some(measurements),
+                )?;
+                if !violations.is_empty() {
+                    return Ok(None);
+                }
+                let base_product_id = pkg_for_user.base_product_id;
+                let packages = vec![pkg_for_user];
+                let company_packages = load_company_packages(&*company_package_repo, &packages)?;
+                // package is available on the same terms as in the list of the available packages of the product
+                let (packages, _) = filter_by_suspensions(&*company_suspensions_repo, &company_packages, clock.today(), packages)?;
+                let (packages, _) = filter_by_hazard_classes(&*products_repo, &company_packages, base_product_id, packages)?;
+                let pkg_for_user = price_packages(
+                    &company_packages,
+                    &*countries_repo,
                     &*shipping_rates_repo,
+                    &*surcharges_repo,
+                    &*store_margins_repo,
+                    &*pricing_rules_repo,
                     delivery_from,
                     delivery_to,
                     volume,
                     weight,
-                    pkg_for_user,
-                )
+                    coordinates,
+                    clock.today(),
+                    &rounding_policies,
+                    packages,
+                )?
+                .pop();
+                let order_time = order_time.unwrap_or_else(|| clock.now());
+                let pkg_for_user = match pkg_for_user {
+                    Some(pkg) => schedule_shipments(&*shipment_bookings_repo, &company_packages, order_time, vec![pkg])?.0.pop(),
+                    None => None,
+                };
+
+                match pkg_for_user {
+                    Some(pkg) => with_cost_for_managers(&*user_store_margins_repo, with_cost, pkg).map(Some),
+                    None => Ok(None),
+                }
             };
 
             run().map_err(|e: FailureError| {
@@ -378,72 +683,894 @@ impl<
     ) -> ServiceFuture<Products> {
         let repo_factory = self.static_context.repo_factory.clone();
         let user_id = self.dynamic_context.user_id;
+        let events_enabled = self.events_enabled();
+        let service = self.clone();
 
-        self.spawn_on_pool(move |conn| {
+        let product = self.spawn_transaction_on_pool(move |conn| {
             let products_repo = repo_factory.create_products_repo(&*conn, user_id);
-            products_repo
-                .update(base_product_id_arg, company_package_id, payload)
-                .map_err(|e| e.context("Service Products, update endpoint error occured.").into())
-        })
+            let event_outbox_repo = repo_factory.create_event_outbox_repo_with_sys_acl(&*conn);
+            let run = || {
+                let product = products_repo.update(base_product_id_arg, company_package_id, payload)?;
+                let event = DeliveryEvent::ProductShippingUpdated {
+                    base_product_id: product.base_product_id,
+                    company_package_ids: vec![product.company_package_id],
+                };
+                let outbox_events = events::store(&*event_outbox_repo, events_enabled, vec![event])?;
+                Ok((product, outbox_events))
+            };
+
+            run().map_err(|e: FailureError| e.context("Service Products, update endpoint error occured.").into())
+        });
+
+        Box::new(product.and_then(move |(product, outbox_events)| service.publish_events(outbox_events).map(move |_| product)))
     }
 
     fn delete_products(&self, base_product_id_arg: BaseProductId) -> ServiceFuture<()> {
         let repo_factory = self.static_context.repo_factory.clone();
         let user_id = self.dynamic_context.user_id;
 
-        self.spawn_on_pool(move |conn| {
-            conn.transaction::<(), _, _>(|| {
+        Box::new(
+            self.spawn_transaction_on_pool(move |conn| {
                 let products_repo = repo_factory.create_products_repo(&*conn, user_id);
                 let pickups_repo = repo_factory.create_pickups_repo(&*conn, user_id);
                 products_repo
                     .delete(base_product_id_arg)
                     .and_then(|_| pickups_repo.delete(base_product_id_arg).and_then(|_| Ok(())))
             })
-            .map_err(|e| e.context("Service Products, delete endpoint error occured.").into())
-        })
+            .map_err(|e| e.context("Service Products, delete endpoint error occured.").into()),
+        )
     }
+
+    fn clone_shipping(&self, base_product_id: BaseProductId, targets: Vec<BaseProductId>) -> ServiceFuture<Vec<Shipping>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+        let events_enabled = self.events_enabled();
+        let service = self.clone();
+
+        let cloned = self
+            .spawn_transaction_on_pool(move |conn| {
+                let mut targets = targets;
+                targets.sort_by_key(|target| target.0);
+                targets.dedup();
+                if targets.is_empty() || targets.contains(&base_product_id) {
+                    return Err(Error::Validate(validation_errors!({
+                        "base_product_ids": ["base_product_ids" => "Targets must not be empty nor contain the source base product"]
+                    }))
+                    .into());
+                }
+
+                let products_repo = repo_factory.create_products_repo(&*conn, user_id);
+                let pickups_repo = repo_factory.create_pickups_repo(&*conn, user_id);
+                let countries_repo = repo_factory.create_countries_repo(&*conn, user_id);
+                let customs_info_repo = repo_factory.create_customs_info_repo(&*conn, user_id);
+                let event_outbox_repo = repo_factory.create_event_outbox_repo_with_sys_acl(&*conn);
+
+                let products = products_repo.get_by_base_product_id(base_product_id)?;
+                let pickup = pickups_repo.get(base_product_id)?;
+                if products.is_empty() && pickup.is_none() {
+                    return Err(format_err!("Shipping of base product {} not found", base_product_id)
+                        .context(Error::NotFound)
+                        .into());
+                }
+                let countries = countries_repo.get_all()?;
+
+                let mut cloned = vec![];
+                for target in targets {
+                    products_repo.delete(target)?;
+                    let items = if products.is_empty() {
+                        vec![]
+                    } else {
+                        products_repo.create_many(products.iter().map(|product| product.clone_to(target)).collect())?
+                    };
+                    let items = items
+                        .into_iter()
+                        .map(|product| {
+                            let deliveries_to = create_tree_used_countries(&countries, &product.deliveries_to);
+                            ShippingProducts { product, deliveries_to }
+                        })
+                        .collect();
+
+                    pickups_repo.delete(target)?;
+                    let pickup = match pickup {
+                        Some(ref pickup) => Some(pickups_repo.create(pickup.clone_to(target))?),
+                        None => None,
+                    };
+
+                    // customs info describes the goods of the base product, so the target keeps its own
+                    let customs_info = customs_info_repo.get(target)?;
+
+                    cloned.push((
+                        target,
+                        Shipping {
+                            items,
+                            pickup,
+                            customs_info,
+                        },
+                    ));
+                }
+
+                let events = cloned
+                    .iter()
+                    .map(|(target, shipping)| DeliveryEvent::ProductShippingUpdated {
+                        base_product_id: *target,
+                        company_package_ids: shipping.items.iter().map(|item| item.product.company_package_id).collect(),
+                    })
+                    .collect();
+                let outbox_events = events::store(&*event_outbox_repo, events_enabled, events)?;
+
+                Ok((cloned.into_iter().map(|(_, shipping)| shipping).collect::<Vec<_>>(), outbox_events))
+            })
+            .map_err(|e: FailureError| e.context("Service Products, clone_shipping endpoint error occured.").into());
+
+        Box::new(cloned.and_then(move |(cloned, outbox_events)| service.publish_events(outbox_events).map(move |_| cloned)))
+    }
+}
+
+/// Company packages of the available packages with their companies by id
+type CompanyPackagesById = HashMap<CompanyPackageId, (CompanyPackage, Company)>;
+
+/// Loads company packages of all the available packages with a single query instead of one per package
+/// Validates the shipping of the product before it is stored: the company packages must accept the hazard classes
+/// of the product, deliver from and to its countries and fit its measurements
+pub fn validate_new_products<'a>(
+    company_packages_repo: &'a dyn CompaniesPackagesRepo,
+    companies_repo: &'a dyn CompaniesRepo,
+    packages_repo: &'a dyn PackagesRepo,
+    items: &[NewProducts],
+) -> Result<(), FailureError> {
+    let mut package_limits = vec![];
+
+    for new_product in items {
+        let company_package = company_packages_repo
+            .get(new_product.company_package_id)?
+            .ok_or(Error::Validate(validation_errors!({
+                "company_package_id": ["company_package_id" => format!("Company package with id: {} not found", new_product.company_package_id)]
+            })))?;
+
+        let refused_hazard_classes = company_package.refused_hazard_classes(&new_product.hazard_classes);
+        if !refused_hazard_classes.is_empty() {
+            return Err(Error::Validate(validation_errors!({
+                "hazard_classes": ["hazard_classes" => format!("Company package with id: {} does not accept hazard classes: {:?}", new_product.company_package_id, refused_hazard_classes)]
+            }))
+            .into());
+        }
+
+        let company = companies_repo
+            .find(company_package.company_id)?
+            .ok_or(format_err!("Company with id = {} not found", company_package.company_id))?;
+        let package = packages_repo
+            .find(company_package.package_id)?
+            .ok_or(format_err!("Package with id = {} not found", company_package.package_id))?;
+
+        if let Some(measurements) = new_product.measurements.clone() {
+            package_limits.push(PackageLimitsValidation {
+                company_package_id: new_product.company_package_id,
+                measurements,
+                package: package.clone(),
+            });
+        }
+
+        NewProductValidation {
+            product: new_product.clone(),
+            shipping: ShippingValidation {
+                delivery_from: new_product.delivery_from.clone(),
+                deliveries_to: new_product.deliveries_to.clone(),
+                company,
+                package,
+            },
+        }
+        .validate()
+        .map_err(|e| FailureError::from(Error::Validate(e)))?;
+    }
+
+    validate_packages_limits(&package_limits).map_err(|e| FailureError::from(Error::Unprocessable(e)))?;
+
+    Ok(())
+}
+
+fn load_company_packages<'a>(
+    company_package_repo: &'a dyn CompaniesPackagesRepo,
+    packages: &[AvailablePackageForUser],
+) -> Result<CompanyPackagesById, FailureError> {
+    let mut ids = vec![];
+    for pkg in packages {
+        if !ids.contains(&pkg.id) {
+            ids.push(pkg.id);
+        }
+    }
+
+    Ok(company_package_repo
+        .get_many_with_companies(ids)?
+        .into_iter()
+        .map(|(company_package, company)| (company_package.id, (company_package, company)))
+        .collect())
+}
+
+fn company_package_of(company_packages: &CompanyPackagesById, id: CompanyPackageId) -> Result<&(CompanyPackage, Company), FailureError> {
+    company_packages
+        .get(&id)
+        .ok_or_else(|| format_err!("Company package with id {} not found", id))
 }
 
-fn with_price_from_rates<'a>(
-    company_package_repo: &'a CompaniesPackagesRepo,
-    company_repo: &'a CompaniesRepo,
-    shipping_rates_repo: &'a ShippingRatesRepo,
+/// Sets prices of the packages delivering on the route, the ones without a price are skipped.
+/// Packages shipped from a warehouse of the seller are priced from it instead of `delivery_from`.
+/// Stored rates are looked up in the rate resolution order of the company packages like the delivery price.
+#[allow(clippy::too_many_arguments)]
+fn price_packages<'a>(
+    company_packages: &CompanyPackagesById,
+    countries_repo: &'a dyn CountriesRepo,
+    shipping_rates_repo: &'a dyn ShippingRatesRepo,
+    surcharges_repo: &'a dyn SurchargesRepo,
+    store_margins_repo: &'a dyn StoreMarginsRepo,
+    pricing_rules_repo: &'a dyn PricingRulesRepo,
     delivery_from: Alpha3,
     delivery_to: Alpha3,
     volume: u32,
     weight: u32,
-    mut pkg_for_user: AvailablePackageForUser,
-) -> Result<Option<AvailablePackageForUser>, FailureError> {
+    coordinates: Option<DeliveryCoordinates>,
+    today: NaiveDate,
+    rounding_policies: &[PriceRoundingPolicy],
+    packages: Vec<AvailablePackageForUser>,
+) -> Result<Vec<AvailablePackageForUser>, FailureError> {
+    let mut priced = price_packages_to_countries(
+        company_packages,
+        countries_repo,
+        shipping_rates_repo,
+        surcharges_repo,
+        store_margins_repo,
+        pricing_rules_repo,
+        delivery_from,
+        volume,
+        weight,
+        coordinates,
+        today,
+        rounding_policies,
+        vec![(delivery_to, packages)],
+    )?;
+
+    Ok(priced.pop().map(|(_, packages)| packages).unwrap_or_default())
+}
+
+/// Prices the packages of each destination country like `price_packages`.
+/// Rates of all the packages and destinations, surcharges and store margins are loaded at once,
+/// so the number of queries depends neither on the number of packages nor on the number of countries
+#[allow(clippy::too_many_arguments)]
+fn price_packages_to_countries<'a>(
+    company_packages: &CompanyPackagesById,
+    countries_repo: &'a dyn CountriesRepo,
+    shipping_rates_repo: &'a dyn ShippingRatesRepo,
+    surcharges_repo: &'a dyn SurchargesRepo,
+    store_margins_repo: &'a dyn StoreMarginsRepo,
+    pricing_rules_repo: &'a dyn PricingRulesRepo,
+    delivery_from: Alpha3,
+    volume: u32,
+    weight: u32,
+    coordinates: Option<DeliveryCoordinates>,
+    today: NaiveDate,
+    rounding_policies: &[PriceRoundingPolicy],
+    packages_by_country: Vec<(Alpha3, Vec<AvailablePackageForUser>)>,
+) -> Result<Vec<(Alpha3, Vec<AvailablePackageForUser>)>, FailureError> {
     // if price was set by seller in product currency we do not need to do anything
-    if pkg_for_user.price.is_some() {
-        return Ok(Some(pkg_for_user));
+    if packages_by_country
+        .iter()
+        .all(|&(_, ref packages)| packages.iter().all(|pkg| pkg.price.is_some()))
+    {
+        return Ok(packages_by_country);
     }
 
-    let company_package_id = pkg_for_user.id;
-    let company_package = company_package_repo
-        .get(company_package_id)?
-        .ok_or(format_err!("Company package with id {} not found", company_package_id))?;
+    let countries = if company_packages
+        .values()
+        .any(|&(ref company_package, _)| uses_countries_tree(&company_package.rate_resolution_steps()))
+    {
+        Some(countries_repo.get_all()?)
+    } else {
+        None
+    };
 
-    let company = company_repo
-        .find(company_package.company_id)?
-        .ok_or(format_err!("Company with id {} not found", company_package.company_id))?;
+    let mut routes = vec![];
+    let mut store_ids: Vec<StoreId> = vec![];
+    for &(ref delivery_to, ref packages) in &packages_by_country {
+        for pkg in packages.iter().filter(|pkg| pkg.price.is_none()) {
+            let (ref company_package, _) = *company_package_of(company_packages, pkg.id)?;
+            match company_package.shipping_rate_source {
+                ShippingRateSource::Static { .. } | ShippingRateSource::OnDemand { .. } => {
+                    let route = stored_rates_route(&company_package.rate_resolution_steps(), countries.as_ref(), delivery_to.clone());
+                    for rates_to in route.country_destinations() {
+                        let route = (pkg.id, package_origin(pkg, &delivery_from), rates_to);
+                        if !routes.contains(&route) {
+                            routes.push(route);
+                        }
+                    }
+                }
+                ShippingRateSource::NotAvailable | ShippingRateSource::Distance(_) => {}
+            }
+            if !store_ids.contains(&pkg.store_id) {
+                store_ids.push(pkg.store_id);
+            }
+        }
+    }
+
+    let rates = shipping_rates_repo.get_rates_for_routes(routes)?;
+    let surcharges = surcharges_repo.list()?;
+    let mut margins = vec![];
+    let pricing_rules = pricing_rules_repo.find_by_stores(store_ids.clone())?;
+    for store_id in store_ids {
+        margins.push((store_id, store_margins_repo.get(store_id)?));
+    }
 
+    let mut priced_by_country = vec![];
+    for (delivery_to, packages) in packages_by_country {
+        let mut priced = vec![];
+        for pkg in packages {
+            if pkg.price.is_some() {
+                priced.push(pkg);
+                continue;
+            }
+
+            let (ref company_package, ref company) = *company_package_of(company_packages, pkg.id)?;
+            // rates are ordered by id descending, so the first ones are the latest
+            let origin = package_origin(&pkg, &delivery_from);
+            let route = stored_rates_route(&company_package.rate_resolution_steps(), countries.as_ref(), delivery_to.clone());
+            let route_destinations = route.country_destinations();
+            let route_rates = rates
+                .iter()
+                .filter(|rates| {
+                    rates.company_package_id == pkg.id && rates.from_alpha3 == origin && route_destinations.contains(&rates.to_alpha3)
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+            let margin = margins
+                .iter()
+                .find(|&&(store_id, _)| store_id == pkg.store_id)
+                .and_then(|&(_, ref margin)| margin.as_ref());
+
+            let pkg = with_price_from_rates(
+                company_package,
+                company,
+                &route,
+                &route_rates,
+                &surcharges,
+                &pricing_rules,
+                margin,
+                &delivery_to,
+                volume,
+                weight,
+                coordinates,
+                today,
+                rounding_policies,
+                pkg,
+            );
+            if let Some(pkg) = pkg {
+                priced.push(pkg);
+            }
+        }
+        priced_by_country.push((delivery_to, priced));
+    }
+
+    Ok(priced_by_country)
+}
+
+fn package_origin(pkg: &AvailablePackageForUser, delivery_from: &Alpha3) -> Alpha3 {
+    pkg.delivery_from.clone().unwrap_or_else(|| delivery_from.clone())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn with_price_from_rates(
+    company_package: &CompanyPackage,
+    company: &Company,
+    route: &RateRoute,
+    rates: &[ShippingRates],
+    surcharges: &[Surcharge],
+    pricing_rules: &[PricingRule],
+    margin: Option<&StoreMargin>,
+    delivery_to: &Alpha3,
+    volume: u32,
+    weight: u32,
+    coordinates: Option<DeliveryCoordinates>,
+    today: NaiveDate,
+    rounding_policies: &[PriceRoundingPolicy],
+    mut pkg_for_user: AvailablePackageForUser,
+) -> Option<AvailablePackageForUser> {
     let price = match company_package.shipping_rate_source {
         ShippingRateSource::NotAvailable => None,
-        ShippingRateSource::Static { dimensional_factor } => shipping_rates_repo
-            .get_rates(company_package_id, delivery_from, delivery_to)?
-            .and_then(|rates| {
-                let measurements = ShipmentMeasurements {
-                    volume_cubic_cm: volume,
-                    weight_g: weight,
-                };
-                rates.calculate_delivery_price(measurements, dimensional_factor).map(ProductPrice)
-            }),
+        ShippingRateSource::Static { dimensional_factor } | ShippingRateSource::OnDemand { dimensional_factor } => {
+            let measurements = ShipmentMeasurements {
+                volume_cubic_cm: volume,
+                weight_g: weight,
+            };
+            let (resolved, _) = resolve_stored_rates(&company_package.rate_resolution_steps(), route, rates, |rates| {
+                rates
+                    .calculate_delivery_price_on(measurements, dimensional_factor, today)
+                    .map(|price| (price, rates.delivery_time_min_days, rates.delivery_time_max_days))
+            });
+
+            resolved.map(|(_, (price, delivery_time_min_days, delivery_time_max_days))| {
+                // delivery time of the particular route is more precise than the default one of the company package
+                if let (Some(min_days), Some(max_days)) = (delivery_time_min_days, delivery_time_max_days) {
+                    pkg_for_user.delivery_time_min_days = Some(min_days);
+                    pkg_for_user.delivery_time_max_days = Some(max_days);
+                }
+                ProductPrice(price)
+            })
+        }
+        ShippingRateSource::Distance(ref distance_pricing) => coordinates
+            .and_then(|coordinates| distance_pricing.calculate_price(&coordinates))
+            .map(ProductPrice),
+    };
+
+    let ProductPrice(price) = price?;
+    // markups and discounts of the store change the base price the surcharges are calculated from
+    let price = apply_pricing_rules(
+        pricing_rules
+            .iter()
+            .filter(|rule| rule.applies_to(pkg_for_user.store_id, company_package.id, delivery_to)),
+        price,
+    );
+    let surcharges_total: f64 = surcharges
+        .iter()
+        .filter(|surcharge| surcharge.applies_to(company.id, company_package.id, delivery_to))
+        .map(|surcharge| surcharge.calculate_amount(price))
+        .sum();
+    let cost = price + surcharges_total;
+
+    // buyers pay the carrier cost with the margin of the store
+    let price = match margin {
+        Some(margin) => margin.apply(cost),
+        None => cost,
+    };
+    // rounding is the last step, the price buyers see at the checkout
+    let price = match find_rounding_policy(rounding_policies, company.currency) {
+        Some(policy) => policy.round(price),
+        None => price,
+    };
+
+    pkg_for_user.price = Some(ProductPrice(price));
+    pkg_for_user.cost = Some(ProductPrice(cost));
+    pkg_for_user.currency = company.currency; // setting currency from company currency
+    Some(pkg_for_user)
+}
+
+/// Carrier cost of the delivery is returned only on request of the store managers, buyers see the price only
+fn with_cost_for_managers<'a>(
+    store_margins_repo: &'a dyn StoreMarginsRepo,
+    with_cost: bool,
+    mut pkg_for_user: AvailablePackageForUser,
+) -> Result<AvailablePackageForUser, FailureError> {
+    if with_cost {
+        // margin of the store is readable by its managers only, so this is denied for the buyers
+        store_margins_repo.get(pkg_for_user.store_id)?;
+    } else {
+        pkg_for_user.cost = None;
+    }
+
+    Ok(pkg_for_user)
+}
+
+/// Finds the region by its code with or without the `X` prefix of the regions, e.g. `XEU` or `EU`
+fn find_region(root: &Country, code: &str) -> Result<Country, FailureError> {
+    let code = code.trim().to_uppercase();
+    let prefixed_code = format!("X{}", code);
+
+    get_countries_by(root, |country| {
+        country.level < Country::COUNTRY_LEVEL && (country.alpha3.0 == code || country.alpha3.0 == prefixed_code)
+    })
+    .into_iter()
+    .next()
+    .ok_or_else(|| {
+        Error::Validate(validation_errors!({
+            "to_region": ["to_region" => format!("Region {} is not found", code)]
+        }))
+        .into()
+    })
+}
+
+/// Splits packages into the ones taking parcels on the date and the ones of suspended companies or company packages
+/// Packages of the base product available to `user_country` on the date, the packages filtered out are returned with reasons
+pub fn find_available_packages<'a>(
+    products_repo: &'a dyn ProductsRepo,
+    company_package_repo: &'a dyn CompaniesPackagesRepo,
+    country_regulations_repo: &'a dyn CountryRegulationsRepo,
+    company_suspensions_repo: &'a dyn CompanySuspensionsRepo,
+    base_product_id: BaseProductId,
+    user_country: Alpha3,
+    today: NaiveDate,
+) -> Result<(Vec<AvailablePackageForUser>, Vec<UnavailablePackageForUser>), FailureError> {
+    let packages = products_repo.find_available_to(base_product_id, user_country.clone())?;
+    let company_packages = load_company_packages(company_package_repo, &packages)?;
+    let (packages, mut unavailable_packages) = filter_by_suspensions(company_suspensions_repo, &company_packages, today, packages)?;
+    let (packages, refusing_packages) = filter_by_hazard_classes(products_repo, &company_packages, base_product_id, packages)?;
+    unavailable_packages.extend(refusing_packages);
+    let (packages, prohibited_packages) = filter_by_country_regulations(
+        country_regulations_repo,
+        products_repo,
+        base_product_id,
+        user_country,
+        None,
+        packages,
+    )?;
+    unavailable_packages.extend(prohibited_packages);
+
+    Ok((packages, unavailable_packages))
+}
+
+fn filter_by_suspensions<'a>(
+    company_suspensions_repo: &'a dyn CompanySuspensionsRepo,
+    company_packages: &CompanyPackagesById,
+    date: NaiveDate,
+    packages: Vec<AvailablePackageForUser>,
+) -> Result<(Vec<AvailablePackageForUser>, Vec<UnavailablePackageForUser>), FailureError> {
+    let suspensions = company_suspensions_repo.find_active_on(date)?;
+    if suspensions.is_empty() {
+        return Ok((packages, vec![]));
+    }
+
+    let mut available = vec![];
+    let mut unavailable = vec![];
+    for pkg in packages {
+        let (ref company_package, _) = *company_package_of(company_packages, pkg.id)?;
+
+        let reasons = suspensions
+            .iter()
+            .filter(|suspension| suspension.applies_to(company_package.company_id, pkg.id))
+            .map(|suspension| UnavailabilityReason::Suspended {
+                reason: suspension.reason.clone(),
+                ends_on: suspension.ends_on,
+            })
+            .collect::<Vec<_>>();
+
+        if reasons.is_empty() {
+            available.push(pkg);
+        } else {
+            unavailable.push(UnavailablePackageForUser {
+                id: pkg.id,
+                shipping_id: pkg.shipping_id,
+                name: pkg.name,
+                reasons,
+            });
+        }
+    }
+
+    Ok((available, unavailable))
+}
+
+/// Splits packages into the ones accepting hazard classes of the product and the ones refusing them
+fn filter_by_hazard_classes<'a>(
+    products_repo: &'a dyn ProductsRepo,
+    company_packages: &CompanyPackagesById,
+    base_product_id: BaseProductId,
+    packages: Vec<AvailablePackageForUser>,
+) -> Result<(Vec<AvailablePackageForUser>, Vec<UnavailablePackageForUser>), FailureError> {
+    let hazard_classes = product_hazard_classes(products_repo, base_product_id)?;
+    if hazard_classes.is_empty() {
+        return Ok((packages, vec![]));
+    }
+
+    let mut available = vec![];
+    let mut unavailable = vec![];
+    for pkg in packages {
+        let (ref company_package, _) = *company_package_of(company_packages, pkg.id)?;
+
+        let refused_hazard_classes = company_package.refused_hazard_classes(&hazard_classes);
+        if refused_hazard_classes.is_empty() {
+            available.push(pkg);
+        } else {
+            unavailable.push(UnavailablePackageForUser {
+                id: pkg.id,
+                shipping_id: pkg.shipping_id,
+                name: pkg.name,
+                reasons: refused_hazard_classes
+                    .into_iter()
+                    .map(|hazard_class| UnavailabilityReason::HazardClassRestricted { hazard_class })
+                    .collect(),
+            });
+        }
+    }
+
+    Ok((available, unavailable))
+}
+
+/// Splits packages into the ones supporting the cash on delivery and the ones not supporting it
+fn filter_by_cash_on_delivery(
+    company_packages: &CompanyPackagesById,
+    packages: Vec<AvailablePackageForUser>,
+) -> Result<(Vec<AvailablePackageForUser>, Vec<UnavailablePackageForUser>), FailureError> {
+    let mut available = vec![];
+    let mut unavailable = vec![];
+    for pkg in packages {
+        let (ref company_package, _) = *company_package_of(company_packages, pkg.id)?;
+
+        if company_package.cod_supported {
+            available.push(pkg);
+        } else {
+            unavailable.push(UnavailablePackageForUser {
+                id: pkg.id,
+                shipping_id: pkg.shipping_id,
+                name: pkg.name,
+                reasons: vec![UnavailabilityReason::CashOnDeliveryNotSupported],
+            });
+        }
+    }
+
+    Ok((available, unavailable))
+}
+
+/// Sets the shipping dates of the packages by the cut-off times of the company packages,
+/// packages booked up on their shipping date are unavailable
+fn schedule_shipments(
+    shipment_bookings_repo: &dyn ShipmentBookingsRepo,
+    company_packages: &CompanyPackagesById,
+    order_time: DateTime<Utc>,
+    packages: Vec<AvailablePackageForUser>,
+) -> Result<(Vec<AvailablePackageForUser>, Vec<UnavailablePackageForUser>), FailureError> {
+    let mut scheduled = vec![];
+    let mut keys = vec![];
+    for pkg in packages {
+        let (ref company_package, _) = *company_package_of(company_packages, pkg.id)?;
+        let shipping_date = company_package.shipping_date(order_time);
+        if company_package.max_daily_shipments.is_some() && !keys.contains(&(pkg.id, shipping_date)) {
+            keys.push((pkg.id, shipping_date));
+        }
+        scheduled.push((pkg, shipping_date));
+    }
+
+    let bookings = shipment_bookings_repo.find_many(keys)?;
+
+    let mut available = vec![];
+    let mut unavailable = vec![];
+    for (pkg, shipping_date) in scheduled {
+        let (ref company_package, _) = *company_package_of(company_packages, pkg.id)?;
+        let shipments_count = bookings
+            .iter()
+            .find(|booking| booking.company_package_id == pkg.id && booking.shipping_date == shipping_date)
+            .map_or(0, |booking| booking.shipments_count);
+
+        if company_package.is_booked_up(shipments_count) {
+            unavailable.push(UnavailablePackageForUser {
+                id: pkg.id,
+                shipping_id: pkg.shipping_id,
+                name: pkg.name,
+                reasons: vec![UnavailabilityReason::BookedUp { shipping_date }],
+            });
+        } else {
+            available.push(pkg.with_shipping_date(shipping_date));
+        }
+    }
+
+    Ok((available, unavailable))
+}
+
+/// Hazard classes of all products with the base product id, without duplicates
+fn product_hazard_classes(products_repo: &dyn ProductsRepo, base_product_id: BaseProductId) -> Result<Vec<HazardClass>, FailureError> {
+    let mut hazard_classes: Vec<HazardClass> = vec![];
+    for product in products_repo.get_by_base_product_id(base_product_id)? {
+        for hazard_class in product.hazard_classes {
+            if !hazard_classes.contains(&hazard_class) {
+                hazard_classes.push(hazard_class);
+            }
+        }
+    }
+
+    Ok(hazard_classes)
+}
+
+/// Reasons the destination country does not accept the parcel with the product, whatever the carrier limits are
+fn country_regulation_violations<'a>(
+    country_regulations_repo: &'a dyn CountryRegulationsRepo,
+    products_repo: &'a dyn ProductsRepo,
+    base_product_id: BaseProductId,
+    delivery_to: Alpha3,
+    measurements: Option<ShipmentMeasurements>,
+) -> Result<Vec<UnavailabilityReason>, FailureError> {
+    match country_regulations_repo.get(delivery_to)? {
+        None => Ok(vec![]),
+        Some(regulation) => {
+            let hazard_classes = product_hazard_classes(products_repo, base_product_id)?;
+            Ok(regulation.violations(&hazard_classes, measurements))
+        }
+    }
+}
+
+/// Final filter of the availability: if the destination country does not accept the parcel,
+/// none of the packages is available whatever the carriers accept
+fn filter_by_country_regulations<'a>(
+    country_regulations_repo: &'a dyn CountryRegulationsRepo,
+    products_repo: &'a dyn ProductsRepo,
+    base_product_id: BaseProductId,
+    delivery_to: Alpha3,
+    measurements: Option<ShipmentMeasurements>,
+    packages: Vec<AvailablePackageForUser>,
+) -> Result<(Vec<AvailablePackageForUser>, Vec<UnavailablePackageForUser>), FailureError> {
+    if packages.is_empty() {
+        return Ok((packages, vec![]));
+    }
+
+    let reasons = country_regulation_violations(country_regulations_repo, products_repo, base_product_id, delivery_to, measurements)?;
+    if reasons.is_empty() {
+        return Ok((packages, vec![]));
+    }
+
+    let unavailable = packages
+        .into_iter()
+        .map(|pkg| UnavailablePackageForUser {
+            id: pkg.id,
+            shipping_id: pkg.shipping_id,
+            name: pkg.name,
+            reasons: reasons.clone(),
+        })
+        .collect();
+
+    Ok((vec![], unavailable))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, TimeZone, Utc};
+
+    use stq_types::*;
+
+    use models::AvailablePackageForUser;
+    use repos::pickups::PickupsRepo;
+    use repos::products::ProductsRepo;
+    use repos::repo_factory::tests::*;
+
+    use super::{
+        filter_by_country_regulations, filter_by_hazard_classes, filter_by_suspensions, load_company_packages, price_packages,
+        price_packages_to_countries, schedule_shipments,
     };
 
-    Ok(price.map(|price| {
-        pkg_for_user.price = Some(price);
-        pkg_for_user.currency = company.currency; // setting currency from company currency
-        pkg_for_user
-    }))
+    /// Copies of the package with `count` different ids
+    fn with_count(packages: Vec<AvailablePackageForUser>, count: i32) -> Vec<AvailablePackageForUser> {
+        let pkg = packages[0].clone();
+
+        (1..=count)
+            .map(|id| AvailablePackageForUser {
+                id: CompanyPackageId(id),
+                ..pkg.clone()
+            })
+            .collect()
+    }
+
+    /// Finds the packages of the base product available to the user like `find_available_shipping_for_user_v2` does,
+    /// the products repo finds `count` packages. Returns them with the number of queries it took
+    fn find_available_packages(count: i32) -> (Vec<AvailablePackageForUser>, usize) {
+        let user_id = Some(MOCK_USER_ID);
+        let products_repo = ProductsRepoMock { user_id };
+        let company_package_repo = CompaniesPackagesRepoMock { user_id };
+        let company_suspensions_repo = CompanySuspensionsRepoMock { user_id };
+        let countries_repo = CountriesRepoMock { user_id };
+        let shipping_rates_repo = ShippingRatesRepoMock { user_id };
+        let surcharges_repo = SurchargesRepoMock { user_id };
+        let store_margins_repo = StoreMarginsRepoMock { user_id };
+        let pricing_rules_repo = PricingRulesRepoMock { user_id };
+        let country_regulations_repo = CountryRegulationsRepoMock { user_id };
+        let shipment_bookings_repo = ShipmentBookingsRepoMock { user_id };
+        let pickups_repo = PickupsRepoMock { user_id };
+        let delivery_to = Alpha3("RUS".to_string());
+        let today = NaiveDate::from_ymd(2018, 10, 1);
+
+        let queries_before = mock_queries();
+        let packages = with_count(
+            products_repo.find_available_to(MOCK_BASE_PRODUCT_ID, delivery_to.clone()).unwrap(),
+            count,
+        );
+        let company_packages = load_company_packages(&company_package_repo, &packages).unwrap();
+        let (packages, _) = filter_by_suspensions(&company_suspensions_repo, &company_packages, today, packages).unwrap();
+        let (packages, _) = filter_by_hazard_classes(&products_repo, &company_packages, MOCK_BASE_PRODUCT_ID, packages).unwrap();
+        let packages = price_packages(
+            &company_packages,
+            &countries_repo,
+            &shipping_rates_repo,
+            &surcharges_repo,
+            &store_margins_repo,
+            &pricing_rules_repo,
+            Alpha3("USA".to_string()),
+            delivery_to.clone(),
+            0,
+            500,
+            None,
+            today,
+            &[],
+            packages,
+        )
+        .unwrap();
+        let (packages, _) = filter_by_country_regulations(
+            &country_regulations_repo,
+            &products_repo,
+            MOCK_BASE_PRODUCT_ID,
+            delivery_to,
+            None,
+            packages,
+        )
+        .unwrap();
+        let order_time = Utc.ymd(2018, 10, 1).and_hms(10, 0, 0);
+        let (packages, _) = schedule_shipments(&shipment_bookings_repo, &company_packages, order_time, packages).unwrap();
+        pickups_repo.get(MOCK_BASE_PRODUCT_ID).unwrap();
+
+        (packages, mock_queries() - queries_before)
+    }
+
+    #[test]
+    fn number_of_queries_does_not_depend_on_number_of_packages() {
+        // products, company packages, suspensions, hazard classes, rates, surcharges, pricing rules,
+        // margin of the store, country regulation, shipment bookings and pickups
+        let (packages, queries_for_one) = find_available_packages(1);
+        assert_eq!(packages.len(), 1);
+        assert_eq!(queries_for_one, 11);
+
+        let (packages, queries_for_many) = find_available_packages(20);
+        assert_eq!(packages.len(), 20);
+        assert_eq!(queries_for_many, 11);
+
+        // rate of 999 with the fuel surcharge of 5% and the store margin of 10%
+        let cost = 999.0 + 999.0 * 5.0 / 100.0;
+        for pkg in packages {
+            assert_eq!(pkg.cost.map(|cost| cost.0), Some(cost));
+            assert_eq!(pkg.price.map(|price| price.0), Some(cost + cost * 10.0 / 100.0));
+        }
+    }
+
+    /// Prices the packages delivering to each of the countries and returns them with the number of queries it took
+    fn price_packages_to(countries: &[&str]) -> (Vec<(Alpha3, Vec<AvailablePackageForUser>)>, usize) {
+        let user_id = Some(MOCK_USER_ID);
+        let products_repo = ProductsRepoMock { user_id };
+        let company_package_repo = CompaniesPackagesRepoMock { user_id };
+        let countries_repo = CountriesRepoMock { user_id };
+        let shipping_rates_repo = ShippingRatesRepoMock { user_id };
+        let surcharges_repo = SurchargesRepoMock { user_id };
+        let store_margins_repo = StoreMarginsRepoMock { user_id };
+        let pricing_rules_repo = PricingRulesRepoMock { user_id };
+        let countries = countries.iter().map(|country| Alpha3(country.to_string())).collect();
+
+        let queries_before = mock_queries();
+        let packages_by_country = products_repo
+            .find_available_to_countries(MOCK_BASE_PRODUCT_ID, countries)
+            .unwrap()
+            .into_iter()
+            .map(|(country, packages)| (country, with_count(packages, 2)))
+            .collect::<Vec<_>>();
+        let packages = packages_by_country
+            .iter()
+            .flat_map(|(_, packages)| packages.clone())
+            .collect::<Vec<_>>();
+        let company_packages = load_company_packages(&company_package_repo, &packages).unwrap();
+        let packages_by_country = price_packages_to_countries(
+            &company_packages,
+            &countries_repo,
+            &shipping_rates_repo,
+            &surcharges_repo,
+            &store_margins_repo,
+            &pricing_rules_repo,
+            Alpha3("USA".to_string()),
+            0,
+            500,
+            None,
+            NaiveDate::from_ymd(2018, 10, 1),
+            &[],
+            packages_by_country,
+        )
+        .unwrap();
+
+        (packages_by_country, mock_queries() - queries_before)
+    }
+
+    #[test]
+    fn number_of_queries_does_not_depend_on_number_of_countries() {
+        // products, company packages, rates, surcharges, pricing rules and margin of the store
+        let (packages_by_country, queries_for_one) = price_packages_to(&["RUS"]);
+        assert_eq!(packages_by_country.len(), 1);
+        assert_eq!(queries_for_one, 6);
+
+        let (packages_by_country, queries_for_many) = price_packages_to(&["RUS", "DEU", "FRA"]);
+        assert_eq!(queries_for_many, 6);
+
+        let countries = packages_by_country
+            .iter()
+            .map(|(country, _)| country.0.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(countries, vec!["RUS", "DEU", "FRA"]);
+        for (_, packages) in packages_by_country {
+            assert_eq!(packages.len(), 2);
+            assert!(packages.iter().all(|pkg| pkg.price.is_some()));
+        }
+    }
 }
@@ -0,0 +1,179 @@
+//! Shipping exports Services, export of the shipping configured by the store and its import to another store
+//! or environment
+use failure::Error as FailureError;
+use futures::Future;
+use r2d2::ManageConnection;
+
+use stq_types::StoreId;
+
+use errors::Error;
+use events::{self, DeliveryEvent};
+use models::{OutboxEvent, ShippingImportConflict, StoreShippingExport, StoreShippingImportReport};
+use repos::{DbConnection, ReposFactory};
+use services::products::validate_new_products;
+use services::types::{Service, ServiceFuture};
+
+pub trait ShippingExportsService {
+    /// Returns shipping of all base products of the store together with its shipping templates
+    fn export_store_shipping(&self, store_id: StoreId) -> ServiceFuture<StoreShippingExport>;
+
+    /// Sets up shipping and templates of the exported document in the store. Base products and templates
+    /// already set up in the store are handled by `on_conflict`
+    fn import_store_shipping(
+        &self,
+        store_id: StoreId,
+        payload: StoreShippingExport,
+        on_conflict: ShippingImportConflict,
+    ) -> ServiceFuture<StoreShippingImportReport>;
+}
+
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> ShippingExportsService for Service<T, M, F> {
+    /// Returns shipping of all base products of the store together with its shipping templates
+    fn export_store_shipping(&self, store_id: StoreId) -> ServiceFuture<StoreShippingExport> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_admin_pool(move |conn| {
+            let shipping_templates_repo = repo_factory.create_shipping_templates_repo(&*conn, user_id);
+            let products_repo = repo_factory.create_products_repo(&*conn, user_id);
+            let pickups_repo = repo_factory.create_pickups_repo(&*conn, user_id);
+
+            // templates are private to the store unlike the shipping of the products, so they are read first
+            shipping_templates_repo
+                .list(store_id)
+                .and_then(|templates| {
+                    let products = products_repo.get_by_store_id(store_id)?;
+                    let pickups = pickups_repo.get_by_store_id(store_id)?;
+                    Ok(StoreShippingExport::new(store_id, products, pickups, templates))
+                })
+                .map_err(|e: FailureError| {
+                    e.context("Service ShippingExports, export_store_shipping endpoint error occured.")
+                        .into()
+                })
+        })
+    }
+
+    /// Sets up shipping and templates of the exported document in the store. Base products and templates
+    /// already set up in the store are handled by `on_conflict`
+    fn import_store_shipping(
+        &self,
+        store_id: StoreId,
+        payload: StoreShippingExport,
+        on_conflict: ShippingImportConflict,
+    ) -> ServiceFuture<StoreShippingImportReport> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+        let events_enabled = self.events_enabled();
+        let service = self.clone();
+
+        let imported = self.spawn_transaction_on_admin_pool(move |conn| {
+            let shipping_templates_repo = repo_factory.create_shipping_templates_repo(&*conn, user_id);
+            let products_repo = repo_factory.create_products_repo(&*conn, user_id);
+            let pickups_repo = repo_factory.create_pickups_repo(&*conn, user_id);
+            let companies_packages_repo = repo_factory.create_companies_packages_repo(&*conn, user_id);
+            let companies_repo = repo_factory.create_companies_repo(&*conn, user_id);
+            let packages_repo = repo_factory.create_packages_repo(&*conn, user_id);
+            let event_outbox_repo = repo_factory.create_event_outbox_repo_with_sys_acl(&*conn);
+
+            let run = move || -> Result<(StoreShippingImportReport, Vec<OutboxEvent>), FailureError> {
+                let payload = payload.moved_to(store_id);
+                let store_templates = shipping_templates_repo.list(store_id)?;
+
+                // shipping is validated like the one set up by the store, so that the import does not bypass the checks
+                for product in &payload.products {
+                    validate_new_products(&*companies_packages_repo, &*companies_repo, &*packages_repo, &product.items)?;
+                }
+
+                let mut products = vec![];
+                for product in payload.products {
+                    let configured = !products_repo.get_by_base_product_id(product.base_product_id)?.is_empty()
+                        || pickups_repo.get(product.base_product_id)?.is_some();
+                    products.push((product, configured));
+                }
+                let templates = payload
+                    .templates
+                    .into_iter()
+                    .map(|template| {
+                        let store_template_id = store_templates
+                            .iter()
+                            .find(|store_template| store_template.name == template.name)
+                            .map(|store_template| store_template.id);
+                        (template, store_template_id)
+                    })
+                    .collect::<Vec<_>>();
+
+                if on_conflict == ShippingImportConflict::Fail {
+                    let conflicts = products
+                        .iter()
+                        .filter(|(_, configured)| *configured)
+                        .map(|(product, _)| format!("base product {}", product.base_product_id))
+                        .chain(
+                            templates
+                                .iter()
+                                .filter(|(_, store_template_id)| store_template_id.is_some())
+                                .map(|(template, _)| format!("template {}", template.name)),
+                        )
+                        .collect::<Vec<_>>();
+                    if !conflicts.is_empty() {
+                        return Err(Error::Conflict(validation_errors!({
+                            "on_conflict": ["on_conflict" => format!("Shipping is already set up in the store for: {}", conflicts.join(", "))]
+                        }))
+                        .into());
+                    }
+                }
+
+                let mut report = StoreShippingImportReport::default();
+                let mut updated = vec![];
+                for (product, configured) in products {
+                    let base_product_id = product.base_product_id;
+                    if configured {
+                        if on_conflict == ShippingImportConflict::Skip {
+                            report.skipped_products.push(base_product_id);
+                            continue;
+                        }
+                        products_repo.delete(base_product_id)?;
+                        pickups_repo.delete(base_product_id)?;
+                    }
+
+                    let company_package_ids = product.items.iter().map(|item| item.company_package_id).collect();
+                    if !product.items.is_empty() {
+                        products_repo.create_many(product.items)?;
+                    }
+                    if let Some(pickup) = product.pickup {
+                        pickups_repo.create(pickup)?;
+                    }
+                    report.imported_products.push(base_product_id);
+                    updated.push((base_product_id, company_package_ids));
+                }
+
+                for (template, store_template_id) in templates {
+                    if let Some(store_template_id) = store_template_id {
+                        if on_conflict == ShippingImportConflict::Skip {
+                            report.skipped_templates.push(template.name);
+                            continue;
+                        }
+                        shipping_templates_repo.delete(store_id, store_template_id)?;
+                    }
+
+                    report.imported_templates.push(template.name.clone());
+                    shipping_templates_repo.create(store_id, template)?;
+                }
+
+                let events = updated
+                    .into_iter()
+                    .map(|(base_product_id, company_package_ids)| DeliveryEvent::ProductShippingUpdated {
+                        base_product_id,
+                        company_package_ids,
+                    })
+                    .collect();
+                let outbox_events = events::store(&*event_outbox_repo, events_enabled, events)?;
+
+                Ok((report, outbox_events))
+            };
+
+            run().map_err(|e| e.context("Service ShippingExports, import_store_shipping endpoint error occured.").into())
+        });
+
+        Box::new(imported.and_then(move |(report, outbox_events)| service.publish_events(outbox_events).map(move |_| report)))
+    }
+}
@@ -0,0 +1,28 @@
+//! Shipping rates anomalies Services, presents the report of the nightly shipping rates integrity check
+
+use r2d2::ManageConnection;
+
+use super::types::{Service, ServiceFuture};
+use models::ShippingRatesAnomaly;
+use repos::{DbConnection, ReposFactory};
+
+pub trait ShippingRatesAnomaliesService {
+    /// Returns anomalies found by the last integrity check
+    fn list_shipping_rates_anomalies(&self) -> ServiceFuture<Vec<ShippingRatesAnomaly>>;
+}
+
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> ShippingRatesAnomaliesService for Service<T, M, F> {
+    /// Returns anomalies found by the last integrity check
+    fn list_shipping_rates_anomalies(&self) -> ServiceFuture<Vec<ShippingRatesAnomaly>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_admin_pool(move |conn| {
+            let anomalies_repo = repo_factory.create_shipping_rates_anomalies_repo(&*conn, user_id);
+            anomalies_repo.list().map_err(|e| {
+                e.context("Service ShippingRatesAnomalies, list_shipping_rates_anomalies endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+}
@@ -0,0 +1,104 @@
+//! Shipping resolver Services, resolves the shipping of the base product by the company package
+//! for the clients of the route without the shipping id. The pair identifies the shipping only if the base product
+//! is shipped by the company package once, otherwise the clients have to choose the shipping id themselves
+
+use failure::Error as FailureError;
+use r2d2::ManageConnection;
+
+use stq_types::{BaseProductId, CompanyPackageId};
+
+use super::types::{Service, ServiceFuture};
+use errors::Error;
+use models::AvailablePackageForUser;
+use repos::{DbConnection, ReposFactory};
+
+pub trait ShippingResolverService {
+    /// Returns the package available for user by the base product and the company package,
+    /// the request conflicts if the base product is shipped by the company package several times
+    fn resolve_available_package_for_user(
+        &self,
+        base_product_id: BaseProductId,
+        company_package_id: CompanyPackageId,
+    ) -> ServiceFuture<Option<AvailablePackageForUser>>;
+}
+
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> ShippingResolverService for Service<T, M, F> {
+    /// Returns the package available for user by the base product and the company package,
+    /// the request conflicts if the base product is shipped by the company package several times
+    fn resolve_available_package_for_user(
+        &self,
+        base_product_id: BaseProductId,
+        company_package_id: CompanyPackageId,
+    ) -> ServiceFuture<Option<AvailablePackageForUser>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_read_pool(move |conn| {
+            let products_repo = repo_factory.create_products_repo(&*conn, user_id);
+
+            products_repo
+                .find_available_packages_for_user(base_product_id, company_package_id)
+                .and_then(resolve_shipping)
+                .map_err(|e| {
+                    e.context("Service ShippingResolver, resolve_available_package_for_user endpoint error occurred.")
+                        .into()
+                })
+        })
+    }
+}
+
+/// The only candidate, candidates are listed in the conflict if there are several of them
+fn resolve_shipping(mut candidates: Vec<AvailablePackageForUser>) -> Result<Option<AvailablePackageForUser>, FailureError> {
+    if candidates.len() > 1 {
+        let shipping_ids = candidates
+            .iter()
+            .map(|candidate| candidate.shipping_id.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        return Err(Error::Conflict(validation_errors!({
+            "shipping_id": ["ambiguous" => format!(
+                "Base product is shipped by the company package several times, shipping ids: {}. \
+                 Use GET /v2/available_packages_for_user/by_shipping_id/:id",
+                shipping_ids
+            )]
+        }))
+        .into());
+    }
+
+    Ok(candidates.pop())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use tokio_core::reactor::Core;
+
+    use stq_types::*;
+
+    use repos::repo_factory::tests::*;
+    use services::shipping_resolver::ShippingResolverService;
+
+    #[test]
+    fn shipping_is_resolved_only_if_unambiguous() {
+        let mut core = Core::new().unwrap();
+        let handle = Arc::new(core.handle());
+        let service = create_service(Some(MOCK_USER_ID), handle);
+
+        let package = core
+            .run(service.resolve_available_package_for_user(MOCK_BASE_PRODUCT_ID, CompanyPackageId(1)))
+            .unwrap()
+            .unwrap();
+        assert_eq!(package.shipping_id, ShippingId(1));
+
+        assert_conflict(
+            core.run(service.resolve_available_package_for_user(MOCK_BASE_PRODUCT_ID, CompanyPackageId(2))),
+            "Shipping of the base product by the company package is ambiguous",
+        );
+
+        assert!(core
+            .run(service.resolve_available_package_for_user(MOCK_BASE_PRODUCT_ID, CompanyPackageId(3)))
+            .unwrap()
+            .is_none());
+    }
+}
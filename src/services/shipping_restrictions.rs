@@ -0,0 +1,114 @@
+//! Shipping restrictions Services, presents CRUD operations with restrictions of the carriers
+//! and checking the goods against them
+
+use r2d2::ManageConnection;
+
+use super::types::{Service, ServiceFuture};
+use models::{
+    CheckShippingRestrictions, NewShippingRestriction, ShippingRestriction, ShippingRestrictionsCheck, UpdateShippingRestriction,
+};
+use repos::{DbConnection, ReposFactory};
+
+pub trait ShippingRestrictionsService {
+    /// Returns list of restrictions
+    fn list_shipping_restrictions(&self) -> ServiceFuture<Vec<ShippingRestriction>>;
+    /// Returns restriction by id
+    fn get_shipping_restriction(&self, restriction_id: i32) -> ServiceFuture<Option<ShippingRestriction>>;
+    /// Create a new restriction
+    fn create_shipping_restriction(&self, payload: NewShippingRestriction) -> ServiceFuture<ShippingRestriction>;
+    /// Update a restriction
+    fn update_shipping_restriction(&self, restriction_id: i32, payload: UpdateShippingRestriction) -> ServiceFuture<ShippingRestriction>;
+    /// Delete a restriction
+    fn delete_shipping_restriction(&self, restriction_id: i32) -> ServiceFuture<ShippingRestriction>;
+    /// Returns carriers refusing the goods or accepting them only with special handling
+    fn check_shipping_restrictions(&self, payload: CheckShippingRestrictions) -> ServiceFuture<ShippingRestrictionsCheck>;
+}
+
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> ShippingRestrictionsService for Service<T, M, F> {
+    /// Returns list of restrictions
+    fn list_shipping_restrictions(&self) -> ServiceFuture<Vec<ShippingRestriction>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let shipping_restrictions_repo = repo_factory.create_shipping_restrictions_repo(&*conn, user_id);
+            shipping_restrictions_repo.list().map_err(|e| {
+                e.context("Service ShippingRestrictions, list_shipping_restrictions endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Returns restriction by id
+    fn get_shipping_restriction(&self, restriction_id: i32) -> ServiceFuture<Option<ShippingRestriction>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let shipping_restrictions_repo = repo_factory.create_shipping_restrictions_repo(&*conn, user_id);
+            shipping_restrictions_repo.find(restriction_id).map_err(|e| {
+                e.context("Service ShippingRestrictions, get_shipping_restriction endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Create a new restriction
+    fn create_shipping_restriction(&self, payload: NewShippingRestriction) -> ServiceFuture<ShippingRestriction> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let shipping_restrictions_repo = repo_factory.create_shipping_restrictions_repo(&*conn, user_id);
+            shipping_restrictions_repo.create(payload).map_err(|e| {
+                e.context("Service ShippingRestrictions, create_shipping_restriction endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Update a restriction
+    fn update_shipping_restriction(&self, restriction_id: i32, payload: UpdateShippingRestriction) -> ServiceFuture<ShippingRestriction> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let shipping_restrictions_repo = repo_factory.create_shipping_restrictions_repo(&*conn, user_id);
+            shipping_restrictions_repo.update(restriction_id, payload).map_err(|e| {
+                e.context("Service ShippingRestrictions, update_shipping_restriction endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Delete a restriction
+    fn delete_shipping_restriction(&self, restriction_id: i32) -> ServiceFuture<ShippingRestriction> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let shipping_restrictions_repo = repo_factory.create_shipping_restrictions_repo(&*conn, user_id);
+            shipping_restrictions_repo.delete(restriction_id).map_err(|e| {
+                e.context("Service ShippingRestrictions, delete_shipping_restriction endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Returns carriers refusing the goods or accepting them only with special handling
+    fn check_shipping_restrictions(&self, payload: CheckShippingRestrictions) -> ServiceFuture<ShippingRestrictionsCheck> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let shipping_restrictions_repo = repo_factory.create_shipping_restrictions_repo(&*conn, user_id);
+            shipping_restrictions_repo
+                .find_by_destination(payload.delivery_to.clone())
+                .map(|restrictions| ShippingRestrictionsCheck::new(restrictions, &payload))
+                .map_err(|e| {
+                    e.context("Service ShippingRestrictions, check_shipping_restrictions endpoint error occured.")
+                        .into()
+                })
+        })
+    }
+}
@@ -0,0 +1,104 @@
+//! Shipping templates Services, presents CRUD operations with shipping setups saved by the stores
+
+use r2d2::ManageConnection;
+
+use stq_types::StoreId;
+
+use super::types::{Service, ServiceFuture};
+use models::{NewShippingTemplate, ShippingTemplate, UpdateShippingTemplate};
+use repos::{DbConnection, ReposFactory};
+
+pub trait ShippingTemplatesService {
+    /// Returns shipping templates of the store
+    fn list_shipping_templates(&self, store_id: StoreId) -> ServiceFuture<Vec<ShippingTemplate>>;
+    /// Returns shipping template of the store by id
+    fn get_shipping_template(&self, store_id: StoreId, template_id: i32) -> ServiceFuture<Option<ShippingTemplate>>;
+    /// Creates a new shipping template of the store
+    fn create_shipping_template(&self, store_id: StoreId, payload: NewShippingTemplate) -> ServiceFuture<ShippingTemplate>;
+    /// Updates shipping template of the store, shipping of the products it was applied to stays as is
+    fn update_shipping_template(
+        &self,
+        store_id: StoreId,
+        template_id: i32,
+        payload: UpdateShippingTemplate,
+    ) -> ServiceFuture<ShippingTemplate>;
+    /// Deletes shipping template of the store
+    fn delete_shipping_template(&self, store_id: StoreId, template_id: i32) -> ServiceFuture<Option<ShippingTemplate>>;
+}
+
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> ShippingTemplatesService for Service<T, M, F> {
+    /// Returns shipping templates of the store
+    fn list_shipping_templates(&self, store_id: StoreId) -> ServiceFuture<Vec<ShippingTemplate>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let shipping_templates_repo = repo_factory.create_shipping_templates_repo(&*conn, user_id);
+            shipping_templates_repo.list(store_id).map_err(|e| {
+                e.context("Service ShippingTemplates, list_shipping_templates endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Returns shipping template of the store by id
+    fn get_shipping_template(&self, store_id: StoreId, template_id: i32) -> ServiceFuture<Option<ShippingTemplate>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let shipping_templates_repo = repo_factory.create_shipping_templates_repo(&*conn, user_id);
+            shipping_templates_repo.find(store_id, template_id).map_err(|e| {
+                e.context("Service ShippingTemplates, get_shipping_template endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Creates a new shipping template of the store
+    fn create_shipping_template(&self, store_id: StoreId, payload: NewShippingTemplate) -> ServiceFuture<ShippingTemplate> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let shipping_templates_repo = repo_factory.create_shipping_templates_repo(&*conn, user_id);
+            shipping_templates_repo.create(store_id, payload).map_err(|e| {
+                e.context("Service ShippingTemplates, create_shipping_template endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Updates shipping template of the store, shipping of the products it was applied to stays as is
+    fn update_shipping_template(
+        &self,
+        store_id: StoreId,
+        template_id: i32,
+        payload: UpdateShippingTemplate,
+    ) -> ServiceFuture<ShippingTemplate> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let shipping_templates_repo = repo_factory.create_shipping_templates_repo(&*conn, user_id);
+            shipping_templates_repo.update(store_id, template_id, payload).map_err(|e| {
+                e.context("Service ShippingTemplates, update_shipping_template endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Deletes shipping template of the store
+    fn delete_shipping_template(&self, store_id: StoreId, template_id: i32) -> ServiceFuture<Option<ShippingTemplate>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let shipping_templates_repo = repo_factory.create_shipping_templates_repo(&*conn, user_id);
+            shipping_templates_repo.delete(store_id, template_id).map_err(|e| {
+                e.context("Service ShippingTemplates, delete_shipping_template endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+}
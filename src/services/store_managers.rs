@@ -0,0 +1,63 @@
+//! Store managers Services, store managers delegate the management of the shipping of their store
+//! to the shipping managers
+
+use r2d2::ManageConnection;
+
+use stq_types::{StoreId, UserId};
+
+use super::types::{Service, ServiceFuture};
+use models::UserRole;
+use repos::{DbConnection, ReposFactory};
+
+pub trait StoreManagersService {
+    /// Returns shipping managers of the store
+    fn list_store_managers(&self, store_id: StoreId) -> ServiceFuture<Vec<UserRole>>;
+    /// Grants shipping manager role of the store to the user
+    fn grant_store_manager(&self, store_id: StoreId, user_id: UserId) -> ServiceFuture<UserRole>;
+    /// Revokes shipping manager role of the store from the user
+    fn revoke_store_manager(&self, store_id: StoreId, user_id: UserId) -> ServiceFuture<Option<UserRole>>;
+}
+
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> StoreManagersService for Service<T, M, F> {
+    /// Returns shipping managers of the store
+    fn list_store_managers(&self, store_id: StoreId) -> ServiceFuture<Vec<UserRole>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let store_managers_repo = repo_factory.create_store_managers_repo(&*conn, user_id);
+            store_managers_repo.list(store_id).map_err(|e| {
+                e.context("Service StoreManagers, list_store_managers endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Grants shipping manager role of the store to the user
+    fn grant_store_manager(&self, store_id: StoreId, manager_id: UserId) -> ServiceFuture<UserRole> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let store_managers_repo = repo_factory.create_store_managers_repo(&*conn, user_id);
+            store_managers_repo.grant(store_id, manager_id).map_err(|e| {
+                e.context("Service StoreManagers, grant_store_manager endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+
+    /// Revokes shipping manager role of the store from the user
+    fn revoke_store_manager(&self, store_id: StoreId, manager_id: UserId) -> ServiceFuture<Option<UserRole>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let store_managers_repo = repo_factory.create_store_managers_repo(&*conn, user_id);
+            store_managers_repo.revoke(store_id, manager_id).map_err(|e| {
+                e.context("Service StoreManagers, revoke_store_manager endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+}
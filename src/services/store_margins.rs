@@ -0,0 +1,60 @@
+//! Store margins Services, presents operations with margins stores add to the delivery prices
+
+use r2d2::ManageConnection;
+
+use stq_types::StoreId;
+
+use super::types::{Service, ServiceFuture};
+use models::{SetStoreMargin, StoreMargin};
+use repos::{DbConnection, ReposFactory};
+
+pub trait StoreMarginsService {
+    /// Returns margin of the store
+    fn get_store_margin(&self, store_id: StoreId) -> ServiceFuture<Option<StoreMargin>>;
+    /// Sets margin of the store
+    fn set_store_margin(&self, store_id: StoreId, payload: SetStoreMargin) -> ServiceFuture<StoreMargin>;
+    /// Delete margin of the store, buyers are charged the carrier cost after that
+    fn delete_store_margin(&self, store_id: StoreId) -> ServiceFuture<Option<StoreMargin>>;
+}
+
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> StoreMarginsService for Service<T, M, F> {
+    /// Returns margin of the store
+    fn get_store_margin(&self, store_id: StoreId) -> ServiceFuture<Option<StoreMargin>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let store_margins_repo = repo_factory.create_store_margins_repo(&*conn, user_id);
+            store_margins_repo
+                .get(store_id)
+                .map_err(|e| e.context("Service StoreMargins, get_store_margin endpoint error occured.").into())
+        })
+    }
+
+    /// Sets margin of the store
+    fn set_store_margin(&self, store_id: StoreId, payload: SetStoreMargin) -> ServiceFuture<StoreMargin> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let store_margins_repo = repo_factory.create_store_margins_repo(&*conn, user_id);
+            store_margins_repo
+                .set(store_id, payload)
+                .map_err(|e| e.context("Service StoreMargins, set_store_margin endpoint error occured.").into())
+        })
+    }
+
+    /// Delete margin of the store, buyers are charged the carrier cost after that
+    fn delete_store_margin(&self, store_id: StoreId) -> ServiceFuture<Option<StoreMargin>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let store_margins_repo = repo_factory.create_store_margins_repo(&*conn, user_id);
+            store_margins_repo.delete(store_id).map_err(|e| {
+                e.context("Service StoreMargins, delete_store_margin endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+}
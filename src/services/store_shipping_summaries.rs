@@ -0,0 +1,30 @@
+//! Store shipping summaries Services, presents the overview of the shipping configured by the store
+
+use r2d2::ManageConnection;
+
+use stq_types::StoreId;
+
+use super::types::{Service, ServiceFuture};
+use models::StoreShippingSummary;
+use repos::{DbConnection, ReposFactory};
+
+pub trait StoreShippingSummariesService {
+    /// Returns shipping summary of the store for the seller dashboard
+    fn get_store_shipping_summary(&self, store_id: StoreId) -> ServiceFuture<StoreShippingSummary>;
+}
+
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> StoreShippingSummariesService for Service<T, M, F> {
+    /// Returns shipping summary of the store for the seller dashboard
+    fn get_store_shipping_summary(&self, store_id: StoreId) -> ServiceFuture<StoreShippingSummary> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let store_shipping_summaries_repo = repo_factory.create_store_shipping_summaries_repo(&*conn, user_id);
+            store_shipping_summaries_repo.get(store_id).map_err(|e| {
+                e.context("Service StoreShippingSummaries, get_store_shipping_summary endpoint error occured.")
+                    .into()
+            })
+        })
+    }
+}
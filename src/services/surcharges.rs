@@ -0,0 +1,87 @@
+//! Surcharges Services, presents CRUD operations with surcharges
+
+use r2d2::ManageConnection;
+
+use super::types::{Service, ServiceFuture};
+use models::{NewSurcharge, Surcharge, UpdateSurcharge};
+use repos::{DbConnection, ReposFactory};
+
+pub trait SurchargesService {
+    /// Returns list of surcharges
+    fn list_surcharges(&self) -> ServiceFuture<Vec<Surcharge>>;
+    /// Returns surcharge by id
+    fn get_surcharge(&self, surcharge_id: i32) -> ServiceFuture<Option<Surcharge>>;
+    /// Create a new surcharge
+    fn create_surcharge(&self, payload: NewSurcharge) -> ServiceFuture<Surcharge>;
+    /// Update a surcharge
+    fn update_surcharge(&self, surcharge_id: i32, payload: UpdateSurcharge) -> ServiceFuture<Surcharge>;
+    /// Delete a surcharge
+    fn delete_surcharge(&self, surcharge_id: i32) -> ServiceFuture<Surcharge>;
+}
+
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> SurchargesService for Service<T, M, F> {
+    /// Returns list of surcharges
+    fn list_surcharges(&self) -> ServiceFuture<Vec<Surcharge>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let surcharges_repo = repo_factory.create_surcharges_repo(&*conn, user_id);
+            surcharges_repo
+                .list()
+                .map_err(|e| e.context("Service Surcharges, list_surcharges endpoint error occured.").into())
+        })
+    }
+
+    /// Returns surcharge by id
+    fn get_surcharge(&self, surcharge_id: i32) -> ServiceFuture<Option<Surcharge>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let surcharges_repo = repo_factory.create_surcharges_repo(&*conn, user_id);
+            surcharges_repo
+                .find(surcharge_id)
+                .map_err(|e| e.context("Service Surcharges, get_surcharge endpoint error occured.").into())
+        })
+    }
+
+    /// Create a new surcharge
+    fn create_surcharge(&self, payload: NewSurcharge) -> ServiceFuture<Surcharge> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_transaction_on_pool(move |conn| {
+            let surcharges_repo = repo_factory.create_surcharges_repo(&*conn, user_id);
+            surcharges_repo
+                .create(payload)
+                .map_err(|e| e.context("Service Surcharges, create_surcharge endpoint error occured.").into())
+        })
+    }
+
+    /// Update a surcharge
+    fn update_surcharge(&self, surcharge_id: i32, payload: UpdateSurcharge) -> ServiceFuture<Surcharge> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let surcharges_repo = repo_factory.create_surcharges_repo(&*conn, user_id);
+            surcharges_repo
+                .update(surcharge_id, payload)
+                .map_err(|e| e.context("Service Surcharges, update_surcharge endpoint error occured.").into())
+        })
+    }
+
+    /// Delete a surcharge
+    fn delete_surcharge(&self, surcharge_id: i32) -> ServiceFuture<Surcharge> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let surcharges_repo = repo_factory.create_surcharges_repo(&*conn, user_id);
+            surcharges_repo
+                .delete(surcharge_id)
+                .map_err(|e| e.context("Service Surcharges, delete_surcharge endpoint error occured.").into())
+        })
+    }
+}
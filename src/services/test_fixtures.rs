@@ -0,0 +1,117 @@
+//! Test fixtures Services, reset the data of the test environments and load the deterministic fixture set.
+//! Built only with `test-endpoints` feature
+
+use std::collections::HashMap;
+
+use failure::Error as FailureError;
+use futures::Future;
+use r2d2::ManageConnection;
+
+use super::company_rates_quotas::insert_rates_within_quota;
+use super::types::{Service, ServiceFuture};
+use content_versions::{VersionedContent, COUNTRIES_CONTENT};
+use models::{test_fixtures, NewShippingRates, TestFixtures, TestFixturesReport};
+use repos::{DbConnection, ReposFactory};
+
+pub trait TestFixturesService {
+    /// Empties the test data tables and loads the fixtures, ids of the fixtures are the same after every seeding
+    fn seed_test_fixtures(&self) -> ServiceFuture<TestFixturesReport>;
+    /// Empties the test data tables, the countries tree is kept
+    fn reset_test_fixtures(&self) -> ServiceFuture<()>;
+}
+
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> TestFixturesService for Service<T, M, F> {
+    /// Empties the test data tables and loads the fixtures, ids of the fixtures are the same after every seeding
+    fn seed_test_fixtures(&self) -> ServiceFuture<TestFixturesReport> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+        let content_versions = self.static_context.content_versions.clone();
+        let default_max_entries = self
+            .static_context
+            .config
+            .rates_quota
+            .as_ref()
+            .map(|quota| quota.default_max_entries);
+
+        let seeded = self
+            .spawn_transaction_on_admin_pool(move |conn| {
+                let test_fixtures_repo = repo_factory.create_test_fixtures_repo(&*conn, user_id);
+                let countries_repo = repo_factory.create_countries_repo(&*conn, user_id);
+                let companies_repo = repo_factory.create_companies_repo(&*conn, user_id);
+                let packages_repo = repo_factory.create_packages_repo(&*conn, user_id);
+                let companies_packages_repo = repo_factory.create_companies_packages_repo(&*conn, user_id);
+                let shipping_rates_repo = repo_factory.create_shipping_rates_repo(&*conn, user_id);
+                let company_rates_quotas_repo = repo_factory.create_company_rates_quotas_repo_with_sys_acl(&*conn);
+
+                let fixtures = test_fixtures();
+                let report = fixtures.report();
+                let TestFixtures {
+                    countries,
+                    companies,
+                    packages,
+                    companies_packages,
+                    shipping_rates,
+                } = fixtures;
+
+                test_fixtures_repo.reset()?;
+                test_fixtures_repo.delete_countries()?;
+                for country in countries {
+                    countries_repo.create(country)?;
+                }
+                for company in companies {
+                    companies_repo.create(company)?;
+                }
+                for package in packages {
+                    packages_repo.create(package)?;
+                }
+                let mut companies_ids = HashMap::new();
+                for company_package in companies_packages {
+                    let company_package = companies_packages_repo.create(company_package)?;
+                    companies_ids.insert(company_package.id, company_package.company_id);
+                }
+
+                // fixtures are stored against the quotas like the rates uploaded by the companies
+                let mut rates_by_company = HashMap::<_, Vec<NewShippingRates>>::new();
+                for rates in shipping_rates {
+                    let company_id = companies_ids
+                        .get(&rates.company_package_id)
+                        .cloned()
+                        .ok_or_else(|| format_err!("Company package {} of fixture rates not found", rates.company_package_id))?;
+                    rates_by_company.entry(company_id).or_default().push(rates);
+                }
+                for (company_id, rates) in rates_by_company {
+                    insert_rates_within_quota(
+                        &*company_rates_quotas_repo,
+                        &*shipping_rates_repo,
+                        default_max_entries,
+                        company_id,
+                        rates,
+                    )?;
+                }
+
+                Ok(report)
+            })
+            .map_err(|e| FailureError::from(e.context("Service TestFixtures, seed endpoint error occured.")));
+
+        Box::new(seeded.map(move |report| {
+            content_versions.invalidate(COUNTRIES_CONTENT);
+            report
+        }))
+    }
+
+    /// Empties the test data tables, the countries tree is kept
+    fn reset_test_fixtures(&self) -> ServiceFuture<()> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+        let content_versions = self.static_context.content_versions.clone();
+
+        let reset = self.spawn_transaction_on_admin_pool(move |conn| {
+            let test_fixtures_repo = repo_factory.create_test_fixtures_repo(&*conn, user_id);
+            test_fixtures_repo
+                .reset()
+                .map_err(|e| e.context("Service TestFixtures, reset endpoint error occured.").into())
+        });
+
+        Box::new(reset.map(move |_| content_versions.invalidate(&[VersionedContent::Packages])))
+    }
+}
@@ -1,22 +1,28 @@
-use diesel::connection::AnsiTransactionManager;
-use diesel::pg::Pg;
-use diesel::Connection;
+use std::sync::Arc;
+use std::time::Instant;
+
 use failure::Error as FailureError;
 use failure::Fail;
+use futures::future;
 use futures::Future;
+use futures_cpupool::CpuPool;
 use r2d2::{ManageConnection, PooledConnection};
 
 use controller::context::{DynamicContext, StaticContext};
+use diagnostics::TaskTracker;
 use errors::Error;
+use events;
+use models::OutboxEvent;
 use repos::repo_factory::*;
+use repos::DbConnection;
 
 /// Service layer Future
-pub type ServiceFuture<T> = Box<Future<Item = T, Error = FailureError>>;
+pub type ServiceFuture<T> = Box<dyn Future<Item = T, Error = FailureError>>;
 
 /// Service
 pub struct Service<T, M, F>
 where
-    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    T: DbConnection,
     M: ManageConnection<Connection = T>,
     F: ReposFactory<T>,
 {
@@ -24,12 +30,7 @@ where
     pub dynamic_context: DynamicContext,
 }
 
-impl<
-        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
-        M: ManageConnection<Connection = T>,
-        F: ReposFactory<T>,
-    > Service<T, M, F>
-{
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> Service<T, M, F> {
     /// Create a new service
     pub fn new(static_context: StaticContext<T, M, F>, dynamic_context: DynamicContext) -> Self {
         Self {
@@ -43,18 +44,122 @@ impl<
         Func: FnOnce(PooledConnection<M>) -> Result<R, FailureError> + Send + 'static,
         R: Send + 'static,
     {
-        let db_pool = self.static_context.db_pool.clone();
         let cpu_pool = self.static_context.cpu_pool.clone();
-        Box::new(cpu_pool.spawn_fn(move || db_pool.get().map_err(|e| e.context(Error::Connection).into()).and_then(f)))
+        let tasks = self.static_context.cpu_pool_tasks.clone();
+        self.spawn_on(&cpu_pool, &tasks, false, f)
+    }
+
+    /// Same as `spawn_on_pool`, but the connection is taken from the fastest healthy read replica if there are any.
+    /// Replicas lag behind the primary, so only price and availability reads, which tolerate that, use it
+    pub fn spawn_on_read_pool<R, Func>(&self, f: Func) -> ServiceFuture<R>
+    where
+        Func: FnOnce(PooledConnection<M>) -> Result<R, FailureError> + Send + 'static,
+        R: Send + 'static,
+    {
+        let cpu_pool = self.static_context.cpu_pool.clone();
+        let tasks = self.static_context.cpu_pool_tasks.clone();
+        self.spawn_on(&cpu_pool, &tasks, true, f)
+    }
+
+    /// Same as `spawn_on_pool`, but everything `f` does runs in a single transaction, rolled back if it fails.
+    /// Service operations writing more than once use it, so that a failure in the middle leaves nothing half written
+    pub fn spawn_transaction_on_pool<R, Func>(&self, f: Func) -> ServiceFuture<R>
+    where
+        Func: FnOnce(&T) -> Result<R, FailureError> + Send + 'static,
+        R: Send + 'static,
+    {
+        self.spawn_on_pool(move |conn| conn.transaction::<R, FailureError, _>(|| f(&*conn)))
+    }
+
+    /// Same as `spawn_on_pool`, but for long-running admin imports and exports,
+    /// which must not occupy the threads serving price and availability requests
+    pub fn spawn_on_admin_pool<R, Func>(&self, f: Func) -> ServiceFuture<R>
+    where
+        Func: FnOnce(PooledConnection<M>) -> Result<R, FailureError> + Send + 'static,
+        R: Send + 'static,
+    {
+        let cpu_pool = self.static_context.admin_cpu_pool.clone();
+        let tasks = self.static_context.admin_cpu_pool_tasks.clone();
+        self.spawn_on(&cpu_pool, &tasks, false, f)
+    }
+
+    /// Same as `spawn_transaction_on_pool`, but on the admin pool like `spawn_on_admin_pool`
+    pub fn spawn_transaction_on_admin_pool<R, Func>(&self, f: Func) -> ServiceFuture<R>
+    where
+        Func: FnOnce(&T) -> Result<R, FailureError> + Send + 'static,
+        R: Send + 'static,
+    {
+        self.spawn_on_admin_pool(move |conn| conn.transaction::<R, FailureError, _>(|| f(&*conn)))
+    }
+
+    /// Whether the message bus is configured, events are stored to the outbox only if it is
+    pub fn events_enabled(&self) -> bool {
+        self.static_context.events_publisher.is_some()
+    }
+
+    /// Publishes the events stored to the outbox by `events::store` and marks them published.
+    /// Called after the change is committed, the returned future never fails
+    pub fn publish_events(&self, outbox_events: Vec<OutboxEvent>) -> ServiceFuture<()> {
+        let publisher = match self.static_context.events_publisher.clone() {
+            Some(publisher) => publisher,
+            None => return Box::new(future::ok(())),
+        };
+        if outbox_events.is_empty() {
+            return Box::new(future::ok(()));
+        }
+
+        let repo_factory = self.static_context.repo_factory.clone();
+        let request_context = self.dynamic_context.request_context.clone();
+        let service = self.clone();
+
+        Box::new(
+            events::publish(&*publisher, outbox_events, &request_context)
+                .and_then(move |published| {
+                    service.spawn_on_pool(move |conn| {
+                        let outbox_repo = repo_factory.create_event_outbox_repo_with_sys_acl(&*conn);
+                        published
+                            .into_iter()
+                            .map(|id| outbox_repo.mark_published(id).map(|_| ()))
+                            .collect::<Result<(), FailureError>>()
+                    })
+                })
+                .then(move |res| {
+                    if let Err(e) = res {
+                        error!(
+                            "{}",
+                            request_context.log_line_with("Failed to mark events published", json!({ "error": e.to_string() }))
+                        );
+                    }
+                    Ok(())
+                }),
+        )
+    }
+
+    fn spawn_on<R, Func>(&self, cpu_pool: &CpuPool, tasks: &Arc<TaskTracker>, read_only: bool, f: Func) -> ServiceFuture<R>
+    where
+        Func: FnOnce(PooledConnection<M>) -> Result<R, FailureError> + Send + 'static,
+        R: Send + 'static,
+    {
+        let db_pool = self.static_context.db_pool.clone();
+        let read_replicas = self.static_context.read_replicas.clone();
+        let connection_tracker = self.static_context.connection_tracker.clone();
+        let request_context = self.dynamic_context.request_context.clone();
+        let queued = TaskTracker::queue(tasks);
+        Box::new(cpu_pool.spawn_fn(move || {
+            let _running = queued.start();
+            let _entered = request_context.enter();
+            let checkout_started_at = Instant::now();
+            let conn = if read_only { read_replicas.get(&db_pool) } else { db_pool.get() };
+            connection_tracker.record_checkout_wait(checkout_started_at.elapsed());
+            conn.map_err(|e| e.context(Error::Connection).into()).and_then(|conn| {
+                let _checkout = connection_tracker.checkout(request_context.route.clone());
+                f(conn)
+            })
+        }))
     }
 }
 
-impl<
-        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
-        M: ManageConnection<Connection = T>,
-        F: ReposFactory<T>,
-    > Clone for Service<T, M, F>
-{
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> Clone for Service<T, M, F> {
     fn clone(&self) -> Self {
         Self {
             static_context: self.static_context.clone(),
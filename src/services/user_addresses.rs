@@ -1,47 +1,61 @@
 //! UserAddress Services, presents CRUD operations with user_roles
 
-use diesel::connection::AnsiTransactionManager;
-use diesel::pg::Pg;
-use diesel::Connection;
-
 use r2d2::ManageConnection;
 
 use failure::Error as FailureError;
+use futures::future;
+use futures::prelude::*;
 
 use stq_types::UserId;
 
 use super::types::{Service, ServiceFuture};
-use models::{NewUserAddress, UpdateUserAddress, UserAddress};
-use repos::ReposFactory;
+use errors::Error;
+use geocoding::geocoding_query;
+use models::{ListedUserAddress, NewUserAddress, UpdateUserAddress, UserAddress};
+use repos::{DbConnection, ReposFactory};
 
 pub trait UserAddressService {
-    /// Returns list of user  address
-    fn get_addresses(&self, user_id: UserId) -> ServiceFuture<Vec<UserAddress>>;
+    /// Returns list of user  address with the default one first, addresses without coordinates are geocoded if `with_coordinates` is set
+    fn get_addresses(&self, user_id: UserId, with_coordinates: bool) -> ServiceFuture<Vec<ListedUserAddress>>;
     /// Create a new user addresses
     fn create_address(&self, payload: NewUserAddress) -> ServiceFuture<UserAddress>;
     /// Update a user addresses
     fn update_address(&self, id: i32, payload: UpdateUserAddress) -> ServiceFuture<UserAddress>;
     /// Delete user addresses
     fn delete_address(&self, id: i32) -> ServiceFuture<UserAddress>;
+    /// Make user address the default one
+    fn set_default_address(&self, id: i32) -> ServiceFuture<UserAddress>;
 }
 
-impl<
-        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
-        M: ManageConnection<Connection = T>,
-        F: ReposFactory<T>,
-    > UserAddressService for Service<T, M, F>
-{
-    /// Returns list of user  address
-    fn get_addresses(&self, user_id: UserId) -> ServiceFuture<Vec<UserAddress>> {
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> UserAddressService for Service<T, M, F> {
+    /// Returns list of user  address with the default one first, addresses without coordinates are geocoded if `with_coordinates` is set
+    fn get_addresses(&self, user_id: UserId, with_coordinates: bool) -> ServiceFuture<Vec<ListedUserAddress>> {
         let repo_factory = self.static_context.repo_factory.clone();
         let current_user_id = self.dynamic_context.user_id;
 
-        self.spawn_on_pool(move |conn| {
+        let addresses = self.spawn_on_pool(move |conn| {
             let users_addresses_repo = repo_factory.create_users_addresses_repo(&*conn, current_user_id);
             users_addresses_repo
                 .list_for_user(user_id)
                 .map_err(|e| e.context("Service UserAddress, get_addresses endpoint error occured.").into())
-        })
+        });
+
+        if !with_coordinates {
+            return Box::new(addresses.map(ListedUserAddress::from_ordered));
+        }
+
+        let service = self.clone();
+        Box::new(
+            addresses
+                .and_then(move |addresses| {
+                    future::join_all(
+                        addresses
+                            .into_iter()
+                            .map(move |address| resolve_coordinates(&service, address, false)),
+                    )
+                })
+                .map(ListedUserAddress::from_ordered),
+        )
     }
 
     /// Delete user addresses
@@ -58,30 +72,216 @@ impl<
     }
 
     /// Create a new user addresses
-    fn create_address(&self, payload: NewUserAddress) -> ServiceFuture<UserAddress> {
+    fn create_address(&self, mut payload: NewUserAddress) -> ServiceFuture<UserAddress> {
         let repo_factory = self.static_context.repo_factory.clone();
+        let address_validator = self.static_context.address_validator.clone();
         let user_id = self.dynamic_context.user_id;
 
-        self.spawn_on_pool(move |conn| {
+        let address = self.spawn_transaction_on_pool(move |conn| {
             let users_addresses_repo = repo_factory.create_users_addresses_repo(&*conn, user_id);
-            conn.transaction::<UserAddress, FailureError, _>(move || {
-                users_addresses_repo
-                    .create(payload)
-                    .map_err(|e| e.context("Service UserAddress, create endpoint error occured.").into())
-            })
-        })
+            let countries_repo = repo_factory.create_countries_repo(&*conn, user_id);
+            let country_subdivisions_repo = repo_factory.create_country_subdivisions_repo(&*conn, user_id);
+
+            let run = move || {
+                if let Some(country_code) = payload.country_code.clone() {
+                    let country_code = address_validator.validate(&*countries_repo, &country_code, Some(&payload.postal_code))?;
+                    payload.country_code = Some(country_code);
+                }
+
+                if let Some(subdivision) = payload.subdivision.clone() {
+                    let country_code = payload.country_code.clone().ok_or_else(subdivision_without_country_error)?;
+                    let subdivision = address_validator.validate_subdivision(&*country_subdivisions_repo, &country_code, &subdivision)?;
+                    payload.subdivision = Some(subdivision);
+                }
+
+                users_addresses_repo.create(payload)
+            };
+
+            run().map_err(|e: FailureError| e.context("Service UserAddress, create endpoint error occured.").into())
+        });
+
+        let service = self.clone();
+        Box::new(address.and_then(move |address| resolve_coordinates(&service, address, false)))
     }
 
     /// Update a user addresses
-    fn update_address(&self, id: i32, payload: UpdateUserAddress) -> ServiceFuture<UserAddress> {
+    fn update_address(&self, id: i32, mut payload: UpdateUserAddress) -> ServiceFuture<UserAddress> {
         let repo_factory = self.static_context.repo_factory.clone();
+        let address_validator = self.static_context.address_validator.clone();
         let user_id = self.dynamic_context.user_id;
+        let refresh_coordinates = payload.changes_location() && payload.latitude.is_none() && payload.longitude.is_none();
 
-        self.spawn_on_pool(move |conn| {
+        let address = self.spawn_transaction_on_pool(move |conn| {
+            let users_addresses_repo = repo_factory.create_users_addresses_repo(&*conn, user_id);
+            let countries_repo = repo_factory.create_countries_repo(&*conn, user_id);
+            let country_subdivisions_repo = repo_factory.create_country_subdivisions_repo(&*conn, user_id);
+
+            let run = move || {
+                // the postal code and the subdivision updated without the country are checked against the stored country
+                let updates_country = payload.country_code.is_some();
+                let country_code = if updates_country {
+                    payload.country_code.clone()
+                } else if payload.postal_code.is_some() || payload.subdivision.is_some() {
+                    users_addresses_repo.find(id)?.and_then(|address| address.country_code)
+                } else {
+                    None
+                };
+
+                let country_code = match country_code {
+                    Some(country_code) => {
+                        let postal_code = payload.postal_code.clone();
+                        Some(address_validator.validate(&*countries_repo, &country_code, postal_code.as_ref().map(String::as_str))?)
+                    }
+                    None => None,
+                };
+                if updates_country {
+                    payload.country_code = country_code.clone();
+                }
+
+                if let Some(subdivision) = payload.subdivision.clone() {
+                    let country_code = country_code.ok_or_else(subdivision_without_country_error)?;
+                    let subdivision = address_validator.validate_subdivision(&*country_subdivisions_repo, &country_code, &subdivision)?;
+                    payload.subdivision = Some(subdivision);
+                }
+
+                users_addresses_repo.update(id, payload)
+            };
+
+            run().map_err(|e: FailureError| e.context("Service UserAddress, update endpoint error occured.").into())
+        });
+
+        let service = self.clone();
+        Box::new(address.and_then(move |address| resolve_coordinates(&service, address, refresh_coordinates)))
+    }
+
+    /// Make user address the default one
+    fn set_default_address(&self, id: i32) -> ServiceFuture<UserAddress> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_transaction_on_pool(move |conn| {
             let users_addresses_repo = repo_factory.create_users_addresses_repo(&*conn, user_id);
             users_addresses_repo
-                .update(id, payload)
-                .map_err(|e| e.context("Service UserAddress, update endpoint error occured.").into())
+                .set_default(id)
+                .map_err(|e| e.context("Service UserAddress, set_default_address endpoint error occured.").into())
         })
     }
 }
+
+fn subdivision_without_country_error() -> Error {
+    Error::Validate(validation_errors!({
+        "subdivision": ["subdivision" => "Subdivision can only be set on addresses with the country code"]
+    }))
+}
+
+/// Resolves coordinates of the address with the geocoder and stores them. Addresses that already have coordinates
+/// are geocoded only with `refresh` set. Geocoding is optional, so its failures are logged and the address is returned as is
+fn resolve_coordinates<T, M, F>(service: &Service<T, M, F>, address: UserAddress, refresh: bool) -> ServiceFuture<UserAddress>
+where
+    T: DbConnection,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+{
+    let geocoder = match service.static_context.geocoder.clone() {
+        Some(geocoder) => geocoder,
+        None => return Box::new(future::ok(address)),
+    };
+
+    if address.latitude.is_some() && address.longitude.is_some() && !refresh {
+        return Box::new(future::ok(address));
+    }
+
+    let request_context = service.dynamic_context.request_context.clone();
+    let service = service.clone();
+    Box::new(
+        geocoder
+            .geocode(geocoding_query(&address), &request_context)
+            .then(move |res| -> ServiceFuture<UserAddress> {
+                let coordinates = match res {
+                    Ok(Some(coordinates)) => coordinates,
+                    Ok(None) => {
+                        warn!(
+                            "{}",
+                            request_context.log_line(&format!("Geocoder could not find user address {}", address.id))
+                        );
+                        return Box::new(future::ok(address));
+                    }
+                    Err(e) => {
+                        warn!(
+                            "{}",
+                            request_context.log_line(&format!("Geocoding of user address {} failed: {}", address.id, e))
+                        );
+                        return Box::new(future::ok(address));
+                    }
+                };
+
+                let repo_factory = service.static_context.repo_factory.clone();
+                let user_id = service.dynamic_context.user_id;
+                let address_id = address.id;
+                let payload = UpdateUserAddress {
+                    latitude: Some(coordinates.latitude),
+                    longitude: Some(coordinates.longitude),
+                    ..Default::default()
+                };
+
+                let mut address = address;
+                address.latitude = Some(coordinates.latitude);
+                address.longitude = Some(coordinates.longitude);
+
+                Box::new(
+                    service
+                        .spawn_transaction_on_pool(move |conn| {
+                            let users_addresses_repo = repo_factory.create_users_addresses_repo(&*conn, user_id);
+                            users_addresses_repo.update(address_id, payload)
+                        })
+                        .then(move |res| {
+                            res.or_else(|e| {
+                                warn!(
+                                    "{}",
+                                    request_context.log_line(&format!("Coordinates of user address {} were not saved: {}", address.id, e))
+                                );
+                                Ok(address)
+                            })
+                        }),
+                )
+            }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use tokio_core::reactor::Core;
+
+    use stq_http::errors::ErrorMessageWrapper;
+
+    use errors::Error;
+    use models::UpdateUserAddress;
+    use repos::repo_factory::tests::*;
+    use services::user_addresses::UserAddressService;
+
+    #[test]
+    fn postal_code_is_validated_against_stored_country() {
+        let mut core = Core::new().unwrap();
+        let handle = Arc::new(core.handle());
+        let service = create_service(Some(MOCK_USER_ID), handle);
+
+        // the stored address is in Russia, postal codes of Russia have 6 digits
+        let payload = UpdateUserAddress {
+            postal_code: Some("SW1A 1AA".to_string()),
+            ..Default::default()
+        };
+        let err = core.run(service.update_address(1, payload)).unwrap_err();
+        assert_eq!(ErrorMessageWrapper::<Error>::from(&err).inner.code, 400);
+
+        let payload = UpdateUserAddress {
+            postal_code: Some("190000".to_string()),
+            latitude: Some(59.93),
+            longitude: Some(30.31),
+            ..Default::default()
+        };
+        let address = core.run(service.update_address(1, payload)).unwrap();
+        assert_eq!(address.postal_code, "190000");
+        assert_eq!(address.country_code, None);
+    }
+}
@@ -1,8 +1,5 @@
 //! UserRoles Services, presents CRUD operations with user_roles
 
-use diesel::connection::AnsiTransactionManager;
-use diesel::pg::Pg;
-use diesel::Connection;
 use failure::Error as FailureError;
 
 use r2d2::ManageConnection;
@@ -10,8 +7,8 @@ use r2d2::ManageConnection;
 use stq_types::{DeliveryRole, RoleId, UserId};
 
 use super::types::{Service, ServiceFuture};
-use models::{NewUserRole, UserRole};
-use repos::ReposFactory;
+use models::{group_permissions_by_role, NewUserRole, RolePermissions, UserRole};
+use repos::{DbConnection, ReposFactory};
 
 pub trait UserRolesService {
     /// Creates new user_role
@@ -22,13 +19,10 @@ pub trait UserRolesService {
     fn delete_by_user_id(&self, user_id_arg: UserId) -> ServiceFuture<Vec<UserRole>>;
     /// Deletes role for user by id
     fn delete_by_id(&self, id_arg: RoleId) -> ServiceFuture<UserRole>;
+    /// Returns permissions matrix of all roles
+    fn get_permissions(&self) -> ServiceFuture<Vec<RolePermissions>>;
 }
-impl<
-        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
-        M: ManageConnection<Connection = T>,
-        F: ReposFactory<T>,
-    > UserRolesService for Service<T, M, F>
-{
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> UserRolesService for Service<T, M, F> {
     /// Returns role by user ID
     fn get_roles(&self, user_id: UserId) -> ServiceFuture<Vec<DeliveryRole>> {
         let repo_factory = self.static_context.repo_factory.clone();
@@ -73,10 +67,25 @@ impl<
         let repo_factory = self.static_context.repo_factory.clone();
         let current_uid = self.dynamic_context.user_id;
 
-        self.spawn_on_pool(move |conn| {
+        self.spawn_transaction_on_pool(move |conn| {
             let user_roles_repo = repo_factory.create_user_roles_repo(&*conn, current_uid);
-            conn.transaction::<UserRole, FailureError, _>(move || user_roles_repo.create(new_user_role))
+            user_roles_repo
+                .create(new_user_role)
                 .map_err(|e: FailureError| e.context("Service user_roles, create endpoint error occured.").into())
         })
     }
+
+    /// Returns permissions matrix of all roles
+    fn get_permissions(&self) -> ServiceFuture<Vec<RolePermissions>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let current_uid = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let permissions_repo = repo_factory.create_permissions_repo(&*conn, current_uid);
+            permissions_repo
+                .list()
+                .map(group_permissions_by_role)
+                .map_err(|e: FailureError| e.context("Service user_roles, get_permissions endpoint error occured.").into())
+        })
+    }
 }
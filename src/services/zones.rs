@@ -0,0 +1,100 @@
+//! Zones Services, presents CRUD operations with zones
+
+use r2d2::ManageConnection;
+
+use super::types::{Service, ServiceFuture};
+use content_versions::VersionedContent;
+use models::{NewZone, UpdateZone, Zone};
+use repos::{DbConnection, ReposFactory};
+
+pub trait ZonesService {
+    /// Returns list of zones
+    fn list_zones(&self) -> ServiceFuture<Vec<Zone>>;
+    /// Returns zone by id
+    fn get_zone(&self, zone_id: i32) -> ServiceFuture<Option<Zone>>;
+    /// Create a new zone
+    fn create_zone(&self, payload: NewZone) -> ServiceFuture<Zone>;
+    /// Update a zone
+    fn update_zone(&self, zone_id: i32, payload: UpdateZone) -> ServiceFuture<Zone>;
+    /// Delete a zone
+    fn delete_zone(&self, zone_id: i32) -> ServiceFuture<Zone>;
+}
+
+impl<T: DbConnection, M: ManageConnection<Connection = T>, F: ReposFactory<T>> ZonesService for Service<T, M, F> {
+    /// Returns list of zones
+    fn list_zones(&self) -> ServiceFuture<Vec<Zone>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let zones_repo = repo_factory.create_zones_repo(&*conn, user_id);
+            zones_repo
+                .list()
+                .map_err(|e| e.context("Service Zones, list_zones endpoint error occured.").into())
+        })
+    }
+
+    /// Returns zone by id
+    fn get_zone(&self, zone_id: i32) -> ServiceFuture<Option<Zone>> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_on_pool(move |conn| {
+            let zones_repo = repo_factory.create_zones_repo(&*conn, user_id);
+            zones_repo
+                .find(zone_id)
+                .map_err(|e| e.context("Service Zones, get_zone endpoint error occured.").into())
+        })
+    }
+
+    /// Create a new zone
+    fn create_zone(&self, payload: NewZone) -> ServiceFuture<Zone> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        self.spawn_transaction_on_pool(move |conn| {
+            let zones_repo = repo_factory.create_zones_repo(&*conn, user_id);
+            zones_repo
+                .create(payload)
+                .map_err(|e| e.context("Service Zones, create_zone endpoint error occured.").into())
+        })
+    }
+
+    /// Update a zone
+    fn update_zone(&self, zone_id: i32, payload: UpdateZone) -> ServiceFuture<Zone> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+        let content_versions = self.static_context.content_versions.clone();
+
+        self.spawn_on_pool(move |conn| {
+            let zones_repo = repo_factory.create_zones_repo(&*conn, user_id);
+            zones_repo
+                .update(zone_id, payload)
+                // packages deliver to the countries of their zones
+                .map(|zone| {
+                    content_versions.invalidate(&[VersionedContent::Packages]);
+                    zone
+                })
+                .map_err(|e| e.context("Service Zones, update_zone endpoint error occured.").into())
+        })
+    }
+
+    /// Delete a zone
+    fn delete_zone(&self, zone_id: i32) -> ServiceFuture<Zone> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+        let content_versions = self.static_context.content_versions.clone();
+
+        self.spawn_on_pool(move |conn| {
+            let zones_repo = repo_factory.create_zones_repo(&*conn, user_id);
+            zones_repo
+                .delete(zone_id)
+                // packages deliver to the countries of their zones
+                .map(|zone| {
+                    content_versions.invalidate(&[VersionedContent::Packages]);
+                    zone
+                })
+                .map_err(|e| e.context("Service Zones, delete_zone endpoint error occured.").into())
+        })
+    }
+}
@@ -0,0 +1,171 @@
+//! Graceful shutdown of the server.
+//!
+//! On SIGTERM or SIGINT the server stops accepting connections and waits until the requests in flight are answered
+//! and every database connection is back in the pool, so that rate replacements and imports running on the CPU pools
+//! finish their transactions. Waiting is limited by the grace period, the server exits when it is over anyway.
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::{self, Either};
+use futures::{Future, Stream};
+use hyper;
+use hyper::header::Connection;
+use hyper::server::{Request, Response, Service};
+use r2d2::{ManageConnection, Pool};
+use tokio_core::reactor::{Handle, Interval, Timeout};
+use tokio_signal;
+use tokio_signal::unix::{Signal, SIGTERM};
+
+/// Grace period of the servers without one configured
+pub const DEFAULT_GRACE_PERIOD_MS: u64 = 30_000;
+
+/// How often the drained state is checked during the shutdown
+const DRAIN_CHECK_INTERVAL_MS: u64 = 100;
+
+/// Requests being handled, shared by all the connections
+#[derive(Clone, Debug, Default)]
+pub struct InFlightRequests {
+    count: Arc<AtomicUsize>,
+    shutting_down: Arc<AtomicBool>,
+}
+
+impl InFlightRequests {
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::SeqCst)
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
+
+    pub fn start_shutdown(&self) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+    }
+
+    fn start(&self) -> InFlightGuard {
+        self.count.fetch_add(1, Ordering::SeqCst);
+        InFlightGuard(self.count.clone())
+    }
+}
+
+/// Request is counted until the guard is dropped, even if the connection is closed before the response is sent
+struct InFlightGuard(Arc<AtomicUsize>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Counts the requests the inner service handles. Responses sent during the shutdown close the connection,
+/// so that the clients with kept alive connections reconnect to another instance
+pub struct TrackInFlight<S> {
+    inner: S,
+    in_flight: InFlightRequests,
+}
+
+impl<S> TrackInFlight<S> {
+    pub fn new(inner: S, in_flight: InFlightRequests) -> Self {
+        Self { inner, in_flight }
+    }
+}
+
+impl<S> Service for TrackInFlight<S>
+where
+    S: Service<Request = Request, Response = Response, Error = hyper::Error>,
+    S::Future: 'static,
+{
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<dyn Future<Item = Response, Error = hyper::Error>>;
+
+    fn call(&self, req: Request) -> Self::Future {
+        let guard = self.in_flight.start();
+        let in_flight = self.in_flight.clone();
+
+        Box::new(self.inner.call(req).map(move |mut res| {
+            if in_flight.is_shutting_down() {
+                res.headers_mut().set(Connection::close());
+            }
+            drop(guard);
+            res
+        }))
+    }
+}
+
+/// Resolves with the name of the first shutdown signal received
+pub fn signal() -> Box<dyn Future<Item = &'static str, Error = io::Error>> {
+    let sigint = tokio_signal::ctrl_c().flatten_stream().map(|()| "SIGINT");
+    let sigterm = Signal::new(SIGTERM).flatten_stream().map(|_| "SIGTERM");
+
+    Box::new(
+        sigint
+            .select(sigterm)
+            .into_future()
+            .map_err(|(e, _)| e)
+            .and_then(|(signal, _)| signal.ok_or_else(|| io::Error::new(io::ErrorKind::Other, "Signal stream ended"))),
+    )
+}
+
+/// Waits until no request is in flight and no database connection is checked out, resolves with `false`
+/// if the grace period is over first
+pub fn drain<M>(
+    handle: &Handle,
+    in_flight: InFlightRequests,
+    db_pool: Pool<M>,
+    grace_period: Duration,
+) -> Box<dyn Future<Item = bool, Error = io::Error>>
+where
+    M: ManageConnection,
+{
+    let is_busy = move || {
+        let state = db_pool.state();
+        in_flight.count() > 0 || state.idle_connections < state.connections
+    };
+
+    let checks = match Interval::new(Duration::from_millis(DRAIN_CHECK_INTERVAL_MS), handle) {
+        Ok(checks) => checks,
+        Err(e) => return Box::new(future::err(e)),
+    };
+    let timeout = match Timeout::new(grace_period, handle) {
+        Ok(timeout) => timeout,
+        Err(e) => return Box::new(future::err(e)),
+    };
+
+    let drained = future::lazy(move || {
+        if is_busy() {
+            Either::A(checks.take_while(move |_| Ok(is_busy())).for_each(|_| Ok(())))
+        } else {
+            Either::B(future::ok(()))
+        }
+    });
+
+    Box::new(drained.select2(timeout).then(|res| match res {
+        Ok(Either::A(_)) => Ok(true),
+        Ok(Either::B(_)) => Ok(false),
+        Err(Either::A((e, _))) => Err(e),
+        Err(Either::B((e, _))) => Err(e),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_are_counted_until_guard_is_dropped() {
+        let in_flight = InFlightRequests::default();
+
+        let first = in_flight.start();
+        let second = in_flight.start();
+        assert_eq!(in_flight.count(), 2);
+
+        drop(first);
+        assert_eq!(in_flight.count(), 1);
+        drop(second);
+        assert_eq!(in_flight.count(), 0);
+    }
+}
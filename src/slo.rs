@@ -0,0 +1,242 @@
+//! Service level objectives tracking.
+//!
+//! Latency and outcome of every request are recorded per route group, compliance with the configured
+//! targets is computed over a rolling window and reported by `GET /slo/status`.
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use config;
+
+/// Memory limit, the oldest observations are dropped first
+const MAX_OBSERVATIONS_PER_GROUP: usize = 100_000;
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteGroup {
+    /// Delivery price calculation
+    Price,
+    /// Available packages for products and users
+    Availability,
+    /// Management of companies, packages, rates and other settings
+    Admin,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Observation {
+    at: Instant,
+    latency: Duration,
+    failed: bool,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct LatencyStatus {
+    pub percentile: f64,
+    pub target_ms: u64,
+    pub actual_ms: Option<u64>,
+    pub compliant: bool,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ErrorRateStatus {
+    pub target: f64,
+    pub actual: f64,
+    pub compliant: bool,
+    /// Share of the allowed failed requests that is not spent yet, negative when the budget is exceeded
+    pub error_budget_remaining: f64,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct SloStatus {
+    pub route_group: RouteGroup,
+    pub window_sec: u64,
+    pub requests: usize,
+    pub failed_requests: usize,
+    pub latency: LatencyStatus,
+    pub error_rate: ErrorRateStatus,
+}
+
+pub struct SloTracker {
+    window: Duration,
+    targets: Vec<config::SloTarget>,
+    observations: Mutex<HashMap<RouteGroup, VecDeque<Observation>>>,
+}
+
+impl SloTracker {
+    pub fn new(config: Option<config::Slo>) -> Self {
+        let (window, targets) = match config {
+            Some(config) => (Duration::from_secs(config.window_sec), config.targets),
+            None => (Duration::from_secs(0), vec![]),
+        };
+
+        Self {
+            window,
+            targets,
+            observations: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Records a finished request, requests of groups without targets are ignored
+    pub fn record(&self, route_group: RouteGroup, latency: Duration, failed: bool) {
+        self.record_at(Instant::now(), route_group, latency, failed)
+    }
+
+    fn record_at(&self, at: Instant, route_group: RouteGroup, latency: Duration, failed: bool) {
+        if !self.targets.iter().any(|target| target.route_group == route_group) {
+            return;
+        }
+
+        let mut observations = match self.observations.lock() {
+            Ok(observations) => observations,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        let group_observations = observations.entry(route_group).or_insert_with(VecDeque::new);
+        if group_observations.len() >= MAX_OBSERVATIONS_PER_GROUP {
+            group_observations.pop_front();
+        }
+        group_observations.push_back(Observation { at, latency, failed });
+    }
+
+    /// Returns compliance of every configured target over the rolling window
+    pub fn status(&self) -> Vec<SloStatus> {
+        self.status_at(Instant::now())
+    }
+
+    fn status_at(&self, now: Instant) -> Vec<SloStatus> {
+        let mut observations = match self.observations.lock() {
+            Ok(observations) => observations,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        let window = self.window;
+        self.targets
+            .iter()
+            .map(|target| {
+                let group_observations = observations.entry(target.route_group).or_insert_with(VecDeque::new);
+                while group_observations
+                    .front()
+                    .map(|observation| now.duration_since(observation.at) > window)
+                    .unwrap_or(false)
+                {
+                    group_observations.pop_front();
+                }
+
+                calculate_status(target, window, group_observations)
+            })
+            .collect()
+    }
+}
+
+fn calculate_status(target: &config::SloTarget, window: Duration, observations: &VecDeque<Observation>) -> SloStatus {
+    let requests = observations.len();
+    let failed_requests = observations.iter().filter(|observation| observation.failed).count();
+
+    let mut latencies_ms = observations
+        .iter()
+        .map(|observation| duration_to_ms(observation.latency))
+        .collect::<Vec<_>>();
+    latencies_ms.sort_unstable();
+
+    // nearest-rank percentile
+    let actual_ms = if latencies_ms.is_empty() {
+        None
+    } else {
+        let rank = (target.latency_percentile / 100.0 * latencies_ms.len() as f64).ceil() as usize;
+        let index = rank.max(1).min(latencies_ms.len()) - 1;
+        Some(latencies_ms[index])
+    };
+
+    let actual_error_rate = if requests == 0 {
+        0.0
+    } else {
+        failed_requests as f64 / requests as f64
+    };
+
+    let allowed_failures = target.max_error_rate * requests as f64;
+    let error_budget_remaining = if allowed_failures > 0.0 {
+        1.0 - failed_requests as f64 / allowed_failures
+    } else if failed_requests == 0 {
+        1.0
+    } else {
+        -1.0
+    };
+
+    SloStatus {
+        route_group: target.route_group,
+        window_sec: window.as_secs(),
+        requests,
+        failed_requests,
+        latency: LatencyStatus {
+            percentile: target.latency_percentile,
+            target_ms: target.latency_ms,
+            actual_ms,
+            compliant: actual_ms.map(|actual_ms| actual_ms <= target.latency_ms).unwrap_or(true),
+        },
+        error_rate: ErrorRateStatus {
+            target: target.max_error_rate,
+            actual: actual_error_rate,
+            compliant: actual_error_rate <= target.max_error_rate,
+            error_budget_remaining,
+        },
+    }
+}
+
+pub fn duration_to_ms(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + u64::from(duration.subsec_millis())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker() -> SloTracker {
+        SloTracker::new(Some(config::Slo {
+            window_sec: 60,
+            targets: vec![config::SloTarget {
+                route_group: RouteGroup::Price,
+                latency_percentile: 99.0,
+                latency_ms: 150,
+                max_error_rate: 0.01,
+            }],
+        }))
+    }
+
+    #[test]
+    fn status_reports_latency_percentile_and_error_budget() {
+        let tracker = tracker();
+        let now = Instant::now();
+
+        for i in 0..200 {
+            let latency = if i < 198 { 100 } else { 400 };
+            tracker.record_at(now, RouteGroup::Price, Duration::from_millis(latency), i == 0);
+        }
+        tracker.record_at(now, RouteGroup::Admin, Duration::from_millis(1000), true);
+
+        let status = tracker.status_at(now);
+
+        assert_eq!(status.len(), 1);
+        let status = &status[0];
+        assert_eq!(status.requests, 200);
+        assert_eq!(status.failed_requests, 1);
+        assert_eq!(status.latency.actual_ms, Some(100));
+        assert!(status.latency.compliant);
+        assert!(status.error_rate.compliant);
+        assert!((status.error_rate.error_budget_remaining - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn observations_out_of_window_are_dropped() {
+        let tracker = tracker();
+        let now = Instant::now();
+
+        tracker.record_at(now, RouteGroup::Price, Duration::from_millis(500), true);
+
+        let status = tracker.status_at(now + Duration::from_secs(61));
+
+        assert_eq!(status[0].requests, 0);
+        assert_eq!(status[0].latency.actual_ms, None);
+        assert!(status[0].latency.compliant);
+        assert!(status[0].error_rate.compliant);
+    }
+}
@@ -197,6 +197,7 @@ pub fn create_delivery_objects(
         company_id: company_id.clone(),
         package_id: package_id.clone(),
         shipping_rate_source,
+        restricted_hazard_classes: vec![],
     };
 
     let create_result = create_companies_packages(new_company_package, core, http_client, base_url.clone(), user_id);
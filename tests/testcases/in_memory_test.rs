@@ -0,0 +1,101 @@
+//! Services over the in-memory repos, run without a database by `cargo test --features in-memory-repos`
+use std::sync::Arc;
+
+use r2d2::ManageConnection;
+use stq_cache::cache::{Cache, NullCache};
+use stq_http::errors::ErrorMessageWrapper;
+use stq_static_resources::Currency;
+use stq_types::*;
+use tokio_core::reactor::Core;
+
+use lib::credentials::CredentialsCipher;
+use lib::errors::Error;
+use lib::models::*;
+use lib::repos::in_memory::{create_in_memory_service, InMemoryConnection, InMemoryReposFactory};
+use lib::repos::{CountryCacheImpl, ReposFactory, ReposFactoryImpl, RolesCacheImpl};
+use lib::services::companies::CompaniesService;
+use lib::services::packages::PackagesService;
+
+static SUPERUSER_ID: UserId = UserId(1);
+
+fn create_repo_factory() -> InMemoryReposFactory<impl ReposFactory<InMemoryConnection>> {
+    let fallback = ReposFactoryImpl::new(
+        CountryCacheImpl::new(Box::new(NullCache::new()) as Box<dyn Cache<_, Error = _> + Send + Sync>),
+        RolesCacheImpl::new(Box::new(NullCache::new()) as Box<dyn Cache<_, Error = _> + Send + Sync>),
+        CredentialsCipher::new(None).unwrap(),
+    );
+    let repo_factory = InMemoryReposFactory::new(fallback);
+
+    let conn = repo_factory.connection_manager().connect().unwrap();
+    repo_factory
+        .create_user_roles_repo_with_sys_acl(&conn)
+        .create(NewUserRole {
+            id: RoleId::new(),
+            user_id: SUPERUSER_ID,
+            name: DeliveryRole::Superuser,
+            data: None,
+        })
+        .unwrap();
+
+    repo_factory
+}
+
+fn new_company() -> NewCompany {
+    NewCompany {
+        name: "UPS Russia".to_string(),
+        label: "UPS".to_string(),
+        description: None,
+        deliveries_from: vec![Alpha3("RUS".to_string())],
+        logo: "".to_string(),
+        currency: Currency::STQ,
+        capabilities: vec![CompanyCapability::Tracking],
+        label_translations: vec![],
+    }
+}
+
+fn new_packages() -> NewPackages {
+    NewPackages {
+        name: "package1".to_string(),
+        max_size: 100,
+        min_size: 0,
+        max_weight: 100,
+        min_weight: 0,
+        deliveries_to: vec![Alpha3("RUS".to_string())],
+        deliveries_to_zones: vec![],
+        name_translations: vec![],
+    }
+}
+
+#[test]
+fn companies_and_packages_are_managed_without_database() {
+    let mut core = Core::new().unwrap();
+    let handle = Arc::new(core.handle());
+    let service = create_in_memory_service(create_repo_factory(), Some(SUPERUSER_ID), handle);
+
+    let company = core.run(service.create_company(new_company())).unwrap();
+    let package = core.run(service.create_package(new_packages())).unwrap();
+
+    let companies = core.run(service.list_companies(CompaniesSearch::default())).unwrap();
+    assert_eq!(companies.iter().map(|company| company.id).collect::<Vec<_>>(), vec![company.id]);
+    let packages = core.run(service.list_packages()).unwrap();
+    assert_eq!(packages.iter().map(|package| package.id).collect::<Vec<_>>(), vec![package.id]);
+
+    core.run(service.delete_company(company.id, false)).unwrap();
+    assert!(core.run(service.find_company(company.id)).unwrap().is_none());
+}
+
+#[test]
+fn actions_are_denied_to_users_without_roles() {
+    let mut core = Core::new().unwrap();
+    let handle = Arc::new(core.handle());
+    let service = create_in_memory_service(create_repo_factory(), Some(UserId(2)), handle);
+
+    let err = core
+        .run(service.create_company(new_company()))
+        .expect_err("Company must not be created");
+    assert_eq!(ErrorMessageWrapper::<Error>::from(&err).inner.code, 403);
+    let err = core
+        .run(service.create_package(new_packages()))
+        .expect_err("Package must not be created");
+    assert_eq!(ErrorMessageWrapper::<Error>::from(&err).inner.code, 403);
+}
@@ -21,6 +21,7 @@ fn create_companies_packages(
         company_id,
         package_id,
         shipping_rate_source: Some(shipping_rate_source),
+        restricted_hazard_classes: vec![],
     };
     let body: String = serde_json::to_string(&new_companies_packages).unwrap().to_string();
     let create_result = core.run(http_client.request_with_auth_header::<CompanyPackage>(
@@ -64,6 +65,8 @@ fn create_company(name: String) -> NewCompany {
         deliveries_from: vec![Alpha3("RUS".to_string())],
         logo: "".to_string(),
         currency: Currency::STQ,
+        capabilities: vec![],
+        label_translations: vec![],
     }
 }
 
@@ -75,6 +78,8 @@ fn create_package(name: String) -> NewPackages {
         max_weight: 0,
         min_weight: 0,
         deliveries_to: vec![Alpha3("USA".to_string()), Alpha3("CHN".to_string())],
+        deliveries_to_zones: vec![],
+        name_translations: vec![],
     }
 }
 
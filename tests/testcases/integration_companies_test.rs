@@ -16,6 +16,8 @@ fn create_update_company(name: &str) -> UpdateCompany {
         deliveries_from: None,
         logo: None,
         currency: None,
+        capabilities: None,
+        label_translations: None,
     }
 }
 
@@ -33,6 +35,8 @@ fn create_company(
         deliveries_from: vec![Alpha3("RUS".to_string())],
         logo: "".to_string(),
         currency: Currency::STQ,
+        capabilities: vec![CompanyCapability::Tracking],
+        label_translations: vec![],
     };
 
     let body: String = serde_json::to_string(&new_company).unwrap().to_string();
@@ -86,6 +90,44 @@ fn test_company_superuser_crud(core: &mut tokio_core::reactor::Core, http_client
     println!("{:?}", read_result);
     assert!(read_result.is_ok());
 
+    // list with filters
+    println!("run list companies with filters");
+    let list_result = core.run(http_client.request_with_auth_header::<Vec<Company>>(
+        Method::Get,
+        format!(
+            "{}?deliveries_from=RUS&label_contains=up&sort=name&order=desc",
+            get_url_request(base_url.clone())
+        ),
+        None,
+        Some(user_id.to_string()),
+    ));
+    println!("{:?}", list_result);
+    assert!(list_result.is_ok());
+    assert!(list_result.unwrap().iter().any(|c| c.id == company.id));
+
+    // hostile country code is a bind parameter of the query and matches no company
+    println!("run list companies delivering to hostile country code");
+    let hostile_result = core.run(http_client.request_with_auth_header::<Vec<Company>>(
+        Method::Get,
+        format!("{}?delivers_to=RUS%27%5D%20OR%20TRUE%20--", get_url_request(base_url.clone())),
+        None,
+        Some(user_id.to_string()),
+    ));
+    println!("{:?}", hostile_result);
+    assert!(hostile_result.unwrap().is_empty());
+
+    // search by capabilities
+    println!("run search companies by capabilities");
+    let search_result = core.run(http_client.request_with_auth_header::<Vec<Company>>(
+        Method::Get,
+        format!("{}/search?supports=tracking", get_url_request(base_url.clone())),
+        None,
+        Some(user_id.to_string()),
+    ));
+    println!("{:?}", search_result);
+    assert!(search_result.is_ok());
+    assert!(search_result.unwrap().iter().any(|c| c.id == company.id));
+
     // update
     println!("run update company ");
     let update_company = create_update_company("UPS USA 2");
@@ -15,6 +15,8 @@ fn create_update_package(name: &str) -> UpdatePackages {
         max_weight: Some(0),
         min_weight: Some(0),
         deliveries_to: Some(vec![]),
+        deliveries_to_zones: Some(vec![]),
+        name_translations: None,
     }
 }
 
@@ -32,6 +34,8 @@ fn create_package(
         max_weight: 0,
         min_weight: 0,
         deliveries_to: vec![],
+        deliveries_to_zones: vec![],
+        name_translations: vec![],
     };
 
     let body: String = serde_json::to_string(&new).unwrap().to_string();
@@ -32,6 +32,7 @@ fn create_shipping(
         }),
         delivery_from: None,
         currency: Currency::USD,
+        hazard_classes: vec![],
     };
 
     let new_pickup = NewPickups {
@@ -44,6 +45,7 @@ fn create_shipping(
     let shipping = NewShipping {
         items: vec![new_product],
         pickup: Some(new_pickup),
+        hazard_classes: vec![],
     };
 
     let body: String = serde_json::to_string(&shipping).unwrap().to_string();
@@ -81,6 +83,8 @@ fn create_company(name: String) -> NewCompany {
         deliveries_from: vec![Alpha3("RUS".to_string())],
         logo: "".to_string(),
         currency: Currency::STQ,
+        capabilities: vec![],
+        label_translations: vec![],
     }
 }
 
@@ -92,6 +96,8 @@ fn create_package(name: String) -> NewPackages {
         max_weight: 10_000,
         min_weight: 0,
         deliveries_to: vec![Alpha3("BRA".to_string()), Alpha3("USA".to_string()), Alpha3("RUS".to_string())],
+        deliveries_to_zones: vec![],
+        name_translations: vec![],
     }
 }
 
@@ -129,7 +135,22 @@ fn test_products_superuser_crud(core: &mut tokio_core::reactor::Core, http_clien
         Some(user_id.to_string()),
     );
     println!("create shipping {:?}", create_result);
-    assert!(create_result.is_ok());
+    let shipping_id = create_result.unwrap().items[0].product.id;
+
+    // hostile country code is a bind parameter of the query, so it can not widen the search
+    // to the countries the existing shipping does not deliver to
+    println!("run get available package for shipping {} with hostile delivery_to", shipping_id);
+    let hostile_result = core.run(http_client.request_with_auth_header::<Option<AvailablePackageForUser>>(
+        Method::Get,
+        format!(
+            "{}/v2/available_packages_for_user/by_shipping_id/{}?delivery_from=RUS&delivery_to={}&volume=1&weight=1",
+            base_url, shipping_id, "RUS%27%5D%20OR%20TRUE%20--"
+        ),
+        None,
+        Some(user_id.to_string()),
+    ));
+    println!("{:?}", hostile_result);
+    assert!(hostile_result.unwrap().is_none());
 
     super::common::delete_deliveries_objects(
         (package_id.clone(), company_id.clone(), companies_package_id.clone()),
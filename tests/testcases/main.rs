@@ -1,8 +1,12 @@
 extern crate delivery_lib as lib;
 extern crate futures;
 extern crate hyper;
+#[cfg(feature = "in-memory-repos")]
+extern crate r2d2;
 extern crate rand;
 extern crate serde_json;
+#[cfg(feature = "in-memory-repos")]
+extern crate stq_cache;
 extern crate stq_http;
 extern crate stq_static_resources;
 extern crate stq_types;
@@ -16,3 +20,6 @@ mod integration_countries_test;
 mod integration_packages_test;
 mod integration_products_test;
 mod integration_user_addresses_test;
+
+#[cfg(feature = "in-memory-repos")]
+mod in_memory_test;